@@ -255,6 +255,8 @@ can get pretty complex."
                   (const :tag "Default generic Mac OS Keychain"
                          macos-keychain-generic)
 
+                  (const :tag "Keychain of the system" native-keychain)
+
                   (list :tag "Source definition"
                         (const :format "" :value :source)
                         (choice :tag "Authentication backend choice"
@@ -495,6 +497,20 @@ soon as a function returns non-nil.")
 
 (add-hook 'auth-source-backend-parser-functions 'auth-source-backends-parser-secrets)
 
+(defun auth-source-backends-parser-native-keychain (entry)
+  ;; take 'native-keychain and use the keychain of the system, through
+  ;; `secrets-store' and `secrets-lookup'
+  (when (and (eq entry 'native-keychain)
+             (fboundp 'secrets-lookup))
+    (auth-source-backend
+     "Native keychain"
+     :source "native-keychain"
+     :type 'native-keychain
+     :search-function #'auth-source-native-keychain-search
+     :create-function #'auth-source-native-keychain-create)))
+
+(add-hook 'auth-source-backend-parser-functions 'auth-source-backends-parser-native-keychain)
+
 (defun auth-source-backend-parse-parameters (entry backend)
   "Fills in the extra auth-source-backend parameters of ENTRY.
 Using the plist ENTRY, get the :host, :port, and :user search
@@ -1770,6 +1786,59 @@ entries for git.gnus.org:
   ;; TODO
   (debug spec))
 
+;;; Backend specific parsing: native keychain backend
+
+(defun auth-source-native-keychain-service (host port)
+  "Return the keychain service name for HOST and PORT."
+  (if port (format "%s:%s" host port) host))
+
+(cl-defun auth-source-native-keychain-search (&rest spec
+                                              &key create delete host user port
+                                              &allow-other-keys)
+  "Search the keychain of the system; SPEC is like `auth-source'.
+Secrets are kept under the service \"HOST:PORT\", or \"HOST\" if
+there is no port, and the account USER.  Only the first match is
+returned.  When CREATE is non-nil and nothing matches, ask for a
+new secret; see `auth-source-native-keychain-create'."
+  (cl-assert (not delete) nil
+             "The native keychain auth-source backend doesn't support deletion")
+  (let ((hosts (if (and host (listp host)) host (list host)))
+        (ports (if (and port (listp port)) port (list port)))
+        (user (and (stringp user) user)))
+    (or (catch 'match
+          (dolist (h hosts)
+            (when (stringp h)
+              (dolist (p (if (memq t ports) '(nil) ports))
+                (let ((found (secrets-lookup
+                              (auth-source-native-keychain-service h p) user)))
+                  (when found
+                    (throw 'match
+                           (list (list :host h :port p :user (car found)
+                                       :secret (let ((v (cdr found)))
+                                                 (lambda () v)))))))))))
+        (and create
+             (apply #'auth-source-native-keychain-create spec)))))
+
+(cl-defun auth-source-native-keychain-create (&rest _spec
+                                              &key host user port
+                                              &allow-other-keys)
+  "Ask for a secret for USER at HOST and PORT.
+The result has a :save-function that stores the secret in the
+keychain of the system with `secrets-store'."
+  (let* ((host (if (listp host) (car host) host))
+         (port (if (listp port) (car port) port))
+         (port (unless (eq port t) port))
+         (user (if (stringp user)
+                   user
+                 (read-string (format "User name for %s: " host)
+                              nil nil (user-login-name))))
+         (secret (read-passwd (format "Password for %s@%s: " user host)))
+         (service (auth-source-native-keychain-service host port)))
+    (list (list :host host :port port :user user
+                :secret (lambda () secret)
+                :save-function (lambda ()
+                                 (secrets-store service user secret))))))
+
 ;;; Backend specific parsing: PLSTORE backend
 
 (cl-defun auth-source-plstore-search (&rest spec
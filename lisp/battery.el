@@ -52,7 +52,10 @@
   :group 'battery)
 
 (defcustom battery-status-function
-  (cond ((and (eq system-type 'gnu/linux)
+  (cond ((and (fboundp 'battery-status-native)
+	      (battery-status-native))
+	 #'battery-status-native)
+	((and (eq system-type 'gnu/linux)
 	      (file-readable-p "/proc/apm"))
 	 #'battery-linux-proc-apm)
 	((and (eq system-type 'gnu/linux)
@@ -1167,6 +1167,12 @@ if `inhibit-field-text-motion' is non-nil."
 
 (define-key global-map [mouse-movement] 'ignore)
 
+;; Touchpad gestures.  Pinching zooms the text; rotating and swiping
+;; do nothing unless a mode binds them.
+(define-key global-map [pinch] 'text-scale-pinch)
+(define-key global-map [rotate] 'ignore)
+(define-key global-map [swipe] 'ignore)
+
 (define-key global-map "\C-t" 'transpose-chars)
 (define-key esc-map "t" 'transpose-words)
 (define-key esc-map "\C-t" 'transpose-sexps)
@@ -96,6 +96,22 @@ To specify the file in which to save them, modify the variable
   :type 'file
   :group 'bookmark)
 
+(defcustom bookmark-native-file nil
+  "File for the native bookmark store, or nil not to use the store.
+When this is non-nil and Emacs has `bookmark-query', the bookmarks are
+also kept in a native store, which `bookmark-save' writes to this file
+in the background instead of writing `bookmark-default-file'.  The
+store is read back when this file is newer than `bookmark-default-file'.
+It records how often and when each bookmark is jumped to, and
+`bookmark-query' finds bookmarks by fuzzy matching their names and
+annotations.  Bookmarks containing objects the store cannot save,
+such as markers, make `bookmark-save' fall back to
+`bookmark-default-file'."
+  :type '(choice (const :tag "Don't use" nil)
+                 file)
+  :version "27.1"
+  :group 'bookmark)
+
 
 (defcustom bookmark-version-control 'nospecial
   "Whether or not to make numbered backups of the bookmark file.
@@ -1030,6 +1046,36 @@ it to the name of the bookmark currently being set, advancing
     (t (error "Buffer not visiting a file or directory")))))
 
 
+(defun bookmark--native-p ()
+  "Return non-nil if bookmarks are kept in the native store."
+  (and bookmark-native-file (fboundp 'bookmark-query)))
+
+(defun bookmark--native-sync ()
+  "Give the native bookmark store the contents of `bookmark-alist'.
+Return the names of the bookmarks the store could not take."
+  (when (bookmark--native-p)
+    (bookmark-store-replace bookmark-alist)))
+
+(defun bookmark--native-current-p ()
+  "Return non-nil if the bookmarks should be read from the native store."
+  (and (bookmark--native-p)
+       (file-exists-p bookmark-native-file)
+       (or (not (file-exists-p bookmark-default-file))
+           (file-newer-than-file-p bookmark-native-file
+                                   bookmark-default-file))))
+
+(defun bookmark--native-touch (bookmark-name-or-record)
+  "Record in the native store that BOOKMARK-NAME-OR-RECORD was used."
+  (when (bookmark--native-p)
+    (let ((name (bookmark-name-from-full-record
+                 (bookmark-get-bookmark bookmark-name-or-record))))
+      (when (or (bookmark-store-touch name)
+                (progn (bookmark--native-sync)
+                       (bookmark-store-touch name)))
+        ;; Save the usage along with the bookmarks.
+        (setq bookmark-alist-modification-count
+              (1+ bookmark-alist-modification-count))))))
+
 (defun bookmark-maybe-load-default-file ()
   "If bookmarks have not been loaded from the default place, load them."
   (and (not bookmarks-already-loaded)
@@ -1045,8 +1091,16 @@ it to the name of the bookmark currently being set, advancing
            ;; return t so the `and' will continue...
            t)
 
-       (file-readable-p bookmark-default-file)
-       (bookmark-load bookmark-default-file t t)
+       (if (bookmark--native-current-p)
+           (progn
+             (setq bookmark-alist (bookmark-store-load bookmark-native-file))
+             (bookmark-bmenu-surreptitiously-rebuild-list)
+             t)
+         (and (file-readable-p bookmark-default-file)
+              (progn
+                (bookmark-load bookmark-default-file t t)
+                (bookmark--native-sync)
+                t)))
        (setq bookmarks-already-loaded t)))
 
 
@@ -1072,6 +1126,7 @@ After calling DISPLAY-FUNCTION, set window point to the point specified
 by BOOKMARK-NAME-OR-RECORD, if necessary, run `bookmark-after-jump-hook',
 and then show any annotations for this bookmark."
   (bookmark-handle-bookmark bookmark-name-or-record)
+  (bookmark--native-touch bookmark-name-or-record)
   (save-current-buffer
     (funcall display-function (current-buffer)))
   (let ((win (get-buffer-window (current-buffer) 0)))
@@ -1397,6 +1452,14 @@ for a file, defaulting to the file defined by variable
   (interactive "P")
   (bookmark-maybe-load-default-file)
   (cond
+   ((and (null parg) (null file) (bookmark--native-p))
+    ;; Write the native store, unless some bookmarks can't go there.
+    (let ((failed (bookmark--native-sync)))
+      (if (null failed)
+          (bookmark-store-save bookmark-native-file)
+        (message "Bookmarks %s can't be stored natively"
+                 (mapconcat #'identity failed ", "))
+        (bookmark-write-file bookmark-default-file))))
    ((and (null parg) (null file))
     ;;whether interactive or not, write to default file
     (bookmark-write-file bookmark-default-file))
@@ -2005,7 +2005,8 @@ buffer `*icalendar-errors*'."
           ;; read ical
           (message "Reading iCalendar...")
           (beginning-of-line)
-          (setq ical-contents (icalendar--read-element nil nil))
+          (setq ical-contents
+                (icalendar-parse-region-native (point) (point-max)))
           (message "Reading iCalendar...done")
           ;; convert ical
           (message "Converting iCalendar...")
@@ -218,45 +218,11 @@ any values that are unknown are returned as nil."
   "Parse an ISO 8601 time string, such as 2016-12-01T23:35:06-05:00.
 If DATE-STRING cannot be parsed, it falls back to
 `parse-time-string'."
-  (let* ((date-re (nth 0 parse-time-iso8601-regexp))
-	 (time-re (nth 1 parse-time-iso8601-regexp))
-	 (tz-re (nth 2 parse-time-iso8601-regexp))
-         re-start
-         time seconds minute hour
-         day month year day-of-week dst tz)
-    ;; We need to populate 'time' with
-    ;; (SEC MIN HOUR DAY MON YEAR DOW DST TZ)
-
-    ;; Nobody else handles iso8601 correctly, let's do it ourselves.
-    (when (string-match date-re date-string re-start)
-      (setq year (string-to-number (match-string 1 date-string))
-	    month (string-to-number (match-string 2 date-string))
-	    day (string-to-number (match-string 3 date-string))
-	    re-start (match-end 0))
-      (when (string-match time-re date-string re-start)
-	(setq hour (string-to-number (match-string 1 date-string))
-	      minute (string-to-number (match-string 2 date-string))
-	      seconds (string-to-number (match-string 3 date-string))
-	      re-start (match-end 0))
-	(when (string-match tz-re date-string re-start)
-          (if (string= "Z" (match-string 1 date-string))
-              (setq tz 0)  ;; UTC timezone indicated by Z
-            (setq tz (+
-                      (* 3600
-                         (string-to-number (match-string 3 date-string)))
-                      (* 60
-                         (string-to-number
-                          (or (match-string 4 date-string) "0")))))
-            (when (string= "-" (match-string 2 date-string))
-              (setq tz (- tz)))))
-	(setq time (list seconds minute hour day month year day-of-week dst tz))))
-
-    ;; Fall back to having `parse-time-string' do fancy things for us.
-    (when (not time)
-      (setq time (parse-time-string date-string)))
-
-    (and time
-	 (apply 'encode-time time))))
+  (or (parse-iso8601-time-string-native date-string)
+      ;; Fall back to having `parse-time-string' do fancy things for us.
+      (let ((time (parse-time-string date-string)))
+	(and time
+	     (apply 'encode-time time)))))
 
 (provide 'parse-time)
 
@@ -231,6 +231,19 @@ Zero or nil means disable auto-saving due to idleness."
   :group 'desktop
   :version "24.4")
 
+(defcustom desktop-auto-save-snapshot nil
+  "Non-nil means auto-save the desktop as a session snapshot.
+A snapshot records the buffers visiting files, the windows of the
+selected frame and the variables in `desktop-globals-to-save', and is
+written in the background, so auto-saving stays fast however many
+buffers there are.  It is kept next to the desktop file, and
+`desktop-read' restores it when it is newer than the desktop file,
+for instance after Emacs crashed.  The desktop file itself is still
+written when Emacs exits and by \\[desktop-save]."
+  :type 'boolean
+  :group 'desktop
+  :version "27.1")
+
 (defcustom desktop-load-locked-desktop 'ask
   "Specifies whether the desktop should be loaded if locked.
 Possible values are:
@@ -620,6 +633,18 @@ See also `desktop-minor-mode-table'.")
 DIRNAME omitted or nil means use `desktop-dirname'."
   (expand-file-name desktop-base-file-name (or dirname desktop-dirname)))
 
+(defun desktop-snapshot-file-name (&optional dirname)
+  "Return the full name of the desktop snapshot file in DIRNAME.
+DIRNAME omitted or nil means use `desktop-dirname'."
+  (concat (desktop-full-file-name dirname) ".snapshot"))
+
+(defun desktop--save-snapshot (dirname)
+  "Save a session snapshot of the desktop in DIRNAME."
+  (session-snapshot-save
+   (desktop-snapshot-file-name dirname)
+   (mapcar (lambda (var) (if (consp var) (car var) var))
+           desktop-globals-to-save)))
+
 (defun desktop-full-lock-name (&optional dirname)
   "Return the full name of the desktop lock file in DIRNAME.
 DIRNAME omitted or nil means use `desktop-dirname'."
@@ -771,6 +796,9 @@ is nil, ask the user where to save the desktop."
       (file-error
        (unless (yes-or-no-p "Error while saving the desktop.  Ignore? ")
 	 (signal (car err) (cdr err))))))
+  ;; Don't leave a snapshot half written.
+  (when (fboundp 'session-snapshot-wait)
+    (ignore-errors (session-snapshot-wait)))
   ;; If we own it, we don't anymore.
   (when (eq (emacs-pid) (desktop-owner)) (desktop-release-lock)))
 
@@ -1243,6 +1271,18 @@ Using it may cause conflicts.  Use it anyway? " owner)))))
 	    ;; Evaluate desktop buffer and remember when it was modified.
 	    (setq desktop-file-modtime (nth 5 (file-attributes (desktop-full-file-name))))
 	    (load (desktop-full-file-name) t t t)
+	    ;; An auto-saved snapshot newer than the desktop file
+	    ;; records the state of a session that did not exit cleanly.
+	    (when (and desktop-auto-save-snapshot
+		       (fboundp 'session-snapshot-restore)
+		       (file-newer-than-file-p (desktop-snapshot-file-name)
+					       (desktop-full-file-name)))
+	      (condition-case err
+		  (session-snapshot-restore (desktop-snapshot-file-name)
+					    (desktop-restoring-frameset-p))
+		(error (message "Desktop: snapshot not restored: %s"
+				(error-message-string err))
+		       (sit-for 1))))
 	    ;; If it wasn't already, mark it as in-use, to bother other
 	    ;; desktop instances.
 	    (unless (eq (emacs-pid) owner)
@@ -1358,7 +1398,10 @@ Called by the timer created in `desktop-auto-save-set-timer'."
 	     ;; Save only to own desktop file.
 	     (eq (emacs-pid) (desktop-owner))
 	     desktop-dirname)
-    (desktop-save desktop-dirname nil t)))
+    (if (and desktop-auto-save-snapshot
+	     (fboundp 'session-snapshot-save))
+	(desktop--save-snapshot desktop-dirname)
+      (desktop-save desktop-dirname nil t))))
 
 (defun desktop-auto-save-set-timer ()
   "Set the auto-save timer.
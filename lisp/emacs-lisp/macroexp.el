@@ -22,77 +22,13 @@
 
 ;;; Commentary:
 ;;
-;; This file contains macro-expansions functions that are not defined in
-;; the Lisp core, namely `macroexpand-all', which expands all macros in
-;; a form, not just a top-level one.
+;; This file contains helpers for macro expansion.  `macroexpand-1',
+;; `macroexpand' and `macroexpand-all', which expands all macros in a
+;; form, not just a top-level one, are defined in the Lisp core; they
+;; call back into the warning and compiler-macro helpers below.
 
 ;;; Code:
 
-;; Bound by the top-level `macroexpand-all', and modified to include any
-;; macros defined by `defmacro'.
-(defvar macroexpand-all-environment nil)
-
-(defun macroexp--cons (car cdr original-cons)
-  "Return (CAR . CDR), using ORIGINAL-CONS if possible."
-  (if (and (eq car (car original-cons)) (eq cdr (cdr original-cons)))
-      original-cons
-    (cons car cdr)))
-
-;; We use this special macro to iteratively process forms and share list
-;; structure of the result with the input.  Doing so recursively using
-;; `macroexp--cons' results in excessively deep recursion for very long
-;; input forms.
-(defmacro macroexp--accumulate (var+list &rest body)
-  "Return a list of the results of evaluating BODY for each element of LIST.
-Evaluate BODY with VAR bound to each `car' from LIST, in turn.
-Return a list of the values of the final form in BODY.
-The list structure of the result will share as much with LIST as
-possible (for instance, when BODY just returns VAR unchanged, the
-result will be eq to LIST).
-
-\(fn (VAR LIST) BODY...)"
-  (declare (indent 1))
-  (let ((var (car var+list))
-	(list (cadr var+list))
-	(shared (make-symbol "shared"))
-	(unshared (make-symbol "unshared"))
-	(tail (make-symbol "tail"))
-	(new-el (make-symbol "new-el")))
-    `(let* ((,shared ,list)
-	    (,unshared nil)
-	    (,tail ,shared)
-	    ,var ,new-el)
-       (while (consp ,tail)
-	 (setq ,var (car ,tail)
-	       ,new-el (progn ,@body))
-	 (unless (eq ,var ,new-el)
-	   (while (not (eq ,shared ,tail))
-	     (push (pop ,shared) ,unshared))
-	   (setq ,shared (cdr ,shared))
-	   (push ,new-el ,unshared))
-	 (setq ,tail (cdr ,tail)))
-       (nconc (nreverse ,unshared) ,shared))))
-
-(defun macroexp--all-forms (forms &optional skip)
-  "Return FORMS with macros expanded.  FORMS is a list of forms.
-If SKIP is non-nil, then don't expand that many elements at the start of
-FORMS."
-  (macroexp--accumulate (form forms)
-    (if (or (null skip) (zerop skip))
-	(macroexp--expand-all form)
-      (setq skip (1- skip))
-      form)))
-
-(defun macroexp--all-clauses (clauses &optional skip)
-  "Return CLAUSES with macros expanded.
-CLAUSES is a list of lists of forms; any clause that's not a list is ignored.
-If SKIP is non-nil, then don't expand that many elements at the start of
-each clause."
-  (macroexp--accumulate (clause clauses)
-    (if (listp clause)
-	(macroexp--all-forms clause skip)
-      clause)))
-
 (defun macroexp--compiler-macro (handler form)
   (condition-case err
       (apply handler form (cdr form))
@@ -100,6 +36,11 @@ each clause."
      (message "Compiler-macro error for %S: %S" (car form) err)
            form)))
 
+(defun macroexp--load-compiler-macro (func)
+  "Try to (auto)load FUNC, which may in turn load its compiler-macro."
+  (with-demoted-errors "macroexp--expand-all: %S"
+    (autoload-do-load (indirect-function func) func)))
+
 (defun macroexp--funcall-if-compiled (_form)
   "Pseudo function used internally by macroexp to delay warnings.
 The purpose is to delay warnings to bytecomp.el, so they can use things
@@ -153,30 +94,6 @@ and also to avoid outputting the warning during normal execution."
            (instead (format-message "; use `%s' instead." instead))
            (t ".")))))
 
-(defun macroexpand-1 (form &optional environment)
-  "Perform (at most) one step of macroexpansion."
-  (cond
-   ((consp form)
-    (let* ((head (car form))
-           (env-expander (assq head environment)))
-      (if env-expander
-          (if (cdr env-expander)
-              (apply (cdr env-expander) (cdr form))
-            form)
-        (if (not (and (symbolp head) (fboundp head)))
-            form
-          (let ((def (autoload-do-load (symbol-function head) head 'macro)))
-            (cond
-             ;; Follow alias, but only for macros, otherwise we may end up
-             ;; skipping an important compiler-macro (e.g. cl--block-wrapper).
-             ((and (symbolp def) (macrop def)) (cons def (cdr form)))
-             ((not (consp def)) form)
-             (t
-              (if (eq 'macro (car def))
-                  (apply (cdr def) (cdr form))
-                form))))))))
-   (t form)))
-
 (defun macroexp-macroexpand (form env)
   "Like `macroexpand' but checking obsolescence."
   (let ((new-form
@@ -201,108 +118,7 @@ and also to avoid outputting the warning during normal execution."
   "Expand all macros in FORM.
 This is an internal version of `macroexpand-all'.
 Assumes the caller has bound `macroexpand-all-environment'."
-  (if (eq (car-safe form) 'backquote-list*)
-      ;; Special-case `backquote-list*', as it is normally a macro that
-      ;; generates exceedingly deep expansions from relatively shallow input
-      ;; forms.  We just process it `in reverse' -- first we expand all the
-      ;; arguments, _then_ we expand the top-level definition.
-      (macroexpand (macroexp--all-forms form 1)
-		   macroexpand-all-environment)
-    ;; Normal form; get its expansion, and then expand arguments.
-    (setq form (macroexp-macroexpand form macroexpand-all-environment))
-    (pcase form
-      (`(cond . ,clauses)
-       (macroexp--cons 'cond (macroexp--all-clauses clauses) form))
-      (`(condition-case . ,(or `(,err ,body . ,handlers) dontcare))
-       (macroexp--cons
-        'condition-case
-        (macroexp--cons err
-                        (macroexp--cons (macroexp--expand-all body)
-                                        (macroexp--all-clauses handlers 1)
-                                        (cddr form))
-                        (cdr form))
-        form))
-      (`(,(or `defvar `defconst) . ,_) (macroexp--all-forms form 2))
-      (`(function ,(and f `(lambda . ,_)))
-       (macroexp--cons 'function
-                       (macroexp--cons (macroexp--all-forms f 2)
-                                       nil
-                                       (cdr form))
-                       form))
-      (`(,(or `function `quote) . ,_) form)
-      (`(,(and fun (or `let `let*)) . ,(or `(,bindings . ,body) dontcare))
-       (macroexp--cons fun
-                       (macroexp--cons (macroexp--all-clauses bindings 1)
-                                       (macroexp--all-forms body)
-                                       (cdr form))
-                       form))
-      (`(,(and fun `(lambda . ,_)) . ,args)
-       ;; Embedded lambda in function position.
-       (macroexp--cons (macroexp--all-forms fun 2)
-                       (macroexp--all-forms args)
-                       form))
-      ;; The following few cases are for normal function calls that
-      ;; are known to funcall one of their arguments.  The byte
-      ;; compiler has traditionally handled these functions specially
-      ;; by treating a lambda expression quoted by `quote' as if it
-      ;; were quoted by `function'.  We make the same transformation
-      ;; here, so that any code that cares about the difference will
-      ;; see the same transformation.
-      ;; First arg is a function:
-      (`(,(and fun (or `funcall `apply `mapcar `mapatoms `mapconcat `mapc))
-         ',(and f `(lambda . ,_)) . ,args)
-       (macroexp--warn-and-return
-        (format "%s quoted with ' rather than with #'"
-                (list 'lambda (nth 1 f) '...))
-        (macroexp--expand-all `(,fun ,f . ,args))))
-      ;; Second arg is a function:
-      (`(,(and fun (or `sort)) ,arg1 ',(and f `(lambda . ,_)) . ,args)
-       (macroexp--warn-and-return
-        (format "%s quoted with ' rather than with #'"
-                (list 'lambda (nth 1 f) '...))
-        (macroexp--expand-all `(,fun ,arg1 ,f . ,args))))
-      (`(funcall #',(and f (pred symbolp)) . ,args)
-       ;; Rewrite (funcall #'foo bar) to (foo bar), in case `foo'
-       ;; has a compiler-macro.
-       (macroexp--expand-all `(,f . ,args)))
-      (`(,func . ,_)
-       ;; Macro expand compiler macros.  This cannot be delayed to
-       ;; byte-optimize-form because the output of the compiler-macro can
-       ;; use macros.
-       (let ((handler (function-get func 'compiler-macro)))
-         (if (null handler)
-             ;; No compiler macro.  We just expand each argument (for
-             ;; setq/setq-default this works alright because the variable names
-             ;; are symbols).
-             (macroexp--all-forms form 1)
-           ;; If the handler is not loaded yet, try (auto)loading the
-           ;; function itself, which may in turn load the handler.
-           (unless (functionp handler)
-             (with-demoted-errors "macroexp--expand-all: %S"
-               (autoload-do-load (indirect-function func) func)))
-           (let ((newform (macroexp--compiler-macro handler form)))
-             (if (eq form newform)
-                 ;; The compiler macro did not find anything to do.
-                 (if (equal form (setq newform (macroexp--all-forms form 1)))
-                     form
-                   ;; Maybe after processing the args, some new opportunities
-                   ;; appeared, so let's try the compiler macro again.
-                   (setq form (macroexp--compiler-macro handler newform))
-                   (if (eq newform form)
-                       newform
-                     (macroexp--expand-all newform)))
-               (macroexp--expand-all newform))))))
-
-      (_ form))))
-
-;;;###autoload
-(defun macroexpand-all (form &optional environment)
-  "Return result of expanding macros at all levels in FORM.
-If no macros are expanded, FORM is returned unchanged.
-The second optional arg ENVIRONMENT specifies an environment of macro
-definitions to shadow the loaded ones for use in file byte-compilation."
-  (let ((macroexpand-all-environment environment))
-    (macroexp--expand-all form)))
+  (macroexpand-all form macroexpand-all-environment))
 
 ;;; Handy functions to use in macros.
 
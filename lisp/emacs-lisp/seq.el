@@ -287,6 +287,10 @@ list."
                                exclude))
                            sequence))))
 
+;; faster implementation for sequences (sequencep)
+(cl-defmethod seq-filter (pred (sequence sequence))
+  (seq-filter-native pred sequence))
+
 (cl-defgeneric seq-remove (pred sequence)
   "Return a list of all the elements for which (PRED element) is nil in SEQUENCE."
   (seq-filter (lambda (elt) (not (funcall pred elt)))
@@ -308,6 +312,10 @@ If SEQUENCE is empty, return INITIAL-VALUE and FUNCTION is not called."
         (setq acc (funcall function acc elt)))
       acc)))
 
+;; faster implementation for sequences (sequencep)
+(cl-defmethod seq-reduce (function (sequence sequence) initial-value)
+  (seq-reduce-native function sequence initial-value))
+
 (cl-defgeneric seq-every-p (pred sequence)
   "Return non-nil if (PRED element) is non-nil for all elements of SEQUENCE."
   (catch 'seq--break
@@ -381,6 +389,10 @@ TESTFN is used to compare elements, or `equal' if TESTFN is nil."
         (setq result (cons elt result))))
     (nreverse result)))
 
+;; faster implementation for sequences (sequencep)
+(cl-defmethod seq-uniq ((sequence sequence) &optional testfn)
+  (seq-uniq-native sequence testfn))
+
 (cl-defgeneric seq-mapcat (function sequence &optional type)
   "Concatenate the result of applying FUNCTION to each element of SEQUENCE.
 The result is a sequence of type TYPE, or a list if TYPE is nil."
@@ -418,6 +430,12 @@ Equality is defined by TESTFN if non-nil or by `equal' if nil."
               (seq-reverse sequence1)
               '()))
 
+;; faster implementation for sequences (sequencep)
+(cl-defmethod seq-difference ((sequence1 sequence) sequence2 &optional testfn)
+  (if (sequencep sequence2)
+      (seq-difference-native sequence1 sequence2 testfn)
+    (cl-call-next-method)))
+
 (cl-defgeneric seq-group-by (function sequence)
   "Apply FUNCTION to each element of SEQUENCE.
 Separate the elements of SEQUENCE into an alist using the results as
@@ -433,6 +451,13 @@ keys.  Keys are compared using `equal'."
    (seq-reverse sequence)
    nil))
 
+;; faster implementation for sequences (sequencep)
+(cl-defmethod seq-group-by (function (sequence sequence))
+  (let ((result nil))
+    (maphash (lambda (key elts) (push (cons key elts) result))
+             (seq-group-by-native function sequence))
+    result))
+
 (cl-defgeneric seq-min (sequence)
   "Return the smallest element of SEQUENCE.
 SEQUENCE must be a sequence of numbers or markers."
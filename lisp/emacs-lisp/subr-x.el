@@ -205,8 +205,6 @@ Like `when-let*' except SPEC can have the form (SYMBOL VALUEFORM)."
   "Join all STRINGS using SEPARATOR."
   (mapconcat 'identity strings separator))
 
-(define-obsolete-function-alias 'string-reverse 'reverse "25.1")
-
 (defsubst string-trim-left (string &optional regexp)
   "Trim STRING of leading string matching REGEXP.
 
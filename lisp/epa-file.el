@@ -49,6 +49,20 @@ encryption is used."
 		 (const :tag "Don't ask" silent))
   :group 'epa-file)
 
+(defcustom epa-file-use-native-symmetric-encryption nil
+  "If non-nil, encrypt and decrypt with a passphrase without GnuPG.
+Files are then read with `decrypt-region-native' and written with
+`encrypt-region-native' as OpenPGP messages, and the passphrase is
+read in the minibuffer instead of by pinentry.  This applies when
+writing files that have no recipients, and files that can't be
+read natively are still decrypted by GnuPG.
+
+This needs Emacs built with OpenPGP support, see
+`native-encryption-formats'; otherwise GnuPG is used anyway."
+  :type 'boolean
+  :group 'epa-file
+  :version "27.1")
+
 (defvar epa-file-passphrase-alist nil)
 
 (eval-and-compile
@@ -82,6 +96,57 @@ encryption is used."
 		passphrase))))
     (epa-passphrase-callback-function context key-id file)))
 
+;; Passphrases for native encryption are cached like the ones GnuPG
+;; asks for.
+(defun epa-file--native-passphrase (file &optional confirm)
+  (let ((entry (and epa-file-cache-passphrase-for-symmetric-encryption
+		    (assoc (file-truename file) epa-file-passphrase-alist))))
+    (or (copy-sequence (cdr entry))
+	(let ((passphrase
+	       (read-passwd (format "Passphrase for %s: " file) confirm)))
+	  (when epa-file-cache-passphrase-for-symmetric-encryption
+	    (unless entry
+	      (setq entry (list (file-truename file)))
+	      (push entry epa-file-passphrase-alist))
+	    (setcdr entry (copy-sequence passphrase)))
+	  passphrase))))
+
+(defun epa-file--native-p ()
+  "Return non-nil if OpenPGP files are to be encrypted natively."
+  (and epa-file-use-native-symmetric-encryption
+       (fboundp 'native-encryption-formats)
+       (memq 'openpgp (native-encryption-formats))))
+
+(defun epa-file--native-decrypt (file)
+  "Return the decrypted contents of FILE as a unibyte string.
+Return nil if native encryption is not used or FILE can't be
+decrypted natively."
+  (when (epa-file--native-p)
+    (condition-case nil
+	(with-temp-buffer
+	  (set-buffer-multibyte nil)
+	  (insert-file-contents-literally file)
+	  (let ((coding-system-for-read 'binary))
+	    (decrypt-region-native (point-min) (point-max)
+				   (epa-file--native-passphrase file)))
+	  (buffer-string))
+      (error
+       (let ((entry (assoc (file-truename file) epa-file-passphrase-alist)))
+	 (if entry
+	     (setcdr entry nil)))
+       nil))))
+
+(defun epa-file--native-encrypt (string file)
+  "Return STRING, a unibyte string, encrypted for FILE."
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert string)
+    (let ((coding-system-for-write 'binary))
+      (encrypt-region-native (point-min) (point-max)
+			     (epa-file--native-passphrase file t)
+			     'openpgp epa-armor))
+    (buffer-string)))
+
 (defvar epa-inhibit nil
   "Non-nil means don't try to decrypt .gpg files when operating on them.")
 
@@ -153,7 +218,8 @@ encryption is used."
 	  (if replace
 	      (goto-char (point-min)))
 	  (condition-case error
-	      (setq string (epg-decrypt-file context local-file nil))
+	      (setq string (or (epa-file--native-decrypt local-file)
+			       (epg-decrypt-file context local-file nil)))
 	    (error
 	     (if (setq entry (assoc file epa-file-passphrase-alist))
 		 (setcdr entry nil))
@@ -238,9 +304,7 @@ encryption is used."
     (setf (epg-context-armor context) epa-armor)
     (setf (epg-context-pinentry-mode context) epa-pinentry-mode)
     (condition-case error
-	(setq string
-	      (epg-encrypt-string
-	       context
+	(let ((plaintext
 	       (if (stringp start)
 		   (epa-file--encode-coding-string start coding-system)
 		 (unless start
@@ -257,18 +321,24 @@ encryption is used."
 		   (format-encode-buffer (with-current-buffer buffer
 					   buffer-file-format))
 		   (epa-file--encode-coding-string (buffer-string)
-						   coding-system)))
-	       (if (or (eq epa-file-select-keys t)
-		       (and (null epa-file-select-keys)
-			    (not (local-variable-p 'epa-file-encrypt-to
-						   (current-buffer)))))
-		   (epa-select-keys
-		    context
-		    "Select recipients for encryption.
+						   coding-system)))))
+	  (setq string
+		(if (and (epa-file--native-p) (null recipients))
+		    (epa-file--native-encrypt plaintext file)
+		  (epg-encrypt-string
+		   context
+		   plaintext
+		   (if (or (eq epa-file-select-keys t)
+			   (and (null epa-file-select-keys)
+				(not (local-variable-p 'epa-file-encrypt-to
+						       (current-buffer)))))
+		       (epa-select-keys
+			context
+			"Select recipients for encryption.
 If no one is selected, symmetric encryption will be performed.  "
-		    recipients)
-		 (if epa-file-encrypt-to
-		     (epg-list-keys context recipients)))))
+			recipients)
+		     (if epa-file-encrypt-to
+			 (epg-list-keys context recipients)))))))
       (error
        (epa-display-error context)
        (if (setq entry (assoc file epa-file-passphrase-alist))
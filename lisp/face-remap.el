@@ -313,6 +313,32 @@ See `text-scale-increase' for more details."
   (interactive "p")
   (text-scale-increase (- dec)))
 
+;; The `text-scale-mode-amount' when the current pinch began.
+(defvar text-scale--pinch-start-amount nil)
+
+;;;###autoload
+(defun text-scale-pinch (event)
+  "Adjust the height of the default face for the pinch gesture EVENT.
+Spreading the fingers apart makes the text bigger, and pinching them
+together makes it smaller, in proportion to the change of the
+distance between them since the start of the gesture."
+  (interactive "e")
+  (let* ((touch (nth 2 event))
+         (window (posn-window (event-start event)))
+         (scale (plist-get touch :scale)))
+    (with-current-buffer (if (windowp window) (window-buffer window)
+                           (current-buffer))
+      (when (or (eq (plist-get touch :phase) 'begin)
+                (null text-scale--pinch-start-amount))
+        (setq text-scale--pinch-start-amount
+              (if text-scale-mode text-scale-mode-amount 0)))
+      (when (and (numberp scale) (> scale 0))
+        (text-scale-set
+         (+ text-scale--pinch-start-amount
+            (/ (log scale) (log text-scale-mode-step)))))
+      (when (eq (plist-get touch :phase) 'end)
+        (setq text-scale--pinch-start-amount nil)))))
+
 ;;;###autoload (define-key ctl-x-map [(control ?+)] 'text-scale-adjust)
 ;;;###autoload (define-key ctl-x-map [(control ?-)] 'text-scale-adjust)
 ;;;###autoload (define-key ctl-x-map [(control ?=)] 'text-scale-adjust)
@@ -7161,31 +7161,25 @@ based on existing mode bits, as in \"og+rX-w\"."
 ;; Trashcan handling.
 (defcustom trash-directory nil
   "Directory for `move-file-to-trash' to move files and directories to.
-This directory is only used when the function `system-move-file-to-trash'
-is not defined.
 Relative paths are interpreted relative to `default-directory'.
-If the value is nil, Emacs uses a freedesktop.org-style trashcan."
+If the value is nil, Emacs uses the trash of the system; see
+`move-file-to-trash'."
   :type  '(choice (const nil) directory)
   :group 'auto-save
   :version "23.2")
 
-(defvar trash--hexify-table)
-
-(declare-function system-move-file-to-trash "w32fns.c" (filename))
-
 (defun move-file-to-trash (filename)
   "Move the file (or directory) named FILENAME to the trash.
 When `delete-by-moving-to-trash' is non-nil, this function is
 called by `delete-file' and `delete-directory' instead of
 deleting files outright.
 
-If the function `system-move-file-to-trash' is defined, call it
- with FILENAME as an argument.
-Otherwise, if `trash-directory' is non-nil, move FILENAME to that
- directory.
-Otherwise, trash FILENAME using the freedesktop.org conventions,
- like the GNOME, KDE and XFCE desktop environments.  Emacs only
- moves files to \"home trash\", ignoring per-volume trashcans."
+If `trash-directory' is non-nil, move FILENAME to that directory.
+Otherwise, call `system-move-file-to-trash', which moves FILENAME
+ to the recycle bin on MS-Windows and to ~/.Trash on macOS.
+ Elsewhere it follows the freedesktop.org conventions, like the
+ GNOME, KDE and XFCE desktop environments, using the trash can of
+ the file system FILENAME is on when there is one."
   (interactive "fMove file to trash: ")
   (cond (trash-directory
 	 ;; If `trash-directory' is non-nil, move the file there.
@@ -7206,91 +7200,9 @@ Otherwise, trash FILENAME using the freedesktop.org conventions,
 		 (setq new-fn (car (find-backup-file-name new-fn)))))
 	   (let (delete-by-moving-to-trash)
 	     (rename-file fn new-fn))))
-	;; If `system-move-file-to-trash' is defined, use it.
-	((fboundp 'system-move-file-to-trash)
-	 (system-move-file-to-trash filename))
-	;; Otherwise, use the freedesktop.org method, as specified at
-	;; http://freedesktop.org/wiki/Specifications/trash-spec
+	;; Otherwise, use the trash of the system.
 	(t
-	 (let* ((xdg-data-dir
-		 (directory-file-name
-		  (expand-file-name "Trash"
-				    (or (getenv "XDG_DATA_HOME")
-					"~/.local/share"))))
-		(trash-files-dir (expand-file-name "files" xdg-data-dir))
-		(trash-info-dir (expand-file-name "info" xdg-data-dir))
-		(fn (directory-file-name (expand-file-name filename))))
-
-	   ;; Check if we have permissions to delete.
-	   (unless (file-writable-p (directory-file-name
-				     (file-name-directory fn)))
-	     (error "Cannot move %s to trash: Permission denied" filename))
-	   ;; The trashed file cannot be the trash dir or its parent.
-	   (if (string-prefix-p fn trash-files-dir)
-	       (error "The trash directory %s is a subdirectory of %s"
-		      trash-files-dir filename))
-	   (if (string-prefix-p fn trash-info-dir)
-	       (error "The trash directory %s is a subdirectory of %s"
-		      trash-info-dir filename))
-
-	   ;; Ensure that the trash directory exists; otherwise, create it.
-	   (with-file-modes #o700
-	     (unless (file-exists-p trash-files-dir)
-	       (make-directory trash-files-dir t))
-	     (unless (file-exists-p trash-info-dir)
-	       (make-directory trash-info-dir t)))
-
-	   ;; Try to move to trash with .trashinfo undo information
-	   (save-excursion
-	     (with-temp-buffer
-	       (set-buffer-file-coding-system 'utf-8-unix)
-	       (insert "[Trash Info]\nPath=")
-	       ;; Perform url-encoding on FN.  For compatibility with
-	       ;; other programs (e.g. XFCE Thunar), allow literal "/"
-	       ;; for path separators.
-	       (unless (boundp 'trash--hexify-table)
-		 (setq trash--hexify-table (make-vector 256 nil))
-		 (let ((unreserved-chars
-			(list ?/ ?a ?b ?c ?d ?e ?f ?g ?h ?i ?j ?k ?l ?m
-			      ?n ?o ?p ?q ?r ?s ?t ?u ?v ?w ?x ?y ?z ?A
-			      ?B ?C ?D ?E ?F ?G ?H ?I ?J ?K ?L ?M ?N ?O
-			      ?P ?Q ?R ?S ?T ?U ?V ?W ?X ?Y ?Z ?0 ?1 ?2
-			      ?3 ?4 ?5 ?6 ?7 ?8 ?9 ?- ?_ ?. ?! ?~ ?* ?'
-			      ?\( ?\))))
-		   (dotimes (byte 256)
-		     (aset trash--hexify-table byte
-			   (if (memq byte unreserved-chars)
-			       (char-to-string byte)
-			     (format "%%%02x" byte))))))
-	       (mapc (lambda (byte)
-		       (insert (aref trash--hexify-table byte)))
-		     (if (multibyte-string-p fn)
-			 (encode-coding-string fn 'utf-8)
-		       fn))
-	       (insert "\nDeletionDate="
-		       (format-time-string "%Y-%m-%dT%T")
-		       "\n")
-
-	       ;; Make a .trashinfo file.  Use O_EXCL, as per trash-spec 1.0.
-	       (let* ((files-base (file-name-nondirectory fn))
-		      (info-fn (expand-file-name
-				(concat files-base ".trashinfo")
-				trash-info-dir)))
-		 (condition-case nil
-		     (write-region nil nil info-fn nil 'quiet info-fn 'excl)
-		   (file-already-exists
-		    ;; Uniquify new-fn.  Some file managers do not
-		    ;; like Emacs-style backup file names.  E.g.:
-		    ;; https://bugs.kde.org/170956
-		    (setq info-fn (make-temp-file
-				   (expand-file-name files-base trash-info-dir)
-				   nil ".trashinfo"))
-		    (setq files-base (file-name-nondirectory info-fn))
-		    (write-region nil nil info-fn nil 'quiet info-fn)))
-		 ;; Finally, try to move the file to the trashcan.
-		 (let ((delete-by-moving-to-trash nil)
-		       (new-fn (expand-file-name files-base trash-files-dir)))
-		   (rename-file fn new-fn)))))))))
+	 (system-move-file-to-trash filename))))
 
 (defsubst file-attribute-type (attributes)
   "The type field in ATTRIBUTES returned by `file-attributes'.
@@ -4588,7 +4588,9 @@ commands:
   (dolist (timer (and (boundp 'timer-list)
 		      timer-list))
     (when (eq (timer--function timer) 'image-animate-timeout)
-      (cancel-timer timer))))
+      (cancel-timer timer)))
+  (when (boundp 'image--animations)
+    (mapc #'image-animate-stop (mapcar #'cdr image--animations))))
 
 (defun gnus-stop-downloads ()
   (when (boundp 'url-queue)
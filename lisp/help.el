@@ -851,6 +851,39 @@ runs the command %S%s, which is "
                                               (format " (found in %s)" key-locus-up-tricky)
                                             "")))
 	    (describe-function-1 defn-up-tricky)))))))
+
+(defun describe-key-translation-pipeline (key)
+  "Display how KEY is translated and looked up when it is read.
+KEY is a key sequence as it comes from the terminal.  Show in turn
+what `input-decode-map', `local-function-key-map' and
+`key-translation-map' make of it, in the order `read-key-sequence'
+applies them, then the binding of the translated key and the command
+it is remapped to, if any.  Translations that are made by calling a
+function are shown, but the function is not called.
+See `key-translation-pipeline'."
+  (interactive
+   (list (progn (read-key-sequence-vector "Describe translation of key: "
+                                          nil t)
+                (this-single-command-raw-keys))))
+  (help-setup-xref (list #'describe-key-translation-pipeline key)
+                   (called-interactively-p 'interactive))
+  (with-help-window (help-buffer)
+    (princ (format "%s is read as follows:\n\n" (key-description key)))
+    (dolist (step (key-translation-pipeline key))
+      (pcase step
+        (`(binding ,keys ,binding)
+         (princ (format "%s %s\n" (key-description keys)
+                        (cond ((null binding) "is undefined")
+                              ((keymapp binding) "is a prefix key")
+                              (t (format "runs `%S'" binding))))))
+        (`(remap ,command ,remapped)
+         (princ (format "`%S' is remapped to `%S'\n" command remapped)))
+        (`(,map ,from ,(and to (pred arrayp)))
+         (princ (format "`%s' translates %s to %s\n" map
+                        (key-description from) (key-description to))))
+        (`(,map ,from ,function)
+         (princ (format "`%s' translates part of %s by calling %S\n"
+                        map (key-description from) function)))))))
 
 (defun describe-mode (&optional buffer)
   "Display documentation of current major mode and minor modes.
@@ -829,6 +829,14 @@ to produce the command line options to pass to the hexl command."
       (setq opts (format "%s -group-by-%d-bits " opts hexl-bits)) )
     opts))
 
+(defun hexl--use-native-p ()
+  "Return non-nil if the hex dump can be converted without `hexl-program'.
+This is the case when the conversion functions are built in and
+`hexl-program' is the standard `hexl' program, whose output they
+reproduce."
+  (and (fboundp 'hexlify-region-native)
+       (equal hexl-program "hexl")))
+
 ;;;###autoload
 (defun hexlify-buffer ()
   "Convert a binary buffer to hexl format.
@@ -842,16 +850,19 @@ This discards the buffer's undo information."
   (let ((coding-system-for-read 'raw-text)
 	(coding-system-for-write buffer-file-coding-system)
 	(buffer-undo-list t))
-    (apply 'call-process-region (point-min) (point-max)
-	   (expand-file-name hexl-program exec-directory)
-	   t t nil
-           ;; Manually encode the args, otherwise they're encoded using
-           ;; coding-system-for-write (i.e. buffer-file-coding-system) which
-           ;; may not be what we want (e.g. utf-16 on a non-utf-16 system).
-           (mapcar (lambda (s)
-                     (if (not (multibyte-string-p s)) s
-                       (encode-coding-string s locale-coding-system)))
-                   (split-string (hexl-options))))
+    (if (hexl--use-native-p)
+        (hexlify-region-native (point-min) (point-max) hexl-bits
+                               (member "-iso" (split-string (hexl-options))))
+      (apply 'call-process-region (point-min) (point-max)
+             (expand-file-name hexl-program exec-directory)
+             t t nil
+             ;; Manually encode the args, otherwise they're encoded using
+             ;; coding-system-for-write (i.e. buffer-file-coding-system) which
+             ;; may not be what we want (e.g. utf-16 on a non-utf-16 system).
+             (mapcar (lambda (s)
+                       (if (not (multibyte-string-p s)) s
+                         (encode-coding-string s locale-coding-system)))
+                     (split-string (hexl-options)))))
     (if (> (point) (hexl-address-to-marker hexl-max-address))
 	(hexl-goto-address hexl-max-address))))
 
@@ -866,9 +877,11 @@ This discards the buffer's undo information."
   (let ((coding-system-for-write 'raw-text)
 	(coding-system-for-read buffer-file-coding-system)
 	(buffer-undo-list t))
-    (apply 'call-process-region (point-min) (point-max)
-	   (expand-file-name hexl-program exec-directory)
-	   t t nil "-de" (split-string (hexl-options)))))
+    (if (hexl--use-native-p)
+        (dehexlify-region-native (point-min) (point-max) hexl-bits)
+      (apply 'call-process-region (point-min) (point-max)
+             (expand-file-name hexl-program exec-directory)
+             t t nil "-de" (split-string (hexl-options))))))
 
 (defun hexl-char-after-point ()
   "Return char for ASCII hex digits at point."
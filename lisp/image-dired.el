@@ -812,8 +812,38 @@ Queued items live in `image-dired-queue'."
   (while (and image-dired-queue
               (< image-dired-queue-active-jobs
                  image-dired-queue-active-limit))
-    (cl-incf image-dired-queue-active-jobs)
-    (apply #'image-dired-create-thumb-1 (pop image-dired-queue))))
+    (let ((job (pop image-dired-queue)))
+      (if (and (fboundp 'image-resize-native)
+               (not (executable-find
+                     image-dired-cmd-create-thumbnail-program)))
+          (apply #'image-dired-create-thumb-native job)
+        (cl-incf image-dired-queue-active-jobs)
+        (apply #'image-dired-create-thumb-1 job)))))
+
+(defun image-dired-create-thumb-native (original-file thumbnail-file)
+  "For ORIGINAL-FILE, create thumbnail image named THUMBNAIL-FILE.
+Unlike `image-dired-create-thumb-1', this scales the image in Emacs
+with `image-resize-native', for when
+`image-dired-cmd-create-thumbnail-program' is not installed."
+  (let ((thumbnail-dir (file-name-directory thumbnail-file)))
+    (when (not (file-exists-p thumbnail-dir))
+      (message "Creating thumbnail directory")
+      (make-directory thumbnail-dir t)
+      (set-file-modes thumbnail-dir #o700))
+    (condition-case err
+        (let ((thumb (image-resize-native
+                      (list 'image :file original-file)
+                      (image-dired-thumb-size 'width)
+                      (image-dired-thumb-size 'height)))
+              (coding-system-for-write 'no-conversion))
+          (write-region (plist-get (cdr thumb) :data) nil thumbnail-file
+                        nil 'silent)
+          (set-file-modes thumbnail-file #o600)
+          (clear-image-cache thumbnail-file))
+      (error
+       (message "Thumb could not be created for %s: %s"
+                (abbreviate-file-name original-file)
+                (error-message-string err))))))
 
 (defun image-dired-create-thumb (original-file thumbnail-file)
   "Add a job for generating thumbnail to `image-dired-queue'."
@@ -2091,6 +2121,14 @@ default value at the prompt."
 
 (defun image-dired-get-exif-data (file tag-name)
   "From FILE, return EXIF tag TAG-NAME."
+  (if (and (fboundp 'image-file-metadata)
+           (not (executable-find image-dired-cmd-read-exif-data-program)))
+      (or (cdr (assoc tag-name (plist-get (image-file-metadata file) :exif)))
+          "")
+    (image-dired-get-exif-data-1 file tag-name)))
+
+(defun image-dired-get-exif-data-1 (file tag-name)
+  "From FILE, return EXIF tag TAG-NAME, using an external program."
   (image-dired--check-executable-exists
    'image-dired-cmd-read-exif-data-program)
   (let ((buf (get-buffer-create "*image-dired-get-exif-data*"))
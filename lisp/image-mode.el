@@ -485,7 +485,7 @@ call."
 	"--"
 	["Animate Image" image-toggle-animation :style toggle
 	 :selected (let ((image (image-get-display-property)))
-		     (and image (image-animate-timer image)))
+		     (and image (image-animating-p image)))
 	 :active image-multi-frame
          :help "Toggle image animation"]
 	["Loop Animation"
@@ -494,7 +494,7 @@ call."
 	   ;; FIXME this is a hacky way to make it affect a currently
 	   ;; animating image.
 	   (when (let ((image (image-get-display-property)))
-		   (and image (image-animate-timer image)))
+		   (and image (image-animating-p image)))
 	     (image-toggle-animation)
 	     (image-toggle-animation)))
 	 :style toggle :selected image-animate-loop
@@ -553,6 +553,7 @@ Key bindings:
 
 	(kill-all-local-variables)
 	(setq major-mode 'image-mode)
+	(image-mode--auto-orient)
 
 	(if (not (image-get-display-property))
 	    (progn
@@ -863,9 +864,9 @@ Otherwise it plays once, then stops."
      ((null (setq animation (image-multi-frame-p image)))
       (message "No image animation."))
      (t
-      (let ((timer (image-animate-timer image)))
-	(if timer
-	    (cancel-timer timer)
+      (let ((animating (image-animating-p image)))
+	(if animating
+	    (image-animate-stop image)
 	  (let ((index (plist-get (cdr image) :index)))
 	    ;; If we're at the end, restart.
 	    (and index
@@ -891,7 +892,7 @@ If SPEED is `reset', reset the magnitude of the speed to 1."
 		multiply nil))
       (image-animate-set-speed image speed multiply)
       ;; FIXME Hack to refresh an active image.
-      (when (image-animate-timer image)
+      (when (image-animating-p image)
 	(image-toggle-animation)
 	(image-toggle-animation))
       (message "Image speed is now %s" (image-animate-get-speed image))))))
@@ -1217,6 +1218,26 @@ Emacs is compiled with ImageMagick support."
   (setq image-transform-rotation (float (mod rotation 360)))
   (image-toggle-display-image))
 
+(defcustom image-auto-orient t
+  "Non-nil means rotate images as the orientation in their EXIF data says.
+This has no effect unless Emacs is compiled with
+ImageMagick support."
+  :type 'boolean
+  :version "27.1"
+  :group 'image)
+
+(defun image-mode--auto-orient ()
+  "Set `image-transform-rotation' from the EXIF orientation of the file."
+  (when (and image-auto-orient buffer-file-name
+             (fboundp 'image-file-metadata))
+    (let ((orientation (plist-get (ignore-errors
+                                    (image-file-metadata buffer-file-name))
+                                  :orientation)))
+      (pcase orientation
+        (3 (setq-local image-transform-rotation 180.0))
+        (6 (setq-local image-transform-rotation 90.0))
+        (8 (setq-local image-transform-rotation 270.0))))))
+
 (defun image-transform-reset ()
   "Display the current image with the default size and rotation.
 This command has no effect unless Emacs is compiled with
@@ -711,6 +711,13 @@ in which case you might want to use `image-default-frame-delay'."
 
 (make-obsolete 'image-animated-p 'image-multi-frame-p "24.4")
 
+(defvar image--animations nil
+  "Alist of (HANDLE . IMAGE) for the images animated natively.
+HANDLE is the value of `image-animation-start' for IMAGE.")
+
+(defvar image--animation-timer nil
+  "The timer that shows the due frames of `image--animations'.")
+
 ;; "Destructively"?
 (defun image-animate (image &optional index limit)
   "Start animating IMAGE.
@@ -720,15 +727,61 @@ With optional INDEX, begin animating from that animation frame.
 LIMIT specifies how long to animate the image.  If omitted or
 nil, play the animation until the end.  If t, loop forever.  If a
 number, play until that number of seconds has elapsed."
-  (let ((animation (image-multi-frame-p image))
-	timer)
+  (let ((animation (image-multi-frame-p image)))
     (when animation
-      (if (setq timer (image-animate-timer image))
-	  (cancel-timer timer))
+      (image-animate-stop image)
       (plist-put (cdr image) :animate-buffer (current-buffer))
-      (run-with-timer 0.2 nil #'image-animate-timeout
-		      image (or index 0) (car animation)
-		      0 limit (+ (float-time) 0.2)))))
+      (let ((handle (and (fboundp 'image-animation-start)
+                         (image-animation-start image index limit))))
+        (if handle
+            (progn
+              (push (cons handle image) image--animations)
+              (image--animation-schedule 0))
+          (run-with-timer 0.2 nil #'image-animate-timeout
+                          image (or index 0) (car animation)
+                          0 limit (+ (float-time) 0.2)))))))
+
+(defun image--animation-schedule (delay)
+  "Run `image--animation-run' in DELAY seconds."
+  (when image--animation-timer
+    (cancel-timer image--animation-timer))
+  (setq image--animation-timer
+        (run-with-timer delay nil #'image--animation-run)))
+
+(defun image--animation-run ()
+  "Show the frames of `image--animations' that are due.
+One timer serves all the animations; `image-animation-tick' decides
+which frames to show, skipping frames if Emacs was too busy to show
+them in time."
+  (setq image--animation-timer nil)
+  (dolist (elt image--animations)
+    (unless (buffer-live-p (plist-get (cddr elt) :animate-buffer))
+      (image-animation-stop (car elt))
+      (setq image--animations (delq elt image--animations))))
+  (let ((result (image-animation-tick)))
+    (dolist (flip (cdr result))
+      (let ((elt (assq (car flip) image--animations)))
+        (cond ((null elt))
+              ((cdr flip) (image-show-frame (cdr elt) (cdr flip) t))
+              (t (setq image--animations (delq elt image--animations))))))
+    (when (car result)
+      (image--animation-schedule (car result)))))
+
+(defun image-animate-stop (image)
+  "Stop animating IMAGE."
+  (let ((timer (image-animate-timer image))
+        (elt (rassq image image--animations)))
+    (when timer
+      (cancel-timer timer))
+    (when elt
+      (image-animation-stop (car elt))
+      (setq image--animations (delq elt image--animations)))))
+
+(defun image-animating-p (image)
+  "Return non-nil if IMAGE is being animated."
+  (and (or (rassq image image--animations)
+           (image-animate-timer image))
+       t))
 
 (defun image-animate-timer (image)
   "Return the animation timer for image IMAGE."
@@ -246,6 +246,15 @@ See `imenu--index-alist' for the format of the buffer index alist.")
 ;;;###autoload
 (make-variable-buffer-local 'imenu-create-index-function)
 
+;;;###autoload
+(defvar-local imenu-native-language nil
+  "The queries to index the current buffer with natively, or nil.
+If non-nil, this is the LANGUAGE argument of `imenu-build-index-native'
+\(a symbol in `imenu-native-languages', or a list of queries) and the
+index is built by `imenu-native-create-index' instead of
+`imenu-create-index-function'.")
+;;;###autoload(put 'imenu-native-language 'safe-local-variable 'symbolp)
+
 ;;;###autoload
 (defvar imenu-prev-index-position-function 'beginning-of-defun
   "Function for finding the next index position.
@@ -584,6 +593,34 @@ NOT share structure with ALIST."
 	    (imenu--truncate-items (cdr item))))
 	menulist))
 
+;; The index of the buffer text as of a modification tick, (TICK . INDEX),
+;; and the native job building the index of later text, (TICK . JOB).
+(defvar-local imenu-native--index nil)
+(defvar-local imenu-native--job nil)
+
+(defun imenu-native-create-index (&optional nowait)
+  "Return the index of the current buffer found by `imenu-native-language'.
+The index is kept until the buffer text changes.  If NOWAIT is
+non-nil and the text has changed, start building the index on a
+separate thread and return the index of the old text meanwhile."
+  (let ((tick (buffer-chars-modified-tick)))
+    (unless (eql (car imenu-native--index) tick)
+      (when (and imenu-native--job
+                 (not (eql (car imenu-native--job) tick)))
+        (imenu-native-job-cancel (cdr imenu-native--job))
+        (setq imenu-native--job nil))
+      (unless imenu-native--job
+        (setq imenu-native--job
+              (cons tick (save-restriction
+                           (widen)
+                           (imenu-build-index-native imenu-native-language t)))))
+      (when (or (not nowait)
+                (imenu-native-job-finished-p (cdr imenu-native--job)))
+        (setq imenu-native--index
+              (cons tick (imenu-native-job-index (cdr imenu-native--job))))
+        (setq imenu-native--job nil)))
+    (cdr imenu-native--index)))
+
 (defun imenu--make-index-alist (&optional noerror)
   "Create an index alist for the definitions in the current buffer.
 This works by using the hook function `imenu-create-index-function'.
@@ -601,7 +638,10 @@ See `imenu--index-alist' for the format of the index alist."
 	      (save-excursion
 		(save-restriction
 		  (widen)
-		  (funcall imenu-create-index-function))))
+		  (if (and imenu-native-language
+			   (fboundp 'imenu-build-index-native))
+		      (copy-tree (imenu-native-create-index))
+		    (funcall imenu-create-index-function)))))
 	(imenu--truncate-items imenu--index-alist)))
   (or imenu--index-alist noerror
       (imenu-unavailable-error
@@ -2424,7 +2424,9 @@ It returns the number of characters changed."
 	(or (char-table-p val)
 	    (error "Invalid translation table name: %s" table))
 	(setq table val)))
-  (translate-region-internal start end table))
+  (if (stringp table)
+      (translate-region-native start end table)
+    (translate-region-internal start end table)))
 
 (defmacro with-category-table (table &rest body)
   "Execute BODY like `progn' with TABLE the current category table.
@@ -240,6 +240,17 @@ we will act as though we couldn't find a full name in the address."
   :type 'boolean
   :group 'mail-extr)
 
+(defcustom mail-extr-use-native-parser t
+  "Whether to parse addresses with `mail-parse-addresses'.
+That parser follows the grammar of RFC 5322, so quoted strings,
+comments and groups come out right, but it takes names as written.
+Addresses it can't parse, and any address when
+`mail-extr-guess-middle-initial' or `mail-extr-mangle-uucp' is
+non-nil, are still handled by the heuristics of this package."
+  :type 'boolean
+  :version "27.1"
+  :group 'mail-extr)
+
 ;; Matches a leading title that is not part of the name (does not
 ;; contribute to uniquely identifying the person).
 (defcustom mail-extr-full-name-prefixes
@@ -713,6 +724,57 @@ ADDRESS may be a string or a buffer.  If it is a buffer, the visible
 \(narrowed) portion of the buffer will be interpreted as the address.
 \(This feature exists so that the clever caller might be able to avoid
 consing a string.)"
+  (let ((mailboxes (and mail-extr-use-native-parser
+			(not mail-extr-guess-middle-initial)
+			(not mail-extr-mangle-uucp)
+			(mail-extr--parse-natively address))))
+    (if mailboxes
+	(let ((components (mapcar #'mail-extr--native-components mailboxes)))
+	  (if all components (car components)))
+      (mail-extr--heuristic-components address all))))
+
+(defun mail-extr--parse-natively (address)
+  "Return the mailboxes of ADDRESS, as `mail-parse-addresses' does.
+The mailboxes of groups are included.  Return nil if part of ADDRESS
+can't be parsed."
+  (let ((string (cond ((stringp address) address)
+		      ((bufferp address)
+		       (with-current-buffer address
+			 (buffer-substring-no-properties (point-min) (point-max))))
+		      (t (error "Invalid address: %s" address))))
+	mailboxes)
+    (catch 'invalid
+      (dolist (entry (mail-parse-addresses string))
+	(dolist (mailbox (if (plist-member entry :group)
+			     (plist-get entry :addresses)
+			   (list entry)))
+	  (when (plist-member mailbox :invalid)
+	    (throw 'invalid nil))
+	  (push mailbox mailboxes)))
+      (nreverse mailboxes))))
+
+(defun mail-extr--native-components (mailbox)
+  "Return the full name and address of MAILBOX, a parsed mailbox.
+A comment stands for the name if there is no display name."
+  (let* ((address (plist-get mailbox :address))
+	 (name (or (plist-get mailbox :name)
+		   (car (plist-get mailbox :comments))))
+	 (name (and name (mapconcat #'identity (split-string name) " "))))
+    (when (and name mail-extr-ignore-single-names)
+      (when (or (not (string-match-p "[- ]" name))
+		(and mail-extr-ignore-realname-equals-mailbox-name
+		     (eq t (compare-strings
+			    name nil nil
+			    address nil (string-match-p "@\\|\\'" address)
+			    t))))
+	(setq name nil)))
+    (list (and name (not (string= name "")) name)
+	  (and (not (string= address "")) address))))
+
+(defun mail-extr--heuristic-components (address all)
+  "Extract names and addresses from ADDRESS by heuristics.
+This is `mail-extract-address-components' for ADDRESS and ALL when
+`mail-parse-addresses' isn't used."
   (let ((canonicalization-buffer (get-buffer-create " *canonical address*"))
 	(extraction-buffer (get-buffer-create " *extract address components*"))
 	value-list)
@@ -42,50 +42,7 @@ them into characters should be done separately."
   (interactive
    ;; Let the user determine the coding system with "C-x RET c".
    (list (region-beginning) (region-end) coding-system-for-read))
-  (when (and coding-system
-	     (not (coding-system-p coding-system))) ; e.g. `ascii' from Gnus
-    (setq coding-system nil))
-  (save-excursion
-    (save-restriction
-      ;; RFC 2045:  ``An "=" followed by two hexadecimal digits, one
-      ;; or both of which are lowercase letters in "abcdef", is
-      ;; formally illegal. A robust implementation might choose to
-      ;; recognize them as the corresponding uppercase letters.''
-      (let ((case-fold-search t))
-	(narrow-to-region from to)
-	;; Do this in case we're called from Gnus, say, in a buffer
-	;; which already contains non-ASCII characters which would
-	;; then get doubly-decoded below.
-	(if coding-system
-	    (encode-coding-region (point-min) (point-max) coding-system))
-	(goto-char (point-min))
-	(while (and (skip-chars-forward "^=")
-		    (not (eobp)))
-	  (cond ((eq (char-after (1+ (point))) ?\n)
-		 (delete-char 2))
-		((looking-at "\\(=[0-9A-F][0-9A-F]\\)+")
-		 ;; Decode this sequence at once; i.e. by a single
-		 ;; deletion and insertion.
-		 (let* ((n (/ (- (match-end 0) (point)) 3))
-			(str (make-string n 0)))
-		   (dotimes (i n)
-                     (let ((n1 (char-after (1+ (point))))
-                           (n2 (char-after (+ 2 (point)))))
-                       (aset str i
-                             (+ (* 16 (- n1 (if (<= n1 ?9) ?0
-                                              (if (<= n1 ?F) (- ?A 10)
-                                                (- ?a 10)))))
-                                (- n2 (if (<= n2 ?9) ?0
-                                        (if (<= n2 ?F) (- ?A 10)
-                                          (- ?a 10)))))))
-		     (forward-char 3))
-		   (delete-region (match-beginning 0) (match-end 0))
-		   (insert str)))
-		(t
-		 (message "Malformed quoted-printable text")
-		 (forward-char)))))
-      (if coding-system
-	  (decode-coding-region (point-min) (point-max) coding-system)))))
+  (quoted-printable-decode-region-native from to coding-system))
 
 (defun quoted-printable-decode-string (string &optional coding-system)
   "Decode the quoted-printable encoded STRING and return the result.
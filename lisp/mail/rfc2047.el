@@ -1102,19 +1102,26 @@ strings are stripped."
 If ADDRESS-MIME is non-nil, strip backslashes which precede characters
 other than `\"' and `\\' in quoted strings."
   (if (string-match "=\\?" string)
-      (with-temp-buffer
-	;; We used to only call mm-enable-multibyte if `m' is non-nil,
-	;; but this can't be the right criterion.  Don't just revert this
-	;; change if it encounters a bug.  Please help me fix it
-	;; right instead.  --Stef
-	;; The string returned should always be multibyte in a multibyte
-	;; session, i.e. the buffer should be multibyte before
-	;; `buffer-string' is called.
-	(mm-enable-multibyte)
-	(insert string)
-	(inline
-	  (rfc2047-decode-region (point-min) (point-max) address-mime))
-	(buffer-string))
+      (if (and (not address-mime)
+	       (memq mail-parse-charset '(nil us-ascii gnus-decoded))
+	       rfc2047-allow-incomplete-encoded-text
+	       rfc2047-allow-irregular-q-encoded-words
+	       (not rfc2047-quote-decoded-words-containing-tspecials))
+	  ;; The common case, which is decoded natively.
+	  (rfc2047-decode-string-native string)
+	(with-temp-buffer
+	  ;; We used to only call mm-enable-multibyte if `m' is non-nil,
+	  ;; but this can't be the right criterion.  Don't just revert this
+	  ;; change if it encounters a bug.  Please help me fix it
+	  ;; right instead.  --Stef
+	  ;; The string returned should always be multibyte in a multibyte
+	  ;; session, i.e. the buffer should be multibyte before
+	  ;; `buffer-string' is called.
+	  (mm-enable-multibyte)
+	  (insert string)
+	  (inline
+	    (rfc2047-decode-region (point-min) (point-max) address-mime))
+	  (buffer-string)))
     (when address-mime
       (setq string
 	    (with-temp-buffer
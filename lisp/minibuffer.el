@@ -1201,6 +1201,72 @@ scroll the window of possible completions."
     (if (eq (car bounds) base) md-at-point
       (completion-metadata (substring string 0 base) table pred))))
 
+(defcustom completion-sort-by-frecency nil
+  "Non-nil means sort completions by how often and lately they were chosen.
+The completions chosen in `completing-read' are recorded for each
+completion category, or, for completions without a category, each
+minibuffer history variable.  When cycling and in the *Completions*
+buffer, the completions chosen most often and most lately come first.
+This has no effect if Emacs does not have `frecency-sort'."
+  :type 'boolean
+  :version "27.1")
+
+(defcustom completion-frecency-file
+  (locate-user-emacs-file "frecency.eld" ".emacs-frecency")
+  "File in which to keep the choices recorded for `completion-sort-by-frecency'.
+If nil, the choices are not kept between sessions."
+  :type '(choice (const :tag "Don't keep" nil)
+                 file)
+  :initialize #'custom-initialize-delay
+  :version "27.1")
+
+(defvar completion--frecency-loaded nil
+  "Non-nil if `completion-frecency-file' has been read.")
+
+(defun completion--frecency-p ()
+  "Return non-nil if completions are sorted by frecency.
+Read `completion-frecency-file' the first time."
+  (when (and completion-sort-by-frecency (fboundp 'frecency-sort))
+    (unless completion--frecency-loaded
+      (setq completion--frecency-loaded t)
+      (when (and completion-frecency-file
+                 (file-exists-p completion-frecency-file))
+        (with-demoted-errors "Error reading frecency data: %S"
+          (frecency-load completion-frecency-file)))
+      (add-hook 'kill-emacs-hook #'completion--frecency-save))
+    t))
+
+(defun completion--frecency-save ()
+  "Save the choices recorded for `completion-sort-by-frecency'."
+  (when completion-frecency-file
+    (frecency-save completion-frecency-file)))
+
+(defun completion--frecency-category (metadata hist)
+  "Return the category under which completions are recorded.
+This is the category in the completion METADATA, if any, or else the
+history variable HIST."
+  (or (completion-metadata-get metadata 'category)
+      (car-safe hist)
+      hist
+      'minibuffer-history))
+
+(defun completion--frecency-file-key (file)
+  "Return the absolute name under which FILE is recorded."
+  (expand-file-name (substitute-in-file-name file)))
+
+(defun completion--frecency-sort (completions metadata prefix)
+  "Return COMPLETIONS sorted by frecency.
+METADATA is the completion metadata and PREFIX the text in front of
+the completions."
+  (let ((category (completion--frecency-category
+                   metadata minibuffer-history-variable)))
+    (frecency-sort completions
+                   (when (eq category 'file)
+                     (lambda (completion)
+                       (completion--frecency-file-key
+                        (concat prefix completion))))
+                   category)))
+
 (defun completion-all-sorted-completions (&optional start end)
   (or completion-all-sorted-completions
       (let* ((start (or start (minibuffer-prompt-end)))
@@ -1239,6 +1305,10 @@ scroll the window of possible completions."
               (setq all (sort all (lambda (c1 c2)
                                     (> (length (member c1 hist))
                                        (length (member c2 hist))))))))
+          ;; And then the ones chosen often and lately.
+          (when (and (minibufferp) (completion--frecency-p))
+            (setq all (completion--frecency-sort
+                       all all-md (substring string 0 base-size))))
           ;; Cache the result.  This is not just for speed, but also so that
           ;; repeated calls to minibuffer-force-complete can cycle through
           ;; all possibilities.
@@ -1854,6 +1924,9 @@ variables.")
                   (if sort-fun
                       (funcall sort-fun completions)
                     (sort completions 'string-lessp))))
+          (when (and (minibufferp) (completion--frecency-p))
+            (setq completions (completion--frecency-sort
+                               completions all-md (or prefix ""))))
           (when afun
             (setq completions
                   (mapcar (lambda (s)
@@ -3417,6 +3490,14 @@ See `completing-read' for the meaning of the arguments."
                                        nil hist def inherit-input-method)))
     (when (and (equal result "") def)
       (setq result (if (consp def) (car def) def)))
+    (when (and (stringp result) (not (equal result ""))
+               (completion--frecency-p))
+      (let ((category (completion--frecency-category
+                       (completion-metadata "" collection predicate) hist)))
+        (frecency-record (if (eq category 'file)
+                             (completion--frecency-file-key result)
+                           result)
+                         category)))
     result))
 
 ;; Miscellaneous
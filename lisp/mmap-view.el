@@ -0,0 +1,107 @@
+;;; mmap-view.el --- view very large files without reading them  -*- lexical-binding:t -*-
+
+;; Copyright (C) 2018 Free Software Foundation, Inc.
+
+;; Keywords: files
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Commentary:
+
+;; M-x mmap-view-file shows a file that is too large to visit, such as
+;; a log of several gigabytes, without reading all of it.  The file is
+;; mapped into memory by `insert-file-contents-mmap' and becomes the
+;; text of the buffer, so only the parts of it that are looked at are
+;; ever read.  The text is not decoded.  The buffer is read-only and
+;; does not visit the file.
+
+;;; Code:
+
+(defgroup mmap-view nil
+  "View very large files without reading them."
+  :group 'files
+  :version "27.1")
+
+(defvar-local mmap-view--file nil
+  "The file shown in the current `mmap-view-mode' buffer.")
+
+(defvar mmap-view-mode-map
+  (let ((map (make-sparse-keymap)))
+    (define-key map "j" 'mmap-view-goto-percent)
+    map)
+  "Keymap for `mmap-view-mode'.")
+
+(defun mmap-view--insert (file)
+  "Replace the contents of the buffer with FILE, mapped into memory."
+  (let ((inhibit-read-only t))
+    ;; Deleting all the text does not move it, and the old mapping is
+    ;; released when the new one replaces it.
+    (erase-buffer)
+    (insert-file-contents-mmap file)
+    (set-buffer-modified-p nil)
+    (setq mmap-view--file file)))
+
+(defun mmap-view-goto-percent (percent)
+  "Go to the line PERCENT percent of the way through the file."
+  (interactive "nPercent: ")
+  (let ((percent (min (max percent 0) 100)))
+    (goto-char (+ (point-min) (truncate (* (buffer-size) percent) 100))))
+  (forward-line 0))
+
+(defun mmap-view-revert (&rest _)
+  "Map the file again, to show what it holds now."
+  (unless mmap-view--file
+    (user-error "Buffer is not viewing a mapped file"))
+  (let ((point (point)))
+    (mmap-view--insert mmap-view--file)
+    (goto-char (min point (point-max)))))
+
+(defun mmap-view--mode-line ()
+  "Return the size of the file, for the mode line."
+  (format " [%s]" (file-size-human-readable (buffer-size))))
+
+(define-derived-mode mmap-view-mode special-mode "Mmap-View"
+  "Major mode for viewing a file mapped into memory.
+The text of the buffer is the file itself, which is only read where
+it is looked at.  \[revert-buffer] maps the file again.
+
+The file must not be truncated while it is viewed, since the parts
+past its new end could no longer be read.
+
+\{mmap-view-mode-map}"
+  (setq buffer-undo-list t)
+  (setq-local revert-buffer-function #'mmap-view-revert)
+  (setq mode-line-process '(:eval (mmap-view--mode-line))))
+
+;;;###autoload
+(defun mmap-view-file (file)
+  "View FILE without reading all of it.
+FILE is mapped into memory and shown in `mmap-view-mode'."
+  (interactive "fView mapped file: ")
+  (let* ((file (expand-file-name file))
+         (buffer (generate-new-buffer (file-name-nondirectory file))))
+    (with-current-buffer buffer
+      (mmap-view-mode)
+      (setq default-directory (file-name-directory file))
+      (condition-case err
+          (mmap-view--insert file)
+        (error (kill-buffer buffer)
+               (signal (car err) (cdr err)))))
+    (switch-to-buffer buffer)))
+
+(provide 'mmap-view)
+
+;;; mmap-view.el ends here
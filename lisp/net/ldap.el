@@ -159,6 +159,14 @@ program's password prompt."
   :type 'regexp
   :version "25.1")
 
+(defcustom ldap-use-native t
+  "If non-nil, search LDAP servers without running ldapsearch if possible.
+The searches are then done by `ldap-search-native', which supports
+simple authentication only; `ldap-ldapsearch-prog' is still run for
+Kerberos authentication."
+  :type 'boolean
+  :version "27.1")
+
 (defcustom ldap-ignore-attribute-codings nil
   "If non-nil, do not encode/decode LDAP attribute values."
   :type 'boolean)
@@ -525,6 +533,29 @@ is desired for HOST."
       (password-cache-add host-key password))
     password))
 
+(defun ldap--search-native (host filter attributes parameters withdn)
+  "Search the first server of HOST with `ldap-search-native'.
+FILTER, ATTRIBUTES and WITHDN are as for `ldap-search', and
+PARAMETERS is the plist of search parameters.  Return the entries in
+the format of `ldap-search-internal'."
+  (let (result)
+    (dolist (entry (ldap-search-native filter (car (split-string host))
+                                       attributes parameters))
+      (let (record)
+        (maphash (lambda (name values)
+                   (unless (equal name "dn")
+                     (if values
+                         (dolist (value values)
+                           (push (list name value) record))
+                       (push (list name) record))))
+                 entry)
+        (setq record (nreverse record))
+        (cond (withdn
+               (push (cons (concat "dn: " (gethash "dn" entry)) record) result))
+              (record
+               (push record result)))))
+    (nreverse result)))
+
 (defun ldap-search-internal (search-plist)
   "Perform a search on a LDAP server.
 SEARCH-PLIST is a property list describing the search request.
@@ -606,129 +637,139 @@ an alist of attribute/value pairs."
     (if (or (null filter)
 	    (equal "" filter))
 	(error "No search filter"))
-    (setq filter (cons filter attributes))
-    (with-current-buffer buf
-      (erase-buffer)
-      (if (and host
-	       (not (equal "" host)))
-	  (setq arglist (nconc arglist
-			       (list (format
-				      ;; Use -H if host is a new-style LDAP URI.
-				      (if (string-match "^[a-zA-Z]+://" host)
-					  "-H%s"
-					"-h%s")
-				      host)))))
-      (if (and attrsonly
-	       (not (equal "" attrsonly)))
-	  (setq arglist (nconc arglist (list "-A"))))
-      (if (and base
-	       (not (equal "" base)))
-	  (setq arglist (nconc arglist (list (format "-b%s" base)))))
-      (if (and scope
-	       (not (equal "" scope)))
-	  (setq arglist (nconc arglist (list (format "-s%s" scope)))))
-      (if (and binddn
-	       (not (equal "" binddn)))
-	  (setq arglist (nconc arglist (list (format "-D%s" binddn)))))
-      (if (and auth
-	       (equal 'simple auth))
-	  (setq arglist (nconc arglist (list "-x"))))
-      ;; Allow passwd to be set to "", representing a blank password.
-      (if passwd
-	  (setq arglist (nconc arglist (list "-W"))))
-      (if (and deref
-	       (not (equal "" deref)))
-	  (setq arglist (nconc arglist (list (format "-a%s" deref)))))
-      (if (and timelimit
-	       (not (equal "" timelimit)))
-	  (setq arglist (nconc arglist (list (format "-l%s" timelimit)))))
-      (if (and sizelimit
-	       (not (equal "" sizelimit)))
-	  (setq arglist (nconc arglist (list (format "-z%s" sizelimit)))))
-      (if passwd
-	  (let* ((process-connection-type nil)
-		 (proc-args (append arglist ldap-ldapsearch-args
-				    filter))
-		 (proc (apply #'start-process "ldapsearch" buf
-			      ldap-ldapsearch-prog
-			      proc-args)))
-	    (while (null (progn
-			   (goto-char (point-min))
-			   (re-search-forward
-			    ldap-ldapsearch-password-prompt-regexp
-			    (point-max) t)))
-	      (accept-process-output proc 1))
-	    (process-send-string proc passwd)
-	    (process-send-string proc "\n")
-	    (while (not (memq (process-status proc) '(exit signal)))
-	      (sit-for 0.1))
-	    (let ((status (process-exit-status proc)))
-	      (when (not (eq status 0))
-		;; Handle invalid credentials exit status specially
-		;; for ldap-password-read.
-		(if (eq status 49)
-		    (error (concat "Incorrect LDAP password or"
-				   " bind distinguished name (binddn)"))
-		  (error "Failed ldapsearch invocation: %s \"%s\""
-			 ldap-ldapsearch-prog
-			 (mapconcat 'identity proc-args "\" \""))))))
-	(apply #'call-process ldap-ldapsearch-prog
-	       ;; Ignore stderr, which can corrupt results
-	       nil (list buf nil) nil
-	       (append arglist ldap-ldapsearch-args filter)))
-      (insert "\n")
-      (goto-char (point-min))
-
-      (while (re-search-forward (concat "[\t\n\f]+ \\|"
-					ldap-ldapsearch-password-prompt-regexp)
-				nil t)
-	(replace-match "" nil nil))
-      (goto-char (point-min))
-
-      (if (looking-at "usage")
-	  (error "Incorrect ldapsearch invocation")
-	(message "Parsing results... ")
-	;; Skip error message when retrieving attribute list
-	(if (looking-at "Size limit exceeded")
-	    (forward-line 1))
-        (if (looking-at "version:") (forward-line 1)) ;bug#12724.
-	(while (progn
-		 (skip-chars-forward " \t\n")
-		 (not (eobp)))
-	  (setq dn (buffer-substring (point) (point-at-eol)))
-	  (forward-line 1)
-          (while (looking-at "^\\([A-Za-z][-A-Za-z0-9]*\
+    (if (and ldap-use-native
+             (fboundp 'ldap-search-native)
+             host (not (equal "" host))
+             (memq auth '(nil simple)))
+        (ldap--search-native host filter attributes
+                             (list 'base base 'scope scope 'deref deref
+                                   'binddn binddn 'passwd passwd
+                                   'sizelimit sizelimit 'timelimit timelimit
+                                   'attrsonly attrsonly)
+                             withdn)
+      (setq filter (cons filter attributes))
+      (with-current-buffer buf
+	(erase-buffer)
+	(if (and host
+		 (not (equal "" host)))
+	    (setq arglist (nconc arglist
+				 (list (format
+					;; Use -H if host is a new-style LDAP URI.
+					(if (string-match "^[a-zA-Z]+://" host)
+					    "-H%s"
+					  "-h%s")
+					host)))))
+	(if (and attrsonly
+		 (not (equal "" attrsonly)))
+	    (setq arglist (nconc arglist (list "-A"))))
+	(if (and base
+		 (not (equal "" base)))
+	    (setq arglist (nconc arglist (list (format "-b%s" base)))))
+	(if (and scope
+		 (not (equal "" scope)))
+	    (setq arglist (nconc arglist (list (format "-s%s" scope)))))
+	(if (and binddn
+		 (not (equal "" binddn)))
+	    (setq arglist (nconc arglist (list (format "-D%s" binddn)))))
+	(if (and auth
+		 (equal 'simple auth))
+	    (setq arglist (nconc arglist (list "-x"))))
+	;; Allow passwd to be set to "", representing a blank password.
+	(if passwd
+	    (setq arglist (nconc arglist (list "-W"))))
+	(if (and deref
+		 (not (equal "" deref)))
+	    (setq arglist (nconc arglist (list (format "-a%s" deref)))))
+	(if (and timelimit
+		 (not (equal "" timelimit)))
+	    (setq arglist (nconc arglist (list (format "-l%s" timelimit)))))
+	(if (and sizelimit
+		 (not (equal "" sizelimit)))
+	    (setq arglist (nconc arglist (list (format "-z%s" sizelimit)))))
+	(if passwd
+	    (let* ((process-connection-type nil)
+		   (proc-args (append arglist ldap-ldapsearch-args
+				      filter))
+		   (proc (apply #'start-process "ldapsearch" buf
+				ldap-ldapsearch-prog
+				proc-args)))
+	      (while (null (progn
+			     (goto-char (point-min))
+			     (re-search-forward
+			      ldap-ldapsearch-password-prompt-regexp
+			      (point-max) t)))
+		(accept-process-output proc 1))
+	      (process-send-string proc passwd)
+	      (process-send-string proc "\n")
+	      (while (not (memq (process-status proc) '(exit signal)))
+		(sit-for 0.1))
+	      (let ((status (process-exit-status proc)))
+		(when (not (eq status 0))
+		  ;; Handle invalid credentials exit status specially
+		  ;; for ldap-password-read.
+		  (if (eq status 49)
+		      (error (concat "Incorrect LDAP password or"
+				     " bind distinguished name (binddn)"))
+		    (error "Failed ldapsearch invocation: %s \"%s\""
+			   ldap-ldapsearch-prog
+			   (mapconcat 'identity proc-args "\" \""))))))
+	  (apply #'call-process ldap-ldapsearch-prog
+		 ;; Ignore stderr, which can corrupt results
+		 nil (list buf nil) nil
+		 (append arglist ldap-ldapsearch-args filter)))
+	(insert "\n")
+	(goto-char (point-min))
+
+	(while (re-search-forward (concat "[\t\n\f]+ \\|"
+					  ldap-ldapsearch-password-prompt-regexp)
+				  nil t)
+	  (replace-match "" nil nil))
+	(goto-char (point-min))
+
+	(if (looking-at "usage")
+	    (error "Incorrect ldapsearch invocation")
+	  (message "Parsing results... ")
+	  ;; Skip error message when retrieving attribute list
+	  (if (looking-at "Size limit exceeded")
+	      (forward-line 1))
+	  (if (looking-at "version:") (forward-line 1)) ;bug#12724.
+	  (while (progn
+		   (skip-chars-forward " \t\n")
+		   (not (eobp)))
+	    (setq dn (buffer-substring (point) (point-at-eol)))
+	    (forward-line 1)
+	    (while (looking-at "^\\([A-Za-z][-A-Za-z0-9]*\
 \\|[0-9]+\\(?:\\.[0-9]+\\)*\\)\\(;[-A-Za-z0-9]+\\)*[=:\t ]+\
 \\(<[\t ]*file://\\)\\(.*\\)$")
-	    (setq name (match-string 1)
-		  value (match-string 4))
-            ;; Need to handle file:///D:/... as generated by OpenLDAP
-            ;; on DOS/Windows as local files.
-            (if (and (memq system-type '(windows-nt ms-dos))
-                     (eq (string-match "/\\(.:.*\\)$" value) 0))
-                (setq value (match-string 1 value)))
-	    ;; Do not try to open non-existent files
-	    (if (equal value "")
-		(setq value " ")
-	      (with-current-buffer bufval
-		(erase-buffer)
-		(set-buffer-multibyte nil)
-		(insert-file-contents-literally value)
-		(delete-file value)
-		(setq value (buffer-string))))
-	    (setq record (cons (list name value)
-			       record))
-	    (forward-line 1))
-	  (cond (withdn
-		 (push (cons dn (nreverse record)) result))
-		(record
-		 (push (nreverse record) result)))
-	  (setq record nil)
-	  (skip-chars-forward " \t\n")
-	  (message "Parsing results... %d" numres)
-	  (1+ numres))
-	(message "Parsing results... done")
-	(nreverse result)))))
+	      (setq name (match-string 1)
+		    value (match-string 4))
+	      ;; Need to handle file:///D:/... as generated by OpenLDAP
+	      ;; on DOS/Windows as local files.
+	      (if (and (memq system-type '(windows-nt ms-dos))
+		       (eq (string-match "/\\(.:.*\\)$" value) 0))
+		  (setq value (match-string 1 value)))
+	      ;; Do not try to open non-existent files
+	      (if (equal value "")
+		  (setq value " ")
+		(with-current-buffer bufval
+		  (erase-buffer)
+		  (set-buffer-multibyte nil)
+		  (insert-file-contents-literally value)
+		  (delete-file value)
+		  (setq value (buffer-string))))
+	      (setq record (cons (list name value)
+				 record))
+	      (forward-line 1))
+	    (cond (withdn
+		   (push (cons dn (nreverse record)) result))
+		  (record
+		   (push (nreverse record) result)))
+	    (setq record nil)
+	    (skip-chars-forward " \t\n")
+	    (message "Parsing results... %d" numres)
+	    (1+ numres))
+	  (message "Parsing results... done")
+	  (nreverse result))))))
 
 (provide 'ldap)
 
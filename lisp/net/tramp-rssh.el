@@ -0,0 +1,437 @@
+;;; tramp-rssh.el --- Tramp access functions for built-in SFTP  -*- lexical-binding:t -*-
+
+;; Copyright (C) 2018 Free Software Foundation, Inc.
+
+;; Keywords: comm, processes
+;; Package: tramp
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Commentary:
+
+;; The "rssh" method accesses remote files through the SSH client
+;; built into Emacs, with file names like
+;;
+;;   /rssh:user@host#port:/path/to/file
+;;
+;; Files are read, written and listed over SFTP by the `remote-'
+;; functions, without a remote shell, so no shell prompt has to be
+;; recognized and no output parsed.  Processes can't be run on the
+;; remote host; use the "ssh" method for that.
+;;
+;; Authentication tries the SSH agent, then the private keys in
+;; ~/.ssh that have no passphrase, and then asks for the password.
+;; Host keys are checked against ~/.ssh/known_hosts.
+
+;;; Code:
+
+(require 'tramp)
+
+;;;###tramp-autoload
+(defconst tramp-rssh-method "rssh"
+  "Method to access remote files with the built-in SSH client.")
+
+;;;###tramp-autoload
+(add-to-list 'tramp-methods `(,tramp-rssh-method (tramp-default-port 22)))
+
+;;;###tramp-autoload
+(eval-after-load 'tramp
+  '(tramp-set-completion-function
+    tramp-rssh-method
+    '((tramp-parse-sconfig "/etc/ssh_config")
+      (tramp-parse-sconfig "~/.ssh/config")
+      (tramp-parse-shosts "~/.ssh/known_hosts"))))
+
+;;;###tramp-autoload
+(defconst tramp-rssh-file-name-handler-alist
+  '((access-file . ignore)
+    (add-name-to-file . tramp-handle-add-name-to-file)
+    ;; `byte-compiler-base-file-name' performed by default handler.
+    ;; `copy-directory' performed by default handler.
+    (copy-file . tramp-rssh-handle-copy-file)
+    (delete-directory . tramp-rssh-handle-delete-directory)
+    (delete-file . tramp-rssh-handle-delete-file)
+    ;; `diff-latest-backup-file' performed by default handler.
+    (directory-file-name . tramp-handle-directory-file-name)
+    (directory-files . tramp-handle-directory-files)
+    (directory-files-and-attributes
+     . tramp-rssh-handle-directory-files-and-attributes)
+    (dired-compress-file . ignore)
+    (dired-uncache . tramp-handle-dired-uncache)
+    (expand-file-name . tramp-rssh-handle-expand-file-name)
+    (file-accessible-directory-p . tramp-handle-file-accessible-directory-p)
+    (file-acl . ignore)
+    (file-attributes . tramp-rssh-handle-file-attributes)
+    (file-directory-p . tramp-rssh-handle-file-directory-p)
+    (file-equal-p . tramp-handle-file-equal-p)
+    (file-executable-p . tramp-rssh-handle-file-executable-p)
+    (file-exists-p . tramp-handle-file-exists-p)
+    (file-in-directory-p . tramp-handle-file-in-directory-p)
+    (file-local-copy . tramp-rssh-handle-file-local-copy)
+    (file-modes . tramp-handle-file-modes)
+    (file-name-all-completions . tramp-rssh-handle-file-name-all-completions)
+    (file-name-as-directory . tramp-handle-file-name-as-directory)
+    (file-name-case-insensitive-p . tramp-handle-file-name-case-insensitive-p)
+    (file-name-completion . tramp-handle-file-name-completion)
+    (file-name-directory . tramp-handle-file-name-directory)
+    (file-name-nondirectory . tramp-handle-file-name-nondirectory)
+    ;; `file-name-sans-versions' performed by default handler.
+    (file-newer-than-file-p . tramp-handle-file-newer-than-file-p)
+    (file-notify-add-watch . tramp-handle-file-notify-add-watch)
+    (file-notify-rm-watch . tramp-handle-file-notify-rm-watch)
+    (file-notify-valid-p . tramp-handle-file-notify-valid-p)
+    (file-ownership-preserved-p . ignore)
+    (file-readable-p . tramp-handle-file-exists-p)
+    (file-regular-p . tramp-handle-file-regular-p)
+    (file-remote-p . tramp-handle-file-remote-p)
+    (file-selinux-context . ignore)
+    (file-symlink-p . tramp-handle-file-symlink-p)
+    (file-truename . tramp-rssh-handle-file-truename)
+    (file-writable-p . tramp-rssh-handle-file-writable-p)
+    (find-backup-file-name . tramp-handle-find-backup-file-name)
+    ;; `find-file-noselect' performed by default handler.
+    ;; `get-file-buffer' performed by default handler.
+    (insert-directory . tramp-handle-insert-directory)
+    (insert-file-contents . tramp-handle-insert-file-contents)
+    (load . tramp-handle-load)
+    (make-auto-save-file-name . tramp-handle-make-auto-save-file-name)
+    (make-directory . tramp-rssh-handle-make-directory)
+    (make-directory-internal . ignore)
+    (make-nearby-temp-file . tramp-handle-make-nearby-temp-file)
+    (make-symbolic-link . tramp-handle-make-symbolic-link)
+    (process-file . ignore)
+    (rename-file . tramp-rssh-handle-rename-file)
+    (set-file-acl . ignore)
+    (set-file-modes . ignore)
+    (set-file-selinux-context . ignore)
+    (set-file-times . ignore)
+    (set-visited-file-modtime . tramp-handle-set-visited-file-modtime)
+    (shell-command . ignore)
+    (start-file-process . ignore)
+    (substitute-in-file-name . tramp-handle-substitute-in-file-name)
+    (temporary-file-directory . tramp-handle-temporary-file-directory)
+    (unhandled-file-name-directory . ignore)
+    (vc-registered . ignore)
+    (verify-visited-file-modtime . tramp-handle-verify-visited-file-modtime)
+    (write-region . tramp-rssh-handle-write-region))
+  "Alist of handler functions for Tramp RSSH method.
+Operations not mentioned here will be handled by the default Emacs primitives.")
+
+;; It must be a `defsubst' in order to push the whole code into
+;; tramp-loaddefs.el.  Otherwise, there would be recursive autoloading.
+;;;###tramp-autoload
+(defsubst tramp-rssh-file-name-p (filename)
+  "Check if it's a filename for the built-in SSH client."
+  (and (tramp-tramp-file-p filename)
+       (string= (tramp-file-name-method (tramp-dissect-file-name filename))
+		tramp-rssh-method)))
+
+;;;###tramp-autoload
+(defun tramp-rssh-file-name-handler (operation &rest args)
+  "Invoke the RSSH handler for OPERATION.
+First arg specifies the OPERATION, second arg is a list of arguments to
+pass to the OPERATION."
+  (let ((fn (assoc operation tramp-rssh-file-name-handler-alist)))
+    (if fn
+	(save-match-data (apply (cdr fn) args))
+      (tramp-run-real-handler operation args))))
+
+;;;###tramp-autoload
+(when (fboundp 'remote-read-file)
+  (tramp-register-foreign-file-name-handler
+   'tramp-rssh-file-name-p 'tramp-rssh-file-name-handler))
+
+
+;; File name conversions.
+
+(defun tramp-rssh-remote-name (vec &optional localname)
+  "Return the file name the `remote-' functions take for VEC.
+LOCALNAME, if given, replaces the local name of VEC."
+  (concat "/rssh:"
+	  (if (tramp-file-name-user vec)
+	      (concat (tramp-file-name-user vec) "@")
+	    "")
+	  (tramp-file-name-host vec)
+	  (if (tramp-file-name-port vec)
+	      (format "#%s" (tramp-file-name-port vec))
+	    "")
+	  ":"
+	  (tramp-compat-file-name-unquote
+	   (or localname (tramp-file-name-localname vec)))))
+
+(defun tramp-rssh-convert-ids (attributes id-format)
+  "Return ATTRIBUTES with the user and group ids as ID-FORMAT asks.
+SFTP gives numeric ids only, so for `string' they become the
+numbers as strings."
+  (when (and attributes (eq id-format 'string))
+    (setcar (nthcdr 2 attributes) (number-to-string (nth 2 attributes)))
+    (setcar (nthcdr 3 attributes) (number-to-string (nth 3 attributes))))
+  attributes)
+
+
+;; File name primitives.
+
+(defun tramp-rssh-handle-copy-file
+  (filename newname &optional ok-if-already-exists _keep-date
+   _preserve-uid-gid _preserve-extended-attributes)
+  "Like `copy-file' for Tramp files."
+  (setq filename (expand-file-name filename)
+	newname (expand-file-name newname))
+  (when (directory-name-p newname)
+    (setq newname (expand-file-name (file-name-nondirectory filename) newname)))
+  (when (and (not ok-if-already-exists) (file-exists-p newname))
+    (tramp-error
+     (tramp-dissect-file-name
+      (if (tramp-rssh-file-name-p newname) newname filename))
+     'file-already-exists newname))
+  (let ((contents
+	 (if (tramp-rssh-file-name-p filename)
+	     (remote-read-file
+	      (tramp-rssh-remote-name (tramp-dissect-file-name filename)))
+	   (with-temp-buffer
+	     (set-buffer-multibyte nil)
+	     (insert-file-contents-literally filename)
+	     (buffer-string)))))
+    (if (tramp-rssh-file-name-p newname)
+	(remote-write-file
+	 (tramp-rssh-remote-name (tramp-dissect-file-name newname)) contents)
+      (let ((coding-system-for-write 'binary))
+	(write-region contents nil newname nil 'silent)))))
+
+(defun tramp-rssh-handle-delete-directory (directory &optional recursive _trash)
+  "Like `delete-directory' for Tramp files."
+  (setq directory (directory-file-name (expand-file-name directory)))
+  (when recursive
+    (dolist (file (directory-files directory 'full
+				   directory-files-no-dot-files-regexp))
+      (if (eq t (tramp-compat-file-attribute-type (file-attributes file)))
+	  (delete-directory file recursive)
+	(delete-file file))))
+  (with-parsed-tramp-file-name directory nil
+    (remote-delete-file (tramp-rssh-remote-name v))))
+
+(defun tramp-rssh-handle-delete-file (filename &optional _trash)
+  "Like `delete-file' for Tramp files."
+  (with-parsed-tramp-file-name (expand-file-name filename) nil
+    (remote-delete-file (tramp-rssh-remote-name v))))
+
+(defun tramp-rssh-handle-directory-files-and-attributes
+  (directory &optional full match nosort id-format)
+  "Like `directory-files-and-attributes' for Tramp files."
+  (setq directory (file-name-as-directory (expand-file-name directory)))
+  (with-parsed-tramp-file-name directory nil
+    (let ((result
+	   (append
+	    (list (cons "." (file-attributes directory id-format))
+		  (cons ".." (file-attributes
+			      (expand-file-name ".." directory) id-format)))
+	    (mapcar
+	     (lambda (entry)
+	       (cons (car entry) (tramp-rssh-convert-ids (cdr entry) id-format)))
+	     (remote-directory-files-and-attributes
+	      (tramp-rssh-remote-name v))))))
+      (when match
+	(setq result
+	      (delq nil (mapcar (lambda (entry)
+				  (and (string-match-p match (car entry)) entry))
+				result))))
+      (when full
+	(dolist (entry result)
+	  (setcar entry (concat directory (car entry)))))
+      (if nosort
+	  result
+	(sort result (lambda (x y) (string< (car x) (car y))))))))
+
+(defun tramp-rssh-handle-expand-file-name (name &optional dir)
+  "Like `expand-file-name' for Tramp files."
+  ;; If DIR is not given, use DEFAULT-DIRECTORY or "/".
+  (setq dir (or dir default-directory "/"))
+  ;; Unless NAME is absolute, concat DIR and NAME.
+  (unless (file-name-absolute-p name)
+    (setq name (concat (file-name-as-directory dir) name)))
+  ;; If NAME is not a Tramp file, run the real handler.
+  (if (not (tramp-tramp-file-p name))
+      (tramp-run-real-handler 'expand-file-name (list name nil))
+    ;; Dissect NAME.
+    (with-parsed-tramp-file-name name nil
+      ;; Tilde means the home directory on the remote host, which is
+      ;; where relative SFTP file names start.
+      (when (string-match "\\`\\(~\\)\\(/\\|\\'\\)" localname)
+	(setq localname
+	      (replace-match
+	       (with-tramp-connection-property v "home-directory"
+		 (tramp-file-name-localname
+		  (tramp-dissect-file-name
+		   (remote-file-truename (tramp-rssh-remote-name v "")))))
+	       nil t localname 1)))
+      (unless (tramp-run-real-handler 'file-name-absolute-p (list localname))
+	(setq localname (concat "/" localname)))
+      ;; Do normal `expand-file-name' (this does "/./" and "/../").
+      ;; `default-directory' is bound, because on Windows there would
+      ;; be problems with UNC shares or Cygwin mounts.
+      (let ((default-directory (tramp-compat-temporary-file-directory)))
+	(tramp-make-tramp-file-name
+	 method user domain host port
+	 (tramp-drop-volume-letter
+	  (tramp-run-real-handler
+	   'expand-file-name (list localname))))))))
+
+(defun tramp-rssh-handle-file-attributes (filename &optional id-format)
+  "Like `file-attributes' for Tramp files."
+  (with-parsed-tramp-file-name (expand-file-name filename) nil
+    (tramp-rssh-convert-ids
+     (remote-file-attributes (tramp-rssh-remote-name v)) id-format)))
+
+(defun tramp-rssh-handle-file-directory-p (filename)
+  "Like `file-directory-p' for Tramp files."
+  (with-parsed-tramp-file-name (expand-file-name filename) nil
+    (eq t (tramp-compat-file-attribute-type
+	   (remote-file-attributes (tramp-rssh-remote-name v) t)))))
+
+(defun tramp-rssh-handle-file-executable-p (filename)
+  "Like `file-executable-p' for Tramp files."
+  (with-parsed-tramp-file-name (expand-file-name filename) nil
+    (let ((modes (tramp-compat-file-attribute-modes
+		  (remote-file-attributes (tramp-rssh-remote-name v) t))))
+      (and modes (eq (aref modes 3) ?x)))))
+
+(defun tramp-rssh-handle-file-local-copy (filename)
+  "Like `file-local-copy' for Tramp files."
+  (with-parsed-tramp-file-name (expand-file-name filename) nil
+    (unless (file-exists-p filename)
+      (tramp-error
+       v tramp-file-missing
+       "Cannot make local copy of non-existing file `%s'" filename))
+    (let ((tmpfile (tramp-compat-make-temp-file filename))
+	  (coding-system-for-write 'binary))
+      (write-region (remote-read-file (tramp-rssh-remote-name v))
+		    nil tmpfile nil 'silent)
+      tmpfile)))
+
+(defun tramp-rssh-handle-file-name-all-completions (filename directory)
+  "Like `file-name-all-completions' for Tramp files."
+  (unless (string-match-p "/" filename)
+    (with-parsed-tramp-file-name (expand-file-name directory) nil
+      (all-completions
+       filename
+       (append '("./" "../")
+	       (mapcar (lambda (entry)
+			 (if (eq t (tramp-compat-file-attribute-type (cdr entry)))
+			     (file-name-as-directory (car entry))
+			   (car entry)))
+		       (remote-directory-files-and-attributes
+			(tramp-rssh-remote-name v))))))))
+
+(defun tramp-rssh-handle-file-truename (filename)
+  "Like `file-truename' for Tramp files."
+  (setq filename (expand-file-name filename))
+  (with-parsed-tramp-file-name filename nil
+    ;; A file that doesn't exist has no true name on the host.
+    (condition-case nil
+	(tramp-make-tramp-file-name
+	 method user domain host port
+	 (tramp-file-name-localname
+	  (tramp-dissect-file-name
+	   (remote-file-truename (tramp-rssh-remote-name v)))))
+      (file-error filename))))
+
+(defun tramp-rssh-handle-file-writable-p (filename)
+  "Like `file-writable-p' for Tramp files."
+  (setq filename (expand-file-name filename))
+  (if (file-exists-p filename)
+      (with-parsed-tramp-file-name filename nil
+	(let ((modes (tramp-compat-file-attribute-modes
+		      (remote-file-attributes (tramp-rssh-remote-name v) t))))
+	  (and modes (eq (aref modes 2) ?w))))
+    (file-directory-p (file-name-directory filename))))
+
+(defun tramp-rssh-handle-make-directory (dir &optional parents)
+  "Like `make-directory' for Tramp files."
+  (setq dir (directory-file-name (expand-file-name dir)))
+  (let ((parent (file-name-directory dir)))
+    (when (and parents (not (file-directory-p parent)))
+      (make-directory parent parents)))
+  (with-parsed-tramp-file-name dir nil
+    (remote-make-directory (tramp-rssh-remote-name v))))
+
+(defun tramp-rssh-handle-rename-file
+  (filename newname &optional ok-if-already-exists)
+  "Like `rename-file' for Tramp files."
+  (setq filename (expand-file-name filename)
+	newname (expand-file-name newname))
+  (when (directory-name-p newname)
+    (setq newname (expand-file-name (file-name-nondirectory filename) newname)))
+  (if (and (tramp-rssh-file-name-p filename)
+	   (tramp-rssh-file-name-p newname)
+	   (tramp-equal-remote filename newname))
+      (progn
+	(when (and (not ok-if-already-exists) (file-exists-p newname))
+	  (tramp-error
+	   (tramp-dissect-file-name newname) 'file-already-exists newname))
+	(remote-rename-file
+	 (tramp-rssh-remote-name (tramp-dissect-file-name filename))
+	 (tramp-rssh-remote-name (tramp-dissect-file-name newname))))
+    (copy-file filename newname ok-if-already-exists)
+    (delete-file filename)))
+
+(defun tramp-rssh-handle-write-region
+  (start end filename &optional append visit lockname mustbenew)
+  "Like `write-region' for Tramp files."
+  (setq filename (expand-file-name filename))
+  (with-parsed-tramp-file-name filename nil
+    (when (and mustbenew (file-exists-p filename)
+	       (or (eq mustbenew 'excl)
+		   (not
+		    (y-or-n-p
+		     (format "File %s exists; overwrite anyway? " filename)))))
+      (tramp-error v 'file-already-exists filename))
+
+    (let ((tmpfile (tramp-compat-make-temp-file filename)))
+      (unwind-protect
+	  (progn
+	    ;; We say `no-message' here because we don't want the
+	    ;; visited file modtime data to be clobbered from the temp
+	    ;; file.  We call `set-visited-file-modtime' ourselves later
+	    ;; on.
+	    (tramp-run-real-handler
+	     'write-region (list start end tmpfile nil 'no-message lockname))
+	    (remote-write-file
+	     (tramp-rssh-remote-name v)
+	     (with-temp-buffer
+	       (set-buffer-multibyte nil)
+	       (insert-file-contents-literally tmpfile)
+	       (buffer-string))
+	     append))
+	(delete-file tmpfile)))
+
+    ;; Set file modification time.
+    (when (or (eq visit t) (stringp visit))
+      (set-visited-file-modtime
+       (tramp-compat-file-attribute-modification-time
+	(file-attributes filename))))
+
+    ;; The end.
+    (when (or (eq visit t) (null visit) (stringp visit))
+      (tramp-message v 0 "Wrote %s" filename))
+    (run-hooks 'tramp-handle-write-region-hook)))
+
+(add-hook 'tramp-unload-hook
+	  (lambda ()
+	    (unload-feature 'tramp-rssh 'force)))
+
+(provide 'tramp-rssh)
+
+;;; tramp-rssh.el ends here
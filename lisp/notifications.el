@@ -306,41 +306,46 @@ of another `notifications-notify' call."
 			      (or hints '(:array :signature "{sv}"))
 			      :int32 (or timeout -1)))
 
-      ;; Register close/action callback function.  We must also
-      ;; remember the daemon's unique name, because the daemon could
-      ;; have restarted.
-      (let ((on-action (plist-get params :on-action))
-	    (on-close (plist-get params :on-close))
-	    (unique-name (dbus-get-name-owner bus notifications-service)))
-	(when on-action
-	  (add-to-list 'notifications-on-action-map
-		       (list (list bus unique-name id) on-action))
-	  (unless notifications-on-action-object
-	    (setq notifications-on-action-object
-		  (dbus-register-signal
-		   bus
-		   nil
-		   notifications-path
-		   notifications-interface
-		   notifications-action-signal
-		   'notifications-on-action-signal))))
-
-	(when on-close
-	  (add-to-list 'notifications-on-close-map
-		       (list (list bus unique-name id) on-close))
-	  (unless notifications-on-close-object
-	    (setq notifications-on-close-object
-		  (dbus-register-signal
-		   bus
-		   nil
-		   notifications-path
-		   notifications-interface
-		   notifications-closed-signal
-		   'notifications-on-closed-signal)))))
+      ;; Register close/action callback function.
+      (notifications--register-callbacks
+       bus id (plist-get params :on-action) (plist-get params :on-close))
 
       ;; Return notification id
       id)))
 
+(defun notifications--register-callbacks (bus id on-action on-close)
+  "Call ON-ACTION and ON-CLOSE for the signals about notification ID.
+BUS is the bus the notification was sent on.  This is also used by
+`notify-send-native'."
+  ;; We must also remember the daemon's unique name, because the
+  ;; daemon could have restarted.
+  (let ((unique-name (dbus-get-name-owner bus notifications-service)))
+    (when on-action
+      (add-to-list 'notifications-on-action-map
+		   (list (list bus unique-name id) on-action))
+      (unless notifications-on-action-object
+	(setq notifications-on-action-object
+	      (dbus-register-signal
+	       bus
+	       nil
+	       notifications-path
+	       notifications-interface
+	       notifications-action-signal
+	       'notifications-on-action-signal))))
+
+    (when on-close
+      (add-to-list 'notifications-on-close-map
+		   (list (list bus unique-name id) on-close))
+      (unless notifications-on-close-object
+	(setq notifications-on-close-object
+	      (dbus-register-signal
+	       bus
+	       nil
+	       notifications-path
+	       notifications-interface
+	       notifications-closed-signal
+	       'notifications-on-closed-signal))))))
+
 (defun notifications-close-notification (id &optional bus)
   "Close a notification with identifier ID.
 BUS can be a string denoting a D-Bus connection, the default is `:session'."
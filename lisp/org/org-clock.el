@@ -814,7 +814,7 @@ If PLAY-SOUND is non-nil, it overrides `org-clock-sound'."
 (defun org-show-notification (notification)
   "Show notification.
 Use `org-show-notification-handler' if defined,
-use libnotify if available, or fall back on a message."
+use desktop notifications if available, or fall back on a message."
   (cond ((functionp org-show-notification-handler)
 	 (funcall org-show-notification-handler notification))
 	((stringp org-show-notification-handler)
@@ -827,9 +827,8 @@ use libnotify if available, or fall back on a message."
 	  ;; FIXME how to link to the Org icon?
 	  ;; :app-icon "~/.emacs.d/icons/mail.png"
 	  :urgency 'low))
-	((executable-find "notify-send")
-	 (start-process "emacs-timer-notification" nil
-			"notify-send" notification))
+	((fboundp 'notify-send-native)
+	 (notify-send-native "Org mode message" notification :urgency 'low))
 	;; Maybe the handler will send a message, so only use message as
 	;; a fall back option
 	(t (message "%s" notification))))
@@ -637,6 +637,16 @@ Highlight entire line if t; don't highlight source lines if nil.")
 (defvar compilation-highlight-overlay nil
   "Overlay used to temporarily highlight compilation matches.")
 
+(defcustom compilation-parse-native t
+  "If non-nil, scan compilation output for messages natively.
+The rules of `compilation-error-regexp-alist' are then matched by
+`compilation-parse-region-native', which is much faster on long
+output.  Rules whose regexps it can't handle are still searched for
+with `re-search-forward'."
+  :type 'boolean
+  :group 'compilation
+  :version "27.1")
+
 (defcustom compilation-error-screen-columns t
   "If non-nil, column numbers in error messages are screen columns.
 Otherwise they are interpreted as character positions, with
@@ -1370,7 +1380,38 @@ This updates the appropriate variable used by the mode-line."
   "Parse errors between START and END.
 The errors recognized are the ones specified in RULES which default
 to `compilation-error-regexp-alist' if RULES is nil."
-  (dolist (item (or rules compilation-error-regexp-alist))
+  (let* ((rules (or rules compilation-error-regexp-alist))
+         (native (compilation--parse-native start end rules)))
+    (dolist (item rules)
+      (compilation--parse-rule item start end native))))
+
+(defun compilation--parse-native (start end rules)
+  "Scan the text between START and END for the messages of RULES natively.
+Return the value of `compilation-parse-region-native', or nil if
+messages are not scanned natively."
+  (when (and compilation-parse-native
+             (fboundp 'compilation-parse-region-native)
+             ;; The regexps are changed for omake's indented messages.
+             (not (memq 'omake compilation-error-regexp-alist)))
+    (compilation-parse-region-native start end rules)))
+
+(defun compilation--native-matches (item native)
+  "Return the messages of the rule ITEM that were scanned natively.
+NATIVE is the value of `compilation--parse-native', and the records
+of ITEM are taken off its front.  Return t if ITEM has to be searched
+for in Lisp."
+  (if (or (null native) (memq item (cdr native)))
+      t
+    (let ((matches nil))
+      (while (and (car native)
+                  (eq (plist-get (caar native) :rule) item))
+        (push (pop (car native)) matches))
+      (nreverse matches))))
+
+(defun compilation--parse-rule (item start end native)
+  "Parse the errors between START and END recognized by the rule ITEM.
+NATIVE is the value of `compilation--parse-native'."
+  (let ((matches (compilation--native-matches item native)))
     (if (symbolp item)
         (setq item (cdr (assq item
                               compilation-error-regexp-alist-alist))))
@@ -1424,7 +1465,11 @@ to `compilation-error-regexp-alist' if RULES is nil."
           (error "HYPERLINK should be an integer: %s" (nth 5 item)))
 
         (goto-char start)
-        (while (re-search-forward pat end t)
+        (while (if (eq matches t)
+                   (re-search-forward pat end t)
+                 (when matches
+                   (set-match-data (plist-get (pop matches) :match-data))
+                   (goto-char (match-end 0))))
           (when (setq props (compilation-error-properties
                              file line end-line col end-col (or type 2) fmt))
 
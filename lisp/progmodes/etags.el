@@ -1276,7 +1276,24 @@ buffer-local values of tags table format variables."
 	(expand-file-name str (file-truename default-directory))))))
 
 
+(defun etags--use-native-p ()
+  "Return non-nil if the current tags table can be read natively.
+That is when its buffer is unmodified and visits a local file that
+is not compressed."
+  (and (fboundp 'tags-lookup-native)
+       buffer-file-name
+       (not (buffer-modified-p))
+       (not (file-remote-p buffer-file-name))
+       (not (and (fboundp 'jka-compr-get-compression-info)
+                 (jka-compr-get-compression-info buffer-file-name)))))
+
 (defun etags-tags-completion-table () ; Doc string?
+  (if (etags--use-native-p)
+      (tags-completions-native "" buffer-file-name)
+    (etags--scan-tags-completion-table)))
+
+(defun etags--scan-tags-completion-table ()
+  "Make the completion table of the current tags table by scanning it."
   (let (table
 	(progress-reporter
 	 (make-progress-reporter
@@ -2138,23 +2155,40 @@ for \\[find-tag] (which see)."
     (save-excursion
       (while (visit-tags-table-buffer (not first-time) cbuf)
         (setq first-time nil)
-        (dolist (order-fun (cond (regexp? find-tag-regexp-tag-order)
-                                 (t etags-xref-find-definitions-tag-order)))
-          (goto-char (point-min))
-          (while (and (funcall search-fun pattern nil t)
-                      (< (hash-table-count marks) etags--xref-limit))
-            (when (funcall order-fun pattern)
-              (beginning-of-line)
-              (pcase-let* ((tag-info (etags-snarf-tag))
-                           (`(,hint ,line . _) tag-info))
-                (unless (eq hint t) ; hint==t if we are in a filename line
-                  (let* ((file (file-of-tag))
-                         (mark-key (cons file line)))
-                    (unless (gethash mark-key marks)
-                      (let ((loc (xref-make-etags-location
-                                  tag-info (expand-file-name file))))
-                        (push (xref-make hint loc) xrefs)
-                        (puthash mark-key t marks)))))))))))
+        (if (and (not regexp?)
+                 (etags--use-native-p)
+                 (equal etags-xref-find-definitions-tag-order
+                        '(tag-exact-match-p tag-implicit-name-match-p)))
+            ;; The native lookup finds the tags those two would.
+            (pcase-dolist (`(,source . ,tag-info)
+                           (tags-lookup-native pattern buffer-file-name
+                                               case-fold-search))
+              (let* ((file (expand-file-name
+                            (convert-standard-filename source)
+                            (file-truename default-directory)))
+                     (mark-key (cons file (nth 1 tag-info))))
+                (unless (or (gethash mark-key marks)
+                            (>= (hash-table-count marks) etags--xref-limit))
+                  (let ((loc (xref-make-etags-location tag-info file)))
+                    (push (xref-make (car tag-info) loc) xrefs)
+                    (puthash mark-key t marks)))))
+          (dolist (order-fun (cond (regexp? find-tag-regexp-tag-order)
+                                   (t etags-xref-find-definitions-tag-order)))
+            (goto-char (point-min))
+            (while (and (funcall search-fun pattern nil t)
+                        (< (hash-table-count marks) etags--xref-limit))
+              (when (funcall order-fun pattern)
+                (beginning-of-line)
+                (pcase-let* ((tag-info (etags-snarf-tag))
+                             (`(,hint ,line . _) tag-info))
+                  (unless (eq hint t) ; hint==t if we are in a filename line
+                    (let* ((file (file-of-tag))
+                           (mark-key (cons file line)))
+                      (unless (gethash mark-key marks)
+                        (let ((loc (xref-make-etags-location
+                                    tag-info (expand-file-name file))))
+                          (push (xref-make hint loc) xrefs)
+                          (puthash mark-key t marks))))))))))))
     (nreverse xrefs)))
 
 (defclass xref-etags-location (xref-location)
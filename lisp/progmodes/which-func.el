@@ -282,6 +282,11 @@ If no function name is found, return nil."
 	 ;; Try the `which-func-functions' functions first.
 	 (run-hook-with-args-until-success 'which-func-functions)))
 
+    ;; A native index is kept up to date without waiting for it.
+    (when (and (null name)
+               (bound-and-true-p imenu-native-language)
+               (fboundp 'imenu-build-index-native))
+      (setq imenu--index-alist (imenu-native-create-index t)))
     ;; If Imenu is loaded, try to make an index alist with it.
     (when (and (null name)
 	       (boundp 'imenu--index-alist) (null imenu--index-alist)
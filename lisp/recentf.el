@@ -91,6 +91,29 @@ file.  See also the function `set-file-modes'."
   :type '(choice (const :tag "Don't change" nil)
           integer))
 
+(defcustom recentf-native-save-file (locate-user-emacs-file "recentf.data")
+  "File to save the recent list into when Emacs maintains it natively.
+When this is non-nil and Emacs has `recent-files-native', the recent
+list is kept by Emacs itself and saved into this file instead of
+`recentf-save-file'.  The file is written and read without going
+through the Lisp printer and reader, it is written in the background,
+and files that no longer exist are looked for in the background
+rather than by `recentf-keep-default-predicate'.
+A nil value means to always use `recentf-save-file'."
+  :group 'recentf
+  :version "27.1"
+  :type '(choice (const :tag "Don't use" nil)
+                 file))
+
+(defsubst recentf--native-p ()
+  "Return non-nil if Emacs maintains the recent list natively."
+  (and recentf-native-save-file (fboundp 'recent-files-native)))
+
+(defun recentf--native-sync ()
+  "Give the native recent list the contents of `recentf-list'."
+  (when (recentf--native-p)
+    (setq recentf-list (recent-files-native-set recentf-list))))
+
 (defcustom recentf-exclude nil
   "List of regexps and predicates for filenames excluded from the recent list.
 When a filename matches any of the regexps or satisfies any of the
@@ -382,8 +405,13 @@ If it is there yet, move it at the beginning of the list.
 If `recentf-case-fold-search' is non-nil, ignore case when comparing
 filenames."
   (let ((m (recentf-string-member filename recentf-list)))
-    (and m (setq recentf-list (delq (car m) recentf-list)))
-    (push filename recentf-list)))
+    (if (recentf--native-p)
+        (progn
+          (and m (recent-files-native-remove (car m)))
+          (recent-files-native-add filename)
+          (setq recentf-list (recent-files-native)))
+      (and m (setq recentf-list (delq (car m) recentf-list)))
+      (push filename recentf-list))))
 
 (defun recentf-apply-filename-handlers (name)
   "Apply `recentf-filename-handlers' to file NAME.
@@ -454,7 +482,10 @@ Return non-nil if FILENAME has been removed."
   (unless (recentf-keep-p filename)
     (let ((m (recentf-string-member
               (recentf-expand-file-name filename) recentf-list)))
-      (and m (setq recentf-list (delq (car m) recentf-list))))))
+      (when m
+        (when (recentf--native-p)
+          (recent-files-native-remove (car m)))
+        (setq recentf-list (delq (car m) recentf-list))))))
 
 (defsubst recentf-directory-compare (f1 f2)
   "Compare absolute filenames F1 and F2.
@@ -1112,6 +1143,7 @@ IGNORE arguments."
         (dolist (e recentf-edit-list)
           (setq recentf-list (delq e recentf-list)
                 i (1+ i)))
+        (recentf--native-sync)
         (kill-buffer (current-buffer))
         (message "%S file(s) removed from the list" i))
     (message "No file selected")))
@@ -1278,9 +1310,20 @@ Optional argument N must be a valid digit number.  It defaults to 1.
 
 (defun recentf-save-list ()
   "Save the recent list.
-Write data into the file specified by `recentf-save-file'."
+Write data into the file specified by `recentf-save-file', or by
+`recentf-native-save-file' if Emacs maintains the list natively."
   (interactive)
   (condition-case error
+      (if (recentf--native-p)
+          (let ((file (expand-file-name recentf-native-save-file)))
+            ;; Files written in the background keep the permissions
+            ;; of the file they replace, so set them up front.
+            (when (and recentf-save-file-modes (not (file-exists-p file)))
+              (write-region "" nil file nil 'silent)
+              (set-file-modes file recentf-save-file-modes))
+            (recentf--native-sync)
+            (recent-files-native-save file recentf-max-saved-items)
+            nil)
       (with-temp-buffer
         (erase-buffer)
         (set-buffer-file-coding-system recentf-save-file-coding-system)
@@ -1294,32 +1337,50 @@ Write data into the file specified by `recentf-save-file'."
         (write-file (expand-file-name recentf-save-file))
         (when recentf-save-file-modes
           (set-file-modes recentf-save-file recentf-save-file-modes))
-        nil)
+        nil))
     (error
      (warn "recentf mode: %s" (error-message-string error)))))
 
 (defun recentf-load-list ()
   "Load a previously saved recent list.
-Read data from the file specified by `recentf-save-file'.
+Read data from the file specified by `recentf-native-save-file' if
+Emacs maintains the list natively and that file exists, or else from
+the file specified by `recentf-save-file'.
 When `recentf-initialize-file-name-history' is non-nil, initialize an
 empty `file-name-history' with the recent list."
   (interactive)
   (let ((file (expand-file-name recentf-save-file))
         ;; We do not want Tramp asking for passwords.
         (non-essential t))
-    (when (file-readable-p file)
+    (cond
+     ((and (recentf--native-p)
+           (file-exists-p (expand-file-name recentf-native-save-file)))
+      (setq recentf-list
+            (recent-files-native-load
+             (expand-file-name recentf-native-save-file)))
+      (recent-files-native-check))
+     ((file-readable-p file)
       (load-file file)
-      (and recentf-initialize-file-name-history
-           (not file-name-history)
-           (setq file-name-history (mapcar 'abbreviate-file-name
-                                           recentf-list))))))
+      (recentf--native-sync)))
+    (and recentf-list
+         recentf-initialize-file-name-history
+         (not file-name-history)
+         (setq file-name-history (mapcar 'abbreviate-file-name
+                                         recentf-list)))))
 
 (defun recentf-cleanup ()
   "Cleanup the recent list.
 That is, remove duplicates, non-kept, and excluded files."
   (interactive)
   (message "Cleaning up the recentf list...")
+  (when (recentf--native-p)
+    ;; Drop the files found missing in the background.
+    (setq recentf-list (recent-files-native)))
   (let ((n 0)
+        ;; Files that don't exist are looked for in the background.
+        (recentf-keep (if (recentf--native-p)
+                          (remq 'recentf-keep-default-predicate recentf-keep)
+                        recentf-keep))
 	(ht (make-hash-table
 	     :size recentf-max-saved-items
 	     :test 'equal))
@@ -1336,7 +1397,11 @@ That is, remove duplicates, non-kept, and excluded files."
         (setq n (1+ n))
         (message "File %s removed from the recentf list" f)))
     (message "Cleaning up the recentf list...done (%d removed)" n)
-    (setq recentf-list (nreverse newlist))))
+    (setq recentf-list (nreverse newlist))
+    (when (recentf--native-p)
+      (recentf--native-sync)
+      (recent-files-native-check))
+    recentf-list))
 
 ;;; The minor mode
 ;;
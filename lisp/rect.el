@@ -184,38 +184,6 @@ The final point after the last operation will be returned."
       (setcdr lines (cons (filter-buffer-substring pt (point) t) (cdr lines))))
     ))
 
-;; This is actually the only function that needs to do complicated
-;; stuff like what's happening in `operate-on-rectangle', because the
-;; buffer might be read-only.
-(defun extract-rectangle-line (startcol endcol lines)
-  (let (start end begextra endextra line)
-    (move-to-column startcol)
-    (setq start (point)
-	  begextra (- (current-column) startcol))
-    (move-to-column endcol)
-    (setq end (point)
-	  endextra (- endcol (current-column)))
-    (setq line (buffer-substring start (point)))
-    (if (< begextra 0)
-	(setq endextra (+ endextra begextra)
-	      begextra 0))
-    (if (< endextra 0)
-	(setq endextra 0))
-    (goto-char start)
-    (while (search-forward "\t" end t)
-      (let ((width (- (current-column)
-		      (save-excursion (forward-char -1)
-				      (current-column)))))
-	(setq line (concat (substring line 0 (- (point) end 1))
-			   (spaces-string width)
-			   (substring line (+ (length line)
-					      (- (point) end)))))))
-    (if (or (> begextra 0) (> endextra 0))
-	(setq line (concat (spaces-string begextra)
-			   line
-			   (spaces-string endextra))))
-    (setcdr lines (cons line (cdr lines)))))
-
 (defconst spaces-strings
   '["" " " "  " "   " "    " "     " "      " "       " "        "])
 
@@ -314,28 +282,6 @@ even beep.)"
   (interactive "*")
   (insert-rectangle killed-rectangle))
 
-;;;###autoload
-(defun insert-rectangle (rectangle)
-  "Insert text of RECTANGLE with upper left corner at point.
-RECTANGLE's first line is inserted at point, its second
-line is inserted at a point vertically under point, etc.
-RECTANGLE should be a list of strings.
-After this command, the mark is at the upper left corner
-and point is at the lower right corner."
-  (let ((lines rectangle)
-	(insertcolumn (current-column))
-	(first t))
-    (push-mark)
-    (while lines
-      (or first
-	  (progn
-	   (forward-line 1)
-	   (or (bolp) (insert ?\n))
-	   (move-to-column insertcolumn t)))
-      (setq first nil)
-      (insert-for-yank (car lines))
-      (setq lines (cdr lines)))))
-
 ;;;###autoload
 (defun open-rectangle (start end &optional fill)
   "Blank out the region-rectangle, shifting text right.
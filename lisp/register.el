@@ -98,15 +98,6 @@ If nil, do not show register previews, unless `help-char' (or a member of
   :type '(choice number (const :tag "No preview unless requested" nil))
   :group 'register)
 
-(defun get-register (register)
-  "Return contents of Emacs register named REGISTER, or nil if none."
-  (alist-get register register-alist))
-
-(defun set-register (register value)
-  "Set contents of Emacs register named REGISTER to VALUE.  Returns VALUE.
-See the documentation of the variable `register-alist' for possible VALUEs."
-  (setf (alist-get register register-alist) value))
-
 (defun register-describe-oneline (c)
   "One-line description of register C."
   (let ((d (replace-regexp-in-string
@@ -80,11 +80,7 @@ and END, and return the encrypted string."
     (rot13-region (point-min) (point-max))
     (buffer-string)))
 
-;;;###autoload
-(defun rot13-region (start end)
-  "ROT13 encrypt the region between START and END in current buffer."
-  (interactive "r")
-  (translate-region start end rot13-translate-table))
+;; `rot13-region' is defined in transform.rs.
 
 ;;;###autoload
 (defun rot13-other-window ()
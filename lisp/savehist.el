@@ -98,6 +98,23 @@ set to a coding system that exists in both emacsen."
   :type 'file
   :group 'savehist)
 
+(defcustom savehist-native-file (locate-user-emacs-file "history.data")
+  "File to save minibuffer history into when Emacs can do it natively.
+When this is non-nil and Emacs has `history-native-save', the
+variables are saved into this file instead of `savehist-file'.  The
+file is written and read without going through the Lisp printer and
+reader, and it is written in the background, so that long histories
+don't hold Emacs up.
+A nil value means to always use `savehist-file'."
+  :type '(choice (const :tag "Don't use" nil)
+                 file)
+  :version "27.1"
+  :group 'savehist)
+
+(defsubst savehist--native-p ()
+  "Return non-nil if the history is saved natively."
+  (and savehist-native-file (fboundp 'history-native-save)))
+
 (defcustom savehist-file-modes #o600
   "Default permissions of the history file.
 This is decimal, not octal.  The default is 384 (0600 in octal).
@@ -187,14 +204,20 @@ histories, which is probably undesirable."
   (if (not savehist-mode)
       (savehist-uninstall)
     (when (and (not savehist-loaded)
-	       (file-exists-p savehist-file))
+	       (or (file-exists-p savehist-file)
+		   (and (savehist--native-p)
+			(file-exists-p savehist-native-file))))
       (condition-case errvar
 	  (progn
-	    ;; Don't set coding-system-for-read -- we rely on the
-	    ;; coding cookie to convey that information.  That way, if
-	    ;; the user changes the value of savehist-coding-system,
-	    ;; we can still correctly load the old file.
-	    (load savehist-file nil (not (called-interactively-p 'interactive)))
+	    (if (and (savehist--native-p)
+		     (file-exists-p savehist-native-file))
+		(history-native-load savehist-native-file)
+	      ;; Don't set coding-system-for-read -- we rely on the
+	      ;; coding cookie to convey that information.  That way, if
+	      ;; the user changes the value of savehist-coding-system,
+	      ;; we can still correctly load the old file.
+	      (load savehist-file nil
+		    (not (called-interactively-p 'interactive))))
 	    (setq savehist-loaded t))
 	(error
 	 ;; Don't install the mode if reading failed.  Doing so would
@@ -266,8 +289,36 @@ Normally invoked by calling `savehist-mode' to unset the minor mode."
   "Save the values of minibuffer history variables.
 Unbound symbols referenced in `savehist-additional-variables' are ignored.
 If AUTO-SAVE is non-nil, compare the saved contents to the one last saved,
- and don't save the buffer if they are the same."
+ and don't save the buffer if they are the same.
+If Emacs saves the history natively, save into `savehist-native-file'
+instead of `savehist-file'."
   (interactive)
+  (if (savehist--native-p)
+      (savehist--save-natively)
+    (savehist--save-printed auto-save
+                            (called-interactively-p 'interactive))))
+
+(defun savehist--save-natively ()
+  "Save the history variables into `savehist-native-file'."
+  (run-hooks 'savehist-save-hook)
+  (let ((file (expand-file-name savehist-native-file))
+        (variables savehist-additional-variables))
+    (when savehist-save-minibuffer-history
+      (dolist (symbol savehist-minibuffer-history-variables)
+        (unless (memq symbol savehist-ignored-variables)
+          (push symbol variables)))
+      (push 'savehist-minibuffer-history-variables variables))
+    ;; Files written in the background keep the permissions of the
+    ;; file they replace, so set them up front.
+    (when (and savehist-file-modes (not (file-exists-p file)))
+      (write-region "" nil file nil 'silent)
+      (set-file-modes file savehist-file-modes))
+    (history-native-save file variables)))
+
+(defun savehist--save-printed (auto-save interactive)
+  "Save the history variables into `savehist-file'.
+See `savehist-save' for the meaning of AUTO-SAVE.  If INTERACTIVE
+is nil, write the file quietly."
   (with-temp-buffer
     (insert
      (format-message
@@ -346,7 +397,7 @@ If AUTO-SAVE is non-nil, compare the saved contents to the one last saved,
 	(let ((file-precious-flag t)
 	      (coding-system-for-write savehist-coding-system))
 	  (write-region (point-min) (point-max) savehist-file nil
-			(unless (called-interactively-p 'interactive) 'quiet)))
+			(unless interactive 'quiet)))
 	(when savehist-file-modes
 	  (set-file-modes savehist-file savehist-file-modes))
 	(setq savehist-last-checksum checksum)))))
@@ -279,6 +279,78 @@ the symbols `PRIMARY', `SECONDARY', or `CLIPBOARD'.
 \(Those are literal upper-case symbol names, since that's what X expects.)"
   nil)
 
+;; Text terminals.  Terminals that `xterm' found to support OSC 52
+;; have more specific methods in term/xterm.el.
+
+(defcustom select-tty-clipboard 'ssh
+  "How cutting and pasting reach the clipboard on a text terminal.
+If nil, they don't.  If `native', use the clipboard of the machine
+Emacs runs on, if it has one.  If `osc52', send the text to the
+terminal emulator with the OSC 52 escape sequence, which works over
+SSH if the terminal supports it; the clipboard is not read back.
+If `ssh', use OSC 52 in an SSH session and the native clipboard
+otherwise.
+This only affects the `CLIPBOARD' selection, and only if
+`select-enable-clipboard' is non-nil, except that the native clipboard
+of a Wayland session also serves the `PRIMARY' selection."
+  :type '(choice (const :tag "None" nil)
+                 (const :tag "Native clipboard" native)
+                 (const :tag "OSC 52" osc52)
+                 (const :tag "OSC 52 over SSH, else native" ssh))
+  :group 'killing
+  :version "26.1")
+
+(defcustom select-tty-clipboard-persist t
+  "Non-nil means text put into the native clipboard outlives Emacs.
+Under X, the clipboard of a program is lost when it exits, so on exit
+the text that Emacs put there with the `native' method of
+`select-tty-clipboard' is handed to a helper program, xclip or xsel;
+see `clipboard-native-persist'.  Under Wayland, the text is served by
+wl-copy from the start."
+  :type 'boolean
+  :group 'killing
+  :version "26.1")
+
+(defvar select--tty-native-owned nil
+  "Alist of (SELECTION . TEXT) for the text Emacs put in the native clipboard.")
+
+(defun select--tty-clipboard ()
+  "Return the clipboard method `select-tty-clipboard' asks for."
+  (if (eq select-tty-clipboard 'ssh)
+      (if (getenv "SSH_TTY") 'osc52 'native)
+    select-tty-clipboard))
+
+(defun select--tty-clipboard-persist ()
+  "Hand the text Emacs put in the native clipboard to a helper program.
+This is done for each selection that still holds that text, when
+`select-tty-clipboard-persist' is non-nil."
+  (when (and select-tty-clipboard-persist (not (getenv "WAYLAND_DISPLAY")))
+    (dolist (owned select--tty-native-owned)
+      (when (equal (clipboard-native-get (car owned)) (cdr owned))
+        (clipboard-native-persist (car owned) (cdr owned))))))
+
+(add-hook 'kill-emacs-hook #'select--tty-clipboard-persist)
+
+(cl-defmethod gui-backend-set-selection (type value
+                                         &context (window-system nil))
+  (when (and (memq type '(CLIPBOARD PRIMARY)) (stringp value)
+             (not noninteractive))
+    (pcase (select--tty-clipboard)
+      ('native (when (clipboard-native-set type value)
+                 (setf (alist-get type select--tty-native-owned) value)))
+      ('osc52 (when (eq type 'CLIPBOARD)
+                (send-string-to-terminal
+                 (clipboard-osc52-sequence
+                  type value (eq (terminal-parameter nil 'terminal-initted)
+                                 'terminal-init-screen))))))))
+
+(cl-defmethod gui-backend-get-selection (type data-type
+                                         &context (window-system nil))
+  (when (and (memq type '(CLIPBOARD PRIMARY)) (eq data-type 'STRING)
+             (not noninteractive)
+             (eq (select--tty-clipboard) 'native))
+    (clipboard-native-get type)))
+
 (defun gui-get-selection (&optional type data-type)
   "Return the value of an X Windows selection.
 The argument TYPE (default `PRIMARY') says which selection,
@@ -478,33 +478,6 @@ If CLIENT is non-nil, add a description of it to the logged message."
       (file-error                       ;The pipe/socket was closed.
        (ignore-errors (server-delete-client proc))))))
 
-(defun server-unquote-arg (arg)
-  "Remove &-quotation from ARG.
-See `server-quote-arg' and `server-process-filter'."
-  (replace-regexp-in-string
-   "&." (lambda (s)
-	  (pcase (aref s 1)
-	    (?& "&")
-	    (?- "-")
-	    (?n "\n")
-	    (_ " ")))
-   arg t t))
-
-(defun server-quote-arg (arg)
-  "In ARG, insert a & before each &, each space, each newline, and -.
-Change spaces to underscores, too, so that the return value never
-contains a space.
-
-See `server-unquote-arg' and `server-process-filter'."
-  (replace-regexp-in-string
-   "[-&\n ]" (lambda (s)
-	       (pcase (aref s 0)
-		 (?& "&&")
-		 (?- "&-")
-		 (?\n "&n")
-		 (?\s "&_")))
-   arg t t))
-
 (defun server-send-string (proc string)
   "A wrapper around `process-send-string' for logging."
   (server-log (concat "Sent " string) proc)
@@ -784,19 +757,8 @@ Server mode runs a process that accepts commands from the
 QTEXT must be already quoted.
 This handles splitting the command if it would be bigger than
 `server-msg-size'."
-  (let ((prefix "-print ")
-	part)
-    (while (> (+ (length qtext) (length prefix) 1) server-msg-size)
-      ;; We have to split the string
-      (setq part (substring qtext 0 (- server-msg-size (length prefix) 1)))
-      ;; Don't split in the middle of a quote sequence
-      (if (string-match "\\(^\\|[^&]\\)&\\(&&\\)*$" part)
-	  ;; There is an uneven number of & at the end
-	  (setq part (substring part 0 -1)))
-      (setq qtext (substring qtext (length part)))
-      (server-send-string proc (concat prefix part "\n"))
-      (setq prefix "-print-nonl "))
-    (server-send-string proc (concat prefix qtext "\n"))))
+  (dolist (message (server-split-reply qtext server-msg-size))
+    (server-send-string proc message)))
 
 (defun server-create-tty-frame (tty type proc)
   (unless tty
@@ -1023,32 +985,32 @@ The following commands are accepted by the client:
   (server-log (concat "Received " string) proc)
   ;; First things first: let's check the authentication
   (unless (process-get proc :authenticated)
-    (if (and (string-match "-auth \\([!-~]+\\)\n?" string)
-	     (equal (match-string 1 string) (process-get proc :auth-key)))
-	(progn
-	  (setq string (substring string (match-end 0)))
-	  (process-put proc :authenticated t)
-	  (server-log "Authentication successful" proc))
-      (server-log "Authentication failed" proc)
-      (server-send-string
-       proc (concat "-error " (server-quote-arg "Authentication failed")))
-      ;; Before calling `delete-process', give emacsclient time to
-      ;; receive the error string and shut down on its own.
-      (sit-for 1)
-      (delete-process proc)
-      ;; We return immediately.
-      (cl-return-from server-process-filter)))
+    (let ((rest (server-authenticate string (process-get proc :auth-key))))
+      (if rest
+	  (progn
+	    (setq string rest)
+	    (process-put proc :authenticated t)
+	    (server-log "Authentication successful" proc))
+	(server-log "Authentication failed" proc)
+	(server-send-string
+	 proc (concat "-error " (server-quote-arg "Authentication failed")))
+	;; Before calling `delete-process', give emacsclient time to
+	;; receive the error string and shut down on its own.
+	(sit-for 1)
+	(delete-process proc)
+	;; We return immediately.
+	(cl-return-from server-process-filter))))
   (let ((prev (process-get proc 'previous-string)))
     (when prev
       (setq string (concat prev string))
       (process-put proc 'previous-string nil)))
   (condition-case err
-      (progn
+      (let ((request (server-parse-request string)))
 	(server-add-client proc)
 	;; Send our pid
 	(server-send-string proc (concat "-emacs-pid "
 					 (number-to-string (emacs-pid)) "\n"))
-	(if (not (string-match "\n" string))
+	(if (not request)
             ;; Save for later any partial line that remains.
             (when (> (length string) 0)
               (process-put proc 'previous-string string))
@@ -1056,9 +1018,8 @@ The following commands are accepted by the client:
           ;; In earlier versions of server.el (where we used an `emacsserver'
           ;; process), there could be multiple lines.  Nowadays this is not
           ;; supported any more.
-          (cl-assert (eq (match-end 0) (length string)))
-	  (let ((request (substring string 0 (match-beginning 0)))
-		(coding-system (and (default-value 'enable-multibyte-characters)
+          (cl-assert (equal (cdr request) ""))
+	  (let ((coding-system (and (default-value 'enable-multibyte-characters)
 				    (or file-name-coding-system
 					default-file-name-coding-system)))
 		nowait     ; t if emacsclient does not want to wait for us.
@@ -1076,9 +1037,8 @@ The following commands are accepted by the client:
 		filepos
 		args-left)
 	    ;; Remove this line from STRING.
-	    (setq string (substring string (match-end 0)))
-	    (setq args-left
-		  (mapcar 'server-unquote-arg (split-string request " " t)))
+	    (setq string (cdr request))
+	    (setq args-left (car request))
 	    (while args-left
               (pcase (pop args-left)
                 ;; -version CLIENT-VERSION: obsolete at birth.
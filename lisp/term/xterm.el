@@ -889,8 +889,7 @@ The title is constructed from `frame-title-format'."
                                       (insert char)
                                       t))))))
        'no-async)
-      (base64-decode-region (point-min) (point-max))
-      (decode-coding-region (point-min) (point-max) 'utf-8-unix t))))
+      (clipboard-osc52-decode (buffer-string)))))
 
 (cl-defmethod gui-backend-set-selection
     (type data
@@ -918,23 +917,13 @@ chops long DCS sequences into multiple smaller ones to avoid
 hitting screen's max DCS length."
   (let* ((screen (eq (terminal-parameter nil 'terminal-initted)
                      'terminal-init-screen))
-         (bytes (encode-coding-string data 'utf-8-unix))
-         (base-64 (if screen
-                      (replace-regexp-in-string
-                       "\n" "\e\\\eP"
-                       (base64-encode-string bytes)
-                       :fixedcase :literal)
-                    (base64-encode-string bytes :no-line-break)))
-         (length (length base-64)))
+         (sequence (clipboard-osc52-sequence type data screen))
+         (length (length sequence)))
     (if (> length xterm-max-cut-length)
         (progn
           (warn "Selection too long to send to terminal: %d bytes" length)
           (sit-for 2))
-      (send-string-to-terminal
-       (concat
-        (when screen "\eP")
-        "\e]52;" (xterm--selection-char type) ";" base-64 "\a"
-        (when screen "\e\\"))))))
+      (send-string-to-terminal sequence))))
 
 (defun xterm-rgb-convert-to-16bit (prim)
   "Convert an 8-bit primary color value PRIM to a corresponding 16-bit value."
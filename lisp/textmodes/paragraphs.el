@@ -223,135 +223,8 @@ to which the end of the previous line belongs, or the end of the buffer.
 Returns the count of paragraphs left to move."
   (interactive "^p")
   (or arg (setq arg 1))
-  (let* ((opoint (point))
-	 (fill-prefix-regexp
-	  (and fill-prefix (not (equal fill-prefix ""))
-	       (not paragraph-ignore-fill-prefix)
-	       (regexp-quote fill-prefix)))
-	 ;; Remove ^ from paragraph-start and paragraph-sep if they are there.
-	 ;; These regexps shouldn't be anchored, because we look for them
-	 ;; starting at the left-margin.  This allows paragraph commands to
-	 ;; work normally with indented text.
-	 ;; This hack will not find problem cases like "whatever\\|^something".
-	 (parstart (if (and (not (equal "" paragraph-start))
-			    (equal ?^ (aref paragraph-start 0)))
-		       (substring paragraph-start 1)
-		     paragraph-start))
-	 (parsep (if (and (not (equal "" paragraph-separate))
-			  (equal ?^ (aref paragraph-separate 0)))
-		     (substring paragraph-separate 1)
-		   paragraph-separate))
-	 (parsep
-	  (if fill-prefix-regexp
-	      (concat parsep "\\|"
-		      fill-prefix-regexp "[ \t]*$")
-	    parsep))
-	 ;; This is used for searching.
-	 (sp-parstart (concat "^[ \t]*\\(?:" parstart "\\|" parsep "\\)"))
-	 start found-start)
-    (while (and (< arg 0) (not (bobp)))
-      (if (and (not (looking-at parsep))
-	       (re-search-backward "^\n" (max (1- (point)) (point-min)) t)
-	       (looking-at parsep))
-	  (setq arg (1+ arg))
-	(setq start (point))
-	;; Move back over paragraph-separating lines.
-	(forward-char -1) (beginning-of-line)
-	(while (and (not (bobp))
-		    (progn (move-to-left-margin)
-			   (looking-at parsep)))
-	  (forward-line -1))
-	(if (bobp)
-	    nil
-	  (setq arg (1+ arg))
-	  ;; Go to end of the previous (non-separating) line.
-	  (end-of-line)
-	  ;; Search back for line that starts or separates paragraphs.
-	  (if (if fill-prefix-regexp
-		  ;; There is a fill prefix; it overrides parstart.
-		  (let (multiple-lines)
-		    (while (and (progn (beginning-of-line) (not (bobp)))
-				(progn (move-to-left-margin)
-				       (not (looking-at parsep)))
-				(looking-at fill-prefix-regexp))
-		      (unless (= (point) start)
-			(setq multiple-lines t))
-		      (forward-line -1))
-		    (move-to-left-margin)
-		    ;; This deleted code caused a long hanging-indent line
-		    ;; not to be filled together with the following lines.
-		    ;; ;; Don't move back over a line before the paragraph
-		    ;; ;; which doesn't start with fill-prefix
-		    ;; ;; unless that is the only line we've moved over.
-		    ;; (and (not (looking-at fill-prefix-regexp))
-		    ;;      multiple-lines
-		    ;;      (forward-line 1))
-		    (not (bobp)))
-		(while (and (re-search-backward sp-parstart nil 1)
-			    (setq found-start t)
-			    ;; Found a candidate, but need to check if it is a
-			    ;; REAL parstart.
-			    (progn (setq start (point))
-				   (move-to-left-margin)
-				   (not (looking-at parsep)))
-			    (not (and (looking-at parstart)
-				      (or (not use-hard-newlines)
-					  (bobp)
-					  (get-text-property
-					   (1- start) 'hard)))))
-		  (setq found-start nil)
-		  (goto-char start))
-		found-start)
-	      ;; Found one.
-	      (progn
-		;; Move forward over paragraph separators.
-		;; We know this cannot reach the place we started
-		;; because we know we moved back over a non-separator.
-		(while (and (not (eobp))
-			    (progn (move-to-left-margin)
-				   (looking-at parsep)))
-		  (forward-line 1))
-		;; If line before paragraph is just margin, back up to there.
-		(end-of-line 0)
-		(if (> (current-column) (current-left-margin))
-		    (forward-char 1)
-		  (skip-chars-backward " \t")
-		  (if (not (bolp))
-		      (forward-line 1))))
-	    ;; No starter or separator line => use buffer beg.
-	    (goto-char (point-min))))))
-
-    (while (and (> arg 0) (not (eobp)))
-      ;; Move forward over separator lines...
-      (while (and (not (eobp))
-		  (progn (move-to-left-margin) (not (eobp)))
-		  (looking-at parsep))
-	(forward-line 1))
-      (unless (eobp) (setq arg (1- arg)))
-      ;; ... and one more line.
-      (forward-line 1)
-      (if fill-prefix-regexp
-	  ;; There is a fill prefix; it overrides parstart.
-	  (while (and (not (eobp))
-		      (progn (move-to-left-margin) (not (eobp)))
-		      (not (looking-at parsep))
-		      (looking-at fill-prefix-regexp))
-	    (forward-line 1))
-	(while (and (re-search-forward sp-parstart nil 1)
-		    (progn (setq start (match-beginning 0))
-			   (goto-char start)
-			   (not (eobp)))
-		    (progn (move-to-left-margin)
-			   (not (looking-at parsep)))
-		    (or (not (looking-at parstart))
-			(and use-hard-newlines
-			     (not (get-text-property (1- start) 'hard)))))
-	  (forward-char 1))
-	(if (< (point) (point-max))
-	    (goto-char start))))
-    (constrain-to-field nil opoint t)
-    ;; Return the number of steps that could not be done.
-    arg))
+  ;; The scanning is done natively, see paragraphs.rs.
+  (forward-paragraph-internal arg))
 
 (defun backward-paragraph (&optional arg)
   "Move backward to start of paragraph.
@@ -456,35 +329,8 @@ The variable `sentence-end' is a regular expression that matches ends of
 sentences.  Also, every paragraph boundary terminates sentences as well."
   (interactive "^p")
   (or arg (setq arg 1))
-  (let ((opoint (point))
-        (sentence-end (sentence-end)))
-    (while (< arg 0)
-      (let ((pos (point))
-	    par-beg par-text-beg)
-	(save-excursion
-	  (start-of-paragraph-text)
-	  ;; Start of real text in the paragraph.
-	  ;; We move back to here if we don't see a sentence-end.
-	  (setq par-text-beg (point))
-	  ;; Start of the first line of the paragraph.
-	  ;; We use this as the search limit
-	  ;; to allow sentence-end to match if it is anchored at
-	  ;; BOL and the paragraph starts indented.
-	  (beginning-of-line)
-	  (setq par-beg (point)))
-	(if (and (re-search-backward sentence-end par-beg t)
-		 (or (< (match-end 0) pos)
-		     (re-search-backward sentence-end par-beg t)))
-	    (goto-char (match-end 0))
-	  (goto-char par-text-beg)))
-      (setq arg (1+ arg)))
-    (while (> arg 0)
-      (let ((par-end (save-excursion (end-of-paragraph-text) (point))))
-	(if (re-search-forward sentence-end par-end t)
-	    (skip-chars-backward " \t\n")
-	  (goto-char par-end)))
-      (setq arg (1- arg)))
-    (constrain-to-field nil opoint t)))
+  ;; The scanning is done natively, see paragraphs.rs.
+  (forward-sentence-internal arg))
 
 (defun repunctuate-sentences ()
   "Put two spaces at the end of sentences from point to the end of buffer.
@@ -66,13 +66,33 @@ telling Microsoft that."
   :type 'boolean
   :group 'url-cookie)
 
+(defcustom url-cookie-native t
+  "If non-nil, keep cookies in the native cookie jar when available.
+The native jar parses Set-Cookie headers as RFC 6265 says and saves
+cookies to `url-cookie-file' with \".dat\" appended.  Use
+`url-cookie-native-list' to see its cookies."
+  :type 'boolean
+  :version "27.1"
+  :group 'url-cookie)
+
 (defvar url-cookies-changed-since-last-save nil
   "Whether the cookies list has changed since the last save operation.")
 
+(defun url-cookie--native-p ()
+  "Return non-nil if cookies are kept in the native cookie jar."
+  (and url-cookie-native (fboundp 'url-cookie-native-store)))
+
+(defun url-cookie--native-file (fname)
+  (concat (expand-file-name (or fname url-cookie-file)) ".dat"))
+
 (defun url-cookie-parse-file (&optional fname)
   "Load FNAME, default `url-cookie-file'."
-  ;; It's completely normal for the cookies file not to exist yet.
-  (load (or fname url-cookie-file) t t))
+  (if (url-cookie--native-p)
+      (let ((file (url-cookie--native-file fname)))
+        (when (file-exists-p file)
+          (url-cookie-native-load file)))
+    ;; It's completely normal for the cookies file not to exist yet.
+    (load (or fname url-cookie-file) t t)))
 
 (defun url-cookie-clean-up (&optional secure)
   (let ((var (if secure 'url-cookie-secure-storage 'url-cookie-storage))
@@ -98,6 +118,8 @@ telling Microsoft that."
               nil)
           (error t))
         (message "Error accessing cookie file `%s'" fname)
+    (if (url-cookie--native-p)
+        (url-cookie-native-save (url-cookie--native-file fname))
     (url-cookie-clean-up)
     (url-cookie-clean-up t)
     (with-temp-buffer
@@ -114,7 +136,7 @@ telling Microsoft that."
               ";; no-byte-compile: t\n"
               ";; End:\n")
       (set (make-local-variable 'version-control) 'never)
-      (write-file fname))
+      (write-file fname)))
     (setq url-cookies-changed-since-last-save nil))))
 
 (defun url-cookie-store (name value &optional expires domain localpart secure)
@@ -196,6 +218,11 @@ telling Microsoft that."
     retval))
 
 (defun url-cookie-generate-header-lines (host localpart secure)
+  (if (url-cookie--native-p)
+      (let ((header (url-cookie-native-header host localpart secure)))
+        (if header
+            (concat "Cookie: " header "\r\n")
+          ""))
   (let ((cookies (url-cookie-retrieve host localpart secure))
 	retval chunk)
     ;; Have to sort this for sending most specific cookies first.
@@ -214,7 +241,7 @@ telling Microsoft that."
 		       (concat "Cookie: " chunk)))))
     (if retval
 	(concat retval "\r\n")
-      "")))
+      ""))))
 
 (defcustom url-cookie-trusted-urls nil
   "A list of regular expressions matching URLs to always accept cookies from."
@@ -320,6 +347,12 @@ telling Microsoft that."
 		   (kill-buffer "*Cookie Warning*")))))
       ;; User wants to be asked, and declined.
       nil)
+     ((url-cookie--native-p)
+      ;; The native jar does its own domain checks.
+      (unless (url-cookie-native-store str (url-host url-current-object)
+                                       (url-filename url-current-object))
+        (url-lazy-message "%s tried to set an invalid cookie - rejected."
+                          (url-host url-current-object))))
      ((url-cookie-host-can-set-p (url-host url-current-object) domain)
       ;; Cookie is accepted by the user, and passes our security checks.
       (dolist (cur rest)
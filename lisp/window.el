@@ -9115,7 +9115,7 @@ Otherwise, consult the value of `truncate-partial-width-windows'
   "Control how Emacs chooses inferior process window sizes.
 Emacs uses this function to tell processes the space they have
 available for displaying their output.  After each window
-configuration change, Emacs calls the value of
+configuration change or window resize, Emacs calls the value of
 `window-adjust-process-window-size-function' for each process
 with a buffer being displayed in at least one window.
 This function is responsible for combining the sizes of the
@@ -9208,6 +9208,16 @@ displaying that processes's buffer."
 
 (add-hook 'window-configuration-change-hook 'window--adjust-process-windows)
 
+(defun window--adjust-process-windows-on-resize (_frame)
+  "Update process window sizes after windows on a frame change size.
+This makes processes running in a pty receive SIGWINCH when the
+window displaying them is resized, even if the window configuration
+did not otherwise change."
+  (window--adjust-process-windows))
+
+(add-hook 'window-size-change-functions
+          'window--adjust-process-windows-on-resize)
+
 
 ;; Some of these are in tutorial--default-keys, so update that if you
 ;; change these.
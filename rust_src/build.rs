@@ -93,6 +93,13 @@ fn export_function(
 }
 
 static C_NAME: &str = "c_name = \"";
+static CFG_FEATURE: &str = "#![cfg(feature = \"";
+
+// Whether the Cargo feature NAME is enabled for this build.
+fn feature_enabled(name: &str) -> bool {
+    let var = format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"));
+    env::var_os(var).is_some()
+}
 
 fn c_exports_for_module<R>(
     modname: &str,
@@ -113,7 +120,16 @@ where
 
         match parse_state {
             ParseState::Looking => {
-                if line.starts_with("#[lisp_fn") {
+                if line.starts_with(CFG_FEATURE) {
+                    // A module that is only compiled with a feature
+                    // exports nothing without it.
+                    let start = CFG_FEATURE.len();
+                    let end = line[start..].find('"').unwrap() + start;
+                    if !feature_enabled(&line[start..end]) {
+                        exported.clear();
+                        break;
+                    }
+                } else if line.starts_with("#[lisp_fn") {
                     if let Some(begin) = line.find(C_NAME) {
                         let start = begin + C_NAME.len();
                         let end = line[start..].find('"').unwrap() + start;
@@ -254,17 +270,47 @@ fn ignore(path: &str) -> bool {
     path == "" || path.starts_with('.') || path == "lib.rs"
 }
 
+static CFG_UNIX: &str = "#[cfg(unix)]";
+
+// The modules that lib.rs declares only when building for Unix.
+fn unix_modules() -> Result<Vec<String>, io::Error> {
+    let lib_path: PathBuf = [&env_var("CARGO_MANIFEST_DIR"), "src", "lib.rs"]
+        .iter()
+        .collect();
+    let mut modules = Vec::new();
+    let mut unix_only = false;
+
+    for line in BufReader::new(File::open(lib_path)?).lines() {
+        let line = line?;
+        if unix_only && line.starts_with("mod ") {
+            modules.push(line[4..].trim_right_matches(';').to_string());
+        }
+        unix_only = line == CFG_UNIX;
+    }
+
+    Ok(modules)
+}
+
 fn generate_c_exports() -> Result<(), io::Error> {
     let out_path: PathBuf = [&env_var("OUT_DIR"), "c_exports.rs"].iter().collect();
     let mut out_file = File::create(out_path)?;
 
     let mut modules: Vec<String> = Vec::new();
 
+    // Modules left out of this build export nothing.
+    let skipped = if env::var_os("CARGO_CFG_UNIX").is_some() {
+        Vec::new()
+    } else {
+        unix_modules()?
+    };
+
     let in_path: PathBuf = [&env_var("CARGO_MANIFEST_DIR"), "src"].iter().collect();
     for entry in fs::read_dir(in_path)? {
         let mod_path = entry?.path();
 
-        if !ignore(path_as_str(mod_path.file_name())) {
+        if !ignore(path_as_str(mod_path.file_name()))
+            && !skipped.iter().any(|m| m == path_as_str(mod_path.file_stem()))
+        {
             if let Some(modname) = handle_file(mod_path, &out_file)? {
                 modules.push(modname);
             }
@@ -278,12 +324,25 @@ fn generate_c_exports() -> Result<(), io::Error> {
             out_file,
             "#[no_mangle]\npub extern \"C\" fn rust_init_syms() {{\n"
         )?;
-        for module in modules {
+        for module in &modules {
             write!(out_file, "    {}::rust_init_syms();\n", module)?;
         }
         // Add this one by hand.
         write!(out_file, "    floatfns::rust_init_extra_syms();\n")?;
         write!(out_file, "}}\n")?;
+
+        write!(
+            out_file,
+            "\npub fn rust_subrs() -> Vec<*const ::remacs_sys::Lisp_Subr> {{\n"
+        )?;
+        write!(out_file, "    let mut subrs = Vec::new();\n")?;
+        for module in &modules {
+            write!(out_file, "    subrs.extend({}::rust_subrs());\n", module)?;
+        }
+        // As above.
+        write!(out_file, "    subrs.extend(floatfns::rust_extra_subrs());\n")?;
+        write!(out_file, "    subrs\n")?;
+        write!(out_file, "}}\n")?;
     }
 
     Ok(())
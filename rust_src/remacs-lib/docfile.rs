@@ -102,7 +102,13 @@ pub extern "C" fn scan_rust_file(
                 let c_name_str = CString::new(format!("F{}", attr_props.c_name)).unwrap();
                 // -1 is MANY
                 // -2 is UNEVALLED
-                let maxargs = if has_many_args { -1 } else { nargs as c_int };
+                let maxargs = if has_many_args {
+                    -1
+                } else if attr_props.unevalled {
+                    -2
+                } else {
+                    nargs as c_int
+                };
                 add_global(FUNCTION, c_name_str.as_ptr(), maxargs, ptr::null());
             } else {
                 // Create usage line (fn ARG1 ...) from signature if necessary
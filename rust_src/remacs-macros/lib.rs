@@ -70,9 +70,16 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
     let rname = function.name;
     let min_args = lisp_fn_args.min;
     let mut windows_header = quote!{};
-    let max_args = match function.fntype {
-        function::LispFnType::Normal(_) => quote! { #max_args },
-        function::LispFnType::Many => quote! { ::lisp::MANY  },
+    let max_args = if lisp_fn_args.unevalled {
+        match function.fntype {
+            function::LispFnType::Normal(1) => quote! { ::lisp::UNEVALLED },
+            _ => panic!("unevalled lisp functions must take exactly one `LispObject` argument"),
+        }
+    } else {
+        match function.fntype {
+            function::LispFnType::Normal(_) => quote! { #max_args },
+            function::LispFnType::Many => quote! { ::lisp::MANY  },
+        }
     };
     let symbol_name = CByteLiteral(&lisp_fn_args.name);
 
@@ -89,7 +96,7 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
             #body
 
             let ret = #rname(#rargs);
-            ret.to_raw()
+            ::eval::LispFnReturn::into_lisp(ret)
         }
 
         lazy_static! {
@@ -84,6 +84,15 @@ struct BitfieldPadding {
     _p2: u16,
 }
 
+/// A range of text and the properties to add to it, for
+/// `add_text_property_runs'.
+#[repr(C)]
+pub struct text_property_run {
+    pub start: ptrdiff_t,
+    pub end: ptrdiff_t,
+    pub properties: Lisp_Object,
+}
+
 /// Bit pattern used in the least significant bits of a lisp object,
 /// to denote its type.
 #[repr(u8)]
@@ -215,10 +224,10 @@ pub struct Lisp_String {
 
 #[repr(C)]
 pub union SymbolUnion {
-    value: Lisp_Object,
+    pub value: Lisp_Object,
     pub alias: *mut Lisp_Symbol,
-    blv: *mut c_void, // @TODO implement Lisp_Buffer_Local_Value
-    fwd: *mut c_void, // @TODO implement Lisp_Fwd
+    pub blv: *mut c_void, // @TODO implement Lisp_Buffer_Local_Value
+    pub fwd: *mut c_void, // @TODO implement Lisp_Fwd
 }
 
 /// This struct has 4 bytes of padding, representing the bitfield that
@@ -418,6 +427,7 @@ extern "C" {
     pub fn wget_parent(w: *const Lisp_Window) -> Lisp_Object;
     pub fn wget_pixel_height(w: *const Lisp_Window) -> c_int;
     pub fn wget_pseudo_window_p(w: *const Lisp_Window) -> bool;
+    pub fn wget_horizontal(w: *const Lisp_Window) -> bool;
 
     pub fn window_parameter(w: *const Lisp_Window, parameter: Lisp_Object) -> Lisp_Object;
 }
@@ -610,6 +620,66 @@ pub enum EqualKind {
     IncludingProperties,
 }
 
+/// Where the value of a symbol can be found.  See `enum
+/// symbol_redirect` in lisp.h.
+#[repr(C)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SymbolRedirect {
+    VarAlias = 1,
+    Localized = 2,
+    Forwarded = 3,
+    PlainVal = 4,
+}
+
+/// Kinds of entries on the specpdl stack.  See `enum specbind_tag`
+/// in lisp.h. Tags greater than `Let` are "subkinds" of `Let`.
+#[repr(C)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SpecbindTag {
+    Unwind,
+    UnwindPtr,
+    UnwindInt,
+    UnwindVoid,
+    Backtrace,
+    Let,
+    LetLocal,
+    LetDefault,
+}
+
+/// Why `set_internal` is being called.  See `enum Set_Internal_Bind`
+/// in lisp.h.
+#[repr(C)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SetInternalBind {
+    Set,
+    Bind,
+    Unbind,
+    ThreadSwitch,
+}
+
+/// Kinds of handler on the catch/condition-case stack. Equivalent to
+/// `enum handlertype` in lisp.h.
+#[repr(C)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum HandlerType {
+    Catcher,
+    ConditionCase,
+    CatcherAll,
+}
+
+/// A catch tag or condition handler. Only the leading fields of
+/// `struct handler` in lisp.h are declared here; the rest (the jump
+/// buffer and saved interpreter state) are only ever touched from C,
+/// so handlers must not be allocated from Rust.
+#[repr(C)]
+pub struct handler {
+    pub type_: HandlerType,
+    pub tag_or_ch: Lisp_Object,
+    pub val: Lisp_Object,
+    pub next: *mut handler,
+    pub nextfree: *mut handler,
+}
+
 #[repr(C)]
 pub struct re_registers {
     pub num_regs: libc::c_uint,
@@ -653,8 +723,8 @@ pub struct thread_state {
     /// of Fcondition_case and internal_condition_case.
     /// When an error is signaled (by calling Fsignal),
     /// this chain is searched for an element that applies.
-    pub m_handlerlist: *mut c_void, // TODO
-    pub m_handlerlist_list: *mut c_void, // TODO
+    pub m_handlerlist: *mut handler,
+    pub m_handlerlist_list: *mut handler,
 
     /// Current number of specbindings allocated in specpdl.
     pub m_specpdl_size: ptrdiff_t,
@@ -757,6 +827,22 @@ pub struct Lisp_Char_Table {
     pub extras: [Lisp_Object; 1],
 }
 
+#[repr(C)]
+pub struct Lisp_Sub_Char_Table {
+    pub header: Lisp_Vectorlike_Header,
+
+    /// Depth of this sub char-table.  It should be 1, 2, or 3.  A sub
+    /// char-table of depth 1 contains 16 elements, and each element
+    /// covers 4096 (128*32) characters.
+    pub depth: c_int,
+
+    /// Minimum character covered by the sub char-table.
+    pub min_char: c_int,
+
+    // actually any number of items
+    pub contents: [Lisp_Object; 1],
+}
+
 #[repr(C)]
 pub struct Lisp_Process {
     pub header: Lisp_Vectorlike_Header,
@@ -833,11 +919,37 @@ pub struct Lisp_Process {
 extern "C" {
     pub fn pget_pid(p: *const Lisp_Process) -> pid_t;
     pub fn pget_kill_without_query(p: *const Lisp_Process) -> BoolBF;
+    pub fn pget_accumulation(p: *const Lisp_Process) -> Lisp_Object;
+    pub fn pget_infd(p: *const Lisp_Process) -> c_int;
+    pub fn pget_pty_flag(p: *const Lisp_Process) -> BoolBF;
+    pub fn pget_alive(p: *const Lisp_Process) -> BoolBF;
+}
+
+/// Functions to change the state of a process on behalf of Rust code.
+extern "C" {
+    pub fn emacs_get_tty_pgrp(p: *const Lisp_Process) -> pid_t;
+    pub fn process_continued(p: *mut Lisp_Process, nomsg: bool);
+    pub fn set_connection_stopped(p: *mut Lisp_Process, stopped: bool);
+}
+
+#[cfg(unix)]
+extern "C" {
+    pub fn block_child_signal(oldset: *mut libc::sigset_t);
+    pub fn unblock_child_signal(oldset: *const libc::sigset_t);
+}
+
+#[cfg(not(unix))]
+extern "C" {
+    pub fn sys_kill(pid: pid_t, sig: c_int) -> c_int;
+    pub fn network_interface_list() -> Lisp_Object;
+    pub fn network_interface_info(ifname: Lisp_Object) -> Lisp_Object;
+    pub fn w32_move_file_to_trash(filename: Lisp_Object) -> Lisp_Object;
 }
 
 /// Functions to set members of `struct Lisp_Process`.
 extern "C" {
     pub fn pset_kill_without_query(p: *mut Lisp_Process, b: BoolBF);
+    pub fn pset_accumulation(p: *mut Lisp_Process, val: Lisp_Object);
 }
 
 #[repr(C)]
@@ -983,6 +1095,7 @@ pub struct Lisp_Hash_Table {
     pub key_and_value: Lisp_Object,
     pub test: hash_table_test,
     pub next_weak: *mut Lisp_Hash_Table,
+    pub key_stamp: EmacsUint,
 }
 
 extern "C" {
@@ -997,13 +1110,20 @@ extern "C" {
     pub static Vfeatures: Lisp_Object;
     pub static mut Vautoload_queue: Lisp_Object;
     pub static minibuf_level: EmacsInt;
+    pub static string_text_stamp: EmacsUint;
+    pub static initialized: bool;
     pub static mut minibuf_window: Lisp_Object;
     pub static selected_window: Lisp_Object;
     pub static minibuf_selected_window: Lisp_Object;
     pub static selected_frame: Lisp_Object;
+    pub static current_kboard: *mut c_void;
+    pub static mut update_mode_lines: c_int;
+    pub static mut executing_kbd_macro: Lisp_Object;
+    pub static mut executing_kbd_macro_iterations: EmacsInt;
 
     pub fn Faref(array: Lisp_Object, idx: Lisp_Object) -> Lisp_Object;
     pub fn Fcons(car: Lisp_Object, cdr: Lisp_Object) -> Lisp_Object;
+    pub fn Fmake_vector(length: Lisp_Object, init: Lisp_Object) -> Lisp_Object;
     pub fn Fsignal(error_symbol: Lisp_Object, data: Lisp_Object) -> !;
     pub fn Fcopy_sequence(seq: Lisp_Object) -> Lisp_Object;
     pub fn Ffind_operation_coding_system(nargs: ptrdiff_t, args: *mut Lisp_Object) -> Lisp_Object;
@@ -1027,6 +1147,12 @@ extern "C" {
         multibyte: bool,
     ) -> Lisp_Object;
     pub fn string_to_multibyte(string: Lisp_Object) -> Lisp_Object;
+    pub fn lisp_string_width(
+        string: Lisp_Object,
+        precision: ptrdiff_t,
+        nchars: *mut ptrdiff_t,
+        nbytes: *mut ptrdiff_t,
+    ) -> ptrdiff_t;
     pub fn initial_define_key(keymap: Lisp_Object, key: c_int, defname: *const c_char);
 
     pub fn preferred_coding_system() -> Lisp_Object;
@@ -1052,6 +1178,43 @@ extern "C" {
 
     pub fn record_unwind_current_buffer();
     pub fn set_buffer_internal(buffer: *mut Lisp_Buffer);
+    pub fn del_range_byte(from_byte: libc::ptrdiff_t, to_byte: libc::ptrdiff_t);
+    pub fn del_range(from: libc::ptrdiff_t, to: libc::ptrdiff_t);
+    pub fn modify_text(start: libc::ptrdiff_t, end: libc::ptrdiff_t);
+    pub fn record_change(beg: libc::ptrdiff_t, length: libc::ptrdiff_t);
+    pub fn signal_after_change(
+        charpos: libc::ptrdiff_t,
+        lendel: libc::ptrdiff_t,
+        lenins: libc::ptrdiff_t,
+    );
+    pub fn update_compositions(from: libc::ptrdiff_t, to: libc::ptrdiff_t, check_mask: c_int);
+    pub fn prepare_for_mapped_text();
+    pub fn insert_mapped_text(text: *mut c_uchar, nbytes: ptrdiff_t, gap: ptrdiff_t);
+    pub fn replace_range(
+        from: libc::ptrdiff_t,
+        to: libc::ptrdiff_t,
+        new: Lisp_Object,
+        prepare: bool,
+        inherit: bool,
+        markers: bool,
+        adjust_match_data: bool,
+    );
+    pub fn char_table_ref(table: Lisp_Object, c: c_int) -> Lisp_Object;
+    pub fn add_read_fd(
+        fd: c_int,
+        func: extern "C" fn(fd: c_int, data: *mut libc::c_void),
+        data: *mut libc::c_void,
+    );
+    pub fn delete_read_fd(fd: c_int);
+    pub fn kbd_buffer_store_dbus_event(arg: Lisp_Object);
+    pub fn insert_from_string(
+        string: Lisp_Object,
+        pos: libc::ptrdiff_t,
+        pos_byte: libc::ptrdiff_t,
+        length: libc::ptrdiff_t,
+        length_byte: libc::ptrdiff_t,
+        inherit: bool,
+    );
     pub fn make_buffer_string(
         start: libc::ptrdiff_t,
         end: libc::ptrdiff_t,
@@ -1072,6 +1235,30 @@ extern "C" {
         size_bytes: ptrdiff_t,
     ) -> Lisp_Object;
 
+    pub fn FUNCTIONP(object: Lisp_Object) -> bool;
+    pub fn Fcommandp(function: Lisp_Object, for_call_interactively: Lisp_Object) -> Lisp_Object;
+    pub fn Fcompare_strings(
+        str1: Lisp_Object,
+        start1: Lisp_Object,
+        end1: Lisp_Object,
+        str2: Lisp_Object,
+        start2: Lisp_Object,
+        end2: Lisp_Object,
+        ignore_case: Lisp_Object,
+    ) -> Lisp_Object;
+    pub fn Fstring_match(regexp: Lisp_Object, string: Lisp_Object, start: Lisp_Object)
+        -> Lisp_Object;
+    pub fn Fassoc_string(key: Lisp_Object, list: Lisp_Object, case_fold: Lisp_Object)
+        -> Lisp_Object;
+    pub fn Fstring_make_unibyte(string: Lisp_Object) -> Lisp_Object;
+    pub fn Fstring_make_multibyte(string: Lisp_Object) -> Lisp_Object;
+    pub fn Fsubstring(string: Lisp_Object, from: Lisp_Object, to: Lisp_Object) -> Lisp_Object;
+    pub fn concat2(s1: Lisp_Object, s2: Lisp_Object) -> Lisp_Object;
+    pub fn Fnreverse(seq: Lisp_Object) -> Lisp_Object;
+    pub fn Fmove_to_column(column: Lisp_Object, force: Lisp_Object) -> Lisp_Object;
+    pub fn Fforward_line(n: Lisp_Object) -> Lisp_Object;
+    pub fn Fbuffer_substring(start: Lisp_Object, end: Lisp_Object) -> Lisp_Object;
+
     pub fn CHECK_IMPURE(obj: Lisp_Object, ptr: *const c_void);
     pub fn internal_equal(
         o1: Lisp_Object,
@@ -1166,10 +1353,27 @@ extern "C" {
         object: Lisp_Object,
     ) -> Lisp_Object;
 
+    pub fn validate_interval_range(
+        object: Lisp_Object,
+        begin: *mut Lisp_Object,
+        end: *mut Lisp_Object,
+        force: bool,
+    ) -> *mut c_void;
+    pub fn validate_plist(list: Lisp_Object) -> Lisp_Object;
+    pub fn add_text_property_runs(
+        object: Lisp_Object,
+        nruns: ptrdiff_t,
+        runs: *mut text_property_run,
+    ) -> bool;
+
     pub fn find_symbol_value(symbol: Lisp_Object) -> Lisp_Object;
     pub fn symbol_is_interned(symbol: *const Lisp_Symbol) -> bool;
     pub fn symbol_is_alias(symbol: *const Lisp_Symbol) -> bool;
     pub fn symbol_is_constant(symbol: *const Lisp_Symbol) -> bool;
+    pub fn symbol_is_declared_special(symbol: *const Lisp_Symbol) -> bool;
+    pub fn symbol_get_redirect(symbol: *const Lisp_Symbol) -> SymbolRedirect;
+    pub fn blv_found(blv: *const c_void) -> bool;
+    pub fn BUFFER_OBJFWDP(fwd: *const c_void) -> bool;
     pub fn misc_get_ty(any: *const Lisp_Misc_Any) -> u16;
     pub fn is_minibuffer(w: *const Lisp_Window) -> bool;
     pub fn xmalloc(size: size_t) -> *mut c_void;
@@ -1193,6 +1397,27 @@ extern "C" {
         cnt: ptrdiff_t,
         bytepos: *mut ptrdiff_t,
     ) -> ptrdiff_t;
+    pub fn find_newline_no_quit(
+        from: ptrdiff_t,
+        frombyte: ptrdiff_t,
+        cnt: ptrdiff_t,
+        bytepos: *mut ptrdiff_t,
+    ) -> ptrdiff_t;
+    pub fn indented_beyond_p(pos: ptrdiff_t, pos_byte: ptrdiff_t, column: EmacsInt) -> bool;
+    pub fn skip_invisible(
+        pos: ptrdiff_t,
+        next_boundary_p: *mut ptrdiff_t,
+        to: ptrdiff_t,
+        window: Lisp_Object,
+    ) -> ptrdiff_t;
+    pub fn invisible_prop(propval: Lisp_Object, list: Lisp_Object) -> c_int;
+    pub fn overlay_strings(
+        pos: ptrdiff_t,
+        w: *mut Lisp_Window,
+        pstr: *mut *mut c_uchar,
+    ) -> ptrdiff_t;
+    pub fn strwidth(s: *const c_char, len: ptrdiff_t) -> ptrdiff_t;
+    pub fn window_display_table(w: *mut Lisp_Window) -> *mut Lisp_Char_Table;
     pub fn Fconstrain_to_field(
         new_pos: Lisp_Object,
         old_pos: Lisp_Object,
@@ -1226,7 +1451,101 @@ extern "C" {
     ) -> Lisp_Object;
     pub fn message_with_string(m: *const c_char, string: Lisp_Object, log: bool);
     pub fn maybe_quit();
+    pub fn command_loop_1() -> Lisp_Object;
+    pub fn message1(m: *const c_char);
+    pub fn make_event_array(nargs: ptrdiff_t, args: *mut Lisp_Object) -> Lisp_Object;
+    pub fn mark_object(obj: Lisp_Object);
     pub fn Fselect_window(window: Lisp_Object, norecord: Lisp_Object) -> Lisp_Object;
+    pub fn Fprogn(body: Lisp_Object) -> Lisp_Object;
+    pub fn eval_sub(form: Lisp_Object) -> Lisp_Object;
+    pub fn signal_error(s: *const c_char, arg: Lisp_Object) -> !;
+    pub fn SPECPDL_INDEX() -> ptrdiff_t;
+    pub fn unbind_to(count: ptrdiff_t, value: Lisp_Object) -> Lisp_Object;
+    pub fn record_specbind(
+        kind: SpecbindTag,
+        symbol: Lisp_Object,
+        old_value: Lisp_Object,
+        place: Lisp_Object,
+    ) -> *mut c_void;
+    pub fn do_specbind(
+        sym: *mut Lisp_Symbol,
+        bind: *mut c_void,
+        value: Lisp_Object,
+        bindflag: SetInternalBind,
+    );
+    pub fn internal_catch(
+        tag: Lisp_Object,
+        func: unsafe extern "C" fn(Lisp_Object) -> Lisp_Object,
+        arg: Lisp_Object,
+    ) -> Lisp_Object;
+    pub fn unwind_to_catch(catch: *mut handler, value: Lisp_Object) -> !;
+    pub fn internal_condition_case_1(
+        bfun: unsafe extern "C" fn(Lisp_Object) -> Lisp_Object,
+        arg: Lisp_Object,
+        handlers: Lisp_Object,
+        hfun: unsafe extern "C" fn(Lisp_Object) -> Lisp_Object,
+    ) -> Lisp_Object;
+    pub fn call0(function: Lisp_Object) -> Lisp_Object;
+    pub fn signal_or_quit(
+        error_symbol: Lisp_Object,
+        data: Lisp_Object,
+        keyboard_quit: bool,
+    ) -> Lisp_Object;
+    pub fn record_unwind_protect(
+        function: unsafe extern "C" fn(Lisp_Object),
+        arg: Lisp_Object,
+    );
+    pub fn clear_unwind_protect(count: ptrdiff_t);
+    pub fn record_unwind_protect_ptr(function: unsafe extern "C" fn(*mut c_void), arg: *mut c_void);
+    pub fn record_unwind_save_match_data();
+    pub fn save_excursion_save() -> Lisp_Object;
+    pub fn save_excursion_restore(info: Lisp_Object);
+    pub fn save_restriction_save() -> Lisp_Object;
+    pub fn save_restriction_restore(info: Lisp_Object);
+    pub fn un_autoload(oldqueue: Lisp_Object);
+    pub fn Fload(
+        file: Lisp_Object,
+        noerror: Lisp_Object,
+        nomessage: Lisp_Object,
+        nosuffix: Lisp_Object,
+        must_suffix: Lisp_Object,
+    ) -> Lisp_Object;
+    pub fn Fdefalias(symbol: Lisp_Object, definition: Lisp_Object, docstring: Lisp_Object)
+        -> Lisp_Object;
+    pub fn Fread(stream: Lisp_Object) -> Lisp_Object;
+    pub fn Ffile_name_absolute_p(filename: Lisp_Object) -> Lisp_Object;
+    pub fn Fdocumentation_property(
+        symbol: Lisp_Object,
+        prop: Lisp_Object,
+        raw: Lisp_Object,
+    ) -> Lisp_Object;
+    pub fn Fsubstitute_command_keys(string: Lisp_Object) -> Lisp_Object;
+    pub fn reread_doc_file(file: Lisp_Object) -> bool;
+    pub fn encode_file_name(fname: Lisp_Object) -> Lisp_Object;
+    pub fn decode_file_name(fname: Lisp_Object) -> Lisp_Object;
+    pub fn report_file_error(string: *const c_char, name: Lisp_Object) -> !;
+    pub fn report_file_errno(string: *const c_char, name: Lisp_Object, errorno: c_int) -> !;
+    pub fn Fexpand_file_name(name: Lisp_Object, default_directory: Lisp_Object) -> Lisp_Object;
+    pub fn get_truename_buffer(filename: Lisp_Object) -> Lisp_Object;
+    pub fn get_boot_time() -> time_t;
+    pub fn multibyte_chars_in_text(ptr: *const c_uchar, nbytes: ptrdiff_t) -> ptrdiff_t;
+    pub fn make_string_from_bytes(
+        contents: *const c_char,
+        nchars: ptrdiff_t,
+        nbytes: ptrdiff_t,
+    ) -> Lisp_Object;
+    pub fn print_output_string(string: Lisp_Object, printcharfun: Lisp_Object);
+    pub fn print_object_to_string(obj: Lisp_Object, escapeflag: bool) -> Lisp_Object;
+    pub fn Fvector(nargs: ptrdiff_t, args: *mut Lisp_Object) -> Lisp_Object;
+    pub fn Fmake_byte_code(nargs: ptrdiff_t, args: *mut Lisp_Object) -> Lisp_Object;
+    pub fn Fmake_symbol(name: Lisp_Object) -> Lisp_Object;
+    pub fn build_load_history(filename: Lisp_Object, entire: bool);
+    pub fn read_elc_form_at(
+        stream: *mut c_void,
+        offset: ptrdiff_t,
+        end: *mut ptrdiff_t,
+    ) -> Lisp_Object;
+    pub fn Fapply(nargs: ptrdiff_t, args: *mut Lisp_Object) -> Lisp_Object;
 }
 
 /// Contains C definitions from the font.h header.
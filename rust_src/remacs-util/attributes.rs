@@ -26,6 +26,11 @@ struct LispFnArgsRaw {
     /// If the function is not interactive, this should be None.
     #[darling(default)]
     intspec: Option<String>,
+    /// Whether the function is a special form, i.e. receives its
+    /// arguments unevaluated as a single list.  Such functions must
+    /// take exactly one `LispObject` argument.
+    #[darling(default)]
+    unevalled: Option<String>,
 }
 
 impl LispFnArgsRaw {
@@ -44,6 +49,12 @@ impl LispFnArgsRaw {
                 def_min_args
             },
             intspec: self.intspec,
+            unevalled: if let Some(s) = self.unevalled {
+                s.parse()
+                    .map_err(|_| "invalid \"unevalled\" value, expected a boolean")?
+            } else {
+                false
+            },
         })
     }
 }
@@ -53,6 +64,7 @@ pub struct LispFnArgs {
     pub c_name: String,
     pub min: i16,
     pub intspec: Option<String>,
+    pub unevalled: bool,
 }
 
 pub fn parse_lisp_fn<D>(src: &str, def_name: &D, def_min_args: i16) -> Result<LispFnArgs, String>
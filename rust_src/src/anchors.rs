@@ -0,0 +1,641 @@
+//! Anchors: positions in buffer text that move with edits, like
+//! markers, but that are plain integer IDs.
+//!
+//! Markers are Lisp objects chained to their buffer and all adjusted by
+//! every edit, so packages that need thousands of positions, such as
+//! collaborative editing or language server annotations, pay for each
+//! of them on each keystroke.  Anchors are kept in a treap per buffer
+//! text, ordered by position, whose subtrees are moved lazily, so an
+//! edit costs a logarithmic number of steps.  Their IDs are stable and
+//! can be written out and given back to `make-anchor'.
+
+use libc::{c_void, ptrdiff_t};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_lisp_ptr, EmacsInt, Lisp_Buffer, Lisp_Type};
+
+use buffer_text::text_key;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use threads::ThreadState;
+
+/// A node of an anchor tree.
+struct Node {
+    /// The position of the anchor, once the moves pending in its
+    /// ancestors are applied.
+    position: EmacsInt,
+    /// The distance the descendants of the node still have to move.
+    pending: EmacsInt,
+    priority: u32,
+    left: Option<usize>,
+    right: Option<usize>,
+    parent: Option<usize>,
+    id: EmacsInt,
+    /// Whether the anchor moves past text inserted at it.
+    after: bool,
+}
+
+/// The anchors of a buffer text, as a treap stored in a vector.
+struct AnchorTree {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    by_id: HashMap<EmacsInt, usize>,
+    seed: u32,
+}
+
+impl AnchorTree {
+    fn new() -> AnchorTree {
+        AnchorTree {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            by_id: HashMap::new(),
+            seed: 0x9E37_79B9,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    fn next_priority(&mut self) -> u32 {
+        // Xorshift, which is plenty to keep the tree balanced.
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+
+    fn set_left(&mut self, node: usize, child: Option<usize>) {
+        self.nodes[node].left = child;
+        if let Some(child) = child {
+            self.nodes[child].parent = Some(node);
+        }
+    }
+
+    fn set_right(&mut self, node: usize, child: Option<usize>) {
+        self.nodes[node].right = child;
+        if let Some(child) = child {
+            self.nodes[child].parent = Some(node);
+        }
+    }
+
+    fn set_root(&mut self, root: Option<usize>) {
+        self.root = root;
+        if let Some(root) = root {
+            self.nodes[root].parent = None;
+        }
+    }
+
+    /// Move the anchors of the subtree at NODE by DISTANCE.
+    fn shift(&mut self, node: Option<usize>, distance: EmacsInt) {
+        if let Some(node) = node {
+            self.nodes[node].position += distance;
+            self.nodes[node].pending += distance;
+        }
+    }
+
+    /// Apply the move pending in NODE to its children.
+    fn push(&mut self, node: usize) {
+        let pending = self.nodes[node].pending;
+        if pending != 0 {
+            let (left, right) = (self.nodes[node].left, self.nodes[node].right);
+            self.shift(left, pending);
+            self.shift(right, pending);
+            self.nodes[node].pending = 0;
+        }
+    }
+
+    /// Split the subtree at NODE into the anchors before POSITION, or
+    /// at or before it if INCLUSIVE, and the others.
+    fn split(
+        &mut self,
+        node: Option<usize>,
+        position: EmacsInt,
+        inclusive: bool,
+    ) -> (Option<usize>, Option<usize>) {
+        let node = match node {
+            Some(node) => node,
+            None => return (None, None),
+        };
+        self.push(node);
+        let here = self.nodes[node].position;
+        if here < position || (inclusive && here == position) {
+            let right = self.nodes[node].right;
+            let (middle, rest) = self.split(right, position, inclusive);
+            self.set_right(node, middle);
+            self.nodes[node].parent = None;
+            (Some(node), rest)
+        } else {
+            let left = self.nodes[node].left;
+            let (rest, middle) = self.split(left, position, inclusive);
+            self.set_left(node, middle);
+            self.nodes[node].parent = None;
+            (rest, Some(node))
+        }
+    }
+
+    /// Join the subtrees at FIRST and SECOND, whose anchors are all
+    /// before those of SECOND or at the same position.
+    fn merge(&mut self, first: Option<usize>, second: Option<usize>) -> Option<usize> {
+        match (first, second) {
+            (None, tree) | (tree, None) => tree,
+            (Some(first), Some(second)) => {
+                if self.nodes[first].priority > self.nodes[second].priority {
+                    self.push(first);
+                    let right = self.nodes[first].right;
+                    let merged = self.merge(right, Some(second));
+                    self.set_right(first, merged);
+                    Some(first)
+                } else {
+                    self.push(second);
+                    let left = self.nodes[second].left;
+                    let merged = self.merge(Some(first), left);
+                    self.set_left(second, merged);
+                    Some(second)
+                }
+            }
+        }
+    }
+
+    /// Return the nodes of the subtree at NODE in order, applying the
+    /// moves pending in them.
+    fn collect(&mut self, node: Option<usize>, nodes: &mut Vec<usize>) {
+        if let Some(node) = node {
+            self.push(node);
+            let (left, right) = (self.nodes[node].left, self.nodes[node].right);
+            self.collect(left, nodes);
+            nodes.push(node);
+            self.collect(right, nodes);
+        }
+    }
+
+    /// Make a tree of NODES, which are in order.
+    fn build(&mut self, nodes: &[usize]) -> Option<usize> {
+        let mut tree = None;
+        for &node in nodes {
+            self.nodes[node].left = None;
+            self.nodes[node].right = None;
+            self.nodes[node].parent = None;
+            tree = self.merge(tree, Some(node));
+        }
+        tree
+    }
+
+    fn position(&self, node: usize) -> EmacsInt {
+        let mut position = self.nodes[node].position;
+        let mut ancestor = self.nodes[node].parent;
+        while let Some(parent) = ancestor {
+            position += self.nodes[parent].pending;
+            ancestor = self.nodes[parent].parent;
+        }
+        position
+    }
+
+    fn insert(&mut self, id: EmacsInt, position: EmacsInt, after: bool) {
+        let node = Node {
+            position,
+            pending: 0,
+            priority: self.next_priority(),
+            left: None,
+            right: None,
+            parent: None,
+            id,
+            after,
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        self.by_id.insert(id, index);
+        let root = self.root;
+        let (before, rest) = self.split(root, position, true);
+        let tree = self.merge(before, Some(index));
+        let tree = self.merge(tree, rest);
+        self.set_root(tree);
+    }
+
+    fn remove(&mut self, id: EmacsInt) -> bool {
+        let node = match self.by_id.remove(&id) {
+            Some(node) => node,
+            None => return false,
+        };
+        // Apply the moves pending above the node before taking it out.
+        let mut path = Vec::new();
+        let mut ancestor = self.nodes[node].parent;
+        while let Some(parent) = ancestor {
+            path.push(parent);
+            ancestor = self.nodes[parent].parent;
+        }
+        for &ancestor in path.iter().rev() {
+            self.push(ancestor);
+        }
+        self.push(node);
+        let (left, right) = (self.nodes[node].left, self.nodes[node].right);
+        let children = self.merge(left, right);
+        match self.nodes[node].parent {
+            None => self.set_root(children),
+            Some(parent) => {
+                if self.nodes[parent].left == Some(node) {
+                    self.set_left(parent, children);
+                } else {
+                    self.set_right(parent, children);
+                }
+            }
+        }
+        self.free.push(node);
+        true
+    }
+
+    /// Move the anchors for the replacement of DELETED characters at
+    /// FROM by INSERTED characters, as the markers are moved.
+    fn adjust(&mut self, from: EmacsInt, deleted: EmacsInt, inserted: EmacsInt, before: bool) {
+        let root = self.root;
+        let tree = if deleted == 0 {
+            // Anchors at an insertion stay before it unless their
+            // gravity or BEFORE says otherwise.
+            let (before_text, rest) = self.split(root, from, false);
+            let (at, after_text) = self.split(rest, from, true);
+            self.shift(after_text, inserted);
+            let mut nodes = Vec::new();
+            self.collect(at, &mut nodes);
+            let (moved, stayed): (Vec<usize>, Vec<usize>) = nodes
+                .into_iter()
+                .partition(|&node| before || self.nodes[node].after);
+            for &node in &moved {
+                self.nodes[node].position += inserted;
+            }
+            let stayed = self.build(&stayed);
+            let moved = self.build(&moved);
+            let tree = self.merge(before_text, stayed);
+            let tree = self.merge(tree, moved);
+            self.merge(tree, after_text)
+        } else {
+            // Anchors in the deleted text go to its start.
+            let (before_text, rest) = self.split(root, from, true);
+            let (within, after_text) = self.split(rest, from + deleted, false);
+            self.shift(after_text, inserted - deleted);
+            let mut nodes = Vec::new();
+            self.collect(within, &mut nodes);
+            for &node in &nodes {
+                self.nodes[node].position = from;
+            }
+            let within = self.build(&nodes);
+            let tree = self.merge(before_text, within);
+            self.merge(tree, after_text)
+        };
+        self.set_root(tree);
+    }
+
+    /// Return the ID, position and gravity of each anchor, in order.
+    fn anchors(&mut self) -> Vec<(EmacsInt, EmacsInt, bool)> {
+        let mut nodes = Vec::new();
+        let root = self.root;
+        self.collect(root, &mut nodes);
+        nodes
+            .into_iter()
+            .map(|node| {
+                let node = &self.nodes[node];
+                (node.id, node.position, node.after)
+            })
+            .collect()
+    }
+}
+
+/// The anchors of all buffers.
+struct Anchors {
+    trees: HashMap<usize, AnchorTree>,
+    /// The key of the tree of each anchor.
+    owners: HashMap<EmacsInt, usize>,
+    next_id: EmacsInt,
+}
+
+impl Anchors {
+    fn add(&mut self, key: usize, id: EmacsInt, position: EmacsInt, after: bool) {
+        self.trees
+            .entry(key)
+            .or_insert_with(AnchorTree::new)
+            .insert(id, position, after);
+        self.owners.insert(id, key);
+        self.next_id = self.next_id.max(id + 1);
+    }
+
+    fn position(&self, id: EmacsInt) -> Option<EmacsInt> {
+        self.owners.get(&id).map(|key| {
+            let tree = &self.trees[key];
+            tree.position(tree.by_id[&id])
+        })
+    }
+
+    fn remove(&mut self, id: EmacsInt) -> bool {
+        let key = match self.owners.remove(&id) {
+            Some(key) => key,
+            None => return false,
+        };
+        let empty = {
+            let tree = self.trees.get_mut(&key).unwrap();
+            tree.remove(id);
+            tree.len() == 0
+        };
+        if empty {
+            self.trees.remove(&key);
+        }
+        true
+    }
+}
+
+lazy_static! {
+    static ref ANCHORS: Mutex<Anchors> = Mutex::new(Anchors {
+        trees: HashMap::new(),
+        owners: HashMap::new(),
+        next_id: 1,
+    });
+}
+
+/// Move the anchors of the current buffer for the replacement of
+/// DELETED characters at FROM by INSERTED characters.  BEFORE_MARKERS
+/// is true if the text was inserted before all markers at FROM.  Called
+/// wherever the markers are adjusted.
+#[no_mangle]
+pub extern "C" fn adjust_anchors(
+    from: ptrdiff_t,
+    deleted: ptrdiff_t,
+    inserted: ptrdiff_t,
+    before_markers: bool,
+) {
+    let key = text_key(ThreadState::current_buffer());
+    let mut anchors = ANCHORS.lock().unwrap();
+    if let Some(tree) = anchors.trees.get_mut(&key) {
+        tree.adjust(
+            from as EmacsInt,
+            deleted as EmacsInt,
+            inserted as EmacsInt,
+            before_markers,
+        );
+    }
+}
+
+/// Forget the anchors of BUFFER.  Called when BUFFER is killed.
+#[no_mangle]
+pub extern "C" fn forget_buffer_anchors(buffer: *const Lisp_Buffer) {
+    let mut anchors = ANCHORS.lock().unwrap();
+    let tree = anchors.trees.remove(&(buffer as usize));
+    if let Some(tree) = tree {
+        for id in tree.by_id.keys() {
+            anchors.owners.remove(id);
+        }
+    }
+}
+
+/// Exchange the anchors of BUFFER and OTHER, whose texts were swapped
+/// by `buffer-swap-text'.
+#[no_mangle]
+pub extern "C" fn swap_buffer_anchors(buffer: *const Lisp_Buffer, other: *const Lisp_Buffer) {
+    let (buffer, other) = (buffer as usize, other as usize);
+    let mut anchors = ANCHORS.lock().unwrap();
+    let first = anchors.trees.remove(&buffer);
+    let second = anchors.trees.remove(&other);
+    for (tree, key) in vec![(first, other), (second, buffer)] {
+        if let Some(tree) = tree {
+            for id in tree.by_id.keys() {
+                anchors.owners.insert(*id, key);
+            }
+            anchors.trees.insert(key, tree);
+        }
+    }
+}
+
+/// Return the ID given by ANCHOR, signaling an error if it is not one.
+fn anchor_id(anchor: LispObject) -> EmacsInt {
+    match anchor.as_fixnum() {
+        Some(id) if id > 0 => id,
+        _ => wrong_type!(intern("anchorp"), anchor),
+    }
+}
+
+/// Return the value of the property NAME in PROPERTIES, a property list
+/// given as arguments.
+fn property(properties: &[LispObject], name: &str) -> LispObject {
+    properties
+        .chunks(2)
+        .find(|pair| pair[0].eq(intern(name)))
+        .and_then(|pair| pair.get(1).cloned())
+        .unwrap_or_else(LispObject::constant_nil)
+}
+
+/// Create anchors at each of POSITIONS, as `make-anchor' does with
+/// PROPERTIES, and return them in a list.  The anchors are added to the
+/// buffer in one step, which is much faster than making them one by one.
+/// usage: (make-anchors POSITIONS &rest PROPERTIES)
+#[lisp_fn(min = "1")]
+pub fn make_anchors(args: &mut [LispObject]) -> LispObject {
+    let positions = args[0];
+    let properties = &args[1..];
+    let buffer = property(properties, ":buffer").as_buffer_or_current_buffer();
+    if !buffer.is_live() {
+        error!("Selecting deleted buffer");
+    }
+    let after = match property(properties, ":gravity") {
+        gravity if gravity.is_nil() || gravity.eq(intern("before")) => false,
+        gravity if gravity.eq(intern("after")) => true,
+        gravity => args_out_of_range!(gravity, intern("before"), intern("after")),
+    };
+    let id = property(properties, ":id");
+    let id = if id.is_nil() { None } else { Some(anchor_id(id)) };
+    if id.is_some() && positions.iter_cars().count() != 1 {
+        error!("Only one anchor can be given an ID");
+    }
+    let positions: Vec<EmacsInt> = positions
+        .iter_cars()
+        .map(|position| {
+            let position = position.as_fixnum_coerce_marker_or_error();
+            position.max(buffer.beg() as EmacsInt).min(buffer.z() as EmacsInt)
+        })
+        .collect();
+
+    let key = text_key(buffer);
+    let ids = {
+        let mut anchors = ANCHORS.lock().unwrap();
+        let first = id.unwrap_or(anchors.next_id);
+        if anchors.owners.contains_key(&first) {
+            Err(first)
+        } else {
+            for (i, &position) in positions.iter().enumerate() {
+                anchors.add(key, first + i as EmacsInt, position, after);
+            }
+            Ok(first..first + positions.len() as EmacsInt)
+        }
+    };
+    let ids = match ids {
+        Ok(ids) => ids,
+        Err(id) => error!("Anchor {} already exists", id),
+    };
+    ids.rev().fold(LispObject::constant_nil(), |list, id| {
+        LispObject::cons(LispObject::from_fixnum(id), list)
+    })
+}
+
+/// Create an anchor at POSITION in a buffer and return it.
+/// An anchor is a position that moves with the text around it, like a
+/// marker.  It is an integer ID, which stays the same for as long as the
+/// anchor exists, and can be saved and given back as the `:id' property
+/// to make the anchor again.
+///
+/// PROPERTIES is a property list: `:buffer' is the buffer, the current
+/// one by default; `:gravity' is `before', the default, if text inserted
+/// at the anchor goes after it, or `after' if the anchor moves past such
+/// text, like a marker whose insertion type is t; `:id' is the ID of the
+/// anchor, which must not be in use.
+///
+/// The anchors of an indirect buffer belong to its base buffer, and are
+/// forgotten when the base buffer is killed.
+/// usage: (make-anchor POSITION &rest PROPERTIES)
+#[lisp_fn(min = "1")]
+pub fn make_anchor(args: &mut [LispObject]) -> LispObject {
+    let mut make_args = args.to_vec();
+    make_args[0] = list!(args[0]);
+    make_anchors(&mut make_args).as_cons_or_error().car()
+}
+
+/// Return the position of ANCHOR, or nil if it has been deleted.
+#[lisp_fn]
+pub fn anchor_position(anchor: LispObject) -> LispObject {
+    let id = anchor_id(anchor);
+    match ANCHORS.lock().unwrap().position(id) {
+        Some(position) => LispObject::from_fixnum(position),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Return the buffer of ANCHOR, or nil if it has been deleted.
+/// For an anchor made in an indirect buffer, this is the base buffer.
+#[lisp_fn]
+pub fn anchor_buffer(anchor: LispObject) -> LispObject {
+    let id = anchor_id(anchor);
+    let key = ANCHORS.lock().unwrap().owners.get(&id).cloned();
+    match key {
+        Some(key) => LispObject::from(unsafe {
+            make_lisp_ptr(key as *mut c_void, Lisp_Type::Lisp_Vectorlike)
+        }),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Delete ANCHOR.  Return t if it existed, and nil otherwise.
+#[lisp_fn]
+pub fn delete_anchor(anchor: LispObject) -> LispObject {
+    let id = anchor_id(anchor);
+    LispObject::from_bool(ANCHORS.lock().unwrap().remove(id))
+}
+
+/// Return the anchors of BUFFER, in order of position.
+/// Each element is (ANCHOR POSITION GRAVITY), which is what is needed to
+/// save the anchors and make them again with `make-anchor'.  No argument
+/// or nil as argument means use the current buffer.
+#[lisp_fn(min = "0")]
+pub fn buffer_anchors(buffer: LispObject) -> LispObject {
+    let key = text_key(buffer.as_buffer_or_current_buffer());
+    let anchors = ANCHORS
+        .lock()
+        .unwrap()
+        .trees
+        .get_mut(&key)
+        .map_or_else(Vec::new, |tree| tree.anchors());
+    anchors
+        .into_iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, (id, position, after)| {
+            let gravity = intern(if after { "after" } else { "before" });
+            LispObject::cons(
+                list!(
+                    LispObject::from_fixnum(id),
+                    LispObject::from_fixnum(position),
+                    gravity
+                ),
+                list,
+            )
+        })
+}
+
+#[cfg(test)]
+fn positions(tree: &mut AnchorTree) -> Vec<(EmacsInt, EmacsInt)> {
+    tree.anchors()
+        .into_iter()
+        .map(|(id, position, _)| (id, position))
+        .collect()
+}
+
+#[test]
+fn test_insert_and_remove() {
+    let mut tree = AnchorTree::new();
+    for (id, &position) in [50, 10, 30, 10, 40].iter().enumerate() {
+        tree.insert(id as EmacsInt, position, false);
+    }
+    assert_eq!(
+        positions(&mut tree),
+        vec![(1, 10), (3, 10), (2, 30), (4, 40), (0, 50)]
+    );
+    assert!(tree.remove(2));
+    assert!(!tree.remove(2));
+    assert_eq!(positions(&mut tree), vec![(1, 10), (3, 10), (4, 40), (0, 50)]);
+    tree.insert(7, 20, false);
+    assert_eq!(tree.position(tree.by_id[&7]), 20);
+    assert_eq!(tree.len(), 5);
+}
+
+#[test]
+fn test_adjust() {
+    let mut tree = AnchorTree::new();
+    tree.insert(1, 5, false);
+    tree.insert(2, 10, false);
+    tree.insert(3, 10, true);
+    tree.insert(4, 20, false);
+    // Insertion at an anchor moves only those with `after' gravity.
+    tree.adjust(10, 0, 3, false);
+    assert_eq!(positions(&mut tree), vec![(1, 5), (2, 10), (3, 13), (4, 23)]);
+    tree.adjust(5, 0, 1, true);
+    assert_eq!(positions(&mut tree), vec![(1, 6), (2, 11), (3, 14), (4, 24)]);
+    // Deletion moves the anchors in the text to its start.
+    tree.adjust(10, 5, 0, false);
+    assert_eq!(positions(&mut tree), vec![(1, 6), (2, 10), (3, 10), (4, 19)]);
+    // So does replacement, and the anchors after it move along.
+    tree.adjust(6, 4, 2, false);
+    assert_eq!(positions(&mut tree), vec![(1, 6), (2, 8), (3, 8), (4, 17)]);
+    for &id in &[1, 2, 3, 4] {
+        let node = tree.by_id[&id];
+        let position = tree.position(node);
+        assert_eq!(Some(&(id, position)), positions(&mut tree).iter().find(|a| a.0 == id));
+    }
+}
+
+#[test]
+fn test_many_anchors() {
+    let mut tree = AnchorTree::new();
+    for id in 0..10_000 {
+        tree.insert(id, id * 2 + 1, false);
+    }
+    // Insert a character before every anchor, from the end.
+    for id in (0..10_000).rev() {
+        tree.adjust(id * 2 + 1, 0, 1, false);
+    }
+    for id in (0..10_000).filter(|id| id % 997 == 0) {
+        assert_eq!(tree.position(tree.by_id[&id]), id * 3 + 2);
+    }
+    for id in 0..5_000 {
+        assert!(tree.remove(id * 2));
+    }
+    let anchors = positions(&mut tree);
+    assert_eq!(anchors.len(), 5_000);
+    assert!(anchors.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+}
+
+include!(concat!(env!("OUT_DIR"), "/anchors_exports.rs"));
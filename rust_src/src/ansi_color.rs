@@ -0,0 +1,449 @@
+//! Native translation of ANSI SGR escape sequences into faces.
+//!
+//! Only CSI sequences (ESC [ ... final) are recognized.  SGR sequences
+//! (final byte `m') update the current graphic state, and every other
+//! CSI sequence is removed from the text.  The remaining text gets a
+//! `font-lock-face' property holding an anonymous face for each run
+//! with a non-default state.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_specified_string, EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use textprop::put_text_property;
+
+const ESC: u8 = 0x1b;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Names used for the eight basic colors, as in `ansi-color-names-vector'.
+const BASIC_COLORS: [&str; 8] = [
+    "black", "red3", "green3", "yellow3", "blue2", "magenta3", "cyan3", "gray90"
+];
+
+/// The xterm defaults for the eight bright colors.
+const BRIGHT_COLORS: [&str; 8] = [
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff"
+];
+
+impl Color {
+    fn name(self) -> String {
+        match self {
+            Color::Indexed(n) if n < 8 => BASIC_COLORS[n as usize].to_string(),
+            Color::Indexed(n) if n < 16 => BRIGHT_COLORS[n as usize - 8].to_string(),
+            Color::Indexed(n) if n < 232 => {
+                // The 6x6x6 color cube.
+                let level = |c: u8| if c == 0 { 0 } else { 55 + 40 * c };
+                let n = n - 16;
+                format!(
+                    "#{:02x}{:02x}{:02x}",
+                    level(n / 36),
+                    level(n / 6 % 6),
+                    level(n % 6)
+                )
+            }
+            Color::Indexed(n) => {
+                let gray = 8 + 10 * (n - 232);
+                format!("#{:02x}{:02x}{:02x}", gray, gray, gray)
+            }
+            Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+
+    /// Append the SGR parameters selecting this color.  BASE is 30 for
+    /// the foreground and 40 for the background.
+    fn push_codes(self, base: EmacsInt, codes: &mut Vec<EmacsInt>) {
+        match self {
+            Color::Indexed(n) if n < 8 => codes.push(base + n as EmacsInt),
+            Color::Indexed(n) if n < 16 => codes.push(base + 60 + n as EmacsInt - 8),
+            Color::Indexed(n) => codes.extend_from_slice(&[base + 8, 5, n as EmacsInt]),
+            Color::Rgb(r, g, b) => codes.extend_from_slice(&[
+                base + 8,
+                2,
+                r as EmacsInt,
+                g as EmacsInt,
+                b as EmacsInt,
+            ]),
+        }
+    }
+}
+
+/// The current SGR graphic state.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct GraphicState {
+    bold: bool,
+    faint: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+    strike_through: bool,
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+impl GraphicState {
+    /// Parse an extended color (the parameters after 38 or 48), and
+    /// return it with the number of parameters consumed.
+    fn extended_color(params: &[EmacsInt]) -> (Option<Color>, usize) {
+        let byte = |i: usize| params.get(i).map(|&p| p.max(0).min(255) as u8);
+        match params.first() {
+            Some(&5) => match byte(1) {
+                Some(n) => (Some(Color::Indexed(n)), 2),
+                None => (None, params.len()),
+            },
+            Some(&2) => match (byte(1), byte(2), byte(3)) {
+                (Some(r), Some(g), Some(b)) => (Some(Color::Rgb(r, g, b)), 4),
+                _ => (None, params.len()),
+            },
+            _ => (None, params.len()),
+        }
+    }
+
+    /// Apply the parameters of one SGR sequence.  An empty parameter
+    /// list means a reset, as does an empty parameter.
+    pub fn apply(&mut self, params: &[EmacsInt]) {
+        if params.is_empty() {
+            *self = GraphicState::default();
+            return;
+        }
+        let mut i = 0;
+        while i < params.len() {
+            let code = params[i];
+            i += 1;
+            match code {
+                0 => *self = GraphicState::default(),
+                1 => self.bold = true,
+                2 => self.faint = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                9 => self.strike_through = true,
+                21 | 22 => {
+                    self.bold = false;
+                    self.faint = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                29 => self.strike_through = false,
+                30...37 => self.foreground = Some(Color::Indexed((code - 30) as u8)),
+                39 => self.foreground = None,
+                40...47 => self.background = Some(Color::Indexed((code - 40) as u8)),
+                49 => self.background = None,
+                90...97 => self.foreground = Some(Color::Indexed((code - 90 + 8) as u8)),
+                100...107 => self.background = Some(Color::Indexed((code - 100 + 8) as u8)),
+                38 | 48 => {
+                    let (color, used) = GraphicState::extended_color(&params[i..]);
+                    i += used;
+                    if let Some(color) = color {
+                        if code == 38 {
+                            self.foreground = Some(color);
+                        } else {
+                            self.background = Some(color);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Return the SGR parameters that recreate this state from the
+    /// default one.
+    fn codes(&self) -> Vec<EmacsInt> {
+        let mut codes = Vec::new();
+        let flags = [
+            (self.bold, 1),
+            (self.faint, 2),
+            (self.italic, 3),
+            (self.underline, 4),
+            (self.inverse, 7),
+            (self.strike_through, 9),
+        ];
+        for &(set, code) in &flags {
+            if set {
+                codes.push(code);
+            }
+        }
+        if let Some(color) = self.foreground {
+            color.push_codes(30, &mut codes);
+        }
+        if let Some(color) = self.background {
+            color.push_codes(40, &mut codes);
+        }
+        codes
+    }
+
+    /// Return the anonymous face for this state, or nil for the
+    /// default state.
+    pub fn face(&self) -> LispObject {
+        fn push(face: LispObject, attribute: &str, value: LispObject) -> LispObject {
+            LispObject::cons(intern(attribute), LispObject::cons(value, face))
+        }
+        let t = LispObject::constant_t();
+        let mut face = LispObject::constant_nil();
+        if let Some(color) = self.background {
+            face = push(face, ":background", lisp_string(&color.name()));
+        }
+        if let Some(color) = self.foreground {
+            face = push(face, ":foreground", lisp_string(&color.name()));
+        }
+        if self.strike_through {
+            face = push(face, ":strike-through", t);
+        }
+        if self.inverse {
+            face = push(face, ":inverse-video", t);
+        }
+        if self.underline {
+            face = push(face, ":underline", t);
+        }
+        if self.italic {
+            face = push(face, ":slant", intern("italic"));
+        }
+        if self.bold {
+            face = push(face, ":weight", intern("bold"));
+        } else if self.faint {
+            face = push(face, ":weight", intern("light"));
+        }
+        face
+    }
+}
+
+/// Parse the parameter bytes of a CSI sequence.  Both `;' and `:'
+/// separate parameters, and an empty parameter counts as 0.
+pub fn parse_params(bytes: &[u8]) -> Vec<EmacsInt> {
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+    bytes
+        .split(|&b| b == b';' || b == b':')
+        .map(|p| {
+            p.iter()
+                .take_while(|&&b| b >= b'0' && b <= b'9')
+                .fold(0 as EmacsInt, |n, &b| {
+                    (n * 10 + (b - b'0') as EmacsInt).min(1 << 16)
+                })
+        })
+        .collect()
+}
+
+/// The text of a string with its escape sequences removed, and the
+/// graphic state of each run of the remaining text.
+struct Translation {
+    text: Vec<u8>,
+    nchars: usize,
+    /// (START, END, STATE) in characters, for runs in a non-default state.
+    runs: Vec<(usize, usize, GraphicState)>,
+    /// The unfinished escape sequence at the end of the input, if any.
+    fragment: Vec<u8>,
+}
+
+/// Translate BYTES, the contents of a string, starting in STATE.
+/// Escape sequences are ASCII, so they can be found by scanning bytes
+/// even in multibyte text.
+fn translate(bytes: &[u8], multibyte: bool, state: &mut GraphicState) -> Translation {
+    let mut result = Translation {
+        text: Vec::with_capacity(bytes.len()),
+        nchars: 0,
+        runs: Vec::new(),
+        fragment: Vec::new(),
+    };
+    let mut run_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b != ESC {
+            result.text.push(b);
+            if !multibyte || b & 0xc0 != 0x80 {
+                result.nchars += 1;
+            }
+            i += 1;
+            continue;
+        }
+
+        if i + 1 == bytes.len() {
+            result.fragment.extend_from_slice(&bytes[i..]);
+            break;
+        }
+        if bytes[i + 1] != b'[' {
+            // Not a control sequence; leave the escape in the text.
+            result.text.push(b);
+            result.nchars += 1;
+            i += 1;
+            continue;
+        }
+
+        // Parameter bytes, then intermediate bytes, then the final byte.
+        let params_start = i + 2;
+        let mut j = params_start;
+        while j < bytes.len() && bytes[j] >= 0x30 && bytes[j] <= 0x3f {
+            j += 1;
+        }
+        let params_end = j;
+        while j < bytes.len() && bytes[j] >= 0x20 && bytes[j] <= 0x2f {
+            j += 1;
+        }
+        if j == bytes.len() {
+            result.fragment.extend_from_slice(&bytes[i..]);
+            break;
+        }
+        let final_byte = bytes[j];
+        if final_byte < 0x40 || final_byte > 0x7e {
+            // Malformed: drop the introducer and go on with the text.
+            i = j;
+            continue;
+        }
+        if final_byte == b'm' && params_end == j {
+            let mut new_state = *state;
+            new_state.apply(&parse_params(&bytes[params_start..params_end]));
+            if new_state != *state {
+                if result.nchars > run_start && *state != GraphicState::default() {
+                    result.runs.push((run_start, result.nchars, *state));
+                }
+                run_start = result.nchars;
+                *state = new_state;
+            }
+        }
+        i = j + 1;
+    }
+
+    if result.nchars > run_start && *state != GraphicState::default() {
+        result.runs.push((run_start, result.nchars, *state));
+    }
+    result
+}
+
+/// Make the Lisp string for TRANSLATION, with faces on its runs.
+fn propertized_string(translation: &Translation, multibyte: bool) -> LispObject {
+    let string = LispObject::from(unsafe {
+        make_specified_string(
+            translation.text.as_ptr() as *const c_char,
+            translation.nchars as ptrdiff_t,
+            translation.text.len() as ptrdiff_t,
+            multibyte,
+        )
+    });
+    let property = intern("font-lock-face");
+    for &(start, end, ref state) in &translation.runs {
+        put_text_property(
+            LispObject::from_natnum(start as EmacsInt),
+            LispObject::from_natnum(end as EmacsInt),
+            property,
+            state.face(),
+            string,
+        );
+    }
+    string
+}
+
+/// Translate the SGR control sequences in STRING into text properties.
+/// Return a new string without the control sequences, in which each
+/// colored or highlighted run has a `font-lock-face' property holding
+/// an anonymous face.  The 16 basic colors, the 256-color palette and
+/// 24-bit colors are supported.
+///
+/// Other CSI control sequences are removed.  An unfinished control
+/// sequence at the end of STRING is left alone; use
+/// `ansi-color-apply-native-stream' to handle text that arrives in
+/// chunks.
+#[lisp_fn]
+pub fn ansi_color_apply_native(string: LispObject) -> LispObject {
+    let s = string.as_string_or_error();
+    let mut state = GraphicState::default();
+    let mut translation = translate(s.as_slice(), s.is_multibyte(), &mut state);
+    // Keep the unfinished sequence as plain text.
+    for &b in &translation.fragment {
+        translation.text.push(b);
+        translation.nchars += 1;
+    }
+    propertized_string(&translation, s.is_multibyte())
+}
+
+/// Like `ansi-color-apply-native', but for text that arrives in chunks,
+/// such as the output of a process.
+/// CONTEXT is a cons cell (CODES . FRAGMENT) carrying the graphic state
+/// from one chunk to the next; pass a fresh `(list nil)' for the first
+/// chunk and the same cons for each following chunk.  It is updated in
+/// place: CODES is the list of SGR parameters that recreate the current
+/// state, and FRAGMENT is an unfinished control sequence held back from
+/// the end of the previous chunk, or nil.
+#[lisp_fn]
+pub fn ansi_color_apply_native_stream(string: LispObject, context: LispObject) -> LispObject {
+    let s = string.as_string_or_error();
+    let context = context.as_cons_or_error();
+
+    let mut state = GraphicState::default();
+    let codes: Vec<EmacsInt> = context
+        .car()
+        .iter_cars()
+        .map(|code| code.as_fixnum_or_error())
+        .collect();
+    state.apply(&codes);
+
+    let mut bytes = Vec::new();
+    if let Some(fragment) = context.cdr().as_string() {
+        bytes.extend_from_slice(fragment.as_slice());
+    }
+    bytes.extend_from_slice(s.as_slice());
+
+    let translation = translate(&bytes, s.is_multibyte(), &mut state);
+    let result = propertized_string(&translation, s.is_multibyte());
+
+    let codes = state
+        .codes()
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, &code| {
+            LispObject::cons(LispObject::from_fixnum(code), list)
+        });
+    context.set_car(codes);
+    context.set_cdr(if translation.fragment.is_empty() {
+        LispObject::constant_nil()
+    } else {
+        lisp_string(unsafe { ::std::str::from_utf8_unchecked(&translation.fragment) })
+    });
+    result
+}
+
+#[test]
+fn test_translate_sgr() {
+    let mut state = GraphicState::default();
+    let t = translate(b"a\x1b[1;31mbc\x1b[0md\x1b[K", false, &mut state);
+    assert_eq!(t.text, b"abcd".to_vec());
+    assert_eq!(t.nchars, 4);
+    assert_eq!(t.runs.len(), 1);
+    let (start, end, run) = t.runs[0];
+    assert_eq!((start, end), (1, 3));
+    assert!(run.bold && run.foreground == Some(Color::Indexed(1)));
+    assert!(state == GraphicState::default());
+    assert!(t.fragment.is_empty());
+}
+
+#[test]
+fn test_translate_fragment() {
+    let mut state = GraphicState::default();
+    let t = translate(b"\x1b[38;2;1;2;3mab\x1b[4", false, &mut state);
+    assert_eq!(t.text, b"ab".to_vec());
+    assert_eq!(t.fragment, b"\x1b[4".to_vec());
+    assert!(state.foreground == Some(Color::Rgb(1, 2, 3)));
+    assert_eq!(state.codes(), vec![38, 2, 1, 2, 3]);
+}
+
+#[test]
+fn test_color_names() {
+    assert_eq!(Color::Indexed(1).name(), "red3");
+    assert_eq!(Color::Indexed(196).name(), "#ff0000");
+    assert_eq!(Color::Indexed(232).name(), "#080808");
+    assert_eq!(Color::Rgb(1, 2, 255).name(), "#0102ff");
+}
+
+include!(concat!(env!("OUT_DIR"), "/ansi_color_exports.rs"));
@@ -0,0 +1,342 @@
+//! A store of bookmarks with fuzzy lookup.
+//!
+//! Bookmarks are kept as the records of `bookmark-alist', (NAME . ALIST),
+//! encoded in the format of `lisp-data-write'.  Along with each one the
+//! store keeps the text of its name and annotation, for matching, and
+//! how often and when it was last used.  The store is saved as a list of
+//! vectors [RECORD COUNT TIME], where TIME is nil for a bookmark that was
+//! never used.
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use fuzzy::{ignore_case_for, score};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{assq, list};
+use persist::{decode, encode, finish_background_writes, is_writable, write_in_background};
+use util::{expand_file_name_to_path, report_io_error};
+
+struct Bookmark {
+    name: String,
+    annotation: String,
+    record: Vec<u8>,
+    count: u64,
+    last_used: Option<i64>,
+}
+
+impl Bookmark {
+    /// Return the bookmark for RECORD, or None if RECORD is not a
+    /// bookmark record or cannot be written.
+    fn new(record: LispObject) -> Option<Bookmark> {
+        let cons = record.as_cons()?;
+        if !cons.car().is_string() || !is_writable(record) {
+            return None;
+        }
+        let annotation = match assq(intern("annotation"), cons.cdr()).as_cons() {
+            Some(entry) if entry.cdr().is_string() => text(entry.cdr()),
+            _ => String::new(),
+        };
+        Some(Bookmark {
+            name: text(cons.car()),
+            annotation,
+            record: encode(record),
+            count: 0,
+            last_used: None,
+        })
+    }
+
+    /// Return how well the bookmark matches PATTERN.  A match of the
+    /// annotation counts for half as much as a match of the name.
+    fn score(&self, pattern: &str, ignore_case: bool) -> Option<i64> {
+        let name = score(pattern, &self.name, ignore_case);
+        let annotation = score(pattern, &self.annotation, ignore_case).map(|n| n / 2);
+        match (name, annotation) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+fn text(string: LispObject) -> String {
+    String::from_utf8_lossy(string.as_string_or_error().as_slice()).into_owned()
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+lazy_static! {
+    /// The bookmarks, in the order of `bookmark-alist'.
+    static ref BOOKMARKS: Mutex<Vec<Bookmark>> = Mutex::new(Vec::new());
+}
+
+/// Return the bookmark records encoded in RECORDS.
+fn records(records: Vec<Vec<u8>>) -> LispObject {
+    let mut records: Vec<LispObject> = records.iter().map(|data| decode(data)).collect();
+    list(&mut records)
+}
+
+/// Put BOOKMARKS in the store in place of the ones it has, keeping
+/// what is known about the use of those with the same names.
+fn replace(bookmarks: Vec<Bookmark>) {
+    let mut store = BOOKMARKS.lock().unwrap();
+    let old: Vec<Bookmark> = store.drain(..).collect();
+    for mut bookmark in bookmarks {
+        if store.iter().any(|b| b.name == bookmark.name) {
+            continue;
+        }
+        if bookmark.last_used.is_none() {
+            if let Some(previous) = old.iter().find(|b| b.name == bookmark.name) {
+                bookmark.count = previous.count;
+                bookmark.last_used = previous.last_used;
+            }
+        }
+        store.push(bookmark);
+    }
+}
+
+/// Make the bookmark records in RECORDS the contents of the store.
+/// For each bookmark whose name was already in the store, how often and
+/// when it was used is kept.  Only the first of the records with the
+/// same name is stored.  Return the names of the records that could not
+/// be stored because they contain objects that `lisp-data-write' cannot
+/// write.
+#[lisp_fn]
+pub fn bookmark_store_replace(records: LispObject) -> LispObject {
+    let mut bookmarks = Vec::new();
+    let mut failed = Vec::new();
+    for record in records.iter_cars() {
+        match Bookmark::new(record) {
+            Some(bookmark) => bookmarks.push(bookmark),
+            None => failed.push(call!(intern("car-safe"), record)),
+        }
+    }
+    replace(bookmarks);
+    list(&mut failed)
+}
+
+/// Store the bookmark RECORD, of the form (NAME . ALIST), replacing any
+/// bookmark with the same name.
+#[lisp_fn]
+pub fn bookmark_store_set(record: LispObject) -> LispObject {
+    let mut bookmark = match Bookmark::new(record) {
+        Some(bookmark) => bookmark,
+        None => wrong_type!(intern("bookmark-record"), record),
+    };
+    let mut store = BOOKMARKS.lock().unwrap();
+    match store.iter().position(|b| b.name == bookmark.name) {
+        Some(index) => {
+            bookmark.count = store[index].count;
+            bookmark.last_used = store[index].last_used;
+            store[index] = bookmark;
+        }
+        None => store.push(bookmark),
+    }
+    LispObject::constant_nil()
+}
+
+/// Return the stored record of the bookmark called NAME, or nil.
+#[lisp_fn]
+pub fn bookmark_store_get(name: LispObject) -> LispObject {
+    let name = text(name);
+    let record = BOOKMARKS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|b| b.name == name)
+        .map(|b| b.record.clone());
+    record.map_or_else(LispObject::constant_nil, |data| decode(&data))
+}
+
+/// Remove the bookmark called NAME from the store.
+/// Return t if it was there, nil otherwise.
+#[lisp_fn]
+pub fn bookmark_store_delete(name: LispObject) -> LispObject {
+    let name = text(name);
+    let mut store = BOOKMARKS.lock().unwrap();
+    let before = store.len();
+    store.retain(|b| b.name != name);
+    LispObject::from_bool(store.len() != before)
+}
+
+/// Return the records of all the stored bookmarks.
+#[lisp_fn]
+pub fn bookmark_store_records() -> LispObject {
+    let data: Vec<Vec<u8>> = BOOKMARKS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|b| b.record.clone())
+        .collect();
+    records(data)
+}
+
+/// Record that the bookmark called NAME was used just now.
+/// Return t if it is in the store, nil otherwise.
+#[lisp_fn]
+pub fn bookmark_store_touch(name: LispObject) -> LispObject {
+    let name = text(name);
+    let mut store = BOOKMARKS.lock().unwrap();
+    match store.iter_mut().find(|b| b.name == name) {
+        Some(bookmark) => {
+            bookmark.count += 1;
+            bookmark.last_used = Some(now());
+            LispObject::constant_t()
+        }
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Return how often and when the bookmark called NAME was used.
+/// The value is (COUNT . TIME), where TIME is the number of seconds
+/// since the epoch when it was last used, or nil if it was never used.
+/// Return nil if there is no such bookmark in the store.
+#[lisp_fn]
+pub fn bookmark_store_usage(name: LispObject) -> LispObject {
+    let name = text(name);
+    let usage = BOOKMARKS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|b| b.name == name)
+        .map(|b| (b.count, b.last_used));
+    match usage {
+        Some((count, last_used)) => LispObject::cons(
+            LispObject::from_natnum(count as EmacsInt),
+            last_used.map_or_else(LispObject::constant_nil, |time| {
+                LispObject::from_fixnum(time as EmacsInt)
+            }),
+        ),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Return the records of the stored bookmarks that match PATTERN.
+/// A bookmark matches if its name or annotation contains the characters
+/// of PATTERN in order, as for `fuzzy-match-score'.  The best matches
+/// come first, a match of the name counting for more than one of the
+/// annotation; bookmarks that match equally well are ordered by when
+/// they were last used, the most recent first.  Case is ignored unless
+/// PATTERN contains upper case letters.  If LIMIT is non-nil, return at
+/// most that many records.
+#[lisp_fn(min = "1")]
+pub fn bookmark_query(pattern: LispObject, limit: LispObject) -> LispObject {
+    let pattern = text(pattern);
+    let limit = if limit.is_nil() {
+        None
+    } else {
+        Some(limit.as_natnum_or_error() as usize)
+    };
+    let ignore_case = ignore_case_for(&pattern);
+    let data: Vec<Vec<u8>> = {
+        let store = BOOKMARKS.lock().unwrap();
+        let mut matches: Vec<(i64, &Bookmark)> = store
+            .iter()
+            .filter_map(|b| b.score(&pattern, ignore_case).map(|n| (n, b)))
+            .collect();
+        matches.sort_by(|&(n1, b1), &(n2, b2)| match n2.cmp(&n1) {
+            Ordering::Equal => b2.last_used.cmp(&b1.last_used),
+            order => order,
+        });
+        if let Some(limit) = limit {
+            matches.truncate(limit);
+        }
+        matches.iter().map(|&(_, b)| b.record.clone()).collect()
+    };
+    records(data)
+}
+
+/// Read the bookmarks saved in FILE by `bookmark-store-save' and make
+/// them the contents of the store.  If FILE does not exist, the store is
+/// made empty.  Return the records of the bookmarks.
+#[lisp_fn]
+pub fn bookmark_store_load(file: LispObject) -> LispObject {
+    finish_background_writes();
+    let path = expand_file_name_to_path(file);
+    let mut data = Vec::new();
+    let saved = match File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+        Ok(_) => decode(&data),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => LispObject::constant_nil(),
+        Err(err) => report_io_error(b"Reading bookmarks\0", file, &err),
+    };
+    if !saved.is_list() {
+        error!("Invalid bookmark store");
+    }
+    let mut bookmarks = Vec::new();
+    for entry in saved.iter_cars() {
+        let slots = match entry.as_vectorlike().and_then(|v| v.as_vector()) {
+            Some(ref vector) if vector.len() == 3 => vector.as_slice().to_vec(),
+            _ => error!("Invalid bookmark store"),
+        };
+        if let Some(mut bookmark) = Bookmark::new(slots[0]) {
+            bookmark.count = slots[1].as_fixnum().map_or(0, |n| n.max(0) as u64);
+            bookmark.last_used = slots[2].as_fixnum().map(|time| time as i64);
+            bookmarks.push(bookmark);
+        }
+    }
+    BOOKMARKS.lock().unwrap().clear();
+    replace(bookmarks);
+    bookmark_store_records()
+}
+
+/// Save the bookmarks in the store to FILE.
+/// The file is written on a separate thread, so this returns at once;
+/// Emacs waits for the write to finish before it exits.
+#[lisp_fn]
+pub fn bookmark_store_save(file: LispObject) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let name = text(file);
+    let saved: Vec<(Vec<u8>, u64, Option<i64>)> = BOOKMARKS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|b| (b.record.clone(), b.count, b.last_used))
+        .collect();
+    let mut entries: Vec<LispObject> = saved
+        .iter()
+        .map(|&(ref record, count, last_used)| {
+            call!(
+                intern("vector"),
+                decode(record),
+                LispObject::from_natnum(count as EmacsInt),
+                last_used.map_or_else(LispObject::constant_nil, |time| {
+                    LispObject::from_fixnum(time as EmacsInt)
+                })
+            )
+        })
+        .collect();
+    write_in_background(path, name, encode(list(&mut entries)));
+    LispObject::constant_nil()
+}
+
+#[cfg(test)]
+fn test_bookmark(name: &str, annotation: &str, last_used: Option<i64>) -> Bookmark {
+    Bookmark {
+        name: name.to_string(),
+        annotation: annotation.to_string(),
+        record: Vec::new(),
+        count: 0,
+        last_used,
+    }
+}
+
+#[test]
+fn test_bookmark_score() {
+    let init = test_bookmark("init.el", "", None);
+    let notes = test_bookmark("notes", "about init", Some(1));
+    assert!(init.score("init", true) > notes.score("init", true));
+    assert!(notes.score("init", true).is_some());
+    assert_eq!(init.score("notes", true), None);
+}
+
+include!(concat!(env!("OUT_DIR"), "/bookmarks_exports.rs"));
@@ -0,0 +1,434 @@
+//! Hashing buffer text and tracking where it changes.
+//!
+//! Every insertion and deletion that is signaled to the after-change
+//! functions is also recorded in a journal kept for the buffer, so that
+//! the changes made since any recent character-change tick, and the
+//! regions they touched, can be found without hooking
+//! `after-change-functions'.
+
+use libc::{c_char, ptrdiff_t};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hasher;
+use std::slice;
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{globals, make_buffer_string, make_specified_string, nsberror, EmacsInt,
+                 Lisp_Buffer};
+
+use buffers::{get_buffer, LispBufferRef};
+use lisp::LispObject;
+use lisp::defsubr;
+use strings::lisp_string;
+use threads::ThreadState;
+
+/// The number of changes remembered for each buffer.
+const JOURNAL_LENGTH: usize = 256;
+
+/// The number of characters from which inserted text is not recorded.
+const TEXT_LIMIT: ptrdiff_t = 64 * 1024;
+
+/// Text inserted by a change, in the internal representation.
+#[derive(Clone, Debug, PartialEq)]
+struct Text {
+    bytes: Vec<u8>,
+    chars: ptrdiff_t,
+    multibyte: bool,
+}
+
+impl Text {
+    fn to_lisp(&self) -> LispObject {
+        LispObject::from(unsafe {
+            make_specified_string(
+                self.bytes.as_ptr() as *const c_char,
+                self.chars,
+                self.bytes.len() as ptrdiff_t,
+                self.multibyte,
+            )
+        })
+    }
+}
+
+/// An insertion, deletion or replacement of text.
+#[derive(Clone, Debug, PartialEq)]
+struct Change {
+    /// The character-change tick of the buffer after the change.
+    tick: EmacsInt,
+    /// The position of the change.
+    start: EmacsInt,
+    /// The number of characters deleted at START.
+    deleted: EmacsInt,
+    /// The number of characters inserted at START in their place.
+    inserted: EmacsInt,
+    /// The inserted text, if `buffer-recent-changes-text' asked for it.
+    text: Option<Text>,
+}
+
+/// The recent changes of a buffer's text.
+struct Journal {
+    /// The tick since which every change is in `changes`.
+    since: EmacsInt,
+    changes: VecDeque<Change>,
+}
+
+impl Journal {
+    fn new(since: EmacsInt) -> Journal {
+        Journal {
+            since,
+            changes: VecDeque::with_capacity(JOURNAL_LENGTH),
+        }
+    }
+
+    fn last_tick(&self) -> EmacsInt {
+        self.changes.back().map_or(self.since, |change| change.tick)
+    }
+
+    /// Record CHANGE, forgetting the oldest change if the journal is
+    /// full.  Changes that leave the tick as it was only changed text
+    /// properties, and are ignored.
+    fn record(&mut self, change: Change) {
+        if change.tick <= self.last_tick() {
+            return;
+        }
+        if self.changes.len() == JOURNAL_LENGTH {
+            if let Some(oldest) = self.changes.pop_front() {
+                self.since = oldest.tick;
+            }
+        }
+        self.changes.push_back(change);
+    }
+
+    /// Return the changes made since TICK, oldest first, or None if the
+    /// journal does not go back that far.
+    fn changes_since<'a>(&'a self, tick: EmacsInt) -> Option<Vec<&'a Change>> {
+        if tick < self.since {
+            return None;
+        }
+        Some(self.changes.iter().filter(|change| change.tick > tick).collect())
+    }
+
+    /// Return the regions changed since TICK, in current positions, or
+    /// None if the journal does not go back that far.
+    fn changed_since(&self, tick: EmacsInt) -> Option<Vec<(EmacsInt, EmacsInt)>> {
+        self.changes_since(tick).map(|changes| {
+            let mut regions = Vec::new();
+            for change in changes {
+                add_change(&mut regions, change);
+            }
+            regions
+        })
+    }
+}
+
+/// Add CHANGE to REGIONS, a sorted list of disjoint regions changed
+/// before it.  The regions it touches are merged with the text it
+/// inserted, and the ones after it are moved along.  Deleted text
+/// leaves an empty region.
+fn add_change(regions: &mut Vec<(EmacsInt, EmacsInt)>, change: &Change) {
+    let end = change.start + change.deleted;
+    let delta = change.inserted - change.deleted;
+    let mut merged = (change.start, change.start + change.inserted);
+    let mut result = Vec::with_capacity(regions.len() + 1);
+    for &(start, stop) in regions.iter() {
+        if stop < change.start {
+            result.push((start, stop));
+        } else if start > end {
+            result.push((start + delta, stop + delta));
+        } else {
+            merged = (merged.0.min(start), merged.1.max(stop + delta));
+        }
+    }
+    let index = result
+        .iter()
+        .position(|&(start, _)| start > merged.0)
+        .unwrap_or(result.len());
+    result.insert(index, merged);
+    *regions = result;
+}
+
+lazy_static! {
+    static ref JOURNALS: Mutex<HashMap<usize, Journal>> = Mutex::new(HashMap::new());
+}
+
+/// The key of the text of BUFFER, which indirect buffers share with
+/// their base buffer.
+pub fn text_key(buffer: LispBufferRef) -> usize {
+    if buffer.base_buffer.is_null() {
+        buffer.as_ptr() as usize
+    } else {
+        buffer.base_buffer as usize
+    }
+}
+
+/// Record that DELETED characters at START in the current buffer were
+/// replaced by INSERTED characters.  Called by `signal_after_change',
+/// even when modification hooks are inhibited.
+#[no_mangle]
+pub extern "C" fn record_buffer_change(start: ptrdiff_t, deleted: ptrdiff_t, inserted: ptrdiff_t) {
+    if deleted == 0 && inserted == 0 {
+        return;
+    }
+    let buffer = ThreadState::current_buffer();
+    let key = text_key(buffer);
+    let tick = buffer.char_modifications();
+    let known = JOURNALS
+        .lock()
+        .unwrap()
+        .get(&key)
+        .map_or(false, |journal| tick <= journal.last_tick());
+    if known {
+        return;
+    }
+    let record_text = LispObject::from(unsafe { globals.f_Vbuffer_recent_changes_text });
+    let text = if inserted > 0 && inserted <= TEXT_LIMIT && record_text.is_not_nil() {
+        let string = unsafe { make_buffer_string(start, start + inserted, false) };
+        let string = LispObject::from(string).as_string_or_error();
+        Some(Text {
+            bytes: string.as_slice().to_vec(),
+            chars: string.len_chars(),
+            multibyte: string.is_multibyte(),
+        })
+    } else {
+        None
+    };
+    JOURNALS
+        .lock()
+        .unwrap()
+        .entry(key)
+        // The tick before the first change is not known, but it is at
+        // most one less.
+        .or_insert_with(|| Journal::new(tick - 1))
+        .record(Change {
+            tick,
+            start: start as EmacsInt,
+            deleted: deleted as EmacsInt,
+            inserted: inserted as EmacsInt,
+            text,
+        });
+}
+
+/// Forget the changes of BUFFER.  Called when BUFFER is killed.
+#[no_mangle]
+pub extern "C" fn forget_buffer_changes(buffer: *const Lisp_Buffer) {
+    JOURNALS.lock().unwrap().remove(&(buffer as usize));
+}
+
+/// Exchange the changes of BUFFER and OTHER, whose texts were swapped
+/// by `buffer-swap-text'.
+#[no_mangle]
+pub extern "C" fn swap_buffer_changes(buffer: *const Lisp_Buffer, other: *const Lisp_Buffer) {
+    let mut journals = JOURNALS.lock().unwrap();
+    let first = journals.remove(&(buffer as usize));
+    let second = journals.remove(&(other as usize));
+    if let Some(journal) = first {
+        journals.insert(other as usize, journal);
+    }
+    if let Some(journal) = second {
+        journals.insert(buffer as usize, journal);
+    }
+}
+
+/// Return the position before which the text of BUFFER is known not to
+/// have changed since the character-change tick TICK.
+pub fn unchanged_before(buffer: LispBufferRef, tick: EmacsInt) -> ptrdiff_t {
+    let journals = JOURNALS.lock().unwrap();
+    match journals.get(&text_key(buffer)) {
+        // Changes that were not signaled leave the journal behind the
+        // buffer, and could be anywhere.
+        Some(journal) if journal.last_tick() == buffer.char_modifications() => journal
+            .changes_since(tick)
+            .map_or(buffer.beg(), |changes| {
+                changes
+                    .iter()
+                    .map(|change| change.start as ptrdiff_t)
+                    .min()
+                    .unwrap_or_else(|| buffer.z())
+            }),
+        _ => buffer.beg(),
+    }
+}
+
+/// Return a hash of the contents of BUFFER-OR-NAME.
+/// The hash is computed on the raw internal representation of the whole
+/// buffer, ignoring any narrowing and coding systems, with a fast hash
+/// function that is not suitable for security.  Two buffers with the same
+/// contents have the same hash, which can be compared with an earlier one
+/// to tell whether the text has changed.  The hash may differ between
+/// builds of Emacs.  No argument or nil as argument means use the current
+/// buffer.
+#[lisp_fn(min = "0")]
+pub fn buffer_hash(buffer_or_name: LispObject) -> LispObject {
+    let buffer = if buffer_or_name.is_nil() {
+        ThreadState::current_buffer()
+    } else {
+        let buffer = get_buffer(buffer_or_name);
+        if buffer.is_nil() {
+            unsafe { nsberror(buffer_or_name.to_raw()) }
+        }
+        buffer.as_buffer_or_error()
+    };
+    if !buffer.is_live() {
+        error!("Selecting deleted buffer");
+    }
+    let mut hasher = DefaultHasher::new();
+    unsafe {
+        let before_gap = (buffer.gpt_byte() - buffer.beg_byte()) as usize;
+        let after_gap = buffer.z_addr() as usize - buffer.gap_end_addr() as usize;
+        hasher.write(slice::from_raw_parts(buffer.beg_addr(), before_gap));
+        hasher.write(slice::from_raw_parts(buffer.gap_end_addr(), after_gap));
+    }
+    lisp_string(&format!("{:016x}", hasher.finish()))
+}
+
+/// Return the regions of BUFFER's text changed since its character-change
+/// tick was TICK, a value returned by `buffer-chars-modified-tick'.
+/// The value is a list of (BEG . END) of the text now in each region, in
+/// order, where text that was only deleted gives an empty region.  It is
+/// nil if the text has not changed, and t if the changes since TICK are
+/// no longer known: only the last few hundred are remembered.
+///
+/// Only the changes signaled to `after-change-functions' are known, but
+/// they are recorded even when `inhibit-modification-hooks' is non-nil.
+/// No argument or nil as argument means use the current buffer.
+#[lisp_fn(min = "1")]
+pub fn buffer_chars_modified_tick_range(tick: LispObject, buffer: LispObject) -> LispObject {
+    let tick = tick.as_fixnum_or_error();
+    let buffer = buffer.as_buffer_or_current_buffer();
+    if tick >= buffer.char_modifications() {
+        return LispObject::constant_nil();
+    }
+    let regions = JOURNALS
+        .lock()
+        .unwrap()
+        .get(&text_key(buffer))
+        .and_then(|journal| journal.changed_since(tick));
+    match regions {
+        None => LispObject::constant_t(),
+        Some(regions) => regions.iter().rev().fold(
+            LispObject::constant_nil(),
+            |list, &(start, end)| {
+                LispObject::cons(
+                    LispObject::cons(
+                        LispObject::from_fixnum(start),
+                        LispObject::from_fixnum(end),
+                    ),
+                    list,
+                )
+            },
+        ),
+    }
+}
+
+/// Return the changes made to the text of BUFFER since its
+/// character-change tick was SINCE-TICK, a value returned by
+/// `buffer-chars-modified-tick', oldest first.
+/// Each change is a list (TICK POSITION DELETED INSERTED TEXT): at
+/// POSITION, DELETED characters were replaced by INSERTED characters,
+/// which made the tick TICK.  POSITION is where the change was made, so
+/// the changes can be replayed in order.  TEXT is the inserted text if
+/// `buffer-recent-changes-text' was non-nil in BUFFER at the time and
+/// the text was not too long, and nil otherwise.
+///
+/// The value is t if the changes since SINCE-TICK are no longer known:
+/// only the last few hundred are remembered.  As for
+/// `buffer-chars-modified-tick-range', only the changes signaled to
+/// `after-change-functions' are known.  No argument or nil as argument
+/// means use the current buffer.
+#[lisp_fn(min = "1")]
+pub fn buffer_recent_changes(since_tick: LispObject, buffer: LispObject) -> LispObject {
+    let tick = since_tick.as_fixnum_or_error();
+    let buffer = buffer.as_buffer_or_current_buffer();
+    if tick >= buffer.char_modifications() {
+        return LispObject::constant_nil();
+    }
+    let changes: Option<Vec<Change>> = JOURNALS
+        .lock()
+        .unwrap()
+        .get(&text_key(buffer))
+        .and_then(|journal| journal.changes_since(tick))
+        .map(|changes| changes.into_iter().cloned().collect());
+    match changes {
+        None => LispObject::constant_t(),
+        Some(changes) => changes
+            .iter()
+            .rev()
+            .fold(LispObject::constant_nil(), |list, change| {
+                let text = change
+                    .text
+                    .as_ref()
+                    .map_or(LispObject::constant_nil(), Text::to_lisp);
+                LispObject::cons(
+                    list!(
+                        LispObject::from_fixnum(change.tick),
+                        LispObject::from_fixnum(change.start),
+                        LispObject::from_fixnum(change.deleted),
+                        LispObject::from_fixnum(change.inserted),
+                        text
+                    ),
+                    list,
+                )
+            }),
+    }
+}
+
+#[cfg(test)]
+fn change(tick: EmacsInt, start: EmacsInt, deleted: EmacsInt, inserted: EmacsInt) -> Change {
+    Change {
+        tick,
+        start,
+        deleted,
+        inserted,
+        text: None,
+    }
+}
+
+#[test]
+fn test_add_change() {
+    let mut regions = Vec::new();
+    add_change(&mut regions, &change(2, 10, 0, 5));
+    assert_eq!(regions, vec![(10, 15)]);
+    // Insertions before a region move it.
+    add_change(&mut regions, &change(3, 1, 0, 2));
+    assert_eq!(regions, vec![(1, 3), (12, 17)]);
+    // Deletions after it leave it alone and leave an empty region.
+    add_change(&mut regions, &change(4, 30, 4, 0));
+    assert_eq!(regions, vec![(1, 3), (12, 17), (30, 30)]);
+    // A replacement overlapping two regions merges them.
+    add_change(&mut regions, &change(5, 15, 16, 1));
+    assert_eq!(regions, vec![(1, 3), (12, 16)]);
+    // Adjacent changes merge too.
+    add_change(&mut regions, &change(6, 3, 0, 1));
+    assert_eq!(regions, vec![(1, 4), (13, 17)]);
+}
+
+#[test]
+fn test_journal() {
+    let mut journal = Journal::new(1);
+    journal.record(change(2, 1, 0, 3));
+    // Text property changes leave the tick alone.
+    journal.record(change(2, 1, 3, 3));
+    journal.record(change(5, 2, 1, 0));
+    assert_eq!(journal.changes.len(), 2);
+    assert_eq!(journal.changed_since(1), Some(vec![(1, 3)]));
+    assert_eq!(journal.changed_since(2), Some(vec![(2, 2)]));
+    assert_eq!(journal.changed_since(5), Some(vec![]));
+    assert_eq!(journal.changed_since(0), None);
+    assert_eq!(
+        journal.changes_since(1).unwrap(),
+        vec![&change(2, 1, 0, 3), &change(5, 2, 1, 0)]
+    );
+
+    for tick in 6..(6 + JOURNAL_LENGTH as EmacsInt) {
+        journal.record(change(tick, 1, 0, 1));
+    }
+    assert_eq!(journal.changes.len(), JOURNAL_LENGTH);
+    assert_eq!(journal.since, 5);
+    assert_eq!(journal.changed_since(4), None);
+    assert_eq!(
+        journal.changed_since(5),
+        Some(vec![(1, 1 + JOURNAL_LENGTH as EmacsInt)])
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/buffer_text_exports.rs"));
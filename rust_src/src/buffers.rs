@@ -103,6 +103,12 @@ impl LispBufferRef {
         unsafe { (*self.text).chars_modiff }
     }
 
+    /// Number of modifications to the buffer's overlays.
+    #[inline]
+    pub fn overlay_modifications(&self) -> EmacsInt {
+        unsafe { (*self.text).overlay_modiff }
+    }
+
     #[inline]
     pub fn mark_active(&self) -> LispObject {
         LispObject::from(self.mark_active)
@@ -124,6 +130,18 @@ impl LispBufferRef {
         LispObject::from(self.name).is_not_nil()
     }
 
+    /// Return the address of the byte at position N, like `BYTE_POS_ADDR`.
+    #[inline]
+    pub fn byte_pos_addr(&self, n: ptrdiff_t) -> *mut c_uchar {
+        let offset = if n >= self.gpt_byte() {
+            self.gap_size()
+        } else {
+            0
+        };
+
+        unsafe { self.beg_addr().offset(offset + n - self.beg_byte()) }
+    }
+
     #[inline]
     pub fn fetch_byte(&self, n: ptrdiff_t) -> u8 {
         let offset = if n >= self.gpt_byte() {
@@ -1,12 +1,19 @@
 //! Operations on characters.
 
+use std::collections::HashMap;
+use std::ptr;
+
+use libc::{c_char, ptrdiff_t};
+
 use remacs_macros::lisp_fn;
-use remacs_sys::EmacsInt;
+use remacs_sys::{build_string, find_symbol_value, lisp_string_width, EmacsInt, Fnreverse,
+                 Fsubstring};
 
-use lisp::LispObject;
+use lisp::{intern, LispObject};
 use lisp::defsubr;
 use multibyte::{make_char_multibyte, raw_byte_from_codepoint_safe};
-use multibyte::MAX_CHAR;
+use multibyte::{Codepoint, MAX_CHAR};
+use strings::lisp_string;
 
 /// Return the character of the maximum code.
 #[lisp_fn]
@@ -54,4 +61,318 @@ pub fn multibyte_char_to_unibyte(ch: LispObject) -> LispObject {
     }
 }
 
+/// Apply F to each string in the list or vector STRINGS, returning the
+/// results in a sequence of the same kind.
+fn map_strings<F: FnMut(LispObject) -> LispObject>(strings: LispObject, mut f: F) -> LispObject {
+    if let Some(vector) = strings.as_vectorlike().and_then(|v| v.as_vector()) {
+        let result = call!(
+            intern("make-vector"),
+            LispObject::from_natnum(vector.len() as EmacsInt),
+            LispObject::constant_nil()
+        );
+        for (i, &string) in vector.as_slice().iter().enumerate() {
+            let value = f(string);
+            let slots = result.as_vectorlike().and_then(|v| v.as_vector()).unwrap();
+            slots.as_mut_slice()[i] = value;
+        }
+        result
+    } else {
+        let mut result = LispObject::constant_nil();
+        for string in strings.iter_cars() {
+            result = LispObject::cons(f(string), result);
+        }
+        LispObject::from(unsafe { Fnreverse(result.to_raw()) })
+    }
+}
+
+/// Return ELLIPSIS if it is nil or a string, and otherwise the value
+/// of `truncate-string-ellipsis', as `truncate-string-to-width' does.
+fn ellipsis_string(ellipsis: LispObject) -> LispObject {
+    if ellipsis.is_nil() || ellipsis.is_string() {
+        return ellipsis;
+    }
+    let default = LispObject::from(unsafe {
+        find_symbol_value(intern("truncate-string-ellipsis").to_raw())
+    });
+    if default.is_string() {
+        default
+    } else {
+        LispObject::from(unsafe { build_string(b"...\0".as_ptr() as *const c_char) })
+    }
+}
+
+fn string_width(string: LispObject) -> ptrdiff_t {
+    string.as_string_or_error();
+    unsafe { lisp_string_width(string.to_raw(), 0, ptr::null_mut(), ptr::null_mut()) }
+}
+
+/// Return the widths of the strings in STRINGS, a list or vector, as a
+/// sequence of the same kind.
+/// Each width is what `string-width' returns for the string when
+/// displayed in the current buffer.
+#[lisp_fn]
+pub fn string_width_batch(strings: LispObject) -> LispObject {
+    map_strings(strings, |string| {
+        LispObject::from_natnum(string_width(string) as EmacsInt)
+    })
+}
+
+/// Truncate each string in STRINGS, a list or vector, to END-COLUMN.
+/// Return the truncated strings as a sequence of the same kind.
+/// This is like calling `truncate-string-to-width' on each string with
+/// no START-COLUMN and the same END-COLUMN, PADDING and ELLIPSIS, but
+/// measures each string only once.  Strings that already fit and need
+/// no padding are returned unchanged rather than copied.
+#[lisp_fn(min = "2")]
+pub fn truncate_string_to_width_batch(
+    strings: LispObject,
+    end_column: LispObject,
+    padding: LispObject,
+    ellipsis: LispObject,
+) -> LispObject {
+    let end_column = end_column.as_natnum_or_error() as ptrdiff_t;
+    if padding.is_not_nil() {
+        padding.as_character_or_error();
+    }
+    let ellipsis = ellipsis_string(ellipsis);
+    let ellipsis_width = if ellipsis.is_nil() {
+        0
+    } else {
+        string_width(ellipsis)
+    };
+
+    map_strings(strings, |string| {
+        let width = string_width(string);
+        let (end, ellipsis) = if end_column < width && width > ellipsis_width {
+            (end_column - ellipsis_width, ellipsis)
+        } else {
+            (end_column, LispObject::constant_nil())
+        };
+        let (mut nchars, mut nbytes) = (0, 0);
+        let kept = if end <= 0 {
+            0
+        } else {
+            unsafe { lisp_string_width(string.to_raw(), end, &mut nchars, &mut nbytes) }
+        };
+        let tail = if padding.is_not_nil() && kept < end {
+            call!(
+                intern("make-string"),
+                LispObject::from_natnum((end - kept) as EmacsInt),
+                padding
+            )
+        } else {
+            LispObject::constant_nil()
+        };
+        if kept == width && tail.is_nil() && ellipsis.is_nil() {
+            return string;
+        }
+        let head = LispObject::from(unsafe {
+            Fsubstring(
+                string.to_raw(),
+                LispObject::from_natnum(0).to_raw(),
+                LispObject::from_natnum(nchars as EmacsInt).to_raw(),
+            )
+        });
+        call!(intern("concat"), head, tail, ellipsis)
+    })
+}
+
+/// How `truncate-and-pad-string' measures strings.
+enum Measure {
+    /// In columns, as `string-width' does.
+    Columns,
+    /// In pixels, with the fonts the selected window would use.  The
+    /// width of each character in each font is only asked of the font
+    /// once per call.
+    Pixels {
+        char_width: ptrdiff_t,
+        widths: HashMap<(EmacsInt, Codepoint), ptrdiff_t>,
+    },
+}
+
+impl Measure {
+    /// Return the pixel width of the character C at INDEX in STRING.
+    /// Characters that have no font, as on text terminals, are as wide
+    /// as their columns of the frame's default font.
+    fn char_pixels(
+        char_width: ptrdiff_t,
+        widths: &mut HashMap<(EmacsInt, Codepoint), ptrdiff_t>,
+        string: LispObject,
+        index: EmacsInt,
+        c: Codepoint,
+    ) -> ptrdiff_t {
+        let index = LispObject::from_natnum(index);
+        let font = call!(intern("font-at"), index, LispObject::constant_nil(), string);
+        if let Some(&width) = widths.get(&(font.to_raw(), c)) {
+            return width;
+        }
+        let glyph = if font.is_nil() {
+            LispObject::constant_nil()
+        } else {
+            let end = LispObject::from_natnum(index.as_natnum_or_error() + 1);
+            let glyphs = call!(intern("font-get-glyphs"), font, index, end, string);
+            call!(intern("aref"), glyphs, LispObject::from_natnum(0))
+        };
+        let width = if glyph.is_nil() {
+            let character = LispObject::from_natnum(EmacsInt::from(c));
+            let columns = call!(intern("char-width"), character);
+            columns.as_natnum_or_error() as ptrdiff_t * char_width
+        } else {
+            call!(intern("aref"), glyph, LispObject::from_natnum(4)).as_natnum_or_error()
+                as ptrdiff_t
+        };
+        widths.insert((font.to_raw(), c), width);
+        width
+    }
+
+    /// Return the width of STRING, and the number of its first characters
+    /// that fit in LIMIT with their width.
+    fn fit(
+        &mut self,
+        string: LispObject,
+        limit: ptrdiff_t,
+    ) -> (ptrdiff_t, ptrdiff_t, ptrdiff_t) {
+        match *self {
+            Measure::Columns => {
+                let width = string_width(string);
+                let (mut nchars, mut nbytes) = (0, 0);
+                let kept = if limit <= 0 {
+                    0
+                } else {
+                    unsafe {
+                        lisp_string_width(string.to_raw(), limit, &mut nchars, &mut nbytes)
+                    }
+                };
+                (width, nchars, kept)
+            }
+            Measure::Pixels {
+                char_width,
+                ref mut widths,
+            } => {
+                let (mut width, mut nchars, mut kept) = (0, 0, 0);
+                // Measuring runs Lisp, which may move the string's data.
+                let chars: Vec<Codepoint> = string.as_string_or_error().chars().collect();
+                for (index, c) in chars.into_iter().enumerate() {
+                    let pixels =
+                        Measure::char_pixels(char_width, widths, string, index as EmacsInt, c);
+                    width += pixels;
+                    if width <= limit && nchars == index as ptrdiff_t {
+                        nchars += 1;
+                        kept = width;
+                    }
+                }
+                (width, nchars, kept)
+            }
+        }
+    }
+
+    /// Return a blank as wide as WIDTH.
+    fn blank(&self, width: ptrdiff_t) -> LispObject {
+        match *self {
+            Measure::Columns => call!(
+                intern("make-string"),
+                LispObject::from_natnum(width as EmacsInt),
+                LispObject::from_natnum(EmacsInt::from(b' '))
+            ),
+            Measure::Pixels { .. } => call!(
+                intern("propertize"),
+                lisp_string(" "),
+                intern("display"),
+                list!(
+                    intern("space"),
+                    intern(":width"),
+                    list!(LispObject::from_natnum(width as EmacsInt))
+                )
+            ),
+        }
+    }
+}
+
+/// Make each string in STRINGS, a list or vector, exactly WIDTH wide.
+/// Return the results as a sequence of the same kind.
+/// Strings wider than WIDTH are truncated and end with ELLIPSIS, which
+/// is a string, t for `truncate-string-ellipsis', or nil for none; an
+/// ellipsis wider than WIDTH is left out.  Narrower strings are padded
+/// with spaces: ALIGN `right' puts the padding before the string,
+/// `center' on both sides, and anything else after it.
+///
+/// WIDTH is in columns, as `string-width' measures them, unless
+/// PIXELWISE is non-nil, in which case it is in pixels, as the fonts of
+/// the selected window's frame would draw the strings with their faces.
+/// The padding is then a space with a `display' property of that many
+/// pixels, so that tabs and header line fields line up exactly.  Each
+/// character is measured once per font in a call, so that measuring all
+/// the tabs of a tab line at once is cheap.
+#[lisp_fn(min = "2")]
+pub fn truncate_and_pad_string(
+    strings: LispObject,
+    width: LispObject,
+    align: LispObject,
+    ellipsis: LispObject,
+    pixelwise: LispObject,
+) -> LispObject {
+    let width = width.as_natnum_or_error() as ptrdiff_t;
+    let mut measure = if pixelwise.is_nil() {
+        Measure::Columns
+    } else {
+        let char_width = call!(intern("frame-char-width")).as_natnum_or_error();
+        Measure::Pixels {
+            char_width: char_width as ptrdiff_t,
+            widths: HashMap::new(),
+        }
+    };
+    let ellipsis = ellipsis_string(ellipsis);
+    let ellipsis_width = if ellipsis.is_nil() {
+        0
+    } else {
+        measure.fit(ellipsis, 0).0
+    };
+
+    map_strings(strings, |string| {
+        let (string_width, nchars, kept) = measure.fit(string, width);
+        let (text, used) = if string_width <= width {
+            (string, string_width)
+        } else {
+            let (ellipsis, ellipsis_width) = if ellipsis_width <= width {
+                (ellipsis, ellipsis_width)
+            } else {
+                (LispObject::constant_nil(), 0)
+            };
+            let (nchars, kept) = if ellipsis_width > 0 {
+                let (_, nchars, kept) = measure.fit(string, width - ellipsis_width);
+                (nchars, kept)
+            } else {
+                (nchars, kept)
+            };
+            let head = call!(
+                intern("substring"),
+                string,
+                LispObject::from_natnum(0),
+                LispObject::from_natnum(nchars as EmacsInt)
+            );
+            (call!(intern("concat"), head, ellipsis), kept + ellipsis_width)
+        };
+        let padding = width - used;
+        if padding <= 0 {
+            return text;
+        }
+        let before = if align.eq(intern("right")) {
+            padding
+        } else if align.eq(intern("center")) {
+            padding / 2
+        } else {
+            0
+        };
+        let after = padding - before;
+        let blank = |width: ptrdiff_t| {
+            if width > 0 {
+                measure.blank(width)
+            } else {
+                LispObject::constant_nil()
+            }
+        };
+        call!(intern("concat"), blank(before), text, blank(after))
+    })
+}
+
 include!(concat!(env!("OUT_DIR"), "/character_exports.rs"));
@@ -0,0 +1,342 @@
+//! Clipboard access for text terminals.
+//!
+//! Two routes are offered: the OSC 52 escape sequence, which asks the
+//! terminal emulator to set or report the clipboard and so works over
+//! SSH, and the system clipboard of the machine Emacs runs on, for
+//! sessions that have one but no GUI frame.
+//!
+//! Under Wayland the system clipboard and the primary selection are
+//! reached through the wl-copy and wl-paste programs, since the
+//! clipboard crate only speaks X11.  wl-copy keeps serving what it was
+//! given after Emacs exits; for X11, `clipboard-native-persist' hands
+//! the text to xclip or xsel to the same end.
+//!
+//! The clipboard crate is only used with the `native-clipboard' feature,
+//! which configure turns on when libxcb is found.  Without it, only the
+//! Wayland clipboard is reached natively.
+
+use std::env;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+#[cfg(feature = "native-clipboard")]
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "native-clipboard")]
+use clipboard_crate::{ClipboardContext, ClipboardProvider};
+
+use base64_crate;
+use remacs_macros::lisp_fn;
+use remacs_sys::Qerror;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// The largest DCS string GNU screen passes on.
+const SCREEN_CHUNK: usize = 76;
+
+/// How many milliseconds to wait for a clipboard helper program.
+const HELPER_TIMEOUT: u64 = 2000;
+
+#[cfg(feature = "native-clipboard")]
+lazy_static! {
+    // Setting the X clipboard means serving it until someone else takes
+    // it over, so the context has to outlive the call that set it.
+    static ref NATIVE: Mutex<Option<ClipboardContext>> = Mutex::new(None);
+}
+
+/// Return the OSC 52 sequence that sets selection SELECTION (`c' or
+/// `p') to TEXT.  With SCREEN, wrap it in DCS strings short enough for
+/// GNU screen.
+fn osc52_sequence(selection: char, text: &[u8], screen: bool) -> String {
+    let encoded = base64_crate::encode(text);
+    let mut body = format!("\x1b]52;{};", selection);
+    if !screen {
+        body.push_str(&encoded);
+        body.push('\x07');
+        return body;
+    }
+    let mut result = String::from("\x1bP");
+    result.push_str(&body);
+    for (i, chunk) in encoded.as_bytes().chunks(SCREEN_CHUNK).enumerate() {
+        if i > 0 {
+            result.push_str("\x1b\\\x1bP");
+        }
+        result.push_str(&String::from_utf8_lossy(chunk));
+    }
+    result.push_str("\x07\x1b\\");
+    result
+}
+
+/// Return the text in the data part of an OSC 52 reply, or None if it
+/// isn't valid base64.  A leading `SELECTION;' is skipped.
+fn osc52_payload(reply: &[u8]) -> Option<Vec<u8>> {
+    let data = match reply.iter().rposition(|&b| b == b';') {
+        Some(i) => &reply[i + 1..],
+        None => reply,
+    };
+    let data: Vec<u8> = data.iter()
+        .cloned()
+        .filter(|b| !b"\r\n\x07".contains(b))
+        .collect();
+    base64_crate::decode(&data).ok()
+}
+
+fn selection_char(selection: LispObject) -> char {
+    if selection.eq(intern("CLIPBOARD")) {
+        'c'
+    } else if selection.eq(intern("PRIMARY")) {
+        'p'
+    } else {
+        xsignal!(Qerror, lisp_string("Invalid selection type"), selection)
+    }
+}
+
+/// Return the UTF-8 bytes of STRING.
+fn utf8_bytes(string: LispObject) -> Vec<u8> {
+    string.as_string_or_error();
+    call!(
+        intern("encode-coding-string"),
+        string,
+        intern("utf-8-unix")
+    ).as_string_or_error()
+        .as_slice()
+        .to_vec()
+}
+
+/// Whether the system clipboard is that of a Wayland compositor.
+fn wayland() -> bool {
+    !cfg!(any(target_os = "macos", windows)) && env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Run PROGRAM with ARGS, writing INPUT to its standard input, and
+/// return its output if it succeeds in time.  Helpers that serve a
+/// selection fork and leave their parent to exit.
+fn run_helper(program: &str, args: &[&str], input: Option<&[u8]>) -> Option<Vec<u8>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    if let Some(input) = input {
+        // Dropping stdin closes it, which ends the input.
+        let mut stdin = child.stdin.take()?;
+        if stdin.write_all(input).is_err() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+    }
+    // Read the output in a thread of its own, so that a helper that
+    // hangs cannot block Emacs.
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).ok().map(|_| output)
+    });
+    let deadline = Instant::now() + Duration::from_millis(HELPER_TIMEOUT);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+    };
+    if !status.map_or(false, |status| status.success()) {
+        return None;
+    }
+    reader.join().ok().and_then(|output| output)
+}
+
+/// Return the option of the wl-clipboard programs for SELECTION.
+fn wayland_args(selection: char) -> Vec<&'static str> {
+    if selection == 'p' {
+        vec!["--primary"]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Set SELECTION of the Wayland compositor to TEXT.
+fn wayland_set(selection: char, text: &[u8]) -> bool {
+    let mut args = wayland_args(selection);
+    args.extend_from_slice(&["--type", "text/plain;charset=utf-8"]);
+    run_helper("wl-copy", &args, Some(text)).is_some()
+}
+
+/// Return the text in SELECTION of the Wayland compositor.
+fn wayland_get(selection: char) -> Option<Vec<u8>> {
+    let mut args = wayland_args(selection);
+    args.extend_from_slice(&["--no-newline", "--type", "text"]);
+    run_helper("wl-paste", &args, None)
+}
+
+/// Whether this session can have a system clipboard at all.
+#[cfg(feature = "native-clipboard")]
+fn native_possible() -> bool {
+    cfg!(any(target_os = "macos", windows)) || env::var_os("DISPLAY").is_some()
+        || env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Call F with the system clipboard, connecting to it on first use.
+/// Return None if there is no system clipboard.
+#[cfg(feature = "native-clipboard")]
+fn with_native<T, F: FnOnce(&mut ClipboardContext) -> Option<T>>(f: F) -> Option<T> {
+    if !native_possible() {
+        return None;
+    }
+    let mut native = NATIVE.lock().unwrap();
+    if native.is_none() {
+        *native = ClipboardProvider::new().ok();
+    }
+    native.as_mut().and_then(f)
+}
+
+/// Set the system clipboard to TEXT.  Return false if there is no
+/// system clipboard.
+#[cfg(feature = "native-clipboard")]
+fn native_set(text: String) -> bool {
+    with_native(|native| native.set_contents(text).ok()).is_some()
+}
+
+#[cfg(not(feature = "native-clipboard"))]
+fn native_set(_text: String) -> bool {
+    false
+}
+
+/// Return the text in the system clipboard, or None if there is no
+/// system clipboard or it holds no text.
+#[cfg(feature = "native-clipboard")]
+fn native_get() -> Option<String> {
+    with_native(|native| native.get_contents().ok())
+}
+
+#[cfg(not(feature = "native-clipboard"))]
+fn native_get() -> Option<String> {
+    None
+}
+
+/// Return the OSC 52 escape sequence that sets SELECTION to STRING.
+/// SELECTION is `CLIPBOARD' or `PRIMARY'.  The text is sent as UTF-8.
+/// If SCREEN is non-nil, wrap the sequence in Device Control Strings
+/// short enough to pass through the GNU screen program.
+#[lisp_fn(min = "2")]
+pub fn clipboard_osc52_sequence(
+    selection: LispObject,
+    string: LispObject,
+    screen: LispObject,
+) -> LispObject {
+    let c = selection_char(selection);
+    lisp_string(&osc52_sequence(c, &utf8_bytes(string), screen.is_not_nil()))
+}
+
+/// Decode REPLY, the data that a terminal sent for an OSC 52 query.
+/// REPLY may include the `SELECTION;' part that precedes the base64
+/// text.  Return the text as a string decoded from UTF-8, or nil if
+/// REPLY isn't valid base64.
+#[lisp_fn]
+pub fn clipboard_osc52_decode(reply: LispObject) -> LispObject {
+    let reply = reply.as_string_or_error();
+    match osc52_payload(reply.as_slice()) {
+        Some(bytes) => lisp_string(&String::from_utf8_lossy(&bytes)),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Set the system clipboard to STRING.
+/// SELECTION is `CLIPBOARD', or under Wayland also `PRIMARY'; for other
+/// selections nothing is done.  Return t if the clipboard was set, nil
+/// if this session has no system clipboard.
+#[lisp_fn]
+pub fn clipboard_native_set(selection: LispObject, string: LispObject) -> LispObject {
+    if wayland() && (selection.eq(intern("CLIPBOARD")) || selection.eq(intern("PRIMARY"))) {
+        let c = selection_char(selection);
+        return LispObject::from_bool(wayland_set(c, &utf8_bytes(string)));
+    }
+    if !selection.eq(intern("CLIPBOARD")) {
+        return LispObject::constant_nil();
+    }
+    let text = String::from_utf8_lossy(&utf8_bytes(string)).into_owned();
+    LispObject::from_bool(native_set(text))
+}
+
+/// Return the contents of the system clipboard as a string.
+/// SELECTION is `CLIPBOARD', or under Wayland also `PRIMARY'.  Return
+/// nil if this session has no system clipboard or it holds no text.
+#[lisp_fn]
+pub fn clipboard_native_get(selection: LispObject) -> LispObject {
+    if wayland() && (selection.eq(intern("CLIPBOARD")) || selection.eq(intern("PRIMARY"))) {
+        return match wayland_get(selection_char(selection)) {
+            Some(ref text) if !text.is_empty() => lisp_string(&String::from_utf8_lossy(text)),
+            _ => LispObject::constant_nil(),
+        };
+    }
+    if !selection.eq(intern("CLIPBOARD")) {
+        return LispObject::constant_nil();
+    }
+    match native_get() {
+        Some(text) => lisp_string(&text),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Make SELECTION, `CLIPBOARD' or `PRIMARY', keep STRING after Emacs
+/// exits.  The text is handed to a program that serves the selection
+/// until something else takes it over: wl-copy under Wayland, and xclip
+/// or xsel under X.  On macOS and Windows, which keep the clipboard
+/// themselves, the clipboard is just set.  Return t if the text was
+/// handed over, nil if no such program could take it.
+#[lisp_fn]
+pub fn clipboard_native_persist(selection: LispObject, string: LispObject) -> LispObject {
+    let c = selection_char(selection);
+    let text = utf8_bytes(string);
+    let done = if wayland() {
+        wayland_set(c, &text)
+    } else if cfg!(any(target_os = "macos", windows)) {
+        c == 'c' && clipboard_native_set(selection, string).is_not_nil()
+    } else if env::var_os("DISPLAY").is_some() {
+        let name = if c == 'c' { "clipboard" } else { "primary" };
+        let flag = format!("--{}", name);
+        run_helper("xclip", &["-selection", name, "-in"], Some(&text)).is_some()
+            || run_helper("xsel", &[flag.as_str(), "--input"], Some(&text)).is_some()
+    } else {
+        false
+    };
+    LispObject::from_bool(done)
+}
+
+#[test]
+fn test_run_helper() {
+    assert_eq!(run_helper("cat", &[], Some(b"in")), Some(b"in".to_vec()));
+    assert_eq!(run_helper("false", &[], None), None);
+    assert_eq!(run_helper("/nonexistent/program", &[], None), None);
+    assert_eq!(wayland_args('p'), vec!["--primary"]);
+}
+
+#[test]
+fn test_osc52() {
+    assert_eq!(osc52_sequence('c', b"hi", false), "\x1b]52;c;aGk=\x07");
+    let long = vec![b'x'; 100];
+    let wrapped = osc52_sequence('p', &long, true);
+    assert!(wrapped.starts_with("\x1bP\x1b]52;p;eHh4"));
+    assert!(wrapped.ends_with("\x07\x1b\\"));
+    assert_eq!(wrapped.matches("\x1b\\\x1bP").count(), 1);
+
+    assert_eq!(osc52_payload(b"c;aGk="), Some(b"hi".to_vec()));
+    assert_eq!(osc52_payload(b"aGk=\x07"), Some(b"hi".to_vec()));
+    assert_eq!(osc52_payload(b"c;!!"), None);
+}
+
+include!(concat!(env!("OUT_DIR"), "/clipboard_exports.rs"));
@@ -0,0 +1,387 @@
+//! Decoders for legacy character sets, built on encoding_rs.
+//!
+//! The coding systems whose MIME charset is listed in `CODECS' decode
+//! through `codecs_decode' instead of the table-driven decoders in
+//! coding.c.  Bytes that are not valid in the charset, or that the
+//! charset leaves undefined, decode to raw 8-bit characters as they do
+//! in C, so that encoding the text again gives back the same bytes.
+//! Encoding is still done in C.
+
+use libc::{c_int, ptrdiff_t};
+use std::slice;
+
+use encoding_rs::{DecoderResult, Encoding};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::Lisp_Object;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+
+/// The MIME charsets decoded here, with the encoding_rs label of the
+/// encoding that decodes them and whether the charset is an ISO one,
+/// where 0x80..0x9F are C1 controls.  The WHATWG encodings that
+/// encoding_rs implements map a few ISO charsets to the Windows code
+/// page that extends them.
+const CODECS: &[(&str, &str, bool)] = &[
+    ("iso-8859-1", "windows-1252", true),
+    ("iso-8859-2", "iso-8859-2", true),
+    ("iso-8859-3", "iso-8859-3", true),
+    ("iso-8859-4", "iso-8859-4", true),
+    ("iso-8859-5", "iso-8859-5", true),
+    ("iso-8859-6", "iso-8859-6", true),
+    ("iso-8859-7", "iso-8859-7", true),
+    ("iso-8859-8", "iso-8859-8", true),
+    ("iso-8859-9", "windows-1254", true),
+    ("iso-8859-10", "iso-8859-10", true),
+    ("iso-8859-11", "windows-874", true),
+    ("iso-8859-13", "iso-8859-13", true),
+    ("iso-8859-14", "iso-8859-14", true),
+    ("iso-8859-15", "iso-8859-15", true),
+    ("iso-8859-16", "iso-8859-16", true),
+    ("windows-1250", "windows-1250", false),
+    ("windows-1251", "windows-1251", false),
+    ("windows-1252", "windows-1252", false),
+    ("windows-1253", "windows-1253", false),
+    ("windows-1254", "windows-1254", false),
+    ("windows-1255", "windows-1255", false),
+    ("windows-1256", "windows-1256", false),
+    ("windows-1257", "windows-1257", false),
+    ("windows-1258", "windows-1258", false),
+    ("shift_jis", "shift_jis", false),
+    ("euc-jp", "euc-jp", false),
+    ("gbk", "gbk", false),
+    ("big5", "big5", false),
+    ("big5-hkscs", "big5", false),
+];
+
+/// How a single-byte charset decodes each byte, or None for the
+/// multibyte charsets.
+type ByteTable = Option<Vec<i32>>;
+
+lazy_static! {
+    static ref ENCODINGS: Vec<(&'static Encoding, ByteTable)> = CODECS
+        .iter()
+        .map(|&(_, label, iso)| {
+            let encoding = Encoding::for_label(label.as_bytes()).unwrap();
+            let table = if encoding.is_single_byte() {
+                Some((0..256).map(|b| decode_byte(encoding, b as u8, iso)).collect())
+            } else {
+                None
+            };
+            (encoding, table)
+        })
+        .collect();
+}
+
+/// The character code of the raw 8-bit byte B.
+fn byte8_to_char(b: u8) -> i32 {
+    b as i32 + 0x3F_FF00
+}
+
+/// Decode the byte B of the single-byte ENCODING.  encoding_rs maps
+/// the bytes that Windows code pages leave undefined to C1 controls,
+/// which Emacs decodes to raw bytes instead.
+fn decode_byte(encoding: &'static Encoding, b: u8, iso: bool) -> i32 {
+    if b < 0x80 {
+        return b as i32;
+    }
+    if b < 0xA0 && iso {
+        return b as i32;
+    }
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut utf8 = [0; 8];
+    let (result, _, written) = decoder.decode_to_utf8_without_replacement(&[b], &mut utf8, true);
+    let c = match (result, ::std::str::from_utf8(&utf8[..written])) {
+        (DecoderResult::InputEmpty, Ok(s)) => s.chars().next(),
+        _ => None,
+    };
+    match c {
+        Some(c) if c as u32 >= 0xA0 || (c as u32) < 0x80 => c as i32,
+        _ => byte8_to_char(b),
+    }
+}
+
+/// A unit of the source text: a byte to decode or, when decoding
+/// multibyte text, a character that is already decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Unit {
+    Byte(u8),
+    Char(i32),
+}
+
+/// Split the multibyte text SRC into units, each with the offset just
+/// past it.  Raw 8-bit characters become bytes again.
+fn multibyte_units(src: &[u8]) -> Vec<(Unit, usize)> {
+    let mut units = Vec::with_capacity(src.len());
+    let mut i = 0;
+    while i < src.len() {
+        let b = src[i];
+        let len = match b {
+            0x00...0x7F => 1,
+            0xC0...0xDF => 2,
+            0xE0...0xEF => 3,
+            0xF0...0xF7 => 4,
+            0xF8 => 5,
+            _ => 1,
+        };
+        if len == 1 || i + len > src.len() {
+            units.push((Unit::Byte(b), i + 1));
+            i += 1;
+            continue;
+        }
+        let unit = if b == 0xC0 || b == 0xC1 {
+            Unit::Byte((((b & 1) << 6) | (src[i + 1] & 0x3F)) | 0x80)
+        } else {
+            let lead = match len {
+                2 => b & 0x1F,
+                3 => b & 0x0F,
+                4 => b & 0x07,
+                _ => 0,
+            };
+            let c = src[i + 1..i + len]
+                .iter()
+                .fold(lead as i32, |c, &b| (c << 6) | (b & 0x3F) as i32);
+            Unit::Char(c)
+        };
+        units.push((unit, i + len));
+        i += len;
+    }
+    units
+}
+
+/// The longest character of the multibyte charsets, in bytes.
+const MAX_CHAR_BYTES: usize = 4;
+
+/// Decode BYTES with the multibyte ENCODING into OUT, stopping when OUT
+/// holds about MAX_CHARS characters; it may get MAX_CHAR_BYTES - 1 more.
+/// Return the number of bytes decoded.  A character cut off by the end
+/// of BYTES is left for the next call unless LAST.
+fn decode_multibyte(
+    encoding: &'static Encoding,
+    bytes: &[u8],
+    last: bool,
+    out: &mut Vec<i32>,
+    max_chars: usize,
+) -> usize {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut utf8 = vec![0; 4 * bytes.len() + 16];
+    let mut pos = 0;
+    while pos < bytes.len() && out.len() < max_chars {
+        // No byte decodes to more than one character.  Going a little
+        // further makes sure the last character fits.
+        let limit = bytes
+            .len()
+            .min(pos + (max_chars - out.len()) + MAX_CHAR_BYTES - 1);
+        let (result, read, written) =
+            decoder.decode_to_utf8_without_replacement(&bytes[pos..limit], &mut utf8, true);
+        if let Ok(s) = ::std::str::from_utf8(&utf8[..written]) {
+            out.extend(s.chars().map(|c| c as i32));
+        }
+        match result {
+            DecoderResult::InputEmpty => {
+                pos += read;
+                if limit == bytes.len() {
+                    break;
+                }
+            }
+            DecoderResult::OutputFull => pos += read,
+            DecoderResult::Malformed(bad, after) => {
+                let bad_end = pos + read - after as usize;
+                let bad_start = bad_end - bad as usize;
+                if bad_end == limit && (limit < bytes.len() || !last) {
+                    // Perhaps the start of a character whose other
+                    // bytes have not been seen yet.
+                    pos = bad_start;
+                    break;
+                }
+                for &b in &bytes[bad_start..bad_end] {
+                    out.push(byte8_to_char(b));
+                }
+                pos = bad_end;
+            }
+        }
+        decoder = encoding.new_decoder_without_bom_handling();
+    }
+    pos
+}
+
+/// Decode UNITS with codec CODEC into OUT, stopping when it holds about
+/// MAX_CHARS characters, as for `decode_multibyte'.  Return the number
+/// of units decoded.
+fn decode_units(
+    codec: usize,
+    units: &[Unit],
+    last: bool,
+    out: &mut Vec<i32>,
+    max_chars: usize,
+) -> usize {
+    let &(encoding, ref table) = &ENCODINGS[codec];
+    let mut i = 0;
+    while i < units.len() && out.len() < max_chars {
+        match units[i] {
+            Unit::Char(c) => {
+                out.push(c);
+                i += 1;
+            }
+            Unit::Byte(b) if table.is_some() => {
+                out.push(table.as_ref().unwrap()[b as usize]);
+                i += 1;
+            }
+            Unit::Byte(_) => {
+                let bytes: Vec<u8> = units[i..]
+                    .iter()
+                    .take_while(|unit| match **unit {
+                        Unit::Byte(_) => true,
+                        Unit::Char(_) => false,
+                    })
+                    .map(|unit| match *unit {
+                        Unit::Byte(b) => b,
+                        Unit::Char(_) => unreachable!(),
+                    })
+                    .collect();
+                let run_is_last = last || i + bytes.len() < units.len();
+                let decoded = decode_multibyte(encoding, &bytes, run_is_last, out, max_chars);
+                i += decoded;
+                if decoded < bytes.len() {
+                    break;
+                }
+            }
+        }
+    }
+    i
+}
+
+/// Return the index of the decoder for MIME-CHARSET, or -1 if there is
+/// none.
+#[no_mangle]
+pub extern "C" fn codecs_find(mime_charset: Lisp_Object) -> c_int {
+    let mime_charset = LispObject::from(mime_charset);
+    let name = match mime_charset.as_symbol() {
+        Some(symbol) => symbol.symbol_name().as_string_or_error(),
+        None => return -1,
+    };
+    CODECS
+        .iter()
+        .position(|&(charset, _, _)| charset.as_bytes() == name.as_slice())
+        .map_or(-1, |codec| codec as c_int)
+}
+
+/// Decode the SRC_BYTES bytes at SRC with decoder CODEC into the
+/// CHARBUF_SIZE elements of CHARBUF, as the `decoder' of a coding
+/// system does.  MULTIBYTE says whether the source is multibyte text,
+/// LAST whether it is the last block of the source, and EOL_DOS whether
+/// the end of lines are CRLF, in which case a CR is never decoded
+/// without the byte after it.
+///
+/// Return the number of characters produced, and store the number of
+/// bytes and characters of the source consumed in CONSUMED and
+/// CONSUMED_CHARS.
+#[no_mangle]
+pub unsafe extern "C" fn codecs_decode(
+    codec: c_int,
+    src: *const u8,
+    src_bytes: ptrdiff_t,
+    multibyte: bool,
+    last: bool,
+    eol_dos: bool,
+    charbuf: *mut c_int,
+    charbuf_size: ptrdiff_t,
+    consumed: *mut ptrdiff_t,
+    consumed_chars: *mut ptrdiff_t,
+) -> ptrdiff_t {
+    let src = slice::from_raw_parts(src, src_bytes as usize);
+    let units: Vec<(Unit, usize)> = if multibyte {
+        multibyte_units(src)
+    } else {
+        src.iter()
+            .enumerate()
+            .map(|(i, &b)| (Unit::Byte(b), i + 1))
+            .collect()
+    };
+    let plain: Vec<Unit> = units.iter().map(|&(unit, _)| unit).collect();
+    let mut out = Vec::with_capacity(charbuf_size as usize);
+    let max_chars = (charbuf_size as usize).saturating_sub(MAX_CHAR_BYTES - 1);
+    let mut decoded = decode_units(codec as usize, &plain, last, &mut out, max_chars);
+    if eol_dos && decoded > 0 && out.last() == Some(&(b'\r' as i32))
+        && !(last && decoded == units.len())
+    {
+        out.pop();
+        decoded -= 1;
+    }
+
+    *consumed = if decoded == 0 {
+        0
+    } else {
+        units[decoded - 1].1 as ptrdiff_t
+    };
+    *consumed_chars = decoded as ptrdiff_t;
+    slice::from_raw_parts_mut(charbuf, out.len()).copy_from_slice(&out);
+    out.len() as ptrdiff_t
+}
+
+/// Return the MIME charsets whose coding systems decode natively.
+/// Coding systems with one of these as their `:mime-charset' decode
+/// with encoding_rs rather than with the decoders written in C.
+#[lisp_fn]
+pub fn native_decoder_charsets() -> LispObject {
+    CODECS
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, &(charset, _, _)| {
+            LispObject::cons(intern(charset), list)
+        })
+}
+
+#[cfg(test)]
+fn decode_all(mime_charset: &str, bytes: &[u8]) -> Vec<i32> {
+    let codec = CODECS
+        .iter()
+        .position(|&(charset, _, _)| charset == mime_charset)
+        .unwrap();
+    let units: Vec<Unit> = bytes.iter().map(|&b| Unit::Byte(b)).collect();
+    let mut out = Vec::new();
+    assert_eq!(decode_units(codec, &units, true, &mut out, 1000), units.len());
+    out
+}
+
+#[test]
+fn test_single_byte() {
+    assert_eq!(decode_all("iso-8859-1", b"a\xe9\x85"), vec![0x61, 0xE9, 0x85]);
+    assert_eq!(decode_all("windows-1252", b"\x80\x81"), vec![0x20AC, byte8_to_char(0x81)]);
+    assert_eq!(decode_all("iso-8859-2", b"\xb1"), vec![0x105]);
+    assert_eq!(decode_all("iso-8859-3", b"\xa5"), vec![byte8_to_char(0xA5)]);
+}
+
+#[test]
+fn test_multibyte() {
+    assert_eq!(decode_all("shift_jis", b"\x82\xa0a"), vec![0x3042, 0x61]);
+    assert_eq!(decode_all("euc-jp", b"\xa4\xa2"), vec![0x3042]);
+    assert_eq!(decode_all("big5", b"\xa4\x40"), vec![0x4E00]);
+    assert_eq!(decode_all("gbk", b"\xc4\xe3"), vec![0x4F60]);
+    assert_eq!(
+        decode_all("shift_jis", b"\xff\x82"),
+        vec![byte8_to_char(0xFF), byte8_to_char(0x82)]
+    );
+
+    // A character cut off at the end waits for the rest.
+    let codec = CODECS
+        .iter()
+        .position(|&(charset, _, _)| charset == "shift_jis")
+        .unwrap();
+    let mut out = Vec::new();
+    let units = [Unit::Byte(b'a'), Unit::Byte(0x82)];
+    assert_eq!(decode_units(codec, &units, false, &mut out, 10), 1);
+    assert_eq!(out, vec![0x61]);
+}
+
+#[test]
+fn test_multibyte_units() {
+    let units: Vec<Unit> = multibyte_units(b"a\xc1\xa9\xc3\xa9")
+        .into_iter()
+        .map(|(unit, _)| unit)
+        .collect();
+    assert_eq!(units, vec![Unit::Byte(b'a'), Unit::Byte(0xE9), Unit::Char(0xE9)]);
+}
+
+include!(concat!(env!("OUT_DIR"), "/codecs_exports.rs"));
@@ -0,0 +1,683 @@
+//! Native scanning of compilation output for error messages.
+//!
+//! `compilation-parse-errors' searches the new output once for each rule
+//! of `compilation-error-regexp-alist', with the Emacs regexp engine,
+//! and goes through the match data of every hit in Lisp.  On huge build
+//! logs that dominates compilation-mode.  Here each rule's regexp is
+//! translated to the syntax of the regex crate and compiled once, and
+//! the output is copied out of the buffer once and scanned by all the
+//! rules in turn, returning a record for each message.
+//!
+//! The translation covers what compilation regexps use.  `\w' and `\s-'
+//! are taken with their meaning in the standard syntax table.  Rules
+//! whose regexps need anything else, such as back references, word or
+//! symbol boundaries, or syntax-dependent character classes, are
+//! returned to the caller to be searched in Lisp.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use regex::{Regex, RegexBuilder};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{EmacsInt, Qcase_fold_search};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{assq, nth};
+use strings::lisp_string;
+use symbols::symbol_value;
+
+/// An Emacs regexp translated to the syntax of the regex crate.
+#[derive(Debug, PartialEq)]
+struct Translation {
+    source: String,
+    /// The Emacs group number of each capturing group, in order.
+    groups: Vec<usize>,
+}
+
+/// The class used for `\w', word constituents in the standard syntax
+/// table.
+const WORD_CLASS: &str = r"\p{L}\p{Nd}";
+
+/// The class used for `\s-', whitespace in the standard syntax table.
+const SPACE_CLASS: &str = r" \t\n\r\f";
+
+/// Append C to SOURCE as a literal character.  Everything but letters,
+/// digits and spaces is written as a hex escape, which the regex crate
+/// takes as literal both inside and outside of brackets.
+fn push_literal(source: &mut String, c: char) {
+    if c.is_alphanumeric() || c == ' ' {
+        source.push(c);
+    } else {
+        write!(source, r"\x{{{:X}}}", c as u32).unwrap();
+    }
+}
+
+/// Return the ranges of the character class `[:NAME:]', for use inside
+/// brackets.  Classes whose meaning depends on the syntax table are not
+/// translated.
+fn class_ranges(name: &str) -> Result<&'static str, ()> {
+    match name {
+        "alpha" => Ok(r"\p{L}"),
+        "alnum" => Ok(r"\p{L}\p{Nd}"),
+        "digit" => Ok("0-9"),
+        "xdigit" => Ok("0-9A-Fa-f"),
+        "upper" => Ok(r"\p{Lu}"),
+        "lower" => Ok(r"\p{Ll}"),
+        "blank" => Ok(r"\p{Zs}\t"),
+        "cntrl" => Ok(r"\x{0}-\x{1F}"),
+        "ascii" => Ok(r"\x{0}-\x{7F}"),
+        "nonascii" => Ok(r"\x{80}-\x{10FFFF}"),
+        _ => Err(()),
+    }
+}
+
+/// Translate the bracket expression of PATTERN whose contents start at
+/// index I, and return the index after its closing `]'.  Backslashes
+/// are not special in Emacs brackets, and a `]' right after the opening
+/// `[' or `[^' is a member.
+fn translate_bracket(pattern: &[char], mut i: usize, source: &mut String) -> Result<usize, ()> {
+    source.push('[');
+    if pattern.get(i) == Some(&'^') {
+        source.push('^');
+        i += 1;
+    }
+    let first = i;
+    loop {
+        let c = match pattern.get(i) {
+            Some(&c) => c,
+            None => return Err(()),
+        };
+        if c == ']' && i > first {
+            source.push(']');
+            return Ok(i + 1);
+        }
+        if c == '[' && pattern.get(i + 1) == Some(&':') {
+            let name_start = i + 2;
+            let name_end = match pattern[name_start..]
+                .windows(2)
+                .position(|w| w == [':', ']'])
+            {
+                Some(len) => name_start + len,
+                None => return Err(()),
+            };
+            let name: String = pattern[name_start..name_end].iter().collect();
+            source.push_str(class_ranges(&name)?);
+            i = name_end + 2;
+            continue;
+        }
+        i += 1;
+        match (pattern.get(i), pattern.get(i + 1)) {
+            (Some(&'-'), Some(&last)) if last != ']' => {
+                // Emacs takes a reversed range as empty, which the regex
+                // crate rejects.
+                if last < c {
+                    return Err(());
+                }
+                push_literal(source, c);
+                source.push('-');
+                push_literal(source, last);
+                i += 2;
+            }
+            _ => push_literal(source, c),
+        }
+    }
+}
+
+/// Translate the interval whose contents start at index I, up to the
+/// closing `\}', and return the index after it.
+fn translate_interval(pattern: &[char], i: usize, source: &mut String) -> Result<usize, ()> {
+    let len = match pattern[i..].windows(2).position(|w| w == ['\\', '}']) {
+        Some(len) => len,
+        None => return Err(()),
+    };
+    let contents: String = pattern[i..i + len].iter().collect();
+    let valid = |bound: &str| bound.chars().all(|c| c.is_digit(10));
+    let mut bounds = contents.splitn(2, ',');
+    let min = bounds.next().unwrap_or("");
+    let max = bounds.next();
+    if !valid(min) || !max.map_or(true, valid) || (min.is_empty() && max.is_none()) {
+        return Err(());
+    }
+    source.push('{');
+    source.push_str(if min.is_empty() { "0" } else { min });
+    if let Some(max) = max {
+        source.push(',');
+        source.push_str(max);
+    }
+    source.push('}');
+    Ok(i + len + 2)
+}
+
+/// Whether a `$' before index I of PATTERN is an anchor, which it is at
+/// the end of the regexp, of a group or of an alternative.
+fn at_end(pattern: &[char], i: usize) -> bool {
+    match (pattern.get(i), pattern.get(i + 1)) {
+        (None, _) | (Some(&'\\'), Some(&')')) | (Some(&'\\'), Some(&'|')) => true,
+        _ => false,
+    }
+}
+
+/// Translate the Emacs regexp PATTERN, or fail if it uses something
+/// that can't be translated.
+fn translate(pattern: &str) -> Result<Translation, ()> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut source = String::new();
+    let mut groups = Vec::new();
+    // The highest group number so far.  A group without an explicit
+    // number gets the next one.
+    let mut nsub = 0;
+    // Whether `^' is an anchor and `*' a literal here, as they are at the
+    // start of the regexp, of a group or of an alternative.
+    let mut at_start = true;
+    let mut i = 0;
+    while i < pattern.len() {
+        let c = pattern[i];
+        i += 1;
+        let mut starts = false;
+        match c {
+            '^' if at_start => {
+                source.push('^');
+                starts = true;
+            }
+            '$' if at_end(&pattern, i) => source.push('$'),
+            '*' | '+' | '?' if !at_start => {
+                source.push(c);
+                if pattern.get(i) == Some(&'?') {
+                    source.push('?');
+                    i += 1;
+                }
+            }
+            '.' => source.push('.'),
+            '[' => i = translate_bracket(&pattern, i, &mut source)?,
+            '\\' => {
+                let c = match pattern.get(i) {
+                    Some(&c) => c,
+                    None => return Err(()),
+                };
+                i += 1;
+                match c {
+                    '(' => {
+                        starts = true;
+                        if pattern.get(i) != Some(&'?') {
+                            nsub += 1;
+                            groups.push(nsub);
+                            source.push('(');
+                        } else {
+                            let digits = pattern[i + 1..]
+                                .iter()
+                                .take_while(|c| c.is_digit(10))
+                                .count();
+                            if pattern.get(i + 1 + digits) != Some(&':') {
+                                return Err(());
+                            }
+                            if digits == 0 {
+                                source.push_str("(?:");
+                            } else {
+                                let number: String =
+                                    pattern[i + 1..i + 1 + digits].iter().collect();
+                                let number = match number.parse::<usize>() {
+                                    Ok(0) | Err(_) => return Err(()),
+                                    Ok(number) => number,
+                                };
+                                nsub = nsub.max(number);
+                                groups.push(number);
+                                source.push('(');
+                            }
+                            i += digits + 2;
+                        }
+                    }
+                    ')' => source.push(')'),
+                    '|' => {
+                        source.push('|');
+                        starts = true;
+                    }
+                    '{' if !at_start => i = translate_interval(&pattern, i, &mut source)?,
+                    'w' | 'W' => {
+                        source.push_str(if c == 'w' { "[" } else { "[^" });
+                        source.push_str(WORD_CLASS);
+                        source.push(']');
+                    }
+                    's' | 'S' => {
+                        match pattern.get(i) {
+                            Some(&'-') | Some(&' ') => i += 1,
+                            _ => return Err(()),
+                        }
+                        source.push_str(if c == 's' { "[" } else { "[^" });
+                        source.push_str(SPACE_CLASS);
+                        source.push(']');
+                    }
+                    '{' | 'b' | 'B' | '<' | '>' | '_' | '=' | '`' | '\'' | 'c' | 'C' => {
+                        return Err(())
+                    }
+                    c if c.is_digit(10) => return Err(()),
+                    c => push_literal(&mut source, c),
+                }
+            }
+            c => push_literal(&mut source, c),
+        }
+        at_start = starts;
+    }
+    Ok(Translation { source, groups })
+}
+
+/// A compiled rule regexp.
+#[derive(Clone)]
+struct Matcher {
+    regex: Regex,
+    /// The capturing groups of the regex for each Emacs group number.
+    /// Emacs allows several groups with the same explicit number, of
+    /// which at most one matches.
+    groups: Vec<Vec<usize>>,
+}
+
+impl Matcher {
+    fn new(pattern: &str, fold: bool) -> Option<Matcher> {
+        let translation = match translate(pattern) {
+            Ok(translation) => translation,
+            Err(()) => return None,
+        };
+        let regex = match RegexBuilder::new(&translation.source)
+            .case_insensitive(fold)
+            .multi_line(true)
+            .build()
+        {
+            Ok(regex) => regex,
+            Err(_) => return None,
+        };
+        let count = translation.groups.iter().cloned().max().unwrap_or(0);
+        let mut groups = vec![Vec::new(); count + 1];
+        groups[0].push(0);
+        for (index, &number) in translation.groups.iter().enumerate() {
+            groups[number].push(index + 1);
+        }
+        Some(Matcher { regex, groups })
+    }
+}
+
+lazy_static! {
+    /// Compiled rule regexps, by regexp and case folding.  `None` marks a
+    /// regexp that can't be translated.
+    static ref MATCHERS: Mutex<HashMap<(String, bool), Option<Matcher>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Return the matcher for PATTERN, compiling it on first use.
+fn matcher(pattern: &str, fold: bool) -> Option<Matcher> {
+    let mut matchers = MATCHERS.lock().unwrap();
+    matchers
+        .entry((pattern.to_string(), fold))
+        .or_insert_with(|| Matcher::new(pattern, fold))
+        .clone()
+}
+
+/// How a rule sets the type of its messages.
+enum MessageType {
+    Fixed(EmacsInt),
+    /// The groups that mark a warning and an info message.
+    Groups(Option<usize>, Option<usize>),
+}
+
+/// A rule of `compilation-error-regexp-alist' that can be scanned
+/// natively.
+struct Rule {
+    matcher: Matcher,
+    file: Option<usize>,
+    line: Option<usize>,
+    end_line: Option<usize>,
+    column: Option<usize>,
+    end_column: Option<usize>,
+    message_type: MessageType,
+}
+
+/// Return the group number OBJECT, or None if it is not a group number.
+fn group(object: LispObject) -> Option<usize> {
+    match object.as_fixnum() {
+        Some(n) if n >= 0 => Some(n as usize),
+        _ => None,
+    }
+}
+
+/// Split the `(START . END)' groups of a rule.
+fn group_pair(object: LispObject) -> (Option<usize>, Option<usize>) {
+    match object.as_cons() {
+        Some(cons) => (group(cons.car()), group(cons.cdr())),
+        None => (group(object), None),
+    }
+}
+
+impl Rule {
+    /// Make the rule for ITEM, an element of
+    /// `compilation-error-regexp-alist'.  Return None if it must be
+    /// searched in Lisp: its regexp can't be translated, its line is
+    /// computed by a function, or it is malformed.
+    fn new(item: LispObject, fold: bool) -> Option<Rule> {
+        let item = if item.is_symbol() {
+            let alist = symbol_value(intern("compilation-error-regexp-alist-alist"));
+            assq(item, alist).as_cons().map_or(LispObject::constant_nil(), |c| c.cdr())
+        } else {
+            item
+        };
+        if !item.is_cons() {
+            return None;
+        }
+        let field = |n| nth(LispObject::from_natnum(n), item);
+        let line = field(2);
+        let first_line = line.as_cons().map_or(line, |c| c.car());
+        let hyperlink = field(5);
+        if !(first_line.is_nil() || first_line.is_natnum())
+            || !(hyperlink.is_nil() || hyperlink.is_natnum())
+        {
+            return None;
+        }
+        let matcher = match item.as_cons().and_then(|c| c.car().as_string()) {
+            Some(pattern) => match ::std::str::from_utf8(pattern.as_slice()) {
+                Ok(pattern) => matcher(pattern, fold),
+                Err(_) => None,
+            },
+            None => None,
+        };
+        let matcher = match matcher {
+            Some(matcher) => matcher,
+            None => return None,
+        };
+        let (file, _) = group_pair(field(1));
+        let (line, end_line) = group_pair(line);
+        let (column, end_column) = group_pair(field(3));
+        let message_type = match field(4).as_cons() {
+            Some(cons) => MessageType::Groups(group(cons.car()), group(cons.cdr())),
+            None => MessageType::Fixed(field(4).as_fixnum().unwrap_or(2)),
+        };
+        Some(Rule {
+            matcher,
+            file,
+            line,
+            end_line,
+            column,
+            end_column,
+            message_type,
+        })
+    }
+}
+
+/// A match of a rule, with the byte offsets of each Emacs group.
+struct Match<'a> {
+    text: &'a str,
+    groups: Vec<Option<(usize, usize)>>,
+}
+
+impl<'a> Match<'a> {
+    fn group(&self, n: Option<usize>) -> Option<&'a str> {
+        match n.and_then(|n| self.groups.get(n).cloned()) {
+            Some(Some((start, end))) => Some(&self.text[start..end]),
+            _ => None,
+        }
+    }
+
+    /// The number at the start of group N, as `string-to-number' would
+    /// read it.
+    fn number(&self, n: Option<usize>) -> LispObject {
+        match self.group(n) {
+            Some(s) => {
+                let digits = s.chars().take_while(|c| c.is_digit(10)).count();
+                s[..digits]
+                    .parse()
+                    .map(LispObject::from_natnum)
+                    .unwrap_or_else(|_| LispObject::from_natnum(0))
+            }
+            None => LispObject::constant_nil(),
+        }
+    }
+
+    fn message_type(&self, message_type: &MessageType) -> EmacsInt {
+        match *message_type {
+            MessageType::Fixed(t) => t,
+            MessageType::Groups(warning, info) => if self.group(warning).is_some() {
+                1
+            } else if self.group(info).is_some() {
+                0
+            } else {
+                2
+            },
+        }
+    }
+}
+
+/// Turns byte offsets of the scanned text into buffer positions.
+/// Offsets must be asked for in increasing order, except within a match.
+struct Positions<'a> {
+    text: &'a str,
+    byte: usize,
+    pos: EmacsInt,
+}
+
+impl<'a> Positions<'a> {
+    /// Move to BYTE, the start of a match, and return its position.
+    fn advance(&mut self, byte: usize) -> EmacsInt {
+        self.pos += self.text[self.byte..byte].chars().count() as EmacsInt;
+        self.byte = byte;
+        self.pos
+    }
+
+    /// Return the position of BYTE, which is after the last offset moved
+    /// to.
+    fn get(&self, byte: usize) -> EmacsInt {
+        self.pos + self.text[self.byte..byte].chars().count() as EmacsInt
+    }
+}
+
+/// Return the match data of M, as `match-data' would.
+fn match_data(m: &Match, positions: &Positions) -> LispObject {
+    let last = m.groups.iter().rposition(|g| g.is_some()).unwrap_or(0);
+    let mut data = LispObject::constant_nil();
+    for g in m.groups[..last + 1].iter().rev() {
+        let (start, end) = match *g {
+            Some((start, end)) => (
+                LispObject::from_natnum(positions.get(start)),
+                LispObject::from_natnum(positions.get(end)),
+            ),
+            None => (LispObject::constant_nil(), LispObject::constant_nil()),
+        };
+        data = LispObject::cons(start, LispObject::cons(end, data));
+    }
+    data
+}
+
+/// Return the record of M, a match of the rule ITEM.
+fn record(item: LispObject, rule: &Rule, m: &Match, positions: &Positions) -> LispObject {
+    let file = m.group(rule.file)
+        .map_or(LispObject::constant_nil(), lisp_string);
+    list!(
+        intern(":rule"),
+        item,
+        intern(":file"),
+        file,
+        intern(":line"),
+        m.number(rule.line),
+        intern(":end-line"),
+        m.number(rule.end_line),
+        intern(":column"),
+        m.number(rule.column),
+        intern(":end-column"),
+        m.number(rule.end_column),
+        intern(":type"),
+        LispObject::from_natnum(m.message_type(&rule.message_type)),
+        intern(":match-data"),
+        match_data(m, positions)
+    )
+}
+
+/// Scan TEXT from byte offset FROM for the matches of RULE, and push
+/// their records onto RECORDS.  POS is the buffer position of TEXT.
+fn scan(
+    item: LispObject,
+    rule: &Rule,
+    text: &str,
+    from: usize,
+    pos: EmacsInt,
+    mut records: LispObject,
+) -> LispObject {
+    let regex = &rule.matcher.regex;
+    let mut locations = regex.locations();
+    let mut positions = Positions {
+        text,
+        byte: 0,
+        pos,
+    };
+    let mut start = from;
+    while start <= text.len() {
+        let (match_start, match_end) = match regex.read_captures_at(&mut locations, text, start) {
+            Some(bounds) => bounds,
+            None => break,
+        };
+        let groups = rule.matcher
+            .groups
+            .iter()
+            .map(|indices| indices.iter().filter_map(|&i| locations.pos(i)).next())
+            .collect();
+        let m = Match { text, groups };
+        positions.advance(match_start);
+        records = LispObject::cons(record(item, rule, &m, &positions), records);
+        // Go past an empty match, which would otherwise be found again.
+        start = if match_end > match_start {
+            match_end
+        } else {
+            match text[match_end..].chars().next() {
+                Some(c) => match_end + c.len_utf8(),
+                None => break,
+            }
+        };
+    }
+    records
+}
+
+/// Scan the compilation output between START and END for messages.
+/// RULES are the rules to use, as in `compilation-error-regexp-alist',
+/// which is the default.  The text is scanned once per rule, with
+/// `case-fold-search' in effect, as `compilation-parse-errors' does.
+///
+/// Return (RECORDS . UNHANDLED).  RECORDS are the messages found, in
+/// the order of their rules and then in buffer order.  Each is a plist
+///
+///   (:rule ITEM :file FILE :line LINE :end-line END-LINE :column COLUMN
+///    :end-column END-COLUMN :type TYPE :match-data MATCH-DATA)
+///
+/// where ITEM is the element of RULES that matched, FILE the file name
+/// as it appears in the output, LINE to END-COLUMN the numbers of the
+/// message or nil, and TYPE 2 for an error, 1 for a warning and 0 for
+/// info.  MATCH-DATA is the match data of the message, as `match-data'
+/// would return it after `re-search-forward' found it.
+///
+/// UNHANDLED are the elements of RULES that have to be searched for in
+/// Lisp, because their regexp uses something the native scanner does not
+/// support or their line is computed by a function.
+#[lisp_fn(min = "2")]
+pub fn compilation_parse_region_native(
+    start: LispObject,
+    end: LispObject,
+    rules: LispObject,
+) -> LispObject {
+    let rules = if rules.is_nil() {
+        symbol_value(intern("compilation-error-regexp-alist"))
+    } else {
+        rules
+    };
+    let (start, end) = {
+        let start = start.as_fixnum_coerce_marker_or_error();
+        let end = end.as_fixnum_coerce_marker_or_error();
+        (start.min(end), start.max(end))
+    };
+
+    // `^' can match at START only if it is at the beginning of a line.
+    // Otherwise the text starts with the character before START, which
+    // is only there so that `^' sees it.
+    let previous = call!(intern("char-before"), LispObject::from_fixnum(start));
+    let bol = previous.is_nil() || previous.as_fixnum() == Some(EmacsInt::from(b'\n'));
+    let text_start = if bol { start } else { start - 1 };
+    let text = call!(
+        intern("buffer-substring-no-properties"),
+        LispObject::from_fixnum(text_start),
+        LispObject::from_fixnum(end)
+    );
+    // Copy the text, as GC may move the string's data while records are
+    // made.
+    let text = {
+        let text = text.as_string_or_error();
+        let bytes = text.as_slice();
+        if !text.is_multibyte() && bytes.iter().any(|&b| b >= 0x80) {
+            None
+        } else {
+            ::std::str::from_utf8(bytes).ok().map(String::from)
+        }
+    };
+    let text = match text {
+        Some(text) => text,
+        // Raw bytes, which the regex crate can't see.
+        None => return LispObject::cons(LispObject::constant_nil(), rules),
+    };
+    let from = if bol {
+        0
+    } else {
+        text.chars().next().map_or(0, |c| c.len_utf8())
+    };
+
+    let fold = symbol_value(LispObject::from(Qcase_fold_search)).is_not_nil();
+    let mut records = LispObject::constant_nil();
+    let mut unhandled = LispObject::constant_nil();
+    for item in rules.iter_cars() {
+        match Rule::new(item, fold) {
+            Some(rule) => records = scan(item, &rule, &text, from, text_start, records),
+            None => unhandled = LispObject::cons(item, unhandled),
+        }
+    }
+    LispObject::cons(
+        call!(intern("nreverse"), records),
+        call!(intern("nreverse"), unhandled),
+    )
+}
+
+#[test]
+fn test_translate() {
+    let source = |pattern| translate(pattern).unwrap().source;
+    assert_eq!(
+        source("^\\([^: \n]+\\):\\([0-9]+\\)"),
+        r"^([^\x{3A} \x{A}]+)\x{3A}([0-9]+)"
+    );
+    assert_eq!(source(r"a\|b*?c\{2,\}d\{,3\}"), r"a|b*?c{2,}d{0,3}");
+    assert_eq!(source(r"\(?:x\)$\|$y"), r"(?:x)$|\x{24}y");
+    // `*' is a literal at the start, and `^' after it.
+    assert_eq!(source(r"*a^"), r"\x{2A}a\x{5E}");
+    assert_eq!(source(r"\(^*\)"), r"(^\x{2A})");
+    assert_eq!(source(r"[]a-]"), r"[\x{5D}a\x{2D}]");
+    assert_eq!(source(r"[^\[:alpha:]]"), r"[^\x{5C}\p{L}]");
+    assert_eq!(source(r"[[:alnum:]_/.]"), r"[\p{L}\p{Nd}\x{5F}\x{2F}\x{2E}]");
+    assert_eq!(source(r"\w\s-\S \."), r"[\p{L}\p{Nd}][ \t\n\r\f][^ \t\n\r\f]\x{2E}");
+
+    let unsupported = [
+        r"\(a\)\1",
+        r"\<a",
+        r"a\_>",
+        r"\sw",
+        r"[[:space:]]",
+        r"[z-a]",
+        r"\{2\}",
+        r"[a",
+    ];
+    for pattern in &unsupported {
+        assert!(translate(pattern).is_err(), "{}", pattern);
+    }
+}
+
+#[test]
+fn test_translate_groups() {
+    let groups = |pattern| translate(pattern).unwrap().groups;
+    assert_eq!(groups(r"\(a\)\(?:b\)\(c\)"), vec![1, 2]);
+    // An unnumbered group follows the highest number so far.
+    assert_eq!(groups(r"\(?3:a\)\(b\)\(?1:c\)\(d\)"), vec![3, 4, 1, 5]);
+    assert_eq!(groups(r"\(?1:a\)\|\(?1:b\)"), vec![1, 1]);
+    assert!(translate(r"\(?0:a\)").is_err());
+}
+
+include!(concat!(env!("OUT_DIR"), "/compile_exports.rs"));
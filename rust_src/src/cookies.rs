@@ -0,0 +1,605 @@
+//! A cookie jar for the URL package, following RFC 6265.
+//!
+//! url-cookie.el keeps cookies in alists that are searched linearly on
+//! every request, parses Set-Cookie headers leniently with
+//! `url-parse-args' and only understands a few date formats.  Here
+//! headers are parsed as the RFC says browsers do, cookies are matched
+//! by domain and path, and expired ones are dropped as they are found.
+//! The jar is saved and loaded with `lisp-data-write' and
+//! `lisp-data-read'.
+
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use persist::{lisp_data_read, lisp_data_write};
+use strings::lisp_string;
+
+/// A cookie.  Times are in seconds since the epoch.
+#[derive(Clone, Debug, PartialEq)]
+struct Cookie {
+    name: String,
+    value: String,
+    /// The domain, in lowercase and without a leading dot.
+    domain: String,
+    /// Whether the cookie is only sent to DOMAIN itself, as it is when
+    /// the header has no Domain attribute.
+    host_only: bool,
+    path: String,
+    /// When the cookie expires, or None if it lasts for the session.
+    expires: Option<i64>,
+    secure: bool,
+    http_only: bool,
+    /// The order in which cookies were made, which breaks ties between
+    /// cookies with paths of the same length.
+    creation: u64,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires.map_or(false, |expires| expires <= now)
+    }
+
+    /// Whether the cookie is sent with a request to HOST for PATH.
+    fn matches(&self, host: &str, path: &str, secure: bool) -> bool {
+        let host_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, &self.domain)
+        };
+        host_matches && path_match(path, &self.path) && (secure || !self.secure)
+    }
+}
+
+/// Return the current time in seconds since the epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn is_date_delimiter(c: char) -> bool {
+    match c {
+        '\t' | ' '...'/' | ';'...'@' | '['...'`' | '{'...'~' => true,
+        _ => false,
+    }
+}
+
+/// Return the number made of the 1 to MAX leading digits of TOKEN, if
+/// there are at least MIN of them and no other digit follows.
+fn leading_number(token: &str, min: usize, max: usize) -> Option<u32> {
+    let digits = token.chars().take_while(|c| c.is_digit(10)).count();
+    if digits < min || digits > max {
+        return None;
+    }
+    token[..digits].parse().ok()
+}
+
+/// Parse the time of day of TOKEN, which is hh:mm:ss with one or two
+/// digits each, possibly followed by other characters.
+fn parse_time(token: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = token.splitn(3, ':');
+    let hour = leading_number(parts.next()?, 1, 2)?;
+    let minute = parts.next().and_then(|part| {
+        if part.len() <= 2 {
+            part.parse().ok()
+        } else {
+            None
+        }
+    })?;
+    let second = leading_number(parts.next()?, 1, 2)?;
+    Some((hour, minute, second))
+}
+
+/// Return the number of days from January 1, 1970 to MONTH DAY, YEAR,
+/// or None if there is no such date.  YEAR must not be negative.
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let length = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1...12 => 31,
+        _ => return None,
+    };
+    if day < 1 || day > length {
+        return None;
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let (era, year_of_era) = (year / 400, year % 400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+/// Parse DATE, the value of an Expires attribute, with the lenient
+/// algorithm of section 5.1.1 of RFC 6265.
+fn parse_cookie_date(date: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"
+    ];
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    for token in date.split(is_date_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            if let Some(found) = parse_time(token) {
+                time = Some(found);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(found) = leading_number(token, 1, 2) {
+                day = Some(found);
+                continue;
+            }
+        }
+        if month.is_none() && token.len() >= 3 {
+            let prefix = token[..3].to_lowercase();
+            if let Some(index) = MONTHS.iter().position(|&name| name == prefix) {
+                month = Some(index as u32 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(found) = leading_number(token, 2, 4) {
+                year = Some(found);
+            }
+        }
+    }
+    let (hour, minute, second) = time?;
+    let year = match year? {
+        year @ 70...99 => year + 1900,
+        year @ 0...69 => year + 2000,
+        year => year,
+    };
+    if year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let days = days_from_civil(i64::from(year), i64::from(month?), i64::from(day?))?;
+    Some(days * 86_400 + i64::from(hour * 3600 + minute * 60 + second))
+}
+
+/// Whether HOST is in DOMAIN, as section 5.1.3 of RFC 6265 says.
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    let is_address = host.parse::<Ipv4Addr>().is_ok() || host.contains(':');
+    !is_address && host.ends_with(domain)
+        && host[..host.len() - domain.len()].ends_with('.')
+}
+
+/// Whether a cookie for COOKIE_PATH is sent for REQUEST_PATH.
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Return the path of cookies set for a request for PATH that do not
+/// have a Path attribute: its directory, without the final slash.
+fn default_path(path: &str) -> String {
+    let path = path.split('?').next().unwrap_or("");
+    if !path.starts_with('/') {
+        return "/".to_string();
+    }
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(slash) => path[..slash].to_string(),
+    }
+}
+
+/// Parse HEADER, the value of a Set-Cookie header received from HOST
+/// for PATH, at NOW.  Return None if the header is to be ignored.  The
+/// returned cookie has no creation number.
+fn parse_set_cookie(header: &str, host: &str, path: &str, now: i64) -> Option<Cookie> {
+    let mut parts = header.split(';');
+    let pair = parts.next()?;
+    let equals = pair.find('=')?;
+    let name = pair[..equals].trim();
+    if name.is_empty() {
+        return None;
+    }
+    let host = host.to_lowercase();
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: pair[equals + 1..].trim().to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(path),
+        expires: None,
+        secure: false,
+        http_only: false,
+        creation: 0,
+    };
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = match attribute.find('=') {
+            Some(equals) => (&attribute[..equals], attribute[equals + 1..].trim()),
+            None => (attribute, ""),
+        };
+        match key.trim().to_lowercase().as_str() {
+            "expires" => {
+                if let Some(expires) = parse_cookie_date(value) {
+                    cookie.expires = Some(expires);
+                }
+            }
+            "max-age" => {
+                let valid = value.starts_with(|c: char| c == '-' || c.is_digit(10));
+                if let (true, Ok(delta)) = (valid, value.parse::<i64>()) {
+                    max_age = Some(if delta <= 0 {
+                        i64::min_value()
+                    } else {
+                        now.saturating_add(delta)
+                    });
+                }
+            }
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_left_matches('.').to_lowercase();
+                if !domain_match(&host, &domain) {
+                    return None;
+                }
+                cookie.host_only = domain == host;
+                cookie.domain = domain;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "path" => cookie.path = default_path(path),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            _ => (),
+        }
+    }
+    if max_age.is_some() {
+        cookie.expires = max_age;
+    }
+    Some(cookie)
+}
+
+/// The cookies, and the creation number of the next one.
+struct Jar {
+    cookies: Vec<Cookie>,
+    next: u64,
+}
+
+impl Jar {
+    /// Add COOKIE, replacing the cookie it updates.  A cookie that has
+    /// already expired only removes the one it replaces.
+    fn store(&mut self, mut cookie: Cookie, now: i64) {
+        let old = self.cookies.iter().position(|old| {
+            old.name == cookie.name && old.domain == cookie.domain && old.path == cookie.path
+        });
+        cookie.creation = match old {
+            Some(index) => self.cookies.remove(index).creation,
+            None => {
+                self.next += 1;
+                self.next
+            }
+        };
+        if !cookie.is_expired(now) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// Return the cookies to send to HOST for PATH, the most specific
+    /// first, dropping those that have expired.
+    fn matching(&mut self, host: &str, path: &str, secure: bool, now: i64) -> Vec<&Cookie> {
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+        let host = host.to_lowercase();
+        let path = path.split('?').next().unwrap_or("");
+        let path = if path.is_empty() { "/" } else { path };
+        let mut cookies: Vec<&Cookie> = self.cookies
+            .iter()
+            .filter(|cookie| cookie.matches(&host, path, secure))
+            .collect();
+        cookies.sort_by(|a, b| {
+            b.path
+                .len()
+                .cmp(&a.path.len())
+                .then(a.creation.cmp(&b.creation))
+        });
+        cookies
+    }
+}
+
+lazy_static! {
+    static ref JAR: Mutex<Jar> = Mutex::new(Jar {
+        cookies: Vec::new(),
+        next: 0,
+    });
+}
+
+fn rust_string(string: LispObject) -> String {
+    String::from_utf8_lossy(string.as_string_or_error().as_slice()).into_owned()
+}
+
+/// Store the cookie of HEADER, a Set-Cookie header, sent by HOST for PATH.
+/// HEADER is the value of the header, without "Set-Cookie:".  It is
+/// parsed as RFC 6265 says: attributes are case-insensitive, Max-Age
+/// takes precedence over Expires, whose date may be in any of the usual
+/// formats, and a cookie whose Domain does not contain HOST, or is a
+/// public suffix such as "co.uk", is ignored.  A cookie that has
+/// already expired deletes the one it replaces.  Return the name of the
+/// cookie, or nil if it was ignored.
+#[lisp_fn]
+pub fn url_cookie_native_store(header: LispObject, host: LispObject, path: LispObject) -> LispObject {
+    let now = now();
+    let cookie = match parse_set_cookie(&rust_string(header), &rust_string(host), &rust_string(path), now) {
+        Some(cookie) => cookie,
+        None => return LispObject::constant_nil(),
+    };
+    if !cookie.host_only {
+        let allowed = intern("url-domsuf-cookie-allowed-p");
+        if call!(intern("fboundp"), allowed).is_not_nil()
+            && call!(allowed, lisp_string(&cookie.domain)).is_nil()
+        {
+            return LispObject::constant_nil();
+        }
+    }
+    let name = lisp_string(&cookie.name);
+    JAR.lock().unwrap().store(cookie, now);
+    name
+}
+
+/// Return the value of a Cookie header for a request to HOST for PATH.
+/// Only cookies for secure connections are included if SECURE is nil.
+/// The cookies with the longest paths come first.  Return nil if there
+/// are no cookies to send.
+#[lisp_fn]
+pub fn url_cookie_native_header(host: LispObject, path: LispObject, secure: LispObject) -> LispObject {
+    let mut jar = JAR.lock().unwrap();
+    let pairs: Vec<String> = jar.matching(&rust_string(host), &rust_string(path), secure.is_not_nil(), now())
+        .iter()
+        .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+        .collect();
+    if pairs.is_empty() {
+        LispObject::constant_nil()
+    } else {
+        lisp_string(&pairs.join("; "))
+    }
+}
+
+fn from_time(time: Option<i64>) -> LispObject {
+    time.map_or(LispObject::constant_nil(), |time| {
+        LispObject::from_fixnum(time as EmacsInt)
+    })
+}
+
+/// Return the cookies in the jar, as a list of plists.
+/// Each plist has the properties `:name', `:value', `:domain', `:path',
+/// `:expires', the time in seconds since the epoch or nil for a session
+/// cookie, `:secure', `:http-only' and `:host-only'.  If DOMAIN is
+/// non-nil, only return the cookies that would be sent to it.
+#[lisp_fn(min = "0")]
+pub fn url_cookie_native_list(domain: LispObject) -> LispObject {
+    let domain = if domain.is_nil() {
+        None
+    } else {
+        Some(rust_string(domain).to_lowercase())
+    };
+    let now = now();
+    let cookies: Vec<Cookie> = {
+        let mut jar = JAR.lock().unwrap();
+        jar.cookies.retain(|cookie| !cookie.is_expired(now));
+        jar.cookies
+            .iter()
+            .filter(|cookie| {
+                domain.as_ref().map_or(true, |domain| {
+                    cookie.matches(domain, &cookie.path, true)
+                })
+            })
+            .cloned()
+            .collect()
+    };
+    let mut list = LispObject::constant_nil();
+    for cookie in cookies.iter().rev() {
+        let plist = list!(
+            intern(":name"),
+            lisp_string(&cookie.name),
+            intern(":value"),
+            lisp_string(&cookie.value),
+            intern(":domain"),
+            lisp_string(&cookie.domain),
+            intern(":path"),
+            lisp_string(&cookie.path),
+            intern(":expires"),
+            from_time(cookie.expires),
+            intern(":secure"),
+            LispObject::from_bool(cookie.secure),
+            intern(":http-only"),
+            LispObject::from_bool(cookie.http_only),
+            intern(":host-only"),
+            LispObject::from_bool(cookie.host_only)
+        );
+        list = LispObject::cons(plist, list);
+    }
+    list
+}
+
+/// Delete the cookies whose domain is DOMAIN, or all cookies if DOMAIN is nil.
+/// If NAME is non-nil, only delete the cookies with that name.  Return
+/// the number of cookies deleted.
+#[lisp_fn(min = "0")]
+pub fn url_cookie_native_delete(domain: LispObject, name: LispObject) -> LispObject {
+    let domain = if domain.is_nil() {
+        None
+    } else {
+        Some(rust_string(domain).trim_left_matches('.').to_lowercase())
+    };
+    let name = if name.is_nil() {
+        None
+    } else {
+        Some(rust_string(name))
+    };
+    let mut jar = JAR.lock().unwrap();
+    let before = jar.cookies.len();
+    jar.cookies.retain(|cookie| {
+        !(domain.as_ref().map_or(true, |domain| &cookie.domain == domain)
+            && name.as_ref().map_or(true, |name| &cookie.name == name))
+    });
+    LispObject::from_natnum((before - jar.cookies.len()) as EmacsInt)
+}
+
+/// Save the cookies that outlast the session to FILE.
+/// The file is written with `lisp-data-write'.
+#[lisp_fn]
+pub fn url_cookie_native_save(file: LispObject) -> LispObject {
+    let now = now();
+    let cookies: Vec<Cookie> = JAR.lock()
+        .unwrap()
+        .cookies
+        .iter()
+        .filter(|cookie| cookie.expires.is_some() && !cookie.is_expired(now))
+        .cloned()
+        .collect();
+    let mut list = LispObject::constant_nil();
+    for cookie in cookies.iter().rev() {
+        let saved = call!(
+            intern("vector"),
+            lisp_string(&cookie.name),
+            lisp_string(&cookie.value),
+            lisp_string(&cookie.domain),
+            LispObject::from_bool(cookie.host_only),
+            lisp_string(&cookie.path),
+            from_time(cookie.expires),
+            LispObject::from_bool(cookie.secure),
+            LispObject::from_bool(cookie.http_only)
+        );
+        list = LispObject::cons(saved, list);
+    }
+    lisp_data_write(file, list)
+}
+
+/// Load the cookies saved in FILE by `url-cookie-native-save'.
+/// They replace the cookies of the jar that have the same name, domain
+/// and path.  Cookies that have expired since are dropped.  Return the
+/// number of cookies loaded.
+#[lisp_fn]
+pub fn url_cookie_native_load(file: LispObject) -> LispObject {
+    let saved = lisp_data_read(file);
+    let now = now();
+    let mut cookies = Vec::new();
+    for entry in saved.iter_cars() {
+        let field = |index: EmacsInt| call!(intern("aref"), entry, LispObject::from_natnum(index));
+        cookies.push(Cookie {
+            name: rust_string(field(0)),
+            value: rust_string(field(1)),
+            domain: rust_string(field(2)),
+            host_only: field(3).is_not_nil(),
+            path: rust_string(field(4)),
+            expires: field(5).as_fixnum().map(|time| time as i64),
+            secure: field(6).is_not_nil(),
+            http_only: field(7).is_not_nil(),
+            creation: 0,
+        });
+    }
+    let mut jar = JAR.lock().unwrap();
+    let mut loaded = 0;
+    for cookie in cookies {
+        if !cookie.is_expired(now) {
+            jar.store(cookie, now);
+            loaded += 1;
+        }
+    }
+    LispObject::from_natnum(loaded)
+}
+
+#[test]
+fn test_parse_cookie_date() {
+    // 1994-11-06 08:49:37 UTC.
+    let expected = Some(784111777);
+    assert_eq!(parse_cookie_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+    assert_eq!(parse_cookie_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+    assert_eq!(parse_cookie_date("Sun Nov  6 08:49:37 1994"), expected);
+    assert_eq!(parse_cookie_date("6 november 1994 8:49:37"), expected);
+    assert_eq!(parse_cookie_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+    assert_eq!(parse_cookie_date("Wed, 30 Dec 2037 16:00:00 GMT"), Some(2145801600));
+    assert_eq!(parse_cookie_date("Sun, 31 Feb 1994 08:49:37 GMT"), None);
+    assert_eq!(parse_cookie_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+    assert_eq!(parse_cookie_date("06 Nov 1994"), None);
+    assert_eq!(parse_cookie_date(""), None);
+}
+
+#[test]
+fn test_domain_and_path_match() {
+    assert!(domain_match("example.com", "example.com"));
+    assert!(domain_match("www.example.com", "example.com"));
+    assert!(!domain_match("badexample.com", "example.com"));
+    assert!(!domain_match("example.com", "www.example.com"));
+    assert!(!domain_match("1.2.3.4", "2.3.4"));
+    assert!(path_match("/", "/"));
+    assert!(path_match("/a/b", "/a"));
+    assert!(path_match("/a/b", "/a/"));
+    assert!(!path_match("/ab", "/a"));
+    assert!(!path_match("/", "/a"));
+    assert_eq!(default_path("/a/b/c.html?x=/y"), "/a/b");
+    assert_eq!(default_path("/index.html"), "/");
+    assert_eq!(default_path(""), "/");
+}
+
+#[test]
+fn test_parse_set_cookie() {
+    let now = 1_000_000;
+    let cookie = parse_set_cookie(
+        " id = a3fWa ; Max-Age=60; expires=Thu, 01 Jan 1970 00:00:00 GMT; \
+         Domain=.Example.com; Path=/docs; Secure; HttpOnly",
+        "www.example.com",
+        "/index.html",
+        now,
+    ).unwrap();
+    assert_eq!(cookie.name, "id");
+    assert_eq!(cookie.value, "a3fWa");
+    assert_eq!(cookie.domain, "example.com");
+    assert!(!cookie.host_only);
+    assert_eq!(cookie.path, "/docs");
+    assert_eq!(cookie.expires, Some(now + 60));
+    assert!(cookie.secure && cookie.http_only);
+
+    let cookie = parse_set_cookie("lang=en", "Example.com", "/a/b", now).unwrap();
+    assert_eq!(cookie.domain, "example.com");
+    assert!(cookie.host_only);
+    assert_eq!(cookie.path, "/a");
+    assert_eq!(cookie.expires, None);
+
+    assert!(parse_set_cookie("novalue", "example.com", "/", now).is_none());
+    assert!(parse_set_cookie("=x", "example.com", "/", now).is_none());
+    assert!(parse_set_cookie("a=b; Domain=other.com", "example.com", "/", now).is_none());
+    let gone = parse_set_cookie("a=b; Max-Age=0", "example.com", "/", now).unwrap();
+    assert!(gone.is_expired(now));
+}
+
+#[test]
+fn test_jar() {
+    let now = 1000;
+    let mut jar = Jar {
+        cookies: Vec::new(),
+        next: 0,
+    };
+    let set = |header: &str| parse_set_cookie(header, "www.example.com", "/", now).unwrap();
+    jar.store(set("a=1; Path=/"), now);
+    jar.store(set("b=2; Path=/docs"), now);
+    jar.store(set("c=3; Domain=example.com; Secure"), now);
+    jar.store(set("d=4; Max-Age=10"), now);
+    jar.store(set("a=5; Path=/"), now);
+    let names = |jar: &mut Jar, host: &str, path: &str, secure: bool, now: i64| {
+        jar.matching(host, path, secure, now)
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(&mut jar, "www.example.com", "/docs/x", true, now),
+        vec!["b=2", "a=5", "c=3", "d=4"]
+    );
+    assert_eq!(names(&mut jar, "www.example.com", "/", false, now), vec!["a=5", "d=4"]);
+    assert_eq!(names(&mut jar, "api.example.com", "/", true, now), vec!["c=3"]);
+    assert_eq!(names(&mut jar, "www.example.com", "/", false, now + 20), vec!["a=5"]);
+    jar.store(set("a=gone; Max-Age=0"), now);
+    assert!(names(&mut jar, "www.example.com", "/", false, now).is_empty());
+}
+
+include!(concat!(env!("OUT_DIR"), "/cookies_exports.rs"));
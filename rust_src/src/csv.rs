@@ -0,0 +1,199 @@
+//! Parsing comma and tab separated values.
+//!
+//! The syntax is that of RFC 4180: records end with LF or CRLF, fields
+//! are separated by a single character, and a field in double quotes may
+//! contain separators, newlines and quotes doubled as `""'.  A quote in
+//! the middle of an unquoted field is taken literally, as is any text
+//! between the closing quote of a field and the next separator.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_specified_string, EmacsInt, Fbuffer_substring, Fnreverse};
+
+use hashtable::puthash;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+
+type Record = Vec<Vec<u8>>;
+
+/// Split TEXT into records of fields separated by SEPARATOR.  Empty
+/// lines are skipped.  On failure, return the offset of a quoted field
+/// that isn't closed.
+fn parse_records(text: &[u8], separator: u8) -> Result<Vec<Record>, usize> {
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let line_start = pos;
+        let mut record = Vec::new();
+        loop {
+            let mut field = Vec::new();
+            if text.get(pos) == Some(&b'"') {
+                let open = pos;
+                pos += 1;
+                loop {
+                    match text[pos..].iter().position(|&b| b == b'"') {
+                        None => return Err(open),
+                        Some(quote) => {
+                            field.extend_from_slice(&text[pos..pos + quote]);
+                            pos += quote + 1;
+                            if text.get(pos) == Some(&b'"') {
+                                field.push(b'"');
+                                pos += 1;
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            let start = pos;
+            while pos < text.len() && text[pos] != separator && text[pos] != b'\n' {
+                pos += 1;
+            }
+            let mut end = pos;
+            if pos < text.len() && text[pos] == b'\n' && end > start && text[end - 1] == b'\r' {
+                end -= 1;
+            }
+            field.extend_from_slice(&text[start..end]);
+            record.push(field);
+            if pos < text.len() && text[pos] == separator {
+                pos += 1;
+            } else {
+                break;
+            }
+        }
+        // Skip the newline ending the record.
+        pos += 1;
+        let line = &text[line_start..pos.min(text.len())];
+        if line != b"\n" && line != b"\r\n" {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+fn separator_byte(separator: LispObject) -> u8 {
+    if separator.is_nil() {
+        return b',';
+    }
+    let c = separator.as_character_or_error();
+    if c >= 0x80 || c == u32::from(b'"') || c == u32::from(b'\n') || c == u32::from(b'\r') {
+        error!("Invalid CSV separator");
+    }
+    c as u8
+}
+
+fn make_field(field: &[u8], multibyte: bool) -> LispObject {
+    LispObject::from(unsafe {
+        make_specified_string(
+            field.as_ptr() as *const c_char,
+            -1,
+            field.len() as ptrdiff_t,
+            multibyte,
+        )
+    })
+}
+
+fn make_record(record: &Record, multibyte: bool) -> LispObject {
+    let vector = call!(
+        intern("make-vector"),
+        LispObject::from_natnum(record.len() as EmacsInt),
+        LispObject::constant_nil()
+    );
+    for (i, field) in record.iter().enumerate() {
+        let field = make_field(field, multibyte);
+        let slots = vector.as_vectorlike().and_then(|v| v.as_vector()).unwrap();
+        slots.as_mut_slice()[i] = field;
+    }
+    vector
+}
+
+/// Parse STRING into a list of records as described for
+/// `csv-parse-string'.
+fn parse(string: LispObject, separator: LispObject, header: LispObject) -> LispObject {
+    let separator = separator_byte(separator);
+    let string = string.as_string_or_error();
+    let multibyte = string.is_multibyte();
+    let records = match parse_records(string.as_slice(), separator) {
+        Ok(records) => records,
+        Err(offset) => error!("Unterminated quoted field at byte {}", offset),
+    };
+
+    let mut result = LispObject::constant_nil();
+    if header.is_nil() {
+        for record in &records {
+            result = LispObject::cons(make_record(record, multibyte), result);
+        }
+    } else if let Some((names, rows)) = records.split_first() {
+        let names = make_record(names, multibyte);
+        let names = names.as_vectorlike().and_then(|v| v.as_vector()).unwrap();
+        for row in rows {
+            let table = call!(
+                intern("make-hash-table"),
+                intern(":test"),
+                intern("equal"),
+                intern(":size"),
+                LispObject::from_natnum(names.len() as EmacsInt)
+            );
+            result = LispObject::cons(table, result);
+            for (&name, field) in names.as_slice().iter().zip(row) {
+                puthash(name, make_field(field, multibyte), table);
+            }
+        }
+    }
+    LispObject::from(unsafe { Fnreverse(result.to_raw()) })
+}
+
+/// Parse STRING as comma separated values and return a list of records.
+/// Fields may be quoted with double quotes as described in RFC 4180;
+/// empty lines are skipped.  SEPARATOR is the ASCII character separating
+/// fields, `?,' if nil; use `?\t' for tab separated values.
+///
+/// Each record is a vector of strings, unless HEADER is non-nil.  Then
+/// the first record names the fields, and each following record is a
+/// hash table with test `equal' mapping those names to the strings in
+/// the record.  Fields beyond the header are dropped, and fields missing
+/// from a short record are absent from its table.
+#[lisp_fn(min = "1")]
+pub fn csv_parse_string(
+    string: LispObject,
+    separator: LispObject,
+    header: LispObject,
+) -> LispObject {
+    parse(string, separator, header)
+}
+
+/// Parse the text between BEG and END in the current buffer as comma
+/// separated values.  SEPARATOR and HEADER are as for `csv-parse-string'.
+#[lisp_fn(min = "2")]
+pub fn csv_parse_region(
+    beg: LispObject,
+    end: LispObject,
+    separator: LispObject,
+    header: LispObject,
+) -> LispObject {
+    let text = LispObject::from(unsafe { Fbuffer_substring(beg.to_raw(), end.to_raw()) });
+    parse(text, separator, header)
+}
+
+#[test]
+fn test_parse_records() {
+    let text = b"a,b,c\r\n\"x,\"\"y\"\"\",,\"multi\nline\"\n\nlast";
+    let records = parse_records(text, b',').unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0], vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+    assert_eq!(
+        records[1],
+        vec![b"x,\"y\"".to_vec(), Vec::new(), b"multi\nline".to_vec()]
+    );
+    assert_eq!(records[2], vec![b"last".to_vec()]);
+
+    let records = parse_records(b"a\tb\t\n\"q\"r\t\"\"\n", b'\t').unwrap();
+    assert_eq!(records[0], vec![b"a".to_vec(), b"b".to_vec(), Vec::new()]);
+    assert_eq!(records[1], vec![b"qr".to_vec(), Vec::new()]);
+
+    assert_eq!(parse_records(b"a,\"open", b','), Err(2));
+}
+
+include!(concat!(env!("OUT_DIR"), "/csv_exports.rs"));
@@ -1,7 +1,7 @@
 //! data helpers
 
 use remacs_macros::lisp_fn;
-use remacs_sys::{Lisp_Misc_Type, Lisp_Object, Lisp_Type, PseudovecType};
+use remacs_sys::{Lisp_Misc_Type, Lisp_Object, Lisp_Subr, Lisp_Type, PseudovecType};
 use remacs_sys::{Qbool_vector, Qbuffer, Qchar_table, Qcompiled_function, Qcondition_variable,
                  Qcons, Qcyclic_function_indirection, Qfinalizer, Qfloat, Qfont, Qfont_entity,
                  Qfont_object, Qfont_spec, Qframe, Qhash_table, Qinteger, Qmarker,
@@ -64,6 +64,32 @@ pub fn indirect_function_lisp(object: LispObject, _noerror: LispObject) -> LispO
     return result;
 }
 
+lazy_static! {
+    /// The addresses of the primitives defined in Rust, sorted, so that
+    /// frames of a backtrace can be flagged without a scan of them all.
+    static ref RUST_SUBRS: Vec<usize> = {
+        let mut subrs: Vec<usize> = ::rust_subrs().into_iter().map(|subr| subr as usize).collect();
+        subrs.sort();
+        subrs
+    };
+}
+
+fn is_rust_subr(subr: *const Lisp_Subr) -> bool {
+    RUST_SUBRS.binary_search(&(subr as usize)).is_ok()
+}
+
+/// Return t if OBJECT is a built-in function implemented in Rust.
+/// Return nil for primitives still implemented in C, and for anything
+/// that is not a primitive.
+#[lisp_fn]
+pub fn subr_rust_p(object: LispObject) -> LispObject {
+    LispObject::from_bool(
+        object
+            .as_subr()
+            .map_or(false, |subr| is_rust_subr(subr.as_ptr())),
+    )
+}
+
 /// Return a symbol representing the type of OBJECT.
 /// The symbol returned names the object's basic type;
 /// for example, (type-of 1) returns `integer'.
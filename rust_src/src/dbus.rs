@@ -0,0 +1,1645 @@
+//! D-Bus client.
+//!
+//! Emacs speaks the D-Bus wire protocol itself, over Unix domain
+//! sockets: it authenticates with the EXTERNAL mechanism, marshals the
+//! arguments of `dbus-message-internal' by the type conversion rules of
+//! dbus.el, and turns incoming messages into `dbus-event's.  Nothing
+//! here blocks once the connection is up, and nothing runs outside the
+//! main thread: replies to method calls come back through the event
+//! queue like any other message, and the synchronous
+//! `dbus-call-method' of dbus.el waits for them there.  Rust code that
+//! needs an answer itself waits in short slices, with the connections
+//! unlocked and `quit-flag' checked in between.
+
+use std::cmp;
+use std::env;
+use std::io;
+use std::io::{Read, Write};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::net::UnixStream;
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use libc;
+use libc::{c_int, c_void};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{add_read_fd, delete_read_fd, kbd_buffer_store_dbus_event, maybe_quit,
+                 EmacsInt, MOST_POSITIVE_FIXNUM};
+
+use hashtable::{gethash, puthash, remhash};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{car_safe, cdr_safe};
+use strings::lisp_string;
+use symbols::symbol_value;
+
+// Message types.
+pub const METHOD_CALL: u8 = 1;
+pub const METHOD_RETURN: u8 = 2;
+pub const ERROR: u8 = 3;
+pub const SIGNAL: u8 = 4;
+
+/// Message flag telling the receiver not to send a reply.
+const NO_REPLY_EXPECTED: u8 = 1;
+
+// Type codes.  Structs and dict entries have no code of their own in
+// signatures, where they are written in parentheses and braces; `r'
+// and `e' are the codes libdbus uses for them.
+pub const BYTE: u8 = b'y';
+const BOOLEAN: u8 = b'b';
+const INT16: u8 = b'n';
+const UINT16: u8 = b'q';
+const INT32: u8 = b'i';
+const UINT32: u8 = b'u';
+const INT64: u8 = b'x';
+const UINT64: u8 = b't';
+const DOUBLE: u8 = b'd';
+pub const STRING: u8 = b's';
+pub const OBJECT_PATH: u8 = b'o';
+const SIGNATURE: u8 = b'g';
+const UNIX_FD: u8 = b'h';
+const ARRAY: u8 = b'a';
+const VARIANT: u8 = b'v';
+const STRUCT: u8 = b'r';
+const DICT_ENTRY: u8 = b'e';
+
+/// The largest message accepted from the bus, as in the specification.
+const MAX_MESSAGE_LENGTH: usize = 1 << 27;
+
+const DBUS_SERVICE: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+
+/// How long to wait for the reply to a method call, in seconds.
+const CALL_TIMEOUT: u64 = 25;
+/// How long to wait for the bus in one go, in milliseconds, before
+/// checking for a quit.
+const POLL_SLICE: u64 = 100;
+
+/// A value in a D-Bus message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(u8),
+    Boolean(bool),
+    Int16(i16),
+    Uint16(u16),
+    Int32(i32),
+    Uint32(u32),
+    Int64(i64),
+    Uint64(u64),
+    Double(f64),
+    UnixFd(u32),
+    /// A string, object path or signature, after its type code.
+    Str(u8, String),
+    /// An array, after the signature of its elements.
+    Array(String, Vec<Value>),
+    Variant(Box<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+}
+
+impl Value {
+    pub fn signature(&self) -> String {
+        match *self {
+            Value::Byte(_) => "y".to_string(),
+            Value::Boolean(_) => "b".to_string(),
+            Value::Int16(_) => "n".to_string(),
+            Value::Uint16(_) => "q".to_string(),
+            Value::Int32(_) => "i".to_string(),
+            Value::Uint32(_) => "u".to_string(),
+            Value::Int64(_) => "x".to_string(),
+            Value::Uint64(_) => "t".to_string(),
+            Value::Double(_) => "d".to_string(),
+            Value::UnixFd(_) => "h".to_string(),
+            Value::Str(code, _) => (code as char).to_string(),
+            Value::Array(ref element, _) => format!("a{}", element),
+            Value::Variant(_) => "v".to_string(),
+            Value::Struct(ref values) => format!(
+                "({})",
+                values.iter().map(|v| v.signature()).collect::<String>()
+            ),
+            Value::DictEntry(ref key, ref value) => {
+                format!("{{{}{}}}", key.signature(), value.signature())
+            }
+        }
+    }
+}
+
+/// Return the alignment of the type whose signature starts with CODE.
+fn alignment(code: u8) -> usize {
+    match code {
+        b'y' | b'g' | b'v' => 1,
+        b'n' | b'q' => 2,
+        b'b' | b'i' | b'u' | b'h' | b's' | b'o' | b'a' => 4,
+        _ => 8,
+    }
+}
+
+fn is_basic(code: u8) -> bool {
+    b"ybnqiuxtdsogh".contains(&code)
+}
+
+/// Return the length of the complete type at the start of SIGNATURE.
+fn type_len(signature: &[u8]) -> Result<usize, ()> {
+    match signature.first() {
+        Some(&b'a') => Ok(1 + type_len(&signature[1..])?),
+        Some(&open) if open == b'(' || open == b'{' => {
+            let close = if open == b'(' { b')' } else { b'}' };
+            let mut i = 1;
+            while signature.get(i) != Some(&close) {
+                i += type_len(&signature[i..])?;
+            }
+            Ok(i + 1)
+        }
+        Some(&code) if is_basic(code) || code == b'v' => Ok(1),
+        _ => Err(()),
+    }
+}
+
+/// Split SIGNATURE into its complete types.
+fn types(signature: &[u8]) -> Result<Vec<&[u8]>, ()> {
+    let mut result = Vec::new();
+    let mut rest = signature;
+    while !rest.is_empty() {
+        let len = type_len(rest)?;
+        result.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    Ok(result)
+}
+
+/// Marshals values in little-endian byte order.
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn new() -> Writer {
+        Writer { buf: Vec::new() }
+    }
+
+    fn align(&mut self, n: usize) {
+        while self.buf.len() % n != 0 {
+            self.buf.push(0);
+        }
+    }
+
+    fn uint(&mut self, n: u64, size: usize) {
+        self.align(size);
+        for i in 0..size {
+            self.buf.push((n >> (8 * i)) as u8);
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.uint(s.len() as u64, 4);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn signature(&mut self, s: &str) {
+        self.buf.push(s.len() as u8);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn value(&mut self, value: &Value) {
+        match *value {
+            Value::Byte(b) => self.buf.push(b),
+            Value::Boolean(b) => self.uint(b as u64, 4),
+            Value::Int16(n) => self.uint(n as u16 as u64, 2),
+            Value::Uint16(n) => self.uint(n as u64, 2),
+            Value::Int32(n) => self.uint(n as u32 as u64, 4),
+            Value::Uint32(n) | Value::UnixFd(n) => self.uint(n as u64, 4),
+            Value::Int64(n) => self.uint(n as u64, 8),
+            Value::Uint64(n) => self.uint(n, 8),
+            Value::Double(d) => self.uint(d.to_bits(), 8),
+            Value::Str(SIGNATURE, ref s) => self.signature(s),
+            Value::Str(_, ref s) => self.string(s),
+            Value::Array(ref element, ref values) => {
+                self.uint(0, 4);
+                let len_at = self.buf.len() - 4;
+                self.align(alignment(element.as_bytes()[0]));
+                let start = self.buf.len();
+                for v in values {
+                    self.value(v);
+                }
+                let len = self.buf.len() - start;
+                for i in 0..4 {
+                    self.buf[len_at + i] = (len >> (8 * i)) as u8;
+                }
+            }
+            Value::Variant(ref v) => {
+                self.signature(&v.signature());
+                self.value(v);
+            }
+            Value::Struct(ref values) => {
+                self.align(8);
+                for v in values {
+                    self.value(v);
+                }
+            }
+            Value::DictEntry(ref key, ref value) => {
+                self.align(8);
+                self.value(key);
+                self.value(value);
+            }
+        }
+    }
+}
+
+/// Unmarshals values from a whole message, so that alignment is
+/// relative to its start.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn align(&mut self, n: usize) -> Result<(), ()> {
+        let pos = (self.pos + n - 1) / n * n;
+        if pos > self.buf.len() {
+            return Err(());
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], ()> {
+        if n > self.buf.len() - self.pos {
+            return Err(());
+        }
+        let bytes = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u64, ()> {
+        self.align(size)?;
+        let big_endian = self.big_endian;
+        let bytes = self.bytes(size)?;
+        Ok(bytes.iter().enumerate().fold(0, |acc, (i, &b)| {
+            let shift = if big_endian { size - 1 - i } else { i };
+            acc | (u64::from(b) << (8 * shift))
+        }))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, ()> {
+        let s = self.bytes(len)?;
+        self.bytes(1)?;
+        Ok(String::from_utf8_lossy(s).into_owned())
+    }
+
+    /// Read a value of the complete type SIGNATURE.
+    fn value(&mut self, signature: &[u8]) -> Result<Value, ()> {
+        Ok(match signature[0] {
+            BYTE => Value::Byte(self.bytes(1)?[0]),
+            BOOLEAN => Value::Boolean(self.uint(4)? != 0),
+            INT16 => Value::Int16(self.uint(2)? as u16 as i16),
+            UINT16 => Value::Uint16(self.uint(2)? as u16),
+            INT32 => Value::Int32(self.uint(4)? as u32 as i32),
+            UINT32 => Value::Uint32(self.uint(4)? as u32),
+            UNIX_FD => Value::UnixFd(self.uint(4)? as u32),
+            INT64 => Value::Int64(self.uint(8)? as i64),
+            UINT64 => Value::Uint64(self.uint(8)?),
+            DOUBLE => Value::Double(f64::from_bits(self.uint(8)?)),
+            STRING | OBJECT_PATH => {
+                let len = self.uint(4)? as usize;
+                Value::Str(signature[0], self.string(len)?)
+            }
+            SIGNATURE => {
+                let len = self.bytes(1)?[0] as usize;
+                Value::Str(SIGNATURE, self.string(len)?)
+            }
+            VARIANT => {
+                let len = self.bytes(1)?[0] as usize;
+                let inner = self.string(len)?;
+                if type_len(inner.as_bytes())? != inner.len() {
+                    return Err(());
+                }
+                Value::Variant(Box::new(self.value(inner.as_bytes())?))
+            }
+            ARRAY => {
+                let element = &signature[1..1 + type_len(&signature[1..])?];
+                let len = self.uint(4)? as usize;
+                self.align(alignment(element[0]))?;
+                if len > self.buf.len() - self.pos {
+                    return Err(());
+                }
+                let end = self.pos + len;
+                let mut values = Vec::new();
+                while self.pos < end {
+                    values.push(self.value(element)?);
+                }
+                if self.pos != end {
+                    return Err(());
+                }
+                Value::Array(String::from_utf8_lossy(element).into_owned(), values)
+            }
+            b'(' => {
+                self.align(8)?;
+                let mut values = Vec::new();
+                for t in types(&signature[1..signature.len() - 1])? {
+                    values.push(self.value(t)?);
+                }
+                Value::Struct(values)
+            }
+            b'{' => {
+                self.align(8)?;
+                let inner = types(&signature[1..signature.len() - 1])?;
+                if inner.len() != 2 {
+                    return Err(());
+                }
+                let key = self.value(inner[0])?;
+                Value::DictEntry(Box::new(key), Box::new(self.value(inner[1])?))
+            }
+            _ => return Err(()),
+        })
+    }
+}
+
+/// A D-Bus message.
+#[derive(Debug, Default, PartialEq)]
+pub struct Message {
+    pub mtype: u8,
+    pub flags: u8,
+    pub serial: u32,
+    pub reply_serial: Option<u32>,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub destination: Option<String>,
+    pub sender: Option<String>,
+    pub body: Vec<Value>,
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Writer::new();
+        for v in &self.body {
+            body.value(v);
+        }
+        let signature: String = self.body.iter().map(|v| v.signature()).collect();
+
+        let mut fields = Vec::new();
+        {
+            let mut field = |code: u8, value: Value| {
+                fields.push(Value::Struct(vec![
+                    Value::Byte(code),
+                    Value::Variant(Box::new(value)),
+                ]))
+            };
+            let strings = [
+                (1, OBJECT_PATH, &self.path),
+                (2, STRING, &self.interface),
+                (3, STRING, &self.member),
+                (4, STRING, &self.error_name),
+                (6, STRING, &self.destination),
+            ];
+            for &(code, dtype, value) in &strings {
+                if let Some(ref s) = *value {
+                    field(code, Value::Str(dtype, s.clone()));
+                }
+            }
+            if let Some(serial) = self.reply_serial {
+                field(5, Value::Uint32(serial));
+            }
+            if !signature.is_empty() {
+                field(8, Value::Str(SIGNATURE, signature));
+            }
+        }
+
+        let mut message = Writer::new();
+        message.buf.extend_from_slice(&[b'l', self.mtype, self.flags, 1]);
+        message.uint(body.buf.len() as u64, 4);
+        message.uint(u64::from(self.serial), 4);
+        message.value(&Value::Array("(yv)".to_string(), fields));
+        message.align(8);
+        message.buf.extend_from_slice(&body.buf);
+        message.buf
+    }
+
+    /// Decode the message at the start of BUF.  Return the message and
+    /// its length, or None if BUF doesn't hold all of it yet.
+    fn decode(buf: &[u8]) -> Result<Option<(Message, usize)>, ()> {
+        if buf.len() < 16 {
+            return Ok(None);
+        }
+        let big_endian = match buf[0] {
+            b'l' => false,
+            b'B' => true,
+            _ => return Err(()),
+        };
+        let mut reader = Reader {
+            buf,
+            pos: 4,
+            big_endian,
+        };
+        let body_len = reader.uint(4)? as usize;
+        let serial = reader.uint(4)? as u32;
+        let fields_len = reader.uint(4)? as usize;
+        let header_len = (16 + fields_len + 7) / 8 * 8;
+        if header_len + body_len > MAX_MESSAGE_LENGTH {
+            return Err(());
+        }
+        if buf.len() < header_len + body_len {
+            return Ok(None);
+        }
+
+        let mut message = Message {
+            mtype: buf[1],
+            flags: buf[2],
+            serial,
+            ..Default::default()
+        };
+        let mut reader = Reader {
+            buf: &buf[..header_len + body_len],
+            pos: 12,
+            big_endian,
+        };
+        let mut signature = String::new();
+        if let Value::Array(_, fields) = reader.value(b"a(yv)")? {
+            for field in fields {
+                let (code, value) = match field {
+                    Value::Struct(mut v) => match (v.pop(), v.pop()) {
+                        (Some(Value::Variant(value)), Some(Value::Byte(code))) => (code, *value),
+                        _ => return Err(()),
+                    },
+                    _ => return Err(()),
+                };
+                match (code, value) {
+                    (1, Value::Str(_, s)) => message.path = Some(s),
+                    (2, Value::Str(_, s)) => message.interface = Some(s),
+                    (3, Value::Str(_, s)) => message.member = Some(s),
+                    (4, Value::Str(_, s)) => message.error_name = Some(s),
+                    (5, Value::Uint32(n)) => message.reply_serial = Some(n),
+                    (6, Value::Str(_, s)) => message.destination = Some(s),
+                    (7, Value::Str(_, s)) => message.sender = Some(s),
+                    (8, Value::Str(_, s)) => signature = s,
+                    _ => (),
+                }
+            }
+        }
+        reader.pos = header_len;
+        for t in types(signature.as_bytes())? {
+            message.body.push(reader.value(t)?);
+        }
+        Ok(Some((message, header_len + body_len)))
+    }
+}
+
+/// A bus, as `dbus-message-internal' and friends name it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Bus {
+    System,
+    Session,
+    Address(String),
+}
+
+/// A socket address taken from a D-Bus server address.
+#[derive(Debug, PartialEq)]
+enum Endpoint {
+    Path(Vec<u8>),
+    Abstract(Vec<u8>),
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Undo the %-escapes in a value of a D-Bus address.
+fn unescape(value: &str) -> io::Result<Vec<u8>> {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            match value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(b) => result.push(b),
+                None => return Err(invalid_input(format!("Invalid escape in \"{}\"", value))),
+            }
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(result)
+}
+
+/// Return the endpoints of ADDRESS, a D-Bus server address, that this
+/// client can connect to, in order.
+fn parse_address(address: &str) -> io::Result<Vec<Endpoint>> {
+    let mut endpoints = Vec::new();
+    for entry in address.split(';').filter(|e| !e.is_empty()) {
+        let colon = match entry.find(':') {
+            Some(i) => i,
+            None => return Err(invalid_input(format!("Invalid D-Bus address \"{}\"", entry))),
+        };
+        if &entry[..colon] != "unix" {
+            continue;
+        }
+        for pair in entry[colon + 1..].split(',') {
+            let mut kv = pair.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some("path"), Some(value)) => endpoints.push(Endpoint::Path(unescape(value)?)),
+                (Some("abstract"), Some(value)) => {
+                    endpoints.push(Endpoint::Abstract(unescape(value)?))
+                }
+                _ => (),
+            }
+        }
+    }
+    if endpoints.is_empty() {
+        return Err(invalid_input(format!(
+            "No supported transport in D-Bus address \"{}\"",
+            address
+        )));
+    }
+    Ok(endpoints)
+}
+
+fn bus_address(bus: &Bus) -> String {
+    match *bus {
+        Bus::System => env::var("DBUS_SYSTEM_BUS_ADDRESS")
+            .unwrap_or_else(|_| "unix:path=/var/run/dbus/system_bus_socket".to_string()),
+        Bus::Session => env::var("DBUS_SESSION_BUS_ADDRESS").unwrap_or_default(),
+        Bus::Address(ref address) => address.clone(),
+    }
+}
+
+fn connect(endpoint: &Endpoint) -> io::Result<UnixStream> {
+    let (name, start) = match *endpoint {
+        Endpoint::Path(ref name) => (name, 0),
+        // Abstract socket names start with a null byte.
+        Endpoint::Abstract(ref name) => (name, 1),
+    };
+    unsafe {
+        let mut addr: libc::sockaddr_un = mem::zeroed();
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        if start + name.len() >= addr.sun_path.len() {
+            return Err(invalid_input("Socket name too long".to_string()));
+        }
+        for (i, &b) in name.iter().enumerate() {
+            addr.sun_path[start + i] = b as libc::c_char;
+        }
+        let offset = addr.sun_path.as_ptr() as usize - &addr as *const _ as usize;
+        // Paths include their terminating null byte, abstract names don't.
+        let len = offset + start + name.len() + (1 - start);
+
+        let fd = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let stream = UnixStream::from_raw_fd(fd);
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        let addr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+        if libc::connect(fd, addr, len as libc::socklen_t) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stream)
+    }
+}
+
+/// Read one line of the authentication conversation.
+fn read_line(stream: &mut UnixStream) -> io::Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0; 1];
+    while !line.ends_with(b"\r\n") {
+        if stream.read(&mut byte)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed during authentication",
+            ));
+        }
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_right().to_string())
+}
+
+fn authenticate(stream: &mut UnixStream) -> io::Result<()> {
+    let uid = unsafe { libc::getuid() }.to_string();
+    let hex: String = uid.bytes().map(|b| format!("{:02x}", b)).collect();
+    stream.write_all(format!("\0AUTH EXTERNAL {}\r\n", hex).as_bytes())?;
+    let reply = read_line(stream)?;
+    if !reply.starts_with("OK ") {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Authentication failed: {}", reply),
+        ));
+    }
+    stream.write_all(b"BEGIN\r\n")
+}
+
+/// A connection to a bus.
+struct Connection {
+    bus: Bus,
+    stream: UnixStream,
+    unique_name: String,
+    serial: u32,
+    /// Bytes read that don't make up a whole message yet.
+    input: Vec<u8>,
+    /// Messages read but not yet handed to Lisp.
+    pending: Vec<Message>,
+}
+
+impl Connection {
+    fn open(bus: Bus) -> io::Result<Connection> {
+        let mut error = None;
+        for endpoint in parse_address(&bus_address(&bus))? {
+            match Connection::open_endpoint(bus.clone(), &endpoint) {
+                Ok(connection) => return Ok(connection),
+                Err(err) => error = Some(err),
+            }
+        }
+        Err(error.unwrap())
+    }
+
+    /// Connect to ENDPOINT, authenticate and register with the bus,
+    /// waiting for it to tell us our unique name.
+    fn open_endpoint(bus: Bus, endpoint: &Endpoint) -> io::Result<Connection> {
+        let mut stream = connect(endpoint)?;
+        stream.set_read_timeout(Some(Duration::from_secs(25)))?;
+        authenticate(&mut stream)?;
+        stream.set_read_timeout(None)?;
+        stream.set_nonblocking(true)?;
+        let mut connection = Connection {
+            bus,
+            stream,
+            unique_name: String::new(),
+            serial: 0,
+            input: Vec::new(),
+            pending: Vec::new(),
+        };
+        let reply = connection.call(Message {
+            mtype: METHOD_CALL,
+            path: Some(DBUS_PATH.to_string()),
+            interface: Some(DBUS_SERVICE.to_string()),
+            member: Some("Hello".to_string()),
+            destination: Some(DBUS_SERVICE.to_string()),
+            ..Default::default()
+        })?;
+        match (reply.mtype, reply.body.first()) {
+            (METHOD_RETURN, Some(&Value::Str(_, ref name))) => {
+                connection.unique_name = name.clone()
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    "The bus did not accept the connection",
+                ))
+            }
+        }
+        Ok(connection)
+    }
+
+    /// Send MESSAGE and wait for the reply to it.  Other messages that
+    /// come in meanwhile are left in `pending'.  This is only used for
+    /// `Hello', before the connection is shared, so it can't be quit.
+    fn call(&mut self, message: Message) -> io::Result<Message> {
+        let serial = self.send(message)?;
+        let fd = self.stream.as_raw_fd();
+        wait_until(fd, Duration::from_secs(CALL_TIMEOUT), false, || {
+            self.take(|m| m.reply_serial == Some(serial))
+        })
+    }
+
+    /// Take the first message that PREDICATE accepts out of `pending',
+    /// reading what the bus has sent first if there is none yet.
+    fn take<F: Fn(&Message) -> bool>(&mut self, predicate: F) -> io::Result<Option<Message>> {
+        if !self.pending.iter().any(|m| predicate(m)) {
+            self.read_available()?;
+        }
+        let found = self.pending.iter().position(|m| predicate(m));
+        Ok(found.map(|i| self.pending.remove(i)))
+    }
+
+    /// Send MESSAGE, giving it the next serial number, and return that.
+    fn send(&mut self, mut message: Message) -> io::Result<u32> {
+        self.serial = self.serial.wrapping_add(1).max(1);
+        message.serial = self.serial;
+        let bytes = message.encode();
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(&bytes);
+        self.stream.set_nonblocking(true)?;
+        result.map(|_| self.serial)
+    }
+
+    /// Read once from the bus, and move the messages completed by that
+    /// to `pending'.
+    fn read(&mut self) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        let n = self.stream.read(&mut buf)?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The bus closed the connection",
+            ));
+        }
+        self.input.extend_from_slice(&buf[..n]);
+        loop {
+            let decoded = Message::decode(&self.input);
+            match decoded {
+                Ok(Some((message, len))) => {
+                    self.input.drain(..len);
+                    self.pending.push(message);
+                }
+                Ok(None) => return Ok(()),
+                Err(()) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid message from the bus",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Read everything the bus has sent so far.
+    fn read_available(&mut self) -> io::Result<()> {
+        loop {
+            match self.read() {
+                Ok(()) => (),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref CONNECTIONS: Mutex<Vec<Connection>> = Mutex::new(Vec::new());
+}
+
+fn not_connected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "No connection to bus")
+}
+
+/// Wait until FD is readable, or TIMEOUT has passed.
+fn wait_readable(fd: c_int, timeout: Duration) -> io::Result<()> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = timeout.as_secs() * 1000 + u64::from(timeout.subsec_nanos() / 1_000_000);
+    if unsafe { libc::poll(&mut pollfd, 1, millis as c_int) } < 0 {
+        let err = io::Error::last_os_error();
+        // A signal just ends this slice early.
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Call ATTEMPT until it returns a message, waiting for FD to become
+/// readable in between, for up to TIMEOUT in all.  The waits are at
+/// most POLL_SLICE milliseconds long, and if QUITTABLE, a quit is
+/// checked for after each.
+fn wait_until<F>(
+    fd: c_int,
+    timeout: Duration,
+    quittable: bool,
+    mut attempt: F,
+) -> io::Result<Message>
+where
+    F: FnMut() -> io::Result<Option<Message>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(message) = attempt()? {
+            return Ok(message);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out waiting for the bus",
+            ));
+        }
+        wait_readable(fd, cmp::min(deadline - now, Duration::from_millis(POLL_SLICE)))?;
+        if quittable {
+            unsafe { maybe_quit() };
+        }
+    }
+}
+
+/// Return the socket of the connection to BUS, if there is one.
+fn connection_fd(bus: &Bus) -> Option<c_int> {
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|c| c.bus == *bus)
+        .map(|c| c.stream.as_raw_fd())
+}
+
+/// Send MESSAGE on BUS and return its serial number.
+fn send(bus: &Bus, message: Message) -> io::Result<u32> {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    match connections.iter_mut().find(|c| c.bus == *bus) {
+        Some(c) => c.send(message),
+        None => Err(not_connected()),
+    }
+}
+
+/// Wait up to TIMEOUT for a message on BUS that PREDICATE accepts, and
+/// take it out of `pending'.  The connections are only locked while
+/// reading, and the wait can be quit.
+fn wait_for<F: Fn(&Message) -> bool>(
+    bus: &Bus,
+    predicate: F,
+    timeout: Duration,
+) -> io::Result<Message> {
+    let fd = connection_fd(bus).ok_or_else(not_connected)?;
+    wait_until(fd, timeout, true, || {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        match connections.iter_mut().find(|c| c.bus == *bus) {
+            Some(c) => c.take(&predicate),
+            None => Err(not_connected()),
+        }
+    })
+}
+
+fn dbus_error(message: &str, object: LispObject) -> ! {
+    xsignal!(intern("dbus-error").to_raw(), lisp_string(message), object)
+}
+
+/// Return the bus named by BUS, a keyword or an address string.
+fn lisp_bus(bus: LispObject) -> Bus {
+    let session_address = env::var("DBUS_SESSION_BUS_ADDRESS").ok();
+    if let Some(s) = bus.as_string() {
+        let address = String::from_utf8_lossy(s.as_slice()).into_owned();
+        if let Err(err) = parse_address(&address) {
+            dbus_error(&err.to_string(), bus);
+        }
+        if session_address.as_ref() == Some(&address) {
+            Bus::Session
+        } else {
+            Bus::Address(address)
+        }
+    } else if bus.eq(intern(":system")) {
+        Bus::System
+    } else if bus.eq(intern(":session")) {
+        // We do not want to have an autolaunch for the session bus.
+        if session_address.is_none() {
+            dbus_error("No connection to bus", bus);
+        }
+        Bus::Session
+    } else {
+        dbus_error("Wrong bus name", bus)
+    }
+}
+
+fn lisp_bus_object(bus: &Bus) -> LispObject {
+    match *bus {
+        Bus::System => intern(":system"),
+        Bus::Session => intern(":session"),
+        Bus::Address(ref address) => lisp_string(address),
+    }
+}
+
+fn name_byte(b: u8) -> bool {
+    match b {
+        b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'_' => true,
+        _ => false,
+    }
+}
+
+fn is_digit(b: u8) -> bool {
+    match b {
+        b'0'...b'9' => true,
+        _ => false,
+    }
+}
+
+fn valid_path(s: &str) -> bool {
+    s == "/"
+        || (s.starts_with('/') && s[1..].split('/').all(|element| {
+            !element.is_empty() && element.bytes().all(name_byte)
+        }))
+}
+
+/// Whether S is a dot-separated name of at least two elements, made of
+/// name bytes and, if DASH, dashes.  With DIGIT_START, elements may
+/// start with a digit.
+fn valid_dotted(s: &str, dash: bool, digit_start: bool) -> bool {
+    s.len() <= 255 && s.contains('.') && s.split('.').all(|element| {
+        !element.is_empty()
+            && (digit_start || !is_digit(element.as_bytes()[0]))
+            && element.bytes().all(|b| name_byte(b) || (dash && b == b'-'))
+    })
+}
+
+fn valid_bus_name(s: &str) -> bool {
+    if s.starts_with(':') {
+        valid_dotted(&s[1..], true, true)
+    } else {
+        valid_dotted(s, true, false)
+    }
+}
+
+fn valid_interface(s: &str) -> bool {
+    valid_dotted(s, false, false)
+}
+
+fn valid_member(s: &str) -> bool {
+    !s.is_empty() && s.len() <= 255 && !is_digit(s.as_bytes()[0])
+        && s.bytes().all(name_byte)
+}
+
+/// Return the contents of OBJECT, which must be a string that VALID
+/// accepts; WHAT names it in the error otherwise.
+fn checked_name<F: Fn(&str) -> bool>(object: LispObject, valid: F, what: &str) -> String {
+    let s = String::from_utf8_lossy(object.as_string_or_error().as_slice()).into_owned();
+    if !valid(&s) {
+        dbus_error(&format!("{} was not valid: '{}'", what, s), object);
+    }
+    s
+}
+
+/// Return the type code that type keyword OBJECT stands for, if it is
+/// one.
+fn keyword_type(object: LispObject) -> Option<u8> {
+    if !object.is_symbol() {
+        return None;
+    }
+    let keywords = [
+        (":byte", BYTE),
+        (":boolean", BOOLEAN),
+        (":int16", INT16),
+        (":uint16", UINT16),
+        (":int32", INT32),
+        (":uint32", UINT32),
+        (":int64", INT64),
+        (":uint64", UINT64),
+        (":double", DOUBLE),
+        (":string", STRING),
+        (":object-path", OBJECT_PATH),
+        (":signature", SIGNATURE),
+        (":unix-fd", UNIX_FD),
+        (":array", ARRAY),
+        (":variant", VARIANT),
+        (":struct", STRUCT),
+        (":dict-entry", DICT_ENTRY),
+    ];
+    keywords
+        .iter()
+        .find(|&&(name, _)| object.eq(intern(name)))
+        .map(|&(_, code)| code)
+}
+
+/// Return the type code for OBJECT by the conversion rules of
+/// `dbus-call-method'.
+fn object_type(object: LispObject) -> Option<u8> {
+    if object.is_nil() || object.eq(LispObject::constant_t()) {
+        Some(BOOLEAN)
+    } else if object.is_natnum() {
+        Some(UINT32)
+    } else if object.is_fixnum() {
+        Some(INT32)
+    } else if object.is_float() {
+        Some(DOUBLE)
+    } else if object.is_string() {
+        Some(STRING)
+    } else if let Some(code) = keyword_type(object) {
+        Some(code)
+    } else if object.is_cons() {
+        match keyword_type(car_safe(object)) {
+            Some(code) if !is_basic(code) => Some(code),
+            _ => Some(ARRAY),
+        }
+    } else {
+        None
+    }
+}
+
+/// Skip the type keyword at the start of LIST, if there is one.
+fn next_value(list: LispObject) -> LispObject {
+    if keyword_type(car_safe(list)).is_some() {
+        cdr_safe(list)
+    } else {
+        list
+    }
+}
+
+fn int_value(n: i64) -> LispObject {
+    LispObject::int_or_float_from_fixnum(n as EmacsInt)
+}
+
+fn uint_value(n: u64) -> LispObject {
+    if n > MOST_POSITIVE_FIXNUM as u64 {
+        LispObject::from_float(n as f64)
+    } else {
+        LispObject::from_fixnum(n as EmacsInt)
+    }
+}
+
+fn extract_signed(x: LispObject, lo: i64, hi: i64) -> i64 {
+    if let Some(n) = x.as_fixnum() {
+        if lo <= n as i64 && n as i64 <= hi {
+            return n as i64;
+        }
+    } else if let Some(d) = x.as_float() {
+        if lo as f64 <= d && d < 1.0 + hi as f64 && d == (d as i64) as f64 {
+            return d as i64;
+        }
+    } else {
+        wrong_type!(intern("numberp"), x);
+    }
+    args_out_of_range!(x, int_value(lo), int_value(hi))
+}
+
+fn extract_unsigned(x: LispObject, hi: u64) -> u64 {
+    if let Some(n) = x.as_fixnum() {
+        if 0 <= n && n as u64 <= hi {
+            return n as u64;
+        }
+    } else if let Some(d) = x.as_float() {
+        if 0.0 <= d && d < 1.0 + hi as f64 && d == (d as u64) as f64 {
+            return d as u64;
+        }
+    } else {
+        wrong_type!(intern("numberp"), x);
+    }
+    args_out_of_range!(x, LispObject::from_fixnum(0), uint_value(hi))
+}
+
+fn wrong_dbus_type(object: LispObject) -> ! {
+    wrong_type!(intern("D-Bus"), object)
+}
+
+/// Convert OBJECT to a value of type DTYPE.  PARENT is the type of the
+/// container it goes in, if any.
+fn to_value(dtype: Option<u8>, object: LispObject, parent: Option<u8>) -> Value {
+    let dtype = match dtype {
+        Some(dtype) => dtype,
+        None => wrong_dbus_type(object),
+    };
+    let string = |object: LispObject| {
+        String::from_utf8_lossy(object.as_string_or_error().as_slice()).into_owned()
+    };
+    match dtype {
+        BYTE => Value::Byte(object.as_natnum_or_error() as u8),
+        BOOLEAN => {
+            if object.is_not_nil() && !object.eq(LispObject::constant_t()) {
+                wrong_type!(intern("booleanp"), object);
+            }
+            Value::Boolean(object.is_not_nil())
+        }
+        INT16 => {
+            object.as_fixnum_or_error();
+            Value::Int16(extract_signed(object, i16::min_value().into(), i16::max_value().into())
+                as i16)
+        }
+        UINT16 => {
+            object.as_natnum_or_error();
+            Value::Uint16(extract_unsigned(object, u16::max_value().into()) as u16)
+        }
+        INT32 => Value::Int32(extract_signed(
+            object,
+            i32::min_value().into(),
+            i32::max_value().into(),
+        ) as i32),
+        UINT32 => Value::Uint32(extract_unsigned(object, u32::max_value().into()) as u32),
+        UNIX_FD => Value::UnixFd(extract_unsigned(object, u32::max_value().into()) as u32),
+        INT64 => Value::Int64(extract_signed(object, i64::min_value(), i64::max_value())),
+        UINT64 => Value::Uint64(extract_unsigned(object, u64::max_value())),
+        DOUBLE => Value::Double(object.any_to_float_or_error()),
+        STRING | OBJECT_PATH | SIGNATURE => Value::Str(dtype, string(object)),
+        ARRAY => {
+            object.as_cons_or_error();
+            // The type keyword is optional.
+            let mut elt = if car_safe(object).eq(intern(":array")) {
+                cdr_safe(object)
+            } else {
+                object
+            };
+            let first = car_safe(next_value(elt));
+            // A lone signature gives the element type of an empty array.
+            if object_type(car_safe(elt)) == Some(SIGNATURE) && first.is_string()
+                && cdr_safe(next_value(elt)).is_nil()
+            {
+                let signature = string(first);
+                if type_len(signature.as_bytes()) != Ok(signature.len()) {
+                    wrong_dbus_type(first);
+                }
+                return Value::Array(signature, Vec::new());
+            }
+            let subtype = object_type(car_safe(elt));
+            let mut values = Vec::new();
+            while elt.is_not_nil() {
+                if object_type(car_safe(elt)) != subtype {
+                    wrong_dbus_type(car_safe(elt));
+                }
+                let value = to_value(subtype, car_safe(next_value(elt)), Some(ARRAY));
+                if values.first().map_or(false, |v: &Value| {
+                    v.signature() != value.signature()
+                }) {
+                    wrong_dbus_type(car_safe(next_value(elt)));
+                }
+                values.push(value);
+                elt = cdr_safe(next_value(elt));
+            }
+            let signature = values
+                .first()
+                .map_or_else(|| "s".to_string(), |v| v.signature());
+            Value::Array(signature, values)
+        }
+        VARIANT => {
+            object.as_cons_or_error();
+            let elt = next_value(object);
+            let value = to_value(
+                object_type(car_safe(elt)),
+                car_safe(next_value(elt)),
+                Some(VARIANT),
+            );
+            if cdr_safe(next_value(elt)).is_not_nil() {
+                wrong_dbus_type(car_safe(cdr_safe(next_value(elt))));
+            }
+            Value::Variant(Box::new(value))
+        }
+        STRUCT => {
+            object.as_cons_or_error();
+            let mut elt = next_value(object);
+            let mut values = Vec::new();
+            while elt.is_not_nil() {
+                values.push(to_value(
+                    object_type(car_safe(elt)),
+                    car_safe(next_value(elt)),
+                    Some(STRUCT),
+                ));
+                elt = cdr_safe(next_value(elt));
+            }
+            Value::Struct(values)
+        }
+        DICT_ENTRY => {
+            object.as_cons_or_error();
+            // A dict entry must be an array element.
+            if parent != Some(ARRAY) {
+                wrong_dbus_type(object);
+            }
+            let elt = next_value(object);
+            let key_type = object_type(car_safe(elt));
+            let key = to_value(key_type, car_safe(next_value(elt)), Some(DICT_ENTRY));
+            if !key_type.map_or(false, is_basic) {
+                wrong_dbus_type(car_safe(next_value(elt)));
+            }
+            let elt = cdr_safe(next_value(elt));
+            let value = to_value(
+                object_type(car_safe(elt)),
+                car_safe(next_value(elt)),
+                Some(DICT_ENTRY),
+            );
+            if cdr_safe(next_value(elt)).is_not_nil() {
+                wrong_dbus_type(car_safe(cdr_safe(next_value(elt))));
+            }
+            Value::DictEntry(Box::new(key), Box::new(value))
+        }
+        _ => wrong_dbus_type(object),
+    }
+}
+
+/// Convert VALUE to Lisp.  Containers become lists of their elements.
+fn lisp_value(value: &Value) -> LispObject {
+    match *value {
+        Value::Byte(b) => LispObject::from_natnum(EmacsInt::from(b)),
+        Value::Boolean(b) => LispObject::from_bool(b),
+        Value::Int16(n) => LispObject::from_fixnum(EmacsInt::from(n)),
+        Value::Uint16(n) => LispObject::from_natnum(EmacsInt::from(n)),
+        Value::Int32(n) => int_value(i64::from(n)),
+        Value::Uint32(n) | Value::UnixFd(n) => uint_value(u64::from(n)),
+        Value::Int64(n) => int_value(n),
+        Value::Uint64(n) => uint_value(n),
+        Value::Double(d) => LispObject::from_float(d),
+        Value::Str(_, ref s) => lisp_string(s),
+        Value::Array(_, ref values) | Value::Struct(ref values) => lisp_list(values),
+        Value::Variant(ref v) => list!(lisp_value(v)),
+        Value::DictEntry(ref key, ref value) => list!(lisp_value(key), lisp_value(value)),
+    }
+}
+
+fn lisp_list(values: &[Value]) -> LispObject {
+    let mut result = LispObject::constant_nil();
+    for v in values.iter().rev() {
+        result = LispObject::cons(lisp_value(v), result);
+    }
+    result
+}
+
+fn lisp_optional_string(s: &Option<String>) -> LispObject {
+    s.as_ref()
+        .map_or_else(LispObject::constant_nil, |s| lisp_string(s))
+}
+
+/// Whether the string OBJECT, when not nil, equals VALUE, when that is
+/// known.
+fn matches(object: LispObject, value: &Option<String>) -> bool {
+    match (object.as_string(), value.as_ref()) {
+        (Some(s), Some(value)) => s.as_slice() == value.as_bytes(),
+        _ => true,
+    }
+}
+
+/// Turn MESSAGE from BUS into a `dbus-event', if there is a handler for
+/// it in `dbus-registered-objects-table'.
+fn store_event(bus: &Bus, message: &Message) {
+    let table = symbol_value(intern("dbus-registered-objects-table"));
+    let nil = LispObject::constant_nil();
+    let bus = lisp_bus_object(bus);
+    let (serial, handler) = match message.mtype {
+        METHOD_RETURN | ERROR => {
+            let serial = match message.reply_serial {
+                Some(serial) => serial,
+                None => return,
+            };
+            let key = list!(intern(":serial"), bus, uint_value(u64::from(serial)));
+            let handler = gethash(key, table, nil);
+            if handler.is_nil() {
+                return;
+            }
+            remhash(key, table);
+            (serial, handler)
+        }
+        METHOD_CALL | SIGNAL => {
+            let (interface, member) = match (&message.interface, &message.member) {
+                (&Some(ref interface), &Some(ref member)) => (interface, member),
+                _ => return,
+            };
+            let kind = if message.mtype == METHOD_CALL {
+                ":method"
+            } else {
+                ":signal"
+            };
+            let key = list!(
+                intern(kind),
+                bus,
+                lisp_string(interface),
+                lisp_string(member)
+            );
+            // Each entry is (UNAME SERVICE PATH HANDLER [RULE]).
+            let entry = gethash(key, table, nil).iter_cars_safe().find(|&entry| {
+                let fields: Vec<LispObject> = entry.iter_cars_safe().take(4).collect();
+                fields.len() == 4 && matches(fields[0], &message.sender)
+                    && matches(fields[2], &message.path) && fields[3].is_not_nil()
+            });
+            match entry {
+                Some(entry) => (message.serial, entry.iter_cars_safe().nth(3).unwrap()),
+                None => return,
+            }
+        }
+        _ => return,
+    };
+
+    let mut event = LispObject::cons(handler, lisp_list(&message.body));
+    for &field in [&message.member, &message.interface, &message.path, &message.sender].iter() {
+        event = LispObject::cons(lisp_optional_string(field), event);
+    }
+    event = LispObject::cons(uint_value(u64::from(serial)), event);
+    event = LispObject::cons(LispObject::from_natnum(EmacsInt::from(message.mtype)), event);
+    event = LispObject::cons(bus, event);
+    unsafe { kbd_buffer_store_dbus_event(event.to_raw()) };
+}
+
+/// Called when the socket of a bus is readable.
+extern "C" fn read_queued_messages(fd: c_int, _data: *mut c_void) {
+    let (bus, messages) = {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        let index = match connections
+            .iter()
+            .position(|c| c.stream.as_raw_fd() == fd)
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let closed = connections[index].read_available().is_err();
+        let messages: Vec<Message> = connections[index].pending.drain(..).collect();
+        let bus = connections[index].bus.clone();
+        if closed {
+            unsafe { delete_read_fd(fd) };
+            connections.remove(index);
+        }
+        (bus, messages)
+    };
+    for message in &messages {
+        store_event(&bus, message);
+    }
+}
+
+/// Close the connection to BUS, if there is one.
+fn close_bus(bus: &Bus) {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    if let Some(index) = connections.iter().position(|c| c.bus == *bus) {
+        unsafe { delete_read_fd(connections[index].stream.as_raw_fd()) };
+        connections.remove(index);
+    }
+}
+
+/// Connect to BUS and watch its socket for input.
+fn open_bus(bus: Bus) {
+    let connection = match Connection::open(bus.clone()) {
+        Ok(connection) => connection,
+        Err(err) => dbus_error(&err.to_string(), lisp_bus_object(&bus)),
+    };
+    let fd = connection.stream.as_raw_fd();
+    CONNECTIONS.lock().unwrap().push(connection);
+    unsafe { add_read_fd(fd, read_queued_messages, ptr::null_mut()) };
+    // Messages may have come in along with the reply to `Hello'.
+    read_queued_messages(fd, ptr::null_mut());
+}
+
+/// Connect to BUS if need be, call F, and pass the messages that came
+/// in meanwhile on to Lisp.
+fn with_connection<F>(bus: &Bus, f: F) -> io::Result<Message>
+where
+    F: FnOnce() -> io::Result<Message>,
+{
+    if connection_fd(bus).is_none() {
+        open_bus(bus.clone());
+    }
+    let result = f();
+    if let Some(fd) = connection_fd(bus) {
+        read_queued_messages(fd, ptr::null_mut());
+    }
+    result
+}
+
+/// Send MESSAGE, a method call, on BUS and return the body of the
+/// reply.  Signal a `dbus-error' if the call fails.  Messages that come
+/// in while waiting for the reply are passed on to Lisp as usual, and
+/// the wait can be quit.
+pub fn call_method(bus: Bus, message: Message) -> Vec<Value> {
+    let result = with_connection(&bus, || {
+        let serial = send(&bus, message)?;
+        wait_for(
+            &bus,
+            |m| m.reply_serial == Some(serial),
+            Duration::from_secs(CALL_TIMEOUT),
+        )
+    });
+    match result {
+        Ok(Message {
+            mtype: METHOD_RETURN,
+            body,
+            ..
+        }) => body,
+        Ok(reply) => {
+            let name = reply.error_name.unwrap_or_default();
+            match reply.body.first() {
+                Some(&Value::Str(_, ref text)) => xsignal!(
+                    intern("dbus-error").to_raw(),
+                    lisp_string(&name),
+                    lisp_string(text)
+                ),
+                _ => dbus_error(&name, lisp_bus_object(&bus)),
+            }
+        }
+        Err(err) => dbus_error(&err.to_string(), lisp_bus_object(&bus)),
+    }
+}
+
+/// Establish the connection to D-Bus BUS.
+///
+/// This function is dbus internal.  You almost certainly want to use
+/// `dbus-init-bus'.
+///
+/// BUS can be either the symbol `:system' or the symbol `:session', or it
+/// can be a string denoting the address of the corresponding bus.  For
+/// the system and session buses, this function is called when loading
+/// `dbus.el', there is no need to call it again.
+///
+/// An existing connection to BUS is closed first, so the new connection
+/// gets a new unique name at the bus.  The function returns the number
+/// of connections to BUS, which is always 1.  PRIVATE is accepted for
+/// compatibility; connections are never shared with other libraries.
+#[lisp_fn(name = "dbus--init-bus", c_name = "dbus__init_bus", min = "1")]
+pub fn dbus_init_bus(bus: LispObject, _private: LispObject) -> LispObject {
+    let key = lisp_bus(bus);
+    close_bus(&key);
+    open_bus(key);
+    LispObject::from_natnum(1)
+}
+
+/// Return the unique name of Emacs registered at D-Bus BUS.
+#[lisp_fn]
+pub fn dbus_get_unique_name(bus: LispObject) -> LispObject {
+    let key = lisp_bus(bus);
+    let name = CONNECTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|c| c.bus == key)
+        .map(|c| c.unique_name.clone());
+    match name {
+        Some(name) => lisp_string(&name),
+        None => dbus_error("No connection to bus", bus),
+    }
+}
+
+/// Send a D-Bus message.
+/// This is an internal function, it shall not be used outside dbus.el.
+///
+/// The following usages are expected:
+///
+/// `dbus-call-method', `dbus-call-method-asynchronously':
+///   (dbus-message-internal
+///     dbus-message-type-method-call BUS SERVICE PATH INTERFACE METHOD HANDLER
+///     &optional :timeout TIMEOUT &rest ARGS)
+///
+/// `dbus-send-signal':
+///   (dbus-message-internal
+///     dbus-message-type-signal BUS SERVICE PATH INTERFACE SIGNAL &rest ARGS)
+///
+/// `dbus-method-return-internal':
+///   (dbus-message-internal
+///     dbus-message-type-method-return BUS SERVICE SERIAL &rest ARGS)
+///
+/// `dbus-method-error-internal':
+///   (dbus-message-internal
+///     dbus-message-type-error BUS SERVICE SERIAL &rest ARGS)
+///
+/// TIMEOUT is checked, but not enforced here; `dbus-call-method' gives
+/// up waiting for the reply by itself.
+///
+/// usage: (dbus-message-internal &rest REST)
+#[lisp_fn(min = "4")]
+pub fn dbus_message_internal(args: &mut [LispObject]) -> LispObject {
+    let mtype = args[0].as_natnum_or_error();
+    if !(EmacsInt::from(METHOD_CALL) <= mtype && mtype <= EmacsInt::from(SIGNAL)) {
+        dbus_error("Invalid message type", args[0]);
+    }
+    let mtype = mtype as u8;
+    let mut message = Message {
+        mtype,
+        ..Default::default()
+    };
+    let mut handler = LispObject::constant_nil();
+    let mut count = 4;
+    if mtype == METHOD_CALL || mtype == SIGNAL {
+        count = if mtype == METHOD_CALL { 7 } else { 6 };
+    } else {
+        message.reply_serial = Some(extract_unsigned(args[3], u32::max_value().into()) as u32);
+        if mtype == ERROR {
+            message.error_name = Some("org.freedesktop.DBus.Error.Failed".to_string());
+        }
+    }
+
+    let bus = lisp_bus(args[1]);
+    let service = args[2];
+    if service.is_not_nil() {
+        checked_name(service, valid_bus_name, "Bus name");
+    }
+    if args.len() < count {
+        xsignal!(
+            intern("wrong-number-of-arguments").to_raw(),
+            intern("dbus-message-internal"),
+            LispObject::from_natnum(args.len() as EmacsInt)
+        );
+    }
+
+    if mtype == METHOD_CALL || mtype == SIGNAL {
+        message.path = Some(checked_name(args[3], valid_path, "Object path"));
+        message.interface = Some(checked_name(args[4], valid_interface, "Interface name"));
+        message.member = Some(checked_name(args[5], valid_member, "Member name"));
+        if mtype == METHOD_CALL {
+            handler = args[6];
+            if handler.is_nil() {
+                message.flags = NO_REPLY_EXPECTED;
+            } else if call!(intern("functionp"), handler).is_nil() {
+                wrong_type!(intern("invalid-function"), handler);
+            }
+        }
+    }
+
+    if let Some(s) = service.as_string() {
+        let service_name = String::from_utf8_lossy(s.as_slice()).into_owned();
+        if mtype != SIGNAL {
+            message.destination = Some(service_name);
+        } else {
+            // A signal to our own or an unknown name is a broadcast, for
+            // backward compatibility.
+            let owner = call!(intern("dbus-get-name-owner"), args[1], service);
+            let own = call!(intern("dbus-get-unique-name"), args[1]);
+            if owner.is_string() && call!(intern("string-equal"), owner, own).is_nil() {
+                message.destination = Some(service_name);
+            }
+        }
+    }
+
+    // Check for timeout parameter.
+    if count + 2 <= args.len() && args[count].eq(intern(":timeout")) {
+        args[count + 1].as_natnum_or_error();
+        count += 2;
+    }
+
+    // Append parameters to the message.
+    while count < args.len() {
+        let dtype = object_type(args[count]);
+        if keyword_type(args[count]).is_some() {
+            count += 1;
+            if count == args.len() {
+                wrong_dbus_type(args[count - 1]);
+            }
+        }
+        message.body.push(to_value(dtype, args[count], None));
+        count += 1;
+    }
+
+    let sent = {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        connections
+            .iter_mut()
+            .find(|c| c.bus == bus)
+            .map(|c| c.send(message))
+    };
+    let serial = match sent {
+        Some(Ok(serial)) => serial,
+        Some(Err(err)) => dbus_error(&format!("Cannot send message: {}", err), args[1]),
+        None => dbus_error("No connection to bus", args[1]),
+    };
+
+    if handler.is_nil() {
+        return LispObject::constant_nil();
+    }
+    // The result is the key in `dbus-registered-objects-table'.
+    let result = list!(
+        intern(":serial"),
+        lisp_bus_object(&bus),
+        uint_value(u64::from(serial))
+    );
+    puthash(
+        result,
+        handler,
+        symbol_value(intern("dbus-registered-objects-table")),
+    );
+    result
+}
+
+#[test]
+fn test_marshalling() {
+    let body = vec![
+        Value::Str(STRING, "hello".to_string()),
+        Value::Array(
+            "{sv}".to_string(),
+            vec![Value::DictEntry(
+                Box::new(Value::Str(STRING, "key".to_string())),
+                Box::new(Value::Variant(Box::new(Value::Int64(-2)))),
+            )],
+        ),
+        Value::Struct(vec![Value::Byte(7), Value::Double(1.5), Value::Boolean(true)]),
+        Value::Array("s".to_string(), vec![]),
+        Value::Int16(-3),
+    ];
+    let message = Message {
+        mtype: METHOD_CALL,
+        serial: 5,
+        path: Some("/org/gnu/Emacs".to_string()),
+        member: Some("Ping".to_string()),
+        body: body.clone(),
+        ..Default::default()
+    };
+    let bytes = message.encode();
+    assert_eq!(&bytes[..4], b"l\x01\x00\x01");
+    assert_eq!(Message::decode(&bytes[..bytes.len() - 1]), Ok(None));
+    let (decoded, len) = Message::decode(&bytes).unwrap().unwrap();
+    assert_eq!(len, bytes.len());
+    assert_eq!(decoded.serial, 5);
+    assert_eq!(decoded.path, message.path);
+    assert_eq!(decoded.member, message.member);
+    assert_eq!(decoded.body, body);
+
+    assert_eq!(type_len(b"a{sv}i"), Ok(5));
+    assert_eq!(type_len(b"(ii"), Err(()));
+    assert_eq!(types(b"sa(ib)v").unwrap(), vec![&b"s"[..], b"a(ib)", b"v"]);
+}
+
+#[test]
+fn test_names() {
+    assert_eq!(
+        parse_address("tcp:host=x;unix:abstract=/tmp/dbus-1%2c2,guid=0;unix:path=/run/bus").ok(),
+        Some(vec![
+            Endpoint::Abstract(b"/tmp/dbus-1,2".to_vec()),
+            Endpoint::Path(b"/run/bus".to_vec()),
+        ])
+    );
+    assert!(parse_address("tcp:host=localhost").is_err());
+
+    assert!(valid_path("/") && valid_path("/org/gnu/Emacs"));
+    assert!(!valid_path("/org/") && !valid_path("org") && !valid_path("/a//b"));
+    assert!(valid_interface("org.gnu.Emacs") && !valid_interface("Emacs"));
+    assert!(!valid_interface("org.1gnu"));
+    assert!(valid_bus_name(":1.42") && valid_bus_name("org.gnu.Emacs-1"));
+    assert!(!valid_bus_name("org") && !valid_bus_name("org..gnu"));
+    assert!(valid_member("GetAll") && !valid_member("Get.All") && !valid_member("1x"));
+}
+
+include!(concat!(env!("OUT_DIR"), "/dbus_exports.rs"));
@@ -0,0 +1,612 @@
+//! Line-based diffs of strings and buffer regions.
+//!
+//! Two algorithms are available: Myers' O(ND) algorithm in its linear
+//! space variant, and patience diff, which anchors the comparison on
+//! lines that occur exactly once on each side and falls back to Myers
+//! between anchors.  Both work on sequences of interned tokens, so the
+//! same machinery can compare words or characters as well as lines.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{record_unwind_current_buffer, unbind_to, EmacsInt, Fbuffer_substring, Qnil};
+
+use buffers::set_buffer;
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use multibyte::Codepoint;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Patience,
+}
+
+impl DiffAlgorithm {
+    /// Parse the ALGORITHM argument of the diff primitives: nil or
+    /// `myers', or `patience'.
+    pub fn from_lisp(algorithm: LispObject) -> DiffAlgorithm {
+        if algorithm.is_nil() || algorithm.eq(intern("myers")) {
+            DiffAlgorithm::Myers
+        } else if algorithm.eq(intern("patience")) {
+            DiffAlgorithm::Patience
+        } else {
+            error!("Unknown diff algorithm");
+        }
+    }
+}
+
+/// A change: tokens `a_start..a_end` of the first sequence are replaced
+/// by tokens `b_start..b_end` of the second.  One of the ranges may be
+/// empty.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hunk {
+    pub a_start: usize,
+    pub a_end: usize,
+    pub b_start: usize,
+    pub b_end: usize,
+}
+
+/// Map equal tokens of A and B to equal integers.
+fn intern_tokens<T: Hash + Eq>(a: &[T], b: &[T]) -> (Vec<u32>, Vec<u32>) {
+    let mut ids: HashMap<&T, u32> = HashMap::new();
+    let mut a_ids = Vec::with_capacity(a.len());
+    for token in a {
+        let next = ids.len() as u32;
+        a_ids.push(*ids.entry(token).or_insert(next));
+    }
+    let mut b_ids = Vec::with_capacity(b.len());
+    for token in b {
+        let next = ids.len() as u32;
+        b_ids.push(*ids.entry(token).or_insert(next));
+    }
+    (a_ids, b_ids)
+}
+
+/// The state of a comparison: the two sequences, and which of their
+/// tokens have been found to be changed.
+struct Comparison<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    a_changed: Vec<bool>,
+    b_changed: Vec<bool>,
+}
+
+impl<'a> Comparison<'a> {
+    fn new(a: &'a [u32], b: &'a [u32]) -> Comparison<'a> {
+        Comparison {
+            a,
+            b,
+            a_changed: vec![false; a.len()],
+            b_changed: vec![false; b.len()],
+        }
+    }
+
+    /// Strip the common prefix and suffix of `a[xoff..xlim]` and
+    /// `b[yoff..ylim]`.  If one side is then empty, mark the rest of
+    /// the other as changed and return `None`.
+    fn trim(
+        &mut self,
+        mut xoff: usize,
+        mut xlim: usize,
+        mut yoff: usize,
+        mut ylim: usize,
+    ) -> Option<(usize, usize, usize, usize)> {
+        while xoff < xlim && yoff < ylim && self.a[xoff] == self.b[yoff] {
+            xoff += 1;
+            yoff += 1;
+        }
+        while xoff < xlim && yoff < ylim && self.a[xlim - 1] == self.b[ylim - 1] {
+            xlim -= 1;
+            ylim -= 1;
+        }
+        if xoff == xlim || yoff == ylim {
+            for changed in &mut self.a_changed[xoff..xlim] {
+                *changed = true;
+            }
+            for changed in &mut self.b_changed[yoff..ylim] {
+                *changed = true;
+            }
+            None
+        } else {
+            Some((xoff, xlim, yoff, ylim))
+        }
+    }
+
+    /// Compare `a[xoff..xlim]` with `b[yoff..ylim]` using Myers'
+    /// algorithm, recursing on both sides of a middle snake.
+    fn myers(&mut self, xoff: usize, xlim: usize, yoff: usize, ylim: usize) {
+        let (xoff, xlim, yoff, ylim) = match self.trim(xoff, xlim, yoff, ylim) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        match middle_snake(&self.a[xoff..xlim], &self.b[yoff..ylim]) {
+            Some((x, y)) => {
+                self.myers(xoff, xoff + x, yoff, yoff + y);
+                self.myers(xoff + x, xlim, yoff + y, ylim);
+            }
+            None => {
+                for changed in &mut self.a_changed[xoff..xlim] {
+                    *changed = true;
+                }
+                for changed in &mut self.b_changed[yoff..ylim] {
+                    *changed = true;
+                }
+            }
+        }
+    }
+
+    /// Compare `a[xoff..xlim]` with `b[yoff..ylim]` using patience diff.
+    fn patience(&mut self, xoff: usize, xlim: usize, yoff: usize, ylim: usize) {
+        let (xoff, xlim, yoff, ylim) = match self.trim(xoff, xlim, yoff, ylim) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        // Tokens occurring exactly once on each side, with their positions.
+        let mut counts: HashMap<u32, (usize, usize, usize, usize)> = HashMap::new();
+        for x in xoff..xlim {
+            let entry = counts.entry(self.a[x]).or_insert((0, 0, 0, 0));
+            entry.0 += 1;
+            entry.1 = x;
+        }
+        for y in yoff..ylim {
+            if let Some(entry) = counts.get_mut(&self.b[y]) {
+                entry.2 += 1;
+                entry.3 = y;
+            }
+        }
+        let mut unique: Vec<(usize, usize)> = counts
+            .values()
+            .filter(|c| c.0 == 1 && c.2 == 1)
+            .map(|c| (c.1, c.3))
+            .collect();
+        if unique.is_empty() {
+            self.myers(xoff, xlim, yoff, ylim);
+            return;
+        }
+        unique.sort();
+
+        let (mut x, mut y) = (xoff, yoff);
+        for (ax, ay) in longest_increasing_run(&unique) {
+            self.patience(x, ax, y, ay);
+            x = ax + 1;
+            y = ay + 1;
+        }
+        self.patience(x, xlim, y, ylim);
+    }
+
+    fn hunks(&self) -> Vec<Hunk> {
+        let (n, m) = (self.a.len(), self.b.len());
+        let mut hunks = Vec::new();
+        let (mut x, mut y) = (0, 0);
+        while x < n || y < m {
+            if x < n && y < m && !self.a_changed[x] && !self.b_changed[y] {
+                x += 1;
+                y += 1;
+                continue;
+            }
+            let (a_start, b_start) = (x, y);
+            while x < n && self.a_changed[x] {
+                x += 1;
+            }
+            while y < m && self.b_changed[y] {
+                y += 1;
+            }
+            hunks.push(Hunk {
+                a_start,
+                a_end: x,
+                b_start,
+                b_end: y,
+            });
+        }
+        hunks
+    }
+}
+
+/// Find a point on an optimal edit path from the start to the end of A
+/// and B, about halfway along it, or `None` if A and B have nothing in
+/// common.  A and B must differ at both ends.
+///
+/// Diagonals that run off the edit graph are excluded from the search
+/// as soon as they do, as in diff-match-patch.
+fn middle_snake(a: &[u32], b: &[u32]) -> Option<(usize, usize)> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let length = 2 * max_d + 2;
+    // The furthest x reached on each diagonal, indexed by diagonal +
+    // OFFSET, going forward from the start and backward from the end.
+    // -1 means not reached yet.
+    let mut forward = vec![-1isize; length as usize];
+    let mut backward = vec![-1isize; length as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // If the difference is odd, the paths meet during a forward pass.
+    let front = delta % 2 != 0;
+    let (mut k1start, mut k1end, mut k2start, mut k2end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        let mut k1 = -d + k1start;
+        while k1 <= d - k1end {
+            let i = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[i] = x1;
+            if x1 > n {
+                k1end += 2;
+            } else if y1 > m {
+                k1start += 2;
+            } else if front {
+                let j = offset + delta - k1;
+                if j >= 0 && j < length && backward[j as usize] != -1
+                    && x1 >= n - backward[j as usize]
+                {
+                    return Some((x1 as usize, y1 as usize));
+                }
+            }
+            k1 += 2;
+        }
+
+        let mut k2 = -d + k2start;
+        while k2 <= d - k2end {
+            let i = (offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[i] = x2;
+            if x2 > n {
+                k2end += 2;
+            } else if y2 > m {
+                k2start += 2;
+            } else if !front {
+                let j = offset + delta - k2;
+                if j >= 0 && j < length && forward[j as usize] != -1 {
+                    let x1 = forward[j as usize];
+                    let y1 = offset + x1 - j;
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k2 += 2;
+        }
+    }
+    None
+}
+
+/// Given PAIRS sorted by their first element, return the longest
+/// subsequence whose second elements are increasing too.
+fn longest_increasing_run(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Patience sorting: TOPS[i] is the index of the pair ending the
+    // best run of length i + 1 found so far.
+    let mut tops: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(pairs.len());
+    for (i, &(_, y)) in pairs.iter().enumerate() {
+        let pile = match tops.binary_search_by(|&t| pairs[t].1.cmp(&y)) {
+            Ok(pile) | Err(pile) => pile,
+        };
+        previous.push(if pile > 0 { Some(tops[pile - 1]) } else { None });
+        if pile == tops.len() {
+            tops.push(i);
+        } else {
+            tops[pile] = i;
+        }
+    }
+
+    let mut run = Vec::with_capacity(tops.len());
+    let mut next = tops.last().cloned();
+    while let Some(i) = next {
+        run.push(pairs[i]);
+        next = previous[i];
+    }
+    run.reverse();
+    run
+}
+
+/// Compare the token sequences A and B and return the hunks that turn
+/// A into B, in order.
+pub fn diff_sequences<T: Hash + Eq>(a: &[T], b: &[T], algorithm: DiffAlgorithm) -> Vec<Hunk> {
+    let (a_ids, b_ids) = intern_tokens(a, b);
+    let mut comparison = Comparison::new(&a_ids, &b_ids);
+    match algorithm {
+        DiffAlgorithm::Myers => comparison.myers(0, a_ids.len(), 0, b_ids.len()),
+        DiffAlgorithm::Patience => comparison.patience(0, a_ids.len(), 0, b_ids.len()),
+    }
+    comparison.hunks()
+}
+
+/// Split BYTES, the contents of a string, into lines, each including
+/// its newline, and return them along with the character offset of the
+/// start of each line and of the end of the text.
+fn split_lines(bytes: &[u8], multibyte: bool) -> (Vec<&[u8]>, Vec<usize>) {
+    let mut lines = Vec::new();
+    let mut offsets = vec![0];
+    let mut start = 0;
+    let mut chars = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        if !multibyte || b & 0xc0 != 0x80 {
+            chars += 1;
+        }
+        if b == b'\n' {
+            lines.push(&bytes[start..i + 1]);
+            offsets.push(chars);
+            start = i + 1;
+        }
+    }
+    if start < bytes.len() {
+        lines.push(&bytes[start..]);
+        offsets.push(chars);
+    }
+    (lines, offsets)
+}
+
+/// Diff the lines of A and B, and return the hunks as a Lisp list of
+/// character positions, adding A_BASE and B_BASE to them.
+fn diff_texts(
+    a: LispObject,
+    a_base: EmacsInt,
+    b: LispObject,
+    b_base: EmacsInt,
+    algorithm: DiffAlgorithm,
+) -> LispObject {
+    let a = a.as_string_or_error();
+    let b = b.as_string_or_error();
+    let (a_lines, a_offsets) = split_lines(a.as_slice(), a.is_multibyte());
+    let (b_lines, b_offsets) = split_lines(b.as_slice(), b.is_multibyte());
+    let hunks = diff_sequences(&a_lines, &b_lines, algorithm);
+    hunks_to_lisp(&hunks, a_base, &a_offsets, b_base, &b_offsets)
+}
+
+/// Return HUNKS as a Lisp list of (A-START A-END B-START B-END), where
+/// token indices are turned into character positions with the OFFSETS
+/// of each token and the BASE of each text.
+fn hunks_to_lisp(
+    hunks: &[Hunk],
+    a_base: EmacsInt,
+    a_offsets: &[usize],
+    b_base: EmacsInt,
+    b_offsets: &[usize],
+) -> LispObject {
+    let position = |base: EmacsInt, offsets: &[usize], token: usize| {
+        LispObject::from_fixnum(base + offsets[token] as EmacsInt)
+    };
+    hunks
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |result, hunk| {
+            let hunk = list!(
+                position(a_base, a_offsets, hunk.a_start),
+                position(a_base, a_offsets, hunk.a_end),
+                position(b_base, b_offsets, hunk.b_start),
+                position(b_base, b_offsets, hunk.b_end)
+            );
+            LispObject::cons(hunk, result)
+        })
+}
+
+/// Compare the lines of strings A and B.
+/// Return a list of hunks (A-START A-END B-START B-END), in order, each
+/// meaning that the text of A from A-START to A-END is replaced by the
+/// text of B from B-START to B-END.  Positions are 0-based character
+/// indices at line boundaries, and one of the two ranges may be empty.
+/// Return nil if the strings have the same lines.
+///
+/// ALGORITHM is nil or `myers' for Myers' algorithm, which finds a
+/// minimal diff, or `patience' for patience diff, which tends to give
+/// more readable results on source code.
+#[lisp_fn(min = "2")]
+pub fn diff_strings(a: LispObject, b: LispObject, algorithm: LispObject) -> LispObject {
+    let algorithm = DiffAlgorithm::from_lisp(algorithm);
+    diff_texts(a, 0, b, 0, algorithm)
+}
+
+/// Return the text between BEG and END in BUFFER, or the current buffer
+/// if BUFFER is nil, and the position where it starts.
+fn region_text(beg: LispObject, end: LispObject, buffer: LispObject) -> (LispObject, EmacsInt) {
+    let count = specpdl_index();
+    unsafe { record_unwind_current_buffer() };
+    if buffer.is_not_nil() {
+        set_buffer(buffer);
+    }
+    let text = LispObject::from(unsafe { Fbuffer_substring(beg.to_raw(), end.to_raw()) });
+    let start = beg.as_fixnum_coerce_marker_or_error()
+        .min(end.as_fixnum_coerce_marker_or_error());
+    unsafe { unbind_to(count, Qnil) };
+    (text, start)
+}
+
+/// Compare the lines of two regions, from A-BEG to A-END and from B-BEG
+/// to B-END.  The regions are in A-BUFFER and B-BUFFER respectively, or
+/// in the current buffer if those are nil.
+/// Return a list of hunks (A-START A-END B-START B-END) as for
+/// `diff-strings', except that positions are buffer positions.
+/// ALGORITHM is as for `diff-strings'.
+#[lisp_fn(min = "4")]
+pub fn diff_regions(
+    a_beg: LispObject,
+    a_end: LispObject,
+    b_beg: LispObject,
+    b_end: LispObject,
+    a_buffer: LispObject,
+    b_buffer: LispObject,
+    algorithm: LispObject,
+) -> LispObject {
+    let algorithm = DiffAlgorithm::from_lisp(algorithm);
+    let (a, a_start) = region_text(a_beg, a_end, a_buffer);
+    let (b, b_start) = region_text(b_beg, b_end, b_buffer);
+    diff_texts(a, a_start, b, b_start, algorithm)
+}
+
+/// How `diff-refine-hunk-native' splits text into tokens.
+#[derive(Clone, Copy, PartialEq)]
+enum Tokenization {
+    /// Runs of word constituents, runs of whitespace, and single other
+    /// characters.
+    Word,
+    /// Single characters.
+    Char,
+}
+
+impl Tokenization {
+    fn from_lisp(tokenize: LispObject) -> Tokenization {
+        if tokenize.is_nil() || tokenize.eq(intern("word")) {
+            Tokenization::Word
+        } else if tokenize.eq(intern("char")) {
+            Tokenization::Char
+        } else {
+            error!("Unknown tokenization");
+        }
+    }
+}
+
+/// The class of character C for word tokenization: 1 for word
+/// constituents, 2 for whitespace and 0 for anything else.
+fn token_class(c: Codepoint) -> u8 {
+    match ::std::char::from_u32(c) {
+        Some(c) if c.is_alphanumeric() || c == '_' => 1,
+        Some(c) if c.is_whitespace() => 2,
+        _ => 0,
+    }
+}
+
+/// Split CHARS into tokens, and return them along with the index of the
+/// start of each token and of the end of the text.
+fn split_tokens(
+    chars: &[Codepoint],
+    tokenization: Tokenization,
+) -> (Vec<&[Codepoint]>, Vec<usize>) {
+    let mut tokens = Vec::new();
+    let mut offsets = vec![0];
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = start + 1;
+        if tokenization == Tokenization::Word {
+            let class = token_class(chars[start]);
+            if class != 0 {
+                while end < chars.len() && token_class(chars[end]) == class {
+                    end += 1;
+                }
+            }
+        }
+        tokens.push(&chars[start..end]);
+        offsets.push(end);
+        start = end;
+    }
+    (tokens, offsets)
+}
+
+/// Compute the word or character level differences between two
+/// regions, from A-BEG to A-END and from B-BEG to B-END, typically the
+/// removed and added parts of a diff hunk.  The regions are in A-BUFFER
+/// and B-BUFFER respectively, or in the current buffer if those are nil.
+/// Return a list of (A-START A-END B-START B-END) buffer positions, in
+/// order, each meaning that the text of the first region from A-START to
+/// A-END is replaced by the text of the second from B-START to B-END.
+/// These are the ranges to highlight with refine overlays.
+///
+/// TOKENIZE says how to split the text: nil or `word' compares runs of
+/// word constituents, runs of whitespace and single other characters,
+/// while `char' compares single characters.  ALGORITHM is as for
+/// `diff-strings'.
+#[lisp_fn(min = "4")]
+pub fn diff_refine_hunk_native(
+    a_beg: LispObject,
+    a_end: LispObject,
+    b_beg: LispObject,
+    b_end: LispObject,
+    a_buffer: LispObject,
+    b_buffer: LispObject,
+    tokenize: LispObject,
+    algorithm: LispObject,
+) -> LispObject {
+    let tokenization = Tokenization::from_lisp(tokenize);
+    let algorithm = DiffAlgorithm::from_lisp(algorithm);
+    let (a, a_base) = region_text(a_beg, a_end, a_buffer);
+    let (b, b_base) = region_text(b_beg, b_end, b_buffer);
+    let a_chars: Vec<Codepoint> = a.as_string_or_error().chars().collect();
+    let b_chars: Vec<Codepoint> = b.as_string_or_error().chars().collect();
+    let (a_tokens, a_offsets) = split_tokens(&a_chars, tokenization);
+    let (b_tokens, b_offsets) = split_tokens(&b_chars, tokenization);
+    let hunks = diff_sequences(&a_tokens, &b_tokens, algorithm);
+    hunks_to_lisp(&hunks, a_base, &a_offsets, b_base, &b_offsets)
+}
+
+#[cfg(test)]
+fn chars_diff(a: &str, b: &str, algorithm: DiffAlgorithm) -> Vec<(usize, usize, usize, usize)> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    diff_sequences(&a, &b, algorithm)
+        .iter()
+        .map(|h| (h.a_start, h.a_end, h.b_start, h.b_end))
+        .collect()
+}
+
+#[test]
+fn test_myers_diff() {
+    assert_eq!(chars_diff("abc", "abc", DiffAlgorithm::Myers), vec![]);
+    assert_eq!(
+        chars_diff("abcabba", "cbabac", DiffAlgorithm::Myers)
+            .iter()
+            .map(|h| (h.1 - h.0) + (h.3 - h.2))
+            .sum::<usize>(),
+        5
+    );
+    assert_eq!(
+        chars_diff("xaby", "xacy", DiffAlgorithm::Myers),
+        vec![(2, 3, 2, 3)]
+    );
+    assert_eq!(chars_diff("", "ab", DiffAlgorithm::Myers), vec![(0, 0, 0, 2)]);
+}
+
+#[test]
+fn test_patience_diff() {
+    assert_eq!(
+        chars_diff("axbyc", "ayxbc", DiffAlgorithm::Patience),
+        vec![(1, 1, 1, 2), (3, 4, 4, 4)]
+    );
+    assert_eq!(
+        chars_diff("aab", "abb", DiffAlgorithm::Patience),
+        chars_diff("aab", "abb", DiffAlgorithm::Myers)
+    );
+}
+
+#[test]
+fn test_split_tokens() {
+    let chars: Vec<Codepoint> = "foo_1  (bar)".chars().map(|c| c as Codepoint).collect();
+    let (tokens, offsets) = split_tokens(&chars, Tokenization::Word);
+    assert_eq!(tokens.len(), 5);
+    assert_eq!(offsets, vec![0, 5, 7, 8, 11, 12]);
+    let (tokens, _) = split_tokens(&chars, Tokenization::Char);
+    assert_eq!(tokens.len(), chars.len());
+}
+
+#[test]
+fn test_longest_increasing_run() {
+    let pairs = [(0, 3), (1, 1), (2, 4), (3, 2), (4, 5)];
+    assert_eq!(
+        longest_increasing_run(&pairs),
+        vec![(1, 1), (3, 2), (4, 5)]
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/diff_exports.rs"));
@@ -0,0 +1,540 @@
+//! Parsing the data of drag-and-drop operations.
+//!
+//! Each window system hands over dropped data in its own formats: a
+//! text/uri-list from XDND and Wayland, UTF-16 text/x-moz-url from
+//! Firefox, the file list of GNOME and KDE, a property list of file
+//! names from macOS, and plain text, which may well be a list of file
+//! names.  `dnd-parse-payload' turns all of them into the same list of
+//! items, so that the drop handlers need not care where a drop came
+//! from.
+
+use std::path::Path;
+
+use url::Url;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::Qerror;
+
+use imap::unibyte_string;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use urls;
+
+/// The data formats of drops.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    /// A list of URLs, one per line, with comments.
+    UriList,
+    /// "copy" or "cut", then a list of URLs.
+    GnomeFiles,
+    /// UTF-16 lines of a URL followed by its title.
+    MozUrl,
+    /// A URL and optionally its title on the next line.
+    NetscapeUrl,
+    /// A file name, in UTF-8 or, for FileNameW, in UTF-16.
+    FileName { wide: bool },
+    /// A macOS property list with an array of file names.
+    FilenamesPlist,
+    /// The name of a file that the source will save where it is dropped.
+    DirectSave,
+    /// Text, in Latin-1 for the X type STRING.
+    Text { latin1: bool },
+}
+
+fn format(drop_type: &str) -> Option<Format> {
+    let mime = drop_type.split(';').next().unwrap_or("").trim();
+    Some(match mime {
+        "text/uri-list" | "application/x-kde4-urilist" | "public.file-url" | "public.url" => {
+            Format::UriList
+        }
+        "x-special/gnome-copied-files" => Format::GnomeFiles,
+        "text/x-moz-url" => Format::MozUrl,
+        "_NETSCAPE_URL" => Format::NetscapeUrl,
+        "FILE_NAME" | "FileName" => Format::FileName { wide: false },
+        "FileNameW" => Format::FileName { wide: true },
+        "NSFilenamesPboardType" => Format::FilenamesPlist,
+        "XdndDirectSave0" => Format::DirectSave,
+        "STRING" => Format::Text { latin1: true },
+        "text/plain" | "UTF8_STRING" | "TEXT" | "COMPOUND_TEXT" | "public.utf8-plain-text"
+        | "NSStringPboardType" => Format::Text { latin1: false },
+        _ => return None,
+    })
+}
+
+/// A dropped thing.
+#[derive(Debug, PartialEq)]
+enum Item {
+    /// A file, with its name in bytes and its URL.
+    File { name: Vec<u8>, url: String },
+    /// A URL that is not a local file.
+    Url {
+        url: String,
+        scheme: String,
+        host: Option<String>,
+        title: Option<String>,
+    },
+    Text(String),
+    /// A file that is yet to be written by the source of the drop.
+    Promise(String),
+}
+
+/// Return TEXT, which is in UTF-16 if it starts with a byte order mark
+/// or has NUL bytes, as a string.
+fn decode_text(data: &[u8]) -> String {
+    let (data, big_endian) = if data.starts_with(b"\xff\xfe") {
+        (&data[2..], false)
+    } else if data.starts_with(b"\xfe\xff") {
+        (&data[2..], true)
+    } else if data.contains(&0) && data.len() % 2 == 0 {
+        (data, data[0] == 0)
+    } else {
+        return String::from_utf8_lossy(data).into_owned();
+    };
+    let units: Vec<u16> = data
+        .chunks(2)
+        .map(|pair| {
+            let (high, low) = if big_endian {
+                (pair[0], pair[1])
+            } else {
+                (pair[1], pair[0])
+            };
+            u16::from(high) << 8 | u16::from(low)
+        })
+        .collect();
+    let text = String::from_utf16_lossy(&units);
+    text.trim_right_matches('\0').to_string()
+}
+
+/// Return the file name of the file URL URL if it is on a host for
+/// which IS_LOCAL is true, or None if URL is not a file URL.  The
+/// result is Some(Err(HOST)) for files on other hosts.
+fn file_url_name<F>(url: &str, is_local: F) -> Option<Result<Vec<u8>, String>>
+where
+    F: Fn(&str) -> bool,
+{
+    match url.get(..5) {
+        Some(scheme) if scheme.to_lowercase() == "file:" => {}
+        _ => return None,
+    }
+    let rest = &url[5..];
+    let (host, path) = if rest.starts_with("//") {
+        let end = rest[2..].find('/').map_or(rest.len(), |n| n + 2);
+        (&rest[2..end], &rest[end..])
+    } else {
+        ("", rest)
+    };
+    if !host.is_empty() && !is_local(host) {
+        return Some(Err(host.to_string()));
+    }
+    let path = path.split(|c: char| c == '?' || c == '#').next().unwrap_or("");
+    let mut name = urls::decode(path.as_bytes());
+    // file:///C:/dir names a file on drive C.
+    if name.len() >= 3 && name[0] == b'/' && name[1] < 0x80 && (name[1] as char).is_alphabetic()
+        && name[2] == b':'
+    {
+        name.remove(0);
+    }
+    Some(Ok(name))
+}
+
+/// Return the item for URL, or None if it is not a valid URL.
+fn url_item<F>(url: &str, title: Option<&str>, is_local: &F) -> Option<Item>
+where
+    F: Fn(&str) -> bool,
+{
+    match file_url_name(url, is_local) {
+        Some(Ok(name)) => {
+            return Some(Item::File {
+                name: name,
+                url: url.to_string(),
+            })
+        }
+        Some(Err(host)) => {
+            return Some(Item::Url {
+                url: url.to_string(),
+                scheme: "file".to_string(),
+                host: Some(host),
+                title: title.map(str::to_string),
+            })
+        }
+        None => {}
+    }
+    let parsed = Url::parse(url).ok()?;
+    Some(Item::Url {
+        url: url.to_string(),
+        scheme: parsed.scheme().to_string(),
+        host: parsed.host_str().map(str::to_string),
+        title: title.and_then(|title| {
+            if title.is_empty() {
+                None
+            } else {
+                Some(title.to_string())
+            }
+        }),
+    })
+}
+
+/// Return the item for the file name NAME.
+fn file_item(name: &str) -> Item {
+    let slash = if name.starts_with('/') { "" } else { "/" };
+    Item::File {
+        name: name.as_bytes().to_vec(),
+        url: format!("file://{}{}", slash, urls::encode(name.as_bytes(), b"/:")),
+    }
+}
+
+/// Return the lines of TEXT without line ends, blank lines and the
+/// comments of a text/uri-list.
+fn uri_lines(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+/// Return the text between the <string> tags of the property list PLIST.
+fn plist_strings(plist: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut rest = plist;
+    while let Some(start) = rest.find("<string>") {
+        rest = &rest[start + 8..];
+        let end = match rest.find("</string>") {
+            Some(end) => end,
+            None => break,
+        };
+        strings.push(
+            rest[..end]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&"),
+        );
+        rest = &rest[end..];
+    }
+    strings
+}
+
+/// Whether LINE of dropped text looks like a URL or names an existing
+/// file, so that text made of such lines is a list of things rather
+/// than text.
+fn is_url_or_file(line: &str) -> bool {
+    let scheme_end = line.find(':').unwrap_or(0);
+    let has_scheme = scheme_end > 1
+        && line[..scheme_end]
+            .chars()
+            .all(|c: char| c.is_digit(36) || c == '+' || c == '-' || c == '.')
+        && !line.contains(char::is_whitespace)
+        && Url::parse(line).is_ok();
+    has_scheme || (line.starts_with('/') && Path::new(line).exists())
+}
+
+/// Parse DATA, dropped in FORMAT.
+fn parse<F>(format: Format, data: &[u8], is_local: &F) -> Vec<Item>
+where
+    F: Fn(&str) -> bool,
+{
+    let urls = |lines: &[&str]| -> Vec<Item> {
+        lines
+            .iter()
+            .filter_map(|line| url_item(line, None, is_local))
+            .collect()
+    };
+    match format {
+        Format::UriList => urls(&uri_lines(&decode_text(data))),
+        Format::GnomeFiles => {
+            let text = decode_text(data);
+            let lines = uri_lines(&text);
+            match lines.first() {
+                Some(&"copy") | Some(&"cut") => urls(&lines[1..]),
+                _ => urls(&lines),
+            }
+        }
+        Format::MozUrl | Format::NetscapeUrl => {
+            let text = decode_text(data);
+            let lines: Vec<&str> = text.lines().map(str::trim).collect();
+            let mut items = Vec::new();
+            for pair in lines.chunks(2) {
+                if let Some(item) = url_item(pair[0], pair.get(1).cloned(), is_local) {
+                    items.push(item);
+                }
+            }
+            items
+        }
+        Format::FileName { wide } => {
+            let name = if wide {
+                decode_text(data)
+            } else {
+                String::from_utf8_lossy(data).into_owned()
+            };
+            let name = name.trim_right_matches('\0');
+            if name.is_empty() {
+                Vec::new()
+            } else {
+                vec![file_item(name)]
+            }
+        }
+        Format::FilenamesPlist => plist_strings(&decode_text(data))
+            .iter()
+            .map(|name| file_item(name))
+            .collect(),
+        Format::DirectSave => {
+            let name = decode_text(data);
+            let name = name.trim_right_matches('\0');
+            if name.is_empty() {
+                Vec::new()
+            } else {
+                vec![Item::Promise(name.to_string())]
+            }
+        }
+        Format::Text { latin1 } => {
+            let text = if latin1 {
+                data.iter().map(|&b| b as char).collect()
+            } else {
+                decode_text(data)
+            };
+            let lines = uri_lines(&text);
+            if !lines.is_empty() && text.lines().all(|line| {
+                let line = line.trim();
+                line.is_empty() || is_url_or_file(line)
+            }) {
+                lines
+                    .iter()
+                    .map(|line| url_item(line, None, is_local).unwrap_or_else(|| file_item(line)))
+                    .collect()
+            } else if text.is_empty() {
+                Vec::new()
+            } else {
+                vec![Item::Text(text)]
+            }
+        }
+    }
+}
+
+/// Return the host name of this machine, as `system-name' does, in
+/// lowercase.
+fn system_name() -> String {
+    call!(intern("system-name"))
+        .as_string()
+        .map_or(String::new(), |name| {
+            String::from_utf8_lossy(name.as_slice()).to_lowercase()
+        })
+}
+
+/// Return NAME, the bytes of a file name, as a Lisp string.  Names that
+/// are not UTF-8 are decoded with the coding system for file names.
+fn file_name_string(name: &[u8]) -> LispObject {
+    match ::std::str::from_utf8(name) {
+        Ok(name) => lisp_string(name),
+        Err(_) => {
+            let coding = call!(intern("symbol-value"), intern("file-name-coding-system"));
+            let coding = if coding.is_nil() {
+                call!(intern("symbol-value"), intern("default-file-name-coding-system"))
+            } else {
+                coding
+            };
+            call!(intern("decode-coding-string"), unibyte_string(name), coding)
+        }
+    }
+}
+
+fn item_to_lisp(item: &Item) -> LispObject {
+    let optional = |value: &Option<String>| {
+        value
+            .as_ref()
+            .map_or_else(LispObject::constant_nil, |value| lisp_string(value))
+    };
+    match *item {
+        Item::File { ref name, ref url } => list!(
+            intern(":kind"),
+            intern("file"),
+            intern(":file"),
+            file_name_string(name),
+            intern(":url"),
+            lisp_string(url)
+        ),
+        Item::Url {
+            ref url,
+            ref scheme,
+            ref host,
+            ref title,
+        } => list!(
+            intern(":kind"),
+            intern("url"),
+            intern(":url"),
+            lisp_string(url),
+            intern(":scheme"),
+            lisp_string(scheme),
+            intern(":host"),
+            optional(host),
+            intern(":title"),
+            optional(title)
+        ),
+        Item::Text(ref text) => list!(intern(":kind"), intern("text"), intern(":text"), lisp_string(text)),
+        Item::Promise(ref name) => list!(
+            intern(":kind"),
+            intern("promise"),
+            intern(":name"),
+            lisp_string(name)
+        ),
+    }
+}
+
+/// Parse DATA, the data of a drop of type TYPE, into a list of items.
+/// TYPE is a MIME type such as "text/uri-list" or "text/plain", or the
+/// name of an X selection target, Windows clipboard format or macOS
+/// pasteboard type, such as "FILE_NAME" or "NSFilenamesPboardType".
+/// DATA is a string; data in UTF-16, such as that of "text/x-moz-url",
+/// must be passed as a unibyte string.
+///
+/// Each item is a plist whose `:kind' says what it is:
+///
+/// `file'    A local file, with its name as `:file' and its file URL as
+///           `:url'.  File URLs with the host name of this machine or
+///           \"localhost\" are local.
+/// `url'     Anything else with a URL, given as `:url', with its
+///           `:scheme', `:host', if any, and `:title', if the drop
+///           has one.
+/// `text'    Text that is not a list of URLs or file names, as `:text'.
+/// `promise' A file that the source of the drop is going to write, with
+///           its name, without a directory, as `:name'.
+///
+/// Plain text whose lines are all URLs or names of existing files is a
+/// list of those, one item for each line.  Signal an error if TYPE is
+/// not a known type of drop.
+#[lisp_fn]
+pub fn dnd_parse_payload(drop_type: LispObject, data: LispObject) -> LispObject {
+    let type_name = drop_type.as_string_or_error();
+    let type_name = String::from_utf8_lossy(type_name.as_slice()).into_owned();
+    let format = match format(&type_name) {
+        Some(format) => format,
+        None => xsignal!(Qerror, lisp_string("Unknown drag-and-drop type"), drop_type),
+    };
+    let bytes = urls::utf8_bytes(data);
+    let system = system_name();
+    let short = system.split('.').next().unwrap_or("").to_string();
+    let is_local = |host: &str| {
+        let host = host.to_lowercase();
+        host == "localhost" || host == system || host == short
+    };
+    let items = parse(format, &bytes, &is_local);
+    items
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, item| {
+            LispObject::cons(item_to_lisp(item), tail)
+        })
+}
+
+#[cfg(test)]
+fn test_is_local(host: &str) -> bool {
+    host == "localhost" || host == "here"
+}
+
+#[test]
+fn test_decode_text() {
+    assert_eq!(decode_text(b"plain"), "plain");
+    assert_eq!(decode_text(b"h\0i\0\n\0"), "hi\n");
+    assert_eq!(decode_text(b"\xfe\xff\0h\0i"), "hi");
+    assert_eq!(decode_text(b"\xff\xfeh\0i\0\0\0"), "hi");
+}
+
+#[test]
+fn test_file_url_name() {
+    let name = |url| file_url_name(url, test_is_local);
+    assert_eq!(name("file:///tmp/a%20b"), Some(Ok(b"/tmp/a b".to_vec())));
+    assert_eq!(name("file:/tmp/x"), Some(Ok(b"/tmp/x".to_vec())));
+    assert_eq!(name("FILE://here/etc/passwd"), Some(Ok(b"/etc/passwd".to_vec())));
+    assert_eq!(name("file:///C:/Users/x"), Some(Ok(b"C:/Users/x".to_vec())));
+    assert_eq!(name("file://there/share"), Some(Err("there".to_string())));
+    assert_eq!(name("http://here/x"), None);
+}
+
+#[test]
+fn test_parse() {
+    let parse = |format, data: &[u8]| parse(format, data, &test_is_local);
+    let file = |name: &str, url: &str| Item::File {
+        name: name.as_bytes().to_vec(),
+        url: url.to_string(),
+    };
+    assert_eq!(
+        parse(
+            Format::UriList,
+            b"# comment\r\nfile:///a/b\r\nhttps://gnu.org/x\r\n\r\nfile://there/c\r\n"
+        ),
+        vec![
+            file("/a/b", "file:///a/b"),
+            Item::Url {
+                url: "https://gnu.org/x".to_string(),
+                scheme: "https".to_string(),
+                host: Some("gnu.org".to_string()),
+                title: None,
+            },
+            Item::Url {
+                url: "file://there/c".to_string(),
+                scheme: "file".to_string(),
+                host: Some("there".to_string()),
+                title: None,
+            },
+        ]
+    );
+    assert_eq!(
+        parse(Format::GnomeFiles, b"cut\nfile:///x\nfile:///y"),
+        vec![file("/x", "file:///x"), file("/y", "file:///y")]
+    );
+    let moz: Vec<u8> = "https://gnu.org\nGNU\n"
+        .encode_utf16()
+        .flat_map(|unit| vec![unit as u8, (unit >> 8) as u8])
+        .collect();
+    assert_eq!(
+        parse(Format::MozUrl, &moz),
+        vec![Item::Url {
+            url: "https://gnu.org".to_string(),
+            scheme: "https".to_string(),
+            host: Some("gnu.org".to_string()),
+            title: Some("GNU".to_string()),
+        }]
+    );
+    assert_eq!(
+        parse(Format::FileName { wide: false }, b"/tmp/a b"),
+        vec![file("/tmp/a b", "file:///tmp/a%20b")]
+    );
+    assert_eq!(
+        parse(
+            Format::FilenamesPlist,
+            b"<plist><array><string>/a&amp;b</string><string>/c</string></array></plist>"
+        ),
+        vec![file("/a&b", "file:///a%26b"), file("/c", "file:///c")]
+    );
+    assert_eq!(
+        parse(Format::DirectSave, b"report.pdf"),
+        vec![Item::Promise("report.pdf".to_string())]
+    );
+    assert_eq!(
+        parse(Format::Text { latin1: false }, b"hello\nworld"),
+        vec![Item::Text("hello\nworld".to_string())]
+    );
+    assert_eq!(
+        parse(Format::Text { latin1: true }, b"caf\xe9"),
+        vec![Item::Text("caf\u{e9}".to_string())]
+    );
+    assert_eq!(
+        parse(Format::Text { latin1: false }, b"/\nfile:///x\n"),
+        vec![file("/", "file:///"), file("/x", "file:///x")]
+    );
+    assert_eq!(parse(Format::Text { latin1: false }, b""), vec![]);
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(format("text/plain;charset=utf-8"), Some(Format::Text { latin1: false }));
+    assert_eq!(format("text/uri-list"), Some(Format::UriList));
+    assert_eq!(format("FileNameW"), Some(Format::FileName { wide: true }));
+    assert_eq!(format("image/png"), None);
+    assert!(is_url_or_file("mailto:rms@gnu.org"));
+    assert!(!is_url_or_file("note: this"));
+    assert!(!is_url_or_file("C:/x"));
+}
+
+include!(concat!(env!("OUT_DIR"), "/dnd_exports.rs"));
@@ -0,0 +1,561 @@
+//! Documentation strings, as stored in the DOC file and in .elc files.
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use libc;
+use libc::{c_char, c_int, c_uchar, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{EmacsInt, Lisp_Object, PseudovecType};
+use remacs_sys::{Fdocumentation_property, Ffile_name_absolute_p, Fread,
+                 Fsubstitute_command_keys, Qautoload, Qclosure, Qfunction_documentation,
+                 Qinvalid_function, Qkeymap, Qlambda, Qmacro, Qnil};
+use remacs_sys::{build_string, encode_file_name, make_string_from_bytes, make_unibyte_string,
+                 multibyte_chars_in_text, report_file_error, reread_doc_file};
+use remacs_sys::globals;
+
+use data::indirect_function_lisp;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{car, car_safe, cdr, get};
+use util::path_from_bytes;
+
+/// The most documentation files kept mapped at once.  Nearly every
+/// lookup hits the DOC file; the others are .elc files with dynamic
+/// docstrings, of which only a handful tend to be in use.
+const DOC_FILE_CACHE_SIZE: usize = 16;
+
+/// Where to look for the DOC file while preparing to dump, when it is
+/// probably not installed yet.
+const SIBLING_ETC: &[u8] = b"../etc/";
+
+/// Index of the docstring in a byte-code object.  Equivalent to
+/// `COMPILED_DOC_STRING` in C.
+const COMPILED_DOC_STRING: ptrdiff_t = 4;
+
+/// The contents of a file, mapped into memory where that is possible.
+pub enum FileContents {
+    #[cfg(unix)]
+    Mapped(*mut libc::c_void, usize),
+    Read(Vec<u8>),
+}
+
+// The mapping is read-only and owned by the cache.
+unsafe impl Send for FileContents {}
+
+impl FileContents {
+    #[cfg(unix)]
+    pub fn load(file: &File, len: usize) -> io::Result<FileContents> {
+        use std::os::unix::io::AsRawFd;
+
+        if len == 0 {
+            return Ok(FileContents::Read(Vec::new()));
+        }
+
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(FileContents::Mapped(addr, len))
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn load(file: &File, len: usize) -> io::Result<FileContents> {
+        use std::io::Read;
+
+        let mut contents = Vec::with_capacity(len);
+        let mut file = file;
+        file.read_to_end(&mut contents)?;
+        Ok(FileContents::Read(contents))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        match *self {
+            #[cfg(unix)]
+            FileContents::Mapped(addr, len) => unsafe {
+                slice::from_raw_parts(addr as *const u8, len)
+            },
+            FileContents::Read(ref contents) => contents,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for FileContents {
+    fn drop(&mut self) {
+        if let FileContents::Mapped(addr, len) = *self {
+            unsafe { libc::munmap(addr, len) };
+        }
+    }
+}
+
+/// A cached documentation file, along with what we knew about it on
+/// disk when it was mapped.
+struct DocFile {
+    name: Vec<u8>,
+    len: u64,
+    modified: Option<SystemTime>,
+    contents: FileContents,
+}
+
+lazy_static! {
+    /// Recently used documentation files, most recently used last.
+    static ref DOC_FILE_CACHE: Mutex<Vec<DocFile>> = Mutex::new(Vec::new());
+}
+
+/// Call F with the contents of the documentation file NAME, an encoded
+/// file name.
+///
+/// Files are mapped into memory and kept in a small cache, so that a
+/// lookup in a file we have seen before only costs a `stat'.  A cached
+/// file is mapped again if its size or modification time has changed,
+/// so a DOC file rebuilt by `make-docfile' is picked up at the next
+/// lookup.  Only a file truncated in the middle of a lookup could still
+/// fault, as with the .elc files the loader maps.  Nothing is cached
+/// while preparing to dump, since the mappings would not survive into
+/// the dumped Emacs.
+///
+/// F is called with the cache locked, so it must not call back into
+/// Lisp.
+fn with_doc_file<F, T>(name: &[u8], f: F) -> io::Result<T>
+where
+    F: FnOnce(&[u8]) -> T,
+{
+    let path = path_from_bytes(name);
+    let metadata = fs::metadata(&path)?;
+    let modified = metadata.modified().ok();
+
+    let mut cache = DOC_FILE_CACHE.lock().unwrap();
+    let cached = cache.iter().position(|doc| doc.name == name);
+    if let Some(index) = cached {
+        let doc = cache.remove(index);
+        if doc.len == metadata.len() && doc.modified == modified {
+            cache.push(doc);
+            return Ok(f(cache[cache.len() - 1].contents.as_slice()));
+        }
+    }
+
+    let file = File::open(&path)?;
+    let contents = FileContents::load(&file, metadata.len() as usize)?;
+
+    if LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil() {
+        return Ok(f(contents.as_slice()));
+    }
+
+    if cache.len() >= DOC_FILE_CACHE_SIZE {
+        cache.remove(0);
+    }
+    cache.push(DocFile {
+        name: name.to_vec(),
+        len: metadata.len(),
+        modified,
+        contents,
+    });
+    Ok(f(cache[cache.len() - 1].contents.as_slice()))
+}
+
+/// Return true if BEFORE, the text preceding a docstring in the DOC
+/// file, ends with the ^_NAME\n header of an entry.
+fn is_doc_start(before: &[u8]) -> bool {
+    match before.split_last() {
+        Some((&b'\n', rest)) => rest.iter()
+            .rposition(|&b| b <= b' ')
+            .map_or(false, |i| rest[i] == 0o37),
+        _ => false,
+    }
+}
+
+/// Return true if BEFORE, the text preceding a dynamic docstring in an
+/// .elc file, ends either with the start of a "#@ comment" or with a
+/// docstring delimiter (in case several docstrings are packed in the
+/// same comment).
+fn is_dynamic_doc_start(before: &[u8]) -> bool {
+    match before.split_last() {
+        Some((&0o37, _)) => true,
+        Some((&b' ', rest)) => {
+            let digits = rest.iter()
+                .rev()
+                .take_while(|&&b| b'0' <= b && b <= b'9')
+                .count();
+            rest[..rest.len() - digits].ends_with(b"#@")
+        }
+        _ => false,
+    }
+}
+
+/// Undo the quoting done by make-docfile and the byte compiler: ^A^A
+/// stands for ^A, ^A0 for a null byte and ^A_ for ^_.  If ^A is
+/// followed by anything else, return that byte as the error.
+fn unquote_doc_string(text: &[u8]) -> Result<Vec<u8>, u8> {
+    let mut result = Vec::with_capacity(text.len());
+    let mut bytes = text.iter();
+    while let Some(&b) = bytes.next() {
+        if b == 1 {
+            match bytes.next() {
+                Some(&1) => result.push(1),
+                Some(&b'0') => result.push(0),
+                Some(&b'_') => result.push(0o37),
+                other => return Err(other.map_or(0, |&c| c)),
+            }
+        } else {
+            result.push(b);
+        }
+    }
+    Ok(result)
+}
+
+/// Extract the docstring starting at POSITION in CONTENTS, the text of
+/// a documentation file.  DYNAMIC says whether this is an .elc file
+/// rather than the DOC file.
+///
+/// Return `Ok(None)` if POSITION does not point to the beginning of a
+/// docstring, e.g. because the file has been modified and the position
+/// is stale.
+fn extract_doc_string(
+    contents: &[u8],
+    position: usize,
+    dynamic: bool,
+) -> Result<Option<Vec<u8>>, u8> {
+    if position > contents.len() {
+        return Ok(None);
+    }
+
+    let (before, text) = contents.split_at(position);
+    let valid = if dynamic {
+        is_dynamic_doc_start(before)
+    } else {
+        is_doc_start(before)
+    };
+    if !valid {
+        return Ok(None);
+    }
+
+    let end = text.iter().position(|&b| b == 0o37).unwrap_or(text.len());
+    unquote_doc_string(&text[..end]).map(Some)
+}
+
+/// The bytes being read by `read_bytecode_char'.
+static mut READ_BYTECODE_POINTER: *const c_uchar = 0 as *const c_uchar;
+
+/// `readchar' in lread.c calls back here to fetch the next byte of a
+/// function definition being read by `get_doc_string'.
+/// If UNREADFLAG is true, we unread a byte.
+#[no_mangle]
+pub extern "C" fn read_bytecode_char(unreadflag: bool) -> c_int {
+    unsafe {
+        if unreadflag {
+            READ_BYTECODE_POINTER = READ_BYTECODE_POINTER.offset(-1);
+            return 0;
+        }
+        let c = *READ_BYTECODE_POINTER;
+        READ_BYTECODE_POINTER = READ_BYTECODE_POINTER.offset(1);
+        c_int::from(c)
+    }
+}
+
+/// Extract a doc string from a file.  FILEPOS says where to get it.
+/// If it is an integer, use that position in the standard DOC file.
+/// If it is (FILE . INTEGER), use FILE as the file name
+/// and INTEGER as the position in that file.
+/// But if INTEGER is negative, make it positive.
+/// (A negative integer is used for user variables, so we can distinguish
+/// them without actually fetching the doc string.)
+///
+/// If the location does not point to the beginning of a docstring
+/// (e.g. because the file has been modified and the location is stale),
+/// return nil.
+///
+/// If UNIBYTE, always make a unibyte string.
+///
+/// If DEFINITION, assume this is for reading
+/// a dynamic function definition; convert the bytestring
+/// and the constants vector with appropriate byte handling,
+/// and return a cons cell.
+#[no_mangle]
+pub extern "C" fn get_doc_string(
+    filepos: Lisp_Object,
+    unibyte: bool,
+    definition: bool,
+) -> Lisp_Object {
+    let filepos = LispObject::from(filepos);
+    let (file, pos) = if filepos.is_integer() {
+        (LispObject::from(unsafe { globals.f_Vdoc_file_name }), filepos)
+    } else if let Some(cons) = filepos.as_cons() {
+        (cons.car(), cons.cdr())
+    } else {
+        return Qnil;
+    };
+
+    let position = pos.as_fixnum_or_error().abs() as usize;
+    let doc_directory = LispObject::from(unsafe { globals.f_Vdoc_directory });
+    if !doc_directory.is_string() || !file.is_string() {
+        return Qnil;
+    }
+
+    // If the file name is relative, combine it with `doc-directory'.
+    let absolute = LispObject::from(unsafe { Ffile_name_absolute_p(file.to_raw()) }).is_not_nil();
+    let encoded = LispObject::from(unsafe { encode_file_name(file.to_raw()) });
+    let encoded = encoded.as_string_or_error();
+    let mut name = Vec::new();
+    if !absolute {
+        let docdir = LispObject::from(unsafe { encode_file_name(doc_directory.to_raw()) });
+        name.extend_from_slice(docdir.as_string_or_error().as_slice());
+    }
+    name.extend_from_slice(encoded.as_slice());
+
+    let dynamic = filepos.is_cons();
+    let extract = |contents: &[u8]| extract_doc_string(contents, position, dynamic);
+    let mut result = with_doc_file(&name, &extract);
+    if result.is_err() && LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil() {
+        // Preparing to dump; DOC file is probably not installed.
+        // So check in ../etc.
+        result = with_doc_file(&[SIBLING_ETC, encoded.as_slice()].concat(), &extract);
+    }
+
+    let text = match result {
+        Ok(Ok(Some(text))) => text,
+        Ok(Ok(None)) => return Qnil,
+        Ok(Err(code)) => error!(
+            "Invalid data in documentation file -- \u{1} followed by code {:03o}",
+            code
+        ),
+        Err(e) => {
+            if e.raw_os_error() == Some(libc::EMFILE) || e.raw_os_error() == Some(libc::ENFILE) {
+                let msg = b"Read error on documentation file\0";
+                unsafe { report_file_error(msg.as_ptr() as *const c_char, file.to_raw()) };
+            }
+            let (cannot_open, quote_nl) = unsafe {
+                (
+                    build_string(b"Cannot open doc string file \"\0".as_ptr() as *const c_char),
+                    build_string(b"\"\n\0".as_ptr() as *const c_char),
+                )
+            };
+            return call!(
+                intern("concat"),
+                LispObject::from(cannot_open),
+                file,
+                LispObject::from(quote_nl)
+            ).to_raw();
+        }
+    };
+
+    // Make the Lisp string and drop TEXT before anything that may
+    // signal, which would leak it.
+    let ptr = text.as_ptr() as *const c_char;
+    let nbytes = text.len() as ptrdiff_t;
+    let string = if definition || unibyte {
+        unsafe { make_unibyte_string(ptr, nbytes) }
+    } else {
+        // The data determines whether the string is multibyte.
+        unsafe {
+            let nchars = multibyte_chars_in_text(ptr as *const c_uchar, nbytes);
+            make_string_from_bytes(ptr, nchars, nbytes)
+        }
+    };
+    drop(text);
+
+    if definition {
+        // Read the definition from the string the same way we would read
+        // bytes from a file.  Lisp strings end with a null byte, and the
+        // reader runs no Lisp, so the data stays where it is meanwhile.
+        let string = LispObject::from(string).as_string_or_error();
+        unsafe {
+            READ_BYTECODE_POINTER = string.const_data_ptr();
+            Fread(Qlambda)
+        }
+    } else {
+        string
+    }
+}
+
+/// What `documentation' found for a function.
+enum FunctionDoc {
+    /// A docstring or a reference to one in a documentation file.
+    Doc(LispObject),
+    /// Documentation to be returned as is.
+    Final(LispObject),
+}
+
+/// Find the documentation of FUNCTION, without fetching it from a
+/// documentation file.
+fn function_doc(function: LispObject) -> FunctionDoc {
+    let mut fun = indirect_function_lisp(function, LispObject::constant_nil());
+    if let Some(cons) = fun.as_cons() {
+        if cons.car().eq(LispObject::from(Qmacro)) {
+            fun = cons.cdr();
+        }
+    }
+
+    if let Some(subr) = fun.as_subr() {
+        return FunctionDoc::Doc(LispObject::from_fixnum_truncated(subr.doc as EmacsInt));
+    }
+
+    if let Some(vl) = fun.as_vectorlike() {
+        if vl.is_pseudovector(PseudovecType::PVEC_MODULE_FUNCTION) {
+            // The documentation is the first slot of a module function.
+            return FunctionDoc::Doc(unsafe { vl.as_vector_unchecked().get_unchecked(0) });
+        } else if vl.is_pseudovector(PseudovecType::PVEC_COMPILED) {
+            if vl.pseudovector_size() <= COMPILED_DOC_STRING as EmacsInt {
+                return FunctionDoc::Final(LispObject::constant_nil());
+            }
+            let tem = unsafe { vl.as_vector_unchecked().get_unchecked(COMPILED_DOC_STRING) };
+            return if tem.is_string() || tem.is_natnum() || tem.is_cons() {
+                FunctionDoc::Doc(tem)
+            } else {
+                FunctionDoc::Final(LispObject::constant_nil())
+            };
+        } else if vl.is_vector() {
+            return FunctionDoc::Final(keyboard_macro_doc());
+        }
+    }
+
+    if fun.is_string() {
+        return FunctionDoc::Final(keyboard_macro_doc());
+    }
+
+    if let Some(cons) = fun.as_cons() {
+        let funcar = cons.car();
+        if !funcar.is_symbol() {
+            xsignal!(Qinvalid_function, fun);
+        } else if funcar.eq(LispObject::from(Qkeymap)) {
+            let doc = b"Prefix command (definition is a keymap associating keystrokes with \
+                        commands).\0";
+            let doc = unsafe { build_string(doc.as_ptr() as *const c_char) };
+            return FunctionDoc::Final(LispObject::from(doc));
+        } else if funcar.eq(LispObject::from(Qlambda)) || funcar.eq(LispObject::from(Qclosure))
+            || funcar.eq(LispObject::from(Qautoload))
+        {
+            let fun = if funcar.eq(LispObject::from(Qclosure)) {
+                cons.cdr()
+            } else {
+                fun
+            };
+            let tem1 = cdr(cdr(fun));
+            let tem = car(tem1);
+            // Handle a doc reference--but these never come last in the
+            // function body, so reject them if they are last.
+            let is_reference = tem.is_natnum()
+                || tem.as_cons().map_or(false, |c| c.cdr().is_integer());
+            return if tem.is_string() || (is_reference && cdr(tem1).is_not_nil()) {
+                FunctionDoc::Doc(tem)
+            } else {
+                FunctionDoc::Final(LispObject::constant_nil())
+            };
+        }
+    }
+
+    xsignal!(Qinvalid_function, fun)
+}
+
+fn keyboard_macro_doc() -> LispObject {
+    LispObject::from(unsafe { build_string(b"Keyboard macro.\0".as_ptr() as *const c_char) })
+}
+
+/// Return the documentation string of FUNCTION.
+/// Unless a non-nil second argument RAW is given, the
+/// string is passed through `substitute-command-keys'.
+#[lisp_fn(min = "1")]
+pub fn documentation(function: LispObject, raw: LispObject) -> LispObject {
+    if function.is_symbol() && get(function, LispObject::from(Qfunction_documentation)).is_not_nil()
+    {
+        return LispObject::from(unsafe {
+            Fdocumentation_property(function.to_raw(), Qfunction_documentation, raw.to_raw())
+        });
+    }
+
+    let mut try_reload = true;
+    let doc = loop {
+        let doc = match function_doc(function) {
+            FunctionDoc::Doc(doc) => doc,
+            FunctionDoc::Final(doc) => return doc,
+        };
+
+        // If DOC is 0, it's typically because of a dumped file missing
+        // from the DOC file (bug in src/Makefile.in).
+        if doc.eq(LispObject::from_fixnum(0)) {
+            break LispObject::constant_nil();
+        }
+        if !doc.is_integer() && !doc.is_cons() {
+            break doc;
+        }
+
+        let tem = LispObject::from(get_doc_string(doc.to_raw(), false, false));
+        if tem.is_nil() && try_reload {
+            // The file is newer, we need to reset the pointers.
+            unsafe { reread_doc_file(car_safe(doc).to_raw()) };
+            try_reload = false;
+        } else {
+            break tem;
+        }
+    };
+
+    if raw.is_nil() {
+        LispObject::from(unsafe { Fsubstitute_command_keys(doc.to_raw()) })
+    } else {
+        doc
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/doc_exports.rs"));
+
+#[test]
+fn test_is_doc_start() {
+    assert!(is_doc_start(b"\x1fFcar\n"));
+    assert!(is_doc_start(b"text\x1fVfoo-bar\n"));
+    assert!(!is_doc_start(b"\x1fFcar"));
+    assert!(!is_doc_start(b"Fcar\n"));
+    assert!(!is_doc_start(b"\n"));
+}
+
+#[test]
+fn test_is_dynamic_doc_start() {
+    assert!(is_dynamic_doc_start(b"#@12 "));
+    assert!(is_dynamic_doc_start(b"(defun foo () #@ "));
+    assert!(is_dynamic_doc_start(b"first docstring\x1f"));
+    assert!(!is_dynamic_doc_start(b"#12 "));
+    assert!(!is_dynamic_doc_start(b"#@12"));
+    assert!(!is_dynamic_doc_start(b""));
+}
+
+#[test]
+fn test_unquote_doc_string() {
+    assert_eq!(unquote_doc_string(b"plain"), Ok(b"plain".to_vec()));
+    assert_eq!(
+        unquote_doc_string(b"a\x01\x01b\x010c\x01_"),
+        Ok(b"a\x01b\x00c\x1f".to_vec())
+    );
+    assert_eq!(unquote_doc_string(b"bad\x01x"), Err(b'x'));
+    assert_eq!(unquote_doc_string(b"bad\x01"), Err(0));
+}
+
+#[test]
+fn test_extract_doc_string() {
+    let contents = b"\x1fFcar\nReturn the car.\x1fFcdr\nReturn the cdr.\n";
+    assert_eq!(
+        extract_doc_string(contents, 6, false),
+        Ok(Some(b"Return the car.".to_vec()))
+    );
+    assert_eq!(
+        extract_doc_string(contents, 27, false),
+        Ok(Some(b"Return the cdr.\n".to_vec()))
+    );
+    // A stale position.
+    assert_eq!(extract_doc_string(contents, 8, false), Ok(None));
+    assert_eq!(extract_doc_string(contents, 100, false), Ok(None));
+}
@@ -0,0 +1,824 @@
+//! Loading byte-compiled files.
+//!
+//! When `load-use-native-elc' is non-nil, `load' hands the body of a
+//! .elc file to `load_elc_stream', which maps the file into memory and
+//! reads its forms straight from the mapping.  The byte-compiler only
+//! writes a small part of the read syntax, and that part is read here
+//! without going through `readchar' one character at a time.  A form
+//! that uses anything else is read by the C reader instead, from the
+//! stream `load' has open on the same file.
+
+use std::borrow::Cow;
+use std::f64;
+use std::fs::File;
+use std::str;
+
+use libc::{c_char, c_void, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{EmacsInt, Lisp_Object, MOST_NEGATIVE_FIXNUM, MOST_POSITIVE_FIXNUM};
+use remacs_sys::{Qcurrent_load_list, Qinternal_interpreter_environment, Qlexical_binding, Qnil,
+                 Qunbound};
+use remacs_sys::{build_load_history, encode_file_name, eval_sub, find_symbol_value,
+                 make_string_from_bytes, make_unibyte_string, read_elc_form_at,
+                 record_unwind_protect_ptr, report_file_error, unbind_to, Fcons, Fmake_byte_code,
+                 Fmake_symbol, Fvector};
+use remacs_sys::{globals, initialized};
+
+use doc::FileContents;
+use eval_call::{specbind, specpdl_index, with_let_binding};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::assq;
+use lread::read_extended_object;
+use multibyte::{char_string, multibyte_char_at, raw_byte_codepoint, str_as_unibyte, Codepoint,
+                MAX_5_BYTE_CHAR, MAX_CHAR, MAX_MULTIBYTE_LENGTH};
+use obarray::LispObarrayRef;
+use symbols::fboundp;
+use util::path_from_bytes;
+
+/// The magic number at the start of every .elc file.
+const ELC_MAGIC: &[u8] = b";ELC";
+
+/// The oldest .elc format whose forms are encoded in `utf-8-emacs'.
+/// Files compiled by older Emacsen use `emacs-mule', and are left to
+/// the C reader.
+const MIN_ELC_VERSION: u8 = 22;
+
+/// The characters besides whitespace that end a symbol.
+const SYMBOL_DELIMITERS: &[u8] = b"\"';()[]#`,";
+
+/// The characters besides whitespace that make a lone `.' the dot of a
+/// dotted pair rather than the start of a symbol or number.
+const DOT_DELIMITERS: &[u8] = b"\"';([#?`,";
+
+/// Quotation marks that the reader refuses at the start of a symbol,
+/// since they are almost always a mistyped `''.
+const STRANGE_QUOTES: [Codepoint; 9] = [
+    0x2018, 0x2019, 0x201B, 0x201C, 0x201D, 0x201F, 0x301E, 0xFF02, 0xFF07,
+];
+
+/// Check the header of the .elc file CONTENTS, and return the major
+/// version of the Emacs that compiled it.
+fn elc_version(contents: &[u8]) -> Result<u8, &'static str> {
+    if contents.len() < 5 || !contents.starts_with(ELC_MAGIC) {
+        Err("not a byte-compiled file")
+    } else if contents[4] < MIN_ELC_VERSION {
+        Err("compiled by a version of Emacs older than 22")
+    } else {
+        Ok(contents[4])
+    }
+}
+
+fn is_digit(byte: u8) -> bool {
+    byte >= b'0' && byte <= b'9'
+}
+
+fn hex_digit_value(byte: u8) -> Option<u32> {
+    (byte as char).to_digit(16)
+}
+
+/// Return the length of the multibyte sequence at the start of BYTES,
+/// or None if it is truncated or malformed.
+fn multibyte_length(bytes: &[u8]) -> Option<usize> {
+    let (len, second_max) = match bytes[0] {
+        0x00...0x7F => return Some(1),
+        0xC0...0xDF => (2, 0xBF),
+        0xE0...0xEF => (3, 0xBF),
+        0xF0...0xF7 => (4, 0xBF),
+        0xF8 => (5, 0x8F),
+        _ => return None,
+    };
+    if bytes.len() < len || bytes[1] < 0x80 || bytes[1] > second_max {
+        return None;
+    }
+    if bytes[2..len].iter().all(|&b| b >= 0x80 && b <= 0xBF) {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+/// Return the number of characters in BYTES, or None if they are not
+/// valid multibyte text.
+fn multibyte_chars(mut bytes: &[u8]) -> Option<usize> {
+    let mut chars = 0;
+    while !bytes.is_empty() {
+        match multibyte_length(bytes) {
+            Some(len) => bytes = &bytes[len..],
+            None => return None,
+        }
+        chars += 1;
+    }
+    Some(chars)
+}
+
+#[derive(Debug, PartialEq)]
+enum Number {
+    Integer(EmacsInt),
+    Float(f64),
+}
+
+/// Parse TOKEN as a decimal number, the way `string_to_number' does.
+/// Return None if it is not the read syntax of a number, in which case
+/// it is a symbol.
+fn parse_number(token: &[u8]) -> Option<Number> {
+    let negative = token.first() == Some(&b'-');
+    let signed = negative || token.first() == Some(&b'+');
+    let body = &token[signed as usize..];
+    let digits = |s: &[u8]| s.iter().take_while(|&&b| is_digit(b)).count();
+
+    let lead = digits(body);
+    let mut pos = lead;
+    let dot = body.get(pos) == Some(&b'.');
+    if dot {
+        pos += 1;
+    }
+    let trail = digits(&body[pos..]);
+    pos += trail;
+
+    let mut exponent = false;
+    let mut special = None;
+    if body.get(pos) == Some(&b'e') || body.get(pos) == Some(&b'E') {
+        let after = &body[pos + 1..];
+        let sign = after.first() == Some(&b'+') || after.first() == Some(&b'-');
+        let exponent_digits = digits(&after[sign as usize..]);
+        if exponent_digits > 0 {
+            exponent = true;
+            pos += 1 + sign as usize + exponent_digits;
+        } else if after.starts_with(b"+INF") {
+            exponent = true;
+            special = Some(f64::INFINITY);
+            pos += 5;
+        } else if after.starts_with(b"+NaN") {
+            exponent = true;
+            special = Some(f64::NAN);
+            pos += 5;
+        }
+    }
+    if pos != body.len() {
+        return None;
+    }
+
+    let float_syntax = (dot && trail > 0) || (lead > 0 && !dot && trail == 0 && exponent);
+    if !float_syntax {
+        if lead == 0 || exponent {
+            return None;
+        }
+        let text = str::from_utf8(&body[..lead]).unwrap();
+        let limit = if negative {
+            -MOST_NEGATIVE_FIXNUM
+        } else {
+            MOST_POSITIVE_FIXNUM
+        };
+        return Some(match text.parse::<EmacsInt>() {
+            Ok(n) if n <= limit => Number::Integer(if negative { -n } else { n }),
+            _ => {
+                let value = text.parse::<f64>().unwrap();
+                Number::Float(if negative { -value } else { value })
+            }
+        });
+    }
+
+    let value = match special {
+        Some(value) => value,
+        None => match str::from_utf8(&body[..pos]).unwrap().parse::<f64>() {
+            Ok(value) => value,
+            Err(_) => return None,
+        },
+    };
+    Some(Number::Float(if negative { -value } else { value }))
+}
+
+/// Whether the byte at POS in CONTENTS ends a symbol.  A no-break space
+/// ends one too, although it takes two bytes.
+fn ends_symbol(contents: &[u8], pos: usize) -> bool {
+    match contents.get(pos) {
+        None => true,
+        Some(&b) if b <= b' ' => true,
+        Some(&b) if b < 0x80 => SYMBOL_DELIMITERS.contains(&b),
+        Some(&0xC2) => contents.get(pos + 1) == Some(&0xA0),
+        Some(_) => false,
+    }
+}
+
+fn push_char(text: &mut Vec<u8>, c: Codepoint) {
+    let mut buf = [0; MAX_MULTIBYTE_LENGTH];
+    let len = char_string(c, buf.as_mut_ptr());
+    text.extend_from_slice(&buf[..len as usize]);
+}
+
+/// The result of reading an object.  `Err' means the object uses syntax
+/// that this reader leaves to the C reader, or is malformed, in which
+/// case the C reader reports the error.
+type ReadResult = Result<LispObject, ()>;
+
+/// A reader for the forms of a .elc file.
+struct ElcReader<'a> {
+    contents: &'a [u8],
+    pos: usize,
+    /// An alist of the objects labelled with #N= in the current form.
+    labels: LispObject,
+}
+
+impl<'a> ElcReader<'a> {
+    fn new(contents: &'a [u8]) -> ElcReader<'a> {
+        ElcReader {
+            contents,
+            pos: 0,
+            labels: LispObject::constant_nil(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.contents.get(self.pos).cloned()
+    }
+
+    fn next_byte(&mut self) -> Result<u8, ()> {
+        let byte = self.peek().ok_or(())?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    /// Read the character at point, returning its code and its bytes.
+    fn next_char(&mut self) -> Result<(Codepoint, &'a [u8]), ()> {
+        let contents = self.contents;
+        let rest = &contents[self.pos..];
+        if rest.is_empty() {
+            return Err(());
+        }
+        let len = multibyte_length(rest).ok_or(())?;
+        self.pos += len;
+        Ok((multibyte_char_at(rest).0, &rest[..len]))
+    }
+
+    /// Skip whitespace, comments, and the text hidden by #@COUNT, which
+    /// is how the byte-compiler writes docstrings that are only loaded
+    /// when needed.  #@00 hides everything up to the end of the file.
+    fn skip_blanks(&mut self) {
+        while let Some(byte) = self.peek() {
+            if byte <= b' ' {
+                self.pos += 1;
+            } else if byte == 0xC2 && self.contents.get(self.pos + 1) == Some(&0xA0) {
+                self.pos += 2;
+            } else if byte == b';' {
+                match self.contents[self.pos..].iter().position(|&b| b == b'\n') {
+                    Some(newline) => self.pos += newline + 1,
+                    None => self.pos = self.contents.len(),
+                }
+            } else if self.contents[self.pos..].starts_with(b"#@00") {
+                self.pos = self.contents.len();
+            } else if self.contents[self.pos..].starts_with(b"#@") {
+                let start = self.pos + 2;
+                let count = self.contents[start..]
+                    .iter()
+                    .take_while(|&&b| is_digit(b))
+                    .count();
+                let skip = str::from_utf8(&self.contents[start..start + count])
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap_or(0);
+                // The count includes the byte that ends it.
+                self.pos = (start + count + skip).min(self.contents.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Read the next top-level form.
+    fn read_form(&mut self) -> ReadResult {
+        self.labels = LispObject::constant_nil();
+        self.read_object()
+    }
+
+    fn read_object(&mut self) -> ReadResult {
+        self.skip_blanks();
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                self.read_list(b')')
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let elements = self.read_list(b']')?;
+                let mut args: Vec<Lisp_Object> = elements.iter_cars().map(|e| e.to_raw()).collect();
+                Ok(LispObject::from(unsafe {
+                    Fvector(args.len() as ptrdiff_t, args.as_mut_ptr())
+                }))
+            }
+            Some(b'"') => {
+                self.pos += 1;
+                self.read_string()
+            }
+            Some(b'\'') => {
+                self.pos += 1;
+                let object = self.read_object()?;
+                Ok(list!(intern("quote"), object))
+            }
+            Some(b'#') => self.read_hash_syntax(),
+            // Backquotes depend on how deeply they are nested, and `?'
+            // characters have escapes of their own; the byte-compiler
+            // writes neither at top level.
+            None | Some(b')') | Some(b']') | Some(b'`') | Some(b',') | Some(b'?') => Err(()),
+            Some(_) => self.read_atom(),
+        }
+    }
+
+    /// Read the elements of a list or vector up to CLOSE, whose opening
+    /// bracket has been read.
+    fn read_list(&mut self, close: u8) -> ReadResult {
+        let mut head = LispObject::constant_nil();
+        let mut tail = LispObject::constant_nil();
+        loop {
+            self.skip_blanks();
+            let byte = self.peek().ok_or(())?;
+            if byte == close {
+                self.pos += 1;
+                return Ok(head);
+            }
+            if byte == b'.' && self.dot_ends_list() {
+                if close != b')' || tail.is_nil() {
+                    return Err(());
+                }
+                self.pos += 1;
+                let cdr = self.read_object()?;
+                self.skip_blanks();
+                if self.next_byte()? != b')' {
+                    return Err(());
+                }
+                tail.as_cons().unwrap().set_cdr(cdr);
+                return Ok(head);
+            }
+            let cell = LispObject::cons(self.read_object()?, LispObject::constant_nil());
+            match tail.as_cons() {
+                Some(tail) => tail.set_cdr(cell),
+                None => head = cell,
+            }
+            tail = cell;
+        }
+    }
+
+    /// Whether the `.' at point stands for the dot of a dotted pair.
+    fn dot_ends_list(&self) -> bool {
+        match self.contents.get(self.pos + 1) {
+            None => true,
+            Some(&b) => b <= b' ' || (b < 0x80 && DOT_DELIMITERS.contains(&b)),
+        }
+    }
+
+    /// Read a string, whose opening quote has been read.
+    fn read_string(&mut self) -> ReadResult {
+        let mut text = Vec::new();
+        let mut nchars = 0;
+        let mut force_multibyte = false;
+        let mut force_singlebyte = false;
+        let mut cancel = false;
+        loop {
+            let c = match self.next_byte()? {
+                b'"' => break,
+                b'\\' => match self.read_escape()? {
+                    Some(c) => {
+                        push_char(&mut text, c);
+                        c
+                    }
+                    None => {
+                        cancel |= text.is_empty();
+                        continue;
+                    }
+                },
+                _ => {
+                    self.pos -= 1;
+                    let (c, bytes) = self.next_char()?;
+                    text.extend_from_slice(bytes);
+                    c
+                }
+            };
+            if c > MAX_5_BYTE_CHAR {
+                force_singlebyte = true;
+            } else if c >= 0x80 {
+                force_multibyte = true;
+            }
+            nchars += 1;
+        }
+
+        // While dumping, a docstring that starts with \ newline is in
+        // etc/DOC, so it is replaced with the offset the doc code fills in.
+        let dumping = LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil();
+        let doc_file = LispObject::from(unsafe { globals.f_Vdoc_file_name });
+        if cancel && dumping && doc_file.is_nil() {
+            return Ok(LispObject::from_fixnum(0));
+        }
+
+        let string = if force_singlebyte && !force_multibyte {
+            let len = str_as_unibyte(text.as_mut_ptr(), text.len() as ptrdiff_t);
+            unsafe { make_unibyte_string(text.as_ptr() as *const c_char, len) }
+        } else if force_multibyte || text.len() != nchars {
+            unsafe {
+                make_string_from_bytes(
+                    text.as_ptr() as *const c_char,
+                    nchars as ptrdiff_t,
+                    text.len() as ptrdiff_t,
+                )
+            }
+        } else {
+            unsafe { make_unibyte_string(text.as_ptr() as *const c_char, text.len() as ptrdiff_t) }
+        };
+        Ok(LispObject::from(string))
+    }
+
+    /// Read the escape sequence after a backslash in a string.  Return
+    /// None for the escapes that stand for nothing, and give up on the
+    /// ones with modifier keys.
+    fn read_escape(&mut self) -> Result<Option<Codepoint>, ()> {
+        let c = match self.next_byte()? {
+            b'\n' | b' ' => return Ok(None),
+            b'a' => 0o7,
+            b'b' => 0o10,
+            b'd' => 0o177,
+            b'e' => 0o33,
+            b'f' => 0o14,
+            b'n' => 0o12,
+            b'r' => 0o15,
+            b's' => 0o40,
+            b't' => 0o11,
+            b'v' => 0o13,
+            b'M' | b'S' | b'H' | b'A' | b'C' | b'^' => return Err(()),
+            c @ b'0'...b'7' => {
+                let mut value = (c - b'0') as Codepoint;
+                for _ in 0..2 {
+                    match self.peek() {
+                        Some(d) if d >= b'0' && d <= b'7' => {
+                            value = value * 8 + (d - b'0') as Codepoint;
+                            self.pos += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                if value >= 0x80 && value < 0x100 {
+                    raw_byte_codepoint(value as u8)
+                } else {
+                    value
+                }
+            }
+            b'x' => {
+                let mut value: Codepoint = 0;
+                let mut count = 0;
+                while let Some(digit) = self.peek().and_then(hex_digit_value) {
+                    value = (value << 4) + digit;
+                    if value > MAX_CHAR {
+                        return Err(());
+                    }
+                    count += 1;
+                    self.pos += 1;
+                }
+                if count < 3 && value >= 0x80 {
+                    raw_byte_codepoint(value as u8)
+                } else {
+                    value
+                }
+            }
+            c @ b'u' | c @ b'U' => {
+                let count = if c == b'u' { 4 } else { 8 };
+                let mut value: Codepoint = 0;
+                for _ in 0..count {
+                    let digit = hex_digit_value(self.next_byte()?).ok_or(())?;
+                    value = (value << 4) + digit;
+                }
+                if value > 0x10_FFFF {
+                    return Err(());
+                }
+                value
+            }
+            _ => {
+                self.pos -= 1;
+                self.next_char()?.0
+            }
+        };
+        Ok(Some(c))
+    }
+
+    /// Read the text of a symbol or number, resolving backslashes.
+    /// Return it along with whether any of it was escaped.
+    fn read_token(&mut self) -> Result<(Cow<'a, [u8]>, bool), ()> {
+        let start = self.pos;
+        let contents = self.contents;
+        while !ends_symbol(contents, self.pos) && contents[self.pos] != b'\\' {
+            self.pos += 1;
+        }
+        if contents.get(self.pos) != Some(&b'\\') {
+            return Ok((Cow::Borrowed(&contents[start..self.pos]), false));
+        }
+
+        let mut token = contents[start..self.pos].to_vec();
+        while !ends_symbol(contents, self.pos) {
+            if contents[self.pos] == b'\\' {
+                self.pos += 1;
+                let (_, bytes) = self.next_char()?;
+                token.extend_from_slice(bytes);
+            } else {
+                token.push(contents[self.pos]);
+                self.pos += 1;
+            }
+        }
+        Ok((Cow::Owned(token), true))
+    }
+
+    /// Read a number or a symbol interned in `obarray'.
+    fn read_atom(&mut self) -> ReadResult {
+        let (token, quoted) = self.read_token()?;
+        if !quoted {
+            match parse_number(&token) {
+                Some(Number::Integer(n)) => return Ok(LispObject::from_fixnum(n)),
+                Some(Number::Float(f)) => return Ok(LispObject::from_float(f)),
+                None => {}
+            }
+            if multibyte_length(&token).is_some()
+                && STRANGE_QUOTES.contains(&multibyte_char_at(&token).0)
+            {
+                return Err(());
+            }
+        }
+
+        let mut obarray = LispObarrayRef::constant_obarray();
+        let dumping = LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil();
+        if token.iter().all(|&b| b < 0x80) && !dumping {
+            // Look the name up without making a string for it, since the
+            // symbol usually exists already.
+            let len = token.len() as ptrdiff_t;
+            Ok(obarray.intern_cstring(token.as_ptr() as *const c_char, len))
+        } else {
+            Ok(obarray.intern(symbol_name(&token)?))
+        }
+    }
+
+    /// Read the syntax that starts with `#'.
+    fn read_hash_syntax(&mut self) -> ReadResult {
+        let next = self.contents.get(self.pos + 1).cloned();
+        self.pos += 2;
+        match next {
+            Some(b'[') => {
+                let elements = self.read_list(b']')?;
+                let mut args: Vec<Lisp_Object> = elements.iter_cars().map(|e| e.to_raw()).collect();
+                if args.len() < 4 {
+                    return Err(());
+                }
+                Ok(LispObject::from(unsafe {
+                    Fmake_byte_code(args.len() as ptrdiff_t, args.as_mut_ptr())
+                }))
+            }
+            Some(b'\'') => {
+                let object = self.read_object()?;
+                Ok(list!(intern("function"), object))
+            }
+            Some(b'$') => Ok(LispObject::from(unsafe { globals.f_Vload_file_name })),
+            Some(b'#') => Ok(LispObarrayRef::constant_obarray().intern(symbol_name(b"")?)),
+            Some(b':') => {
+                let (token, _) = self.read_token()?;
+                let name = symbol_name(&token)?;
+                Ok(LispObject::from(unsafe { Fmake_symbol(name.to_raw()) }))
+            }
+            Some(b's') if self.peek() == Some(b'(') => {
+                self.pos += 1;
+                let form = self.read_list(b')')?;
+                Ok(LispObject::from(read_extended_object(form.to_raw())))
+            }
+            Some(b) if is_digit(b) => {
+                self.pos -= 1;
+                self.read_label()
+            }
+            _ => Err(()),
+        }
+    }
+
+    /// Read #N=OBJECT or #N#, whose `#' has been read.  These only
+    /// label objects when `read-circle' is non-nil.
+    fn read_label(&mut self) -> ReadResult {
+        if LispObject::from(unsafe { globals.f_Vread_circle }).is_nil() {
+            return Err(());
+        }
+        let start = self.pos;
+        while self.peek().map_or(false, is_digit) {
+            self.pos += 1;
+        }
+        let n = str::from_utf8(&self.contents[start..self.pos])
+            .unwrap()
+            .parse::<EmacsInt>()
+            .map_err(|_| ())?;
+        if n > MOST_POSITIVE_FIXNUM {
+            return Err(());
+        }
+        let number = LispObject::from_natnum(n);
+
+        match self.next_byte()? {
+            b'=' => {
+                let placeholder =
+                    LispObject::cons(LispObject::constant_nil(), LispObject::constant_nil());
+                self.labels = LispObject::cons(LispObject::cons(number, placeholder), self.labels);
+                let object = self.read_object()?;
+                if let Some(cons) = object.as_cons() {
+                    // References to the label inside OBJECT point at the
+                    // placeholder, so the placeholder becomes OBJECT.
+                    let placeholder = placeholder.as_cons().unwrap();
+                    placeholder.set_car(cons.car());
+                    placeholder.set_cdr(cons.cdr());
+                    Ok(placeholder.as_obj())
+                } else if object.is_symbol() || object.is_number() || object.is_string() {
+                    // These cannot refer back to the label.
+                    self.labels = LispObject::cons(LispObject::cons(number, object), self.labels);
+                    Ok(object)
+                } else {
+                    Err(())
+                }
+            }
+            b'#' => match assq(number, self.labels).as_cons() {
+                Some(label) => Ok(label.cdr()),
+                None => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+/// Make a Lisp string for the symbol name NAME, which is in the
+/// internal representation of multibyte text.
+fn symbol_name(name: &[u8]) -> ReadResult {
+    let nchars = multibyte_chars(name).ok_or(())?;
+    let string = if nchars == name.len() {
+        unsafe { make_unibyte_string(name.as_ptr() as *const c_char, name.len() as ptrdiff_t) }
+    } else {
+        unsafe {
+            make_string_from_bytes(
+                name.as_ptr() as *const c_char,
+                nchars as ptrdiff_t,
+                name.len() as ptrdiff_t,
+            )
+        }
+    };
+    Ok(LispObject::from(string))
+}
+
+/// Map the file named FILE into memory.
+fn map_file(file: LispObject) -> FileContents {
+    let encoded = LispObject::from(unsafe { encode_file_name(file.to_raw()) });
+    let path = path_from_bytes(encoded.as_string_or_error().as_slice());
+    let contents = File::open(&path).and_then(|f| {
+        let len = f.metadata()?.len() as usize;
+        FileContents::load(&f, len)
+    });
+    match contents {
+        Ok(contents) => contents,
+        Err(_) => {
+            let msg = b"Opening input file\0";
+            unsafe { report_file_error(msg.as_ptr() as *const c_char, file.to_raw()) }
+        }
+    }
+}
+
+/// Unmap a file once it is loaded, or when an error cuts loading short.
+unsafe extern "C" fn free_file_contents(contents: *mut c_void) {
+    drop(Box::from_raw(contents as *mut FileContents));
+}
+
+/// Read and evaluate the forms of the byte-compiled file FILE, which
+/// `load' has open as STREAM, and record its definitions in
+/// `load-history' under SOURCENAME.  This does what `readevalloop' does
+/// for a file, reading what forms it can from a mapping of the file.
+#[no_mangle]
+pub extern "C" fn load_elc_stream(stream: *mut c_void, file: Lisp_Object, sourcename: Lisp_Object) {
+    let file = LispObject::from(file);
+    let mut sourcename = LispObject::from(sourcename);
+    let count = specpdl_index();
+    let contents = Box::into_raw(Box::new(map_file(file)));
+    unsafe { record_unwind_protect_ptr(free_file_contents, contents as *mut c_void) };
+    let contents = unsafe { (*contents).as_slice() };
+    if let Err(message) = elc_version(contents) {
+        let name = file.as_string_or_error();
+        error!("File `{}' is {}", String::from_utf8_lossy(name.as_slice()), message);
+    }
+
+    specbind(Qcurrent_load_list, Qnil);
+    let lexical = unsafe { find_symbol_value(Qlexical_binding) };
+    let environment = if lexical == Qnil || lexical == Qunbound {
+        LispObject::constant_nil()
+    } else {
+        list!(LispObject::constant_t())
+    };
+    specbind(Qinternal_interpreter_environment, environment.to_raw());
+
+    // Try to ensure SOURCENAME is a truename, except while preloading.
+    let dumping = LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil();
+    if !dumping && sourcename.is_string() && fboundp(intern("file-truename")).is_not_nil()
+        && call!(intern("file-name-absolute-p"), sourcename).is_not_nil()
+    {
+        sourcename = call!(intern("file-truename"), sourcename);
+    }
+    unsafe {
+        if initialized {
+            globals.f_Vcurrent_load_list = Fcons(sourcename.to_raw(), globals.f_Vcurrent_load_list);
+        }
+    }
+
+    let mut reader = ElcReader::new(contents);
+    loop {
+        reader.skip_blanks();
+        if reader.peek().is_none() {
+            break;
+        }
+        let start = reader.pos;
+        let form = match reader.read_form() {
+            Ok(form) => form,
+            Err(()) => {
+                let mut end: ptrdiff_t = 0;
+                let form = unsafe { read_elc_form_at(stream, start as ptrdiff_t, &mut end) };
+                reader.pos = end as usize;
+                LispObject::from(form)
+            }
+        };
+        unsafe { eval_sub(form.to_raw()) };
+    }
+
+    unsafe {
+        build_load_history(sourcename.to_raw(), true);
+        unbind_to(count, Qnil);
+    }
+}
+
+/// Load the byte-compiled file FILE, reading it natively.
+/// This is `load' with `load-use-native-elc' bound to t, for comparing
+/// the speed of the two ways of reading .elc files.  FILE is not
+/// searched for in `load-path' and no suffixes are tried; NOERROR and
+/// NOMESSAGE are as for `load'.
+#[lisp_fn(min = "1")]
+pub fn load_native_elc(file: LispObject, noerror: LispObject, nomessage: LispObject) -> LispObject {
+    file.as_string_or_error();
+    let file = call!(intern("expand-file-name"), file);
+    with_let_binding(
+        intern("load-use-native-elc"),
+        LispObject::constant_t(),
+        || {
+            call!(
+                intern("load"),
+                file,
+                noerror,
+                nomessage,
+                LispObject::constant_t()
+            )
+        },
+    )
+}
+
+#[test]
+fn test_elc_version() {
+    assert_eq!(elc_version(b";ELC\x1a\0\0\0\n"), Ok(26));
+    assert!(elc_version(b";ELC\x13\0\0\0\n").is_err());
+    assert!(elc_version(b";;; foo.el\n").is_err());
+    assert!(elc_version(b";ELC").is_err());
+}
+
+#[test]
+fn test_parse_number() {
+    assert_eq!(parse_number(b"42"), Some(Number::Integer(42)));
+    assert_eq!(parse_number(b"-7."), Some(Number::Integer(-7)));
+    assert_eq!(parse_number(b"+0"), Some(Number::Integer(0)));
+    assert_eq!(parse_number(b"1.5"), Some(Number::Float(1.5)));
+    assert_eq!(parse_number(b".5"), Some(Number::Float(0.5)));
+    assert_eq!(parse_number(b"-2e3"), Some(Number::Float(-2000.0)));
+    assert_eq!(parse_number(b"1.5e-1"), Some(Number::Float(0.15)));
+    assert_eq!(parse_number(b"1.0e+INF"), Some(Number::Float(f64::INFINITY)));
+    assert_eq!(parse_number(b"-1.0e+INF"), Some(Number::Float(f64::NEG_INFINITY)));
+    match parse_number(b"0.0e+NaN") {
+        Some(Number::Float(f)) => assert!(f.is_nan()),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        parse_number(b"100000000000000000000"),
+        Some(Number::Float(1e20))
+    );
+    for symbol in &[&b"1.e5"[..], b"-", b"+", b".", b"1+", b"1e", b".e5", b"foo", b"1.5.2"] {
+        assert_eq!(parse_number(symbol), None);
+    }
+}
+
+#[test]
+fn test_multibyte_chars() {
+    assert_eq!(multibyte_chars(b"abc"), Some(3));
+    assert_eq!(multibyte_chars("\u{e9}t\u{e9}".as_bytes()), Some(3));
+    assert_eq!(multibyte_chars(b"\xc1\xbf"), Some(1));
+    assert_eq!(multibyte_chars(b"\xe2\x80"), None);
+    assert_eq!(multibyte_chars(b"\xff"), None);
+}
+
+#[test]
+fn test_skip_blanks() {
+    let contents = b";ELC\x1a\0\0\0\n;;; comment\n\n#@6 doc\x1f\n(foo)";
+    let mut reader = ElcReader::new(contents);
+    reader.skip_blanks();
+    assert_eq!(&contents[reader.pos..], b"(foo)");
+
+    let mut reader = ElcReader::new(b"  #@00 anything (at all)");
+    reader.skip_blanks();
+    assert_eq!(reader.peek(), None);
+}
+
+include!(concat!(env!("OUT_DIR"), "/elc_exports.rs"));
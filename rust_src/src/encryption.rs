@@ -0,0 +1,844 @@
+//! Encryption of buffer text without an external program.
+//!
+//! Text is encrypted in the age format, version 1, with a passphrase or
+//! for age recipients.  The format is put together here from the
+//! primitives of the rust-crypto crate: X25519 and scrypt to wrap the
+//! file key, HKDF-SHA-256 and HMAC-SHA-256 for the header, and
+//! ChaCha20-Poly1305 in 64 KiB chunks for the payload.  OpenPGP is not
+//! supported; `native-encryption-formats' says so to Lisp.
+//!
+//! The module is only built with the `native-encryption' feature, which
+//! is off by default.
+
+#![cfg(feature = "native-encryption")]
+
+use std::cmp;
+use std::str;
+
+use libc::{c_char, ptrdiff_t};
+
+use base64_crate;
+use crypto_crate::chacha20::ChaCha20;
+use crypto_crate::curve25519::{curve25519, curve25519_base};
+use crypto_crate::hkdf::{hkdf_expand, hkdf_extract};
+use crypto_crate::hmac::Hmac;
+use crypto_crate::mac::Mac;
+use crypto_crate::poly1305::Poly1305;
+use crypto_crate::scrypt::{scrypt, ScryptParams};
+use crypto_crate::sha2::Sha256;
+use crypto_crate::symmetriccipher::SynchronousStreamCipher;
+use crypto_crate::util::fixed_time_eq;
+use rand::{OsRng, Rng};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::make_unibyte_string;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use symbols::symbol_value;
+
+/// The first line of an age file.
+const AGE_VERSION: &[u8] = b"age-encryption.org/v1";
+/// The lines around an armored age file.
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+
+/// The size of a plaintext chunk of the payload.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// The size of a Poly1305 tag.
+const TAG_SIZE: usize = 16;
+/// The columns of a stanza body line.
+const BODY_COLUMNS: usize = 64;
+
+/// The scrypt work factor, a power of two, for new files.  Like age
+/// itself, this takes about a second.
+const SCRYPT_WORK_FACTOR: u8 = 18;
+/// The largest scrypt work factor accepted when decrypting, which
+/// needs 4 GiB of memory already.
+const SCRYPT_MAX_WORK_FACTOR: u8 = 22;
+
+const SCRYPT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+const X25519_LABEL: &[u8] = b"age-encryption.org/v1/X25519";
+
+/// The human-readable parts of age recipients and identities, which are
+/// Bech32 strings.
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+
+const BECH32_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// What the text is encrypted with.
+enum Key {
+    Passphrase(String),
+    /// Age recipients when encrypting, age identities when decrypting.
+    Keys(Vec<String>),
+}
+
+/// A recipient stanza of an age header, which wraps the file key for
+/// one recipient.
+struct Stanza {
+    kind: String,
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+fn invalid_header() -> String {
+    "Invalid age header".to_string()
+}
+
+fn random_bytes(buffer: &mut [u8]) -> Result<(), String> {
+    let mut rng = OsRng::new().map_err(|err| err.to_string())?;
+    rng.fill_bytes(buffer);
+    Ok(())
+}
+
+/// Encode BYTES in base64 without padding, as the age header has it.
+fn b64_encode(bytes: &[u8]) -> String {
+    base64_crate::encode(bytes)
+        .trim_right_matches('=')
+        .to_string()
+}
+
+/// Decode TEXT, base64 without padding.
+fn b64_decode(text: &[u8]) -> Result<Vec<u8>, String> {
+    if text.contains(&b'=') {
+        return Err(invalid_header());
+    }
+    let mut padded = text.to_vec();
+    while padded.len() % 4 != 0 {
+        padded.push(b'=');
+    }
+    base64_crate::decode(&padded).map_err(|_| invalid_header())
+}
+
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut prk = [0u8; 32];
+    hkdf_extract(Sha256::new(), salt, ikm, &mut prk);
+    let mut okm = [0u8; 32];
+    hkdf_expand(Sha256::new(), &prk, info, &mut okm);
+    okm
+}
+
+/// Return the Poly1305 tag of CIPHERTEXT, without associated data, as
+/// ChaCha20-Poly1305 computes it.
+fn poly1305_tag(key: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+    let mut mac = Poly1305::new(key);
+    mac.input(ciphertext);
+    mac.input(&[0u8; 16][..(16 - ciphertext.len() % 16) % 16]);
+    // The length of the associated data, 0, and of the ciphertext, as
+    // little-endian 64-bit numbers.
+    let mut lengths = [0u8; 16];
+    let len = ciphertext.len() as u64;
+    for i in 0..8 {
+        lengths[8 + i] = (len >> (8 * i)) as u8;
+    }
+    mac.input(&lengths);
+    let mut tag = [0u8; TAG_SIZE];
+    mac.raw_result(&mut tag);
+    tag
+}
+
+/// Encrypt PLAINTEXT with ChaCha20-Poly1305 as in RFC 7539, and return
+/// the ciphertext followed by the tag.
+fn seal(key: &[u8], nonce: &[u8; 12], plaintext: &[u8]) -> Vec<u8> {
+    let mut cipher = ChaCha20::new(key, nonce);
+    // The first block of the key stream is the Poly1305 key, the text
+    // is encrypted with the following ones.
+    let mut mac_key = [0u8; 64];
+    cipher.process(&[0u8; 64], &mut mac_key);
+    let mut sealed = vec![0u8; plaintext.len()];
+    cipher.process(plaintext, &mut sealed);
+    let tag = poly1305_tag(&mac_key[..32], &sealed);
+    sealed.extend_from_slice(&tag);
+    sealed
+}
+
+/// Decrypt SEALED, the result of `seal', or return None if it was
+/// tampered with or KEY is wrong.
+fn open(key: &[u8], nonce: &[u8; 12], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < TAG_SIZE {
+        return None;
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_SIZE);
+    let mut cipher = ChaCha20::new(key, nonce);
+    let mut mac_key = [0u8; 64];
+    cipher.process(&[0u8; 64], &mut mac_key);
+    if !fixed_time_eq(&poly1305_tag(&mac_key[..32], ciphertext), tag) {
+        return None;
+    }
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    cipher.process(ciphertext, &mut plaintext);
+    Some(plaintext)
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut check: u32 = 1;
+    for &value in values {
+        let top = check >> 25;
+        check = (check & 0x01ff_ffff) << 5 ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                check ^= *generator;
+            }
+        }
+    }
+    check
+}
+
+fn bech32_hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values
+}
+
+/// Regroup the bits of DATA from groups of FROM bits to groups of TO
+/// bits.  With PAD, the last group is filled up with zeros; without,
+/// the bits left over must be such padding.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let max = (1 << to) - 1;
+    let max_acc = (1 << (from + to - 1)) - 1;
+    let mut result = Vec::new();
+    for &value in data {
+        acc = ((acc << from) | u32::from(value)) & max_acc;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+fn bech32_encode(hrp: &str, data: &[u8]) -> String {
+    let data = convert_bits(data, 8, 5, true).unwrap();
+    let mut values = bech32_hrp_expand(hrp);
+    values.extend_from_slice(&data);
+    values.extend_from_slice(&[0; 6]);
+    let check = bech32_polymod(&values) ^ 1;
+    let mut encoded = format!("{}1", hrp);
+    for &value in &data {
+        encoded.push(BECH32_CHARSET[value as usize] as char);
+    }
+    for i in 0..6 {
+        encoded.push(BECH32_CHARSET[((check >> (5 * (5 - i))) & 31) as usize] as char);
+    }
+    encoded
+}
+
+/// Decode the Bech32 string TEXT.  Return its human-readable part in
+/// lower case and its data, or None if TEXT isn't valid.
+fn bech32_decode(text: &str) -> Option<(String, Vec<u8>)> {
+    let lower = text.to_lowercase();
+    if lower != text && text.to_uppercase() != text {
+        return None;
+    }
+    let separator = match lower.rfind('1') {
+        Some(separator) if separator > 0 && separator + 7 <= lower.len() => separator,
+        _ => return None,
+    };
+    let hrp = &lower[..separator];
+    let mut values = Vec::new();
+    for c in lower[separator + 1..].bytes() {
+        match BECH32_CHARSET.iter().position(|&b| b == c) {
+            Some(value) => values.push(value as u8),
+            None => return None,
+        }
+    }
+    let mut check = bech32_hrp_expand(hrp);
+    check.extend_from_slice(&values);
+    if bech32_polymod(&check) != 1 {
+        return None;
+    }
+    convert_bits(&values[..values.len() - 6], 5, 8, false).map(|data| (hrp.to_string(), data))
+}
+
+/// Return the key in TEXT, a Bech32 string with the human-readable part
+/// HRP, or None if it isn't one.
+fn bech32_key(text: &str, hrp: &str) -> Option<[u8; 32]> {
+    match bech32_decode(text) {
+        Some((ref text_hrp, ref data)) if text_hrp == hrp && data.len() == 32 => {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(data);
+            Some(key)
+        }
+        _ => None,
+    }
+}
+
+fn parse_recipient(text: &str) -> Result<[u8; 32], String> {
+    bech32_key(text, RECIPIENT_HRP).ok_or_else(|| format!("Invalid age recipient: {}", text))
+}
+
+fn parse_identity(text: &str) -> Result<[u8; 32], String> {
+    bech32_key(text, IDENTITY_HRP).ok_or_else(|| "Invalid age identity".to_string())
+}
+
+fn scrypt_key(passphrase: &str, salt: &[u8], work_factor: u8) -> [u8; 32] {
+    let mut full_salt = SCRYPT_LABEL.to_vec();
+    full_salt.extend_from_slice(salt);
+    let mut key = [0u8; 32];
+    scrypt(
+        passphrase.as_bytes(),
+        &full_salt,
+        &ScryptParams::new(work_factor, 8, 1),
+        &mut key,
+    );
+    key
+}
+
+fn x25519_wrap_key(shared: &[u8], share: &[u8], recipient: &[u8]) -> [u8; 32] {
+    let mut salt = share.to_vec();
+    salt.extend_from_slice(recipient);
+    hkdf(&salt, shared, X25519_LABEL)
+}
+
+fn scrypt_stanza(passphrase: &str, file_key: &[u8], work_factor: u8) -> Result<Stanza, String> {
+    let mut salt = [0u8; 16];
+    random_bytes(&mut salt)?;
+    let key = scrypt_key(passphrase, &salt, work_factor);
+    Ok(Stanza {
+        kind: "scrypt".to_string(),
+        args: vec![b64_encode(&salt), work_factor.to_string()],
+        body: seal(&key, &[0; 12], file_key),
+    })
+}
+
+fn x25519_stanza(recipient: &[u8; 32], file_key: &[u8]) -> Result<Stanza, String> {
+    let mut ephemeral = [0u8; 32];
+    random_bytes(&mut ephemeral)?;
+    let share = curve25519_base(&ephemeral);
+    let shared = curve25519(&ephemeral, recipient);
+    if shared.iter().all(|&b| b == 0) {
+        return Err("Invalid age recipient".to_string());
+    }
+    let key = x25519_wrap_key(&shared, &share, recipient);
+    Ok(Stanza {
+        kind: "X25519".to_string(),
+        args: vec![b64_encode(&share)],
+        body: seal(&key, &[0; 12], file_key),
+    })
+}
+
+fn write_stanza(header: &mut Vec<u8>, stanza: &Stanza) {
+    header.extend_from_slice(b"-> ");
+    header.extend_from_slice(stanza.kind.as_bytes());
+    for arg in &stanza.args {
+        header.push(b' ');
+        header.extend_from_slice(arg.as_bytes());
+    }
+    header.push(b'\n');
+    // The body is wrapped, and its last line is shorter than the others
+    // even if that leaves it empty.
+    let body = b64_encode(&stanza.body);
+    let mut rest = body.as_bytes();
+    loop {
+        let len = cmp::min(rest.len(), BODY_COLUMNS);
+        header.extend_from_slice(&rest[..len]);
+        header.push(b'\n');
+        if len < BODY_COLUMNS {
+            break;
+        }
+        rest = &rest[len..];
+    }
+}
+
+/// Return the line of DATA at *POS, without its newline, and move *POS
+/// past it.
+fn next_line<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let start = *pos;
+    match data[start..].iter().position(|&b| b == b'\n') {
+        Some(len) => {
+            *pos = start + len + 1;
+            Some(&data[start..start + len])
+        }
+        None => None,
+    }
+}
+
+/// Split the age file DATA into the stanzas of its header, the part of
+/// the header the MAC is computed over, the MAC and the payload.
+fn parse_header(data: &[u8]) -> Result<(Vec<Stanza>, &[u8], Vec<u8>, &[u8]), String> {
+    let mut pos = 0;
+    if next_line(data, &mut pos) != Some(AGE_VERSION) {
+        return Err("Unsupported age version".to_string());
+    }
+    let mut stanzas = Vec::new();
+    loop {
+        let start = pos;
+        let line = next_line(data, &mut pos).ok_or_else(invalid_header)?;
+        if line.starts_with(b"--- ") {
+            let mac = b64_decode(&line[4..])?;
+            return Ok((stanzas, &data[..start + 3], mac, &data[pos..]));
+        }
+        if !line.starts_with(b"-> ") {
+            return Err(invalid_header());
+        }
+        let words: Vec<String> = str::from_utf8(&line[3..])
+            .map_err(|_| invalid_header())?
+            .split(' ')
+            .map(|word| word.to_string())
+            .collect();
+        if words.iter().any(|word| word.is_empty()) {
+            return Err(invalid_header());
+        }
+        let mut body = Vec::new();
+        loop {
+            let line = next_line(data, &mut pos).ok_or_else(invalid_header)?;
+            if line.len() > BODY_COLUMNS {
+                return Err(invalid_header());
+            }
+            body.extend_from_slice(line);
+            if line.len() < BODY_COLUMNS {
+                break;
+            }
+        }
+        stanzas.push(Stanza {
+            kind: words[0].clone(),
+            args: words[1..].to_vec(),
+            body: b64_decode(&body)?,
+        });
+    }
+}
+
+fn header_mac(file_key: &[u8], header: &[u8]) -> [u8; 32] {
+    let key = hkdf(&[], file_key, b"header");
+    let mut hmac = Hmac::new(Sha256::new(), &key);
+    hmac.input(header);
+    let mut mac = [0u8; 32];
+    hmac.raw_result(&mut mac);
+    mac
+}
+
+fn payload_key(file_key: &[u8], nonce: &[u8]) -> [u8; 32] {
+    hkdf(nonce, file_key, b"payload")
+}
+
+/// Return the nonce of payload chunk number COUNTER, a big-endian
+/// number followed by a flag for the last chunk.
+fn chunk_nonce(counter: u64, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    for i in 0..8 {
+        nonce[10 - i] = (counter >> (8 * i)) as u8;
+    }
+    nonce[11] = last as u8;
+    nonce
+}
+
+fn encrypt_payload(key: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut chunks: Vec<&[u8]> = plaintext.chunks(CHUNK_SIZE).collect();
+    // Even empty text has a chunk.
+    if chunks.is_empty() {
+        chunks.push(plaintext);
+    }
+    let last = chunks.len() - 1;
+    let mut ciphertext = Vec::with_capacity(plaintext.len() + chunks.len() * TAG_SIZE);
+    for (i, chunk) in chunks.iter().enumerate() {
+        ciphertext.extend(seal(key, &chunk_nonce(i as u64, i == last), chunk));
+    }
+    ciphertext
+}
+
+fn decrypt_payload(key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    let mut rest = ciphertext;
+    let mut counter = 0;
+    loop {
+        let last = rest.len() <= CHUNK_SIZE + TAG_SIZE;
+        let len = cmp::min(rest.len(), CHUNK_SIZE + TAG_SIZE);
+        let chunk = match open(key, &chunk_nonce(counter, last), &rest[..len]) {
+            Some(ref chunk) if chunk.is_empty() && counter > 0 => None,
+            chunk => chunk,
+        };
+        match chunk {
+            Some(chunk) => plaintext.extend_from_slice(&chunk),
+            None => return Err("The encrypted text is damaged".to_string()),
+        }
+        if last {
+            return Ok(plaintext);
+        }
+        rest = &rest[len..];
+        counter += 1;
+    }
+}
+
+fn armor(data: &[u8]) -> Vec<u8> {
+    let text = base64_crate::encode(data);
+    let mut armored = format!("{}\n", ARMOR_BEGIN);
+    for line in text.as_bytes().chunks(BODY_COLUMNS) {
+        armored.push_str(str::from_utf8(line).unwrap());
+        armored.push('\n');
+    }
+    armored.push_str(ARMOR_END);
+    armored.push('\n');
+    armored.into_bytes()
+}
+
+fn dearmor(data: &[u8]) -> Result<Vec<u8>, String> {
+    let text = str::from_utf8(data).unwrap_or("").trim();
+    if text.len() < ARMOR_BEGIN.len() + ARMOR_END.len() || !text.starts_with(ARMOR_BEGIN)
+        || !text.ends_with(ARMOR_END)
+    {
+        return Err("Invalid age armor".to_string());
+    }
+    let body: String = text[ARMOR_BEGIN.len()..text.len() - ARMOR_END.len()]
+        .split_whitespace()
+        .collect();
+    base64_crate::decode(&body).map_err(|_| "Invalid age armor".to_string())
+}
+
+fn skip_whitespace(data: &[u8]) -> &[u8] {
+    let start = data.iter()
+        .position(|&b| !b" \t\r\n".contains(&b))
+        .unwrap_or(data.len());
+    &data[start..]
+}
+
+fn age_encrypt(
+    plaintext: &[u8],
+    key: &Key,
+    armored: bool,
+    work_factor: u8,
+) -> Result<Vec<u8>, String> {
+    let mut file_key = [0u8; 16];
+    random_bytes(&mut file_key)?;
+    let mut stanzas = Vec::new();
+    match *key {
+        Key::Passphrase(ref passphrase) => {
+            stanzas.push(scrypt_stanza(passphrase, &file_key, work_factor)?)
+        }
+        Key::Keys(ref keys) => for key in keys {
+            stanzas.push(x25519_stanza(&parse_recipient(key)?, &file_key)?);
+        },
+    }
+    if stanzas.is_empty() {
+        return Err("No age recipients".to_string());
+    }
+
+    let mut output = AGE_VERSION.to_vec();
+    output.push(b'\n');
+    for stanza in &stanzas {
+        write_stanza(&mut output, stanza);
+    }
+    output.extend_from_slice(b"---");
+    let mac = header_mac(&file_key, &output);
+    output.push(b' ');
+    output.extend_from_slice(b64_encode(&mac).as_bytes());
+    output.push(b'\n');
+
+    let mut nonce = [0u8; 16];
+    random_bytes(&mut nonce)?;
+    output.extend_from_slice(&nonce);
+    output.extend(encrypt_payload(
+        &payload_key(&file_key, &nonce),
+        plaintext,
+    ));
+    Ok(if armored { armor(&output) } else { output })
+}
+
+/// Return the file key wrapped for PASSPHRASE in STANZAS.
+fn unwrap_scrypt(stanzas: &[Stanza], passphrase: &str) -> Result<Vec<u8>, String> {
+    let stanza = match stanzas.iter().find(|stanza| stanza.kind == "scrypt") {
+        // A passphrase is the only way to open such a file.
+        Some(stanza) if stanzas.len() == 1 => stanza,
+        Some(_) => return Err(invalid_header()),
+        None => return Err("The text is encrypted for age recipients".to_string()),
+    };
+    if stanza.args.len() != 2 || stanza.args[1].starts_with('0') {
+        return Err(invalid_header());
+    }
+    let salt = b64_decode(stanza.args[0].as_bytes())?;
+    let work_factor: u8 = stanza.args[1].parse().map_err(|_| invalid_header())?;
+    if salt.len() != 16 || work_factor == 0 {
+        return Err(invalid_header());
+    }
+    if work_factor > SCRYPT_MAX_WORK_FACTOR {
+        return Err("The scrypt work factor is too large".to_string());
+    }
+    let key = scrypt_key(passphrase, &salt, work_factor);
+    match open(&key, &[0; 12], &stanza.body) {
+        Some(ref file_key) if file_key.len() != 16 => Err(invalid_header()),
+        Some(file_key) => Ok(file_key),
+        None => Err("Wrong passphrase".to_string()),
+    }
+}
+
+/// Return the file key wrapped in STANZAS for one of IDENTITIES.
+fn unwrap_x25519(stanzas: &[Stanza], identities: &[String]) -> Result<Vec<u8>, String> {
+    if stanzas.iter().any(|stanza| stanza.kind == "scrypt") {
+        return Err("The text is encrypted with a passphrase".to_string());
+    }
+    for identity in identities {
+        let secret = parse_identity(identity)?;
+        let recipient = curve25519_base(&secret);
+        for stanza in stanzas.iter().filter(|stanza| stanza.kind == "X25519") {
+            if stanza.args.len() != 1 {
+                return Err(invalid_header());
+            }
+            let share = b64_decode(stanza.args[0].as_bytes())?;
+            if share.len() != 32 {
+                return Err(invalid_header());
+            }
+            let shared = curve25519(&secret, &share);
+            if shared.iter().all(|&b| b == 0) {
+                return Err(invalid_header());
+            }
+            let key = x25519_wrap_key(&shared, &share, &recipient);
+            if let Some(file_key) = open(&key, &[0; 12], &stanza.body) {
+                if file_key.len() != 16 {
+                    return Err(invalid_header());
+                }
+                return Ok(file_key);
+            }
+        }
+    }
+    Err("None of the identities can decrypt the text".to_string())
+}
+
+fn age_decrypt(data: &[u8], key: &Key) -> Result<Vec<u8>, String> {
+    let dearmored;
+    let data = skip_whitespace(data);
+    let data = if data.starts_with(ARMOR_BEGIN.as_bytes()) {
+        dearmored = dearmor(data)?;
+        &dearmored[..]
+    } else if data.starts_with(AGE_VERSION) {
+        data
+    } else {
+        return Err("The text is not in the age format".to_string());
+    };
+
+    let (stanzas, header, mac, payload) = parse_header(data)?;
+    let file_key = match *key {
+        Key::Passphrase(ref passphrase) => unwrap_scrypt(&stanzas, passphrase)?,
+        Key::Keys(ref identities) => unwrap_x25519(&stanzas, identities)?,
+    };
+    if !fixed_time_eq(&header_mac(&file_key, header), &mac) {
+        return Err("The age header is damaged".to_string());
+    }
+    if payload.len() < 16 {
+        return Err("The encrypted text is damaged".to_string());
+    }
+    let (nonce, ciphertext) = payload.split_at(16);
+    decrypt_payload(&payload_key(&file_key, nonce), ciphertext)
+}
+
+fn string_value(object: LispObject) -> String {
+    String::from_utf8_lossy(object.as_string_or_error().as_slice()).into_owned()
+}
+
+fn lisp_key(key: LispObject) -> Key {
+    if key.is_string() {
+        Key::Passphrase(string_value(key))
+    } else if key.is_cons() {
+        Key::Keys(key.iter_cars_safe().map(string_value).collect())
+    } else {
+        wrong_type!(intern("stringp"), key)
+    }
+}
+
+/// Check that FORMAT is one of `native-encryption-formats', or nil.
+fn check_format(format: LispObject) {
+    if !(format.is_nil() || format.eq(intern("age"))) {
+        error!("Unsupported encryption format")
+    }
+}
+
+/// Return the coding system in VARIABLE, or `utf-8-unix' if it is nil.
+fn coding_system(variable: &str) -> LispObject {
+    let coding = symbol_value(intern(variable));
+    if coding.is_nil() {
+        intern("utf-8-unix")
+    } else {
+        coding
+    }
+}
+
+/// Return the bytes of the text between START and END, encoded with
+/// CODING.
+fn region_bytes(start: LispObject, end: LispObject, coding: LispObject) -> Vec<u8> {
+    let text = call!(intern("buffer-substring-no-properties"), start, end);
+    call!(intern("encode-coding-string"), text, coding, LispObject::constant_t())
+        .as_string_or_error()
+        .as_slice()
+        .to_vec()
+}
+
+/// Replace the text between START and END with STRING.
+fn replace_region(start: LispObject, end: LispObject, string: LispObject) {
+    call!(intern("delete-region"), start, end);
+    call!(intern("goto-char"), start);
+    call!(intern("insert"), string);
+}
+
+fn unibyte_string(bytes: &[u8]) -> LispObject {
+    unsafe {
+        LispObject::from(make_unibyte_string(
+            bytes.as_ptr() as *const c_char,
+            bytes.len() as ptrdiff_t,
+        ))
+    }
+}
+
+/// Return the list of formats `encrypt-region-native' can write and
+/// `decrypt-region-native' can read.  This is `(age)'; OpenPGP still
+/// needs GnuPG.
+#[lisp_fn]
+pub fn native_encryption_formats() -> LispObject {
+    LispObject::cons(intern("age"), LispObject::constant_nil())
+}
+
+/// Encrypt the text between START and END and replace it with the
+/// result.
+///
+/// KEY is a passphrase string, or a list of age recipients like
+/// "age1...".  FORMAT is `age', the default and only format, see
+/// `native-encryption-formats'.  If ARMOR is non-nil, the result is
+/// ASCII text, otherwise it is binary.
+///
+/// The text is encoded with `coding-system-for-write' before it is
+/// encrypted, or with `utf-8-unix' if that is nil.
+#[lisp_fn(min = "3")]
+pub fn encrypt_region_native(
+    start: LispObject,
+    end: LispObject,
+    key: LispObject,
+    format: LispObject,
+    armor: LispObject,
+) -> LispObject {
+    let key = lisp_key(key);
+    check_format(format);
+    let plaintext = region_bytes(start, end, coding_system("coding-system-for-write"));
+    match age_encrypt(&plaintext, &key, armor.is_not_nil(), SCRYPT_WORK_FACTOR) {
+        Ok(ciphertext) => replace_region(start, end, unibyte_string(&ciphertext)),
+        Err(err) => error!("Encryption failed: {}", err),
+    }
+    LispObject::constant_nil()
+}
+
+/// Decrypt the text between START and END and replace it with the
+/// result.
+///
+/// The text must be in the age format, binary or armored.  KEY is the
+/// passphrase string, or a list of age identities like
+/// "AGE-SECRET-KEY-1...".
+///
+/// The result is decoded with `coding-system-for-read', or with
+/// `utf-8-unix' if that is nil.
+#[lisp_fn]
+pub fn decrypt_region_native(start: LispObject, end: LispObject, key: LispObject) -> LispObject {
+    let key = lisp_key(key);
+    let ciphertext = region_bytes(start, end, intern("no-conversion"));
+    match age_decrypt(&ciphertext, &key) {
+        Ok(plaintext) => {
+            let text = call!(
+                intern("decode-coding-string"),
+                unibyte_string(&plaintext),
+                coding_system("coding-system-for-read"),
+                LispObject::constant_t()
+            );
+            replace_region(start, end, text)
+        }
+        Err(err) => error!("Decryption failed: {}", err),
+    }
+    LispObject::constant_nil()
+}
+
+#[cfg(test)]
+fn generate_identity() -> (String, String) {
+    let mut secret = [0u8; 32];
+    random_bytes(&mut secret).unwrap();
+    let identity = bech32_encode(IDENTITY_HRP, &secret).to_uppercase();
+    let recipient = bech32_encode(RECIPIENT_HRP, &curve25519_base(&secret));
+    (identity, recipient)
+}
+
+#[test]
+fn test_bech32() {
+    assert_eq!(bech32_decode("a12uel5l"), Some(("a".to_string(), vec![])));
+    assert_eq!(bech32_decode("A12UEL5L"), Some(("a".to_string(), vec![])));
+    assert_eq!(bech32_decode("A12uEL5L"), None);
+    assert_eq!(bech32_decode("a12uel5m"), None);
+    assert_eq!(bech32_encode("a", &[]), "a12uel5l");
+
+    let (identity, recipient) = generate_identity();
+    assert!(identity.starts_with("AGE-SECRET-KEY-1"));
+    assert!(recipient.starts_with("age1"));
+    assert_eq!(recipient.len(), 62);
+    assert!(parse_identity(&identity).is_ok());
+    assert!(parse_recipient(&recipient).is_ok());
+    assert!(parse_recipient(&identity).is_err());
+}
+
+#[test]
+fn test_chacha20_poly1305() {
+    let key = [7u8; 32];
+    let nonce = chunk_nonce(1, true);
+    assert_eq!(nonce, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1]);
+    let sealed = seal(&key, &nonce, b"hello");
+    assert_eq!(sealed.len(), 5 + TAG_SIZE);
+    assert_eq!(open(&key, &nonce, &sealed), Some(b"hello".to_vec()));
+    assert_eq!(open(&key, &chunk_nonce(1, false), &sealed), None);
+    let mut tampered = sealed.clone();
+    tampered[0] ^= 1;
+    assert_eq!(open(&key, &nonce, &tampered), None);
+}
+
+#[test]
+fn test_age_recipients() {
+    let (identity, recipient) = generate_identity();
+    let (other_identity, _) = generate_identity();
+    for &armored in &[false, true] {
+        let ciphertext =
+            age_encrypt(b"hello", &Key::Keys(vec![recipient.clone()]), armored, 10).unwrap();
+        assert_eq!(
+            ciphertext.starts_with(ARMOR_BEGIN.as_bytes()),
+            armored
+        );
+        let key = Key::Keys(vec![other_identity.clone(), identity.clone()]);
+        assert_eq!(age_decrypt(&ciphertext, &key).unwrap(), b"hello");
+        let key = Key::Keys(vec![other_identity.clone()]);
+        assert!(age_decrypt(&ciphertext, &key).is_err());
+        let key = Key::Passphrase("x".to_string());
+        assert!(age_decrypt(&ciphertext, &key).is_err());
+    }
+}
+
+#[test]
+fn test_age_passphrase() {
+    let key = Key::Passphrase("secret".to_string());
+    // Texts that fill no chunk, one chunk exactly and more than one.
+    for &len in &[0, CHUNK_SIZE, CHUNK_SIZE * 2 + 1] {
+        let plaintext = vec![b'x'; len];
+        let mut ciphertext = age_encrypt(&plaintext, &key, false, 10).unwrap();
+        assert_eq!(age_decrypt(&ciphertext, &key).unwrap(), plaintext);
+        assert!(age_decrypt(&ciphertext, &Key::Passphrase("wrong".to_string())).is_err());
+
+        // Dropping the last chunk is noticed.
+        if len > CHUNK_SIZE {
+            let truncated = ciphertext.len() - (len % CHUNK_SIZE + TAG_SIZE);
+            assert!(age_decrypt(&ciphertext[..truncated], &key).is_err());
+        }
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 1;
+        assert!(age_decrypt(&ciphertext, &key).is_err());
+    }
+    assert!(age_decrypt(b"-----BEGIN PGP MESSAGE-----\n", &key).is_err());
+}
+
+include!(concat!(env!("OUT_DIR"), "/encryption_exports.rs"));
@@ -0,0 +1,611 @@
+//! Reading EPUB documents.
+//!
+//! An EPUB book is a zip archive holding XHTML chapters, a package
+//! document that lists them in reading order (the spine), and a table
+//! of contents, either an EPUB 3 navigation document or an EPUB 2 NCX
+//! file.  Reader modes used to unpack the archive with an external
+//! program and parse these files with xml.el; here both are done in
+//! Rust.  Chapters are returned as HTML text for shr or
+//! `html-render-region' to display.
+
+use std::fs::File;
+use std::io::{Read, Seek};
+
+use encoding_rs::UTF_8;
+use url::percent_encoding::percent_decode;
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+use zip::ZipArchive;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use imap::unibyte_string;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use util::{expand_file_name_to_path, report_io_error};
+
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const NCX_MEDIA_TYPE: &str = "application/x-dtbncx+xml";
+
+/// An entry of the package manifest.
+#[derive(Clone, Debug, PartialEq)]
+struct Item {
+    id: String,
+    /// The name of the file in the archive.
+    path: String,
+    media_type: String,
+    properties: String,
+}
+
+/// The parts of the package document a reader needs.
+#[derive(Debug, Default, PartialEq)]
+struct Package {
+    title: Option<String>,
+    creator: Option<String>,
+    language: Option<String>,
+    items: Vec<Item>,
+    /// The idrefs of the spine, in reading order.
+    spine: Vec<String>,
+    /// The id of the NCX file named by the spine, if any.
+    toc: Option<String>,
+}
+
+impl Package {
+    fn item(&self, id: &str) -> Option<&Item> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// The file names of the spine, skipping references to items
+    /// missing from the manifest.
+    fn spine_paths(&self) -> Vec<String> {
+        self.spine
+            .iter()
+            .filter_map(|id| self.item(id))
+            .map(|item| item.path.clone())
+            .collect()
+    }
+
+    /// The navigation document or NCX file, and whether it is the former.
+    fn toc_item(&self) -> Option<(&Item, bool)> {
+        if let Some(item) = self.items
+            .iter()
+            .find(|item| item.properties.split_whitespace().any(|p| p == "nav"))
+        {
+            return Some((item, true));
+        }
+        self.toc
+            .as_ref()
+            .and_then(|id| self.item(id))
+            .or_else(|| {
+                self.items
+                    .iter()
+                    .find(|item| item.media_type == NCX_MEDIA_TYPE)
+            })
+            .map(|item| (item, false))
+    }
+}
+
+/// An entry of the table of contents.
+#[derive(Debug, Default, PartialEq)]
+struct TocEntry {
+    label: String,
+    path: String,
+    fragment: Option<String>,
+    children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    fn set_target(&mut self, target: &str, base: &str) {
+        let (file, fragment) = match target.find('#') {
+            Some(index) => (&target[..index], Some(target[index + 1..].to_string())),
+            None => (target, None),
+        };
+        self.path = resolve(base, file);
+        self.fragment = fragment;
+    }
+
+    /// Collapse the whitespace of the labels below this entry.
+    fn normalize(&mut self) {
+        self.label = self.label.split_whitespace().collect::<Vec<_>>().join(" ");
+        for child in &mut self.children {
+            child.normalize();
+        }
+    }
+}
+
+fn attribute(attributes: &[OwnedAttribute], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.clone())
+}
+
+/// Return the directory part of the archive member PATH, with a
+/// trailing slash, or "" if it is at the top level.
+fn directory(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(index) => &path[..index + 1],
+        None => "",
+    }
+}
+
+/// Return the archive member that the relative URL HREF refers to
+/// from the directory BASE.
+fn resolve(base: &str, href: &str) -> String {
+    let href = percent_decode(href.as_bytes()).decode_utf8_lossy();
+    let joined = format!("{}{}", base, href);
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Return the package document named in META-INF/container.xml.
+fn rootfile(container: &[u8]) -> Option<String> {
+    for event in EventReader::new(container) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => if name.local_name == "rootfile" {
+                return attribute(&attributes, "full-path");
+            },
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Title,
+    Creator,
+    Language,
+}
+
+/// Parse the package document DATA, whose directory is BASE.  A
+/// malformed document yields whatever was read before the error.
+fn parse_package(data: &[u8], base: &str) -> Package {
+    let mut package = Package::default();
+    let mut field = None;
+    for event in EventReader::new(data) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let dc = name.namespace.as_ref().map_or(false, |ns| ns == DC_NAMESPACE);
+                field = match name.local_name.as_str() {
+                    "title" if dc => Some(Field::Title),
+                    "creator" if dc => Some(Field::Creator),
+                    "language" if dc => Some(Field::Language),
+                    _ => None,
+                };
+                match name.local_name.as_str() {
+                    "item" => {
+                        let id = attribute(&attributes, "id");
+                        let href = attribute(&attributes, "href");
+                        if let (Some(id), Some(href)) = (id, href) {
+                            package.items.push(Item {
+                                id,
+                                path: resolve(base, &href),
+                                media_type: attribute(&attributes, "media-type")
+                                    .unwrap_or_default(),
+                                properties: attribute(&attributes, "properties")
+                                    .unwrap_or_default(),
+                            });
+                        }
+                    }
+                    "itemref" => if let Some(idref) = attribute(&attributes, "idref") {
+                        package.spine.push(idref);
+                    },
+                    "spine" => package.toc = attribute(&attributes, "toc"),
+                    _ => {}
+                }
+            }
+            Ok(XmlEvent::Characters(text)) => {
+                // Only the first title, creator and language are kept.
+                let slot = match field {
+                    Some(Field::Title) => &mut package.title,
+                    Some(Field::Creator) => &mut package.creator,
+                    Some(Field::Language) => &mut package.language,
+                    None => continue,
+                };
+                if slot.is_none() {
+                    *slot = Some(text.trim().to_string());
+                }
+            }
+            Ok(XmlEvent::EndElement { .. }) => field = None,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    package
+}
+
+/// Pop the innermost entry of STACK into the children of its parent.
+fn close_entry(stack: &mut Vec<TocEntry>) {
+    if stack.len() > 1 {
+        let entry = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(entry);
+    }
+}
+
+/// Return the entries of the stack root, with normalized labels.
+fn finish_toc(mut stack: Vec<TocEntry>) -> Vec<TocEntry> {
+    while stack.len() > 1 {
+        close_entry(&mut stack);
+    }
+    let mut root = stack.pop().unwrap();
+    root.normalize();
+    root.children
+}
+
+/// Parse the NCX file DATA, whose directory is BASE.
+fn parse_ncx(data: &[u8], base: &str) -> Vec<TocEntry> {
+    let mut stack = vec![TocEntry::default()];
+    let mut in_label = false;
+    let mut in_text = false;
+    for event in EventReader::new(data) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => match name.local_name.as_str() {
+                "navPoint" => stack.push(TocEntry::default()),
+                "navLabel" => in_label = true,
+                "text" => in_text = in_label,
+                "content" => if stack.len() > 1 {
+                    if let Some(src) = attribute(&attributes, "src") {
+                        stack.last_mut().unwrap().set_target(&src, base);
+                    }
+                },
+                _ => {}
+            },
+            Ok(XmlEvent::Characters(text)) => if in_text && stack.len() > 1 {
+                stack.last_mut().unwrap().label.push_str(&text);
+            },
+            Ok(XmlEvent::EndElement { name }) => match name.local_name.as_str() {
+                "navPoint" => close_entry(&mut stack),
+                "navLabel" => in_label = false,
+                "text" => in_text = false,
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    finish_toc(stack)
+}
+
+/// Parse the `toc' nav element of the navigation document DATA, whose
+/// directory is BASE.
+fn parse_nav(data: &[u8], base: &str) -> Vec<TocEntry> {
+    let mut stack = vec![TocEntry::default()];
+    let mut in_toc = false;
+    let mut in_label = false;
+    for event in EventReader::new(data) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => match name.local_name.as_str() {
+                "nav" => {
+                    in_toc = attribute(&attributes, "type")
+                        .map_or(false, |t| t.split_whitespace().any(|t| t == "toc"))
+                }
+                "li" if in_toc => stack.push(TocEntry::default()),
+                "a" | "span" if in_toc && stack.len() > 1 => {
+                    in_label = true;
+                    if let Some(href) = attribute(&attributes, "href") {
+                        stack.last_mut().unwrap().set_target(&href, base);
+                    }
+                }
+                _ => {}
+            },
+            Ok(XmlEvent::Characters(text)) => if in_label {
+                stack.last_mut().unwrap().label.push_str(&text);
+            },
+            Ok(XmlEvent::EndElement { name }) => match name.local_name.as_str() {
+                "nav" => in_toc = false,
+                "li" if in_toc => close_entry(&mut stack),
+                "a" | "span" => in_label = false,
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    finish_toc(stack)
+}
+
+/// A book whose package and table of contents have been read.
+struct Book {
+    package: Package,
+    spine: Vec<String>,
+    toc: Vec<TocEntry>,
+}
+
+fn read_member<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Vec<u8>, String> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(|err| format!("Cannot read {} from the archive: {}", name, err))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(|err| format!("Cannot read {} from the archive: {}", name, err))?;
+    Ok(bytes)
+}
+
+fn load<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Book, String> {
+    let container = read_member(archive, "META-INF/container.xml")?;
+    let opf = rootfile(&container).ok_or("No package document in META-INF/container.xml")?;
+    let package = parse_package(&read_member(archive, &opf)?, directory(&opf));
+    let spine = package.spine_paths();
+    let toc = match package.toc_item() {
+        Some((item, nav)) => match read_member(archive, &item.path) {
+            Ok(data) => if nav {
+                parse_nav(&data, directory(&item.path))
+            } else {
+                parse_ncx(&data, directory(&item.path))
+            },
+            // A book is still readable without its table of contents.
+            Err(_) => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    Ok(Book {
+        package,
+        spine,
+        toc,
+    })
+}
+
+fn open_archive(file: LispObject) -> ZipArchive<File> {
+    let path = expand_file_name_to_path(file);
+    let handle = match File::open(&path) {
+        Ok(handle) => handle,
+        Err(err) => report_io_error(b"Opening EPUB file\0", file, &err),
+    };
+    match ZipArchive::new(handle) {
+        Ok(archive) => archive,
+        Err(err) => error!("Not an EPUB file: {}", err),
+    }
+}
+
+fn optional_string(s: &Option<String>) -> LispObject {
+    s.as_ref()
+        .map_or_else(LispObject::constant_nil, |s| lisp_string(s))
+}
+
+fn toc_to_lisp(entries: &[TocEntry], spine: &[String]) -> LispObject {
+    entries
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, entry| {
+            let index = spine
+                .iter()
+                .position(|path| *path == entry.path)
+                .map_or_else(LispObject::constant_nil, |i| {
+                    LispObject::from_natnum(i as EmacsInt)
+                });
+            let item = list!(
+                intern(":label"),
+                lisp_string(&entry.label),
+                intern(":file"),
+                if entry.path.is_empty() {
+                    LispObject::constant_nil()
+                } else {
+                    lisp_string(&entry.path)
+                },
+                intern(":fragment"),
+                optional_string(&entry.fragment),
+                intern(":index"),
+                index,
+                intern(":children"),
+                toc_to_lisp(&entry.children, spine)
+            );
+            LispObject::cons(item, tail)
+        })
+}
+
+/// Return chapter INDEX of the EPUB book FILE and the structure of the book.
+/// INDEX counts from 0 in the spine, the reading order of the book.
+/// The value is a plist with these properties:
+///
+/// :html      The text of the chapter, usually XHTML.
+/// :file      The name of the chapter in the archive, which relative
+///            links in the chapter, such as images, are resolved against.
+/// :spine     The names of all chapters, in reading order.
+/// :toc       The table of contents, a list of plists with the properties
+///            :label, :file (nil for entries without a link),
+///            :fragment (the part after \"#\" in the link, or nil),
+///            :index (the position of :file in the spine, or nil) and
+///            :children (the nested entries).
+/// :title, :creator, :language
+///            The metadata of the book, or nil if missing.
+///
+/// Use `epub-extract-file' to get images and style sheets.
+#[lisp_fn]
+pub fn epub_extract_chapter(file: LispObject, index: LispObject) -> LispObject {
+    let n = index.as_natnum_or_error() as usize;
+    let mut archive = open_archive(file);
+    let book = match load(&mut archive) {
+        Ok(book) => book,
+        Err(message) => error!("{}", message),
+    };
+    if n >= book.spine.len() {
+        args_out_of_range!(file, index);
+    }
+    let html = match read_member(&mut archive, &book.spine[n]) {
+        Ok(bytes) => UTF_8.decode(&bytes).0.into_owned(),
+        Err(message) => error!("{}", message),
+    };
+
+    let spine = book.spine
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, path| {
+            LispObject::cons(lisp_string(path), tail)
+        });
+    list!(
+        intern(":html"),
+        lisp_string(&html),
+        intern(":file"),
+        lisp_string(&book.spine[n]),
+        intern(":spine"),
+        spine,
+        intern(":toc"),
+        toc_to_lisp(&book.toc, &book.spine),
+        intern(":title"),
+        optional_string(&book.package.title),
+        intern(":creator"),
+        optional_string(&book.package.creator),
+        intern(":language"),
+        optional_string(&book.package.language)
+    )
+}
+
+/// Return the contents of NAME in the EPUB book FILE as a unibyte string.
+/// NAME is the full name of the file in the archive, as returned in the
+/// :file properties of `epub-extract-chapter'.
+#[lisp_fn]
+pub fn epub_extract_file(file: LispObject, name: LispObject) -> LispObject {
+    let name = String::from_utf8_lossy(name.as_string_or_error().as_slice()).into_owned();
+    let mut archive = open_archive(file);
+    match read_member(&mut archive, &name) {
+        Ok(bytes) => unibyte_string(&bytes),
+        Err(message) => error!("{}", message),
+    }
+}
+
+#[cfg(test)]
+const TEST_CONTAINER: &[u8] = br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+#[cfg(test)]
+const TEST_PACKAGE: &[u8] = br#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>A Book</dc:title>
+    <dc:creator>Someone</dc:creator>
+    <dc:language>en</dc:language>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="c1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="text/chapter2.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="c1"/>
+    <itemref idref="missing"/>
+    <itemref idref="c2"/>
+  </spine>
+</package>"#;
+
+#[cfg(test)]
+const TEST_NAV: &[u8] = br#"<?xml version="1.0"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+  <nav epub:type="toc"><ol>
+    <li><a href="text/chapter%201.xhtml">One</a>
+      <ol><li><a href="text/chapter%201.xhtml#part">Part
+        one</a></li></ol></li>
+    <li><span>Two</span></li>
+  </ol></nav>
+  <nav epub:type="landmarks"><ol><li><a href="text/chapter2.xhtml">Skip</a></li></ol></nav>
+</body>
+</html>"#;
+
+#[test]
+fn test_resolve() {
+    assert_eq!(resolve("OEBPS/", "text/a%20b.xhtml"), "OEBPS/text/a b.xhtml");
+    assert_eq!(resolve("OEBPS/text/", "../images/c.png"), "OEBPS/images/c.png");
+    assert_eq!(resolve("", "./c.xhtml"), "c.xhtml");
+    assert_eq!(directory("OEBPS/content.opf"), "OEBPS/");
+    assert_eq!(directory("content.opf"), "");
+}
+
+#[test]
+fn test_package() {
+    assert_eq!(rootfile(TEST_CONTAINER), Some("OEBPS/content.opf".to_string()));
+    let package = parse_package(TEST_PACKAGE, "OEBPS/");
+    assert_eq!(package.title, Some("A Book".to_string()));
+    assert_eq!(package.creator, Some("Someone".to_string()));
+    assert_eq!(package.language, Some("en".to_string()));
+    assert_eq!(
+        package.spine_paths(),
+        vec!["OEBPS/text/chapter 1.xhtml", "OEBPS/text/chapter2.xhtml"]
+    );
+    assert_eq!(package.toc_item().map(|(item, nav)| (item.id.as_str(), nav)), Some(("nav", true)));
+}
+
+#[test]
+fn test_nav() {
+    let toc = parse_nav(TEST_NAV, "OEBPS/");
+    assert_eq!(toc.len(), 2);
+    assert_eq!(toc[0].label, "One");
+    assert_eq!(toc[0].path, "OEBPS/text/chapter 1.xhtml");
+    assert_eq!(toc[0].children[0].label, "Part one");
+    assert_eq!(toc[0].children[0].fragment, Some("part".to_string()));
+    assert_eq!(toc[1].label, "Two");
+    assert_eq!(toc[1].path, "");
+}
+
+#[test]
+fn test_ncx() {
+    let ncx = br#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="p1" playOrder="1">
+      <navLabel><text>Chapter 1</text></navLabel>
+      <content src="c1.xhtml"/>
+      <navPoint id="p2" playOrder="2">
+        <navLabel><text>Section</text></navLabel>
+        <content src="c1.xhtml#s1"/>
+      </navPoint>
+    </navPoint>
+  </navMap>
+</ncx>"#;
+    let toc = parse_ncx(ncx, "OPS/");
+    assert_eq!(toc.len(), 1);
+    assert_eq!(toc[0].label, "Chapter 1");
+    assert_eq!(toc[0].path, "OPS/c1.xhtml");
+    assert_eq!(toc[0].children[0].label, "Section");
+    assert_eq!(toc[0].children[0].fragment, Some("s1".to_string()));
+}
+
+#[test]
+fn test_load() {
+    use std::io::{Cursor, Write};
+    use zip::{CompressionMethod, ZipWriter};
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let members: [(&str, &[u8]); 4] = [
+        ("META-INF/container.xml", TEST_CONTAINER),
+        ("OEBPS/content.opf", TEST_PACKAGE),
+        ("OEBPS/nav.xhtml", TEST_NAV),
+        ("OEBPS/text/chapter 1.xhtml", b"<html/>"),
+    ];
+    for &(name, data) in &members {
+        writer.start_file(name, CompressionMethod::Deflated).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+    let book = load(&mut archive).unwrap();
+    assert_eq!(book.spine.len(), 2);
+    assert_eq!(book.toc[0].label, "One");
+    assert_eq!(read_member(&mut archive, &book.spine[0]).unwrap(), b"<html/>");
+    assert!(read_member(&mut archive, &book.spine[1]).is_err());
+}
+
+include!(concat!(env!("OUT_DIR"), "/epub_exports.rs"));
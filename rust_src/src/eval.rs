@@ -1,5 +1,10 @@
 //! Generic Lisp eval functions and macros.
 
+use remacs_sys::{Fsignal, Lisp_Object};
+use remacs_sys::Qwrong_type_argument;
+
+use lisp::LispObject;
+
 /// Macro to generate an error with a list from any number of arguments.
 /// Replaces xsignal0, etc. in the C layer.
 ///
@@ -22,6 +27,9 @@ macro_rules! xsignal {
 /// Macro to call Lisp functions with any number of arguments.
 /// Replaces CALLN, call1, etc. in the C layer.
 macro_rules! call {
+    ($func:expr) => {
+        call!($func,)
+    };
     ($func:expr, $($arg:expr),*) => {{
         let mut argsarray = [$func.to_raw(), $($arg.to_raw()),*];
         unsafe {
@@ -78,3 +86,64 @@ macro_rules! list {
     ($arg:expr) => { $crate::lisp::LispObject::cons($arg, list!()) };
     () => { $crate::lisp::LispObject::constant_nil() };
 }
+
+/// A Lisp error that has not been signaled yet: the error symbol and
+/// its associated data, as they would be passed to `signal'.
+///
+/// Rust functions can return a `LispResult` instead of calling
+/// `xsignal!` directly. This lets callers inspect or replace the error
+/// before it turns into a non-local exit. The error is signaled when
+/// it reaches the boundary with Lisp, i.e. when a `#[lisp_fn]` returns
+/// it, or explicitly with `LispError::signal`.
+#[derive(Clone, Copy, Debug)]
+pub struct LispError {
+    symbol: LispObject,
+    data: LispObject,
+}
+
+pub type LispResult<T> = Result<T, LispError>;
+
+impl LispError {
+    pub fn new(symbol: Lisp_Object, data: LispObject) -> LispError {
+        LispError {
+            symbol: LispObject::from(symbol),
+            data,
+        }
+    }
+
+    /// Equivalent to the `wrong_type!` macro.
+    pub fn wrong_type(predicate: Lisp_Object, value: LispObject) -> LispError {
+        LispError::new(
+            Qwrong_type_argument,
+            list!(LispObject::from(predicate), value),
+        )
+    }
+
+    /// Signal this error, as `signal' does. Never returns.
+    pub fn signal(self) -> ! {
+        unsafe { Fsignal(self.symbol.to_raw(), self.data.to_raw()) }
+    }
+}
+
+/// Conversion of the return value of a `#[lisp_fn]` into the
+/// `Lisp_Object` handed back to C. Errors are signaled here.
+pub trait LispFnReturn {
+    fn into_lisp(self) -> Lisp_Object;
+}
+
+impl LispFnReturn for LispObject {
+    #[inline]
+    fn into_lisp(self) -> Lisp_Object {
+        self.to_raw()
+    }
+}
+
+impl LispFnReturn for LispResult<LispObject> {
+    #[inline]
+    fn into_lisp(self) -> Lisp_Object {
+        match self {
+            Ok(obj) => obj.to_raw(),
+            Err(err) => err.signal(),
+        }
+    }
+}
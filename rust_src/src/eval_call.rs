@@ -0,0 +1,610 @@
+//! Evaluator primitives ported from eval.c: variable binding, non-local
+//! exits, backtraces and autoloading.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{BUFFER_OBJFWDP, SPECPDL_INDEX, blv_found, do_specbind, eval_sub,
+                 find_symbol_value, internal_catch, make_string, make_string_from_bytes,
+                 maybe_quit, record_specbind, record_unwind_protect,
+                 record_unwind_save_match_data, signal_error, signal_or_quit, un_autoload,
+                 unbind_to, unwind_to_catch};
+use remacs_sys::{Fdefalias, Fload, Flocal_variable_p, Fprogn, HandlerType, Lisp_Object,
+                 SetInternalBind, SpecbindTag, SymbolRedirect};
+use remacs_sys::{QCdebug_on_exit, QCrust, Qautoload, Qerror, Qinternal_interpreter_environment,
+                 Qlistp, Qmacro, Qnil, Qno_catch, Qprint_escape_control_characters, Qstringp,
+                 Qsymbolp, Qt};
+use remacs_sys::{current_thread, globals, Vautoload_queue};
+
+use buffers::current_buffer;
+use data::indirect_function_lisp;
+use eval::{LispError, LispResult};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{car, cdr, member, memq, nth, plist_get};
+use printer::prin1_to_string;
+
+/// Return the current depth of the specpdl stack.
+/// Equivalent to `SPECPDL_INDEX` in C.
+#[inline]
+pub fn specpdl_index() -> ptrdiff_t {
+    unsafe { SPECPDL_INDEX() }
+}
+
+/// Return the current lexical environment of the interpreter, i.e. the
+/// value of `internal-interpreter-environment'.
+#[inline]
+fn interpreter_environment() -> LispObject {
+    LispObject::from(unsafe { globals.f_Vinternal_interpreter_environment })
+}
+
+#[inline]
+fn set_interpreter_environment(env: LispObject) {
+    unsafe { globals.f_Vinternal_interpreter_environment = env.to_raw() };
+}
+
+/// Dynamically bind SYMBOL to VALUE, recording the old value on the
+/// specpdl stack so that `unbind_to` can restore it.
+///
+/// Each record describes which variable is let-bound, so it can be
+/// properly undone.  It can be either a plain `SpecbindTag::Let` or a
+/// `LetLocal`/`LetDefault`:
+/// - the symbol is the variable being bound.  Note that it is never
+///   aliased (i.e. when let-binding V1 that's aliased to V2, we record
+///   V2 here).
+/// - the buffer tells us in which buffer the binding took place.
+///   This is used for `LetLocal` bindings (i.e. bindings to a
+///   buffer-local variable) as well as for `LetDefault` bindings,
+///   i.e. bindings to the default value of a variable which can be
+///   buffer-local.
+#[no_mangle]
+pub extern "C" fn specbind(symbol: Lisp_Object, value: Lisp_Object) {
+    let mut sym = LispObject::from(symbol)
+        .as_symbol_or_error()
+        .get_indirect_variable();
+    let symbol = sym.as_lisp_obj();
+
+    match sym.get_redirect() {
+        SymbolRedirect::PlainVal => {
+            // The most common case is that of a non-constant symbol with a
+            // trivial value.  Make that as fast as we can.
+            unsafe {
+                let bind = record_specbind(
+                    SpecbindTag::Let,
+                    symbol.to_raw(),
+                    sym.get_value().to_raw(),
+                    Qnil,
+                );
+                do_specbind(sym.as_mut(), bind, value, SetInternalBind::Bind);
+            }
+        }
+        SymbolRedirect::Localized | SymbolRedirect::Forwarded => {
+            let old_value = unsafe { find_symbol_value(symbol.to_raw()) };
+            let kind = if sym.get_redirect() == SymbolRedirect::Localized {
+                if unsafe { blv_found(sym.get_blv()) } {
+                    SpecbindTag::LetLocal
+                } else {
+                    SpecbindTag::LetDefault
+                }
+            } else if unsafe { BUFFER_OBJFWDP(sym.get_fwd()) } {
+                // If SYMBOL is a per-buffer variable which doesn't have a
+                // buffer-local value here, make the `let' change the global
+                // value by changing the value of SYMBOL in all buffers not
+                // having their own value.  This is consistent with what
+                // happens with other buffer-local variables.
+                if LispObject::from(unsafe { Flocal_variable_p(symbol.to_raw(), Qnil) }).is_nil() {
+                    SpecbindTag::LetDefault
+                } else {
+                    SpecbindTag::LetLocal
+                }
+            } else {
+                SpecbindTag::Let
+            };
+
+            unsafe {
+                let bind = record_specbind(
+                    kind,
+                    symbol.to_raw(),
+                    old_value,
+                    current_buffer().to_raw(),
+                );
+                do_specbind(sym.as_mut(), bind, value, SetInternalBind::Bind);
+            }
+        }
+        SymbolRedirect::VarAlias => unreachable!("variable aliases are resolved above"),
+    }
+}
+
+/// Call F with SYMBOL dynamically bound to VALUE, as `let' would, and
+/// return its result.
+///
+/// The binding is undone when F returns.  If F exits non-locally (via
+/// `signal' or `throw'), the handler that catches the exit unwinds the
+/// specpdl stack and undoes the binding instead.
+pub fn with_let_binding<F, T>(symbol: LispObject, value: LispObject, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let count = specpdl_index();
+    specbind(symbol.to_raw(), value.to_raw());
+    let result = f();
+    unsafe { unbind_to(count, Qnil) };
+    result
+}
+
+/// Return true if VAR should be bound lexically rather than
+/// dynamically, given that LEXENV was the lexical environment when the
+/// binding form was entered.
+fn binds_lexically(var: LispObject, lexenv: LispObject) -> bool {
+    lexenv.is_not_nil() && var.as_symbol().map_or(false, |sym| !sym.is_declared_special())
+        && memq(var, interpreter_environment()).is_nil()
+}
+
+/// Evaluate the value form of a single `let' binding ELT, which is
+/// either a symbol (bound to nil) or a list (SYMBOL VALUEFORM).
+fn let_binding_value(elt: LispObject) -> LispObject {
+    if elt.is_symbol() {
+        return LispObject::constant_nil();
+    }
+
+    let rest = cdr(elt);
+    if cdr(rest).is_not_nil() {
+        let msg = b"`let' bindings can have only one value-form\0";
+        unsafe { signal_error(msg.as_ptr() as *const c_char, elt.to_raw()) };
+    }
+    LispObject::from(unsafe { eval_sub(car(rest).to_raw()) })
+}
+
+/// Bind variables according to VARLIST then eval BODY.
+/// The value of the last form in BODY is returned.
+/// Each element of VARLIST is a symbol (which is bound to nil)
+/// or a list (SYMBOL VALUEFORM) (which binds SYMBOL to the value of VALUEFORM).
+/// Each VALUEFORM can refer to the symbols already bound by this VARLIST.
+/// usage: (let* VARLIST BODY...)
+#[lisp_fn(name = "let*", c_name = "letX", unevalled = "true")]
+pub fn let_star(args: LispObject) -> LispObject {
+    let count = specpdl_index();
+    let lexenv = interpreter_environment();
+    let varlist = car(args);
+
+    let mut elts = varlist.iter_cars_safe();
+    for elt in &mut elts {
+        unsafe { maybe_quit() };
+
+        let var = if elt.is_symbol() { elt } else { car(elt) };
+        let val = let_binding_value(elt);
+
+        if binds_lexically(var, lexenv) {
+            // Lexically bind VAR by adding it to the interpreter's binding
+            // alist.
+            let newenv = LispObject::cons(LispObject::cons(var, val), interpreter_environment());
+            if interpreter_environment() == lexenv {
+                // Save the old lexical environment on the specpdl stack,
+                // but only for the first lexical binding, since we'll never
+                // need to revert to one of the intermediate ones.
+                specbind(Qinternal_interpreter_environment, newenv.to_raw());
+            } else {
+                set_interpreter_environment(newenv);
+            }
+        } else {
+            specbind(var.to_raw(), val.to_raw());
+        }
+    }
+    if elts.rest().is_not_nil() {
+        wrong_type!(Qlistp, varlist);
+    }
+
+    let val = unsafe { Fprogn(cdr(args).to_raw()) };
+    LispObject::from(unsafe { unbind_to(count, val) })
+}
+
+/// Bind variables according to VARLIST then eval BODY.
+/// The value of the last form in BODY is returned.
+/// Each element of VARLIST is a symbol (which is bound to nil)
+/// or a list (SYMBOL VALUEFORM) (which binds SYMBOL to the value of VALUEFORM).
+/// All the VALUEFORMs are evalled before any symbols are bound.
+/// usage: (let VARLIST BODY...)
+#[lisp_fn(name = "let", c_name = "let", unevalled = "true")]
+pub fn let_(args: LispObject) -> LispObject {
+    let count = specpdl_index();
+    let varlist = car(args);
+    if !varlist.is_list() {
+        wrong_type!(Qlistp, varlist);
+    }
+
+    // Compute the values first.  They are kept in a Lisp list rather
+    // than a Rust vector so that the GC can see them while the
+    // remaining value forms are evaluated.
+    let mut values = LispObject::constant_nil();
+    for elt in varlist.iter_cars() {
+        unsafe { maybe_quit() };
+        values = LispObject::cons(let_binding_value(elt), values);
+    }
+
+    // `values' was built backwards.
+    let values = values.iter_cars().fold(LispObject::constant_nil(), |acc, val| {
+        LispObject::cons(val, acc)
+    });
+
+    let mut lexenv = interpreter_environment();
+    for (elt, val) in varlist.iter_cars().zip(values.iter_cars()) {
+        let var = if elt.is_symbol() { elt } else { car(elt) };
+        if binds_lexically(var, lexenv) {
+            // Lexically bind VAR by adding it to the lexenv alist.
+            lexenv = LispObject::cons(LispObject::cons(var, val), lexenv);
+        } else {
+            // Dynamically bind VAR.
+            specbind(var.to_raw(), val.to_raw());
+        }
+    }
+
+    if lexenv != interpreter_environment() {
+        // Instantiate a new lexical environment.
+        specbind(Qinternal_interpreter_environment, lexenv.to_raw());
+    }
+
+    let val = unsafe { Fprogn(cdr(args).to_raw()) };
+    LispObject::from(unsafe { unbind_to(count, val) })
+}
+
+/// Eval BODY allowing nonlocal exits using `throw'.
+/// TAG is evalled to get the tag to use; it must not be nil.
+///
+/// Then the BODY is executed.
+/// Within BODY, a call to `throw' with the same TAG exits BODY and this `catch'.
+/// If no throw happens, `catch' returns the value of the last BODY form.
+/// If a throw happens, it specifies the value to return from `catch'.
+/// usage: (catch TAG BODY...)
+#[lisp_fn(unevalled = "true")]
+pub fn catch(args: LispObject) -> LispObject {
+    let tag = unsafe { eval_sub(car(args).to_raw()) };
+    LispObject::from(unsafe { internal_catch(tag, Fprogn, cdr(args).to_raw()) })
+}
+
+/// Throw to the catch for TAG and return VALUE from it.
+/// Both TAG and VALUE are evalled.
+#[lisp_fn]
+pub fn throw(tag: LispObject, value: LispObject) -> LispResult<LispObject> {
+    if tag.is_not_nil() {
+        let mut c = unsafe { (*current_thread).m_handlerlist };
+        while !c.is_null() {
+            unsafe {
+                match (*c).type_ {
+                    HandlerType::CatcherAll => {
+                        unwind_to_catch(c, LispObject::cons(tag, value).to_raw())
+                    }
+                    HandlerType::Catcher if tag.eq(LispObject::from((*c).tag_or_ch)) => {
+                        unwind_to_catch(c, value.to_raw())
+                    }
+                    _ => {}
+                }
+                c = (*c).next;
+            }
+        }
+    }
+    Err(LispError::new(Qno_catch, list!(tag, value)))
+}
+
+/// Signal an error.  Args are ERROR-SYMBOL and associated DATA.
+/// This function does not return.
+///
+/// An error symbol is a symbol with an `error-conditions' property
+/// that is a list of condition names.
+/// A handler for any of those names will get to handle this signal.
+/// The symbol `error' should normally be one of them.
+///
+/// DATA should be a list.  Its elements are printed as part of the error message.
+/// See Info anchor `(elisp)Definition of signal' for some details on how this
+/// error message is constructed.
+/// If the signal is handled, DATA is made available to the handler.
+/// See also the function `condition-case'.
+#[lisp_fn]
+pub fn signal(error_symbol: LispObject, data: LispObject) -> LispObject {
+    unsafe { signal_or_quit(error_symbol.to_raw(), data.to_raw(), false) };
+    // Only a keyboard quit returns from `signal_or_quit'.
+    unreachable!()
+}
+
+fn lisp_string(s: &str) -> LispObject {
+    LispObject::from(unsafe { make_string(s.as_ptr() as *const c_char, s.len() as ptrdiff_t) })
+}
+
+/// The text of a backtrace, as it is built up.
+#[derive(Default)]
+struct BacktraceText {
+    bytes: Vec<u8>,
+    chars: usize,
+}
+
+impl BacktraceText {
+    /// Append S, which is ASCII.
+    fn push_str(&mut self, s: &str) {
+        self.bytes.extend_from_slice(s.as_bytes());
+        self.chars += s.len();
+    }
+
+    /// Append the printed representation of OBJ.
+    fn push_printed(&mut self, obj: LispObject) {
+        let printed = prin1_to_string(obj, LispObject::constant_nil());
+        let string = printed.as_string().unwrap();
+        self.bytes.extend_from_slice(string.as_slice());
+        self.chars += string.len_chars() as usize;
+    }
+
+    /// Append FRAME, an element of the list returned by
+    /// `backtrace-frames', as a line in the format used by `backtrace'.
+    fn push_frame(&mut self, frame: LispObject) {
+        let evald = car(frame);
+        let func = car(cdr(frame));
+        let args = car(cdr(cdr(frame)));
+        let flags = car(cdr(cdr(cdr(frame))));
+
+        if plist_get(flags, LispObject::from(QCdebug_on_exit)).is_nil() {
+            self.push_str("  ");
+        } else {
+            self.push_str("* ");
+        }
+        if evald.is_not_nil() {
+            self.push_printed(func);
+            if args.is_nil() {
+                self.push_str("()");
+            } else {
+                self.push_printed(args);
+            }
+        } else {
+            self.push_printed(LispObject::cons(func, args));
+        }
+        if plist_get(flags, LispObject::from(QCrust)).is_not_nil() {
+            self.push_str("  [Rust]");
+        }
+        self.push_str("\n");
+    }
+
+    fn into_string(self) -> LispObject {
+        LispObject::from(unsafe {
+            make_string_from_bytes(
+                self.bytes.as_ptr() as *const c_char,
+                self.chars as ptrdiff_t,
+                self.bytes.len() as ptrdiff_t,
+            )
+        })
+    }
+}
+
+/// Return the current Lisp backtrace as a string.
+/// Frames are printed one per line, innermost first, in the same format
+/// as `backtrace'.  Frames whose function is a primitive implemented in
+/// Rust are marked with "[Rust]".
+/// If non-nil, BASE should be a function, and frames before its nearest
+/// activation frame are discarded.
+#[lisp_fn(min = "0")]
+pub fn backtrace_to_string(base: LispObject) -> LispObject {
+    let base = if base.is_nil() {
+        intern("backtrace-to-string")
+    } else {
+        base
+    };
+    let print_level = intern("print-level");
+    let level = LispObject::from(unsafe { find_symbol_value(print_level.to_raw()) });
+    let level = if level.is_nil() {
+        LispObject::from_fixnum(8)
+    } else {
+        level
+    };
+    let escape_control = LispObject::from(Qprint_escape_control_characters);
+
+    with_let_binding(print_level, level, || {
+        with_let_binding(escape_control, LispObject::constant_t(), || {
+            let mut text = BacktraceText::default();
+            for frame in call!(intern("backtrace-frames"), base).iter_cars() {
+                text.push_frame(frame);
+            }
+            text.into_string()
+        })
+    })
+}
+
+/// An autoload object, i.e. a list of the form
+/// (autoload FILE DOCSTRING INTERACTIVE TYPE), as stored in the
+/// function cell of a symbol by `autoload'.
+#[derive(Clone, Copy)]
+pub struct Autoload(LispObject);
+
+impl Autoload {
+    pub fn from_object(object: LispObject) -> Option<Autoload> {
+        if object.is_autoload() {
+            Some(Autoload(object))
+        } else {
+            None
+        }
+    }
+
+    /// The file to load to get the real definition.
+    pub fn file(self) -> LispObject {
+        nth(LispObject::from_natnum(1), self.0)
+    }
+
+    /// The TYPE element: nil for a function, `keymap', or `macro' or t
+    /// for a macro.
+    pub fn kind(self) -> LispObject {
+        nth(LispObject::from_natnum(4), self.0)
+    }
+
+    pub fn is_macro(self) -> bool {
+        let kind = self.kind();
+        kind.eq(LispObject::constant_t()) || kind.eq(LispObject::from(Qmacro))
+    }
+}
+
+/// Build an `error' whose message is FORMAT, formatted with
+/// `format-message' applied to ARGS.
+pub fn format_error(format: &str, args: LispObject) -> LispError {
+    let message = call!(
+        intern("apply"),
+        intern("format-message"),
+        LispObject::cons(lisp_string(format), args)
+    );
+    LispError::new(Qerror, list!(message))
+}
+
+/// Define FUNCTION to autoload from FILE.
+/// FUNCTION is a symbol; FILE is a file name string to pass to `load'.
+/// Third arg DOCSTRING is documentation for the function.
+/// Fourth arg INTERACTIVE if non-nil says function can be called interactively.
+/// Fifth arg TYPE indicates the type of the object:
+///    nil or omitted says FUNCTION is a function,
+///    `keymap' says FUNCTION is really a keymap, and
+///    `macro' or t says FUNCTION is really a macro.
+/// Third through fifth args give info about the real definition.
+/// They default to nil.
+/// If FUNCTION is already defined other than as an autoload,
+/// this does nothing and returns nil.
+/// usage: (autoload FUNCTION FILE &optional DOCSTRING INTERACTIVE TYPE)
+#[lisp_fn(min = "2")]
+pub fn autoload(
+    function: LispObject,
+    file: LispObject,
+    docstring: LispObject,
+    interactive: LispObject,
+    kind: LispObject,
+) -> LispResult<LispObject> {
+    let definition = match function.as_symbol() {
+        Some(sym) => sym.get_function(),
+        None => return Err(LispError::wrong_type(Qsymbolp, function)),
+    };
+    if !file.is_string() {
+        return Err(LispError::wrong_type(Qstringp, file));
+    }
+
+    // If function is defined and not as an autoload, don't override.
+    if definition.is_not_nil() && !definition.is_autoload() {
+        return Ok(LispObject::constant_nil());
+    }
+
+    let purifying = LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil();
+    let docstring = if purifying && docstring.eq(LispObject::from_fixnum(0)) {
+        // `read1' in lread.c has found the docstring starting with "\
+        // and assumed the docstring will be provided by
+        // Snarf-documentation, so it passed us 0 instead.  But that
+        // leads to accidental sharing in purecopy's hash-consing, so we
+        // use a (hopefully) unique integer instead.
+        LispObject::from_fixnum_truncated(function.to_raw())
+    } else {
+        docstring
+    };
+
+    let definition = list!(
+        LispObject::from(Qautoload),
+        file,
+        docstring,
+        interactive,
+        kind
+    );
+    Ok(LispObject::from(unsafe {
+        Fdefalias(function.to_raw(), definition.to_raw(), Qnil)
+    }))
+}
+
+/// Load FUNDEF which should be an autoload.
+/// If non-nil, FUNNAME should be the symbol whose function value is FUNDEF,
+/// in which case the function returns the new autoloaded function value.
+/// If equal to `macro', MACRO-ONLY specifies that FUNDEF should only be loaded if
+/// it defines a macro.
+#[lisp_fn(min = "1")]
+pub fn autoload_do_load(
+    fundef: LispObject,
+    funname: LispObject,
+    macro_only: LispObject,
+) -> LispResult<LispObject> {
+    let autoload = match Autoload::from_object(fundef) {
+        Some(autoload) => autoload,
+        None => return Ok(fundef),
+    };
+
+    // If `macro_only', assume this autoload to be a "best-effort", so
+    // don't signal an error if autoloading fails.
+    let best_effort = macro_only.eq(LispObject::from(Qmacro));
+    if best_effort && !autoload.is_macro() {
+        return Ok(fundef);
+    }
+
+    // This is to make sure that loadup.el gives a clear picture of what
+    // files are preloaded and when.
+    if LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil() {
+        return Err(format_error(
+            "Attempt to autoload %s while preparing to dump",
+            list!(funname),
+        ));
+    }
+
+    funname.as_symbol_or_error();
+
+    // Loading FILE must not end up triggering the very same autoload,
+    // or we would recurse until `load' gives up with a much less
+    // helpful error.
+    let file = autoload.file();
+    let in_progress = LispObject::from(unsafe { globals.f_Vautoload_in_progress });
+    if member(file, in_progress).is_not_nil() {
+        if best_effort {
+            return Ok(fundef);
+        }
+        return Err(format_error(
+            "Recursive autoload of `%s' while loading %s",
+            list!(funname, file),
+        ));
+    }
+
+    let count = specpdl_index();
+    unsafe {
+        // Preserve the match data.
+        record_unwind_save_match_data();
+
+        // If autoloading gets an error (which includes the error of
+        // failing to define the function being called), we use
+        // Vautoload_queue to undo function definitions and `provide'
+        // calls made by the function.  We do this in the specific case
+        // of autoloading because autoloading is not an explicit request
+        // "load this file", but rather a request to "call this
+        // function".
+        //
+        // The value saved here is to be restored into Vautoload_queue.
+        record_unwind_protect(un_autoload, Vautoload_queue);
+        Vautoload_queue = Qt;
+    }
+    specbind(
+        intern("autoload--in-progress").to_raw(),
+        LispObject::cons(file, in_progress).to_raw(),
+    );
+
+    unsafe {
+        Fload(file.to_raw(), macro_only.to_raw(), Qt, Qnil, Qt);
+
+        // Once loading finishes, don't undo it.
+        Vautoload_queue = Qt;
+        unbind_to(count, Qnil);
+    }
+
+    if funname.is_nil() {
+        return Ok(LispObject::constant_nil());
+    }
+
+    let fun = indirect_function_lisp(funname, LispObject::constant_nil());
+    let loaded = car(car(LispObject::from(unsafe { globals.f_Vload_history })));
+    let loaded = if loaded.is_string() { loaded } else { file };
+
+    if fun.equal(fundef) {
+        Err(format_error(
+            "Autoloading file %s failed to define function %s",
+            list!(loaded, funname),
+        ))
+    } else if fun.is_nil() {
+        Err(format_error(
+            "Autoloading file %s left function %s undefined",
+            list!(loaded, funname),
+        ))
+    } else {
+        Ok(fun)
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/eval_call_exports.rs"));
@@ -0,0 +1,685 @@
+//! File name wildcard expansion.
+//!
+//! Patterns are matched against encoded file names one component at a
+//! time.  `*' matches any sequence of characters, `?' any single
+//! character and `[...]' any character in the set, which may contain
+//! ranges and be negated by a leading `!' or `^'.  A backslash quotes
+//! the next character.  Braces expand to each of their comma separated
+//! alternatives before matching, and a `**' component matches any
+//! number of directory levels.  Wildcards never match a leading `.',
+//! so hidden files are only found by patterns that name the dot.
+//!
+//! This file also lets a buffer hold a very large file without reading
+//! all of it; see `insert-file-contents-mmap'.
+
+use libc;
+use libc::{c_uchar, ptrdiff_t};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{encode_file_name, insert_mapped_text, prepare_for_mapped_text,
+                 signal_after_change, EmacsInt, Fexpand_file_name, Qnil, MOST_POSITIVE_FIXNUM};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+#[cfg(not(unix))]
+use lists::{car, cdr};
+use util::{expand_file_name_to_path, file_name_from_bytes, path_from_bytes, report_io_error};
+
+/// Return the character at byte I of the encoded name S and its length
+/// in bytes.  Bytes that do not start a valid UTF-8 sequence are raw
+/// bytes, which match only themselves.
+fn next_char(s: &[u8], i: usize) -> (u32, usize) {
+    let b = s[i];
+    let len = if b & 0xE0 == 0xC0 {
+        2
+    } else if b & 0xF0 == 0xE0 {
+        3
+    } else if b & 0xF8 == 0xF0 {
+        4
+    } else {
+        1
+    };
+    if len > 1 && i + len <= s.len() && s[i + 1..i + len].iter().all(|&c| c & 0xC0 == 0x80) {
+        let mut c = u32::from(b) & (0x7F >> len);
+        for &cont in &s[i + 1..i + len] {
+            c = (c << 6) | u32::from(cont & 0x3F);
+        }
+        (c, len)
+    } else if b >= 0x80 {
+        (0x3F_FF00 | u32::from(b), 1)
+    } else {
+        (u32::from(b), 1)
+    }
+}
+
+/// Match the bracket expression starting at byte P of PATTERN against
+/// the character C.  Return whether it matches and the offset following
+/// the expression, or None if the bracket is not closed.
+fn match_bracket(pattern: &[u8], p: usize, c: u32) -> Option<(bool, usize)> {
+    let mut i = p + 1;
+    let negate = i < pattern.len() && (pattern[i] == b'!' || pattern[i] == b'^');
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        if i >= pattern.len() {
+            return None;
+        }
+        if pattern[i] == b']' && !first {
+            break;
+        }
+        first = false;
+        let (lo, len) = next_char(pattern, i);
+        i += len;
+        if i + 1 < pattern.len() && pattern[i] == b'-' && pattern[i + 1] != b']' {
+            let (hi, len) = next_char(pattern, i + 1);
+            i += 1 + len;
+            matched |= lo <= c && c <= hi;
+        } else {
+            matched |= lo == c;
+        }
+    }
+    Some((matched != negate, i + 1))
+}
+
+/// Match the pattern element at byte P of PATTERN, which is not `*',
+/// against the character at byte N of NAME.  Return the offsets
+/// following both if they match.
+fn match_element(pattern: &[u8], p: usize, name: &[u8], n: usize) -> Option<(usize, usize)> {
+    let (c, len) = next_char(name, n);
+    let (literal, next) = match pattern[p] {
+        b'?' => return Some((p + 1, n + len)),
+        b'[' => match match_bracket(pattern, p, c) {
+            Some((true, next)) => return Some((next, n + len)),
+            Some((false, _)) => return None,
+            None => next_char(pattern, p),
+        },
+        b'\\' if p + 1 < pattern.len() => {
+            let (literal, plen) = next_char(pattern, p + 1);
+            (literal, plen + 1)
+        }
+        _ => next_char(pattern, p),
+    };
+    if literal == c {
+        Some((p + next, n + len))
+    } else {
+        None
+    }
+}
+
+/// Return true if NAME, a single file name component, matches PATTERN.
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*' if the rest fails to match.
+    let mut backtrack: Option<(usize, usize)> = None;
+    loop {
+        if p < pattern.len() && pattern[p] == b'*' {
+            p += 1;
+            backtrack = Some((p, n));
+            continue;
+        }
+        if p == pattern.len() && n == name.len() {
+            return true;
+        }
+        if p < pattern.len() && n < name.len() {
+            if let Some((next_p, next_n)) = match_element(pattern, p, name, n) {
+                p = next_p;
+                n = next_n;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((star_p, star_n)) if star_n < name.len() => {
+                let star_n = star_n + next_char(name, star_n).1;
+                backtrack = Some((star_p, star_n));
+                p = star_p;
+                n = star_n;
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn has_wildcards(component: &[u8]) -> bool {
+    let mut escaped = false;
+    for &b in component {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'*' | b'?' | b'[' => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+fn unescape(component: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(component.len());
+    let mut bytes = component.iter();
+    while let Some(&b) = bytes.next() {
+        if b == b'\\' {
+            match bytes.next() {
+                Some(&c) => result.push(c),
+                None => result.push(b),
+            }
+        } else {
+            result.push(b);
+        }
+    }
+    result
+}
+
+/// Return the offset of the brace closing the one at byte OPEN of
+/// PATTERN and the offsets of the commas directly inside it.
+fn brace_group(pattern: &[u8], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = open;
+    while i < pattern.len() {
+        match pattern[i] {
+            b'\\' => i += 1,
+            b'{' => depth += 1,
+            b',' if depth == 1 => commas.push(i),
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((i, commas));
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Expand the braces in PATTERN.  `a{b,c}d' expands to `abd' and `acd';
+/// braces may nest, and a brace without a comma in it is literal.
+fn expand_braces(pattern: &[u8]) -> Vec<Vec<u8>> {
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            b'\\' => i += 1,
+            b'{' => if let Some((close, commas)) = brace_group(pattern, i) {
+                if !commas.is_empty() {
+                    let mut result = Vec::new();
+                    let mut start = i + 1;
+                    for &end in commas.iter().chain(Some(&close)) {
+                        let mut alternative = pattern[..i].to_vec();
+                        alternative.extend_from_slice(&pattern[start..end]);
+                        alternative.extend_from_slice(&pattern[close + 1..]);
+                        result.extend(expand_braces(&alternative));
+                        start = end + 1;
+                    }
+                    return result;
+                }
+            },
+            _ => (),
+        }
+        i += 1;
+    }
+    vec![pattern.to_vec()]
+}
+
+fn join(display: &[u8], name: &[u8]) -> Vec<u8> {
+    let mut result = display.to_vec();
+    if !result.is_empty() && !result.ends_with(b"/") {
+        result.push(b'/');
+    }
+    result.extend_from_slice(name);
+    result
+}
+
+/// The state of a wildcard expansion.
+struct Expansion {
+    /// Whether the pattern ends in a slash, so that only directories
+    /// match it.
+    directories_only: bool,
+    matches: BTreeSet<Vec<u8>>,
+}
+
+impl Expansion {
+    /// Add the files matching COMPONENTS in the directory PATH, which is
+    /// written DISPLAY in the results.
+    fn expand(&mut self, components: &[Vec<u8>], display: Vec<u8>, path: PathBuf) {
+        let (component, rest) = match components.split_first() {
+            Some(split) => split,
+            None => {
+                self.add(display, &path);
+                return;
+            }
+        };
+
+        if component.as_slice() == b"**" {
+            // Match any number of directories, and at the end of the
+            // pattern, any file in them.
+            let star = [b"*".to_vec()];
+            let rest = if rest.is_empty() { &star[..] } else { rest };
+            self.expand(rest, display.clone(), path.clone());
+            for (name, file_type) in directory_entries(&path) {
+                if !name.starts_with(b".") && file_type.is_dir() {
+                    let subdirectory = path.join(path_from_bytes(&name));
+                    self.expand(components, join(&display, &name), subdirectory);
+                }
+            }
+        } else if !has_wildcards(component) {
+            let name = unescape(component);
+            let file = path.join(path_from_bytes(&name));
+            if rest.is_empty() || file.is_dir() {
+                self.expand(rest, join(&display, &name), file);
+            }
+        } else {
+            let hidden = component.starts_with(b".") || component.starts_with(b"\\.");
+            for (name, _) in directory_entries(&path) {
+                if (name.starts_with(b".") && !hidden) || !wildcard_match(component, &name) {
+                    continue;
+                }
+                let file = path.join(path_from_bytes(&name));
+                if rest.is_empty() || file.is_dir() {
+                    self.expand(rest, join(&display, &name), file);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, mut display: Vec<u8>, path: &Path) {
+        if self.directories_only {
+            if !path.is_dir() {
+                return;
+            }
+            if !display.ends_with(b"/") {
+                display.push(b'/');
+            }
+        } else if fs::symlink_metadata(path).is_err() {
+            return;
+        }
+        if !display.is_empty() {
+            self.matches.insert(display);
+        }
+    }
+}
+
+/// Return the names and types of the entries of directory PATH, or
+/// nothing if it can't be read.
+fn directory_entries(path: &Path) -> Vec<(Vec<u8>, fs::FileType)> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => return None,
+            };
+            Some((os_str_bytes(entry.file_name()), file_type))
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn os_str_bytes(name: ::std::ffi::OsString) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    name.into_vec()
+}
+
+#[cfg(not(unix))]
+fn os_str_bytes(name: ::std::ffi::OsString) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Split the encoded PATTERN into the directory it starts from, the way
+/// that directory is written in the results, and the rest of it.
+fn pattern_root(pattern: &[u8]) -> (PathBuf, Vec<u8>, &[u8]) {
+    if pattern.starts_with(b"/") {
+        (PathBuf::from("/"), b"/".to_vec(), pattern)
+    } else if pattern.starts_with(b"~") {
+        let end = pattern
+            .iter()
+            .position(|&b| b == b'/')
+            .unwrap_or_else(|| pattern.len());
+        let home = expand_file_name_to_path(file_name_from_bytes(&pattern[..end]));
+        (home, pattern[..end].to_vec(), &pattern[end..])
+    } else {
+        let directory = expand_file_name_to_path(file_name_from_bytes(b"."));
+        (directory, Vec::new(), pattern)
+    }
+}
+
+/// Expand wildcard pattern PATTERN natively.
+/// This returns a list of file names which match the pattern, sorted in
+/// `string<' order and without duplicates.
+///
+/// Besides the wildcards `*', `?' and `[...]' understood by
+/// `file-expand-wildcards', PATTERN may contain braces, so that
+/// `{src,lib}/*.{c,h}' matches the C files in two directories, and the
+/// component `**', which matches any number of nested directories.
+/// `**' at the end of PATTERN matches every file in those directories.
+/// Wildcards never match a leading `.', and `**' does not descend into
+/// hidden directories or follow symbolic links to directories.  If
+/// PATTERN ends in a slash, it only matches directories.
+///
+/// If PATTERN is written as an absolute file name, the values are
+/// absolute also.  Otherwise they are relative to `default-directory',
+/// unless FULL is non-nil.
+#[lisp_fn(min = "1")]
+pub fn file_expand_wildcards_native(pattern: LispObject, full: LispObject) -> LispObject {
+    pattern.as_string_or_error();
+    let handler = call!(
+        intern("find-file-name-handler"),
+        pattern,
+        intern("file-expand-wildcards")
+    );
+    if handler.is_not_nil() {
+        return call!(intern("file-expand-wildcards"), pattern, full);
+    }
+
+    let pattern = if full.is_nil() {
+        pattern
+    } else {
+        LispObject::from(unsafe { Fexpand_file_name(pattern.to_raw(), Qnil) })
+    };
+    let encoded = LispObject::from(unsafe { encode_file_name(pattern.to_raw()) });
+    let mut expansion = Expansion {
+        directories_only: false,
+        matches: BTreeSet::new(),
+    };
+    for alternative in expand_braces(encoded.as_string_or_error().as_slice()) {
+        let (root, display, rest) = pattern_root(&alternative);
+        let components: Vec<Vec<u8>> = rest.split(|&b| b == b'/')
+            .filter(|component| !component.is_empty())
+            .map(|component| component.to_vec())
+            .collect();
+        expansion.directories_only = rest.ends_with(b"/");
+        expansion.expand(&components, display, root);
+    }
+
+    expansion
+        .matches
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, name| {
+            LispObject::cons(file_name_from_bytes(name), list)
+        })
+}
+
+/// The size of the gap that follows the text of a mapped file, like
+/// `GAP_BYTES_DFL' in C.
+const MAPPED_TEXT_GAP: usize = 2000;
+
+/// Addresses reserved after the text of a mapped file, so that the gap
+/// can grow this much before the text has to be copied.
+#[cfg(unix)]
+const MAPPED_TEXT_HEADROOM: usize = 64 * 1024 * 1024;
+
+/// The text of a buffer that views a file through a private mapping.
+///
+/// The file occupies the start of a reserved range of addresses, and the
+/// gap and the byte after the text follow it in anonymous memory.  The
+/// system reads the pages of the file when they are first touched and
+/// copies a page only when it is changed, so the file itself is never
+/// written.
+#[cfg(unix)]
+struct MappedText {
+    addr: *mut libc::c_void,
+    reserved: usize,
+    /// How much of the reserved range is readable and writable.
+    committed: usize,
+}
+
+// The text is only ever used from the main thread.
+#[cfg(unix)]
+unsafe impl Send for MappedText {}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(unix)]
+fn round_to_page(n: usize) -> usize {
+    let page = page_size();
+    (n + page - 1) / page * page
+}
+
+#[cfg(unix)]
+impl MappedText {
+    /// Map FILE, which is LEN bytes long, followed by a gap of GAP bytes.
+    fn map(file: &File, len: usize, gap: usize) -> io::Result<MappedText> {
+        use std::os::unix::io::AsRawFd;
+        use std::ptr;
+
+        let reserved = round_to_page(len + gap + 1 + MAPPED_TEXT_HEADROOM);
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                reserved,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let mut text = MappedText {
+            addr,
+            reserved,
+            committed: round_to_page(len),
+        };
+        // mmap refuses empty mappings.
+        if len > 0 {
+            let mapped = unsafe {
+                libc::mmap(
+                    addr,
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_FIXED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if mapped == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        text.commit(len + gap + 1)?;
+        Ok(text)
+    }
+
+    /// Make the first NBYTES bytes of the text usable.  Return false if
+    /// they don't fit in the reserved range.
+    fn commit(&mut self, nbytes: usize) -> io::Result<bool> {
+        let end = round_to_page(nbytes);
+        if end <= self.committed {
+            return Ok(true);
+        }
+        if end > self.reserved {
+            return Ok(false);
+        }
+        let result = unsafe {
+            libc::mprotect(
+                (self.addr as *mut u8).offset(self.committed as isize) as *mut libc::c_void,
+                end - self.committed,
+                libc::PROT_READ | libc::PROT_WRITE,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.committed = end;
+        Ok(true)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedText {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.addr, self.reserved) };
+    }
+}
+
+#[cfg(unix)]
+lazy_static! {
+    /// The texts of buffers that view mapped files, by their address.
+    static ref MAPPED_TEXTS: Mutex<HashMap<usize, MappedText>> = Mutex::new(HashMap::new());
+}
+
+/// Return true if the buffer text at BEG is a file mapped by
+/// `insert-file-contents-mmap'.
+#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn mapped_text_p(beg: *const c_uchar) -> bool {
+    MAPPED_TEXTS.lock().unwrap().contains_key(&(beg as usize))
+}
+
+/// Make the mapped buffer text at BEG NBYTES long, in place.  Return
+/// false if that is not possible, in which case the caller copies the
+/// text elsewhere.  Shrinking the text leaves the memory as it is.
+#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn resize_mapped_text(beg: *mut c_uchar, nbytes: ptrdiff_t) -> bool {
+    let mut texts = MAPPED_TEXTS.lock().unwrap();
+    match texts.get_mut(&(beg as usize)) {
+        Some(text) => text.commit(nbytes as usize).unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Unmap the buffer text at BEG.
+#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn free_mapped_text(beg: *mut c_uchar) {
+    MAPPED_TEXTS.lock().unwrap().remove(&(beg as usize));
+}
+
+#[cfg(not(unix))]
+#[no_mangle]
+pub extern "C" fn mapped_text_p(_beg: *const c_uchar) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+#[no_mangle]
+pub extern "C" fn resize_mapped_text(_beg: *mut c_uchar, _nbytes: ptrdiff_t) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+#[no_mangle]
+pub extern "C" fn free_mapped_text(_beg: *mut c_uchar) {}
+
+/// Map FILE and make it the text of the current buffer.  Return its
+/// size in bytes.
+#[cfg(unix)]
+fn insert_mapped_file(file: LispObject, path: &Path) -> usize {
+    let mapped = File::open(path).and_then(|f| {
+        let len = f.metadata()?.len();
+        if len + MAPPED_TEXT_GAP as u64 >= MOST_POSITIVE_FIXNUM as u64 {
+            return Err(io::Error::from_raw_os_error(libc::EFBIG));
+        }
+        let len = len as usize;
+        MappedText::map(&f, len, MAPPED_TEXT_GAP).map(|text| (text, len))
+    });
+    let (text, len) = match mapped {
+        Ok(mapped) => mapped,
+        Err(err) => report_io_error(b"Opening input file\0", file, &err),
+    };
+    let beg = text.addr as *mut c_uchar;
+    MAPPED_TEXTS.lock().unwrap().insert(beg as usize, text);
+    unsafe {
+        insert_mapped_text(beg, len as ptrdiff_t, MAPPED_TEXT_GAP as ptrdiff_t);
+        signal_after_change(1, 0, len as ptrdiff_t);
+    }
+    len
+}
+
+/// Without mmap, the file is read like `insert-file-contents-literally'.
+#[cfg(not(unix))]
+fn insert_mapped_file(file: LispObject, _path: &Path) -> usize {
+    let inserted = call!(intern("insert-file-contents-literally"), file);
+    car(cdr(inserted)).as_natnum_or_error() as usize
+}
+
+/// Make the contents of FILENAME the text of the current buffer.
+/// The buffer must be empty.  The file is mapped into memory rather than
+/// read, so that its pages are only read when they are looked at and
+/// only copied when editing changes them; a buffer can thus hold a file
+/// of several gigabytes without reading all of it.  The file itself is
+/// never written.
+///
+/// The text is not decoded: the buffer is made unibyte and holds the
+/// bytes of the file, as with `insert-file-contents-literally'.  The
+/// file must not be truncated while the buffer holds it, since the
+/// pages past its new end could no longer be read.  The mapping is
+/// released when the buffer is killed.  On systems without mmap, the
+/// file is simply read.
+///
+/// Return a list of the absolute file name and the size of the file in
+/// bytes.
+#[lisp_fn]
+pub fn insert_file_contents_mmap(filename: LispObject) -> LispObject {
+    filename.as_string_or_error();
+    let file = LispObject::from(unsafe { Fexpand_file_name(filename.to_raw(), Qnil) });
+    let handler = call!(
+        intern("find-file-name-handler"),
+        file,
+        intern("insert-file-contents")
+    );
+    if handler.is_not_nil() {
+        error!("Cannot map a file that has a file name handler");
+    }
+    unsafe { prepare_for_mapped_text() };
+
+    let path = expand_file_name_to_path(file);
+    let size = insert_mapped_file(file, &path);
+    list!(file, LispObject::from_natnum(size as EmacsInt))
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match(b"*.el", b"files.el"));
+    assert!(!wildcard_match(b"*.el", b"files.elc"));
+    assert!(wildcard_match(b"f?le*", b"file"));
+    assert!(wildcard_match(b"*a*b", b"xaxab"));
+    assert!(wildcard_match(b"[a-c]x[!y]", b"bxz"));
+    assert!(!wildcard_match(b"[a-c]x[!y]", b"bxy"));
+    assert!(wildcard_match(b"[]]", b"]"));
+    assert!(wildcard_match(b"a\\*", b"a*"));
+    assert!(!wildcard_match(b"a\\*", b"ab"));
+    assert!(wildcard_match(b"[", b"["));
+    assert!(wildcard_match("?.txt".as_bytes(), "\u{e9}.txt".as_bytes()));
+    assert!(wildcard_match("[\u{e0}-\u{ff}]".as_bytes(), "\u{e9}".as_bytes()));
+}
+
+#[test]
+fn test_expand_braces() {
+    assert_eq!(
+        expand_braces(b"a{b,c{d,e}}f"),
+        vec![b"abf".to_vec(), b"acdf".to_vec(), b"acef".to_vec()]
+    );
+    assert_eq!(expand_braces(b"{x}"), vec![b"{x}".to_vec()]);
+    assert_eq!(expand_braces(b"\\{a,b}"), vec![b"\\{a,b}".to_vec()]);
+    assert_eq!(
+        expand_braces(b"{a,}.c"),
+        vec![b"a.c".to_vec(), b".c".to_vec()]
+    );
+}
+
+#[test]
+fn test_has_wildcards() {
+    assert!(has_wildcards(b"*.c"));
+    assert!(!has_wildcards(b"a\\*"));
+    assert_eq!(unescape(b"a\\*b"), b"a*b".to_vec());
+}
+
+include!(concat!(env!("OUT_DIR"), "/fileio_exports.rs"));
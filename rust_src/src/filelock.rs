@@ -0,0 +1,621 @@
+//! Lock files for editing.
+//!
+//! The lock for FILE is `.#FILE' in the same directory.  Its contents
+//! are USER@HOST.PID, with `:BOOT-TIME' appended when the boot time is
+//! known.  The `symlink' backend stores them as the target of a dangling
+//! symbolic link, or in a regular file on file systems without links.
+//! The `flock' backend stores them in a regular file that this Emacs
+//! keeps open with an advisory `flock' lock, so that the lock goes away
+//! with the process holding it.  Either kind of lock file is recognized
+//! whichever backend is selected.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use libc::{self, c_char, pid_t, ptrdiff_t, time_t};
+use rand::{self, Rng};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{encode_file_name, get_boot_time, get_truename_buffer, globals, make_string,
+                 EmacsInt, Fexpand_file_name, Lisp_Object, Qflock, Qnil};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use util::path_from_bytes;
+
+/// An arbitrary limit on lock contents length.
+const MAX_LOCK_INFO: u64 = 8 * 1024;
+
+/// The UTF-8 encoding of U+F022, which the Linux CIFS client may store
+/// in symbolic links instead of `:' (Bug#24656).
+const CIFS_COLON: &[u8] = b"\xef\x80\xa2";
+
+#[derive(Debug, PartialEq)]
+struct LockInfo {
+    user: Vec<u8>,
+    host: Vec<u8>,
+    /// The PID of the locking Emacs, or -1 if it does not fit.
+    pid: i64,
+    /// The boot time of the locking host, or 0 if unknown.
+    boot_time: i64,
+}
+
+enum LockOwner {
+    Nobody,
+    Us(LockInfo),
+    Other(LockInfo),
+}
+
+lazy_static! {
+    // Lock files held with the `flock' backend, by lock file name.
+    // Closing a file releases its lock.
+    static ref FLOCKED: Mutex<HashMap<PathBuf, File>> = Mutex::new(HashMap::new());
+}
+
+fn is_digit(b: u8) -> bool {
+    b >= b'0' && b <= b'9'
+}
+
+/// Parse DIGITS as a decimal number, or -1 if it is too large.
+fn parse_number(digits: &[u8]) -> Option<i64> {
+    if digits.is_empty() || !digits.iter().all(|&b| is_digit(b)) {
+        return None;
+    }
+    Some(
+        digits
+            .iter()
+            .fold(Some(0i64), |n, &d| {
+                n.and_then(|n| n.checked_mul(10))
+                    .and_then(|n| n.checked_add(i64::from(d - b'0')))
+            })
+            .unwrap_or(-1),
+    )
+}
+
+/// Parse lock contents USER@HOST.PID with an optional :BOOT-TIME.
+/// The USER is everything before the last `@' and the HOST everything
+/// up to the last `.' after it.
+fn parse_lock_info(data: &[u8]) -> Option<LockInfo> {
+    let at = match data.iter().rposition(|&b| b == b'@') {
+        Some(at) => at,
+        None => return None,
+    };
+    let dot = match data[at..].iter().rposition(|&b| b == b'.') {
+        Some(dot) => at + dot,
+        None => return None,
+    };
+    let pid_end = data[dot + 1..]
+        .iter()
+        .position(|&b| !is_digit(b))
+        .map_or(data.len(), |end| dot + 1 + end);
+    let pid = match parse_number(&data[dot + 1..pid_end]) {
+        Some(pid) => pid,
+        None => return None,
+    };
+    let rest = &data[pid_end..];
+    let boot_time = if rest.is_empty() {
+        0
+    } else {
+        let digits = if rest.starts_with(b":") {
+            &rest[1..]
+        } else if rest.starts_with(CIFS_COLON) {
+            &rest[CIFS_COLON.len()..]
+        } else {
+            return None;
+        };
+        match parse_number(digits) {
+            Some(boot_time) => boot_time,
+            None => return None,
+        }
+    };
+    Some(LockInfo {
+        user: data[..at].to_vec(),
+        host: data[at + 1..dot].to_vec(),
+        pid,
+        boot_time,
+    })
+}
+
+fn format_lock_info(info: &LockInfo) -> Vec<u8> {
+    let mut data = info.user.clone();
+    data.push(b'@');
+    data.extend_from_slice(&info.host);
+    data.extend_from_slice(format!(".{}", info.pid).as_bytes());
+    if info.boot_time != 0 {
+        data.extend_from_slice(format!(":{}", info.boot_time).as_bytes());
+    }
+    data
+}
+
+/// Return the name of the lock file for the encoded file name FILE.
+fn lock_file_name(file: &[u8]) -> Vec<u8> {
+    let base = file.iter().rposition(|&b| b == b'/').map_or(0, |slash| slash + 1);
+    let mut name = file[..base].to_vec();
+    name.extend_from_slice(b".#");
+    name.extend_from_slice(&file[base..]);
+    name
+}
+
+fn string_bytes(object: LispObject) -> Vec<u8> {
+    object
+        .as_string()
+        .map_or_else(Vec::new, |string| string.as_slice().to_vec())
+}
+
+fn system_name() -> Vec<u8> {
+    string_bytes(call!(intern("system-name")))
+}
+
+/// Return the lock information for this Emacs.
+fn our_lock_info() -> LockInfo {
+    // Call this first because it can GC.
+    let boot_time = unsafe { get_boot_time() };
+    LockInfo {
+        user: string_bytes(call!(intern("user-login-name"))),
+        host: system_name(),
+        pid: i64::from(unsafe { libc::getpid() }),
+        boot_time: boot_time as i64,
+    }
+}
+
+fn invalid_lock_file() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Invalid lock file")
+}
+
+fn errno_of(err: &io::Error) -> i32 {
+    err.raw_os_error().unwrap_or(0)
+}
+
+/// Return true if the process PID on this host is alive.
+fn process_exists(pid: i64) -> bool {
+    if pid <= 0 || pid > i64::from(pid_t::max_value()) {
+        return false;
+    }
+    signal_zero(pid as pid_t) >= 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(unix)]
+fn signal_zero(pid: pid_t) -> libc::c_int {
+    unsafe { libc::kill(pid, 0) }
+}
+
+#[cfg(not(unix))]
+fn signal_zero(pid: pid_t) -> libc::c_int {
+    unsafe { ::remacs_sys::sys_kill(pid, 0) }
+}
+
+/// Read the contents of the lock file LOCK, and whether it is a
+/// symbolic link.
+fn read_lock_data(lock: &Path) -> io::Result<(Vec<u8>, bool)> {
+    loop {
+        match fs::read_link(lock) {
+            Ok(target) => return Ok((os_str_bytes(target.into_os_string()), true)),
+            Err(ref err) if errno_of(err) == libc::EINVAL => (),
+            Err(err) => return Err(err),
+        }
+        match open_nofollow(lock, OpenOptions::new().read(true)) {
+            Ok(file) => {
+                let mut data = Vec::new();
+                file.take(MAX_LOCK_INFO + 1).read_to_end(&mut data)?;
+                return Ok((data, false));
+            }
+            // The lock file was replaced by a symbolic link since we
+            // looked; try again.
+            Err(ref err) if errno_of(err) == libc::ELOOP => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn os_str_bytes(name: ::std::ffi::OsString) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    name.into_vec()
+}
+
+#[cfg(not(unix))]
+fn os_str_bytes(name: ::std::ffi::OsString) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn open_nofollow(path: &Path, options: &mut OpenOptions) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC).open(path)
+}
+
+#[cfg(not(unix))]
+fn open_nofollow(path: &Path, options: &mut OpenOptions) -> io::Result<File> {
+    options.open(path)
+}
+
+/// Return true if another process holds a `flock' lock on LOCK.
+#[cfg(unix)]
+fn flocked_elsewhere(lock: &Path) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    match open_nofollow(lock, OpenOptions::new().read(true)) {
+        Ok(file) => unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) != 0 },
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn flocked_elsewhere(_lock: &Path) -> bool {
+    false
+}
+
+/// Return who owns the lock file LOCK.  A stale lock left by a dead
+/// process on this host is removed and reported as owned by nobody.
+fn current_lock_owner(lock: &Path) -> io::Result<LockOwner> {
+    let (data, symlink) = match read_lock_data(lock) {
+        Ok(data) => data,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(LockOwner::Nobody),
+        Err(err) => return Err(err),
+    };
+    if data.len() as u64 > MAX_LOCK_INFO {
+        return Err(invalid_lock_file());
+    }
+    let info = match parse_lock_info(&data) {
+        Some(info) => info,
+        None => return Err(invalid_lock_file()),
+    };
+
+    if FLOCKED.lock().unwrap().contains_key(lock) {
+        return Ok(LockOwner::Us(info));
+    }
+    if !symlink && flocked_elsewhere(lock) {
+        return Ok(LockOwner::Other(info));
+    }
+    if info.host != system_name() {
+        // If we wanted to support the check for stale locks on remote
+        // machines, here's where we'd do it.
+        return Ok(LockOwner::Other(info));
+    }
+    if info.pid == i64::from(unsafe { libc::getpid() }) {
+        return Ok(LockOwner::Us(info));
+    }
+    let same_boot = info.boot_time == 0
+        || (info.boot_time <= time_t::max_value() as i64
+            && (info.boot_time - unsafe { get_boot_time() } as i64).abs() <= 1);
+    if process_exists(info.pid) && same_boot {
+        return Ok(LockOwner::Other(info));
+    }
+    // The owner process is dead or has a strange pid, so zap the lock.
+    fs::remove_file(lock)?;
+    Ok(LockOwner::Nobody)
+}
+
+/// Rename OLD to NEW.  Unless FORCE, fail if NEW exists.
+fn rename_lock_file(old: &Path, new: &Path, force: bool) -> io::Result<()> {
+    if !force {
+        match fs::hard_link(old, new) {
+            Ok(()) => {
+                return match fs::remove_file(old) {
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                    result => result,
+                };
+            }
+            // Linux returns EPERM on file systems without links.
+            Err(ref err)
+                if errno_of(err) == libc::ENOSYS || errno_of(err) == libc::EPERM => {}
+            Err(err) => return Err(err),
+        }
+        // Links don't work on this file system, e.g. FAT32 mounted on
+        // GNU/Linux.  Fall back on renaming after checking that NEW does
+        // not exist, which is racy but the best we can portably do.
+        if fs::symlink_metadata(new).is_ok() {
+            return Err(io::Error::from_raw_os_error(libc::EEXIST));
+        }
+    }
+    fs::rename(old, new)
+}
+
+#[cfg(unix)]
+fn symlink(target: &[u8], link: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(path_from_bytes(target), link)
+}
+
+/// Symbolic links are supported only by later versions of Windows,
+/// and creating them often triggers elevation prompts, so pretend that
+/// they don't work.
+#[cfg(not(unix))]
+fn symlink(_target: &[u8], _link: &Path) -> io::Result<()> {
+    Err(io::Error::from_raw_os_error(libc::ENOSYS))
+}
+
+/// Create the lock file LOCK with contents DATA as a symbolic link, or
+/// as a regular file if links don't work.  If FORCE, replace any
+/// existing LOCK.
+fn create_symlink_lock(lock: &Path, data: &[u8], force: bool) -> io::Result<()> {
+    let mut result = symlink(data, lock);
+    if force {
+        if let Err(ref err) = result {
+            if errno_of(err) == libc::EEXIST {
+                let _ = fs::remove_file(lock);
+            }
+        }
+        result = result.or_else(|_| symlink(data, lock));
+    }
+    let errno = match result {
+        Ok(()) => return Ok(()),
+        Err(ref err) => errno_of(err),
+    };
+    if errno != libc::ENOSYS && errno != libc::EPERM && errno != libc::ENAMETOOLONG {
+        return result;
+    }
+
+    let suffix: String = rand::thread_rng().gen_ascii_chars().take(6).collect();
+    let nonce = lock.with_file_name(format!(".#-emacs{}", suffix));
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&nonce)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            // The contents need not survive system crashes, so there
+            // is no need to sync.
+            set_read_only(&file)
+        })
+        .and_then(|_| rename_lock_file(&nonce, lock, force));
+    if written.is_err() {
+        let _ = fs::remove_file(&nonce);
+    }
+    written
+}
+
+#[cfg(unix)]
+fn set_read_only(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(fs::Permissions::from_mode(0o444))
+}
+
+#[cfg(not(unix))]
+fn set_read_only(file: &File) -> io::Result<()> {
+    let mut permissions = file.metadata()?.permissions();
+    permissions.set_readonly(true);
+    file.set_permissions(permissions)
+}
+
+/// Lock LOCK with the `symlink' backend, unless someone else has it.
+fn symlink_lock_if_free(lock: &Path, data: &[u8]) -> io::Result<LockOwner> {
+    loop {
+        match create_symlink_lock(lock, data, false) {
+            Ok(()) => return Ok(LockOwner::Nobody),
+            Err(ref err) if errno_of(err) == libc::EEXIST => (),
+            Err(err) => return Err(err),
+        }
+        match current_lock_owner(lock)? {
+            // We deleted a stale lock; try again.
+            LockOwner::Nobody => (),
+            owner => return Ok(owner),
+        }
+    }
+}
+
+/// Lock LOCK with the `flock' backend, unless someone else has it.  If
+/// FORCE, replace a lock held by someone else.
+#[cfg(unix)]
+fn flock_lock(lock: &Path, data: &[u8], force: bool) -> io::Result<LockOwner> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut flocked = FLOCKED.lock().unwrap();
+    if flocked.contains_key(lock) {
+        return Ok(LockOwner::Nobody);
+    }
+    if force {
+        let _ = fs::remove_file(lock);
+    }
+    loop {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true);
+        let mut file = match open_nofollow(lock, &mut options) {
+            Ok(file) => file,
+            Err(ref err) if errno_of(err) == libc::ELOOP => {
+                // A lock made by the `symlink' backend.
+                drop(flocked);
+                match current_lock_owner(lock)? {
+                    LockOwner::Nobody => {
+                        flocked = FLOCKED.lock().unwrap();
+                        continue;
+                    }
+                    owner => return Ok(owner),
+                }
+            }
+            Err(err) => return Err(err),
+        };
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+            if errno_of(&err) != libc::EWOULDBLOCK {
+                return Err(err);
+            }
+            let mut contents = Vec::new();
+            file.take(MAX_LOCK_INFO + 1).read_to_end(&mut contents)?;
+            return parse_lock_info(&contents)
+                .map(LockOwner::Other)
+                .ok_or_else(invalid_lock_file);
+        }
+        // Whoever made the file may have unlocked and removed it before
+        // we locked it, in which case our lock would guard nothing.
+        if fs::metadata(lock).is_err() {
+            continue;
+        }
+        file.set_len(0)?;
+        file.write_all(data)?;
+        flocked.insert(lock.to_path_buf(), file);
+        return Ok(LockOwner::Nobody);
+    }
+}
+
+#[cfg(not(unix))]
+fn flock_lock(lock: &Path, data: &[u8], force: bool) -> io::Result<LockOwner> {
+    if force {
+        create_symlink_lock(lock, data, true).map(|_| LockOwner::Nobody)
+    } else {
+        symlink_lock_if_free(lock, data)
+    }
+}
+
+/// Lock LOCK unless someone else has it, with the backend selected by
+/// `file-lock-backend'.  If FORCE, take the lock even so.  Return the
+/// other owner if there is one.
+fn lock_if_free(lock: &Path, force: bool) -> io::Result<LockOwner> {
+    let data = format_lock_info(&our_lock_info());
+    if data.len() as u64 > MAX_LOCK_INFO {
+        return Err(io::Error::from_raw_os_error(libc::ENAMETOOLONG));
+    }
+    let backend = LispObject::from(unsafe { globals.f_Vfile_lock_backend });
+    if backend.to_raw() == Qflock {
+        flock_lock(lock, &data, force)
+    } else if force {
+        create_symlink_lock(lock, &data, true).map(|_| LockOwner::Nobody)
+    } else {
+        symlink_lock_if_free(lock, &data)
+    }
+}
+
+/// Return the lock file name for FILE, expanding and encoding it.
+fn lock_path(file: LispObject) -> PathBuf {
+    let encoded = LispObject::from(unsafe { encode_file_name(file.to_raw()) });
+    path_from_bytes(&lock_file_name(encoded.as_string_or_error().as_slice()))
+}
+
+fn lisp_bytes(bytes: &[u8]) -> LispObject {
+    LispObject::from(unsafe {
+        make_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t)
+    })
+}
+
+/// Lock file FN, meaning serve notice on the world that you intend to
+/// edit that file.  This should be done only when about to modify a
+/// file-visiting buffer previously unmodified.
+///
+/// If the file is locked by someone else, this calls
+/// `ask-user-about-lock' with the file name and a description of the
+/// user who did the locking.  That function can signal an error, or
+/// return t meaning take away the lock, or nil meaning ignore it.
+#[no_mangle]
+pub extern "C" fn lock_file(fn_: Lisp_Object) {
+    // Don't do locking while dumping Emacs.
+    if LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil() {
+        return;
+    }
+
+    let orig_fn = LispObject::from(fn_);
+    let file = LispObject::from(unsafe { Fexpand_file_name(fn_, Qnil) });
+
+    // See if this file is visited and has changed on disk since it was
+    // visited.
+    let subject_buf = LispObject::from(unsafe { get_truename_buffer(orig_fn.to_raw()) });
+    if subject_buf.is_not_nil()
+        && call!(intern("verify-visited-file-modtime"), subject_buf).is_nil()
+        && call!(intern("file-exists-p"), file).is_not_nil()
+    {
+        call!(
+            intern("userlock--ask-user-about-supersession-threat"),
+            file
+        );
+    }
+
+    // Don't do locking if the user has opted out.
+    if !unsafe { globals.f_create_lockfiles } {
+        return;
+    }
+
+    let lock = lock_path(file);
+    if let Ok(LockOwner::Other(info)) = lock_if_free(&lock, false) {
+        // Someone else has the lock.  Consider breaking it.
+        let mut description = info.user;
+        description.push(b'@');
+        description.extend_from_slice(&info.host);
+        description.extend_from_slice(format!(" (pid {})", info.pid).as_bytes());
+        let attack = call!(intern("ask-user-about-lock"), file, lisp_bytes(&description));
+        // Take the lock if the user said so.
+        if attack.is_not_nil() {
+            let _ = lock_if_free(&lock, true);
+        }
+    }
+}
+
+/// Unlock file FN if this Emacs holds its lock.
+#[no_mangle]
+pub extern "C" fn unlock_file(fn_: Lisp_Object) {
+    let file = LispObject::from(unsafe { Fexpand_file_name(fn_, Qnil) });
+    let lock = lock_path(file);
+
+    let held = FLOCKED.lock().unwrap().remove(&lock);
+    if let Some(held) = held {
+        let _ = fs::remove_file(&lock);
+        drop(held);
+    } else if let Ok(LockOwner::Us(_)) = current_lock_owner(&lock) {
+        let _ = fs::remove_file(&lock);
+    }
+}
+
+fn lock_info_plist(info: &LockInfo, ours: bool) -> LispObject {
+    list!(
+        intern(":user"),
+        lisp_bytes(&info.user),
+        intern(":host"),
+        lisp_bytes(&info.host),
+        intern(":pid"),
+        LispObject::from_fixnum(info.pid as EmacsInt),
+        intern(":boot-time"),
+        LispObject::from_fixnum(info.boot_time as EmacsInt),
+        intern(":self"),
+        LispObject::from_bool(ours)
+    )
+}
+
+/// Return a value indicating whether FILENAME is locked.
+/// The value is nil if the FILENAME is not locked, t if it is locked by
+/// you, else a string saying which user has locked it.
+///
+/// If DETAILS is non-nil, return a plist describing any lock instead:
+/// (:user USER :host HOST :pid PID :boot-time TIME :self SELF), where
+/// TIME is 0 if the boot time of HOST is unknown and SELF is non-nil if
+/// you hold the lock.
+#[lisp_fn(min = "1")]
+pub fn file_locked_p(filename: LispObject, details: LispObject) -> LispObject {
+    let file = LispObject::from(unsafe { Fexpand_file_name(filename.to_raw(), Qnil) });
+    let lock = lock_path(file);
+    match current_lock_owner(&lock) {
+        Ok(LockOwner::Us(ref info)) if details.is_not_nil() => lock_info_plist(info, true),
+        Ok(LockOwner::Us(_)) => LispObject::constant_t(),
+        Ok(LockOwner::Other(ref info)) if details.is_not_nil() => lock_info_plist(info, false),
+        Ok(LockOwner::Other(info)) => lisp_bytes(&info.user),
+        _ => LispObject::constant_nil(),
+    }
+}
+
+#[test]
+fn test_parse_lock_info() {
+    let info = parse_lock_info(b"jdoe@host.example.com.1234:1500000000").unwrap();
+    assert_eq!(info.user, b"jdoe".to_vec());
+    assert_eq!(info.host, b"host.example.com".to_vec());
+    assert_eq!(info.pid, 1234);
+    assert_eq!(info.boot_time, 1_500_000_000);
+    assert_eq!(format_lock_info(&info), b"jdoe@host.example.com.1234:1500000000".to_vec());
+
+    let info = parse_lock_info(b"a@b@c.7\xef\x80\xa242").unwrap();
+    assert_eq!(info.user, b"a@b".to_vec());
+    assert_eq!(info.boot_time, 42);
+    assert_eq!(parse_lock_info(b"u@h.99999999999999999999").unwrap().pid, -1);
+    assert_eq!(format_lock_info(&parse_lock_info(b"u@h.5").unwrap()), b"u@h.5".to_vec());
+
+    assert_eq!(parse_lock_info(b"nohost.12"), None);
+    assert_eq!(parse_lock_info(b"u@h.x12"), None);
+    assert_eq!(parse_lock_info(b"u@h.12:"), None);
+    assert_eq!(parse_lock_info(b"u@h.12;3"), None);
+}
+
+#[test]
+fn test_lock_file_name() {
+    assert_eq!(lock_file_name(b"/tmp/foo.txt"), b"/tmp/.#foo.txt".to_vec());
+    assert_eq!(lock_file_name(b"foo"), b".#foo".to_vec());
+}
+
+include!(concat!(env!("OUT_DIR"), "/filelock_exports.rs"));
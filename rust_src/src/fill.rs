@@ -0,0 +1,576 @@
+//! The line breaking core of `fill-region'.
+//!
+//! Filling works on a copy of the region: it is split into paragraphs
+//! at blank lines, each paragraph is cut into unbreakable segments, the
+//! segments are laid out greedily up to `fill-column', and the result is
+//! assembled from substrings of the original text so that the text
+//! properties of the words survive.  Widths come from `char-width', and
+//! the line breaking categories of the current category table decide
+//! where lines may be broken between characters that have no space
+//! between them, as kinsoku.el does for CJK text.
+
+use std::collections::HashMap;
+
+use libc::c_char;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{build_string, EmacsInt, Fbuffer_substring, Fnreverse};
+
+use editfns::goto_char;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use multibyte::Codepoint;
+use symbols::symbol_value;
+
+/// A character of the text being filled, with the properties the line
+/// breaking needs.
+#[derive(Clone, Copy)]
+struct Unit {
+    c: Codepoint,
+    width: usize,
+    /// A line may be broken before or after this character even when
+    /// there is no space, category `|'.
+    breakable: bool,
+    /// The character may not begin a line, category `>'.
+    no_bol: bool,
+    /// The character may not end a line, category `<'.
+    no_eol: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Justify {
+    Left,
+    Right,
+    Full,
+    Center,
+}
+
+struct Params {
+    fill_column: usize,
+    tab_width: usize,
+    double_space: bool,
+    justify: Justify,
+}
+
+/// A part of the filled text.
+#[derive(Debug, PartialEq)]
+enum Piece {
+    /// The characters from START to END of the original text.
+    Copy(usize, usize),
+    /// The value of `fill-prefix'.
+    Prefix,
+    Spaces(usize),
+    Newline,
+}
+
+/// A run of characters that is never broken.
+struct Segment {
+    start: usize,
+    end: usize,
+    width: usize,
+    /// The number of spaces between this segment and the previous one
+    /// when both are on the same line.
+    gap: usize,
+    /// Whether a line may be broken before this segment.
+    break_before: bool,
+}
+
+struct Line {
+    start: usize,
+    end: usize,
+    newline: bool,
+}
+
+fn is_whitespace(c: Codepoint) -> bool {
+    c == ' ' as Codepoint || c == '\t' as Codepoint
+}
+
+/// Return the column reached by displaying UNITS from column zero.
+fn columns(units: &[Unit], tab_width: usize) -> usize {
+    units.iter().fold(0, |column, unit| {
+        if unit.c == '\t' as Codepoint && tab_width > 0 {
+            column + tab_width - column % tab_width
+        } else {
+            column + unit.width
+        }
+    })
+}
+
+/// Whether the word in UNITS ends a sentence, ignoring closing quotes
+/// and parentheses after the punctuation.
+fn ends_sentence(units: &[Unit]) -> bool {
+    units
+        .iter()
+        .rev()
+        .map(|unit| unit.c)
+        .find(|&c| !"\"')]".chars().any(|closer| c == closer as Codepoint))
+        .map_or(false, |c| "?!.".chars().any(|end| c == end as Codepoint))
+}
+
+fn split_lines(units: &[Unit]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, unit) in units.iter().enumerate() {
+        if unit.c == '\n' as Codepoint {
+            lines.push(Line {
+                start: start,
+                end: i,
+                newline: true,
+            });
+            start = i + 1;
+        }
+    }
+    if start < units.len() {
+        lines.push(Line {
+            start: start,
+            end: units.len(),
+            newline: false,
+        });
+    }
+    lines
+}
+
+/// Return the position in LINE after PREFIX, if the line starts with it.
+fn skip_prefix(units: &[Unit], line: &Line, prefix: Option<&[Unit]>) -> usize {
+    match prefix {
+        Some(prefix)
+            if line.end - line.start >= prefix.len()
+                && units[line.start..line.start + prefix.len()]
+                    .iter()
+                    .zip(prefix)
+                    .all(|(a, b)| a.c == b.c) =>
+        {
+            line.start + prefix.len()
+        }
+        _ => line.start,
+    }
+}
+
+fn skip_whitespace(units: &[Unit], mut pos: usize, end: usize) -> usize {
+    while pos < end && is_whitespace(units[pos].c) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Cut the words of LINES into segments.
+fn segments(units: &[Unit], lines: &[&Line], starts: &[usize], params: &Params) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut spaces = 0;
+    let mut newline = false;
+    for (line, &start) in lines.iter().zip(starts) {
+        let mut pos = start;
+        while pos < line.end {
+            if is_whitespace(units[pos].c) {
+                spaces += 1;
+                pos += 1;
+                continue;
+            }
+            let word_start = pos;
+            while pos < line.end && !is_whitespace(units[pos].c) {
+                pos += 1;
+            }
+            let (gap, can_break) = match segments.last() {
+                None => (0, true),
+                Some(previous) => {
+                    let before = &units[previous.end - 1];
+                    let after = &units[word_start];
+                    if newline && spaces == 0 && (before.breakable || after.breakable) {
+                        // Lines of CJK text are joined without a space.
+                        (0, !before.no_eol && !after.no_bol)
+                    } else if params.double_space && (spaces >= 2 || newline)
+                        && ends_sentence(&units[previous.start..previous.end])
+                    {
+                        (2, true)
+                    } else {
+                        (1, true)
+                    }
+                }
+            };
+            let mut seg_start = word_start;
+            for i in word_start + 1..pos + 1 {
+                let split = i == pos || {
+                    let (a, b) = (&units[i - 1], &units[i]);
+                    (a.breakable || b.breakable) && !a.no_eol && !b.no_bol
+                };
+                if split {
+                    let first = seg_start == word_start;
+                    segments.push(Segment {
+                        start: seg_start,
+                        end: i,
+                        width: units[seg_start..i].iter().map(|unit| unit.width).sum(),
+                        gap: if first { gap } else { 0 },
+                        break_before: !first || can_break,
+                    });
+                    seg_start = i;
+                }
+            }
+            spaces = 0;
+            newline = false;
+        }
+        spaces = 0;
+        newline = true;
+    }
+    segments
+}
+
+/// Lay out SEGMENTS in lines, the first starting at column FIRST and the
+/// others at column REST.  Return the index range of each line.
+fn break_lines(
+    segments: &[Segment],
+    first: usize,
+    rest: usize,
+    fill_column: usize,
+) -> Vec<(usize, usize)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < segments.len() {
+        let mut width = if lines.is_empty() { first } else { rest };
+        let mut last_break = None;
+        let mut i = start;
+        while i < segments.len() {
+            let segment = &segments[i];
+            if i > start {
+                let wanted = width + segment.gap + segment.width;
+                if segment.break_before {
+                    if wanted > fill_column {
+                        break;
+                    }
+                    last_break = Some(i);
+                } else if wanted > fill_column {
+                    if let Some(k) = last_break {
+                        i = k;
+                        break;
+                    }
+                }
+                width = wanted;
+            } else {
+                width += segment.width;
+            }
+            i += 1;
+        }
+        lines.push((start, i));
+        start = i;
+    }
+    lines
+}
+
+fn push_copy(pieces: &mut Vec<Piece>, start: usize, end: usize) {
+    if start == end {
+        return;
+    }
+    if let Some(&mut Piece::Copy(_, ref mut last)) = pieces.last_mut() {
+        if *last == start {
+            *last = end;
+            return;
+        }
+    }
+    pieces.push(Piece::Copy(start, end));
+}
+
+/// Fill the paragraph made of LINES into PIECES.
+fn fill_paragraph(
+    units: &[Unit],
+    lines: &[&Line],
+    prefix: Option<&[Unit]>,
+    params: &Params,
+    pieces: &mut Vec<Piece>,
+) {
+    let starts: Vec<usize> = lines
+        .iter()
+        .map(|line| skip_whitespace(units, skip_prefix(units, line, prefix), line.end))
+        .collect();
+    let lead_end = starts[0];
+    let first = columns(&units[lines[0].start..lead_end], params.tab_width);
+    // Without a fill prefix, continuation lines are indented like the
+    // second line of the paragraph.
+    let (rest, rest_range) = match prefix {
+        Some(prefix) => (columns(prefix, params.tab_width), None),
+        None => {
+            let line = lines.get(1).unwrap_or(&lines[0]);
+            let end = skip_whitespace(units, line.start, line.end);
+            (
+                columns(&units[line.start..end], params.tab_width),
+                Some((line.start, end)),
+            )
+        }
+    };
+    let segments = segments(units, lines, &starts, params);
+    let layout = break_lines(&segments, first, rest, params.fill_column);
+    let last_line = lines[lines.len() - 1];
+
+    for (n, &(start, end)) in layout.iter().enumerate() {
+        let indent = if n == 0 {
+            push_copy(pieces, lines[0].start, lead_end);
+            first
+        } else {
+            match rest_range {
+                Some((start, end)) => push_copy(pieces, start, end),
+                None => pieces.push(Piece::Prefix),
+            }
+            rest
+        };
+        let line = &segments[start..end];
+        let width = line.iter().map(|segment| segment.width).sum::<usize>()
+            + line[1..].iter().map(|segment| segment.gap).sum::<usize>();
+        let room = params.fill_column.saturating_sub(indent + width);
+        match params.justify {
+            Justify::Right if room > 0 => pieces.push(Piece::Spaces(room)),
+            Justify::Center if room > 1 => pieces.push(Piece::Spaces(room / 2)),
+            _ => {}
+        }
+        let last = n + 1 == layout.len();
+        let gaps = line[1..].iter().filter(|segment| segment.gap > 0).count();
+        // Spread the extra spaces over the gaps the way
+        // `justify-current-line' does.
+        let mut fraction = room + gaps / 2;
+        for (i, segment) in line.iter().enumerate() {
+            if i > 0 && segment.gap > 0 {
+                let mut spaces = segment.gap;
+                if params.justify == Justify::Full && !last {
+                    spaces += fraction / gaps;
+                    fraction = fraction % gaps + room;
+                }
+                pieces.push(Piece::Spaces(spaces));
+            }
+            push_copy(pieces, segment.start, segment.end);
+        }
+        if !last || last_line.newline {
+            pieces.push(Piece::Newline);
+        }
+    }
+}
+
+/// Fill the text in UNITS, returning the pieces of the result.  Lines
+/// that are blank, apart from PREFIX, separate paragraphs and are kept
+/// as they are.
+fn fill_text(units: &[Unit], prefix: Option<&[Unit]>, params: &Params) -> Vec<Piece> {
+    let lines = split_lines(units);
+    let mut pieces = Vec::new();
+    let mut paragraph: Vec<&Line> = Vec::new();
+    for line in &lines {
+        let start = skip_prefix(units, line, prefix);
+        if skip_whitespace(units, start, line.end) < line.end {
+            paragraph.push(line);
+            continue;
+        }
+        if !paragraph.is_empty() {
+            fill_paragraph(units, &paragraph, prefix, params, &mut pieces);
+            paragraph.clear();
+        }
+        push_copy(&mut pieces, line.start, line.end);
+        if line.newline {
+            pieces.push(Piece::Newline);
+        }
+    }
+    if !paragraph.is_empty() {
+        fill_paragraph(units, &paragraph, prefix, params, &mut pieces);
+    }
+    pieces
+}
+
+fn string(s: &[u8]) -> LispObject {
+    // S is NUL-terminated.
+    LispObject::from(unsafe { build_string(s.as_ptr() as *const c_char) })
+}
+
+/// Return the units for the characters of STRING, looking up widths and
+/// categories once per distinct character.
+fn units(string: LispObject, cache: &mut HashMap<Codepoint, Unit>) -> Vec<Unit> {
+    let chars: Vec<Codepoint> = string.as_string_or_error().chars().collect();
+    chars
+        .into_iter()
+        .map(|c| {
+            *cache.entry(c).or_insert_with(|| {
+                let ch = LispObject::from_natnum(EmacsInt::from(c));
+                let categories = call!(intern("char-category-set"), ch);
+                let has = |category: char| {
+                    call!(
+                        intern("aref"),
+                        categories,
+                        LispObject::from_natnum(category as EmacsInt)
+                    ).is_not_nil()
+                };
+                Unit {
+                    c: c,
+                    width: call!(intern("char-width"), ch).as_natnum_or_error() as usize,
+                    breakable: has('|'),
+                    no_bol: has('>'),
+                    no_eol: has('<'),
+                }
+            })
+        })
+        .collect()
+}
+
+fn justify_style(justify: LispObject) -> Justify {
+    let justify = if justify.is_nil() {
+        call!(intern("current-justification"))
+    } else {
+        justify
+    };
+    if justify.eq(intern("right")) {
+        Justify::Right
+    } else if justify.eq(intern("center")) {
+        Justify::Center
+    } else if justify.eq(intern("left")) || justify.eq(intern("none")) || justify.is_nil() {
+        Justify::Left
+    } else {
+        Justify::Full
+    }
+}
+
+/// Fill each of the paragraphs in the region from FROM to TO, like
+/// `fill-region'.  Paragraphs are separated by blank lines, ignoring
+/// `fill-prefix'; each is filled to `fill-column', and lines may also be
+/// broken between characters of the line breaking category `|', except
+/// before a character that may not begin a line (category `>') or after
+/// one that may not end it (category `<').
+///
+/// The optional third argument JUSTIFY specifies which kind of
+/// justification to do: `full', `left', `right' or `center'; t means
+/// `full', and nil means to use `current-justification'.
+///
+/// Continuation lines start with `fill-prefix', or with the indentation
+/// of the second line of the paragraph if that is nil.  Return the
+/// value of `fill-prefix'.
+#[lisp_fn(min = "2")]
+pub fn fill_region_native(from: LispObject, to: LispObject, justify: LispObject) -> LispObject {
+    let (from, to) = {
+        let (a, b) = (from.as_fixnum_or_error(), to.as_fixnum_or_error());
+        (a.min(b), a.max(b))
+    };
+    goto_char(LispObject::from_fixnum(from));
+    let from = call!(intern("line-beginning-position"));
+    let to = LispObject::from_fixnum(to);
+    let params = Params {
+        fill_column: symbol_value(intern("fill-column")).as_natnum_or_error() as usize,
+        tab_width: symbol_value(intern("tab-width")).as_natnum_or_error() as usize,
+        double_space: symbol_value(intern("sentence-end-double-space")).is_not_nil(),
+        justify: justify_style(justify),
+    };
+    let fill_prefix = symbol_value(intern("fill-prefix"));
+    let mut cache = HashMap::new();
+    let text = LispObject::from(unsafe { Fbuffer_substring(from.to_raw(), to.to_raw()) });
+    let prefix = if fill_prefix.is_nil() || fill_prefix.as_string_or_error().len_bytes() == 0 {
+        None
+    } else {
+        Some(units(fill_prefix, &mut cache))
+    };
+    let pieces = fill_text(
+        &units(text, &mut cache),
+        prefix.as_ref().map(|prefix| &prefix[..]),
+        &params,
+    );
+
+    let mut strings = LispObject::constant_nil();
+    for piece in &pieces {
+        let string = match *piece {
+            Piece::Copy(start, end) => call!(
+                intern("substring"),
+                text,
+                LispObject::from_natnum(start as EmacsInt),
+                LispObject::from_natnum(end as EmacsInt)
+            ),
+            Piece::Prefix => fill_prefix,
+            Piece::Spaces(n) => call!(
+                intern("make-string"),
+                LispObject::from_natnum(n as EmacsInt),
+                LispObject::from_natnum(' ' as EmacsInt)
+            ),
+            Piece::Newline => string(b"\n\0"),
+        };
+        strings = LispObject::cons(string, strings);
+    }
+    let strings = LispObject::from(unsafe { Fnreverse(strings.to_raw()) });
+    let filled = call!(intern("apply"), intern("concat"), strings);
+    if call!(intern("string="), filled, text).is_nil() {
+        call!(intern("delete-region"), from, to);
+        call!(intern("insert"), filled);
+    } else {
+        goto_char(to);
+    }
+    fill_prefix
+}
+
+#[cfg(test)]
+fn test_units(text: &str) -> Vec<Unit> {
+    text.chars()
+        .map(|c| {
+            let wide = c as u32 >= 0x3000;
+            Unit {
+                c: c as Codepoint,
+                width: if wide { 2 } else { 1 },
+                breakable: wide,
+                no_bol: c == '。' || c == '、',
+                no_eol: c == '「',
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn render(units: &[Unit], pieces: &[Piece], prefix: &str) -> String {
+    let mut result = String::new();
+    for piece in pieces {
+        match *piece {
+            Piece::Copy(start, end) => result.extend(
+                units[start..end]
+                    .iter()
+                    .map(|unit| ::std::char::from_u32(unit.c).unwrap()),
+            ),
+            Piece::Prefix => result.push_str(prefix),
+            Piece::Spaces(n) => result.extend(::std::iter::repeat(' ').take(n)),
+            Piece::Newline => result.push('\n'),
+        }
+    }
+    result
+}
+
+#[test]
+fn test_fill_text() {
+    let fill = |text: &str, prefix: Option<&str>, fill_column: usize, justify: Justify| {
+        let params = Params {
+            fill_column: fill_column,
+            tab_width: 8,
+            double_space: true,
+            justify: justify,
+        };
+        let units = test_units(text);
+        let prefix_units = prefix.map(test_units);
+        let pieces = fill_text(
+            &units,
+            prefix_units.as_ref().map(|prefix| &prefix[..]),
+            &params,
+        );
+        render(&units, &pieces, prefix.unwrap_or(""))
+    };
+
+    assert_eq!(
+        fill("aaa bbb ccc\nddd  eee.  fff\n\nggg\n", None, 9, Justify::Left),
+        "aaa bbb\nccc ddd\neee.  fff\n\nggg\n"
+    );
+    assert_eq!(
+        fill("  aaa bbb\n ccc ddd eee", None, 10, Justify::Left),
+        "  aaa bbb\n ccc ddd\n eee"
+    );
+    assert_eq!(
+        fill("> aa bb cc dd\n> ee\n> \n> ff", Some("> "), 9, Justify::Left),
+        "> aa bb\n> cc dd\n> ee\n> \n> ff"
+    );
+    assert_eq!(
+        fill("a bb c dd e\n", None, 8, Justify::Full),
+        "a  bb  c\ndd e\n"
+    );
+    assert_eq!(fill("ab cd\n", None, 8, Justify::Right), "   ab cd\n");
+    assert_eq!(fill("ab\n", None, 6, Justify::Center), "  ab\n");
+    // CJK lines are joined without a space and broken between
+    // characters, but never before `。'.
+    assert_eq!(
+        fill("あいう\nえお。かき", None, 10, Justify::Left),
+        "あいうえ\nお。かき"
+    );
+    assert_eq!(fill("「あいうえ", None, 2, Justify::Left), "「あ\nい\nう\nえ");
+}
+
+include!(concat!(env!("OUT_DIR"), "/fill_exports.rs"));
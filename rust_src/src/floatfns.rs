@@ -4,7 +4,7 @@ use libc;
 use std::mem;
 
 use remacs_macros::lisp_fn;
-use remacs_sys::{EmacsDouble, EmacsInt, EmacsUint, Lisp_Object, MOST_NEGATIVE_FIXNUM,
+use remacs_sys::{EmacsDouble, EmacsInt, EmacsUint, Lisp_Object, Lisp_Subr, MOST_NEGATIVE_FIXNUM,
                  MOST_POSITIVE_FIXNUM};
 use remacs_sys::{Qarith_error, Qinteger_or_marker_p, Qnumberp, Qrange_error};
 use remacs_sys::build_string;
@@ -115,11 +115,9 @@ pub fn isnan(x: LispObject) -> LispObject {
 pub fn atan(y: LispObject, x: LispObject) -> LispObject {
     let y = y.any_to_float_or_error();
 
-    if x.is_nil() {
-        LispObject::from_float(y.atan())
-    } else {
-        let x = x.any_to_float_or_error();
-        LispObject::from_float(y.atan2(x))
+    match x.into_option() {
+        None => LispObject::from_float(y.atan()),
+        Some(x) => LispObject::from_float(y.atan2(x.any_to_float_or_error())),
     }
 }
 
@@ -128,16 +126,17 @@ pub fn atan(y: LispObject, x: LispObject) -> LispObject {
 #[lisp_fn(min = "1")]
 pub fn log(arg: LispObject, base: LispObject) -> LispObject {
     let d = arg.any_to_float_or_error();
-    let res = if base.is_nil() {
-        d.ln()
-    } else {
-        let base = base.any_to_float_or_error();
-        if base == 10.0 {
-            d.log10()
-        } else if base == 2.0 {
-            d.log2()
-        } else {
-            d.log(base)
+    let res = match base.into_option() {
+        None => d.ln(),
+        Some(base) => {
+            let base = base.any_to_float_or_error();
+            if base == 10.0 {
+                d.log10()
+            } else if base == 2.0 {
+                d.log2()
+            } else {
+                d.log(base)
+            }
         }
     };
     LispObject::from_float(res)
@@ -319,26 +318,28 @@ fn rounding_driver<F>(
 where
     F: Fn(f64) -> f64,
 {
-    let d;
-    if divisor.is_nil() {
-        if arg.is_fixnum() {
-            return arg;
-        } else if let Some(f) = arg.as_float() {
-            d = f;
-        } else {
-            wrong_type!(Qnumberp, arg)
+    let d = match divisor.into_option() {
+        None => {
+            if arg.is_fixnum() {
+                return arg;
+            } else if let Some(f) = arg.as_float() {
+                f
+            } else {
+                wrong_type!(Qnumberp, arg)
+            }
         }
-    } else {
-        if let (Some(arg), Some(div)) = (arg.as_fixnum(), divisor.as_fixnum()) {
-            if div == 0 {
-                xsignal!(Qarith_error);
+        Some(divisor) => {
+            if let (Some(arg), Some(div)) = (arg.as_fixnum(), divisor.as_fixnum()) {
+                if div == 0 {
+                    xsignal!(Qarith_error);
+                }
+                return LispObject::from_fixnum(int_round2(arg, div));
             }
-            return LispObject::from_fixnum(int_round2(arg, div));
+            let arg = arg.any_to_float_or_error();
+            let div = divisor.any_to_float_or_error();
+            arg / div
         }
-        let arg = arg.any_to_float_or_error();
-        let div = divisor.any_to_float_or_error();
-        d = arg / div;
-    }
+    };
 
     // Round, coarsely test for fixnum overflow before converting to
     // EmacsInt (to avoid undefined behavior), and then exactly test
@@ -402,4 +403,16 @@ pub fn rust_init_extra_syms() {
     }
 }
 
+pub fn rust_extra_subrs() -> Vec<*const Lisp_Subr> {
+    vec![
+        Sacos.as_ptr(),
+        Sasin.as_ptr(),
+        Scos.as_ptr(),
+        Ssin.as_ptr(),
+        Stan.as_ptr(),
+        Sexp.as_ptr(),
+        Ssqrt.as_ptr(),
+    ]
+}
+
 include!(concat!(env!("OUT_DIR"), "/floatfns_exports.rs"));
@@ -1,12 +1,15 @@
 //* Random utility Lisp functions.
 
 use remacs_macros::lisp_fn;
-use remacs_sys::{Fcons, Fmapc, Qfuncall, Qlistp, Qprovide, Qsubfeatures, Vautoload_queue};
+use remacs_sys::{Faref, Fcons, Fmapc, Fnreverse, Qeq, Qeql, Qequal, Qfuncall, Qlistp, Qprovide,
+                 Qsequencep, Qsubfeatures, Vautoload_queue};
 use remacs_sys::globals;
 
-use lisp::LispObject;
+use hashtable::{gethash, puthash};
+use lisp::{intern, LispObject};
 use lisp::defsubr;
 use lists::{assq, get, member, memq, put};
+use vectors::length;
 
 
 /// Return t if FEATURE is present in this Emacs.
@@ -70,4 +73,164 @@ pub fn provide(feature: LispObject, subfeature: LispObject) -> LispObject {
     feature
 }
 
+/// Return an iterator over the elements of SEQUENCE, which must be a
+/// list, vector, string or bool-vector.
+///
+/// Array elements are fetched one at a time rather than through a
+/// slice, since the functions called between elements may run GC, which
+/// can relocate string data.
+fn sequence_elements(sequence: LispObject) -> Box<Iterator<Item = LispObject>> {
+    if sequence.is_list() {
+        Box::new(sequence.iter_cars())
+    } else if sequence.is_string() || sequence.is_vector() || sequence.is_bool_vector() {
+        let len = length(sequence).as_natnum_or_error();
+        Box::new((0..len).map(move |idx| {
+            LispObject::from(unsafe {
+                Faref(sequence.to_raw(), LispObject::from_natnum(idx).to_raw())
+            })
+        }))
+    } else {
+        wrong_type!(Qsequencep, sequence)
+    }
+}
+
+fn nreverse(list: LispObject) -> LispObject {
+    LispObject::from(unsafe { Fnreverse(list.to_raw()) })
+}
+
+/// If TESTFN names one of the standard hash table tests, return a new
+/// hash table using that test.  A nil TESTFN means `equal'.
+fn hash_table_for_test(testfn: LispObject) -> Option<LispObject> {
+    let test = if testfn.is_nil() {
+        LispObject::from(Qequal)
+    } else {
+        testfn
+    };
+    if test.eq(LispObject::from(Qequal)) || test.eq(LispObject::from(Qeql))
+        || test.eq(LispObject::from(Qeq))
+    {
+        Some(call!(intern("make-hash-table"), intern(":test"), test))
+    } else {
+        None
+    }
+}
+
+/// Return true if (TESTFN ELT E) is non-nil for some element E of
+/// LIST, calling TESTFN in the same order as `seq-contains'.
+fn contains_with_test(list: LispObject, elt: LispObject, testfn: LispObject) -> bool {
+    list.iter_cars().any(|e| call!(testfn, elt, e).is_not_nil())
+}
+
+/// Return a list of all the elements for which (PRED element) is non-nil
+/// in SEQUENCE.  SEQUENCE may be a list, vector, string or bool-vector.
+/// This is the native implementation of `seq-filter'.
+#[lisp_fn]
+pub fn seq_filter_native(pred: LispObject, sequence: LispObject) -> LispObject {
+    let mut result = LispObject::constant_nil();
+    for elt in sequence_elements(sequence) {
+        if call!(pred, elt).is_not_nil() {
+            result = LispObject::cons(elt, result);
+        }
+    }
+    nreverse(result)
+}
+
+/// Reduce the function FUNCTION across SEQUENCE, starting with
+/// INITIAL-VALUE.  SEQUENCE may be a list, vector, string or
+/// bool-vector.  This is the native implementation of `seq-reduce'.
+#[lisp_fn]
+pub fn seq_reduce_native(
+    function: LispObject,
+    sequence: LispObject,
+    initial_value: LispObject,
+) -> LispObject {
+    sequence_elements(sequence).fold(initial_value, |acc, elt| call!(function, acc, elt))
+}
+
+/// Return a list of the elements of SEQUENCE with duplicates removed.
+/// TESTFN is used to compare elements, or `equal' if TESTFN is nil.
+/// When TESTFN is nil, `equal', `eql' or `eq', the elements are
+/// compared through a hash table, so this takes linear time.  This is
+/// the native implementation of `seq-uniq'.
+#[lisp_fn(min = "1")]
+pub fn seq_uniq_native(sequence: LispObject, testfn: LispObject) -> LispObject {
+    let mut result = LispObject::constant_nil();
+    match hash_table_for_test(testfn) {
+        Some(seen) => for elt in sequence_elements(sequence) {
+            if gethash(elt, seen, LispObject::constant_nil()).is_nil() {
+                puthash(elt, LispObject::constant_t(), seen);
+                result = LispObject::cons(elt, result);
+            }
+        },
+        None => for elt in sequence_elements(sequence) {
+            if !contains_with_test(result, elt, testfn) {
+                result = LispObject::cons(elt, result);
+            }
+        },
+    }
+    nreverse(result)
+}
+
+/// Return a list of the elements that appear in SEQUENCE1 but not in
+/// SEQUENCE2.  Equality is defined by TESTFN if non-nil or by `equal'
+/// if nil.  As with `seq-uniq-native', the standard hash table tests
+/// take linear time.  This is the native implementation of
+/// `seq-difference'.
+#[lisp_fn(min = "2")]
+pub fn seq_difference_native(
+    sequence1: LispObject,
+    sequence2: LispObject,
+    testfn: LispObject,
+) -> LispObject {
+    let mut result = LispObject::constant_nil();
+    match hash_table_for_test(testfn) {
+        Some(excluded) => {
+            for elt in sequence_elements(sequence2) {
+                puthash(elt, LispObject::constant_t(), excluded);
+            }
+            for elt in sequence_elements(sequence1) {
+                if gethash(elt, excluded, LispObject::constant_nil()).is_nil() {
+                    result = LispObject::cons(elt, result);
+                }
+            }
+        }
+        None => {
+            let excluded = sequence_elements(sequence2)
+                .fold(LispObject::constant_nil(), |acc, elt| LispObject::cons(elt, acc));
+            let excluded = nreverse(excluded);
+            for elt in sequence_elements(sequence1) {
+                if !contains_with_test(excluded, elt, testfn) {
+                    result = LispObject::cons(elt, result);
+                }
+            }
+        }
+    }
+    nreverse(result)
+}
+
+/// Apply FUNCTION to each element of SEQUENCE, and group the elements
+/// by the results.  Return a hash table, using `equal' to compare keys,
+/// that maps each key to the list of elements that produced it, in the
+/// order they appear in SEQUENCE.
+///
+/// SEQUENCE is processed from its end, as `seq-group-by' does, so the
+/// keys are stored in the order of their last occurrence, latest first.
+/// This is the native implementation of `seq-group-by'.
+#[lisp_fn]
+pub fn seq_group_by_native(function: LispObject, sequence: LispObject) -> LispObject {
+    let table = call!(
+        intern("make-hash-table"),
+        intern(":test"),
+        LispObject::from(Qequal)
+    );
+    let elements = sequence_elements(sequence)
+        .fold(LispObject::constant_nil(), |acc, elt| LispObject::cons(elt, acc));
+    for elt in elements.iter_cars() {
+        let key = call!(function, elt);
+        let group = gethash(key, table, LispObject::constant_nil());
+        puthash(key, LispObject::cons(elt, group), table);
+    }
+    table
+}
+
 include!(concat!(env!("OUT_DIR"), "/fns_exports.rs"));
@@ -0,0 +1,479 @@
+//! Formatting numbers for people to read.
+//!
+//! `format' groups the digits of %d and %f conversions in thousands
+//! when given the `,' flag, through `group_digits'.  `format-number'
+//! also follows the separators of the locale, and writes numbers in
+//! engineering notation, with SI prefixes, or spelled out as cardinals
+//! or ordinals, which mode lines, tables and reports used to do in Lisp
+//! each in their own way.
+
+use std::slice;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{find_symbol_value, EmacsInt, Qerror};
+
+use floatfns::extract_float;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// How a locale writes numbers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct NumberLocale {
+    decimal: &'static str,
+    separator: &'static str,
+    /// The sizes of the digit groups from the right; the last size
+    /// repeats.
+    groups: &'static [usize],
+}
+
+const ENGLISH: NumberLocale = NumberLocale {
+    decimal: ".",
+    separator: ",",
+    groups: &[3],
+};
+
+/// Return how the locale NAME, such as "de_DE.UTF-8", writes numbers.
+/// Locales not known here write them as in English.
+fn number_locale(name: &str) -> NumberLocale {
+    let name = name.split(|c: char| c == '.' || c == '@').next().unwrap_or("");
+    let language = name.split('_').next().unwrap_or("");
+    let comma = NumberLocale {
+        decimal: ",",
+        separator: ".",
+        groups: &[3],
+    };
+    let space = NumberLocale {
+        decimal: ",",
+        separator: "\u{a0}",
+        groups: &[3],
+    };
+    match name {
+        "de_CH" | "fr_CH" | "it_CH" | "de_LI" => {
+            return NumberLocale {
+                decimal: ".",
+                separator: "\u{2019}",
+                groups: &[3],
+            }
+        }
+        "en_IN" => {
+            return NumberLocale {
+                decimal: ".",
+                separator: ",",
+                groups: &[3, 2],
+            }
+        }
+        "es_MX" | "es_US" => return ENGLISH,
+        _ => {}
+    }
+    match language {
+        "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl"
+        | "sr" | "vi" | "is" => comma,
+        "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "nn" | "no" | "uk" | "hu"
+        | "bg" | "et" | "lt" | "lv" => space,
+        "hi" | "bn" | "mr" | "ta" | "te" | "gu" | "kn" | "ml" => NumberLocale {
+            decimal: ".",
+            separator: ",",
+            groups: &[3, 2],
+        },
+        _ => ENGLISH,
+    }
+}
+
+/// Insert SEPARATOR between the groups of DIGITS.
+fn group(digits: &str, separator: &str, groups: &[usize]) -> String {
+    let mut pieces = Vec::new();
+    let mut end = digits.len();
+    let mut sizes = groups.iter();
+    let mut size = *sizes.next().unwrap_or(&3);
+    while end > size {
+        pieces.push(&digits[end - size..end]);
+        end -= size;
+        if let Some(&next) = sizes.next() {
+            size = next;
+        }
+    }
+    pieces.push(&digits[..end]);
+    pieces.reverse();
+    pieces.join(separator)
+}
+
+/// Return NUMBER, as written by `format' or Rust, with the digits
+/// before the decimal point grouped and the decimal point replaced as
+/// LOCALE does.  Anything else, such as a sign or an exponent, is
+/// kept.
+fn localize(number: &str, locale: &NumberLocale) -> String {
+    let start = number
+        .find(|c: char| c.is_digit(10))
+        .unwrap_or_else(|| number.len());
+    let end = number[start..]
+        .find(|c: char| !c.is_digit(10))
+        .map_or(number.len(), |i| start + i);
+    let rest = &number[end..];
+    let rest = if rest.starts_with('.') {
+        format!("{}{}", locale.decimal, &rest[1..])
+    } else {
+        rest.to_string()
+    };
+    format!(
+        "{}{}{}",
+        &number[..start],
+        group(&number[start..end], locale.separator, locale.groups),
+        rest
+    )
+}
+
+/// Group the integer part of the number of LEN bytes that sprintf
+/// wrote into BUF, which has room for SIZE bytes, with commas.  Return
+/// the new length.  Called by `format' for the `,' flag.
+#[no_mangle]
+pub extern "C" fn group_digits(buf: *mut c_char, len: ptrdiff_t, size: ptrdiff_t) -> ptrdiff_t {
+    let bytes = unsafe { slice::from_raw_parts_mut(buf as *mut u8, size as usize) };
+    let grouped = localize(&String::from_utf8_lossy(&bytes[..len as usize]), &ENGLISH);
+    if grouped.len() >= size as usize {
+        return len;
+    }
+    bytes[..grouped.len()].copy_from_slice(grouped.as_bytes());
+    grouped.len() as ptrdiff_t
+}
+
+/// A number to format.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Number {
+    Integer(EmacsInt),
+    Float(f64),
+}
+
+impl Number {
+    fn from_lisp(number: LispObject) -> Number {
+        match number.as_fixnum() {
+            Some(n) => Number::Integer(n),
+            None => {
+                if !number.is_float() {
+                    wrong_type!(intern("numberp"), number);
+                }
+                Number::Float(extract_float(number.to_raw()))
+            }
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        match self {
+            Number::Integer(n) => n as f64,
+            Number::Float(x) => x,
+        }
+    }
+
+    /// Write the number in decimal, with PRECISION digits after the
+    /// point or, by default, none for integers and as many as needed
+    /// to read the same float back.
+    fn decimal(self, precision: Option<usize>) -> String {
+        match (self, precision) {
+            (Number::Integer(n), None) | (Number::Integer(n), Some(0)) => n.to_string(),
+            (Number::Integer(n), Some(p)) => format!("{}.{}", n, "0".repeat(p)),
+            (Number::Float(x), None) => x.to_string(),
+            (Number::Float(x), Some(p)) => format!("{:.*}", p, x),
+        }
+    }
+}
+
+fn exponent(scientific: &str) -> i32 {
+    scientific
+        .rfind('e')
+        .and_then(|i| scientific[i + 1..].parse().ok())
+        .unwrap_or(0)
+}
+
+/// Return the mantissa and exponent of X in engineering notation, where
+/// the exponent is a multiple of three.  The mantissa has PRECISION
+/// digits after the point or, by default, as many as needed.
+fn engineering(x: f64, precision: Option<usize>) -> (String, i32) {
+    if x == 0.0 || !x.is_finite() {
+        return (Number::Float(x).decimal(precision), 0);
+    }
+    let shift = |e: i32| ((e % 3 + 3) % 3) as usize;
+    let mut scientific = format!("{:e}", x);
+    if let Some(p) = precision {
+        let e = exponent(&scientific);
+        scientific = format!("{:.*e}", p + shift(e), x);
+        // Rounding may have carried into the next power of ten.
+        let rounded = exponent(&scientific);
+        if rounded != e {
+            scientific = format!("{:.*e}", p + shift(rounded), x);
+        }
+    }
+    let e = exponent(&scientific);
+    let mantissa = &scientific[..scientific.rfind('e').unwrap()];
+    let negative = mantissa.starts_with('-');
+    let mut digits: String = mantissa.chars().filter(|c| c.is_digit(10)).collect();
+    let whole = shift(e) + 1;
+    while digits.len() < whole {
+        digits.push('0');
+    }
+    let mut result = String::new();
+    if negative {
+        result.push('-');
+    }
+    result.push_str(&digits[..whole]);
+    if digits.len() > whole {
+        result.push('.');
+        result.push_str(&digits[whole..]);
+    }
+    (result, e - shift(e) as i32)
+}
+
+const SI_PREFIXES: [&str; 17] = [
+    "y", "z", "a", "f", "p", "n", "\u{b5}", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+];
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"
+];
+
+const SCALES: [&str; 7] = [
+    "",
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+];
+
+/// Spell out N, which is below 1000 and not 0, into WORDS.
+fn spell_below_thousand(n: usize, words: &mut Vec<String>) {
+    if n >= 100 {
+        words.push(ONES[n / 100].to_string());
+        words.push("hundred".to_string());
+    }
+    let rest = n % 100;
+    if rest >= 20 {
+        if rest % 10 == 0 {
+            words.push(TENS[rest / 10].to_string());
+        } else {
+            words.push(format!("{}-{}", TENS[rest / 10], ONES[rest % 10]));
+        }
+    } else if rest > 0 {
+        words.push(ONES[rest].to_string());
+    }
+}
+
+/// Spell out N in English, as in "one hundred twenty-three".
+fn spell(n: i64) -> String {
+    if n == 0 {
+        return ONES[0].to_string();
+    }
+    let mut words = Vec::new();
+    if n < 0 {
+        words.push("minus".to_string());
+    }
+    // The magnitude of the most negative number does not fit in an i64.
+    let magnitude = if n < 0 {
+        (-(n + 1)) as u64 + 1
+    } else {
+        n as u64
+    };
+    let mut groups = Vec::new();
+    let mut rest = magnitude;
+    while rest > 0 {
+        groups.push((rest % 1000) as usize);
+        rest /= 1000;
+    }
+    for (scale, &group) in groups.iter().enumerate().rev() {
+        if group > 0 {
+            spell_below_thousand(group, &mut words);
+            if scale > 0 {
+                words.push(SCALES[scale].to_string());
+            }
+        }
+    }
+    words.join(" ")
+}
+
+fn ordinal_suffix(n: i64) -> &'static str {
+    let n = (n % 100).abs();
+    match (n / 10, n % 10) {
+        (1, _) => "th",
+        (_, 1) => "st",
+        (_, 2) => "nd",
+        (_, 3) => "rd",
+        _ => "th",
+    }
+}
+
+/// Turn the spelled-out cardinal CARDINAL into an ordinal.
+fn ordinal_words(cardinal: &str) -> String {
+    let split = cardinal
+        .rfind(|c: char| c == ' ' || c == '-')
+        .map_or(0, |i| i + 1);
+    let (head, last) = cardinal.split_at(split);
+    let ordinal = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        _ if last.ends_with('y') => format!("{}ieth", &last[..last.len() - 1]),
+        _ => format!("{}th", last),
+    };
+    format!("{}{}", head, ordinal)
+}
+
+/// Return a string that shows NUMBER for people to read.
+/// STYLE says how:
+///
+/// nil or `grouped'  The digits before the decimal point grouped in
+///                   thousands, as in \"1,234,567.5\".
+/// `engineering'     Engineering notation, whose exponent is a multiple
+///                   of three, as in \"1.2345e6\".
+/// `si'              Like `engineering', with the SI prefix for the
+///                   exponent, as in \"1.2345M\".
+/// `words'           The integer NUMBER spelled out in English, as in
+///                   \"one hundred twenty-three\".
+/// `ordinal'         The integer NUMBER as an ordinal, as in \"1,001st\".
+/// `ordinal-words'   The ordinal spelled out in English, as in
+///                   \"twenty-first\".
+///
+/// PRECISION, if non-nil, is the number of digits after the decimal
+/// point, to which NUMBER is rounded.  By default integers have none
+/// and floats as many as it takes to read back the same float.
+///
+/// The decimal point and the separator of the groups are those of
+/// LOCALE, a locale name such as \"de_DE.UTF-8\", which defaults to
+/// `current-locale-environment'.  Use \"C\" for the English separators
+/// whatever the locale.
+#[lisp_fn(min = "1")]
+pub fn format_number(
+    number: LispObject,
+    style: LispObject,
+    precision: LispObject,
+    locale: LispObject,
+) -> LispObject {
+    let locale = if locale.is_nil() {
+        LispObject::from(unsafe { find_symbol_value(intern("current-locale-environment").to_raw()) })
+    } else {
+        locale.as_string_or_error();
+        locale
+    };
+    let locale = match locale.as_string() {
+        Some(name) => number_locale(&String::from_utf8_lossy(name.as_slice())),
+        None => ENGLISH,
+    };
+    let precision = if precision.is_nil() {
+        None
+    } else {
+        Some(precision.as_natnum_or_error() as usize)
+    };
+
+    let text = if style.is_nil() || style.eq(intern("grouped")) {
+        localize(&Number::from_lisp(number).decimal(precision), &locale)
+    } else if style.eq(intern("engineering")) || style.eq(intern("si")) {
+        let (mantissa, e) = engineering(Number::from_lisp(number).to_f64(), precision);
+        let mantissa = mantissa.replace('.', locale.decimal);
+        let prefix = if e % 3 == 0 && e.abs() <= 24 {
+            SI_PREFIXES.get(((e + 24) / 3) as usize)
+        } else {
+            None
+        };
+        match prefix {
+            Some(prefix) if style.eq(intern("si")) => format!("{}{}", mantissa, prefix),
+            _ if e == 0 => mantissa,
+            _ => format!("{}e{}", mantissa, e),
+        }
+    } else if style.eq(intern("words")) {
+        spell(number.as_fixnum_or_error() as i64)
+    } else if style.eq(intern("ordinal")) {
+        let n = number.as_fixnum_or_error();
+        format!("{}{}", localize(&n.to_string(), &locale), ordinal_suffix(n as i64))
+    } else if style.eq(intern("ordinal-words")) {
+        ordinal_words(&spell(number.as_fixnum_or_error() as i64))
+    } else {
+        xsignal!(Qerror, lisp_string("Unknown number style"), style);
+    };
+    lisp_string(&text)
+}
+
+#[test]
+fn test_localize() {
+    assert_eq!(localize("1234567", &ENGLISH), "1,234,567");
+    assert_eq!(localize("-1234.5678", &ENGLISH), "-1,234.5678");
+    assert_eq!(localize("+123", &ENGLISH), "+123");
+    assert_eq!(localize("1.5e+07", &ENGLISH), "1.5e+07");
+    assert_eq!(localize("inf", &ENGLISH), "inf");
+    assert_eq!(localize("1234567.5", &number_locale("de_DE.UTF-8")), "1.234.567,5");
+    assert_eq!(localize("1234567", &number_locale("en_IN")), "12,34,567");
+    assert_eq!(localize("1234", &number_locale("fr_FR")), "1\u{a0}234");
+    assert_eq!(number_locale("pt_BR"), number_locale("pt_PT"));
+    assert_eq!(number_locale("es_MX"), ENGLISH);
+    assert_eq!(number_locale("C"), ENGLISH);
+}
+
+#[test]
+fn test_group_digits() {
+    let mut buf = [0u8; 32];
+    buf[..8].copy_from_slice(b"-1234567");
+    let len = group_digits(buf.as_mut_ptr() as *mut c_char, 8, 32);
+    assert_eq!(&buf[..len as usize], b"-1,234,567");
+    let len = group_digits(buf.as_mut_ptr() as *mut c_char, len, 11);
+    assert_eq!(&buf[..len as usize], b"-1,234,567");
+}
+
+#[test]
+fn test_engineering() {
+    assert_eq!(engineering(12345.0, None), ("12.345".to_string(), 3));
+    assert_eq!(engineering(0.0015, None), ("1.5".to_string(), -3));
+    assert_eq!(engineering(-123456.0, Some(1)), ("-123.5".to_string(), 3));
+    assert_eq!(engineering(999.96, Some(1)), ("1.0".to_string(), 3));
+    assert_eq!(engineering(100.0, None), ("100".to_string(), 0));
+}
+
+#[test]
+fn test_spell() {
+    assert_eq!(spell(0), "zero");
+    assert_eq!(spell(21), "twenty-one");
+    assert_eq!(spell(-1_002_030), "minus one million two thousand thirty");
+    assert_eq!(
+        spell(i64::min_value()),
+        "minus nine quintillion two hundred twenty-three quadrillion \
+         three hundred seventy-two trillion thirty-six billion \
+         eight hundred fifty-four million seven hundred seventy-five thousand \
+         eight hundred eight"
+    );
+    assert_eq!(ordinal_words(&spell(21)), "twenty-first");
+    assert_eq!(ordinal_words(&spell(40)), "fortieth");
+    assert_eq!(ordinal_words(&spell(112)), "one hundred twelfth");
+    assert_eq!(ordinal_words(&spell(1000)), "one thousandth");
+    assert_eq!(ordinal_suffix(1), "st");
+    assert_eq!(ordinal_suffix(12), "th");
+    assert_eq!(ordinal_suffix(-22), "nd");
+    assert_eq!(ordinal_suffix(113), "th");
+}
+
+include!(concat!(env!("OUT_DIR"), "/format_exports.rs"));
@@ -0,0 +1,192 @@
+//! Expanding `format-spec' templates.
+//!
+//! Tab, header and mode line code formats the same templates, such as
+//! "%n %f", for every tab or window on each redisplay.  `format-spec'
+//! does that in a temporary buffer with regexp searches; here the
+//! template is parsed directly and the pieces concatenated.
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use multibyte::Codepoint;
+use strings::lisp_string;
+
+/// A part of a template, by character indices into it.
+#[derive(Debug, PartialEq)]
+enum Piece {
+    /// Text copied as it is, from START to END.
+    Text(usize, usize),
+    /// A %-spec at START, with the `format' flags between START + 1
+    /// and the spec character.
+    Spec {
+        start: usize,
+        flags: String,
+        spec: Codepoint,
+    },
+}
+
+/// Whether C may be in the flags of a spec: `-', `.' or a digit.
+fn is_flag(c: Codepoint) -> bool {
+    match c {
+        0x2D | 0x2E | 0x30...0x39 => true,
+        _ => false,
+    }
+}
+
+/// Whether C is an ASCII letter, which ends a spec.
+fn is_spec(c: Codepoint) -> bool {
+    match c {
+        0x41...0x5A | 0x61...0x7A => true,
+        _ => false,
+    }
+}
+
+/// Split the template CHARS into pieces, or return None if a `%' is
+/// followed by neither another `%' nor a spec.
+fn parse(chars: &[Codepoint]) -> Option<Vec<Piece>> {
+    let percent = Codepoint::from(b'%');
+    let mut pieces = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != percent {
+            i += 1;
+            continue;
+        }
+        if chars.get(i + 1) == Some(&percent) {
+            // A quoted percent sign keeps the first `%'.
+            pieces.push(Piece::Text(text_start, i + 1));
+            i += 2;
+            text_start = i;
+            continue;
+        }
+        let flags_end = (i + 1..chars.len())
+            .find(|&j| !is_flag(chars[j]))
+            .unwrap_or(chars.len());
+        match chars.get(flags_end) {
+            Some(&spec) if is_spec(spec) => {
+                if text_start < i {
+                    pieces.push(Piece::Text(text_start, i));
+                }
+                let flags = chars[i + 1..flags_end]
+                    .iter()
+                    .map(|&c| c as u8 as char)
+                    .collect();
+                pieces.push(Piece::Spec {
+                    start: i,
+                    flags,
+                    spec,
+                });
+                i = flags_end + 1;
+                text_start = i;
+            }
+            _ => return None,
+        }
+    }
+    if text_start < chars.len() {
+        pieces.push(Piece::Text(text_start, chars.len()));
+    }
+    Some(pieces)
+}
+
+/// Return a string based on FORMAT and SPECIFICATION, like `format-spec'.
+/// FORMAT is a string with `format'-like specs such as "%n %-10f", and
+/// SPECIFICATION an alist from spec characters to their values.  "%%"
+/// stands for a single `%'.  The text properties of the `%' of a spec
+/// are added to the text it stands for.  The format is parsed directly
+/// instead of being searched in a temporary buffer, so that this is
+/// cheap enough to call for each tab or window on every redisplay.
+#[lisp_fn]
+pub fn format_spec_native(format: LispObject, specification: LispObject) -> LispObject {
+    let chars: Vec<Codepoint> = format.as_string_or_error().chars().collect();
+    let pieces = match parse(&chars) {
+        Some(pieces) => pieces,
+        None => error!("Invalid format string"),
+    };
+    let substring = |start: usize, end: usize| {
+        call!(
+            intern("substring"),
+            format,
+            LispObject::from_natnum(start as EmacsInt),
+            LispObject::from_natnum(end as EmacsInt)
+        )
+    };
+    let mut result = lisp_string("");
+    for piece in pieces {
+        let text = match piece {
+            Piece::Text(start, end) => substring(start, end),
+            Piece::Spec { start, flags, spec } => {
+                let value = call!(
+                    intern("assq"),
+                    LispObject::from_natnum(EmacsInt::from(spec)),
+                    specification
+                );
+                if value.is_nil() {
+                    error!(
+                        "Invalid format character: `%{}'",
+                        ::std::char::from_u32(spec).unwrap_or('?')
+                    );
+                }
+                let directive = lisp_string(&format!("%{}s", flags));
+                let text = call!(intern("format"), directive, call!(intern("cdr"), value));
+                let properties = call!(
+                    intern("text-properties-at"),
+                    LispObject::from_natnum(start as EmacsInt),
+                    format
+                );
+                if properties.is_not_nil() {
+                    let text = call!(intern("copy-sequence"), text);
+                    call!(
+                        intern("add-text-properties"),
+                        LispObject::from_natnum(0),
+                        call!(intern("length"), text),
+                        properties,
+                        text
+                    );
+                    text
+                } else {
+                    text
+                }
+            }
+        };
+        result = call!(intern("concat"), result, text);
+    }
+    result
+}
+
+#[test]
+fn test_parse() {
+    let chars = |s: &str| s.chars().map(|c| c as Codepoint).collect::<Vec<_>>();
+    assert_eq!(parse(&chars("")), Some(vec![]));
+    assert_eq!(parse(&chars("abc")), Some(vec![Piece::Text(0, 3)]));
+    assert_eq!(
+        parse(&chars("a %-10n!")),
+        Some(vec![
+            Piece::Text(0, 2),
+            Piece::Spec {
+                start: 2,
+                flags: "-10".to_string(),
+                spec: Codepoint::from(b'n'),
+            },
+            Piece::Text(7, 8),
+        ])
+    );
+    assert_eq!(
+        parse(&chars("100%% %f")),
+        Some(vec![
+            Piece::Text(0, 4),
+            Piece::Text(5, 6),
+            Piece::Spec {
+                start: 6,
+                flags: String::new(),
+                spec: Codepoint::from(b'f'),
+            },
+        ])
+    );
+    assert_eq!(parse(&chars("50%")), None);
+    assert_eq!(parse(&chars("%-3!")), None);
+}
+
+include!(concat!(env!("OUT_DIR"), "/format_spec_exports.rs"));
@@ -0,0 +1,310 @@
+//! How often and how lately things were chosen.
+//!
+//! Completion UIs record here which command, file, buffer or other
+//! candidate was selected, under a category such as `command' or `file',
+//! and sort their candidates with `frecency-sort'.  Each selection adds
+//! 1 to the score of its key, and scores decay exponentially, halving
+//! every HALF_LIFE seconds, so that things chosen often but long ago
+//! come after things chosen a few times lately.  Only the decayed score
+//! and the time it was computed for are kept for each key.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use remacs_macros::lisp_fn;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::list;
+use persist::{decode, encode, finish_background_writes, write_in_background};
+use strings::lisp_string;
+use symbols::symbol_name;
+use util::{expand_file_name_to_path, report_io_error};
+
+/// A week, in seconds.
+const HALF_LIFE: f64 = 7.0 * 24.0 * 3600.0;
+/// Keys whose score has decayed below this are forgotten.
+const MIN_SCORE: f64 = 0.01;
+/// The most keys kept in a category.  Beyond that, those with the
+/// lowest scores are forgotten.
+const MAX_KEYS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Record {
+    score: f64,
+    time: f64,
+}
+
+impl Record {
+    fn score_at(&self, now: f64) -> f64 {
+        self.score * (-(now - self.time).max(0.0) / HALF_LIFE).exp2()
+    }
+}
+
+struct Frecency {
+    /// The records of each category, by key.
+    categories: HashMap<String, HashMap<String, Record>>,
+}
+
+impl Frecency {
+    fn record(&mut self, category: String, key: String, now: f64) {
+        let records = self.categories.entry(category).or_insert_with(HashMap::new);
+        let score = records.get(&key).map_or(0.0, |record| record.score_at(now)) + 1.0;
+        records.insert(key, Record { score, time: now });
+        if records.len() > MAX_KEYS {
+            prune(records, now);
+        }
+    }
+
+    fn score(&self, category: &str, key: &str, now: f64) -> f64 {
+        self.categories
+            .get(category)
+            .and_then(|records| records.get(key))
+            .map_or(0.0, |record| record.score_at(now))
+    }
+
+    /// Forget the keys whose scores have decayed away.
+    fn prune(&mut self, now: f64) {
+        for records in self.categories.values_mut() {
+            prune(records, now);
+        }
+        self.categories.retain(|_, records| !records.is_empty());
+    }
+}
+
+/// Forget the RECORDS that scored below MIN_SCORE at NOW and, if there
+/// are still more than MAX_KEYS, the lowest scoring ones.
+fn prune(records: &mut HashMap<String, Record>, now: f64) {
+    records.retain(|_, record| record.score_at(now) >= MIN_SCORE);
+    if records.len() > MAX_KEYS {
+        let mut scores: Vec<f64> = records.values().map(|r| r.score_at(now)).collect();
+        scores.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        let lowest = scores[MAX_KEYS - 1];
+        records.retain(|_, record| record.score_at(now) >= lowest);
+    }
+}
+
+lazy_static! {
+    static ref FRECENCY: Mutex<Frecency> = Mutex::new(Frecency {
+        categories: HashMap::new(),
+    });
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as f64 + f64::from(d.subsec_nanos()) / 1e9)
+        .unwrap_or(0.0)
+}
+
+/// Return the text of KEY, a string or symbol.
+fn key_text(key: LispObject) -> String {
+    let name = if key.is_symbol() {
+        symbol_name(key)
+    } else {
+        key
+    };
+    match name.as_string() {
+        Some(string) => String::from_utf8_lossy(string.as_slice()).into_owned(),
+        None => wrong_type!(intern("stringp"), key),
+    }
+}
+
+/// Return the name of CATEGORY, which must be a symbol.
+fn category_text(category: LispObject) -> String {
+    if !category.is_symbol() {
+        wrong_type!(intern("symbolp"), category);
+    }
+    key_text(category)
+}
+
+/// Record that KEY was selected just now.
+/// KEY is a string or symbol, and CATEGORY a symbol such as `command',
+/// `file' or `buffer' saying what kind of thing KEY names.
+#[lisp_fn(min = "1")]
+pub fn frecency_record(key: LispObject, category: LispObject) -> LispObject {
+    let key = key_text(key);
+    let category = category_text(category);
+    FRECENCY.lock().unwrap().record(category, key, now());
+    LispObject::constant_nil()
+}
+
+/// Return the frecency score of KEY in CATEGORY.
+/// Each selection of KEY recorded by `frecency-record' adds 1 to the
+/// score, and the score halves every week.  The score of a key that was
+/// never selected is 0.0.
+#[lisp_fn(min = "1")]
+pub fn frecency_score(key: LispObject, category: LispObject) -> LispObject {
+    let key = key_text(key);
+    let category = category_text(category);
+    let score = FRECENCY.lock().unwrap().score(&category, &key, now());
+    LispObject::from_float(score)
+}
+
+/// Return a copy of the list CANDIDATES sorted by frecency in CATEGORY.
+/// The candidates selected most often and most lately come first, and
+/// those with equal scores, such as the ones never selected, keep their
+/// order.  KEY-FN is called with each candidate to get the string or
+/// symbol it was recorded as by `frecency-record'.  If KEY-FN is nil, a
+/// candidate is its own key, or, if it is a cons, its car is.
+#[lisp_fn(min = "2")]
+pub fn frecency_sort(
+    candidates: LispObject,
+    key_fn: LispObject,
+    category: LispObject,
+) -> LispObject {
+    let category = category_text(category);
+    let candidates: Vec<LispObject> = candidates.iter_cars().collect();
+    let keys: Vec<String> = candidates
+        .iter()
+        .map(|&candidate| {
+            if key_fn.is_not_nil() {
+                key_text(call!(key_fn, candidate))
+            } else {
+                key_text(candidate.as_cons().map_or(candidate, |cons| cons.car()))
+            }
+        })
+        .collect();
+    let scores: Vec<f64> = {
+        let frecency = FRECENCY.lock().unwrap();
+        let now = now();
+        keys.iter()
+            .map(|key| frecency.score(&category, key, now))
+            .collect()
+    };
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| {
+        scores[b]
+            .partial_cmp(&scores[a])
+            .unwrap_or(Ordering::Equal)
+    });
+    let mut sorted: Vec<LispObject> = order.iter().map(|&i| candidates[i]).collect();
+    list(&mut sorted)
+}
+
+/// Forget the selections of KEY in CATEGORY.
+/// Return t if any were recorded, nil otherwise.
+#[lisp_fn(min = "1")]
+pub fn frecency_forget(key: LispObject, category: LispObject) -> LispObject {
+    let key = key_text(key);
+    let category = category_text(category);
+    let removed = FRECENCY
+        .lock()
+        .unwrap()
+        .categories
+        .get_mut(&category)
+        .and_then(|records| records.remove(&key))
+        .is_some();
+    LispObject::from_bool(removed)
+}
+
+/// Forget all the selections recorded by `frecency-record'.
+#[lisp_fn]
+pub fn frecency_clear() -> LispObject {
+    FRECENCY.lock().unwrap().categories.clear();
+    LispObject::constant_nil()
+}
+
+/// Read the selections saved in FILE by `frecency-save' and use them in
+/// place of the ones recorded.  If FILE does not exist, the recorded
+/// selections are all forgotten.
+#[lisp_fn]
+pub fn frecency_load(file: LispObject) -> LispObject {
+    finish_background_writes();
+    let path = expand_file_name_to_path(file);
+    let mut data = Vec::new();
+    let saved = match File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+        Ok(_) => decode(&data),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => LispObject::constant_nil(),
+        Err(err) => report_io_error(b"Reading frecency data\0", file, &err),
+    };
+    if !saved.is_list() {
+        error!("Invalid frecency data");
+    }
+    let mut categories: HashMap<String, HashMap<String, Record>> = HashMap::new();
+    for entry in saved.iter_cars() {
+        let slots = match entry.as_vectorlike().and_then(|v| v.as_vector()) {
+            Some(ref vector) if vector.len() == 4 => vector.as_slice().to_vec(),
+            _ => error!("Invalid frecency data"),
+        };
+        if !slots[0].is_string() || !slots[1].is_string() {
+            error!("Invalid frecency data");
+        }
+        let record = Record {
+            score: slots[2].any_to_float_or_error(),
+            time: slots[3].any_to_float_or_error(),
+        };
+        categories
+            .entry(key_text(slots[0]))
+            .or_insert_with(HashMap::new)
+            .insert(key_text(slots[1]), record);
+    }
+    FRECENCY.lock().unwrap().categories = categories;
+    LispObject::constant_nil()
+}
+
+/// Save the recorded selections to FILE.
+/// Keys whose scores have decayed to almost nothing are forgotten first.
+/// The file is written on a separate thread, so this returns at once;
+/// Emacs waits for the write to finish before it exits.
+#[lisp_fn]
+pub fn frecency_save(file: LispObject) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let name = key_text(file);
+    let saved: Vec<(String, String, Record)> = {
+        let mut frecency = FRECENCY.lock().unwrap();
+        frecency.prune(now());
+        frecency
+            .categories
+            .iter()
+            .flat_map(|(category, records)| {
+                records
+                    .iter()
+                    .map(move |(key, &record)| (category.clone(), key.clone(), record))
+            })
+            .collect()
+    };
+    let mut entries: Vec<LispObject> = saved
+        .iter()
+        .map(|&(ref category, ref key, record)| {
+            call!(
+                intern("vector"),
+                lisp_string(category),
+                lisp_string(key),
+                LispObject::from_float(record.score),
+                LispObject::from_float(record.time)
+            )
+        })
+        .collect();
+    write_in_background(path, name, encode(list(&mut entries)));
+    LispObject::constant_nil()
+}
+
+#[test]
+fn test_frecency() {
+    let mut frecency = Frecency {
+        categories: HashMap::new(),
+    };
+    let day = 24.0 * 3600.0;
+    for _ in 0..4 {
+        frecency.record("file".to_string(), "old".to_string(), 0.0);
+    }
+    frecency.record("file".to_string(), "new".to_string(), 14.0 * day);
+    assert_eq!(frecency.score("file", "old", 0.0), 4.0);
+    assert_eq!(frecency.score("file", "old", 7.0 * day), 2.0);
+    assert_eq!(frecency.score("file", "old", 14.0 * day), 1.0);
+    assert!(frecency.score("file", "new", 15.0 * day) > frecency.score("file", "old", 15.0 * day));
+    assert_eq!(frecency.score("buffer", "old", 0.0), 0.0);
+
+    frecency.record("file".to_string(), "new".to_string(), 21.0 * day);
+    assert_eq!(frecency.score("file", "new", 21.0 * day), 1.5);
+
+    frecency.prune(100.0 * day);
+    assert!(frecency.categories.is_empty());
+}
+
+include!(concat!(env!("OUT_DIR"), "/frecency_exports.rs"));
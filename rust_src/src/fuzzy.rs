@@ -0,0 +1,137 @@
+//! Fuzzy matching of strings against a pattern.
+//!
+//! A string matches a pattern if it contains the characters of the
+//! pattern in order, though not necessarily next to each other.  The
+//! score of a match rewards characters at the start of the string or of
+//! a word and runs of adjacent characters, and it penalizes the gaps
+//! between them, so that "ff" ranks "find-file" above "buffer-offset".
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lisp::LispObject;
+use lisp::defsubr;
+
+const MATCH: i64 = 16;
+const START_BONUS: i64 = 32;
+const WORD_BONUS: i64 = 12;
+const ADJACENT_BONUS: i64 = 16;
+const GAP_PENALTY: i64 = 1;
+/// How many characters before the first match are penalized.
+const MAX_LEADING_GAP: usize = 8;
+
+/// Return the bonus for a match of the character at INDEX of CHARS.
+fn position_bonus(chars: &[char], index: usize) -> i64 {
+    if index == 0 {
+        return START_BONUS;
+    }
+    let previous = chars[index - 1];
+    let current = chars[index];
+    if (!previous.is_alphanumeric() && current.is_alphanumeric())
+        || (previous.is_lowercase() && current.is_uppercase())
+    {
+        WORD_BONUS
+    } else {
+        0
+    }
+}
+
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+/// Return the score of the best match of PATTERN in CANDIDATE, or None
+/// if it does not match.  The empty pattern matches everything with a
+/// score of 0.
+pub fn score(pattern: &str, candidate: &str, ignore_case: bool) -> Option<i64> {
+    let pattern: Vec<char> = pattern.chars().map(|c| fold(c, ignore_case)).collect();
+    let chars: Vec<char> = candidate.chars().collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    if pattern.len() > chars.len() {
+        return None;
+    }
+    let folded: Vec<char> = chars.iter().map(|&c| fold(c, ignore_case)).collect();
+
+    // BEST[j] is the best score of the pattern so far with its last
+    // character matched at J.
+    let mut best: Vec<Option<i64>> = folded
+        .iter()
+        .enumerate()
+        .map(|(j, &c)| {
+            if c == pattern[0] {
+                let gap = j.min(MAX_LEADING_GAP) as i64;
+                Some(MATCH + position_bonus(&chars, j) - gap * GAP_PENALTY)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    for &p in &pattern[1..] {
+        let mut next = vec![None; chars.len()];
+        // The best score matched before J - 1, less the gap up to J.
+        let mut distant: Option<i64> = None;
+        for j in 1..chars.len() {
+            if j >= 2 {
+                distant = match (distant, best[j - 2]) {
+                    (Some(a), Some(b)) => Some(a.max(b) - GAP_PENALTY),
+                    (a, b) => a.or(b).map(|s| s - GAP_PENALTY),
+                };
+            }
+            if folded[j] != p {
+                continue;
+            }
+            let adjacent = best[j - 1].map(|s| s + ADJACENT_BONUS);
+            let previous = match (adjacent, distant) {
+                (Some(a), Some(d)) => Some(a.max(d)),
+                (a, d) => a.or(d),
+            };
+            next[j] = previous.map(|s| s + MATCH + position_bonus(&chars, j));
+        }
+        best = next;
+    }
+    best.into_iter().filter_map(|s| s).max()
+}
+
+/// Return true if PATTERN should match without regard to case, which
+/// is when it has no upper case letters.
+pub fn ignore_case_for(pattern: &str) -> bool {
+    !pattern.chars().any(|c| c.is_uppercase())
+}
+
+/// Return how well STRING matches PATTERN as a fuzzy pattern, or nil.
+/// STRING matches if it contains the characters of PATTERN in order,
+/// not necessarily next to each other.  The score is an integer that
+/// is higher when the matched characters start words or the string and
+/// when they are adjacent, and lower when they are far apart.  Case is
+/// ignored unless PATTERN contains upper case letters.
+#[lisp_fn]
+pub fn fuzzy_match_score(pattern: LispObject, string: LispObject) -> LispObject {
+    let pattern = String::from_utf8_lossy(pattern.as_string_or_error().as_slice()).into_owned();
+    let string = String::from_utf8_lossy(string.as_string_or_error().as_slice()).into_owned();
+    match score(&pattern, &string, ignore_case_for(&pattern)) {
+        Some(n) => LispObject::from_fixnum(n as EmacsInt),
+        None => LispObject::constant_nil(),
+    }
+}
+
+#[test]
+fn test_score() {
+    assert_eq!(score("", "anything", true), Some(0));
+    assert_eq!(score("abc", "ab", true), None);
+    assert_eq!(score("ba", "abc", true), None);
+    assert!(score("ff", "find-file", true) > score("ff", "buffer-offset", true));
+    assert!(score("fi", "find", true) > score("fi", "a-find", true));
+    assert!(score("fb", "fooBar", true) > score("fb", "foobar", true));
+    assert!(score("abc", "abc", true) > score("abc", "a-b-c", true));
+    assert_eq!(score("A", "a", false), None);
+    assert!(score("A", "a", true).is_some());
+}
+
+include!(concat!(env!("OUT_DIR"), "/fuzzy_exports.rs"));
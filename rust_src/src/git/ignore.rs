@@ -0,0 +1,269 @@
+//! Matching file names against `.gitignore' patterns.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+struct Pattern {
+    glob: Vec<u8>,
+    /// The directory of the file the pattern comes from, relative to the
+    /// top of the work tree, with a trailing slash unless empty.
+    base: Vec<u8>,
+    negated: bool,
+    directories_only: bool,
+    /// The pattern contains a slash, so it matches whole paths relative
+    /// to BASE rather than file names at any depth.
+    anchored: bool,
+}
+
+/// The ignore patterns in effect in some directory.
+pub struct Ignores {
+    patterns: Vec<Pattern>,
+}
+
+impl Ignores {
+    pub fn new() -> Ignores {
+        Ignores {
+            patterns: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Forget the patterns added after the first LEN.
+    pub fn truncate(&mut self, len: usize) {
+        self.patterns.truncate(len);
+    }
+
+    /// Add the patterns of the ignore file at PATH, if any, which apply
+    /// to the directory BASE.
+    pub fn add_file(&mut self, path: &Path, base: &[u8]) -> io::Result<()> {
+        let mut contents = Vec::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_end(&mut contents)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        for line in contents.split(|&b| b == b'\n') {
+            self.add_line(line, base);
+        }
+        Ok(())
+    }
+
+    fn add_line(&mut self, line: &[u8], base: &[u8]) {
+        let mut line = line;
+        if line.last() == Some(&b'\r') {
+            line = &line[..line.len() - 1];
+        }
+        // Trailing spaces don't count unless escaped.
+        while line.last() == Some(&b' ') && !line[..line.len() - 1].ends_with(b"\\") {
+            line = &line[..line.len() - 1];
+        }
+        if line.is_empty() || line[0] == b'#' {
+            return;
+        }
+        let negated = line[0] == b'!';
+        if negated {
+            line = &line[1..];
+        }
+        let directories_only = line.last() == Some(&b'/');
+        if directories_only {
+            line = &line[..line.len() - 1];
+        }
+        let anchored = line.contains(&b'/');
+        if line.first() == Some(&b'/') {
+            line = &line[1..];
+        }
+        if line.is_empty() {
+            return;
+        }
+        self.patterns.push(Pattern {
+            glob: line.to_vec(),
+            base: base.to_vec(),
+            negated,
+            directories_only,
+            anchored,
+        });
+    }
+
+    /// Return true if PATH, relative to the top of the work tree, is
+    /// ignored.  The last pattern that matches decides.
+    pub fn is_ignored(&self, path: &[u8], is_directory: bool) -> bool {
+        for pattern in self.patterns.iter().rev() {
+            if (pattern.directories_only && !is_directory) || !path.starts_with(&pattern.base) {
+                continue;
+            }
+            let relative = &path[pattern.base.len()..];
+            let subject = if pattern.anchored {
+                relative
+            } else {
+                match relative.iter().rposition(|&b| b == b'/') {
+                    Some(slash) => &relative[slash + 1..],
+                    None => relative,
+                }
+            };
+            if wildmatch(&pattern.glob, subject) {
+                return !pattern.negated;
+            }
+        }
+        false
+    }
+}
+
+/// Match the character class at the start of PATTERN, just after its
+/// `[', against C.  Return whether it matched and the length of the
+/// class including its closing `]', or `None' if it isn't closed.
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negated = match pattern.first() {
+        Some(&b'!') | Some(&b'^') => {
+            i += 1;
+            true
+        }
+        _ => false,
+    };
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        let mut low = pattern[i];
+        if low == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if low == b'\\' && i + 1 < pattern.len() {
+            i += 1;
+            low = pattern[i];
+        }
+        if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+            let mut high = pattern[i + 2];
+            i += 2;
+            if high == b'\\' && i + 1 < pattern.len() {
+                i += 1;
+                high = pattern[i];
+            }
+            matched |= low <= c && c <= high;
+        } else {
+            matched |= low == c;
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Match TEXT against the glob PATTERN the way git does for paths:
+/// wildcards don't match `/', except for `**' between slashes, which
+/// matches any number of directories.
+pub fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                let mut rest = p;
+                while rest < pattern.len() && pattern[rest] == b'*' {
+                    rest += 1;
+                }
+                let at_start = p == 0 || pattern[p - 1] == b'/';
+                let at_end = rest == pattern.len() || pattern[rest] == b'/';
+                if rest - p >= 2 && at_start && at_end {
+                    if rest == pattern.len() {
+                        return true;
+                    }
+                    let after = &pattern[rest + 1..];
+                    if wildmatch(after, &text[t..]) {
+                        return true;
+                    }
+                    return (t..text.len())
+                        .any(|i| text[i] == b'/' && wildmatch(after, &text[i + 1..]));
+                }
+                if rest == pattern.len() {
+                    return !text[t..].contains(&b'/');
+                }
+                for i in t..text.len() + 1 {
+                    if wildmatch(&pattern[rest..], &text[i..]) {
+                        return true;
+                    }
+                    if i < text.len() && text[i] == b'/' {
+                        break;
+                    }
+                }
+                return false;
+            }
+            b'?' => {
+                if t >= text.len() || text[t] == b'/' {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+            b'[' => {
+                if t >= text.len() || text[t] == b'/' {
+                    return false;
+                }
+                match match_class(&pattern[p + 1..], text[t]) {
+                    Some((true, len)) => {
+                        p += len + 1;
+                        t += 1;
+                    }
+                    Some((false, _)) => return false,
+                    None => {
+                        // An unclosed bracket is an ordinary character.
+                        if text[t] != b'[' {
+                            return false;
+                        }
+                        p += 1;
+                        t += 1;
+                    }
+                }
+            }
+            c => {
+                let (c, len) = if c == b'\\' && p + 1 < pattern.len() {
+                    (pattern[p + 1], 2)
+                } else {
+                    (c, 1)
+                };
+                if t >= text.len() || text[t] != c {
+                    return false;
+                }
+                p += len;
+                t += 1;
+            }
+        }
+    }
+    t == text.len()
+}
+
+#[test]
+fn test_wildmatch() {
+    assert!(wildmatch(b"*.o", b"foo.o"));
+    assert!(!wildmatch(b"*.o", b"dir/foo.o"));
+    assert!(wildmatch(b"**/foo", b"a/b/foo"));
+    assert!(wildmatch(b"**/foo", b"foo"));
+    assert!(wildmatch(b"a/**/b", b"a/b"));
+    assert!(wildmatch(b"a/**/b", b"a/x/y/b"));
+    assert!(wildmatch(b"build/**", b"build/x/y"));
+    assert!(wildmatch(b"f?o[a-c]", b"foob"));
+    assert!(!wildmatch(b"f?o[!a-c]", b"foob"));
+    assert!(wildmatch(b"\\*x", b"*x"));
+    assert!(!wildmatch(b"\\*x", b"ax"));
+}
+
+#[test]
+fn test_ignores() {
+    let mut ignores = Ignores::new();
+    for line in &["*.o", "!keep.o", "/TAGS", "build/", "doc/*.html  "] {
+        ignores.add_line(line.as_bytes(), b"");
+    }
+    ignores.add_line(b"*.tmp", b"sub/");
+    assert!(ignores.is_ignored(b"src/foo.o", false));
+    assert!(!ignores.is_ignored(b"src/keep.o", false));
+    assert!(ignores.is_ignored(b"TAGS", false));
+    assert!(!ignores.is_ignored(b"src/TAGS", false));
+    assert!(ignores.is_ignored(b"src/build", true));
+    assert!(!ignores.is_ignored(b"src/build", false));
+    assert!(ignores.is_ignored(b"doc/a.html", false));
+    assert!(!ignores.is_ignored(b"doc/sub/a.html", false));
+    assert!(ignores.is_ignored(b"sub/x/a.tmp", false));
+    assert!(!ignores.is_ignored(b"a.tmp", false));
+}
@@ -0,0 +1,146 @@
+//! Reading the git index, the staging area recording the contents of
+//! the next commit along with the file metadata seen when each file was
+//! last staged.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use super::odb::{invalid, ObjectId};
+
+pub struct IndexEntry {
+    pub mtime: (u32, u32),
+    pub mode: u32,
+    pub size: u32,
+    pub id: ObjectId,
+    /// 0 normally, or 1 to 3 for the base, ours and theirs versions of
+    /// a file with merge conflicts.
+    pub stage: u8,
+    /// The file is not checked out in a sparse checkout.
+    pub skip_worktree: bool,
+    /// The file was added with `git add -N'.
+    pub intent_to_add: bool,
+    pub path: Vec<u8>,
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    (bytes[pos] as u32) << 24 | (bytes[pos + 1] as u32) << 16 | (bytes[pos + 2] as u32) << 8
+        | bytes[pos + 3] as u32
+}
+
+fn read_u16(bytes: &[u8], pos: usize) -> u16 {
+    (bytes[pos] as u16) << 8 | bytes[pos + 1] as u16
+}
+
+/// Read the index file at PATH, returning its entries sorted by path.
+/// A missing index is empty.
+pub fn read_index(path: &Path) -> io::Result<Vec<IndexEntry>> {
+    let mut data = Vec::new();
+    match File::open(path) {
+        Ok(mut file) => file.read_to_end(&mut data)?,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    if data.len() < 12 || &data[..4] != b"DIRC" {
+        return Err(invalid("bad index signature"));
+    }
+    let version = read_u32(&data, 4);
+    if version < 2 || version > 4 {
+        return Err(invalid("unsupported index version"));
+    }
+    let count = read_u32(&data, 8) as usize;
+    let truncated = || invalid("truncated index");
+
+    let mut entries: Vec<IndexEntry> = Vec::with_capacity(count);
+    let mut pos = 12;
+    for _ in 0..count {
+        let start = pos;
+        if pos + 62 > data.len() {
+            return Err(truncated());
+        }
+        let mut id = [0u8; 20];
+        id.copy_from_slice(&data[pos + 40..pos + 60]);
+        let flags = read_u16(&data, pos + 60);
+        pos += 62;
+        let extended = if version >= 3 && flags & 0x4000 != 0 {
+            if pos + 2 > data.len() {
+                return Err(truncated());
+            }
+            pos += 2;
+            read_u16(&data, pos - 2)
+        } else {
+            0
+        };
+
+        let mut path = Vec::new();
+        if version == 4 {
+            // The path is compressed against the previous one: a count
+            // of bytes to drop from its end, then the bytes to add.
+            let mut byte = *data.get(pos).ok_or_else(&truncated)?;
+            let mut strip = (byte & 0x7f) as usize;
+            while byte & 0x80 != 0 {
+                pos += 1;
+                byte = *data.get(pos).ok_or_else(&truncated)?;
+                strip = ((strip + 1) << 7) | (byte & 0x7f) as usize;
+            }
+            pos += 1;
+            if let Some(previous) = entries.last() {
+                if strip > previous.path.len() {
+                    return Err(invalid("bad index path compression"));
+                }
+                path.extend_from_slice(&previous.path[..previous.path.len() - strip]);
+            }
+        }
+        let nul = data[pos..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(&truncated)?;
+        path.extend_from_slice(&data[pos..pos + nul]);
+        pos += nul + 1;
+        if version < 4 {
+            // Entries are padded with NULs to a multiple of 8 bytes.
+            pos = start + (pos - start + 7) / 8 * 8;
+        }
+
+        entries.push(IndexEntry {
+            mtime: (read_u32(&data, start + 8), read_u32(&data, start + 12)),
+            mode: read_u32(&data, start + 24),
+            size: read_u32(&data, start + 36),
+            id,
+            stage: ((flags >> 12) & 3) as u8,
+            skip_worktree: extended & 0x4000 != 0,
+            intent_to_add: extended & 0x2000 != 0,
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+#[test]
+fn test_read_index() {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    let mut data = b"DIRC\0\0\0\x02\0\0\0\x01".to_vec();
+    let mut entry = vec![0u8; 62];
+    entry[26] = 0x81; // mode 0o100644
+    entry[27] = 0xa4;
+    entry[39] = 5; // size
+    entry[40..60].copy_from_slice(&[7; 20]);
+    entry[61] = 5; // name length
+    entry.extend_from_slice(b"a.txt\0\0\0\0\0");
+    data.extend_from_slice(&entry);
+    let path = env::temp_dir().join("remacs-git-index-test");
+    File::create(&path).unwrap().write_all(&data).unwrap();
+    let entries = read_index(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path, b"a.txt");
+    assert_eq!(entries[0].mode, 0o100644);
+    assert_eq!(entries[0].size, 5);
+    assert_eq!(entries[0].stage, 0);
+    assert_eq!(entries[0].id, [7; 20]);
+    assert!(read_index(&env::temp_dir().join("remacs-no-such-index")).unwrap().is_empty());
+}
@@ -0,0 +1,610 @@
+//! Native access to git repositories.
+//!
+//! This reads the repository directly, index, refs, loose objects and
+//! pack files alike, so that version control support can find the
+//! state of files or the contents of a revision without running git
+//! for every buffer.
+
+mod ignore;
+mod index;
+mod odb;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use libc::{self, c_char, ptrdiff_t};
+use sha1;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_string, make_unibyte_string};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use util::{expand_file_name_to_path, file_name_from_bytes, os_str_bytes, path_from_bytes,
+           report_io_error};
+
+use self::ignore::Ignores;
+use self::index::{read_index, IndexEntry};
+use self::odb::{header_id, invalid, parse_hex_id, tree_entries, Kind, ObjectId, Odb, MODE_GITLINK,
+                MODE_TREE};
+
+/// The longest chain of symbolic refs followed.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+const MODE_SYMLINK: u32 = 0o120000;
+
+/// Read the file at PATH, or return `None` if there is no such file.
+fn read_file(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut contents = Vec::new();
+    let result = File::open(path).and_then(|mut file| file.read_to_end(&mut contents));
+    match result {
+        Ok(_) => Ok(Some(contents)),
+        Err(ref err)
+            if err.kind() == io::ErrorKind::NotFound || err.raw_os_error() == Some(libc::EISDIR) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn trim_end(bytes: &[u8]) -> &[u8] {
+    let end = bytes
+        .iter()
+        .rposition(|&b| b != b'\n' && b != b'\r' && b != b' ')
+        .map_or(0, |i| i + 1);
+    &bytes[..end]
+}
+
+/// Return true if NAME is safe to use as a ref name, which is to say
+/// that it can't name a file outside the repository.
+fn valid_ref_name(name: &[u8]) -> bool {
+    !name.is_empty() && name[0] != b'/' && !name.contains(&0)
+        && !name.split(|&b| b == b'/').any(|part| part == b".." || part == b".")
+}
+
+/// Return the git object name of a blob with CONTENTS.
+fn blob_id(contents: &[u8]) -> ObjectId {
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(format!("blob {}\0", contents.len()).as_bytes());
+    hasher.update(contents);
+    hasher.digest().bytes()
+}
+
+/// Return the git mode of the regular file of METADATA.
+#[cfg(unix)]
+fn regular_file_mode(metadata: &fs::Metadata, _entry: &IndexEntry) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    if metadata.permissions().mode() & 0o111 != 0 {
+        0o100755
+    } else {
+        0o100644
+    }
+}
+
+/// Without an executable bit, keep the mode of ENTRY in the index, as
+/// git does when `core.fileMode' is false.
+#[cfg(not(unix))]
+fn regular_file_mode(_metadata: &fs::Metadata, entry: &IndexEntry) -> u32 {
+    if entry.mode == 0o100755 {
+        0o100755
+    } else {
+        0o100644
+    }
+}
+
+enum Head {
+    Branch(Vec<u8>),
+    Detached(ObjectId),
+}
+
+/// The state of a file, named as by `vc-state'.
+#[derive(Clone, Copy, PartialEq)]
+enum FileState {
+    Edited,
+    Added,
+    Removed,
+    Conflict,
+    Unregistered,
+}
+
+impl FileState {
+    fn symbol(self) -> LispObject {
+        intern(match self {
+            FileState::Edited => "edited",
+            FileState::Added => "added",
+            FileState::Removed => "removed",
+            FileState::Conflict => "conflict",
+            FileState::Unregistered => "unregistered",
+        })
+    }
+}
+
+struct Repository {
+    git_dir: PathBuf,
+    /// Where refs and objects shared by all work trees live.
+    common_dir: PathBuf,
+    work_tree: PathBuf,
+    odb: Odb,
+}
+
+impl Repository {
+    /// Find the repository whose work tree contains DIR.
+    fn discover(dir: &Path) -> io::Result<Option<Repository>> {
+        let mut current = Some(dir);
+        while let Some(dir) = current {
+            if let Some(git_dir) = Repository::git_dir_at(&dir.join(".git"))? {
+                let common_dir = match read_file(&git_dir.join("commondir"))? {
+                    Some(contents) => git_dir.join(path_from_bytes(trim_end(&contents))),
+                    None => git_dir.clone(),
+                };
+                let odb = Odb::open(&common_dir.join("objects"))?;
+                return Ok(Some(Repository {
+                    git_dir,
+                    common_dir,
+                    work_tree: dir.to_path_buf(),
+                    odb,
+                }));
+            }
+            current = dir.parent();
+        }
+        Ok(None)
+    }
+
+    /// Return the git directory that DOT_GIT stands for, if any: either
+    /// DOT_GIT itself or, in linked work trees and submodules, the one
+    /// named by the `gitdir:' line it contains.
+    fn git_dir_at(dot_git: &Path) -> io::Result<Option<PathBuf>> {
+        match fs::metadata(dot_git) {
+            Ok(ref metadata) if metadata.is_dir() => Ok(Some(dot_git.to_path_buf())),
+            Ok(_) => {
+                let contents = read_file(dot_git)?.unwrap_or_default();
+                let contents = trim_end(&contents);
+                if !contents.starts_with(b"gitdir: ") {
+                    return Err(invalid("bad .git file"));
+                }
+                let parent = dot_git.parent().unwrap_or_else(|| Path::new("/"));
+                Ok(Some(parent.join(path_from_bytes(&contents[8..]))))
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn head(&self) -> io::Result<Head> {
+        let contents = read_file(&self.git_dir.join("HEAD"))?.unwrap_or_default();
+        let contents = trim_end(&contents);
+        if contents.starts_with(b"ref: ") {
+            Ok(Head::Branch(contents[5..].to_vec()))
+        } else {
+            parse_hex_id(contents)
+                .map(Head::Detached)
+                .ok_or_else(|| invalid("bad HEAD"))
+        }
+    }
+
+    /// Return the object that the ref NAME points to, following
+    /// symbolic refs, or `None` if there is no such ref.
+    fn resolve_ref(&self, name: &[u8]) -> io::Result<Option<ObjectId>> {
+        let mut name = name.to_vec();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            if !valid_ref_name(&name) {
+                return Ok(None);
+            }
+            let mut contents = read_file(&self.git_dir.join(path_from_bytes(&name)))?;
+            if contents.is_none() && self.common_dir != self.git_dir {
+                contents = read_file(&self.common_dir.join(path_from_bytes(&name)))?;
+            }
+            match contents {
+                Some(contents) => {
+                    let contents = trim_end(&contents);
+                    if contents.starts_with(b"ref: ") {
+                        name = contents[5..].to_vec();
+                    } else {
+                        return Ok(parse_hex_id(contents));
+                    }
+                }
+                None => return self.packed_ref(&name),
+            }
+        }
+        Ok(None)
+    }
+
+    fn packed_ref(&self, name: &[u8]) -> io::Result<Option<ObjectId>> {
+        let contents = read_file(&self.common_dir.join("packed-refs"))?.unwrap_or_default();
+        for line in contents.split(|&b| b == b'\n') {
+            let line = trim_end(line);
+            if line.len() > 41 && line[40] == b' ' && &line[41..] == name {
+                return Ok(parse_hex_id(&line[..40]));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Resolve REV, a full object name or the name of a ref, looking it
+    /// up in the same places as git does.
+    fn resolve_revision(&self, rev: &[u8]) -> io::Result<Option<ObjectId>> {
+        if let Some(id) = parse_hex_id(rev) {
+            return Ok(Some(id));
+        }
+        for prefix in &["", "refs/", "refs/tags/", "refs/heads/", "refs/remotes/"] {
+            if let Some(id) = self.resolve_ref(&[prefix.as_bytes(), rev].concat())? {
+                return Ok(Some(id));
+            }
+        }
+        self.resolve_ref(&[&b"refs/remotes/"[..], rev, b"/HEAD"].concat())
+    }
+
+    /// Return the tree of the commit, or commit tag, ID.
+    fn peel_to_tree(&self, id: &ObjectId) -> io::Result<Option<ObjectId>> {
+        let mut id = *id;
+        for _ in 0..MAX_SYMREF_DEPTH {
+            let object = match self.odb.read(&id)? {
+                Some(object) => object,
+                None => return Ok(None),
+            };
+            let next = match object.kind {
+                Kind::Tree => return Ok(Some(id)),
+                Kind::Blob => return Ok(None),
+                Kind::Commit => header_id(&object.data, b"tree"),
+                Kind::Tag => header_id(&object.data, b"object"),
+            };
+            id = next.ok_or_else(|| invalid("bad commit or tag"))?;
+        }
+        Ok(None)
+    }
+
+    fn read_tree(&self, id: &ObjectId) -> io::Result<Vec<u8>> {
+        match self.odb.read(id)? {
+            Some(ref object) if object.kind != Kind::Tree => Err(invalid("not a tree")),
+            Some(object) => Ok(object.data),
+            None => Err(invalid("missing tree")),
+        }
+    }
+
+    /// Return the mode and object of PATH in TREE, if present.
+    fn tree_entry(&self, tree: &ObjectId, path: &[u8]) -> io::Result<Option<(u32, ObjectId)>> {
+        let mut current = (MODE_TREE, *tree);
+        for name in path.split(|&b| b == b'/').filter(|name| !name.is_empty()) {
+            if current.0 != MODE_TREE {
+                return Ok(None);
+            }
+            let data = self.read_tree(&current.1)?;
+            let found = tree_entries(&data)?
+                .into_iter()
+                .find(|entry| entry.name == name)
+                .map(|entry| (entry.mode, entry.id));
+            current = match found {
+                Some(found) => found,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(current))
+    }
+
+    /// Add the files of TREE to FILES, with their mode and object,
+    /// naming them by their path with PREFIX prepended.
+    fn flatten_tree(
+        &self,
+        tree: &ObjectId,
+        prefix: &mut Vec<u8>,
+        files: &mut HashMap<Vec<u8>, (u32, ObjectId)>,
+    ) -> io::Result<()> {
+        let data = self.read_tree(tree)?;
+        for entry in tree_entries(&data)? {
+            let len = prefix.len();
+            prefix.extend_from_slice(entry.name);
+            if entry.mode == MODE_TREE {
+                prefix.push(b'/');
+                self.flatten_tree(&entry.id, prefix, files)?;
+            } else {
+                files.insert(prefix.clone(), (entry.mode, entry.id));
+            }
+            prefix.truncate(len);
+        }
+        Ok(())
+    }
+
+    /// Return the files of the tree of HEAD under the directory PREFIX.
+    fn head_files(&self, prefix: &[u8]) -> io::Result<HashMap<Vec<u8>, (u32, ObjectId)>> {
+        let mut files = HashMap::new();
+        let commit = match self.head()? {
+            Head::Branch(name) => self.resolve_ref(&name)?,
+            Head::Detached(id) => Some(id),
+        };
+        // There is no commit yet on an unborn branch.
+        let tree = match commit {
+            Some(commit) => self.peel_to_tree(&commit)?,
+            None => None,
+        };
+        if let Some(tree) = tree {
+            if let Some((MODE_TREE, subtree)) = self.tree_entry(&tree, prefix)? {
+                self.flatten_tree(&subtree, &mut prefix.to_vec(), &mut files)?;
+            }
+        }
+        Ok(files)
+    }
+
+    /// Return the state of the file of ENTRY in the work tree, or `None`
+    /// if it matches the index.
+    fn worktree_state(&self, entry: &IndexEntry) -> io::Result<Option<FileState>> {
+        let path = self.work_tree.join(path_from_bytes(&entry.path));
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(ref err)
+                if err.kind() == io::ErrorKind::NotFound
+                    || err.raw_os_error() == Some(libc::ENOTDIR) =>
+            {
+                return Ok(Some(FileState::Removed))
+            }
+            Err(err) => return Err(err),
+        };
+        let file_type = metadata.file_type();
+        let mode = if file_type.is_symlink() {
+            MODE_SYMLINK
+        } else if !file_type.is_file() {
+            return Ok(Some(FileState::Removed));
+        } else {
+            regular_file_mode(&metadata, entry)
+        };
+        if mode != entry.mode {
+            return Ok(Some(FileState::Edited));
+        }
+        // If the size and modification time are those recorded in the
+        // index, assume the file hasn't changed, as git does.
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|time| (time.as_secs() as u32, time.subsec_nanos()))
+            .unwrap_or((0, 0));
+        if metadata.len() as u32 == entry.size && mtime == entry.mtime {
+            return Ok(None);
+        }
+        let contents = if mode == MODE_SYMLINK {
+            os_str_bytes(fs::read_link(&path)?.as_os_str())
+        } else {
+            read_file(&path)?.unwrap_or_default()
+        };
+        Ok(if blob_id(&contents) == entry.id {
+            None
+        } else {
+            Some(FileState::Edited)
+        })
+    }
+
+    /// Add the files under the directory DIR, relative to the top of
+    /// the work tree, that are neither in TRACKED nor ignored to FILES.
+    fn untracked_files(
+        &self,
+        dir: &[u8],
+        tracked: &HashSet<&[u8]>,
+        ignores: &mut Ignores,
+        files: &mut Vec<Vec<u8>>,
+    ) -> io::Result<()> {
+        let path = self.work_tree.join(path_from_bytes(dir));
+        // Skip directories that can't be read, as git does.
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        let mark = ignores.len();
+        ignores.add_file(&path.join(".gitignore"), dir)?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name.to_str() == Some(".git") {
+                continue;
+            }
+            let mut file = dir.to_vec();
+            file.extend_from_slice(&os_str_bytes(&name));
+            if entry.file_type()?.is_dir() {
+                // Nested repositories are not part of this one.
+                if tracked.contains(&file[..]) || ignores.is_ignored(&file, true)
+                    || entry.path().join(".git").exists()
+                {
+                    continue;
+                }
+                file.push(b'/');
+                self.untracked_files(&file, tracked, ignores, files)?;
+            } else if !tracked.contains(&file[..]) && !ignores.is_ignored(&file, false) {
+                files.push(file);
+            }
+        }
+        ignores.truncate(mark);
+        Ok(())
+    }
+
+    /// Return the state of the files under the directory PREFIX that are
+    /// not up to date, and of untracked files too if UNTRACKED.
+    fn status(&self, prefix: &[u8], untracked: bool) -> io::Result<BTreeMap<Vec<u8>, FileState>> {
+        let index = read_index(&self.git_dir.join("index"))?;
+        let mut head_files = self.head_files(prefix)?;
+        let mut states = BTreeMap::new();
+        for entry in index.iter().filter(|entry| entry.path.starts_with(prefix)) {
+            let head = head_files.remove(&entry.path);
+            let state = if entry.stage != 0 {
+                Some(FileState::Conflict)
+            } else if entry.intent_to_add || head.is_none() {
+                Some(FileState::Added)
+            } else if head != Some((entry.mode, entry.id)) {
+                Some(FileState::Edited)
+            } else if entry.skip_worktree || entry.mode == MODE_GITLINK {
+                None
+            } else {
+                self.worktree_state(entry)?
+            };
+            if let Some(state) = state {
+                states.insert(entry.path.clone(), state);
+            }
+        }
+        for (path, _) in head_files {
+            states.insert(path, FileState::Removed);
+        }
+
+        if untracked {
+            let tracked: HashSet<&[u8]> = index.iter().map(|entry| &entry.path[..]).collect();
+            // Patterns apply from the top of the work tree down.
+            let mut ignores = Ignores::new();
+            ignores.add_file(&self.git_dir.join("info").join("exclude"), b"")?;
+            let mut dir = 0;
+            while let Some(slash) = prefix[dir..].iter().position(|&b| b == b'/') {
+                let parent = &prefix[..dir];
+                ignores.add_file(
+                    &self.work_tree.join(path_from_bytes(parent)).join(".gitignore"),
+                    parent,
+                )?;
+                dir += slash + 1;
+            }
+            let mut files = Vec::new();
+            self.untracked_files(prefix, &tracked, &mut ignores, &mut files)?;
+            for file in files {
+                states.insert(file, FileState::Unregistered);
+            }
+        }
+        Ok(states)
+    }
+}
+
+/// Run F on the repository containing the file or directory FILE,
+/// which is a directory if IS_DIRECTORY, and signal an error if there
+/// is no such repository or F fails.
+fn with_repository<T, F>(file: LispObject, is_directory: bool, f: F) -> T
+where
+    F: FnOnce(&Repository, &Path) -> io::Result<T>,
+{
+    let path = expand_file_name_to_path(file);
+    let dir = if is_directory {
+        path.as_path()
+    } else {
+        path.parent().unwrap_or_else(|| Path::new("/"))
+    };
+    let result = match Repository::discover(dir) {
+        Ok(Some(repository)) => f(&repository, &path).map(Some),
+        Ok(None) => Ok(None),
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(Some(value)) => value,
+        Ok(None) => error!("Not inside a git repository"),
+        Err(err) => report_io_error(b"Reading git repository\0", file, &err),
+    }
+}
+
+/// Return PATH relative to the work tree of REPOSITORY.
+fn work_tree_path(repository: &Repository, path: &Path) -> io::Result<Vec<u8>> {
+    path.strip_prefix(&repository.work_tree)
+        .map(|relative| os_str_bytes(relative.as_os_str()))
+        .map_err(|_| invalid("file is outside the work tree"))
+}
+
+/// Return the git status of the files under the directory DIR.
+/// The value is an alist of (FILE . STATE) for the files that differ
+/// from the last commit, sorted by file name, where FILE is relative to
+/// DIR and STATE is one of the symbols `edited', `added', `removed' or
+/// `conflict', with the same meaning as for `vc-state'.  Files that are
+/// up to date are left out.
+///
+/// If the optional second argument UNTRACKED is non-nil, also include
+/// files that are neither tracked nor ignored, with the state
+/// `unregistered'.
+///
+/// Like git, this trusts the size and modification time recorded in the
+/// index, and only compares the contents of files whose metadata changed.
+/// Signal an error if DIR is not inside a git repository.
+#[lisp_fn(min = "1")]
+pub fn git_status_files(dir: LispObject, untracked: LispObject) -> LispObject {
+    let states = with_repository(dir, true, |repository, path| {
+        let mut prefix = work_tree_path(repository, path)?;
+        if !prefix.is_empty() {
+            prefix.push(b'/');
+        }
+        let states = repository.status(&prefix, untracked.is_not_nil())?;
+        Ok((prefix.len(), states))
+    });
+    let (skip, states) = states;
+    states
+        .into_iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |result, (file, state)| {
+            let entry = LispObject::cons(file_name_from_bytes(&file[skip..]), state.symbol());
+            LispObject::cons(entry, result)
+        })
+}
+
+/// Return the contents of FILE in revision REV of its git repository.
+/// REV can be a full hexadecimal object name or the name of a branch,
+/// tag or other ref, and defaults to HEAD.  If REV is the symbol
+/// `index', return the contents staged in the index instead.
+///
+/// The value is a unibyte string holding the raw contents, or nil if
+/// FILE is not in REV.  Signal an error if FILE is not inside a git
+/// repository or REV doesn't exist.
+#[lisp_fn(min = "1")]
+pub fn git_blob_content(file: LispObject, rev: LispObject) -> LispObject {
+    let from_index = rev.eq(intern("index"));
+    let rev_name = if rev.is_nil() || from_index {
+        b"HEAD".to_vec()
+    } else {
+        rev.as_string_or_error().as_slice().to_vec()
+    };
+    let contents = with_repository(file, false, |repository, path| {
+        let path = work_tree_path(repository, path)?;
+        let id = if from_index {
+            read_index(&repository.git_dir.join("index"))?
+                .into_iter()
+                .find(|entry| entry.stage == 0 && entry.path == path)
+                .map(|entry| entry.id)
+        } else {
+            let commit = repository
+                .resolve_revision(&rev_name)?
+                .ok_or_else(|| invalid("unknown revision"))?;
+            let tree = repository
+                .peel_to_tree(&commit)?
+                .ok_or_else(|| invalid("revision is not a commit"))?;
+            match repository.tree_entry(&tree, &path)? {
+                Some((mode, id)) if mode != MODE_TREE && mode != MODE_GITLINK => Some(id),
+                _ => None,
+            }
+        };
+        match id {
+            Some(id) => match repository.odb.read(&id)? {
+                Some(object) => Ok(Some(object.data)),
+                None => Err(invalid("missing blob")),
+            },
+            None => Ok(None),
+        }
+    });
+    match contents {
+        Some(contents) => LispObject::from(unsafe {
+            make_unibyte_string(contents.as_ptr() as *const c_char, contents.len() as ptrdiff_t)
+        }),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Return the ref that HEAD points to in the git repository of DIR.
+/// The value is a string such as "refs/heads/master", or nil if HEAD is
+/// detached.  If the optional second argument SHORT is non-nil, strip
+/// the "refs/heads/" prefix from branch names.
+/// Signal an error if DIR is not inside a git repository.
+#[lisp_fn(min = "1")]
+pub fn git_symbolic_ref(dir: LispObject, short: LispObject) -> LispObject {
+    let head = with_repository(dir, true, |repository, _| repository.head());
+    match head {
+        Head::Branch(mut name) => {
+            if short.is_not_nil() && name.starts_with(b"refs/heads/") {
+                name.drain(..11);
+            }
+            LispObject::from(unsafe {
+                make_string(name.as_ptr() as *const c_char, name.len() as ptrdiff_t)
+            })
+        }
+        Head::Detached(_) => LispObject::constant_nil(),
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/git_exports.rs"));
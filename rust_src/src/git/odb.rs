@@ -0,0 +1,490 @@
+//! Reading objects from a git object database, whether loose or in
+//! pack files.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use inflate::inflate_bytes_zlib;
+
+pub type ObjectId = [u8; 20];
+
+/// The longest chain of deltas followed before giving up.
+const MAX_DELTA_DEPTH: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn from_name(name: &[u8]) -> Option<Kind> {
+        if name == b"commit" {
+            Some(Kind::Commit)
+        } else if name == b"tree" {
+            Some(Kind::Tree)
+        } else if name == b"blob" {
+            Some(Kind::Blob)
+        } else if name == b"tag" {
+            Some(Kind::Tag)
+        } else {
+            None
+        }
+    }
+
+    fn from_pack_type(code: u8) -> Option<Kind> {
+        match code {
+            1 => Some(Kind::Commit),
+            2 => Some(Kind::Tree),
+            3 => Some(Kind::Blob),
+            4 => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+}
+
+pub struct Object {
+    pub kind: Kind,
+    pub data: Vec<u8>,
+}
+
+pub fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Parse a 40 digit hexadecimal object name.
+pub fn parse_hex_id(hex: &[u8]) -> Option<ObjectId> {
+    fn digit(c: u8) -> Option<u8> {
+        match c {
+            b'0'...b'9' => Some(c - b'0'),
+            b'a'...b'f' => Some(c - b'a' + 10),
+            b'A'...b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    }
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut id = [0u8; 20];
+    for (i, pair) in hex.chunks(2).enumerate() {
+        match (digit(pair[0]), digit(pair[1])) {
+            (Some(high), Some(low)) => id[i] = high << 4 | low,
+            _ => return None,
+        }
+    }
+    Some(id)
+}
+
+pub fn hex_id(id: &ObjectId) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    (bytes[pos] as u32) << 24 | (bytes[pos + 1] as u32) << 16 | (bytes[pos + 2] as u32) << 8
+        | bytes[pos + 3] as u32
+}
+
+/// A pack file and its version 2 index.
+struct Pack {
+    path: PathBuf,
+    index: Vec<u8>,
+    count: usize,
+    /// The offsets of all objects in the pack, in increasing order,
+    /// followed by the offset of the trailing checksum.  An object's
+    /// data extends up to the next offset.
+    offsets: Vec<u64>,
+}
+
+impl Pack {
+    fn open(index_path: &Path) -> io::Result<Pack> {
+        let mut index = Vec::new();
+        File::open(index_path)?.read_to_end(&mut index)?;
+        if index.len() < 8 + 256 * 4 || &index[..4] != b"\xfftOc" || read_u32(&index, 4) != 2 {
+            return Err(invalid("unsupported pack index"));
+        }
+        let count = read_u32(&index, 8 + 255 * 4) as usize;
+        if index.len() < 8 + 256 * 4 + count * 28 + 40 {
+            return Err(invalid("truncated pack index"));
+        }
+        let path = index_path.with_extension("pack");
+        let pack_len = fs::metadata(&path)?.len();
+        let mut pack = Pack {
+            path,
+            index,
+            count,
+            offsets: Vec::new(),
+        };
+        let mut offsets: Vec<u64> = (0..count).map(|i| pack.offset_at(i)).collect();
+        offsets.sort();
+        offsets.push(pack_len.saturating_sub(20));
+        pack.offsets = offsets;
+        Ok(pack)
+    }
+
+    /// The offset in the pack of the Ith object of the index.
+    fn offset_at(&self, i: usize) -> u64 {
+        let table = 8 + 256 * 4 + self.count * 24;
+        let offset = read_u32(&self.index, table + i * 4);
+        if offset & 0x8000_0000 == 0 {
+            u64::from(offset)
+        } else {
+            let large = table + self.count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+            u64::from(read_u32(&self.index, large)) << 32
+                | u64::from(read_u32(&self.index, large + 4))
+        }
+    }
+
+    /// Return the offset in the pack of the object ID, if present.
+    fn find(&self, id: &ObjectId) -> Option<u64> {
+        let fanout = |byte: usize| read_u32(&self.index, 8 + byte * 4) as usize;
+        let first = id[0] as usize;
+        let (mut low, mut high) = (if first == 0 { 0 } else { fanout(first - 1) }, fanout(first));
+        let names = 8 + 256 * 4;
+        while low < high {
+            let middle = (low + high) / 2;
+            let name = &self.index[names + middle * 20..names + middle * 20 + 20];
+            if name < &id[..] {
+                low = middle + 1;
+            } else if name > &id[..] {
+                high = middle;
+            } else {
+                return Some(self.offset_at(middle));
+            }
+        }
+        None
+    }
+
+    /// Read the raw entry at OFFSET, up to the start of the next one.
+    fn entry(&self, offset: u64) -> io::Result<Vec<u8>> {
+        let end = match self.offsets.binary_search(&offset) {
+            Ok(i) if i + 1 < self.offsets.len() => self.offsets[i + 1],
+            _ => return Err(invalid("bad pack offset")),
+        };
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut entry = vec![0; (end - offset) as usize];
+        file.read_exact(&mut entry)?;
+        Ok(entry)
+    }
+}
+
+/// Decompress the zlib stream DATA.
+fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    inflate_bytes_zlib(data).map_err(|err| invalid(&err))
+}
+
+/// Decompress the zlib stream DATA of a pack entry, which should come
+/// to SIZE bytes.
+fn inflate_sized(data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let data = inflate(data)?;
+    if data.len() != size {
+        return Err(invalid("object has the wrong size"));
+    }
+    Ok(data)
+}
+
+/// Read the variable length size of a delta header starting at *POS.
+fn delta_size(delta: &[u8], pos: &mut usize) -> io::Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *delta.get(*pos).ok_or_else(|| invalid("truncated delta"))?;
+        *pos += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+/// Rebuild an object from BASE and a DELTA against it.
+fn apply_delta(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let mut pos = 0;
+    if delta_size(delta, &mut pos)? != base.len() {
+        return Err(invalid("delta does not match its base"));
+    }
+    let size = delta_size(delta, &mut pos)?;
+    let mut result = Vec::with_capacity(size);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            // Copy from the base: the low bits say which bytes of the
+            // offset and size follow.
+            let mut fields = [0usize; 2];
+            let mut bit = 0;
+            for (field, &nbytes) in fields.iter_mut().zip(&[4usize, 3]) {
+                for byte in 0..nbytes {
+                    if op & (1 << bit) != 0 {
+                        let value = *delta.get(pos).ok_or_else(|| invalid("truncated delta"))?;
+                        *field |= (value as usize) << (8 * byte);
+                        pos += 1;
+                    }
+                    bit += 1;
+                }
+            }
+            let start = fields[0];
+            let len = if fields[1] == 0 { 0x10000 } else { fields[1] };
+            if start + len > base.len() {
+                return Err(invalid("delta copies past its base"));
+            }
+            result.extend_from_slice(&base[start..start + len]);
+        } else if op != 0 {
+            let len = op as usize;
+            if pos + len > delta.len() {
+                return Err(invalid("truncated delta"));
+            }
+            result.extend_from_slice(&delta[pos..pos + len]);
+            pos += len;
+        } else {
+            return Err(invalid("bad delta opcode"));
+        }
+    }
+    if result.len() != size {
+        return Err(invalid("delta result has the wrong size"));
+    }
+    Ok(result)
+}
+
+/// The object database of a repository.
+pub struct Odb {
+    objects: PathBuf,
+    packs: Vec<Pack>,
+}
+
+impl Odb {
+    /// Open the object database in the directory OBJECTS.
+    pub fn open(objects: &Path) -> io::Result<Odb> {
+        let mut packs = Vec::new();
+        if let Ok(entries) = fs::read_dir(objects.join("pack")) {
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().map_or(false, |ext| ext == "idx") {
+                    packs.push(Pack::open(&path)?);
+                }
+            }
+        }
+        Ok(Odb {
+            objects: objects.to_path_buf(),
+            packs,
+        })
+    }
+
+    /// Read the object ID, or return `None` if there is no such object.
+    pub fn read(&self, id: &ObjectId) -> io::Result<Option<Object>> {
+        self.read_at_depth(id, 0)
+    }
+
+    fn read_at_depth(&self, id: &ObjectId, depth: usize) -> io::Result<Option<Object>> {
+        if let Some(object) = self.read_loose(id)? {
+            return Ok(Some(object));
+        }
+        for pack in &self.packs {
+            if let Some(offset) = pack.find(id) {
+                return self.read_packed(pack, offset, depth).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_loose(&self, id: &ObjectId) -> io::Result<Option<Object>> {
+        let hex = hex_id(id);
+        let path = self.objects.join(&hex[..2]).join(&hex[2..]);
+        let mut compressed = Vec::new();
+        match File::open(&path) {
+            Ok(mut file) => file.read_to_end(&mut compressed)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut data = inflate(&compressed)?;
+        let header_end = data.iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("bad object header"))?;
+        let kind = {
+            let header = &data[..header_end];
+            let space = header.iter().position(|&b| b == b' ').unwrap_or(header.len());
+            Kind::from_name(&header[..space]).ok_or_else(|| invalid("bad object type"))?
+        };
+        data.drain(..header_end + 1);
+        Ok(Some(Object { kind, data }))
+    }
+
+    fn read_packed(&self, pack: &Pack, offset: u64, depth: usize) -> io::Result<Object> {
+        if depth > MAX_DELTA_DEPTH {
+            return Err(invalid("delta chain too long"));
+        }
+        let entry = pack.entry(offset)?;
+        let truncated = || invalid("truncated pack entry");
+
+        // Type and size, 4 bits of size in the first byte and 7 in the
+        // following ones.
+        let mut pos = 0;
+        let mut byte = *entry.get(pos).ok_or_else(&truncated)?;
+        let code = (byte >> 4) & 7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            pos += 1;
+            byte = *entry.get(pos).ok_or_else(&truncated)?;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+        pos += 1;
+
+        match code {
+            6 => {
+                // Delta against the object at a relative offset.
+                let mut byte = *entry.get(pos).ok_or_else(&truncated)?;
+                let mut distance = u64::from(byte & 0x7f);
+                while byte & 0x80 != 0 {
+                    pos += 1;
+                    byte = *entry.get(pos).ok_or_else(&truncated)?;
+                    distance = ((distance + 1) << 7) | u64::from(byte & 0x7f);
+                }
+                pos += 1;
+                if distance > offset {
+                    return Err(invalid("bad delta base offset"));
+                }
+                let base = self.read_packed(pack, offset - distance, depth + 1)?;
+                let delta = inflate_sized(&entry[pos..], size)?;
+                Ok(Object {
+                    kind: base.kind,
+                    data: apply_delta(&base.data, &delta)?,
+                })
+            }
+            7 => {
+                // Delta against an object named by its id.
+                if pos + 20 > entry.len() {
+                    return Err(truncated());
+                }
+                let mut base_id = [0u8; 20];
+                base_id.copy_from_slice(&entry[pos..pos + 20]);
+                let base = self.read_at_depth(&base_id, depth + 1)?
+                    .ok_or_else(|| invalid("missing delta base"))?;
+                let delta = inflate_sized(&entry[pos + 20..], size)?;
+                Ok(Object {
+                    kind: base.kind,
+                    data: apply_delta(&base.data, &delta)?,
+                })
+            }
+            _ => {
+                let kind = Kind::from_pack_type(code).ok_or_else(|| invalid("bad object type"))?;
+                let data = inflate_sized(&entry[pos..], size)?;
+                Ok(Object { kind, data })
+            }
+        }
+    }
+}
+
+/// An entry of a tree object.
+pub struct TreeEntry<'a> {
+    pub mode: u32,
+    pub name: &'a [u8],
+    pub id: ObjectId,
+}
+
+/// Mode of tree entries that are trees themselves.
+pub const MODE_TREE: u32 = 0o40000;
+/// Mode of tree entries that are submodules.
+pub const MODE_GITLINK: u32 = 0o160000;
+
+/// Parse the DATA of a tree object.
+pub fn tree_entries(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let space = data[pos..].iter().position(|&b| b == b' ');
+        let nul = data[pos..].iter().position(|&b| b == 0);
+        let (space, nul) = match (space, nul) {
+            (Some(space), Some(nul)) if space < nul && pos + nul + 21 <= data.len() => {
+                (pos + space, pos + nul)
+            }
+            _ => return Err(invalid("bad tree object")),
+        };
+        let mut mode = 0;
+        for &digit in &data[pos..space] {
+            if digit < b'0' || digit > b'7' {
+                return Err(invalid("bad tree entry mode"));
+            }
+            mode = mode << 3 | u32::from(digit - b'0');
+        }
+        let mut id = [0u8; 20];
+        id.copy_from_slice(&data[nul + 1..nul + 21]);
+        entries.push(TreeEntry {
+            mode,
+            name: &data[space + 1..nul],
+            id,
+        });
+        pos = nul + 21;
+    }
+    Ok(entries)
+}
+
+/// Return the object named by the header FIELD of a commit or tag
+/// object, such as the `tree' of a commit.
+pub fn header_id(data: &[u8], field: &[u8]) -> Option<ObjectId> {
+    for line in data.split(|&b| b == b'\n') {
+        if line.is_empty() {
+            break;
+        }
+        if line.len() == field.len() + 41 && line.starts_with(field) && line[field.len()] == b' ' {
+            return parse_hex_id(&line[field.len() + 1..]);
+        }
+    }
+    None
+}
+
+#[test]
+fn test_parse_hex_id() {
+    let id = parse_hex_id(b"0123456789abcdef0123456789ABCDEF01234567").unwrap();
+    assert_eq!(id[0], 0x01);
+    assert_eq!(id[19], 0x67);
+    assert_eq!(hex_id(&id), "0123456789abcdef0123456789abcdef01234567");
+    assert!(parse_hex_id(b"0123").is_none());
+    assert!(parse_hex_id(b"g123456789abcdef0123456789abcdef01234567").is_none());
+}
+
+#[test]
+fn test_inflate() {
+    // zlib.compress(b"hello hello hello\n")
+    let compressed = [
+        0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00, 0x40, 0xb5,
+        0x06, 0x87,
+    ];
+    assert_eq!(inflate(&compressed).unwrap(), b"hello hello hello\n".to_vec());
+    assert_eq!(
+        inflate_sized(&compressed, 18).unwrap(),
+        b"hello hello hello\n".to_vec()
+    );
+    assert!(inflate_sized(&compressed, 17).is_err());
+    assert!(inflate(&[0x78, 0x9c, 0xff]).is_err());
+}
+
+#[test]
+fn test_apply_delta() {
+    let base = b"hello world";
+    // Sizes 11 and 12, copy 6 bytes from offset 0, insert "there!".
+    let mut delta = vec![11, 12, 0x90, 6, 6];
+    delta.extend_from_slice(b"there!");
+    assert_eq!(apply_delta(base, &delta).unwrap(), b"hello there!".to_vec());
+    assert!(apply_delta(b"short", &delta).is_err());
+}
+
+#[test]
+fn test_tree_entries() {
+    let mut data = b"100644 a.txt\0".to_vec();
+    data.extend_from_slice(&[1; 20]);
+    data.extend_from_slice(b"40000 dir\0");
+    data.extend_from_slice(&[2; 20]);
+    let entries = tree_entries(&data).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].mode, 0o100644);
+    assert_eq!(entries[0].name, b"a.txt");
+    assert_eq!(entries[1].mode, MODE_TREE);
+    assert_eq!(entries[1].id, [2; 20]);
+    assert!(tree_entries(b"100644 a.txt\0abc").is_err());
+}
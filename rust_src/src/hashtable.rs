@@ -2,15 +2,18 @@
 
 use libc::c_void;
 use std::ptr;
+use std::sync::Mutex;
 
 use remacs_macros::lisp_fn;
 use remacs_sys::{EmacsDouble, EmacsInt, EmacsUint, Faref, Fcopy_sequence, Lisp_Hash_Table,
-                 PseudovecType, Qhash_table_test, CHECK_IMPURE};
-use remacs_sys::{gc_aset, hash_clear, hash_lookup, hash_put, hash_remove_from_table};
+                 PseudovecType, QCsize, Qhash_table_test, Qplistp, CHECK_IMPURE};
+use remacs_sys::{gc_aset, hash_clear, hash_lookup, hash_put, hash_remove_from_table,
+                 string_text_stamp};
 
-use lisp::{ExternalPtr, LispObject};
+use lisp::{intern, ExternalPtr, LispObject};
 use lisp::defsubr;
 use lists::{list, put};
+use multibyte::LispStringRef;
 
 pub type LispHashTableRef = ExternalPtr<Lisp_Hash_Table>;
 
@@ -107,6 +110,14 @@ impl LispHashTableRef {
         unsafe { LispObject::from(Faref(self.key_and_value, index.to_raw())) }
     }
 
+    /// Return the key and value stored in slot IDX, or `None` if the
+    /// slot is unused.
+    pub fn get_slot(self, idx: isize) -> Option<(LispObject, LispObject)> {
+        self.get_hash_hash(idx)
+            .into_option()
+            .map(|_| (self.get_hash_key(idx), self.get_hash_value(idx)))
+    }
+
     pub fn size(self) -> usize {
         unsafe { self.get_next().as_vector_unchecked().len() }
     }
@@ -153,11 +164,9 @@ impl<'a> Iterator for KeyAndValueIter<'a> {
 
     fn next(&mut self) -> Option<(LispObject, LispObject)> {
         while let Some(idx) = self.0.next() {
-            let is_not_nil = self.0.table.get_hash_hash(idx).is_not_nil();
-            if is_not_nil {
-                let key = self.0.table.get_hash_key(idx);
-                let value = self.0.table.get_hash_value(idx);
-                return Some((key, value));
+            let slot = self.0.table.get_slot(idx);
+            if slot.is_some() {
+                return slot;
             }
         }
 
@@ -178,6 +187,120 @@ impl LispHashTableRef {
     }
 }
 
+/// The string and symbol keys of one hash table, sorted by the bytes
+/// of their names, so that all keys starting with a given prefix can be
+/// found with a binary search instead of a scan over the whole table.
+struct SortedKeyIndex {
+    table: usize,
+    key_stamp: EmacsUint,
+    /// `string_text_stamp' when the index was built: a string key
+    /// changed in place may no longer be where the index has it.
+    text_stamp: EmacsUint,
+    /// Slots of the indexed keys, in sorted order.  `None` if some key
+    /// cannot be ordered bytewise, i.e. it is a unibyte string containing
+    /// non-ASCII bytes.
+    slots: Option<Vec<isize>>,
+}
+
+lazy_static! {
+    /// Index of the hash table most recently searched by prefix.
+    static ref SORTED_KEY_INDEX: Mutex<Option<SortedKeyIndex>> = Mutex::new(None);
+}
+
+/// Return the name of KEY as a string if it is a string or a symbol.
+fn key_name(key: LispObject) -> Option<LispStringRef> {
+    match key.as_symbol() {
+        Some(sym) => sym.symbol_name().as_string(),
+        None => key.as_string(),
+    }
+}
+
+/// Return true if the bytes of S order the same way as its characters,
+/// which holds for multibyte strings and pure ASCII unibyte strings.
+fn is_bytewise_ordered(s: LispStringRef) -> bool {
+    s.is_multibyte() || s.as_slice().iter().all(|&b| b < 0x80)
+}
+
+impl LispHashTableRef {
+    fn build_sorted_key_index(self) -> Option<Vec<isize>> {
+        let mut keys = Vec::with_capacity(self.count as usize);
+        for idx in self.indices() {
+            let key = match self.get_slot(idx) {
+                Some((key, _)) => key,
+                None => continue,
+            };
+            if let Some(name) = key_name(key) {
+                if !is_bytewise_ordered(name) {
+                    return None;
+                }
+                keys.push((idx, name));
+            }
+        }
+        keys.sort_by(|a, b| a.1.as_slice().cmp(b.1.as_slice()));
+        Some(keys.into_iter().map(|(idx, _)| idx).collect())
+    }
+
+    /// Return the slots of all string and symbol keys whose name starts
+    /// with PREFIX, in slot order, or `None` if the keys cannot be
+    /// searched bytewise.  The comparison is case-sensitive.
+    ///
+    /// The sorted index is cached for the most recently searched table
+    /// and rebuilt whenever a key is added to or removed from it, or
+    /// the text of any string is changed with `aset' and the like.
+    pub fn slots_with_prefix(self, prefix: LispStringRef) -> Option<Vec<isize>> {
+        if !is_bytewise_ordered(prefix) {
+            return None;
+        }
+
+        let table = self.as_ptr() as usize;
+        let text_stamp = unsafe { string_text_stamp };
+        let mut cache = SORTED_KEY_INDEX.lock().unwrap();
+        let stale = match *cache {
+            Some(ref index) => {
+                index.table != table || index.key_stamp != self.key_stamp
+                    || index.text_stamp != text_stamp
+            }
+            None => true,
+        };
+        if stale {
+            *cache = Some(SortedKeyIndex {
+                table,
+                key_stamp: self.key_stamp,
+                text_stamp,
+                slots: self.build_sorted_key_index(),
+            });
+        }
+
+        let slots = match *cache {
+            Some(SortedKeyIndex {
+                slots: Some(ref slots),
+                ..
+            }) => slots,
+            _ => return None,
+        };
+        let name_at = |i: usize| key_name(self.get_hash_key(slots[i])).unwrap();
+        let prefix = prefix.as_slice();
+
+        // Find the first key not ordered before PREFIX, then collect keys
+        // for as long as they start with it.
+        let (mut lo, mut hi) = (0, slots.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if name_at(mid).as_slice() < prefix {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let mut found: Vec<isize> = (lo..slots.len())
+            .take_while(|&i| name_at(i).as_slice().starts_with(prefix))
+            .map(|i| slots[i])
+            .collect();
+        found.sort();
+        Some(found)
+    }
+}
+
 /// Return a copy of hash table TABLE.
 /// Keys and values are not copied, only the table itself is.
 #[lisp_fn]
@@ -327,4 +450,99 @@ pub fn define_hash_table_test(name: LispObject, test: LispObject, hash: LispObje
     put(name, sym, list(&mut [test, hash]))
 }
 
+/// Make a hash table to hold COUNT entries, passing ARGS, which are
+/// keyword arguments, on to `make-hash-table'.  The table is sized for
+/// COUNT entries unless ARGS give a `:size'.
+fn make_hash_table_for(args: &[LispObject], count: usize) -> LispHashTableRef {
+    let sized = args.chunks(2)
+        .any(|pair| pair[0].eq(LispObject::from(QCsize)));
+    let tail = if sized {
+        LispObject::constant_nil()
+    } else {
+        list!(
+            LispObject::from(QCsize),
+            LispObject::from_natnum(count as EmacsInt)
+        )
+    };
+    let args = args.iter()
+        .rev()
+        .fold(tail, |tail, &arg| LispObject::cons(arg, tail));
+    call!(intern("apply"), intern("make-hash-table"), args).as_hash_table_or_error()
+}
+
+/// Associate KEY with VALUE in TABLE, unless KEY already has a value.
+fn puthash_if_absent(table: LispHashTableRef, key: LispObject, value: LispObject) {
+    let mut hash: EmacsUint = 0;
+    if table.lookup(key, &mut hash) < 0 {
+        table.put(key, value, hash);
+    }
+}
+
+/// Return a new hash table holding the associations of ALIST.
+/// KEYWORD-ARGS are passed on to `make-hash-table', so `:test' selects
+/// how keys are compared; the default is `eql'.
+///
+/// When a key occurs more than once, the first association wins, as
+/// with `assoc'.  Elements of ALIST that are not conses are ignored.
+/// usage: (alist-to-hash-table ALIST &rest KEYWORD-ARGS)
+#[lisp_fn(min = "1")]
+pub fn alist_to_hash_table(args: &mut [LispObject]) -> LispObject {
+    let alist = args[0];
+    let count = alist.iter_cars().filter(|elt| elt.is_cons()).count();
+    let table = make_hash_table_for(&args[1..], count);
+    for elt in alist.iter_cars() {
+        if let Some(cell) = elt.as_cons() {
+            puthash_if_absent(table, cell.car(), cell.cdr());
+        }
+    }
+    LispObject::from_hash_table(table)
+}
+
+/// Return a new hash table holding the properties of PLIST.
+/// KEYWORD-ARGS are passed on to `make-hash-table', so `:test' selects
+/// how keys are compared; the default is `eql'.
+///
+/// When a property occurs more than once, the first value wins, as with
+/// `plist-get'.  An error is signaled if PLIST has an odd number of
+/// elements.
+/// usage: (plist-to-hash-table PLIST &rest KEYWORD-ARGS)
+#[lisp_fn(min = "1")]
+pub fn plist_to_hash_table(args: &mut [LispObject]) -> LispObject {
+    let plist = args[0];
+    let count = plist.iter_cars().count();
+    if count % 2 != 0 {
+        wrong_type!(Qplistp, plist);
+    }
+    let table = make_hash_table_for(&args[1..], count / 2);
+    let mut tail = plist;
+    while let Some(cell) = tail.as_cons() {
+        let value = cell.cdr().as_cons_or_error();
+        puthash_if_absent(table, cell.car(), value.car());
+        tail = value.cdr();
+    }
+    LispObject::from_hash_table(table)
+}
+
+/// Return an alist of the associations in hash table TABLE.
+/// The elements are (KEY . VALUE), in the order the keys were added.
+#[lisp_fn]
+pub fn hash_table_to_alist(table: LispObject) -> LispObject {
+    let hash_table = table.as_hash_table_or_error();
+    let pairs: Vec<(LispObject, LispObject)> = hash_table.iter().collect();
+    pairs.into_iter().rev().fold(LispObject::constant_nil(), |tail, (key, value)| {
+        LispObject::cons(LispObject::cons(key, value), tail)
+    })
+}
+
+/// Return a property list of the associations in hash table TABLE.
+/// The properties are in the order the keys were added.
+#[lisp_fn]
+pub fn hash_table_to_plist(table: LispObject) -> LispObject {
+    let hash_table = table.as_hash_table_or_error();
+    let pairs: Vec<(LispObject, LispObject)> = hash_table.iter().collect();
+    pairs.into_iter().rev().fold(LispObject::constant_nil(), |tail, (key, value)| {
+        LispObject::cons(key, LispObject::cons(value, tail))
+    })
+}
+
 include!(concat!(env!("OUT_DIR"), "/hashtable_exports.rs"));
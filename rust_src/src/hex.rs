@@ -0,0 +1,307 @@
+//! Conversion between binary data and the hex dump format of hexl-mode.
+//!
+//! hexl-mode used to pipe the whole buffer through the `hexl' program
+//! in both directions, which is slow for large files and needs the
+//! program installed.  The conversions here produce exactly its output,
+//! `ADDRESS: HEX-GROUPS  ASCII', sixteen bytes to a line.
+
+use std::io::Write;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_unibyte_string, EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use symbols::symbol_value;
+
+/// The number of bytes on a line of the hex dump.
+const LINE_BYTES: usize = 16;
+
+/// The width of the address and the colon and space after it.
+const ADDRESS_WIDTH: usize = 10;
+
+/// Return the hex dump of DATA, with the hex digits in groups of GROUP
+/// bytes.  If ISO is true, ISO 8859 characters are shown as themselves
+/// in the character column, otherwise as `.'.
+fn hexlify(data: &[u8], group: usize, iso: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / LINE_BYTES * (60 + LINE_BYTES) + 80);
+    for (line, bytes) in data.chunks(LINE_BYTES).enumerate() {
+        write!(out, "{:08x}: ", line * LINE_BYTES).unwrap();
+        for i in 0..LINE_BYTES {
+            match bytes.get(i) {
+                Some(b) => write!(out, "{:02x}", b).unwrap(),
+                None => out.extend_from_slice(b"  "),
+            }
+            if (i + 1) % group == 0 {
+                out.push(b' ');
+            }
+        }
+        out.push(b' ');
+        out.extend(bytes.iter().map(|&b| {
+            if b < 0x20 || (b >= 0x7f && (!iso || b < 0xa0)) {
+                b'.'
+            } else {
+                b
+            }
+        }));
+        out.push(b'\n');
+    }
+    out
+}
+
+/// The value of the hex digit C, computed as the `hexl' program does,
+/// which is only meaningful for lower case digits.
+fn hexchar(c: u8) -> i32 {
+    i32::from(c) - i32::from(if c >= b'0' && c <= b'9' { b'0' } else { b'a' - 10 })
+}
+
+/// Return the data of the hex dump TEXT, whose hex digits are in groups
+/// of GROUP bytes.  Each line is read from the space after its address
+/// to the end of its hex digits; the character column is ignored.
+fn dehexlify(text: &[u8], group: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len() / 4);
+    let mut chars = text.iter().cloned();
+    'lines: loop {
+        // Skip the address at the start of the line.
+        let mut c = match chars.next() {
+            Some(b' ') => Some(b' '),
+            Some(_) => continue,
+            None => break,
+        };
+        for i in 0..LINE_BYTES {
+            c = chars.next();
+            let high = match c {
+                Some(b' ') | None => break,
+                Some(high) => hexchar(high),
+            };
+            c = chars.next();
+            match c {
+                Some(low) => out.push((high * 0x10 + hexchar(low)) as u8),
+                None => break,
+            }
+            if (i + 1) % group == 0 {
+                c = chars.next();
+                if c.is_none() {
+                    break;
+                }
+            }
+        }
+        while c != Some(b'\n') {
+            c = chars.next();
+            if c.is_none() {
+                break 'lines;
+            }
+        }
+    }
+    out
+}
+
+/// Return the byte at column COLUMN of LINE, a line of a hex dump whose
+/// hex digits are in groups of GROUP bytes.  Upper case digits are
+/// accepted, as `hexl-hex-char-to-integer' does.
+fn byte_in_line(line: &[u8], column: usize, group: usize) -> Option<u8> {
+    let offset = ADDRESS_WIDTH + column * 2 + column / group;
+    let digit = |i: usize| {
+        line.get(offset + i)
+            .and_then(|&c| (c as char).to_digit(16))
+    };
+    match (digit(0), digit(1)) {
+        (Some(high), Some(low)) => Some((high * 16 + low) as u8),
+        _ => None,
+    }
+}
+
+/// Return the number of bytes in a group of BITS bits, which defaults
+/// to `hexl-bits', or 16 if hexl is not loaded.
+fn group_size(bits: LispObject) -> usize {
+    let bits = if bits.is_nil() {
+        let hexl_bits = intern("hexl-bits");
+        if call!(intern("boundp"), hexl_bits).is_nil() {
+            return 2;
+        }
+        symbol_value(hexl_bits)
+    } else {
+        bits
+    };
+    match bits.as_fixnum() {
+        Some(n) if [8, 16, 32, 64].contains(&n) => n as usize / 8,
+        _ => args_out_of_range!(bits, LispObject::from_natnum(8), LispObject::from_natnum(64)),
+    }
+}
+
+fn unibyte_string(bytes: &[u8]) -> LispObject {
+    LispObject::from(unsafe {
+        make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t)
+    })
+}
+
+/// Return START and END as positions, in increasing order.
+fn region(start: LispObject, end: LispObject) -> (LispObject, LispObject) {
+    let start = start.as_fixnum_coerce_marker_or_error();
+    let end = end.as_fixnum_coerce_marker_or_error();
+    (
+        LispObject::from_fixnum(start.min(end)),
+        LispObject::from_fixnum(start.max(end)),
+    )
+}
+
+/// Replace the text between START and END with TEXT, leaving point
+/// after it.
+fn replace_region(start: LispObject, end: LispObject, text: LispObject) {
+    call!(intern("delete-region"), start, end);
+    call!(intern("goto-char"), start);
+    call!(intern("insert"), text);
+}
+
+/// Replace the text between START and END with its hex dump.
+/// The text is encoded with `buffer-file-coding-system', as the
+/// `hexl' program would see it when the buffer is written, and the dump
+/// has sixteen bytes to a line, with addresses starting at 0.
+///
+/// BITS is the grouping of the hex digits, 8, 16, 32 or 64, and defaults
+/// to `hexl-bits'.  If ISO is non-nil, ISO 8859 characters are shown as
+/// themselves in the character column, as `-iso' does for `hexl'.
+/// Return the number of bytes converted.
+#[lisp_fn(min = "2")]
+pub fn hexlify_region_native(
+    start: LispObject,
+    end: LispObject,
+    bits: LispObject,
+    iso: LispObject,
+) -> LispObject {
+    let group = group_size(bits);
+    let (start, end) = region(start, end);
+    let text = call!(intern("buffer-substring-no-properties"), start, end);
+    let coding = symbol_value(intern("buffer-file-coding-system"));
+    let encoded = call!(intern("encode-coding-string"), text, coding);
+    let data = encoded.as_string_or_error().as_slice();
+    let length = data.len();
+    let dump = unibyte_string(&hexlify(data, group, iso.is_not_nil()));
+    replace_region(start, end, dump);
+    LispObject::from_natnum(length as EmacsInt)
+}
+
+/// Replace the hex dump between START and END with the data it shows.
+/// This is the inverse of `hexlify-region-native': the data is decoded
+/// with `buffer-file-coding-system'.  BITS is the grouping of the hex
+/// digits, and defaults to `hexl-bits'.  Return the number of bytes
+/// converted.
+#[lisp_fn(min = "2")]
+pub fn dehexlify_region_native(start: LispObject, end: LispObject, bits: LispObject) -> LispObject {
+    let group = group_size(bits);
+    let (start, end) = region(start, end);
+    let text = call!(intern("buffer-substring-no-properties"), start, end);
+    let data = dehexlify(text.as_string_or_error().as_slice(), group);
+    let coding = symbol_value(intern("buffer-file-coding-system"));
+    let decoded = call!(intern("decode-coding-string"), unibyte_string(&data), coding);
+    replace_region(start, end, decoded);
+    LispObject::from_natnum(data.len() as EmacsInt)
+}
+
+/// Return up to LENGTH bytes of the data shown by the hex dump in the
+/// current buffer, starting at ADDRESS, as a unibyte string.  The string
+/// is shorter if the dump ends before.  BITS is the grouping of the hex
+/// digits, and defaults to `hexl-bits'.
+#[lisp_fn(min = "2")]
+pub fn buffer_bytes_at(address: LispObject, length: LispObject, bits: LispObject) -> LispObject {
+    let group = group_size(bits);
+    let address = address.as_natnum_or_error() as usize;
+    let length = length.as_natnum_or_error() as usize;
+    if length == 0 {
+        return unibyte_string(&[]);
+    }
+    let line_length = 60 + 16 / group;
+    let first_line = address / LINE_BYTES;
+    let last_line = (address + length - 1) / LINE_BYTES;
+    let point_min = call!(intern("point-min")).as_fixnum_or_error();
+    let point_max = call!(intern("point-max")).as_fixnum_or_error();
+    let start = point_min + (first_line * line_length) as EmacsInt;
+    if start >= point_max {
+        return unibyte_string(&[]);
+    }
+    let end = point_max.min(point_min + ((last_line + 1) * line_length) as EmacsInt);
+    let text = call!(
+        intern("buffer-substring-no-properties"),
+        LispObject::from_fixnum(start),
+        LispObject::from_fixnum(end)
+    );
+    let text = text.as_string_or_error();
+    let lines: Vec<&[u8]> = text.as_slice().split(|&b| b == b'\n').collect();
+    let bytes: Vec<u8> = (address..address + length)
+        .map(|a| {
+            lines
+                .get(a / LINE_BYTES - first_line)
+                .and_then(|line| byte_in_line(line, a % LINE_BYTES, group))
+        })
+        .take_while(Option::is_some)
+        .map(Option::unwrap)
+        .collect();
+    unibyte_string(&bytes)
+}
+
+/// Return the byte at ADDRESS of the data shown by the hex dump in the
+/// current buffer, or nil if the dump ends before.  BITS is the grouping
+/// of the hex digits, and defaults to `hexl-bits'.
+#[lisp_fn(min = "1")]
+pub fn buffer_byte_at(address: LispObject, bits: LispObject) -> LispObject {
+    let bytes = buffer_bytes_at(address, LispObject::from_natnum(1), bits);
+    match bytes.as_string_or_error().as_slice().first() {
+        Some(&b) => LispObject::from_natnum(EmacsInt::from(b)),
+        None => LispObject::constant_nil(),
+    }
+}
+
+#[test]
+fn test_hexlify() {
+    let data: Vec<u8> = (0u8..20).map(|b| b.wrapping_mul(13).wrapping_add(b'A')).collect();
+    let dump = hexlify(&data, 2, false);
+    let expected = "00000000: 414e 5b68 7582 8f9c a9b6 c3d0 ddea f704  AN[hu...........\n\
+                    00000010: 111e 2b38                                ..+8\n";
+    assert_eq!(String::from_utf8(dump).unwrap(), expected);
+
+    let dump = hexlify(b"ab\xe9", 1, true);
+    assert_eq!(
+        dump,
+        b"00000000: 61 62 e9                                         ab\xe9\n".to_vec()
+    );
+    assert!(hexlify(b"", 2, false).is_empty());
+}
+
+#[test]
+fn test_line_length() {
+    // `hexl-line-displen' is 60 plus 128 divided by the bits of a group.
+    let data = [0u8; 40];
+    for &group in &[1, 2, 4, 8] {
+        let dump = hexlify(&data, group, false);
+        let line = dump.split(|&b| b == b'\n').next().unwrap();
+        assert_eq!(line.len() + 1, 60 + 16 / group);
+    }
+}
+
+#[test]
+fn test_dehexlify() {
+    let data: Vec<u8> = (0..256).map(|b| b as u8).chain(b"tail".iter().cloned()).collect();
+    for &group in &[1, 2, 4, 8] {
+        for &iso in &[false, true] {
+            assert_eq!(dehexlify(&hexlify(&data, group, iso), group), data);
+        }
+    }
+    assert!(dehexlify(b"", 2).is_empty());
+    assert_eq!(dehexlify(b"00000000: 4142\n", 2), b"AB".to_vec());
+}
+
+#[test]
+fn test_byte_in_line() {
+    let dump = hexlify(b"0123456789abcdefXYZ", 4, false);
+    let lines: Vec<&[u8]> = dump.split(|&b| b == b'\n').collect();
+    assert_eq!(byte_in_line(lines[0], 0, 4), Some(b'0'));
+    assert_eq!(byte_in_line(lines[0], 5, 4), Some(b'5'));
+    assert_eq!(byte_in_line(lines[0], 15, 4), Some(b'f'));
+    assert_eq!(byte_in_line(lines[1], 2, 4), Some(b'Z'));
+    assert_eq!(byte_in_line(lines[1], 3, 4), None);
+    assert_eq!(byte_in_line(b"00000000: 4A", 0, 2), Some(0x4a));
+}
+
+include!(concat!(env!("OUT_DIR"), "/hex_exports.rs"));
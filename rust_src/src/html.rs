@@ -0,0 +1,684 @@
+//! Rendering HTML to text for the common case.
+//!
+//! shr renders a document by inserting every piece of text into the
+//! buffer and then filling the lines with `vertical-motion', which is
+//! slow for long mail and web pages.  Most of them only use paragraphs,
+//! headings, emphasis, links, lists and simple tables, which are laid
+//! out here in columns, as shr lays them out when `shr-use-fonts' is
+//! nil, and inserted line by line.  Documents that use anything else
+//! are handed to shr.
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{record_unwind_current_buffer, unbind_to, EmacsInt, Qnil};
+
+use buffers::set_buffer;
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::assq;
+use strings::lisp_string;
+use symbols::{symbol_name, symbol_value};
+use threads::ThreadState;
+
+/// Something that makes shr hand the document over.
+struct Unsupported;
+
+type Rendered = Result<(), Unsupported>;
+
+/// A face or a link that applies to some text.
+#[derive(Clone, Debug, PartialEq)]
+enum Mark {
+    Face(&'static str),
+    Link { url: String, title: Option<String> },
+}
+
+/// The marks of a piece of text, from the outermost element in.
+type Style = Vec<Mark>;
+
+/// A piece of text with one style.
+#[derive(Clone, Debug, PartialEq)]
+struct Run {
+    text: String,
+    style: Style,
+}
+
+impl Run {
+    fn plain(text: String) -> Run {
+        Run {
+            text,
+            style: Vec::new(),
+        }
+    }
+}
+
+/// A line of output, without its newline.
+type Line = Vec<Run>;
+
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r'
+}
+
+fn is_blank(line: &[Run]) -> bool {
+    line.iter().all(|run| run.text.chars().all(|c| c == ' '))
+}
+
+/// Return the width of TEXT in columns.
+fn text_width(text: &str) -> usize {
+    if text.bytes().all(|b| b < 0x80) {
+        text.len()
+    } else {
+        call!(intern("string-width"), lisp_string(text)).as_natnum_or_error() as usize
+    }
+}
+
+/// A word or the space between words, in text that is filled.
+enum Item {
+    Word(Vec<Run>),
+    Space(Style),
+}
+
+/// Split RUNS into words and spaces, collapsing runs of whitespace and
+/// dropping it at the start, as `shr-insert' does.
+fn words(runs: &[Run]) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    for run in runs {
+        for c in run.text.chars() {
+            if is_space(c) {
+                if let Some(&Item::Word(_)) = items.last() {
+                    items.push(Item::Space(run.style.clone()));
+                }
+                continue;
+            }
+            if let Some(&mut Item::Word(ref mut pieces)) = items.last_mut() {
+                if pieces.last().map_or(false, |piece| piece.style == run.style) {
+                    pieces.last_mut().unwrap().text.push(c);
+                } else {
+                    pieces.push(Run {
+                        text: c.to_string(),
+                        style: run.style.clone(),
+                    });
+                }
+                continue;
+            }
+            items.push(Item::Word(vec![Run {
+                text: c.to_string(),
+                style: run.style.clone(),
+            }]));
+        }
+    }
+    items
+}
+
+/// Fill WORDS into lines WIDTH columns wide.  The first line starts
+/// with FIRST, and the others with INDENT spaces; both are INDENT
+/// columns wide.  WIDTH_OF measures the text of a word.
+fn fill<F: FnMut(&str) -> usize>(
+    words: Vec<Item>,
+    first: String,
+    indent: usize,
+    width: usize,
+    mut width_of: F,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line: Line = vec![Run::plain(first)];
+    let mut column = indent;
+    let mut line_empty = true;
+    let mut space: Option<Style> = None;
+    for item in words {
+        match item {
+            Item::Space(style) => space = Some(style),
+            Item::Word(pieces) => {
+                let word_width: usize = pieces.iter().map(|piece| width_of(&piece.text)).sum();
+                if !line_empty && column + 1 + word_width > width {
+                    lines.push(line);
+                    line = vec![Run::plain(" ".repeat(indent))];
+                    column = indent;
+                    line_empty = true;
+                } else if let Some(style) = space {
+                    if !line_empty {
+                        line.push(Run {
+                            text: " ".to_string(),
+                            style,
+                        });
+                        column += 1;
+                    }
+                }
+                space = None;
+                line.extend(pieces);
+                column += word_width;
+                line_empty = false;
+            }
+        }
+    }
+    if !line_empty {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The state of laying out a document.
+struct Layout {
+    /// The width to fill to, in columns.
+    width: usize,
+    lines: Vec<Line>,
+    /// The text of the block being laid out.
+    pending: Vec<Run>,
+    /// The indentation of the block, in columns.
+    indent: usize,
+    /// The bullet of a list item whose first line is not laid out yet,
+    /// and the indentation before it.
+    bullet: Option<(String, usize)>,
+    /// Whether the text is preformatted.
+    pre: bool,
+    /// The next number of an ordered list, or None in other lists.
+    number: Option<EmacsInt>,
+    /// The base URL that links are relative to.
+    base: Option<String>,
+}
+
+impl Layout {
+    fn new(width: usize) -> Layout {
+        Layout {
+            width,
+            lines: Vec::new(),
+            pending: Vec::new(),
+            indent: 0,
+            bullet: None,
+            pre: false,
+            number: None,
+            base: None,
+        }
+    }
+
+    /// Return the start of the first line of the pending block.
+    fn first_prefix(&mut self) -> String {
+        match self.bullet.take() {
+            Some((bullet, before)) => " ".repeat(before) + &bullet,
+            None => " ".repeat(self.indent),
+        }
+    }
+
+    /// Lay out the pending text, so that what follows starts a line.
+    fn ensure_newline(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let pending: Vec<Run> = self.pending.drain(..).collect();
+        if self.pre {
+            let mut line = vec![Run::plain(self.first_prefix())];
+            for run in pending {
+                let mut segments = run.text.split('\n').peekable();
+                while let Some(segment) = segments.next() {
+                    if !segment.is_empty() {
+                        line.push(Run {
+                            text: segment.to_string(),
+                            style: run.style.clone(),
+                        });
+                    }
+                    if segments.peek().is_some() {
+                        self.lines.push(line);
+                        line = vec![Run::plain(" ".repeat(self.indent))];
+                    }
+                }
+            }
+            if !is_blank(&line) {
+                self.lines.push(line);
+            }
+            return;
+        }
+        let words = words(&pending);
+        if words.is_empty() {
+            return;
+        }
+        let first = self.first_prefix();
+        let lines = fill(words, first, self.indent, self.width, text_width);
+        self.lines.extend(lines);
+    }
+
+    /// Start a new paragraph, as `shr-ensure-paragraph' does.
+    fn ensure_paragraph(&mut self) {
+        self.ensure_newline();
+        // Nothing separates the start of a list item from its text.
+        if self.bullet.is_some() {
+            return;
+        }
+        if self.lines.last().map_or(false, |line| !is_blank(line)) {
+            self.lines.push(Vec::new());
+        }
+    }
+
+    fn text(&mut self, text: String, style: &Style) {
+        self.pending.push(Run {
+            text,
+            style: style.clone(),
+        });
+    }
+
+    /// Lay out the children of DOM with STYLE.
+    fn children(&mut self, dom: LispObject, style: &Style) -> Rendered {
+        let children = call!(intern("cddr"), dom);
+        for child in children.iter_cars_safe() {
+            self.node(child, style)?;
+        }
+        Ok(())
+    }
+
+    /// Lay out the children of DOM with MARK added to STYLE.
+    fn marked(&mut self, dom: LispObject, style: &Style, mark: Mark) -> Rendered {
+        let mut style = style.clone();
+        style.push(mark);
+        self.children(dom, &style)
+    }
+
+    fn heading(&mut self, dom: LispObject, style: &Style, faces: &[&'static str]) -> Rendered {
+        self.ensure_paragraph();
+        let mut style = style.clone();
+        // shr appends each face after those of the text inside.
+        style.extend(faces.iter().rev().map(|&face| Mark::Face(face)));
+        self.children(dom, &style)?;
+        self.ensure_paragraph();
+        Ok(())
+    }
+
+    fn node(&mut self, node: LispObject, style: &Style) -> Rendered {
+        if let Some(string) = node.as_string() {
+            let text = String::from_utf8_lossy(string.as_slice()).into_owned();
+            self.text(text, style);
+            return Ok(());
+        }
+        let tag = call!(intern("car-safe"), node);
+        if !tag.is_symbol() {
+            return Ok(());
+        }
+        let external = symbol_value(intern("shr-external-rendering-functions"));
+        if assq(tag, external).is_not_nil() {
+            return Err(Unsupported);
+        }
+        let css = attribute(node, "style").unwrap_or_default();
+        if css.contains("color") || css.contains("display") || css.contains("border-collapse") {
+            return Err(Unsupported);
+        }
+        let name = string(symbol_name(tag));
+        match name.as_str() {
+            "comment" | "script" | "style" | "meta" | "link" => Ok(()),
+            "html" | "head" | "body" | "span" | "tt" | "code" | "kbd" | "samp" | "var"
+            | "abbr" | "cite" | "q" | "small" | "big" | "sub" | "sup" | "mark" | "time"
+            | "section" | "article" | "header" | "footer" | "main" | "nav" | "aside"
+            | "address" | "noscript" => self.children(node, style),
+            "p" | "dl" => {
+                self.ensure_paragraph();
+                self.children(node, style)?;
+                self.ensure_paragraph();
+                Ok(())
+            }
+            "label" => {
+                self.children(node, style)?;
+                self.ensure_paragraph();
+                Ok(())
+            }
+            "div" | "dt" => {
+                self.ensure_newline();
+                self.children(node, style)?;
+                self.ensure_newline();
+                Ok(())
+            }
+            "dd" => {
+                self.ensure_newline();
+                self.indent += 4;
+                let rendered = self.children(node, style);
+                self.ensure_newline();
+                self.indent -= 4;
+                rendered
+            }
+            "b" | "strong" => self.marked(node, style, Mark::Face("bold")),
+            "i" | "em" => self.marked(node, style, Mark::Face("italic")),
+            "u" => self.marked(node, style, Mark::Face("underline")),
+            "s" => self.marked(node, style, Mark::Face("shr-strike-through")),
+            "h1" | "h2" => self.heading(node, style, &["bold"]),
+            "h3" => self.heading(node, style, &["italic"]),
+            "h4" | "h5" | "h6" => self.heading(node, style, &[]),
+            "title" => self.heading(node, style, &["bold", "underline"]),
+            "a" => match attribute(node, "href") {
+                Some(href) => {
+                    let base = self.base
+                        .as_ref()
+                        .map_or(LispObject::constant_nil(), |base| lisp_string(base));
+                    let url = call!(intern("shr-expand-url"), lisp_string(&href), base);
+                    let link = Mark::Link {
+                        url: if url.is_string() { string(url) } else { href },
+                        title: attribute(node, "title"),
+                    };
+                    self.marked(node, style, link)
+                }
+                None => self.children(node, style),
+            },
+            "base" => {
+                let outer = self.base.clone();
+                if let Some(href) = attribute(node, "href") {
+                    self.base = Some(href);
+                }
+                let rendered = self.children(node, style);
+                self.base = outer;
+                rendered
+            }
+            "br" => {
+                if !self.pending.is_empty() {
+                    self.ensure_newline();
+                } else if self.lines.last().map_or(false, |line| !is_blank(line)) {
+                    self.lines.push(Vec::new());
+                }
+                self.children(node, style)
+            }
+            "hr" => {
+                self.ensure_newline();
+                let line = "-".repeat(self.width);
+                self.lines.push(vec![Run::plain(line)]);
+                Ok(())
+            }
+            "pre" => {
+                self.ensure_newline();
+                let outer = self.pre;
+                self.pre = true;
+                let rendered = self.children(node, style);
+                self.ensure_newline();
+                self.pre = outer;
+                rendered
+            }
+            "blockquote" => {
+                self.ensure_paragraph();
+                self.indent += 4;
+                let rendered = self.children(node, style);
+                self.ensure_paragraph();
+                self.indent -= 4;
+                rendered
+            }
+            "ul" | "ol" => {
+                self.ensure_paragraph();
+                let outer = self.number;
+                self.number = if name == "ol" { Some(1) } else { None };
+                let rendered = self.children(node, style);
+                self.number = outer;
+                self.ensure_paragraph();
+                rendered
+            }
+            "li" => self.list_item(node, style),
+            "table" => self.table(node, style),
+            _ => Err(Unsupported),
+        }
+    }
+
+    fn list_item(&mut self, node: LispObject, style: &Style) -> Rendered {
+        self.ensure_newline();
+        let bullet = match self.number {
+            Some(number) => {
+                self.number = Some(number + 1);
+                format!("{} ", number)
+            }
+            None => string(symbol_value(intern("shr-bullet"))),
+        };
+        let outer = self.indent;
+        self.indent += text_width(&bullet);
+        self.bullet = Some((bullet, outer));
+        let rendered = self.children(node, style);
+        self.ensure_newline();
+        // An empty item still gets its bullet.
+        if let Some((bullet, before)) = self.bullet.take() {
+            self.lines.push(vec![Run::plain(" ".repeat(before) + &bullet)]);
+        }
+        self.indent = outer;
+        rendered
+    }
+
+    /// Lay out a table whose cells each fit on a line, with the columns
+    /// as wide as their widest cell.
+    fn table(&mut self, node: LispObject, style: &Style) -> Rendered {
+        let mut rows: Vec<Vec<(Line, usize)>> = Vec::new();
+        for row in table_rows(node)? {
+            let mut cells = Vec::new();
+            for cell in row {
+                if attribute(cell, "colspan").is_some() || attribute(cell, "rowspan").is_some() {
+                    return Err(Unsupported);
+                }
+                let mut layout = Layout::new(usize::max_value() / 2);
+                layout.base = self.base.clone();
+                layout.children(cell, style)?;
+                layout.ensure_newline();
+                if layout.lines.len() > 1 {
+                    return Err(Unsupported);
+                }
+                let line = layout.lines.pop().unwrap_or_default();
+                let width = line.iter().map(|run| text_width(&run.text)).sum();
+                cells.push((line, width));
+            }
+            rows.push(cells);
+        }
+        let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                rows.iter()
+                    .filter_map(|row| row.get(column).map(|cell| cell.1))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        if self.indent + widths.iter().sum::<usize>() + columns + 1 > self.width {
+            return Err(Unsupported);
+        }
+        self.ensure_paragraph();
+        for row in rows {
+            let mut line = vec![Run::plain(" ".repeat(self.indent))];
+            for (column, (cell, width)) in row.into_iter().enumerate() {
+                line.push(Run::plain(" ".to_string()));
+                line.extend(cell);
+                if column + 1 < columns {
+                    line.push(Run::plain(" ".repeat(widths[column] - width)));
+                }
+            }
+            self.lines.push(line);
+        }
+        self.ensure_paragraph();
+        Ok(())
+    }
+}
+
+/// Return the text of the Lisp string STRING.
+fn string(string: LispObject) -> String {
+    String::from_utf8_lossy(string.as_string_or_error().as_slice()).into_owned()
+}
+
+/// Return the attribute NAME of the element DOM, if it is a string.
+fn attribute(dom: LispObject, name: &str) -> Option<String> {
+    let attributes = call!(intern("cadr"), dom);
+    let value = call!(intern("cdr"), assq(intern(name), attributes));
+    if value.is_string() {
+        Some(string(value))
+    } else {
+        None
+    }
+}
+
+/// Return the cells of each row of TABLE, or Unsupported if it has
+/// anything but rows of cells, in a head, body and foot or not.
+fn table_rows(table: LispObject) -> Result<Vec<Vec<LispObject>>, Unsupported> {
+    let mut rows = Vec::new();
+    let add_row = |row: LispObject, rows: &mut Vec<Vec<LispObject>>| -> Rendered {
+        let mut cells = Vec::new();
+        for cell in call!(intern("cddr"), row).iter_cars_safe() {
+            if cell.is_string() {
+                continue;
+            }
+            match string(symbol_name(call!(intern("car"), cell))).as_str() {
+                "td" | "th" => cells.push(cell),
+                "comment" => (),
+                _ => return Err(Unsupported),
+            }
+        }
+        rows.push(cells);
+        Ok(())
+    };
+    for child in call!(intern("cddr"), table).iter_cars_safe() {
+        if child.is_string() {
+            continue;
+        }
+        match string(symbol_name(call!(intern("car"), child))).as_str() {
+            "tr" => add_row(child, &mut rows)?,
+            "thead" | "tbody" | "tfoot" => {
+                for row in call!(intern("cddr"), child).iter_cars_safe() {
+                    if row.is_string() {
+                        continue;
+                    }
+                    match string(symbol_name(call!(intern("car"), row))).as_str() {
+                        "tr" => add_row(row, &mut rows)?,
+                        "comment" => (),
+                        _ => return Err(Unsupported),
+                    }
+                }
+            }
+            "comment" => (),
+            _ => return Err(Unsupported),
+        }
+    }
+    Ok(rows)
+}
+
+/// Insert LINES at point, with the faces and links of their text.
+fn insert_lines(lines: Vec<Line>) {
+    let point = || LispObject::from_natnum(ThreadState::current_buffer().pt() as EmacsInt);
+    for line in lines {
+        for run in line {
+            let start = point();
+            call!(intern("insert"), lisp_string(&run.text));
+            // The innermost marks come first, as in shr.
+            for mark in run.style.into_iter().rev() {
+                match mark {
+                    Mark::Face(face) => {
+                        call!(
+                            intern("add-face-text-property"),
+                            start,
+                            point(),
+                            intern(face),
+                            LispObject::constant_t()
+                        );
+                    }
+                    Mark::Link { url, title } => {
+                        let title = title
+                            .as_ref()
+                            .map_or(LispObject::constant_nil(), |title| lisp_string(title));
+                        call!(intern("shr-urlify"), start, lisp_string(&url), title);
+                    }
+                }
+            }
+        }
+        call!(intern("insert"), lisp_string("\n"));
+    }
+}
+
+/// Render the HTML between START and END into BUFFER, like `shr-render-region'.
+/// BUFFER defaults to the current buffer, and the text is inserted at its
+/// point.  Documents made of paragraphs, headings, emphasis, links,
+/// lists, preformatted text and tables of one line per cell are laid out
+/// directly, in columns, as shr lays them out when `shr-use-fonts' is
+/// nil, which is much faster for long mail and web pages.  Anything
+/// else, such as images, forms, colors or cells that span columns, is
+/// rendered by `shr-insert-document'.  Return t if the document was laid
+/// out directly, and nil if shr rendered it.
+#[lisp_fn(min = "2")]
+pub fn html_render_region_native(
+    start: LispObject,
+    end: LispObject,
+    buffer: LispObject,
+) -> LispObject {
+    let parse = intern("libxml-parse-html-region");
+    if call!(intern("fboundp"), parse).is_nil() {
+        error!("This function requires Emacs to be compiled with libxml2");
+    }
+    call!(intern("require"), intern("shr"));
+    let dom = call!(parse, start, end);
+    let count = specpdl_index();
+    unsafe { record_unwind_current_buffer() };
+    if buffer.is_not_nil() {
+        set_buffer(buffer);
+    }
+    let width = match symbol_value(intern("shr-width")).as_fixnum() {
+        Some(width) => width,
+        None => call!(intern("window-body-width")).as_fixnum_or_error() - 1,
+    };
+    let mut layout = Layout::new(width.max(1) as usize);
+    let native = match layout.node(dom, &Vec::new()) {
+        Ok(()) => {
+            layout.ensure_newline();
+            let mut lines = layout.lines;
+            while lines.last().map_or(false, |line| is_blank(line)) {
+                lines.pop();
+            }
+            insert_lines(lines);
+            true
+        }
+        Err(Unsupported) => {
+            call!(intern("shr-insert-document"), dom);
+            false
+        }
+    };
+    unsafe { unbind_to(count, Qnil) };
+    LispObject::from_bool(native)
+}
+
+#[cfg(test)]
+fn runs(texts: &[&str]) -> Vec<Run> {
+    texts.iter().map(|text| Run::plain(text.to_string())).collect()
+}
+
+#[cfg(test)]
+fn line_texts(lines: &[Line]) -> Vec<String> {
+    lines
+        .iter()
+        .map(|line| line.iter().map(|run| run.text.as_str()).collect())
+        .collect()
+}
+
+#[test]
+fn test_fill() {
+    let width = |text: &str| text.chars().count();
+    let lines = fill(
+        words(&runs(&["  The quick\n brown", " fox jumps over the lazy dog "])),
+        String::new(),
+        0,
+        15,
+        width,
+    );
+    assert_eq!(
+        line_texts(&lines),
+        vec!["The quick brown", "fox jumps over", "the lazy dog"]
+    );
+    let lines = fill(
+        words(&runs(&["one two three"])),
+        "* ".to_string(),
+        2,
+        9,
+        width,
+    );
+    assert_eq!(line_texts(&lines), vec!["* one two", "  three"]);
+    // A word longer than the line is not broken.
+    let lines = fill(words(&runs(&["abcdefghij k"])), String::new(), 0, 5, width);
+    assert_eq!(line_texts(&lines), vec!["abcdefghij", "k"]);
+    assert!(fill(words(&runs(&[" \n "])), String::new(), 0, 5, width).is_empty());
+}
+
+#[test]
+fn test_words_keep_styles() {
+    let bold = Run {
+        text: "bold".to_string(),
+        style: vec![Mark::Face("bold")],
+    };
+    let items = words(&[Run::plain("not".to_string()), bold.clone()]);
+    assert_eq!(items.len(), 1);
+    match items[0] {
+        Item::Word(ref pieces) => {
+            assert_eq!(pieces.len(), 2);
+            assert_eq!(pieces[1], bold);
+        }
+        Item::Space(_) => panic!("expected a word"),
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/html_exports.rs"));
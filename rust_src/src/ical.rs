@@ -0,0 +1,1101 @@
+//! Parsing of iCalendar data (RFC 5545).
+//!
+//! `icalendar-import-buffer' used to read calendars with one regexp
+//! search and one `append' per line, which is quadratic in the size of
+//! a component.  The parser here builds the same tree in one pass, and
+//! expands the recurrence rules of events and to-dos as RFC 5545
+//! describes, so callers don't have to interpret RRULE themselves.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_specified_string, EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// The default for the maximum number of occurrences of a component.
+const DEFAULT_LIMIT: u16 = 500;
+
+/// Whether the names A and B are the same, ignoring case.
+fn same_name(a: &[u8], b: &[u8]) -> bool {
+    let upcase = |b: u8| if b >= b'a' && b <= b'z' { b - 32 } else { b };
+    a.len() == b.len() && a.iter().zip(b).all(|(&x, &y)| upcase(x) == upcase(y))
+}
+
+/// A content line, `NAME;PARAM=VALUE:VALUE'.  A parameter with several
+/// values keeps them joined by commas.
+#[derive(Debug, PartialEq)]
+struct Property<'a> {
+    name: &'a [u8],
+    params: Vec<(&'a [u8], Vec<u8>)>,
+    value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    fn param(&self, name: &[u8]) -> Option<&[u8]> {
+        self.params
+            .iter()
+            .find(|&&(n, _)| same_name(n, name))
+            .map(|&(_, ref value)| value.as_slice())
+    }
+}
+
+/// A component between `BEGIN:NAME' and `END:NAME'.
+#[derive(Debug, PartialEq)]
+struct Component<'a> {
+    name: &'a [u8],
+    params: Vec<(&'a [u8], Vec<u8>)>,
+    properties: Vec<Property<'a>>,
+    children: Vec<Component<'a>>,
+}
+
+impl<'a> Component<'a> {
+    fn property(&self, name: &[u8]) -> Option<&Property<'a>> {
+        self.properties
+            .iter()
+            .find(|property| same_name(property.name, name))
+    }
+}
+
+/// Remove the line folding of TEXT, and return its lines without their
+/// line ends.
+fn unfold(text: &[u8]) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut i = 0;
+    while i < text.len() {
+        let b = text[i];
+        i += 1;
+        if b != b'\r' && b != b'\n' {
+            line.push(b);
+            continue;
+        }
+        if b == b'\r' && text.get(i) == Some(&b'\n') {
+            i += 1;
+        }
+        match text.get(i) {
+            Some(&b' ') | Some(&b'\t') => i += 1,
+            _ => lines.push(::std::mem::replace(&mut line, Vec::new())),
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn is_name_byte(b: u8) -> bool {
+    match b {
+        b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' => true,
+        _ => false,
+    }
+}
+
+/// Read the parameter value at I in LINE, quoted or not.  Return it and
+/// where it ends.
+fn param_value(line: &[u8], i: usize) -> (&[u8], usize) {
+    if line.get(i) == Some(&b'"') {
+        let len = line[i + 1..]
+            .iter()
+            .position(|&b| b == b'"')
+            .unwrap_or(line.len() - i - 1);
+        (&line[i + 1..i + 1 + len], (i + 2 + len).min(line.len()))
+    } else {
+        let len = line[i..]
+            .iter()
+            .position(|&b| b == b';' || b == b',' || b == b':')
+            .unwrap_or(line.len() - i);
+        (&line[i..i + len], i + len)
+    }
+}
+
+/// Parse the content line LINE, or return None if it isn't one.
+fn parse_line(line: &[u8]) -> Option<Property> {
+    let name_len = line.iter().take_while(|&&b| is_name_byte(b)).count();
+    if name_len == 0 {
+        return None;
+    }
+    let mut property = Property {
+        name: &line[..name_len],
+        params: Vec::new(),
+        value: b"",
+    };
+    let mut i = name_len;
+    while line.get(i) == Some(&b';') {
+        let start = i + 1;
+        let len = line[start..].iter().take_while(|&&b| is_name_byte(b)).count();
+        if len == 0 || line.get(start + len) != Some(&b'=') {
+            return None;
+        }
+        let (first, end) = param_value(line, start + len + 1);
+        let mut value = first.to_vec();
+        i = end;
+        while line.get(i) == Some(&b',') {
+            let (next, end) = param_value(line, i + 1);
+            value.push(b',');
+            value.extend_from_slice(next);
+            i = end;
+        }
+        property.params.push((&line[start..start + len], value));
+    }
+    if line.get(i) != Some(&b':') {
+        return None;
+    }
+    property.value = &line[i + 1..];
+    Some(property)
+}
+
+/// Parse the unfolded LINES into the tree of their components.  Lines
+/// outside any component and lines that aren't content lines are
+/// ignored, a stray END is ignored, and components still open at the
+/// end are closed.
+fn parse_components(lines: &[Vec<u8>]) -> Vec<Component> {
+    let mut stack: Vec<Component> = Vec::new();
+    let mut top = Vec::new();
+    for line in lines {
+        let property = match parse_line(line) {
+            Some(property) => property,
+            None => continue,
+        };
+        if same_name(property.name, b"BEGIN") {
+            stack.push(Component {
+                name: property.value,
+                params: property.params,
+                properties: Vec::new(),
+                children: Vec::new(),
+            });
+        } else if same_name(property.name, b"END") {
+            if let Some(component) = stack.pop() {
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(component),
+                    None => top.push(component),
+                }
+            }
+        } else if let Some(component) = stack.last_mut() {
+            component.properties.push(property);
+        }
+    }
+    while let Some(component) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(component),
+            None => top.push(component),
+        }
+    }
+    top
+}
+
+/// Seconds in a day.
+const DAY: i64 = 24 * 3600;
+
+/// Recurrences are not expanded past the end of this year, so that
+/// rules that never match come to an end.
+const LAST_YEAR: i64 = 9999;
+
+fn floor_div(a: i64, b: i64) -> i64 {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn floor_mod(a: i64, b: i64) -> i64 {
+    a - b * floor_div(a, b)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    floor_mod(year, 4) == 0 && (floor_mod(year, 100) != 0 || floor_mod(year, 400) == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days from January 1, 1970 to MONTH DAY, YEAR.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = floor_div(year, 400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The (YEAR, MONTH, DAY) of DAYS after January 1, 1970.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = floor_div(days, 146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// The day of the week of DAYS after January 1, 1970, from 0 for
+/// Monday to 6 for Sunday.
+fn weekday(days: i64) -> i64 {
+    floor_mod(days + 3, 7)
+}
+
+/// The first day of week 1 of YEAR, for weeks that start on the
+/// weekday WEEK_START.  Week 1 is the first one with at least four days
+/// in the year, the one containing January 4.
+fn first_week_start(year: i64, week_start: i64) -> i64 {
+    let january_4 = days_from_civil(year, 1, 4);
+    january_4 - floor_mod(weekday(january_4) - week_start, 7)
+}
+
+/// The number of the week DAY is in, and the number of weeks of the
+/// year that week belongs to.
+fn week_number(day: i64, week_start: i64) -> (i64, i64) {
+    let (mut year, _, _) = civil_from_days(day);
+    if day >= first_week_start(year + 1, week_start) {
+        year += 1;
+    } else if day < first_week_start(year, week_start) {
+        year -= 1;
+    }
+    let first = first_week_start(year, week_start);
+    let weeks = (first_week_start(year + 1, week_start) - first) / 7;
+    ((day - first) / 7 + 1, weeks)
+}
+
+/// Whether the Nth item of COUNT items, counting from the end for
+/// negative N as in RRULE, is the INDEXth, counting from 1.
+fn nth_is(n: i64, index: i64, count: i64) -> bool {
+    if n > 0 {
+        index == n
+    } else {
+        index == count + 1 + n
+    }
+}
+
+/// A date and time as written, before its time zone is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct LocalTime {
+    /// Days after January 1, 1970.
+    day: i64,
+    /// Seconds after midnight.
+    second: i64,
+}
+
+/// The time zone a date and time is in.
+#[derive(Clone, Debug, PartialEq)]
+enum Zone {
+    Utc,
+    /// Local time, as for times without a zone.
+    Floating,
+    /// The zone named by a TZID parameter.
+    Named(String),
+}
+
+/// The value of BYTES, which must consist of decimal digits.
+fn number(bytes: &[u8]) -> Option<i64> {
+    if bytes.is_empty() || bytes.len() > 9 || bytes.iter().any(|&b| b < b'0' || b > b'9') {
+        return None;
+    }
+    Some(bytes.iter().fold(0, |n, &b| n * 10 + i64::from(b - b'0')))
+}
+
+fn signed_number(bytes: &[u8]) -> Option<i64> {
+    match bytes.first() {
+        Some(&b'-') => number(&bytes[1..]).map(|n| -n),
+        Some(&b'+') => number(&bytes[1..]),
+        _ => number(bytes),
+    }
+}
+
+/// Parse VALUE, a DATE such as 20200106 or a DATE-TIME such as
+/// 20200106T090000, which ends in Z if it is in UTC.  Return the time
+/// and whether it is in UTC; a DATE stands for its midnight.
+fn parse_date_time(value: &[u8]) -> Option<(LocalTime, bool)> {
+    if value.len() < 8 {
+        return None;
+    }
+    let year = number(&value[..4])?;
+    let month = number(&value[4..6])?;
+    let day = number(&value[6..8])?;
+    if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let day = days_from_civil(year, month, day);
+    let time = &value[8..];
+    if time.is_empty() {
+        return Some((LocalTime { day, second: 0 }, false));
+    }
+    if time.len() < 7 || (time[0] != b'T' && time[0] != b't') {
+        return None;
+    }
+    let hour = number(&time[1..3])?;
+    let minute = number(&time[3..5])?;
+    let second = number(&time[5..7])?;
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let utc = match time.len() {
+        7 => false,
+        8 if time[7] == b'Z' || time[7] == b'z' => true,
+        _ => return None,
+    };
+    let second = hour * 3600 + minute * 60 + second;
+    Some((LocalTime { day, second }, utc))
+}
+
+/// The zone of the times in PROPERTY, given whether they are in UTC.
+fn property_zone(property: &Property, utc: bool) -> Zone {
+    match property.param(b"TZID") {
+        _ if utc => Zone::Utc,
+        Some(tzid) => Zone::Named(String::from_utf8_lossy(tzid).into_owned()),
+        None => Zone::Floating,
+    }
+}
+
+/// The times listed by an EXDATE or RDATE PROPERTY, in seconds since
+/// the epoch, or None if one of them can't be read.  For the periods
+/// of an RDATE, only their start counts.
+fn property_times<F>(property: &Property, seconds: &F) -> Option<Vec<i64>>
+where
+    F: Fn(LocalTime, &Zone) -> i64,
+{
+    let mut times = Vec::new();
+    for value in property.value.split(|&b| b == b',') {
+        let start = value.split(|&b| b == b'/').next().unwrap_or(value);
+        let (time, utc) = parse_date_time(start)?;
+        times.push(seconds(time, &property_zone(property, utc)));
+    }
+    Some(times)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Frequency {
+    Yearly,
+    Monthly,
+    Weekly,
+    Daily,
+    Hourly,
+    Minutely,
+    Secondly,
+}
+
+const FREQUENCIES: [(&[u8], Frequency); 7] = [
+    (b"YEARLY", Frequency::Yearly),
+    (b"MONTHLY", Frequency::Monthly),
+    (b"WEEKLY", Frequency::Weekly),
+    (b"DAILY", Frequency::Daily),
+    (b"HOURLY", Frequency::Hourly),
+    (b"MINUTELY", Frequency::Minutely),
+    (b"SECONDLY", Frequency::Secondly),
+];
+
+const WEEKDAYS: [&[u8]; 7] = [b"MO", b"TU", b"WE", b"TH", b"FR", b"SA", b"SU"];
+
+/// A recurrence rule, the value of RRULE.
+#[derive(Clone, Debug)]
+struct Rule {
+    frequency: Frequency,
+    interval: i64,
+    count: Option<i64>,
+    /// The last time an occurrence may start at, and whether it is in
+    /// UTC rather than in the zone of DTSTART.
+    until: Option<(LocalTime, bool)>,
+    by_second: Vec<i64>,
+    by_minute: Vec<i64>,
+    by_hour: Vec<i64>,
+    /// Weekdays from 0 for Monday, each with the number of its
+    /// occurrence in the month or year, or 0 for all of them.
+    by_day: Vec<(i64, i64)>,
+    by_month_day: Vec<i64>,
+    by_year_day: Vec<i64>,
+    by_week_no: Vec<i64>,
+    by_month: Vec<i64>,
+    by_set_pos: Vec<i64>,
+    week_start: i64,
+}
+
+fn parse_weekday(name: &[u8]) -> Option<i64> {
+    WEEKDAYS
+        .iter()
+        .position(|&day| same_name(day, name))
+        .map(|day| day as i64)
+}
+
+/// Parse the comma-separated list of numbers VALUE.  The numbers must
+/// lie between LOW and HIGH, or between -HIGH and -LOW if NEGATIVE.
+fn parse_numbers(value: &[u8], low: i64, high: i64, negative: bool) -> Option<Vec<i64>> {
+    value
+        .split(|&b| b == b',')
+        .map(|item| {
+            let n = signed_number(item)?;
+            if (n >= low && n <= high) || (negative && -n >= low && -n <= high) {
+                Some(n)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse the BYDAY list VALUE, such as MO,-1FR.
+fn parse_days(value: &[u8]) -> Option<Vec<(i64, i64)>> {
+    value
+        .split(|&b| b == b',')
+        .map(|item| {
+            if item.len() < 2 {
+                return None;
+            }
+            let (n, day) = item.split_at(item.len() - 2);
+            let n = if n.is_empty() { 0 } else { signed_number(n)? };
+            if n.abs() > 53 {
+                return None;
+            }
+            Some((n, parse_weekday(day)?))
+        })
+        .collect()
+}
+
+/// Parse VALUE, the value of an RRULE property.  Return None if it
+/// isn't a valid rule.  Parts that RFC 5545 doesn't know are ignored.
+fn parse_rule(value: &[u8]) -> Option<Rule> {
+    let mut frequency = None;
+    let mut rule = Rule {
+        frequency: Frequency::Yearly,
+        interval: 1,
+        count: None,
+        until: None,
+        by_second: Vec::new(),
+        by_minute: Vec::new(),
+        by_hour: Vec::new(),
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+        by_year_day: Vec::new(),
+        by_week_no: Vec::new(),
+        by_month: Vec::new(),
+        by_set_pos: Vec::new(),
+        week_start: 0,
+    };
+    for part in value.split(|&b| b == b';').filter(|part| !part.is_empty()) {
+        let equals = part.iter().position(|&b| b == b'=')?;
+        let (name, value) = (&part[..equals], &part[equals + 1..]);
+        let is = |expected: &[u8]| same_name(name, expected);
+        if is(b"FREQ") {
+            frequency = Some(FREQUENCIES
+                .iter()
+                .find(|&&(freq, _)| same_name(freq, value))?
+                .1);
+        } else if is(b"INTERVAL") {
+            rule.interval = number(value).and_then(|n| if n > 0 { Some(n) } else { None })?;
+        } else if is(b"COUNT") {
+            rule.count = Some(number(value)?);
+        } else if is(b"UNTIL") {
+            let (mut until, utc) = parse_date_time(value)?;
+            if value.len() == 8 {
+                // A date includes all of its day.
+                until.second = DAY - 1;
+            }
+            rule.until = Some((until, utc));
+        } else if is(b"BYSECOND") {
+            rule.by_second = parse_numbers(value, 0, 59, false)?;
+        } else if is(b"BYMINUTE") {
+            rule.by_minute = parse_numbers(value, 0, 59, false)?;
+        } else if is(b"BYHOUR") {
+            rule.by_hour = parse_numbers(value, 0, 23, false)?;
+        } else if is(b"BYDAY") {
+            rule.by_day = parse_days(value)?;
+        } else if is(b"BYMONTHDAY") {
+            rule.by_month_day = parse_numbers(value, 1, 31, true)?;
+        } else if is(b"BYYEARDAY") {
+            rule.by_year_day = parse_numbers(value, 1, 366, true)?;
+        } else if is(b"BYWEEKNO") {
+            rule.by_week_no = parse_numbers(value, 1, 53, true)?;
+        } else if is(b"BYMONTH") {
+            rule.by_month = parse_numbers(value, 1, 12, false)?;
+        } else if is(b"BYSETPOS") {
+            rule.by_set_pos = parse_numbers(value, 1, 366, true)?;
+        } else if is(b"WKST") {
+            rule.week_start = parse_weekday(value)?;
+        }
+    }
+    rule.frequency = frequency?;
+    Some(rule)
+}
+
+impl Rule {
+    /// Fill in the parts that the rule takes from START, its first
+    /// occurrence, when it doesn't give them, as RFC 5545 says.
+    fn with_defaults(&self, start: LocalTime) -> Rule {
+        let mut rule = self.clone();
+        let (_, month, day) = civil_from_days(start.day);
+        if rule.by_week_no.is_empty() && rule.by_year_day.is_empty()
+            && rule.by_month_day.is_empty() && rule.by_day.is_empty()
+        {
+            match rule.frequency {
+                Frequency::Yearly => {
+                    if rule.by_month.is_empty() {
+                        rule.by_month = vec![month];
+                    }
+                    rule.by_month_day = vec![day];
+                }
+                Frequency::Monthly => rule.by_month_day = vec![day],
+                Frequency::Weekly => rule.by_day = vec![(0, weekday(start.day))],
+                _ => (),
+            }
+        }
+        if rule.frequency < Frequency::Hourly && rule.by_hour.is_empty() {
+            rule.by_hour = vec![start.second / 3600];
+        }
+        if rule.frequency < Frequency::Minutely && rule.by_minute.is_empty() {
+            rule.by_minute = vec![start.second / 60 % 60];
+        }
+        if rule.frequency < Frequency::Secondly && rule.by_second.is_empty() {
+            rule.by_second = vec![start.second % 60];
+        }
+        for list in &mut [&mut rule.by_hour, &mut rule.by_minute, &mut rule.by_second] {
+            list.sort();
+            list.dedup();
+        }
+        rule
+    }
+
+    /// Whether DAY is one of the days the rule selects.
+    fn matches_day(&self, day: i64) -> bool {
+        let (year, month, month_day) = civil_from_days(day);
+        let matches = |list: &[i64], value: i64, count: i64| {
+            list.is_empty() || list.iter().any(|&n| nth_is(n, value, count))
+        };
+        let year_day = day - days_from_civil(year, 1, 1) + 1;
+        let year_length = if is_leap_year(year) { 366 } else { 365 };
+        let (week, weeks) = week_number(day, self.week_start);
+        (self.by_month.is_empty() || self.by_month.contains(&month))
+            && matches(&self.by_week_no, week, weeks)
+            && matches(&self.by_year_day, year_day, year_length)
+            && matches(&self.by_month_day, month_day, days_in_month(year, month))
+            && self.matches_weekday(day, year, month)
+    }
+
+    /// Whether DAY is one of the weekdays of BYDAY, where a number in
+    /// front of a weekday counts them in the month or the year.
+    fn matches_weekday(&self, day: i64, year: i64, month: i64) -> bool {
+        let scope = match self.frequency {
+            Frequency::Yearly if !self.by_week_no.is_empty() => None,
+            Frequency::Yearly if self.by_month.is_empty() => Some((
+                days_from_civil(year, 1, 1),
+                days_from_civil(year, 12, 31),
+            )),
+            Frequency::Yearly | Frequency::Monthly => Some((
+                days_from_civil(year, month, 1),
+                days_from_civil(year, month, days_in_month(year, month)),
+            )),
+            _ => None,
+        };
+        self.by_day.is_empty() || self.by_day.iter().any(|&(n, weekday_)| {
+            if weekday(day) != weekday_ {
+                return false;
+            }
+            match scope {
+                Some((first, _)) if n > 0 => (day - first) / 7 + 1 == n,
+                Some((_, last)) if n < 0 => (last - day) / 7 + 1 == -n,
+                _ => true,
+            }
+        })
+    }
+
+    /// The candidate days of the INDEXth period after the one of START,
+    /// before they are matched against the rule, or None if the period
+    /// starts after LAST_YEAR.  Only for frequencies of a day or more.
+    fn period_days(&self, start: LocalTime, index: i64) -> Option<Vec<i64>> {
+        let (start_year, start_month, _) = civil_from_days(start.day);
+        let step = index * self.interval;
+        let days: Vec<i64> = match self.frequency {
+            Frequency::Yearly => {
+                let year = start_year + step;
+                if year > LAST_YEAR {
+                    return None;
+                }
+                if self.by_week_no.is_empty() {
+                    (days_from_civil(year, 1, 1)..days_from_civil(year + 1, 1, 1)).collect()
+                } else {
+                    let first = first_week_start(year, self.week_start);
+                    let weeks = (first_week_start(year + 1, self.week_start) - first) / 7;
+                    let mut days: Vec<i64> = (1..weeks + 1)
+                        .filter(|&week| self.by_week_no.iter().any(|&n| nth_is(n, week, weeks)))
+                        .flat_map(|week| {
+                            let week_first = first + (week - 1) * 7;
+                            week_first..week_first + 7
+                        })
+                        .collect();
+                    days.sort();
+                    days
+                }
+            }
+            Frequency::Monthly => {
+                let months = start_year * 12 + start_month - 1 + step;
+                let (year, month) = (floor_div(months, 12), floor_mod(months, 12) + 1);
+                if year > LAST_YEAR {
+                    return None;
+                }
+                let first = days_from_civil(year, month, 1);
+                (first..first + days_in_month(year, month)).collect()
+            }
+            Frequency::Weekly => {
+                let first = start.day - floor_mod(weekday(start.day) - self.week_start, 7) + 7 * step;
+                (first..first + 7).collect()
+            }
+            _ => vec![start.day + step],
+        };
+        match days.first() {
+            Some(&first) if civil_from_days(first).0 > LAST_YEAR => None,
+            _ => Some(days),
+        }
+    }
+
+    /// The seconds after midnight from BYHOUR, BYMINUTE and BYSECOND.
+    fn day_times(&self) -> Vec<i64> {
+        let mut times = Vec::new();
+        for &hour in &self.by_hour {
+            for &minute in &self.by_minute {
+                for &second in &self.by_second {
+                    times.push(hour * 3600 + minute * 60 + second);
+                }
+            }
+        }
+        times
+    }
+
+    /// Keep the members of the period PERIOD that BYSETPOS selects.
+    fn select_positions(&self, period: Vec<LocalTime>) -> Vec<LocalTime> {
+        if self.by_set_pos.is_empty() {
+            return period;
+        }
+        let count = period.len() as i64;
+        let mut selected: Vec<LocalTime> = self.by_set_pos
+            .iter()
+            .filter_map(|&n| {
+                let index = if n > 0 { n - 1 } else { count + n };
+                if index >= 0 && index < count {
+                    Some(period[index as usize])
+                } else {
+                    None
+                }
+            })
+            .collect();
+        selected.sort();
+        selected.dedup();
+        selected
+    }
+
+    /// Call EMIT with each occurrence of the rule in order, starting
+    /// from START, until EMIT returns false, the rule's COUNT is
+    /// reached or the occurrences pass LAST_YEAR.  UNTIL is left to
+    /// EMIT, since it depends on time zones.
+    fn expand<F: FnMut(LocalTime) -> bool>(&self, start: LocalTime, mut emit: F) {
+        let rule = self.with_defaults(start);
+        let mut remaining = rule.count.unwrap_or(-1);
+        let mut deliver = |period: Vec<LocalTime>| {
+            for time in rule.select_positions(period) {
+                if time < start {
+                    continue;
+                }
+                if remaining == 0 || !emit(time) {
+                    return false;
+                }
+                remaining -= 1;
+            }
+            remaining != 0
+        };
+        if rule.frequency <= Frequency::Daily {
+            let times = rule.day_times();
+            let mut index = 0;
+            while let Some(days) = rule.period_days(start, index) {
+                let period = days.into_iter()
+                    .filter(|&day| rule.matches_day(day))
+                    .flat_map(|day| times.iter().map(move |&second| LocalTime { day, second }))
+                    .collect();
+                if !deliver(period) {
+                    return;
+                }
+                index += 1;
+            }
+            return;
+        }
+        let unit = match rule.frequency {
+            Frequency::Hourly => 3600,
+            Frequency::Minutely => 60,
+            _ => 1,
+        };
+        let step = unit * rule.interval;
+        // Advance TIME by whole steps to LIMIT or beyond.
+        let skip_to = |time: i64, limit: i64| time + (limit - time + step - 1) / step * step;
+        let mut time = start.day * DAY + start.second - floor_mod(start.second, unit);
+        loop {
+            let day = floor_div(time, DAY);
+            if civil_from_days(day).0 > LAST_YEAR {
+                return;
+            }
+            if !rule.matches_day(day) {
+                time = skip_to(time, (day + 1) * DAY);
+                continue;
+            }
+            let second = time - day * DAY;
+            let (hour, minute) = (second / 3600, second / 60 % 60);
+            if !rule.by_hour.is_empty() && !rule.by_hour.contains(&hour) {
+                time = skip_to(time, day * DAY + (hour + 1) * 3600);
+                continue;
+            }
+            let minute_matches = unit > 60 || rule.by_minute.is_empty()
+                || rule.by_minute.contains(&minute);
+            let second_matches = unit > 1 || rule.by_second.is_empty()
+                || rule.by_second.contains(&(second % 60));
+            if minute_matches && second_matches {
+                let mut period = Vec::new();
+                match rule.frequency {
+                    Frequency::Hourly => {
+                        for &at_minute in &rule.by_minute {
+                            for &at_second in &rule.by_second {
+                                period.push(second + at_minute * 60 + at_second);
+                            }
+                        }
+                    }
+                    Frequency::Minutely => {
+                        for &at_second in &rule.by_second {
+                            period.push(second + at_second);
+                        }
+                    }
+                    _ => period.push(second),
+                }
+                let period = period
+                    .into_iter()
+                    .map(|second| LocalTime { day, second })
+                    .collect();
+                if !deliver(period) {
+                    return;
+                }
+            }
+            time += step;
+        }
+    }
+}
+
+/// The start times of the occurrences of COMPONENT, in seconds since
+/// the epoch, between FROM and TO when they are given, and at most
+/// LIMIT of them.  SECONDS converts a time in a zone to seconds since
+/// the epoch.  Return None if COMPONENT has no recurrence rule or it
+/// can't be understood.
+fn occurrences<F>(
+    component: &Component,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: u16,
+    seconds: &F,
+) -> Option<Vec<i64>>
+where
+    F: Fn(LocalTime, &Zone) -> i64,
+{
+    let rule = parse_rule(component.property(b"RRULE")?.value)?;
+    let dtstart = component.property(b"DTSTART")?;
+    let (start, utc) = parse_date_time(dtstart.value)?;
+    let zone = property_zone(dtstart, utc);
+    let until = rule.until.map(|(until, utc)| {
+        seconds(until, if utc { &Zone::Utc } else { &zone })
+    });
+    let mut excluded = Vec::new();
+    let mut added = Vec::new();
+    for property in &component.properties {
+        if same_name(property.name, b"EXDATE") {
+            excluded.extend(property_times(property, seconds)?);
+        } else if same_name(property.name, b"RDATE") {
+            added.extend(property_times(property, seconds)?);
+        }
+    }
+    let in_window = |time: i64| {
+        from.map_or(true, |from| time >= from) && to.map_or(true, |to| time <= to)
+            && !excluded.contains(&time)
+    };
+    let limit = limit as usize;
+    let mut times = Vec::new();
+    rule.expand(start, |time| {
+        let time = seconds(time, &zone);
+        if until.map_or(false, |until| time > until) || to.map_or(false, |to| time > to) {
+            return false;
+        }
+        if in_window(time) {
+            times.push(time);
+        }
+        times.len() < limit
+    });
+    times.extend(added.into_iter().filter(|&time| in_window(time)));
+    times.sort();
+    times.dedup();
+    times.truncate(limit);
+    Some(times)
+}
+
+/// The seconds since the epoch of TIME in ZONE.  Named zones and local
+/// time follow the time zone rules of the system.
+fn zone_seconds(time: LocalTime, zone: &Zone) -> i64 {
+    let utc = time.day * DAY + time.second;
+    let zone = match *zone {
+        Zone::Utc => return utc,
+        Zone::Floating => LispObject::constant_nil(),
+        Zone::Named(ref name) => lisp_string(name),
+    };
+    let (year, month, day) = civil_from_days(time.day);
+    let number = |n: i64| LispObject::from_fixnum(n as EmacsInt);
+    let encoded = call!(
+        intern("encode-time"),
+        number(time.second % 60),
+        number(time.second / 60 % 60),
+        number(time.second / 3600),
+        number(day),
+        number(month),
+        number(year),
+        zone
+    );
+    call!(intern("float-time"), encoded).any_to_float_or_error() as i64
+}
+
+fn make_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    LispObject::from(unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            -1,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    })
+}
+
+fn make_name(name: &[u8]) -> LispObject {
+    intern(&String::from_utf8_lossy(name))
+}
+
+/// Return a time value for SECONDS since the epoch.
+fn lisp_time(seconds: i64) -> LispObject {
+    list!(
+        LispObject::from_fixnum((seconds >> 16) as EmacsInt),
+        LispObject::from_fixnum((seconds & 0xffff) as EmacsInt)
+    )
+}
+
+fn list_from<I: DoubleEndedIterator<Item = LispObject>>(items: I) -> LispObject {
+    items.rev().fold(LispObject::constant_nil(), |list, item| {
+        LispObject::cons(item, list)
+    })
+}
+
+/// The parameters PARAMS as a list (NAME VALUE NAME VALUE ...).
+fn params_to_lisp(params: &[(&[u8], Vec<u8>)], multibyte: bool) -> LispObject {
+    let mut list = LispObject::constant_nil();
+    for &(name, ref value) in params.iter().rev() {
+        list = LispObject::cons(make_string(value, multibyte), list);
+        list = LispObject::cons(make_name(name), list);
+    }
+    list
+}
+
+/// Options for the expansion of recurrence rules.
+struct Expansion {
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: u16,
+}
+
+fn component_to_lisp(component: &Component, expansion: &Expansion, multibyte: bool) -> LispObject {
+    let properties = list_from(component.properties.iter().map(|property| {
+        list!(
+            make_name(property.name),
+            params_to_lisp(&property.params, multibyte),
+            make_string(property.value, multibyte)
+        )
+    }));
+    let children = list_from(
+        component
+            .children
+            .iter()
+            .map(|child| component_to_lisp(child, expansion, multibyte)),
+    );
+    let mut fields = vec![
+        make_name(component.name),
+        params_to_lisp(&component.params, multibyte),
+        properties,
+        children,
+    ];
+    let recurring = same_name(component.name, b"VEVENT")
+        || same_name(component.name, b"VTODO");
+    if recurring {
+        let times = occurrences(
+            component,
+            expansion.from,
+            expansion.to,
+            expansion.limit,
+            &zone_seconds,
+        );
+        if let Some(times) = times {
+            fields.push(list_from(times.into_iter().map(lisp_time)));
+        }
+    }
+    list_from(fields.into_iter())
+}
+
+/// Parse the iCalendar data between START and END in the current buffer.
+/// Return a list of its top-level components, each of the form
+/// (NAME PARAMS PROPERTIES CHILDREN) as `icalendar--read-element'
+/// returns them: NAME is a symbol such as `VCALENDAR', PROPERTIES is a
+/// list of (NAME PARAMS VALUE) for the content lines, with PARAMS a list
+/// (PARAM-NAME PARAM-VALUE ...), and CHILDREN the nested components.
+///
+/// VEVENT and VTODO components with a recurrence rule have a fifth
+/// element, the list of the start times of their occurrences, taking
+/// RDATE and EXDATE into account.  Only occurrences between the time
+/// values FROM and TO are listed, if those are non-nil, and no more
+/// than LIMIT of them, which defaults to 500.  The fifth element is
+/// missing if the rule can't be understood.  Times with a TZID are in
+/// the time zone of that name as the system knows it, and times without
+/// a zone are in local time.
+///
+/// Folded lines and any kind of line ends are handled here.  Lines that
+/// aren't content lines are ignored.
+#[lisp_fn(min = "2")]
+pub fn icalendar_parse_region_native(
+    start: LispObject,
+    end: LispObject,
+    from: LispObject,
+    to: LispObject,
+    limit: LispObject,
+) -> LispObject {
+    let seconds = |time: LispObject| {
+        if time.is_nil() {
+            None
+        } else {
+            Some(call!(intern("float-time"), time).any_to_float_or_error() as i64)
+        }
+    };
+    let expansion = Expansion {
+        from: seconds(from),
+        to: seconds(to),
+        limit: if limit.is_nil() {
+            DEFAULT_LIMIT
+        } else {
+            limit.as_natnum_or_error().min(EmacsInt::from(u16::max_value())) as u16
+        },
+    };
+    let text = call!(intern("buffer-substring-no-properties"), start, end);
+    let text = text.as_string_or_error();
+    let multibyte = text.is_multibyte();
+    let lines = unfold(text.as_slice());
+    list_from(
+        parse_components(&lines)
+            .iter()
+            .map(|component| component_to_lisp(component, &expansion, multibyte)),
+    )
+}
+
+#[test]
+fn test_parse_components() {
+    let text = b"BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\n\
+DTSTART;TZID=\"Europe/Berlin\":20200106T090000\r\nSUMMARY:Long \r\n sum\r\n\tmary\r\n\
+ATTENDEE;ROLE=REQ-PARTICIPANT;MEMBER=\"a:b\",c:mailto:x@y\r\n\
+junk line\r\nEND:VEVENT\r\nBEGIN:VTODO\nSUMMARY:open\n";
+    let lines = unfold(text);
+    let components = parse_components(&lines);
+    assert_eq!(components.len(), 1);
+    let calendar = &components[0];
+    assert_eq!(calendar.name, b"VCALENDAR");
+    assert_eq!(calendar.properties[0].value, b"2.0");
+    assert_eq!(calendar.children.len(), 2);
+    let event = &calendar.children[0];
+    assert_eq!(event.property(b"summary").unwrap().value, b"Long summary");
+    let dtstart = event.property(b"DTSTART").unwrap();
+    assert_eq!(dtstart.param(b"tzid"), Some(&b"Europe/Berlin"[..]));
+    let attendee = event.property(b"ATTENDEE").unwrap();
+    assert_eq!(attendee.param(b"MEMBER"), Some(&b"a:b,c"[..]));
+    assert_eq!(attendee.value, b"mailto:x@y");
+    assert_eq!(event.properties.len(), 3);
+    assert_eq!(calendar.children[1].name, b"VTODO");
+}
+
+#[cfg(test)]
+fn utc_seconds(time: LocalTime, _: &Zone) -> i64 {
+    time.day * DAY + time.second
+}
+
+#[test]
+fn test_dates() {
+    assert_eq!(days_from_civil(1970, 1, 1), 0);
+    assert_eq!(days_from_civil(2020, 1, 6), 18_267);
+    assert_eq!(civil_from_days(18_267), (2020, 1, 6));
+    assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    assert_eq!(weekday(18_267), 0);
+    // 2020 has 53 weeks, and December 31, 2019 is in its first.
+    assert_eq!(week_number(days_from_civil(2019, 12, 31), 0), (1, 53));
+    assert_eq!(week_number(days_from_civil(2021, 1, 3), 0), (53, 53));
+}
+
+#[test]
+fn test_occurrences() {
+    let text = b"BEGIN:VEVENT\nDTSTART:20200106T090000Z\n\
+RRULE:FREQ=WEEKLY;COUNT=4\nEXDATE:20200113T090000Z\nEND:VEVENT\n";
+    let lines = unfold(text);
+    let components = parse_components(&lines);
+    let monday = 1_578_301_200;
+    let week = 7 * 24 * 3600;
+    assert_eq!(
+        occurrences(&components[0], None, None, 10, &utc_seconds),
+        Some(vec![monday, monday + 2 * week, monday + 3 * week])
+    );
+    assert_eq!(
+        occurrences(&components[0], Some(monday + 1), None, 1, &utc_seconds),
+        Some(vec![monday + 2 * week])
+    );
+}
+
+#[test]
+fn test_rules() {
+    let expand = |rule: &str| {
+        let text = format!(
+            "BEGIN:VEVENT\nDTSTART:20200101T080000Z\nRRULE:{}\nEND:VEVENT\n",
+            rule
+        );
+        let lines = unfold(text.as_bytes());
+        occurrences(&parse_components(&lines)[0], None, None, 10, &utc_seconds)
+    };
+    // The last Friday of the month.
+    assert_eq!(
+        expand("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3"),
+        Some(vec![1_580_457_600, 1_582_876_800, 1_585_296_000])
+    );
+    // The last workday of the month.
+    assert_eq!(
+        expand("FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1;COUNT=3"),
+        Some(vec![1_580_457_600, 1_582_876_800, 1_585_641_600])
+    );
+    // Monday of week 20.
+    assert_eq!(
+        expand("FREQ=YEARLY;BYWEEKNO=20;BYDAY=MO;COUNT=2"),
+        Some(vec![1_589_184_000, 1_621_238_400])
+    );
+    // Every five hours, on Saturdays only.
+    assert_eq!(
+        expand("FREQ=HOURLY;INTERVAL=5;BYDAY=SA;COUNT=3"),
+        Some(vec![1_578_099_600, 1_578_117_600, 1_578_135_600])
+    );
+    assert_eq!(expand("FREQ=FORTNIGHTLY"), None);
+    assert_eq!(expand("FREQ=DAILY;BYMONTHDAY=0"), None);
+}
+
+include!(concat!(env!("OUT_DIR"), "/ical_exports.rs"));
@@ -0,0 +1,1449 @@
+//! Image operations on decoded bitmaps.
+//!
+//! These work on image specs with `:file' or `:data', decode them with
+//! the image crate and return a new spec for a PNG with the result, so
+//! they need neither a window system nor external programs.  The same
+//! decoding shows images on text terminals that speak the sixel or the
+//! kitty graphics protocol.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use libc::{self, c_char, c_int, c_void, ptrdiff_t};
+
+use base64_crate;
+use exif;
+use image;
+use image::{ColorType, DynamicImage, FilterType, GenericImage};
+use image::png::PNGEncoder;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{fget_output_method, make_unibyte_string, EmacsInt, Lisp_Frame, Qerror};
+
+use frames::{frame_live_or_selected, frame_or_selected, output_termcap, selected_frame};
+use imap::unibyte_string;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::plist_get;
+use strings::lisp_string;
+use symbols::symbol_value;
+use util::{expand_file_name_to_path, report_io_error};
+
+/// Return the bytes of the image described by SPEC.
+fn image_bytes(spec: LispObject) -> Vec<u8> {
+    if !spec.as_cons().map_or(false, |cons| cons.car().eq(intern("image"))) {
+        wrong_type!(intern("imagep"), spec);
+    }
+    let props = spec.as_cons().unwrap().cdr();
+    let data = plist_get(props, intern(":data"));
+    if data.is_not_nil() {
+        return data.as_string_or_error().as_slice().to_vec();
+    }
+    let file = plist_get(props, intern(":file"));
+    if file.is_nil() {
+        error!("Image spec has neither :file nor :data");
+    }
+    let path = expand_file_name_to_path(file);
+    let mut bytes = Vec::new();
+    if let Err(err) = File::open(&path).and_then(|mut f| f.read_to_end(&mut bytes)) {
+        report_io_error(b"Opening image file\0", file, &err);
+    }
+    bytes
+}
+
+fn decode(spec: LispObject) -> DynamicImage {
+    match image::load_from_memory(&image_bytes(spec)) {
+        Ok(image) => image,
+        Err(err) => error!("Cannot decode image: {}", err),
+    }
+}
+
+/// Return IMAGE encoded as PNG.
+fn png_bytes(image: &DynamicImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let mut png = Vec::new();
+    if let Err(err) = PNGEncoder::new(&mut png).encode(
+        &image.to_rgba().into_raw(),
+        width,
+        height,
+        ColorType::RGBA(8),
+    ) {
+        error!("Cannot encode image: {}", err);
+    }
+    png
+}
+
+/// Return an image spec for IMAGE encoded as PNG.
+fn png_spec(image: &DynamicImage) -> LispObject {
+    let png = png_bytes(image);
+    let data = LispObject::from(unsafe {
+        make_unibyte_string(png.as_ptr() as *const c_char, png.len() as ptrdiff_t)
+    });
+    list!(
+        intern("image"),
+        intern(":type"),
+        intern("png"),
+        intern(":data"),
+        data
+    )
+}
+
+/// Return SIZE as a pixel count, or None if SIZE is nil.
+fn dimension(size: LispObject) -> Option<u32> {
+    if size.is_nil() {
+        return None;
+    }
+    let pixels = size.as_natnum_or_error();
+    if pixels == 0 || pixels > EmacsInt::from(u32::max_value()) {
+        args_out_of_range!(size, LispObject::from_natnum(EmacsInt::from(u32::max_value())));
+    }
+    Some(pixels as u32)
+}
+
+/// Return the size of an image of WIDTH by HEIGHT pixels scaled to fit
+/// into MAX_WIDTH by MAX_HEIGHT, keeping its aspect ratio.
+fn fit(width: u32, height: u32, max_width: Option<u32>, max_height: Option<u32>) -> (u32, u32) {
+    let (w, h) = (u64::from(width), u64::from(height));
+    let (new_w, new_h) = match (max_width, max_height) {
+        (None, None) => (w, h),
+        (Some(mw), None) => (u64::from(mw), h * u64::from(mw) / w),
+        (None, Some(mh)) => (w * u64::from(mh) / h, u64::from(mh)),
+        (Some(mw), Some(mh)) => {
+            let (mw, mh) = (u64::from(mw), u64::from(mh));
+            if w * mh <= h * mw {
+                (w * mh / h, mh)
+            } else {
+                (mw, h * mw / w)
+            }
+        }
+    };
+    (new_w.max(1) as u32, new_h.max(1) as u32)
+}
+
+/// Return a PNG image spec for the image SPEC scaled to W by H pixels.
+/// SPEC is an image spec with `:file' or `:data'.  The image keeps its
+/// aspect ratio, so it is made as large as fits into W by H; if W or H
+/// is nil, only the other dimension limits the size.
+#[lisp_fn]
+pub fn image_resize_native(spec: LispObject, w: LispObject, h: LispObject) -> LispObject {
+    let image = decode(spec);
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = fit(width, height, dimension(w), dimension(h));
+    png_spec(&image.resize_exact(new_width, new_height, FilterType::Triangle))
+}
+
+/// Return a PNG image spec for the W by H pixel rectangle of the image
+/// SPEC whose top left corner is at X, Y.
+/// The rectangle is clipped to the image.
+#[lisp_fn]
+pub fn image_crop_native(
+    spec: LispObject,
+    x: LispObject,
+    y: LispObject,
+    w: LispObject,
+    h: LispObject,
+) -> LispObject {
+    let mut image = decode(spec);
+    let (width, height) = image.dimensions();
+    if x.as_natnum_or_error() >= EmacsInt::from(width)
+        || y.as_natnum_or_error() >= EmacsInt::from(height)
+    {
+        args_out_of_range!(x, y);
+    }
+    let (x, y) = (x.as_natnum_or_error() as u32, y.as_natnum_or_error() as u32);
+    let w = dimension(w).unwrap_or(width).min(width - x);
+    let h = dimension(h).unwrap_or(height).min(height - y);
+    png_spec(&image.crop(x, y, w, h))
+}
+
+/// Return a PNG image spec for the image SPEC rotated clockwise by
+/// ANGLE degrees, which must be a multiple of 90.
+#[lisp_fn]
+pub fn image_rotate_native(spec: LispObject, angle: LispObject) -> LispObject {
+    let degrees = angle.as_fixnum_or_error();
+    if degrees % 90 != 0 {
+        error!("Rotation angle must be a multiple of 90 degrees");
+    }
+    let image = decode(spec);
+    png_spec(&match (degrees % 360 + 360) % 360 {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => image,
+    })
+}
+
+/// What the header of an image file says about it.
+#[derive(Debug, PartialEq)]
+struct Header {
+    format: &'static str,
+    width: u32,
+    height: u32,
+    /// Horizontal and vertical resolution in dots per inch.
+    dpi: Option<(f64, f64)>,
+}
+
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, &b| n << 8 | b as usize)
+}
+
+fn png_header(data: &[u8]) -> Result<Header, ()> {
+    let mut header = Header {
+        format: "png",
+        width: be(bytes(data, 16, 20)?) as u32,
+        height: be(bytes(data, 20, 24)?) as u32,
+        dpi: None,
+    };
+    let mut pos = 8;
+    while let Ok(chunk) = bytes(data, pos, pos + 8) {
+        let length = be(&chunk[..4]);
+        match &chunk[4..] {
+            b"pHYs" => {
+                let phys = bytes(data, pos + 8, pos + 17)?;
+                // Unit 1 is the meter.
+                if phys[8] == 1 {
+                    let dpi = |ppm: &[u8]| be(ppm) as f64 * 0.0254;
+                    header.dpi = Some((dpi(&phys[..4]), dpi(&phys[4..8])));
+                }
+            }
+            b"IDAT" | b"IEND" => break,
+            _ => {}
+        }
+        pos += 12 + length;
+    }
+    Ok(header)
+}
+
+fn jpeg_header(data: &[u8]) -> Result<Header, ()> {
+    let mut header = Header {
+        format: "jpeg",
+        width: 0,
+        height: 0,
+        dpi: None,
+    };
+    let mut pos = 2;
+    loop {
+        if byte(data, pos)? != 0xFF {
+            return Err(());
+        }
+        let marker = byte(data, pos + 1)?;
+        let length = be(bytes(data, pos + 2, pos + 4)?);
+        let payload = pos + 4;
+        match marker {
+            0xE0 if bytes(data, payload, payload + 5)? == b"JFIF\0" => {
+                let jfif = bytes(data, payload + 7, payload + 12)?;
+                let scale = match jfif[0] {
+                    1 => 1.0,
+                    2 => 2.54,
+                    _ => 0.0,
+                };
+                if scale > 0.0 {
+                    header.dpi = Some((
+                        be(&jfif[1..3]) as f64 * scale,
+                        be(&jfif[3..5]) as f64 * scale,
+                    ));
+                }
+            }
+            0xC0...0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                header.height = be(bytes(data, payload + 1, payload + 3)?) as u32;
+                header.width = be(bytes(data, payload + 3, payload + 5)?) as u32;
+                return Ok(header);
+            }
+            0xD9 | 0xDA => return Err(()),
+            _ => {}
+        }
+        pos += 2 + length;
+    }
+}
+
+fn webp_header(data: &[u8]) -> Result<Header, ()> {
+    let payload = 20;
+    let (width, height) = match bytes(data, 12, 16)? {
+        b"VP8X" => (
+            le(bytes(data, payload + 4, payload + 7)?) + 1,
+            le(bytes(data, payload + 7, payload + 10)?) + 1,
+        ),
+        b"VP8 " => (
+            le(bytes(data, payload + 6, payload + 8)?) & 0x3FFF,
+            le(bytes(data, payload + 8, payload + 10)?) & 0x3FFF,
+        ),
+        b"VP8L" => {
+            let bits = le(bytes(data, payload + 1, payload + 5)?);
+            ((bits & 0x3FFF) + 1, (bits >> 14 & 0x3FFF) + 1)
+        }
+        _ => return Err(()),
+    };
+    Ok(Header {
+        format: "webp",
+        width: width as u32,
+        height: height as u32,
+        dpi: None,
+    })
+}
+
+/// Read the header of the image in DATA, which starts with at least the
+/// first few kilobytes of the file.
+fn read_header(data: &[u8]) -> Result<Header, ()> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_header(data)
+    } else if data.starts_with(b"\xFF\xD8") {
+        jpeg_header(data)
+    } else if data.starts_with(b"GIF8") {
+        Ok(Header {
+            format: "gif",
+            width: le(bytes(data, 6, 8)?) as u32,
+            height: le(bytes(data, 8, 10)?) as u32,
+            dpi: None,
+        })
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        webp_header(data)
+    } else {
+        Err(())
+    }
+}
+
+/// Return the value of FIELD as a string.
+fn exif_value(field: &exif::Field) -> String {
+    match field.value {
+        exif::Value::Ascii(ref strings) => strings
+            .iter()
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect::<Vec<_>>()
+            .join(", "),
+        ref value => format!("{}", value.display_as(field.tag)),
+    }
+}
+
+fn rational(field: Option<&exif::Field>) -> Option<f64> {
+    match field.map(|field| &field.value) {
+        Some(&exif::Value::Rational(ref values)) if !values.is_empty() => {
+            Some(values[0].to_f64())
+        }
+        _ => None,
+    }
+}
+
+/// Return a plist of what FILE, an image file, says about itself,
+/// reading its header and its EXIF data but not decoding the image.
+/// The properties are:
+///
+/// `:format' -- the file format, `png', `jpeg', `gif', `webp' or `tiff'.
+/// `:width', `:height' -- the size of the image in pixels.
+/// `:orientation' -- the EXIF orientation, 1 to 8; an image with
+///   orientation 6 must be rotated by 90 degrees clockwise to be upright,
+///   3 by 180 degrees and 8 by 270 degrees, and 2, 4, 5 and 7 are
+///   mirrored.
+/// `:dpi' -- (X . Y), the resolution in dots per inch.
+/// `:exif' -- an alist of (TAG . VALUE) for all EXIF fields of the
+///   main image, where TAG is the name of the tag, such as
+///   "DateTimeOriginal", and VALUE is a string.
+///
+/// Properties that the file doesn't provide are left out, except for
+/// `:exif'.  This function is called `image-file-metadata' because
+/// `image-metadata' returns the metadata of a loaded image.
+#[lisp_fn]
+pub fn image_file_metadata(file: LispObject) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let mut head = Vec::new();
+    if let Err(err) = File::open(&path).and_then(|f| f.take(65536).read_to_end(&mut head)) {
+        report_io_error(b"Opening image file\0", file, &err);
+    }
+    let exif = File::open(&path)
+        .ok()
+        .and_then(|f| exif::Reader::new(&mut BufReader::new(f)).ok());
+    let mut header = read_header(&head).ok();
+    if header.is_none() && (head.starts_with(b"II*\0") || head.starts_with(b"MM\0*")) {
+        let dimension = |tag| {
+            exif.as_ref()
+                .and_then(|exif| exif.get_field(tag, false))
+                .and_then(|field| field.value.get_uint(0))
+        };
+        header = Some(Header {
+            format: "tiff",
+            width: dimension(exif::Tag::ImageWidth).unwrap_or(0),
+            height: dimension(exif::Tag::ImageLength).unwrap_or(0),
+            dpi: None,
+        });
+    }
+
+    let mut plist = LispObject::constant_nil();
+    let mut fields = LispObject::constant_nil();
+    if let Some(ref exif) = exif {
+        for field in exif.fields().iter().rev().filter(|field| !field.thumbnail) {
+            let name = lisp_string(&format!("{}", field.tag));
+            let value = lisp_string(&exif_value(field));
+            fields = LispObject::cons(LispObject::cons(name, value), fields);
+        }
+        let field = |tag| exif.get_field(tag, false);
+        let x = rational(field(exif::Tag::XResolution));
+        let y = rational(field(exif::Tag::YResolution));
+        let dpi = match (x, y) {
+            (Some(x), Some(y)) => match field(exif::Tag::ResolutionUnit)
+                .and_then(|unit| unit.value.get_uint(0))
+                .unwrap_or(2)
+            {
+                2 => Some((x, y)),
+                3 => Some((x * 2.54, y * 2.54)),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(ref mut header) = header {
+            if header.dpi.is_none() {
+                header.dpi = dpi;
+            }
+        }
+        let orientation = field(exif::Tag::Orientation).and_then(|f| f.value.get_uint(0));
+        if let Some(orientation) = orientation {
+            plist = list!(
+                intern(":orientation"),
+                LispObject::from_natnum(EmacsInt::from(orientation))
+            );
+        }
+    }
+    plist = LispObject::cons(intern(":exif"), LispObject::cons(fields, plist));
+    if let Some(header) = header {
+        if let Some((x, y)) = header.dpi {
+            let dpi = LispObject::cons(LispObject::from_float(x), LispObject::from_float(y));
+            plist = LispObject::cons(intern(":dpi"), LispObject::cons(dpi, plist));
+        }
+        if header.width > 0 {
+            let height = LispObject::from_natnum(EmacsInt::from(header.height));
+            plist = LispObject::cons(intern(":height"), LispObject::cons(height, plist));
+            let width = LispObject::from_natnum(EmacsInt::from(header.width));
+            plist = LispObject::cons(intern(":width"), LispObject::cons(width, plist));
+        }
+        let format = intern(header.format);
+        plist = LispObject::cons(intern(":format"), LispObject::cons(format, plist));
+    }
+    plist
+}
+
+/// The frame delays of an animated GIF or WebP image, found by walking
+/// the blocks of the file only as far as the animation has got.  The
+/// pixels are decoded by the display code when a frame is shown.
+struct Frames {
+    data: Vec<u8>,
+    webp: bool,
+    /// The offset of the first block not looked at yet.
+    next: usize,
+    /// The delay of each frame found so far, in seconds.
+    delays: Vec<f64>,
+    /// The delay from the last GIF graphic control extension, for the
+    /// image that follows it.
+    pending: Option<f64>,
+    complete: bool,
+    default_delay: f64,
+    minimum_delay: f64,
+}
+
+fn le(bytes: &[u8]) -> usize {
+    bytes.iter().rev().fold(0, |n, &b| n << 8 | b as usize)
+}
+
+fn byte(data: &[u8], pos: usize) -> Result<u8, ()> {
+    data.get(pos).cloned().ok_or(())
+}
+
+fn bytes(data: &[u8], start: usize, end: usize) -> Result<&[u8], ()> {
+    data.get(start..end).ok_or(())
+}
+
+/// Return the offset after the GIF data sub-blocks starting at POS.
+fn skip_sub_blocks(data: &[u8], mut pos: usize) -> Result<usize, ()> {
+    loop {
+        let size = byte(data, pos)? as usize;
+        pos += 1 + size;
+        if size == 0 {
+            return if pos <= data.len() { Ok(pos) } else { Err(()) };
+        }
+    }
+}
+
+/// Return the size of the color table announced by the packed fields
+/// byte PACKED.
+fn color_table_size(packed: u8) -> usize {
+    if packed & 0x80 != 0 {
+        3 << ((packed & 7) + 1)
+    } else {
+        0
+    }
+}
+
+impl Frames {
+    fn new(data: Vec<u8>, default_delay: f64, minimum_delay: f64) -> Option<Frames> {
+        let (webp, next) = if data.len() > 10
+            && (data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a"))
+        {
+            (false, 13 + color_table_size(data[10]))
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            (true, 12)
+        } else {
+            return None;
+        };
+        Some(Frames {
+            data: data,
+            webp: webp,
+            next: next,
+            delays: Vec::new(),
+            pending: None,
+            complete: false,
+            default_delay: default_delay,
+            minimum_delay: minimum_delay,
+        })
+    }
+
+    fn push(&mut self, delay: Option<f64>) {
+        let delay = match delay {
+            Some(delay) if delay > 0.0 => delay,
+            _ => self.default_delay,
+        };
+        self.delays.push(delay.max(self.minimum_delay));
+    }
+
+    /// Look at the next block, failing at the end of the file or if it
+    /// is malformed.
+    fn scan_block(&mut self) -> Result<(), ()> {
+        let pos = self.next;
+        let frame = if self.webp {
+            let size = le(bytes(&self.data, pos + 4, pos + 8)?);
+            let frame = bytes(&self.data, pos, pos + 4)? == b"ANMF";
+            if frame {
+                let duration = le(bytes(&self.data, pos + 20, pos + 23)?);
+                self.pending = Some(duration as f64 / 1000.0);
+            }
+            self.next = pos + 8 + size + size % 2;
+            frame
+        } else {
+            match byte(&self.data, pos)? {
+                0x21 => {
+                    if byte(&self.data, pos + 1)? == 0xF9 {
+                        let delay = le(bytes(&self.data, pos + 4, pos + 6)?);
+                        self.pending = Some(delay as f64 / 100.0);
+                    }
+                    self.next = skip_sub_blocks(&self.data, pos + 2)?;
+                    false
+                }
+                0x2C => {
+                    let packed = byte(&self.data, pos + 9)?;
+                    let lzw = pos + 10 + color_table_size(packed);
+                    self.next = skip_sub_blocks(&self.data, lzw + 1)?;
+                    true
+                }
+                _ => return Err(()),
+            }
+        };
+        if frame {
+            let delay = self.pending.take();
+            self.push(delay);
+        }
+        Ok(())
+    }
+
+    /// Make sure that the delay of frame N is known, if there is such a
+    /// frame.  Return whether there is.
+    fn have(&mut self, n: usize) -> bool {
+        while self.delays.len() <= n && !self.complete {
+            if self.scan_block().is_err() {
+                self.complete = true;
+            }
+        }
+        n < self.delays.len()
+    }
+
+    fn scan_all(&mut self) {
+        loop {
+            let n = self.delays.len();
+            if !self.have(n) {
+                break;
+            }
+        }
+    }
+}
+
+enum Limit {
+    /// Stop after the last frame.
+    Once,
+    Forever,
+    Seconds(f64),
+}
+
+/// The state of an animated image.  The frame to show is computed from
+/// the time that has passed, so that a late timer skips frames instead
+/// of slowing the animation down.
+struct Animation {
+    frames: Frames,
+    reverse: bool,
+    speed: f64,
+    limit: Limit,
+    /// The time into the animation, in seconds of the image's own
+    /// timeline, counted from the start of its first frame (the last
+    /// frame if REVERSE).
+    position: f64,
+    /// Seconds animated so far.
+    elapsed: f64,
+    last: Instant,
+    shown: Option<usize>,
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+impl Animation {
+    fn frame_index(&self, n: usize) -> usize {
+        if self.reverse {
+            self.frames.delays.len() - 1 - n
+        } else {
+            n
+        }
+    }
+
+    /// Advance the animation to NOW.  Return the frame to show and the
+    /// seconds until the next frame is due, or None once the animation
+    /// is over.
+    fn advance(&mut self, now: Instant) -> Option<(usize, f64)> {
+        if now < self.last {
+            // Not started yet.
+            let wait = seconds(self.last - now);
+            return Some((self.shown.unwrap_or(0), wait));
+        }
+        let delta = seconds(now - self.last);
+        self.last = now;
+        self.elapsed += delta;
+        self.position += delta * self.speed;
+        if let Limit::Seconds(limit) = self.limit {
+            if self.elapsed >= limit {
+                return None;
+            }
+        }
+        let mut start = 0.0;
+        let mut n = 0;
+        loop {
+            if !self.frames.have(n) {
+                if n == 0 {
+                    return None;
+                }
+                match self.limit {
+                    Limit::Once => return None,
+                    _ if start <= 0.0 => return None,
+                    _ => {
+                        // Start over; skip whole cycles at once.
+                        let cycle = start;
+                        self.position %= cycle;
+                        start = 0.0;
+                        n = 0;
+                        continue;
+                    }
+                }
+            }
+            let end = start + self.frames.delays[self.frame_index(n)];
+            if self.position < end {
+                let index = self.frame_index(n);
+                return Some((index, (end - self.position) / self.speed));
+            }
+            start = end;
+            n += 1;
+        }
+    }
+}
+
+lazy_static! {
+    static ref ANIMATIONS: Mutex<(EmacsInt, HashMap<EmacsInt, Animation>)> =
+        Mutex::new((0, HashMap::new()));
+}
+
+fn number(object: LispObject) -> f64 {
+    match object.as_float() {
+        Some(n) => n,
+        None => match object.as_fixnum() {
+            Some(n) => n as f64,
+            None => wrong_type!(intern("numberp"), object),
+        },
+    }
+}
+
+/// Start animating the GIF or WebP image IMAGE, beginning at frame INDEX.
+/// LIMIT is as for `image-animate'; the `:speed' property of IMAGE
+/// scales the speed, and a negative speed plays the animation in
+/// reverse.  Return a handle for `image-animation-tick' and
+/// `image-animation-stop', or nil if IMAGE isn't an animation in one of
+/// those formats.
+///
+/// The first frame is due after 0.2 seconds.  The delays of the frames
+/// are read from the image as it plays, and frames are skipped when the
+/// timer runs late, so that the animation keeps to its schedule.
+#[lisp_fn(min = "1")]
+pub fn image_animation_start(
+    image: LispObject,
+    index: LispObject,
+    limit: LispObject,
+) -> LispObject {
+    let bytes = image_bytes(image);
+    let index = if index.is_nil() {
+        0
+    } else {
+        index.as_natnum_or_error() as usize
+    };
+    let speed = plist_get(image.as_cons().unwrap().cdr(), intern(":speed"));
+    let speed = if speed.is_nil() { 1.0 } else { number(speed) };
+    let limit = if limit.is_nil() {
+        Limit::Once
+    } else if limit.is_t() {
+        Limit::Forever
+    } else {
+        Limit::Seconds(number(limit))
+    };
+    let default_delay = number(symbol_value(intern("image-default-frame-delay")));
+    let minimum_delay = number(symbol_value(intern("image-minimum-frame-delay")));
+    let mut frames = match Frames::new(bytes, default_delay, minimum_delay) {
+        Some(frames) => frames,
+        None => return LispObject::constant_nil(),
+    };
+    if !frames.have(1) {
+        return LispObject::constant_nil();
+    }
+    let reverse = speed < 0.0;
+    if reverse {
+        frames.scan_all();
+    }
+    let index = if frames.have(index) { index } else { 0 };
+    let position = if reverse {
+        frames.delays[index + 1..].iter().sum()
+    } else {
+        frames.delays[..index].iter().sum()
+    };
+
+    let mut animations = ANIMATIONS.lock().unwrap();
+    animations.0 += 1;
+    let handle = animations.0;
+    animations.1.insert(
+        handle,
+        Animation {
+            frames: frames,
+            reverse: reverse,
+            speed: speed.abs().max(1e-3),
+            limit: limit,
+            position: position,
+            elapsed: 0.0,
+            last: Instant::now() + Duration::from_millis(200),
+            shown: None,
+        },
+    );
+    LispObject::from_natnum(handle)
+}
+
+/// Stop the animation ANIMATION started by `image-animation-start'.
+#[lisp_fn]
+pub fn image_animation_stop(animation: LispObject) -> LispObject {
+    let handle = animation.as_fixnum_or_error();
+    ANIMATIONS.lock().unwrap().1.remove(&handle);
+    LispObject::constant_nil()
+}
+
+/// Advance all animations started by `image-animation-start'.
+/// Return (DELAY . FLIPS).  FLIPS is a list of (ANIMATION . INDEX) for
+/// each animation that should now show frame INDEX of its image, with
+/// INDEX nil for animations that are over.  DELAY is the number of
+/// seconds until the next frame is due, or nil if no animation is left.
+#[lisp_fn]
+pub fn image_animation_tick() -> LispObject {
+    let now = Instant::now();
+    let mut flips = Vec::new();
+    let mut delay: Option<f64> = None;
+    {
+        let mut animations = ANIMATIONS.lock().unwrap();
+        let mut finished = Vec::new();
+        for (&handle, animation) in animations.1.iter_mut() {
+            match animation.advance(now) {
+                Some((index, wait)) => {
+                    if animation.last <= now && animation.shown != Some(index) {
+                        animation.shown = Some(index);
+                        flips.push((handle, Some(index)));
+                    }
+                    delay = Some(delay.map_or(wait, |delay| delay.min(wait)));
+                }
+                None => {
+                    finished.push(handle);
+                    flips.push((handle, None));
+                }
+            }
+        }
+        for handle in finished {
+            animations.1.remove(&handle);
+        }
+    }
+
+    let minimum = number(symbol_value(intern("image-minimum-frame-delay")));
+    let mut list = LispObject::constant_nil();
+    for &(handle, index) in flips.iter().rev() {
+        let index = index.map_or(LispObject::constant_nil(), |index| {
+            LispObject::from_natnum(index as EmacsInt)
+        });
+        list = LispObject::cons(LispObject::cons(LispObject::from_natnum(handle), index), list);
+    }
+    let delay = delay.map_or(LispObject::constant_nil(), |delay| {
+        LispObject::from_float(delay.max(minimum))
+    });
+    LispObject::cons(delay, list)
+}
+
+/// The graphics protocols of text terminals that images can be shown
+/// with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Protocol {
+    Sixel,
+    Kitty,
+}
+
+fn tty_protocol(protocol: LispObject) -> Protocol {
+    if protocol.eq(intern("sixel")) {
+        Protocol::Sixel
+    } else if protocol.eq(intern("kitty")) {
+        Protocol::Kitty
+    } else {
+        xsignal!(Qerror, lisp_string("Unknown image protocol"), protocol)
+    }
+}
+
+/// The size of a character cell in pixels if the caller doesn't know it.
+const DEFAULT_CELL_SIZE: (u32, u32) = (10, 20);
+
+/// Return PAIR, a cons of two integers, as a tuple.  If POSITIVE, the
+/// integers must not be zero.
+fn cell_pair(pair: LispObject, positive: bool) -> (u32, u32) {
+    let cons = pair.as_cons_or_error();
+    let (x, y) = (cons.car().as_natnum_or_error(), cons.cdr().as_natnum_or_error());
+    let limit = EmacsInt::from(u16::max_value());
+    if x > limit || y > limit || (positive && (x == 0 || y == 0)) {
+        args_out_of_range!(pair, LispObject::from_natnum(limit));
+    }
+    (x as u32, y as u32)
+}
+
+/// Scale IMAGE to fit into SIZE, (COLUMNS, ROWS), character cells of
+/// CELL pixels, keeping its aspect ratio.  Return the scaled image and
+/// the number of columns and rows it covers.
+fn fit_cells(image: &DynamicImage, size: (u32, u32), cell: (u32, u32)) -> (DynamicImage, u32, u32) {
+    let (width, height) = image.dimensions();
+    let (w, h) = fit(width, height, Some(size.0 * cell.0), Some(size.1 * cell.1));
+    let scaled = image.resize_exact(w, h, FilterType::Triangle);
+    (scaled, (w + cell.0 - 1) / cell.0, (h + cell.1 - 1) / cell.1)
+}
+
+/// The number of levels of red, green and blue in the palette of sixel
+/// images.
+const SIXEL_LEVELS: (u32, u32, u32) = (6, 7, 6);
+
+/// Return the index of the color nearest to R, G, B in the palette of
+/// sixel images.
+fn sixel_color(r: u8, g: u8, b: u8) -> usize {
+    let (nr, ng, nb) = SIXEL_LEVELS;
+    let level = |value: u8, n: u32| (u32::from(value) * (n - 1) + 127) / 255;
+    ((level(r, nr) * ng + level(g, ng)) * nb + level(b, nb)) as usize
+}
+
+/// Return the sixel command that defines color INDEX of the palette.
+fn sixel_palette_entry(index: usize) -> String {
+    let (nr, ng, nb) = SIXEL_LEVELS;
+    let i = index as u32;
+    let percent = |level: u32, n: u32| level * 100 / (n - 1);
+    format!(
+        "#{};2;{};{};{}",
+        index,
+        percent(i / (ng * nb), nr),
+        percent(i / nb % ng, ng),
+        percent(i % nb, nb)
+    )
+}
+
+fn push_sixel_run(out: &mut Vec<u8>, sixel: u8, count: usize) {
+    if count > 3 {
+        out.extend_from_slice(format!("!{}", count).as_bytes());
+        out.push(sixel);
+    } else {
+        for _ in 0..count {
+            out.push(sixel);
+        }
+    }
+}
+
+/// Return IMAGE as a sixel sequence.  Pixels that are more than half
+/// transparent leave the screen as it is.
+fn sixel_sequence(image: &DynamicImage) -> Vec<u8> {
+    let rgba = image.to_rgba();
+    let (width, height) = rgba.dimensions();
+    let (w, h) = (width as usize, height as usize);
+    let colors: Vec<Option<usize>> = rgba.pixels()
+        .map(|pixel| {
+            let p = pixel.data;
+            if p[3] < 128 {
+                None
+            } else {
+                Some(sixel_color(p[0], p[1], p[2]))
+            }
+        })
+        .collect();
+    let (nr, ng, nb) = SIXEL_LEVELS;
+    let palette_size = (nr * ng * nb) as usize;
+
+    let mut used = vec![false; palette_size];
+    for color in colors.iter().filter_map(|&color| color) {
+        used[color] = true;
+    }
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height).into_bytes();
+    for color in (0..palette_size).filter(|&color| used[color]) {
+        out.extend_from_slice(sixel_palette_entry(color).as_bytes());
+    }
+
+    // The image is sent in bands of six rows, each color of a band in a
+    // pass of its own.
+    let mut top = 0;
+    while top < h {
+        let bottom = (top + 6).min(h);
+        let mut in_band = vec![false; palette_size];
+        for color in colors[top * w..bottom * w].iter().filter_map(|&color| color) {
+            in_band[color] = true;
+        }
+        let mut first = true;
+        for color in (0..palette_size).filter(|&color| in_band[color]) {
+            if !first {
+                out.push(b'$');
+            }
+            first = false;
+            out.extend_from_slice(format!("#{}", color).as_bytes());
+            let mut run = (b'?', 0);
+            for x in 0..w {
+                let mut bits = 0u8;
+                for y in top..bottom {
+                    if colors[y * w + x] == Some(color) {
+                        bits |= 1 << (y - top);
+                    }
+                }
+                let sixel = b'?' + bits;
+                if sixel == run.0 {
+                    run.1 += 1;
+                } else {
+                    push_sixel_run(&mut out, run.0, run.1);
+                    run = (sixel, 1);
+                }
+            }
+            // Empty sixels at the end of a pass need not be sent.
+            if run.0 != b'?' {
+                push_sixel_run(&mut out, run.0, run.1);
+            }
+        }
+        if bottom < h {
+            out.push(b'-');
+        }
+        top = bottom;
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+/// The size of the chunks the base64 data of kitty graphics commands is
+/// split into.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// Return the kitty graphics commands that send the image PNG with the
+/// control data CONTROL.
+fn kitty_sequence(png: &[u8], control: &str) -> Vec<u8> {
+    let data = base64_crate::encode(png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    let mut out = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let head = if i == 0 {
+            format!("\x1b_G{},m={};", control, more)
+        } else {
+            format!("\x1b_Gm={};", more)
+        };
+        out.extend_from_slice(head.as_bytes());
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    out
+}
+
+/// Return the escape sequence that shows the image SPEC on a text
+/// terminal using the graphics protocol PROTOCOL, `sixel' or `kitty'.
+/// SIZE is (COLUMNS . ROWS), the character cells the image is scaled to
+/// fit into, keeping its aspect ratio.  CELL-SIZE is the (WIDTH . HEIGHT)
+/// of a cell in pixels, as returned by `tty-query-capabilities', and
+/// defaults to (10 . 20).
+///
+/// The image is drawn with its top left corner at the cursor.  A kitty
+/// image leaves the cursor where it was, while a sixel image moves it
+/// below the image.
+#[lisp_fn(min = "3")]
+pub fn image_tty_sequence(
+    spec: LispObject,
+    protocol: LispObject,
+    size: LispObject,
+    cell_size: LispObject,
+) -> LispObject {
+    let protocol = tty_protocol(protocol);
+    let size = cell_pair(size, true);
+    let cell = if cell_size.is_nil() {
+        DEFAULT_CELL_SIZE
+    } else {
+        cell_pair(cell_size, true)
+    };
+    let (image, columns, rows) = fit_cells(&decode(spec), size, cell);
+    unibyte_string(&match protocol {
+        Protocol::Sixel => sixel_sequence(&image),
+        Protocol::Kitty => kitty_sequence(
+            &png_bytes(&image),
+            &format!("a=T,f=100,c={},r={},C=1,q=2", columns, rows),
+        ),
+    })
+}
+
+/// An image shown on a text terminal frame by `image-tty-place'.
+struct Placement {
+    id: EmacsInt,
+    /// The address of the frame, which identifies it until it is deleted.
+    frame: usize,
+    column: u32,
+    row: u32,
+    columns: u32,
+    rows: u32,
+    protocol: Protocol,
+    /// The sixel sequence, or the kitty command that transmits the image.
+    data: Vec<u8>,
+    /// Whether text has been written over the image since it was drawn.
+    damaged: bool,
+}
+
+impl Placement {
+    fn covers_row(&self, row: u32) -> bool {
+        row >= self.row && row < self.row + self.rows
+    }
+
+    /// Return the commands that draw the image at its place and restore
+    /// the cursor.  Kitty keeps the pixels of the image once they are
+    /// sent, so they are only sent again if TRANSMIT.
+    fn draw(&self, transmit: bool) -> Vec<u8> {
+        let mut out = format!("\x1b7\x1b[{};{}H", self.row + 1, self.column + 1).into_bytes();
+        match self.protocol {
+            Protocol::Sixel => out.extend_from_slice(&self.data),
+            Protocol::Kitty => {
+                if transmit {
+                    out.extend_from_slice(&self.data);
+                }
+                let place = format!(
+                    "\x1b_Ga=p,i={},p=1,c={},r={},C=1,q=2\x1b\\",
+                    self.id, self.columns, self.rows
+                );
+                out.extend_from_slice(place.as_bytes());
+            }
+        }
+        out.extend_from_slice(b"\x1b8");
+        out
+    }
+}
+
+lazy_static! {
+    static ref TTY_IMAGES: Mutex<(EmacsInt, Vec<Placement>)> = Mutex::new((0, Vec::new()));
+}
+
+/// Note that row VPOS of the text terminal frame F has been written to,
+/// so that the images on it must be drawn again.  Called by the display
+/// code for each row it updates.
+#[no_mangle]
+pub extern "C" fn tty_image_row_updated(f: *const Lisp_Frame, vpos: c_int) {
+    if vpos < 0 {
+        return;
+    }
+    let mut images = TTY_IMAGES.lock().unwrap();
+    for placement in images.1.iter_mut() {
+        if placement.frame == f as usize && placement.covers_row(vpos as u32) {
+            placement.damaged = true;
+        }
+    }
+}
+
+/// Draw the images of the text terminal frame F that were written over
+/// again, writing to the file descriptor FD.  Called at the end of an
+/// update of F.
+#[no_mangle]
+pub extern "C" fn tty_images_repaint(f: *const Lisp_Frame, fd: c_int) {
+    let mut out = Vec::new();
+    {
+        let mut images = TTY_IMAGES.lock().unwrap();
+        for placement in images.1.iter_mut() {
+            if placement.frame == f as usize && placement.damaged {
+                out.extend(placement.draw(false));
+                placement.damaged = false;
+            }
+        }
+    }
+    let mut rest = &out[..];
+    while !rest.is_empty() {
+        let written = unsafe { libc::write(fd, rest.as_ptr() as *const c_void, rest.len()) };
+        if written < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return;
+        }
+        rest = &rest[written as usize..];
+    }
+}
+
+/// Forget the images of the frame F, which is being deleted.
+#[no_mangle]
+pub extern "C" fn tty_images_forget_frame(f: *const Lisp_Frame) {
+    TTY_IMAGES
+        .lock()
+        .unwrap()
+        .1
+        .retain(|placement| placement.frame != f as usize);
+}
+
+/// Return the live frame at ADDRESS, or nil if there is none.
+fn frame_at(address: usize) -> LispObject {
+    call!(intern("frame-list"))
+        .iter_cars()
+        .find(|frame| frame.as_frame().map_or(false, |f| f.as_ptr() as usize == address))
+        .unwrap_or_else(LispObject::constant_nil)
+}
+
+/// Show the image SPEC on the text terminal frame FRAME, which defaults
+/// to the selected frame, with its top left corner in the character cell
+/// at POSITION, (COLUMN . ROW).  PROTOCOL, SIZE and CELL-SIZE are as for
+/// `image-tty-sequence'; the image must fit into the frame.  Return an
+/// id for `image-tty-remove'.
+///
+/// The image is drawn again after each redisplay that writes to the rows
+/// it covers, so that text doesn't hide it.  It stays at POSITION when
+/// the text under it scrolls.
+#[lisp_fn(min = "4")]
+pub fn image_tty_place(
+    spec: LispObject,
+    protocol: LispObject,
+    position: LispObject,
+    size: LispObject,
+    cell_size: LispObject,
+    frame: LispObject,
+) -> LispObject {
+    let frame = if frame.is_nil() { selected_frame() } else { frame };
+    let frame_ref = frame_live_or_selected(frame);
+    if unsafe { fget_output_method(frame_ref.as_ptr()) } != output_termcap {
+        error!("Not a text terminal frame");
+    }
+    let protocol = tty_protocol(protocol);
+    let (column, row) = cell_pair(position, false);
+    let cell = if cell_size.is_nil() {
+        DEFAULT_CELL_SIZE
+    } else {
+        cell_pair(cell_size, true)
+    };
+    let (image, columns, rows) = fit_cells(&decode(spec), cell_pair(size, true), cell);
+    let lines = call!(intern("frame-total-lines"), frame).as_natnum_or_error() as u32;
+    let cols = call!(intern("frame-total-cols"), frame).as_natnum_or_error() as u32;
+    if row + rows > lines || column + columns > cols {
+        args_out_of_range!(position, size);
+    }
+    let data = match protocol {
+        Protocol::Sixel => sixel_sequence(&image),
+        Protocol::Kitty => png_bytes(&image),
+    };
+
+    let (id, output) = {
+        let mut images = TTY_IMAGES.lock().unwrap();
+        images.0 += 1;
+        let id = images.0;
+        let data = match protocol {
+            Protocol::Sixel => data,
+            Protocol::Kitty => kitty_sequence(&data, &format!("a=t,f=100,i={},q=2", id)),
+        };
+        let placement = Placement {
+            id: id,
+            frame: frame_ref.as_ptr() as usize,
+            column: column,
+            row: row,
+            columns: columns,
+            rows: rows,
+            protocol: protocol,
+            data: data,
+            damaged: false,
+        };
+        let output = placement.draw(true);
+        images.1.push(placement);
+        (id, output)
+    };
+    call!(intern("send-string-to-terminal"), unibyte_string(&output), frame);
+    LispObject::from_natnum(id)
+}
+
+/// Remove the image ID shown by `image-tty-place'.  Text under a sixel
+/// image appears when its frame is next redisplayed.  Return nil if
+/// there is no such image.
+#[lisp_fn]
+pub fn image_tty_remove(id: LispObject) -> LispObject {
+    let number = id.as_fixnum_or_error();
+    let removed = {
+        let mut images = TTY_IMAGES.lock().unwrap();
+        match images.1.iter().position(|placement| placement.id == number) {
+            Some(index) => Some(images.1.remove(index)),
+            None => None,
+        }
+    };
+    let placement = match removed {
+        Some(placement) => placement,
+        None => return LispObject::constant_nil(),
+    };
+    let frame = frame_at(placement.frame);
+    if frame.is_not_nil() {
+        match placement.protocol {
+            Protocol::Sixel => {
+                call!(intern("redraw-frame"), frame);
+            }
+            Protocol::Kitty => {
+                let delete = format!("\x1b_Ga=d,d=I,i={},q=2\x1b\\", placement.id);
+                call!(intern("send-string-to-terminal"), lisp_string(&delete), frame);
+            }
+        }
+    }
+    LispObject::constant_t()
+}
+
+/// Return the images shown on FRAME by `image-tty-place', which defaults
+/// to the selected frame.  Each element is (ID PROTOCOL POSITION SIZE),
+/// where POSITION is the (COLUMN . ROW) of the top left corner of the
+/// image and SIZE the (COLUMNS . ROWS) it covers.
+#[lisp_fn(min = "0")]
+pub fn image_tty_placements(frame: LispObject) -> LispObject {
+    let address = frame_or_selected(frame).as_ptr() as usize;
+    let placements: Vec<_> = TTY_IMAGES
+        .lock()
+        .unwrap()
+        .1
+        .iter()
+        .filter(|placement| placement.frame == address)
+        .map(|placement| {
+            (
+                placement.id,
+                placement.protocol,
+                (placement.column, placement.row),
+                (placement.columns, placement.rows),
+            )
+        })
+        .collect();
+    let pair = |(x, y): (u32, u32)| {
+        LispObject::cons(
+            LispObject::from_natnum(EmacsInt::from(x)),
+            LispObject::from_natnum(EmacsInt::from(y)),
+        )
+    };
+    placements
+        .into_iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, (id, protocol, position, size)| {
+            let protocol = match protocol {
+                Protocol::Sixel => intern("sixel"),
+                Protocol::Kitty => intern("kitty"),
+            };
+            let entry = list!(
+                LispObject::from_natnum(id),
+                protocol,
+                pair(position),
+                pair(size)
+            );
+            LispObject::cons(entry, tail)
+        })
+}
+
+#[test]
+fn test_fit() {
+    assert_eq!(fit(400, 200, Some(100), Some(100)), (100, 50));
+    assert_eq!(fit(200, 400, Some(100), Some(100)), (50, 100));
+    assert_eq!(fit(400, 200, None, Some(50)), (100, 50));
+    assert_eq!(fit(400, 200, Some(800), None), (800, 400));
+    assert_eq!(fit(1000, 1, Some(10), None), (10, 1));
+    assert_eq!(fit(30, 20, None, None), (30, 20));
+}
+
+#[cfg(test)]
+fn test_gif() -> Vec<u8> {
+    let mut gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00".to_vec();
+    for &delay in &[10, 20] {
+        gif.extend_from_slice(&[0x21, 0xF9, 4, 0, delay, 0, 0, 0]);
+        gif.extend_from_slice(&[0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0, 2, 1, 0, 0]);
+    }
+    gif.push(0x3B);
+    gif
+}
+
+#[test]
+fn test_frames() {
+    let mut frames = Frames::new(test_gif(), 0.1, 0.01).unwrap();
+    assert!(frames.have(0));
+    assert_eq!(frames.delays, vec![0.1]);
+    assert!(frames.have(1));
+    assert!(!frames.have(2));
+    assert_eq!(frames.delays, vec![0.1, 0.2]);
+    assert!(Frames::new(b"\x89PNG".to_vec(), 0.1, 0.01).is_none());
+
+    let mut webp = b"RIFF\0\0\0\0WEBPANIM\x06\0\0\0\0\0\0\0\0\0".to_vec();
+    webp.extend_from_slice(b"ANMF\x10\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\xF4\x01\0\0");
+    let mut frames = Frames::new(webp, 0.1, 0.01).unwrap();
+    frames.scan_all();
+    assert_eq!(frames.delays, vec![0.5]);
+}
+
+#[test]
+fn test_animation_advance() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let animation = |limit| Animation {
+        frames: Frames::new(test_gif(), 0.1, 0.01).unwrap(),
+        reverse: false,
+        speed: 1.0,
+        limit: limit,
+        position: 0.0,
+        elapsed: 0.0,
+        last: start,
+        shown: None,
+    };
+    let close = |step: Option<(usize, f64)>, index, wait: f64| {
+        let (i, w) = step.unwrap();
+        i == index && (w - wait).abs() < 1e-6
+    };
+
+    let mut once = animation(Limit::Once);
+    assert!(close(once.advance(at(50)), 0, 0.05));
+    // A late tick skips to the frame that is due.
+    assert!(close(once.advance(at(250)), 1, 0.05));
+    assert!(once.advance(at(400)).is_none());
+
+    let mut forever = animation(Limit::Forever);
+    assert!(close(forever.advance(at(750)), 1, 0.15));
+
+    let mut limited = animation(Limit::Seconds(0.5));
+    assert!(limited.advance(at(350)).is_some());
+    assert!(limited.advance(at(500)).is_none());
+
+    let mut reverse = animation(Limit::Once);
+    reverse.reverse = true;
+    reverse.frames.scan_all();
+    assert!(close(reverse.advance(at(100)), 1, 0.1));
+    assert!(close(reverse.advance(at(250)), 0, 0.05));
+}
+
+#[test]
+fn test_read_header() {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x01\x2c\0\0\0\xc8\x08\x06\0\0\0".to_vec();
+    png.extend_from_slice(b"\0\0\0\0\0\0\0\x09pHYs\0\0\x0b\x13\0\0\x0b\x13\x01\0\0\0\0");
+    png.extend_from_slice(b"\0\0\0\0IDAT");
+    let header = read_header(&png).unwrap();
+    assert_eq!((header.format, header.width, header.height), ("png", 300, 200));
+    let (x, y) = header.dpi.unwrap();
+    assert!((x - 72.0).abs() < 0.1 && (y - 72.0).abs() < 0.1);
+
+    let jpeg = b"\xff\xd8\xff\xe0\0\x10JFIF\0\x01\x01\x01\0\x60\0\x60\0\0\
+                 \xff\xc0\0\x11\x08\0\x20\0\x40\x03";
+    assert_eq!(
+        read_header(jpeg),
+        Ok(Header {
+            format: "jpeg",
+            width: 64,
+            height: 32,
+            dpi: Some((96.0, 96.0)),
+        })
+    );
+
+    assert_eq!(read_header(&test_gif()).map(|h| (h.width, h.height)), Ok((1, 1)));
+
+    let webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0\x7f\x02\0\xdf\x01\0";
+    assert_eq!(read_header(webp).map(|h| (h.width, h.height)), Ok((640, 480)));
+
+    assert!(read_header(b"\xff\xd8\xff\xd9").is_err());
+    assert!(read_header(b"text").is_err());
+}
+
+#[cfg(test)]
+fn test_rgba(width: u32, height: u32, pixel: [u8; 4]) -> DynamicImage {
+    DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+        width,
+        height,
+        image::Rgba { data: pixel },
+    ))
+}
+
+#[test]
+fn test_sixel_sequence() {
+    let mut pixels = test_rgba(2, 1, [0, 0, 0, 0]);
+    pixels.put_pixel(0, 0, image::Rgba { data: [255, 0, 0, 255] });
+    assert_eq!(
+        sixel_sequence(&pixels),
+        b"\x1bP0;1;0q\"1;1;2;1#210;2;100;0;0#210@\x1b\\".to_vec()
+    );
+    assert_eq!(
+        sixel_sequence(&test_rgba(8, 6, [255, 255, 255, 255])),
+        b"\x1bP0;1;0q\"1;1;8;6#251;2;100;100;100#251!8~\x1b\\".to_vec()
+    );
+    assert_eq!(
+        sixel_sequence(&test_rgba(1, 12, [0, 0, 250, 255])),
+        b"\x1bP0;1;0q\"1;1;1;12#5;2;0;0;100#5~-#5~\x1b\\".to_vec()
+    );
+}
+
+#[test]
+fn test_kitty_sequence() {
+    assert_eq!(kitty_sequence(b"abc", "a=T"), b"\x1b_Ga=T,m=0;YWJj\x1b\\".to_vec());
+    let long = kitty_sequence(&[0; 3073], "a=t,i=1");
+    assert!(long.starts_with(b"\x1b_Ga=t,i=1,m=1;AAAA"));
+    assert!(long.ends_with(b"\x1b\\\x1b_Gm=0;AA==\x1b\\"));
+}
+
+#[test]
+fn test_fit_cells() {
+    let (image, columns, rows) = fit_cells(&test_rgba(100, 50, [0; 4]), (20, 20), (10, 20));
+    assert_eq!(image.dimensions(), (200, 100));
+    assert_eq!((columns, rows), (20, 5));
+}
+
+#[test]
+fn test_tty_placement() {
+    let frame = 8 as *const Lisp_Frame;
+    TTY_IMAGES.lock().unwrap().1.push(Placement {
+        id: 7,
+        frame: frame as usize,
+        column: 4,
+        row: 2,
+        columns: 3,
+        rows: 2,
+        protocol: Protocol::Kitty,
+        data: b"DATA".to_vec(),
+        damaged: false,
+    });
+    {
+        let images = TTY_IMAGES.lock().unwrap();
+        let placement = &images.1[0];
+        assert_eq!(
+            placement.draw(true),
+            b"\x1b7\x1b[3;5HDATA\x1b_Ga=p,i=7,p=1,c=3,r=2,C=1,q=2\x1b\\\x1b8".to_vec()
+        );
+        assert!(!placement.draw(false).windows(4).any(|w| w == b"DATA"));
+    }
+
+    let damaged = || TTY_IMAGES.lock().unwrap().1[0].damaged;
+    tty_image_row_updated(frame, 1);
+    tty_image_row_updated(9 as *const Lisp_Frame, 2);
+    assert!(!damaged());
+    tty_image_row_updated(frame, 3);
+    assert!(damaged());
+    // Drawing the image to an invalid descriptor fails quietly.
+    tty_images_repaint(frame, -1);
+    assert!(!damaged());
+    tty_images_forget_frame(frame);
+    assert!(TTY_IMAGES.lock().unwrap().1.is_empty());
+}
+
+include!(concat!(env!("OUT_DIR"), "/images_exports.rs"));
@@ -0,0 +1,790 @@
+//! The protocol side of an IMAP client (RFC 3501).
+//!
+//! Connections are network processes made by `open-network-stream', so
+//! TLS is done by GnuTLS as for other network processes.  What is done
+//! here is the protocol: commands are tagged and sent, and the output
+//! of the server, whose messages can be split across reads and carry
+//! literals of any size, is parsed into Lisp data and handed to the
+//! callbacks of the commands.  The callbacks and the untagged responses
+//! waiting for them are kept in the process plist; only the unparsed
+//! output and the tag counter are kept here.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use libc::{c_char, ptrdiff_t};
+
+use base64_crate;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_unibyte_string, EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// A piece of data in a response.
+#[derive(Debug, PartialEq)]
+enum Token {
+    Atom(String),
+    Number(u32),
+    /// A quoted string or a literal.
+    Str(Vec<u8>),
+    Nil,
+    List(Vec<Token>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Response {
+    /// A "+" continuation request, with its text.
+    Continuation(String),
+    /// The completion of a command: its tag, status and text.
+    Tagged {
+        tag: String,
+        status: String,
+        text: String,
+    },
+    /// A "*" response.  The status responses, such as "* OK ...", have
+    /// the status followed by the text as a string.
+    Untagged(Vec<Token>),
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// More output is needed.
+    Incomplete,
+    Malformed,
+}
+
+const STATUSES: [&str; 5] = ["OK", "NO", "BAD", "PREAUTH", "BYE"];
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Result<u8, ParseError> {
+        self.input
+            .get(self.pos)
+            .cloned()
+            .ok_or(ParseError::Incomplete)
+    }
+
+    fn skip_spaces(&mut self) -> Result<(), ParseError> {
+        while self.peek()? == b' ' {
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    /// Whether the parser is at the end of a line, which is CRLF or a
+    /// bare LF.  The line end is consumed.
+    fn at_line_end(&mut self) -> Result<bool, ParseError> {
+        match self.peek()? {
+            b'\n' => {
+                self.pos += 1;
+                Ok(true)
+            }
+            b'\r' => {
+                self.pos += 1;
+                if self.peek()? == b'\n' {
+                    self.pos += 1;
+                    Ok(true)
+                } else {
+                    Err(ParseError::Malformed)
+                }
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Return the rest of the line, without its end.
+    fn rest_of_line(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        let end = match self.input[start..].iter().position(|&b| b == b'\n') {
+            Some(offset) => start + offset,
+            None => return Err(ParseError::Incomplete),
+        };
+        self.pos = end + 1;
+        let text = &self.input[start..end];
+        let text = if text.ends_with(b"\r") {
+            &text[..text.len() - 1]
+        } else {
+            text
+        };
+        Ok(String::from_utf8_lossy(text).into_owned())
+    }
+
+    /// Read an atom.  A `[' in an atom, as in "BODY[HEADER.FIELDS (TO)]",
+    /// makes everything up to the matching `]' part of it.
+    fn atom(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            match self.peek()? {
+                b'[' => depth += 1,
+                b']' if depth > 0 => depth -= 1,
+                b'\r' | b'\n' => break,
+                b' ' | b'(' | b')' | b'{' | b'"' if depth == 0 => break,
+                _ => (),
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(ParseError::Malformed);
+        }
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+
+    fn quoted(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.pos += 1;
+        let mut string = Vec::new();
+        loop {
+            match self.peek()? {
+                b'"' => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                b'\\' => {
+                    self.pos += 1;
+                    string.push(self.peek()?);
+                }
+                b'\r' | b'\n' => return Err(ParseError::Malformed),
+                b => string.push(b),
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Read a literal, {N} and the line end followed by N bytes.  The
+    /// non-synchronizing form {N+} is accepted too.
+    fn literal(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.pos += 1;
+        let start = self.pos;
+        while let b'0'...b'9' = self.peek()? {
+            self.pos += 1;
+        }
+        let size: usize = ::std::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or(ParseError::Malformed)?;
+        if self.peek()? == b'+' {
+            self.pos += 1;
+        }
+        if self.peek()? != b'}' {
+            return Err(ParseError::Malformed);
+        }
+        self.pos += 1;
+        if !self.at_line_end()? {
+            return Err(ParseError::Malformed);
+        }
+        if self.input.len() - self.pos < size {
+            return Err(ParseError::Incomplete);
+        }
+        let literal = self.input[self.pos..self.pos + size].to_vec();
+        self.pos += size;
+        Ok(literal)
+    }
+
+    fn token(&mut self) -> Result<Token, ParseError> {
+        match self.peek()? {
+            b'(' => {
+                self.pos += 1;
+                let mut tokens = Vec::new();
+                loop {
+                    self.skip_spaces()?;
+                    if self.peek()? == b')' {
+                        self.pos += 1;
+                        return Ok(Token::List(tokens));
+                    }
+                    tokens.push(self.token()?);
+                }
+            }
+            b'"' => Ok(Token::Str(self.quoted()?)),
+            b'{' => Ok(Token::Str(self.literal()?)),
+            b')' | b'\r' | b'\n' => Err(ParseError::Malformed),
+            _ => {
+                let atom = self.atom()?;
+                if atom.to_uppercase() == "NIL" {
+                    Ok(Token::Nil)
+                } else if let Ok(number) = atom.parse() {
+                    Ok(Token::Number(number))
+                } else {
+                    Ok(Token::Atom(atom))
+                }
+            }
+        }
+    }
+
+    fn response(&mut self) -> Result<Response, ParseError> {
+        let tag = self.atom()?;
+        if tag == "+" {
+            if self.peek()? == b' ' {
+                self.pos += 1;
+            }
+            return Ok(Response::Continuation(self.rest_of_line()?));
+        }
+        self.skip_spaces()?;
+        if tag != "*" {
+            let status = self.atom()?.to_uppercase();
+            if self.peek()? == b' ' {
+                self.pos += 1;
+            }
+            let text = self.rest_of_line()?;
+            return Ok(Response::Tagged { tag, status, text });
+        }
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_spaces()?;
+            if self.at_line_end()? {
+                return Ok(Response::Untagged(tokens));
+            }
+            let token = self.token()?;
+            let status = match token {
+                Token::Atom(ref atom) if tokens.is_empty() => {
+                    let upper = atom.to_uppercase();
+                    if STATUSES.contains(&upper.as_str()) {
+                        Some(upper)
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            if let Some(status) = status {
+                if self.peek()? == b' ' {
+                    self.pos += 1;
+                }
+                let text = self.rest_of_line()?;
+                return Ok(Response::Untagged(vec![
+                    Token::Atom(status),
+                    Token::Str(text.into_bytes()),
+                ]));
+            }
+            tokens.push(token);
+        }
+    }
+}
+
+/// Parse the complete responses at the start of INPUT.  Return them and
+/// the number of bytes they took.  A line that cannot be parsed is
+/// skipped.
+fn parse_responses(input: &[u8]) -> (Vec<Response>, usize) {
+    let mut responses = Vec::new();
+    let mut consumed = 0;
+    while consumed < input.len() {
+        let mut parser = Parser {
+            input: &input[consumed..],
+            pos: 0,
+        };
+        match parser.response() {
+            Ok(response) => {
+                responses.push(response);
+                consumed += parser.pos;
+            }
+            Err(ParseError::Incomplete) => break,
+            Err(ParseError::Malformed) => {
+                match input[consumed + parser.pos..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                {
+                    Some(offset) => consumed += parser.pos + offset + 1,
+                    None => break,
+                }
+            }
+        }
+    }
+    (responses, consumed)
+}
+
+/// Return TEXT as an IMAP string: an atom-safe quoted string.
+fn quote(text: &[u8]) -> Option<Vec<u8>> {
+    if text.iter().any(|&b| b == b'\r' || b == b'\n' || b == 0) {
+        return None;
+    }
+    let mut quoted = Vec::with_capacity(text.len() + 2);
+    quoted.push(b'"');
+    for &b in text {
+        if b == b'"' || b == b'\\' {
+            quoted.push(b'\\');
+        }
+        quoted.push(b);
+    }
+    quoted.push(b'"');
+    Some(quoted)
+}
+
+/// The state of a connection that is not Lisp data.
+#[derive(Default)]
+struct Connection {
+    /// Output of the server not parsed yet.
+    pending: Vec<u8>,
+    /// The number of the last tag.
+    last_tag: u32,
+    /// The tag of the IDLE command in progress.
+    idle: Option<String>,
+    /// The tag of the AUTHENTICATE command in progress.
+    authenticate: Option<String>,
+}
+
+lazy_static! {
+    /// The connections, by process name.
+    static ref CONNECTIONS: Mutex<HashMap<String, Connection>> = Mutex::new(HashMap::new());
+}
+
+pub fn process_name(process: LispObject) -> String {
+    let name = call!(intern("process-name"), process);
+    String::from_utf8_lossy(name.as_string_or_error().as_slice()).into_owned()
+}
+
+pub fn unibyte_string(bytes: &[u8]) -> LispObject {
+    LispObject::from(unsafe {
+        make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t)
+    })
+}
+
+pub fn string_bytes(string: LispObject) -> Vec<u8> {
+    let lisp = string.as_string_or_error();
+    if lisp.is_multibyte() {
+        let encoded = call!(intern("encode-coding-string"), string, intern("utf-8"));
+        encoded.as_string_or_error().as_slice().to_vec()
+    } else {
+        lisp.as_slice().to_vec()
+    }
+}
+
+/// Return TEXT quoted, or signal an error if it cannot be.
+fn quote_or_error(text: LispObject) -> Vec<u8> {
+    match quote(&string_bytes(text)) {
+        Some(quoted) => quoted,
+        None => error!("Cannot send a string with a line break"),
+    }
+}
+
+fn token_to_lisp(token: &Token) -> LispObject {
+    match *token {
+        Token::Atom(ref atom) => lisp_string(atom),
+        Token::Number(number) => LispObject::from_natnum(EmacsInt::from(number)),
+        Token::Str(ref bytes) => unibyte_string(bytes),
+        Token::Nil => LispObject::constant_nil(),
+        Token::List(ref tokens) => tokens_to_lisp(tokens),
+    }
+}
+
+fn tokens_to_lisp(tokens: &[Token]) -> LispObject {
+    tokens
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, token| {
+            LispObject::cons(token_to_lisp(token), list)
+        })
+}
+
+/// Send COMMAND to PROCESS with a new tag, to be answered by CALLBACK.
+/// Return the tag.
+fn send_command(process: LispObject, command: &[u8], callback: LispObject) -> LispObject {
+    let name = process_name(process);
+    let tag = {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        let connection = connections.entry(name).or_insert_with(Connection::default);
+        connection.last_tag += 1;
+        format!("A{:04}", connection.last_tag)
+    };
+    let mut line = format!("{} ", tag).into_bytes();
+    line.extend_from_slice(command);
+    line.extend_from_slice(b"\r\n");
+    let lisp_tag = lisp_string(&tag);
+    if callback.is_not_nil() {
+        let callbacks = call!(intern("process-get"), process, intern("imap--callbacks"));
+        call!(
+            intern("process-put"),
+            process,
+            intern("imap--callbacks"),
+            LispObject::cons(LispObject::cons(lisp_tag, callback), callbacks)
+        );
+    }
+    call!(intern("process-send-string"), process, unibyte_string(&line));
+    lisp_tag
+}
+
+/// Hand RESPONSE, received by PROCESS, to the callback it is for.
+fn dispatch(process: LispObject, name: &str, response: Response) {
+    match response {
+        Response::Continuation(_) => {
+            let authenticating = CONNECTIONS
+                .lock()
+                .unwrap()
+                .get(name)
+                .map_or(false, |connection| connection.authenticate.is_some());
+            // A challenge to AUTHENTICATE with an initial response is an
+            // error report, which is acknowledged with an empty line.
+            if authenticating {
+                call!(intern("process-send-string"), process, lisp_string("\r\n"));
+            }
+        }
+        Response::Untagged(tokens) => {
+            let idle = CONNECTIONS
+                .lock()
+                .unwrap()
+                .get(name)
+                .map_or(false, |connection| connection.idle.is_some());
+            let response = tokens_to_lisp(&tokens);
+            let callbacks = call!(intern("process-get"), process, intern("imap--callbacks"));
+            if idle || callbacks.is_nil() {
+                let function = call!(
+                    intern("process-get"),
+                    process,
+                    intern("imap--untagged-function")
+                );
+                if function.is_not_nil() {
+                    call!(function, process, response);
+                }
+            } else {
+                let untagged = call!(intern("process-get"), process, intern("imap--untagged"));
+                call!(
+                    intern("process-put"),
+                    process,
+                    intern("imap--untagged"),
+                    LispObject::cons(response, untagged)
+                );
+            }
+        }
+        Response::Tagged { tag, status, text } => {
+            if let Some(connection) = CONNECTIONS.lock().unwrap().get_mut(name) {
+                if connection.idle.as_ref() == Some(&tag) {
+                    connection.idle = None;
+                }
+                if connection.authenticate.as_ref() == Some(&tag) {
+                    connection.authenticate = None;
+                }
+            }
+            let lisp_tag = lisp_string(&tag);
+            let callbacks = call!(intern("process-get"), process, intern("imap--callbacks"));
+            let entry = call!(intern("assoc"), lisp_tag, callbacks);
+            let untagged = call!(
+                intern("nreverse"),
+                call!(intern("process-get"), process, intern("imap--untagged"))
+            );
+            call!(
+                intern("process-put"),
+                process,
+                intern("imap--untagged"),
+                LispObject::constant_nil()
+            );
+            if entry.is_not_nil() {
+                call!(
+                    intern("process-put"),
+                    process,
+                    intern("imap--callbacks"),
+                    call!(intern("delq"), entry, callbacks)
+                );
+                call!(
+                    call!(intern("cdr"), entry),
+                    intern(&status.to_lowercase()),
+                    lisp_string(&text),
+                    untagged
+                );
+            }
+        }
+    }
+}
+
+/// Open an IMAP connection to HOST at SERVICE and return its process.
+/// SERVICE is a port number or a service name such as "imaps".  If TLS
+/// is non-nil, the connection is encrypted from the start, as on port
+/// 993.  UNTAGGED-FUNCTION, if non-nil, is called with the process and
+/// each untagged response that no command is waiting for, such as the
+/// greeting of the server and the updates sent during `imap-idle'.
+///
+/// An untagged response is a list of its data: atoms are strings,
+/// numbers are integers, quoted strings and literals are unibyte
+/// strings, NIL is nil and parenthesized lists are lists.  A status
+/// response such as "* OK [ALERT] text" is the list ("OK" "[ALERT]
+/// text").
+#[lisp_fn(min = "2")]
+pub fn imap_open(
+    host: LispObject,
+    service: LispObject,
+    tls: LispObject,
+    untagged_function: LispObject,
+) -> LispObject {
+    let process = call!(
+        intern("open-network-stream"),
+        lisp_string("imap"),
+        LispObject::constant_nil(),
+        host,
+        service,
+        intern(":type"),
+        if tls.is_nil() {
+            intern("plain")
+        } else {
+            intern("tls")
+        },
+        intern(":coding"),
+        intern("binary")
+    );
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .insert(process_name(process), Connection::default());
+    call!(
+        intern("process-put"),
+        process,
+        intern("imap--untagged-function"),
+        untagged_function
+    );
+    call!(intern("set-process-query-on-exit-flag"), process, LispObject::constant_nil());
+    call!(intern("set-process-filter"), process, intern("imap-process-filter"));
+    process
+}
+
+/// Handle STRING, output of PROCESS, an IMAP connection.
+/// This is the process filter of the connections of `imap-open'.
+#[lisp_fn]
+pub fn imap_process_filter(process: LispObject, string: LispObject) -> LispObject {
+    let name = process_name(process);
+    let bytes = string_bytes(string);
+    let responses = {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        let connection = connections
+            .entry(name.clone())
+            .or_insert_with(Connection::default);
+        connection.pending.extend_from_slice(&bytes);
+        let (responses, consumed) = parse_responses(&connection.pending);
+        connection.pending.drain(..consumed);
+        responses
+    };
+    for response in responses {
+        dispatch(process, &name, response);
+    }
+    LispObject::constant_nil()
+}
+
+/// Send COMMAND to PROCESS, an IMAP connection, and return its tag.
+/// COMMAND is a string such as "SELECT INBOX", sent as it is; use
+/// `imap-quote' for its arguments.  When the command completes,
+/// CALLBACK is called with the status, one of the symbols `ok', `no'
+/// and `bad', the text of the completion and the list of the untagged
+/// responses received since the previous completion.
+#[lisp_fn(min = "2")]
+pub fn imap_send(process: LispObject, command: LispObject, callback: LispObject) -> LispObject {
+    send_command(process, &string_bytes(command), callback)
+}
+
+/// Return STRING as an IMAP quoted string.
+/// Signal an error if STRING has a line break, which only a literal can
+/// carry.
+#[lisp_fn]
+pub fn imap_quote(string: LispObject) -> LispObject {
+    unibyte_string(&quote_or_error(string))
+}
+
+/// Log in to PROCESS as USER with PASSWORD, calling CALLBACK when done.
+/// CALLBACK is as for `imap-send'.  Return the tag of the command.
+#[lisp_fn]
+pub fn imap_login(
+    process: LispObject,
+    user: LispObject,
+    password: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    let mut command = b"LOGIN ".to_vec();
+    command.extend_from_slice(&quote_or_error(user));
+    command.push(b' ');
+    command.extend_from_slice(&quote_or_error(password));
+    send_command(process, &command, callback)
+}
+
+/// Authenticate to PROCESS as USER with the OAuth 2.0 access TOKEN.
+/// This uses the XOAUTH2 mechanism of Gmail and Outlook, with the
+/// token as an initial response; see `oauth2-native-access-token' to
+/// get one.  CALLBACK is as for `imap-send'.  Return the tag of the
+/// command.
+#[lisp_fn]
+pub fn imap_authenticate_xoauth2(
+    process: LispObject,
+    user: LispObject,
+    token: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    let mut response = b"user=".to_vec();
+    response.extend_from_slice(&string_bytes(user));
+    response.extend_from_slice(b"\x01auth=Bearer ");
+    response.extend_from_slice(&string_bytes(token));
+    response.extend_from_slice(b"\x01\x01");
+    let command = format!("AUTHENTICATE XOAUTH2 {}", base64_crate::encode(&response));
+    let tag = send_command(process, command.as_bytes(), callback);
+    let tag_string = String::from_utf8_lossy(tag.as_string_or_error().as_slice()).into_owned();
+    let name = process_name(process);
+    if let Some(connection) = CONNECTIONS.lock().unwrap().get_mut(&name) {
+        connection.authenticate = Some(tag_string);
+    }
+    tag
+}
+
+/// List the mailboxes of PROCESS matching PATTERN under REFERENCE.
+/// PATTERN may have the wildcards `*' and `%'.  The "LIST" untagged
+/// responses are passed to CALLBACK, which is as for `imap-send'.
+/// Return the tag of the command.
+#[lisp_fn]
+pub fn imap_list(
+    process: LispObject,
+    reference: LispObject,
+    pattern: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    let mut command = b"LIST ".to_vec();
+    command.extend_from_slice(&quote_or_error(reference));
+    command.push(b' ');
+    command.extend_from_slice(&quote_or_error(pattern));
+    send_command(process, &command, callback)
+}
+
+/// Fetch ITEMS of the messages SEQUENCE of the mailbox selected on PROCESS.
+/// SEQUENCE is a string such as "1:10" or "4,7", and ITEMS one such as
+/// "(FLAGS BODY.PEEK[HEADER])".  If UID is non-nil, SEQUENCE holds UIDs.
+/// The "FETCH" untagged responses, with the message data as unibyte
+/// strings, are passed to CALLBACK, which is as for `imap-send'.
+/// Return the tag of the command.
+#[lisp_fn(min = "4")]
+pub fn imap_fetch(
+    process: LispObject,
+    sequence: LispObject,
+    items: LispObject,
+    callback: LispObject,
+    uid: LispObject,
+) -> LispObject {
+    let mut command = if uid.is_nil() {
+        b"FETCH ".to_vec()
+    } else {
+        b"UID FETCH ".to_vec()
+    };
+    command.extend_from_slice(&string_bytes(sequence));
+    command.push(b' ');
+    command.extend_from_slice(&string_bytes(items));
+    send_command(process, &command, callback)
+}
+
+/// Wait on PROCESS for changes to the selected mailbox (RFC 2177).
+/// Until `imap-idle-done' is called, the untagged responses announcing
+/// changes are passed to the untagged function of the connection as
+/// they come.  CALLBACK, as for `imap-send', is called when the idling
+/// ends.  Return the tag of the command.
+#[lisp_fn]
+pub fn imap_idle(process: LispObject, callback: LispObject) -> LispObject {
+    let tag = send_command(process, b"IDLE", callback);
+    let tag_string = String::from_utf8_lossy(tag.as_string_or_error().as_slice()).into_owned();
+    let name = process_name(process);
+    if let Some(connection) = CONNECTIONS.lock().unwrap().get_mut(&name) {
+        connection.idle = Some(tag_string);
+    }
+    tag
+}
+
+/// End the `imap-idle' in progress on PROCESS.
+#[lisp_fn]
+pub fn imap_idle_done(process: LispObject) -> LispObject {
+    call!(intern("process-send-string"), process, lisp_string("DONE\r\n"));
+    LispObject::constant_nil()
+}
+
+/// Close PROCESS, an IMAP connection, without logging out.
+#[lisp_fn]
+pub fn imap_close(process: LispObject) -> LispObject {
+    CONNECTIONS.lock().unwrap().remove(&process_name(process));
+    call!(intern("delete-process"), process);
+    LispObject::constant_nil()
+}
+
+#[cfg(test)]
+fn parse_one(input: &[u8]) -> Result<Response, ParseError> {
+    Parser { input, pos: 0 }.response()
+}
+
+#[test]
+fn test_parse_status_responses() {
+    assert_eq!(
+        parse_one(b"* OK [CAPABILITY IMAP4rev1] Ready\r\n"),
+        Ok(Response::Untagged(vec![
+            Token::Atom("OK".to_string()),
+            Token::Str(b"[CAPABILITY IMAP4rev1] Ready".to_vec()),
+        ]))
+    );
+    assert_eq!(
+        parse_one(b"A0001 ok LOGIN completed\r\n"),
+        Ok(Response::Tagged {
+            tag: "A0001".to_string(),
+            status: "OK".to_string(),
+            text: "LOGIN completed".to_string(),
+        })
+    );
+    assert_eq!(
+        parse_one(b"+ idling\r\n"),
+        Ok(Response::Continuation("idling".to_string()))
+    );
+    assert_eq!(parse_one(b"+\r\n"), Ok(Response::Continuation(String::new())));
+    assert_eq!(
+        parse_one(b"* 23 EXISTS\n"),
+        Ok(Response::Untagged(vec![
+            Token::Number(23),
+            Token::Atom("EXISTS".to_string()),
+        ]))
+    );
+}
+
+#[test]
+fn test_parse_data() {
+    assert_eq!(
+        parse_one(b"* LIST (\\HasNoChildren) \"/\" \"a \\\"b\\\"\"\r\n"),
+        Ok(Response::Untagged(vec![
+            Token::Atom("LIST".to_string()),
+            Token::List(vec![Token::Atom("\\HasNoChildren".to_string())]),
+            Token::Str(b"/".to_vec()),
+            Token::Str(b"a \"b\"".to_vec()),
+        ]))
+    );
+    assert_eq!(
+        parse_one(b"* 1 FETCH (UID 7 BODY[HEADER.FIELDS (TO)] {5}\r\nab\r\nc X NIL)\r\n"),
+        Ok(Response::Untagged(vec![
+            Token::Number(1),
+            Token::Atom("FETCH".to_string()),
+            Token::List(vec![
+                Token::Atom("UID".to_string()),
+                Token::Number(7),
+                Token::Atom("BODY[HEADER.FIELDS (TO)]".to_string()),
+                Token::Str(b"ab\r\nc".to_vec()),
+                Token::Atom("X".to_string()),
+                Token::Nil,
+            ]),
+        ]))
+    );
+}
+
+#[test]
+fn test_parse_responses() {
+    let input = b"* 1 FETCH (BODY[] {10}\r\n0123";
+    assert_eq!(parse_one(input), Err(ParseError::Incomplete));
+    assert_eq!(parse_responses(input), (vec![], 0));
+    let input = b"* 2 EXPUNGE\r\n* FOO (\r\n) line\r\nA1 OK done\r\n* 3 EX";
+    let (responses, consumed) = parse_responses(input);
+    assert_eq!(responses.len(), 2);
+    assert_eq!(
+        responses[1],
+        Response::Tagged {
+            tag: "A1".to_string(),
+            status: "OK".to_string(),
+            text: "done".to_string(),
+        }
+    );
+    assert_eq!(&input[consumed..], b"* 3 EX");
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote(b"INBOX"), Some(b"\"INBOX\"".to_vec()));
+    assert_eq!(quote(b"a\"b\\c"), Some(b"\"a\\\"b\\\\c\"".to_vec()));
+    assert_eq!(quote(b"a\r\nb"), None);
+}
+
+include!(concat!(env!("OUT_DIR"), "/imap_exports.rs"));
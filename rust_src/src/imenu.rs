@@ -0,0 +1,478 @@
+//! Native imenu indexes.
+//!
+//! `imenu-default-create-index-function' and which-func search the
+//! whole buffer with the Lisp regexps of `imenu-generic-expression'
+//! each time the index is wanted.  Here each language has a set of
+//! queries, patterns of the regex crate with a `name' group, that are
+//! compiled once and run over a copy of the text on a thread of its
+//! own.  The caller can start a job and collect the index later, so a
+//! timer never waits for the search; imenu.el keeps the index of the
+//! last text for each buffer.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use libc::{c_char, ptrdiff_t};
+use regex::bytes::Regex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_specified_string, maybe_quit, EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use symbols::symbol_name;
+use threads::ThreadState;
+
+/// How long to wait for a job before checking for quit.
+const WAIT_SLICE: u64 = 20;
+
+/// A category of definitions and the pattern that finds them.  Entries
+/// without a category go to the top level of the index.
+struct Query {
+    category: Option<&'static str>,
+    pattern: &'static str,
+}
+
+macro_rules! queries {
+    ($($category:expr => $pattern:expr,)*) => {
+        &[$(Query { category: $category, pattern: $pattern },)*]
+    };
+}
+
+static C_QUERIES: &[Query] = queries! {
+    Some("Macros") => r"(?m)^[ \t]*#[ \t]*define[ \t]+(?P<name>[A-Za-z_]\w*)",
+    Some("Types") => concat!(
+        r"(?m)^(?:typedef[ \t]+)?(?:struct|union|enum)",
+        r"[ \t]+(?P<name>[A-Za-z_]\w*)[ \t\n]*\{"
+    ),
+    // A name at the start of a line or after a type, followed by an
+    // argument list that isn't a declaration's.
+    None => r"(?m)^(?:[A-Za-z_][\w \t*]*[ \t*])?(?P<name>[A-Za-z_]\w*)[ \t]*\([^;{}]*$",
+};
+
+static EMACS_LISP_QUERIES: &[Query] = queries! {
+    Some("Variables") => concat!(
+        r"(?m)^[ \t]*\((?:defvar|defvar-local|defconst|defcustom)",
+        r"[ \t]+(?P<name>[^\s()]+)"
+    ),
+    Some("Types") => concat!(
+        r"(?m)^[ \t]*\((?:defgroup|defface|deftheme|define-error|define-widget",
+        r"|cl-defstruct|cl-deftype|defclass)[ \t]+'?\(?(?P<name>[^\s()]+)"
+    ),
+    None => concat!(
+        r"(?m)^[ \t]*\((?:defun|defmacro|defsubst|define-inline|defalias",
+        r"|cl-defun|cl-defmacro|cl-defgeneric|cl-defmethod",
+        r"|define-minor-mode|define-derived-mode|define-globalized-minor-mode)",
+        r"[ \t]+'?(?P<name>[^\s()]+)"
+    ),
+};
+
+static PYTHON_QUERIES: &[Query] = queries! {
+    Some("Classes") => r"(?m)^[ \t]*class[ \t]+(?P<name>\w+)",
+    None => r"(?m)^[ \t]*(?:async[ \t]+)?def[ \t]+(?P<name>\w+)",
+};
+
+static RUST_QUERIES: &[Query] = queries! {
+    Some("Modules") => r"(?m)^[ \t]*(?:pub(?:\([^)]*\))?[ \t]+)?mod[ \t]+(?P<name>\w+)",
+    Some("Types") => concat!(
+        r"(?m)^[ \t]*(?:pub(?:\([^)]*\))?[ \t]+)?",
+        r"(?:struct|enum|union|trait|type)[ \t]+(?P<name>\w+)"
+    ),
+    Some("Macros") => r"(?m)^[ \t]*macro_rules![ \t]*(?P<name>\w+)",
+    None => concat!(
+        r"(?m)^[ \t]*(?:pub(?:\([^)]*\))?[ \t]+)?(?:const[ \t]+)?(?:unsafe[ \t]+)?",
+        r#"(?:extern[ \t]+(?:"[^"]*"[ \t]+)?)?fn[ \t]+(?P<name>\w+)"#
+    ),
+};
+
+/// The languages with built-in queries.
+static LANGUAGES: &[(&str, &[Query])] = &[
+    ("c", C_QUERIES),
+    ("emacs-lisp", EMACS_LISP_QUERIES),
+    ("python", PYTHON_QUERIES),
+    ("rust", RUST_QUERIES),
+];
+
+/// A query compiled for a job.
+struct Compiled {
+    category: Option<Vec<u8>>,
+    regex: Regex,
+}
+
+/// A definition found, with the offset of its name in characters.
+#[derive(Debug, PartialEq)]
+struct Item {
+    category: Option<Vec<u8>>,
+    name: Vec<u8>,
+    position: usize,
+}
+
+struct Job {
+    multibyte: bool,
+    /// The start of the text searched.
+    begv: EmacsInt,
+    receiver: Receiver<Vec<Item>>,
+    /// The items once they have arrived.
+    items: Option<Vec<Item>>,
+}
+
+lazy_static! {
+    static ref NEXT_JOB: Mutex<EmacsInt> = Mutex::new(0);
+    static ref JOBS: Mutex<HashMap<EmacsInt, Job>> = Mutex::new(HashMap::new());
+    /// The built-in queries, compiled on first use.
+    static ref BUILT_IN: Mutex<HashMap<&'static str, Vec<(Option<&'static str>, Regex)>>> =
+        Mutex::new(HashMap::new());
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+    if regex.capture_names().any(|name| name == Some("name")) {
+        Ok(regex)
+    } else {
+        Err(format!("Query without a `name' group: {}", pattern))
+    }
+}
+
+/// Return the queries for LANGUAGE, compiled.
+fn built_in_queries(language: &str) -> Option<Vec<Compiled>> {
+    let (key, queries) = match LANGUAGES.iter().find(|&&(name, _)| name == language) {
+        Some(&(name, queries)) => (name, queries),
+        None => return None,
+    };
+    let mut built_in = BUILT_IN.lock().unwrap();
+    let compiled = built_in.entry(key).or_insert_with(|| {
+        queries
+            .iter()
+            .map(|query| (query.category, compile(query.pattern).unwrap()))
+            .collect()
+    });
+    Some(
+        compiled
+            .iter()
+            .map(|&(category, ref regex)| Compiled {
+                category: category.map(|c| c.as_bytes().to_vec()),
+                regex: regex.clone(),
+            })
+            .collect(),
+    )
+}
+
+/// Return the queries of LANGUAGE, a symbol naming built-in queries or
+/// a list of (CATEGORY . PATTERN).
+fn lisp_queries(language: LispObject) -> Vec<Compiled> {
+    if language.is_symbol() {
+        let name = symbol_name(language);
+        let name = String::from_utf8_lossy(name.as_string_or_error().as_slice()).into_owned();
+        return match built_in_queries(&name) {
+            Some(queries) => queries,
+            None => error!("No imenu queries for {}", name),
+        };
+    }
+    let mut queries = Vec::new();
+    for query in language.iter_cars_safe() {
+        let query = query.as_cons_or_error();
+        let category = query.car();
+        let category = if category.is_nil() {
+            None
+        } else {
+            Some(category.as_string_or_error().as_slice().to_vec())
+        };
+        let pattern = query.cdr().as_string_or_error();
+        match compile(&String::from_utf8_lossy(pattern.as_slice())) {
+            Ok(regex) => queries.push(Compiled { category, regex }),
+            Err(err) => error!("Invalid imenu query: {}", err),
+        }
+    }
+    queries
+}
+
+/// Run QUERIES over TEXT and return the items found, in order of their
+/// positions.  TEXT is in Emacs' internal representation if MULTIBYTE,
+/// otherwise each byte is a character.
+fn find_items(queries: &[Compiled], text: &[u8], multibyte: bool) -> Vec<Item> {
+    let mut found = Vec::new();
+    for query in queries {
+        for captures in query.regex.captures_iter(text) {
+            if let Some(name) = captures.name("name") {
+                found.push((name.start(), query.category.clone(), name.as_bytes().to_vec()));
+            }
+        }
+    }
+    found.sort_by_key(|&(offset, _, _)| offset);
+
+    // Turn byte offsets into character offsets in one pass.
+    let mut items = Vec::with_capacity(found.len());
+    let mut byte = 0;
+    let mut chars = 0;
+    for (offset, category, name) in found {
+        if multibyte {
+            chars += text[byte..offset]
+                .iter()
+                .filter(|&&b| b & 0xc0 != 0x80)
+                .count();
+        } else {
+            chars += offset - byte;
+        }
+        byte = offset;
+        items.push(Item {
+            category,
+            name,
+            position: chars,
+        });
+    }
+    items
+}
+
+fn item_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    LispObject::from(unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            -1,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    })
+}
+
+/// Return the imenu index alist of ITEMS: an alist of (CATEGORY ENTRY
+/// ...) for the categories, in the order they first occur, followed by
+/// the entries without one.  Each entry is (NAME . POSITION).
+fn index_alist(items: &[Item], multibyte: bool, begv: EmacsInt) -> LispObject {
+    let mut categories: Vec<(&[u8], Vec<LispObject>)> = Vec::new();
+    let mut top_level = Vec::new();
+    for item in items {
+        let entry = LispObject::cons(
+            item_string(&item.name, multibyte),
+            LispObject::from_natnum(begv + item.position as EmacsInt),
+        );
+        match item.category {
+            None => top_level.push(entry),
+            Some(ref category) => {
+                match categories.iter().position(|&(c, _)| c == &category[..]) {
+                    Some(i) => categories[i].1.push(entry),
+                    None => categories.push((&category[..], vec![entry])),
+                }
+            }
+        }
+    }
+    let list = |entries: Vec<LispObject>, tail: LispObject| {
+        entries
+            .into_iter()
+            .rev()
+            .fold(tail, |rest, entry| LispObject::cons(entry, rest))
+    };
+    let submenus: Vec<LispObject> = categories
+        .into_iter()
+        .map(|(category, entries)| {
+            LispObject::cons(
+                item_string(category, multibyte),
+                list(entries, LispObject::constant_nil()),
+            )
+        })
+        .collect();
+    let top_level = list(top_level, LispObject::constant_nil());
+    list(submenus, top_level)
+}
+
+fn job_id(job: LispObject) -> EmacsInt {
+    let id = job.as_fixnum_or_error();
+    if !JOBS.lock().unwrap().contains_key(&id) {
+        error!("No such imenu job")
+    }
+    id
+}
+
+/// Return whether job ID has found its items, storing them if they have
+/// just arrived.
+fn job_finished(id: EmacsInt) -> bool {
+    let mut jobs = JOBS.lock().unwrap();
+    let job = jobs.get_mut(&id).unwrap();
+    if job.items.is_none() {
+        job.items = match job.receiver.try_recv() {
+            Ok(items) => Some(items),
+            Err(TryRecvError::Empty) => None,
+            // The search panicked; there is nothing to show.
+            Err(TryRecvError::Disconnected) => Some(Vec::new()),
+        };
+    }
+    job.items.is_some()
+}
+
+/// Return a list of the languages with built-in queries for
+/// `imenu-build-index-native'.
+#[lisp_fn]
+pub fn imenu_native_languages() -> LispObject {
+    LANGUAGES
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, &(name, _)| {
+            LispObject::cons(intern(name), list)
+        })
+}
+
+/// Return the imenu index of the accessible portion of the current
+/// buffer, found with the queries for LANGUAGE.
+///
+/// LANGUAGE is one of `imenu-native-languages', or a list of queries
+/// (CATEGORY . PATTERN).  PATTERN is a regexp in the syntax of the Rust
+/// regex crate, with a group called `name' around the name of each
+/// definition.  The definitions found by queries with a CATEGORY string
+/// are gathered under that title; a nil CATEGORY puts them at the top
+/// level of the index.  Entries are (NAME . POSITION), as
+/// `imenu--index-alist' describes.
+///
+/// The text is copied and searched on a separate thread.  If NOWAIT is
+/// nil, wait for the search and return the index.  Otherwise return a
+/// job at once, an integer to pass to `imenu-native-job-finished-p',
+/// `imenu-native-job-index' and `imenu-native-job-cancel'.
+#[lisp_fn(min = "1")]
+pub fn imenu_build_index_native(language: LispObject, nowait: LispObject) -> LispObject {
+    let queries = lisp_queries(language);
+    let buffer = ThreadState::current_buffer();
+    let multibyte = LispObject::from(buffer.enable_multibyte_characters).is_not_nil();
+    let text: Vec<u8> = (buffer.begv_byte..buffer.zv_byte)
+        .map(|pos| buffer.fetch_byte(pos))
+        .collect();
+
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        // The receiver is gone if the job was cancelled.
+        let _ = sender.send(find_items(&queries, &text, multibyte));
+    });
+    let id = {
+        let mut next = NEXT_JOB.lock().unwrap();
+        *next += 1;
+        *next
+    };
+    JOBS.lock().unwrap().insert(
+        id,
+        Job {
+            multibyte,
+            begv: buffer.begv as EmacsInt,
+            receiver,
+            items: None,
+        },
+    );
+
+    let job = LispObject::from_fixnum(id);
+    if nowait.is_nil() {
+        imenu_native_job_index(job)
+    } else {
+        job
+    }
+}
+
+/// Return t if the imenu JOB has finished its search.
+#[lisp_fn]
+pub fn imenu_native_job_finished_p(job: LispObject) -> LispObject {
+    LispObject::from_bool(job_finished(job_id(job)))
+}
+
+/// Return the index found by the imenu JOB, waiting for it if need be.
+/// The job is done with afterwards.  See `imenu-build-index-native'.
+#[lisp_fn]
+pub fn imenu_native_job_index(job: LispObject) -> LispObject {
+    let id = job_id(job);
+    while !job_finished(id) {
+        let arrived = {
+            let jobs = JOBS.lock().unwrap();
+            match jobs[&id]
+                .receiver
+                .recv_timeout(Duration::from_millis(WAIT_SLICE))
+            {
+                Ok(items) => Some(items),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => Some(Vec::new()),
+            }
+        };
+        match arrived {
+            Some(items) => JOBS.lock().unwrap().get_mut(&id).unwrap().items = Some(items),
+            None => unsafe { maybe_quit() },
+        }
+    }
+    let job = JOBS.lock().unwrap().remove(&id).unwrap();
+    index_alist(&job.items.unwrap(), job.multibyte, job.begv)
+}
+
+/// Forget the imenu JOB, whose index is no longer wanted.
+#[lisp_fn]
+pub fn imenu_native_job_cancel(job: LispObject) -> LispObject {
+    let id = job.as_fixnum_or_error();
+    JOBS.lock().unwrap().remove(&id);
+    LispObject::constant_nil()
+}
+
+#[cfg(test)]
+fn names(items: &[Item]) -> Vec<(Option<&str>, &str, usize)> {
+    items
+        .iter()
+        .map(|item| {
+            (
+                item.category
+                    .as_ref()
+                    .map(|c| ::std::str::from_utf8(c).unwrap()),
+                ::std::str::from_utf8(&item.name).unwrap(),
+                item.position,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_built_in_queries() {
+    for &(language, _) in LANGUAGES {
+        assert!(built_in_queries(language).is_some());
+    }
+    assert!(built_in_queries("cobol").is_none());
+    assert!(compile(r"fn (\w+)").is_err());
+}
+
+#[test]
+fn test_find_items() {
+    let text = b"use std;\n\npub struct Point;\n\nfn main() {}\n\n\
+                 mod tests {\n    fn helper() {}\n}\n";
+    let items = find_items(&built_in_queries("rust").unwrap(), text, true);
+    assert_eq!(
+        names(&items),
+        vec![
+            (Some("Types"), "Point", 21),
+            (None, "main", 32),
+            (Some("Modules"), "tests", 47),
+            (None, "helper", 62),
+        ]
+    );
+
+    let text = b"class A:\n    def f(self):\n        pass\n\ndef g():\n    pass\n";
+    let items = find_items(&built_in_queries("python").unwrap(), text, true);
+    assert_eq!(
+        names(&items),
+        vec![(Some("Classes"), "A", 6), (None, "f", 17), (None, "g", 44)]
+    );
+
+    let text = "(defvar é 1)\n(defun f ()\n  nil)\n".as_bytes();
+    let items = find_items(&built_in_queries("emacs-lisp").unwrap(), text, true);
+    assert_eq!(
+        names(&items),
+        vec![(Some("Variables"), "é", 8), (None, "f", 20)]
+    );
+}
+
+#[test]
+fn test_find_items_c() {
+    let text = b"#define MAX 10\n\nstruct point {\n  int x;\n};\n\n\
+                 int foo (void);\n\nstatic int\nbar (int x)\n{\n  return x;\n}\n";
+    let items = find_items(&built_in_queries("c").unwrap(), text, false);
+    assert_eq!(
+        names(&items),
+        vec![
+            (Some("Macros"), "MAX", 8),
+            (Some("Types"), "point", 23),
+            (None, "bar", 72),
+        ]
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/imenu_exports.rs"));
@@ -1,9 +1,13 @@
 //! Indentation functions
 
+use std::collections::BTreeMap;
+
 use remacs_macros::lisp_fn;
 use remacs_sys;
+use remacs_sys::EmacsInt;
 
-use lisp::LispObject;
+use buffers::LispBufferRef;
+use lisp::{intern, LispObject};
 use lisp::defsubr;
 
 /// Return the horizontal position of point.
@@ -24,4 +28,230 @@ pub fn current_column() -> LispObject {
     LispObject::from_natnum(unsafe { remacs_sys::current_column() })
 }
 
+/// Counts of the kinds of indentation found in some text.
+#[derive(Default, Debug)]
+struct IndentationStats {
+    lines: usize,
+    blank: usize,
+    unindented: usize,
+    tabs: usize,
+    spaces: usize,
+    mixed: usize,
+    trailing_whitespace: usize,
+    /// How many space-indented lines start with each number of spaces.
+    widths: BTreeMap<usize, usize>,
+    /// How often the indentation of a non-blank line exceeds that of
+    /// the previous non-blank line by each number of columns.
+    deltas: BTreeMap<usize, usize>,
+}
+
+impl IndentationStats {
+    /// Gather statistics about the lines of TEXT, with tabs stopping
+    /// every TAB_WIDTH columns.
+    fn scan<I: Iterator<Item = u8>>(text: I, tab_width: usize) -> IndentationStats {
+        let mut stats = IndentationStats::default();
+        let mut previous: Option<usize> = None;
+        let (mut at_start, mut column, mut tabs, mut spaces) = (true, 0, false, false);
+        let (mut empty, mut last) = (true, b'\n');
+        for byte in text {
+            if byte == b'\n' {
+                stats.end_line(empty, at_start, tabs, spaces, last);
+                if !empty && !at_start {
+                    previous = stats.record_column(column, spaces && !tabs, previous);
+                }
+                at_start = true;
+                column = 0;
+                tabs = false;
+                spaces = false;
+                empty = true;
+            } else {
+                empty = false;
+                if at_start && byte == b' ' {
+                    spaces = true;
+                    column += 1;
+                } else if at_start && byte == b'\t' {
+                    tabs = true;
+                    column = (column / tab_width + 1) * tab_width;
+                } else if at_start {
+                    at_start = false;
+                }
+            }
+            last = byte;
+        }
+        if !empty {
+            stats.end_line(false, at_start, tabs, spaces, last);
+            if !at_start {
+                stats.record_column(column, spaces && !tabs, previous);
+            }
+        }
+        stats
+    }
+
+    /// Count a line that has ended.  It is blank if it is EMPTY or still
+    /// AT_START, that is, holds nothing but whitespace.
+    fn end_line(&mut self, empty: bool, at_start: bool, tabs: bool, spaces: bool, last: u8) {
+        self.lines += 1;
+        if empty || at_start {
+            self.blank += 1;
+            return;
+        }
+        if last == b' ' || last == b'\t' {
+            self.trailing_whitespace += 1;
+        }
+        match (tabs, spaces) {
+            (false, false) => self.unindented += 1,
+            (true, false) => self.tabs += 1,
+            (false, true) => self.spaces += 1,
+            (true, true) => self.mixed += 1,
+        }
+    }
+
+    fn record_column(
+        &mut self,
+        column: usize,
+        only_spaces: bool,
+        previous: Option<usize>,
+    ) -> Option<usize> {
+        if only_spaces {
+            *self.widths.entry(column).or_insert(0) += 1;
+        }
+        if let Some(previous) = previous {
+            if column > previous {
+                *self.deltas.entry(column - previous).or_insert(0) += 1;
+            }
+        }
+        Some(column)
+    }
+
+    /// Guess whether the text is indented with tabs, and by how many
+    /// columns each level of indentation is.
+    fn guess(&self) -> Option<(bool, usize)> {
+        if self.tabs + self.spaces + self.mixed == 0 {
+            return None;
+        }
+        let use_tabs = self.tabs + self.mixed > self.spaces;
+        // The most common step between consecutive lines, preferring
+        // the smaller when equally common.
+        let offset = self.deltas
+            .iter()
+            .filter(|&(&delta, _)| delta <= 8)
+            .fold(None, |best: Option<(usize, usize)>, (&delta, &count)| match best {
+                Some((_, best_count)) if best_count >= count => best,
+                _ => Some((delta, count)),
+            })
+            .map(|(delta, _)| delta);
+        offset.map(|offset| (use_tabs, offset))
+    }
+}
+
+fn buffer_tab_width(buffer: LispBufferRef) -> usize {
+    match LispObject::from(buffer.tab_width).as_fixnum() {
+        Some(n) if 0 < n && n <= 1000 => n as usize,
+        _ => 8,
+    }
+}
+
+fn buffer_stats(buffer: LispObject) -> IndentationStats {
+    let buffer = buffer.as_buffer_or_current_buffer();
+    let bytes = (buffer.begv_byte..buffer.zv_byte).map(|pos| buffer.fetch_byte(pos));
+    IndentationStats::scan(bytes, buffer_tab_width(buffer))
+}
+
+fn count_alist(counts: &BTreeMap<usize, usize>) -> LispObject {
+    counts
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |alist, (&key, &count)| {
+            LispObject::cons(
+                LispObject::cons(
+                    LispObject::from_natnum(key as EmacsInt),
+                    LispObject::from_natnum(count as EmacsInt),
+                ),
+                alist,
+            )
+        })
+}
+
+/// Return statistics about the indentation of the lines of BUFFER.
+/// BUFFER defaults to the current buffer; only its accessible portion is
+/// examined.  The value is a plist with these properties:
+///
+///  :lines     the number of lines
+///  :blank     the number of lines with nothing but whitespace
+///  :unindented  the number of other lines that are not indented
+///  :tabs      the number of lines indented with tabs only
+///  :spaces    the number of lines indented with spaces only
+///  :mixed     the number of lines indented with both
+///  :trailing  the number of non-blank lines ending in whitespace
+///  :widths    an alist (SPACES . COUNT) of how many lines are indented
+///             with each number of spaces
+///  :deltas    an alist (COLUMNS . COUNT) of how often a non-blank line
+///             is indented by each number of columns more than the
+///             non-blank line before it
+///
+/// Columns are counted with the buffer's `tab-width'.
+#[lisp_fn(min = "0")]
+pub fn buffer_indentation_stats(buffer: LispObject) -> LispObject {
+    let stats = buffer_stats(buffer);
+    let count = |n: usize| LispObject::from_natnum(n as EmacsInt);
+    list!(
+        intern(":lines"),
+        count(stats.lines),
+        intern(":blank"),
+        count(stats.blank),
+        intern(":unindented"),
+        count(stats.unindented),
+        intern(":tabs"),
+        count(stats.tabs),
+        intern(":spaces"),
+        count(stats.spaces),
+        intern(":mixed"),
+        count(stats.mixed),
+        intern(":trailing"),
+        count(stats.trailing_whitespace),
+        intern(":widths"),
+        count_alist(&stats.widths),
+        intern(":deltas"),
+        count_alist(&stats.deltas)
+    )
+}
+
+/// Guess the indentation style of BUFFER, or the current buffer.
+/// Return (INDENT-TABS-MODE . OFFSET), where INDENT-TABS-MODE says
+/// whether more lines are indented with tabs than with spaces only, and
+/// OFFSET is the most common number of columns by which a line is
+/// indented more than the line before it, up to 8.  Return nil if the
+/// buffer has too little indentation to tell.
+#[lisp_fn(min = "0")]
+pub fn detect_indentation(buffer: LispObject) -> LispObject {
+    match buffer_stats(buffer).guess() {
+        Some((use_tabs, offset)) => LispObject::cons(
+            LispObject::from_bool(use_tabs),
+            LispObject::from_natnum(offset as EmacsInt),
+        ),
+        None => LispObject::constant_nil(),
+    }
+}
+
+#[test]
+fn test_indentation_stats() {
+    let text = b"int f()\n{\n    if (x) {\n        y;   \n    }\n\n\treturn;\n  \n}";
+    let stats = IndentationStats::scan(text.iter().cloned(), 8);
+    assert_eq!(stats.lines, 9);
+    assert_eq!(stats.blank, 2);
+    assert_eq!(stats.unindented, 3);
+    assert_eq!(stats.spaces, 3);
+    assert_eq!(stats.tabs, 1);
+    assert_eq!(stats.mixed, 0);
+    assert_eq!(stats.trailing_whitespace, 1);
+    assert_eq!(stats.widths.get(&4), Some(&2));
+    assert_eq!(stats.widths.get(&8), Some(&1));
+    assert_eq!(stats.deltas.get(&4), Some(&3));
+    assert_eq!(stats.guess(), Some((false, 4)));
+
+    let stats = IndentationStats::scan(b"a\n\tb\n\t\tc\n".iter().cloned(), 4);
+    assert_eq!(stats.guess(), Some((true, 4)));
+    assert_eq!(IndentationStats::scan(b"a\nb".iter().cloned(), 8).guess(), None);
+}
+
 include!(concat!(env!("OUT_DIR"), "/indent_exports.rs"));
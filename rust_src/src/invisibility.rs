@@ -0,0 +1,217 @@
+//! An index of the invisible text of buffers.
+//!
+//! Skipping folded text by following changes of the `invisible'
+//! property costs a scan of every property change in between, which
+//! makes outline navigation slow in huge buffers.  Here the invisible
+//! ranges of a buffer are found once and kept sorted, so that the next
+//! or previous visible position is found by a binary search.  The index
+//! is made again when the text properties, the overlays, the narrowing
+//! or the invisibility spec of the buffer change.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{EmacsInt, Lisp_Buffer, Lisp_Object};
+
+use buffers::LispBufferRef;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use threads::ThreadState;
+
+/// What the index of a buffer was made from.  The text properties and
+/// the overlays of the buffer are counted by its modification ticks;
+/// its invisibility spec is kept as the elements of the list and the
+/// cars and cdrs of its conses, so that a copy of the list with the
+/// same elements does not count as a change.
+#[derive(PartialEq)]
+struct Source {
+    modiff: EmacsInt,
+    overlay_modiff: EmacsInt,
+    begv: EmacsInt,
+    zv: EmacsInt,
+    spec: Vec<(Lisp_Object, Lisp_Object)>,
+}
+
+impl Source {
+    fn of(buffer: LispBufferRef) -> Source {
+        let spec = LispObject::from(buffer.invisibility_spec);
+        let spec = if spec.is_cons() {
+            spec.iter_cars_safe()
+                .map(|element| match element.as_cons() {
+                    Some(cons) => (cons.car().to_raw(), cons.cdr().to_raw()),
+                    None => (element.to_raw(), LispObject::constant_nil().to_raw()),
+                })
+                .collect()
+        } else {
+            vec![(spec.to_raw(), LispObject::constant_nil().to_raw())]
+        };
+        Source {
+            modiff: buffer.modifications(),
+            overlay_modiff: buffer.overlay_modifications(),
+            begv: buffer.begv as EmacsInt,
+            zv: buffer.zv() as EmacsInt,
+            spec,
+        }
+    }
+}
+
+/// The invisible ranges of the accessible portion of a buffer, as
+/// sorted, disjoint and non-adjacent (START . END) pairs.
+struct Index {
+    source: Source,
+    ranges: Vec<(EmacsInt, EmacsInt)>,
+}
+
+impl Index {
+    /// Find the invisible ranges of the current buffer.
+    fn make(source: Source) -> Index {
+        let property = intern("invisible");
+        let mut ranges: Vec<(EmacsInt, EmacsInt)> = Vec::new();
+        let mut position = source.begv;
+        while position < source.zv {
+            let start = LispObject::from_fixnum(position);
+            let next = call!(intern("next-single-char-property-change"), start, property)
+                .as_fixnum_or_error();
+            if call!(intern("invisible-p"), start).is_not_nil() {
+                match ranges.last_mut() {
+                    Some(last) if last.1 == position => last.1 = next,
+                    _ => ranges.push((position, next)),
+                }
+            }
+            position = next;
+        }
+        Index { source, ranges }
+    }
+
+    /// Return the invisible range that contains the character at
+    /// POSITION, if there is one.
+    fn range_at(&self, position: EmacsInt) -> Option<(EmacsInt, EmacsInt)> {
+        let i = match self.ranges.binary_search_by_key(&position, |range| range.0) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let range = self.ranges[i];
+        if position < range.1 {
+            Some(range)
+        } else {
+            None
+        }
+    }
+
+    /// Return POSITION, or the end of the invisible text at it.
+    fn next_visible(&self, position: EmacsInt) -> EmacsInt {
+        self.range_at(position).map_or(position, |range| range.1)
+    }
+
+    /// Return POSITION, or the start of the invisible text before it.
+    fn previous_visible(&self, position: EmacsInt) -> EmacsInt {
+        self.range_at(position - 1).map_or(position, |range| range.0)
+    }
+}
+
+lazy_static! {
+    static ref INDEXES: Mutex<HashMap<usize, Index>> = Mutex::new(HashMap::new());
+}
+
+/// Forget the index of BUFFER.  Called when BUFFER is killed.
+#[no_mangle]
+pub extern "C" fn forget_invisible_ranges(buffer: *const Lisp_Buffer) {
+    INDEXES.lock().unwrap().remove(&(buffer as usize));
+}
+
+/// Call F with the index of the current buffer, made again if it is out
+/// of date.
+fn with_index<T, F: FnOnce(&Index) -> T>(f: F) -> T {
+    let buffer = ThreadState::current_buffer();
+    let key = buffer.as_ptr() as usize;
+    let source = Source::of(buffer);
+    let current = INDEXES
+        .lock()
+        .unwrap()
+        .get(&key)
+        .map_or(false, |index| index.source == source);
+    if !current {
+        // `invisible-p' can run Lisp, so the lock is not held meanwhile.
+        let index = Index::make(source);
+        INDEXES.lock().unwrap().insert(key, index);
+    }
+    f(&INDEXES.lock().unwrap()[&key])
+}
+
+/// Return POSITION, a number or marker, clipped to the accessible
+/// portion of the current buffer.
+fn clip_position(position: LispObject) -> EmacsInt {
+    let buffer = ThreadState::current_buffer();
+    let position = position.as_fixnum_coerce_marker_or_error();
+    position
+        .max(buffer.begv as EmacsInt)
+        .min(buffer.zv() as EmacsInt)
+}
+
+/// Return the first position at or after POS where the text is visible.
+/// This is POS itself unless the character after POS is invisible, as
+/// `invisible-p' says, in which case it is the end of the invisible text.
+/// The optional second argument LIMIT bounds the search; if the text up
+/// to LIMIT is all invisible, return LIMIT.  The invisible text of the
+/// buffer is indexed, so that this is fast however many invisible
+/// regions there are; the index is made again after any change of the
+/// text properties, overlays or `buffer-invisibility-spec'.
+#[lisp_fn(min = "1")]
+pub fn next_visible_position(pos: LispObject, limit: LispObject) -> LispObject {
+    let position = clip_position(pos);
+    let next = with_index(|index| index.next_visible(position));
+    let next = if limit.is_nil() {
+        next
+    } else {
+        next.min(clip_position(limit).max(position))
+    };
+    LispObject::from_fixnum(next)
+}
+
+/// Return the last position at or before POS where the text is visible.
+/// This is POS itself unless the character before POS is invisible, as
+/// `invisible-p' says, in which case it is the start of the invisible
+/// text.  The optional second argument LIMIT bounds the search; if the
+/// text from LIMIT is all invisible, return LIMIT.  See also
+/// `next-visible-position'.
+#[lisp_fn(min = "1")]
+pub fn previous_visible_position(pos: LispObject, limit: LispObject) -> LispObject {
+    let position = clip_position(pos);
+    let previous = with_index(|index| index.previous_visible(position));
+    let previous = if limit.is_nil() {
+        previous
+    } else {
+        previous.max(clip_position(limit).min(position))
+    };
+    LispObject::from_fixnum(previous)
+}
+
+#[test]
+fn test_visible_positions() {
+    let index = Index {
+        source: Source {
+            modiff: 0,
+            overlay_modiff: 0,
+            begv: 1,
+            zv: 100,
+            spec: Vec::new(),
+        },
+        ranges: vec![(5, 10), (20, 30), (99, 100)],
+    };
+    assert_eq!(index.next_visible(1), 1);
+    assert_eq!(index.next_visible(5), 10);
+    assert_eq!(index.next_visible(9), 10);
+    assert_eq!(index.next_visible(10), 10);
+    assert_eq!(index.next_visible(25), 30);
+    assert_eq!(index.next_visible(99), 100);
+    assert_eq!(index.previous_visible(5), 5);
+    assert_eq!(index.previous_visible(6), 5);
+    assert_eq!(index.previous_visible(10), 5);
+    assert_eq!(index.previous_visible(11), 11);
+    assert_eq!(index.previous_visible(30), 20);
+    assert_eq!(index.previous_visible(1), 1);
+}
+
+include!(concat!(env!("OUT_DIR"), "/invisibility_exports.rs"));
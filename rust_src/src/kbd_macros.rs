@@ -0,0 +1,686 @@
+//! Keyboard macros.
+//!
+//! While a macro is being defined, `store_kbd_macro_char' records each
+//! event read from the keyboard for the current kboard.  The events of
+//! a command only become part of the macro once the command is done
+//! (see `finalize_kbd_macro_chars'), so that the keys of `end-kbd-macro'
+//! itself can be left out and `cancel-kbd-macro-events' can drop them.
+//!
+//! A macro is executed by running the command loop with
+//! `executing-kbd-macro' set, which makes `read_char' take its events
+//! from the macro instead of the keyboard.  Events that were already
+//! waiting in `unread-command-events' when the macro started belong to
+//! whatever comes after it, so they are held back until it is done.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use libc::{c_char, c_void, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{command_loop_1, current_kboard, executing_kbd_macro,
+                 executing_kbd_macro_iterations, globals, make_event_array, mark_object,
+                 maybe_quit, message1, record_unwind_protect, unbind_to, update_mode_lines};
+use remacs_sys::{EmacsInt, Fset, Lisp_Object, Qarrayp, Qnil, CHAR_META};
+
+use data::indirect_function_1;
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::list;
+use symbols::symbol_value;
+
+/// The events recorded for the macro being defined on a kboard.
+struct Recording {
+    events: Vec<LispObject>,
+    /// How many of the events belong to the macro.  The rest were read
+    /// by the command still running.
+    end: usize,
+}
+
+lazy_static! {
+    /// The recordings, by the address of their kboard.
+    static ref RECORDINGS: Mutex<HashMap<usize, Recording>> = Mutex::new(HashMap::new());
+}
+
+/// Call F with the recording of the current kboard.
+fn with_recording<T, F: FnOnce(&mut Recording) -> T>(f: F) -> T {
+    let mut recordings = RECORDINGS.lock().unwrap();
+    let recording = recordings
+        .entry(unsafe { current_kboard } as usize)
+        .or_insert_with(|| Recording {
+            events: Vec::new(),
+            end: 0,
+        });
+    f(recording)
+}
+
+/// Return the value of the terminal-local variable NAME.
+fn kboard_value(name: &str) -> LispObject {
+    symbol_value(intern(name))
+}
+
+fn set_kboard_value(name: &str, value: LispObject) {
+    unsafe { Fset(intern(name).to_raw(), value.to_raw()) };
+}
+
+fn message(text: &[u8]) {
+    unsafe { message1(text.as_ptr() as *const c_char) };
+}
+
+/// Return the events of the keyboard macro MACRO, a string or vector.
+/// Characters of a string with the 0x80 bit set stand for meta
+/// characters, as in `read_char'.
+fn macro_events(macro_: LispObject) -> Vec<LispObject> {
+    if macro_.is_string() {
+        call!(intern("append"), macro_, LispObject::constant_nil())
+            .iter_cars()
+            .map(|c| match c.as_fixnum() {
+                Some(n) if n >= 0x80 && n <= 0xff => {
+                    LispObject::from_natnum(CHAR_META as EmacsInt | (n & !0x80))
+                }
+                _ => c,
+            })
+            .collect()
+    } else if let Some(vector) = macro_.as_vectorlike().and_then(|v| v.as_vector()) {
+        vector.as_slice().to_vec()
+    } else {
+        wrong_type!(Qarrayp, macro_)
+    }
+}
+
+/// Store event C in the keyboard macro being defined, if any.
+#[no_mangle]
+pub extern "C" fn store_kbd_macro_char(c: Lisp_Object) {
+    if kboard_value("defining-kbd-macro").is_not_nil() {
+        with_recording(|recording| recording.events.push(LispObject::from(c)));
+    }
+}
+
+/// Declare that all the events stored so far in the keyboard macro
+/// being defined really belong to it.  This is done in between editor
+/// commands.
+#[no_mangle]
+pub extern "C" fn finalize_kbd_macro_chars() {
+    with_recording(|recording| recording.end = recording.events.len());
+}
+
+/// Finish defining the current keyboard macro.
+#[no_mangle]
+pub extern "C" fn end_kbd_macro() {
+    set_kboard_value("defining-kbd-macro", LispObject::constant_nil());
+    unsafe { update_mode_lines = 20 };
+    let mut events: Vec<Lisp_Object> = with_recording(|recording| {
+        recording.events[..recording.end]
+            .iter()
+            .map(|event| event.to_raw())
+            .collect()
+    });
+    let value = unsafe { make_event_array(events.len() as ptrdiff_t, events.as_mut_ptr()) };
+    set_kboard_value("last-kbd-macro", LispObject::from(value));
+}
+
+/// Mark the events of the keyboard macros being defined.
+/// Called by `mark_kboards'.
+#[no_mangle]
+pub extern "C" fn mark_kbd_macros() {
+    for recording in RECORDINGS.lock().unwrap().values() {
+        for event in &recording.events {
+            unsafe { mark_object(event.to_raw()) };
+        }
+    }
+}
+
+/// Forget the keyboard macro being defined on the kboard KB, which is
+/// being deleted.
+#[no_mangle]
+pub extern "C" fn forget_kbd_macro(kb: *const c_void) {
+    RECORDINGS.lock().unwrap().remove(&(kb as usize));
+}
+
+/// Record subsequent keyboard input, defining a keyboard macro.
+/// The commands are recorded even as they are executed.
+/// Use \\[end-kbd-macro] to finish recording and make the macro available.
+/// Use \\[name-last-kbd-macro] to give it a permanent name.
+/// Non-nil arg (prefix arg) means append to last macro defined;
+/// this begins by re-executing that macro as if you typed it again.
+/// If optional second arg, NO-EXEC, is non-nil, do not re-execute last
+/// macro before appending to it.
+#[lisp_fn(min = "1", intspec = "P")]
+pub fn start_kbd_macro(append: LispObject, no_exec: LispObject) -> LispObject {
+    if kboard_value("defining-kbd-macro").is_not_nil() {
+        error!("Already defining kbd macro");
+    }
+    unsafe { update_mode_lines = 19 };
+    if append.is_nil() {
+        with_recording(|recording| {
+            recording.events.clear();
+            recording.end = 0;
+        });
+        message(b"Defining kbd macro...\0");
+    } else {
+        // Copy last-kbd-macro into the recording, in case the Lisp code
+        // has put another macro there.
+        let last = kboard_value("last-kbd-macro");
+        let events = macro_events(last);
+        with_recording(|recording| {
+            recording.end = events.len();
+            recording.events = events;
+        });
+
+        // Re-execute the macro we are appending to, for consistency of
+        // behavior.
+        if no_exec.is_nil() {
+            execute_kbd_macro(last, LispObject::from_natnum(1), LispObject::constant_nil());
+        }
+        message(b"Appending to kbd macro...\0");
+    }
+    set_kboard_value("defining-kbd-macro", LispObject::constant_t());
+    LispObject::constant_nil()
+}
+
+/// Finish defining a keyboard macro.
+/// The definition was started by \\[start-kbd-macro].
+/// The macro is now available for use via \\[call-last-kbd-macro],
+/// or it can be given a name with \\[name-last-kbd-macro] and then invoked
+/// under that name.
+///
+/// With numeric arg, repeat macro now that many times,
+/// counting the definition just completed as the first repetition.
+/// An argument of zero means repeat until error.
+///
+/// In Lisp, optional second arg LOOPFUNC may be a function that is called prior to
+/// each iteration of the macro.  Iteration stops if LOOPFUNC returns nil.
+#[lisp_fn(min = "0", intspec = "p", c_name = "end_kbd_macro", name = "end-kbd-macro")]
+pub fn end_kbd_macro_lisp(repeat: LispObject, loopfunc: LispObject) -> LispObject {
+    if kboard_value("defining-kbd-macro").is_nil() {
+        error!("Not defining kbd macro");
+    }
+    let repeat = if repeat.is_nil() {
+        1
+    } else {
+        repeat.as_fixnum_or_error()
+    };
+    end_kbd_macro();
+    message(b"Keyboard macro defined\0");
+
+    let last = kboard_value("last-kbd-macro");
+    if repeat == 0 {
+        execute_kbd_macro(last, LispObject::from_fixnum(0), loopfunc);
+    } else if repeat > 1 {
+        execute_kbd_macro(last, LispObject::from_fixnum(repeat - 1), loopfunc);
+    }
+    LispObject::constant_nil()
+}
+
+/// Cancel the events added to a keyboard macro for this command.
+#[lisp_fn]
+pub fn cancel_kbd_macro_events() -> LispObject {
+    with_recording(|recording| {
+        let end = recording.end;
+        recording.events.truncate(end);
+    });
+    LispObject::constant_nil()
+}
+
+/// Store EVENT into the keyboard macro being defined.
+#[lisp_fn]
+pub fn store_kbd_macro_event(event: LispObject) -> LispObject {
+    store_kbd_macro_char(event.to_raw());
+    LispObject::constant_nil()
+}
+
+/// Call the last keyboard macro that you defined with \\[start-kbd-macro].
+///
+/// A prefix argument serves as a repeat count.  Zero means repeat until error.
+///
+/// To make a macro permanent so you can call it even after
+/// defining others, use \\[name-last-kbd-macro].
+///
+/// In Lisp, optional second arg LOOPFUNC may be a function that is called prior to
+/// each iteration of the macro.  Iteration stops if LOOPFUNC returns nil.
+#[lisp_fn(min = "0", intspec = "p")]
+pub fn call_last_kbd_macro(prefix: LispObject, loopfunc: LispObject) -> LispObject {
+    let last = kboard_value("last-kbd-macro");
+    unsafe {
+        // Don't interfere with recognition of the previous command from
+        // before this macro started.
+        globals.f_Vthis_command = kboard_value("last-command").to_raw();
+        // C-x z after the macro should repeat the macro.
+        globals.f_Vreal_this_command = last.to_raw();
+    }
+
+    if kboard_value("defining-kbd-macro").is_not_nil() {
+        error!("Can't execute anonymous macro while defining one");
+    } else if last.is_nil() {
+        error!("No kbd macro has been defined");
+    }
+    execute_kbd_macro(last, prefix, loopfunc);
+
+    // command_loop_1 sets this to nil before it returns; get back the
+    // last command within the macro so that it can be last, again,
+    // after we return.
+    unsafe { globals.f_Vthis_command = kboard_value("last-command").to_raw() };
+    LispObject::constant_nil()
+}
+
+/// Restore the state saved by `execute-kbd-macro' when it is done:
+/// `executing-kbd-macro', `executing-kbd-macro-index',
+/// `real-this-command' and the events that were unread before it.
+unsafe extern "C" fn pop_kbd_macro(info: Lisp_Object) {
+    let info: Vec<LispObject> = LispObject::from(info).iter_cars().collect();
+    globals.f_Vexecuting_kbd_macro = info[0].to_raw();
+    globals.f_executing_kbd_macro_index = info[1].as_fixnum_or_error();
+    globals.f_Vreal_this_command = info[2].to_raw();
+    // The events held back go after any the macro left unread.
+    globals.f_Vunread_command_events = call!(
+        intern("append"),
+        LispObject::from(globals.f_Vunread_command_events),
+        info[3]
+    ).to_raw();
+    call!(intern("run-hooks"), intern("kbd-macro-termination-hook"));
+}
+
+/// Execute MACRO as string of editor command characters.
+/// MACRO can also be a vector of keyboard events.  If MACRO is a symbol,
+/// its function definition is used.
+/// COUNT is a repeat count, or nil for once, or 0 for infinite loop.
+///
+/// Events in `unread-command-events' when the macro starts are not read
+/// by it; they are put back when it is done, after any events that it
+/// left unread.
+///
+/// Optional third arg LOOPFUNC may be a function that is called prior to
+/// each iteration of the macro.  Iteration stops if LOOPFUNC returns nil.
+#[lisp_fn(min = "1")]
+pub fn execute_kbd_macro(macro_: LispObject, count: LispObject, loopfunc: LispObject) -> LispObject {
+    let pdlcount = specpdl_index();
+    let mut repeat: EmacsInt = 1;
+    let mut success_count: EmacsInt = 0;
+
+    unsafe { executing_kbd_macro_iterations = 0 };
+
+    if count.is_not_nil() {
+        repeat = call!(intern("prefix-numeric-value"), count).as_fixnum_or_error();
+    }
+
+    let final_ = indirect_function_1(macro_);
+    if !final_.is_string() && !final_.is_vector() {
+        error!("Keyboard macros must be strings or vectors");
+    }
+
+    unsafe {
+        let info = list!(
+            LispObject::from(globals.f_Vexecuting_kbd_macro),
+            LispObject::from_fixnum(globals.f_executing_kbd_macro_index),
+            LispObject::from(globals.f_Vreal_this_command),
+            LispObject::from(globals.f_Vunread_command_events)
+        );
+        record_unwind_protect(pop_kbd_macro, info.to_raw());
+        globals.f_Vunread_command_events = Qnil;
+    }
+
+    loop {
+        unsafe {
+            globals.f_Vexecuting_kbd_macro = final_.to_raw();
+            executing_kbd_macro = final_.to_raw();
+            globals.f_executing_kbd_macro_index = 0;
+        }
+        set_kboard_value("prefix-arg", LispObject::constant_nil());
+
+        if loopfunc.is_not_nil() && call!(loopfunc).is_nil() {
+            break;
+        }
+
+        unsafe { command_loop_1() };
+
+        success_count += 1;
+        unsafe {
+            executing_kbd_macro_iterations = success_count;
+            maybe_quit();
+        }
+
+        // A count of 0 never gets back to 0, so it repeats until an
+        // error, or until something replaces the macro with t.
+        repeat -= 1;
+        let current = LispObject::from(unsafe { globals.f_Vexecuting_kbd_macro });
+        if repeat == 0 || !(current.is_string() || current.is_vector()) {
+            break;
+        }
+    }
+
+    unsafe {
+        executing_kbd_macro = Qnil;
+        globals.f_Vreal_this_command = globals.f_Vexecuting_kbd_macro;
+        unbind_to(pdlcount, Qnil);
+    }
+    LispObject::constant_nil()
+}
+
+/// The prefix argument typed so far in a keyboard macro, as in the
+/// variable `prefix-arg'.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Prefix {
+    Nothing,
+    /// (N), from C-u.
+    Raw(EmacsInt),
+    Number(EmacsInt),
+    /// The symbol `-'.
+    Minus,
+}
+
+impl Prefix {
+    /// Return the numeric value of the argument, as for
+    /// `prefix-numeric-value'.
+    fn numeric_value(self) -> EmacsInt {
+        match self {
+            Prefix::Nothing => 1,
+            Prefix::Raw(n) | Prefix::Number(n) => n,
+            Prefix::Minus => -1,
+        }
+    }
+
+    fn to_lisp(self) -> LispObject {
+        match self {
+            Prefix::Nothing => LispObject::constant_nil(),
+            Prefix::Raw(n) => list!(
+                intern("quote"),
+                list!(LispObject::from_fixnum(n))
+            ),
+            Prefix::Number(n) => LispObject::from_fixnum(n),
+            Prefix::Minus => list!(intern("quote"), intern("-")),
+        }
+    }
+}
+
+/// A key of a keyboard macro that makes up the prefix argument.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ArgumentKey {
+    /// `universal-argument', or `universal-argument-more' once the
+    /// argument has begun.
+    Universal,
+    Negative,
+    Digit(EmacsInt),
+}
+
+/// The prefix argument being typed, and whether the keys of
+/// `universal-argument-map' are in effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Argument {
+    prefix: Prefix,
+    active: bool,
+}
+
+impl Argument {
+    fn new() -> Argument {
+        Argument {
+            prefix: Prefix::Nothing,
+            active: false,
+        }
+    }
+
+    /// Do what the commands of the same names in simple.el do.
+    fn apply(&mut self, key: ArgumentKey) {
+        let (prefix, active) = match (key, self.prefix) {
+            (ArgumentKey::Universal, _) if !self.active => (Prefix::Raw(4), true),
+            (ArgumentKey::Universal, Prefix::Raw(n)) => (Prefix::Raw(4 * n), true),
+            (ArgumentKey::Universal, Prefix::Minus) => (Prefix::Raw(-4), true),
+            (ArgumentKey::Universal, prefix) => (prefix, false),
+            (ArgumentKey::Negative, Prefix::Number(n)) => (Prefix::Number(-n), true),
+            (ArgumentKey::Negative, Prefix::Minus) => (Prefix::Nothing, true),
+            (ArgumentKey::Negative, _) => (Prefix::Minus, true),
+            (ArgumentKey::Digit(d), Prefix::Number(n)) => {
+                (Prefix::Number(n * 10 + if n < 0 { -d } else { d }), true)
+            }
+            (ArgumentKey::Digit(0), Prefix::Minus) => (Prefix::Minus, true),
+            (ArgumentKey::Digit(d), Prefix::Minus) => (Prefix::Number(-d), true),
+            (ArgumentKey::Digit(d), _) => (Prefix::Number(d), true),
+        };
+        self.prefix = prefix;
+        self.active = active;
+    }
+
+    /// Return the prefix argument for the command that comes next, and
+    /// start over.
+    fn take(&mut self) -> Prefix {
+        let prefix = self.prefix;
+        *self = Argument::new();
+        prefix
+    }
+}
+
+/// Return the digit that EVENT types: a digit character, possibly with
+/// modifiers, or a keypad digit.
+fn event_digit(event: LispObject) -> Option<EmacsInt> {
+    let c = match event.as_fixnum() {
+        Some(c) => c & 0o177,
+        None if event.is_symbol() => {
+            let name = symbol_name_text(event);
+            if name.len() == 4 && name.starts_with("kp-") {
+                EmacsInt::from(name.as_bytes()[3])
+            } else {
+                return None;
+            }
+        }
+        None => return None,
+    };
+    if c >= EmacsInt::from(b'0') && c <= EmacsInt::from(b'9') {
+        Some(c - EmacsInt::from(b'0'))
+    } else {
+        None
+    }
+}
+
+fn symbol_name_text(symbol: LispObject) -> String {
+    let name = call!(intern("symbol-name"), symbol);
+    String::from_utf8_lossy(name.as_string_or_error().as_slice()).into_owned()
+}
+
+/// Return what EVENT does to the prefix argument ARGUMENT while the keys
+/// of `universal-argument-map' are in effect, if anything.
+fn argument_map_key(event: LispObject, argument: Argument) -> Option<ArgumentKey> {
+    if event.as_fixnum() == Some(EmacsInt::from(b'u') & 0x1f) {
+        return Some(ArgumentKey::Universal);
+    }
+    let minus = event.as_fixnum() == Some(EmacsInt::from(b'-'))
+        || (event.is_symbol() && symbol_name_text(event) == "kp-subtract");
+    if minus {
+        // Once there are digits, minus is an ordinary key.
+        return match argument.prefix {
+            Prefix::Number(_) => None,
+            _ => Some(ArgumentKey::Negative),
+        };
+    }
+    match event.as_fixnum() {
+        Some(c) if c >= EmacsInt::from(b'0') && c <= EmacsInt::from(b'9') => {
+            event_digit(event).map(ArgumentKey::Digit)
+        }
+        Some(_) => None,
+        None => event_digit(event).map(ArgumentKey::Digit),
+    }
+}
+
+/// Return what running COMMAND with last event EVENT does to the prefix
+/// argument, if anything.
+fn argument_command_key(command: LispObject, event: LispObject) -> Option<ArgumentKey> {
+    if command.eq(intern("universal-argument")) || command.eq(intern("universal-argument-more"))
+    {
+        Some(ArgumentKey::Universal)
+    } else if command.eq(intern("negative-argument")) {
+        Some(ArgumentKey::Negative)
+    } else if command.eq(intern("digit-argument")) {
+        event_digit(event).map(ArgumentKey::Digit)
+    } else {
+        None
+    }
+}
+
+/// Return FORM, run with the prefix argument PREFIX.
+fn with_prefix(form: LispObject, prefix: Prefix) -> LispObject {
+    if prefix == Prefix::Nothing {
+        form
+    } else {
+        list!(
+            intern("let"),
+            list!(list!(intern("current-prefix-arg"), prefix.to_lisp())),
+            form
+        )
+    }
+}
+
+fn vconcat(events: &[LispObject]) -> LispObject {
+    let mut events = events.to_vec();
+    call!(intern("vconcat"), list(&mut events))
+}
+
+/// Return a list of Lisp forms that do what the keyboard macro MACRO
+/// does.  MACRO is a string or vector of events, a symbol whose function
+/// definition is one, or is made by `kmacro-lambda-form', or nil, which
+/// stands for `last-kbd-macro'.
+///
+/// The keys of the macro are looked up in the keymaps now in effect.
+/// Each command becomes a call to `call-interactively', in a `let'
+/// binding `current-prefix-arg' if it was given a prefix argument;
+/// characters inserted by `self-insert-command' are gathered into calls
+/// to `insert', and keys that are not bound to commands are left to
+/// `execute-kbd-macro'.  Keys read by commands, for instance in the
+/// minibuffer, are taken as keys that run commands too, so the result
+/// does what MACRO does only when the macro consists of such commands.
+#[lisp_fn(min = "0")]
+pub fn kmacro_to_lisp(macro_: LispObject) -> LispObject {
+    let mut events = if macro_.is_nil() {
+        kboard_value("last-kbd-macro")
+    } else {
+        indirect_function_1(macro_)
+    };
+    if events.is_cons() && call!(intern("fboundp"), intern("kmacro-extract-lambda")).is_not_nil()
+    {
+        let extracted = call!(intern("kmacro-extract-lambda"), events);
+        if let Some(cons) = extracted.as_cons() {
+            events = cons.car();
+        }
+    }
+    if !events.is_string() && !events.is_vector() {
+        error!("Keyboard macros must be strings or vectors");
+    }
+    let events = macro_events(events);
+
+    // The forms are kept in a Lisp list, where the garbage collector
+    // sees them, since looking up keys can run Lisp code.
+    let mut forms = LispObject::constant_nil();
+    let mut text: Vec<LispObject> = Vec::new();
+    let mut argument = Argument::new();
+    let mut i = 0;
+    while i < events.len() {
+        if argument.active {
+            if let Some(key) = argument_map_key(events[i], argument) {
+                argument.apply(key);
+                i += 1;
+                continue;
+            }
+        }
+
+        // Find the key sequence that starts here.
+        let mut end = i + 1;
+        let binding = loop {
+            let binding = call!(
+                intern("key-binding"),
+                vconcat(&events[i..end]),
+                LispObject::constant_t()
+            );
+            if end < events.len() && call!(intern("keymapp"), binding).is_not_nil() {
+                end += 1;
+            } else {
+                break binding;
+            }
+        };
+        let key = &events[i..end];
+        let last = events[end - 1];
+        i = end;
+
+        if let Some(key) = argument_command_key(binding, last) {
+            argument.apply(key);
+            continue;
+        }
+        let prefix = argument.take();
+        if binding.eq(intern("self-insert-command"))
+            && call!(intern("characterp"), last).is_not_nil()
+        {
+            for _ in 0..prefix.numeric_value().max(0) {
+                text.push(last);
+            }
+            continue;
+        }
+
+        if !text.is_empty() {
+            let string = call!(intern("concat"), list(&mut text));
+            forms = LispObject::cons(list!(intern("insert"), string), forms);
+            text.clear();
+        }
+        let form = if binding.is_nil() || call!(intern("keymapp"), binding).is_not_nil() {
+            list!(intern("execute-kbd-macro"), vconcat(key))
+        } else if binding.is_string() || binding.is_vector() {
+            if prefix == Prefix::Nothing {
+                list!(intern("execute-kbd-macro"), binding)
+            } else {
+                list!(
+                    intern("execute-kbd-macro"),
+                    binding,
+                    LispObject::from_fixnum(prefix.numeric_value())
+                )
+            }
+        } else {
+            let function = if binding.is_symbol() {
+                list!(intern("function"), binding)
+            } else {
+                list!(intern("quote"), binding)
+            };
+            with_prefix(list!(intern("call-interactively"), function), prefix)
+        };
+        forms = LispObject::cons(form, forms);
+    }
+    if !text.is_empty() {
+        let string = call!(intern("concat"), list(&mut text));
+        forms = LispObject::cons(list!(intern("insert"), string), forms);
+    }
+    call!(intern("nreverse"), forms)
+}
+
+#[test]
+fn test_argument() {
+    let mut argument = Argument::new();
+    argument.apply(ArgumentKey::Universal);
+    argument.apply(ArgumentKey::Universal);
+    assert_eq!(argument.prefix, Prefix::Raw(16));
+    argument.apply(ArgumentKey::Digit(1));
+    argument.apply(ArgumentKey::Digit(2));
+    assert_eq!(argument.prefix, Prefix::Number(12));
+    // C-u after the digits ends the argument.
+    argument.apply(ArgumentKey::Universal);
+    assert_eq!(
+        argument,
+        Argument {
+            prefix: Prefix::Number(12),
+            active: false,
+        }
+    );
+    assert_eq!(argument.take(), Prefix::Number(12));
+    assert_eq!(argument, Argument::new());
+
+    argument.apply(ArgumentKey::Negative);
+    assert_eq!(argument.prefix, Prefix::Minus);
+    argument.apply(ArgumentKey::Digit(0));
+    assert_eq!(argument.prefix, Prefix::Minus);
+    argument.apply(ArgumentKey::Digit(3));
+    argument.apply(ArgumentKey::Digit(4));
+    assert_eq!(argument.prefix, Prefix::Number(-34));
+    argument.apply(ArgumentKey::Negative);
+    assert_eq!(argument.take().numeric_value(), 34);
+
+    argument.apply(ArgumentKey::Universal);
+    argument.apply(ArgumentKey::Negative);
+    argument.apply(ArgumentKey::Universal);
+    assert_eq!(argument.prefix, Prefix::Raw(-4));
+}
+
+include!(concat!(env!("OUT_DIR"), "/kbd_macros_exports.rs"));
@@ -0,0 +1,360 @@
+//! Key translation.
+//!
+//! `read-key-sequence' translates the events it reads through three
+//! keymaps, in this order: `input-decode-map', which turns the escape
+//! sequences of a terminal into events; `local-function-key-map', whose
+//! parent is `function-key-map', which applies only to keys that have
+//! no binding; and `key-translation-map', which applies whether or not
+//! they have one.  A translation replaces the events bound in one of
+//! the maps with the events they are bound to, and the whole sequence
+//! is then looked up again from the start.
+//!
+//! `keyremap_step' does a step of that for `read_key_sequence', and
+//! `key-translation-pipeline' follows the same steps for a given key
+//! sequence, without reading anything, to say which map did what.
+
+use std::slice;
+
+use libc::c_int;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{access_keymap, get_keymap, EmacsInt, Faref, Lisp_Object, FUNCTIONP};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::list;
+use symbols::symbol_value;
+
+/// The most events a translated key sequence may have, the size of the
+/// buffers the command loop reads key sequences into.
+const KEY_ELTS: usize = 30;
+
+/// How far the events of a key sequence have been translated through a
+/// map.  This is the `keyremap' structure of keyboard.c.
+#[repr(C)]
+pub struct KeyRemap {
+    /// The map itself.
+    parent: LispObject,
+    /// The submap of PARENT reached by the events from START to END.
+    map: LispObject,
+    /// The events from START to END are the ones looked up so far, which
+    /// are replaced if PARENT binds them to a key sequence.
+    start: c_int,
+    end: c_int,
+}
+
+impl KeyRemap {
+    fn new(parent: LispObject) -> KeyRemap {
+        KeyRemap {
+            parent,
+            map: parent,
+            start: 0,
+            end: 0,
+        }
+    }
+
+    fn shift(&mut self, diff: c_int) {
+        self.start += diff;
+        self.end += diff;
+    }
+}
+
+/// What a step of translation did.
+enum Step {
+    /// The events were not translated, or not yet.
+    Nothing,
+    /// The events were translated, and this many events were added.
+    Translated(c_int),
+    /// The events are bound to this function, which was not called.
+    Function(LispObject),
+}
+
+fn is_keymap(object: LispObject) -> bool {
+    LispObject::from(unsafe { get_keymap(object.to_raw(), false, false) }).is_not_nil()
+}
+
+/// Return whether BINDING, the binding of a key, leaves it undefined.
+fn is_undefined(binding: LispObject) -> bool {
+    let undefined = intern("undefined");
+    binding.is_nil() || binding.eq(undefined)
+        || (binding.is_symbol()
+            && call!(
+                intern("command-remapping"),
+                binding,
+                LispObject::constant_nil(),
+                LispObject::constant_nil()
+            ).eq(undefined))
+}
+
+/// Look up KEY in MAP, a map from keys to key sequences or functions.
+/// If the binding is a function and DO_FUNCALL is true, return what the
+/// function returns when called with PROMPT.
+fn access_keymap_keyremap(
+    map: LispObject,
+    key: LispObject,
+    prompt: LispObject,
+    do_funcall: bool,
+) -> LispObject {
+    let mut next = LispObject::from(unsafe {
+        access_keymap(map.to_raw(), key.to_raw(), true, false, true)
+    });
+
+    // Handle a symbol whose function definition is a keymap or an array.
+    if next.is_symbol() && call!(intern("fboundp"), next).is_not_nil() {
+        let function = call!(intern("symbol-function"), next);
+        if call!(intern("arrayp"), function).is_not_nil() || is_keymap(function) {
+            next = call!(intern("autoload-do-load"), function, next);
+        }
+    }
+
+    if do_funcall && unsafe { FUNCTIONP(next.to_raw()) } {
+        let function = next;
+        next = call!(function, prompt);
+        // If the function returned something invalid, barf--don't
+        // ignore it.
+        if !(next.is_nil() || next.is_vector() || next.is_string()) {
+            let name = call!(intern("prin1-to-string"), function);
+            error!(
+                "Function {} returns invalid key sequence",
+                String::from_utf8_lossy(name.as_string_or_error().as_slice())
+            );
+        }
+    }
+    next
+}
+
+/// Look up the next event of KEYS in the map of REMAP.  If DOIT and the
+/// events from REMAP's start on are bound to a key sequence, replace
+/// them with it; KEYS may not grow to LIMIT events.  Function bindings
+/// are called only if CALL.
+fn remap_step(
+    keys: &mut Vec<LispObject>,
+    remap: &mut KeyRemap,
+    doit: bool,
+    call: bool,
+    prompt: LispObject,
+    limit: usize,
+) -> Step {
+    let start = remap.start as usize;
+    let key = keys[remap.end as usize];
+    remap.end += 1;
+    let end = remap.end as usize;
+
+    let next = if is_keymap(remap.parent) {
+        access_keymap_keyremap(remap.map, key, prompt, doit && call)
+    } else {
+        LispObject::constant_nil()
+    };
+
+    // If the events are bound in the map, replace them with the binding
+    // and carry on after it.
+    if (next.is_vector() || next.is_string()) && doit {
+        let len = next.as_vector_or_string_length() as usize;
+        if keys.len() + len >= limit + (end - start) {
+            error!("Key sequence too long");
+        }
+        let tail = keys.split_off(end);
+        keys.truncate(start);
+        for i in 0..len {
+            keys.push(LispObject::from(unsafe {
+                Faref(next.to_raw(), LispObject::from_natnum(i as EmacsInt).to_raw())
+            }));
+        }
+        keys.extend(tail);
+
+        let diff = len as c_int - (end - start) as c_int;
+        remap.end += diff;
+        remap.start = remap.end;
+        remap.map = remap.parent;
+        return Step::Translated(diff);
+    }
+
+    let function = doit && !call && unsafe { FUNCTIONP(next.to_raw()) };
+    remap.map = LispObject::from(unsafe { get_keymap(next.to_raw(), false, true) });
+    // If the events are not a prefix in the map, start again from the
+    // next one.
+    if !remap.map.is_cons() {
+        remap.start += 1;
+        remap.end = remap.start;
+        remap.map = remap.parent;
+    }
+    if function {
+        Step::Function(next)
+    } else {
+        Step::Nothing
+    }
+}
+
+/// Do a step of the translation of the INPUT events in KEYBUF, which
+/// has room for BUFSIZE, through the map of REMAP, for
+/// `read_key_sequence'.  If DOIT, the events may be translated, calling
+/// a function binding with PROMPT, and DIFF is set to the number of
+/// events added.  Return whether they were translated.
+#[no_mangle]
+pub unsafe extern "C" fn keyremap_step(
+    keybuf: *mut Lisp_Object,
+    bufsize: c_int,
+    remap: *mut KeyRemap,
+    input: c_int,
+    doit: bool,
+    diff: *mut c_int,
+    prompt: Lisp_Object,
+) -> bool {
+    // The events are in KEYBUF, on the stack, for the garbage collector.
+    let mut keys: Vec<LispObject> = slice::from_raw_parts(keybuf, input as usize)
+        .iter()
+        .map(|&key| LispObject::from(key))
+        .collect();
+    let prompt = LispObject::from(prompt);
+    match remap_step(&mut keys, &mut *remap, doit, true, prompt, bufsize as usize) {
+        Step::Translated(n) => {
+            *diff = n;
+            for (i, key) in keys.iter().enumerate() {
+                *keybuf.offset(i as isize) = key.to_raw();
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+fn events(keys: LispObject) -> Vec<LispObject> {
+    keys.as_vectorlike()
+        .and_then(|v| v.as_vector())
+        .map_or_else(Vec::new, |vector| vector.as_slice().to_vec())
+}
+
+fn vector(events: &[LispObject]) -> LispObject {
+    let mut events = events.to_vec();
+    call!(intern("vconcat"), list(&mut events))
+}
+
+/// Return the binding of the key sequence KEYS, before remapping.
+fn key_binding(keys: LispObject) -> LispObject {
+    let t = LispObject::constant_t();
+    call!(intern("key-binding"), keys, t, t)
+}
+
+/// Do a step of the translation of KEYS through REMAP, the map that is
+/// the value of MAP, and push what it did onto STEPS.  Return the
+/// number of events added if KEYS were translated.
+fn translate(
+    keys: &mut LispObject,
+    remap: &mut KeyRemap,
+    doit: bool,
+    map: &str,
+    steps: &mut LispObject,
+) -> Option<c_int> {
+    let mut events = events(*keys);
+    let nil = LispObject::constant_nil();
+    match remap_step(&mut events, remap, doit, false, nil, KEY_ELTS) {
+        Step::Translated(diff) => {
+            let translated = vector(&events);
+            *steps = LispObject::cons(list!(intern(map), *keys, translated), *steps);
+            *keys = translated;
+            Some(diff)
+        }
+        Step::Function(function) => {
+            *steps = LispObject::cons(list!(intern(map), *keys, function), *steps);
+            None
+        }
+        Step::Nothing => None,
+    }
+}
+
+/// Return the steps by which the key sequence KEY is translated and
+/// looked up when it is read by `read-key-sequence'.
+/// KEY is a string or vector of the events as they are read from the
+/// terminal.  Each step is a list:
+///
+///  (MAP FROM TO): the variable MAP, one of `input-decode-map',
+///    `local-function-key-map' and `key-translation-map', translates the
+///    events FROM to TO, both vectors of the whole key sequence.
+///  (MAP FROM FUNCTION): MAP binds events of FROM to FUNCTION, which is
+///    called to translate them when they are read.  It is not called
+///    here, and the events are left as they are.
+///  (binding KEYS BINDING): KEYS, the translated key sequence, has the
+///    binding BINDING in the active keymaps.  KEYS is shorter than the
+///    translation of KEY if its start is a complete key sequence.
+///  (remap COMMAND REMAPPED): COMMAND is remapped to REMAPPED.
+///
+/// The steps come in the order they happen: `input-decode-map' applies
+/// first, then `local-function-key-map', for keys that have no binding,
+/// then `key-translation-map'.  After each translation, the key sequence
+/// is looked up again from its start.
+#[lisp_fn]
+pub fn key_translation_pipeline(key: LispObject) -> LispObject {
+    let mut keys = call!(
+        intern("vconcat"),
+        call!(intern("listify-key-sequence"), key)
+    );
+    let mut indec = KeyRemap::new(symbol_value(intern("input-decode-map")));
+    let mut fkey = KeyRemap::new(symbol_value(intern("local-function-key-map")));
+    let mut keytran = KeyRemap::new(symbol_value(intern("key-translation-map")));
+    let mut steps = LispObject::constant_nil();
+
+    'replay: loop {
+        let mut t = 0;
+        while t < events(keys).len() {
+            t += 1;
+            let binding = key_binding(vector(&events(keys)[..t]));
+
+            while (indec.end as usize) < t {
+                if translate(&mut keys, &mut indec, true, "input-decode-map", &mut steps)
+                    .is_some()
+                {
+                    continue 'replay;
+                }
+            }
+
+            if !is_keymap(binding) && !is_undefined(binding) && indec.start as usize >= t {
+                // A command is bound, so there is no function key here.
+                if (fkey.start as usize) < t {
+                    fkey.start = t as c_int;
+                    fkey.end = fkey.start;
+                    fkey.map = fkey.parent;
+                }
+            } else {
+                while fkey.end < indec.start {
+                    let doit = fkey.end as usize + 1 == t && is_undefined(binding);
+                    let map = "local-function-key-map";
+                    if let Some(diff) = translate(&mut keys, &mut fkey, doit, map, &mut steps) {
+                        indec.shift(diff);
+                        continue 'replay;
+                    }
+                }
+            }
+
+            while keytran.end < fkey.start {
+                let map = "key-translation-map";
+                if let Some(diff) = translate(&mut keys, &mut keytran, true, map, &mut steps) {
+                    indec.shift(diff);
+                    fkey.shift(diff);
+                    continue 'replay;
+                }
+            }
+
+            if !is_keymap(binding) && indec.start as usize >= t && fkey.start as usize >= t
+                && keytran.start as usize >= t
+            {
+                // A complete key sequence: the events after it would be
+                // read as the next one.
+                keys = vector(&events(keys)[..t]);
+                break 'replay;
+            }
+        }
+        break;
+    }
+
+    let binding = key_binding(keys);
+    steps = LispObject::cons(list!(intern("binding"), keys, binding), steps);
+    if binding.is_symbol() && binding.is_not_nil() {
+        let remapped = call!(intern("command-remapping"), binding);
+        if remapped.is_not_nil() {
+            steps = LispObject::cons(list!(intern("remap"), binding, remapped), steps);
+        }
+    }
+    call!(intern("nreverse"), steps)
+}
+
+include!(concat!(env!("OUT_DIR"), "/key_translation_exports.rs"));
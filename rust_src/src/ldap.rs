@@ -0,0 +1,811 @@
+//! LDAP searches without ldapsearch.
+//!
+//! ldap.el runs the ldapsearch program of OpenLDAP and parses its LDIF
+//! output, which needs the program installed and configured, and writes
+//! every value to a temporary file.  Here the protocol (RFC 4511) is
+//! spoken directly over a network process, encrypted by GnuTLS for
+//! ldaps: requests are encoded in BER, with the search filter parsed
+//! from its string form (RFC 4515), and the entries decoded from the
+//! responses.
+
+use std::time::{Duration, Instant};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{record_unwind_current_buffer, unbind_to, EmacsInt, Qnil};
+
+use eval_call::specpdl_index;
+use imap::{string_bytes, unibyte_string};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const ENUMERATED: u8 = 0x0a;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+
+const BIND_REQUEST: u8 = 0x60;
+const BIND_RESPONSE: u8 = 0x61;
+const UNBIND_REQUEST: u8 = 0x42;
+const SEARCH_REQUEST: u8 = 0x63;
+const SEARCH_RESULT_ENTRY: u8 = 0x64;
+const SEARCH_RESULT_DONE: u8 = 0x65;
+
+/// The result codes that are not errors: success and sizeLimitExceeded.
+const SUCCESS: i64 = 0;
+const SIZE_LIMIT_EXCEEDED: i64 = 4;
+const INVALID_CREDENTIALS: i64 = 49;
+
+/// How long to wait for the server when there is no time limit.
+const DEFAULT_TIMEOUT: u64 = 30;
+
+fn push_length(length: usize, out: &mut Vec<u8>) {
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes: Vec<u8> = (0..8)
+            .rev()
+            .map(|i| (length >> (8 * i)) as u8)
+            .skip_while(|&b| b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+}
+
+/// Return the BER element with TAG and CONTENT.
+fn element(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 6);
+    out.push(tag);
+    push_length(content.len(), &mut out);
+    out.extend_from_slice(content);
+    out
+}
+
+fn integer(tag: u8, value: i64) -> Vec<u8> {
+    let bytes: Vec<u8> = (0..8).rev().map(|i| (value >> (8 * i)) as u8).collect();
+    // Drop the leading bytes that only repeat the sign.
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0 && bytes[start + 1] < 0x80)
+            || (bytes[start] == 0xff && bytes[start + 1] >= 0x80))
+    {
+        start += 1;
+    }
+    element(tag, &bytes[start..])
+}
+
+fn concat(elements: &[Vec<u8>]) -> Vec<u8> {
+    elements.iter().flat_map(|e| e.iter().cloned()).collect()
+}
+
+/// A BER element of a response.
+struct Element<'a> {
+    tag: u8,
+    content: &'a [u8],
+}
+
+/// Read the element at the start of INPUT.  Return it and its length,
+/// None if INPUT does not hold all of it, or an error.
+fn read_element(input: &[u8]) -> Result<Option<(Element, usize)>, String> {
+    if input.len() < 2 {
+        return Ok(None);
+    }
+    let (length, header) = match input[1] {
+        length @ 0...0x7f => (length as usize, 2),
+        0x80 => return Err("Indefinite lengths are not allowed".to_string()),
+        first => {
+            let count = (first & 0x7f) as usize;
+            if count > 4 {
+                return Err("Element too long".to_string());
+            }
+            if input.len() < 2 + count {
+                return Ok(None);
+            }
+            let length = input[2..2 + count]
+                .iter()
+                .fold(0, |length, &b| (length << 8) | b as usize);
+            (length, 2 + count)
+        }
+    };
+    if input.len() < header + length {
+        return Ok(None);
+    }
+    Ok(Some((
+        Element {
+            tag: input[0],
+            content: &input[header..header + length],
+        },
+        header + length,
+    )))
+}
+
+/// Return the elements that make up CONTENT.
+fn elements(mut content: &[u8]) -> Result<Vec<Element>, String> {
+    let mut result = Vec::new();
+    while !content.is_empty() {
+        match read_element(content)? {
+            Some((element, length)) => {
+                result.push(element);
+                content = &content[length..];
+            }
+            None => return Err("Truncated element".to_string()),
+        }
+    }
+    Ok(result)
+}
+
+fn decode_integer(content: &[u8]) -> i64 {
+    let initial = if content.first().map_or(false, |&b| b >= 0x80) {
+        -1
+    } else {
+        0
+    };
+    content
+        .iter()
+        .fold(initial, |value, &b| (value << 8) | i64::from(b))
+}
+
+/// Return the value of a filter item with its escapes decoded, split
+/// at its unescaped `*'s.  Both the "\2a" escapes of RFC 4515 and the
+/// "\*" escapes of RFC 1960 are understood.
+fn split_value(value: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    fn hex(b: u8) -> Option<u8> {
+        match b {
+            b'0'...b'9' => Some(b - b'0'),
+            b'a'...b'f' => Some(b - b'a' + 10),
+            b'A'...b'F' => Some(b - b'A' + 10),
+            _ => None,
+        }
+    }
+    let mut pieces = vec![Vec::new()];
+    let mut i = 0;
+    while i < value.len() {
+        match value[i] {
+            b'*' => pieces.push(Vec::new()),
+            b'\\' => {
+                let high = value.get(i + 1).cloned().and_then(hex);
+                let low = value.get(i + 2).cloned().and_then(hex);
+                let piece = pieces.last_mut().unwrap();
+                if let (Some(high), Some(low)) = (high, low) {
+                    piece.push(high * 16 + low);
+                    i += 2;
+                } else if let Some(&b) = value.get(i + 1) {
+                    piece.push(b);
+                    i += 1;
+                } else {
+                    return Err("Bad escape in filter".to_string());
+                }
+            }
+            b => pieces.last_mut().unwrap().push(b),
+        }
+        i += 1;
+    }
+    Ok(pieces)
+}
+
+struct FilterParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> FilterParser<'a> {
+    fn expect(&mut self, b: u8) -> Result<(), String> {
+        if self.input.get(self.pos) == Some(&b) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected `{}' at {} in filter", b as char, self.pos))
+        }
+    }
+
+    fn filter(&mut self) -> Result<Vec<u8>, String> {
+        self.expect(b'(')?;
+        let encoded = match self.input.get(self.pos).cloned() {
+            Some(b'&') => {
+                self.pos += 1;
+                element(0xa0, &self.filter_list()?)
+            }
+            Some(b'|') => {
+                self.pos += 1;
+                element(0xa1, &self.filter_list()?)
+            }
+            Some(b'!') => {
+                self.pos += 1;
+                element(0xa2, &self.filter()?)
+            }
+            _ => self.item()?,
+        };
+        self.expect(b')')?;
+        Ok(encoded)
+    }
+
+    fn filter_list(&mut self) -> Result<Vec<u8>, String> {
+        let mut list = Vec::new();
+        while self.input.get(self.pos) == Some(&b'(') {
+            list.extend(self.filter()?);
+        }
+        Ok(list)
+    }
+
+    fn item(&mut self) -> Result<Vec<u8>, String> {
+        let input = self.input;
+        let start = self.pos;
+        while let Some(&b) = input.get(self.pos) {
+            match b {
+                b'=' | b'~' | b'<' | b'>' | b'(' | b')' => break,
+                _ => self.pos += 1,
+            }
+        }
+        let name = &input[start..self.pos];
+        if name.is_empty() || name.contains(&b':') {
+            return Err("Unsupported filter item".to_string());
+        }
+        let tag = match input.get(self.pos) {
+            Some(&b'=') => 0xa3,
+            Some(&b'~') => 0xa8,
+            Some(&b'>') => 0xa5,
+            Some(&b'<') => 0xa6,
+            _ => return Err("Expected a filter operator".to_string()),
+        };
+        if tag != 0xa3 {
+            self.pos += 1;
+        }
+        self.expect(b'=')?;
+        let start = self.pos;
+        while let Some(&b) = input.get(self.pos) {
+            if b == b')' {
+                break;
+            }
+            // Skip the character after a backslash, which may be `)'.
+            self.pos += if b == b'\\' { 2 } else { 1 };
+        }
+        self.pos = self.pos.min(input.len());
+        let mut pieces = split_value(&input[start..self.pos])?;
+        let attribute = element(OCTET_STRING, name);
+        if pieces.len() == 1 {
+            let value = element(OCTET_STRING, &pieces[0]);
+            return Ok(element(tag, &concat(&[attribute, value])));
+        }
+        if tag != 0xa3 {
+            return Err("Wildcards are only allowed with `='".to_string());
+        }
+        if pieces.len() == 2 && pieces.iter().all(|piece| piece.is_empty()) {
+            return Ok(element(0x87, name));
+        }
+        let last = pieces.len() - 1;
+        let mut substrings = Vec::new();
+        for (i, piece) in pieces.drain(..).enumerate() {
+            if !piece.is_empty() {
+                let choice = match i {
+                    0 => 0x80,
+                    _ if i == last => 0x82,
+                    _ => 0x81,
+                };
+                substrings.push(element(choice, &piece));
+            }
+        }
+        let substrings = element(SEQUENCE, &concat(&substrings));
+        Ok(element(0xa4, &concat(&[attribute, substrings])))
+    }
+}
+
+/// Encode FILTER, in the string form of RFC 4515.  As with ldapsearch,
+/// the outer parentheses may be left out.
+fn encode_filter(filter: &str) -> Result<Vec<u8>, String> {
+    let filter = filter.trim();
+    let wrapped;
+    let filter = if filter.starts_with('(') {
+        filter
+    } else {
+        wrapped = format!("({})", filter);
+        &wrapped
+    };
+    let mut parser = FilterParser {
+        input: filter.as_bytes(),
+        pos: 0,
+    };
+    let encoded = parser.filter()?;
+    if parser.pos != filter.len() {
+        return Err("Trailing text after filter".to_string());
+    }
+    Ok(encoded)
+}
+
+fn message(id: i64, operation: &[u8]) -> Vec<u8> {
+    element(SEQUENCE, &concat(&[integer(INTEGER, id), operation.to_vec()]))
+}
+
+fn bind_request(id: i64, dn: &[u8], password: &[u8]) -> Vec<u8> {
+    let bind = element(
+        BIND_REQUEST,
+        &concat(&[
+            integer(INTEGER, 3),
+            element(OCTET_STRING, dn),
+            element(0x80, password),
+        ]),
+    );
+    message(id, &bind)
+}
+
+struct Search<'a> {
+    base: &'a [u8],
+    scope: i64,
+    deref: i64,
+    size_limit: i64,
+    time_limit: i64,
+    types_only: bool,
+    filter: Vec<u8>,
+    attributes: &'a [Vec<u8>],
+}
+
+fn search_request(id: i64, search: &Search) -> Vec<u8> {
+    let attributes: Vec<Vec<u8>> = search
+        .attributes
+        .iter()
+        .map(|attribute| element(OCTET_STRING, attribute))
+        .collect();
+    let request = element(
+        SEARCH_REQUEST,
+        &concat(&[
+            element(OCTET_STRING, search.base),
+            integer(ENUMERATED, search.scope),
+            integer(ENUMERATED, search.deref),
+            integer(INTEGER, search.size_limit),
+            integer(INTEGER, search.time_limit),
+            element(BOOLEAN, &[if search.types_only { 0xff } else { 0 }]),
+            search.filter.clone(),
+            element(SEQUENCE, &concat(&attributes)),
+        ]),
+    );
+    message(id, &request)
+}
+
+/// An entry found by a search: its DN and attributes with their values.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    dn: Vec<u8>,
+    attributes: Vec<(Vec<u8>, Vec<Vec<u8>>)>,
+}
+
+/// What a response holds.
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Entry(Entry),
+    /// The result of an operation: its code and diagnostic message.
+    Result(i64, String),
+    Other,
+}
+
+/// Decode MESSAGE, an LDAPMessage, into its id and what it holds.
+fn decode_message(message: &[u8]) -> Result<(i64, Reply), String> {
+    let parts = elements(message)?;
+    if parts.len() < 2 || parts[0].tag != INTEGER {
+        return Err("Malformed LDAP message".to_string());
+    }
+    let id = decode_integer(parts[0].content);
+    let operation = &parts[1];
+    let fields = match operation.tag {
+        BIND_RESPONSE | SEARCH_RESULT_ENTRY | SEARCH_RESULT_DONE => elements(operation.content)?,
+        _ => return Ok((id, Reply::Other)),
+    };
+    if operation.tag == SEARCH_RESULT_ENTRY {
+        if fields.len() < 2 {
+            return Err("Malformed search result entry".to_string());
+        }
+        let mut attributes = Vec::new();
+        for attribute in elements(fields[1].content)? {
+            let parts = elements(attribute.content)?;
+            if parts.len() < 2 {
+                return Err("Malformed attribute".to_string());
+            }
+            let values = elements(parts[1].content)?
+                .iter()
+                .map(|value| value.content.to_vec())
+                .collect();
+            attributes.push((parts[0].content.to_vec(), values));
+        }
+        return Ok((
+            id,
+            Reply::Entry(Entry {
+                dn: fields[0].content.to_vec(),
+                attributes,
+            }),
+        ));
+    }
+    if fields.len() < 3 {
+        return Err("Malformed LDAP result".to_string());
+    }
+    let diagnostic = String::from_utf8_lossy(fields[2].content).into_owned();
+    Ok((id, Reply::Result(decode_integer(fields[0].content), diagnostic)))
+}
+
+/// The host and port to connect to, and whether to use TLS, from HOST,
+/// which is "host", "host:port" or an ldap:// or ldaps:// URL.
+fn parse_host(host: &str) -> (String, u16, bool) {
+    let (rest, tls) = if host.starts_with("ldaps://") {
+        (&host[8..], true)
+    } else if host.starts_with("ldap://") {
+        (&host[7..], false)
+    } else {
+        (host, false)
+    };
+    let rest = rest.split('/').next().unwrap_or("");
+    let default_port = if tls { 636 } else { 389 };
+    match rest.rfind(':') {
+        Some(colon) if !rest.ends_with(']') => {
+            let port = rest[colon + 1..].parse().unwrap_or(default_port);
+            (rest[..colon].to_string(), port, tls)
+        }
+        _ => (rest.to_string(), default_port, tls),
+    }
+}
+
+/// A connection to the server, with the buffer its output goes to.
+struct Connection {
+    process: LispObject,
+    buffer: LispObject,
+    /// The number of bytes of the buffer already read.
+    read: usize,
+    deadline: Instant,
+}
+
+impl Connection {
+    fn send(&self, bytes: &[u8]) {
+        call!(intern("process-send-string"), self.process, unibyte_string(bytes));
+    }
+
+    /// Return the output not read yet.
+    fn unread(&self) -> Vec<u8> {
+        let count = specpdl_index();
+        unsafe { record_unwind_current_buffer() };
+        call!(intern("set-buffer"), self.buffer);
+        let text = call!(
+            intern("buffer-substring-no-properties"),
+            LispObject::from_natnum((self.read + 1) as EmacsInt),
+            call!(intern("point-max"))
+        );
+        unsafe { unbind_to(count, Qnil) };
+        text.as_string_or_error().as_slice().to_vec()
+    }
+
+    /// Wait for the next message of the server and return it decoded.
+    fn receive(&mut self) -> Result<(i64, Reply), String> {
+        loop {
+            let unread = self.unread();
+            if let Some((message, length)) = read_element(&unread)? {
+                if message.tag != SEQUENCE {
+                    return Err("Malformed LDAP message".to_string());
+                }
+                let reply = decode_message(message.content)?;
+                self.read += length;
+                return Ok(reply);
+            }
+            if call!(intern("process-live-p"), self.process).is_nil() {
+                return Err("The LDAP server closed the connection".to_string());
+            }
+            if Instant::now() > self.deadline {
+                return Err("Timed out waiting for the LDAP server".to_string());
+            }
+            call!(
+                intern("accept-process-output"),
+                self.process,
+                LispObject::from_float(0.1)
+            );
+        }
+    }
+
+    /// Wait for the result of the operation ID, collecting the entries
+    /// that come before it.
+    fn result(&mut self, id: i64, entries: &mut Vec<Entry>) -> Result<(i64, String), String> {
+        loop {
+            let (reply_id, reply) = self.receive()?;
+            if reply_id != id {
+                continue;
+            }
+            match reply {
+                Reply::Entry(entry) => entries.push(entry),
+                Reply::Result(code, diagnostic) => return Ok((code, diagnostic)),
+                Reply::Other => (),
+            }
+        }
+    }
+}
+
+/// Bind if CREDENTIALS are given, and run SEARCH.
+fn run(
+    connection: &mut Connection,
+    credentials: Option<(Vec<u8>, Vec<u8>)>,
+    search: &Search,
+) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    if let Some((dn, password)) = credentials {
+        connection.send(&bind_request(1, &dn, &password));
+        match connection.result(1, &mut entries)? {
+            (SUCCESS, _) => (),
+            (INVALID_CREDENTIALS, _) => {
+                return Err("Incorrect LDAP password or bind distinguished name (binddn)".to_string())
+            }
+            (code, diagnostic) => return Err(format!("LDAP bind failed ({}): {}", code, diagnostic)),
+        }
+    }
+    connection.send(&search_request(2, search));
+    match connection.result(2, &mut entries)? {
+        (SUCCESS, _) | (SIZE_LIMIT_EXCEEDED, _) => Ok(entries),
+        (code, diagnostic) => Err(format!("LDAP search failed ({}): {}", code, diagnostic)),
+    }
+}
+
+/// Return BYTES as a string if they are UTF-8, or as a unibyte string.
+fn utf8_or_unibyte(bytes: &[u8]) -> LispObject {
+    match ::std::str::from_utf8(bytes) {
+        Ok(text) => lisp_string(text),
+        Err(_) => unibyte_string(bytes),
+    }
+}
+
+fn parameter(parameters: LispObject, name: &str) -> LispObject {
+    call!(intern("plist-get"), parameters, intern(name))
+}
+
+fn optional_bytes(object: LispObject) -> Option<Vec<u8>> {
+    if object.is_nil() {
+        None
+    } else {
+        Some(string_bytes(object))
+    }
+}
+
+/// Search the LDAP server HOST for the entries matching FILTER.
+/// FILTER is a search filter such as "(&(objectClass=person)(cn=Ada*))";
+/// the outer parentheses may be left out.  HOST is a host name,
+/// optionally followed by a colon and a port, or an ldap:// or ldaps://
+/// URL.  ATTRIBUTES is a list of the names of the attributes to
+/// return, or nil for all of them.
+///
+/// PARAMETERS is a plist as in `ldap-host-parameters-alist', of which
+/// these properties are used:
+///   `base' is the DN under which to search, by default "".
+///   `scope' is `base', `onelevel' or `subtree', the default.
+///   `deref' is `never', the default, `search', `find' or `always'.
+///   `binddn' and `passwd' are the DN and password to bind with,
+///     using simple authentication.  Without them, the search is
+///     anonymous.
+///   `sizelimit' is the maximum number of entries to return.
+///   `timelimit' is the maximum number of seconds for the search.
+///   `attrsonly' non-nil means to return no values.
+///
+/// Return a list of hash tables, one for each entry, that map the names
+/// of its attributes to the lists of their values, and "dn" to its DN.
+/// Values are unibyte strings, as they are binary for some attributes;
+/// see `ldap-decode-attribute' to decode them.
+#[lisp_fn(min = "2")]
+pub fn ldap_search_native(
+    filter: LispObject,
+    host: LispObject,
+    attributes: LispObject,
+    parameters: LispObject,
+) -> LispObject {
+    let filter = match encode_filter(&String::from_utf8_lossy(&string_bytes(filter))) {
+        Ok(filter) => filter,
+        Err(message) => error!("Invalid LDAP filter: {}", message),
+    };
+    let attributes: Vec<Vec<u8>> = attributes.iter_cars().map(string_bytes).collect();
+    let base = optional_bytes(parameter(parameters, "base")).unwrap_or_default();
+    let scope = parameter(parameters, "scope");
+    let scope = if scope.eq(intern("base")) {
+        0
+    } else if scope.eq(intern("onelevel")) || scope.eq(intern("one")) {
+        1
+    } else {
+        2
+    };
+    let deref = parameter(parameters, "deref");
+    let deref = if deref.eq(intern("search")) {
+        1
+    } else if deref.eq(intern("find")) {
+        2
+    } else if deref.eq(intern("always")) {
+        3
+    } else {
+        0
+    };
+    let limit = |name: &str| {
+        parameter(parameters, name)
+            .as_fixnum()
+            .map_or(0, |n| n.max(0) as i64)
+    };
+    let (size_limit, time_limit) = (limit("sizelimit"), limit("timelimit"));
+    let binddn = optional_bytes(parameter(parameters, "binddn"));
+    let passwd = optional_bytes(parameter(parameters, "passwd"));
+    let credentials = if binddn.is_some() || passwd.is_some() {
+        Some((binddn.unwrap_or_default(), passwd.unwrap_or_default()))
+    } else {
+        None
+    };
+    let search = Search {
+        base: &base,
+        scope,
+        deref,
+        size_limit,
+        time_limit,
+        types_only: parameter(parameters, "attrsonly").is_not_nil(),
+        filter,
+        attributes: &attributes,
+    };
+
+    let (name, port, tls) = parse_host(&String::from_utf8_lossy(&string_bytes(host)));
+    let buffer = call!(intern("generate-new-buffer"), lisp_string(" *ldap-native*"));
+    let count = specpdl_index();
+    unsafe { record_unwind_current_buffer() };
+    call!(intern("set-buffer"), buffer);
+    call!(intern("set-buffer-multibyte"), LispObject::constant_nil());
+    unsafe { unbind_to(count, Qnil) };
+    let process = call!(
+        intern("open-network-stream"),
+        lisp_string("ldap"),
+        buffer,
+        lisp_string(&name),
+        LispObject::from_natnum(EmacsInt::from(port)),
+        intern(":type"),
+        if tls { intern("tls") } else { intern("plain") },
+        intern(":coding"),
+        intern("binary")
+    );
+    call!(intern("set-process-query-on-exit-flag"), process, LispObject::constant_nil());
+    let timeout = if time_limit > 0 {
+        time_limit as u64 + 5
+    } else {
+        DEFAULT_TIMEOUT
+    };
+    let mut connection = Connection {
+        process,
+        buffer,
+        read: 0,
+        deadline: Instant::now() + Duration::from_secs(timeout),
+    };
+    let result = run(&mut connection, credentials, &search);
+    if call!(intern("process-live-p"), process).is_not_nil() {
+        connection.send(&message(3, &element(UNBIND_REQUEST, &[])));
+    }
+    call!(intern("delete-process"), process);
+    call!(intern("kill-buffer"), buffer);
+    let entries = match result {
+        Ok(entries) => entries,
+        Err(message) => error!("{}", message),
+    };
+
+    let mut list = LispObject::constant_nil();
+    for entry in entries.iter().rev() {
+        let table = call!(intern("make-hash-table"), intern(":test"), intern("equal"));
+        call!(intern("puthash"), lisp_string("dn"), utf8_or_unibyte(&entry.dn), table);
+        for &(ref name, ref values) in &entry.attributes {
+            let values = values
+                .iter()
+                .rev()
+                .fold(LispObject::constant_nil(), |list, value| {
+                    LispObject::cons(unibyte_string(value), list)
+                });
+            call!(intern("puthash"), utf8_or_unibyte(name), values, table);
+        }
+        list = LispObject::cons(table, list);
+    }
+    list
+}
+
+#[test]
+fn test_integer() {
+    assert_eq!(integer(INTEGER, 0), vec![0x02, 0x01, 0x00]);
+    assert_eq!(integer(INTEGER, 127), vec![0x02, 0x01, 0x7f]);
+    assert_eq!(integer(INTEGER, 128), vec![0x02, 0x02, 0x00, 0x80]);
+    assert_eq!(integer(INTEGER, -1), vec![0x02, 0x01, 0xff]);
+    assert_eq!(integer(INTEGER, -129), vec![0x02, 0x02, 0xff, 0x7f]);
+    assert_eq!(decode_integer(&[0x00, 0x80]), 128);
+    assert_eq!(decode_integer(&[0xff, 0x7f]), -129);
+}
+
+#[test]
+fn test_element() {
+    let long = vec![7; 300];
+    let encoded = element(OCTET_STRING, &long);
+    assert_eq!(&encoded[..4], &[0x04, 0x82, 0x01, 0x2c]);
+    let (read, length) = read_element(&encoded).unwrap().unwrap();
+    assert_eq!((read.tag, read.content.len(), length), (OCTET_STRING, 300, 304));
+    assert!(read_element(&encoded[..100]).unwrap().is_none());
+    assert!(read_element(&[0x30, 0x80]).is_err());
+}
+
+#[test]
+fn test_encode_filter() {
+    let attribute = |name: &[u8]| element(OCTET_STRING, name);
+    assert_eq!(
+        encode_filter("cn=Ada").unwrap(),
+        element(0xa3, &concat(&[attribute(b"cn"), element(OCTET_STRING, b"Ada")]))
+    );
+    assert_eq!(encode_filter("(mail=*)").unwrap(), element(0x87, b"mail"));
+    assert_eq!(
+        encode_filter("(cn=A*d*a)").unwrap(),
+        element(
+            0xa4,
+            &concat(&[
+                attribute(b"cn"),
+                element(
+                    SEQUENCE,
+                    &concat(&[element(0x80, b"A"), element(0x81, b"d"), element(0x82, b"a")])
+                ),
+            ])
+        )
+    );
+    assert_eq!(
+        encode_filter("(&(uid>=5)(!(cn=a\\2ab\\29)))").unwrap(),
+        element(
+            0xa0,
+            &concat(&[
+                element(0xa5, &concat(&[attribute(b"uid"), element(OCTET_STRING, b"5")])),
+                element(
+                    0xa2,
+                    &element(0xa3, &concat(&[attribute(b"cn"), element(OCTET_STRING, b"a*b)")]))
+                ),
+            ])
+        )
+    );
+    assert!(encode_filter("(cn=a").is_err());
+    assert!(encode_filter("(cn:dn:=a)").is_err());
+    assert!(encode_filter("(cn>=a*)").is_err());
+}
+
+#[test]
+fn test_decode_message() {
+    let attribute = element(
+        SEQUENCE,
+        &concat(&[
+            element(OCTET_STRING, b"mail"),
+            element(
+                SET,
+                &concat(&[element(OCTET_STRING, b"a@b"), element(OCTET_STRING, b"c@d")]),
+            ),
+        ]),
+    );
+    let entry = element(
+        SEARCH_RESULT_ENTRY,
+        &concat(&[element(OCTET_STRING, b"cn=a"), element(SEQUENCE, &attribute)]),
+    );
+    let encoded = message(2, &entry);
+    let (outer, _) = read_element(&encoded).unwrap().unwrap();
+    assert_eq!(
+        decode_message(outer.content).unwrap(),
+        (
+            2,
+            Reply::Entry(Entry {
+                dn: b"cn=a".to_vec(),
+                attributes: vec![(b"mail".to_vec(), vec![b"a@b".to_vec(), b"c@d".to_vec()])],
+            })
+        )
+    );
+    let done = element(
+        SEARCH_RESULT_DONE,
+        &concat(&[
+            integer(ENUMERATED, 32),
+            element(OCTET_STRING, b""),
+            element(OCTET_STRING, b"no such object"),
+        ]),
+    );
+    let encoded = message(2, &done);
+    let (outer, _) = read_element(&encoded).unwrap().unwrap();
+    assert_eq!(
+        decode_message(outer.content).unwrap(),
+        (2, Reply::Result(32, "no such object".to_string()))
+    );
+}
+
+#[test]
+fn test_parse_host() {
+    assert_eq!(parse_host("ldap.example.com"), ("ldap.example.com".to_string(), 389, false));
+    assert_eq!(parse_host("ldap.example.com:3268"), ("ldap.example.com".to_string(), 3268, false));
+    assert_eq!(parse_host("ldaps://dc1/"), ("dc1".to_string(), 636, true));
+    assert_eq!(parse_host("ldap://dc1:1389"), ("dc1".to_string(), 1389, false));
+}
+
+include!(concat!(env!("OUT_DIR"), "/ldap_exports.rs"));
@@ -10,11 +10,27 @@
 extern crate lazy_static;
 
 extern crate base64 as base64_crate;
+#[cfg(feature = "native-clipboard")]
+extern crate clipboard as clipboard_crate;
+#[cfg(feature = "native-encryption")]
+extern crate crypto as crypto_crate;
+extern crate encoding_rs;
+extern crate exif;
+extern crate idna;
+extern crate image;
+extern crate inflate;
 extern crate libc;
+extern crate lopdf;
 extern crate md5;
 extern crate rand;
+extern crate regex;
 extern crate sha1;
 extern crate sha2;
+#[cfg(feature = "native-ssh")]
+extern crate ssh2;
+extern crate url;
+extern crate xml;
+extern crate zip;
 
 // Wilfred/remacs#38 : Need to override the allocator for legacy unexec support on Mac.
 #[cfg(all(not(test), target_os = "macos"))]
@@ -40,39 +56,114 @@ mod lisp;
 mod vector_macros;
 mod str2sig;
 
+mod anchors;
+mod ansi_color;
 mod base64;
+mod bookmarks;
+mod buffer_text;
 mod buffers;
 mod category;
 mod character;
 mod chartable;
+mod clipboard;
 mod cmds;
+mod codecs;
+mod compile;
+mod cookies;
 mod crypto;
+mod csv;
 mod data;
+#[cfg(unix)]
+mod dbus;
+mod diff;
 mod dispnew;
+mod dnd;
+mod doc;
 mod editfns;
+mod elc;
+mod encryption;
+mod epub;
+mod eval_call;
+mod fileio;
+mod filelock;
+mod fill;
 mod floatfns;
 mod fns;
 mod fonts;
+mod format;
+mod format_spec;
 mod frames;
+mod frecency;
+mod fuzzy;
+mod git;
 mod hashtable;
+mod hex;
+mod html;
+mod ical;
+mod images;
+mod imenu;
+mod imap;
 mod indent;
 mod interactive;
+mod invisibility;
+mod kbd_macros;
+mod key_translation;
 mod keyboard;
 mod keymap;
+mod ldap;
+mod line_numbers;
+mod lines;
 mod lists;
+mod longlines;
+mod lread;
+mod macroexp;
+mod mailnet;
 mod marker;
 mod math;
+mod mime;
 mod minibuf;
+mod modeline;
+mod motion;
 mod multibyte;
+mod network;
+mod notifications;
 mod numbers;
+mod oauth2;
 mod obarray;
 mod objects;
+mod orgdates;
+mod paragraphs;
+mod pdf;
+mod persist;
+mod printer;
 mod process;
+mod recent_files;
+mod rect;
+mod registers;
+mod remote;
+mod scrolling;
+mod secrets;
+mod server;
+mod session;
+mod spell;
 mod strings;
+mod svg;
 mod symbols;
+mod sysinfo;
+mod tags;
+mod textprop;
 mod threads;
+mod time;
+mod touch;
+mod transform;
+mod transient_maps;
+mod trash;
+mod tty;
+mod urls;
 mod util;
 mod vectors;
+mod visual_lines;
+mod vterm;
 mod windows;
 
 #[cfg(all(not(test), target_os = "macos"))]
@@ -0,0 +1,259 @@
+//! Line numbers for `display-line-numbers'.
+//!
+//! Every redisplay of a window that shows line numbers counts the lines
+//! from the start of the buffer to the window start and to point.  In a
+//! large buffer that is a scan of most of its text, once per window.
+//! Here the number of lines before some positions of each buffer text
+//! is remembered, so that lines are only counted from the nearest such
+//! checkpoint.  Checkpoints before the first change of the text stay
+//! valid, so editing near the end of a buffer does not throw them away.
+
+use libc::{c_uchar, ptrdiff_t};
+use std::collections::HashMap;
+use std::slice;
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{buf_charpos_to_bytepos, EmacsInt, Lisp_Buffer};
+
+use buffer_text::{text_key, unchanged_before};
+use buffers::LispBufferRef;
+use lisp::LispObject;
+use lisp::defsubr;
+use threads::ThreadState;
+
+/// Lines counted over at least this many bytes are worth a checkpoint.
+const CHECKPOINT_SPACING: ptrdiff_t = 16 * 1024;
+
+/// The most checkpoints kept for a buffer text.
+const MAX_CHECKPOINTS: usize = 4096;
+
+/// Count the line ends in TEXT.  With selective display, a carriage
+/// return also ends a line.
+fn count_line_ends(text: &[u8], selective: bool) -> EmacsInt {
+    if selective {
+        text.iter().filter(|&&b| b == b'\n' || b == b'\r').count() as EmacsInt
+    } else {
+        text.iter().filter(|&&b| b == b'\n').count() as EmacsInt
+    }
+}
+
+/// Count the line ends of BUFFER between the byte positions FROM and TO.
+fn count_lines_between(
+    buffer: LispBufferRef,
+    from: ptrdiff_t,
+    to: ptrdiff_t,
+    selective: bool,
+) -> EmacsInt {
+    let segment = |start: ptrdiff_t, end: ptrdiff_t| {
+        if start >= end {
+            return 0;
+        }
+        let address: *const c_uchar = buffer.byte_pos_addr(start);
+        let text = unsafe { slice::from_raw_parts(address, (end - start) as usize) };
+        count_line_ends(text, selective)
+    };
+    let gap = buffer.gpt_byte().max(from).min(to);
+    segment(from, gap) + segment(gap, to)
+}
+
+/// The checkpoints of a buffer text.
+struct Checkpoints {
+    /// The character-change tick of the text they were counted in.
+    tick: EmacsInt,
+    /// Whether carriage returns were counted as line ends.
+    selective: bool,
+    /// Byte positions, in order, and the number of line ends before
+    /// each of them.
+    lines_before: Vec<(ptrdiff_t, EmacsInt)>,
+}
+
+impl Checkpoints {
+    /// Drop the checkpoints after the byte position END.
+    fn truncate(&mut self, end: ptrdiff_t) {
+        let kept = self.lines_before
+            .iter()
+            .position(|&(position, _)| position > end)
+            .unwrap_or(self.lines_before.len());
+        self.lines_before.truncate(kept);
+    }
+
+    /// Remember that there are LINES line ends before POSITION.
+    fn add(&mut self, position: ptrdiff_t, lines: EmacsInt) {
+        if self.lines_before.len() >= MAX_CHECKPOINTS {
+            // Keep every other checkpoint, which doubles their spacing.
+            let thinned = self.lines_before
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| index % 2 == 0)
+                .map(|(_, &checkpoint)| checkpoint)
+                .collect();
+            self.lines_before = thinned;
+        }
+        let index = match self.lines_before
+            .binary_search_by_key(&position, |&(position, _)| position)
+        {
+            Ok(_) => return,
+            Err(index) => index,
+        };
+        self.lines_before.insert(index, (position, lines));
+    }
+
+    /// Return the last checkpoint at or before POSITION.
+    fn before(&self, position: ptrdiff_t) -> Option<(ptrdiff_t, EmacsInt)> {
+        match self.lines_before
+            .binary_search_by_key(&position, |&(position, _)| position)
+        {
+            Ok(index) => Some(self.lines_before[index]),
+            Err(0) => None,
+            Err(index) => Some(self.lines_before[index - 1]),
+        }
+    }
+}
+
+lazy_static! {
+    static ref CHECKPOINTS: Mutex<HashMap<usize, Checkpoints>> = Mutex::new(HashMap::new());
+}
+
+/// Whether BUFFER counts carriage returns as line ends, as it does when
+/// `selective-display' is t.
+fn is_selective(buffer: LispBufferRef) -> bool {
+    let selective_display = LispObject::from(buffer.selective_display);
+    selective_display.is_not_nil() && !selective_display.is_integer()
+}
+
+/// Return the number of line ends of BUFFER before the byte position
+/// POSITION, counting from the nearest checkpoint.
+fn lines_before(buffer: LispBufferRef, position: ptrdiff_t) -> EmacsInt {
+    let selective = is_selective(buffer);
+    let tick = buffer.char_modifications();
+    let mut all = CHECKPOINTS.lock().unwrap();
+    let checkpoints = all.entry(text_key(buffer)).or_insert_with(|| Checkpoints {
+        tick,
+        selective,
+        lines_before: Vec::new(),
+    });
+    if checkpoints.selective != selective {
+        checkpoints.lines_before.clear();
+        checkpoints.selective = selective;
+    }
+    if checkpoints.tick != tick {
+        let unchanged = unchanged_before(buffer, checkpoints.tick).min(buffer.z());
+        let unchanged = unsafe { buf_charpos_to_bytepos(buffer.as_ptr(), unchanged) };
+        checkpoints.truncate(unchanged);
+        checkpoints.tick = tick;
+    }
+    let (start, lines) = checkpoints
+        .before(position)
+        .unwrap_or((buffer.beg_byte(), 0));
+    let lines = lines + count_lines_between(buffer, start, position, selective);
+    if position - start >= CHECKPOINT_SPACING {
+        checkpoints.add(position, lines);
+    }
+    lines
+}
+
+/// Count the line ends of the current buffer between the byte positions
+/// START and END, which may be outside the accessible portion.  Short
+/// stretches are counted directly, and long ones from checkpoints.
+/// Called by redisplay to number lines.
+#[no_mangle]
+pub extern "C" fn count_display_lines(start: ptrdiff_t, end: ptrdiff_t) -> ptrdiff_t {
+    let buffer = ThreadState::current_buffer();
+    let lines = if end - start < CHECKPOINT_SPACING {
+        count_lines_between(buffer, start, end, is_selective(buffer))
+    } else {
+        lines_before(buffer, end) - lines_before(buffer, start)
+    };
+    lines as ptrdiff_t
+}
+
+/// Forget the checkpoints of BUFFER.  Called when BUFFER is killed.
+#[no_mangle]
+pub extern "C" fn forget_line_checkpoints(buffer: *const Lisp_Buffer) {
+    CHECKPOINTS.lock().unwrap().remove(&(buffer as usize));
+}
+
+/// Exchange the checkpoints of BUFFER and OTHER, whose texts were
+/// swapped by `buffer-swap-text'.
+#[no_mangle]
+pub extern "C" fn swap_line_checkpoints(buffer: *const Lisp_Buffer, other: *const Lisp_Buffer) {
+    let mut all = CHECKPOINTS.lock().unwrap();
+    let first = all.remove(&(buffer as usize));
+    let second = all.remove(&(other as usize));
+    if let Some(checkpoints) = first {
+        all.insert(other as usize, checkpoints);
+    }
+    if let Some(checkpoints) = second {
+        all.insert(buffer as usize, checkpoints);
+    }
+}
+
+/// Return the line number at POSITION in the current buffer, as
+/// `display-line-numbers' shows it.
+/// POSITION defaults to point.  Lines are counted from the start of the
+/// accessible portion of the buffer, or from the start of the buffer if
+/// ABSOLUTE is non-nil.  This is like `line-number-at-pos', but the
+/// number of lines before some positions is remembered, so that in a
+/// large buffer only the lines after the nearest of them are counted.
+#[lisp_fn(min = "0")]
+pub fn buffer_line_number(position: LispObject, absolute: LispObject) -> LispObject {
+    let buffer = ThreadState::current_buffer();
+    let position = if position.is_nil() {
+        buffer.pt()
+    } else {
+        let position = position.as_fixnum_coerce_marker_or_error() as ptrdiff_t;
+        if position < buffer.begv || position > buffer.zv() {
+            args_out_of_range!(
+                LispObject::from_fixnum(position as EmacsInt),
+                LispObject::from_fixnum(buffer.begv as EmacsInt),
+                LispObject::from_fixnum(buffer.zv() as EmacsInt)
+            );
+        }
+        position
+    };
+    let start = if absolute.is_nil() {
+        buffer.begv_byte
+    } else {
+        buffer.beg_byte()
+    };
+    let end = unsafe { buf_charpos_to_bytepos(buffer.as_ptr(), position) };
+    LispObject::from_fixnum(count_display_lines(start, end) as EmacsInt + 1)
+}
+
+#[test]
+fn test_count_line_ends() {
+    assert_eq!(count_line_ends(b"", false), 0);
+    assert_eq!(count_line_ends(b"a\nb\r\nc\rd\n", false), 3);
+    assert_eq!(count_line_ends(b"a\nb\r\nc\rd\n", true), 5);
+}
+
+#[test]
+fn test_checkpoints() {
+    let mut checkpoints = Checkpoints {
+        tick: 0,
+        selective: false,
+        lines_before: Vec::new(),
+    };
+    assert_eq!(checkpoints.before(100), None);
+    checkpoints.add(300, 30);
+    checkpoints.add(100, 10);
+    checkpoints.add(200, 20);
+    checkpoints.add(200, 20);
+    assert_eq!(checkpoints.lines_before, vec![(100, 10), (200, 20), (300, 30)]);
+    assert_eq!(checkpoints.before(99), None);
+    assert_eq!(checkpoints.before(100), Some((100, 10)));
+    assert_eq!(checkpoints.before(250), Some((200, 20)));
+    checkpoints.truncate(250);
+    assert_eq!(checkpoints.lines_before, vec![(100, 10), (200, 20)]);
+    for position in 0..MAX_CHECKPOINTS as ptrdiff_t {
+        checkpoints.add(1000 + position, position);
+    }
+    assert!(checkpoints.lines_before.len() <= MAX_CHECKPOINTS);
+    assert!(checkpoints
+        .lines_before
+        .windows(2)
+        .all(|pair| pair[0].0 < pair[1].0));
+}
+
+include!(concat!(env!("OUT_DIR"), "/line_numbers_exports.rs"));
@@ -0,0 +1,279 @@
+//! Streaming line reader for file and process input.
+//!
+//! Input arrives in chunks whose boundaries fall anywhere, including in
+//! the middle of a line or between the CR and LF of a CRLF.
+//! `LineSplitter' keeps the unfinished part of the last line until the
+//! chunk that completes it, so each line is delivered to Lisp exactly
+//! once, and no string larger than a line is ever made.
+
+use std::fs::File;
+use std::io::Read;
+
+use libc::{c_char, c_uchar, c_void, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_string, make_string_from_bytes, make_unibyte_string,
+                 multibyte_chars_in_text, record_unwind_protect_ptr, unbind_to, EmacsInt};
+use remacs_sys::Qnil;
+
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{plist_get, plist_put};
+use process::{process_plist, set_process_plist};
+use util::{expand_file_name_to_path, report_io_error};
+
+/// The size of the chunks `with-file-lines' reads.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Split a stream of bytes into lines.  Lines end with LF or CRLF; the
+/// terminator is not part of the line.
+#[derive(Default)]
+pub struct LineSplitter {
+    pending: Vec<u8>,
+}
+
+impl LineSplitter {
+    pub fn new() -> LineSplitter {
+        LineSplitter::default()
+    }
+
+    /// Split CHUNK, calling F with each line it completes.
+    pub fn feed<F>(&mut self, chunk: &[u8], mut f: F)
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut rest = chunk;
+        while let Some(newline) = rest.iter().position(|&b| b == b'\n') {
+            if self.pending.is_empty() {
+                f(strip_cr(&rest[..newline]));
+            } else {
+                self.pending.extend_from_slice(&rest[..newline]);
+                f(strip_cr(&self.pending));
+                self.pending.clear();
+            }
+            rest = &rest[newline + 1..];
+        }
+        self.pending.extend_from_slice(rest);
+    }
+
+    /// Return the unfinished last line, which is empty if the input so
+    /// far ended with a line terminator.
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+}
+
+fn strip_cr(line: &[u8]) -> &[u8] {
+    match line.last() {
+        Some(&b'\r') => &line[..line.len() - 1],
+        _ => line,
+    }
+}
+
+/// Make a Lisp string of LINE, a line read from a file.  LINE is
+/// decoded with CODING if that is non-nil.  Otherwise it is taken as
+/// UTF-8 if it is valid UTF-8, and as raw bytes if not.
+pub fn file_line_string(line: &[u8], coding: LispObject) -> LispObject {
+    let ptr = line.as_ptr() as *const c_char;
+    let len = line.len() as ptrdiff_t;
+    if coding.is_not_nil() {
+        let raw = LispObject::from(unsafe { make_unibyte_string(ptr, len) });
+        call!(intern("decode-coding-string"), raw, coding)
+    } else if ::std::str::from_utf8(line).is_ok() {
+        LispObject::from(unsafe { make_string(ptr, len) })
+    } else {
+        LispObject::from(unsafe { make_unibyte_string(ptr, len) })
+    }
+}
+
+/// Make a Lisp string of LINE, a line cut from a string whose
+/// multibyteness is MULTIBYTE.
+fn string_line(line: &[u8], multibyte: bool) -> LispObject {
+    let nbytes = line.len() as ptrdiff_t;
+    let nchars = if multibyte {
+        unsafe { multibyte_chars_in_text(line.as_ptr() as *const c_uchar, nbytes) }
+    } else {
+        nbytes
+    };
+    let string = unsafe { make_string_from_bytes(line.as_ptr() as *const c_char, nchars, nbytes) };
+    LispObject::from(string)
+}
+
+/// The state of `with-file-lines'.  It is kept off the stack so that it
+/// can be freed when FN exits nonlocally.
+struct FileLines {
+    file: File,
+    splitter: LineSplitter,
+    buf: Vec<u8>,
+}
+
+/// Close the file and free the buffers of a `with-file-lines', once it
+/// is done or when FN exits nonlocally.
+unsafe extern "C" fn free_file_lines(state: *mut c_void) {
+    drop(Box::from_raw(state as *mut FileLines));
+}
+
+/// Call FN with each line of FILE, and return the number of lines.
+/// FILE is read in chunks, so its size does not matter.
+///
+/// Lines end with LF or CRLF, and are passed to FN without their
+/// terminator.  A final line without a terminator is passed as well.
+///
+/// If CODING is non-nil, it is the coding system that decodes each line.
+/// Otherwise each line that is valid UTF-8 is decoded as UTF-8, and
+/// other lines are passed as unibyte strings.
+#[lisp_fn(min = "2")]
+pub fn with_file_lines(file: LispObject, function: LispObject, coding: LispObject) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let state = match File::open(&path) {
+        Ok(handle) => Box::into_raw(Box::new(FileLines {
+            file: handle,
+            splitter: LineSplitter::new(),
+            buf: vec![0; CHUNK_SIZE],
+        })),
+        Err(err) => report_io_error(b"Opening input file\0", file, &err),
+    };
+    let count = specpdl_index();
+    unsafe { record_unwind_protect_ptr(free_file_lines, state as *mut c_void) };
+    let state = unsafe { &mut *state };
+
+    let mut lines: EmacsInt = 0;
+    loop {
+        let nread = match state.file.read(&mut state.buf) {
+            Ok(0) => break,
+            Ok(nread) => nread,
+            Err(err) => report_io_error(b"Read error\0", file, &err),
+        };
+        state.splitter.feed(&state.buf[..nread], |line| {
+            call!(function, file_line_string(line, coding));
+            lines += 1;
+        });
+    }
+    if !state.splitter.pending().is_empty() {
+        call!(function, file_line_string(state.splitter.pending(), coding));
+        lines += 1;
+    }
+
+    unsafe { unbind_to(count, Qnil) };
+    LispObject::from_natnum(lines)
+}
+
+/// The process property holding the unfinished line of a line filter.
+fn pending_property() -> LispObject {
+    intern("line-filter--pending")
+}
+
+/// Process filter that delivers complete lines of output.
+/// STRING is output from PROCESS; FN is called with PROCESS and each line
+/// that STRING completes, without its LF or CRLF terminator.  The
+/// unfinished last line is kept on PROCESS's plist until more output
+/// arrives; `line-filter-flush' delivers it.
+///
+/// Use `make-line-filter' to make a filter function from FN.
+#[lisp_fn]
+pub fn line_filter_feed(
+    function: LispObject,
+    process: LispObject,
+    string: LispObject,
+) -> LispObject {
+    let plist = process_plist(process);
+    let pending = plist_get(plist, pending_property());
+    let string = if pending.is_nil() {
+        string.as_string_or_error();
+        string
+    } else {
+        call!(intern("concat"), pending, string)
+    };
+
+    let multibyte = string.as_string_or_error().is_multibyte();
+    let mut splitter = LineSplitter::new();
+    let mut lines = LispObject::constant_nil();
+    splitter.feed(string.as_string_or_error().as_slice(), |line| {
+        lines = LispObject::cons(string_line(line, multibyte), lines);
+    });
+
+    let rest = if splitter.pending().is_empty() {
+        LispObject::constant_nil()
+    } else {
+        string_line(splitter.pending(), multibyte)
+    };
+    set_process_plist(process, plist_put(plist, pending_property(), rest));
+
+    // The lines are made before FN runs, since FN may run GC, which can
+    // move the data of STRING.
+    let lines = lines
+        .iter_cars()
+        .fold(LispObject::constant_nil(), |acc, line| LispObject::cons(line, acc));
+    for line in lines.iter_cars() {
+        call!(function, process, line);
+    }
+    LispObject::constant_nil()
+}
+
+/// Call FN with PROCESS and the unfinished last line of output that
+/// `line-filter-feed' has kept for PROCESS, if there is one.  Use this
+/// in the process sentinel, to see output that does not end in a
+/// newline.  Return the line, or nil if there was none.
+#[lisp_fn]
+pub fn line_filter_flush(function: LispObject, process: LispObject) -> LispObject {
+    let plist = process_plist(process);
+    let pending = plist_get(plist, pending_property());
+    if pending.is_not_nil() {
+        set_process_plist(
+            process,
+            plist_put(plist, pending_property(), LispObject::constant_nil()),
+        );
+        call!(function, process, pending);
+    }
+    pending
+}
+
+/// Return a process filter that calls FN with the process and each
+/// complete line of its output.  See `line-filter-feed'.
+#[lisp_fn]
+pub fn make_line_filter(function: LispObject) -> LispObject {
+    call!(intern("apply-partially"), intern("line-filter-feed"), function)
+}
+
+#[cfg(test)]
+fn split_all(chunks: &[&[u8]]) -> (Vec<Vec<u8>>, Vec<u8>) {
+    let mut splitter = LineSplitter::new();
+    let mut lines = Vec::new();
+    for chunk in chunks {
+        splitter.feed(chunk, |line| lines.push(line.to_vec()));
+    }
+    (lines, splitter.pending().to_vec())
+}
+
+#[test]
+fn test_line_splitter() {
+    let (lines, pending) = split_all(&[b"one\ntwo\r\nthree"]);
+    assert_eq!(lines, vec![b"one".to_vec(), b"two".to_vec()]);
+    assert_eq!(pending, b"three".to_vec());
+
+    let (lines, pending) = split_all(&[b"\n\n"]);
+    assert_eq!(lines, vec![Vec::new(), Vec::new()]);
+    assert!(pending.is_empty());
+
+    let (lines, pending) = split_all(&[]);
+    assert!(lines.is_empty());
+    assert!(pending.is_empty());
+}
+
+#[test]
+fn test_line_splitter_chunk_boundaries() {
+    // A line split across chunks, and a CRLF split between its CR and LF.
+    let (lines, pending) = split_all(&[b"hel", b"lo\r", b"\nwor", b"ld\n", b"!"]);
+    assert_eq!(lines, vec![b"hello".to_vec(), b"world".to_vec()]);
+    assert_eq!(pending, b"!".to_vec());
+
+    // Every byte in its own chunk.
+    let input = b"a\r\nbc\nd";
+    let chunks: Vec<&[u8]> = input.chunks(1).collect();
+    let (lines, pending) = split_all(&chunks);
+    assert_eq!(lines, vec![b"a".to_vec(), b"bc".to_vec()]);
+    assert_eq!(pending, b"d".to_vec());
+}
+
+include!(concat!(env!("OUT_DIR"), "/lines_exports.rs"));
@@ -18,10 +18,11 @@ use remacs_sys::{font, EmacsDouble, EmacsInt, EmacsUint, EqualKind, Fcons, Pseud
                  USE_LSB_TAG, VALBITS, VALMASK};
 use remacs_sys::{Lisp_Cons, Lisp_Float, Lisp_Misc_Any, Lisp_Misc_Type, Lisp_Object, Lisp_Subr,
                  Lisp_Type};
-use remacs_sys::{Qarrayp, Qbufferp, Qchar_table_p, Qcharacterp, Qconsp, Qfloatp, Qframe_live_p,
-                 Qframep, Qhash_table_p, Qinteger_or_marker_p, Qintegerp, Qlistp, Qmarkerp, Qnil,
-                 Qnumber_or_marker_p, Qnumberp, Qoverlayp, Qplistp, Qprocessp, Qstringp, Qsymbolp,
-                 Qt, Qthreadp, Qunbound, Qwholenump, Qwindow_live_p, Qwindow_valid_p, Qwindowp};
+use remacs_sys::{Qarrayp, Qautoload, Qbufferp, Qchar_table_p, Qcharacterp, Qconsp, Qfloatp,
+                 Qframe_live_p, Qframep, Qhash_table_p, Qinteger_or_marker_p, Qintegerp, Qlistp,
+                 Qmarkerp, Qnil, Qnumber_or_marker_p, Qnumberp, Qoverlayp, Qplistp, Qprocessp,
+                 Qstringp, Qsymbolp, Qt, Qthreadp, Qunbound, Qwholenump, Qwindow_live_p,
+                 Qwindow_valid_p, Qwindowp};
 
 use remacs_sys::{internal_equal, lispsym, make_float, misc_get_ty};
 
@@ -106,6 +107,14 @@ impl From<EmacsInt> for LispObject {
     }
 }
 
+/// Convert an `Option` back into a Lisp value: `None` becomes nil.
+impl From<Option<LispObject>> for LispObject {
+    #[inline]
+    fn from(o: Option<LispObject>) -> Self {
+        o.unwrap_or_else(LispObject::constant_nil)
+    }
+}
+
 impl LispObject {
     pub fn get_type(self) -> Lisp_Type {
         let raw = self.to_raw() as EmacsUint;
@@ -497,6 +506,10 @@ impl LispObject {
             .map_or(false, |v| v.is_pseudovector(PseudovecType::PVEC_SUBR))
     }
 
+    pub fn as_subr(self) -> Option<LispSubrRef> {
+        self.as_vectorlike().map_or(None, |v| v.as_subr())
+    }
+
     pub fn is_buffer(self) -> bool {
         self.as_vectorlike()
             .map_or(false, |v| v.is_pseudovector(PseudovecType::PVEC_BUFFER))
@@ -833,6 +846,14 @@ impl LispObject {
         }
     }
 
+    /// Return true if this is an autoload object, i.e. a list whose car
+    /// is `autoload'.  Equivalent to `AUTOLOADP` in C.
+    #[inline]
+    pub fn is_autoload(self) -> bool {
+        self.as_cons()
+            .map_or(false, |cons| cons.car().eq(LispObject::from(Qautoload)))
+    }
+
     #[inline]
     pub fn as_cons_or_error(self) -> LispCons {
         if self.is_cons() {
@@ -1068,6 +1089,16 @@ impl LispObject {
         self.to_raw() != Qnil
     }
 
+    /// Return `None` if this object is nil, `Some(self)` otherwise.
+    #[inline]
+    pub fn into_option(self) -> Option<LispObject> {
+        if self.is_nil() {
+            None
+        } else {
+            Some(self)
+        }
+    }
+
     #[inline]
     pub fn is_t(self) -> bool {
         self.to_raw() == Qt
@@ -1168,6 +1199,10 @@ impl LispObject {
 /// of arguments.
 pub const MANY: i16 = -2;
 
+/// Used to denote special forms, which receive their arguments
+/// unevaluated as a single list.
+pub const UNEVALLED: i16 = -1;
+
 /// Internal function to get a displayable string out of a Lisp string.
 fn display_string(obj: LispObject) -> String {
     let s = obj.as_string().unwrap();
@@ -1263,6 +1298,11 @@ macro_rules! export_lisp_fns {
                 )+
             }
         }
+
+        /// Return the primitives defined in this module.
+        pub fn rust_subrs() -> Vec<*const ::remacs_sys::Lisp_Subr> {
+            vec![$(concat_idents!(S, $f).as_ptr()),+]
+        }
     }
 }
 
@@ -1272,3 +1312,13 @@ fn test_basic_float() {
     let result = mock_float!(val);
     assert!(result.is_float() && result.as_float() == Some(val));
 }
+
+#[test]
+fn test_option_conversion() {
+    let nil = LispObject::constant_nil();
+    let t = LispObject::constant_t();
+    assert!(nil.into_option().is_none());
+    assert!(t.into_option() == Some(t));
+    assert!(LispObject::from(None).is_nil());
+    assert!(LispObject::from(Some(t)) == t);
+}
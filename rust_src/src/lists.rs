@@ -4,7 +4,7 @@ use remacs_macros::lisp_fn;
 use remacs_sys::{EmacsInt, Qcircular_list, Qplistp};
 use remacs_sys::globals;
 
-use lisp::LispObject;
+use lisp::{LispCons, LispObject};
 use lisp::defsubr;
 
 /// Return t if OBJECT is not a cons cell.  This includes nil.
@@ -81,17 +81,13 @@ pub fn cdr(list: LispObject) -> LispObject {
 /// Return the car of OBJECT if it is a cons cell, or else nil.
 #[lisp_fn]
 pub fn car_safe(object: LispObject) -> LispObject {
-    object
-        .as_cons()
-        .map_or(LispObject::constant_nil(), |cons| cons.car())
+    object.as_cons().map(|cons| cons.car()).into()
 }
 
 /// Return the cdr of OBJECT if it is a cons cell, or else nil.
 #[lisp_fn]
 pub fn cdr_safe(object: LispObject) -> LispObject {
-    object
-        .as_cons()
-        .map_or(LispObject::constant_nil(), |cons| cons.cdr())
+    object.as_cons().map(|cons| cons.cdr()).into()
 }
 
 /// Take cdr N times on LIST, return the result.
@@ -109,9 +105,7 @@ pub fn nthcdr(n: LispObject, list: LispObject) -> LispObject {
 /// N counts from zero.  If LIST is not that long, nil is returned.
 #[lisp_fn]
 pub fn nth(n: LispObject, list: LispObject) -> LispObject {
-    list.iter_cars()
-        .nth(n.as_fixnum_or_error() as usize)
-        .map_or(LispObject::constant_nil(), |c| c)
+    list.iter_cars().nth(n.as_fixnum_or_error() as usize).into()
 }
 
 /// Return non-nil if ELT is an element of LIST.  Comparison done with `eq'.
@@ -178,10 +172,9 @@ pub fn assoc(key: LispObject, list: LispObject, testfn: LispObject) -> LispObjec
     for tail in list.iter_tails() {
         let item = tail.car();
         if let Some(item_cons) = item.as_cons() {
-            let is_equal = if testfn.is_nil() {
-                key.eq(item_cons.car()) || key.equal(item_cons.car())
-            } else {
-                call!(testfn, key, item_cons.car()).is_not_nil()
+            let is_equal = match testfn.into_option() {
+                None => key.eq(item_cons.car()) || key.equal(item_cons.car()),
+                Some(testfn) => call!(testfn, key, item_cons.car()).is_not_nil(),
             };
             if is_equal {
                 return item;
@@ -233,18 +226,17 @@ pub fn rassoc(key: LispObject, list: LispObject) -> LispObject {
 /// argument.
 #[lisp_fn]
 pub fn delq(elt: LispObject, mut list: LispObject) -> LispObject {
-    let mut prev = LispObject::constant_nil();
+    let mut prev: Option<LispCons> = None;
     for tail in list.iter_tails() {
         let item = tail.car();
         if elt.eq(item) {
             let rest = tail.cdr();
-            if prev.is_nil() {
-                list = rest;
-            } else {
-                setcdr(prev, rest);
+            match prev {
+                None => list = rest,
+                Some(prev) => prev.set_cdr(rest),
             }
         } else {
-            prev = tail.as_obj();
+            prev = Some(tail);
         }
     }
     list
@@ -386,12 +378,9 @@ pub fn lax_plist_put(plist: LispObject, prop: LispObject, val: LispObject) -> Li
 pub fn get(symbol: LispObject, propname: LispObject) -> LispObject {
     let sym = symbol.as_symbol_or_error();
     let plist_env = LispObject::from(unsafe { globals.f_Voverriding_plist_environment });
-    let propval = plist_get(cdr(assq(symbol, plist_env)), propname);
-    if propval.is_not_nil() {
-        propval
-    } else {
-        plist_get(sym.get_plist(), propname)
-    }
+    plist_get(cdr(assq(symbol, plist_env)), propname)
+        .into_option()
+        .unwrap_or_else(|| plist_get(sym.get_plist(), propname))
 }
 
 /// Store SYMBOL's PROPNAME property with value VALUE.
@@ -459,23 +448,27 @@ pub fn inorder(pred: LispObject, a: LispObject, b: LispObject) -> bool {
 /// Merge step of linked-list sorting.
 #[no_mangle]
 pub fn merge(mut l1: LispObject, mut l2: LispObject, pred: LispObject) -> LispObject {
-    let mut tail = LispObject::constant_nil();
+    let mut tail: Option<LispObject> = None;
     let mut value = LispObject::constant_nil();
 
     loop {
         if l1.is_nil() {
-            if tail.is_nil() {
-                return l2;
-            }
-            setcdr(tail, l2);
-            return value;
+            return match tail {
+                None => l2,
+                Some(tail) => {
+                    setcdr(tail, l2);
+                    value
+                }
+            };
         }
         if l2.is_nil() {
-            if tail.is_nil() {
-                return l1;
-            }
-            setcdr(tail, l1);
-            return value;
+            return match tail {
+                None => l1,
+                Some(tail) => {
+                    setcdr(tail, l1);
+                    value
+                }
+            };
         }
 
         let item;
@@ -486,12 +479,13 @@ pub fn merge(mut l1: LispObject, mut l2: LispObject, pred: LispObject) -> LispOb
             item = l2;
             l2 = cdr(l2);
         }
-        if tail.is_nil() {
-            value = item;
-        } else {
-            setcdr(tail, item);
+        match tail {
+            None => value = item,
+            Some(tail) => {
+                setcdr(tail, item);
+            }
         }
-        tail = item;
+        tail = Some(item);
     }
 }
 
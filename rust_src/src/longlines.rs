@@ -0,0 +1,195 @@
+//! Classification of file contents that make redisplay slow.
+//!
+//! Redisplay scans whole lines, so a buffer holding minified code or
+//! binary data can freeze Emacs as soon as it is shown.  The text
+//! inserted by `insert-file-contents' is classified here before it is
+//! displayed, so that `file-content-classification-functions' can
+//! choose a cheaper mode in time.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{globals, make_buffer_string, EmacsInt, Lisp_Object};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+
+/// Only this many characters at the start of a text are examined.
+const SCAN_LIMIT: EmacsInt = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Classification {
+    /// Some line is at least as long as the threshold.
+    LongLines,
+    /// Long lines with almost no whitespace, as produced by minifiers.
+    Minified,
+    /// Null bytes or many control characters or undecodable bytes.
+    Binary,
+}
+
+impl Classification {
+    fn to_lisp(self) -> LispObject {
+        match self {
+            Classification::LongLines => intern("long-lines"),
+            Classification::Minified => intern("minified"),
+            Classification::Binary => intern("binary"),
+        }
+    }
+}
+
+/// Whether byte B of a text is a sign of binary data.  In multibyte
+/// text, the bytes 0xC0 and 0xC1 only start raw 8-bit bytes, which are
+/// what is left of the bytes that could not be decoded.
+fn is_binary_byte(b: u8, multibyte: bool) -> bool {
+    match b {
+        b'\t' | b'\n' | 0x0B | 0x0C | b'\r' | 0x1B => false,
+        0x00...0x1F | 0x7F => true,
+        0xC0 | 0xC1 => multibyte,
+        _ => false,
+    }
+}
+
+/// Classify TEXT, the internal representation of a string.  THRESHOLD
+/// is the line length, in bytes, from which a line is long.
+fn classify(text: &[u8], multibyte: bool, threshold: usize) -> Option<Classification> {
+    if text.contains(&0) {
+        return Some(Classification::Binary);
+    }
+    let binary = text.iter()
+        .filter(|&&b| is_binary_byte(b, multibyte))
+        .count();
+    if binary * 10 > text.len() {
+        return Some(Classification::Binary);
+    }
+
+    let mut long_bytes = 0;
+    let mut long_whitespace = 0;
+    for line in text.split(|&b| b == b'\n') {
+        if line.len() >= threshold {
+            long_bytes += line.len();
+            long_whitespace += line.iter().filter(|&&b| b == b' ' || b == b'\t').count();
+        }
+    }
+    if long_bytes == 0 {
+        None
+    } else if long_whitespace * 20 < long_bytes {
+        Some(Classification::Minified)
+    } else {
+        Some(Classification::LongLines)
+    }
+}
+
+/// The line length from which lines are long, or None if text should
+/// not be classified.
+fn long_line_threshold() -> Option<usize> {
+    let threshold = LispObject::from(unsafe { globals.f_Vfile_content_long_line_threshold });
+    match threshold.as_fixnum() {
+        Some(threshold) if threshold > 0 => Some(threshold as usize),
+        _ => None,
+    }
+}
+
+/// Classify the text of STRING, a string copied from a buffer.
+fn classify_string(string: LispObject, threshold: usize) -> LispObject {
+    let string = string.as_string_or_error();
+    match classify(string.as_slice(), string.is_multibyte(), threshold) {
+        Some(classification) => classification.to_lisp(),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Classify the text from START to END that `insert-file-contents' just
+/// inserted in the current buffer.  Record the result in
+/// `file-content-classification' and run the hook if there is one.
+/// VISIT is non-nil if the buffer visits the file.
+#[no_mangle]
+pub extern "C" fn classify_inserted_text(start: ptrdiff_t, end: ptrdiff_t, visit: Lisp_Object) {
+    let threshold = match long_line_threshold() {
+        Some(threshold) => threshold,
+        None => return,
+    };
+    let end = end.min(start + SCAN_LIMIT as ptrdiff_t);
+    let text = LispObject::from(unsafe { make_buffer_string(start, end, false) });
+    let classification = classify_string(text, threshold);
+    // Inserting a harmless file in a buffer does not make the rest of
+    // its text harmless, but visiting one does.
+    if classification.is_not_nil() || LispObject::from(visit).is_not_nil() {
+        call!(
+            intern("set"),
+            intern("file-content-classification"),
+            classification
+        );
+    }
+    if classification.is_not_nil() {
+        call!(
+            intern("run-hook-with-args"),
+            intern("file-content-classification-functions"),
+            classification
+        );
+    }
+}
+
+/// Classify the text between START and END in the current buffer.
+/// Return `binary' if it looks like binary data, `minified' if it has
+/// long lines with hardly any whitespace, as minified code does,
+/// `long-lines' if it has other lines of at least
+/// `file-content-long-line-threshold' characters, and nil otherwise.
+/// Only the first megabyte or so of the text is examined.
+///
+/// This is how `insert-file-contents' sets `file-content-classification'.
+#[lisp_fn]
+pub fn file_content_classify_region(start: LispObject, end: LispObject) -> LispObject {
+    let threshold = match long_line_threshold() {
+        Some(threshold) => threshold,
+        None => return LispObject::constant_nil(),
+    };
+    let start = start.as_fixnum_coerce_marker_or_error();
+    let end = end.as_fixnum_coerce_marker_or_error();
+    let (start, end) = (start.min(end), start.max(end));
+    let text = call!(
+        intern("buffer-substring-no-properties"),
+        LispObject::from_fixnum(start),
+        LispObject::from_fixnum(end.min(start + SCAN_LIMIT))
+    );
+    classify_string(text, threshold)
+}
+
+#[test]
+fn test_classify() {
+    let threshold = 100;
+    assert_eq!(classify(b"short\nlines\n", true, threshold), None);
+
+    let mut prose = Vec::new();
+    for _ in 0..30 {
+        prose.extend_from_slice(b"word ");
+    }
+    assert_eq!(
+        classify(&prose, true, threshold),
+        Some(Classification::LongLines)
+    );
+
+    let mut minified = Vec::new();
+    for _ in 0..30 {
+        minified.extend_from_slice(b"function(a,b){return a+b};");
+    }
+    assert_eq!(
+        classify(&minified, true, threshold),
+        Some(Classification::Minified)
+    );
+
+    assert_eq!(
+        classify(b"ELF\0\x01\x02", true, threshold),
+        Some(Classification::Binary)
+    );
+    assert_eq!(
+        classify(b"a\x01b\x02c\x03", false, threshold),
+        Some(Classification::Binary)
+    );
+    assert_eq!(classify(b"caf\xc3\xa9\n", true, threshold), None);
+    assert_eq!(
+        classify(b"\xc1\x80\xc1\x81ab", true, threshold),
+        Some(Classification::Binary)
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/longlines_exports.rs"));
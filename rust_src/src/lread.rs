@@ -0,0 +1,96 @@
+//! Lisp reader support.
+//!
+//! The reader proper is still `read1' in lread.c.  This holds the parts
+//! of it that construct objects from their read syntax, so that the
+//! reader can move here piece by piece.
+
+use remacs_sys::{globals, EmacsInt, Fpurecopy, Lisp_Object};
+
+use hashtable::puthash;
+use lisp::{intern, LispObject};
+use lists::plist_get;
+use strings::lisp_string;
+
+/// The `#s(hash-table ...)' properties, with the `make-hash-table'
+/// keywords they stand for.
+const HASH_TABLE_PARAMETERS: [(&str, &str); 6] = [
+    ("size", ":size"),
+    ("test", ":test"),
+    ("weakness", ":weakness"),
+    ("rehash-size", ":rehash-size"),
+    ("rehash-threshold", ":rehash-threshold"),
+    ("purecopy", ":purecopy"),
+];
+
+fn invalid_read_syntax(message: &str) -> ! {
+    xsignal!(intern("invalid-read-syntax").to_raw(), lisp_string(message));
+}
+
+/// Build the hash table read as #s(hash-table PLIST...).  While dumping,
+/// a table with a non-nil `purecopy' property is copied to pure storage
+/// right away, so that tables in preloaded files end up in the dump.
+fn read_hash_table(plist: LispObject) -> LispObject {
+    let mut args = Vec::new();
+    for &(property, keyword) in &HASH_TABLE_PARAMETERS {
+        let value = plist_get(plist, intern(property));
+        if value.is_not_nil() {
+            args.push(intern(keyword));
+            args.push(value);
+        }
+    }
+    let args = args.into_iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, arg| LispObject::cons(arg, tail));
+    let table = call!(intern("apply"), intern("make-hash-table"), args);
+
+    let mut data = plist_get(plist, intern("data"));
+    while let Some(cell) = data.as_cons() {
+        let value = match cell.cdr().as_cons() {
+            Some(value) => value,
+            None => error!("Odd number of elements in hash table data"),
+        };
+        puthash(cell.car(), value.car(), table);
+        data = value.cdr();
+    }
+
+    let dumping = LispObject::from(unsafe { globals.f_Vpurify_flag }).is_not_nil();
+    if dumping && plist_get(plist, intern("purecopy")).is_not_nil() {
+        LispObject::from(unsafe { Fpurecopy(table.to_raw()) })
+    } else {
+        table
+    }
+}
+
+/// Build the record read as #s(TYPE SLOTS...).
+fn read_record(form: LispObject) -> LispObject {
+    let elements: Vec<LispObject> = form.iter_cars().collect();
+    if elements.is_empty() {
+        invalid_read_syntax("#s");
+    }
+    let slots = LispObject::from_natnum((elements.len() - 1) as EmacsInt);
+    let record = call!(
+        intern("make-record"),
+        elements[0],
+        slots,
+        LispObject::constant_nil()
+    );
+    for (i, &elt) in elements.iter().enumerate().skip(1) {
+        call!(intern("aset"), record, LispObject::from_natnum(i as EmacsInt), elt);
+    }
+    record
+}
+
+/// Construct the object whose read syntax is #s(FORM...), where FORM is
+/// the list read after the `#s'.  This is a hash table if FORM starts
+/// with `hash-table', and a record otherwise.
+#[no_mangle]
+pub extern "C" fn read_extended_object(form: Lisp_Object) -> Lisp_Object {
+    let form = LispObject::from(form);
+    let head = form.as_cons().map_or(LispObject::constant_nil(), |c| c.car());
+    let object = if head.eq(intern("hash-table")) {
+        read_hash_table(form.as_cons().unwrap().cdr())
+    } else {
+        read_record(form)
+    };
+    object.to_raw()
+}
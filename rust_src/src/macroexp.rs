@@ -0,0 +1,550 @@
+//! Macro expansion: `macroexpand-1', `macroexpand' and
+//! `macroexpand-all'.
+//!
+//! The warnings about obsolete macros and quoted lambdas, and the
+//! error handling around compiler macros, are still done by the helpers
+//! in macroexp.el.  Until that file is loaded they are skipped.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{maybe_quit, EmacsInt, FUNCTIONP, Fapply, Lisp_Object};
+use remacs_sys::{Qapply, Qbackquote_listX, Qbyte_obsolete_info, Qcompiler_macro, Qcond,
+                 Qcondition_case, Qdefconst, Qdefvar, Qfuncall, Qfunction, Qlambda,
+                 Qlet, Qletx, Qmacro, Qmacroexpand_all_environment, Qmapatoms, Qmapc,
+                 Qmapcar, Qmapconcat, Qquote, Qsort};
+use remacs_sys::globals;
+
+use eval::LispResult;
+use eval_call::{autoload_do_load, format_error, with_let_binding, Autoload};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{assq, car, car_safe, cdr, cdr_safe, get};
+use strings::lisp_string;
+
+/// Return the value of `max-macroexpand-depth', or None if expansion
+/// is unlimited.
+fn depth_limit() -> Option<EmacsInt> {
+    let limit = unsafe { globals.f_max_macroexpand_depth };
+    if limit > 0 {
+        Some(limit)
+    } else {
+        None
+    }
+}
+
+/// Return true if SYMBOL is a symbol with a function definition.
+fn is_fbound(symbol: LispObject) -> bool {
+    symbol
+        .as_symbol()
+        .map_or(false, |sym| sym.get_function().is_not_nil())
+}
+
+/// Return true if the function definition of OBJECT, following
+/// aliases, is a macro or an autoload of a macro.  Like `macrop'.
+fn is_macro(object: LispObject) -> bool {
+    let mut def = object;
+    while let Some(sym) = def.as_symbol() {
+        def = sym.get_function();
+        if def.is_nil() {
+            return false;
+        }
+    }
+    match Autoload::from_object(def) {
+        Some(autoload) => autoload.is_macro(),
+        None => car_safe(def).eq(LispObject::from(Qmacro)),
+    }
+}
+
+/// Return the function that expands FORM, if FORM is a macro call in
+/// ENVIRONMENT.  Aliases are followed, and autoloaded macros loaded.
+fn macro_expander(form: LispObject, environment: LispObject) -> LispResult<Option<LispObject>> {
+    let head = match form.as_cons() {
+        Some(cons) => cons.car(),
+        None => return Ok(None),
+    };
+
+    // Trace symbol aliases to other symbols until we get a symbol that
+    // is not an alias, or one that ENVIRONMENT defines.
+    let mut def = head;
+    let mut sym = head;
+    let mut tem = LispObject::constant_nil();
+    while let Some(s) = def.as_symbol() {
+        unsafe { maybe_quit() };
+        sym = def;
+        tem = assq(sym, environment);
+        if tem.is_nil() {
+            def = s.get_function();
+            if def.is_not_nil() {
+                continue;
+            }
+        }
+        break;
+    }
+
+    if tem.is_not_nil() {
+        // A nil expander in ENVIRONMENT hides the global definition.
+        let expander = cdr(tem);
+        return Ok(if expander.is_nil() { None } else { Some(expander) });
+    }
+
+    let def = autoload_do_load(def, sym, LispObject::from(Qmacro))?;
+    match def.as_cons() {
+        Some(cons) if cons.car().eq(LispObject::from(Qmacro)) => Ok(Some(cons.cdr())),
+        _ => Ok(None),
+    }
+}
+
+/// Call EXPANDER on the arguments of FORM.
+fn apply_expander(expander: LispObject, form: LispObject) -> LispObject {
+    let mut args = [expander.to_raw(), cdr(form).to_raw()];
+    LispObject::from(unsafe { Fapply(args.len() as ptrdiff_t, args.as_mut_ptr()) })
+}
+
+/// Perform (at most) one step of macroexpansion.
+#[lisp_fn(min = "1")]
+pub fn macroexpand_1(form: LispObject, environment: LispObject) -> LispResult<LispObject> {
+    let head = match form.as_cons() {
+        Some(cons) => cons.car(),
+        None => return Ok(form),
+    };
+
+    let env_expander = assq(head, environment);
+    if env_expander.is_not_nil() {
+        let expander = cdr(env_expander);
+        return Ok(if expander.is_nil() {
+            form
+        } else {
+            apply_expander(expander, form)
+        });
+    }
+
+    if !is_fbound(head) {
+        return Ok(form);
+    }
+
+    let function = head.as_symbol_or_error().get_function();
+    let def = autoload_do_load(function, head, LispObject::from(Qmacro))?;
+    if def.is_symbol() && is_macro(def) {
+        // Follow aliases, but only for macros, otherwise we may end up
+        // skipping an important compiler macro.
+        return Ok(LispObject::cons(def, cdr(form)));
+    }
+    match def.as_cons() {
+        Some(cons) if cons.car().eq(LispObject::from(Qmacro)) => {
+            Ok(apply_expander(cons.cdr(), form))
+        }
+        _ => Ok(form),
+    }
+}
+
+/// Return result of expanding macros at top level of FORM.
+/// If FORM is not a macro call, it is returned unchanged.
+/// Otherwise, the macro is expanded and the expansion is considered
+/// in place of FORM.  When a non-macro-call results, it is returned.
+///
+/// The second optional arg ENVIRONMENT specifies an environment of macro
+/// definitions to shadow the loaded ones for use in file byte-compilation.
+///
+/// An error is signaled if FORM is expanded more than
+/// `max-macroexpand-depth' times.
+#[lisp_fn(min = "1")]
+pub fn macroexpand(form: LispObject, environment: LispObject) -> LispResult<LispObject> {
+    let limit = depth_limit();
+    let original = form;
+    let mut form = form;
+    let mut steps: EmacsInt = 0;
+
+    // Come back here each time we expand a macro call, in case it
+    // expands into another macro call.
+    while let Some(expander) = macro_expander(form, environment)? {
+        steps += 1;
+        if limit.map_or(false, |limit| steps > limit) {
+            return Err(format_error(
+                "Expansion of `%S' did not finish after %d steps; see `max-macroexpand-depth'",
+                list!(car(original), LispObject::from_fixnum(steps - 1)),
+            ));
+        }
+
+        let newform = apply_expander(expander, form);
+        if newform.eq(form) {
+            break;
+        }
+        form = newform;
+    }
+    Ok(form)
+}
+
+/// Return the value of `macroexpand-all-environment'.  It is read
+/// afresh at each step since expanders such as `cl-macrolet' rebind it.
+fn environment() -> LispObject {
+    LispObject::from(unsafe { globals.f_Vmacroexpand_all_environment })
+}
+
+/// Return (CAR . CDR), using ORIGINAL if it already is that cons.
+/// This keeps as much of the input structure as possible when nothing
+/// was expanded.
+fn cons_shared(car: LispObject, cdr: LispObject, original: LispObject) -> LispObject {
+    match original.as_cons() {
+        Some(cons) if cons.car().eq(car) && cons.cdr().eq(cdr) => original,
+        _ => LispObject::cons(car, cdr),
+    }
+}
+
+/// Return a list of the results of calling F on each element of LIST.
+/// The result shares its longest unchanged tail with LIST, and is
+/// LIST itself if F returns every element unchanged.  A non-nil final
+/// cdr of LIST is kept.
+///
+/// This is iterative so that very long lists don't recurse deeply.  The
+/// new elements are kept in a Lisp list so that the GC can see them.
+fn accumulate<F>(list: LispObject, mut f: F) -> LispResult<LispObject>
+where
+    F: FnMut(LispObject) -> LispResult<LispObject>,
+{
+    let mut shared = list;
+    let mut unshared = LispObject::constant_nil();
+    let mut tail = list;
+
+    while let Some(cons) = tail.as_cons() {
+        let elt = cons.car();
+        let new_elt = f(elt)?;
+        if !new_elt.eq(elt) {
+            while !shared.eq(tail) {
+                unshared = LispObject::cons(car(shared), unshared);
+                shared = cdr(shared);
+            }
+            shared = cdr(shared);
+            unshared = LispObject::cons(new_elt, unshared);
+        }
+        tail = cons.cdr();
+    }
+
+    // `unshared' was built backwards.
+    Ok(unshared
+        .iter_cars()
+        .fold(shared, |acc, elt| LispObject::cons(elt, acc)))
+}
+
+/// Return FORMS with macros expanded, leaving the first SKIP forms
+/// alone.
+fn all_forms(forms: LispObject, skip: usize, depth: EmacsInt) -> LispResult<LispObject> {
+    let mut skip = skip;
+    accumulate(forms, |form| {
+        if skip > 0 {
+            skip -= 1;
+            Ok(form)
+        } else {
+            expand_all(form, depth)
+        }
+    })
+}
+
+/// Return CLAUSES with macros expanded in each clause, leaving the
+/// first SKIP forms of each clause alone.  Clauses that are not lists
+/// are ignored.
+fn all_clauses(clauses: LispObject, skip: usize, depth: EmacsInt) -> LispResult<LispObject> {
+    accumulate(clauses, |clause| {
+        if clause.is_list() {
+            all_forms(clause, skip, depth)
+        } else {
+            Ok(clause)
+        }
+    })
+}
+
+/// Return FORM after warning that it may be compiled with MESSAGE, or
+/// printing MESSAGE if it is not being compiled.
+fn warn_and_return(message: LispObject, form: LispObject) -> LispObject {
+    let warn = intern("macroexp--warn-and-return");
+    if is_fbound(warn) {
+        call!(warn, message, form)
+    } else {
+        form
+    }
+}
+
+/// Like `macroexpand' in `macroexpand-all-environment', but warn when
+/// FORM is a call to an obsolete macro.
+fn macroexpand_checking_obsolescence(form: LispObject) -> LispResult<LispObject> {
+    let new_form = macroexpand(form, environment())?;
+    if new_form.eq(form) {
+        return Ok(new_form);
+    }
+
+    let fun = car_safe(form);
+    let sym = match fun.as_symbol() {
+        Some(sym) if fun.is_not_nil() => sym,
+        _ => return Ok(new_form),
+    };
+    let obsolete = get(fun, LispObject::from(Qbyte_obsolete_info));
+    if obsolete.is_nil() {
+        return Ok(new_form);
+    }
+
+    let enabled_p = intern("byte-compile-warning-enabled-p");
+    if is_fbound(enabled_p) && call!(enabled_p, intern("obsolete")).is_nil() {
+        return Ok(new_form);
+    }
+
+    let obsolete_warning = intern("macroexp--obsolete-warning");
+    if !is_fbound(obsolete_warning) {
+        return Ok(new_form);
+    }
+    let kind = if sym.get_function().is_symbol() {
+        "alias"
+    } else {
+        "macro"
+    };
+    let message = call!(obsolete_warning, fun, obsolete, lisp_string(kind));
+    Ok(warn_and_return(message, new_form))
+}
+
+/// If OBJECT is a lambda expression quoted with `quote', return the
+/// lambda expression.
+fn quoted_lambda(object: LispObject) -> Option<LispObject> {
+    let cons = match object.as_cons() {
+        Some(cons) if cons.car().eq(LispObject::from(Qquote)) => cons,
+        _ => return None,
+    };
+    let quoted = car_safe(cons.cdr());
+    if cdr_safe(cons.cdr()).is_nil() && car_safe(quoted).eq(LispObject::from(Qlambda)) {
+        Some(quoted)
+    } else {
+        None
+    }
+}
+
+/// Expand FUN applied to ARGS, whose lambda expression argument F was
+/// quoted with `quote' rather than `function', and warn about it.
+fn expand_quoted_lambda(
+    f: LispObject,
+    new_form: LispObject,
+    depth: EmacsInt,
+) -> LispResult<LispObject> {
+    let message = call!(
+        intern("format"),
+        lisp_string("%s quoted with ' rather than with #'"),
+        list!(LispObject::from(Qlambda), car(cdr(f)), intern("..."))
+    );
+    let expanded = expand_all(new_form, depth)?;
+    Ok(warn_and_return(message, expanded))
+}
+
+/// Return the compiler macro of FUNCTION, if any.
+fn compiler_macro_handler(function: LispObject) -> LispObject {
+    let function_get = intern("function-get");
+    if is_fbound(function_get) {
+        call!(function_get, function, LispObject::from(Qcompiler_macro))
+    } else {
+        get(function, LispObject::from(Qcompiler_macro))
+    }
+}
+
+/// Call the compiler macro HANDLER on FORM.  If the handler signals an
+/// error, report it and return FORM.
+fn apply_compiler_macro(handler: LispObject, form: LispObject) -> LispObject {
+    let helper = intern("macroexp--compiler-macro");
+    if is_fbound(helper) {
+        call!(helper, handler, form)
+    } else {
+        let mut args = [handler.to_raw(), form.to_raw(), cdr(form).to_raw()];
+        LispObject::from(unsafe { Fapply(args.len() as ptrdiff_t, args.as_mut_ptr()) })
+    }
+}
+
+/// Expand FORM, a call to FUNCTION, which has the compiler macro
+/// HANDLER.
+fn expand_compiler_macro(
+    function: LispObject,
+    handler: LispObject,
+    form: LispObject,
+    depth: EmacsInt,
+) -> LispResult<LispObject> {
+    // If the handler is not loaded yet, try (auto)loading the function
+    // itself, which may in turn load the handler.
+    if !unsafe { FUNCTIONP(handler.to_raw()) } {
+        let load = intern("macroexp--load-compiler-macro");
+        if is_fbound(load) {
+            call!(load, function);
+        }
+    }
+
+    let newform = apply_compiler_macro(handler, form);
+    if !newform.eq(form) {
+        return expand_all(newform, depth);
+    }
+
+    // The compiler macro did not find anything to do.  Maybe after
+    // processing the arguments some new opportunities appear, so try
+    // it again.
+    let newform = all_forms(form, 1, depth)?;
+    if form.equal(newform) {
+        return Ok(form);
+    }
+    let again = apply_compiler_macro(handler, newform);
+    if again.eq(newform) {
+        Ok(newform)
+    } else {
+        expand_all(again, depth)
+    }
+}
+
+/// Expand all macros in FORM, which is nested DEPTH levels deep in the
+/// form passed to `macroexpand-all'.
+fn expand_all(form: LispObject, depth: EmacsInt) -> LispResult<LispObject> {
+    if depth_limit().map_or(false, |limit| depth >= limit) {
+        return Err(format_error(
+            "Forms nested more than %d levels deep; see `max-macroexpand-depth'",
+            list!(LispObject::from_fixnum(depth)),
+        ));
+    }
+    let depth = depth + 1;
+
+    if car_safe(form).eq(LispObject::from(Qbackquote_listX)) {
+        // Special-case `backquote-list*', as it is normally a macro that
+        // generates exceedingly deep expansions from relatively shallow
+        // input forms.  We just process it in reverse: first we expand
+        // all the arguments, then we expand the top-level definition.
+        let args_expanded = all_forms(form, 1, depth)?;
+        return macroexpand(args_expanded, environment());
+    }
+
+    // Normal form; get its expansion, and then expand arguments.
+    let form = macroexpand_checking_obsolescence(form)?;
+    let (head, args) = match form.as_cons() {
+        Some(cons) => (cons.car(), cons.cdr()),
+        None => return Ok(form),
+    };
+    let is = |sym: Lisp_Object| head.eq(LispObject::from(sym));
+
+    if is(Qcond) {
+        return Ok(cons_shared(head, all_clauses(args, 0, depth)?, form));
+    }
+
+    if is(Qcondition_case) {
+        // (condition-case VAR BODYFORM HANDLERS...)
+        let rest = match cdr_safe(args).as_cons() {
+            Some(rest) => rest,
+            None => return Ok(form),
+        };
+        let body = expand_all(rest.car(), depth)?;
+        let handlers = all_clauses(rest.cdr(), 1, depth)?;
+        let rest = cons_shared(body, handlers, rest.as_obj());
+        return Ok(cons_shared(head, cons_shared(car(args), rest, args), form));
+    }
+
+    if is(Qdefvar) || is(Qdefconst) {
+        return all_forms(form, 2, depth);
+    }
+
+    if is(Qfunction) {
+        let f = car_safe(args);
+        if car_safe(f).eq(LispObject::from(Qlambda)) && cdr(args).is_nil() {
+            let lambda = all_forms(f, 2, depth)?;
+            return Ok(cons_shared(
+                head,
+                cons_shared(lambda, LispObject::constant_nil(), args),
+                form,
+            ));
+        }
+        return Ok(form);
+    }
+
+    if is(Qquote) {
+        return Ok(form);
+    }
+
+    if is(Qlet) || is(Qletx) {
+        // (let BINDINGS BODY...)
+        let cons = match args.as_cons() {
+            Some(cons) => cons,
+            None => return Ok(form),
+        };
+        let bindings = all_clauses(cons.car(), 1, depth)?;
+        let body = all_forms(cons.cdr(), 0, depth)?;
+        return Ok(cons_shared(head, cons_shared(bindings, body, args), form));
+    }
+
+    if car_safe(head).eq(LispObject::from(Qlambda)) {
+        // Embedded lambda in function position.
+        let fun = all_forms(head, 2, depth)?;
+        return Ok(cons_shared(fun, all_forms(args, 0, depth)?, form));
+    }
+
+    // The following few cases are for normal function calls that are
+    // known to funcall one of their arguments.  The byte compiler has
+    // traditionally handled these functions specially by treating a
+    // lambda expression quoted by `quote' as if it were quoted by
+    // `function'.  We make the same transformation here, so that any
+    // code that cares about the difference will see the same
+    // transformation.
+    if is(Qfuncall) || is(Qapply) || is(Qmapcar) || is(Qmapatoms) || is(Qmapconcat)
+        || is(Qmapc)
+    {
+        // The first argument is a function.
+        if let Some(f) = quoted_lambda(car_safe(args)) {
+            let new_form = LispObject::cons(head, LispObject::cons(f, cdr(args)));
+            return expand_quoted_lambda(f, new_form, depth);
+        }
+    }
+
+    if is(Qsort) {
+        // The second argument is a function.
+        if let Some(f) = quoted_lambda(car_safe(cdr_safe(args))) {
+            let new_form = LispObject::cons(
+                head,
+                LispObject::cons(car(args), LispObject::cons(f, cdr(cdr(args)))),
+            );
+            return expand_quoted_lambda(f, new_form, depth);
+        }
+    }
+
+    if is(Qfuncall) {
+        // Rewrite (funcall #'foo bar) to (foo bar), in case `foo' has a
+        // compiler macro.
+        let fun = car_safe(args);
+        let f = car_safe(cdr_safe(fun));
+        if car_safe(fun).eq(LispObject::from(Qfunction)) && cdr_safe(cdr_safe(fun)).is_nil()
+            && f.is_symbol()
+        {
+            return expand_all(LispObject::cons(f, cdr(args)), depth);
+        }
+    }
+
+    if !head.is_symbol() {
+        return all_forms(form, 1, depth);
+    }
+
+    // Expand compiler macros.  This cannot be delayed to
+    // `byte-optimize-form' because the output of the compiler macro can
+    // use macros.
+    let handler = compiler_macro_handler(head);
+    if handler.is_nil() {
+        // No compiler macro.  We just expand each argument (for
+        // `setq'/`setq-default' this works alright because the variable
+        // names are symbols).
+        all_forms(form, 1, depth)
+    } else {
+        expand_compiler_macro(head, handler, form, depth)
+    }
+}
+
+/// Return result of expanding macros at all levels in FORM.
+/// If no macros are expanded, FORM is returned unchanged.
+/// The second optional arg ENVIRONMENT specifies an environment of macro
+/// definitions to shadow the loaded ones for use in file byte-compilation.
+///
+/// `macroexpand-all-environment' is bound to ENVIRONMENT during the
+/// expansion.  An error is signaled if forms are nested more than
+/// `max-macroexpand-depth' levels deep.
+#[lisp_fn(min = "1")]
+pub fn macroexpand_all(form: LispObject, environment: LispObject) -> LispResult<LispObject> {
+    with_let_binding(
+        LispObject::from(Qmacroexpand_all_environment),
+        environment,
+        || expand_all(form, 0),
+    )
+}
+
+include!(concat!(env!("OUT_DIR"), "/macroexp_exports.rs"));
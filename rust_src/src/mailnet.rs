@@ -0,0 +1,601 @@
+//! SMTP and NNTP clients.
+//!
+//! As with IMAP, connections are network processes, encrypted by
+//! GnuTLS, and their output is parsed here: multi-line SMTP replies
+//! (RFC 5321) and NNTP responses with their dot-terminated data blocks
+//! (RFC 3977).  Messages and articles are dot-stuffed and sent in
+//! chunks here too, so that a large message never goes through Lisp
+//! string manipulation line by line.  Each command gets its reply in
+//! the order it was sent; the callbacks wait in the process plist, and
+//! what the replies they wait for need in the protocol is kept here.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use base64_crate;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use imap::{process_name, string_bytes, unibyte_string};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// The size of the pieces in which messages are sent.
+const CHUNK_SIZE: usize = 65536;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Protocol {
+    Smtp,
+    Nntp,
+}
+
+/// What a command waits for besides its reply.
+#[derive(Debug)]
+enum Kind {
+    Plain,
+    /// STARTTLS, to the host in the string.
+    StartTls(String),
+    /// AUTH with an initial response: a 334 challenge is an error
+    /// report, answered with an empty line.
+    Auth,
+    /// DATA or POST, which get READY before BODY is sent.
+    Upload { ready: u16, body: Vec<u8> },
+}
+
+#[derive(Debug)]
+struct Pending {
+    kind: Kind,
+    /// Whether a successful NNTP response has a data block.
+    multiline: bool,
+}
+
+#[derive(Default)]
+struct Connection {
+    protocol: Option<Protocol>,
+    /// Output of the server not parsed yet.
+    input: Vec<u8>,
+    queue: VecDeque<Pending>,
+}
+
+/// What to do for a reply, once the lock on the connections is released.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Send(Vec<u8>),
+    Negotiate(String),
+    Complete {
+        code: u16,
+        lines: Vec<String>,
+        data: Option<Vec<u8>>,
+    },
+}
+
+lazy_static! {
+    /// The connections, by process name.
+    static ref CONNECTIONS: Mutex<HashMap<String, Connection>> = Mutex::new(HashMap::new());
+}
+
+/// Return the line at the start of INPUT, without its end, and the
+/// length of the line with its end.
+fn next_line(input: &[u8]) -> Option<(&[u8], usize)> {
+    let newline = input.iter().position(|&b| b == b'\n')?;
+    let line = &input[..newline];
+    let line = if line.ends_with(b"\r") {
+        &line[..line.len() - 1]
+    } else {
+        line
+    };
+    Some((line, newline + 1))
+}
+
+/// Return the status code at the start of LINE, or 0 if there is none.
+fn status_code(line: &[u8]) -> u16 {
+    if line.len() >= 3 && line[..3].iter().all(|&b| b >= b'0' && b <= b'9') {
+        line[..3]
+            .iter()
+            .fold(0, |code, &b| code * 10 + u16::from(b - b'0'))
+    } else {
+        0
+    }
+}
+
+/// Parse the SMTP reply at the start of INPUT.  Return its code, the
+/// text of its lines and its length, or None if it is not complete.
+fn parse_smtp_reply(input: &[u8]) -> Option<(u16, Vec<String>, usize)> {
+    let mut lines = Vec::new();
+    let mut consumed = 0;
+    loop {
+        let (line, length) = next_line(&input[consumed..])?;
+        consumed += length;
+        let text = if line.len() > 4 { &line[4..] } else { &[][..] };
+        lines.push(String::from_utf8_lossy(text).into_owned());
+        if line.get(3) != Some(&b'-') {
+            return Some((status_code(line), lines, consumed));
+        }
+    }
+}
+
+/// Parse the NNTP response at the start of INPUT.  If MULTILINE and the
+/// response is a success, it is followed by a data block, which is
+/// returned without dot-stuffing and with LF line ends.  Return the
+/// code, the text of the status line, the data and the length, or None
+/// if the response is not complete.
+fn parse_nntp_response(
+    input: &[u8],
+    multiline: bool,
+) -> Option<(u16, String, Option<Vec<u8>>, usize)> {
+    let (line, mut consumed) = next_line(input)?;
+    let code = status_code(line);
+    let text = if line.len() > 4 { &line[4..] } else { &[][..] };
+    let text = String::from_utf8_lossy(text).into_owned();
+    if !multiline || code == 0 || code >= 300 {
+        return Some((code, text, None, consumed));
+    }
+    let mut data = Vec::new();
+    loop {
+        let (line, length) = next_line(&input[consumed..])?;
+        consumed += length;
+        if line == b"." {
+            return Some((code, text, Some(data), consumed));
+        }
+        data.extend_from_slice(if line.starts_with(b".") {
+            &line[1..]
+        } else {
+            line
+        });
+        data.push(b'\n');
+    }
+}
+
+/// Return BODY with CRLF line ends, its lines starting with `.' doubled,
+/// and the final line with a single `.'.
+fn dot_stuff(body: &[u8]) -> Vec<u8> {
+    let mut stuffed = Vec::with_capacity(body.len() + body.len() / 32 + 5);
+    let body = if body.ends_with(b"\n") {
+        &body[..body.len() - 1]
+    } else {
+        body
+    };
+    if !body.is_empty() {
+        for line in body.split(|&b| b == b'\n') {
+            let line = if line.ends_with(b"\r") {
+                &line[..line.len() - 1]
+            } else {
+                line
+            };
+            if line.starts_with(b".") {
+                stuffed.push(b'.');
+            }
+            stuffed.extend_from_slice(line);
+            stuffed.extend_from_slice(b"\r\n");
+        }
+    }
+    stuffed.extend_from_slice(b".\r\n");
+    stuffed
+}
+
+/// Parse the replies at the start of the input of CONNECTION and
+/// return what to do for them.
+fn process_input(connection: &mut Connection) -> Vec<Action> {
+    let mut actions = Vec::new();
+    let protocol = connection.protocol.unwrap_or(Protocol::Smtp);
+    loop {
+        let multiline = connection
+            .queue
+            .front()
+            .map_or(false, |pending| pending.multiline);
+        let parsed = match protocol {
+            Protocol::Smtp => parse_smtp_reply(&connection.input)
+                .map(|(code, lines, consumed)| (code, lines, None, consumed)),
+            Protocol::Nntp => parse_nntp_response(&connection.input, multiline)
+                .map(|(code, text, data, consumed)| (code, vec![text], data, consumed)),
+        };
+        let (code, lines, data, consumed) = match parsed {
+            Some(parsed) => parsed,
+            None => return actions,
+        };
+        connection.input.drain(..consumed);
+        let pending = match connection.queue.pop_front() {
+            Some(pending) => pending,
+            // An unsolicited reply, such as a 421 before closing.
+            None => continue,
+        };
+        let multiline = pending.multiline;
+        let next = match pending.kind {
+            Kind::Upload { ready, body } => if ready == code {
+                actions.push(Action::Send(body));
+                Some(Kind::Plain)
+            } else {
+                None
+            },
+            Kind::Auth => if code == 334 {
+                actions.push(Action::Send(b"\r\n".to_vec()));
+                Some(Kind::Auth)
+            } else {
+                None
+            },
+            Kind::StartTls(host) => {
+                if code == 220 {
+                    actions.push(Action::Negotiate(host));
+                }
+                None
+            }
+            Kind::Plain => None,
+        };
+        match next {
+            Some(kind) => connection.queue.push_front(Pending { kind, multiline }),
+            None => actions.push(Action::Complete { code, lines, data }),
+        }
+    }
+}
+
+/// Send BYTES to PROCESS in chunks.
+fn send_bytes(process: LispObject, bytes: &[u8]) {
+    for chunk in bytes.chunks(CHUNK_SIZE) {
+        call!(intern("process-send-string"), process, unibyte_string(chunk));
+    }
+}
+
+/// Queue PENDING on PROCESS with CALLBACK and send COMMAND, if any.
+fn send_command(process: LispObject, command: Option<&[u8]>, pending: Pending, callback: LispObject) {
+    let name = process_name(process);
+    let known = match CONNECTIONS.lock().unwrap().get_mut(&name) {
+        Some(connection) => {
+            connection.queue.push_back(pending);
+            true
+        }
+        None => false,
+    };
+    if !known {
+        error!("Not a mail connection");
+    }
+    let callbacks = call!(intern("process-get"), process, intern("mailnet--callbacks"));
+    call!(
+        intern("process-put"),
+        process,
+        intern("mailnet--callbacks"),
+        call!(intern("append"), callbacks, list!(callback))
+    );
+    if let Some(command) = command {
+        let mut line = command.to_vec();
+        line.extend_from_slice(b"\r\n");
+        send_bytes(process, &line);
+    }
+}
+
+fn plain(multiline: bool) -> Pending {
+    Pending {
+        kind: Kind::Plain,
+        multiline,
+    }
+}
+
+/// Open a connection to HOST at SERVICE for PROTOCOL, with the greeting
+/// going to CALLBACK.
+fn open(
+    protocol: Protocol,
+    host: LispObject,
+    service: LispObject,
+    tls: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    let name = match protocol {
+        Protocol::Smtp => "smtp",
+        Protocol::Nntp => "nntp",
+    };
+    let process = call!(
+        intern("open-network-stream"),
+        lisp_string(name),
+        LispObject::constant_nil(),
+        host,
+        service,
+        intern(":type"),
+        if tls.is_nil() {
+            intern("plain")
+        } else {
+            intern("tls")
+        },
+        intern(":coding"),
+        intern("binary")
+    );
+    let connection = Connection {
+        protocol: Some(protocol),
+        ..Connection::default()
+    };
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .insert(process_name(process), connection);
+    call!(intern("set-process-query-on-exit-flag"), process, LispObject::constant_nil());
+    call!(intern("set-process-filter"), process, intern("mailnet-process-filter"));
+    send_command(process, None, plain(false), callback);
+    process
+}
+
+/// Handle STRING, output of PROCESS, an SMTP or NNTP connection.
+/// This is the process filter of the connections of `smtp-native-open'
+/// and `nntp-native-open'.
+#[lisp_fn]
+pub fn mailnet_process_filter(process: LispObject, string: LispObject) -> LispObject {
+    let name = process_name(process);
+    let bytes = string_bytes(string);
+    let (protocol, actions) = match CONNECTIONS.lock().unwrap().get_mut(&name) {
+        Some(connection) => {
+            connection.input.extend_from_slice(&bytes);
+            (connection.protocol, process_input(connection))
+        }
+        None => (None, Vec::new()),
+    };
+    for action in actions {
+        match action {
+            Action::Send(bytes) => send_bytes(process, &bytes),
+            Action::Negotiate(host) => {
+                call!(
+                    intern("gnutls-negotiate"),
+                    intern(":process"),
+                    process,
+                    intern(":hostname"),
+                    lisp_string(&host)
+                );
+            }
+            Action::Complete { code, lines, data } => {
+                let callbacks =
+                    call!(intern("process-get"), process, intern("mailnet--callbacks"));
+                call!(
+                    intern("process-put"),
+                    process,
+                    intern("mailnet--callbacks"),
+                    call!(intern("cdr"), callbacks)
+                );
+                let callback = call!(intern("car"), callbacks);
+                if callback.is_nil() {
+                    continue;
+                }
+                let code = LispObject::from_natnum(EmacsInt::from(code));
+                if protocol == Some(Protocol::Nntp) {
+                    let data = data.map_or(LispObject::constant_nil(), |data| {
+                        unibyte_string(&data)
+                    });
+                    call!(callback, code, lisp_string(&lines[0]), data);
+                } else {
+                    let lines = lines
+                        .iter()
+                        .rev()
+                        .fold(LispObject::constant_nil(), |list, line| {
+                            LispObject::cons(lisp_string(line), list)
+                        });
+                    call!(callback, code, lines);
+                }
+            }
+        }
+    }
+    LispObject::constant_nil()
+}
+
+/// Open an SMTP connection to HOST at SERVICE and return its process.
+/// SERVICE is a port number or a service name such as "submission".  If
+/// TLS is non-nil, the connection is encrypted from the start, as on
+/// port 465; otherwise use `smtp-native-starttls'.  CALLBACK, if
+/// non-nil, is called with the greeting of the server as for
+/// `smtp-native-command'.
+#[lisp_fn(min = "2")]
+pub fn smtp_native_open(
+    host: LispObject,
+    service: LispObject,
+    tls: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    open(Protocol::Smtp, host, service, tls, callback)
+}
+
+/// Send COMMAND, a string such as "EHLO example.org", to PROCESS.
+/// CALLBACK, if non-nil, is called with the code of the reply and the
+/// list of the texts of its lines.  Commands may be sent before the
+/// replies to earlier ones come; each callback gets the reply to its
+/// command.
+#[lisp_fn(min = "2")]
+pub fn smtp_native_command(process: LispObject, command: LispObject, callback: LispObject) -> LispObject {
+    send_command(process, Some(&string_bytes(command)), plain(false), callback);
+    LispObject::constant_nil()
+}
+
+/// Switch PROCESS, an SMTP connection to HOST, to TLS with STARTTLS.
+/// Once the server agrees, the TLS handshake is done with
+/// `gnutls-negotiate', and then CALLBACK is called as for
+/// `smtp-native-command'.  The client must send EHLO again afterwards.
+#[lisp_fn(min = "2")]
+pub fn smtp_native_starttls(process: LispObject, host: LispObject, callback: LispObject) -> LispObject {
+    let host = String::from_utf8_lossy(&string_bytes(host)).into_owned();
+    let pending = Pending {
+        kind: Kind::StartTls(host),
+        multiline: false,
+    };
+    send_command(process, Some(b"STARTTLS"), pending, callback);
+    LispObject::constant_nil()
+}
+
+/// Authenticate to PROCESS, an SMTP connection, as USER.
+/// MECHANISM is `plain', in which case SECRET is the password, or
+/// `xoauth2', in which case it is an OAuth 2.0 access token, as from
+/// `oauth2-native-access-token'.  CALLBACK is as for
+/// `smtp-native-command'.
+#[lisp_fn(min = "4")]
+pub fn smtp_native_auth(
+    process: LispObject,
+    user: LispObject,
+    secret: LispObject,
+    mechanism: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    let user = string_bytes(user);
+    let secret = string_bytes(secret);
+    let (name, mut response) = if mechanism.eq(intern("plain")) {
+        ("PLAIN", vec![0])
+    } else if mechanism.eq(intern("xoauth2")) {
+        ("XOAUTH2", b"user=".to_vec())
+    } else {
+        error!("Unsupported authentication mechanism");
+    };
+    response.extend_from_slice(&user);
+    if name == "PLAIN" {
+        response.push(0);
+        response.extend_from_slice(&secret);
+    } else {
+        response.extend_from_slice(b"\x01auth=Bearer ");
+        response.extend_from_slice(&secret);
+        response.extend_from_slice(b"\x01\x01");
+    }
+    let command = format!("AUTH {} {}", name, base64_crate::encode(&response));
+    let pending = Pending {
+        kind: Kind::Auth,
+        multiline: false,
+    };
+    send_command(process, Some(command.as_bytes()), pending, callback);
+    LispObject::constant_nil()
+}
+
+/// Send MESSAGE, a string, to PROCESS with the SMTP DATA command.
+/// The envelope must have been given with MAIL FROM and RCPT TO.  The
+/// message is sent once the server is ready for it, dot-stuffed, with
+/// CRLF line ends and in chunks, so that it may be of any size.
+/// CALLBACK is called with the final reply as for `smtp-native-command'.
+#[lisp_fn(min = "2")]
+pub fn smtp_native_send_data(process: LispObject, message: LispObject, callback: LispObject) -> LispObject {
+    let pending = Pending {
+        kind: Kind::Upload {
+            ready: 354,
+            body: dot_stuff(&string_bytes(message)),
+        },
+        multiline: false,
+    };
+    send_command(process, Some(b"DATA"), pending, callback);
+    LispObject::constant_nil()
+}
+
+/// Open an NNTP connection to HOST at SERVICE and return its process.
+/// SERVICE is a port number or a service name such as "nntps".  If TLS
+/// is non-nil, the connection is encrypted from the start.  CALLBACK,
+/// if non-nil, is called with the greeting of the server as for
+/// `nntp-native-command'.
+#[lisp_fn(min = "2")]
+pub fn nntp_native_open(
+    host: LispObject,
+    service: LispObject,
+    tls: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    open(Protocol::Nntp, host, service, tls, callback)
+}
+
+/// Send COMMAND, a string such as "GROUP gnu.emacs.help", to PROCESS.
+/// MULTILINE non-nil means that a successful response to the command,
+/// as to ARTICLE, LIST or OVER, is followed by a data block.  CALLBACK,
+/// if non-nil, is called with the code of the response, its text and
+/// the data block as a unibyte string with LF line ends and without
+/// dot-stuffing, or nil if there is none.
+#[lisp_fn(min = "3")]
+pub fn nntp_native_command(
+    process: LispObject,
+    command: LispObject,
+    multiline: LispObject,
+    callback: LispObject,
+) -> LispObject {
+    send_command(
+        process,
+        Some(&string_bytes(command)),
+        plain(multiline.is_not_nil()),
+        callback,
+    );
+    LispObject::constant_nil()
+}
+
+/// Post ARTICLE, a string with headers and body, to PROCESS with POST.
+/// The article is sent once the server is ready for it, dot-stuffed
+/// and in chunks.  CALLBACK is called with the final response as for
+/// `nntp-native-command'.
+#[lisp_fn(min = "2")]
+pub fn nntp_native_post(process: LispObject, article: LispObject, callback: LispObject) -> LispObject {
+    let pending = Pending {
+        kind: Kind::Upload {
+            ready: 340,
+            body: dot_stuff(&string_bytes(article)),
+        },
+        multiline: false,
+    };
+    send_command(process, Some(b"POST"), pending, callback);
+    LispObject::constant_nil()
+}
+
+/// Close PROCESS, an SMTP or NNTP connection, without saying QUIT.
+#[lisp_fn]
+pub fn mailnet_close(process: LispObject) -> LispObject {
+    let name = process_name(process);
+    CONNECTIONS.lock().unwrap().remove(&name);
+    call!(intern("delete-process"), process);
+    LispObject::constant_nil()
+}
+
+#[test]
+fn test_parse_smtp_reply() {
+    let input = b"250-mail.example.org\r\n250-PIPELINING\r\n250 SIZE 1000\r\n220";
+    let (code, lines, consumed) = parse_smtp_reply(input).unwrap();
+    assert_eq!(code, 250);
+    assert_eq!(lines, vec!["mail.example.org", "PIPELINING", "SIZE 1000"]);
+    assert_eq!(&input[consumed..], b"220");
+    assert_eq!(parse_smtp_reply(b"250-a\r\n250 b"), None);
+    assert_eq!(parse_smtp_reply(b"354\r\n"), Some((354, vec![String::new()], 5)));
+}
+
+#[test]
+fn test_parse_nntp_response() {
+    let input = b"220 3 <a@b> article\r\nSubject: x\r\n\r\n..dot\r\n.\r\n";
+    let (code, text, data, consumed) = parse_nntp_response(input, true).unwrap();
+    assert_eq!((code, text.as_str()), (220, "3 <a@b> article"));
+    assert_eq!(data.unwrap(), b"Subject: x\n\n.dot\n".to_vec());
+    assert_eq!(consumed, input.len());
+    assert_eq!(parse_nntp_response(b"220 ok\r\nSubject: x\r\n", true), None);
+    assert_eq!(
+        parse_nntp_response(b"430 no such article\r\n", true),
+        Some((430, "no such article".to_string(), None, 21))
+    );
+}
+
+#[test]
+fn test_dot_stuff() {
+    assert_eq!(dot_stuff(b"a\n.b\r\n..c"), b"a\r\n..b\r\n...c\r\n.\r\n".to_vec());
+    assert_eq!(dot_stuff(b"a\n"), b"a\r\n.\r\n".to_vec());
+    assert_eq!(dot_stuff(b""), b".\r\n".to_vec());
+}
+
+#[test]
+fn test_process_input() {
+    let mut connection = Connection {
+        protocol: Some(Protocol::Smtp),
+        ..Connection::default()
+    };
+    connection.queue.push_back(Pending {
+        kind: Kind::Upload {
+            ready: 354,
+            body: b"x\r\n.\r\n".to_vec(),
+        },
+        multiline: false,
+    });
+    connection.input.extend_from_slice(b"354 go ahead\r\n");
+    assert_eq!(
+        process_input(&mut connection),
+        vec![Action::Send(b"x\r\n.\r\n".to_vec())]
+    );
+    connection.input.extend_from_slice(b"250 queued\r\n421 bye\r\n");
+    assert_eq!(
+        process_input(&mut connection),
+        vec![Action::Complete {
+            code: 250,
+            lines: vec!["queued".to_string()],
+            data: None,
+        }]
+    );
+    assert!(connection.queue.is_empty() && connection.input.is_empty());
+}
+
+include!(concat!(env!("OUT_DIR"), "/mailnet_exports.rs"));
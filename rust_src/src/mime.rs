@@ -0,0 +1,1400 @@
+//! MIME encodings of mail messages.
+//!
+//! Mail readers decode every quoted-printable body part and every
+//! encoded header they show, which is slow in Lisp for large messages.
+//! The functions here work on the bytes of the text, since both
+//! encodings only ever involve ASCII characters.
+//!
+//! `mime-parse-buffer' splits a whole message into its parts in one
+//! pass, which is what mail readers and multipart HTTP responses need
+//! before anything can be shown, and `mail-parse-addresses' parses
+//! address lists by the grammar of RFC 5322 rather than by regexps.
+
+use libc::{c_char, ptrdiff_t};
+
+use base64_crate;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_specified_string, record_unwind_current_buffer, record_unwind_protect,
+                 save_excursion_restore, save_excursion_save, unbind_to, EmacsInt, Qnil};
+
+use buffers::set_buffer;
+use eval_call::specpdl_index;
+use hashtable::puthash;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'...b'9' => Some(b - b'0'),
+        b'A'...b'F' => Some(b - b'A' + 10),
+        b'a'...b'f' => Some(b - b'a' + 10),
+        _ => None,
+    }
+}
+
+/// The byte encoded by the two hex digits at the start of TEXT, if any.
+fn hex_byte(text: &[u8]) -> Option<u8> {
+    if text.len() < 2 {
+        return None;
+    }
+    match (hex_value(text[0]), hex_value(text[1])) {
+        (Some(high), Some(low)) => Some(high << 4 | low),
+        _ => None,
+    }
+}
+
+/// Append the decoded byte B to OUT.  In multibyte text, bytes above
+/// 127 are raw 8-bit characters.
+fn push_byte(out: &mut Vec<u8>, b: u8, multibyte: bool) {
+    if b < 0x80 || !multibyte {
+        out.push(b);
+    } else {
+        out.push(0xC0 | ((b >> 6) & 1));
+        out.push(0x80 | (b & 0x3F));
+    }
+}
+
+/// Decode the quoted-printable TEXT.  Return the decoded text and
+/// whether TEXT had `=' signs that start neither an encoded byte nor a
+/// soft line break; those are left alone.
+fn qp_decode(text: &[u8], multibyte: bool) -> (Vec<u8>, bool) {
+    let mut out = Vec::with_capacity(text.len());
+    let mut malformed = false;
+    let mut i = 0;
+    while i < text.len() {
+        let b = text[i];
+        if b != b'=' {
+            out.push(b);
+            i += 1;
+            continue;
+        }
+        // A soft line break, which may have trailing whitespace.
+        let blanks = text[i + 1..]
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        let rest = &text[i + 1 + blanks..];
+        if rest.starts_with(b"\n") {
+            i += 2 + blanks;
+        } else if rest.starts_with(b"\r\n") {
+            i += 3 + blanks;
+        } else if let Some(byte) = hex_byte(&text[i + 1..]) {
+            push_byte(&mut out, byte, multibyte);
+            i += 3;
+        } else {
+            malformed = true;
+            out.push(b);
+            i += 1;
+        }
+    }
+    (out, malformed)
+}
+
+/// Decode the text of a Q-encoded word, where `_' stands for a space.
+fn q_decode(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            b'_' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b'=' if hex_byte(&text[i + 1..]).is_some() => {
+                out.push(hex_byte(&text[i + 1..]).unwrap());
+                i += 3;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Decode the text of a B-encoded word, whose padding may be wrong.
+fn b_decode(text: &[u8]) -> Option<Vec<u8>> {
+    let mut text: Vec<u8> = text.iter().cloned().filter(|&b| b != b'=').collect();
+    match text.len() % 4 {
+        1 => return None,
+        2 => text.extend_from_slice(b"=="),
+        3 => text.push(b'='),
+        _ => (),
+    }
+    base64_crate::decode(&text).ok()
+}
+
+/// An encoded word, `=?CHARSET?ENCODING?TEXT?=', found at START..END.
+/// A language suffix of CHARSET, as in `us-ascii*en', is dropped.
+#[derive(Debug, PartialEq)]
+struct EncodedWord<'a> {
+    charset: &'a [u8],
+    encoding: u8,
+    text: &'a [u8],
+    start: usize,
+    end: usize,
+}
+
+impl<'a> EncodedWord<'a> {
+    /// The bytes encoded by the word, or None if they can't be decoded.
+    fn decode(&self) -> Option<Vec<u8>> {
+        match self.encoding {
+            b'B' | b'b' => b_decode(self.text),
+            _ => Some(q_decode(self.text)),
+        }
+    }
+}
+
+fn is_charset_byte(b: u8) -> bool {
+    b > b' ' && b < 0x7F && !b"()<>@,;:\\\"/[]?.=".contains(&b)
+}
+
+/// Parse the encoded word at START in TEXT, which starts with `=?'.
+/// Q-encoded text may contain `?' signs that are not followed by `=',
+/// as some mailers write them.
+fn parse_encoded_word(text: &[u8], start: usize) -> Option<EncodedWord> {
+    let mut i = start + 2;
+    let charset_start = i;
+    while i < text.len() && is_charset_byte(text[i]) && text[i] != b'*' {
+        i += 1;
+    }
+    let charset = &text[charset_start..i];
+    if charset.is_empty() {
+        return None;
+    }
+    if text.get(i) == Some(&b'*') {
+        while i < text.len() && text[i] != b'?' {
+            i += 1;
+        }
+    }
+    if text.len() < i + 3 || text[i] != b'?' || text[i + 2] != b'?' {
+        return None;
+    }
+    let encoding = text[i + 1];
+    let text_start = i + 3;
+    let valid: fn(u8) -> bool = match encoding {
+        b'B' | b'b' => |b| match b {
+            b'0'...b'9' | b'A'...b'Z' | b'a'...b'z' | b'+' | b'/' | b'=' => true,
+            _ => false,
+        },
+        b'Q' | b'q' => |b| b > b' ' && b < 0x7F,
+        _ => return None,
+    };
+    let mut j = text_start;
+    while j + 1 < text.len() && !(text[j] == b'?' && text[j + 1] == b'=') {
+        if !valid(text[j]) {
+            return None;
+        }
+        j += 1;
+    }
+    if j + 1 >= text.len() {
+        return None;
+    }
+    Some(EncodedWord {
+        charset,
+        encoding,
+        text: &text[text_start..j],
+        start,
+        end: j + 2,
+    })
+}
+
+/// Return the encoded words of TEXT, in order.
+fn encoded_words(text: &[u8]) -> Vec<EncodedWord> {
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i + 1 < text.len() {
+        if text[i] == b'=' && text[i + 1] == b'?' {
+            if let Some(word) = parse_encoded_word(text, i) {
+                i = word.end;
+                words.push(word);
+                continue;
+            }
+        }
+        i += 1;
+    }
+    words
+}
+
+fn is_blank(text: &[u8]) -> bool {
+    text.iter()
+        .all(|&b| b == b' ' || b == b'\t' || b == b'\r' || b == b'\n')
+}
+
+/// Replace each run of newlines in TEXT by a space.
+fn unfold(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut newline = false;
+    for &b in text {
+        if b == b'\r' || b == b'\n' {
+            if !newline {
+                out.push(b' ');
+            }
+            newline = true;
+        } else {
+            out.push(b);
+            newline = false;
+        }
+    }
+    out
+}
+
+fn make_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    LispObject::from(unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            -1,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    })
+}
+
+/// The coding system for the MIME charset CHARSET, or nil.
+fn charset_coding_system(charset: &[u8]) -> LispObject {
+    let name = String::from_utf8_lossy(charset).to_lowercase();
+    let converter = intern("rfc2047-charset-to-coding-system");
+    if call!(intern("fboundp"), converter).is_not_nil() {
+        return call!(converter, lisp_string(&name), LispObject::constant_t());
+    }
+    let coding = intern(&name);
+    if call!(intern("coding-system-p"), coding).is_not_nil() {
+        coding
+    } else {
+        LispObject::constant_nil()
+    }
+}
+
+/// Decode the bytes of the words in PENDING, if any, and add the result
+/// to PIECES.
+fn flush_words(pending: &mut Option<(LispObject, Vec<u8>)>, pieces: &mut Vec<LispObject>) {
+    if let Some((coding, decoded)) = pending.take() {
+        let decoded = make_string(&unfold(&decoded), false);
+        pieces.push(call!(intern("decode-coding-string"), decoded, coding));
+    }
+}
+
+/// Decode quoted-printable in the region between START and END, per RFC 2045.
+/// A `=' followed by two hexadecimal digits, in either case, stands for
+/// that byte, and a `=' at the end of a line joins the line to the next
+/// one.  Other `=' signs are left alone, with a message.
+///
+/// If CODING-SYSTEM is a coding system, the text is encoded with it
+/// before the quoted-printable is decoded, and the result is decoded
+/// with it.  Otherwise decoded bytes above 127 are raw bytes in a
+/// multibyte buffer.
+///
+/// This is the primitive behind `quoted-printable-decode-region'.
+#[lisp_fn(min = "2")]
+pub fn quoted_printable_decode_region_native(
+    start: LispObject,
+    end: LispObject,
+    coding_system: LispObject,
+) -> LispObject {
+    let coding_system = if coding_system.is_not_nil()
+        && call!(intern("coding-system-p"), coding_system).is_not_nil()
+    {
+        coding_system
+    } else {
+        LispObject::constant_nil()
+    };
+    let count = specpdl_index();
+    unsafe { record_unwind_protect(save_excursion_restore, save_excursion_save()) };
+
+    let start = start.as_fixnum_coerce_marker_or_error();
+    let end = end.as_fixnum_coerce_marker_or_error();
+    let (start, end) = (start.min(end), start.max(end));
+    let start = LispObject::from_fixnum(start);
+    let end = call!(
+        intern("copy-marker"),
+        LispObject::from_fixnum(end),
+        LispObject::constant_t()
+    );
+    if coding_system.is_not_nil() {
+        call!(intern("encode-coding-region"), start, end, coding_system);
+    }
+    let text = call!(intern("buffer-substring-no-properties"), start, end);
+    let text = text.as_string_or_error();
+    let multibyte = text.is_multibyte();
+    let (decoded, malformed) = qp_decode(text.as_slice(), multibyte);
+    if decoded.as_slice() != text.as_slice() {
+        call!(intern("goto-char"), start);
+        call!(intern("delete-region"), start, end);
+        call!(intern("insert"), make_string(&decoded, multibyte));
+    }
+    if malformed {
+        call!(intern("message"), lisp_string("Malformed quoted-printable text"));
+    }
+    if coding_system.is_not_nil() {
+        call!(intern("decode-coding-region"), start, end, coding_system);
+    }
+    call!(intern("set-marker"), end, LispObject::constant_nil());
+    unsafe { unbind_to(count, Qnil) };
+    LispObject::constant_nil()
+}
+
+/// Decode the MIME encoded words in STRING and return the result, per RFC 2047.
+/// Whitespace between adjacent encoded words is dropped, and the text
+/// of successive words in the same charset is decoded together, so
+/// that a character may be split between words.  Newlines in the
+/// decoded text become spaces.  Words whose charset is unknown or whose
+/// text can't be decoded are left alone.  Charsets are mapped to coding
+/// systems by `rfc2047-charset-to-coding-system' when it is defined.
+///
+/// The result is always multibyte.  This is the primitive behind
+/// `rfc2047-decode-string'.
+#[lisp_fn]
+pub fn rfc2047_decode_string_native(string: LispObject) -> LispObject {
+    string.as_string_or_error();
+    let string = call!(intern("string-to-multibyte"), string);
+    let text = string.as_string_or_error();
+    let bytes = text.as_slice();
+
+    let mut pieces = Vec::new();
+    // The coding system and the bytes of the last words decoded, not
+    // yet converted to characters.
+    let mut pending: Option<(LispObject, Vec<u8>)> = None;
+    let mut pos = 0;
+    let mut last_decoded = false;
+    for word in encoded_words(bytes) {
+        let gap = &bytes[pos..word.start];
+        let adjacent = last_decoded && is_blank(gap);
+        let coding = charset_coding_system(word.charset);
+        let decoded = if coding.is_nil() { None } else { word.decode() };
+        match decoded {
+            Some(decoded) => {
+                let same_coding = match pending {
+                    Some((ref pending_coding, _)) => pending_coding.eq(coding),
+                    None => false,
+                };
+                if adjacent && same_coding {
+                    if let Some((_, ref mut text)) = pending {
+                        text.extend_from_slice(&decoded);
+                    }
+                } else {
+                    flush_words(&mut pending, &mut pieces);
+                    if !adjacent {
+                        pieces.push(make_string(gap, true));
+                    }
+                    pending = Some((coding, decoded));
+                }
+                last_decoded = true;
+            }
+            None => {
+                flush_words(&mut pending, &mut pieces);
+                pieces.push(make_string(&bytes[pos..word.end], true));
+                last_decoded = false;
+            }
+        }
+        pos = word.end;
+    }
+    flush_words(&mut pending, &mut pieces);
+    pieces.push(make_string(&bytes[pos..], true));
+
+    let pieces = pieces
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, &piece| {
+            LispObject::cons(piece, list)
+        });
+    call!(intern("apply"), intern("concat"), pieces)
+}
+
+/// Multipart bodies nested deeper than this are not split into parts.
+const MAX_DEPTH: usize = 50;
+
+/// A part of a message, with byte offsets into the message text.
+#[derive(Debug)]
+struct Part {
+    /// Where the headers start.
+    start: usize,
+    /// Where the body starts, after the empty line ending the headers.
+    body: usize,
+    /// Where the body ends.
+    end: usize,
+    /// The header fields in order, with lowercase names and unfolded
+    /// values.
+    headers: Vec<(String, Vec<u8>)>,
+    /// The lowercase media type, as in `text/plain'.
+    media_type: String,
+    /// The parameters of the Content-Type field, with lowercase names.
+    parameters: Vec<(String, Vec<u8>)>,
+    /// The parts of a multipart body, or the message in a
+    /// message/rfc822 body.
+    parts: Vec<Part>,
+}
+
+impl Part {
+    fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|&&(ref n, _)| n == name)
+            .map(|&(_, ref value)| value.as_slice())
+    }
+
+    fn parameter(&self, name: &str) -> Option<&[u8]> {
+        self.parameters
+            .iter()
+            .find(|&&(ref n, _)| n == name)
+            .map(|&(_, ref value)| value.as_slice())
+    }
+}
+
+/// The end of the line starting at POS in TEXT, not counting a CR
+/// before the newline, and where the next line starts.
+fn line_at(text: &[u8], pos: usize, end: usize) -> (usize, usize) {
+    match text[pos..end].iter().position(|&b| b == b'\n') {
+        Some(n) => {
+            let next = pos + n + 1;
+            if n > 0 && text[pos + n - 1] == b'\r' {
+                (pos + n - 1, next)
+            } else {
+                (pos + n, next)
+            }
+        }
+        None => (end, end),
+    }
+}
+
+fn is_field_name(name: &[u8]) -> bool {
+    !name.is_empty() && name.iter().all(|&b| b > b' ' && b < 0x7F)
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let is_space = |b: &u8| *b == b' ' || *b == b'\t' || *b == b'\r' || *b == b'\n';
+    let start = bytes.iter().position(|b| !is_space(b)).unwrap_or(bytes.len());
+    let end = bytes.iter().rposition(|b| !is_space(b)).map_or(start, |n| n + 1);
+    &bytes[start..end]
+}
+
+/// Parse the header fields from START to END in TEXT.  Return them and
+/// where the body starts.  Text that does not start with a header field
+/// has no headers, and a line that is not a field ends the headers
+/// early, as if it was preceded by an empty line.
+fn parse_headers(text: &[u8], start: usize, end: usize) -> (Vec<(String, Vec<u8>)>, usize) {
+    let mut headers: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut pos = start;
+    while pos < end {
+        let (line_end, next) = line_at(text, pos, end);
+        let line = &text[pos..line_end];
+        if line.is_empty() {
+            return (headers, next);
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            if let Some(&mut (_, ref mut value)) = headers.last_mut() {
+                value.push(b' ');
+                value.extend_from_slice(trim(line));
+                pos = next;
+                continue;
+            }
+        }
+        match line.iter().position(|&b| b == b':') {
+            Some(colon) if is_field_name(&line[..colon]) => {
+                let name = String::from_utf8_lossy(&line[..colon]).to_lowercase();
+                headers.push((name, trim(&line[colon + 1..]).to_vec()));
+            }
+            _ => return (headers, pos),
+        }
+        pos = next;
+    }
+    (headers, end)
+}
+
+/// Split the value of a Content-Type field into the lowercase media type
+/// and the parameters.  Return None if there is no valid media type.
+fn parse_content_type(value: &[u8]) -> Option<(String, Vec<(String, Vec<u8>)>)> {
+    let mut fields = Vec::new();
+    let mut field = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for &b in value {
+        if escaped {
+            field.push(b);
+            escaped = false;
+        } else if quoted && b == b'\\' {
+            escaped = true;
+        } else if b == b'"' {
+            quoted = !quoted;
+        } else if b == b';' && !quoted {
+            fields.push(field);
+            field = Vec::new();
+        } else {
+            field.push(b);
+        }
+    }
+    fields.push(field);
+
+    let media_type = String::from_utf8_lossy(trim(&fields[0])).to_lowercase();
+    let valid = {
+        let mut halves = media_type.splitn(2, '/');
+        match (halves.next(), halves.next()) {
+            (Some(kind), Some(subtype)) => !kind.is_empty() && !subtype.is_empty(),
+            _ => false,
+        }
+    };
+    if !valid {
+        return None;
+    }
+    let parameters = fields[1..]
+        .iter()
+        .filter_map(|field| {
+            field.iter().position(|&b| b == b'=').map(|equals| {
+                let name = String::from_utf8_lossy(trim(&field[..equals])).to_lowercase();
+                (name, trim(&field[equals + 1..]).to_vec())
+            })
+        })
+        .collect();
+    Some((media_type, parameters))
+}
+
+/// Return the bounds of the parts of the multipart body from START to
+/// END in TEXT, whose parts are delimited by BOUNDARY.  A missing close
+/// delimiter ends the last part at END.  Return None if there is no
+/// delimiter at all.
+fn split_multipart(
+    text: &[u8],
+    start: usize,
+    end: usize,
+    boundary: &[u8],
+) -> Option<Vec<(usize, usize)>> {
+    let mut parts = Vec::new();
+    let mut part_start = None;
+    let mut pos = start;
+    while pos < end {
+        let (line_end, next) = line_at(text, pos, end);
+        let line = &text[pos..line_end];
+        if line.starts_with(b"--") && line[2..].starts_with(boundary) {
+            let rest = &line[2 + boundary.len()..];
+            let closing = rest.starts_with(b"--");
+            if closing || trim(rest).is_empty() {
+                if let Some(part_start) = part_start {
+                    // The line break before a delimiter belongs to it.
+                    let mut part_end = pos;
+                    if part_end > part_start && text[part_end - 1] == b'\n' {
+                        part_end -= 1;
+                        if part_end > part_start && text[part_end - 1] == b'\r' {
+                            part_end -= 1;
+                        }
+                    }
+                    parts.push((part_start, part_end));
+                }
+                if closing {
+                    return Some(parts);
+                }
+                part_start = Some(next);
+            }
+        }
+        pos = next;
+    }
+    match part_start {
+        Some(part_start) => {
+            parts.push((part_start, end));
+            Some(parts)
+        }
+        None => None,
+    }
+}
+
+/// Parse the part from START to END in TEXT.  DEFAULT_TYPE is the media
+/// type if the part has no valid Content-Type field, and DEPTH the
+/// number of enclosing parts.
+fn parse_part(text: &[u8], start: usize, end: usize, default_type: &str, depth: usize) -> Part {
+    let (headers, body) = parse_headers(text, start, end);
+    let mut part = Part {
+        start,
+        body,
+        end,
+        headers,
+        media_type: default_type.to_string(),
+        parameters: Vec::new(),
+        parts: Vec::new(),
+    };
+    let content_type = part.header("content-type").and_then(parse_content_type);
+    if let Some((media_type, parameters)) = content_type {
+        part.media_type = media_type;
+        part.parameters = parameters;
+    }
+    if depth >= MAX_DEPTH {
+        return part;
+    }
+    if part.media_type.starts_with("multipart/") {
+        let default_type = if part.media_type == "multipart/digest" {
+            "message/rfc822"
+        } else {
+            "text/plain"
+        };
+        let bounds = match part.parameter("boundary") {
+            Some(boundary) if !boundary.is_empty() => split_multipart(text, body, end, boundary),
+            _ => None,
+        };
+        if let Some(bounds) = bounds {
+            part.parts = bounds
+                .into_iter()
+                .map(|(start, end)| parse_part(text, start, end, default_type, depth + 1))
+                .collect();
+        }
+    } else if part.media_type == "message/rfc822" {
+        part.parts = vec![parse_part(text, body, end, "text/plain", depth + 1)];
+    }
+    part
+}
+
+/// Converts byte offsets in a text to character positions, for offsets
+/// that mostly increase.
+struct CharPositions<'a> {
+    text: &'a [u8],
+    multibyte: bool,
+    origin: EmacsInt,
+    byte: usize,
+    chars: usize,
+}
+
+impl<'a> CharPositions<'a> {
+    fn position(&mut self, byte: usize) -> EmacsInt {
+        if !self.multibyte {
+            return self.origin + byte as EmacsInt;
+        }
+        if byte < self.byte {
+            self.byte = 0;
+            self.chars = 0;
+        }
+        self.chars += self.text[self.byte..byte]
+            .iter()
+            .filter(|&&b| b & 0xC0 != 0x80)
+            .count();
+        self.byte = byte;
+        self.origin + self.chars as EmacsInt
+    }
+
+    /// A marker at BYTE in the current buffer.
+    fn marker(&mut self, byte: usize) -> LispObject {
+        let position = LispObject::from_fixnum(self.position(byte));
+        call!(intern("copy-marker"), position)
+    }
+}
+
+/// Make the Lisp form of PART, as described for `mime-parse-buffer'.
+fn part_to_lisp(part: &Part, positions: &mut CharPositions, multibyte: bool) -> LispObject {
+    let headers = call!(
+        intern("make-hash-table"),
+        intern(":test"),
+        intern("equal"),
+        intern(":size"),
+        LispObject::from_natnum(part.headers.len().max(1) as EmacsInt)
+    );
+    // Keep the first of repeated fields.
+    for &(ref name, ref value) in part.headers.iter().rev() {
+        puthash(lisp_string(name), make_string(value, multibyte), headers);
+    }
+    let parameters = part.parameters
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, &(ref name, ref value)| {
+            let parameter = LispObject::cons(lisp_string(name), make_string(value, multibyte));
+            LispObject::cons(parameter, list)
+        });
+    let start = positions.marker(part.start);
+    let body = positions.marker(part.body);
+    let end = positions.marker(part.end);
+    let mut parts = LispObject::constant_nil();
+    for child in part.parts.iter().rev() {
+        parts = LispObject::cons(part_to_lisp(child, positions, multibyte), parts);
+    }
+    list!(
+        intern(":type"),
+        lisp_string(&part.media_type),
+        intern(":parameters"),
+        parameters,
+        intern(":headers"),
+        headers,
+        intern(":start"),
+        start,
+        intern(":body"),
+        body,
+        intern(":end"),
+        end,
+        intern(":parts"),
+        parts
+    )
+}
+
+/// Parse the MIME message in the accessible portion of BUFFER.
+/// BUFFER defaults to the current buffer.  The message is a header
+/// followed by an empty line and a body, as in a mail message or an
+/// HTTP response.  Return the part tree of the message, where each part
+/// is a plist with these properties:
+///
+///   :type        The media type, in lowercase, as in "text/plain".
+///   :parameters  An alist of the Content-Type parameters, with
+///                lowercase names.
+///   :headers     A hash table mapping lowercase field names to unfolded
+///                values.  Of repeated fields, only the first is kept.
+///   :start       A marker where the part starts.
+///   :body        A marker where the body of the part starts.
+///   :end         A marker where the part ends.
+///   :parts       The parts of a multipart body, or a list of the
+///                message in a message/rfc822 body.
+///
+/// Parsing is tolerant: text that doesn't start with a header field has
+/// no headers, a multipart body without a close delimiter ends at the
+/// end of its enclosing part, and one without any delimiter has no
+/// parts.  Header values are not decoded.
+#[lisp_fn(min = "0")]
+pub fn mime_parse_buffer(buffer: LispObject) -> LispObject {
+    let count = specpdl_index();
+    unsafe { record_unwind_current_buffer() };
+    if buffer.is_not_nil() {
+        set_buffer(buffer);
+    }
+    let origin = call!(intern("point-min"));
+    let text = call!(
+        intern("buffer-substring-no-properties"),
+        origin,
+        call!(intern("point-max"))
+    );
+    let text = text.as_string_or_error();
+    let multibyte = text.is_multibyte();
+    let bytes = text.as_slice();
+    let part = parse_part(bytes, 0, bytes.len(), "text/plain", 0);
+    let mut positions = CharPositions {
+        text: bytes,
+        multibyte,
+        origin: origin.as_fixnum_or_error(),
+        byte: 0,
+        chars: 0,
+    };
+    let result = part_to_lisp(&part, &mut positions, multibyte);
+    unsafe { unbind_to(count, Qnil) };
+    result
+}
+
+/// A lexical token of an address list, with the bytes it spans.
+#[derive(Debug)]
+enum Token {
+    Atom,
+    /// A quoted string, with its quoting removed.
+    Quoted(Vec<u8>),
+    DomainLiteral,
+    /// A comment, with its parentheses and quoting removed.
+    Comment(Vec<u8>),
+    Special(u8),
+}
+
+#[derive(Debug)]
+struct Lexeme {
+    token: Token,
+    start: usize,
+    end: usize,
+    /// Whether whitespace or a comment comes before the token.
+    space_before: bool,
+}
+
+fn is_atext(b: u8) -> bool {
+    b > b' ' && b != 0x7F && !b"()<>[]:;@,.\"".contains(&b)
+}
+
+/// Read the text of the quoted string or comment at START in TEXT, up
+/// to the unescaped CLOSE byte.  Comments nest.  An unterminated one
+/// runs to the end of TEXT.  Return the text and where it ends.
+fn read_delimited(text: &[u8], start: usize, close: u8) -> (Vec<u8>, usize) {
+    let open = text[start];
+    let mut depth = 0;
+    let mut out = Vec::new();
+    let mut i = start + 1;
+    while i < text.len() {
+        let b = text[i];
+        if b == b'\\' && i + 1 < text.len() {
+            out.push(text[i + 1]);
+            i += 2;
+            continue;
+        }
+        i += 1;
+        if b == close {
+            if depth == 0 {
+                return (out, i);
+            }
+            depth -= 1;
+        } else if b == open && close == b')' {
+            depth += 1;
+        }
+        // Folding whitespace loses its line breaks.
+        if b != b'\r' && b != b'\n' {
+            out.push(b);
+        }
+    }
+    (out, i)
+}
+
+fn tokenize(text: &[u8]) -> Vec<Lexeme> {
+    let mut lexemes = Vec::new();
+    let mut space_before = false;
+    let mut i = 0;
+    while i < text.len() {
+        let start = i;
+        let token = match text[i] {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                space_before = true;
+                i += 1;
+                continue;
+            }
+            b'"' => {
+                let (quoted, end) = read_delimited(text, i, b'"');
+                i = end;
+                Token::Quoted(quoted)
+            }
+            b'(' => {
+                let (comment, end) = read_delimited(text, i, b')');
+                i = end;
+                Token::Comment(comment)
+            }
+            b'[' => {
+                i += text[i..]
+                    .iter()
+                    .position(|&b| b == b']')
+                    .map_or(text.len() - i, |n| n + 1);
+                Token::DomainLiteral
+            }
+            b if is_atext(b) => {
+                while i < text.len() && is_atext(text[i]) {
+                    i += 1;
+                }
+                Token::Atom
+            }
+            b => {
+                i += 1;
+                Token::Special(b)
+            }
+        };
+        let is_comment = match token {
+            Token::Comment(_) => true,
+            _ => false,
+        };
+        lexemes.push(Lexeme {
+            token,
+            start,
+            end: i,
+            space_before,
+        });
+        space_before = is_comment;
+    }
+    lexemes
+}
+
+#[derive(Debug, PartialEq)]
+struct Mailbox {
+    name: Option<Vec<u8>>,
+    local: Vec<u8>,
+    domain: Option<Vec<u8>>,
+    comments: Vec<Vec<u8>>,
+}
+
+impl Mailbox {
+    /// The address in its canonical form, `local@domain', with the local
+    /// part quoted if it isn't a dot-atom.
+    fn address(&self) -> Vec<u8> {
+        let dot_atom = !self.local.is_empty() && !self.local.starts_with(b".")
+            && !self.local.ends_with(b".")
+            && !self.local.windows(2).any(|pair| pair == b"..")
+            && self.local.iter().all(|&b| b == b'.' || is_atext(b));
+        let mut address = Vec::new();
+        if dot_atom || self.local.is_empty() {
+            address.extend_from_slice(&self.local);
+        } else {
+            address.push(b'"');
+            for &b in &self.local {
+                if b == b'"' || b == b'\\' {
+                    address.push(b'\\');
+                }
+                address.push(b);
+            }
+            address.push(b'"');
+        }
+        if let Some(ref domain) = self.domain {
+            address.push(b'@');
+            address.extend_from_slice(domain);
+        }
+        address
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Address {
+    Mailbox(Mailbox),
+    /// A named group of addresses.
+    Group(Vec<u8>, Vec<Address>),
+    /// Text that could not be parsed, as it appears in the list.
+    Invalid(Vec<u8>),
+}
+
+/// A parser for address lists, following the grammar of RFC 5322 with
+/// its obsolete forms.
+struct AddressParser<'a> {
+    text: &'a [u8],
+    lexemes: Vec<Lexeme>,
+    pos: usize,
+    /// The comments passed since the current mailbox started.
+    comments: Vec<Vec<u8>>,
+}
+
+impl<'a> AddressParser<'a> {
+    fn new(text: &'a [u8]) -> Self {
+        AddressParser {
+            text,
+            lexemes: tokenize(text),
+            pos: 0,
+            comments: Vec::new(),
+        }
+    }
+
+    /// Skip comments, and return the index of the next token.
+    fn peek(&mut self) -> Option<usize> {
+        while self.pos < self.lexemes.len() {
+            match self.lexemes[self.pos].token {
+                Token::Comment(ref comment) => self.comments.push(comment.clone()),
+                _ => return Some(self.pos),
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    fn peek_special(&mut self) -> Option<u8> {
+        match self.peek() {
+            Some(i) => match self.lexemes[i].token {
+                Token::Special(b) => Some(b),
+                _ => None,
+            },
+            None => None,
+        }
+    }
+
+    fn expect(&mut self, special: u8) -> Result<(), ()> {
+        if self.peek_special() == Some(special) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    /// Read the words and dots of a phrase or local part, and return the
+    /// indexes of their tokens.
+    fn words(&mut self) -> Vec<usize> {
+        let mut words = Vec::new();
+        while let Some(i) = self.peek() {
+            match self.lexemes[i].token {
+                Token::Atom | Token::Quoted(_) | Token::Special(b'.') => words.push(i),
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        words
+    }
+
+    fn word_text(&self, i: usize) -> &[u8] {
+        let lexeme = &self.lexemes[i];
+        match lexeme.token {
+            Token::Quoted(ref quoted) => quoted,
+            _ => &self.text[lexeme.start..lexeme.end],
+        }
+    }
+
+    /// The display name made of the words at WORDS, with a space
+    /// wherever the words were separated.
+    fn phrase(&self, words: &[usize]) -> Vec<u8> {
+        let mut phrase = Vec::new();
+        for (n, &i) in words.iter().enumerate() {
+            if n > 0 && self.lexemes[i].space_before {
+                phrase.push(b' ');
+            }
+            phrase.extend_from_slice(self.word_text(i));
+        }
+        phrase
+    }
+
+    /// The local part made of the words at WORDS.  Whitespace between
+    /// them is obsolete syntax and is dropped.
+    fn local_part(&self, words: &[usize]) -> Vec<u8> {
+        let mut local = Vec::new();
+        for &i in words {
+            local.extend_from_slice(self.word_text(i));
+        }
+        local
+    }
+
+    fn domain(&mut self) -> Result<Vec<u8>, ()> {
+        let i = self.peek().ok_or(())?;
+        if let Token::DomainLiteral = self.lexemes[i].token {
+            self.pos += 1;
+            let lexeme = &self.lexemes[i];
+            return Ok(self.text[lexeme.start..lexeme.end].to_vec());
+        }
+        let mut domain = Vec::new();
+        loop {
+            let i = self.peek().ok_or(())?;
+            match self.lexemes[i].token {
+                Token::Atom => {
+                    let lexeme = &self.lexemes[i];
+                    domain.extend_from_slice(&self.text[lexeme.start..lexeme.end]);
+                }
+                _ => return Err(()),
+            }
+            self.pos += 1;
+            if self.peek_special() != Some(b'.') {
+                return Ok(domain);
+            }
+            self.pos += 1;
+            domain.push(b'.');
+        }
+    }
+
+    /// Parse the address in angle brackets, after the `<'.
+    fn angle_address(&mut self) -> Result<(Vec<u8>, Option<Vec<u8>>), ()> {
+        // An obsolete source route, as in <@a,@b:user@c>.
+        if self.peek_special() == Some(b'@') {
+            while self.peek_special() != Some(b':') {
+                self.peek().ok_or(())?;
+                self.pos += 1;
+            }
+            self.pos += 1;
+        }
+        if self.expect(b'>').is_ok() {
+            // The null address of bounce messages.
+            return Ok((Vec::new(), None));
+        }
+        let words = self.words();
+        if words.is_empty() {
+            return Err(());
+        }
+        let local = self.local_part(&words);
+        self.expect(b'@')?;
+        let domain = self.domain()?;
+        self.expect(b'>')?;
+        Ok((local, Some(domain)))
+    }
+
+    fn mailbox(&mut self) -> Result<Mailbox, ()> {
+        self.comments.clear();
+        let words = self.words();
+        let (name, local, domain) = match self.peek_special() {
+            Some(b'<') => {
+                self.pos += 1;
+                let (local, domain) = self.angle_address()?;
+                let name = if words.is_empty() {
+                    None
+                } else {
+                    Some(self.phrase(&words))
+                };
+                (name, local, domain)
+            }
+            Some(b'@') if !words.is_empty() => {
+                self.pos += 1;
+                let local = self.local_part(&words);
+                (None, local, Some(self.domain()?))
+            }
+            // A bare local name, as some mailers write for local users.
+            _ if !words.is_empty() && words[1..].iter().all(|&i| !self.lexemes[i].space_before) => {
+                (None, self.local_part(&words), None)
+            }
+            _ => return Err(()),
+        };
+        // Take the comments that follow the address too.
+        self.peek();
+        Ok(Mailbox {
+            name,
+            local,
+            domain,
+            comments: self.comments.drain(..).collect(),
+        })
+    }
+
+    /// Whether the address at point is a group, whose display name is
+    /// followed by a colon.
+    fn at_group(&self) -> bool {
+        for lexeme in &self.lexemes[self.pos..] {
+            match lexeme.token {
+                Token::Special(b':') => return true,
+                Token::Special(b'.') | Token::Atom | Token::Quoted(_) | Token::Comment(_) => (),
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// The text from START to the next unparsed separator in STOPS, as
+    /// an invalid address.  Skip the text.
+    fn invalid(&mut self, start: usize, stops: &[u8]) -> Address {
+        while self.pos < self.lexemes.len() {
+            match self.lexemes[self.pos].token {
+                Token::Special(b) if stops.contains(&b) => break,
+                _ => self.pos += 1,
+            }
+        }
+        let from = self.lexemes[start].start;
+        let to = self.lexemes[self.pos - 1].end;
+        Address::Invalid(self.text[from..to].to_vec())
+    }
+
+    /// Parse a mailbox that ends at one of STOPS or at the end.
+    fn member(&mut self, stops: &[u8]) -> Address {
+        let start = self.pos;
+        if let Ok(mailbox) = self.mailbox() {
+            let ended = match self.peek_special() {
+                Some(b) => stops.contains(&b),
+                None => self.at_end(),
+            };
+            if ended {
+                return Address::Mailbox(mailbox);
+            }
+        }
+        self.pos = start;
+        self.invalid(start, stops)
+    }
+
+    fn group(&mut self) -> Address {
+        let start = self.pos;
+        let words = self.words();
+        let name = self.phrase(&words);
+        if self.expect(b':').is_err() {
+            self.pos = start;
+            return self.invalid(start, b",");
+        }
+        let mut members = Vec::new();
+        while !self.at_end() {
+            match self.peek_special() {
+                Some(b';') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b',') => self.pos += 1,
+                _ => members.push(self.member(b",;")),
+            }
+        }
+        Address::Group(name, members)
+    }
+
+    fn addresses(&mut self) -> Vec<Address> {
+        let mut addresses = Vec::new();
+        while !self.at_end() {
+            if self.peek_special() == Some(b',') {
+                self.pos += 1;
+                continue;
+            }
+            let start = self.pos;
+            let address = if self.at_group() {
+                let group = self.group();
+                let ended = self.at_end() || self.peek_special() == Some(b',');
+                match group {
+                    Address::Group(..) if !ended => {
+                        self.pos = start;
+                        self.invalid(start, b",")
+                    }
+                    group => group,
+                }
+            } else {
+                self.member(b",")
+            };
+            addresses.push(address);
+        }
+        addresses
+    }
+}
+
+/// Parse the address list TEXT.
+fn parse_addresses(text: &[u8]) -> Vec<Address> {
+    AddressParser::new(text).addresses()
+}
+
+fn address_to_lisp(address: &Address, multibyte: bool) -> LispObject {
+    let make_list = |items: Vec<LispObject>| {
+        items
+            .into_iter()
+            .rev()
+            .fold(LispObject::constant_nil(), |list, item| LispObject::cons(item, list))
+    };
+    match *address {
+        Address::Mailbox(ref mailbox) => list!(
+            intern(":name"),
+            match mailbox.name {
+                Some(ref name) => make_string(name, multibyte),
+                None => LispObject::constant_nil(),
+            },
+            intern(":address"),
+            make_string(&mailbox.address(), multibyte),
+            intern(":comments"),
+            make_list(
+                mailbox
+                    .comments
+                    .iter()
+                    .map(|comment| make_string(comment, multibyte))
+                    .collect()
+            )
+        ),
+        Address::Group(ref name, ref members) => list!(
+            intern(":group"),
+            make_string(name, multibyte),
+            intern(":addresses"),
+            make_list(
+                members
+                    .iter()
+                    .map(|member| address_to_lisp(member, multibyte))
+                    .collect()
+            )
+        ),
+        Address::Invalid(ref text) => list!(intern(":invalid"), make_string(text, multibyte)),
+    }
+}
+
+/// Parse STRING as a list of mail addresses, per RFC 5322.
+/// Return a list with an element for each address, which is one of:
+///
+///   (:name NAME :address ADDRESS :comments COMMENTS)
+///       A mailbox.  NAME is its display name, or nil if it has none,
+///       and ADDRESS is `local@domain', with the local part quoted if
+///       it needs to be.  COMMENTS is a list of the text of the
+///       comments in the mailbox, as in "user@host (Full Name)".
+///   (:group NAME :addresses ADDRESSES)
+///       A group, as in "Team: a@b, c@d;".  ADDRESSES are its mailboxes.
+///   (:invalid TEXT)
+///       TEXT could not be parsed as an address.
+///
+/// Quoted strings, nested comments and the obsolete forms of the
+/// standard, such as source routes, are understood.  A single word is
+/// taken to be a local address without a domain.  Encoded words in
+/// names are left alone; see `rfc2047-decode-string'.
+#[lisp_fn]
+pub fn mail_parse_addresses(string: LispObject) -> LispObject {
+    let string = string.as_string_or_error();
+    let multibyte = string.is_multibyte();
+    parse_addresses(string.as_slice())
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, address| {
+            LispObject::cons(address_to_lisp(address, multibyte), list)
+        })
+}
+
+#[test]
+fn test_qp_decode() {
+    assert_eq!(
+        qp_decode(b"caf=E9 =3d=\nnext=  \r\nline", false),
+        (b"caf\xe9 =nextline".to_vec(), false)
+    );
+    assert_eq!(qp_decode(b"=e9", true), (b"\xc1\xa9".to_vec(), false));
+    assert_eq!(qp_decode(b"1=2", false), (b"1=2".to_vec(), true));
+    assert_eq!(qp_decode(b"end=", false), (b"end=".to_vec(), true));
+}
+
+#[test]
+fn test_encoded_words() {
+    let text = b"a =?UTF-8?Q?caf=C3=A9?= =?iso-8859-1*fr?b?6Q==?= =?x?z?y?= =?us-ascii?Q?a?b?=";
+    let words = encoded_words(text);
+    assert_eq!(words.len(), 3);
+    assert_eq!(words[0].charset, b"UTF-8");
+    assert_eq!(words[0].decode(), Some(b"caf\xc3\xa9".to_vec()));
+    assert_eq!(words[1].charset, b"iso-8859-1");
+    assert_eq!(words[1].decode(), Some(b"\xe9".to_vec()));
+    assert_eq!(words[2].text, b"a?b");
+    assert_eq!(b_decode(b"6Q"), Some(b"\xe9".to_vec()));
+    assert_eq!(b_decode(b"6"), None);
+    assert_eq!(q_decode(b"a_b=3F=zz"), b"a b?=zz".to_vec());
+}
+
+#[test]
+fn test_parse_part() {
+    let text: &[u8] = b"From: a@b\r\nContent-Type: multipart/mixed;\r\n boundary=\"=-x\"\r\n\r\n\
+preamble\r\n--=-x\r\n\r\nplain\r\n--=-x\r\nContent-type: Message/RFC822\r\n\r\n\
+Subject: hi\r\n\r\ninner\r\n--=-x--\r\nepilogue";
+    let part = parse_part(text, 0, text.len(), "text/plain", 0);
+    assert_eq!(part.media_type, "multipart/mixed");
+    assert_eq!(part.parameter("boundary"), Some(&b"=-x"[..]));
+    assert_eq!(
+        part.header("content-type"),
+        Some(&b"multipart/mixed; boundary=\"=-x\""[..])
+    );
+    assert_eq!(part.parts.len(), 2);
+    let plain = &part.parts[0];
+    assert_eq!(plain.media_type, "text/plain");
+    assert_eq!(&text[plain.body..plain.end], b"plain");
+    let message = &part.parts[1];
+    assert_eq!(message.media_type, "message/rfc822");
+    assert_eq!(message.parts.len(), 1);
+    let inner = &message.parts[0];
+    assert_eq!(inner.header("subject"), Some(&b"hi"[..]));
+    assert_eq!(&text[inner.body..inner.end], b"inner");
+
+    // No close delimiter, and no headers at all.
+    let text: &[u8] = b"Content-Type: multipart/alternative; boundary=b\n\n--b\nno headers\n";
+    let part = parse_part(text, 0, text.len(), "text/plain", 0);
+    assert_eq!(part.parts.len(), 1);
+    assert_eq!(part.parts[0].body, part.parts[0].start);
+    assert_eq!(&text[part.parts[0].body..], b"no headers\n");
+
+    // A boundary that never occurs.
+    let text: &[u8] = b"Content-Type: multipart/mixed; boundary=zz\n\nbody";
+    assert!(parse_part(text, 0, text.len(), "text/plain", 0).parts.is_empty());
+}
+
+#[test]
+fn test_parse_addresses() {
+    fn mailbox(name: Option<&[u8]>, local: &[u8], domain: Option<&[u8]>) -> Address {
+        Address::Mailbox(Mailbox {
+            name: name.map(|name| name.to_vec()),
+            local: local.to_vec(),
+            domain: domain.map(|domain| domain.to_vec()),
+            comments: Vec::new(),
+        })
+    }
+    assert_eq!(
+        parse_addresses(b"J.R. Doe <jr@example.com>, \"Smith, Jane\" <jane@[1.2.3.4]>,,"),
+        vec![
+            mailbox(Some(b"J.R. Doe"), b"jr", Some(b"example.com")),
+            mailbox(Some(b"Smith, Jane"), b"jane", Some(b"[1.2.3.4]")),
+        ]
+    );
+
+    let parsed = parse_addresses(b"user@host (Full (Nested) \\) Name)");
+    match parsed[0] {
+        Address::Mailbox(ref mailbox) => {
+            assert_eq!(mailbox.comments, vec![b"Full (Nested) ) Name".to_vec()]);
+            assert_eq!(mailbox.address(), b"user@host".to_vec());
+        }
+        _ => panic!(),
+    }
+
+    assert_eq!(
+        parse_addresses(b"Team: a@b, \"x y\"@c;, <@r1,@r2:u@h>, <>, postmaster"),
+        vec![
+            Address::Group(
+                b"Team".to_vec(),
+                vec![mailbox(None, b"a", Some(b"b")), mailbox(None, b"x y", Some(b"c"))],
+            ),
+            mailbox(None, b"u", Some(b"h")),
+            mailbox(None, b"", None),
+            mailbox(None, b"postmaster", None),
+        ]
+    );
+    match parse_addresses(b"\"x y\"@c")[0] {
+        Address::Mailbox(ref mailbox) => assert_eq!(mailbox.address(), b"\"x y\"@c".to_vec()),
+        _ => panic!(),
+    }
+
+    assert_eq!(
+        parse_addresses(b"foo bar, a@b c, Undisclosed:;, ok@x"),
+        vec![
+            Address::Invalid(b"foo bar".to_vec()),
+            Address::Invalid(b"a@b c".to_vec()),
+            Address::Group(b"Undisclosed".to_vec(), Vec::new()),
+            mailbox(None, b"ok", Some(b"x")),
+        ]
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/mime_exports.rs"));
@@ -1,13 +1,28 @@
 //! Minibuffer input and completion.
 
+use libc::ptrdiff_t;
+use std::cmp::min;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::vec;
+
 use remacs_macros::lisp_fn;
-use remacs_sys::{minibuf_level, minibuf_window};
-use remacs_sys::Vminibuffer_list;
+use remacs_sys::{globals, minibuf_level, minibuf_window, unbind_to};
+use remacs_sys::{Fassoc_string, Fcommandp, Fcompare_strings, Fnreverse, Fstring_make_multibyte,
+                 Fset, Fstring_make_unibyte, Fstring_match, Fsubstring, FUNCTIONP};
+use remacs_sys::{EmacsInt, Qcase_fold_search, Qcommandp, Qhistory_length, Qlambda, Qnil};
+use remacs_sys::{check_obarray, find_symbol_value, oblookup, Vminibuffer_list};
 
 use buffers::{current_buffer, get_buffer};
+use eval_call::{specbind, specpdl_index, with_let_binding};
+use hashtable::LispHashTableRef;
 use lisp::LispObject;
 use lisp::defsubr;
-use lists::memq;
+use lists::{get, list, memq};
+use multibyte::LispStringRef;
+use persist::{decode, encode, finish_background_writes, is_writable, write_in_background};
+use symbols::{symbol_value, LispSymbolRef};
+use util::{expand_file_name_to_path, report_io_error};
 
 
 /// Return t if BUFFER is a minibuffer.
@@ -52,4 +67,792 @@ pub fn set_minibuffer_window(window: LispObject) -> LispObject {
     window
 }
 
+/// Return the value of `completion-ignore-case'.
+fn completion_ignore_case() -> bool {
+    unsafe { globals.f_completion_ignore_case }
+}
+
+/// Return the value of `completion-regexp-list'.
+fn completion_regexp_list() -> LispObject {
+    LispObject::from(unsafe { globals.f_Vcompletion_regexp_list })
+}
+
+/// Compare the first END1 characters of S1 with the first END2
+/// characters of S2, as `compare-strings' does.  A nil END means the
+/// end of the string.
+fn compare_strings(
+    s1: LispObject,
+    end1: LispObject,
+    s2: LispObject,
+    end2: LispObject,
+    ignore_case: bool,
+) -> LispObject {
+    let zero = LispObject::from_natnum(0);
+    LispObject::from(unsafe {
+        Fcompare_strings(
+            s1.to_raw(),
+            zero.to_raw(),
+            end1.to_raw(),
+            s2.to_raw(),
+            zero.to_raw(),
+            end2.to_raw(),
+            LispObject::from_bool(ignore_case).to_raw(),
+        )
+    })
+}
+
+/// Return true if the string ELTSTRING starts with STRING.
+fn starts_with(eltstring: LispObject, string: LispObject, ignore_case: bool) -> bool {
+    let len = LispObject::from_natnum(string.as_string_or_error().len_chars() as EmacsInt);
+    let nil = LispObject::constant_nil();
+    compare_strings(eltstring, len, string, nil, ignore_case).eq(LispObject::constant_t())
+}
+
+/// Return true if STRING matches REGEXP.
+fn string_matches(regexp: LispObject, string: LispObject) -> bool {
+    let tem = unsafe { Fstring_match(regexp.to_raw(), string.to_raw(), Qnil) };
+    LispObject::from(tem).is_not_nil()
+}
+
+/// Return STRING converted to the same representation (unibyte or
+/// multibyte) as BASIS.
+fn conform_representation(string: LispObject, basis: LispObject) -> LispObject {
+    let multibyte = string.as_string_or_error().is_multibyte();
+    if multibyte == basis.as_string_or_error().is_multibyte() {
+        string
+    } else if multibyte {
+        LispObject::from(unsafe { Fstring_make_unibyte(string.to_raw()) })
+    } else {
+        LispObject::from(unsafe { Fstring_make_multibyte(string.to_raw()) })
+    }
+}
+
+/// The kinds of COLLECTION accepted by the completion primitives.
+#[derive(Clone, Copy, PartialEq)]
+enum CollectionKind {
+    Function,
+    List,
+    Obarray,
+    HashTable,
+}
+
+impl CollectionKind {
+    fn of(collection: LispObject) -> CollectionKind {
+        if collection.is_hash_table() {
+            CollectionKind::HashTable
+        } else if collection.is_vector() {
+            CollectionKind::Obarray
+        } else if collection.is_nil()
+            || (collection.is_cons() && !unsafe { FUNCTIONP(collection.to_raw()) })
+        {
+            CollectionKind::List
+        } else {
+            CollectionKind::Function
+        }
+    }
+}
+
+/// A walk over the possible completions in a list, obarray or hash
+/// table COLLECTION, yielding those that start with STRING and pass
+/// `completion-regexp-list' and PREDICATE.
+///
+/// When completion is case-sensitive, hash tables are searched through
+/// their sorted key index instead of being scanned in full.
+struct CompletionScan {
+    string: LispObject,
+    collection: LispObject,
+    kind: CollectionKind,
+    predicate: LispObject,
+    ignore_case: bool,
+    /// Reject candidates starting with a space unless STRING does.
+    hide_spaces: bool,
+    /// The remaining alist elements.
+    tail: LispObject,
+    /// The current obarray bucket or hash table slot.
+    idx: isize,
+    /// The next symbol in the current obarray bucket, or 0.
+    bucket: LispObject,
+    /// Hash table slots whose keys start with STRING, if known.
+    slots: Option<vec::IntoIter<isize>>,
+    /// The specpdl depth before `case-fold-search' was bound for
+    /// matching `completion-regexp-list', if it is bound.
+    bindcount: Option<ptrdiff_t>,
+}
+
+impl CompletionScan {
+    fn new(
+        string: LispObject,
+        collection: LispObject,
+        kind: CollectionKind,
+        predicate: LispObject,
+        hide_spaces: bool,
+    ) -> CompletionScan {
+        let ignore_case = completion_ignore_case();
+        let mut bucket = LispObject::from_natnum(0);
+        let mut slots = None;
+        match kind {
+            CollectionKind::Obarray => {
+                let obarray = LispObject::from(unsafe { check_obarray(collection.to_raw()) });
+                bucket = unsafe { obarray.as_vector_unchecked() }.get(0);
+            }
+            CollectionKind::HashTable if !ignore_case => {
+                slots = collection
+                    .as_hash_table_or_error()
+                    .slots_with_prefix(string.as_string_or_error())
+                    .map(|s| s.into_iter());
+            }
+            _ => {}
+        }
+
+        CompletionScan {
+            string,
+            collection,
+            kind,
+            predicate,
+            ignore_case,
+            hide_spaces,
+            tail: collection,
+            idx: 0,
+            bucket,
+            slots,
+            bindcount: None,
+        }
+    }
+
+    fn table(&self) -> LispHashTableRef {
+        self.collection.as_hash_table_or_error()
+    }
+
+    /// Return the next element of the collection: the alist element,
+    /// symbol or hash key, the name to check as a completion, and for
+    /// hash tables the slot of the key.
+    fn next_element(&mut self) -> Option<(LispObject, LispObject, isize)> {
+        match self.kind {
+            CollectionKind::List => {
+                let cons = match self.tail.as_cons() {
+                    Some(cons) => cons,
+                    None => return None,
+                };
+                self.tail = cons.cdr();
+                let elt = cons.car();
+                let eltstring = elt.as_cons().map_or(elt, |c| c.car());
+                Some((elt, eltstring, -1))
+            }
+            CollectionKind::Obarray => {
+                let obarray = unsafe { self.collection.as_vector_unchecked() };
+                while self.bucket.eq(LispObject::from_natnum(0)) {
+                    self.idx += 1;
+                    if self.idx as usize >= obarray.len() {
+                        return None;
+                    }
+                    self.bucket = obarray.get(self.idx);
+                }
+                let sym = match self.bucket.as_symbol() {
+                    Some(sym) => sym,
+                    None => error!("Bad data in guts of obarray"),
+                };
+                let elt = self.bucket;
+                self.bucket = if sym.next.is_null() {
+                    LispObject::from_natnum(0)
+                } else {
+                    LispSymbolRef::new(sym.next).as_lisp_obj()
+                };
+                Some((elt, elt, -1))
+            }
+            CollectionKind::HashTable => {
+                let table = self.table();
+                let slot = match self.slots {
+                    Some(ref mut slots) => match slots.next() {
+                        Some(slot) => slot,
+                        None => return None,
+                    },
+                    None => {
+                        let size = table.size() as isize;
+                        while self.idx < size && table.get_hash_hash(self.idx).is_nil() {
+                            self.idx += 1;
+                        }
+                        if self.idx >= size {
+                            return None;
+                        }
+                        self.idx += 1;
+                        self.idx - 1
+                    }
+                };
+                let key = table.get_hash_key(slot);
+                Some((key, key, slot))
+            }
+            CollectionKind::Function => unreachable!("function tables do their own completion"),
+        }
+    }
+
+    fn unbind(&mut self) {
+        if let Some(count) = self.bindcount.take() {
+            unsafe { unbind_to(count, Qnil) };
+        }
+    }
+
+    /// Return true if ELTSTRING, the name of ELT, is a completion of STRING.
+    fn accepts(&mut self, elt: LispObject, eltstring: LispObject, slot: isize) -> bool {
+        let candidate = match eltstring.as_string() {
+            Some(s) => s,
+            None => return false,
+        };
+        let input = self.string.as_string_or_error();
+        if input.len_chars() > candidate.len_chars() {
+            return false;
+        }
+        // If HIDE_SPACES, reject alternatives that start with space
+        // unless the input starts with space.
+        if self.hide_spaces && input.as_slice().first() != Some(&b' ')
+            && candidate.as_slice().first() == Some(&b' ')
+        {
+            return false;
+        }
+        if !starts_with(eltstring, self.string, self.ignore_case) {
+            return false;
+        }
+
+        // Ignore this element if it fails to match all the regexps.
+        for regexp in completion_regexp_list().iter_cars_safe() {
+            if self.bindcount.is_none() {
+                self.bindcount = Some(specpdl_index());
+                specbind(Qcase_fold_search, LispObject::from_bool(self.ignore_case).to_raw());
+            }
+            if !string_matches(regexp, eltstring) {
+                return false;
+            }
+        }
+
+        // Ignore this element if there is a predicate and the predicate
+        // doesn't like it.
+        if self.predicate.is_nil() {
+            return true;
+        }
+        let tem = if self.predicate.eq(LispObject::from(Qcommandp)) {
+            LispObject::from(unsafe { Fcommandp(elt.to_raw(), Qnil) })
+        } else {
+            self.unbind();
+            if self.kind == CollectionKind::HashTable {
+                call!(self.predicate, elt, self.table().get_hash_value(slot))
+            } else {
+                call!(self.predicate, elt)
+            }
+        };
+        tem.is_not_nil()
+    }
+
+    /// Return the next accepted element and its name as a string.
+    fn next_match(&mut self) -> Option<(LispObject, LispObject)> {
+        while let Some((elt, eltstring, slot)) = self.next_element() {
+            let eltstring = match eltstring.as_symbol() {
+                Some(sym) => sym.symbol_name(),
+                None => eltstring,
+            };
+            if self.accepts(elt, eltstring, slot) {
+                return Some((elt, eltstring));
+            }
+        }
+        None
+    }
+}
+
+/// Return common substring of all completions of STRING in COLLECTION.
+/// Test each possible completion specified by COLLECTION
+/// to see if it begins with STRING.  The possible completions may be
+/// strings or symbols.  Symbols are converted to strings before testing,
+/// see `symbol-name'.
+/// All that match STRING are compared together; the longest initial sequence
+/// common to all these matches is the return value.
+/// If there is no match at all, the return value is nil.
+/// For a unique match which is exact, the return value is t.
+///
+/// If COLLECTION is an alist, the keys (cars of elements) are the
+/// possible completions.  If an element is not a cons cell, then the
+/// element itself is the possible completion.
+/// If COLLECTION is a hash-table, all the keys that are strings or symbols
+/// are the possible completions.
+/// If COLLECTION is an obarray, the names of all symbols in the obarray
+/// are the possible completions.
+///
+/// COLLECTION can also be a function to do the completion itself.
+/// It receives three arguments: the values STRING, PREDICATE and nil.
+/// Whatever it returns becomes the value of `try-completion'.
+///
+/// If optional third argument PREDICATE is non-nil,
+/// it is used to test each possible match.
+/// The match is a candidate only if PREDICATE returns non-nil.
+/// The argument given to PREDICATE is the alist element
+/// or the symbol from the obarray.  If COLLECTION is a hash-table,
+/// predicate is called with two arguments: the key and the value.
+/// Additionally to this predicate, `completion-regexp-list'
+/// is used to further constrain the set of candidates.
+#[lisp_fn(min = "2")]
+pub fn try_completion(
+    string: LispObject,
+    collection: LispObject,
+    predicate: LispObject,
+) -> LispObject {
+    let input_len = string.as_string_or_error().len_chars();
+    let kind = CollectionKind::of(collection);
+    if kind == CollectionKind::Function {
+        return call!(collection, string, predicate, LispObject::constant_nil());
+    }
+
+    let ignore_case = completion_ignore_case();
+    let nchars = |s: LispObject| s.as_string_or_error().len_chars();
+    let mut scan = CompletionScan::new(string, collection, kind, predicate, false);
+    let mut bestmatch = LispObject::constant_nil();
+    // Size in characters of the prefix common to all matches.
+    let mut bestmatchsize: ptrdiff_t = 0;
+    let mut matchcount = 0;
+
+    while let Some((_, eltstring)) = scan.next_match() {
+        // Update computation of how much all possible completions match.
+        if bestmatch.is_nil() {
+            matchcount = 1;
+            bestmatch = eltstring;
+            bestmatchsize = nchars(eltstring);
+            continue;
+        }
+
+        let compare = min(bestmatchsize, nchars(eltstring));
+        let end = LispObject::from_natnum(compare as EmacsInt);
+        let tem = compare_strings(bestmatch, end, eltstring, end, ignore_case);
+        let matchsize = if tem.eq(LispObject::constant_t()) {
+            compare
+        } else {
+            tem.as_fixnum_or_error().abs() as ptrdiff_t - 1
+        };
+
+        if ignore_case {
+            let exact = matchsize == nchars(eltstring);
+            let best_exact = matchsize == nchars(bestmatch);
+            // If this is an exact match except for case, use it as the
+            // best match rather than one that is not an exact match.
+            // This way, we get the case pattern of the actual match.
+            // If there is more than one exact match ignoring case, and
+            // one of them is exact including case, prefer that one.  If
+            // there is no exact match ignoring case, prefer a match that
+            // does not change the case of the input.
+            if (exact && !best_exact)
+                || (exact == best_exact && starts_with(eltstring, string, false)
+                    && !starts_with(bestmatch, string, false))
+            {
+                bestmatch = eltstring;
+            }
+        }
+        if bestmatchsize != nchars(eltstring) || bestmatchsize != matchsize {
+            // Don't count the same string multiple times.
+            matchcount = min(matchcount + 1, 2);
+        }
+        bestmatchsize = matchsize;
+        // If completion-ignore-case is non-nil, don't short-circuit
+        // because we want to find the best possible match *including*
+        // case differences.
+        if matchsize <= input_len && !ignore_case && matchcount > 1 {
+            // No need to look any further.
+            break;
+        }
+    }
+    scan.unbind();
+
+    if bestmatch.is_nil() {
+        // No completions found.
+        return LispObject::constant_nil();
+    }
+    // If we are ignoring case, and there is no exact match, and no
+    // additional text was supplied, don't change the case of what the
+    // user typed.
+    if ignore_case && bestmatchsize == input_len && nchars(bestmatch) > bestmatchsize {
+        return conform_representation(string, bestmatch);
+    }
+    // Return t if the supplied string is an exact match (counting
+    // case); it does not require any change to be made.
+    if matchcount == 1 && bestmatch.equal(string) {
+        return LispObject::constant_t();
+    }
+
+    // Else extract the part in which all completions agree.
+    LispObject::from(unsafe {
+        Fsubstring(
+            bestmatch.to_raw(),
+            LispObject::from_natnum(0).to_raw(),
+            LispObject::from_natnum(bestmatchsize as EmacsInt).to_raw(),
+        )
+    })
+}
+
+/// Search for partial matches to STRING in COLLECTION.
+/// Test each of the possible completions specified by COLLECTION
+/// to see if it begins with STRING.  The possible completions may be
+/// strings or symbols.  Symbols are converted to strings before testing,
+/// see `symbol-name'.
+/// The value is a list of all the possible completions that match STRING.
+///
+/// If COLLECTION is an alist, the keys (cars of elements) are the
+/// possible completions.  If an element is not a cons cell, then the
+/// element itself is the possible completion.
+/// If COLLECTION is a hash-table, all the keys that are strings or symbols
+/// are the possible completions.
+/// If COLLECTION is an obarray, the names of all symbols in the obarray
+/// are the possible completions.
+///
+/// COLLECTION can also be a function to do the completion itself.
+/// It receives three arguments: the values STRING, PREDICATE and t.
+/// Whatever it returns becomes the value of `all-completions'.
+///
+/// If optional third argument PREDICATE is non-nil,
+/// it is used to test each possible match.
+/// The match is a candidate only if PREDICATE returns non-nil.
+/// The argument given to PREDICATE is the alist element
+/// or the symbol from the obarray.  If COLLECTION is a hash-table,
+/// predicate is called with two arguments: the key and the value.
+/// Additionally to this predicate, `completion-regexp-list'
+/// is used to further constrain the set of candidates.
+///
+/// An obsolete optional fourth argument HIDE-SPACES is still accepted for
+/// backward compatibility.  If non-nil, strings in COLLECTION that start
+/// with a space are ignored unless STRING itself starts with a space.
+#[lisp_fn(min = "2")]
+pub fn all_completions(
+    string: LispObject,
+    collection: LispObject,
+    predicate: LispObject,
+    hide_spaces: LispObject,
+) -> LispObject {
+    string.as_string_or_error();
+    let kind = CollectionKind::of(collection);
+    if kind == CollectionKind::Function {
+        return call!(collection, string, predicate, LispObject::constant_t());
+    }
+
+    let hide_spaces = hide_spaces.is_not_nil();
+    let mut scan = CompletionScan::new(string, collection, kind, predicate, hide_spaces);
+    let mut allmatches = LispObject::constant_nil();
+    while let Some((_, eltstring)) = scan.next_match() {
+        allmatches = LispObject::cons(eltstring, allmatches);
+    }
+    scan.unbind();
+
+    LispObject::from(unsafe { Fnreverse(allmatches.to_raw()) })
+}
+
+/// Return the symbol in OBARRAY that completes STRING exactly, or nil.
+fn obarray_exact_completion(
+    obarray: LispObject,
+    string: LispObject,
+    ignore_case: bool,
+) -> Option<LispObject> {
+    let lookup = |s: LispStringRef| {
+        LispObject::from(unsafe {
+            oblookup(obarray.to_raw(), s.const_sdata_ptr(), s.len_chars(), s.len_bytes())
+        })
+    };
+
+    // Bypass intern-soft as that loses for nil.
+    let mut tem = lookup(string.as_string_or_error());
+    let mut string = string;
+    if !tem.is_symbol() {
+        string = if string.as_string_or_error().is_multibyte() {
+            LispObject::from(unsafe { Fstring_make_unibyte(string.to_raw()) })
+        } else {
+            LispObject::from(unsafe { Fstring_make_multibyte(string.to_raw()) })
+        };
+        tem = lookup(string.as_string_or_error());
+    }
+
+    if ignore_case && !tem.is_symbol() {
+        let buckets = unsafe { obarray.as_vector_unchecked() };
+        for &bucket in buckets.as_slice().iter().rev() {
+            let mut next = bucket.as_symbol();
+            while let Some(sym) = next {
+                let name = sym.symbol_name();
+                let nil = LispObject::constant_nil();
+                if compare_strings(string, nil, name, nil, true).eq(LispObject::constant_t()) {
+                    tem = sym.as_lisp_obj();
+                    break;
+                }
+                next = if sym.next.is_null() {
+                    None
+                } else {
+                    Some(LispSymbolRef::new(sym.next))
+                };
+            }
+        }
+    }
+
+    if tem.is_symbol() {
+        Some(tem)
+    } else {
+        None
+    }
+}
+
+/// Return the slot of the key in TABLE that completes STRING exactly.
+fn hash_table_exact_completion(
+    table: LispHashTableRef,
+    string: LispObject,
+    ignore_case: bool,
+) -> Option<isize> {
+    let idx = table.lookup(string, ::std::ptr::null_mut());
+    if idx >= 0 {
+        return Some(idx);
+    }
+
+    let matches = |idx: isize| {
+        let key = table.get_hash_key(idx);
+        let strkey = match key.as_symbol() {
+            Some(sym) => sym.symbol_name(),
+            None => key,
+        };
+        let nil = LispObject::constant_nil();
+        strkey.is_string()
+            && compare_strings(string, nil, strkey, nil, ignore_case).eq(LispObject::constant_t())
+    };
+    let candidates = if ignore_case {
+        None
+    } else {
+        table.slots_with_prefix(string.as_string_or_error())
+    };
+    match candidates {
+        Some(slots) => slots.into_iter().find(|&idx| matches(idx)),
+        None => table
+            .indices()
+            .find(|&idx| table.get_hash_hash(idx).is_not_nil() && matches(idx)),
+    }
+}
+
+/// Return non-nil if STRING is a valid completion.
+/// Takes the same arguments as `all-completions' and `try-completion'.
+/// If COLLECTION is a function, it is called with three arguments:
+/// the values STRING, PREDICATE and `lambda'.
+#[lisp_fn(min = "2")]
+pub fn test_completion(
+    string: LispObject,
+    collection: LispObject,
+    predicate: LispObject,
+) -> LispObject {
+    string.as_string_or_error();
+    let ignore_case = completion_ignore_case();
+
+    let (tem, slot) = match CollectionKind::of(collection) {
+        CollectionKind::List => {
+            let tem = LispObject::from(unsafe {
+                Fassoc_string(
+                    string.to_raw(),
+                    collection.to_raw(),
+                    LispObject::from_bool(ignore_case).to_raw(),
+                )
+            });
+            if tem.is_nil() {
+                return LispObject::constant_nil();
+            }
+            (tem, -1)
+        }
+        CollectionKind::Obarray => match obarray_exact_completion(collection, string, ignore_case) {
+            Some(tem) => (tem, -1),
+            None => return LispObject::constant_nil(),
+        },
+        CollectionKind::HashTable => {
+            let table = collection.as_hash_table_or_error();
+            match hash_table_exact_completion(table, string, ignore_case) {
+                Some(slot) => (table.get_hash_key(slot), slot),
+                None => return LispObject::constant_nil(),
+            }
+        }
+        CollectionKind::Function => {
+            return call!(collection, string, predicate, LispObject::from(Qlambda));
+        }
+    };
+
+    // Reject this element if it fails to match all the regexps.  We can
+    // test against STRING, because if we got here, then the element is
+    // equivalent to it.
+    let regexps = completion_regexp_list();
+    if regexps.is_cons() {
+        let case_fold = LispObject::from_bool(ignore_case);
+        let matches = with_let_binding(LispObject::from(Qcase_fold_search), case_fold, || {
+            regexps
+                .iter_cars_safe()
+                .all(|regexp| string_matches(regexp, string))
+        });
+        if !matches {
+            return LispObject::constant_nil();
+        }
+    }
+
+    // Finally, check the predicate.
+    if predicate.is_nil() {
+        LispObject::constant_t()
+    } else if slot >= 0 {
+        call!(predicate, tem, collection.as_hash_table_or_error().get_hash_value(slot))
+    } else {
+        call!(predicate, tem)
+    }
+}
+
+/// Return HISTORY with NEWELT pushed onto its front.  Other elements
+/// `equal' to NEWELT are removed if DELETE_DUPLICATES is true, and the
+/// result is truncated to MAXELT elements if MAXELT is a positive
+/// integer.  A MAXELT of zero empties the history, and a negative one
+/// leaves only NEWELT, as `add-to-history' always did.
+///
+/// HISTORY is modified destructively, in a single pass, so adding to
+/// long histories stays cheap.
+fn push_history(
+    history: LispObject,
+    newelt: LispObject,
+    maxelt: LispObject,
+    delete_duplicates: bool,
+) -> LispObject {
+    let limit = match maxelt.as_fixnum() {
+        Some(0) => return LispObject::constant_nil(),
+        // Like the `nthcdr' of a negative count, keep just NEWELT.
+        Some(n) if n < 0 => Some(1),
+        limit => limit,
+    };
+
+    let head = LispObject::cons(newelt, history);
+    let mut prev = head.as_cons().unwrap();
+    let mut kept = 1;
+    let mut tail = history;
+    while let Some(cell) = tail.as_cons() {
+        if limit == Some(kept) {
+            prev.set_cdr(LispObject::constant_nil());
+            break;
+        }
+        tail = cell.cdr();
+        if delete_duplicates && cell.car().equal(newelt) {
+            prev.set_cdr(tail);
+        } else {
+            prev = cell;
+            kept += 1;
+        }
+    }
+    head
+}
+
+/// Add NEWELT to the history list stored in the variable HISTORY-VAR.
+/// Return the new history list.
+/// If MAXELT is non-nil, it specifies the maximum length of the history.
+/// Otherwise, the maximum history length is the value of the `history-length'
+/// property on symbol HISTORY-VAR, if set, or the value of the `history-length'
+/// variable.  The possible values of maximum length have the same meaning as
+/// the values of `history-length'.
+/// Remove duplicates of NEWELT if `history-delete-duplicates' is non-nil.
+/// If optional fourth arg KEEP-ALL is non-nil, add NEWELT to history even
+/// if it is empty or a duplicate.
+#[lisp_fn(min = "2")]
+pub fn add_to_history(
+    history_var: LispObject,
+    newelt: LispObject,
+    maxelt: LispObject,
+    keep_all: LispObject,
+) -> LispObject {
+    let maxelt = if maxelt.is_not_nil() {
+        maxelt
+    } else {
+        let length = get(history_var, LispObject::from(Qhistory_length));
+        if length.is_not_nil() {
+            length
+        } else {
+            LispObject::from(unsafe { globals.f_Vhistory_length })
+        }
+    };
+
+    let mut history = symbol_value(history_var);
+    let keep_all = keep_all.is_not_nil();
+    if history.is_list()
+        && (keep_all || newelt.as_string().map_or(true, |s| s.len_chars() > 0))
+        && (keep_all || !history.as_cons().map_or(false, |c| c.car().equal(newelt)))
+    {
+        let delete_duplicates = unsafe { globals.f_history_delete_duplicates };
+        history = push_history(history, newelt, maxelt, delete_duplicates);
+    }
+
+    unsafe { Fset(history_var.to_raw(), history.to_raw()) };
+    history
+}
+
+/// Return the part of the history HISTORY that `lisp-data-write' can
+/// write: all of it if possible, or else the elements it can write, if
+/// HISTORY is a proper list.
+fn writable_history(history: LispObject) -> Option<LispObject> {
+    if is_writable(history) {
+        return Some(history);
+    }
+    if !history.is_cons() {
+        return None;
+    }
+    let mut tails = history.iter_tails_safe();
+    let mut elements: Vec<LispObject> = tails
+        .by_ref()
+        .map(|tail| tail.car())
+        .filter(|&element| is_writable(element))
+        .collect();
+    if tails.rest().is_not_nil() {
+        return None;
+    }
+    Some(list(&mut elements))
+}
+
+/// Save the values of the history variables VARIABLES in FILE.
+/// Unbound variables are skipped.  Elements of a history that cannot be
+/// written in the format of `lisp-data-write', like markers or buffers,
+/// are left out, and so are values that are neither lists nor writable.
+/// The file is written on a separate thread, so this returns at once;
+/// Emacs waits for the write to finish before it exits.
+#[lisp_fn]
+pub fn history_native_save(file: LispObject, variables: LispObject) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let name = String::from_utf8_lossy(file.as_string_or_error().as_slice()).into_owned();
+    let mut saved = Vec::new();
+    for variable in variables.iter_cars() {
+        if !variable.as_symbol_or_error().is_interned_in_initial_obarray() {
+            continue;
+        }
+        let value = LispObject::from(unsafe { find_symbol_value(variable.to_raw()) });
+        if value.eq(LispObject::constant_unbound()) {
+            continue;
+        }
+        if let Some(history) = writable_history(value) {
+            saved.push(LispObject::cons(variable, history));
+        }
+    }
+    write_in_background(path, name, encode(list(&mut saved)));
+    LispObject::constant_nil()
+}
+
+/// Set the history variables saved in FILE by `history-native-save'.
+/// Return the list of the variables set, which is nil if FILE does not
+/// exist.
+#[lisp_fn]
+pub fn history_native_load(file: LispObject) -> LispObject {
+    finish_background_writes();
+    let path = expand_file_name_to_path(file);
+    let mut data = Vec::new();
+    let saved = match File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+        Ok(_) => decode(&data),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => LispObject::constant_nil(),
+        Err(err) => report_io_error(b"Reading history\0", file, &err),
+    };
+    let valid = saved.is_list() && saved.iter_cars_safe().all(|entry| {
+        entry
+            .as_cons()
+            .map_or(false, |entry| entry.car().is_symbol())
+    });
+    if !valid {
+        error!("Invalid history data");
+    }
+    let mut variables: Vec<LispObject> = saved
+        .iter_cars()
+        .map(|entry| {
+            let entry = entry.as_cons().unwrap();
+            unsafe { Fset(entry.car().to_raw(), entry.cdr().to_raw()) };
+            entry.car()
+        })
+        .collect();
+    list(&mut variables)
+}
+
 include!(concat!(env!("OUT_DIR"), "/minibuf_exports.rs"));
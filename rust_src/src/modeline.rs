@@ -0,0 +1,228 @@
+//! Formatting mode line constructs with cached `:eval' results.
+//!
+//! `format-mode-line' evaluates every `:eval' form of a mode line
+//! construct each time, and most of the cost of formatting the mode
+//! lines of many windows is in those forms, although their values
+//! rarely change between two redisplays.  `format-mode-line-cached'
+//! walks the construct itself, keeps the value of each form until the
+//! buffer, its modification tick, the window's point or its selection
+//! change, and pads and truncates the pieces by their width.
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{globals, record_unwind_current_buffer, unbind_to, EmacsInt, Qnil};
+
+use buffers::set_buffer;
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use windows::{selected_window, window_buffer, window_or_selected_unchecked, window_point};
+
+/// Constructs nested deeper than this are not formatted, as in
+/// `display_mode_element'.
+const MAX_DEPTH: usize = 100;
+
+/// Return the table of cached `:eval' values, making it if needed.
+fn eval_cache() -> LispObject {
+    let cache = LispObject::from(unsafe { globals.f_Vformat_mode_line_cache });
+    if cache.is_hash_table() {
+        return cache;
+    }
+    let cache = call!(
+        intern("make-hash-table"),
+        intern(":test"),
+        intern("eq"),
+        intern(":weakness"),
+        intern("key")
+    );
+    unsafe { globals.f_Vformat_mode_line_cache = cache.to_raw() };
+    cache
+}
+
+/// The state of a window and its buffer when mode line constructs are
+/// formatted.
+struct Context {
+    window: LispObject,
+    buffer: LispObject,
+    /// What the cached values depend on: the buffer, its modification
+    /// tick, the window's point, and whether it is selected.
+    tick: LispObject,
+}
+
+impl Context {
+    /// Return the value of FORM, evaluated or taken from the cache.
+    /// Errors in FORM make its value nil, as in mode lines.
+    fn eval(&self, form: LispObject) -> LispObject {
+        let cache = eval_cache();
+        let entries = call!(intern("gethash"), form, cache);
+        let entry = call!(intern("assq"), self.window, entries);
+        if entry.is_not_nil() {
+            let tick = call!(intern("cadr"), entry);
+            if call!(intern("equal"), tick, self.tick).is_not_nil() {
+                return call!(intern("cddr"), entry);
+            }
+        }
+        let guarded = list!(
+            intern("condition-case"),
+            LispObject::constant_nil(),
+            form,
+            list!(intern("error"), LispObject::constant_nil())
+        );
+        let value = call!(intern("eval"), guarded, LispObject::constant_t());
+        let entry = LispObject::cons(self.window, LispObject::cons(self.tick, value));
+        let others = call!(intern("assq-delete-all"), self.window, entries);
+        let entries = LispObject::cons(entry, others);
+        call!(intern("puthash"), form, entries, cache);
+        value
+    }
+
+    /// Format ELT, a mode line construct, and return a string.  RISKY
+    /// is true if ELT came from a variable not marked as risky, whose
+    /// `:eval' and `:propertize' forms are ignored.
+    fn format(&self, elt: LispObject, risky: bool, depth: usize) -> LispObject {
+        if depth > MAX_DEPTH {
+            return lisp_string("*too-deep*");
+        }
+        if elt.is_string() {
+            if elt.as_string_or_error().as_slice().contains(&b'%') {
+                return call!(
+                    intern("format-mode-line"),
+                    elt,
+                    LispObject::constant_nil(),
+                    self.window,
+                    self.buffer
+                );
+            }
+            return elt;
+        }
+        if elt.is_symbol() {
+            if call!(intern("boundp"), elt).is_nil() {
+                return lisp_string("");
+            }
+            // The value of a variable that is not marked as risky is
+            // not trusted.
+            let risky =
+                risky || call!(intern("get"), elt, intern("risky-local-variable")).is_nil();
+            let value = call!(intern("symbol-value"), elt);
+            if value.is_string() {
+                // The value of a symbol is used literally.
+                return value;
+            }
+            if value.eq(elt) {
+                return lisp_string("");
+            }
+            return self.format(value, risky, depth + 1);
+        }
+        let cons = match elt.as_cons() {
+            Some(cons) => cons,
+            None => return lisp_string("*invalid*"),
+        };
+        let (car, cdr) = (cons.car(), cons.cdr());
+        if car.eq(intern(":eval")) {
+            if risky || !cdr.is_cons() {
+                return lisp_string("");
+            }
+            let value = self.eval(call!(intern("car"), cdr));
+            self.format(value, risky, depth + 1)
+        } else if car.eq(intern(":propertize")) {
+            if risky || !cdr.is_cons() {
+                return lisp_string("");
+            }
+            let string = self.format(call!(intern("car"), cdr), risky, depth + 1);
+            let string = call!(intern("copy-sequence"), string);
+            let length = string.as_string_or_error().len_chars() as EmacsInt;
+            call!(
+                intern("add-text-properties"),
+                LispObject::from_fixnum(0),
+                LispObject::from_fixnum(length),
+                call!(intern("cdr"), cdr),
+                string
+            );
+            string
+        } else if car.is_symbol() {
+            let chosen = if call!(intern("boundp"), car).is_not_nil()
+                && call!(intern("symbol-value"), car).is_not_nil()
+            {
+                call!(intern("car-safe"), cdr)
+            } else {
+                call!(intern("car-safe"), call!(intern("cdr-safe"), cdr))
+            };
+            self.format(chosen, risky, depth + 1)
+        } else if let Some(width) = car.as_fixnum() {
+            let string = self.format(cdr, risky, depth + 1);
+            if width < 0 {
+                call!(
+                    intern("truncate-string-to-width"),
+                    string,
+                    LispObject::from_fixnum(-width)
+                )
+            } else {
+                let padding =
+                    width - call!(intern("string-width"), string).as_fixnum_or_error();
+                if padding > 0 {
+                    let spaces = call!(
+                        intern("make-string"),
+                        LispObject::from_fixnum(padding),
+                        LispObject::from_fixnum(EmacsInt::from(b' '))
+                    );
+                    call!(intern("concat"), string, spaces)
+                } else {
+                    string
+                }
+            }
+        } else if car.is_string() || car.is_cons() {
+            let mut string = lisp_string("");
+            for piece in elt.iter_cars_safe() {
+                let piece = self.format(piece, risky, depth + 1);
+                string = call!(intern("concat"), string, piece);
+            }
+            string
+        } else {
+            lisp_string("*invalid*")
+        }
+    }
+}
+
+/// Format a string out of a mode line construct, like `format-mode-line'.
+/// FORMAT is a construct of the kind `mode-line-format' holds.  The
+/// construct is formatted for WINDOW, the selected window by default, and
+/// with BUFFER current, by default the buffer of WINDOW.
+///
+/// The value of each `:eval' form is cached, and the form is evaluated
+/// again only when the buffer, its modification tick, the point of
+/// WINDOW or whether WINDOW is selected have changed, so that formatting
+/// the mode lines of many windows is cheap.  Forms whose value depends on
+/// anything else should be formatted with `format-mode-line'.  The
+/// %-constructs of strings are handled by `format-mode-line', and the
+/// text is padded and truncated by width.
+#[lisp_fn(min = "1")]
+pub fn format_mode_line_cached(
+    format: LispObject,
+    window: LispObject,
+    buffer: LispObject,
+) -> LispObject {
+    let window = window_or_selected_unchecked(window);
+    let buffer = if buffer.is_nil() {
+        window_buffer(window)
+    } else {
+        buffer
+    };
+    let count = specpdl_index();
+    unsafe { record_unwind_current_buffer() };
+    set_buffer(buffer);
+    let context = Context {
+        window,
+        buffer,
+        tick: list!(
+            buffer,
+            call!(intern("buffer-modified-tick")),
+            window_point(window),
+            LispObject::from_bool(window.eq(selected_window()))
+        ),
+    };
+    let string = context.format(format, false, 0);
+    unsafe { unbind_to(count, Qnil) };
+    string
+}
+
+include!(concat!(env!("OUT_DIR"), "/modeline_exports.rs"));
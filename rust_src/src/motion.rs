@@ -0,0 +1,596 @@
+//! Vertical motion by screen lines.
+//!
+//! `vertical-motion-native' is the `vmotion' and `compute_motion' loop
+//! of indent.c, the one `vertical-motion' runs in batch mode: screen
+//! lines are measured in columns of the window's text area, with the
+//! widths of `char-width-table', tabs, control characters, invisible
+//! text, overlay strings and selective display as compute_motion
+//! counts them.  Display properties other than `invisible' take no
+//! space there, images included.
+//!
+//! What the loop does not measure itself is left to `vertical-motion':
+//! text under a display table, composed text, and windows that show
+//! some other buffer than the current one.
+
+use std::collections::HashMap;
+use std::ptr;
+
+use libc::{c_char, c_uchar, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{buf_charpos_to_bytepos, find_before_next_newline, find_newline_no_quit,
+                 globals, indented_beyond_p, invisible_prop, maybe_quit, overlay_strings,
+                 set_point_both, skip_invisible, strwidth, window_display_table, EmacsInt};
+
+use buffers::LispBufferRef;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use threads::ThreadState;
+use windows::{window_or_selected_unchecked, LispWindowRef};
+
+/// The target line and column that compute_motion never reaches,
+/// `1 << (SHRT_WIDTH - 1)' in indent.c.
+const FAR: EmacsInt = 1 << 15;
+
+/// The end of a scan, as `struct position' in indent.h.
+struct Position {
+    bufpos: ptrdiff_t,
+    bytepos: ptrdiff_t,
+    hpos: EmacsInt,
+    vpos: EmacsInt,
+}
+
+/// The scan met text it does not measure.
+struct Unsupported;
+
+/// Same as `BYTES_BY_CHAR_HEAD' in character.h.
+fn bytes_by_char_head(byte: u8) -> ptrdiff_t {
+    if byte & 0x80 == 0 {
+        1
+    } else if byte & 0x20 == 0 {
+        2
+    } else if byte & 0x10 == 0 {
+        3
+    } else if byte & 0x08 == 0 {
+        4
+    } else {
+        5
+    }
+}
+
+/// Return how many columns a tab takes at column HPOS, counting the
+/// columns TAB_OFFSET and HSCROLL that are not on the screen.
+fn tab_columns(
+    hpos: EmacsInt,
+    tab_offset: EmacsInt,
+    hscroll: EmacsInt,
+    tab_width: EmacsInt,
+) -> EmacsInt {
+    let mut tem = (hpos + tab_offset + hscroll - (hscroll > 0) as EmacsInt) % tab_width;
+    if tem < 0 {
+        tem += tab_width;
+    }
+    tab_width - tem
+}
+
+/// The settings of a scan of the current buffer in a window.
+struct Motion {
+    window: LispObject,
+    w: LispWindowRef,
+    buffer: LispBufferRef,
+    multibyte: bool,
+    hscroll: EmacsInt,
+    tab_width: EmacsInt,
+    ctl_arrow: bool,
+    selective: EmacsInt,
+    /// The columns available to text, not counting the continuation
+    /// glyph.
+    width: EmacsInt,
+    continuation_glyph_width: EmacsInt,
+    /// Whether long lines are truncated rather than continued.
+    truncate: bool,
+    /// Whether characters may be composed automatically.
+    auto_compose: bool,
+    /// The widths of the characters met so far, and whether
+    /// `composition-function-table' has rules for them.
+    chars: HashMap<i32, (EmacsInt, bool)>,
+    quit_count: u16,
+}
+
+impl Motion {
+    fn new(window: LispObject, w: LispWindowRef, buffer: LispBufferRef) -> Motion {
+        let selective = LispObject::from(buffer.selective_display);
+        let selective = match selective.as_fixnum() {
+            Some(n) => n,
+            None if selective.is_nil() => 0,
+            None => -1,
+        };
+        let tab_width = match LispObject::from(buffer.tab_width).as_fixnum() {
+            Some(n) if 0 < n && n <= 1000 => n,
+            _ => 8,
+        };
+        // On a window system the continuation glyph is in the fringe.
+        let output = call!(intern("framep"), w.frame());
+        let window_system = !(output.eq(LispObject::constant_t()) || output.eq(intern("pc")));
+        let continuation_glyph_width = if window_system { 0 } else { 1 };
+        let width = call!(intern("window-body-width"), window).as_fixnum_or_error()
+            - continuation_glyph_width;
+
+        let total_width = width + continuation_glyph_width;
+        let partial = LispObject::from(unsafe { globals.f_Vtruncate_partial_width_windows });
+        let frame_cols = call!(intern("frame-text-cols"), w.frame()).as_fixnum_or_error();
+        let truncate_partial = partial.is_not_nil() && total_width < frame_cols
+            && match partial.as_fixnum() {
+                Some(n) => total_width < n,
+                None => true,
+            };
+        let hscroll = w.hscroll as EmacsInt;
+        let multibyte = LispObject::from(buffer.enable_multibyte_characters).is_not_nil();
+
+        Motion {
+            window,
+            w,
+            buffer,
+            multibyte,
+            hscroll,
+            tab_width,
+            ctl_arrow: LispObject::from(buffer.ctl_arrow).is_not_nil(),
+            selective,
+            width,
+            continuation_glyph_width,
+            truncate: hscroll != 0 || truncate_partial
+                || LispObject::from(buffer.truncate_lines).is_not_nil(),
+            auto_compose: multibyte
+                && LispObject::from(unsafe { globals.f_Vauto_composition_mode }).is_not_nil(),
+            chars: HashMap::new(),
+            quit_count: 0,
+        }
+    }
+
+    fn rarely_quit(&mut self) {
+        self.quit_count = self.quit_count.wrapping_add(1);
+        if self.quit_count == 0 {
+            unsafe { maybe_quit() };
+        }
+    }
+
+    fn char_to_byte(&self, pos: ptrdiff_t) -> ptrdiff_t {
+        unsafe { buf_charpos_to_bytepos(self.buffer.as_ptr(), pos) }
+    }
+
+    fn dec_both(&self, pos: &mut ptrdiff_t, pos_byte: &mut ptrdiff_t) {
+        *pos -= 1;
+        *pos_byte -= 1;
+        if self.multibyte {
+            while self.buffer.fetch_byte(*pos_byte) & 0xc0 == 0x80 {
+                *pos_byte -= 1;
+            }
+        }
+    }
+
+    fn inc_both(&self, pos: &mut ptrdiff_t, pos_byte: &mut ptrdiff_t) {
+        *pos_byte += if self.multibyte {
+            bytes_by_char_head(self.buffer.fetch_byte(*pos_byte))
+        } else {
+            1
+        };
+        *pos += 1;
+    }
+
+    fn indented_beyond(&self, pos: ptrdiff_t, pos_byte: ptrdiff_t) -> bool {
+        self.selective > 0 && unsafe { indented_beyond_p(pos, pos_byte, self.selective) }
+    }
+
+    /// Return whether the `invisible' property at POS hides it, as
+    /// `TEXT_PROP_MEANS_INVISIBLE' in intervals.h.
+    fn invisible(&self, pos: ptrdiff_t) -> bool {
+        let prop = call!(
+            intern("get-char-property"),
+            LispObject::from_natnum(pos as EmacsInt),
+            intern("invisible"),
+            self.window
+        );
+        let spec = LispObject::from(self.buffer.invisibility_spec);
+        if spec.eq(LispObject::constant_t()) {
+            prop.is_not_nil()
+        } else {
+            unsafe { invisible_prop(prop.to_raw(), spec.to_raw()) != 0 }
+        }
+    }
+
+    /// Return the start of the line before POS that isn't hidden by
+    /// selective display or an invisible newline.  The newline that
+    /// counts is the one before the line if AT_START, otherwise the
+    /// one after it.
+    fn visible_line_start(
+        &self,
+        pos: ptrdiff_t,
+        pos_byte: ptrdiff_t,
+        at_start: bool,
+    ) -> (ptrdiff_t, ptrdiff_t) {
+        let mut bytepos = 0;
+        let mut prevline = unsafe { find_newline_no_quit(pos, pos_byte, -1, &mut bytepos) };
+        while prevline > self.buffer.begv
+            && (self.indented_beyond(prevline, bytepos)
+                || self.invisible(if at_start { prevline - 1 } else { prevline }))
+        {
+            self.dec_both(&mut prevline, &mut bytepos);
+            prevline = unsafe { find_newline_no_quit(prevline, bytepos, -1, &mut bytepos) };
+        }
+        (prevline, bytepos)
+    }
+
+    /// Return the width of the overlay strings at POS.
+    fn overlay_strings_width(&self, pos: ptrdiff_t) -> EmacsInt {
+        let mut string: *mut c_uchar = ptr::null_mut();
+        let mut w = self.w;
+        let len = unsafe { overlay_strings(pos, w.as_mut(), &mut string) };
+        if self.multibyte && len > 0 {
+            unsafe { strwidth(string as *const c_char, len) as EmacsInt }
+        } else {
+            len as EmacsInt
+        }
+    }
+
+    /// Return the width of character C, or `Unsupported' if it may be
+    /// composed with its neighbours.
+    fn char_width(&mut self, c: i32) -> Result<EmacsInt, Unsupported> {
+        let auto_compose = self.auto_compose;
+        let &mut (width, composable) = self.chars.entry(c).or_insert_with(|| {
+            let code = LispObject::from_natnum(c as EmacsInt);
+            let width = call!(intern("char-width"), code).as_fixnum_or_error();
+            let composable = auto_compose
+                && call!(
+                    intern("aref"),
+                    LispObject::from(unsafe { globals.f_Vcomposition_function_table }),
+                    code
+                ).is_not_nil();
+            (width, composable)
+        });
+        if composable {
+            Err(Unsupported)
+        } else {
+            Ok(width)
+        }
+    }
+
+    /// Scan forward from the character and byte positions FROM, which
+    /// are at the screen position FROM_POS, until TO or the screen
+    /// position TO_POS, as `compute_motion' does for the window's width,
+    /// its hscroll and no tab offset.  Screen positions are (HPOS, VPOS).
+    fn compute_motion(
+        &mut self,
+        from: (ptrdiff_t, ptrdiff_t),
+        from_pos: (EmacsInt, EmacsInt),
+        mut did_motion: bool,
+        to: ptrdiff_t,
+        to_pos: (EmacsInt, EmacsInt),
+    ) -> Result<Position, Unsupported> {
+        let (width, hscroll) = (self.width, self.hscroll);
+        let (from, from_byte) = from;
+        let (mut hpos, mut vpos) = from_pos;
+        let (to_hpos, to_vpos) = to_pos;
+        let (mut pos, mut pos_byte) = (from, from_byte);
+        let (mut prev_pos, mut prev_pos_byte) = (from, from_byte);
+        let mut next_boundary = from;
+        // The column where the last character ended if it was wide,
+        // otherwise 0.
+        let mut wide_column_end_hpos = 0;
+        let (mut prev_hpos, mut prev_vpos) = (0, 0);
+        // The column of the last character of a continued line.
+        let mut contin_hpos = 0;
+        let (mut tab_offset, mut prev_tab_offset) = (0, 0);
+
+        'scan: loop {
+            self.rarely_quit();
+
+            while pos == next_boundary {
+                let pos_here = pos;
+
+                // Don't skip invisible text at the margin.
+                if vpos > to_vpos || (vpos == to_vpos && hpos >= to_hpos) {
+                    if contin_hpos != 0 && prev_hpos == 0 && hpos > to_hpos
+                        && (contin_hpos == width || wide_column_end_hpos > width)
+                    {
+                        pos = prev_pos;
+                        pos_byte = prev_pos_byte;
+                        hpos = prev_hpos;
+                        vpos = prev_vpos;
+                        tab_offset = prev_tab_offset;
+                    }
+                    break;
+                }
+
+                if !did_motion {
+                    hpos += self.overlay_strings_width(pos);
+                }
+                did_motion = false;
+
+                if pos >= to {
+                    break;
+                }
+
+                let newpos =
+                    unsafe { skip_invisible(pos, &mut next_boundary, to, self.window.to_raw()) };
+                if newpos >= to {
+                    pos = to;
+                    pos_byte = self.char_to_byte(pos);
+                    break 'scan;
+                }
+                if newpos != pos_here {
+                    pos = newpos;
+                    pos_byte = self.char_to_byte(pos);
+                }
+
+                self.rarely_quit();
+            }
+
+            // Handle the right margin.
+            if hpos > width {
+                if self.truncate {
+                    // Skip to the newline, unless already past TO.
+                    if pos <= to {
+                        pos = unsafe { find_before_next_newline(pos, to, 1, &mut pos_byte) };
+                        hpos = width;
+                        if pos >= next_boundary {
+                            next_boundary = pos + 1;
+                        }
+                        prev_hpos = width;
+                        prev_vpos = vpos;
+                        prev_tab_offset = tab_offset;
+                    }
+                } else {
+                    prev_tab_offset = tab_offset;
+                    // A wide character that doesn't fit goes to the next
+                    // line whole.
+                    if wide_column_end_hpos > width {
+                        hpos -= prev_hpos;
+                        tab_offset += prev_hpos;
+                    } else {
+                        tab_offset += width;
+                        hpos -= width;
+                    }
+                    vpos += 1;
+                    contin_hpos = prev_hpos;
+                    prev_hpos = 0;
+                    prev_vpos = vpos;
+                }
+            }
+
+            // Stop if past the target position, going back to the
+            // previous one.
+            if pos > to {
+                pos = prev_pos;
+                pos_byte = prev_pos_byte;
+                hpos = prev_hpos;
+                vpos = prev_vpos;
+                tab_offset = prev_tab_offset;
+
+                // The line was broken in the middle of a multi-column
+                // character.
+                if contin_hpos != 0 && prev_hpos == 0 && contin_hpos < width
+                    && wide_column_end_hpos == 0
+                {
+                    hpos = contin_hpos;
+                    vpos -= 1;
+                }
+                break;
+            }
+
+            if vpos > to_vpos || (vpos == to_vpos && hpos >= to_hpos) {
+                if contin_hpos != 0 && prev_hpos == 0 && hpos > to_hpos
+                    && (contin_hpos == width || wide_column_end_hpos > width)
+                {
+                    pos = prev_pos;
+                    pos_byte = prev_pos_byte;
+                    hpos = prev_hpos;
+                    vpos = prev_vpos;
+                    tab_offset = prev_tab_offset;
+                }
+                break;
+            }
+            if pos == self.buffer.zv {
+                break;
+            }
+
+            prev_hpos = hpos;
+            prev_vpos = vpos;
+            prev_pos = pos;
+            prev_pos_byte = pos_byte;
+            wide_column_end_hpos = 0;
+
+            let c = self.buffer.fetch_byte(pos_byte);
+            pos += 1;
+            pos_byte += 1;
+
+            if c >= 0o40 && c < 0o177 {
+                hpos += 1;
+            } else if c == b'\t' {
+                hpos += tab_columns(hpos, tab_offset, hscroll, self.tab_width);
+            } else if c == b'\n' {
+                if self.indented_beyond(pos, pos_byte) {
+                    if pos < to {
+                        // Skip any number of invisible lines at once.
+                        loop {
+                            pos = unsafe { find_before_next_newline(pos, to, 1, &mut pos_byte) };
+                            if pos < to {
+                                self.inc_both(&mut pos, &mut pos_byte);
+                            }
+                            self.rarely_quit();
+                            if !(pos < to && self.indented_beyond(pos, pos_byte)) {
+                                break;
+                            }
+                        }
+                        // Stop before the newline after the hidden text.
+                        self.dec_both(&mut pos, &mut pos_byte);
+                    }
+                } else {
+                    vpos += 1;
+                    hpos = -hscroll;
+                    // Count the truncation glyph in column 0.
+                    if hscroll > 0 {
+                        hpos += self.continuation_glyph_width;
+                    }
+                    tab_offset = 0;
+                }
+                contin_hpos = 0;
+            } else if c == b'\r' && self.selective < 0 {
+                // Everything from a ^M to the end of the line is hidden.
+                if pos < to {
+                    pos = unsafe { find_before_next_newline(pos, to, 1, &mut pos_byte) };
+                }
+                if pos > next_boundary {
+                    next_boundary = pos;
+                }
+            } else if self.multibyte && c & 0xc0 == 0xc0 {
+                pos_byte -= 1;
+                let ch = self.buffer.fetch_multibyte_char(pos_byte);
+                pos_byte += bytes_by_char_head(c);
+                let mb_width = self.char_width(ch)?;
+                if mb_width > 1 {
+                    wide_column_end_hpos = hpos + mb_width;
+                }
+                hpos += mb_width;
+            } else if self.ctl_arrow && c < 0o200 {
+                hpos += 2;
+            } else {
+                hpos += 4;
+            }
+        }
+
+        Ok(Position {
+            bufpos: pos,
+            bytepos: pos_byte,
+            hpos,
+            vpos,
+        })
+    }
+
+    /// Move VTARGET screen lines from FROM, as `vmotion' does.
+    fn vmotion(
+        &mut self,
+        mut from: ptrdiff_t,
+        mut from_byte: ptrdiff_t,
+        vtarget: EmacsInt,
+    ) -> Result<Position, Unsupported> {
+        let begv = self.buffer.begv;
+        let lmargin = if self.hscroll > 0 { 1 - self.hscroll } else { 0 };
+        let mut vpos = 0;
+
+        if vpos >= vtarget {
+            // Go up a line at a time until far enough.
+            let mut first = true;
+            while (vpos > vtarget || first) && from > begv {
+                let (mut prevline, mut bytepos) = (from, from_byte);
+                self.dec_both(&mut prevline, &mut bytepos);
+                let (prevline, bytepos) = self.visible_line_start(prevline, bytepos, true);
+                let pos = self.compute_motion(
+                    (prevline, bytepos),
+                    (lmargin, 0),
+                    false,
+                    from,
+                    (FAR, FAR),
+                )?;
+                vpos -= pos.vpos;
+                first = false;
+                from = prevline;
+                from_byte = bytepos;
+            }
+
+            // Done if exactly there or at the beginning of the buffer;
+            // otherwise find the right line by going down.
+            if vpos >= vtarget {
+                return Ok(Position {
+                    bufpos: from,
+                    bytepos: from_byte,
+                    hpos: lmargin,
+                    vpos,
+                });
+            }
+        }
+
+        // Find the column of FROM from the start of its line.
+        let (hpos, did_motion) = if from > begv && self.buffer.fetch_byte(from_byte - 1) != b'\n' {
+            let (prevline, bytepos) = self.visible_line_start(from, from_byte, false);
+            let pos =
+                self.compute_motion((prevline, bytepos), (lmargin, 0), false, from, (FAR, FAR))?;
+            (pos.hpos, true)
+        } else {
+            (lmargin, false)
+        };
+        let zv = self.buffer.zv;
+        self.compute_motion(
+            (from, from_byte),
+            (hpos, vpos),
+            did_motion,
+            zv,
+            (-FAR, vtarget),
+        )
+    }
+}
+
+/// Move point to the start of the screen line LINES lines down.
+/// If LINES is negative, move up.  Return the number of screen lines
+/// moved over, which is closer to zero than LINES if the beginning or
+/// end of the buffer was reached.
+///
+/// Screen lines are measured as `vertical-motion' measures them in
+/// batch mode: by the columns of the text area of WINDOW, which
+/// defaults to the selected window, without regard to fonts, images
+/// or display properties other than `invisible'.  Where that text
+/// holds compositions or a display table applies, or WINDOW shows
+/// another buffer, this calls `vertical-motion'.
+#[lisp_fn(min = "1")]
+pub fn vertical_motion_native(lines: LispObject, window: LispObject) -> LispObject {
+    let vtarget = lines.as_fixnum_or_error();
+    let window = window_or_selected_unchecked(window);
+    let mut w = window.as_live_window_or_error();
+    let buffer = ThreadState::current_buffer();
+
+    let measured = if w.contents().as_buffer() != Some(buffer)
+        || !unsafe { window_display_table(w.as_mut()) }.is_null()
+        || call!(
+            intern("text-property-not-all"),
+            LispObject::from_natnum(buffer.begv as EmacsInt),
+            LispObject::from_natnum(buffer.zv as EmacsInt),
+            intern("composition"),
+            LispObject::constant_nil()
+        ).is_not_nil()
+    {
+        Err(Unsupported)
+    } else {
+        Motion::new(window, w, buffer).vmotion(buffer.pt, buffer.pt_byte, vtarget)
+    };
+
+    match measured {
+        Ok(pos) => {
+            unsafe { set_point_both(pos.bufpos, pos.bytepos) };
+            LispObject::from_fixnum(pos.vpos)
+        }
+        Err(Unsupported) => call!(intern("vertical-motion"), lines, window),
+    }
+}
+
+#[test]
+fn test_tab_columns() {
+    assert_eq!(tab_columns(0, 0, 0, 8), 8);
+    assert_eq!(tab_columns(3, 0, 0, 8), 5);
+    assert_eq!(tab_columns(8, 0, 0, 8), 8);
+    // A continued line starts its tabs from the continued columns.
+    assert_eq!(tab_columns(2, 79, 0, 8), 7);
+    // Hscrolled text counts the hidden columns but not the truncation
+    // glyph.
+    assert_eq!(tab_columns(-4, 0, 5, 8), 8);
+    assert_eq!(tab_columns(1, 0, 5, 4), 3);
+}
+
+#[test]
+fn test_bytes_by_char_head() {
+    assert_eq!(bytes_by_char_head(b'a'), 1);
+    assert_eq!(bytes_by_char_head("é".as_bytes()[0]), 2);
+    assert_eq!(bytes_by_char_head("漢".as_bytes()[0]), 3);
+    assert_eq!(bytes_by_char_head("😀".as_bytes()[0]), 4);
+    assert_eq!(bytes_by_char_head(0xf8), 5);
+}
+
+include!(concat!(env!("OUT_DIR"), "/motion_exports.rs"));
@@ -0,0 +1,1284 @@
+//! Network interfaces, the endpoints of network processes, datagram
+//! sockets, local socket servers and reachability checks.
+//!
+//! Interfaces are enumerated with getifaddrs, which GNU/Linux, macOS
+//! and the BSDs all provide; Windows keeps the implementation in
+//! w32.c.  Addresses are returned in the internal format described
+//! under ADDRESS in `make-network-process'.
+
+#[cfg(unix)]
+use std::ffi::{CStr, CString};
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::mem;
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use libc;
+use libc::{c_int, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{pget_infd, EmacsInt, Ffuncall, Fmake_vector, Lisp_Object, Qnil};
+#[cfg(unix)]
+use remacs_sys::{record_unwind_protect, unbind_to};
+#[cfg(not(unix))]
+use remacs_sys::{network_interface_info as w32_network_interface_info,
+                 network_interface_list as w32_network_interface_list};
+
+#[cfg(unix)]
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use floatfns::extract_float;
+use lists::{assq, delq, get, plist_get, put};
+use strings::lisp_string;
+use symbols::{fboundp, symbol_name};
+
+/// Return a Lisp vector holding ITEMS.
+fn make_vector(items: &[LispObject]) -> LispObject {
+    let length = LispObject::from_natnum(items.len() as EmacsInt);
+    let vector = LispObject::from(unsafe { Fmake_vector(length.to_raw(), Qnil) });
+    vector
+        .as_vectorlike()
+        .and_then(|v| v.as_vector())
+        .unwrap()
+        .as_mut_slice()
+        .copy_from_slice(items);
+    vector
+}
+
+fn make_list(items: Vec<LispObject>) -> LispObject {
+    items
+        .into_iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, item| LispObject::cons(item, list))
+}
+
+/// Return the internal form of an IPv4 address given as its four
+/// bytes in network order, with PORT.
+fn ipv4_to_lisp(octets: [u8; 4], port: u16) -> LispObject {
+    let mut items: Vec<LispObject> = octets
+        .iter()
+        .map(|&b| LispObject::from_natnum(EmacsInt::from(b)))
+        .collect();
+    items.push(LispObject::from_natnum(EmacsInt::from(port)));
+    make_vector(&items)
+}
+
+/// Return the eight 16-bit words of the IPv6 address OCTETS.
+fn ipv6_words(octets: &[u8; 16]) -> Vec<u16> {
+    octets
+        .chunks(2)
+        .map(|pair| u16::from(pair[0]) << 8 | u16::from(pair[1]))
+        .collect()
+}
+
+/// Return the internal form of an IPv6 address given as its sixteen
+/// bytes in network order, with PORT.
+fn ipv6_to_lisp(octets: [u8; 16], port: u16) -> LispObject {
+    let mut items: Vec<LispObject> = ipv6_words(&octets)
+        .into_iter()
+        .map(|word| LispObject::from_natnum(EmacsInt::from(word)))
+        .collect();
+    items.push(LispObject::from_natnum(EmacsInt::from(port)));
+    make_vector(&items)
+}
+
+/// Convert the socket address SA, LEN bytes long, to its internal form:
+/// a vector for IPv4 and IPv6, a file name for local sockets, and
+/// (FAMILY . DATA) otherwise.  Return nil if SA is null.
+#[cfg(unix)]
+unsafe fn sockaddr_to_lisp(sa: *const libc::sockaddr, len: libc::socklen_t) -> LispObject {
+    if sa.is_null() {
+        return LispObject::constant_nil();
+    }
+    match i32::from((*sa).sa_family) {
+        libc::AF_INET => {
+            let sin = &*(sa as *const libc::sockaddr_in);
+            let octets: [u8; 4] = mem::transmute(sin.sin_addr.s_addr);
+            ipv4_to_lisp(octets, u16::from_be(sin.sin_port))
+        }
+        libc::AF_INET6 => {
+            let sin6 = &*(sa as *const libc::sockaddr_in6);
+            ipv6_to_lisp(sin6.sin6_addr.s6_addr, u16::from_be(sin6.sin6_port))
+        }
+        libc::AF_UNIX => {
+            let sun = &*(sa as *const libc::sockaddr_un);
+            let path = CStr::from_ptr(sun.sun_path.as_ptr());
+            lisp_string(&path.to_string_lossy())
+        }
+        family => {
+            let header = mem::size_of::<libc::sa_family_t>();
+            let items: Vec<LispObject> = (*sa)
+                .sa_data
+                .iter()
+                .take((len as usize).saturating_sub(header))
+                .map(|&b| LispObject::from_natnum(EmacsInt::from(b as u8)))
+                .collect();
+            LispObject::cons(
+                LispObject::from_fixnum(EmacsInt::from(family)),
+                make_vector(&items),
+            )
+        }
+    }
+}
+
+/// The interface flags `network-interface-info' knows by name.
+#[cfg(target_os = "linux")]
+const INTERFACE_FLAGS: &[(libc::c_int, &str)] = &[
+    (libc::IFF_UP, "up"),
+    (libc::IFF_BROADCAST, "broadcast"),
+    (libc::IFF_DEBUG, "debug"),
+    (libc::IFF_LOOPBACK, "loopback"),
+    (libc::IFF_POINTOPOINT, "pointopoint"),
+    (libc::IFF_RUNNING, "running"),
+    (libc::IFF_NOARP, "noarp"),
+    (libc::IFF_PROMISC, "promisc"),
+    (libc::IFF_NOTRAILERS, "notrailers"),
+    (libc::IFF_ALLMULTI, "allmulti"),
+    (libc::IFF_MASTER, "master"),
+    (libc::IFF_SLAVE, "slave"),
+    (libc::IFF_MULTICAST, "multicast"),
+    (libc::IFF_PORTSEL, "portsel"),
+    (libc::IFF_AUTOMEDIA, "automedia"),
+    (libc::IFF_DYNAMIC, "dynamic"),
+];
+
+#[cfg(all(unix, not(target_os = "linux")))]
+const INTERFACE_FLAGS: &[(libc::c_int, &str)] = &[
+    (libc::IFF_UP, "up"),
+    (libc::IFF_BROADCAST, "broadcast"),
+    (libc::IFF_DEBUG, "debug"),
+    (libc::IFF_LOOPBACK, "loopback"),
+    (libc::IFF_POINTOPOINT, "pointopoint"),
+    (libc::IFF_RUNNING, "running"),
+    (libc::IFF_NOARP, "noarp"),
+    (libc::IFF_PROMISC, "promisc"),
+    // Really means smart on macOS; notrailers is obsolete.
+    (libc::IFF_NOTRAILERS, "smart"),
+    (libc::IFF_ALLMULTI, "allmulti"),
+    (libc::IFF_MULTICAST, "multicast"),
+    (libc::IFF_OACTIVE, "oactive"),
+    (libc::IFF_SIMPLEX, "simplex"),
+    (libc::IFF_LINK0, "link0"),
+    (libc::IFF_LINK1, "link1"),
+    (libc::IFF_LINK2, "link2"),
+];
+
+/// Return FLAGS as a list of symbols, with any bits that have no name
+/// given as bit numbers at the front.
+#[cfg(unix)]
+fn interface_flags_to_lisp(flags: u32) -> LispObject {
+    let mut flags = flags;
+    let mut result = LispObject::constant_nil();
+    for &(bit, name) in INTERFACE_FLAGS {
+        if flags & bit as u32 != 0 {
+            result = LispObject::cons(intern(name), result);
+            flags &= !(bit as u32);
+        }
+    }
+    for n in 0..32 {
+        if flags & (1 << n) != 0 {
+            result = LispObject::cons(LispObject::from_natnum(n), result);
+        }
+    }
+    result
+}
+
+/// One entry of the list returned by getifaddrs.
+#[cfg(unix)]
+struct InterfaceAddress {
+    name: String,
+    flags: u32,
+    family: i32,
+    addr: *const libc::sockaddr,
+    netmask: *const libc::sockaddr,
+    broadcast: *const libc::sockaddr,
+}
+
+/// Call F with each address of each interface on the system, in the
+/// order the system returns them.  Do nothing if the system cannot
+/// enumerate its interfaces.
+#[cfg(unix)]
+fn for_each_interface_address<F: FnMut(&InterfaceAddress)>(mut f: F) {
+    let mut ifap: *mut libc::ifaddrs = ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut ifap) } < 0 {
+        return;
+    }
+    let mut it = ifap;
+    while !it.is_null() {
+        let ifa = unsafe { &*it };
+        it = ifa.ifa_next;
+        if ifa.ifa_addr.is_null() {
+            continue;
+        }
+        let broadcast = if ifa.ifa_flags & libc::IFF_BROADCAST as u32 != 0 {
+            broadcast_address(ifa)
+        } else {
+            ptr::null()
+        };
+        f(&InterfaceAddress {
+            name: unsafe { CStr::from_ptr(ifa.ifa_name) }
+                .to_string_lossy()
+                .into_owned(),
+            flags: ifa.ifa_flags as u32,
+            family: i32::from(unsafe { (*ifa.ifa_addr).sa_family }),
+            addr: ifa.ifa_addr,
+            netmask: ifa.ifa_netmask,
+            broadcast,
+        });
+    }
+    unsafe { libc::freeifaddrs(ifap) };
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn broadcast_address(ifa: &libc::ifaddrs) -> *const libc::sockaddr {
+    ifa.ifa_ifu
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn broadcast_address(ifa: &libc::ifaddrs) -> *const libc::sockaddr {
+    ifa.ifa_dstaddr
+}
+
+/// Return the length of a socket address of FAMILY.
+#[cfg(unix)]
+fn sockaddr_len(family: i32) -> libc::socklen_t {
+    let len = match family {
+        libc::AF_INET => mem::size_of::<libc::sockaddr_in>(),
+        libc::AF_INET6 => mem::size_of::<libc::sockaddr_in6>(),
+        _ => mem::size_of::<libc::sockaddr>(),
+    };
+    len as libc::socklen_t
+}
+
+/// Return the hardware address of an interface as (FAMILY . VECTOR),
+/// given the link layer entry for it, or None if it has no six byte
+/// address.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn hardware_address(entry: &InterfaceAddress) -> Option<LispObject> {
+    if entry.family != libc::AF_PACKET {
+        return None;
+    }
+    let sll = unsafe { &*(entry.addr as *const libc::sockaddr_ll) };
+    if sll.sll_halen != 6 {
+        return None;
+    }
+    let items: Vec<LispObject> = sll.sll_addr[..6]
+        .iter()
+        .map(|&b| LispObject::from_natnum(EmacsInt::from(b)))
+        .collect();
+    Some(LispObject::cons(
+        LispObject::from_natnum(EmacsInt::from(sll.sll_hatype)),
+        make_vector(&items),
+    ))
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn hardware_address(entry: &InterfaceAddress) -> Option<LispObject> {
+    if entry.family != libc::AF_LINK {
+        return None;
+    }
+    let sdl = unsafe { &*(entry.addr as *const libc::sockaddr_dl) };
+    if sdl.sdl_alen != 6 {
+        return None;
+    }
+    // LLADDR: the address follows the interface name in sdl_data.
+    let start = sdl.sdl_nlen as usize;
+    let data = unsafe {
+        ::std::slice::from_raw_parts(sdl.sdl_data.as_ptr().offset(start as isize) as *const u8, 6)
+    };
+    let items: Vec<LispObject> = data.iter()
+        .map(|&b| LispObject::from_natnum(EmacsInt::from(b)))
+        .collect();
+    Some(LispObject::cons(
+        LispObject::from_natnum(EmacsInt::from(libc::AF_LINK)),
+        make_vector(&items),
+    ))
+}
+
+/// Return the address family FAMILY names, or signal an error.
+#[cfg(unix)]
+fn family_filter(family: LispObject) -> Option<i32> {
+    if family.is_nil() {
+        None
+    } else if family.eq(intern("ipv4")) {
+        Some(libc::AF_INET)
+    } else if family.eq(intern("ipv6")) {
+        Some(libc::AF_INET6)
+    } else {
+        error!("Unsupported address family");
+    }
+}
+
+/// Return an alist of all network interfaces and their network address.
+/// Each element is cons of the form (IFNAME . IP) where IFNAME is a
+/// string containing the interface name, and IP is the network address
+/// in internal format; see the description of ADDRESS in
+/// `make-network-process'.  The interface name is not guaranteed to be
+/// unique.
+///
+/// Optional parameter FULL non-nil means return all IP address info for
+/// each interface.  Each element is then a list of the form
+///     (IFNAME IP BCAST MASK)
+/// where IFNAME is the interface name, IP the IP address,
+/// BCAST the broadcast address, and MASK the network mask.
+///
+/// Optional parameter FAMILY controls the type of addresses to return.
+/// The value should be either `ipv4' or `ipv6', or nil to return the
+/// addresses of both families.
+///
+/// If the information is not available, return nil.
+#[lisp_fn(min = "0")]
+pub fn network_interface_list(full: LispObject, family: LispObject) -> LispObject {
+    network_interface_list_1(full, family)
+}
+
+#[cfg(unix)]
+fn network_interface_list_1(full: LispObject, family: LispObject) -> LispObject {
+    let wanted = family_filter(family);
+    let mut result = Vec::new();
+    for_each_interface_address(|entry| {
+        if entry.family != libc::AF_INET && entry.family != libc::AF_INET6 {
+            return;
+        }
+        if wanted.map_or(false, |f| f != entry.family) {
+            return;
+        }
+        let len = sockaddr_len(entry.family);
+        let name = lisp_string(&entry.name);
+        let addr = unsafe { sockaddr_to_lisp(entry.addr, len) };
+        if full.is_nil() {
+            result.push(LispObject::cons(name, addr));
+        } else {
+            let broadcast = unsafe { sockaddr_to_lisp(entry.broadcast, len) };
+            let netmask = unsafe { sockaddr_to_lisp(entry.netmask, len) };
+            result.push(list!(name, addr, broadcast, netmask));
+        }
+    });
+    make_list(result)
+}
+
+#[cfg(not(unix))]
+fn network_interface_list_1(_full: LispObject, _family: LispObject) -> LispObject {
+    LispObject::from(unsafe { w32_network_interface_list() })
+}
+
+/// Return information about network interface named IFNAME.
+/// The return value is a list (ADDR BCAST NETMASK HWADDR FLAGS),
+/// where ADDR is the layer 3 address, BCAST is the layer 3 broadcast address,
+/// NETMASK is the layer 3 network mask, HWADDR is the layer 2 address, and
+/// FLAGS is the current flags of the interface.
+///
+/// Data that is unavailable is returned as nil.
+#[lisp_fn]
+pub fn network_interface_info(ifname: LispObject) -> LispObject {
+    network_interface_info_1(ifname)
+}
+
+#[cfg(unix)]
+fn network_interface_info_1(ifname: LispObject) -> LispObject {
+    let name = String::from_utf8_lossy(ifname.as_string_or_error().as_slice()).into_owned();
+    let nil = LispObject::constant_nil();
+    let (mut addr, mut broadcast, mut netmask, mut hwaddr, mut flags) = (nil, nil, nil, nil, nil);
+    let mut any = false;
+
+    for_each_interface_address(|entry| {
+        if entry.name != name {
+            return;
+        }
+        if !any {
+            flags = interface_flags_to_lisp(entry.flags);
+            any = true;
+        }
+        if entry.family == libc::AF_INET && addr.is_nil() {
+            let len = sockaddr_len(entry.family);
+            unsafe {
+                addr = sockaddr_to_lisp(entry.addr, len);
+                broadcast = sockaddr_to_lisp(entry.broadcast, len);
+                netmask = sockaddr_to_lisp(entry.netmask, len);
+            }
+        } else if hwaddr.is_nil() {
+            if let Some(hw) = hardware_address(entry) {
+                hwaddr = hw;
+            }
+        }
+    });
+
+    if any {
+        list!(addr, broadcast, netmask, hwaddr, flags)
+    } else {
+        nil
+    }
+}
+
+#[cfg(not(unix))]
+fn network_interface_info_1(ifname: LispObject) -> LispObject {
+    ifname.as_string_or_error();
+    LispObject::from(unsafe { w32_network_interface_info(ifname.to_raw()) })
+}
+
+/// Return the local and remote address of the socket FD, as given by
+/// getsockname and getpeername.
+#[cfg(unix)]
+fn socket_endpoints(fd: libc::c_int) -> (LispObject, LispObject, i32) {
+    let endpoint = |peer: bool| unsafe {
+        let mut storage: libc::sockaddr_storage = mem::zeroed();
+        let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let sa = &mut storage as *mut _ as *mut libc::sockaddr;
+        let result = if peer {
+            libc::getpeername(fd, sa, &mut len)
+        } else {
+            libc::getsockname(fd, sa, &mut len)
+        };
+        if result < 0 {
+            (LispObject::constant_nil(), -1)
+        } else {
+            (sockaddr_to_lisp(sa, len), i32::from(storage.ss_family))
+        }
+    };
+    let (local, family) = endpoint(false);
+    let (remote, _) = endpoint(true);
+    (local, remote, family)
+}
+
+/// Return information about the connection of the network process PROCESS.
+/// The value is a property list with the following properties:
+///
+/// :family   -- `ipv4', `ipv6' or `local', or nil if unknown.
+/// :local    -- The local address of the socket.
+/// :remote   -- The address of the peer, or nil if the socket is not
+///              connected, as for a server or an unconnected datagram
+///              socket.
+/// :type     -- `stream', `datagram' or `seqpacket'.
+/// :server   -- t if PROCESS is listening for connections.
+/// :tls      -- The result of `gnutls-peer-status' if the connection
+///              is encrypted with TLS, nil otherwise.
+///
+/// Addresses are in the internal format; see `format-network-address'.
+/// Return nil if PROCESS is not a network process or is no longer
+/// connected.
+#[lisp_fn]
+pub fn process_connection_info(process: LispObject) -> LispObject {
+    let p = process.as_process_or_error();
+    if !LispObject::from(p.process_type).eq(intern("network")) {
+        return LispObject::constant_nil();
+    }
+    let contact = LispObject::from(p.childp);
+    let plist_get = |prop: &str| call!(intern("plist-get"), contact, intern(prop));
+
+    let (local, remote, family) = connection_endpoints(process);
+    if local.is_nil() && remote.is_nil() {
+        return LispObject::constant_nil();
+    }
+    let tls = if fboundp(intern("gnutls-peer-status")).is_not_nil() {
+        call!(intern("gnutls-peer-status"), process)
+    } else {
+        LispObject::constant_nil()
+    };
+    let socket_type = match plist_get(":type") {
+        t if t.is_nil() => intern("stream"),
+        t => t,
+    };
+
+    list!(
+        intern(":family"),
+        family,
+        intern(":local"),
+        local,
+        intern(":remote"),
+        remote,
+        intern(":type"),
+        socket_type,
+        intern(":server"),
+        LispObject::from_bool(plist_get(":server").is_not_nil()),
+        intern(":tls"),
+        tls
+    )
+}
+
+#[cfg(unix)]
+fn connection_endpoints(process: LispObject) -> (LispObject, LispObject, LispObject) {
+    let p = process.as_process_or_error();
+    let fd = unsafe { pget_infd(p.as_ptr()) };
+    if fd < 0 {
+        let nil = LispObject::constant_nil();
+        return (nil, nil, nil);
+    }
+    let (local, remote, family) = socket_endpoints(fd);
+    let family = match family {
+        libc::AF_INET => intern("ipv4"),
+        libc::AF_INET6 => intern("ipv6"),
+        libc::AF_UNIX => intern("local"),
+        _ => LispObject::constant_nil(),
+    };
+    (local, remote, family)
+}
+
+/// Windows sockets are hidden behind Emacs's own file descriptors, so
+/// fall back on what `process-contact' recorded.
+#[cfg(not(unix))]
+fn connection_endpoints(process: LispObject) -> (LispObject, LispObject, LispObject) {
+    let contact = call!(intern("process-contact"), process, LispObject::constant_t());
+    let plist_get = |prop: &str| call!(intern("plist-get"), contact, intern(prop));
+    (plist_get(":local"), plist_get(":remote"), plist_get(":family"))
+}
+
+/// Return the components of ADDRESS, a vector in internal format, or
+/// None if it isn't one.
+#[cfg(unix)]
+fn address_components(address: LispObject) -> Option<Vec<u16>> {
+    let vector = match address.as_vectorlike().and_then(|v| v.as_vector()) {
+        Some(vector) => vector,
+        None => return None,
+    };
+    let mut components = Vec::new();
+    for item in vector.as_slice() {
+        match item.as_fixnum() {
+            Some(n) if n >= 0 && n <= 0xffff => components.push(n as u16),
+            _ => return None,
+        }
+    }
+    Some(components)
+}
+
+/// Convert ADDRESS, an IPv4 or IPv6 address in internal format, to a
+/// socket address and its length.
+#[cfg(unix)]
+fn lisp_to_sockaddr(address: LispObject) -> Option<(libc::sockaddr_storage, libc::socklen_t)> {
+    let components = match address_components(address) {
+        Some(components) => components,
+        None => return None,
+    };
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match components.len() {
+        5 => {
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            let mut octets = [0u8; 4];
+            for (octet, &c) in octets.iter_mut().zip(&components) {
+                *octet = c as u8;
+            }
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = components[4].to_be();
+            sin.sin_addr.s_addr = unsafe { mem::transmute(octets) };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        9 => {
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            for (i, &word) in components[..8].iter().enumerate() {
+                sin6.sin6_addr.s6_addr[2 * i] = (word >> 8) as u8;
+                sin6.sin6_addr.s6_addr[2 * i + 1] = word as u8;
+            }
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = components[8].to_be();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+        _ => return None,
+    };
+    Some((storage, len as libc::socklen_t))
+}
+
+/// Return true if PROCESS is a datagram network process.
+fn is_datagram_process(process: LispObject) -> bool {
+    process.as_process().map_or(false, |p| {
+        let contact = LispObject::from(p.childp);
+        contact.is_cons() && plist_get(contact, intern(":type")).eq(intern("datagram"))
+    })
+}
+
+/// Return the arguments to call the filter FILTER of PROC with, TEXT
+/// being the output: (FILTER PROC TEXT), with the source address of
+/// the datagram appended if PROC was made with :datagram-source.
+#[no_mangle]
+pub extern "C" fn process_filter_call_args(
+    filter: LispObject,
+    proc: LispObject,
+    text: LispObject,
+) -> LispObject {
+    if is_datagram_process(proc) {
+        let contact = LispObject::from(proc.as_process_or_error().childp);
+        if plist_get(contact, intern(":datagram-source")).is_not_nil() {
+            let source = call!(intern("process-datagram-address"), proc);
+            return list!(filter, proc, text, source);
+        }
+    }
+    list!(filter, proc, text)
+}
+
+/// Send STRING to ADDRESS as a single datagram from PROCESS.
+/// PROCESS must be a datagram network process, and ADDRESS an IPv4 or
+/// IPv6 address in the format described under ADDRESS in
+/// `make-network-process'.  Unlike `set-process-datagram-address'
+/// followed by `process-send-string', this leaves the default
+/// destination of PROCESS unchanged, so a single socket can talk to
+/// many peers, for instance to answer mDNS or SSDP queries.
+///
+/// The bytes of STRING are sent as they are; use `encode-coding-string'
+/// first if it may contain non-ASCII characters.
+#[lisp_fn]
+pub fn process_send_datagram(
+    process: LispObject,
+    string: LispObject,
+    address: LispObject,
+) -> LispObject {
+    if !is_datagram_process(process) {
+        error!("Process is not a datagram process");
+    }
+    let s = string.as_string_or_error();
+    send_datagram(process, s.as_slice(), address);
+    LispObject::constant_nil()
+}
+
+#[cfg(unix)]
+fn send_datagram(process: LispObject, bytes: &[u8], address: LispObject) {
+    let (storage, len) = match lisp_to_sockaddr(address) {
+        Some(sockaddr) => sockaddr,
+        None => {
+            error!("Invalid datagram address");
+        }
+    };
+    let fd = unsafe { pget_infd(process.as_process_or_error().as_ptr()) };
+    if fd < 0 {
+        error!("Process is not active");
+    }
+    let sent = unsafe {
+        libc::sendto(
+            fd,
+            bytes.as_ptr() as *const libc::c_void,
+            bytes.len(),
+            0,
+            &storage as *const _ as *const libc::sockaddr,
+            len,
+        )
+    };
+    if sent < 0 {
+        xsignal!(
+            intern("file-error").to_raw(),
+            lisp_string("Sending datagram"),
+            lisp_string(&io::Error::last_os_error().to_string()),
+            address
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn send_datagram(_process: LispObject, _bytes: &[u8], _address: LispObject) {
+    error!("Sending datagrams to an address is not supported on this system");
+}
+
+#[cfg(unix)]
+unsafe fn set_socket_option<T>(fd: c_int, level: c_int, name: c_int, value: &T) -> c_int {
+    libc::setsockopt(
+        fd,
+        level,
+        name,
+        value as *const T as *const libc::c_void,
+        mem::size_of::<T>() as libc::socklen_t,
+    )
+}
+
+/// Return the address family of the socket FD.
+#[cfg(unix)]
+fn socket_family(fd: c_int) -> c_int {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let sa = &mut storage as *mut _ as *mut libc::sockaddr;
+    if unsafe { libc::getsockname(fd, sa, &mut len) } < 0 {
+        return libc::AF_UNSPEC;
+    }
+    c_int::from(storage.ss_family)
+}
+
+/// Return the IPv4 address of the interface named NAME.
+#[cfg(unix)]
+fn interface_ipv4_address(name: &str) -> Option<libc::in_addr> {
+    let mut found = None;
+    for_each_interface_address(|entry| {
+        if found.is_none() && entry.name == name && entry.family == libc::AF_INET {
+            found = Some(unsafe { (*(entry.addr as *const libc::sockaddr_in)).sin_addr });
+        }
+    });
+    found
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const IPV6_JOIN_GROUP: c_int = libc::IPV6_ADD_MEMBERSHIP;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const IPV6_LEAVE_GROUP: c_int = libc::IPV6_DROP_MEMBERSHIP;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const IPV6_JOIN_GROUP: c_int = libc::IPV6_JOIN_GROUP;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const IPV6_LEAVE_GROUP: c_int = libc::IPV6_LEAVE_GROUP;
+
+/// Join or leave the multicast group given by VALUE on the socket FD of
+/// FAMILY.  VALUE is GROUP or (GROUP . INTERFACE).
+#[cfg(unix)]
+fn set_multicast_membership(fd: c_int, family: c_int, value: LispObject, join: bool) -> c_int {
+    let (group, interface) = match value.as_cons() {
+        Some(cons) => (cons.car(), cons.cdr()),
+        None => (value, LispObject::constant_nil()),
+    };
+    let group = match lisp_to_sockaddr(group) {
+        Some((storage, _)) if c_int::from(storage.ss_family) == family => storage,
+        _ => return -2,
+    };
+
+    if family == libc::AF_INET {
+        let interface = if interface.is_nil() {
+            libc::in_addr { s_addr: libc::INADDR_ANY }
+        } else if let Some(name) = interface.as_string() {
+            match interface_ipv4_address(&String::from_utf8_lossy(name.as_slice())) {
+                Some(addr) => addr,
+                None => return -2,
+            }
+        } else {
+            match lisp_to_sockaddr(interface) {
+                Some((storage, _)) if c_int::from(storage.ss_family) == libc::AF_INET => unsafe {
+                    (*(&storage as *const _ as *const libc::sockaddr_in)).sin_addr
+                },
+                _ => return -2,
+            }
+        };
+        let mreq = libc::ip_mreq {
+            imr_multiaddr: unsafe { (*(&group as *const _ as *const libc::sockaddr_in)).sin_addr },
+            imr_interface: interface,
+        };
+        let name = if join {
+            libc::IP_ADD_MEMBERSHIP
+        } else {
+            libc::IP_DROP_MEMBERSHIP
+        };
+        unsafe { set_socket_option(fd, libc::IPPROTO_IP, name, &mreq) }
+    } else {
+        let index = if interface.is_nil() {
+            0
+        } else if let Some(name) = interface.as_string() {
+            let name = match CString::new(name.as_slice()) {
+                Ok(name) => name,
+                Err(_) => return -2,
+            };
+            match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+                0 => return -2,
+                index => index,
+            }
+        } else {
+            return -2;
+        };
+        let mreq = libc::ipv6_mreq {
+            ipv6mr_multiaddr: unsafe {
+                (*(&group as *const _ as *const libc::sockaddr_in6)).sin6_addr
+            },
+            ipv6mr_interface: index,
+        };
+        let name = if join {
+            IPV6_JOIN_GROUP
+        } else {
+            IPV6_LEAVE_GROUP
+        };
+        unsafe { set_socket_option(fd, libc::IPPROTO_IPV6, name, &mreq) }
+    }
+}
+
+/// Set the multicast option OPT to VAL on the socket S, choosing the
+/// IPv4 or IPv6 variant from the family of S.  Return the result of
+/// setsockopt, with errno set on failure.  Signal an error if VAL is
+/// not valid for OPT.
+#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn set_multicast_option(s: c_int, opt: LispObject, val: LispObject) -> c_int {
+    let family = socket_family(s);
+    if family != libc::AF_INET && family != libc::AF_INET6 {
+        error!("Multicast options need an IPv4 or IPv6 socket");
+    }
+    let ipv4 = family == libc::AF_INET;
+
+    let result = if opt.eq(intern(":multicast-join")) {
+        set_multicast_membership(s, family, val, true)
+    } else if opt.eq(intern(":multicast-leave")) {
+        set_multicast_membership(s, family, val, false)
+    } else if opt.eq(intern(":multicast-loop")) {
+        let on = val.is_not_nil();
+        unsafe {
+            if ipv4 {
+                set_socket_option(s, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP, &(on as u8))
+            } else {
+                let on = on as c_int;
+                set_socket_option(s, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_LOOP, &on)
+            }
+        }
+    } else if opt.eq(intern(":multicast-ttl")) {
+        match val.as_fixnum() {
+            Some(ttl) if ttl >= 0 && ttl <= 255 => unsafe {
+                if ipv4 {
+                    set_socket_option(s, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, &(ttl as u8))
+                } else {
+                    let hops = ttl as c_int;
+                    set_socket_option(s, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_HOPS, &hops)
+                }
+            },
+            _ => -2,
+        }
+    } else {
+        -2
+    };
+
+    if result == -2 {
+        error!(
+            "Bad option value for {}",
+            String::from_utf8_lossy(symbol_name(opt).as_string_or_error().as_slice())
+        );
+    }
+    result
+}
+
+#[cfg(not(unix))]
+#[no_mangle]
+pub extern "C" fn set_multicast_option(_s: c_int, _opt: LispObject, _val: LispObject) -> c_int {
+    error!("Multicast options are not supported on this system");
+}
+
+/// The credentials of the peer of a local socket.
+#[cfg(unix)]
+struct PeerCredentials {
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    pid: Option<libc::pid_t>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(fd: c_int) -> Option<PeerCredentials> {
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result < 0 {
+        return None;
+    }
+    Some(PeerCredentials {
+        uid: cred.uid,
+        gid: cred.gid,
+        pid: Some(cred.pid),
+    })
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn peer_credentials(fd: c_int) -> Option<PeerCredentials> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    if unsafe { libc::getpeereid(fd, &mut uid, &mut gid) } < 0 {
+        return None;
+    }
+    Some(PeerCredentials {
+        uid,
+        gid,
+        pid: peer_pid(fd),
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn peer_pid(fd: c_int) -> Option<libc::pid_t> {
+    // SOL_LOCAL and LOCAL_PEERPID from <sys/un.h>.
+    const SOL_LOCAL: c_int = 0;
+    const LOCAL_PEERPID: c_int = 2;
+    let mut pid: libc::pid_t = 0;
+    let mut len = mem::size_of::<libc::pid_t>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            SOL_LOCAL,
+            LOCAL_PEERPID,
+            &mut pid as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result < 0 {
+        None
+    } else {
+        Some(pid)
+    }
+}
+
+/// The BSDs don't tell the pid of the peer.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android", target_os = "macos"))))]
+fn peer_pid(_fd: c_int) -> Option<libc::pid_t> {
+    None
+}
+
+/// Return the credentials of the process at the other end of the local
+/// socket connection PROCESS, as a property list of the form
+///     (:uid UID :gid GID :pid PID)
+/// PID is nil on systems that don't report it.
+///
+/// These are the credentials the peer had when it connected, as
+/// recorded by the kernel, so they can be trusted to decide whether to
+/// serve a client of a local server; see `make-local-server'.
+///
+/// Return nil if PROCESS is not connected through a local socket.
+#[lisp_fn]
+pub fn process_peer_credentials(process: LispObject) -> LispObject {
+    let p = process.as_process_or_error();
+    if !LispObject::from(p.process_type).eq(intern("network")) {
+        return LispObject::constant_nil();
+    }
+    process_peer_credentials_1(unsafe { pget_infd(p.as_ptr()) })
+}
+
+#[cfg(unix)]
+fn process_peer_credentials_1(fd: c_int) -> LispObject {
+    if fd < 0 || socket_family(fd) != libc::AF_UNIX {
+        return LispObject::constant_nil();
+    }
+    match peer_credentials(fd) {
+        Some(cred) => list!(
+            intern(":uid"),
+            LispObject::from_natnum(EmacsInt::from(cred.uid)),
+            intern(":gid"),
+            LispObject::from_natnum(EmacsInt::from(cred.gid)),
+            intern(":pid"),
+            LispObject::from(cred.pid.map(|pid| LispObject::from_fixnum(EmacsInt::from(pid))))
+        ),
+        None => LispObject::constant_nil(),
+    }
+}
+
+#[cfg(not(unix))]
+fn process_peer_credentials_1(_fd: c_int) -> LispObject {
+    LispObject::constant_nil()
+}
+
+#[cfg(unix)]
+unsafe extern "C" fn restore_umask(mask: Lisp_Object) {
+    libc::umask(LispObject::from(mask).as_fixnum_or_error() as libc::mode_t);
+}
+
+/// Start a server listening on the local (Unix domain) socket FILE.
+/// NAME is the name of the server process.  The remaining ARGS are
+/// keyword arguments passed on to `make-network-process', such as
+/// :filter, :sentinel, :log or :coding.  Return the server process.
+///
+/// A socket file left behind by a server that is no longer running is
+/// removed first.  If another server is listening on FILE, or FILE is
+/// not a socket, signal a `file-already-exists' error.  The socket is
+/// accessible only to the current user.
+///
+/// Each connection gets a process of its own, which can be passed to
+/// `process-peer-credentials' to find out who connected.
+/// usage: (make-local-server NAME FILE &rest ARGS)
+#[lisp_fn(min = "2")]
+pub fn make_local_server(args: &mut [LispObject]) -> LispObject {
+    if args.len() % 2 != 0 {
+        error!("Wrong number of arguments");
+    }
+    let name = args[0];
+    args[1].as_string_or_error();
+    let file = call!(intern("expand-file-name"), args[1]);
+    remove_stale_socket(file);
+
+    let mut contact = vec![
+        intern("make-network-process"),
+        intern(":name"),
+        name,
+        intern(":family"),
+        intern("local"),
+        intern(":service"),
+        file,
+        intern(":server"),
+        LispObject::constant_t(),
+    ];
+    contact.extend_from_slice(&args[2..]);
+    make_private_server(&mut contact)
+}
+
+#[cfg(unix)]
+fn remove_stale_socket(file: LispObject) {
+    let path = String::from_utf8_lossy(file.as_string_or_error().as_slice()).into_owned();
+    let metadata = match fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+    let stale = metadata.file_type().is_socket() && match UnixStream::connect(&path) {
+        Ok(_) => false,
+        Err(err) => err.kind() == io::ErrorKind::ConnectionRefused,
+    };
+    if !stale {
+        xsignal!(
+            intern("file-already-exists").to_raw(),
+            lisp_string("Socket is in use"),
+            file
+        );
+    }
+    if let Err(err) = fs::remove_file(&path) {
+        xsignal!(
+            intern("file-error").to_raw(),
+            lisp_string("Removing old socket"),
+            lisp_string(&err.to_string()),
+            file
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn remove_stale_socket(_file: LispObject) {}
+
+/// Call `make-network-process' with ARGS, the function included, so
+/// that the socket is created readable and writable only by its owner.
+#[cfg(unix)]
+fn make_private_server(args: &mut [LispObject]) -> LispObject {
+    let count = specpdl_index();
+    let old_mask = unsafe { libc::umask(0o077) };
+    unsafe {
+        record_unwind_protect(
+            restore_umask,
+            LispObject::from_natnum(EmacsInt::from(old_mask)).to_raw(),
+        )
+    };
+    let server = call_with_args(args);
+    let server = LispObject::from(unsafe { unbind_to(count, server.to_raw()) });
+
+    // Some systems ignore the umask for sockets.
+    let file = call!(intern("process-contact"), server, intern(":service"));
+    if let Some(path) = file.as_string() {
+        let path = String::from_utf8_lossy(path.as_slice()).into_owned();
+        let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o600));
+    }
+    server
+}
+
+#[cfg(not(unix))]
+fn make_private_server(args: &mut [LispObject]) -> LispObject {
+    call_with_args(args)
+}
+
+fn call_with_args(args: &mut [LispObject]) -> LispObject {
+    let mut raw: Vec<Lisp_Object> = args.iter().map(|arg| arg.to_raw()).collect();
+    LispObject::from(unsafe { Ffuncall(raw.len() as ptrdiff_t, raw.as_mut_ptr()) })
+}
+
+/// Seconds to wait for a connection when no timeout is given.
+const DEFAULT_REACHABLE_TIMEOUT: f64 = 5.0;
+
+type ProbeResult = Result<(), String>;
+
+lazy_static! {
+    /// Pending `network-reachable-async' checks: the identifier, the
+    /// deadline and the channel the connecting thread reports on.
+    static ref PROBES: Mutex<Vec<(EmacsInt, Instant, Receiver<ProbeResult>)>> =
+        Mutex::new(Vec::new());
+    static ref NEXT_PROBE: Mutex<EmacsInt> = Mutex::new(0);
+}
+
+/// Try each address of HOST in turn until one accepts a connection.
+fn probe(host: &str, port: u16, timeout: Duration) -> ProbeResult {
+    let deadline = Instant::now() + timeout;
+    let addresses = (host, port).to_socket_addrs().map_err(|e| e.to_string())?;
+    let mut error = format!("No addresses found for {}", host);
+    for address in addresses {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err("Connection timed out".to_string());
+        }
+        match TcpStream::connect_timeout(&address, deadline - now) {
+            Ok(_) => return Ok(()),
+            Err(e) => error = e.to_string(),
+        }
+    }
+    Err(error)
+}
+
+/// Start checking HOST and PORT on a separate thread, so that neither
+/// name resolution nor the connection blocks the command loop.
+/// Return the deadline and the channel the result arrives on.
+fn start_probe(
+    host: LispObject,
+    port: LispObject,
+    timeout: LispObject,
+) -> (Instant, Receiver<ProbeResult>) {
+    let host = String::from_utf8_lossy(host.as_string_or_error().as_slice()).into_owned();
+    let number = port.as_natnum_or_error();
+    if number > 65535 {
+        args_out_of_range!(port, LispObject::from_natnum(65535));
+    }
+    let seconds = if timeout.is_nil() {
+        DEFAULT_REACHABLE_TIMEOUT
+    } else {
+        extract_float(timeout.to_raw())
+    };
+    if seconds.is_nan() || seconds <= 0.0 {
+        args_out_of_range!(timeout, LispObject::from_natnum(0));
+    }
+    let timeout = Duration::from_millis((seconds * 1000.0) as u64);
+
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        // The receiver is gone if the caller gave up waiting.
+        let _ = sender.send(probe(&host, number as u16, timeout));
+    });
+    (Instant::now() + timeout, receiver)
+}
+
+/// Return the result on RECEIVER, if it has arrived or DEADLINE has
+/// passed.
+fn probe_result(deadline: Instant, receiver: &Receiver<ProbeResult>) -> Option<ProbeResult> {
+    match receiver.try_recv() {
+        Ok(result) => Some(result),
+        Err(TryRecvError::Disconnected) => Some(Err("Connection check failed".to_string())),
+        Err(TryRecvError::Empty) => {
+            if Instant::now() >= deadline {
+                Some(Err("Connection timed out".to_string()))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Return t if a TCP connection to HOST on PORT can be established.
+/// TIMEOUT is the number of seconds to wait, 5 by default.  The
+/// connection is made in the background and closed again at once;
+/// while waiting, process output and timers are still handled and
+/// \\[keyboard-quit] works.  Return nil if HOST cannot be resolved,
+/// the connection is refused or TIMEOUT expires.
+///
+/// To check without waiting at all, use `network-reachable-async'.
+#[lisp_fn(min = "2")]
+pub fn network_reachable_p(host: LispObject, port: LispObject, timeout: LispObject) -> LispObject {
+    let (deadline, receiver) = start_probe(host, port, timeout);
+    loop {
+        if let Some(result) = probe_result(deadline, &receiver) {
+            return LispObject::from_bool(result.is_ok());
+        }
+        call!(
+            intern("accept-process-output"),
+            LispObject::constant_nil(),
+            LispObject::from_float(0.02)
+        );
+    }
+}
+
+/// Check in the background whether HOST accepts TCP connections on PORT.
+/// Return immediately; CALLBACK is called from a timer once the check
+/// is done.  It receives t if the connection succeeded, or nil and a
+/// string describing the failure.  TIMEOUT is the number of seconds to
+/// wait, 5 by default.
+///
+/// Return an integer identifying the check.
+#[lisp_fn(min = "3")]
+pub fn network_reachable_async(
+    host: LispObject,
+    port: LispObject,
+    callback: LispObject,
+    timeout: LispObject,
+) -> LispObject {
+    let (deadline, receiver) = start_probe(host, port, timeout);
+    let id = {
+        let mut next = NEXT_PROBE.lock().unwrap();
+        *next += 1;
+        *next
+    };
+    PROBES.lock().unwrap().push((id, deadline, receiver));
+
+    let key = LispObject::from_fixnum(id);
+    let symbol = intern("network-reachable-async");
+    let callbacks = get(symbol, intern("network--callbacks"));
+    put(
+        symbol,
+        intern("network--callbacks"),
+        LispObject::cons(LispObject::cons(key, callback), callbacks),
+    );
+    if get(symbol, intern("network--timer")).is_nil() {
+        let timer = call!(
+            intern("run-with-timer"),
+            LispObject::from_float(0.05),
+            LispObject::from_float(0.05),
+            intern("network--reachable-poll")
+        );
+        put(symbol, intern("network--timer"), timer);
+    }
+    key
+}
+
+/// Remove and return the first pending check that has finished.
+fn next_finished_probe() -> Option<(EmacsInt, ProbeResult)> {
+    let mut probes = PROBES.lock().unwrap();
+    let mut finished = None;
+    for (index, &(id, deadline, ref receiver)) in probes.iter().enumerate() {
+        if let Some(result) = probe_result(deadline, receiver) {
+            finished = Some((index, id, result));
+            break;
+        }
+    }
+    let (index, id, result) = finished?;
+    probes.remove(index);
+    Some((id, result))
+}
+
+/// Run the callbacks of finished `network-reachable-async' checks.
+/// This is called from a timer while checks are pending.
+#[lisp_fn(name = "network--reachable-poll", c_name = "network__reachable_poll")]
+pub fn network_reachable_poll() -> LispObject {
+    let symbol = intern("network-reachable-async");
+    // Results are handled one at a time, so that an error in one
+    // callback leaves the others for the next run of the timer.
+    while let Some((id, result)) = next_finished_probe() {
+        let callbacks = get(symbol, intern("network--callbacks"));
+        let entry = assq(LispObject::from_fixnum(id), callbacks);
+        put(symbol, intern("network--callbacks"), delq(entry, callbacks));
+        if entry.is_nil() {
+            continue;
+        }
+        match result {
+            Ok(()) => call!(entry.cdr(), LispObject::constant_t()),
+            Err(message) => call!(
+                entry.cdr(),
+                LispObject::constant_nil(),
+                lisp_string(&message)
+            ),
+        };
+    }
+    let idle = PROBES.lock().unwrap().is_empty();
+    if idle {
+        let timer = get(symbol, intern("network--timer"));
+        if !timer.is_nil() {
+            call!(intern("cancel-timer"), timer);
+        }
+        put(symbol, intern("network--timer"), LispObject::constant_nil());
+    }
+    LispObject::constant_nil()
+}
+
+#[test]
+fn test_ipv6_words() {
+    let octets = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 1];
+    assert_eq!(ipv6_words(&octets), vec![0x2001, 0x0db8, 0, 0, 0, 0, 0, 0xff01]);
+}
+
+#[test]
+fn test_probe() {
+    let listener = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    assert!(probe("127.0.0.1", port, Duration::from_secs(2)).is_ok());
+    drop(listener);
+    assert!(probe("127.0.0.1", port, Duration::from_secs(2)).is_err());
+}
+
+include!(concat!(env!("OUT_DIR"), "/network_exports.rs"));
@@ -0,0 +1,265 @@
+//! Desktop notifications.
+//!
+//! The notification goes to the notification server over D-Bus, as the
+//! Desktop Notifications specification describes, and clicks on its
+//! action buttons come back as `dbus-event's.
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{EmacsInt, Qerror};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::list;
+use strings::lisp_string;
+use symbols::symbol_value;
+
+const SERVICE: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+
+/// A notification, as described by the arguments of
+/// `notify-send-native'.
+#[derive(Default)]
+struct Notification {
+    title: String,
+    body: String,
+    app_name: String,
+    app_icon: String,
+    replaces_id: u32,
+    actions: Vec<String>,
+    /// The time to show the notification for, in milliseconds.  -1
+    /// leaves it to the server, 0 means forever.
+    timeout: i32,
+    /// 0, 1 or 2 for low, normal or critical.
+    urgency: Option<u8>,
+    category: Option<String>,
+    sound_name: Option<String>,
+    resident: bool,
+    transient: bool,
+}
+
+/// Return the urgency level called NAME.
+fn urgency_level(name: &str) -> Option<u8> {
+    match name {
+        "low" => Some(0),
+        "normal" => Some(1),
+        "critical" => Some(2),
+        _ => None,
+    }
+}
+
+fn string_value(object: LispObject) -> String {
+    String::from_utf8_lossy(object.as_string_or_error().as_slice()).into_owned()
+}
+
+/// Return the icon for notifications that don't name one: the Emacs
+/// icon from `data-directory'.
+fn default_icon() -> String {
+    string_value(call!(
+        intern("expand-file-name"),
+        lisp_string("images/icons/hicolor/scalable/apps/emacs.svg"),
+        symbol_value(intern("data-directory"))
+    ))
+}
+
+/// Return the hint NAME with VALUE, of the D-Bus type DTYPE, in the
+/// form `dbus-call-method' takes.
+fn hint(name: &str, dtype: &str, value: LispObject) -> LispObject {
+    list!(
+        intern(":dict-entry"),
+        lisp_string(name),
+        list!(intern(":variant"), intern(dtype), value)
+    )
+}
+
+/// Show NOTIFICATION through the notification server on the session
+/// bus, and return the id it gave it.
+///
+/// The call goes through `dbus-call-method', which reads input events
+/// while it waits for the reply, so a server that doesn't answer can't
+/// hang Emacs and the wait can be quit.
+fn show(notification: Notification) -> u32 {
+    let mut hints = vec![intern(":array")];
+    if let Some(level) = notification.urgency {
+        hints.push(hint(
+            "urgency",
+            ":byte",
+            LispObject::from_natnum(EmacsInt::from(level)),
+        ));
+    }
+    if let Some(ref category) = notification.category {
+        hints.push(hint("category", ":string", lisp_string(category)));
+    }
+    if let Some(ref sound) = notification.sound_name {
+        hints.push(hint("sound-name", ":string", lisp_string(sound)));
+    }
+    if notification.resident {
+        hints.push(hint("resident", ":boolean", LispObject::constant_t()));
+    }
+    if notification.transient {
+        hints.push(hint("transient", ":boolean", LispObject::constant_t()));
+    }
+    if hints.len() == 1 {
+        // The type of an empty array must be given.
+        hints.push(intern(":signature"));
+        hints.push(lisp_string("{sv}"));
+    }
+    let mut actions = vec![intern(":array")];
+    actions.extend(notification.actions.iter().map(|s| lisp_string(s)));
+
+    call!(intern("require"), intern("dbus"));
+    let reply = call!(
+        intern("dbus-call-method"),
+        intern(":session"),
+        lisp_string(SERVICE),
+        lisp_string(PATH),
+        lisp_string(SERVICE),
+        lisp_string("Notify"),
+        intern(":string"),
+        lisp_string(&notification.app_name),
+        intern(":uint32"),
+        LispObject::from_natnum(EmacsInt::from(notification.replaces_id)),
+        intern(":string"),
+        lisp_string(&notification.app_icon),
+        intern(":string"),
+        lisp_string(&notification.title),
+        intern(":string"),
+        lisp_string(&notification.body),
+        list(&mut actions),
+        list(&mut hints),
+        intern(":int32"),
+        LispObject::from_fixnum(EmacsInt::from(notification.timeout))
+    );
+    match reply.as_fixnum() {
+        Some(id) if 0 <= id && id <= EmacsInt::from(u32::max_value()) => id as u32,
+        _ => error!("Invalid reply from the notification server"),
+    }
+}
+
+/// Show a desktop notification with TITLE and BODY, and return its id.
+///
+/// PARAMS is a property list of further parameters:
+///
+///  :app-name     The name of the application; the default is "Emacs".
+///  :app-icon     The icon file to show.  The default is the Emacs
+///                icon; nil means no icon.
+///  :replaces-id  The id of a notification this one replaces.
+///  :actions      A list (KEY TITLE KEY TITLE ...) of action buttons.
+///                The action with key "default" is usually invoked by
+///                clicking on the notification itself.
+///  :timeout      The time to show the notification for, in
+///                milliseconds.  0 means until the user closes it, -1,
+///                the default, leaves it to the notification server.
+///  :urgency      One of `low', `normal' or `critical'.
+///  :category     The type of notification, like "email.arrived".
+///  :sound-name   A themable named sound to play.
+///  :resident     Non-nil means keep the notification after an action
+///                was invoked.
+///  :transient    Non-nil means don't keep the notification in the
+///                server's history.
+///  :on-action    A function called with the notification id and the
+///                key of the action when the user invokes an action.
+///  :on-close     A function called with the notification id and the
+///                reason, as in `notifications-notify', when the
+///                notification is closed.
+///
+/// The notification is sent to the notification server on the D-Bus
+/// session bus, and invoked actions arrive as `dbus-event's, which run
+/// the :on-action function.
+///
+/// usage: (notify-send-native TITLE BODY &rest PARAMS)
+#[lisp_fn(min = "2")]
+pub fn notify_send_native(args: &mut [LispObject]) -> LispObject {
+    let mut notification = Notification {
+        title: string_value(args[0]),
+        body: string_value(args[1]),
+        app_name: "Emacs".to_string(),
+        timeout: -1,
+        ..Default::default()
+    };
+    let mut app_icon = None;
+    let mut on_action = LispObject::constant_nil();
+    let mut on_close = LispObject::constant_nil();
+
+    let params = &args[2..];
+    if params.len() % 2 != 0 {
+        xsignal!(
+            Qerror,
+            lisp_string("Odd number of parameters"),
+            params[params.len() - 1]
+        );
+    }
+    for pair in params.chunks(2) {
+        let (key, value) = (pair[0], pair[1]);
+        if key.eq(intern(":app-name")) {
+            notification.app_name = string_value(value);
+        } else if key.eq(intern(":app-icon")) {
+            app_icon = Some(value);
+        } else if key.eq(intern(":replaces-id")) {
+            notification.replaces_id = value.as_natnum_or_error() as u32;
+        } else if key.eq(intern(":actions")) {
+            notification.actions = value.iter_cars_safe().map(string_value).collect();
+            if notification.actions.len() % 2 != 0 {
+                xsignal!(Qerror, lisp_string("Actions must come in pairs"), value);
+            }
+        } else if key.eq(intern(":timeout")) {
+            let timeout = value.as_fixnum_or_error();
+            if timeout < -1 || timeout > EmacsInt::from(i32::max_value()) {
+                args_out_of_range!(
+                    value,
+                    LispObject::from_fixnum(-1),
+                    LispObject::from_fixnum(EmacsInt::from(i32::max_value()))
+                );
+            }
+            notification.timeout = timeout as i32;
+        } else if key.eq(intern(":urgency")) {
+            let name = value.as_symbol_or_error().symbol_name();
+            notification.urgency = match urgency_level(&string_value(name)) {
+                Some(level) => Some(level),
+                None => xsignal!(Qerror, lisp_string("Invalid urgency"), value),
+            };
+        } else if key.eq(intern(":category")) {
+            notification.category = Some(string_value(value));
+        } else if key.eq(intern(":sound-name")) {
+            notification.sound_name = Some(string_value(value));
+        } else if key.eq(intern(":resident")) {
+            notification.resident = value.is_not_nil();
+        } else if key.eq(intern(":transient")) {
+            notification.transient = value.is_not_nil();
+        } else if key.eq(intern(":on-action")) {
+            on_action = value;
+        } else if key.eq(intern(":on-close")) {
+            on_close = value;
+        } else {
+            xsignal!(Qerror, lisp_string("Unknown parameter"), key);
+        }
+    }
+    notification.app_icon = match app_icon {
+        None => default_icon(),
+        Some(icon) if icon.is_nil() => String::new(),
+        Some(icon) => string_value(call!(intern("expand-file-name"), icon)),
+    };
+
+    let id = LispObject::from_natnum(EmacsInt::from(show(notification)));
+    if on_action.is_not_nil() || on_close.is_not_nil() {
+        // notifications.el keeps the callbacks and registers for the
+        // signals of the server.
+        call!(intern("require"), intern("notifications"));
+        call!(
+            intern("notifications--register-callbacks"),
+            intern(":session"),
+            id,
+            on_action,
+            on_close
+        );
+    }
+    id
+}
+
+#[test]
+fn test_urgency_level() {
+    assert_eq!(urgency_level("low"), Some(0));
+    assert_eq!(urgency_level("critical"), Some(2));
+    assert_eq!(urgency_level("urgent"), None);
+}
+
+include!(concat!(env!("OUT_DIR"), "/notifications_exports.rs"));
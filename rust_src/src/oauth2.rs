@@ -0,0 +1,524 @@
+//! OAuth 2.0 authorization for Emacs as a native application.
+//!
+//! Mail and calendar providers want a client to get its tokens as RFC
+//! 8252 says native applications do: the user is sent to the
+//! authorization page in a browser, which redirects back to a listener
+//! on the loopback interface, and the code it brings is exchanged for
+//! tokens with a PKCE verifier (RFC 7636) instead of a client secret.
+//! The listener and PKCE are done here; the token endpoint is posted to
+//! with `url-retrieve-synchronously'.  Refresh tokens are kept in the
+//! keychain of the system with `secrets-store', and access tokens in
+//! memory until they expire.
+
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rand::{OsRng, Rng};
+use sha2::{Digest, Sha256};
+use url::Url;
+use url::form_urlencoded::Serializer;
+
+use base64_crate;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{record_unwind_current_buffer, unbind_to, Qnil};
+
+use eval_call::{specbind, specpdl_index};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use secrets::{secrets_lookup, secrets_store};
+use strings::lisp_string;
+
+/// How long to wait for the browser to come back, in seconds.
+const AUTHORIZATION_TIMEOUT: u64 = 300;
+
+/// How long before it expires an access token is no longer used.
+const EXPIRY_MARGIN: i64 = 60;
+
+/// The page shown in the browser once the redirect is received.
+const DONE_PAGE: &str = "<!DOCTYPE html><html><head><title>Emacs</title></head>\
+                         <body><p>Authorization complete.  You can close this \
+                         window and return to Emacs.</p></body></html>";
+
+/// Return BYTES encoded as base64url without padding.
+fn base64url(bytes: &[u8]) -> String {
+    base64_crate::encode_config(bytes, base64_crate::URL_SAFE_NO_PAD)
+}
+
+/// Return a random string with BYTES bytes of entropy.
+fn random_string(bytes: usize) -> String {
+    let mut rng = match OsRng::new() {
+        Ok(rng) => rng,
+        Err(err) => error!("Cannot get random bytes: {}", err),
+    };
+    let mut buffer = vec![0; bytes];
+    rng.fill_bytes(&mut buffer);
+    base64url(&buffer)
+}
+
+/// Return the S256 code challenge of VERIFIER.
+fn code_challenge(verifier: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input(verifier.as_bytes());
+    base64url(&hasher.result())
+}
+
+/// What the browser brought back to the listener.
+#[derive(Debug, PartialEq)]
+enum Redirect {
+    Code { code: String, state: String },
+    Error(String),
+    /// Another request, such as one for the favicon.
+    Other,
+}
+
+/// Parse REQUEST, the start of an HTTP request received by the listener.
+fn parse_redirect(request: &str) -> Redirect {
+    let target = match request.lines().next().map(|line| line.split(' ').collect::<Vec<_>>()) {
+        Some(ref words) if words.len() == 3 && words[0] == "GET" => words[1].to_string(),
+        _ => return Redirect::Other,
+    };
+    let url = match Url::parse("http://127.0.0.1/").and_then(|base| base.join(&target)) {
+        Ok(url) => url,
+        Err(_) => return Redirect::Other,
+    };
+    let (mut code, mut state, mut error, mut description) = (None, None, None, None);
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "code" => code = Some(value.into_owned()),
+            "state" => state = Some(value.into_owned()),
+            "error" => error = Some(value.into_owned()),
+            "error_description" => description = Some(value.into_owned()),
+            _ => (),
+        }
+    }
+    match (code, error) {
+        (_, Some(error)) => Redirect::Error(match description {
+            Some(description) => format!("{}: {}", error, description),
+            None => error,
+        }),
+        (Some(code), None) => Redirect::Code {
+            code,
+            state: state.unwrap_or_default(),
+        },
+        (None, None) => Redirect::Other,
+    }
+}
+
+/// Read the request on STREAM, answer it and return what it brought.
+fn handle_request(mut stream: TcpStream) -> Redirect {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < 16384 {
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buffer[..n]),
+        }
+    }
+    let redirect = parse_redirect(&String::from_utf8_lossy(&request));
+    let response = match redirect {
+        Redirect::Other => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\
+                            Connection: close\r\n\r\n"
+            .to_string(),
+        _ => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            DONE_PAGE.len(),
+            DONE_PAGE
+        ),
+    };
+    let _ = stream.write_all(response.as_bytes());
+    redirect
+}
+
+lazy_static! {
+    /// The loopback listener of the authorization in progress.  It is
+    /// kept here rather than on the stack so that it is closed by the
+    /// next authorization if the user quits this one.
+    static ref LISTENER: Mutex<Option<TcpListener>> = Mutex::new(None);
+
+    /// The access tokens, by token endpoint and client id, with the
+    /// time at which they expire.
+    static ref ACCESS_TOKENS: Mutex<HashMap<(String, String), (String, Option<i64>)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Listen on a free port of the loopback interface and return the port.
+fn listen() -> u16 {
+    let listener = match TcpListener::bind(("127.0.0.1", 0)) {
+        Ok(listener) => listener,
+        Err(err) => error!("Cannot listen for the authorization: {}", err),
+    };
+    let port = match listener
+        .set_nonblocking(true)
+        .and_then(|_| listener.local_addr())
+    {
+        Ok(address) => address.port(),
+        Err(err) => error!("Cannot listen for the authorization: {}", err),
+    };
+    *LISTENER.lock().unwrap() = Some(listener);
+    port
+}
+
+/// Wait for the browser to be redirected to the listener with a code,
+/// and return the code.  Lisp runs between polls so that the user can
+/// quit, which is why the lock is never held across them.
+fn wait_for_code(state: &str) -> String {
+    let deadline = Instant::now() + Duration::from_secs(AUTHORIZATION_TIMEOUT);
+    loop {
+        let accepted = LISTENER
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|listener| listener.accept());
+        let redirect = match accepted {
+            Some(Ok((stream, _))) => handle_request(stream),
+            Some(Err(ref err)) if err.kind() == ErrorKind::WouldBlock => Redirect::Other,
+            Some(Err(err)) => error!("Cannot accept the authorization: {}", err),
+            None => error!("The authorization was abandoned"),
+        };
+        match redirect {
+            Redirect::Code { code, state: received } => {
+                *LISTENER.lock().unwrap() = None;
+                if received != state {
+                    error!("The authorization came back with the wrong state");
+                }
+                return code;
+            }
+            Redirect::Error(message) => {
+                *LISTENER.lock().unwrap() = None;
+                error!("Authorization refused: {}", message);
+            }
+            Redirect::Other => (),
+        }
+        if Instant::now() > deadline {
+            *LISTENER.lock().unwrap() = None;
+            error!("Timed out waiting for the authorization");
+        }
+        call!(intern("sleep-for"), LispObject::from_float(0.1));
+    }
+}
+
+/// The tokens returned by a token endpoint.
+struct Tokens {
+    access: String,
+    refresh: Option<String>,
+    expires_in: Option<i64>,
+}
+
+fn rust_string(string: LispObject) -> String {
+    String::from_utf8_lossy(string.as_string_or_error().as_slice()).into_owned()
+}
+
+fn optional_string(string: LispObject) -> Option<String> {
+    if string.is_nil() {
+        None
+    } else {
+        Some(rust_string(string))
+    }
+}
+
+/// Return the value of KEY in ALIST, the JSON object of a response.
+fn json_field(alist: LispObject, key: &str) -> LispObject {
+    call!(intern("cdr"), call!(intern("assq"), intern(key), alist))
+}
+
+/// Post the form PARAMS to TOKEN_URL and return the tokens, or the
+/// error message of the endpoint.
+fn request_tokens(token_url: &str, params: &[(&str, &str)]) -> Result<Tokens, String> {
+    let mut form = Serializer::new(String::new());
+    for &(key, value) in params {
+        form.append_pair(key, value);
+    }
+    call!(intern("require"), intern("url"));
+    call!(intern("require"), intern("json"));
+    let count = specpdl_index();
+    let headers = list!(
+        LispObject::cons(
+            lisp_string("Content-Type"),
+            lisp_string("application/x-www-form-urlencoded")
+        ),
+        LispObject::cons(lisp_string("Accept"), lisp_string("application/json"))
+    );
+    specbind(intern("url-request-method").to_raw(), lisp_string("POST").to_raw());
+    specbind(intern("url-request-data").to_raw(), lisp_string(&form.finish()).to_raw());
+    specbind(intern("url-request-extra-headers").to_raw(), headers.to_raw());
+    let buffer = call!(
+        intern("url-retrieve-synchronously"),
+        lisp_string(token_url),
+        LispObject::constant_t(),
+        LispObject::constant_t()
+    );
+    unsafe { unbind_to(count, Qnil) };
+    if buffer.is_nil() {
+        return Err(format!("No response from {}", token_url));
+    }
+    let count = specpdl_index();
+    unsafe { record_unwind_current_buffer() };
+    call!(intern("set-buffer"), buffer);
+    let status = call!(intern("symbol-value"), intern("url-http-response-status"));
+    let end = call!(intern("symbol-value"), intern("url-http-end-of-headers"));
+    let body = call!(
+        intern("buffer-substring-no-properties"),
+        if end.is_nil() {
+            call!(intern("point-min"))
+        } else {
+            end
+        },
+        call!(intern("point-max"))
+    );
+    unsafe { unbind_to(count, Qnil) };
+    call!(intern("kill-buffer"), buffer);
+    let body = call!(intern("decode-coding-string"), body, intern("utf-8"));
+    let object = call!(intern("json-read-from-string"), body);
+    let error = json_field(object, "error");
+    if error.is_not_nil() {
+        let description = json_field(object, "error_description");
+        return Err(match optional_string(description) {
+            Some(description) => format!("{}: {}", rust_string(error), description),
+            None => rust_string(error),
+        });
+    }
+    let access = json_field(object, "access_token");
+    if access.is_nil() {
+        return Err(format!(
+            "No access token in the response of {} (status {})",
+            token_url,
+            status.as_fixnum().unwrap_or(0)
+        ));
+    }
+    Ok(Tokens {
+        access: rust_string(access),
+        refresh: optional_string(json_field(object, "refresh_token")),
+        expires_in: json_field(object, "expires_in").as_fixnum().map(|n| n as i64),
+    })
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Return the keychain service under which the refresh tokens of
+/// TOKEN_URL are kept.
+fn secrets_service(token_url: &str) -> LispObject {
+    lisp_string(&format!("oauth2:{}", token_url))
+}
+
+/// Remember TOKENS, received from TOKEN_URL for CLIENT_ID, and return
+/// the access token.
+fn save_tokens(token_url: &str, client_id: &str, tokens: Tokens) -> LispObject {
+    if let Some(ref refresh) = tokens.refresh {
+        secrets_store(
+            secrets_service(token_url),
+            lisp_string(client_id),
+            lisp_string(refresh),
+        );
+    }
+    let expires = tokens.expires_in.map(|seconds| now() + seconds);
+    let access = lisp_string(&tokens.access);
+    ACCESS_TOKENS.lock().unwrap().insert(
+        (token_url.to_string(), client_id.to_string()),
+        (tokens.access, expires),
+    );
+    access
+}
+
+/// Return a new PKCE code verifier and its challenge, as (VERIFIER . CHALLENGE).
+/// The verifier is 43 random characters, and the challenge the
+/// base64url encoding of its SHA-256 hash, for the "S256" method of
+/// RFC 7636.
+#[lisp_fn]
+pub fn oauth2_pkce_pair() -> LispObject {
+    let verifier = random_string(32);
+    let challenge = code_challenge(&verifier);
+    LispObject::cons(lisp_string(&verifier), lisp_string(&challenge))
+}
+
+/// Authorize CLIENT-ID in the browser and return an access token.
+/// AUTH-URL is the authorization endpoint of the provider and TOKEN-URL
+/// its token endpoint.  SCOPE is a string of space-separated scopes.
+/// CLIENT-SECRET is only needed by providers that give native clients
+/// one anyway.
+///
+/// The authorization page is opened with `browse-url'.  Once the user
+/// has agreed, the browser is redirected to a listener on a free port
+/// of the loopback interface, and the code it brings is exchanged for
+/// tokens with a PKCE verifier.  The refresh token is stored in the
+/// keychain with `secrets-store', for `oauth2-native-refresh'.  Signal
+/// an error if the user refuses, or does not come back within five
+/// minutes.
+#[lisp_fn(min = "3")]
+pub fn oauth2_native_authorize(
+    auth_url: LispObject,
+    token_url: LispObject,
+    client_id: LispObject,
+    scope: LispObject,
+    client_secret: LispObject,
+) -> LispObject {
+    let auth_url = rust_string(auth_url);
+    let token_url = rust_string(token_url);
+    let client_id = rust_string(client_id);
+    let scope = optional_string(scope);
+    let client_secret = optional_string(client_secret);
+    let verifier = random_string(32);
+    let challenge = code_challenge(&verifier);
+    let state = random_string(16);
+    let redirect_uri = format!("http://127.0.0.1:{}/", listen());
+    let mut params = vec![
+        ("response_type", "code"),
+        ("client_id", client_id.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("state", state.as_str()),
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    if let Some(ref scope) = scope {
+        params.push(("scope", scope.as_str()));
+    }
+    let url = match Url::parse_with_params(&auth_url, &params) {
+        Ok(url) => url,
+        Err(err) => error!("Invalid authorization URL: {}", err),
+    };
+    call!(intern("browse-url"), lisp_string(url.as_str()));
+    call!(
+        intern("message"),
+        lisp_string("Waiting for the authorization in the browser...")
+    );
+    let code = wait_for_code(&state);
+    let mut params = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("client_id", client_id.as_str()),
+        ("code_verifier", verifier.as_str()),
+    ];
+    if let Some(ref secret) = client_secret {
+        params.push(("client_secret", secret.as_str()));
+    }
+    match request_tokens(&token_url, &params) {
+        Ok(tokens) => save_tokens(&token_url, &client_id, tokens),
+        Err(message) => error!("Token request failed: {}", message),
+    }
+}
+
+/// Refresh the access token of CLIENT_ID at TOKEN_URL.  Return None
+/// if there is no stored refresh token.
+fn refresh(
+    token_url: &str,
+    client_id: &str,
+    client_secret: Option<&str>,
+) -> Option<Result<LispObject, String>> {
+    let stored = secrets_lookup(secrets_service(token_url), lisp_string(client_id));
+    if stored.is_nil() {
+        return None;
+    }
+    let refresh_token = rust_string(call!(intern("cdr"), stored));
+    let mut params = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.as_str()),
+        ("client_id", client_id),
+    ];
+    if let Some(secret) = client_secret {
+        params.push(("client_secret", secret));
+    }
+    Some(
+        request_tokens(token_url, &params)
+            .map(|tokens| save_tokens(token_url, client_id, tokens)),
+    )
+}
+
+/// Return a new access token for CLIENT-ID from its stored refresh token.
+/// TOKEN-URL is the token endpoint of the provider, and CLIENT-SECRET
+/// is as for `oauth2-native-authorize'.  A new refresh token sent by
+/// the provider replaces the stored one.  Return nil if there is no
+/// stored refresh token, and signal an error if the provider rejects
+/// it.
+#[lisp_fn(min = "2")]
+pub fn oauth2_native_refresh(
+    token_url: LispObject,
+    client_id: LispObject,
+    client_secret: LispObject,
+) -> LispObject {
+    let client_secret = optional_string(client_secret);
+    let refreshed = refresh(
+        &rust_string(token_url),
+        &rust_string(client_id),
+        client_secret.as_ref().map(|secret| secret.as_str()),
+    );
+    match refreshed {
+        Some(Ok(access)) => access,
+        Some(Err(message)) => error!("Token refresh failed: {}", message),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Return an access token for CLIENT-ID, getting a new one if needed.
+/// The arguments are as for `oauth2-native-authorize'.  A token that
+/// is still valid for a minute is returned as it is.  Otherwise it is
+/// refreshed as by `oauth2-native-refresh', and if there is no refresh
+/// token or the provider rejects it, the user is asked to authorize
+/// again as by `oauth2-native-authorize'.  This is what a package calls
+/// before each connection to the provider.
+#[lisp_fn(min = "3")]
+pub fn oauth2_native_access_token(
+    auth_url: LispObject,
+    token_url: LispObject,
+    client_id: LispObject,
+    scope: LispObject,
+    client_secret: LispObject,
+) -> LispObject {
+    let key = (rust_string(token_url), rust_string(client_id));
+    let cached = ACCESS_TOKENS.lock().unwrap().get(&key).cloned();
+    if let Some((access, expires)) = cached {
+        if expires.map_or(true, |expires| expires - EXPIRY_MARGIN > now()) {
+            return lisp_string(&access);
+        }
+    }
+    let secret = optional_string(client_secret);
+    if let Some(Ok(access)) = refresh(&key.0, &key.1, secret.as_ref().map(|s| s.as_str())) {
+        return access;
+    }
+    oauth2_native_authorize(auth_url, token_url, client_id, scope, client_secret)
+}
+
+#[test]
+fn test_code_challenge() {
+    // The example of appendix B of RFC 7636.
+    assert_eq!(
+        code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+        "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+    );
+    assert_eq!(base64url(&[0xfb, 0xff]), "-_8");
+}
+
+#[test]
+fn test_parse_redirect() {
+    assert_eq!(
+        parse_redirect("GET /?state=s%2B1&code=abc HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n"),
+        Redirect::Code {
+            code: "abc".to_string(),
+            state: "s+1".to_string(),
+        }
+    );
+    assert_eq!(
+        parse_redirect("GET /?error=access_denied&error_description=No+way HTTP/1.1\r\n"),
+        Redirect::Error("access_denied: No way".to_string())
+    );
+    assert_eq!(
+        parse_redirect("GET /?error=access_denied&code=x HTTP/1.1\r\n"),
+        Redirect::Error("access_denied".to_string())
+    );
+    assert_eq!(parse_redirect("GET /favicon.ico HTTP/1.1\r\n"), Redirect::Other);
+    assert_eq!(parse_redirect("POST /?code=x HTTP/1.1\r\n"), Redirect::Other);
+    assert_eq!(parse_redirect(""), Redirect::Other);
+}
+
+include!(concat!(env!("OUT_DIR"), "/oauth2_exports.rs"));
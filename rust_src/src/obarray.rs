@@ -1,13 +1,25 @@
 //! obarray code
+//!
+//! Obarrays are vectors of buckets, each holding a chain of symbols.
+//! The symbols of the initial obarray as it was when Emacs was dumped
+//! are also indexed by a perfect hash (the "core"), which `oblookup'
+//! tries first.  Symbols interned after dumping are only found through
+//! the buckets.  The core lives on the Rust heap, which is not saved in
+//! the dump, so it is built afresh each time a dumped Emacs starts.
+
+use std::slice;
+
 use libc;
 
 use remacs_macros::lisp_fn;
-use remacs_sys::{Fpurecopy, Lisp_Object};
+use remacs_sys::{EmacsInt, Fpurecopy, Lisp_Object};
 use remacs_sys::{check_obarray, check_vobarray, globals, intern_driver, make_unibyte_string,
                  oblookup};
 
-use lisp::LispObject;
+use lisp::{intern as intern_str, LispObject};
 use lisp::defsubr;
+use multibyte::LispStringRef;
+use symbols::LispSymbolRef;
 
 /// A lisp object containing an `obarray`.
 pub struct LispObarrayRef(LispObject);
@@ -108,6 +120,205 @@ impl LispObarrayRef {
     }
 }
 
+/// How many names share a displacement in a `PerfectHash`, on average.
+const NAMES_PER_GROUP: usize = 4;
+
+/// The most displacements tried for one group of names before giving up
+/// on building a `PerfectHash`.
+const MAX_DISPLACEMENT: u32 = 1 << 20;
+
+/// A perfect hash over a fixed set of names, which gives every name a
+/// slot of its own, so that looking one up takes a single hash and a
+/// single comparison.  This is the "hash and displace" scheme: the
+/// names are split into small groups, and each group is given the
+/// displacement that sends all its names to free slots.
+struct PerfectHash {
+    displacements: Vec<u32>,
+    /// The index of the name in each slot, if any.
+    slots: Vec<Option<u32>>,
+}
+
+/// FNV-1a.
+fn hash_name(name: &[u8]) -> u64 {
+    name.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// Mix DISPLACEMENT into HASH, using the SplitMix64 finalizer so that
+/// consecutive displacements scatter the names widely.
+fn displace(hash: u64, displacement: u32) -> u64 {
+    let mut z = hash ^ u64::from(displacement).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl PerfectHash {
+    /// Build a perfect hash over NAMES, which must be distinct.  Return
+    /// None if no displacement fits some group.
+    fn build(names: &[&[u8]]) -> Option<PerfectHash> {
+        let ngroups = (names.len() + NAMES_PER_GROUP - 1) / NAMES_PER_GROUP;
+        let ngroups = ngroups.max(1);
+        let nslots = names.len() + names.len() / 4 + 1;
+        let hashes: Vec<u64> = names.iter().map(|name| hash_name(name)).collect();
+
+        let mut groups = vec![Vec::new(); ngroups];
+        for (i, &hash) in hashes.iter().enumerate() {
+            groups[(hash % ngroups as u64) as usize].push(i as u32);
+        }
+        // Place the biggest groups first, while there is most room.
+        let mut order: Vec<usize> = (0..ngroups).collect();
+        order.sort_by(|&a, &b| groups[b].len().cmp(&groups[a].len()));
+
+        let mut displacements = vec![0; ngroups];
+        let mut slots = vec![None; nslots];
+        let mut taken = Vec::new();
+        for &group in &order {
+            let mut displacement = 0;
+            loop {
+                taken.clear();
+                let fits = groups[group].iter().all(|&i| {
+                    let hash = displace(hashes[i as usize], displacement);
+                    let slot = (hash % nslots as u64) as usize;
+                    let free = slots[slot].is_none() && !taken.contains(&slot);
+                    taken.push(slot);
+                    free
+                });
+                if fits {
+                    break;
+                }
+                displacement += 1;
+                if displacement == MAX_DISPLACEMENT {
+                    return None;
+                }
+            }
+            for (&i, &slot) in groups[group].iter().zip(&taken) {
+                slots[slot] = Some(i);
+            }
+            displacements[group] = displacement;
+        }
+        Some(PerfectHash {
+            displacements,
+            slots,
+        })
+    }
+
+    /// Return the slot for NAME.  A name outside the set gets the slot
+    /// of some other name, or an empty one.
+    fn slot(&self, name: &[u8]) -> usize {
+        let hash = hash_name(name);
+        let group = (hash % self.displacements.len() as u64) as usize;
+        (displace(hash, self.displacements[group]) % self.slots.len() as u64) as usize
+    }
+
+    /// Return the index of the name that NAME may be.
+    fn lookup(&self, name: &[u8]) -> Option<u32> {
+        self.slots[self.slot(name)]
+    }
+}
+
+/// The core of the initial obarray: the symbols it held when Emacs was
+/// dumped.
+struct CoreObarray {
+    obarray: Lisp_Object,
+    hash: PerfectHash,
+    symbols: Vec<Lisp_Object>,
+}
+
+/// Set up when a dumped Emacs starts, and changed afterwards only by
+/// `unintern'.  Lisp threads run one at a time, so this needs no lock.
+static mut CORE_OBARRAY: Option<CoreObarray> = None;
+
+/// Return the symbols in each bucket of OBARRAY.
+fn obarray_buckets(obarray: LispObject) -> Vec<Vec<LispObject>> {
+    let vector = obarray.as_vectorlike().and_then(|v| v.as_vector()).unwrap();
+    vector
+        .as_slice()
+        .iter()
+        .map(|&bucket| {
+            let mut chain = Vec::new();
+            let mut tail = bucket.as_symbol();
+            while let Some(symbol) = tail {
+                chain.push(symbol.as_lisp_obj());
+                tail = if symbol.next.is_null() {
+                    None
+                } else {
+                    Some(LispSymbolRef::new(symbol.next))
+                };
+            }
+            chain
+        })
+        .collect()
+}
+
+fn symbol_name_string(symbol: LispObject) -> LispStringRef {
+    symbol.as_symbol().unwrap().symbol_name().as_string().unwrap()
+}
+
+/// Index the symbols in OBARRAY, which is the initial obarray, as its
+/// core.  `main' calls this in a dumped Emacs, before anything else is
+/// interned.
+#[no_mangle]
+pub extern "C" fn init_core_obarray(obarray: Lisp_Object) {
+    let symbols: Vec<LispObject> = obarray_buckets(LispObject::from(obarray))
+        .into_iter()
+        .flat_map(|chain| chain)
+        .collect();
+    let strings: Vec<LispStringRef> = symbols.iter().map(|&s| symbol_name_string(s)).collect();
+    let names: Vec<&[u8]> = strings.iter().map(|s| s.as_slice()).collect();
+    let core = PerfectHash::build(&names).map(|hash| CoreObarray {
+        obarray,
+        hash,
+        symbols: symbols.iter().map(|s| s.to_raw()).collect(),
+    });
+    unsafe { CORE_OBARRAY = core };
+}
+
+/// Look for the symbol whose name is the SIZE_BYTE bytes at PTR, which
+/// are SIZE characters, in the core of OBARRAY.  If it is there, store
+/// it in *SYMBOL and return true.
+#[no_mangle]
+pub extern "C" fn core_obarray_lookup(
+    obarray: Lisp_Object,
+    ptr: *const libc::c_char,
+    size: libc::ptrdiff_t,
+    size_byte: libc::ptrdiff_t,
+    symbol: *mut Lisp_Object,
+) -> bool {
+    let core = match unsafe { CORE_OBARRAY.as_ref() } {
+        Some(core) if core.obarray == obarray => core,
+        _ => return false,
+    };
+    let name = unsafe { slice::from_raw_parts(ptr as *const u8, size_byte as usize) };
+    let candidate = match core.hash.lookup(name) {
+        Some(index) => core.symbols[index as usize],
+        None => return false,
+    };
+    let found = symbol_name_string(LispObject::from(candidate));
+    if found.len_bytes() == size_byte && found.len_chars() == size && found.as_slice() == name {
+        unsafe { *symbol = candidate };
+        true
+    } else {
+        false
+    }
+}
+
+/// Remove SYMBOL from the core, if it is there, as it is being
+/// uninterned.
+#[no_mangle]
+pub extern "C" fn forget_core_symbol(symbol: Lisp_Object) {
+    if let Some(core) = unsafe { CORE_OBARRAY.as_mut() } {
+        let name = symbol_name_string(LispObject::from(symbol));
+        let slot = core.hash.slot(name.as_slice());
+        if let Some(index) = core.hash.slots[slot] {
+            if core.symbols[index as usize] == symbol {
+                core.hash.slots[slot] = None;
+            }
+        }
+    }
+}
+
 /// Intern the C string `s`: return a symbol with that name, interned in the
 /// current obarray.
 #[no_mangle]
@@ -150,4 +361,81 @@ pub fn intern(string: LispObject, obarray: LispObject) -> LispObject {
     }
 }
 
+/// Return statistics on how the symbols in OBARRAY are hashed.
+/// OBARRAY defaults to the value of `obarray'.  The value is an alist
+/// with these keys:
+///
+///  `symbols'         the number of symbols in OBARRAY;
+///  `buckets'         the number of buckets;
+///  `used-buckets'    the number of buckets holding any symbol;
+///  `longest-chain'   the most symbols held in one bucket;
+///  `collisions'      the number of symbols in a bucket after its first;
+///  `collision-rate'  collisions as a fraction of all symbols;
+///  `core-symbols'    the number of symbols in the core, the perfect
+///                    hash over the initial obarray that is searched
+///                    before the buckets;
+///  `core-slots'      the size of the core's table.
+///
+/// The core is built when a dumped Emacs starts, so the last two are 0
+/// for other obarrays, and in an Emacs that has not been dumped.
+#[lisp_fn(min = "0")]
+pub fn obarray_statistics(obarray: LispObject) -> LispObject {
+    let obarray = if obarray.is_nil() {
+        LispObarrayRef::constant_obarray()
+    } else {
+        LispObarrayRef::from_object_or_error(obarray)
+    };
+    let buckets = obarray_buckets(obarray.0);
+    let symbols: usize = buckets.iter().map(|chain| chain.len()).sum();
+    let used = buckets.iter().filter(|chain| !chain.is_empty()).count();
+    let longest = buckets.iter().map(|chain| chain.len()).max().unwrap_or(0);
+    let collisions = symbols - used;
+    let rate = if symbols == 0 {
+        0.0
+    } else {
+        collisions as f64 / symbols as f64
+    };
+
+    let (core_symbols, core_slots) = match unsafe { CORE_OBARRAY.as_ref() } {
+        Some(core) if core.obarray == obarray.0.to_raw() => (
+            core.hash.slots.iter().filter(|slot| slot.is_some()).count(),
+            core.hash.slots.len(),
+        ),
+        _ => (0, 0),
+    };
+
+    let count = |n: usize| LispObject::from_natnum(n as EmacsInt);
+    list!(
+        LispObject::cons(intern_str("symbols"), count(symbols)),
+        LispObject::cons(intern_str("buckets"), count(buckets.len())),
+        LispObject::cons(intern_str("used-buckets"), count(used)),
+        LispObject::cons(intern_str("longest-chain"), count(longest)),
+        LispObject::cons(intern_str("collisions"), count(collisions)),
+        LispObject::cons(intern_str("collision-rate"), LispObject::from_float(rate)),
+        LispObject::cons(intern_str("core-symbols"), count(core_symbols)),
+        LispObject::cons(intern_str("core-slots"), count(core_slots))
+    )
+}
+
+#[test]
+fn test_perfect_hash() {
+    let owned: Vec<String> = (0..1000).map(|i| format!("symbol-{}", i)).collect();
+    let mut names: Vec<&[u8]> = owned.iter().map(|s| s.as_bytes()).collect();
+    names.extend_from_slice(&[b"", b"nil", b"t", b"\xc3\xa9"]);
+    let hash = PerfectHash::build(&names).unwrap();
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(hash.lookup(name), Some(i as u32));
+    }
+    let used = hash.slots.iter().filter(|slot| slot.is_some()).count();
+    assert_eq!(used, names.len());
+    // A name outside the set maps to some slot, which the caller checks.
+    assert!(hash.slot(b"symbol-1000") < hash.slots.len());
+}
+
+#[test]
+fn test_perfect_hash_empty() {
+    let hash = PerfectHash::build(&[]).unwrap();
+    assert_eq!(hash.lookup(b"anything"), None);
+}
+
 include!(concat!(env!("OUT_DIR"), "/obarray_exports.rs"));
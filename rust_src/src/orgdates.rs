@@ -0,0 +1,383 @@
+//! Date arithmetic for Org agenda generation.
+//!
+//! Dates are Gregorian, either as a list (MONTH DAY YEAR) like the
+//! `date' of the calendar and diary, or as an absolute day number
+//! counting from the imaginary date December 31, 1 BC, which is day 0.
+//! The arithmetic follows calendar.el, including its handling of days
+//! past the end of a month, which roll over into the next.
+
+use libc::c_char;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{build_string, EmacsInt, Qerror};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+
+fn floor_div(a: i64, b: i64) -> i64 {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn floor_mod(a: i64, b: i64) -> i64 {
+    a - b * floor_div(a, b)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    floor_mod(year, 4) == 0 && (floor_mod(year, 100) != 0 || floor_mod(year, 400) == 0)
+}
+
+fn last_day_of_month(month: i64, year: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The absolute day number of MONTH DAY, YEAR.  DAY may lie outside the
+/// month, in which case the date moves into the following months.
+fn absolute_from_gregorian(month: i64, day: i64, year: i64) -> i64 {
+    let mut day_of_year = day + 31 * (month - 1);
+    if month > 2 {
+        day_of_year -= (23 + 4 * month) / 10;
+        if is_leap_year(year) {
+            day_of_year += 1;
+        }
+    }
+    let prior = year - 1;
+    day_of_year + 365 * prior + floor_div(prior, 4) - floor_div(prior, 100) + floor_div(prior, 400)
+}
+
+/// The (MONTH, DAY, YEAR) of the absolute day number DATE.
+fn gregorian_from_absolute(date: i64) -> (i64, i64, i64) {
+    let d0 = date - 1;
+    let n400 = floor_div(d0, 146_097);
+    let d1 = floor_mod(d0, 146_097);
+    let n100 = d1 / 36_524;
+    let d2 = d1 % 36_524;
+    let n4 = d2 / 1461;
+    let d3 = d2 % 1461;
+    let n1 = d3 / 365;
+    let mut day = d3 % 365 + 1;
+    let year = 400 * n400 + 100 * n100 + 4 * n4 + n1;
+    if n100 == 4 || n1 == 4 {
+        return (12, 31, year);
+    }
+    let year = year + 1;
+    let mut month = 1;
+    while day > last_day_of_month(month, year) {
+        day -= last_day_of_month(month, year);
+        month += 1;
+    }
+    (month, day, year)
+}
+
+/// 0 for Sunday, 1 for Monday and so on.
+fn day_of_week(date: i64) -> i64 {
+    floor_mod(date, 7)
+}
+
+fn dayname_on_or_before(dayname: i64, date: i64) -> i64 {
+    date - floor_mod(date - dayname, 7)
+}
+
+/// The absolute date of the Nth DAYNAME after (N > 0) or before (N < 0)
+/// MONTH DAY, YEAR inclusive, like `calendar-nth-named-absday'.
+fn nth_named_absday(n: i64, dayname: i64, month: i64, year: i64, day: Option<i64>) -> i64 {
+    if n > 0 {
+        let base = absolute_from_gregorian(month, day.unwrap_or(1), year);
+        7 * (n - 1) + dayname_on_or_before(dayname, base + 6)
+    } else {
+        let day = day.unwrap_or_else(|| last_day_of_month(month, year));
+        7 * (n + 1) + dayname_on_or_before(dayname, absolute_from_gregorian(month, day, year))
+    }
+}
+
+fn integer(object: LispObject) -> i64 {
+    object.as_fixnum_or_error() as i64
+}
+
+/// Convert DATE, a list (MONTH DAY YEAR) or an absolute day number, to
+/// an absolute day number.
+fn absolute_from_lisp(date: LispObject) -> i64 {
+    if let Some(n) = date.as_fixnum() {
+        return n as i64;
+    }
+    let parts: Vec<LispObject> = date.iter_cars().collect();
+    if parts.len() != 3 {
+        wrong_type!(intern("calendar-date-p"), date);
+    }
+    absolute_from_gregorian(integer(parts[0]), integer(parts[1]), integer(parts[2]))
+}
+
+/// Return true if VALUE is accepted by the diary date field SPEC: t, an
+/// integer or a list of integers.
+fn field_matches(spec: LispObject, value: i64) -> bool {
+    if spec.is_t() {
+        true
+    } else if let Some(n) = spec.as_fixnum() {
+        n as i64 == value
+    } else if spec.is_list() {
+        spec.iter_cars().any(|n| n.as_fixnum() == Some(value as EmacsInt))
+    } else {
+        wrong_type!(intern("listp"), spec)
+    }
+}
+
+fn unsupported_sexp(form: LispObject) -> ! {
+    xsignal!(
+        Qerror,
+        LispObject::from(unsafe {
+            build_string(b"Unsupported diary sexp\0".as_ptr() as *const c_char)
+        }),
+        form
+    );
+}
+
+/// Evaluate the diary sexp FORM for the absolute date DATE.
+fn sexp_matches(form: LispObject, date: i64) -> bool {
+    let cons = match form.as_cons() {
+        Some(cons) => cons,
+        None => unsupported_sexp(form),
+    };
+    let args: Vec<LispObject> = cons.cdr().iter_cars().collect();
+    let arg = |i: usize| args.get(i).cloned().unwrap_or_else(LispObject::constant_nil);
+    let function = cons.car();
+    let (month, day, year) = gregorian_from_absolute(date);
+
+    if function.eq(intern("and")) {
+        args.iter().all(|&form| sexp_matches(form, date))
+    } else if function.eq(intern("or")) {
+        args.iter().any(|&form| sexp_matches(form, date))
+    } else if function.eq(intern("not")) && args.len() == 1 {
+        !sexp_matches(args[0], date)
+    } else if function.eq(intern("diary-date")) && args.len() >= 3 {
+        field_matches(arg(0), month) && field_matches(arg(1), day) && field_matches(arg(2), year)
+    } else if function.eq(intern("diary-block")) && args.len() >= 6 {
+        let first = absolute_from_gregorian(integer(arg(0)), integer(arg(1)), integer(arg(2)));
+        let last = absolute_from_gregorian(integer(arg(3)), integer(arg(4)), integer(arg(5)));
+        first <= date && date <= last
+    } else if function.eq(intern("diary-anniversary")) && args.len() >= 2 {
+        let (mut m, mut d) = (integer(arg(0)), integer(arg(1)));
+        let elapsed = if arg(2).is_nil() {
+            100
+        } else {
+            year - integer(arg(2))
+        };
+        if m == 2 && d == 29 && !is_leap_year(year) {
+            m = 3;
+            d = 1;
+        }
+        elapsed > 0 && m == month && d == day
+    } else if function.eq(intern("diary-cyclic")) && args.len() >= 4 {
+        let n = integer(arg(0));
+        if n <= 0 {
+            error!("Day count must be positive");
+        }
+        let start = absolute_from_gregorian(integer(arg(1)), integer(arg(2)), integer(arg(3)));
+        let elapsed = date - start;
+        elapsed >= 0 && elapsed % n == 0
+    } else if function.eq(intern("diary-float")) && args.len() >= 3 {
+        let dayname = integer(arg(1));
+        let n = integer(arg(2));
+        let base_day = if arg(3).is_nil() {
+            None
+        } else {
+            Some(integer(arg(3)))
+        };
+        if n == 0 || day_of_week(date) != dayname {
+            return false;
+        }
+        // The date is the Nth DAYNAME from a base date in a week that
+        // lies |N| - 1 weeks before or after it.  Try the months that
+        // week touches, plus one either side for days past their end.
+        let limit = nth_named_absday(-n, dayname, month, year, Some(day));
+        let (first_month, _, first_year) = gregorian_from_absolute(limit - 37);
+        let mut candidate = absolute_from_gregorian(first_month, 1, first_year);
+        while candidate <= limit + 37 {
+            let (m, _, y) = gregorian_from_absolute(candidate);
+            if field_matches(arg(0), m) && nth_named_absday(n, dayname, m, y, base_day) == date {
+                return true;
+            }
+            candidate += last_day_of_month(m, y);
+        }
+        false
+    } else {
+        unsupported_sexp(form)
+    }
+}
+
+/// Return non-nil if the diary sexp SEXP-SPEC applies to TIMESTAMP.
+/// TIMESTAMP is a date, either a list (MONTH DAY YEAR) or an absolute
+/// day number as returned by `calendar-absolute-from-gregorian'.
+///
+/// SEXP-SPEC may call `diary-date', `diary-block', `diary-anniversary',
+/// `diary-cyclic' and `diary-float' with constant arguments in the
+/// American order, MONTH DAY YEAR, and combine such calls with `and',
+/// `or' and `not'.  Other forms signal an error, so that the caller can
+/// fall back on evaluating them in Lisp.
+#[lisp_fn]
+pub fn org_date_matches_p(timestamp: LispObject, sexp_spec: LispObject) -> bool {
+    sexp_matches(sexp_spec, absolute_from_lisp(timestamp))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Unit {
+    Day,
+    Month,
+}
+
+/// Parse an Org repeater such as `+1w', `++2d' or `.+1m' into a step
+/// counted in days or months.
+fn parse_repeater(repeater: &[u8]) -> Option<(i64, Unit)> {
+    let digits = if repeater.starts_with(b"++") || repeater.starts_with(b".+") {
+        &repeater[2..]
+    } else if repeater.starts_with(b"+") {
+        &repeater[1..]
+    } else {
+        return None;
+    };
+    let (&unit, digits) = match digits.split_last() {
+        Some(split) => split,
+        None => return None,
+    };
+    if digits.is_empty() || digits.len() > 9 || !digits.iter().all(|&b| b'0' <= b && b <= b'9') {
+        return None;
+    }
+    let n = digits.iter().fold(0, |n, &b| n * 10 + i64::from(b - b'0'));
+    match unit {
+        b'd' => Some((n, Unit::Day)),
+        b'w' => Some((7 * n, Unit::Day)),
+        b'm' => Some((n, Unit::Month)),
+        b'y' => Some((12 * n, Unit::Month)),
+        _ => None,
+    }
+}
+
+/// The absolute dates from FROM to TO inclusive on which a timestamp at
+/// START repeating every STEP recurs, START itself included.
+fn repeat_dates(start: i64, step: i64, unit: Unit, from: i64, to: i64) -> Vec<i64> {
+    let mut dates = Vec::new();
+    if step <= 0 || to < start || to < from {
+        return dates;
+    }
+    match unit {
+        Unit::Day => {
+            let mut k = if from > start {
+                (from - start + step - 1) / step
+            } else {
+                0
+            };
+            while start + k * step <= to {
+                dates.push(start + k * step);
+                k += 1;
+            }
+        }
+        Unit::Month => {
+            // Like Org, add months to the month number and let days past
+            // the end of the month roll over.
+            let (month, day, year) = gregorian_from_absolute(start);
+            let (from_month, _, from_year) = gregorian_from_absolute(from);
+            let months_before = (from_year - year) * 12 + from_month - month;
+            let mut k = if months_before > 1 {
+                (months_before - 1) / step
+            } else {
+                0
+            };
+            loop {
+                let months = month - 1 + k * step;
+                let date = absolute_from_gregorian(
+                    floor_mod(months, 12) + 1,
+                    day,
+                    year + floor_div(months, 12),
+                );
+                if date > to {
+                    break;
+                }
+                if date >= from {
+                    dates.push(date);
+                }
+                k += 1;
+            }
+        }
+    }
+    dates
+}
+
+/// Return the dates from FROM to TO on which a timestamp at START with
+/// the Org repeater REPEATER occurs.
+/// REPEATER is a string like "+1w", "++2d", ".+1m" or "+1y"; the kind of
+/// repeater doesn't matter, only its interval.  START, FROM and TO are
+/// dates as for `org-date-matches-p'.  The value is a list of absolute
+/// day numbers in increasing order, including START if it lies between
+/// FROM and TO.  Like Org, a monthly or yearly repeat of a day that
+/// doesn't exist in some month falls on the following days.
+#[lisp_fn]
+pub fn org_date_repeat_expand(
+    start: LispObject,
+    repeater: LispObject,
+    from: LispObject,
+    to: LispObject,
+) -> LispObject {
+    let text = repeater.as_string_or_error();
+    let (step, unit) = match parse_repeater(text.as_slice()) {
+        Some(parsed) => parsed,
+        None => error!("Invalid repeater: {}", String::from_utf8_lossy(text.as_slice())),
+    };
+    let dates = repeat_dates(
+        absolute_from_lisp(start),
+        step,
+        unit,
+        absolute_from_lisp(from),
+        absolute_from_lisp(to),
+    );
+    dates.iter().rev().fold(LispObject::constant_nil(), |list, &date| {
+        LispObject::cons(LispObject::from_fixnum(date as EmacsInt), list)
+    })
+}
+
+#[test]
+fn test_gregorian_conversions() {
+    assert_eq!(absolute_from_gregorian(1, 1, 1), 1);
+    assert_eq!(absolute_from_gregorian(12, 31, 1), 365);
+    assert_eq!(absolute_from_gregorian(3, 1, 2000), 730_180);
+    assert_eq!(gregorian_from_absolute(730_180), (3, 1, 2000));
+    assert_eq!(gregorian_from_absolute(730_179), (2, 29, 2000));
+    assert_eq!(absolute_from_gregorian(2, 30, 2001), absolute_from_gregorian(3, 2, 2001));
+    for date in 700_000..740_000 {
+        let (m, d, y) = gregorian_from_absolute(date);
+        assert_eq!(absolute_from_gregorian(m, d, y), date);
+    }
+    // January 1, 2018 was a Monday.
+    assert_eq!(day_of_week(absolute_from_gregorian(1, 1, 2018)), 1);
+}
+
+#[test]
+fn test_repeat_dates() {
+    assert_eq!(parse_repeater(b"+1w"), Some((7, Unit::Day)));
+    assert_eq!(parse_repeater(b".+2m"), Some((2, Unit::Month)));
+    assert_eq!(parse_repeater(b"++1y"), Some((12, Unit::Month)));
+    assert_eq!(parse_repeater(b"+w"), None);
+    assert_eq!(parse_repeater(b"1d"), None);
+
+    assert_eq!(repeat_dates(10, 7, Unit::Day, 12, 40), vec![17, 24, 31, 38]);
+    assert_eq!(repeat_dates(10, 7, Unit::Day, 0, 10), vec![10]);
+    let start = absolute_from_gregorian(1, 31, 2018);
+    let dates = repeat_dates(
+        start,
+        1,
+        Unit::Month,
+        absolute_from_gregorian(2, 1, 2018),
+        absolute_from_gregorian(4, 30, 2018),
+    );
+    let dates: Vec<_> = dates.into_iter().map(gregorian_from_absolute).collect();
+    assert_eq!(dates, vec![(3, 3, 2018), (3, 31, 2018)]);
+}
+
+include!(concat!(env!("OUT_DIR"), "/orgdates_exports.rs"));
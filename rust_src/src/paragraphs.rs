@@ -0,0 +1,323 @@
+//! The scanning loops of the paragraph and sentence motion commands.
+//!
+//! These follow the Lisp code they replace in paragraphs.el step by
+//! step, calling the same regexp and motion primitives, so that every
+//! setting of `paragraph-start', `paragraph-separate', `sentence-end',
+//! `fill-prefix' and `use-hard-newlines' behaves as before.  What goes
+//! away is the interpretation of the loops themselves, which dominates
+//! in buffers with many short lines.
+
+use libc::c_char;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{build_string, record_unwind_protect, save_excursion_restore,
+                 save_excursion_save, unbind_to, EmacsInt, Fforward_line, Qnil};
+
+use cmds::{beginning_of_line, end_of_line, forward_char};
+use editfns::{bobp, bolp, eobp, goto_char, point, point_max, point_min};
+use eval_call::{specbind, specpdl_index};
+use indent::current_column;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use symbols::symbol_value;
+
+fn string(s: &[u8]) -> LispObject {
+    // S is NUL-terminated.
+    LispObject::from(unsafe { build_string(s.as_ptr() as *const c_char) })
+}
+
+fn variable(name: &str) -> LispObject {
+    symbol_value(intern(name))
+}
+
+fn position() -> EmacsInt {
+    point().as_fixnum_or_error()
+}
+
+fn goto(position: EmacsInt) {
+    goto_char(LispObject::from_fixnum(position));
+}
+
+fn forward_line(n: EmacsInt) {
+    unsafe { Fforward_line(LispObject::from_fixnum(n).to_raw()) };
+}
+
+fn looking_at(regexp: LispObject) -> bool {
+    call!(intern("looking-at"), regexp).is_not_nil()
+}
+
+fn re_search(function: &str, regexp: LispObject, bound: LispObject, noerror: LispObject) -> bool {
+    call!(intern(function), regexp, bound, noerror).is_not_nil()
+}
+
+fn move_to_left_margin() {
+    call!(intern("move-to-left-margin"));
+}
+
+fn is_hard_newline(position: EmacsInt) -> bool {
+    call!(
+        intern("get-text-property"),
+        LispObject::from_fixnum(position),
+        intern("hard")
+    ).is_not_nil()
+}
+
+/// Remove a leading `^' from REGEXP, so that it can match after the
+/// left margin.
+fn unanchor(regexp: LispObject) -> LispObject {
+    let string = regexp.as_string_or_error();
+    if string.len_bytes() > 0 && string.as_slice()[0] == b'^' {
+        call!(intern("substring"), regexp, LispObject::from_fixnum(1))
+    } else {
+        regexp
+    }
+}
+
+fn constrain_to_field(opoint: LispObject) -> LispObject {
+    call!(
+        intern("constrain-to-field"),
+        LispObject::constant_nil(),
+        opoint,
+        LispObject::constant_t()
+    )
+}
+
+/// Move forward over ARG paragraphs, or backward if ARG is negative.
+/// Return the number of paragraphs left to move.  This does the work of
+/// `forward-paragraph', which see.
+#[lisp_fn]
+pub fn forward_paragraph_internal(arg: LispObject) -> LispObject {
+    let mut arg = arg.as_fixnum_or_error();
+    let opoint = point();
+    let fill_prefix = variable("fill-prefix");
+    let fill_prefix_regexp = if fill_prefix.is_not_nil()
+        && fill_prefix.as_string_or_error().len_bytes() > 0
+        && variable("paragraph-ignore-fill-prefix").is_nil()
+    {
+        call!(intern("regexp-quote"), fill_prefix)
+    } else {
+        LispObject::constant_nil()
+    };
+    let parstart = unanchor(variable("paragraph-start"));
+    let mut parsep = unanchor(variable("paragraph-separate"));
+    if fill_prefix_regexp.is_not_nil() {
+        parsep = call!(
+            intern("concat"),
+            parsep,
+            string(b"\\|\0"),
+            fill_prefix_regexp,
+            string(b"[ \t]*$\0")
+        );
+    }
+    let sp_parstart = call!(
+        intern("concat"),
+        string(b"^[ \t]*\\(?:\0"),
+        parstart,
+        string(b"\\|\0"),
+        parsep,
+        string(b"\\)\0")
+    );
+    let use_hard_newlines = variable("use-hard-newlines").is_not_nil();
+    let nil = LispObject::constant_nil();
+    let mut start = 0;
+    let mut found_start = false;
+
+    while arg < 0 && bobp().is_nil() {
+        if !looking_at(parsep)
+            && re_search(
+                "re-search-backward",
+                string(b"^\n\0"),
+                LispObject::from_fixnum((position() - 1).max(point_min().as_fixnum_or_error())),
+                LispObject::constant_t(),
+            ) && looking_at(parsep)
+        {
+            arg += 1;
+            continue;
+        }
+        // Move back over paragraph-separating lines.
+        forward_char(LispObject::from_fixnum(-1));
+        beginning_of_line(nil);
+        while bobp().is_nil() && {
+            move_to_left_margin();
+            looking_at(parsep)
+        } {
+            forward_line(-1);
+        }
+        if bobp().is_not_nil() {
+            continue;
+        }
+        arg += 1;
+        // Go to end of the previous (non-separating) line.
+        end_of_line(nil);
+        // Search back for line that starts or separates paragraphs.
+        let found = if fill_prefix_regexp.is_not_nil() {
+            // There is a fill prefix; it overrides parstart.
+            while {
+                beginning_of_line(nil);
+                bobp().is_nil()
+            } && {
+                move_to_left_margin();
+                !looking_at(parsep)
+            } && looking_at(fill_prefix_regexp)
+            {
+                forward_line(-1);
+            }
+            move_to_left_margin();
+            bobp().is_nil()
+        } else {
+            while re_search("re-search-backward", sp_parstart, nil, LispObject::from_fixnum(1))
+                && {
+                    found_start = true;
+                    // Found a candidate, but need to check if it is a
+                    // REAL parstart.
+                    start = position();
+                    move_to_left_margin();
+                    !looking_at(parsep)
+                } && !(looking_at(parstart)
+                    && (!use_hard_newlines || bobp().is_not_nil() || is_hard_newline(start - 1)))
+            {
+                found_start = false;
+                goto(start);
+            }
+            found_start
+        };
+        if found {
+            // Move forward over paragraph separators.  We know this
+            // cannot reach the place we started because we know we
+            // moved back over a non-separator.
+            while eobp().is_nil() && {
+                move_to_left_margin();
+                looking_at(parsep)
+            } {
+                forward_line(1);
+            }
+            // If line before paragraph is just margin, back up to there.
+            end_of_line(LispObject::from_fixnum(0));
+            let left_margin = call!(intern("current-left-margin")).as_fixnum_or_error();
+            if current_column().as_fixnum_or_error() > left_margin {
+                forward_char(LispObject::from_fixnum(1));
+            } else {
+                call!(intern("skip-chars-backward"), string(b" \t\0"));
+                if bolp().is_nil() {
+                    forward_line(1);
+                }
+            }
+        } else {
+            // No starter or separator line => use buffer beg.
+            goto_char(point_min());
+        }
+    }
+
+    while arg > 0 && eobp().is_nil() {
+        // Move forward over separator lines...
+        while eobp().is_nil() && {
+            move_to_left_margin();
+            eobp().is_nil()
+        } && looking_at(parsep)
+        {
+            forward_line(1);
+        }
+        if eobp().is_nil() {
+            arg -= 1;
+        }
+        // ... and one more line.
+        forward_line(1);
+        if fill_prefix_regexp.is_not_nil() {
+            // There is a fill prefix; it overrides parstart.
+            while eobp().is_nil() && {
+                move_to_left_margin();
+                eobp().is_nil()
+            } && !looking_at(parsep) && looking_at(fill_prefix_regexp)
+            {
+                forward_line(1);
+            }
+        } else {
+            while re_search("re-search-forward", sp_parstart, nil, LispObject::from_fixnum(1))
+                && {
+                    start = call!(intern("match-beginning"), LispObject::from_fixnum(0))
+                        .as_fixnum_or_error();
+                    goto(start);
+                    eobp().is_nil()
+                } && {
+                    move_to_left_margin();
+                    !looking_at(parsep)
+                }
+                && (!looking_at(parstart) || (use_hard_newlines && !is_hard_newline(start - 1)))
+            {
+                forward_char(LispObject::from_fixnum(1));
+            }
+            if position() < point_max().as_fixnum_or_error() {
+                goto(start);
+            }
+        }
+    }
+    constrain_to_field(opoint);
+    // Return the number of steps that could not be done.
+    LispObject::from_fixnum(arg)
+}
+
+/// Return the position that calling FUNCTION with no arguments moves
+/// point to, leaving point where it was.
+fn excursion_position(function: &str) -> LispObject {
+    let count = specpdl_index();
+    unsafe { record_unwind_protect(save_excursion_restore, save_excursion_save()) };
+    call!(intern(function));
+    let position = point();
+    unsafe { unbind_to(count, Qnil) };
+    position
+}
+
+/// Move forward over ARG sentences, or backward if ARG is negative.
+/// This does the work of `forward-sentence', which see.
+#[lisp_fn]
+pub fn forward_sentence_internal(arg: LispObject) -> LispObject {
+    let mut arg = arg.as_fixnum_or_error();
+    let opoint = point();
+    let count = specpdl_index();
+    let sentence_end = call!(intern("sentence-end"));
+    specbind(intern("sentence-end").to_raw(), sentence_end.to_raw());
+    let t = LispObject::constant_t();
+
+    while arg < 0 {
+        let pos = position();
+        let (par_text_beg, par_beg) = {
+            let count = specpdl_index();
+            unsafe { record_unwind_protect(save_excursion_restore, save_excursion_save()) };
+            call!(intern("start-of-paragraph-text"));
+            // Start of real text in the paragraph.  We move back to here
+            // if we don't see a sentence-end.
+            let par_text_beg = point();
+            // Start of the first line of the paragraph.  We use this as
+            // the search limit to allow sentence-end to match if it is
+            // anchored at BOL and the paragraph starts indented.
+            beginning_of_line(LispObject::constant_nil());
+            let par_beg = point();
+            unsafe { unbind_to(count, Qnil) };
+            (par_text_beg, par_beg)
+        };
+        let match_end = || call!(intern("match-end"), LispObject::from_fixnum(0));
+        if re_search("re-search-backward", sentence_end, par_beg, t)
+            && (match_end().as_fixnum_or_error() < pos
+                || re_search("re-search-backward", sentence_end, par_beg, t))
+        {
+            goto_char(match_end());
+        } else {
+            goto_char(par_text_beg);
+        }
+        arg += 1;
+    }
+    while arg > 0 {
+        let par_end = excursion_position("end-of-paragraph-text");
+        if re_search("re-search-forward", sentence_end, par_end, t) {
+            call!(intern("skip-chars-backward"), string(b" \t\n\0"));
+        } else {
+            goto_char(par_end);
+        }
+        arg -= 1;
+    }
+    let result = constrain_to_field(opoint);
+    unsafe { unbind_to(count, result.to_raw()) };
+    result
+}
+
+include!(concat!(env!("OUT_DIR"), "/paragraphs_exports.rs"));
@@ -0,0 +1,724 @@
+//! Text and outlines of PDF documents.
+//!
+//! doc-view only shows pictures of the pages, and packages that search
+//! or annotate PDF files run the epdfinfo server to get at their text.
+//! The text of a page and the outline (bookmarks) of a document are
+//! extracted here with lopdf.  Text is taken from the text showing
+//! operators of the page content in the order they appear, decoded
+//! through the ToUnicode map of the font when it has one; text inside
+//! form XObjects is not extracted.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use lopdf::content::{Content, Operation};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use util::{expand_file_name_to_path, report_io_error};
+
+/// How many references or tree levels are followed before a document
+/// is taken to be cyclic.
+const MAX_DEPTH: usize = 32;
+
+/// Characters 0x80 to 0x9F of WinAnsiEncoding, 0 where undefined.
+const WIN_ANSI: [u16; 32] = [
+    0x20AC, 0, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0, 0x017D, 0, 0, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x02DC,
+    0x2122, 0x0161, 0x203A, 0x0153, 0, 0x017E, 0x0178,
+];
+
+/// Characters 0x80 to 0xA0 of PDFDocEncoding, 0 where undefined.
+const PDF_DOC: [u16; 33] = [
+    0x2022, 0x2020, 0x2021, 0x2026, 0x2014, 0x2013, 0x0192, 0x2044, 0x2039, 0x203A, 0x2212, 0x2030,
+    0x201E, 0x201C, 0x201D, 0x2018, 0x2019, 0x201A, 0x2122, 0xFB01, 0xFB02, 0x0141, 0x0152, 0x0160,
+    0x0178, 0x017D, 0x0131, 0x0142, 0x0153, 0x0161, 0x017E, 0, 0x20AC,
+];
+
+/// Decode the single-byte B, using TABLE for the bytes from 0x80.
+fn single_byte(b: u8, table: &[u16]) -> Option<char> {
+    if b < 0x80 || b as usize >= 0x80 + table.len() {
+        return Some(b as char);
+    }
+    match table[b as usize - 0x80] {
+        0 => None,
+        c => ::std::char::from_u32(u32::from(c)),
+    }
+}
+
+fn utf16_be(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| u16::from(pair[0]) << 8 | u16::from(pair[1]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Decode a PDF text string, as used for outline titles.
+fn text_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(b"\xfe\xff") {
+        utf16_be(&bytes[2..])
+    } else if bytes.starts_with(b"\xef\xbb\xbf") {
+        String::from_utf8_lossy(&bytes[3..]).into_owned()
+    } else {
+        bytes
+            .iter()
+            .filter_map(|&b| single_byte(b, &PDF_DOC))
+            .collect()
+    }
+}
+
+/// Follow OBJECT through indirect references.
+fn deref<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    let mut object = object;
+    for _ in 0..MAX_DEPTH {
+        match *object {
+            Object::Reference(id) => object = doc.objects.get(&id)?,
+            _ => return Some(object),
+        }
+    }
+    None
+}
+
+fn dict<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    match *deref(doc, object)? {
+        Object::Dictionary(ref d) => Some(d),
+        Object::Stream(ref s) => Some(&s.dict),
+        _ => None,
+    }
+}
+
+fn get<'a>(doc: &'a Document, dict: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    deref(doc, dict.get(key)?)
+}
+
+fn number(object: &Object) -> Option<f64> {
+    match *object {
+        Object::Integer(i) => Some(i as f64),
+        Object::Real(r) => Some(f64::from(r)),
+        _ => None,
+    }
+}
+
+fn stream_data(object: &Object) -> Option<Vec<u8>> {
+    match *object {
+        Object::Stream(ref s) => Some(
+            s.decompressed_content()
+                .unwrap_or_else(|| s.content.clone()),
+        ),
+        _ => None,
+    }
+}
+
+/// Look up KEY in PAGE or, as resources are inherited, in the nodes
+/// of the page tree above it.
+fn inherited<'a>(doc: &'a Document, page: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    let mut node = page;
+    for _ in 0..MAX_DEPTH {
+        if let Some(value) = get(doc, node, key) {
+            return Some(value);
+        }
+        node = dict(doc, node.get(b"Parent")?)?;
+    }
+    None
+}
+
+/// A token of a CMap file.
+#[derive(Debug, PartialEq)]
+enum CmapToken {
+    Hex(Vec<u8>),
+    Open,
+    Close,
+    Word(String),
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    match b {
+        b'0'...b'9' => Some(b - b'0'),
+        b'a'...b'f' => Some(b - b'a' + 10),
+        b'A'...b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn cmap_tokens(data: &[u8]) -> Vec<CmapToken> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'%' => while i < data.len() && data[i] != b'\n' {
+                i += 1;
+            },
+            b'<' if data.get(i + 1) == Some(&b'<') => i += 2,
+            b'>' if data.get(i + 1) == Some(&b'>') => i += 2,
+            b'<' => {
+                let mut digits = Vec::new();
+                i += 1;
+                while i < data.len() && data[i] != b'>' {
+                    if let Some(d) = hex_digit(data[i]) {
+                        digits.push(d);
+                    }
+                    i += 1;
+                }
+                if digits.len() % 2 == 1 {
+                    digits.push(0);
+                }
+                tokens.push(CmapToken::Hex(
+                    digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect(),
+                ));
+                i += 1;
+            }
+            b'(' => {
+                let mut depth = 0;
+                while i < data.len() {
+                    match data[i] {
+                        b'\\' => i += 1,
+                        b'(' => depth += 1,
+                        b')' => depth -= 1,
+                        _ => {}
+                    }
+                    i += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            b'[' => {
+                tokens.push(CmapToken::Open);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(CmapToken::Close);
+                i += 1;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0' | b'>' | b')' | b'{' | b'}' => i += 1,
+            _ => {
+                let start = i;
+                while i < data.len() && !b" \t\r\n\x0c\0%<>()[]{}/".contains(&data[i]) {
+                    i += 1;
+                }
+                if i == start {
+                    // A name; its slash is skipped and the rest read as a word.
+                    i += 1;
+                } else {
+                    let word = String::from_utf8_lossy(&data[start..i]).into_owned();
+                    tokens.push(CmapToken::Word(word));
+                }
+            }
+        }
+    }
+    tokens
+}
+
+fn code(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, &b| code << 8 | u32::from(b))
+}
+
+/// Parse the bfchar and bfrange mappings of the ToUnicode CMap DATA.
+fn parse_cmap(data: &[u8]) -> HashMap<u32, String> {
+    let tokens = cmap_tokens(data);
+    let mut map = HashMap::new();
+    let mut i = 0;
+    let mut in_char = false;
+    let mut in_range = false;
+    while i < tokens.len() {
+        match tokens[i] {
+            CmapToken::Word(ref word) => {
+                in_char = word == "beginbfchar";
+                in_range = word == "beginbfrange";
+                i += 1;
+            }
+            CmapToken::Hex(ref src) if in_char => {
+                if let Some(&CmapToken::Hex(ref dst)) = tokens.get(i + 1) {
+                    map.insert(code(src), utf16_be(dst));
+                }
+                i += 2;
+            }
+            CmapToken::Hex(ref low) if in_range => {
+                let high = match tokens.get(i + 1) {
+                    Some(&CmapToken::Hex(ref high)) => code(high),
+                    _ => break,
+                };
+                let low = code(low);
+                i += 2;
+                match tokens.get(i) {
+                    Some(&CmapToken::Hex(ref dst)) => {
+                        let mut units: Vec<u16> = dst.chunks(2)
+                            .filter(|pair| pair.len() == 2)
+                            .map(|pair| u16::from(pair[0]) << 8 | u16::from(pair[1]))
+                            .collect();
+                        for c in low..high.saturating_add(1).min(low.saturating_add(0x10000)) {
+                            map.insert(c, String::from_utf16_lossy(&units));
+                            if let Some(last) = units.last_mut() {
+                                *last = last.wrapping_add(1);
+                            }
+                        }
+                        i += 1;
+                    }
+                    Some(&CmapToken::Open) => {
+                        i += 1;
+                        let mut c = low;
+                        while let Some(&CmapToken::Hex(ref dst)) = tokens.get(i) {
+                            map.insert(c, utf16_be(dst));
+                            c += 1;
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    _ => {}
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    map
+}
+
+/// What is needed to decode the strings shown in a font.
+#[derive(Debug, Default)]
+struct Font {
+    /// Whether character codes are two bytes long, as in the CID-keyed
+    /// fonts of Type0 fonts.
+    two_byte: bool,
+    to_unicode: HashMap<u32, String>,
+}
+
+impl Font {
+    fn load(doc: &Document, font: &Dictionary) -> Font {
+        let two_byte = match get(doc, font, b"Subtype") {
+            Some(&Object::Name(ref subtype)) => subtype.as_slice() == b"Type0",
+            _ => false,
+        };
+        let to_unicode = get(doc, font, b"ToUnicode")
+            .and_then(stream_data)
+            .map_or_else(HashMap::new, |data| parse_cmap(&data));
+        Font {
+            two_byte,
+            to_unicode,
+        }
+    }
+
+    /// Append the text of BYTES to TEXT.  Codes of two-byte fonts that
+    /// have no Unicode mapping are dropped.
+    fn decode(&self, bytes: &[u8], text: &mut String) {
+        if self.two_byte {
+            for pair in bytes.chunks(2) {
+                if let Some(s) = self.to_unicode.get(&code(pair)) {
+                    text.push_str(s);
+                }
+            }
+        } else {
+            for &b in bytes {
+                match self.to_unicode.get(&u32::from(b)) {
+                    Some(s) => text.push_str(s),
+                    None => text.extend(single_byte(b, &WIN_ANSI)),
+                }
+            }
+        }
+    }
+}
+
+fn newline(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+/// Return the text shown by OPERATIONS, decoded with FONTS.  Lines are
+/// broken where the text moves vertically.
+fn operations_text(operations: &[Operation], fonts: &HashMap<Vec<u8>, Font>) -> String {
+    let default = Font::default();
+    let mut font = &default;
+    let mut text = String::new();
+    let mut line = None;
+    for op in operations {
+        let show = match op.operator.as_str() {
+            "Tf" => {
+                if let Some(&Object::Name(ref name)) = op.operands.get(0) {
+                    font = fonts.get(name).unwrap_or(&default);
+                }
+                None
+            }
+            "Td" | "TD" => {
+                if op.operands.get(1).and_then(number).map_or(false, |y| y != 0.0) {
+                    newline(&mut text);
+                }
+                None
+            }
+            "Tm" => {
+                if let Some(y) = op.operands.get(5).and_then(number) {
+                    if line.map_or(false, |last: f64| (last - y).abs() > 1.0) {
+                        newline(&mut text);
+                    }
+                    line = Some(y);
+                }
+                None
+            }
+            "T*" => {
+                newline(&mut text);
+                None
+            }
+            "Tj" | "TJ" => op.operands.get(0),
+            "'" => {
+                newline(&mut text);
+                op.operands.get(0)
+            }
+            "\"" => {
+                newline(&mut text);
+                op.operands.get(2)
+            }
+            _ => None,
+        };
+        match show {
+            Some(&Object::String(ref bytes, _)) => font.decode(bytes, &mut text),
+            Some(&Object::Array(ref items)) => for item in items {
+                match *item {
+                    Object::String(ref bytes, _) => font.decode(bytes, &mut text),
+                    // A large negative adjustment, in thousandths of
+                    // the font size, separates words.
+                    _ => if number(item).map_or(false, |n| n < -200.0) && !text.ends_with(' ') {
+                        text.push(' ');
+                    },
+                }
+            },
+            _ => {}
+        }
+    }
+    text.lines()
+        .map(|l| l.trim_right())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Return the text of the page PAGE_ID of DOC.
+fn page_text(doc: &Document, page_id: ObjectId) -> String {
+    let page = match doc.objects.get(&page_id).and_then(|p| dict(doc, p)) {
+        Some(page) => page,
+        None => return String::new(),
+    };
+    let mut data = Vec::new();
+    let contents: Vec<&Object> = match page.get(b"Contents").and_then(|c| deref(doc, c)) {
+        Some(&Object::Array(ref streams)) => streams.iter().collect(),
+        Some(stream) => vec![stream],
+        None => vec![],
+    };
+    for stream in contents {
+        if let Some(bytes) = deref(doc, stream).and_then(stream_data) {
+            data.extend(bytes);
+            data.push(b'\n');
+        }
+    }
+    let operations = match Content::decode(&data) {
+        Ok(content) => content.operations,
+        Err(_) => return String::new(),
+    };
+
+    let resources = inherited(doc, page, b"Resources")
+        .and_then(|r| dict(doc, r))
+        .and_then(|r| get(doc, r, b"Font"))
+        .and_then(|f| dict(doc, f));
+    let mut fonts = HashMap::new();
+    if let Some(resources) = resources {
+        for op in &operations {
+            if op.operator != "Tf" {
+                continue;
+            }
+            if let Some(&Object::Name(ref name)) = op.operands.get(0) {
+                if !fonts.contains_key(name) {
+                    if let Some(font) = get(doc, resources, name).and_then(|f| dict(doc, f)) {
+                        fonts.insert(name.clone(), Font::load(doc, font));
+                    }
+                }
+            }
+        }
+    }
+    operations_text(&operations, &fonts)
+}
+
+/// An entry of the outline of a document.
+#[derive(Debug, PartialEq)]
+struct OutlineEntry {
+    title: String,
+    /// The number of the page the entry leads to, counting from 1.
+    page: Option<u32>,
+    children: Vec<OutlineEntry>,
+}
+
+/// Look up NAME in the name tree NODE.
+fn name_tree_lookup<'a>(
+    doc: &'a Document,
+    node: &'a Dictionary,
+    name: &[u8],
+    depth: usize,
+) -> Option<&'a Object> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    if let Some(&Object::Array(ref pairs)) = get(doc, node, b"Names") {
+        for pair in pairs.chunks(2).filter(|pair| pair.len() == 2) {
+            if let Some(&Object::String(ref key, _)) = deref(doc, &pair[0]) {
+                if key.as_slice() == name {
+                    return deref(doc, &pair[1]);
+                }
+            }
+        }
+    }
+    if let Some(&Object::Array(ref kids)) = get(doc, node, b"Kids") {
+        for kid in kids {
+            let found = dict(doc, kid).and_then(|kid| name_tree_lookup(doc, kid, name, depth + 1));
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+    None
+}
+
+fn named_destination<'a>(
+    doc: &'a Document,
+    catalog: &'a Dictionary,
+    name: &[u8],
+) -> Option<&'a Object> {
+    if let Some(dests) = catalog.get(b"Dests").and_then(|d| dict(doc, d)) {
+        if let Some(dest) = get(doc, dests, name) {
+            return Some(dest);
+        }
+    }
+    let names = dict(doc, catalog.get(b"Names")?)?;
+    let tree = dict(doc, names.get(b"Dests")?)?;
+    name_tree_lookup(doc, tree, name, 0)
+}
+
+/// Return the page number that the outline ITEM leads to, either
+/// through its destination or through a GoTo action.
+fn destination_page(
+    doc: &Document,
+    catalog: &Dictionary,
+    pages: &HashMap<ObjectId, u32>,
+    item: &Dictionary,
+) -> Option<u32> {
+    let dest = match get(doc, item, b"Dest") {
+        Some(dest) => dest,
+        None => get(doc, dict(doc, item.get(b"A")?)?, b"D")?,
+    };
+    let dest = match *dest {
+        Object::String(ref name, _) | Object::Name(ref name) => {
+            named_destination(doc, catalog, name)?
+        }
+        _ => dest,
+    };
+    let array = match *dest {
+        Object::Array(ref array) => array,
+        Object::Dictionary(ref d) => match *get(doc, d, b"D")? {
+            Object::Array(ref array) => array,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match *array.first()? {
+        Object::Reference(id) => pages.get(&id).cloned(),
+        _ => None,
+    }
+}
+
+struct Outline<'a> {
+    doc: &'a Document,
+    catalog: &'a Dictionary,
+    pages: HashMap<ObjectId, u32>,
+    seen: HashSet<ObjectId>,
+}
+
+impl<'a> Outline<'a> {
+    /// Return the entries of the list starting at FIRST.
+    fn entries(&mut self, first: Option<&Object>, depth: usize) -> Vec<OutlineEntry> {
+        let mut entries = Vec::new();
+        let mut next = first.cloned();
+        while let Some(Object::Reference(id)) = next {
+            if depth > MAX_DEPTH || !self.seen.insert(id) {
+                break;
+            }
+            let doc = self.doc;
+            let item = match doc.objects.get(&id).and_then(|o| dict(doc, o)) {
+                Some(item) => item,
+                None => break,
+            };
+            let title = match get(doc, item, b"Title") {
+                Some(&Object::String(ref bytes, _)) => text_string(bytes),
+                _ => String::new(),
+            };
+            let page = destination_page(doc, self.catalog, &self.pages, item);
+            let children = self.entries(item.get(b"First"), depth + 1);
+            entries.push(OutlineEntry {
+                title,
+                page,
+                children,
+            });
+            next = item.get(b"Next").cloned();
+        }
+        entries
+    }
+}
+
+fn outline(doc: &Document) -> Vec<OutlineEntry> {
+    let catalog = match doc.trailer.get(b"Root").and_then(|r| dict(doc, r)) {
+        Some(catalog) => catalog,
+        None => return Vec::new(),
+    };
+    let first = match catalog.get(b"Outlines").and_then(|o| dict(doc, o)) {
+        Some(outlines) => outlines.get(b"First"),
+        None => return Vec::new(),
+    };
+    let mut outline = Outline {
+        doc,
+        catalog,
+        pages: doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect(),
+        seen: HashSet::new(),
+    };
+    outline.entries(first, 0)
+}
+
+lazy_static! {
+    /// The document read last, with its file name and modification
+    /// time, so that going through the pages one by one does not read
+    /// the file again for each of them.
+    static ref DOCUMENT: Mutex<Option<(PathBuf, SystemTime, Document)>> = Mutex::new(None);
+}
+
+/// Call F with the document in FILE.  F must not call Lisp, as the
+/// document is locked meanwhile.
+fn with_document<T, F: FnOnce(&Document) -> T>(file: LispObject, f: F) -> T {
+    let path = expand_file_name_to_path(file);
+    let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+        Ok(modified) => modified,
+        Err(err) => report_io_error(b"Opening PDF file\0", file, &err),
+    };
+    let result = {
+        let mut cached = DOCUMENT.lock().unwrap();
+        let stale = cached
+            .as_ref()
+            .map_or(true, |&(ref p, m, _)| *p != path || m != modified);
+        let loaded = if stale {
+            *cached = None;
+            match Document::load(&path) {
+                Ok(doc) => if doc.trailer.get(b"Encrypt").is_some() {
+                    Err("Encrypted PDF files are not supported".to_string())
+                } else {
+                    *cached = Some((path.clone(), modified, doc));
+                    Ok(())
+                },
+                Err(err) => Err(format!("Cannot read PDF file {}: {}", path.display(), err)),
+            }
+        } else {
+            Ok(())
+        };
+        loaded.map(|()| f(&cached.as_ref().unwrap().2))
+    };
+    match result {
+        Ok(value) => value,
+        Err(message) => error!("{}", message),
+    }
+}
+
+/// Return the text of page PAGE of the PDF file FILE.
+/// Pages are numbered from 1.  Lines are broken where the text moves
+/// down the page; text drawn by form XObjects and text in fonts that
+/// lack a Unicode mapping are left out.
+#[lisp_fn]
+pub fn pdf_page_text(file: LispObject, page: LispObject) -> LispObject {
+    let n = page.as_natnum_or_error() as u32;
+    let text = with_document(file, |doc| {
+        doc.get_pages()
+            .get(&n)
+            .map(|&page_id| page_text(doc, page_id))
+    });
+    match text {
+        Some(text) => lisp_string(&text),
+        None => args_out_of_range!(file, page),
+    }
+}
+
+fn outline_to_lisp(entries: &[OutlineEntry]) -> LispObject {
+    entries
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, entry| {
+            let page = entry.page.map_or_else(LispObject::constant_nil, |n| {
+                LispObject::from_natnum(EmacsInt::from(n))
+            });
+            let item = list!(
+                intern(":title"),
+                lisp_string(&entry.title),
+                intern(":page"),
+                page,
+                intern(":children"),
+                outline_to_lisp(&entry.children)
+            );
+            LispObject::cons(item, tail)
+        })
+}
+
+/// Return the outline of the PDF file FILE, or nil if it has none.
+/// The outline is a list of plists with the properties :title, :page
+/// (the number of the page the entry leads to, counting from 1, or nil
+/// if it leads elsewhere) and :children (the nested entries).
+#[lisp_fn]
+pub fn pdf_outline(file: LispObject) -> LispObject {
+    outline_to_lisp(&with_document(file, outline))
+}
+
+#[test]
+fn test_text_string() {
+    assert_eq!(text_string(b"\xfe\xff\x00A\x00\xe9"), "A\u{e9}");
+    assert_eq!(text_string(b"Caf\xe9 \x84 \x93x"), "Caf\u{e9} \u{2014} \u{fb01}x");
+}
+
+#[test]
+fn test_parse_cmap() {
+    let cmap = b"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CMapName /Adobe-Identity-UCS def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+2 beginbfchar
+<0003> <0020>
+<0011> <FB01>
+endbfchar
+2 beginbfrange
+<0024> <0026> <0041>
+<0030> <0031> [<0078> <00790079>]
+endbfrange
+endcmap";
+    let map = parse_cmap(cmap);
+    assert_eq!(map.get(&3).map(String::as_str), Some(" "));
+    assert_eq!(map.get(&0x11).map(String::as_str), Some("\u{fb01}"));
+    assert_eq!(map.get(&0x26).map(String::as_str), Some("C"));
+    assert_eq!(map.get(&0x31).map(String::as_str), Some("yy"));
+    assert_eq!(map.len(), 7);
+}
+
+#[test]
+fn test_operations_text() {
+    let content = Content::decode(
+        b"BT /F1 12 Tf 72 700 Td (Hello,) Tj [(wor) -20 (ld) -500 (again)] TJ
+          0 -14 Td (Second \\223line\\224) Tj T* (Third) ' ET",
+    ).unwrap();
+    let mut fonts = HashMap::new();
+    fonts.insert(b"F2".to_vec(), Font::default());
+    assert_eq!(
+        operations_text(&content.operations, &fonts),
+        "Hello,world again\nSecond \u{201c}line\u{201d}\nThird"
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/pdf_exports.rs"));
@@ -0,0 +1,498 @@
+//! Reading and writing Lisp data files in a checksummed binary format.
+//!
+//! A file holds the magic string `LISPDATA', a format version byte, one
+//! encoded object and the SHA-1 of everything before it.  Objects are
+//! encoded as a tag byte followed by their contents; integers, lengths
+//! and counts are variable length.  Strings are stored in Emacs'
+//! internal representation, so whatever they contain comes back
+//! unchanged, without going through a coding system.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+use libc::{c_char, ptrdiff_t};
+use sha1;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{build_string, make_specified_string, EmacsInt, Qerror};
+
+use hashtable::puthash;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use obarray::LispObarrayRef;
+use strings::lisp_string;
+use util::{expand_file_name_to_path, report_io_error};
+
+const MAGIC: &[u8] = b"LISPDATA";
+const VERSION: u8 = 1;
+const CHECKSUM_LEN: usize = 20;
+
+/// How deeply objects may nest.
+const MAX_DEPTH: usize = 4096;
+
+const TAG_NIL: u8 = b'n';
+const TAG_T: u8 = b't';
+const TAG_INTEGER: u8 = b'i';
+const TAG_FLOAT: u8 = b'f';
+const TAG_UNIBYTE_STRING: u8 = b'u';
+const TAG_MULTIBYTE_STRING: u8 = b'm';
+const TAG_SYMBOL: u8 = b'y';
+const TAG_LIST: u8 = b'l';
+const TAG_VECTOR: u8 = b'v';
+const TAG_HASH_TABLE: u8 = b'h';
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(data);
+    hasher.digest().bytes()
+}
+
+fn unsupported(object: LispObject) -> ! {
+    xsignal!(
+        Qerror,
+        LispObject::from(unsafe {
+            build_string(b"Object cannot be written as Lisp data\0".as_ptr() as *const c_char)
+        }),
+        object
+    );
+}
+
+fn corrupt() -> ! {
+    error!("Invalid Lisp data file");
+}
+
+struct Encoder {
+    data: Vec<u8>,
+}
+
+impl Encoder {
+    fn unsigned(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.data.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.data.push(n as u8);
+    }
+
+    fn string(&mut self, object: LispObject) {
+        let string = object.as_string_or_error();
+        self.data.push(if string.is_multibyte() {
+            TAG_MULTIBYTE_STRING
+        } else {
+            TAG_UNIBYTE_STRING
+        });
+        self.unsigned(string.len_bytes() as u64);
+        self.data.extend_from_slice(string.as_slice());
+    }
+
+    fn object(&mut self, object: LispObject, depth: usize) {
+        if depth > MAX_DEPTH {
+            error!("Lisp data nested too deeply");
+        }
+        if object.is_nil() {
+            self.data.push(TAG_NIL);
+        } else if object.is_t() {
+            self.data.push(TAG_T);
+        } else if let Some(n) = object.as_fixnum() {
+            // Zigzag encoding keeps small negative numbers short.
+            self.data.push(TAG_INTEGER);
+            let n = n as i64;
+            self.unsigned(((n << 1) ^ (n >> 63)) as u64);
+        } else if let Some(f) = object.as_float() {
+            self.data.push(TAG_FLOAT);
+            let bits = f.to_bits();
+            for i in 0..8 {
+                self.data.push((bits >> (8 * i)) as u8);
+            }
+        } else if object.is_string() {
+            self.string(object);
+        } else if let Some(symbol) = object.as_symbol() {
+            if !symbol.is_interned_in_initial_obarray() {
+                unsupported(object);
+            }
+            self.data.push(TAG_SYMBOL);
+            self.string(symbol.symbol_name());
+        } else if object.is_cons() {
+            self.list(object, depth);
+        } else if object.is_vector() {
+            let vector = object.as_vectorlike().and_then(|v| v.as_vector()).unwrap();
+            self.data.push(TAG_VECTOR);
+            self.unsigned(vector.len() as u64);
+            for &element in vector.as_slice() {
+                self.object(element, depth + 1);
+            }
+        } else if let Some(table) = object.as_hash_table() {
+            self.data.push(TAG_HASH_TABLE);
+            self.object(LispObject::from(table.test.name), depth + 1);
+            self.unsigned(table.iter().count() as u64);
+            for (key, value) in table.iter() {
+                self.object(key, depth + 1);
+                self.object(value, depth + 1);
+            }
+        } else {
+            unsupported(object);
+        }
+    }
+
+    /// Encode a list as its length, its elements and its final cdr,
+    /// which is nil unless the list is dotted.
+    fn list(&mut self, list: LispObject, depth: usize) {
+        let mut tails = list.iter_tails_safe();
+        let elements: Vec<LispObject> = tails.by_ref().map(|tail| tail.car()).collect();
+        let rest = tails.rest();
+        if rest.is_cons() {
+            ::lists::circular_list(list);
+        }
+        self.data.push(TAG_LIST);
+        self.unsigned(elements.len() as u64);
+        for element in elements {
+            self.object(element, depth + 1);
+        }
+        self.object(rest, depth + 1);
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> u8 {
+        match self.data.get(self.pos) {
+            Some(&byte) => {
+                self.pos += 1;
+                byte
+            }
+            None => corrupt(),
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        if len > self.data.len() - self.pos {
+            corrupt();
+        }
+        self.pos += len;
+        &self.data[self.pos - len..self.pos]
+    }
+
+    fn unsigned(&mut self) -> u64 {
+        let mut n = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte();
+            if shift > 63 {
+                corrupt();
+            }
+            n |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return n;
+            }
+        }
+    }
+
+    /// Read a length or count, which can't exceed the remaining data
+    /// since every element takes at least one byte.
+    fn count(&mut self) -> usize {
+        let n = self.unsigned();
+        if n > (self.data.len() - self.pos) as u64 {
+            corrupt();
+        }
+        n as usize
+    }
+
+    fn object(&mut self, depth: usize) -> LispObject {
+        if depth > MAX_DEPTH {
+            corrupt();
+        }
+        match self.byte() {
+            TAG_NIL => LispObject::constant_nil(),
+            TAG_T => LispObject::constant_t(),
+            TAG_INTEGER => {
+                let n = self.unsigned();
+                let n = ((n >> 1) as i64) ^ -((n & 1) as i64);
+                if LispObject::fixnum_overflow(n as EmacsInt) {
+                    corrupt();
+                }
+                LispObject::from_fixnum(n as EmacsInt)
+            }
+            TAG_FLOAT => {
+                let bits = self.bytes(8)
+                    .iter()
+                    .rev()
+                    .fold(0u64, |bits, &byte| bits << 8 | u64::from(byte));
+                LispObject::from_float(f64::from_bits(bits))
+            }
+            tag @ TAG_UNIBYTE_STRING | tag @ TAG_MULTIBYTE_STRING => {
+                let len = self.count();
+                let bytes = self.bytes(len);
+                LispObject::from(unsafe {
+                    make_specified_string(
+                        bytes.as_ptr() as *const c_char,
+                        -1,
+                        len as ptrdiff_t,
+                        tag == TAG_MULTIBYTE_STRING,
+                    )
+                })
+            }
+            TAG_SYMBOL => {
+                let name = self.object(depth + 1);
+                if !name.is_string() {
+                    corrupt();
+                }
+                LispObarrayRef::constant_obarray().intern(name)
+            }
+            TAG_LIST => {
+                let count = self.count();
+                let mut elements = LispObject::constant_nil();
+                let mut last: Option<::lisp::LispCons> = None;
+                for _ in 0..count {
+                    let cell = LispObject::cons(self.object(depth + 1), LispObject::constant_nil());
+                    match last {
+                        Some(last) => last.set_cdr(cell),
+                        None => elements = cell,
+                    }
+                    last = cell.as_cons();
+                }
+                let rest = self.object(depth + 1);
+                match last {
+                    Some(last) => last.set_cdr(rest),
+                    None => elements = rest,
+                }
+                elements
+            }
+            TAG_VECTOR => {
+                let count = self.count();
+                let vector = call!(
+                    intern("make-vector"),
+                    LispObject::from_natnum(count as EmacsInt),
+                    LispObject::constant_nil()
+                );
+                for i in 0..count {
+                    let element = self.object(depth + 1);
+                    let slots = vector.as_vectorlike().and_then(|v| v.as_vector()).unwrap();
+                    slots.as_mut_slice()[i] = element;
+                }
+                vector
+            }
+            TAG_HASH_TABLE => {
+                let test = self.object(depth + 1);
+                if !test.is_symbol() {
+                    corrupt();
+                }
+                let count = self.count();
+                let table = call!(
+                    intern("make-hash-table"),
+                    intern(":test"),
+                    test,
+                    intern(":size"),
+                    LispObject::from_natnum(count as EmacsInt)
+                );
+                for _ in 0..count {
+                    let key = self.object(depth + 1);
+                    let value = self.object(depth + 1);
+                    puthash(key, value, table);
+                }
+                table
+            }
+            _ => corrupt(),
+        }
+    }
+}
+
+/// Return true if `lisp-data-write' can write OBJECT.
+pub fn is_writable(object: LispObject) -> bool {
+    writable(object, 0)
+}
+
+fn writable(object: LispObject, depth: usize) -> bool {
+    if depth > MAX_DEPTH {
+        false
+    } else if object.is_nil() || object.is_t() || object.is_string() {
+        true
+    } else if object.as_fixnum().is_some() || object.as_float().is_some() {
+        true
+    } else if let Some(symbol) = object.as_symbol() {
+        symbol.is_interned_in_initial_obarray()
+    } else if object.is_cons() {
+        let mut tails = object.iter_tails_safe();
+        let elements_ok = tails.by_ref().all(|tail| writable(tail.car(), depth + 1));
+        let rest = tails.rest();
+        elements_ok && !rest.is_cons() && writable(rest, depth + 1)
+    } else if object.is_vector() {
+        let vector = object.as_vectorlike().and_then(|v| v.as_vector()).unwrap();
+        vector
+            .as_slice()
+            .iter()
+            .all(|&element| writable(element, depth + 1))
+    } else if let Some(table) = object.as_hash_table() {
+        table
+            .iter()
+            .all(|(key, value)| writable(key, depth + 1) && writable(value, depth + 1))
+    } else {
+        false
+    }
+}
+
+/// Return the contents of a Lisp data file holding OBJECT.
+pub fn encode(object: LispObject) -> Vec<u8> {
+    let mut encoder = Encoder {
+        data: MAGIC.to_vec(),
+    };
+    encoder.data.push(VERSION);
+    encoder.object(object, 0);
+    let sum = checksum(&encoder.data);
+    encoder.data.extend_from_slice(&sum);
+    encoder.data
+}
+
+/// Return the object held by DATA, the contents of a Lisp data file.
+pub fn decode(data: &[u8]) -> LispObject {
+    let header = MAGIC.len() + 1;
+    if data.len() < header + CHECKSUM_LEN || !data.starts_with(MAGIC) {
+        corrupt();
+    }
+    if data[MAGIC.len()] != VERSION {
+        error!(
+            "Lisp data file has unsupported version {}",
+            data[MAGIC.len()]
+        );
+    }
+    let (contents, sum) = data.split_at(data.len() - CHECKSUM_LEN);
+    if checksum(contents) != sum {
+        error!("Lisp data file is damaged");
+    }
+    let mut decoder = Decoder {
+        data: contents,
+        pos: header,
+    };
+    let object = decoder.object(0);
+    if decoder.pos != contents.len() {
+        corrupt();
+    }
+    object
+}
+
+/// Write the encoded DATA to PATH, going through a temporary file in the
+/// same directory so that PATH is replaced all at once.  PATH keeps the
+/// permissions it had.
+pub fn write_atomically(path: &PathBuf, data: &[u8]) -> io::Result<()> {
+    let mut temporary = path.clone().into_os_string();
+    temporary.push(".tmp");
+    let result = File::create(&temporary).and_then(|mut file| {
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    match result.and_then(|_| fs::rename(&temporary, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let _ = fs::remove_file(&temporary);
+            Err(err)
+        }
+    }
+}
+
+lazy_static! {
+    /// The writes started by `write_in_background' that may not be done
+    /// yet, with the names of their files.
+    static ref BACKGROUND_WRITES: Mutex<Vec<(String, JoinHandle<io::Result<()>>)>> =
+        Mutex::new(Vec::new());
+}
+
+/// Write DATA to PATH like `write_atomically', but on a separate thread.
+/// NAME is the file name to report if the write fails.  The earlier
+/// background writes are waited for first, so that writes to the same
+/// file happen in order.
+pub fn write_in_background(path: PathBuf, name: String, data: Vec<u8>) {
+    finish_background_writes();
+    let thread = thread::spawn(move || write_atomically(&path, &data));
+    BACKGROUND_WRITES.lock().unwrap().push((name, thread));
+}
+
+/// Wait for the background writes and return the ones that failed.
+fn join_background_writes() -> Vec<(String, io::Error)> {
+    let pending: Vec<_> = BACKGROUND_WRITES.lock().unwrap().drain(..).collect();
+    pending
+        .into_iter()
+        .filter_map(|(name, thread)| match thread.join() {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some((name, err)),
+            Err(_) => Some((
+                name,
+                io::Error::new(io::ErrorKind::Other, "Background write failed"),
+            )),
+        })
+        .collect()
+}
+
+/// Wait for the background writes and signal an error if one failed.
+pub fn finish_background_writes() {
+    let failures = join_background_writes();
+    if let Some(&(ref name, ref err)) = failures.first() {
+        report_io_error(b"Writing Lisp data\0", lisp_string(name), err);
+    }
+}
+
+/// Wait for the background writes when Emacs exits.  It is too late to
+/// report failures then, so they are ignored.
+#[no_mangle]
+pub extern "C" fn lisp_data_finish_writes() {
+    join_background_writes();
+}
+
+/// Write OBJECT to FILE in a binary format that `lisp-data-read' reads
+/// back.  OBJECT may be made of nil, t, integers, floats, strings,
+/// interned symbols, lists, vectors and hash tables.  Anything else, like
+/// functions, markers, buffers or uninterned symbols, signals an error
+/// before FILE is touched.  Text properties, the sharing of structure
+/// between parts of OBJECT and the weakness of hash tables are lost.
+///
+/// The data is written to a temporary file which then replaces FILE, so
+/// that FILE is never left half written, and it is checksummed so that a
+/// damaged file is detected when read.  Strings are saved as they are
+/// represented internally, so their text needs no encoding.
+#[lisp_fn]
+pub fn lisp_data_write(file: LispObject, object: LispObject) -> LispObject {
+    let data = encode(object);
+    let path = expand_file_name_to_path(file);
+    if let Err(err) = write_atomically(&path, &data) {
+        report_io_error(b"Writing Lisp data\0", file, &err);
+    }
+    LispObject::constant_nil()
+}
+
+/// Read the object stored in FILE by `lisp-data-write'.
+/// Signal an error if FILE is not in that format, was written by a newer
+/// version of Emacs or is damaged.
+#[lisp_fn]
+pub fn lisp_data_read(file: LispObject) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let mut data = Vec::new();
+    if let Err(err) = File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+        report_io_error(b"Reading Lisp data\0", file, &err);
+    }
+    decode(&data)
+}
+
+#[test]
+fn test_varint_round_trip() {
+    for &n in &[0u64, 1, 127, 128, 300, 1 << 40, u64::max_value()] {
+        let mut encoder = Encoder { data: Vec::new() };
+        encoder.unsigned(n);
+        let mut decoder = Decoder {
+            data: &encoder.data,
+            pos: 0,
+        };
+        assert_eq!(decoder.unsigned(), n);
+        assert_eq!(decoder.pos, encoder.data.len());
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/persist_exports.rs"));
@@ -0,0 +1,1056 @@
+//! Lisp object printer.
+//!
+//! The printed representation of an object is built in memory, in the
+//! internal multibyte encoding, and handed over to the C output routines
+//! in one piece.  Those know how to insert it into a buffer or at a
+//! marker, feed it to a function character by character, or show it in
+//! the echo area.  Objects without a readable syntax of their own, such
+//! as markers, windows and processes, are still described by the C
+//! printer.
+
+use std::f64;
+use std::sync::atomic::{AtomicIsize, Ordering, ATOMIC_ISIZE_INIT};
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{globals, make_string_from_bytes, maybe_quit, print_object_to_string,
+                 print_output_string, EmacsInt, PseudovecType};
+
+use hashtable::{gethash, hash_table_rehash_threshold, puthash, remhash, LispHashTableRef};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use multibyte::{char_string, raw_byte_codepoint, raw_byte_from_codepoint, Codepoint,
+                LispStringRef, MAX_5_BYTE_CHAR, MAX_MULTIBYTE_LENGTH};
+use vectors::{LispBoolVecRef, LispVectorlikeRef};
+
+/// Maximum nesting depth when `print-circle' is nil; anything deeper is
+/// assumed to be a circular structure.
+const PRINT_CIRCLE: usize = 200;
+
+/// Same as `DBL_DIG'.
+const DBL_DIG: usize = 15;
+
+/// Largest N already used for a #N= label.  Kept across calls when
+/// `print-continuous-numbering' is non-nil.
+static PRINT_NUMBER_INDEX: AtomicIsize = ATOMIC_ISIZE_INIT;
+
+fn number_table() -> LispObject {
+    LispObject::from(unsafe { globals.f_Vprint_number_table })
+}
+
+fn set_number_table(table: LispObject) {
+    unsafe { globals.f_Vprint_number_table = table.to_raw() };
+}
+
+/// Whether OBJ can be shared within a printed structure, so that it
+/// needs an entry in `print-number-table'.
+fn is_circle_candidate(obj: LispObject, gensym: bool) -> bool {
+    if obj.is_string() || obj.is_cons() {
+        return true;
+    }
+    if let Some(v) = obj.as_vectorlike() {
+        return v.is_vector() || v.is_pseudovector(PseudovecType::PVEC_COMPILED)
+            || v.is_pseudovector(PseudovecType::PVEC_CHAR_TABLE)
+            || v.is_pseudovector(PseudovecType::PVEC_SUB_CHAR_TABLE)
+            || v.is_pseudovector(PseudovecType::PVEC_HASH_TABLE)
+            || v.is_pseudovector(PseudovecType::PVEC_FONT)
+            || v.is_pseudovector(PseudovecType::PVEC_RECORD);
+    }
+    match obj.as_symbol() {
+        Some(sym) => gensym && !sym.is_interned_in_initial_obarray(),
+        None => false,
+    }
+}
+
+/// The Lisp objects contained in the vectorlike V, skipping the non-Lisp
+/// header of sub char-tables.
+fn vectorlike_elements(v: LispVectorlikeRef) -> Vec<LispObject> {
+    let slots = match v.as_vector() {
+        Some(vector) => vector.as_slice().to_vec(),
+        None => v.pseudovector_slots().to_vec(),
+    };
+    if v.is_pseudovector(PseudovecType::PVEC_SUB_CHAR_TABLE) {
+        slots[1..].to_vec()
+    } else {
+        slots
+    }
+}
+
+/// The runs of text properties of STRING, as (START END PLIST) triples.
+/// Runs without properties are left out.
+fn string_intervals(string: LispObject) -> Vec<(EmacsInt, EmacsInt, LispObject)> {
+    let len = string.as_string_or_error().len_chars() as EmacsInt;
+    let mut runs = Vec::new();
+    let mut pos = 0;
+    while pos < len {
+        let position = LispObject::from_natnum(pos);
+        let next = call!(intern("next-property-change"), position, string);
+        let end = next.as_fixnum().unwrap_or(len);
+        let plist = call!(intern("text-properties-at"), position, string);
+        if plist.is_not_nil() {
+            runs.push((pos, end, plist));
+        }
+        pos = end;
+    }
+    runs
+}
+
+/// Fills `print-number-table' with the objects that appear more than
+/// once in the object being printed.  An object is recorded with t the
+/// first time it is seen, and is given a negative label number the
+/// second time.
+struct Preprocessor {
+    circle: bool,
+    gensym: bool,
+    continuous: bool,
+    being_printed: Vec<LispObject>,
+}
+
+impl Preprocessor {
+    fn new() -> Self {
+        unsafe {
+            Preprocessor {
+                circle: LispObject::from(globals.f_Vprint_circle).is_not_nil(),
+                gensym: LispObject::from(globals.f_Vprint_gensym).is_not_nil(),
+                continuous: LispObject::from(globals.f_Vprint_continuous_numbering).is_not_nil(),
+                being_printed: Vec::new(),
+            }
+        }
+    }
+
+    fn preprocess(&mut self, obj: LispObject) {
+        // Without print-circle, only guard against infinite recursion.
+        if !self.circle {
+            if self.being_printed.len() >= PRINT_CIRCLE {
+                error!("Apparently circular structure being printed");
+            }
+            if self.being_printed.iter().any(|o| o.eq(obj)) {
+                return;
+            }
+        }
+        self.being_printed.push(obj);
+        self.preprocess_1(obj);
+        self.being_printed.pop();
+    }
+
+    fn preprocess_1(&mut self, mut obj: LispObject) {
+        let mut halftail = obj;
+        let mut loop_count = 0;
+
+        while is_circle_candidate(obj, self.gensym) {
+            let mut table = number_table();
+            if !table.is_hash_table() {
+                table = call!(intern("make-hash-table"), intern(":test"), intern("eq"));
+                set_number_table(table);
+            }
+
+            // With print-gensym but not print-circle, only symbols are
+            // recorded.
+            if self.circle || obj.is_symbol() {
+                let num = gethash(obj, table, LispObject::constant_nil());
+                let gensym = obj.as_symbol()
+                    .map_or(false, |sym| !sym.is_interned_in_initial_obarray());
+                if num.is_not_nil() || (self.continuous && gensym) {
+                    if !num.is_integer() {
+                        let index = PRINT_NUMBER_INDEX.fetch_add(1, Ordering::SeqCst) + 1;
+                        // A negative number means it has not been printed yet.
+                        puthash(obj, LispObject::from_fixnum(-index as EmacsInt), table);
+                    }
+                    return;
+                }
+                puthash(obj, LispObject::constant_t(), table);
+            }
+
+            if obj.is_string() {
+                // Text properties can be circular too.
+                for (_, _, plist) in string_intervals(obj) {
+                    self.preprocess(plist);
+                }
+                return;
+            }
+
+            if let Some(cons) = obj.as_cons() {
+                if loop_count > 0 && obj.eq(halftail) {
+                    return;
+                }
+                self.preprocess(cons.car());
+                obj = cons.cdr();
+                loop_count += 1;
+                if loop_count & 1 == 0 {
+                    halftail = halftail.as_cons().map_or(halftail, |c| c.cdr());
+                }
+                continue;
+            }
+
+            if let Some(v) = obj.as_vectorlike() {
+                for elt in vectorlike_elements(v) {
+                    self.preprocess(elt);
+                }
+                if let Some(table) = obj.as_hash_table() {
+                    self.preprocess(table.get_key_and_value());
+                }
+            }
+            return;
+        }
+    }
+}
+
+/// Return true if NAME could be read as a number, so that printing it
+/// as a symbol needs a leading backslash.  Names containing periods
+/// could be numbers too, but periods are always escaped.
+fn is_confusing_symbol_name(name: &[u8]) -> bool {
+    let digits = match name.first() {
+        Some(&b'-') | Some(&b'+') => &name[1..],
+        _ => name,
+    };
+    match (digits.first(), digits.last()) {
+        (Some(&first), Some(&last)) if is_digit(first) && is_digit(last) => digits
+            .iter()
+            .all(|&b| is_digit(b) || b == b'e' || b == b'E'),
+        _ => false,
+    }
+}
+
+fn is_digit(b: u8) -> bool {
+    b >= b'0' && b <= b'9'
+}
+
+fn is_hex_digit(c: Codepoint) -> bool {
+    (c >= '0' as Codepoint && c <= '9' as Codepoint)
+        || (c >= 'a' as Codepoint && c <= 'f' as Codepoint)
+        || (c >= 'A' as Codepoint && c <= 'F' as Codepoint)
+}
+
+/// Characters that need a backslash in a printed symbol name.
+fn symbol_char_needs_escape(c: Codepoint) -> bool {
+    c <= 0o40 || (c < 0x80 && b"\"\\';#(),.`[]?".contains(&(c as u8)))
+}
+
+/// Parse a `float-output-format' spec of the form %.NNe, %.NNf or %.NNg.
+/// Return the precision, or -1 if it is omitted, or None if the spec is
+/// not one we accept.
+fn float_format_precision(spec: &[u8]) -> Option<i32> {
+    if spec.len() < 3 || spec[0] != b'%' || spec[1] != b'.' {
+        return None;
+    }
+    let mut rest = &spec[2..];
+    let mut width = -1;
+    if !rest.is_empty() && is_digit(rest[0]) {
+        width = 0;
+        while !rest.is_empty() && is_digit(rest[0]) {
+            width = width * 10 + (rest[0] - b'0') as i32;
+            if width > DBL_DIG as i32 {
+                return None;
+            }
+            rest = &rest[1..];
+        }
+        // A precision of zero is valid only for %f.
+        if width == 0 && rest.first() != Some(&b'f') {
+            return None;
+        }
+    }
+    match rest {
+        b"e" | b"f" | b"g" => Some(width),
+        _ => None,
+    }
+}
+
+/// Make sure there is a decimal point with a digit after it, or an
+/// exponent, so that S reads back as a float.
+fn ensure_decimal_point(s: &mut String) {
+    let tail = s.trim_left_matches(|c: char| c.is_digit(10) || c == '-').to_string();
+    if tail == "." {
+        s.push('0');
+    } else if tail.is_empty() {
+        s.push_str(".0");
+    }
+}
+
+/// Format X with the fewest digits that read back as the same value,
+/// the way `%g' would with the smallest sufficient precision.  Like
+/// `dtoastr', try no fewer than `DBL_DIG' digits for normal numbers.
+fn shortest_float(x: f64) -> String {
+    let sci = format!("{:e}", x.abs());
+    let epos = sci.find('e').unwrap();
+    let digits: String = sci[..epos].chars().filter(|&c| c != '.').collect();
+    let exp: i32 = sci[epos + 1..].parse().unwrap();
+    let min_precision = if x.abs() < f64::MIN_POSITIVE { 1 } else { DBL_DIG };
+    let precision = digits.len().max(min_precision) as i32;
+
+    let mut out = String::new();
+    if x.is_sign_negative() {
+        out.push('-');
+    }
+    if exp < -4 || exp >= precision {
+        out.push_str(&digits[..1]);
+        if digits.len() > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push_str(&format!("e{}{:02}", if exp < 0 { '-' } else { '+' }, exp.abs()));
+    } else if exp >= 0 {
+        let int_len = exp as usize + 1;
+        if digits.len() <= int_len {
+            out.push_str(&digits);
+            for _ in digits.len()..int_len {
+                out.push('0');
+            }
+        } else {
+            out.push_str(&digits[..int_len]);
+            out.push('.');
+            out.push_str(&digits[int_len..]);
+        }
+    } else {
+        out.push_str("0.");
+        for _ in 0..(-exp - 1) {
+            out.push('0');
+        }
+        out.push_str(&digits);
+    }
+    out
+}
+
+/// The printed representation of the float X, following
+/// `float-output-format'.
+fn float_to_string(x: f64) -> String {
+    if x.is_infinite() {
+        return if x > 0.0 { "1.0e+INF" } else { "-1.0e+INF" }.to_string();
+    }
+    if x.is_nan() {
+        return if x.is_sign_negative() { "-0.0e+NaN" } else { "0.0e+NaN" }.to_string();
+    }
+
+    let format = LispObject::from(unsafe { globals.f_Vfloat_output_format });
+    let precision = format
+        .as_string()
+        .and_then(|spec| float_format_precision(spec.as_slice()));
+    let mut out = match precision {
+        Some(_) => {
+            let formatted = call!(intern("format"), format, LispObject::from_float(x));
+            String::from_utf8_lossy(formatted.as_string_or_error().as_slice()).into_owned()
+        }
+        None => shortest_float(x),
+    };
+    // "%.0f" is allowed not to produce a decimal point.
+    if precision != Some(0) {
+        ensure_decimal_point(&mut out);
+    }
+    out
+}
+
+/// Builds the printed representation of an object.
+struct Printer {
+    escape: bool,
+    escape_newlines: bool,
+    escape_control: bool,
+    escape_nonascii: bool,
+    escape_multibyte: bool,
+    quoted: bool,
+    circle: bool,
+    gensym: bool,
+    level: Option<EmacsInt>,
+    length: Option<EmacsInt>,
+    /// The objects being printed, outermost first.  Only compared
+    /// against when `print-circle' is nil.
+    being_printed: Vec<LispObject>,
+    /// Nesting depth of backquotes, within which commas are printed
+    /// with their shorthand.
+    backquote: isize,
+    bytes: Vec<u8>,
+    chars: usize,
+}
+
+impl Printer {
+    fn new(escape: bool) -> Self {
+        unsafe {
+            let level = LispObject::from(globals.f_Vprint_level);
+            let length = LispObject::from(globals.f_Vprint_length);
+            Printer {
+                escape,
+                escape_newlines: globals.f_print_escape_newlines,
+                escape_control: globals.f_print_escape_control_characters,
+                escape_nonascii: globals.f_print_escape_nonascii,
+                escape_multibyte: globals.f_print_escape_multibyte,
+                quoted: globals.f_print_quoted,
+                circle: LispObject::from(globals.f_Vprint_circle).is_not_nil(),
+                gensym: LispObject::from(globals.f_Vprint_gensym).is_not_nil(),
+                level: level.as_fixnum(),
+                // Negative values of print-length are invalid; treat them
+                // like nil.
+                length: if length.is_natnum() {
+                    length.as_fixnum()
+                } else {
+                    None
+                },
+                being_printed: Vec::new(),
+                backquote: 0,
+                bytes: Vec::new(),
+                chars: 0,
+            }
+        }
+    }
+
+    /// Adjust the escape settings the way output into PRINTCHARFUN
+    /// would: a unibyte buffer cannot hold multibyte characters and a
+    /// multibyte buffer cannot hold unibyte non-ASCII text unchanged.
+    fn for_output(mut self, printcharfun: LispObject) -> Self {
+        let buffer = match printcharfun.as_marker() {
+            Some(marker) => marker.buffer(),
+            None => printcharfun.as_buffer(),
+        };
+        if let Some(buffer) = buffer {
+            if LispObject::from(buffer.enable_multibyte_characters).is_nil() {
+                self.escape_multibyte = true;
+            } else {
+                self.escape_nonascii = true;
+            }
+        }
+        self
+    }
+
+    fn push_char(&mut self, c: Codepoint) {
+        if c < 0x80 {
+            self.bytes.push(c as u8);
+        } else {
+            let mut buf = [0; MAX_MULTIBYTE_LENGTH];
+            let len = char_string(c, buf.as_mut_ptr()) as usize;
+            self.bytes.extend_from_slice(&buf[..len]);
+        }
+        self.chars += 1;
+    }
+
+    fn push_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.push_char(c as Codepoint);
+        }
+    }
+
+    /// Output the contents of STRING unchanged, with unibyte non-ASCII
+    /// bytes turned into raw-byte characters.
+    fn push_lisp_string(&mut self, string: LispStringRef) {
+        let multibyte = string.is_multibyte();
+        for c in string.chars() {
+            if !multibyte && c >= 0x80 {
+                self.push_char(raw_byte_codepoint(c as u8));
+            } else {
+                self.push_char(c);
+            }
+        }
+    }
+
+    fn into_string(self) -> LispObject {
+        LispObject::from(unsafe {
+            make_string_from_bytes(
+                self.bytes.as_ptr() as *const c_char,
+                self.chars as ptrdiff_t,
+                self.bytes.len() as ptrdiff_t,
+            )
+        })
+    }
+
+    /// Print OBJ from the top, setting up `print-number-table' first if
+    /// `print-circle' or `print-gensym' asks for it.
+    fn print(&mut self, obj: LispObject) {
+        self.backquote = 0;
+
+        let continuous = LispObject::from(unsafe { globals.f_Vprint_continuous_numbering });
+        if continuous.is_nil() || number_table().is_nil() {
+            PRINT_NUMBER_INDEX.store(0, Ordering::SeqCst);
+            set_number_table(LispObject::constant_nil());
+        }
+
+        if self.gensym || self.circle {
+            Preprocessor::new().preprocess(obj);
+
+            // Objects which appear only once need no label.
+            let table = number_table();
+            if let Some(h) = table.as_hash_table() {
+                let once: Vec<LispObject> = h.iter()
+                    .filter(|&(_, status)| status.is_t())
+                    .map(|(key, _)| key)
+                    .collect();
+                for key in once {
+                    remhash(key, table);
+                }
+            }
+        }
+
+        self.being_printed.clear();
+        self.print_object(obj);
+    }
+
+    fn print_object(&mut self, obj: LispObject) {
+        unsafe { maybe_quit() };
+
+        if !self.circle {
+            // Simple but incomplete detection of circularities.
+            if self.being_printed.len() >= PRINT_CIRCLE {
+                error!("Apparently circular structure being printed");
+            }
+            if let Some(depth) = self.being_printed.iter().position(|o| o.eq(obj)) {
+                self.push_str(&format!("#{}", depth));
+                return;
+            }
+        } else if is_circle_candidate(obj, self.gensym) {
+            let table = number_table();
+            let num = if table.is_hash_table() {
+                gethash(obj, table, LispObject::constant_nil())
+            } else {
+                LispObject::constant_nil()
+            };
+            if let Some(n) = num.as_fixnum() {
+                if n > 0 {
+                    // Already printed, refer back to it.
+                    self.push_str(&format!("#{}#", n));
+                    return;
+                }
+                self.push_str(&format!("#{}=", -n));
+                puthash(obj, LispObject::from_fixnum(-n), table);
+            }
+        }
+
+        self.being_printed.push(obj);
+        if let Some(n) = obj.as_fixnum() {
+            self.push_str(&n.to_string());
+        } else if let Some(x) = obj.as_float() {
+            self.push_str(&float_to_string(x));
+        } else if let Some(string) = obj.as_string() {
+            if self.escape {
+                self.print_escaped_string(obj);
+            } else {
+                self.push_lisp_string(string);
+            }
+        } else if obj.is_symbol() {
+            self.print_symbol(obj);
+        } else if obj.is_cons() {
+            self.print_cons(obj);
+        } else if !self.print_vectorlike(obj) {
+            let string = unsafe { print_object_to_string(obj.to_raw(), self.escape) };
+            self.push_lisp_string(LispObject::from(string).as_string_or_error());
+        }
+        self.being_printed.pop();
+    }
+
+    fn print_escaped_string(&mut self, mut obj: LispObject) {
+        let has_intervals = !obj.as_string_or_error().intervals.is_null();
+        let charset_property = LispObject::from(unsafe { globals.f_Vprint_charset_text_property });
+        if has_intervals && !charset_property.is_t() {
+            // Leave out the `charset' properties decoding added.
+            obj = call!(intern("copy-sequence"), obj);
+            let len = LispObject::from_natnum(obj.as_string_or_error().len_chars() as EmacsInt);
+            call!(
+                intern("remove-text-properties"),
+                LispObject::from_natnum(0),
+                len,
+                list!(intern("charset"), LispObject::constant_nil()),
+                obj
+            );
+        }
+
+        let string = obj.as_string_or_error();
+        let multibyte = string.is_multibyte();
+        // True means the next character must not be taken as part of a
+        // hex escape.
+        let mut need_nonhex = false;
+
+        if has_intervals {
+            self.push_str("#(");
+        }
+        self.push_char('"' as Codepoint);
+        for c in string.chars() {
+            if multibyte && c > MAX_5_BYTE_CHAR {
+                // A raw 8-bit byte in a multibyte string.
+                self.push_str(&format!("\\{:03o}", raw_byte_from_codepoint(c)));
+                need_nonhex = false;
+            } else if !multibyte && c >= 0x80 && self.escape_nonascii {
+                self.push_str(&format!("\\{:03o}", c));
+                need_nonhex = false;
+            } else if multibyte && c >= 0x80 && self.escape_multibyte {
+                self.push_str(&format!("\\x{:04x}", c));
+                need_nonhex = true;
+            } else {
+                let mut still_need_nonhex = false;
+                if is_hex_digit(c) {
+                    if need_nonhex {
+                        self.push_str("\\ ");
+                    }
+                    self.push_char(c);
+                } else if c == '\n' as Codepoint && self.escape_newlines {
+                    self.push_str("\\n");
+                } else if c == '\x0c' as Codepoint && self.escape_newlines {
+                    self.push_str("\\f");
+                } else if c == 0 && self.escape_control {
+                    self.push_str("\\0");
+                    still_need_nonhex = true;
+                } else if c == '"' as Codepoint || c == '\\' as Codepoint {
+                    self.push_char('\\' as Codepoint);
+                    self.push_char(c);
+                } else if self.escape_control && (c < 0x20 || c == 0x7f) {
+                    self.push_str(&format!("\\{:03o}", c));
+                } else {
+                    self.push_char(c);
+                }
+                need_nonhex = still_need_nonhex;
+            }
+        }
+        self.push_char('"' as Codepoint);
+
+        if has_intervals {
+            let escape = self.escape;
+            self.escape = true;
+            for (start, end, plist) in string_intervals(obj) {
+                self.push_str(&format!(" {} {} ", start, end));
+                self.print_object(plist);
+            }
+            self.escape = escape;
+            self.push_char(')' as Codepoint);
+        }
+    }
+
+    fn print_symbol(&mut self, obj: LispObject) {
+        let sym = obj.as_symbol().unwrap();
+        let name = sym.symbol_name().as_string_or_error();
+        let mut confusing = is_confusing_symbol_name(name.as_slice());
+
+        if self.gensym && !sym.is_interned_in_initial_obarray() {
+            self.push_str("#:");
+        } else if name.len_bytes() == 0 {
+            self.push_str("##");
+            return;
+        }
+
+        for c in name.chars() {
+            if self.escape && (confusing || symbol_char_needs_escape(c)) {
+                self.push_char('\\' as Codepoint);
+                confusing = false;
+            }
+            self.push_char(c);
+        }
+    }
+
+    fn print_cons(&mut self, obj: LispObject) {
+        if let Some(level) = self.level {
+            if self.being_printed.len() as EmacsInt > level {
+                self.push_str("...");
+                return;
+            }
+        }
+
+        let cons = obj.as_cons().unwrap();
+        let (head, rest) = (cons.car(), cons.cdr());
+        if self.quoted {
+            if let Some(rest) = rest.as_cons() {
+                if rest.cdr().is_nil() && self.print_quote_shorthand(head, rest.car()) {
+                    return;
+                }
+            }
+        }
+
+        self.push_char('(' as Codepoint);
+        self.print_list_elements(obj);
+        self.push_char(')' as Codepoint);
+    }
+
+    /// Print (HEAD ARG) using the reader shorthand for `quote',
+    /// `function', backquote or comma, if HEAD is one of those.
+    fn print_quote_shorthand(&mut self, head: LispObject, arg: LispObject) -> bool {
+        if head.eq(intern("quote")) {
+            self.push_char('\'' as Codepoint);
+            self.print_object(arg);
+        } else if head.eq(intern("function")) {
+            self.push_str("#'");
+            self.print_object(arg);
+        } else if head.eq(intern("`")) {
+            self.push_char('`' as Codepoint);
+            self.backquote += 1;
+            self.print_object(arg);
+            self.backquote -= 1;
+        } else if self.backquote > 0
+            && (head.eq(intern(",")) || head.eq(intern(",@")) || head.eq(intern(",.")))
+        {
+            let escape = self.escape;
+            self.escape = false;
+            self.print_object(head);
+            self.escape = escape;
+            self.backquote -= 1;
+            self.print_object(arg);
+            self.backquote += 1;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn print_list_elements(&mut self, list: LispObject) {
+        let mut tail = list;
+        let mut halftail = list;
+        let mut i: EmacsInt = 0;
+
+        while let Some(cons) = tail.as_cons() {
+            if i != 0 {
+                if !self.circle {
+                    // Simple but incomplete detection of a circular list.
+                    if tail.eq(halftail) {
+                        self.push_str(&format!(" . #{}", i / 2));
+                        return;
+                    }
+                } else if number_table().is_hash_table()
+                    && gethash(tail, number_table(), LispObject::constant_nil()).is_integer()
+                {
+                    // A shared tail gets its own label.
+                    self.push_str(" . ");
+                    self.print_object(tail);
+                    return;
+                }
+                self.push_char(' ' as Codepoint);
+            }
+
+            if self.length.map_or(false, |length| length <= i) {
+                self.push_str("...");
+                return;
+            }
+
+            i += 1;
+            self.print_object(cons.car());
+            tail = cons.cdr();
+            if i & 1 == 0 {
+                halftail = halftail.as_cons().map_or(halftail, |c| c.cdr());
+            }
+        }
+
+        // A non-nil TAIL is the end of a dotted list.
+        if tail.is_not_nil() {
+            self.push_str(" . ");
+            self.print_object(tail);
+        }
+    }
+
+    /// Print the elements of a vector-like object between brackets or
+    /// parentheses, honoring `print-length'.
+    fn print_elements(&mut self, elements: &[LispObject], close: char) {
+        let shown = match self.length {
+            Some(length) if (length as usize) < elements.len() => length as usize,
+            _ => elements.len(),
+        };
+        for (i, &elt) in elements[..shown].iter().enumerate() {
+            if i > 0 {
+                self.push_char(' ' as Codepoint);
+            }
+            self.print_object(elt);
+        }
+        if shown < elements.len() {
+            self.push_str(" ...");
+        }
+        self.push_char(close as Codepoint);
+    }
+
+    /// Print the vector-like objects that have a read syntax.  Return
+    /// false if OBJ is not one of them.
+    fn print_vectorlike(&mut self, obj: LispObject) -> bool {
+        let v = match obj.as_vectorlike() {
+            Some(v) => v,
+            None => return false,
+        };
+
+        if let Some(bool_vector) = v.as_bool_vector() {
+            self.print_bool_vector(bool_vector);
+        } else if let Some(table) = obj.as_hash_table() {
+            self.print_hash_table(obj, table);
+        } else if v.is_pseudovector(PseudovecType::PVEC_RECORD) {
+            self.push_str("#s(");
+            self.print_elements(&vectorlike_elements(v), ')');
+        } else if let Some(sub) = v.as_sub_char_table() {
+            // Make each lowest sub char-table start a new line, to keep
+            // lines from getting extremely long.
+            if sub.depth == 3 {
+                self.push_char('\n' as Codepoint);
+            }
+            self.push_str(&format!("#^^[{} {}", sub.depth, sub.min_char));
+            let elements = vectorlike_elements(v);
+            if !elements.is_empty() {
+                self.push_char(' ' as Codepoint);
+            }
+            self.print_elements(&elements, ']');
+        } else if v.is_vector() || v.is_pseudovector(PseudovecType::PVEC_COMPILED)
+            || v.is_pseudovector(PseudovecType::PVEC_CHAR_TABLE)
+        {
+            if v.is_pseudovector(PseudovecType::PVEC_COMPILED) {
+                self.push_str("#[");
+            } else if v.is_pseudovector(PseudovecType::PVEC_CHAR_TABLE) {
+                self.push_str("#^[");
+            } else {
+                self.push_char('[' as Codepoint);
+            }
+            self.print_elements(&vectorlike_elements(v), ']');
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn print_bool_vector(&mut self, bool_vector: LispBoolVecRef) {
+        let bytes = bool_vector.as_bytes();
+        let shown = match self.length {
+            Some(length) if (length as usize) < bytes.len() => length as usize,
+            _ => bytes.len(),
+        };
+
+        self.push_str(&format!("#&{}\"", bool_vector.len()));
+        for &b in &bytes[..shown] {
+            if b == b'\n' && self.escape_newlines {
+                self.push_str("\\n");
+            } else if b == b'\x0c' && self.escape_newlines {
+                self.push_str("\\f");
+            } else if b > 0o177 {
+                // Use octal escapes to avoid encoding issues.
+                self.push_str(&format!("\\{:o}", b));
+            } else {
+                if b == b'"' || b == b'\\' {
+                    self.push_char('\\' as Codepoint);
+                }
+                self.push_char(b as Codepoint);
+            }
+        }
+        if shown < bytes.len() {
+            self.push_str(" ...");
+        }
+        self.push_char('"' as Codepoint);
+    }
+
+    /// Print TABLE in its read syntax, for example
+    /// #s(hash-table size 2 test equal data (k1 v1 k2 v2)).
+    fn print_hash_table(&mut self, obj: LispObject, table: LispHashTableRef) {
+        self.push_str(&format!("#s(hash-table size {}", table.size()));
+
+        let test = LispObject::from(table.test.name);
+        if test.is_not_nil() {
+            self.push_str(" test ");
+            self.print_object(test);
+        }
+        let weakness = table.get_weak();
+        if weakness.is_not_nil() {
+            self.push_str(" weakness ");
+            self.print_object(weakness);
+        }
+        self.push_str(" rehash-size ");
+        self.print_object(call!(intern("hash-table-rehash-size"), obj));
+        self.push_str(" rehash-threshold ");
+        self.print_object(hash_table_rehash_threshold(obj));
+        if table.pure_ {
+            self.push_str(" purecopy ");
+            self.print_object(LispObject::constant_t());
+        }
+
+        self.push_str(" data (");
+        let size = table.size();
+        let shown = match self.length {
+            Some(length) if (length as usize) < size => length as usize,
+            _ => size,
+        };
+        for idx in 0..shown {
+            if let Some((key, value)) = table.get_slot(idx as isize) {
+                if idx > 0 {
+                    self.push_char(' ' as Codepoint);
+                }
+                self.print_object(key);
+                self.push_char(' ' as Codepoint);
+                self.print_object(value);
+            }
+        }
+        if shown < size {
+            self.push_str(" ...");
+        }
+        self.push_str("))");
+    }
+}
+
+/// Print OBJECT to PRINTCHARFUN, defaulting to `standard-output'.  With
+/// NEWLINES, surround it with newlines as `print' does.
+fn print_to(object: LispObject, printcharfun: LispObject, escape: bool, newlines: bool) {
+    let printcharfun = if printcharfun.is_nil() {
+        LispObject::from(unsafe { globals.f_Vstandard_output })
+    } else {
+        printcharfun
+    };
+
+    let string = if object.is_string() && !escape && !newlines {
+        object
+    } else {
+        let mut printer = Printer::new(escape).for_output(printcharfun);
+        if newlines {
+            printer.push_char('\n' as Codepoint);
+        }
+        printer.print(object);
+        if newlines {
+            printer.push_char('\n' as Codepoint);
+        }
+        printer.into_string()
+    };
+    unsafe { print_output_string(string.to_raw(), printcharfun.to_raw()) };
+}
+
+/// Output the printed representation of OBJECT, any Lisp object.
+/// Quoting characters are printed when needed to make output that `read'
+/// can handle, whenever this is possible.  For complex objects, the behavior
+/// is controlled by `print-level' and `print-length', which see.
+///
+/// OBJECT is any of the Lisp data types: a number, a string, a symbol,
+/// a list, a buffer, a window, a frame, etc.
+///
+/// A printed representation of an object is text which describes that object.
+///
+/// Optional argument PRINTCHARFUN is the output stream, which can be one
+/// of these:
+///
+///    - a buffer, in which case output is inserted into that buffer at point;
+///    - a marker, in which case output is inserted at marker's position;
+///    - a function, in which case that function is called once for each
+///      character of OBJECT's printed representation;
+///    - a symbol, in which case that symbol's function definition is called; or
+///    - t, in which case the output is displayed in the echo area.
+///
+/// If PRINTCHARFUN is omitted, the value of `standard-output' (which see)
+/// is used instead.
+#[lisp_fn(min = "1")]
+pub fn prin1(object: LispObject, printcharfun: LispObject) -> LispObject {
+    print_to(object, printcharfun, true, false);
+    object
+}
+
+/// Return a string containing the printed representation of OBJECT.
+/// OBJECT can be any Lisp object.  This function outputs quoting characters
+/// when necessary to make output that `read' can handle, whenever possible,
+/// unless the optional second argument NOESCAPE is non-nil.  For complex objects,
+/// the behavior is controlled by `print-level' and `print-length', which see.
+///
+/// OBJECT is any of the Lisp data types: a number, a string, a symbol,
+/// a list, a buffer, a window, a frame, etc.
+///
+/// A printed representation of an object is text which describes that object.
+#[lisp_fn(min = "1")]
+pub fn prin1_to_string(object: LispObject, noescape: LispObject) -> LispObject {
+    let mut printer = Printer::new(noescape.is_nil());
+    // The result is multibyte, so unibyte non-ASCII text is escaped.
+    printer.escape_nonascii = true;
+    printer.print(object);
+    printer.into_string()
+}
+
+/// Output the printed representation of OBJECT, any Lisp object.
+/// No quoting characters are used; no delimiters are printed around
+/// the contents of strings.
+///
+/// OBJECT is any of the Lisp data types: a number, a string, a symbol,
+/// a list, a buffer, a window, a frame, etc.
+///
+/// A printed representation of an object is text which describes that object.
+///
+/// Optional argument PRINTCHARFUN is the output stream, which can be one
+/// of these:
+///
+///    - a buffer, in which case output is inserted into that buffer at point;
+///    - a marker, in which case output is inserted at marker's position;
+///    - a function, in which case that function is called once for each
+///      character of OBJECT's printed representation;
+///    - a symbol, in which case that symbol's function definition is called; or
+///    - t, in which case the output is displayed in the echo area.
+///
+/// If PRINTCHARFUN is omitted, the value of `standard-output' (which see)
+/// is used instead.
+#[lisp_fn(min = "1")]
+pub fn princ(object: LispObject, printcharfun: LispObject) -> LispObject {
+    print_to(object, printcharfun, false, false);
+    object
+}
+
+/// Output the printed representation of OBJECT, with newlines around it.
+/// Quoting characters are printed when needed to make output that `read'
+/// can handle, whenever this is possible.  For complex objects, the behavior
+/// is controlled by `print-level' and `print-length', which see.
+///
+/// OBJECT is any of the Lisp data types: a number, a string, a symbol,
+/// a list, a buffer, a window, a frame, etc.
+///
+/// A printed representation of an object is text which describes that object.
+///
+/// Optional argument PRINTCHARFUN is the output stream, which can be one
+/// of these:
+///
+///    - a buffer, in which case output is inserted into that buffer at point;
+///    - a marker, in which case output is inserted at marker's position;
+///    - a function, in which case that function is called once for each
+///      character of OBJECT's printed representation;
+///    - a symbol, in which case that symbol's function definition is called; or
+///    - t, in which case the output is displayed in the echo area.
+///
+/// If PRINTCHARFUN is omitted, the value of `standard-output' (which see)
+/// is used instead.
+#[lisp_fn(min = "1")]
+pub fn print(object: LispObject, printcharfun: LispObject) -> LispObject {
+    print_to(object, printcharfun, true, true);
+    object
+}
+
+/// Extract sharing info from OBJECT needed to print it.
+/// Fills `print-number-table'.
+#[lisp_fn(name = "print--preprocess", c_name = "print_preprocess")]
+pub fn print_preprocess(object: LispObject) -> LispObject {
+    PRINT_NUMBER_INDEX.store(0, Ordering::SeqCst);
+    Preprocessor::new().preprocess(object);
+    LispObject::constant_nil()
+}
+
+#[test]
+fn test_confusing_symbol_names() {
+    assert!(is_confusing_symbol_name(b"12"));
+    assert!(is_confusing_symbol_name(b"-1"));
+    assert!(is_confusing_symbol_name(b"2e10"));
+    assert!(!is_confusing_symbol_name(b"-"));
+    assert!(!is_confusing_symbol_name(b"1+"));
+    assert!(!is_confusing_symbol_name(b"e1"));
+    assert!(!is_confusing_symbol_name(b""));
+}
+
+#[test]
+fn test_shortest_float() {
+    assert_eq!(shortest_float(0.0), "0");
+    assert_eq!(shortest_float(-0.0), "-0");
+    assert_eq!(shortest_float(1.5), "1.5");
+    assert_eq!(shortest_float(100.0), "100");
+    assert_eq!(shortest_float(0.1), "0.1");
+    assert_eq!(shortest_float(0.0001), "0.0001");
+    assert_eq!(shortest_float(0.00001), "1e-05");
+    assert_eq!(shortest_float(1e20), "1e+20");
+    assert_eq!(shortest_float(123456789012345.0), "123456789012345");
+    assert_eq!(shortest_float(1234567890123456.0), "1234567890123456");
+    assert_eq!(shortest_float(12345678901234567890.0), "1.2345678901234567e+19");
+    assert_eq!(shortest_float(-2.5e-300), "-2.5e-300");
+}
+
+#[test]
+fn test_float_format_precision() {
+    assert_eq!(float_format_precision(b"%.3g"), Some(3));
+    assert_eq!(float_format_precision(b"%.0f"), Some(0));
+    assert_eq!(float_format_precision(b"%.e"), Some(-1));
+    assert_eq!(float_format_precision(b"%.0g"), None);
+    assert_eq!(float_format_precision(b"%.16g"), None);
+    assert_eq!(float_format_precision(b"%5.2f"), None);
+    assert_eq!(float_format_precision(b"%.2d"), None);
+}
+
+#[test]
+fn test_ensure_decimal_point() {
+    let check = |s: &str, expected: &str| {
+        let mut s = s.to_string();
+        ensure_decimal_point(&mut s);
+        assert_eq!(s, expected);
+    };
+    check("100", "100.0");
+    check("-3", "-3.0");
+    check("1.", "1.0");
+    check("1.5", "1.5");
+    check("1e+20", "1e+20");
+}
+
+include!(concat!(env!("OUT_DIR"), "/printer_exports.rs"));
@@ -1,17 +1,38 @@
 //! Functions operating on process.
 
+use libc::{c_char, c_int, ptrdiff_t};
+#[cfg(unix)]
+use libc;
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::fs::{self, File};
+#[cfg(target_os = "linux")]
+use std::io::Read;
+#[cfg(unix)]
+use std::{mem, ptr};
+
 use remacs_macros::lisp_fn;
-use remacs_sys::{BoolBF, EmacsInt, Lisp_Process, QCbuffer, Qcdr, Qclosed, Qexit, Qlistp, Qnetwork,
-                 Qopen, Qpipe, Qrun, Qserial, Qstop, Vprocess_alist};
-use remacs_sys::{get_process as cget_process, pget_kill_without_query, pget_pid,
-                 pget_raw_status_new, pset_kill_without_query, send_process,
+use remacs_sys::{pid_t, BoolBF, EmacsInt, Lisp_Process, QCbuffer, Qcdr, Qclosed, Qexit,
+                 Qinhibit_read_only, Qinterrupt_process_functions, Qlambda, Qlistp, Qnetwork,
+                 Qnil, Qopen, Qpipe, Qreal, Qrun, Qserial, Qstop, Qt, Vprocess_alist};
+use remacs_sys::{emacs_get_tty_pgrp, process_continued, set_connection_stopped};
+#[cfg(unix)]
+use remacs_sys::{block_child_signal, unblock_child_signal};
+#[cfg(not(unix))]
+use remacs_sys::sys_kill;
+use remacs_sys::{get_process as cget_process, pget_accumulation, pget_alive, pget_infd,
+                 pget_kill_without_query, pget_pid, pget_pty_flag, pget_raw_status_new,
+                 pset_accumulation, pset_kill_without_query, send_process,
                  setup_process_coding_systems, update_status, Fmapcar, STRING_BYTES};
+use remacs_sys::{concat2, del_range_byte, record_unwind_current_buffer, set_buffer_internal,
+                 unbind_to, Fsubstring};
 
-use lisp::{ExternalPtr, LispObject};
+use lisp::{intern, ExternalPtr, LispObject};
 use lisp::defsubr;
 
 use buffers::get_buffer;
-use lists::{assoc, cdr, plist_put};
+use eval_call::{specbind, specpdl_index};
+use lists::{assoc, assq, cdr, plist_put};
 
 pub type LispProcessRef = ExternalPtr<Lisp_Process>;
 
@@ -56,6 +77,11 @@ impl LispProcessRef {
         LispObject::from(self.buffer)
     }
 
+    #[inline]
+    fn process_type(&self) -> LispObject {
+        LispObject::from(self.process_type)
+    }
+
     #[inline]
     fn set_plist(&mut self, plist: LispObject) {
         self.plist = plist.to_raw();
@@ -151,6 +177,19 @@ pub fn process_tty_name(process: LispObject) -> LispObject {
     process.as_process_or_error().tty_name()
 }
 
+/// Return the file name of the pty PROCESS talks through, such as
+/// "/dev/pts/3", or nil if PROCESS communicates through pipes.
+/// Network, serial and pipe processes never have a pty.
+#[lisp_fn]
+pub fn process_pty_name(process: LispObject) -> LispObject {
+    let p = process.as_process_or_error();
+    if unsafe { pget_pty_flag(p.as_ptr()) } {
+        p.tty_name()
+    } else {
+        LispObject::constant_nil()
+    }
+}
+
 /// Return the command that was executed to start PROCESS.  This is a
 /// list of strings, the first string being the program executed and
 /// the rest of the strings being the arguments given to it.  For a
@@ -320,4 +359,642 @@ pub fn set_process_query_on_exit_flag(process: LispObject, flag: LispObject) ->
     flag
 }
 
+/// Output held back by native accumulation is handed to the filter once
+/// it grows past this many bytes, even if more is on its way.
+const ACCUMULATION_LIMIT: usize = 64 * 1024;
+
+/// Enable or disable native output accumulation for PROCESS.
+/// If FLAG is non-nil, output from PROCESS is assembled natively before
+/// it reaches the process filter: while the process keeps writing, its
+/// output is held back and handed over in large pieces that end at a
+/// line boundary.  Whatever has been received is handed over as soon
+/// as the process pauses or exits, so prompts are not delayed.
+///
+/// If MAX-LINES is a natural number, the process buffer is also trimmed
+/// after each call to the filter, deleting lines from its beginning so
+/// that at most MAX-LINES complete lines remain.
+///
+/// If FLAG is nil, accumulation is disabled.  In that case, return any
+/// output that was being held back, or nil if there is none.
+#[lisp_fn(min = "2")]
+pub fn set_process_native_accumulation(
+    process: LispObject,
+    flag: LispObject,
+    max_lines: LispObject,
+) -> LispObject {
+    let mut p = process.as_process_or_error();
+    let old = LispObject::from(unsafe { pget_accumulation(p.as_ptr()) });
+    let pending = LispObject::from(old.as_cons().map(|acc| acc.cdr()));
+
+    if flag.is_nil() {
+        unsafe { pset_accumulation(p.as_mut(), Qnil) };
+        return pending;
+    }
+    if max_lines.is_not_nil() {
+        max_lines.as_natnum_or_error();
+    }
+    let acc = LispObject::cons(max_lines, pending);
+    unsafe { pset_accumulation(p.as_mut(), acc.to_raw()) };
+    LispObject::constant_nil()
+}
+
+/// Combine TEXT, freshly decoded output of PROC, with the output held
+/// back so far, and return what should be passed to the filter now, or
+/// nil.  MORE is true if the process is probably still writing.
+#[no_mangle]
+pub extern "C" fn accumulate_process_output(
+    proc: LispObject,
+    text: LispObject,
+    more: bool,
+) -> LispObject {
+    let p = proc.as_process_or_error();
+    let acc = match LispObject::from(unsafe { pget_accumulation(p.as_ptr()) }).as_cons() {
+        Some(acc) => acc,
+        None => return text,
+    };
+
+    let text = match acc.cdr().into_option() {
+        Some(pending) => LispObject::from(unsafe { concat2(pending.to_raw(), text.to_raw()) }),
+        None => text,
+    };
+    acc.set_cdr(LispObject::constant_nil());
+    if !more {
+        return text;
+    }
+
+    let s = text.as_string_or_error();
+    let bytes = s.as_slice();
+    if bytes.len() < ACCUMULATION_LIMIT {
+        acc.set_cdr(text);
+        return LispObject::constant_nil();
+    }
+    match bytes.iter().rposition(|&b| b == b'\n') {
+        Some(i) if i + 1 < bytes.len() => {
+            let head = &bytes[..i + 1];
+            let nchars = if s.is_multibyte() {
+                head.iter().filter(|&&b| b & 0xc0 != 0x80).count()
+            } else {
+                head.len()
+            };
+            let split = LispObject::from_natnum(nchars as EmacsInt);
+            let nil = LispObject::constant_nil();
+            unsafe {
+                acc.set_cdr(LispObject::from(
+                    Fsubstring(text.to_raw(), split.to_raw(), nil.to_raw()),
+                ));
+                LispObject::from(Fsubstring(
+                    text.to_raw(),
+                    LispObject::from_natnum(0).to_raw(),
+                    split.to_raw(),
+                ))
+            }
+        }
+        _ => text,
+    }
+}
+
+/// Delete lines from the beginning of the buffer of PROC so that at
+/// most the number of complete lines given to
+/// `set-process-native-accumulation' remain.
+#[no_mangle]
+pub extern "C" fn trim_process_scrollback(proc: LispObject) {
+    let p = proc.as_process_or_error();
+    let max_lines = match LispObject::from(unsafe { pget_accumulation(p.as_ptr()) })
+        .as_cons()
+        .map(|acc| acc.car())
+    {
+        Some(n) if n.is_natnum() => n.as_natnum_or_error() as usize,
+        _ => return,
+    };
+    let mut buffer = match p.buffer().as_buffer() {
+        Some(b) if b.is_live() => b,
+        _ => return,
+    };
+
+    // Find the start of the oldest line to keep, scanning backwards.
+    let beg = buffer.beg_byte();
+    let mut pos = buffer.z_byte();
+    let mut newlines = 0;
+    while pos > beg {
+        pos -= 1;
+        if buffer.fetch_byte(pos) == b'\n' {
+            newlines += 1;
+            if newlines > max_lines {
+                let count = specpdl_index();
+                unsafe {
+                    record_unwind_current_buffer();
+                    specbind(Qinhibit_read_only, Qt);
+                    set_buffer_internal(buffer.as_mut());
+                    del_range_byte(beg, pos + 1);
+                    unbind_to(count, Qnil);
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Return DIMENSION, a window height or width, or signal an error if
+/// it doesn't fit in the 'unsigned short' all known platforms use.
+fn window_dimension(dimension: LispObject) -> u16 {
+    let n = dimension.as_fixnum_or_error();
+    if n < 0 || n > EmacsInt::from(u16::max_value()) {
+        args_out_of_range!(
+            dimension,
+            LispObject::from_natnum(0),
+            LispObject::from_natnum(EmacsInt::from(u16::max_value()))
+        );
+    }
+    n as u16
+}
+
+#[cfg(unix)]
+fn set_pty_window_size(fd: c_int, height: u16, width: u16) -> bool {
+    let size = libc::winsize {
+        ws_row: height,
+        ws_col: width,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // The kernel sends SIGWINCH to the foreground process group of the
+    // terminal when the size actually changes.
+    unsafe { libc::ioctl(fd, libc::TIOCSWINSZ as _, &size) >= 0 }
+}
+
+#[cfg(not(unix))]
+fn set_pty_window_size(_fd: c_int, _height: u16, _width: u16) -> bool {
+    false
+}
+
+/// Tell PROCESS that it has logical window size WIDTH by HEIGHT.
+/// Value is t if PROCESS was successfully told about the window size,
+/// nil otherwise.
+///
+/// If PROCESS runs in a pty, the programs in its foreground process
+/// group receive a SIGWINCH signal when the size changes.  Emacs calls
+/// this automatically whenever a window showing the process buffer is
+/// resized; see `window-adjust-process-window-size-function'.
+#[lisp_fn]
+pub fn set_process_window_size(
+    process: LispObject,
+    height: LispObject,
+    width: LispObject,
+) -> LispObject {
+    let p = process.as_process_or_error();
+    let height = window_dimension(height);
+    let width = window_dimension(width);
+    let fd = unsafe { pget_infd(p.as_ptr()) };
+
+    let is_netconn = LispObject::from(p.process_type).eq(LispObject::from(Qnetwork));
+    LispObject::from_bool(!is_netconn && fd >= 0 && set_pty_window_size(fd, height, width))
+}
+
+/// Open an available pty, returning the file descriptor of its master
+/// side, with the close-on-exec and non-blocking flags set.  Store into
+/// PTY_NAME, a buffer of SIZE bytes, the file name of the terminal
+/// corresponding to the pty.  Return -1 on failure.
+#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn allocate_pty(pty_name: *mut c_char, size: ptrdiff_t) -> c_int {
+    let mut master: c_int = -1;
+    let mut slave: c_int = -1;
+    unsafe {
+        // openpty may fork a helper to set the permissions of the
+        // terminal; keep our SIGCHLD handler from reaping it.
+        let mut blocked: libc::sigset_t = mem::zeroed();
+        let mut oldset: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut blocked);
+        libc::sigaddset(&mut blocked, libc::SIGCHLD);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &blocked, &mut oldset);
+        let result = libc::openpty(
+            &mut master,
+            &mut slave,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        );
+        libc::pthread_sigmask(libc::SIG_SETMASK, &oldset, ptr::null_mut());
+        if result < 0 {
+            return -1;
+        }
+
+        let named = libc::ttyname_r(slave, pty_name, size as libc::size_t) == 0;
+        libc::close(slave);
+        if !named {
+            libc::close(master);
+            return -1;
+        }
+
+        libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+        let flags = libc::fcntl(master, libc::F_GETFL);
+        libc::fcntl(master, libc::F_SETFL, flags | libc::O_NONBLOCK);
+    }
+    master
+}
+
+#[cfg(not(unix))]
+#[no_mangle]
+pub extern "C" fn allocate_pty(_pty_name: *mut c_char, _size: ptrdiff_t) -> c_int {
+    -1
+}
+
+/// Make the terminal PTY_NAME the controlling terminal of the calling
+/// process, which has just been forked and has FD open on that
+/// terminal.  Return the descriptor the child should use for the
+/// terminal, or -1 on failure.
+///
+/// This runs between vfork and exec, so it must not allocate memory or
+/// touch any Lisp data.
+#[cfg(unix)]
+#[no_mangle]
+pub extern "C" fn pty_child_setup(fd: c_int, pty_name: *const c_char) -> c_int {
+    unsafe {
+        // First, disconnect from the current controlling terminal.
+        libc::setsid();
+        if fd >= 0 {
+            libc::ioctl(fd, libc::TIOCSCTTY as _, 0);
+        }
+        reopen_pty(fd, pty_name)
+    }
+}
+
+#[cfg(not(unix))]
+#[no_mangle]
+pub extern "C" fn pty_child_setup(fd: c_int, _pty_name: *const c_char) -> c_int {
+    fd
+}
+
+/// Linux only makes the terminal the controlling terminal of a session
+/// leader reliably when it is opened again after `setsid'.
+#[cfg(target_os = "linux")]
+unsafe fn reopen_pty(fd: c_int, pty_name: *const c_char) -> c_int {
+    if fd >= 0 {
+        libc::close(fd);
+    }
+    libc::open(pty_name, libc::O_RDWR)
+}
+
+/// macOS and the BSDs honor TIOCSCTTY, so the descriptor can be kept.
+#[cfg(all(unix, not(target_os = "linux")))]
+unsafe fn reopen_pty(fd: c_int, pty_name: *const c_char) -> c_int {
+    if fd >= 0 {
+        fd
+    } else {
+        let fd = libc::open(pty_name, libc::O_RDWR);
+        if fd >= 0 {
+            libc::ioctl(fd, libc::TIOCSCTTY as _, 0);
+        }
+        fd
+    }
+}
+
+#[cfg(unix)]
+use libc::{SIGINT, SIGKILL};
+
+// As in nt/inc/ms-w32.h.
+#[cfg(not(unix))]
+const SIGINT: c_int = 2;
+#[cfg(not(unix))]
+const SIGKILL: c_int = 9;
+
+#[cfg(unix)]
+const SIGTSTP: Option<c_int> = Some(libc::SIGTSTP);
+#[cfg(unix)]
+const SIGCONT: Option<c_int> = Some(libc::SIGCONT);
+#[cfg(not(unix))]
+const SIGTSTP: Option<c_int> = None;
+#[cfg(not(unix))]
+const SIGCONT: Option<c_int> = None;
+
+/// Return the parent of each process on the system, keyed by pid.
+#[cfg(target_os = "linux")]
+fn system_process_parents() -> HashMap<pid_t, pid_t> {
+    let mut parents = HashMap::new();
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return parents,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let pid = match entry.file_name().to_str().and_then(|n| n.parse().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let mut stat = String::new();
+        if File::open(entry.path().join("stat"))
+            .and_then(|mut f| f.read_to_string(&mut stat))
+            .is_err()
+        {
+            continue;
+        }
+        if let Some(ppid) = stat_parent(&stat) {
+            parents.insert(pid, ppid);
+        }
+    }
+    parents
+}
+
+/// Return the parent pid recorded in STAT, the contents of a
+/// /proc/PID/stat file.
+#[cfg(target_os = "linux")]
+fn stat_parent(stat: &str) -> Option<pid_t> {
+    // The command name may contain spaces and parentheses, so the
+    // fields after it are found from the last parenthesis.
+    stat.rfind(')')
+        .and_then(|i| stat[i + 1..].split_whitespace().nth(1))
+        .and_then(|field| field.parse().ok())
+}
+
+/// Elsewhere, `process-attributes' already knows how to ask the system.
+#[cfg(not(target_os = "linux"))]
+fn system_process_parents() -> HashMap<pid_t, pid_t> {
+    let mut parents = HashMap::new();
+    for pid in call!(intern("list-system-processes")).iter_cars() {
+        let attributes = call!(intern("process-attributes"), pid);
+        let ppid = cdr(assq(intern("ppid"), attributes));
+        if let (Some(pid), Some(ppid)) = (pid.as_fixnum(), ppid.as_fixnum()) {
+            parents.insert(pid as pid_t, ppid as pid_t);
+        }
+    }
+    parents
+}
+
+/// Return the children of each process on the system, keyed by pid.
+fn system_process_children() -> HashMap<pid_t, Vec<pid_t>> {
+    let mut children = HashMap::new();
+    for (pid, ppid) in system_process_parents() {
+        children.entry(ppid).or_insert_with(Vec::new).push(pid);
+    }
+    for pids in children.values_mut() {
+        pids.sort();
+    }
+    children
+}
+
+/// Return all descendants of PID, parents before their children.
+fn process_descendants(pid: pid_t) -> Vec<pid_t> {
+    let children = system_process_children();
+    let mut descendants = Vec::new();
+    let mut next = 0;
+    let mut current = pid;
+    loop {
+        if let Some(pids) = children.get(&current) {
+            descendants.extend(pids.iter().cloned());
+        }
+        if next == descendants.len() {
+            return descendants;
+        }
+        current = descendants[next];
+        next += 1;
+    }
+}
+
+fn make_process_tree(pid: pid_t, children: &HashMap<pid_t, Vec<pid_t>>) -> LispObject {
+    let subtrees = match children.get(&pid) {
+        Some(pids) => pids.iter()
+            .rev()
+            .fold(LispObject::constant_nil(), |list, &child| {
+                LispObject::cons(make_process_tree(child, children), list)
+            }),
+        None => LispObject::constant_nil(),
+    };
+    LispObject::cons(LispObject::from_fixnum(EmacsInt::from(pid)), subtrees)
+}
+
+/// Return the tree of operating system processes started by PROCESS.
+/// The value has the form (PID CHILD...), where PID is the process ID
+/// of PROCESS and each CHILD is a tree of the same form for one of its
+/// child processes.  Processes that have been orphaned by the death of
+/// their parent are no longer part of the tree.
+///
+/// Return nil if PROCESS is not a running subprocess.
+#[lisp_fn]
+pub fn process_tree(process: LispObject) -> LispObject {
+    let p = LispObject::from(unsafe { cget_process(process.to_raw()) }).as_process_or_error();
+    let pid = unsafe { pget_pid(p.as_ptr()) };
+    if !p.process_type().eq(LispObject::from(Qreal)) || pid <= 0
+        || !unsafe { pget_alive(p.as_ptr()) }
+    {
+        return LispObject::constant_nil();
+    }
+    make_process_tree(pid, &system_process_children())
+}
+
+#[cfg(target_os = "linux")]
+const CDISABLE: libc::cc_t = 0;
+
+#[cfg(all(unix, not(target_os = "linux")))]
+const CDISABLE: libc::cc_t = 0xff;
+
+/// Try to deliver SIGNO to the foreground process group of the pty of
+/// PROC by typing the corresponding control character, which reaches
+/// programs that have changed their uid.  Return true on success.
+#[cfg(unix)]
+fn send_signal_character(proc: LispObject, infd: c_int, signo: c_int) -> bool {
+    let mut t: libc::termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(infd, &mut t) } < 0 {
+        return false;
+    }
+    let c = match signo {
+        libc::SIGINT => t.c_cc[libc::VINTR],
+        libc::SIGQUIT => t.c_cc[libc::VQUIT],
+        libc::SIGTSTP => t.c_cc[libc::VSUSP],
+        _ => return false,
+    };
+    if c == CDISABLE {
+        return false;
+    }
+    unsafe { send_process(proc.to_raw(), &c as *const u8 as *const c_char, 1, Qnil) };
+    true
+}
+
+#[cfg(not(unix))]
+fn send_signal_character(_proc: LispObject, _infd: c_int, _signo: c_int) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn kill_process_group(p: LispProcessRef, gid: pid_t, signo: c_int, descendants: &[pid_t]) {
+    // Do not kill an already-reaped process, as that could kill an
+    // innocent bystander that happens to have the same process ID.
+    unsafe {
+        let mut oldset: libc::sigset_t = mem::zeroed();
+        block_child_signal(&mut oldset);
+        if pget_alive(p.as_ptr()) {
+            libc::kill(-gid, signo);
+            // Children that moved to a process group of their own would
+            // otherwise outlive PROCESS.
+            for &pid in descendants {
+                if libc::getpgid(pid) != gid {
+                    libc::kill(pid, signo);
+                }
+            }
+        }
+        unblock_child_signal(&oldset);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(p: LispProcessRef, gid: pid_t, signo: c_int, _descendants: &[pid_t]) {
+    if unsafe { pget_alive(p.as_ptr()) } {
+        unsafe { sys_kill(-gid, signo) };
+    }
+}
+
+/// Send a signal number SIGNO to PROCESS.
+/// If CURRENT_GROUP is t, that means send to the process group that
+/// currently owns the terminal being used to communicate with PROCESS.
+/// This is used for various commands in shell mode.  If CURRENT_GROUP
+/// is lambda, that means send to the process group that currently owns
+/// the terminal, but only if it is NOT the shell itself.
+///
+/// If NOMSG is false, insert signal-announcements into process's
+/// buffers right away.
+///
+/// If we can, we try to signal PROCESS by sending control characters
+/// down the pty.  This allows us to signal inferiors who have changed
+/// their uid, for which kill would return an EPERM error.
+///
+/// When PROCESS itself is killed, its descendants that have left its
+/// process group are killed too.
+#[no_mangle]
+pub extern "C" fn process_send_signal(
+    process: LispObject,
+    signo: c_int,
+    current_group: LispObject,
+    nomsg: bool,
+) {
+    let proc = LispObject::from(unsafe { cget_process(process.to_raw()) });
+    let mut p = proc.as_process_or_error();
+    let infd = unsafe { pget_infd(p.as_ptr()) };
+    let pid = unsafe { pget_pid(p.as_ptr()) };
+
+    if !p.process_type().eq(LispObject::from(Qreal)) {
+        error!("Process {} is not a subprocess", string_value(p.name()));
+    }
+    if infd < 0 {
+        error!("Process {} is not active", string_value(p.name()));
+    }
+
+    let mut descendants = Vec::new();
+    let gid = if current_group.is_nil() || !unsafe { pget_pty_flag(p.as_ptr()) } {
+        // Send the signal to the shell's process group.
+        if signo == SIGKILL {
+            descendants = process_descendants(pid);
+        }
+        pid
+    } else {
+        if send_signal_character(proc, infd, signo) {
+            return;
+        }
+        // If we can't get the foreground group, assume the shell owns
+        // the tty.
+        let gid = match unsafe { emacs_get_tty_pgrp(p.as_ptr()) } {
+            -1 => pid,
+            gid => gid,
+        };
+        // If current_group is lambda, and the shell owns the terminal,
+        // don't send any signal.
+        if current_group.eq(LispObject::from(Qlambda)) && gid == pid {
+            return;
+        }
+        gid
+    };
+
+    if Some(signo) == SIGCONT {
+        unsafe { process_continued(p.as_mut(), nomsg) };
+    }
+    kill_process_group(p, gid, signo, &descendants);
+}
+
+fn string_value(object: LispObject) -> String {
+    String::from_utf8_lossy(object.as_string_or_error().as_slice()).into_owned()
+}
+
+/// Return true if PROCESS is a network, serial or pipe connection.
+fn is_connection(process: LispObject) -> bool {
+    process.as_process().map_or(false, |p| {
+        let process_type = p.process_type();
+        process_type.eq(LispObject::from(Qnetwork)) || process_type.eq(LispObject::from(Qserial))
+            || process_type.eq(LispObject::from(Qpipe))
+    })
+}
+
+/// Default function to interrupt process PROCESS.
+/// It shall be the last element in list `interrupt-process-functions'.
+/// See function `interrupt-process' for more details on usage.
+#[lisp_fn(min = "0")]
+pub fn internal_default_interrupt_process(
+    process: LispObject,
+    current_group: LispObject,
+) -> LispObject {
+    process_send_signal(process, SIGINT, current_group, false);
+    process
+}
+
+/// Interrupt process PROCESS.
+/// PROCESS may be a process, a buffer, or the name of a process or buffer.
+/// No arg or nil means current buffer's process.
+/// Second arg CURRENT-GROUP non-nil means send signal to
+/// the current process-group of the process's controlling terminal
+/// rather than to the process's own process group.
+/// If the process is a shell, this means interrupt current subjob
+/// rather than the shell.
+///
+/// If CURRENT-GROUP is `lambda', and if the shell owns the terminal,
+/// don't send the signal.
+///
+/// This function calls the functions of `interrupt-process-functions' in
+/// the order of the list, until one of them returns non-`nil'.
+#[lisp_fn(min = "0")]
+pub fn interrupt_process(process: LispObject, current_group: LispObject) -> LispObject {
+    call!(
+        intern("run-hook-with-args-until-success"),
+        LispObject::from(Qinterrupt_process_functions),
+        process,
+        current_group
+    )
+}
+
+/// Stop process PROCESS.  May be process or name of one.
+/// See function `interrupt-process' for more details on usage.
+/// If PROCESS is a network or serial or pipe connection, inhibit handling
+/// of incoming traffic.
+#[lisp_fn(min = "0")]
+pub fn stop_process(process: LispObject, current_group: LispObject) -> LispObject {
+    if is_connection(process) {
+        unsafe { set_connection_stopped(process.as_process_or_error().as_mut(), true) };
+        return process;
+    }
+    match SIGTSTP {
+        Some(signo) => process_send_signal(process, signo, current_group, false),
+        None => error!("No SIGTSTP support"),
+    }
+    process
+}
+
+/// Continue process PROCESS.  May be process or name of one.
+/// See function `interrupt-process' for more details on usage.
+/// If PROCESS is a network or serial process, resume handling of incoming
+/// traffic.
+#[lisp_fn(min = "0")]
+pub fn continue_process(process: LispObject, current_group: LispObject) -> LispObject {
+    if is_connection(process) {
+        unsafe { set_connection_stopped(process.as_process_or_error().as_mut(), false) };
+        return process;
+    }
+    match SIGCONT {
+        Some(signo) => process_send_signal(process, signo, current_group, false),
+        None => error!("No SIGCONT support"),
+    }
+    process
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_stat_parent() {
+    assert_eq!(stat_parent("1234 (sleep) S 1200 1234 1200 34816"), Some(1200));
+    assert_eq!(stat_parent("99 (a (b) c) R 7 99 99 0"), Some(7));
+    assert_eq!(stat_parent("garbage"), None);
+}
+
 include!(concat!(env!("OUT_DIR"), "/process_exports.rs"));
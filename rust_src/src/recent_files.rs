@@ -0,0 +1,354 @@
+//! The list of recently visited files.
+//!
+//! The list is kept here rather than in a Lisp variable so that it can
+//! be saved in the format of `lisp-data-write' without going through
+//! the printer, written on a separate thread, and checked for files
+//! that no longer exist without holding up the command loop.  Files on
+//! remote hosts are never checked.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::make_specified_string;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::list;
+use persist::{decode, encode, finish_background_writes, write_in_background};
+use util::{expand_file_name_to_path, report_io_error};
+
+/// A file in the list.
+struct Entry {
+    /// The file name as given, in Emacs' internal representation.
+    name: Vec<u8>,
+    multibyte: bool,
+    /// Where to look for the file, or None if it is remote.
+    path: Option<PathBuf>,
+    /// When the file was put in the list, counting additions.
+    added: u64,
+}
+
+impl Entry {
+    fn new(file: LispObject, added: u64) -> Entry {
+        let string = file.as_string_or_error();
+        let path = if call!(intern("file-remote-p"), file).is_nil() {
+            Some(expand_file_name_to_path(file))
+        } else {
+            None
+        };
+        Entry {
+            name: string.as_slice().to_vec(),
+            multibyte: string.is_multibyte(),
+            path,
+            added,
+        }
+    }
+}
+
+/// Return the list of the file NAMES, each given with whether it is
+/// multibyte.
+fn lisp_names(names: Vec<(Vec<u8>, bool)>) -> LispObject {
+    let mut names: Vec<LispObject> = names
+        .into_iter()
+        .map(|(name, multibyte)| {
+            LispObject::from(unsafe {
+                make_specified_string(
+                    name.as_ptr() as *const c_char,
+                    -1,
+                    name.len() as ptrdiff_t,
+                    multibyte,
+                )
+            })
+        })
+        .collect();
+    list(&mut names)
+}
+
+/// The result of a check for missing files: the count of additions when
+/// it began and the names of the files it found missing.
+type CheckResult = (u64, Vec<Vec<u8>>);
+
+struct RecentFiles {
+    /// The most recent file first.
+    entries: Vec<Entry>,
+    additions: u64,
+    check: Option<Receiver<CheckResult>>,
+}
+
+impl RecentFiles {
+    fn position(&self, name: &[u8]) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == name)
+    }
+
+    /// Put ENTRY first, dropping another entry with the same name and the
+    /// ones beyond the first MAX.
+    fn push(&mut self, entry: Entry, max: Option<usize>) {
+        if let Some(index) = self.position(&entry.name) {
+            self.entries.remove(index);
+        }
+        self.entries.insert(0, entry);
+        if let Some(max) = max {
+            self.entries.truncate(max);
+        }
+    }
+
+    /// Replace the list with ENTRIES, keeping the first of the ones that
+    /// have the same name.
+    fn replace(&mut self, entries: Vec<Entry>) {
+        self.entries.clear();
+        for entry in entries {
+            if self.position(&entry.name).is_none() {
+                self.entries.push(entry);
+            }
+        }
+        // The files the last check found missing were read back in.
+        self.check = None;
+    }
+
+    /// Drop the files found missing by the last check, if it is done.
+    /// Files added again since it began are kept.
+    fn apply_check(&mut self) {
+        let result = match self.check {
+            Some(ref receiver) => match receiver.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => None,
+            },
+            None => return,
+        };
+        self.check = None;
+        if let Some((began, missing)) = result {
+            self.entries
+                .retain(|entry| entry.added >= began || !missing.contains(&entry.name));
+        }
+    }
+
+    /// Return the names of the first COUNT files, or all of them if
+    /// COUNT is None.
+    fn names(&self, count: Option<usize>) -> Vec<(Vec<u8>, bool)> {
+        let count = count.map_or(self.entries.len(), |count| count.min(self.entries.len()));
+        self.entries[..count]
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.multibyte))
+            .collect()
+    }
+
+    fn next_addition(&mut self) -> u64 {
+        self.additions += 1;
+        self.additions
+    }
+}
+
+lazy_static! {
+    static ref RECENT_FILES: Mutex<RecentFiles> = Mutex::new(RecentFiles {
+        entries: Vec::new(),
+        additions: 0,
+        check: None,
+    });
+}
+
+fn limit_arg(limit: LispObject) -> Option<usize> {
+    if limit.is_nil() {
+        None
+    } else {
+        Some(limit.as_natnum_or_error() as usize)
+    }
+}
+
+/// Return the entries for the file names in FILES, signaling an error
+/// for anything that is not a string.  This calls Lisp, so it must not
+/// be done while holding the lock.
+fn entries_for(files: LispObject, first: u64) -> Vec<Entry> {
+    files
+        .iter_cars()
+        .enumerate()
+        .map(|(i, file)| Entry::new(file, first + i as u64))
+        .collect()
+}
+
+/// Replace the list by FILES and return it, without duplicates.
+fn set_files(files: LispObject) -> LispObject {
+    let first = RECENT_FILES.lock().unwrap().additions + 1;
+    let entries = entries_for(files, first);
+    let names = {
+        let mut recent = RECENT_FILES.lock().unwrap();
+        recent.additions = first + entries.len() as u64;
+        recent.replace(entries);
+        recent.names(None)
+    };
+    lisp_names(names)
+}
+
+/// Return the list of recently visited files, the most recent first.
+/// If LIMIT is non-nil, return at most that many.  Files that the last
+/// `recent-files-native-check' found missing are left out once it is
+/// done.
+#[lisp_fn(min = "0")]
+pub fn recent_files_native(limit: LispObject) -> LispObject {
+    let limit = limit_arg(limit);
+    let names = {
+        let mut recent = RECENT_FILES.lock().unwrap();
+        recent.apply_check();
+        recent.names(limit)
+    };
+    lisp_names(names)
+}
+
+/// Put FILE first in the list of recently visited files.
+/// FILE is taken out of the place it had in the list, if any.  If MAX is
+/// non-nil, the list is cut down to that many files.
+#[lisp_fn(min = "1")]
+pub fn recent_files_native_add(file: LispObject, max: LispObject) -> LispObject {
+    let max = limit_arg(max);
+    let added = RECENT_FILES.lock().unwrap().next_addition();
+    let entry = Entry::new(file, added);
+    let mut recent = RECENT_FILES.lock().unwrap();
+    recent.apply_check();
+    recent.push(entry, max);
+    LispObject::constant_nil()
+}
+
+/// Take FILE out of the list of recently visited files.
+/// Return t if it was in the list, nil otherwise.
+#[lisp_fn]
+pub fn recent_files_native_remove(file: LispObject) -> LispObject {
+    let name = file.as_string_or_error().as_slice().to_vec();
+    let mut recent = RECENT_FILES.lock().unwrap();
+    match recent.position(&name) {
+        Some(index) => {
+            recent.entries.remove(index);
+            LispObject::constant_t()
+        }
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Make FILES the list of recently visited files, the most recent first.
+/// Return the new list, in which only the first of equal file names is
+/// kept.
+#[lisp_fn]
+pub fn recent_files_native_set(files: LispObject) -> LispObject {
+    set_files(files)
+}
+
+/// Start checking on a separate thread which of the recently visited
+/// files still exist.  Return at once.  Once the check is done, the
+/// files found missing are taken out of the list, unless they were added
+/// again in the meantime.  Remote files are not checked.
+#[lisp_fn]
+pub fn recent_files_native_check() -> LispObject {
+    let mut recent = RECENT_FILES.lock().unwrap();
+    let began = recent.additions + 1;
+    let files: Vec<(Vec<u8>, PathBuf)> = recent
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            entry
+                .path
+                .as_ref()
+                .map(|path| (entry.name.clone(), path.clone()))
+        })
+        .collect();
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let missing = files
+            .into_iter()
+            .filter(|&(_, ref path)| !path.exists())
+            .map(|(name, _)| name)
+            .collect();
+        // The receiver is gone if the list was replaced meanwhile.
+        let _ = sender.send((began, missing));
+    });
+    recent.check = Some(receiver);
+    LispObject::constant_nil()
+}
+
+/// Read the list of recently visited files saved in FILE by
+/// `recent-files-native-save', and make it the list.  If FILE does not
+/// exist, the list is made empty.  Return the new list.
+#[lisp_fn]
+pub fn recent_files_native_load(file: LispObject) -> LispObject {
+    finish_background_writes();
+    let path = expand_file_name_to_path(file);
+    let mut data = Vec::new();
+    let files = match File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+        Ok(_) => decode(&data),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => LispObject::constant_nil(),
+        Err(err) => report_io_error(b"Reading recent files\0", file, &err),
+    };
+    if !files.is_list() {
+        error!("Invalid recent files data");
+    }
+    set_files(files)
+}
+
+/// Save the list of recently visited files in FILE.
+/// If LIMIT is non-nil, save at most that many.  The file is written on
+/// a separate thread, so this returns at once; Emacs waits for the write
+/// to finish before it exits.
+#[lisp_fn(min = "1")]
+pub fn recent_files_native_save(file: LispObject, limit: LispObject) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let name = String::from_utf8_lossy(file.as_string_or_error().as_slice()).into_owned();
+    let files = recent_files_native(limit);
+    write_in_background(path, name, encode(files));
+    LispObject::constant_nil()
+}
+
+#[cfg(test)]
+fn test_entry(name: &str, added: u64) -> Entry {
+    Entry {
+        name: name.as_bytes().to_vec(),
+        multibyte: false,
+        path: Some(PathBuf::from(name)),
+        added,
+    }
+}
+
+#[cfg(test)]
+fn test_names(recent: &RecentFiles) -> Vec<&str> {
+    recent
+        .entries
+        .iter()
+        .map(|entry| ::std::str::from_utf8(&entry.name).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_recent_files() {
+    let mut recent = RecentFiles {
+        entries: Vec::new(),
+        additions: 0,
+        check: None,
+    };
+    recent.replace(vec![
+        test_entry("/a", 1),
+        test_entry("/b", 2),
+        test_entry("/a", 3),
+        test_entry("/c", 4),
+    ]);
+    assert_eq!(test_names(&recent), vec!["/a", "/b", "/c"]);
+    recent.push(test_entry("/c", 5), None);
+    assert_eq!(test_names(&recent), vec!["/c", "/a", "/b"]);
+    recent.push(test_entry("/d", 6), Some(2));
+    assert_eq!(test_names(&recent), vec!["/d", "/c"]);
+
+    // "/d" was added after the check began, so it stays.
+    let (sender, receiver) = channel();
+    recent.check = Some(receiver);
+    sender
+        .send((6, vec![b"/c".to_vec(), b"/d".to_vec()]))
+        .unwrap();
+    recent.apply_check();
+    assert_eq!(test_names(&recent), vec!["/d"]);
+    assert!(recent.check.is_none());
+}
+
+include!(concat!(env!("OUT_DIR"), "/recent_files_exports.rs"));
@@ -0,0 +1,149 @@
+//! Rectangle extraction and insertion.
+//!
+//! Columns are display columns, as reported by `current-column', so
+//! tabs and wide characters occupy more than one column.  When such a
+//! character straddles an edge of the rectangle, only the part inside
+//! the rectangle is kept, as spaces.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_string, EmacsInt, Fbuffer_substring, Finsert_char, Fforward_line,
+                 Fmove_to_column, Fnreverse, Qnil, Qt};
+
+use editfns::{bolp, char_after, goto_char};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use threads::ThreadState;
+
+fn column() -> EmacsInt {
+    unsafe { ::remacs_sys::current_column() as EmacsInt }
+}
+
+fn move_to_column(column: EmacsInt, force: LispObject) {
+    unsafe { Fmove_to_column(LispObject::from_fixnum(column).to_raw(), force.to_raw()) };
+}
+
+fn point() -> EmacsInt {
+    ThreadState::current_buffer().pt as EmacsInt
+}
+
+fn goto(pos: EmacsInt) {
+    goto_char(LispObject::from_fixnum(pos));
+}
+
+/// Return the column at which the character at POS starts.
+fn column_at(pos: EmacsInt) -> EmacsInt {
+    goto(pos);
+    column()
+}
+
+fn buffer_substring(start: EmacsInt, end: EmacsInt) -> LispObject {
+    LispObject::from(unsafe {
+        Fbuffer_substring(
+            LispObject::from_fixnum(start).to_raw(),
+            LispObject::from_fixnum(end).to_raw(),
+        )
+    })
+}
+
+/// Return a string of N spaces.
+fn spaces(n: EmacsInt) -> LispObject {
+    let s = " ".repeat(n as usize);
+    LispObject::from(unsafe { make_string(s.as_ptr() as *const c_char, s.len() as ptrdiff_t) })
+}
+
+/// Push the part of the current line between columns STARTCOL and
+/// ENDCOL onto the cdr of LINES, a cons whose car is ignored.
+/// Tabs inside the rectangle are replaced by spaces, and the line is
+/// padded with spaces where it is shorter than ENDCOL.  Point must be
+/// on the line to extract, and is left somewhere on it.
+/// Return the new cdr of LINES.
+#[lisp_fn]
+pub fn extract_rectangle_line(
+    startcol: LispObject,
+    endcol: LispObject,
+    lines: LispObject,
+) -> LispObject {
+    let startcol = startcol.as_fixnum_or_error();
+    let endcol = endcol.as_fixnum_or_error();
+    let lines = lines.as_cons_or_error();
+
+    move_to_column(startcol, LispObject::constant_nil());
+    let start = point();
+    let mut begextra = column() - startcol;
+    move_to_column(endcol, LispObject::constant_nil());
+    let mut end = point();
+    let mut endextra = endcol - column();
+
+    // A character straddling the right edge is left out, and the part
+    // of it inside the rectangle becomes padding.
+    if endextra < 0 && end > start {
+        let col = column_at(end - 1);
+        end -= 1;
+        endextra = endcol - col;
+    }
+    if begextra < 0 {
+        endextra += begextra;
+        begextra = 0;
+    }
+    if endextra < 0 {
+        endextra = 0;
+    }
+
+    // Collect the pieces of the line, most recent first, in a Lisp list
+    // so that they are protected from GC.
+    let mut pieces = LispObject::constant_nil();
+    if begextra > 0 {
+        pieces = LispObject::cons(spaces(begextra), pieces);
+    }
+    let mut segment_start = start;
+    for pos in start..end {
+        if char_after(LispObject::from_fixnum(pos)).as_fixnum() != Some('\t' as EmacsInt) {
+            continue;
+        }
+        let width = column_at(pos + 1) - column_at(pos);
+        pieces = LispObject::cons(buffer_substring(segment_start, pos), pieces);
+        pieces = LispObject::cons(spaces(width), pieces);
+        segment_start = pos + 1;
+    }
+    pieces = LispObject::cons(buffer_substring(segment_start, end), pieces);
+    if endextra > 0 {
+        pieces = LispObject::cons(spaces(endextra), pieces);
+    }
+
+    let pieces = LispObject::from(unsafe { Fnreverse(pieces.to_raw()) });
+    let line = call!(intern("apply"), intern("concat"), pieces);
+    let tail = LispObject::cons(line, lines.cdr());
+    lines.set_cdr(tail);
+    tail
+}
+
+/// Insert text of RECTANGLE with upper left corner at point.
+/// RECTANGLE's first line is inserted at point, its second
+/// line is inserted at a point vertically under point, etc.
+/// RECTANGLE should be a list of strings.
+/// After this command, the mark is at the upper left corner
+/// and point is at the lower right corner.
+#[lisp_fn]
+pub fn insert_rectangle(rectangle: LispObject) -> LispObject {
+    let insertcolumn = column();
+    call!(intern("push-mark"));
+
+    let mut first = true;
+    for line in rectangle.iter_cars() {
+        if !first {
+            unsafe { Fforward_line(LispObject::from_fixnum(1).to_raw()) };
+            if bolp().is_nil() {
+                let newline = LispObject::from_fixnum('\n' as EmacsInt);
+                unsafe { Finsert_char(newline.to_raw(), Qnil, Qnil) };
+            }
+            move_to_column(insertcolumn, LispObject::from(Qt));
+        }
+        first = false;
+        call!(intern("insert-for-yank"), line);
+    }
+    LispObject::constant_nil()
+}
+
+include!(concat!(env!("OUT_DIR"), "/rect_exports.rs"));
@@ -0,0 +1,54 @@
+//! Register storage.
+//!
+//! Registers live in `register-alist', an alist of (NAME . CONTENTS)
+//! where NAME is a character.  The contents are opaque here: strings,
+//! numbers, markers, rectangles (lists of strings), window or frame
+//! configurations and `registerv' structs are all stored as is, and
+//! interpreted by register.el.
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{find_symbol_value, Fset};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::assq;
+
+fn register_alist_symbol() -> LispObject {
+    intern("register-alist")
+}
+
+/// Return the value of `register-alist', or nil if it is void because
+/// register.el has not been loaded yet.
+fn register_alist() -> LispObject {
+    let value = LispObject::from(unsafe { find_symbol_value(register_alist_symbol().to_raw()) });
+    if value.eq(LispObject::constant_unbound()) {
+        LispObject::constant_nil()
+    } else {
+        value
+    }
+}
+
+/// Return contents of Emacs register named REGISTER, or nil if none.
+#[lisp_fn]
+pub fn get_register(register: LispObject) -> LispObject {
+    assq(register, register_alist())
+        .as_cons()
+        .map_or(LispObject::constant_nil(), |entry| entry.cdr())
+}
+
+/// Set contents of Emacs register named REGISTER to VALUE.  Returns VALUE.
+/// See the documentation of the variable `register-alist' for possible VALUEs.
+#[lisp_fn]
+pub fn set_register(register: LispObject, value: LispObject) -> LispObject {
+    let alist = register_alist();
+    match assq(register, alist).as_cons() {
+        Some(entry) => entry.set_cdr(value),
+        None => {
+            let alist = LispObject::cons(LispObject::cons(register, value), alist);
+            unsafe { Fset(register_alist_symbol().to_raw(), alist.to_raw()) };
+        }
+    }
+    value
+}
+
+include!(concat!(env!("OUT_DIR"), "/registers_exports.rs"));
@@ -0,0 +1,525 @@
+//! Remote file access over SSH.
+//!
+//! Remote files are named `/rssh:[USER@]HOST[#PORT]:FILE', as in
+//! TRAMP.  The functions here stat, read, write and list them through
+//! the SFTP subsystem of one SSH connection per host, so there are no
+//! round trips through a remote shell.  tramp-rssh.el makes them a
+//! TRAMP method.
+//!
+//! The module is only built with the `native-ssh' feature, which is off
+//! by default.
+
+#![cfg(feature = "native-ssh")]
+
+use std::collections::HashMap;
+use std::env;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use libc::{c_char, ptrdiff_t};
+
+use base64_crate;
+use ssh2;
+use ssh2::{CheckResult, ErrorCode, FileStat, HashType, KnownHostFileKind, OpenFlags, OpenType};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_unibyte_string, EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+const PREFIX: &str = "/rssh:";
+const DEFAULT_PORT: u16 = 22;
+
+/// The SFTP status code for a missing file.
+const FX_NO_SUCH_FILE: i32 = 2;
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// The parts of a remote file name.
+#[derive(Debug, PartialEq)]
+struct RemoteName {
+    /// The part before FILE, like `/rssh:USER@HOST#PORT:'.
+    prefix: String,
+    user: Option<String>,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl RemoteName {
+    /// The key of the connection this file is reached through.
+    fn connection(&self) -> String {
+        format!(
+            "{}@{}#{}",
+            self.user.as_ref().map_or("", |u| u.as_str()),
+            self.host,
+            self.port
+        )
+    }
+
+    fn user(&self) -> String {
+        match self.user {
+            Some(ref user) => user.clone(),
+            None => env::var("USER")
+                .or_else(|_| env::var("USERNAME"))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The host as it appears in known_hosts.
+    fn known_host(&self) -> String {
+        if self.port == DEFAULT_PORT {
+            self.host.clone()
+        } else {
+            format!("[{}]:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Split NAME, a file name like `/rssh:USER@HOST#PORT:FILE', into its
+/// parts.  Return None if NAME isn't such a file name.  An empty FILE
+/// means the home directory.
+fn parse_remote_name(name: &str) -> Option<RemoteName> {
+    if !name.starts_with(PREFIX) {
+        return None;
+    }
+    let rest = &name[PREFIX.len()..];
+    let colon = match rest.find(':') {
+        Some(i) => i,
+        None => return None,
+    };
+    let (login, path) = (&rest[..colon], &rest[colon + 1..]);
+    let (user, host) = match login.rfind('@') {
+        Some(i) => (Some(login[..i].to_string()), &login[i + 1..]),
+        None => (None, login),
+    };
+    let (host, port) = match host.find('#') {
+        Some(i) => match host[i + 1..].parse() {
+            Ok(port) => (&host[..i], port),
+            Err(_) => return None,
+        },
+        None => (host, DEFAULT_PORT),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some(RemoteName {
+        prefix: name[..PREFIX.len() + colon + 1].to_string(),
+        user,
+        host: host.to_string(),
+        port,
+        path: if path.is_empty() { "." } else { path }.to_string(),
+    })
+}
+
+/// Return the `ls'-style mode string for the permission bits PERM.
+fn mode_string(perm: u32) -> String {
+    let mut modes = String::with_capacity(10);
+    modes.push(match perm & S_IFMT {
+        S_IFDIR => 'd',
+        S_IFLNK => 'l',
+        0o020_000 => 'c',
+        0o060_000 => 'b',
+        0o010_000 => 'p',
+        0o140_000 => 's',
+        _ => '-',
+    });
+    for (i, c) in "rwxrwxrwx".chars().enumerate() {
+        modes.push(if perm & (1 << (8 - i)) != 0 { c } else { '-' });
+    }
+    modes
+}
+
+struct Connection {
+    // The SFTP channel needs the session to stay open.
+    _session: ssh2::Session,
+    sftp: ssh2::Sftp,
+}
+
+lazy_static! {
+    static ref CONNECTIONS: Mutex<HashMap<String, Connection>> = Mutex::new(HashMap::new());
+}
+
+fn string_value(object: LispObject) -> String {
+    String::from_utf8_lossy(object.as_string_or_error().as_slice()).into_owned()
+}
+
+fn unibyte_string(bytes: &[u8]) -> LispObject {
+    unsafe {
+        LispObject::from(make_unibyte_string(
+            bytes.as_ptr() as *const c_char,
+            bytes.len() as ptrdiff_t,
+        ))
+    }
+}
+
+fn remote_name(filename: LispObject) -> RemoteName {
+    match parse_remote_name(&string_value(filename)) {
+        Some(name) => name,
+        None => xsignal!(
+            intern("file-error").to_raw(),
+            lisp_string("Not a remote file name"),
+            filename
+        ),
+    }
+}
+
+fn ssh_directory() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".ssh"))
+}
+
+/// Check the key the host of NAME presented against known_hosts.  The
+/// user is asked whether to trust a key that isn't known yet.
+fn check_host_key(session: &ssh2::Session, name: &RemoteName) -> Result<(), String> {
+    let (key, key_type) = match session.host_key() {
+        Some(key) => key,
+        None => return Err("The host sent no key".to_string()),
+    };
+    let mut known_hosts = session.known_hosts().map_err(|err| err.to_string())?;
+    let file = ssh_directory().map(|dir| dir.join("known_hosts"));
+    if let Some(ref file) = file {
+        // A missing file just means no host is known.
+        let _ = known_hosts.read_file(file, KnownHostFileKind::OpenSSH);
+    }
+    match known_hosts.check_port(&name.host, name.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(format!(
+            "The host key of {} has changed",
+            name.known_host()
+        )),
+        CheckResult::Failure => Err("Cannot check the host key".to_string()),
+        CheckResult::NotFound => {
+            let fingerprint = session
+                .host_key_hash(HashType::Sha256)
+                .map(|hash| base64_crate::encode(hash))
+                .unwrap_or_default();
+            let prompt = format!(
+                "The host key of {} is unknown (SHA256:{}); connect anyway? ",
+                name.known_host(),
+                fingerprint.trim_right_matches('=')
+            );
+            if call!(intern("yes-or-no-p"), lisp_string(&prompt)).is_nil() {
+                return Err("Host key not accepted".to_string());
+            }
+            known_hosts
+                .add(&name.known_host(), key, "", key_type.into())
+                .map_err(|err| err.to_string())?;
+            match file {
+                Some(ref file) => known_hosts
+                    .write_file(file, KnownHostFileKind::OpenSSH)
+                    .map_err(|err| err.to_string()),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// Log in to the host of NAME with the SSH agent, the usual private
+/// key files without passphrase, or a password read from the user.
+fn authenticate(session: &ssh2::Session, name: &RemoteName) -> Result<(), String> {
+    let user = name.user();
+    if session.userauth_agent(&user).is_ok() {
+        return Ok(());
+    }
+    if let Some(dir) = ssh_directory() {
+        for key in &["id_ed25519", "id_ecdsa", "id_rsa"] {
+            let private = dir.join(key);
+            if private.exists() && session.userauth_pubkey_file(&user, None, &private, None).is_ok()
+            {
+                return Ok(());
+            }
+        }
+    }
+    let prompt = format!("Password for {}@{}: ", user, name.host);
+    let password = string_value(call!(intern("read-passwd"), lisp_string(&prompt)));
+    session
+        .userauth_password(&user, &password)
+        .map_err(|err| err.to_string())
+}
+
+fn connect(name: &RemoteName) -> Result<Connection, String> {
+    let tcp = TcpStream::connect((name.host.as_str(), name.port)).map_err(|err| err.to_string())?;
+    let mut session = ssh2::Session::new().map_err(|err| err.to_string())?;
+    session.set_tcp_stream(tcp);
+    session.handshake().map_err(|err| err.to_string())?;
+    check_host_key(&session, name)?;
+    authenticate(&session, name)?;
+    let sftp = session.sftp().map_err(|err| err.to_string())?;
+    Ok(Connection {
+        _session: session,
+        sftp,
+    })
+}
+
+fn is_missing(err: &ssh2::Error) -> bool {
+    match err.code() {
+        ErrorCode::SFTP(FX_NO_SUCH_FILE) => true,
+        _ => false,
+    }
+}
+
+/// Call F with the SFTP channel to the host of FILENAME and the remote
+/// file, connecting first if needed.  Errors are signaled as file
+/// errors, `file-missing' for files that don't exist.
+fn with_sftp<T, F>(filename: LispObject, f: F) -> T
+where
+    F: FnOnce(&ssh2::Sftp, &Path) -> Result<T, ssh2::Error>,
+{
+    let name = remote_name(filename);
+    let key = name.connection();
+    // Connecting may ask the user, so it happens without the lock.
+    let connected = CONNECTIONS.lock().unwrap().contains_key(&key);
+    if !connected {
+        match connect(&name) {
+            Ok(connection) => {
+                CONNECTIONS.lock().unwrap().insert(key.clone(), connection);
+            }
+            Err(err) => xsignal!(
+                intern("file-error").to_raw(),
+                lisp_string("Cannot connect"),
+                lisp_string(&err),
+                filename
+            ),
+        }
+    }
+    let result = {
+        let connections = CONNECTIONS.lock().unwrap();
+        f(&connections[&key].sftp, Path::new(&name.path))
+    };
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            if let ErrorCode::Session(_) = err.code() {
+                // The connection is broken; make a new one next time.
+                CONNECTIONS.lock().unwrap().remove(&key);
+            }
+            let symbol = if is_missing(&err) {
+                "file-missing"
+            } else {
+                "file-error"
+            };
+            xsignal!(
+                intern(symbol).to_raw(),
+                lisp_string("Remote file error"),
+                lisp_string(err.message()),
+                filename
+            )
+        }
+    }
+}
+
+/// Return a time value for SECONDS since the epoch.
+fn lisp_time(seconds: u64) -> LispObject {
+    list!(
+        LispObject::from_natnum((seconds >> 16) as EmacsInt),
+        LispObject::from_natnum((seconds & 0xffff) as EmacsInt)
+    )
+}
+
+/// Return the list `file-attributes' returns for a file with STAT.
+/// TYPE is the first element.
+fn lisp_attributes(stat: &FileStat, file_type: LispObject) -> LispObject {
+    let perm = stat.perm.unwrap_or(0);
+    let mtime = lisp_time(stat.mtime.unwrap_or(0));
+    list!(
+        file_type,
+        LispObject::from_natnum(1),
+        LispObject::from_natnum(EmacsInt::from(stat.uid.unwrap_or(0))),
+        LispObject::from_natnum(EmacsInt::from(stat.gid.unwrap_or(0))),
+        lisp_time(stat.atime.unwrap_or(0)),
+        mtime,
+        mtime,
+        LispObject::int_or_float_from_fixnum(stat.size.unwrap_or(0) as EmacsInt),
+        lisp_string(&mode_string(perm)),
+        LispObject::constant_nil(),
+        LispObject::from_natnum(0),
+        LispObject::from_fixnum(-1)
+    )
+}
+
+/// Return the type element of the attributes of the file at PATH with
+/// STAT: t for a directory, the target of a symbolic link, or nil.
+fn file_type(sftp: &ssh2::Sftp, path: &Path, stat: &FileStat) -> Result<LispObject, ssh2::Error> {
+    Ok(match stat.perm.unwrap_or(0) & S_IFMT {
+        S_IFDIR => LispObject::constant_t(),
+        S_IFLNK => lisp_string(&sftp.readlink(path)?.to_string_lossy()),
+        _ => LispObject::constant_nil(),
+    })
+}
+
+/// Return the attributes of the remote file FILENAME, or nil if it
+/// doesn't exist.  The result is like that of `file-attributes', with
+/// numeric user and group ids.  FILENAME is a name like
+/// `/rssh:USER@HOST#PORT:FILE'.  If FOLLOW is non-nil, give the
+/// attributes of the target of a symbolic link.
+#[lisp_fn(min = "1")]
+pub fn remote_file_attributes(filename: LispObject, follow: LispObject) -> LispObject {
+    with_sftp(filename, |sftp, path| {
+        let stat = if follow.is_nil() {
+            sftp.lstat(path)
+        } else {
+            sftp.stat(path)
+        };
+        match stat {
+            Ok(stat) => Ok(lisp_attributes(&stat, file_type(sftp, path, &stat)?)),
+            Err(ref err) if is_missing(err) => Ok(LispObject::constant_nil()),
+            Err(err) => Err(err),
+        }
+    })
+}
+
+/// Return the contents of the remote file FILENAME as a unibyte string.
+#[lisp_fn]
+pub fn remote_read_file(filename: LispObject) -> LispObject {
+    let contents = with_sftp(filename, |sftp, path| {
+        let mut contents = Vec::new();
+        let mut file = sftp.open(path)?;
+        file.read_to_end(&mut contents)
+            .map_err(|_| ssh2::Error::from_errno(ErrorCode::Session(-1)))?;
+        Ok(contents)
+    });
+    unibyte_string(&contents)
+}
+
+/// Write STRING to the remote file FILENAME, replacing its contents.
+/// If APPEND is non-nil, add STRING to the end of the file instead.
+/// The bytes of STRING are written as they are, so it should be a
+/// unibyte string.
+#[lisp_fn(min = "2")]
+pub fn remote_write_file(
+    filename: LispObject,
+    string: LispObject,
+    append: LispObject,
+) -> LispObject {
+    let contents = string.as_string_or_error().as_slice().to_vec();
+    with_sftp(filename, |sftp, path| {
+        let mode = if append.is_nil() {
+            OpenFlags::TRUNCATE
+        } else {
+            OpenFlags::APPEND
+        };
+        let flags = OpenFlags::WRITE | OpenFlags::CREATE | mode;
+        let mut file = sftp.open_mode(path, flags, 0o644, OpenType::File)?;
+        file.write_all(&contents)
+            .map_err(|_| ssh2::Error::from_errno(ErrorCode::Session(-1)))
+    });
+    LispObject::constant_nil()
+}
+
+/// Return the files in the remote directory DIRECTORY, as an alist of
+/// (NAME . ATTRIBUTES).  ATTRIBUTES are as `remote-file-attributes'
+/// gives them.  The entries "." and ".." are not included.
+#[lisp_fn]
+pub fn remote_directory_files_and_attributes(directory: LispObject) -> LispObject {
+    with_sftp(directory, |sftp, path| {
+        let mut result = LispObject::constant_nil();
+        for (file, stat) in sftp.readdir(path)? {
+            let name = match file.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            let attributes = lisp_attributes(&stat, file_type(sftp, &file, &stat)?);
+            result = LispObject::cons(LispObject::cons(lisp_string(&name), attributes), result);
+        }
+        Ok(result)
+    })
+}
+
+/// Delete the remote file FILENAME.  If FILENAME is a directory, it
+/// must be empty.
+#[lisp_fn]
+pub fn remote_delete_file(filename: LispObject) -> LispObject {
+    with_sftp(filename, |sftp, path| {
+        if sftp.lstat(path)?.perm.unwrap_or(0) & S_IFMT == S_IFDIR {
+            sftp.rmdir(path)
+        } else {
+            sftp.unlink(path)
+        }
+    });
+    LispObject::constant_nil()
+}
+
+/// Create the remote directory DIRECTORY.  Its parent must exist.
+#[lisp_fn]
+pub fn remote_make_directory(directory: LispObject) -> LispObject {
+    with_sftp(directory, |sftp, path| sftp.mkdir(path, 0o755));
+    LispObject::constant_nil()
+}
+
+/// Rename the remote file FILENAME to NEWNAME, a file on the same host.
+/// An existing file NEWNAME is replaced.
+#[lisp_fn]
+pub fn remote_rename_file(filename: LispObject, newname: LispObject) -> LispObject {
+    let target = remote_name(newname);
+    if target.connection() != remote_name(filename).connection() {
+        xsignal!(
+            intern("file-error").to_raw(),
+            lisp_string("Cannot rename to another host"),
+            newname
+        );
+    }
+    with_sftp(filename, |sftp, path| {
+        sftp.rename(path, Path::new(&target.path), Some(ssh2::RenameFlags::OVERWRITE))
+    });
+    LispObject::constant_nil()
+}
+
+/// Return the true name of the remote file FILENAME: its absolute
+/// name with symbolic links and "." and ".." resolved.  A relative
+/// FILENAME is relative to the home directory on the remote host.
+#[lisp_fn]
+pub fn remote_file_truename(filename: LispObject) -> LispObject {
+    let prefix = remote_name(filename).prefix;
+    let path = with_sftp(filename, |sftp, path| sftp.realpath(path));
+    lisp_string(&format!("{}{}", prefix, path.to_string_lossy()))
+}
+
+/// Close the connection to the host of the remote file FILENAME.  If
+/// FILENAME is nil, close all connections.
+#[lisp_fn(min = "0")]
+pub fn remote_disconnect(filename: LispObject) -> LispObject {
+    if filename.is_nil() {
+        CONNECTIONS.lock().unwrap().clear();
+    } else {
+        let key = remote_name(filename).connection();
+        CONNECTIONS.lock().unwrap().remove(&key);
+    }
+    LispObject::constant_nil()
+}
+
+#[test]
+fn test_parse_remote_name() {
+    assert_eq!(
+        parse_remote_name("/rssh:me@example.org#2222:/etc/hosts"),
+        Some(RemoteName {
+            prefix: "/rssh:me@example.org#2222:".to_string(),
+            user: Some("me".to_string()),
+            host: "example.org".to_string(),
+            port: 2222,
+            path: "/etc/hosts".to_string(),
+        })
+    );
+    let name = parse_remote_name("/rssh:example.org:").unwrap();
+    assert_eq!(name.user, None);
+    assert_eq!(name.port, DEFAULT_PORT);
+    assert_eq!(name.path, ".");
+    assert_eq!(name.known_host(), "example.org");
+    assert_eq!(parse_remote_name("/ssh:example.org:/"), None);
+    assert_eq!(parse_remote_name("/rssh:example.org"), None);
+    assert_eq!(parse_remote_name("/rssh:h#port:/"), None);
+}
+
+#[test]
+fn test_mode_string() {
+    assert_eq!(mode_string(0o040_755), "drwxr-xr-x");
+    assert_eq!(mode_string(0o100_644), "-rw-r--r--");
+    assert_eq!(mode_string(0o120_777), "lrwxrwxrwx");
+}
+
+include!(concat!(env!("OUT_DIR"), "/remote_exports.rs"));
@@ -0,0 +1,300 @@
+//! How far redisplay scrolls a window to bring point into view.
+//!
+//! Redisplay measures how far point is past the scroll margins of a
+//! window; the functions here decide, from those distances and the
+//! user's scrolling options, how wide the margins are and by how much
+//! the window should scroll, or whether it should be recentered
+//! instead.  They only do arithmetic, so that each option can be
+//! tested on its own.
+
+use libc::{c_int, ptrdiff_t};
+
+use remacs_sys::{EmacsDouble, EmacsInt, Lisp_Object};
+
+use lisp::LispObject;
+
+/// Redisplay never scrolls by more than this many lines, and a larger
+/// `scroll-conservatively' means to always scroll just enough.  This is
+/// `SCROLL_LIMIT' in xdisp.c.
+const SCROLL_LIMIT: ptrdiff_t = 100;
+
+/// Return the scroll margin of a window WINDOW_LINES lines high, in
+/// lines.  MARGIN is `scroll-margin', and MAX_RATIO is the value of
+/// `maximum-scroll-margin', if it is a float.  The margin never takes
+/// more than MAX_RATIO of the window, a quarter by default, nor leaves
+/// less than one line between the two margins.
+fn clamp_scroll_margin(
+    margin: EmacsInt,
+    window_lines: c_int,
+    max_ratio: Option<EmacsDouble>,
+) -> c_int {
+    if margin <= 0 {
+        return 0;
+    }
+    let ratio = max_ratio.map_or(0.25, |ratio| ratio.max(0.0).min(0.5));
+    let max_margin = ((window_lines - 1) / 2)
+        .min((window_lines as EmacsDouble * ratio) as c_int);
+    (margin.min(max_margin as EmacsInt) as c_int).max(0)
+}
+
+/// The scrolling options that apply to a window.
+#[derive(Clone, Copy, Debug)]
+struct Options {
+    /// `scroll-conservatively'.
+    conservatively: ptrdiff_t,
+    /// `scroll-step'.
+    step: ptrdiff_t,
+    /// Whether to scroll by one line at most, as for a command that
+    /// moves by lines.
+    temp_step: bool,
+    /// `scroll-up-aggressively' or `scroll-down-aggressively', as
+    /// relevant, if it is a number.
+    aggressively: Option<EmacsDouble>,
+    /// Whether either of them is a number.
+    any_aggressively: bool,
+}
+
+impl Options {
+    /// Return `scroll-conservatively' clipped to the scroll limit, and
+    /// the farthest, in pixels, that point can be past the margin for
+    /// scrolling to bring it back.  LINE_HEIGHT is the height of the
+    /// default font.  Past that distance, the window is recentered.
+    fn scroll_max(&self, line_height: c_int) -> (ptrdiff_t, c_int) {
+        let line_height = line_height as ptrdiff_t;
+        if self.conservatively > SCROLL_LIMIT {
+            (SCROLL_LIMIT + 1, (SCROLL_LIMIT * line_height) as c_int)
+        } else if self.step != 0 || self.conservatively != 0 || self.temp_step {
+            let lines = self.step
+                .max(self.conservatively)
+                .max(self.temp_step as ptrdiff_t);
+            (self.conservatively, (lines * line_height) as c_int)
+        } else if self.any_aggressively {
+            (self.conservatively, (10 * line_height) as c_int)
+        } else {
+            (self.conservatively, 0)
+        }
+    }
+
+    /// Return the number of pixels to scroll a window of HEIGHT pixels,
+    /// with margins of MARGIN pixels, to bring point back from DY
+    /// pixels past the margin, or 0 if the window should be recentered.
+    /// DOWN is true if point is past the bottom margin.  SCROLL_MAX is
+    /// as returned by `scroll_max', and CONSERVATIVELY the clipped
+    /// `scroll-conservatively' it returned.
+    fn amount(
+        &self,
+        down: bool,
+        dy: c_int,
+        conservatively: ptrdiff_t,
+        scroll_max: c_int,
+        height: c_int,
+        margin: c_int,
+        line_height: c_int,
+    ) -> c_int {
+        let amount = if conservatively != 0 {
+            if down {
+                // Just enough to show point, but at least one line.
+                let most = line_height as ptrdiff_t * conservatively;
+                (dy.max(line_height) as ptrdiff_t).min(most) as c_int
+            } else {
+                dy.max(line_height * self.step.max(self.temp_step as ptrdiff_t) as c_int)
+            }
+        } else if self.step != 0 || self.temp_step {
+            scroll_max
+        } else if let Some(aggressively) = self.aggressively {
+            // Put point that fraction of the window away from the
+            // margin, without letting it into the other margin.
+            let float_amount = aggressively * height as EmacsDouble;
+            let mut aggressive_scroll = float_amount as c_int;
+            if aggressive_scroll == 0 && float_amount > 0.0 {
+                aggressive_scroll = 1;
+            }
+            if aggressive_scroll + 2 * margin > height {
+                aggressive_scroll = height - 2 * margin;
+            }
+            dy + aggressive_scroll
+        } else {
+            0
+        };
+        amount.max(0)
+    }
+}
+
+/// Return the scroll margin of a window WINDOW_LINES lines high, in
+/// lines, for `scroll-margin' MARGIN and `maximum-scroll-margin'
+/// MAX_RATIO.  Called by `window_scroll_margin'.
+#[no_mangle]
+pub extern "C" fn scroll_margin_lines(
+    margin: EmacsInt,
+    window_lines: c_int,
+    max_ratio: Lisp_Object,
+) -> c_int {
+    clamp_scroll_margin(margin, window_lines, LispObject::from(max_ratio).as_float())
+}
+
+/// Clip *CONSERVATIVELY, the value of `scroll-conservatively', to the
+/// scroll limit, and return the farthest, in pixels, that point may be
+/// past a scroll margin for `try_scrolling' to scroll to it.
+/// AGGRESSIVELY is true if `scroll-up-aggressively' or
+/// `scroll-down-aggressively' is a number.
+#[no_mangle]
+pub extern "C" fn scroll_max_distance(
+    conservatively: *mut ptrdiff_t,
+    step: ptrdiff_t,
+    temp_step: bool,
+    aggressively: bool,
+    line_height: c_int,
+) -> c_int {
+    let options = Options {
+        conservatively: unsafe { *conservatively },
+        step,
+        temp_step,
+        aggressively: None,
+        any_aggressively: aggressively,
+    };
+    let (clipped, scroll_max) = options.scroll_max(line_height);
+    unsafe { *conservatively = clipped };
+    scroll_max
+}
+
+/// Return the number of pixels `try_scrolling' should scroll a window
+/// to bring point back from DY pixels past its bottom margin, if DOWN,
+/// or its top margin.  AGGRESSIVELY is `scroll-up-aggressively' when
+/// scrolling down and `scroll-down-aggressively' when scrolling up.  A
+/// value of 0 means to recenter the window instead.
+#[no_mangle]
+pub extern "C" fn scroll_amount(
+    down: bool,
+    dy: c_int,
+    conservatively: ptrdiff_t,
+    step: ptrdiff_t,
+    temp_step: bool,
+    scroll_max: c_int,
+    aggressively: Lisp_Object,
+    height: c_int,
+    margin: c_int,
+    line_height: c_int,
+) -> c_int {
+    let aggressively = LispObject::from(aggressively).any_to_float();
+    let options = Options {
+        conservatively,
+        step,
+        temp_step,
+        aggressively,
+        any_aggressively: aggressively.is_some(),
+    };
+    options.amount(
+        down,
+        dy,
+        conservatively,
+        scroll_max,
+        height,
+        margin,
+        line_height,
+    )
+}
+
+#[cfg(test)]
+fn options(
+    conservatively: ptrdiff_t,
+    step: ptrdiff_t,
+    aggressively: Option<EmacsDouble>,
+) -> Options {
+    Options {
+        conservatively,
+        step,
+        temp_step: false,
+        aggressively,
+        any_aggressively: aggressively.is_some(),
+    }
+}
+
+#[test]
+fn test_scroll_margin() {
+    // (scroll-margin, window lines, maximum-scroll-margin, margin)
+    let cases = [
+        (0, 40, None, 0),
+        (-3, 40, None, 0),
+        (3, 40, None, 3),
+        (20, 40, None, 10),
+        (20, 40, Some(0.5), 19),
+        (20, 40, Some(0.9), 19),
+        (20, 40, Some(-1.0), 0),
+        (5, 3, None, 0),
+        (5, 1, None, 0),
+        (2, 9, Some(0.1), 0),
+    ];
+    for &(margin, lines, ratio, expected) in cases.iter() {
+        assert_eq!(
+            clamp_scroll_margin(margin, lines, ratio),
+            expected,
+            "scroll-margin {} in {} lines, ratio {:?}",
+            margin,
+            lines,
+            ratio
+        );
+    }
+}
+
+#[test]
+fn test_scroll_max() {
+    // (conservatively, step, temp step, aggressively, result), with
+    // lines 10 pixels high.
+    let cases = [
+        (0, 0, false, None, (0, 0)),
+        (0, 0, true, None, (0, 10)),
+        (0, 3, false, None, (0, 30)),
+        (5, 3, false, None, (5, 50)),
+        (100, 0, false, None, (100, 1000)),
+        (101, 0, false, None, (101, 1000)),
+        (1_000_000, 0, false, None, (101, 1000)),
+        (0, 0, false, Some(0.5), (0, 100)),
+        (2, 0, false, Some(0.5), (2, 20)),
+    ];
+    for &(conservatively, step, temp_step, aggressively, expected) in cases.iter() {
+        let mut options = options(conservatively, step, aggressively);
+        options.temp_step = temp_step;
+        assert_eq!(options.scroll_max(10), expected, "{:?}", options);
+    }
+}
+
+#[test]
+fn test_scroll_amount() {
+    // (options, down, dy, amount) for a window 200 pixels high, with
+    // margins of 20 pixels and lines 10 pixels high.
+    let cases = [
+        // Conservative scrolling just shows point.  Down, it scrolls by
+        // at least a line, and no more than the option says; up, by at
+        // least the scroll step.
+        (options(5, 0, None), true, 25, 25),
+        (options(5, 0, None), true, 3, 10),
+        (options(5, 0, None), true, 80, 50),
+        (options(101, 0, None), true, 500, 500),
+        (options(5, 0, None), false, 25, 25),
+        (options(5, 0, None), false, 3, 3),
+        (options(5, 0, None), false, 80, 80),
+        (options(5, 4, None), false, 25, 40),
+        // A scroll step scrolls by that many lines.
+        (options(0, 3, None), true, 5, 30),
+        (options(0, 3, None), false, 5, 30),
+        // Aggressive scrolling puts point that far into the window.
+        (options(0, 0, Some(0.5)), true, 5, 105),
+        (options(0, 0, Some(0.001)), true, 5, 6),
+        (options(0, 0, Some(0.0)), true, 5, 5),
+        (options(0, 0, Some(0.95)), false, 5, 165),
+        // Otherwise, the window is recentered.
+        (options(0, 0, None), true, 5, 0),
+        (options(0, 0, None), false, 5, 0),
+    ];
+    for &(options, down, dy, expected) in cases.iter() {
+        let (conservatively, scroll_max) = options.scroll_max(10);
+        assert_eq!(
+            options.amount(down, dy, conservatively, scroll_max, 200, 20, 10),
+            expected,
+            "{:?}, down {}, dy {}",
+            options,
+            down,
+            dy
+        );
+    }
+}
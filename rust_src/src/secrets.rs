@@ -0,0 +1,670 @@
+//! Access to the keychain of the system.
+//!
+//! Secrets are kept by the Secret Service of the desktop on free
+//! systems, talked to over D-Bus, by the Keychain on macOS and by the
+//! Credential Manager on Windows.  Each secret belongs to a service,
+//! such as a host name, and an account at that service.
+
+#[cfg(any(target_os = "macos", windows))]
+use std::ptr;
+#[cfg(any(target_os = "macos", windows))]
+use std::slice;
+
+#[cfg(any(target_os = "macos", windows))]
+use libc::c_void;
+#[cfg(target_os = "macos")]
+use libc::c_char;
+
+#[cfg(windows)]
+use std::ffi::OsStr;
+#[cfg(windows)]
+use std::iter;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+
+use remacs_macros::lisp_fn;
+#[cfg(not(any(target_os = "macos", windows)))]
+use remacs_sys::{record_unwind_protect, unbind_to, Lisp_Object, Qnil};
+
+#[cfg(not(any(target_os = "macos", windows)))]
+use dbus;
+#[cfg(not(any(target_os = "macos", windows)))]
+use dbus::{Bus, Message, Value};
+#[cfg(not(any(target_os = "macos", windows)))]
+use eval_call::specpdl_index;
+#[cfg(not(any(target_os = "macos", windows)))]
+use lisp::intern;
+use lisp::LispObject;
+use lisp::defsubr;
+use strings::lisp_string;
+
+#[cfg(not(any(target_os = "macos", windows)))]
+const SERVICE: &str = "org.freedesktop.secrets";
+#[cfg(not(any(target_os = "macos", windows)))]
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+#[cfg(not(any(target_os = "macos", windows)))]
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+#[cfg(not(any(target_os = "macos", windows)))]
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+
+#[cfg(not(any(target_os = "macos", windows)))]
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+
+/// Whether the prompt being waited for was dismissed, once its
+/// `Completed' signal has come in.  Lisp threads run one at a time, so
+/// this needs no lock.
+static mut PROMPT_DISMISSED: Option<bool> = None;
+
+/// Call MEMBER of INTERFACE on the object at PATH of the Secret
+/// Service, and return the reply.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn call(path: &str, interface: &str, member: &str, body: Vec<Value>) -> Vec<Value> {
+    dbus::call_method(
+        Bus::Session,
+        Message {
+            mtype: dbus::METHOD_CALL,
+            path: Some(path.to_string()),
+            interface: Some(interface.to_string()),
+            member: Some(member.to_string()),
+            destination: Some(SERVICE.to_string()),
+            body,
+            ..Default::default()
+        },
+    )
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn string(s: &str) -> Value {
+    Value::Str(dbus::STRING, s.to_string())
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn object_path(s: &str) -> Value {
+    Value::Str(dbus::OBJECT_PATH, s.to_string())
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn entry(key: &str, value: Value) -> Value {
+    Value::DictEntry(Box::new(string(key)), Box::new(value))
+}
+
+/// Return the lookup attributes of the secret for ACCOUNT at SERVICE.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn attributes(service: &str, account: Option<&str>) -> Value {
+    let mut entries = vec![entry("service", string(service))];
+    if let Some(account) = account {
+        entries.push(entry("account", string(account)));
+    }
+    Value::Array("{ss}".to_string(), entries)
+}
+
+/// Return the object paths in VALUE, an array of them.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn paths(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(&Value::Array(_, ref values)) => values
+            .iter()
+            .filter_map(|v| match *v {
+                Value::Str(_, ref path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Return the object path in VALUE, or "/" if it is not one.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn path(value: Option<&Value>) -> String {
+    match value {
+        Some(&Value::Str(_, ref path)) => path.clone(),
+        _ => "/".to_string(),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+unsafe extern "C" fn unregister_prompt_handler(object: Lisp_Object) {
+    call!(intern("dbus-unregister-object"), LispObject::from(object));
+}
+
+/// Show the prompt at PATH, unless that is "/", and wait for the user
+/// to complete it.
+///
+/// As in `secrets-prompt', the `Completed' signal is handled as a
+/// `dbus-event' and events are read meanwhile, so Emacs goes on with
+/// redisplay, timers and process output, and the wait can be quit.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn prompt(path: &str) {
+    if path == "/" {
+        return;
+    }
+    call!(intern("require"), intern("dbus"));
+    unsafe { PROMPT_DISMISSED = None };
+    let count = specpdl_index();
+    let object = call!(
+        intern("dbus-register-signal"),
+        intern(":session"),
+        lisp_string(SERVICE),
+        lisp_string(path),
+        lisp_string(PROMPT_INTERFACE),
+        lisp_string("Completed"),
+        intern("secrets--prompt-completed")
+    );
+    unsafe { record_unwind_protect(unregister_prompt_handler, object.to_raw()) };
+    call(path, PROMPT_INTERFACE, "Prompt", vec![string("")]);
+    let dismissed = loop {
+        if let Some(dismissed) = unsafe { PROMPT_DISMISSED.take() } {
+            break dismissed;
+        }
+        call!(
+            intern("read-event"),
+            LispObject::constant_nil(),
+            LispObject::constant_nil(),
+            LispObject::from_float(0.1)
+        );
+    };
+    unsafe { unbind_to(count, Qnil) };
+    if dismissed {
+        error!("Prompt dismissed");
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn unlock(paths: Vec<String>) {
+    let objects = paths.iter().map(|p| object_path(p)).collect();
+    let reply = call(
+        SERVICE_PATH,
+        SERVICE_INTERFACE,
+        "Unlock",
+        vec![Value::Array("o".to_string(), objects)],
+    );
+    prompt(&path(reply.get(1)));
+}
+
+/// Open a session that passes secrets unencrypted, which is fine on
+/// the local session bus.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn open_session() -> String {
+    let reply = call(
+        SERVICE_PATH,
+        SERVICE_INTERFACE,
+        "OpenSession",
+        vec![string("plain"), Value::Variant(Box::new(string("")))],
+    );
+    path(reply.get(1))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn close_session(session: &str) {
+    call(session, "org.freedesktop.Secret.Session", "Close", vec![]);
+}
+
+/// Return the account attribute of the item at PATH.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn item_account(item: &str) -> String {
+    let reply = call(
+        item,
+        "org.freedesktop.DBus.Properties",
+        "Get",
+        vec![
+            string("org.freedesktop.Secret.Item"),
+            string("Attributes"),
+        ],
+    );
+    let entries = match reply.first() {
+        Some(&Value::Variant(ref attributes)) => match **attributes {
+            Value::Array(_, ref entries) => entries.clone(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    for e in entries {
+        if let Value::DictEntry(key, value) = e {
+            match (*key, *value) {
+                (Value::Str(_, ref key), Value::Str(_, ref value)) if key == "account" => {
+                    return value.clone()
+                }
+                _ => (),
+            }
+        }
+    }
+    String::new()
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn store(service: &str, account: &str, secret: &[u8]) {
+    unlock(vec![DEFAULT_COLLECTION.to_string()]);
+    let session = open_session();
+    let label = format!("{}@{}", account, service);
+    let properties = Value::Array(
+        "{sv}".to_string(),
+        vec![
+            entry(
+                "org.freedesktop.Secret.Item.Label",
+                Value::Variant(Box::new(string(&label))),
+            ),
+            entry(
+                "org.freedesktop.Secret.Item.Attributes",
+                Value::Variant(Box::new(attributes(service, Some(account)))),
+            ),
+        ],
+    );
+    let bytes = |bytes: &[u8]| {
+        Value::Array(
+            "y".to_string(),
+            bytes.iter().map(|&b| Value::Byte(b)).collect(),
+        )
+    };
+    let secret = Value::Struct(vec![
+        object_path(&session),
+        bytes(&[]),
+        bytes(secret),
+        string("text/plain; charset=utf8"),
+    ]);
+    let reply = call(
+        DEFAULT_COLLECTION,
+        "org.freedesktop.Secret.Collection",
+        "CreateItem",
+        vec![properties, secret, Value::Boolean(true)],
+    );
+    close_session(&session);
+    prompt(&path(reply.get(1)));
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn lookup(service: &str, account: Option<&str>) -> Option<(String, Vec<u8>)> {
+    let reply = call(
+        SERVICE_PATH,
+        SERVICE_INTERFACE,
+        "SearchItems",
+        vec![attributes(service, account)],
+    );
+    let unlocked = paths(reply.get(0));
+    let locked = paths(reply.get(1));
+    let item = match (unlocked.first(), locked.first()) {
+        (Some(item), _) => item.clone(),
+        (None, Some(item)) => {
+            unlock(vec![item.clone()]);
+            item.clone()
+        }
+        (None, None) => return None,
+    };
+    let account = match account {
+        Some(account) => account.to_string(),
+        None => item_account(&item),
+    };
+    let session = open_session();
+    let reply = call(
+        &item,
+        "org.freedesktop.Secret.Item",
+        "GetSecret",
+        vec![object_path(&session)],
+    );
+    close_session(&session);
+    // The secret is a struct of session, parameters, value and content
+    // type.
+    match reply.first() {
+        Some(&Value::Struct(ref fields)) => match fields.get(2) {
+            Some(&Value::Array(_, ref bytes)) => {
+                let secret = bytes
+                    .iter()
+                    .filter_map(|b| match *b {
+                        Value::Byte(b) => Some(b),
+                        _ => None,
+                    })
+                    .collect();
+                Some((account, secret))
+            }
+            _ => error!("Invalid secret from the Secret Service"),
+        },
+        _ => error!("Invalid secret from the Secret Service"),
+    }
+}
+
+#[cfg(target_os = "macos")]
+const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+/// The attribute tag of the account of an item, `acct'.
+#[cfg(target_os = "macos")]
+const K_SEC_ACCOUNT_ITEM_ATTR: u32 = 0x6163_6374;
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct SecKeychainAttributeInfo {
+    count: u32,
+    tag: *mut u32,
+    format: *mut u32,
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct SecKeychainAttribute {
+    tag: u32,
+    length: u32,
+    data: *mut c_void,
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct SecKeychainAttributeList {
+    count: u32,
+    attr: *mut SecKeychainAttribute,
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "Security", kind = "framework")]
+extern "C" {
+    fn SecKeychainAddGenericPassword(
+        keychain: *mut c_void,
+        service_length: u32,
+        service: *const c_char,
+        account_length: u32,
+        account: *const c_char,
+        password_length: u32,
+        password: *const c_void,
+        item: *mut *mut c_void,
+    ) -> i32;
+    fn SecKeychainFindGenericPassword(
+        keychains: *const c_void,
+        service_length: u32,
+        service: *const c_char,
+        account_length: u32,
+        account: *const c_char,
+        password_length: *mut u32,
+        password: *mut *mut c_void,
+        item: *mut *mut c_void,
+    ) -> i32;
+    fn SecKeychainItemModifyAttributesAndData(
+        item: *mut c_void,
+        attributes: *const c_void,
+        length: u32,
+        data: *const c_void,
+    ) -> i32;
+    fn SecKeychainItemCopyAttributesAndData(
+        item: *mut c_void,
+        info: *mut SecKeychainAttributeInfo,
+        item_class: *mut u32,
+        attributes: *mut *mut SecKeychainAttributeList,
+        length: *mut u32,
+        data: *mut *mut c_void,
+    ) -> i32;
+    fn SecKeychainItemFreeAttributesAndData(
+        attributes: *mut SecKeychainAttributeList,
+        data: *mut c_void,
+    ) -> i32;
+    fn SecKeychainItemFreeContent(attributes: *mut c_void, data: *mut c_void) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(object: *const c_void);
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_error(status: i32) -> ! {
+    error!("Keychain error {}", status)
+}
+
+/// Return the account of the keychain ITEM.
+#[cfg(target_os = "macos")]
+unsafe fn item_account(item: *mut c_void) -> String {
+    let mut tag = K_SEC_ACCOUNT_ITEM_ATTR;
+    let mut format = 0;
+    let mut info = SecKeychainAttributeInfo {
+        count: 1,
+        tag: &mut tag,
+        format: &mut format,
+    };
+    let mut list = ptr::null_mut();
+    let status = SecKeychainItemCopyAttributesAndData(
+        item,
+        &mut info,
+        ptr::null_mut(),
+        &mut list,
+        ptr::null_mut(),
+        ptr::null_mut(),
+    );
+    if status != 0 || (*list).count == 0 {
+        return String::new();
+    }
+    let attr = &*(*list).attr;
+    let bytes = slice::from_raw_parts(attr.data as *const u8, attr.length as usize);
+    let account = String::from_utf8_lossy(bytes).into_owned();
+    SecKeychainItemFreeAttributesAndData(list, ptr::null_mut());
+    account
+}
+
+#[cfg(target_os = "macos")]
+fn store(service: &str, account: &str, secret: &[u8]) {
+    let status = unsafe {
+        let mut item = ptr::null_mut();
+        let found = SecKeychainFindGenericPassword(
+            ptr::null(),
+            service.len() as u32,
+            service.as_ptr() as *const c_char,
+            account.len() as u32,
+            account.as_ptr() as *const c_char,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut item,
+        );
+        match found {
+            0 => {
+                let status = SecKeychainItemModifyAttributesAndData(
+                    item,
+                    ptr::null(),
+                    secret.len() as u32,
+                    secret.as_ptr() as *const c_void,
+                );
+                CFRelease(item);
+                status
+            }
+            ERR_SEC_ITEM_NOT_FOUND => SecKeychainAddGenericPassword(
+                ptr::null_mut(),
+                service.len() as u32,
+                service.as_ptr() as *const c_char,
+                account.len() as u32,
+                account.as_ptr() as *const c_char,
+                secret.len() as u32,
+                secret.as_ptr() as *const c_void,
+                ptr::null_mut(),
+            ),
+            status => status,
+        }
+    };
+    if status != 0 {
+        keychain_error(status);
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn lookup(service: &str, account: Option<&str>) -> Option<(String, Vec<u8>)> {
+    // An empty account matches any.
+    let account_name = account.unwrap_or("");
+    unsafe {
+        let mut length = 0;
+        let mut data = ptr::null_mut();
+        let mut item = ptr::null_mut();
+        let status = SecKeychainFindGenericPassword(
+            ptr::null(),
+            service.len() as u32,
+            service.as_ptr() as *const c_char,
+            account_name.len() as u32,
+            account_name.as_ptr() as *const c_char,
+            &mut length,
+            &mut data,
+            &mut item,
+        );
+        match status {
+            0 => (),
+            ERR_SEC_ITEM_NOT_FOUND => return None,
+            status => keychain_error(status),
+        }
+        let secret = slice::from_raw_parts(data as *const u8, length as usize).to_vec();
+        SecKeychainItemFreeContent(ptr::null_mut(), data);
+        let account = match account {
+            Some(account) => account.to_string(),
+            None => item_account(item),
+        };
+        CFRelease(item);
+        Some((account, secret))
+    }
+}
+
+#[cfg(windows)]
+const CRED_TYPE_GENERIC: u32 = 1;
+#[cfg(windows)]
+const CRED_PERSIST_LOCAL_MACHINE: u32 = 2;
+#[cfg(windows)]
+const ERROR_NOT_FOUND: u32 = 1168;
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct CREDENTIALW {
+    Flags: u32,
+    Type: u32,
+    TargetName: *mut u16,
+    Comment: *mut u16,
+    LastWritten: u64,
+    CredentialBlobSize: u32,
+    CredentialBlob: *mut u8,
+    Persist: u32,
+    AttributeCount: u32,
+    Attributes: *mut c_void,
+    TargetAlias: *mut u16,
+    UserName: *mut u16,
+}
+
+#[cfg(windows)]
+extern "system" {
+    fn CredWriteW(credential: *const CREDENTIALW, flags: u32) -> i32;
+    fn CredReadW(
+        target: *const u16,
+        kind: u32,
+        flags: u32,
+        credential: *mut *mut CREDENTIALW,
+    ) -> i32;
+    fn CredFree(buffer: *mut c_void);
+    fn GetLastError() -> u32;
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(iter::once(0)).collect()
+}
+
+#[cfg(windows)]
+unsafe fn from_wide(s: *const u16) -> String {
+    let mut len = 0;
+    while *s.offset(len) != 0 {
+        len += 1;
+    }
+    String::from_utf16_lossy(slice::from_raw_parts(s, len as usize))
+}
+
+/// Credentials are named after their service, so there is one account
+/// per service.
+#[cfg(windows)]
+fn store(service: &str, account: &str, secret: &[u8]) {
+    let mut target = wide(service);
+    let mut user = wide(account);
+    let mut blob = secret.to_vec();
+    let credential = CREDENTIALW {
+        Flags: 0,
+        Type: CRED_TYPE_GENERIC,
+        TargetName: target.as_mut_ptr(),
+        Comment: ptr::null_mut(),
+        LastWritten: 0,
+        CredentialBlobSize: blob.len() as u32,
+        CredentialBlob: blob.as_mut_ptr(),
+        Persist: CRED_PERSIST_LOCAL_MACHINE,
+        AttributeCount: 0,
+        Attributes: ptr::null_mut(),
+        TargetAlias: ptr::null_mut(),
+        UserName: user.as_mut_ptr(),
+    };
+    if unsafe { CredWriteW(&credential, 0) } == 0 {
+        error!("Credential Manager error {}", unsafe { GetLastError() });
+    }
+}
+
+#[cfg(windows)]
+fn lookup(service: &str, account: Option<&str>) -> Option<(String, Vec<u8>)> {
+    let target = wide(service);
+    unsafe {
+        let mut credential = ptr::null_mut();
+        if CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) == 0 {
+            match GetLastError() {
+                ERROR_NOT_FOUND => return None,
+                code => error!("Credential Manager error {}", code),
+            }
+        }
+        let user = if (*credential).UserName.is_null() {
+            String::new()
+        } else {
+            from_wide((*credential).UserName)
+        };
+        let secret = slice::from_raw_parts(
+            (*credential).CredentialBlob,
+            (*credential).CredentialBlobSize as usize,
+        ).to_vec();
+        CredFree(credential as *mut c_void);
+        match account {
+            Some(account) if account != user => None,
+            _ => Some((user, secret)),
+        }
+    }
+}
+
+fn string_value(object: LispObject) -> String {
+    String::from_utf8_lossy(object.as_string_or_error().as_slice()).into_owned()
+}
+
+/// Store SECRET for ACCOUNT at SERVICE in the keychain of the system.
+/// SERVICE, ACCOUNT and SECRET are strings.  An existing secret for
+/// ACCOUNT at SERVICE is replaced.
+///
+/// The keychain is the default collection of the Secret Service on
+/// free desktops, the Keychain on macOS and the Credential Manager on
+/// Windows.  The latter keeps only one account for each service.  If
+/// the keychain is locked, the user is asked to unlock it.
+#[lisp_fn]
+pub fn secrets_store(service: LispObject, account: LispObject, secret: LispObject) -> LispObject {
+    let service = string_value(service);
+    let account = string_value(account);
+    store(&service, &account, secret.as_string_or_error().as_slice());
+    LispObject::constant_t()
+}
+
+/// Look up the secret for ACCOUNT at SERVICE in the keychain of the
+/// system.  If ACCOUNT is nil, look up the secret of any account at
+/// SERVICE.  Return a cons (ACCOUNT . SECRET), or nil if there is no
+/// such secret.  See `secrets-store' for the keychains used.
+#[lisp_fn(min = "1")]
+pub fn secrets_lookup(service: LispObject, account: LispObject) -> LispObject {
+    let service = string_value(service);
+    let account = if account.is_nil() {
+        None
+    } else {
+        Some(string_value(account))
+    };
+    match lookup(&service, account.as_ref().map(|a| a.as_str())) {
+        Some((account, secret)) => LispObject::cons(
+            lisp_string(&account),
+            lisp_string(&String::from_utf8_lossy(&secret)),
+        ),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Handle the `Completed' signal of a Secret Service prompt.
+/// DISMISSED is non-nil if the user dismissed the prompt.  This is an
+/// internal function.
+#[lisp_fn(name = "secrets--prompt-completed", c_name = "secrets__prompt_completed", min = "1")]
+pub fn secrets_prompt_completed(dismissed: LispObject, _result: LispObject) -> LispObject {
+    unsafe { PROMPT_DISMISSED = Some(dismissed.is_not_nil()) };
+    LispObject::constant_nil()
+}
+
+include!(concat!(env!("OUT_DIR"), "/secrets_exports.rs"));
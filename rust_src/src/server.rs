@@ -0,0 +1,260 @@
+//! Framing for the Emacs server protocol.
+//!
+//! emacsclient sends each request as a single line of arguments
+//! separated by spaces.  The arguments are &-quoted so that they
+//! contain neither spaces nor newlines: `&_' stands for a space, `&n'
+//! for a newline, `&&' for an ampersand and `&-' for a dash, which
+//! would otherwise start a command.  Replies from the server use the
+//! same quoting.  Everything here works on the bytes of the string,
+//! since the characters involved are all ASCII.
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_specified_string, EmacsInt};
+
+use lisp::LispObject;
+use lisp::defsubr;
+
+const AUTH_PREFIX: &[u8] = b"-auth ";
+
+fn quote_arg(arg: &[u8]) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(arg.len());
+    for &b in arg {
+        match b {
+            b'&' => quoted.extend_from_slice(b"&&"),
+            b'-' => quoted.extend_from_slice(b"&-"),
+            b'\n' => quoted.extend_from_slice(b"&n"),
+            b' ' => quoted.extend_from_slice(b"&_"),
+            _ => quoted.push(b),
+        }
+    }
+    quoted
+}
+
+/// Any other quoted character stands for itself, as in emacsclient.
+/// A lone `&' at the end of ARG is dropped.
+fn unquote_arg(arg: &[u8]) -> Vec<u8> {
+    let mut unquoted = Vec::with_capacity(arg.len());
+    let mut bytes = arg.iter();
+    while let Some(&b) = bytes.next() {
+        if b != b'&' {
+            unquoted.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(&b'n') => unquoted.push(b'\n'),
+            Some(&b'_') => unquoted.push(b' '),
+            Some(&c) => unquoted.push(c),
+            None => (),
+        }
+    }
+    unquoted
+}
+
+/// Split the first complete line of TEXT into unquoted arguments.
+/// Return them with the offset of the text following the line, or
+/// None if TEXT holds no newline yet.
+fn parse_request(text: &[u8]) -> Option<(Vec<Vec<u8>>, usize)> {
+    let newline = match text.iter().position(|&b| b == b'\n') {
+        Some(newline) => newline,
+        None => return None,
+    };
+    let args = text[..newline]
+        .split(|&b| b == b' ')
+        .filter(|arg| !arg.is_empty())
+        .map(unquote_arg)
+        .collect();
+    Some((args, newline + 1))
+}
+
+/// Compare A and B in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Check that TEXT starts with `-auth KEY'.  Return the offset of the
+/// text following the key and an optional newline, or None if the key
+/// is missing or wrong.
+fn authenticate(text: &[u8], key: &[u8]) -> Option<usize> {
+    if !text.starts_with(AUTH_PREFIX) {
+        return None;
+    }
+    let start = AUTH_PREFIX.len();
+    let end = start
+        + text[start..]
+            .iter()
+            .take_while(|&&b| b >= b'!' && b <= b'~')
+            .count();
+    if end == start || !constant_time_eq(&text[start..end], key) {
+        return None;
+    }
+    if text.get(end) == Some(&b'\n') {
+        Some(end + 1)
+    } else {
+        Some(end)
+    }
+}
+
+/// Split the quoted text QTEXT into `-print' and `-print-nonl' messages
+/// of at most SIZE bytes each, newline included.  Messages are never
+/// split inside a quote sequence, nor inside a character if MULTIBYTE.
+fn split_reply(qtext: &[u8], size: usize, multibyte: bool) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut prefix: &[u8] = b"-print ";
+    let mut rest = qtext;
+    while prefix.len() + rest.len() + 1 > size {
+        let mut end = size.saturating_sub(prefix.len() + 1);
+        while multibyte && end > 0 && rest[end] & 0xC0 == 0x80 {
+            end -= 1;
+        }
+        let ampersands = rest[..end].iter().rev().take_while(|&&b| b == b'&').count();
+        if ampersands % 2 == 1 {
+            end -= 1;
+        }
+        if end == 0 {
+            // SIZE is too small to make progress; send the rest whole.
+            break;
+        }
+        messages.push([prefix, &rest[..end], &b"\n"[..]].concat());
+        rest = &rest[end..];
+        prefix = b"-print-nonl ";
+    }
+    messages.push([prefix, rest, &b"\n"[..]].concat());
+    messages
+}
+
+fn make_string(bytes: &[u8], multibyte: bool) -> LispObject {
+    LispObject::from(unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            -1,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    })
+}
+
+/// In ARG, insert a & before each &, each space, each newline, and -.
+/// Change spaces to underscores, too, so that the return value never
+/// contains a space.
+///
+/// See `server-unquote-arg' and `server-process-filter'.
+#[lisp_fn]
+pub fn server_quote_arg(arg: LispObject) -> LispObject {
+    let arg = arg.as_string_or_error();
+    make_string(&quote_arg(arg.as_slice()), arg.is_multibyte())
+}
+
+/// Remove &-quotation from ARG.
+/// A & followed by any character other than &, -, n or _ stands for
+/// that character, as emacsclient reads it.
+///
+/// See `server-quote-arg' and `server-process-filter'.
+#[lisp_fn]
+pub fn server_unquote_arg(arg: LispObject) -> LispObject {
+    let arg = arg.as_string_or_error();
+    make_string(&unquote_arg(arg.as_slice()), arg.is_multibyte())
+}
+
+/// Parse the first request sent by an emacsclient in STRING.
+/// Return nil if STRING does not contain a complete, newline terminated
+/// request yet.  Otherwise return (ARGS . REST), where ARGS is the list
+/// of unquoted arguments of the request and REST is the rest of STRING.
+#[lisp_fn]
+pub fn server_parse_request(string: LispObject) -> LispObject {
+    let string = string.as_string_or_error();
+    let multibyte = string.is_multibyte();
+    let text = string.as_slice();
+    match parse_request(text) {
+        None => LispObject::constant_nil(),
+        Some((args, rest)) => {
+            let args = args.iter()
+                .rev()
+                .fold(LispObject::constant_nil(), |list, arg| {
+                    LispObject::cons(make_string(arg, multibyte), list)
+                });
+            LispObject::cons(args, make_string(&text[rest..], multibyte))
+        }
+    }
+}
+
+/// Check the authentication command at the start of STRING.
+/// STRING must start with `-auth KEY', optionally followed by a
+/// newline, where KEY is the string AUTH-KEY.  Return the rest of
+/// STRING if it does, and nil otherwise.  The keys are compared in
+/// constant time.
+#[lisp_fn]
+pub fn server_authenticate(string: LispObject, auth_key: LispObject) -> LispObject {
+    let string = string.as_string_or_error();
+    let auth_key = match auth_key.as_string() {
+        Some(key) => key,
+        None => return LispObject::constant_nil(),
+    };
+    let text = string.as_slice();
+    match authenticate(text, auth_key.as_slice()) {
+        Some(rest) => make_string(&text[rest..], string.is_multibyte()),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Return the list of messages sending the quoted text QTEXT to a client.
+/// The first message is a `-print' command and the following ones are
+/// `-print-nonl' commands, each terminated by a newline.  No message is
+/// longer than SIZE bytes, and none ends inside a quote sequence or a
+/// multibyte character.
+#[lisp_fn]
+pub fn server_split_reply(qtext: LispObject, size: LispObject) -> LispObject {
+    let qtext = qtext.as_string_or_error();
+    let size = size.as_fixnum_or_error();
+    if size <= 0 {
+        args_out_of_range!(LispObject::from_fixnum(size), 1, EmacsInt::max_value());
+    }
+    let multibyte = qtext.is_multibyte();
+    split_reply(qtext.as_slice(), size as usize, multibyte)
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, message| {
+            LispObject::cons(make_string(message, multibyte), list)
+        })
+}
+
+#[test]
+fn test_quote_arg() {
+    let arg = b"-a b&c\nd";
+    assert_eq!(quote_arg(arg), b"&-a&_b&&c&nd".to_vec());
+    assert_eq!(unquote_arg(&quote_arg(arg)), arg.to_vec());
+    assert_eq!(unquote_arg(b"&x&"), b"x".to_vec());
+}
+
+#[test]
+fn test_parse_request() {
+    assert_eq!(parse_request(b"-dir /tmp/"), None);
+    let (args, rest) = parse_request(b"-file  a&nb&_c\n-eval").unwrap();
+    assert_eq!(args, vec![b"-file".to_vec(), b"a\nb c".to_vec()]);
+    assert_eq!(rest, 15);
+}
+
+#[test]
+fn test_authenticate() {
+    let key = b"0123456789";
+    assert_eq!(authenticate(b"-auth 0123456789 -dir /\n", key), Some(16));
+    assert_eq!(authenticate(b"-auth 0123456789\n-eval", key), Some(17));
+    assert_eq!(authenticate(b"-auth 0123456788 -dir /\n", key), None);
+    assert_eq!(authenticate(b"-auth 012345678 -dir /\n", key), None);
+    assert_eq!(authenticate(b"-dir / -auth 0123456789\n", key), None);
+}
+
+#[test]
+fn test_split_reply() {
+    assert_eq!(split_reply(b"abc", 20, false), vec![b"-print abc\n".to_vec()]);
+    let messages = split_reply(b"abcd&&e", 13, false);
+    assert_eq!(
+        messages,
+        vec![b"-print abcd\n".to_vec(), b"-print-nonl &&e\n".to_vec()]
+    );
+    let messages = split_reply("a\u{e9}".as_bytes(), 10, true);
+    assert_eq!(messages[0], b"-print a\n".to_vec());
+}
+
+include!(concat!(env!("OUT_DIR"), "/server_exports.rs"));
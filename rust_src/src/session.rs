@@ -0,0 +1,212 @@
+//! Session snapshots.
+//!
+//! A snapshot records what is needed to pick up a session where it was
+//! left: the buffers visiting files, with their point, mark and major
+//! mode; the window state of the selected frame; and the global values
+//! of some variables.  It is kept in the format of `lisp-data-write' as
+//! the plist
+//!
+//!     (:buffers BUFFERS :window-state STATE :variables VARIABLES)
+//!
+//! Taking the snapshot is quick.  Writing it out, including syncing it
+//! to disk, happens on a separate thread.
+
+use std::fs::File;
+use std::io::Read;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{record_unwind_current_buffer, unbind_to, EmacsInt, Qnil};
+
+use buffers::{buffer_file_name, buffer_name, set_buffer};
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{list, plist_get};
+use persist::{decode, encode, finish_background_writes, is_writable, write_in_background};
+use util::{expand_file_name_to_path, report_io_error};
+
+fn local_value(variable: &str, buffer: LispObject) -> LispObject {
+    call!(intern("buffer-local-value"), intern(variable), buffer)
+}
+
+/// Return the plists describing the buffers that visit files.
+fn buffer_entries() -> LispObject {
+    let mut entries: Vec<LispObject> = call!(intern("buffer-list"))
+        .iter_cars()
+        .filter(|&buffer| buffer_file_name(buffer).is_not_nil())
+        .map(|buffer| {
+            let buf = buffer.as_buffer_or_error();
+            list!(
+                intern(":file"),
+                buffer_file_name(buffer),
+                intern(":name"),
+                buffer_name(buffer),
+                intern(":point"),
+                LispObject::from_natnum(buf.pt() as EmacsInt),
+                intern(":mark"),
+                call!(intern("marker-position"), buf.mark()),
+                intern(":mode"),
+                local_value("major-mode", buffer),
+                intern(":read-only"),
+                LispObject::from_bool(local_value("buffer-read-only", buffer).is_not_nil())
+            )
+        })
+        .collect();
+    list(&mut entries)
+}
+
+/// Return the window state of the selected frame, or nil if some of it
+/// cannot be saved.
+fn window_state() -> LispObject {
+    let state = call!(
+        intern("window-state-get"),
+        call!(intern("frame-root-window")),
+        LispObject::constant_t()
+    );
+    if is_writable(state) {
+        state
+    } else {
+        LispObject::constant_nil()
+    }
+}
+
+/// Return an alist of the symbols in VARIABLES and their global values,
+/// leaving out the unbound ones and those whose values can't be saved.
+fn variable_values(variables: LispObject) -> LispObject {
+    let mut values: Vec<LispObject> = variables
+        .iter_cars()
+        .filter(|&variable| {
+            variable.is_symbol() && call!(intern("default-boundp"), variable).is_not_nil()
+        })
+        .map(|variable| {
+            LispObject::cons(variable, call!(intern("default-value"), variable))
+        })
+        .filter(|&value| is_writable(value))
+        .collect();
+    list(&mut values)
+}
+
+/// Visit the file of the saved buffer ENTRY, if it can still be read,
+/// and put back its point, mark, major mode and read-only state.
+fn restore_buffer(entry: LispObject) -> Option<LispObject> {
+    let file = plist_get(entry, intern(":file"));
+    if !file.is_string() || call!(intern("file-readable-p"), file).is_nil() {
+        return None;
+    }
+    let buffer = call!(intern("find-file-noselect"), file);
+    let count = specpdl_index();
+    unsafe { record_unwind_current_buffer() };
+    set_buffer(buffer);
+
+    let mode = plist_get(entry, intern(":mode"));
+    if mode.is_symbol()
+        && mode.is_not_nil()
+        && !mode.eq(local_value("major-mode", buffer))
+        && call!(intern("fboundp"), mode).is_not_nil()
+    {
+        call!(mode);
+    }
+    let point = plist_get(entry, intern(":point"));
+    if point.is_natnum() {
+        call!(intern("goto-char"), point);
+    }
+    let mark = plist_get(entry, intern(":mark"));
+    if mark.is_natnum() {
+        call!(intern("set-marker"), call!(intern("mark-marker")), mark);
+    }
+    if plist_get(entry, intern(":read-only")).is_not_nil() {
+        call!(
+            intern("set"),
+            intern("buffer-read-only"),
+            LispObject::constant_t()
+        );
+    }
+    unsafe { unbind_to(count, Qnil) };
+    Some(buffer)
+}
+
+/// Save a snapshot of the session in FILE.
+/// The snapshot records the buffers visiting files, with their point,
+/// mark, major mode and whether they are read-only; the window state of
+/// the selected frame, as `window-state-get' returns it; and the global
+/// values of the symbols in VARIABLES.  Variables that are unbound, or
+/// whose values `lisp-data-write' cannot write, are left out.
+///
+/// FILE is written on a separate thread, so this returns at once
+/// unless WAIT is non-nil.  Saving another snapshot first waits for the
+/// previous one, and `session-snapshot-wait' waits for the one being
+/// written; both signal an error if it could not be written.
+///
+/// Use `session-snapshot-restore' to restore the session.
+#[lisp_fn(min = "1")]
+pub fn session_snapshot_save(
+    file: LispObject,
+    variables: LispObject,
+    wait: LispObject,
+) -> LispObject {
+    let path = expand_file_name_to_path(file);
+    let name = String::from_utf8_lossy(file.as_string_or_error().as_slice()).into_owned();
+    let snapshot = list!(
+        intern(":buffers"),
+        buffer_entries(),
+        intern(":window-state"),
+        window_state(),
+        intern(":variables"),
+        variable_values(variables)
+    );
+    write_in_background(path, name, encode(snapshot));
+    if wait.is_not_nil() {
+        finish_background_writes();
+    }
+    LispObject::constant_nil()
+}
+
+/// Wait until the snapshot being saved by `session-snapshot-save' is
+/// written.  Signal an error if it could not be written.
+#[lisp_fn]
+pub fn session_snapshot_wait() -> LispObject {
+    finish_background_writes();
+    LispObject::constant_nil()
+}
+
+/// Restore the session saved by `session-snapshot-save' in FILE.
+/// Set the global values of the saved variables, visit the saved files
+/// that can still be read and put back their point, mark and major
+/// mode.  Unless NO-WINDOWS is non-nil, also put the saved window state
+/// into the selected frame.  Return the list of buffers visiting the
+/// saved files.
+#[lisp_fn(min = "1")]
+pub fn session_snapshot_restore(file: LispObject, no_windows: LispObject) -> LispObject {
+    finish_background_writes();
+    let path = expand_file_name_to_path(file);
+    let mut data = Vec::new();
+    if let Err(err) = File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+        report_io_error(b"Reading session snapshot\0", file, &err);
+    }
+    let snapshot = decode(&data);
+    if !snapshot.is_cons() {
+        error!("Not a session snapshot");
+    }
+
+    for variable in plist_get(snapshot, intern(":variables")).iter_cars_safe() {
+        if let Some(cons) = variable.as_cons() {
+            call!(intern("set-default"), cons.car(), cons.cdr());
+        }
+    }
+    let mut buffers: Vec<LispObject> = plist_get(snapshot, intern(":buffers"))
+        .iter_cars_safe()
+        .filter_map(restore_buffer)
+        .collect();
+    let state = plist_get(snapshot, intern(":window-state"));
+    if no_windows.is_nil() && state.is_cons() {
+        call!(
+            intern("window-state-put"),
+            state,
+            call!(intern("frame-root-window")),
+            intern("safe")
+        );
+    }
+    list(&mut buffers)
+}
+
+include!(concat!(env!("OUT_DIR"), "/session_exports.rs"));
@@ -0,0 +1,327 @@
+//! Spell checking through the Enchant library.
+//!
+//! Enchant is loaded with `dlopen' the first time it is needed, so
+//! Emacs neither links against it nor fails to start without it.  It
+//! provides a common interface to Hunspell, Aspell, Nuspell and other
+//! spell checkers, chosen by its own configuration for each language.
+//!
+//! Dictionaries stay open once requested, so that words added to a
+//! dictionary's session are remembered until Emacs exits.  Lisp errors
+//! must not be signaled while the library state is locked, so failures
+//! are returned as messages and signaled by the caller.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::CStr;
+use std::mem;
+use std::ptr;
+use std::sync::Mutex;
+
+use libc::{self, c_char, c_int, c_void, ptrdiff_t, size_t, ssize_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::make_unibyte_string;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+
+type Broker = *mut c_void;
+type Dict = *mut c_void;
+type DescribeFn =
+    extern "C" fn(*const c_char, *const c_char, *const c_char, *const c_char, *mut c_void);
+
+/// The Enchant functions we use, looked up in the loaded library.
+struct Enchant {
+    broker: Broker,
+    broker_get_error: unsafe extern "C" fn(Broker) -> *const c_char,
+    broker_request_dict: unsafe extern "C" fn(Broker, *const c_char) -> Dict,
+    broker_list_dicts: unsafe extern "C" fn(Broker, DescribeFn, *mut c_void),
+    dict_check: unsafe extern "C" fn(Dict, *const c_char, ssize_t) -> c_int,
+    dict_suggest:
+        unsafe extern "C" fn(Dict, *const c_char, ssize_t, *mut size_t) -> *mut *mut c_char,
+    dict_free_string_list: unsafe extern "C" fn(Dict, *mut *mut c_char),
+    dict_add: unsafe extern "C" fn(Dict, *const c_char, ssize_t),
+    dict_add_to_session: unsafe extern "C" fn(Dict, *const c_char, ssize_t),
+    dict_remove_from_session: unsafe extern "C" fn(Dict, *const c_char, ssize_t),
+    /// Open dictionaries by language tag.
+    dicts: HashMap<Vec<u8>, Dict>,
+}
+
+// The library is only used from the Lisp thread, under the lock.
+unsafe impl Send for Enchant {}
+
+const LIBRARY_NAMES: &[&[u8]] = &[
+    b"libenchant-2.so.2\0",
+    b"libenchant.so.1\0",
+    b"libenchant-2.dylib\0",
+    b"libenchant.1.dylib\0",
+];
+
+unsafe fn lookup<T>(handle: *mut c_void, name: &[u8]) -> Result<T, ()> {
+    let symbol = libc::dlsym(handle, name.as_ptr() as *const c_char);
+    if symbol.is_null() {
+        Err(())
+    } else {
+        Ok(mem::transmute_copy(&symbol))
+    }
+}
+
+impl Enchant {
+    fn load() -> Result<Enchant, ()> {
+        unsafe {
+            let handle = LIBRARY_NAMES
+                .iter()
+                .map(|name| libc::dlopen(name.as_ptr() as *const c_char, libc::RTLD_NOW))
+                .find(|handle| !handle.is_null())
+                .ok_or(())?;
+            let broker_init: unsafe extern "C" fn() -> Broker =
+                lookup(handle, b"enchant_broker_init\0")?;
+            let mut enchant = Enchant {
+                broker: ptr::null_mut(),
+                broker_get_error: lookup(handle, b"enchant_broker_get_error\0")?,
+                broker_request_dict: lookup(handle, b"enchant_broker_request_dict\0")?,
+                broker_list_dicts: lookup(handle, b"enchant_broker_list_dicts\0")?,
+                dict_check: lookup(handle, b"enchant_dict_check\0")?,
+                dict_suggest: lookup(handle, b"enchant_dict_suggest\0")?,
+                dict_free_string_list: lookup(handle, b"enchant_dict_free_string_list\0")?,
+                dict_add: lookup(handle, b"enchant_dict_add\0")
+                    .or_else(|_| lookup(handle, b"enchant_dict_add_to_personal\0"))?,
+                dict_add_to_session: lookup(handle, b"enchant_dict_add_to_session\0")?,
+                dict_remove_from_session: lookup(handle, b"enchant_dict_remove_from_session\0")?,
+                dicts: HashMap::new(),
+            };
+            enchant.broker = broker_init();
+            if enchant.broker.is_null() {
+                Err(())
+            } else {
+                Ok(enchant)
+            }
+        }
+    }
+
+    fn error_message(&self, default: &str) -> String {
+        let message = unsafe { (self.broker_get_error)(self.broker) };
+        if message.is_null() {
+            default.to_string()
+        } else {
+            unsafe { CStr::from_ptr(message) }
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+
+    fn dict(&mut self, language: &[u8]) -> Result<Dict, String> {
+        if let Some(&dict) = self.dicts.get(language) {
+            return Ok(dict);
+        }
+        let mut tag = language.to_vec();
+        tag.push(0);
+        let dict =
+            unsafe { (self.broker_request_dict)(self.broker, tag.as_ptr() as *const c_char) };
+        if dict.is_null() {
+            let language = String::from_utf8_lossy(language).into_owned();
+            return Err(self.error_message(&format!("No spelling dictionary for {}", language)));
+        }
+        self.dicts.insert(language.to_vec(), dict);
+        Ok(dict)
+    }
+}
+
+lazy_static! {
+    /// The loaded library, or `None` if it couldn't be loaded.
+    static ref ENCHANT: Mutex<Option<Option<Enchant>>> = Mutex::new(None);
+}
+
+/// Run F with the library state and the dictionary for LANGUAGE.
+fn with_dict<F, R>(language: &[u8], f: F) -> Result<R, String>
+where
+    F: FnOnce(&Enchant, Dict) -> R,
+{
+    let mut state = ENCHANT.lock().unwrap();
+    if state.is_none() {
+        *state = Some(Enchant::load().ok());
+    }
+    match *state {
+        Some(Some(ref mut enchant)) => {
+            let dict = enchant.dict(language)?;
+            Ok(f(enchant, dict))
+        }
+        _ => Err("The Enchant spell checking library is not available".to_string()),
+    }
+}
+
+/// The language tag of the current locale, like `en_US', or `en_US' if
+/// the locale doesn't name a language.
+fn default_language() -> Vec<u8> {
+    for variable in &["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Some(value) = env::var_os(variable) {
+            let value = value.to_string_lossy().into_owned();
+            let tag = value.split(|c| c == '.' || c == '@').next().unwrap_or("");
+            if !tag.is_empty() && tag != "C" && tag != "POSIX" {
+                return tag.as_bytes().to_vec();
+            }
+        }
+    }
+    b"en_US".to_vec()
+}
+
+fn language_tag(dictionary: LispObject) -> Vec<u8> {
+    if dictionary.is_nil() {
+        default_language()
+    } else {
+        dictionary.as_string_or_error().as_slice().to_vec()
+    }
+}
+
+/// The UTF-8 bytes of WORD, which Enchant expects.
+fn utf8_bytes(word: LispObject) -> Vec<u8> {
+    word.as_string_or_error();
+    let encoded = call!(intern("encode-coding-string"), word, intern("utf-8"));
+    encoded.as_string_or_error().as_slice().to_vec()
+}
+
+fn utf8_string(bytes: &[u8]) -> LispObject {
+    let string = LispObject::from(unsafe {
+        make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t)
+    });
+    call!(intern("decode-coding-string"), string, intern("utf-8"))
+}
+
+fn check_result<R>(result: Result<R, String>) -> R {
+    match result {
+        Ok(value) => value,
+        Err(message) => error!("{}", message),
+    }
+}
+
+/// Apply the Enchant dictionary function F to WORD.
+fn update_dict(
+    word: LispObject,
+    dictionary: LispObject,
+    f: fn(&Enchant) -> unsafe extern "C" fn(Dict, *const c_char, ssize_t),
+) {
+    let word = utf8_bytes(word);
+    check_result(with_dict(&language_tag(dictionary), |enchant, dict| unsafe {
+        f(enchant)(dict, word.as_ptr() as *const c_char, word.len() as ssize_t)
+    }));
+}
+
+/// Return t if WORD is spelled correctly according to DICTIONARY.
+/// DICTIONARY is a language tag like "en_US" or "de"; nil means the
+/// language of the current locale.  The words added to DICTIONARY's
+/// session and to the personal dictionary are taken into account.
+#[lisp_fn(min = "1")]
+pub fn spell_check_word(word: LispObject, dictionary: LispObject) -> bool {
+    let word = utf8_bytes(word);
+    let result = check_result(with_dict(&language_tag(dictionary), |enchant, dict| unsafe {
+        (enchant.dict_check)(dict, word.as_ptr() as *const c_char, word.len() as ssize_t)
+    }));
+    if result < 0 {
+        error!("Spell checking failed");
+    }
+    result == 0
+}
+
+/// Return a list of suggested spellings for WORD, best first.
+/// DICTIONARY is as for `spell-check-word'.
+#[lisp_fn(min = "1")]
+pub fn spell_suggest(word: LispObject, dictionary: LispObject) -> LispObject {
+    let word = utf8_bytes(word);
+    let suggestions = check_result(with_dict(&language_tag(dictionary), |enchant, dict| unsafe {
+        let mut count = 0;
+        let list = (enchant.dict_suggest)(
+            dict,
+            word.as_ptr() as *const c_char,
+            word.len() as ssize_t,
+            &mut count,
+        );
+        let mut suggestions = Vec::new();
+        if !list.is_null() {
+            for i in 0..count {
+                suggestions.push(CStr::from_ptr(*list.offset(i as isize)).to_bytes().to_vec());
+            }
+            (enchant.dict_free_string_list)(dict, list);
+        }
+        suggestions
+    }));
+    suggestions
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |list, suggestion| {
+            LispObject::cons(utf8_string(suggestion), list)
+        })
+}
+
+/// Accept WORD as correctly spelled in DICTIONARY until Emacs exits.
+/// DICTIONARY is as for `spell-check-word'.
+#[lisp_fn(min = "1")]
+pub fn spell_session_add(word: LispObject, dictionary: LispObject) -> LispObject {
+    update_dict(word, dictionary, |enchant| enchant.dict_add_to_session);
+    LispObject::constant_nil()
+}
+
+/// Stop accepting WORD as correctly spelled in DICTIONARY for the rest
+/// of this session, even if it is in the dictionary.
+/// DICTIONARY is as for `spell-check-word'.
+#[lisp_fn(min = "1")]
+pub fn spell_session_remove(word: LispObject, dictionary: LispObject) -> LispObject {
+    update_dict(word, dictionary, |enchant| enchant.dict_remove_from_session);
+    LispObject::constant_nil()
+}
+
+/// Add WORD to the personal word list for DICTIONARY, which is saved
+/// by Enchant and used in later sessions too.
+/// DICTIONARY is as for `spell-check-word'.
+#[lisp_fn(min = "1")]
+pub fn spell_personal_add(word: LispObject, dictionary: LispObject) -> LispObject {
+    update_dict(word, dictionary, |enchant| enchant.dict_add);
+    LispObject::constant_nil()
+}
+
+extern "C" fn describe_dict(
+    tag: *const c_char,
+    _provider_name: *const c_char,
+    _provider_desc: *const c_char,
+    _provider_file: *const c_char,
+    user_data: *mut c_void,
+) {
+    let tags = unsafe { &mut *(user_data as *mut Vec<Vec<u8>>) };
+    tags.push(unsafe { CStr::from_ptr(tag) }.to_bytes().to_vec());
+}
+
+/// Return a list of the language tags of the available dictionaries.
+/// Return nil if the Enchant library is not available.
+#[lisp_fn]
+pub fn spell_dictionaries() -> LispObject {
+    let mut state = ENCHANT.lock().unwrap();
+    if state.is_none() {
+        *state = Some(Enchant::load().ok());
+    }
+    let mut tags: Vec<Vec<u8>> = Vec::new();
+    if let Some(Some(ref enchant)) = *state {
+        unsafe {
+            (enchant.broker_list_dicts)(
+                enchant.broker,
+                describe_dict,
+                &mut tags as *mut Vec<Vec<u8>> as *mut c_void,
+            )
+        };
+    }
+    drop(state);
+    tags.sort();
+    tags.dedup();
+    tags.iter().rev().fold(LispObject::constant_nil(), |list, tag| {
+        LispObject::cons(utf8_string(tag), list)
+    })
+}
+
+#[test]
+fn test_default_language() {
+    env::set_var("LC_ALL", "de_DE.UTF-8");
+    assert_eq!(default_language(), b"de_DE");
+    env::set_var("LC_ALL", "C");
+    env::set_var("LC_MESSAGES", "sr_RS@latin");
+    assert_eq!(default_language(), b"sr_RS");
+}
+
+include!(concat!(env!("OUT_DIR"), "/spell_exports.rs"));
@@ -5,16 +5,25 @@ use std::ptr;
 use libc;
 
 use remacs_macros::lisp_fn;
-use remacs_sys::{make_unibyte_string, make_uninit_multibyte_string,
+use remacs_sys::{make_string, make_unibyte_string, make_uninit_multibyte_string,
                  string_to_multibyte as c_string_to_multibyte};
-use remacs_sys::EmacsInt;
+use remacs_sys::{EmacsDouble, EmacsInt};
 
 use lisp::LispObject;
 use lisp::defsubr;
+use lists::list;
 use multibyte;
+use multibyte::{Codepoint, LispStringRef};
 
 pub static MIME_LINE_LENGTH: isize = 76;
 
+/// Make a multibyte Lisp string of the UTF-8 text S.
+pub fn lisp_string(s: &str) -> LispObject {
+    LispObject::from(unsafe {
+        make_string(s.as_ptr() as *const libc::c_char, s.len() as libc::ptrdiff_t)
+    })
+}
+
 /// Return t if OBJECT is a string.
 #[lisp_fn]
 pub fn stringp(object: LispObject) -> LispObject {
@@ -140,6 +149,159 @@ pub fn multibyte_string_p(object: LispObject) -> LispObject {
     LispObject::from_bool(object.as_string().map_or(false, |s| s.is_multibyte()))
 }
 
+/// Return the Levenshtein distance between the sequences A and B: the
+/// minimum number of insertions, deletions and substitutions needed to
+/// turn one into the other.
+fn levenshtein_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    // Only the previous row of the edit matrix is needed, so keep a
+    // single row and the value diagonally above the current cell.
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + if x == y { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Return the Jaro-Winkler similarity of the sequences A and B, between
+/// 0.0 for no similarity and 1.0 for equal sequences.
+fn jaro_winkler_similarity<T: PartialEq>(a: &[T], b: &[T]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    // Elements match if they are equal and not farther apart than half
+    // the length of the longer sequence.
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut a_matched = vec![false; a.len()];
+    let mut b_matched = vec![false; b.len()];
+    let mut matches = 0;
+    for (i, x) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        for j in start..end {
+            if !b_matched[j] && *x == b[j] {
+                a_matched[i] = true;
+                b_matched[j] = true;
+                matches += 1;
+                break;
+            }
+        }
+    }
+    if matches == 0 {
+        return 0.0;
+    }
+
+    // Count the matched elements that appear in a different order.
+    let mut transpositions = 0;
+    let mut j = 0;
+    for (i, x) in a.iter().enumerate() {
+        if !a_matched[i] {
+            continue;
+        }
+        while !b_matched[j] {
+            j += 1;
+        }
+        if *x != b[j] {
+            transpositions += 1;
+        }
+        j += 1;
+    }
+
+    let m = matches as f64;
+    let jaro =
+        (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64 / 2.0) / m) / 3.0;
+
+    // Favor sequences sharing a prefix of up to four elements.
+    let prefix = a.iter()
+        .zip(b.iter())
+        .take(4)
+        .take_while(|&(x, y)| x == y)
+        .count();
+    jaro + prefix as f64 * 0.1 * (1.0 - jaro)
+}
+
+fn string_chars(string: LispStringRef) -> Vec<Codepoint> {
+    string.chars().collect()
+}
+
+/// Return Levenshtein distance between STRING1 and STRING2.
+/// The distance is the number of deletions, insertions, and substitutions
+/// required to transform STRING1 into STRING2.
+/// If BYTECOMPARE is nil, compute character distance between STRING1 and
+/// STRING2, otherwise compute the byte distance.
+/// Letter-case is significant, but text properties are ignored.
+#[lisp_fn(min = "2")]
+pub fn string_distance(
+    string1: LispObject,
+    string2: LispObject,
+    bytecompare: LispObject,
+) -> LispObject {
+    let s1 = string1.as_string_or_error();
+    let s2 = string2.as_string_or_error();
+
+    let distance = if bytecompare.is_nil() {
+        levenshtein_distance(&string_chars(s1), &string_chars(s2))
+    } else {
+        levenshtein_distance(s1.as_slice(), s2.as_slice())
+    };
+    LispObject::from_natnum(distance as EmacsInt)
+}
+
+/// Return the Jaro-Winkler similarity of STRING1 and STRING2.
+/// The value is a float between 0.0, when the strings have nothing in
+/// common, and 1.0, when they are equal.  Strings sharing a common prefix
+/// score higher, which suits comparing words and command names.
+/// Letter-case is significant, but text properties are ignored.
+#[lisp_fn]
+pub fn string_jaro_winkler(string1: LispObject, string2: LispObject) -> LispObject {
+    let s1 = string_chars(string1.as_string_or_error());
+    let s2 = string_chars(string2.as_string_or_error());
+    LispObject::from_float(jaro_winkler_similarity(&s1, &s2) as EmacsDouble)
+}
+
+/// Return the N elements of CANDIDATES closest to the string QUERY.
+/// CANDIDATES is a list of strings or symbols; symbols are compared by
+/// their names.  Candidates are ordered by their `string-distance' to
+/// QUERY, ties being broken by `string-jaro-winkler' similarity and then
+/// by their order in CANDIDATES.
+/// If N is nil, return all of CANDIDATES, sorted.
+/// Letter-case is significant, but text properties are ignored.
+#[lisp_fn(min = "2")]
+pub fn closest_strings(query: LispObject, candidates: LispObject, n: LispObject) -> LispObject {
+    let query = string_chars(query.as_string_or_error());
+
+    let mut scored: Vec<(usize, f64, LispObject)> = candidates
+        .iter_cars()
+        .map(|candidate| {
+            let chars = string_chars(candidate.symbol_or_string_as_string());
+            let distance = levenshtein_distance(&query, &chars);
+            let similarity = jaro_winkler_similarity(&query, &chars);
+            (distance, similarity, candidate)
+        })
+        .collect();
+    // `sort_by' is stable, so equally close candidates keep their order.
+    scored.sort_by(|a, b| {
+        a.0
+            .cmp(&b.0)
+            .then_with(|| b.1.partial_cmp(&a.1).unwrap())
+    });
+
+    if n.is_not_nil() {
+        scored.truncate(n.as_natnum_or_error() as usize);
+    }
+    let mut closest: Vec<LispObject> = scored.into_iter().map(|(_, _, c)| c).collect();
+    list(&mut closest)
+}
+
 include!(concat!(env!("OUT_DIR"), "/strings_exports.rs"));
 
 #[test]
@@ -178,3 +340,40 @@ fn test_stringlessp() {
     assert_t!(string_lessp(string, string2));
     assert_nil!(string_lessp(string2, string));
 }
+
+#[test]
+fn test_string_distance() {
+    let kitten = mock_unibyte_string!("kitten");
+    let sitting = mock_unibyte_string!("sitting");
+    let nil = LispObject::constant_nil();
+    assert!(string_distance(kitten, sitting, nil) == LispObject::from_natnum(3));
+    assert!(string_distance(kitten, kitten, nil) == LispObject::from_natnum(0));
+
+    let empty = mock_unibyte_string!("");
+    assert!(string_distance(empty, sitting, nil) == LispObject::from_natnum(7));
+}
+
+#[test]
+fn test_string_distance_multibyte() {
+    let nil = LispObject::constant_nil();
+    let t = LispObject::constant_t();
+    let a = mock_multibyte_string!("\u{e9}t\u{e9}");
+    let b = mock_multibyte_string!("ete");
+    assert!(string_distance(a, b, nil) == LispObject::from_natnum(2));
+    assert!(string_distance(a, b, t) == LispObject::from_natnum(4));
+}
+
+#[test]
+fn test_jaro_winkler_similarity() {
+    let martha: Vec<char> = "MARTHA".chars().collect();
+    let marhta: Vec<char> = "MARHTA".chars().collect();
+    assert!((jaro_winkler_similarity(&martha, &marhta) - 0.961).abs() < 0.001);
+
+    let dixon: Vec<char> = "DIXON".chars().collect();
+    let dicksonx: Vec<char> = "DICKSONX".chars().collect();
+    assert!((jaro_winkler_similarity(&dixon, &dicksonx) - 0.813).abs() < 0.001);
+
+    let empty: Vec<char> = Vec::new();
+    assert!(jaro_winkler_similarity(&martha, &martha) == 1.0);
+    assert!(jaro_winkler_similarity(&martha, &empty) == 0.0);
+}
@@ -0,0 +1,256 @@
+//! Small SVG images for mode line widgets.
+//!
+//! A sparkline or progress bar in the mode line is redrawn on every
+//! update.  Building its SVG with the `svg' library makes a DOM and a
+//! string per update; here the text is written directly, with
+//! coordinates rounded to tenths of a pixel so that the same picture
+//! always gives the same image spec, which the image cache then reuses.
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// The colors used when none are given.
+const DEFAULT_COLOR: &str = "#4a90d9";
+const DEFAULT_BACKGROUND: &str = "#d0d0d0";
+
+/// Format X with at most one decimal.
+fn coordinate(x: f64) -> String {
+    let tenths = (x * 10.0).round() as i64;
+    if tenths % 10 == 0 {
+        format!("{}", tenths / 10)
+    } else {
+        format!("{}.{}", tenths / 10, (tenths % 10).abs())
+    }
+}
+
+/// Escape VALUE for an attribute value in double quotes.
+fn attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+}
+
+fn svg_header(width: u32, height: u32) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+        width, height
+    )
+}
+
+/// Return an SVG of WIDTH by HEIGHT pixels with a line through VALUES,
+/// spread evenly across and scaled to the range of the values.
+fn sparkline(values: &[f64], width: u32, height: u32, color: &str) -> String {
+    let mut svg = svg_header(width, height);
+    if !values.is_empty() {
+        // Half the stroke width, so that the line is not clipped.
+        let margin = 0.5;
+        let min = values.iter().cloned().fold(values[0], f64::min);
+        let max = values.iter().cloned().fold(values[0], f64::max);
+        let inner_width = f64::from(width) - 2.0 * margin;
+        let inner_height = f64::from(height) - 2.0 * margin;
+        let points: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let x = if values.len() == 1 {
+                    f64::from(width) / 2.0
+                } else {
+                    margin + inner_width * i as f64 / (values.len() - 1) as f64
+                };
+                let y = if max == min {
+                    f64::from(height) / 2.0
+                } else {
+                    margin + inner_height * (max - value) / (max - min)
+                };
+                format!("{},{}", coordinate(x), coordinate(y))
+            })
+            .collect();
+        svg.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1\" points=\"{}\"/>",
+            attribute(color),
+            points.join(" ")
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Return an SVG of WIDTH by HEIGHT pixels filled with COLOR up to
+/// FRACTION of its width, and with BACKGROUND after that.
+fn progress_bar(
+    fraction: f64,
+    width: u32,
+    height: u32,
+    color: &str,
+    background: &str,
+) -> String {
+    let fraction = if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.max(0.0).min(1.0)
+    };
+    let filled = (fraction * f64::from(width)).round() as u32;
+    let mut svg = svg_header(width, height);
+    svg.push_str(&format!(
+        "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        width,
+        height,
+        attribute(background)
+    ));
+    if filled > 0 {
+        svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            filled,
+            height,
+            attribute(color)
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn number(object: LispObject) -> f64 {
+    match object.as_float() {
+        Some(n) => n,
+        None => match object.as_fixnum() {
+            Some(n) => n as f64,
+            None => wrong_type!(intern("numberp"), object),
+        },
+    }
+}
+
+/// Return SIZE as a pixel count, or DEFAULT if SIZE is nil.
+fn pixels(size: LispObject, default: EmacsInt) -> u32 {
+    let pixels = if size.is_nil() {
+        default
+    } else {
+        size.as_natnum_or_error()
+    };
+    if pixels == 0 || pixels > 0xFFFF {
+        args_out_of_range!(size, LispObject::from_natnum(0xFFFF));
+    }
+    pixels as u32
+}
+
+/// Return COLOR as a string, or DEFAULT if COLOR is nil.
+fn svg_color(color: LispObject, default: &str) -> String {
+    if color.is_nil() {
+        default.to_string()
+    } else {
+        String::from_utf8_lossy(color.as_string_or_error().as_slice()).into_owned()
+    }
+}
+
+/// Return an image spec for SVG, centered on the text line.
+fn svg_spec(svg: &str) -> LispObject {
+    list!(
+        intern("image"),
+        intern(":type"),
+        intern("svg"),
+        intern(":data"),
+        lisp_string(svg),
+        intern(":ascent"),
+        intern("center")
+    )
+}
+
+/// Return an SVG image spec for a sparkline of DATA, W by H pixels.
+/// DATA is a list or vector of numbers, drawn as a line from left to
+/// right, scaled so that its smallest and largest values touch the
+/// bottom and top of the image.  H defaults to the height of a line of
+/// the selected frame.  COLOR is the color of the line, as an SVG color
+/// such as "#4a90d9".  The image is meant for the mode line, as in
+/// (propertize " " \\='display (sparkline-svg samples 60)).
+#[lisp_fn(min = "2")]
+pub fn sparkline_svg(
+    data: LispObject,
+    w: LispObject,
+    h: LispObject,
+    color: LispObject,
+) -> LispObject {
+    let values: Vec<f64> = match data.as_vectorlike().and_then(|v| v.as_vector()) {
+        Some(vector) => vector.as_slice().iter().map(|&value| number(value)).collect(),
+        None => data.iter_cars().map(number).collect(),
+    };
+    // W has no default, unlike H.
+    w.as_natnum_or_error();
+    let line_height = call!(intern("frame-char-height")).as_natnum_or_error();
+    let svg = sparkline(
+        &values,
+        pixels(w, 0),
+        pixels(h, line_height),
+        &svg_color(color, DEFAULT_COLOR),
+    );
+    svg_spec(&svg)
+}
+
+/// Return an SVG image spec for a progress bar filled up to FRACTION.
+/// FRACTION is a number between 0 and 1.  The bar is W pixels wide, by
+/// default ten columns of the selected frame, and H pixels high, by
+/// default the height of a line.  COLOR is the color of the filled part
+/// and BACKGROUND that of the rest, as SVG colors such as "#4a90d9".
+/// The filled width is rounded to whole pixels, so that updates that
+/// would not change the picture give an equal spec.
+#[lisp_fn(min = "1")]
+pub fn progress_bar_svg(
+    fraction: LispObject,
+    w: LispObject,
+    h: LispObject,
+    color: LispObject,
+    background: LispObject,
+) -> LispObject {
+    let char_width = call!(intern("frame-char-width")).as_natnum_or_error();
+    let line_height = call!(intern("frame-char-height")).as_natnum_or_error();
+    let svg = progress_bar(
+        number(fraction),
+        pixels(w, 10 * char_width),
+        pixels(h, line_height),
+        &svg_color(color, DEFAULT_COLOR),
+        &svg_color(background, DEFAULT_BACKGROUND),
+    );
+    svg_spec(&svg)
+}
+
+#[test]
+fn test_coordinate() {
+    assert_eq!(coordinate(0.0), "0");
+    assert_eq!(coordinate(12.0), "12");
+    assert_eq!(coordinate(0.5), "0.5");
+    assert_eq!(coordinate(3.14159), "3.1");
+    assert_eq!(coordinate(9.96), "10");
+}
+
+#[test]
+fn test_sparkline() {
+    assert_eq!(
+        sparkline(&[], 10, 5, "red"),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"5\"></svg>"
+    );
+    let svg = sparkline(&[0.0, 10.0, 5.0], 11, 11, "red");
+    assert!(svg.contains("stroke=\"red\""));
+    assert!(svg.contains("points=\"0.5,10.5 5.5,0.5 10.5,5.5\""));
+    let flat = sparkline(&[3.0, 3.0], 11, 10, "red");
+    assert!(flat.contains("points=\"0.5,5 10.5,5\""));
+    assert!(sparkline(&[1.0], 10, 10, "a\"b").contains("stroke=\"a&quot;b\""));
+}
+
+#[test]
+fn test_progress_bar() {
+    let svg = progress_bar(0.25, 100, 10, "blue", "white");
+    assert!(svg.contains("<rect width=\"100\" height=\"10\" fill=\"white\"/>"));
+    assert!(svg.contains("<rect width=\"25\" height=\"10\" fill=\"blue\"/>"));
+    assert!(!progress_bar(0.0, 100, 10, "blue", "white").contains("blue"));
+    assert!(progress_bar(7.0, 100, 10, "blue", "white")
+        .contains("<rect width=\"100\" height=\"10\" fill=\"blue\"/>"));
+    assert_eq!(
+        progress_bar(0.501, 100, 10, "blue", "white"),
+        progress_bar(0.499, 100, 10, "blue", "white")
+    );
+}
+
+include!(concat!(env!("OUT_DIR"), "/svg_exports.rs"));
@@ -1,10 +1,12 @@
 //! symbols support
 
+use libc::c_void;
+
 use remacs_macros::lisp_fn;
-use remacs_sys::{Fset, Lisp_Symbol};
+use remacs_sys::{Fset, Lisp_Symbol, SymbolRedirect};
 use remacs_sys::{Qcyclic_variable_indirection, Qsetting_constant, Qunbound, Qvoid_variable};
-use remacs_sys::{find_symbol_value, make_lisp_symbol, symbol_is_alias, symbol_is_constant,
-                 symbol_is_interned};
+use remacs_sys::{find_symbol_value, make_lisp_symbol, symbol_get_redirect, symbol_is_alias,
+                 symbol_is_constant, symbol_is_declared_special, symbol_is_interned};
 
 use lisp::{ExternalPtr, LispObject};
 use lisp::defsubr;
@@ -44,6 +46,33 @@ impl LispSymbolRef {
         unsafe { symbol_is_constant(self.as_ptr()) }
     }
 
+    /// Return true if the symbol has been declared special (with
+    /// `defvar' etc), and shouldn't be lexically bound.
+    pub fn is_declared_special(&self) -> bool {
+        unsafe { symbol_is_declared_special(self.as_ptr()) }
+    }
+
+    pub fn get_redirect(&self) -> SymbolRedirect {
+        unsafe { symbol_get_redirect(self.as_ptr()) }
+    }
+
+    /// Return the value of a plain (non-forwarded, non-local) variable.
+    /// Equivalent to `SYMBOL_VAL` in C.
+    pub fn get_value(&self) -> LispObject {
+        debug_assert!(self.get_redirect() == SymbolRedirect::PlainVal);
+        LispObject::from(unsafe { self.val.value })
+    }
+
+    pub fn get_blv(&self) -> *const c_void {
+        debug_assert!(self.get_redirect() == SymbolRedirect::Localized);
+        unsafe { self.val.blv }
+    }
+
+    pub fn get_fwd(&self) -> *const c_void {
+        debug_assert!(self.get_redirect() == SymbolRedirect::Forwarded);
+        unsafe { self.val.fwd }
+    }
+
     pub fn get_alias(&self) -> LispSymbolRef {
         debug_assert!(self.is_alias());
         LispSymbolRef::new(unsafe { self.val.alias })
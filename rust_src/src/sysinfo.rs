@@ -0,0 +1,713 @@
+//! Battery, processor and memory status.
+//!
+//! These read the system's own interfaces: /sys and /proc on
+//! GNU/Linux, sysctl on the BSDs and macOS, and the Win32 API on
+//! Windows.  They are cheap enough to call from mode line timers.
+
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::io::Read;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+use std::sync::Mutex;
+
+#[cfg(any(target_os = "freebsd", target_os = "macos"))]
+use std::ffi::CString;
+use std::mem;
+#[cfg(any(target_os = "freebsd", target_os = "macos"))]
+use std::ptr;
+
+#[cfg(any(target_os = "freebsd", target_os = "macos"))]
+use libc;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use symbols::symbol_value;
+
+lazy_static! {
+    // The processor times at the last call of `cpu-usage-native'.
+    static ref LAST_CPU_TIMES: Mutex<Option<CpuTimes>> = Mutex::new(None);
+}
+
+/// The merged state of the batteries of the system.
+#[derive(Debug, Default, PartialEq)]
+struct Battery {
+    /// "Charging", "Discharging", "Full" and so on.
+    status: Option<String>,
+    /// The remaining charge, in mAh.
+    charge: Option<f64>,
+    /// The rate of charge or discharge, in W.
+    rate: Option<f64>,
+    percentage: Option<f64>,
+    /// The time until the batteries are full or empty, in hours.
+    hours: Option<f64>,
+    /// In degrees Celsius.
+    temperature: Option<f64>,
+    ac_online: Option<bool>,
+}
+
+impl Battery {
+    /// Return the conversions of `battery-status-function' for this
+    /// state.  A percentage below LOW is low, below CRITICAL critical.
+    fn conversions(&self, low: f64, critical: f64) -> Vec<(char, String)> {
+        fn or_na<T, F: Fn(&T) -> String>(value: &Option<T>, f: F) -> String {
+            value.as_ref().map_or_else(|| "N/A".to_string(), f)
+        }
+        let charging = self.status.as_ref().map_or(false, |s| s == "Charging");
+        let symbol = match self.percentage {
+            _ if charging => "+",
+            Some(p) if p < critical => "!",
+            Some(p) if p < low => "-",
+            _ => "",
+        };
+        vec![
+            ('c', or_na(&self.charge, |c| format!("{:.0}", c))),
+            ('r', or_na(&self.rate, |r| format!("{:.1}", r))),
+            ('B', or_na(&self.status, |s| s.clone())),
+            ('b', symbol.to_string()),
+            ('d', or_na(&self.temperature, |t| format!("{:.1}", t))),
+            (
+                'L',
+                or_na(&self.ac_online, |&on| {
+                    (if on { "AC" } else { "BAT" }).to_string()
+                }),
+            ),
+            ('p', or_na(&self.percentage, |p| format!("{:.1}", p))),
+            ('m', or_na(&self.hours, |h| format!("{}", (h * 60.0) as i64))),
+            ('h', or_na(&self.hours, |&h| format!("{}", h as i64))),
+            (
+                't',
+                or_na(&self.hours, |&h| {
+                    format!("{}:{:02}", h as i64, ((h - h.floor()) * 60.0) as i64)
+                }),
+            ),
+        ]
+    }
+}
+
+/// Return the properties in the uevent file TEXT of a power supply,
+/// without their POWER_SUPPLY_ prefix.
+fn uevent_properties(text: &str) -> HashMap<&str, &str> {
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if key.starts_with("POWER_SUPPLY_") => {
+                    Some((&key["POWER_SUPPLY_".len()..], value.trim()))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Merge the uevent files BATTERIES of the batteries in /sys.  As in
+/// `battery-linux-sysfs', charges are converted to energies so that
+/// batteries with different voltages can be added up.
+fn sysfs_battery(batteries: &[String], ac_online: Option<bool>) -> Option<Battery> {
+    let mut battery = Battery {
+        ac_online,
+        ..Default::default()
+    };
+    // In µWh and µW.
+    let mut energy_full = 0.0;
+    let mut energy_now = 0.0;
+    let mut power_now = 0.0;
+    let mut voltage_sum = 0.0;
+    let mut capacity = None;
+    let mut present = 0;
+
+    for text in batteries {
+        let props = uevent_properties(text);
+        let number = |key: &str| props.get(key).and_then(|v| v.parse::<f64>().ok());
+        if props.get("PRESENT") != Some(&"1") {
+            continue;
+        }
+        present += 1;
+        // An arbitrary default, in case the information is missing.
+        let voltage = number("VOLTAGE_NOW").map_or(10.8, |v| v / 1_000_000.0);
+        voltage_sum += voltage;
+        let unknown = match battery.status {
+            None => true,
+            Some(ref s) => s == "Unknown" || s == "Full",
+        };
+        if unknown {
+            if let Some(status) = props.get("STATUS") {
+                battery.status = Some(status.to_string());
+            }
+        }
+        if let Some(current) = number("CURRENT_NOW") {
+            power_now += current * voltage;
+        } else if let Some(power) = number("POWER_NOW") {
+            power_now += power;
+        }
+        if let Some(temp) = number("TEMP") {
+            battery.temperature = Some(temp / 10.0);
+        }
+        match (number("CHARGE_FULL"), number("CHARGE_NOW")) {
+            (Some(full), Some(now)) => {
+                energy_full += full * voltage;
+                energy_now += now * voltage;
+            }
+            _ => {
+                if let (Some(full), Some(now)) = (number("ENERGY_FULL"), number("ENERGY_NOW")) {
+                    energy_full += full;
+                    energy_now += now;
+                }
+            }
+        }
+        if capacity.is_none() {
+            capacity = number("CAPACITY");
+        }
+    }
+    if present == 0 {
+        return None;
+    }
+
+    let voltage = voltage_sum / f64::from(present);
+    if energy_now > 0.0 {
+        battery.charge = Some(energy_now / voltage / 1000.0);
+    }
+    if power_now > 0.0 {
+        battery.rate = Some(power_now / 1_000_000.0);
+        let remaining = if battery.status.as_ref().map_or(false, |s| s == "Discharging") {
+            energy_now
+        } else {
+            energy_full - energy_now
+        };
+        battery.hours = Some(remaining / power_now);
+    }
+    battery.percentage = if energy_full > 0.0 && energy_now > 0.0 {
+        Some(100.0 * energy_now / energy_full)
+    } else {
+        capacity
+    };
+    Some(battery)
+}
+
+#[cfg(target_os = "linux")]
+fn read_file(path: &Path) -> Option<String> {
+    let mut text = String::new();
+    let result = fs::File::open(path).and_then(|mut f| f.read_to_string(&mut text));
+    result.ok().map(|_| text)
+}
+
+#[cfg(target_os = "linux")]
+fn battery() -> Option<Battery> {
+    let mut batteries = Vec::new();
+    let mut ac_online = None;
+    let entries = match fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return None,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let text = match read_file(&entry.path().join("uevent")) {
+            Some(text) => text,
+            None => continue,
+        };
+        let (kind, scope, online) = {
+            let props = uevent_properties(&text);
+            (
+                props.get("TYPE").map(|s| s.to_string()),
+                props.get("SCOPE").map(|s| s.to_string()),
+                props.get("ONLINE").map(|s| *s == "1"),
+            )
+        };
+        match kind.as_ref().map(|s| s.as_str()) {
+            // Skip the batteries of mice and keyboards.
+            Some("Battery") if scope.as_ref().map_or(true, |s| s != "Device") => {
+                batteries.push(text)
+            }
+            Some("Mains") => ac_online = Some(ac_online.unwrap_or(false) || online == Some(true)),
+            _ => (),
+        }
+    }
+    sysfs_battery(&batteries, ac_online)
+}
+
+/// Return the value of the sysctl NAME, which must be of type T.
+#[cfg(any(target_os = "freebsd", target_os = "macos"))]
+fn sysctl<T: Copy>(name: &str) -> Option<T> {
+    let name = match CString::new(name) {
+        Ok(name) => name,
+        Err(_) => return None,
+    };
+    unsafe {
+        let mut value: T = mem::zeroed();
+        let mut len = mem::size_of::<T>() as libc::size_t;
+        let result = libc::sysctlbyname(
+            name.as_ptr(),
+            &mut value as *mut T as *mut libc::c_void,
+            &mut len,
+            ptr::null_mut(),
+            0,
+        );
+        if result == 0 && len == mem::size_of::<T>() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "freebsd")]
+fn battery() -> Option<Battery> {
+    let life = match sysctl::<libc::c_int>("hw.acpi.battery.life") {
+        Some(life) if life >= 0 => life,
+        _ => return None,
+    };
+    // Bit 0 means discharging, bit 1 charging.
+    let state = sysctl::<libc::c_int>("hw.acpi.battery.state").unwrap_or(0);
+    let minutes = sysctl::<libc::c_int>("hw.acpi.battery.time").unwrap_or(-1);
+    Some(Battery {
+        status: Some(
+            if state & 2 != 0 {
+                "Charging"
+            } else if state & 1 != 0 {
+                "Discharging"
+            } else {
+                "Full"
+            }.to_string(),
+        ),
+        percentage: Some(f64::from(life)),
+        hours: if minutes >= 0 {
+            Some(f64::from(minutes) / 60.0)
+        } else {
+            None
+        },
+        ac_online: sysctl::<libc::c_int>("hw.acpi.acline").map(|on| on != 0),
+        ..Default::default()
+    })
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct SYSTEM_POWER_STATUS {
+    ACLineStatus: u8,
+    BatteryFlag: u8,
+    BatteryLifePercent: u8,
+    SystemStatusFlag: u8,
+    BatteryLifeTime: u32,
+    BatteryFullLifeTime: u32,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct MEMORYSTATUSEX {
+    dwLength: u32,
+    dwMemoryLoad: u32,
+    ullTotalPhys: u64,
+    ullAvailPhys: u64,
+    ullTotalPageFile: u64,
+    ullAvailPageFile: u64,
+    ullTotalVirtual: u64,
+    ullAvailVirtual: u64,
+    ullAvailExtendedVirtual: u64,
+}
+
+#[cfg(windows)]
+extern "system" {
+    fn GetSystemPowerStatus(status: *mut SYSTEM_POWER_STATUS) -> i32;
+    fn GlobalMemoryStatusEx(status: *mut MEMORYSTATUSEX) -> i32;
+    fn GetSystemTimes(idle: *mut u64, kernel: *mut u64, user: *mut u64) -> i32;
+}
+
+#[cfg(windows)]
+fn battery() -> Option<Battery> {
+    let mut status: SYSTEM_POWER_STATUS = unsafe { mem::zeroed() };
+    // Flag 128 means there is no battery.
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 || status.BatteryFlag & 128 != 0 {
+        return None;
+    }
+    Some(Battery {
+        status: Some(
+            if status.BatteryFlag & 8 != 0 {
+                "Charging"
+            } else if status.ACLineStatus == 1 {
+                "Full"
+            } else {
+                "Discharging"
+            }.to_string(),
+        ),
+        percentage: if status.BatteryLifePercent <= 100 {
+            Some(f64::from(status.BatteryLifePercent))
+        } else {
+            None
+        },
+        hours: if status.BatteryLifeTime != u32::max_value() {
+            Some(f64::from(status.BatteryLifeTime) / 3600.0)
+        } else {
+            None
+        },
+        ac_online: match status.ACLineStatus {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        },
+        ..Default::default()
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", windows)))]
+fn battery() -> Option<Battery> {
+    None
+}
+
+/// Processor time spent busy and in total since boot, in ticks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+/// Parse the "cpu" line of /proc/stat: user, nice, system, idle,
+/// iowait and further busy times.
+fn parse_proc_stat(text: &str) -> Option<CpuTimes> {
+    let line = match text.lines().find(|line| line.starts_with("cpu ")) {
+        Some(line) => line,
+        None => return None,
+    };
+    let fields: Vec<u64> = line.split_whitespace()
+        .skip(1)
+        .filter_map(|field| field.parse().ok())
+        .collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    // Guest time is already counted in user time.
+    let total: u64 = fields.iter().take(8).sum();
+    let idle = fields[3] + fields.get(4).cloned().unwrap_or(0);
+    Some(CpuTimes {
+        busy: total - idle,
+        total,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn cpu_times() -> Option<CpuTimes> {
+    read_file(Path::new("/proc/stat")).and_then(|text| parse_proc_stat(&text))
+}
+
+#[cfg(target_os = "freebsd")]
+fn cpu_times() -> Option<CpuTimes> {
+    // User, nice, system, interrupt and idle.
+    sysctl::<[libc::c_long; 5]>("kern.cp_time").map(|times| {
+        let total: u64 = times.iter().map(|&t| t as u64).sum();
+        CpuTimes {
+            busy: total - times[4] as u64,
+            total,
+        }
+    })
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn mach_host_self() -> libc::c_uint;
+    fn host_statistics(
+        host: libc::c_uint,
+        flavor: libc::c_int,
+        info: *mut libc::c_int,
+        count: *mut libc::c_uint,
+    ) -> libc::c_int;
+}
+
+#[cfg(target_os = "macos")]
+fn cpu_times() -> Option<CpuTimes> {
+    const HOST_CPU_LOAD_INFO: libc::c_int = 3;
+    // User, system, idle and nice.
+    let mut ticks = [0 as libc::c_uint; 4];
+    let mut count = ticks.len() as libc::c_uint;
+    let result = unsafe {
+        host_statistics(
+            mach_host_self(),
+            HOST_CPU_LOAD_INFO,
+            ticks.as_mut_ptr() as *mut libc::c_int,
+            &mut count,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    let total: u64 = ticks.iter().map(|&t| u64::from(t)).sum();
+    Some(CpuTimes {
+        busy: total - u64::from(ticks[2]),
+        total,
+    })
+}
+
+#[cfg(windows)]
+fn cpu_times() -> Option<CpuTimes> {
+    let (mut idle, mut kernel, mut user) = (0, 0, 0);
+    if unsafe { GetSystemTimes(&mut idle, &mut kernel, &mut user) } == 0 {
+        return None;
+    }
+    // Kernel time includes idle time.
+    Some(CpuTimes {
+        busy: kernel + user - idle,
+        total: kernel + user,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "macos", windows)))]
+fn cpu_times() -> Option<CpuTimes> {
+    None
+}
+
+/// Return the percentage of time spent busy between BEFORE and AFTER.
+fn busy_percentage(before: CpuTimes, after: CpuTimes) -> f64 {
+    let total = after.total.saturating_sub(before.total);
+    if total == 0 {
+        0.0
+    } else {
+        100.0 * after.busy.saturating_sub(before.busy) as f64 / total as f64
+    }
+}
+
+/// Memory sizes, in KiB.
+#[derive(Debug, Default, PartialEq)]
+struct Memory {
+    total: Option<u64>,
+    free: Option<u64>,
+    available: Option<u64>,
+    swap_total: Option<u64>,
+    swap_free: Option<u64>,
+}
+
+fn parse_meminfo(text: &str) -> Memory {
+    let values: HashMap<&str, u64> = text.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next().and_then(|n| n.parse().ok())) {
+                (Some(key), Some(n)) => Some((key.trim_right_matches(':'), n)),
+                _ => None,
+            }
+        })
+        .collect();
+    let get = |key: &str| values.get(key).cloned();
+    // Kernels before 3.14 don't report the available memory.
+    let available = get("MemAvailable").or_else(|| {
+        match (get("MemFree"), get("Buffers"), get("Cached")) {
+            (Some(free), Some(buffers), Some(cached)) => Some(free + buffers + cached),
+            _ => None,
+        }
+    });
+    Memory {
+        total: get("MemTotal"),
+        free: get("MemFree"),
+        available,
+        swap_total: get("SwapTotal"),
+        swap_free: get("SwapFree"),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn memory() -> Memory {
+    read_file(Path::new("/proc/meminfo")).map_or_else(Memory::default, |text| parse_meminfo(&text))
+}
+
+#[cfg(target_os = "freebsd")]
+fn memory() -> Memory {
+    let page_kib = sysctl::<libc::c_uint>("hw.pagesize").map_or(4, |p| u64::from(p) / 1024);
+    let pages = |name| sysctl::<libc::c_uint>(name).map(|n| u64::from(n) * page_kib);
+    let free = pages("vm.stats.vm.v_free_count");
+    let available = match (free, pages("vm.stats.vm.v_inactive_count")) {
+        (Some(free), Some(inactive)) => Some(free + inactive),
+        _ => free,
+    };
+    Memory {
+        total: sysctl::<libc::c_ulong>("hw.physmem").map(|n| n as u64 / 1024),
+        free,
+        available,
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct xsw_usage {
+    xsu_total: u64,
+    xsu_avail: u64,
+    xsu_used: u64,
+    xsu_pagesize: u32,
+    xsu_encrypted: u32,
+}
+
+#[cfg(target_os = "macos")]
+fn memory() -> Memory {
+    let page_kib = sysctl::<libc::c_uint>("hw.pagesize").map_or(4, |p| u64::from(p) / 1024);
+    let free = sysctl::<libc::c_uint>("vm.page_free_count").map(|n| u64::from(n) * page_kib);
+    let swap = sysctl::<xsw_usage>("vm.swapusage");
+    Memory {
+        total: sysctl::<u64>("hw.memsize").map(|n| n / 1024),
+        free,
+        available: free,
+        swap_total: swap.as_ref().map(|s| s.xsu_total / 1024),
+        swap_free: swap.as_ref().map(|s| s.xsu_avail / 1024),
+    }
+}
+
+#[cfg(windows)]
+fn memory() -> Memory {
+    let mut status: MEMORYSTATUSEX = unsafe { mem::zeroed() };
+    status.dwLength = mem::size_of::<MEMORYSTATUSEX>() as u32;
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return Memory::default();
+    }
+    // The page file limit includes physical memory.
+    Memory {
+        total: Some(status.ullTotalPhys / 1024),
+        free: Some(status.ullAvailPhys / 1024),
+        available: Some(status.ullAvailPhys / 1024),
+        swap_total: Some(status.ullTotalPageFile.saturating_sub(status.ullTotalPhys) / 1024),
+        swap_free: Some(status.ullAvailPageFile.saturating_sub(status.ullAvailPhys) / 1024),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "macos", windows)))]
+fn memory() -> Memory {
+    Memory::default()
+}
+
+/// Return the value of the battery.el option NAME, or DEFAULT if
+/// battery.el isn't loaded.
+fn battery_option(name: &str, default: f64) -> f64 {
+    let symbol = intern(name);
+    if call!(intern("boundp"), symbol).is_nil() {
+        return default;
+    }
+    symbol_value(symbol).any_to_float().unwrap_or(default)
+}
+
+/// Return the status of the batteries of the system, or nil if there
+/// are none or their status is not known.
+///
+/// The value is an alist like those of `battery-status-function', so
+/// this function can serve as that.  The following %-sequences are
+/// provided, each "N/A" when not known:
+/// %c Current capacity (mAh)
+/// %r Current rate of charge or discharge (W)
+/// %B Battery status (verbose)
+/// %b Battery status: empty means high, `-' means low,
+///    `!' means critical, and `+' means charging
+/// %d Temperature (in degrees Celsius)
+/// %L AC line status (verbose)
+/// %p Battery load percentage
+/// %m Remaining time (to charge or discharge) in minutes
+/// %h Remaining time (to charge or discharge) in hours
+/// %t Remaining time (to charge or discharge) in the form `h:min'
+///
+/// The thresholds for low and critical load are `battery-load-low' and
+/// `battery-load-critical'.  Multiple batteries are reported as one.
+#[lisp_fn]
+pub fn battery_status_native() -> LispObject {
+    let battery = match battery() {
+        Some(battery) => battery,
+        None => return LispObject::constant_nil(),
+    };
+    let conversions = battery.conversions(
+        battery_option("battery-load-low", 25.0),
+        battery_option("battery-load-critical", 10.0),
+    );
+    let mut result = LispObject::constant_nil();
+    for &(c, ref text) in conversions.iter().rev() {
+        let key = LispObject::from_natnum(c as EmacsInt);
+        result = LispObject::cons(LispObject::cons(key, lisp_string(text)), result);
+    }
+    result
+}
+
+/// Return the percentage of processor time spent busy, as a float.
+/// The time is measured from the previous call of this function, or
+/// from boot for the first call, over all processors.  Return nil if
+/// the processor times can't be read.
+#[lisp_fn]
+pub fn cpu_usage_native() -> LispObject {
+    let now = match cpu_times() {
+        Some(now) => now,
+        None => return LispObject::constant_nil(),
+    };
+    let before = mem::replace(&mut *LAST_CPU_TIMES.lock().unwrap(), Some(now));
+    let before = before.unwrap_or(CpuTimes { busy: 0, total: 0 });
+    LispObject::from_float(busy_percentage(before, now))
+}
+
+/// Return the memory use of the system, as an alist.
+/// The keys are `total', `free', `available', `swap-total' and
+/// `swap-free', and the values sizes in KiB.  `available' is the
+/// memory that can be used without swapping, including caches.  Keys
+/// whose values aren't known on this system are left out.
+#[lisp_fn]
+pub fn memory_usage_native() -> LispObject {
+    let memory = memory();
+    let entries = [
+        ("swap-free", memory.swap_free),
+        ("swap-total", memory.swap_total),
+        ("available", memory.available),
+        ("free", memory.free),
+        ("total", memory.total),
+    ];
+    let mut result = LispObject::constant_nil();
+    for &(key, value) in &entries {
+        if let Some(kib) = value {
+            let size = LispObject::int_or_float_from_fixnum(kib as EmacsInt);
+            result = LispObject::cons(LispObject::cons(intern(key), size), result);
+        }
+    }
+    result
+}
+
+#[test]
+fn test_sysfs_battery() {
+    let bat0 = "POWER_SUPPLY_NAME=BAT0\nPOWER_SUPPLY_STATUS=Discharging\n\
+                POWER_SUPPLY_PRESENT=1\nPOWER_SUPPLY_VOLTAGE_NOW=12000000\n\
+                POWER_SUPPLY_POWER_NOW=6000000\nPOWER_SUPPLY_ENERGY_FULL=48000000\n\
+                POWER_SUPPLY_ENERGY_NOW=12000000\nPOWER_SUPPLY_TEMP=305\n";
+    let battery = sysfs_battery(&[bat0.to_string()], Some(false)).unwrap();
+    assert_eq!(battery.percentage, Some(25.0));
+    assert_eq!(battery.hours, Some(2.0));
+    assert_eq!(battery.rate, Some(6.0));
+    assert_eq!(battery.charge, Some(1000.0));
+    let conversions = battery.conversions(25.0, 10.0);
+    let get = |c| conversions.iter().find(|&&(k, _)| k == c).unwrap().1.clone();
+    assert_eq!(get('p'), "25.0");
+    assert_eq!(get('t'), "2:00");
+    assert_eq!(get('m'), "120");
+    assert_eq!(get('d'), "30.5");
+    assert_eq!(get('L'), "BAT");
+    assert_eq!(get('b'), "");
+
+    let absent = "POWER_SUPPLY_PRESENT=0\n";
+    assert_eq!(sysfs_battery(&[absent.to_string()], None), None);
+    let capacity = "POWER_SUPPLY_PRESENT=1\nPOWER_SUPPLY_STATUS=Charging\n\
+                    POWER_SUPPLY_CAPACITY=5\n";
+    let battery = sysfs_battery(&[capacity.to_string()], Some(true)).unwrap();
+    assert_eq!(battery.percentage, Some(5.0));
+    assert_eq!(battery.conversions(25.0, 10.0)[3], ('b', "+".to_string()));
+}
+
+#[test]
+fn test_proc_files() {
+    let stat = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 100 0 50 800 50 0 0 0 0 0\n";
+    let times = parse_proc_stat(stat).unwrap();
+    assert_eq!(times, CpuTimes { busy: 150, total: 1000 });
+    let later = CpuTimes { busy: 200, total: 1100 };
+    assert_eq!(busy_percentage(times, later), 50.0);
+    assert_eq!(busy_percentage(later, later), 0.0);
+
+    let meminfo = "MemTotal:  16000 kB\nMemFree:  1000 kB\nBuffers:  500 kB\n\
+                   Cached:  2500 kB\nSwapTotal:  2000 kB\nSwapFree:  1500 kB\n";
+    let memory = parse_meminfo(meminfo);
+    assert_eq!(memory.total, Some(16000));
+    assert_eq!(memory.available, Some(4000));
+    assert_eq!(memory.swap_free, Some(1500));
+}
+
+include!(concat!(env!("OUT_DIR"), "/sysinfo_exports.rs"));
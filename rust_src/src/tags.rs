@@ -0,0 +1,381 @@
+//! Indexing of etags TAGS files.
+//!
+//! etags.el finds a tag by searching the TAGS buffer for its name and
+//! checking each hit against the tag line around it, and builds the
+//! completion table by scanning every line of the buffer.  For the
+//! TAGS files of large trees both take seconds.  Here a TAGS file is
+//! parsed once into a hash table from tag names to tags, and a sorted
+//! list of names for completion, which is kept until the file changes.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lines::file_line_string;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use symbols::symbol_value;
+use util::{expand_file_name_to_path, file_name_from_bytes, report_io_error};
+
+/// The characters that end an implicit tag name, as in
+/// `etags-tags-completion-table'.
+const DELIMITERS: &[u8] = b"\x0c\t\n\r()=,; ";
+
+/// A tag line, `TEXT\x7fNAME\x01LINE,POS' or `TEXT\x7fLINE,POS'.
+#[derive(Clone, Debug, PartialEq)]
+struct Tag {
+    /// The index of the source file in `TagsIndex::files'.
+    file: usize,
+    /// The text of the definition, which locates it in the source.
+    text: Vec<u8>,
+    line: Option<EmacsInt>,
+    pos: Option<EmacsInt>,
+    /// Whether the name is given explicitly, rather than taken from the
+    /// end of TEXT.
+    explicit: bool,
+}
+
+/// The tags of a TAGS file.
+#[derive(Debug, Default)]
+struct TagsIndex {
+    /// The source files, as written in the TAGS file.
+    files: Vec<Vec<u8>>,
+    tags: Vec<Tag>,
+    /// The tags of each name, in the order of the file.
+    by_name: HashMap<Vec<u8>, Vec<usize>>,
+    /// The distinct tag names, sorted.
+    names: Vec<Vec<u8>>,
+}
+
+/// Return the implicit name of the tag whose text is TEXT: the last
+/// run of characters that are not delimiters, disregarding one
+/// delimiter at the very end.
+fn implicit_name(text: &[u8]) -> &[u8] {
+    let text = match text.last() {
+        Some(b) if DELIMITERS.contains(b) => &text[..text.len() - 1],
+        _ => text,
+    };
+    let start = text.iter()
+        .rposition(|b| DELIMITERS.contains(b))
+        .map_or(0, |i| i + 1);
+    &text[start..]
+}
+
+/// Parse the number at the start of FIELD, if there is one.
+fn number(field: &[u8]) -> Option<EmacsInt> {
+    let digits = field.iter().take_while(|&&b| b >= b'0' && b <= b'9').count();
+    ::std::str::from_utf8(&field[..digits])
+        .ok()
+        .and_then(|digits| digits.parse().ok())
+}
+
+/// Parse a tag line of FILE, returning its name and tag.
+fn parse_tag(line: &[u8], file: usize) -> Option<(&[u8], Tag)> {
+    let delete = match line.iter().position(|&b| b == 0x7f) {
+        Some(delete) => delete,
+        None => return None,
+    };
+    let text = &line[..delete];
+    let rest = &line[delete + 1..];
+    let (name, numbers, explicit) = match rest.iter().position(|&b| b == 0x01) {
+        Some(soh) => (&rest[..soh], &rest[soh + 1..], true),
+        None => (implicit_name(text), rest, false),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    let comma = numbers.iter().position(|&b| b == b',');
+    let tag = Tag {
+        file,
+        text: text.to_vec(),
+        line: number(numbers),
+        pos: comma.and_then(|comma| number(&numbers[comma + 1..])),
+        explicit,
+    };
+    Some((name, tag))
+}
+
+impl TagsIndex {
+    /// Parse the contents of a TAGS file, or return None if DATA is not
+    /// one.  Sections start with a formfeed line and a `FILE,SIZE' line;
+    /// included tables, `FILE,include', have no tags.
+    fn parse(data: &[u8]) -> Option<TagsIndex> {
+        if data.first() != Some(&0x0c) {
+            return None;
+        }
+        let mut index = TagsIndex::default();
+        let mut file = None;
+        let mut header = false;
+        for line in data.split(|&b| b == b'\n') {
+            let line = match line.last() {
+                Some(&b'\r') => &line[..line.len() - 1],
+                _ => line,
+            };
+            if line == b"\x0c" {
+                header = true;
+            } else if header {
+                header = false;
+                file = match line.iter().rposition(|&b| b == b',') {
+                    Some(comma) if &line[comma + 1..] != b"include" => {
+                        index.files.push(line[..comma].to_vec());
+                        Some(index.files.len() - 1)
+                    }
+                    _ => None,
+                };
+            } else if let Some(file) = file {
+                if let Some((name, tag)) = parse_tag(line, file) {
+                    index
+                        .by_name
+                        .entry(name.to_vec())
+                        .or_insert_with(Vec::new)
+                        .push(index.tags.len());
+                    index.tags.push(tag);
+                }
+            }
+        }
+        index.names = index.by_name.keys().cloned().collect();
+        index.names.sort();
+        Some(index)
+    }
+
+    /// Return the tags named NAME, those with an explicit name first,
+    /// each in the order of the file.
+    fn lookup(&self, name: &[u8], ignore_case: bool) -> Vec<&Tag> {
+        let indices: Vec<usize> = if ignore_case {
+            let name = fold(name);
+            let mut indices: Vec<usize> = self.names
+                .iter()
+                .filter(|n| fold(n) == name)
+                .flat_map(|n| self.by_name[n].iter().cloned())
+                .collect();
+            indices.sort();
+            indices
+        } else {
+            self.by_name.get(name).cloned().unwrap_or_else(Vec::new)
+        };
+        let explicit = indices.iter().filter(|&&i| self.tags[i].explicit);
+        let implicit = indices.iter().filter(|&&i| !self.tags[i].explicit);
+        explicit.chain(implicit).map(|&i| &self.tags[i]).collect()
+    }
+
+    /// Return the tag names that start with PREFIX, sorted.
+    fn completions(&self, prefix: &[u8], ignore_case: bool) -> Vec<&[u8]> {
+        if ignore_case {
+            let prefix = fold(prefix);
+            return self.names
+                .iter()
+                .filter(|name| fold(name).starts_with(&prefix))
+                .map(|name| name.as_slice())
+                .collect();
+        }
+        let start = match self.names.binary_search_by(|name| name.as_slice().cmp(prefix)) {
+            Ok(start) | Err(start) => start,
+        };
+        self.names[start..]
+            .iter()
+            .take_while(|name| name.starts_with(prefix))
+            .map(|name| name.as_slice())
+            .collect()
+    }
+}
+
+/// Return the lower case form of NAME, for comparing ignoring case.
+fn fold(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).to_lowercase()
+}
+
+/// The modification time and size of a TAGS file when it was indexed.
+type Stamp = (Option<SystemTime>, u64);
+
+lazy_static! {
+    /// The indexed TAGS files, by their expanded file name.
+    static ref TAGS_INDEXES: Mutex<HashMap<PathBuf, (Stamp, TagsIndex)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Return the file name of the TAGS file FILE, which defaults to
+/// `tags-file-name'.
+fn tags_file(file: LispObject) -> LispObject {
+    if file.is_not_nil() {
+        return file;
+    }
+    let file = symbol_value(intern("tags-file-name"));
+    if file.is_nil() {
+        error!("No tags table in use");
+    }
+    file
+}
+
+/// Call F with the index of the TAGS file FILE, parsing FILE if it has
+/// not been indexed or has changed since.  F must not call Lisp, which
+/// could exit nonlocally while the indexes are locked.
+fn with_index<F, T>(file: LispObject, f: F) -> T
+where
+    F: FnOnce(&TagsIndex) -> T,
+{
+    let path = expand_file_name_to_path(file);
+    let stamp = match fs::metadata(&path) {
+        Ok(metadata) => (metadata.modified().ok(), metadata.len()),
+        Err(err) => report_io_error(b"Opening tags table\0", file, &err),
+    };
+    let current = TAGS_INDEXES
+        .lock()
+        .unwrap()
+        .get(&path)
+        .map_or(false, |&(ref indexed, _)| *indexed == stamp);
+    if !current {
+        let mut data = Vec::new();
+        if let Err(err) = File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+            report_io_error(b"Reading tags table\0", file, &err);
+        }
+        let index = match TagsIndex::parse(&data) {
+            Some(index) => index,
+            None => error!("Not a valid tags table: {}", path.display()),
+        };
+        TAGS_INDEXES.lock().unwrap().insert(path.clone(), (stamp, index));
+    }
+    let indexes = TAGS_INDEXES.lock().unwrap();
+    f(&indexes[&path].1)
+}
+
+/// Return the bytes of NAME, a string or a symbol.
+fn name_bytes(name: LispObject) -> Vec<u8> {
+    let name = match name.as_symbol() {
+        Some(sym) => sym.symbol_name(),
+        None => name,
+    };
+    name.as_string_or_error().as_slice().to_vec()
+}
+
+fn number_or_nil(n: Option<EmacsInt>) -> LispObject {
+    n.map_or(LispObject::constant_nil(), LispObject::from_natnum)
+}
+
+/// Return the tags named NAME in the TAGS file FILE.
+/// NAME is a string or a symbol.  FILE defaults to `tags-file-name'.
+/// If IGNORE-CASE is non-nil, the case of names is not significant.
+///
+/// The value is a list of (SOURCE TEXT LINE . STARTPOS), where SOURCE
+/// is the file of the tag as written in FILE, and (TEXT LINE . STARTPOS)
+/// is the tag info `etags-snarf-tag' would return.  Tags with an
+/// explicit name come first, then those whose name is implicit, each in
+/// the order of FILE.  Included tables are not searched.
+#[lisp_fn(min = "1")]
+pub fn tags_lookup_native(
+    name: LispObject,
+    file: LispObject,
+    ignore_case: LispObject,
+) -> LispObject {
+    let name = name_bytes(name);
+    let tags: Vec<(Vec<u8>, Tag)> = with_index(tags_file(file), |index| {
+        index
+            .lookup(&name, ignore_case.is_not_nil())
+            .into_iter()
+            .map(|tag| (index.files[tag.file].clone(), tag.clone()))
+            .collect()
+    });
+    tags.iter().rev().fold(LispObject::constant_nil(), |list, &(ref source, ref tag)| {
+        let info = LispObject::cons(
+            file_line_string(&tag.text, LispObject::constant_nil()),
+            LispObject::cons(number_or_nil(tag.line), number_or_nil(tag.pos)),
+        );
+        LispObject::cons(LispObject::cons(file_name_from_bytes(source), info), list)
+    })
+}
+
+/// Return the names of the tags in the TAGS file FILE that start with
+/// PREFIX, sorted and without duplicates.  FILE defaults to
+/// `tags-file-name'.  If IGNORE-CASE is non-nil, the case of PREFIX is
+/// not significant.  Included tables are not searched.
+#[lisp_fn(min = "1")]
+pub fn tags_completions_native(
+    prefix: LispObject,
+    file: LispObject,
+    ignore_case: LispObject,
+) -> LispObject {
+    let prefix = name_bytes(prefix);
+    let names: Vec<Vec<u8>> = with_index(tags_file(file), |index| {
+        index
+            .completions(&prefix, ignore_case.is_not_nil())
+            .into_iter()
+            .map(|name| name.to_vec())
+            .collect()
+    });
+    names.iter().rev().fold(LispObject::constant_nil(), |list, name| {
+        LispObject::cons(file_line_string(name, LispObject::constant_nil()), list)
+    })
+}
+
+#[cfg(test)]
+const TEST_TAGS: &[u8] = b"\x0c\nsrc/a.c,120\n\
+int foo(\x7f3,10\n\
+#define BAR \x7fBAR\x015,40\n\
+static int foo;\x7f9,80\n\
+\x0c\nsrc/b.el,60\n\
+(defun foo-bar ()\x7ffoo-bar\x011,0\n\
+(defun foo \x7f\r\n\
+\x0c\nother/TAGS,include\n";
+
+#[test]
+fn test_implicit_name() {
+    assert_eq!(implicit_name(b"int foo("), b"foo");
+    assert_eq!(implicit_name(b"static int foo;"), b"foo");
+    assert_eq!(implicit_name(b"foo"), b"foo");
+    assert_eq!(implicit_name(b"int (("), b"");
+}
+
+#[test]
+fn test_parse() {
+    let index = TagsIndex::parse(TEST_TAGS).unwrap();
+    assert_eq!(index.files, vec![b"src/a.c".to_vec(), b"src/b.el".to_vec()]);
+    assert_eq!(index.tags.len(), 5);
+    assert_eq!(
+        index.tags[1],
+        Tag {
+            file: 0,
+            text: b"#define BAR ".to_vec(),
+            line: Some(5),
+            pos: Some(40),
+            explicit: true,
+        }
+    );
+    assert_eq!(index.tags[4].line, None);
+    assert_eq!(index.tags[4].pos, None);
+    assert_eq!(
+        index.names,
+        vec![b"BAR".to_vec(), b"foo".to_vec(), b"foo-bar".to_vec()]
+    );
+    assert!(TagsIndex::parse(b"int foo(\x7f3,10\n").is_none());
+}
+
+#[test]
+fn test_lookup() {
+    let index = TagsIndex::parse(TEST_TAGS).unwrap();
+    let lines = |tags: Vec<&Tag>| tags.iter().map(|tag| tag.line).collect::<Vec<_>>();
+    assert_eq!(lines(index.lookup(b"foo", false)), vec![Some(3), Some(9), None]);
+    assert_eq!(lines(index.lookup(b"BAR", false)), vec![Some(5)]);
+    assert!(index.lookup(b"bar", false).is_empty());
+    assert_eq!(lines(index.lookup(b"bar", true)), vec![Some(5)]);
+    assert!(index.lookup(b"fo", false).is_empty());
+}
+
+#[test]
+fn test_completions() {
+    let index = TagsIndex::parse(TEST_TAGS).unwrap();
+    assert_eq!(
+        index.completions(b"foo", false),
+        vec![&b"foo"[..], &b"foo-bar"[..]]
+    );
+    assert_eq!(index.completions(b"foo-", false), vec![&b"foo-bar"[..]]);
+    assert_eq!(index.completions(b"", false).len(), 3);
+    assert!(index.completions(b"b", false).is_empty());
+    assert_eq!(index.completions(b"b", true), vec![&b"BAR"[..]]);
+}
+
+include!(concat!(env!("OUT_DIR"), "/tags_exports.rs"));
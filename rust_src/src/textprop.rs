@@ -0,0 +1,129 @@
+//! Text property primitives.
+
+use libc::ptrdiff_t;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{add_text_property_runs, text_property_run, validate_interval_range,
+                 validate_plist, Fadd_text_properties, Qvectorp};
+
+use buffers::current_buffer;
+use lisp::LispObject;
+use lisp::defsubr;
+
+fn add_text_properties(
+    start: LispObject,
+    end: LispObject,
+    properties: LispObject,
+    object: LispObject,
+) -> bool {
+    LispObject::from(unsafe {
+        Fadd_text_properties(
+            start.to_raw(),
+            end.to_raw(),
+            properties.to_raw(),
+            object.to_raw(),
+        )
+    }).is_not_nil()
+}
+
+/// Set one property of the text from START to END.
+/// The third and fourth arguments PROPERTY and VALUE
+/// specify the property to add.
+/// If the optional fifth argument OBJECT is a buffer (or nil, which means
+/// the current buffer), START and END are buffer positions (integers or
+/// markers).  If OBJECT is a string, START and END are 0-based indices into it.
+#[lisp_fn(min = "4")]
+pub fn put_text_property(
+    start: LispObject,
+    end: LispObject,
+    property: LispObject,
+    value: LispObject,
+    object: LispObject,
+) -> LispObject {
+    add_text_properties(start, end, list!(property, value), object);
+    LispObject::constant_nil()
+}
+
+/// Check a (START END PROPERTIES) triple against OBJECT, signaling an
+/// error if it is not a list of at least three elements or if its range
+/// is not valid in OBJECT.
+fn property_run(triple: LispObject, object: LispObject) -> text_property_run {
+    let first = triple.as_cons_or_error();
+    let second = first.cdr().as_cons_or_error();
+    let third = second.cdr().as_cons_or_error();
+    let mut start = first.car().to_raw();
+    let mut end = second.car().to_raw();
+    let properties = third.car().to_raw();
+    unsafe {
+        validate_interval_range(object.to_raw(), &mut start, &mut end, true);
+        validate_plist(properties);
+    }
+    text_property_run {
+        start: LispObject::from(start).as_fixnum_or_error() as ptrdiff_t,
+        end: LispObject::from(end).as_fixnum_or_error() as ptrdiff_t,
+        properties,
+    }
+}
+
+/// Add properties to many ranges of text at once.
+/// TRIPLES is a vector whose elements are lists (START END PROPERTIES),
+/// each meaning the same as the arguments of `add-text-properties'.
+///
+/// If the optional second argument OBJECT is a buffer (or nil, which
+/// means the current buffer), START and END are buffer positions
+/// (integers or markers).  If OBJECT is a string, START and END are
+/// 0-based indices into it.
+///
+/// Every triple is checked before any property is added.  The ranges
+/// are then added in order of position, in a single pass over the text;
+/// where two ranges overlap, the one that starts later wins, and ranges
+/// that start together are added in the order given.  Adjoining ranges
+/// with the same (`eq') property list are merged.  The modification
+/// hooks of a buffer run once, for the text from the start of the
+/// first range to the end of the last one.
+///
+/// Return t if any property value actually changed, nil otherwise.
+#[lisp_fn(min = "1")]
+pub fn add_text_properties_batch(triples: LispObject, object: LispObject) -> LispObject {
+    let triples = match triples.as_vectorlike().and_then(|v| v.as_vector()) {
+        Some(v) => v,
+        None => wrong_type!(Qvectorp, triples),
+    };
+    let object = if object.is_nil() {
+        current_buffer()
+    } else {
+        object
+    };
+    let mut runs: Vec<text_property_run> = triples
+        .as_slice()
+        .iter()
+        .map(|&triple| property_run(triple, object))
+        .collect();
+    // A stable sort, so that runs starting together keep their order.
+    runs.sort_by_key(|run| run.start);
+
+    // Merge adjoining runs with the very same property list.
+    let mut merged: Vec<text_property_run> = Vec::with_capacity(runs.len());
+    for run in runs {
+        if let Some(last) = merged.last_mut() {
+            if last.end == run.start && last.properties == run.properties {
+                last.end = run.end;
+                continue;
+            }
+        }
+        merged.push(run);
+    }
+    if merged.is_empty() {
+        return LispObject::constant_nil();
+    }
+
+    LispObject::from_bool(unsafe {
+        add_text_property_runs(
+            object.to_raw(),
+            merged.len() as ptrdiff_t,
+            merged.as_mut_ptr(),
+        )
+    })
+}
+
+include!(concat!(env!("OUT_DIR"), "/textprop_exports.rs"));
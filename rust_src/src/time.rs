@@ -0,0 +1,406 @@
+//! Parsing of ISO 8601 and RFC 3339 time strings.
+//!
+//! Feeds, calendars, mail, version control logs and JSON APIs all write
+//! times in some profile of ISO 8601, and Lisp packages have each grown
+//! their own regexps for it.  The parser here accepts the whole
+//! standard in its lenient mode, including week and ordinal dates, and
+//! exactly the grammar of RFC 3339 in its strict mode.
+
+use remacs_macros::lisp_fn;
+use remacs_sys::EmacsInt;
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+
+/// A time parsed from a string.  Fields may be out of range as
+/// `encode-time' accepts them, as for the hour 24.
+#[derive(Clone, Debug, PartialEq)]
+struct ParsedTime {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    /// The fraction of the second, in picoseconds.
+    picoseconds: i64,
+    /// The offset from UTC in seconds, or None for local time.
+    offset: Option<i64>,
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The (MONTH, DAY) of the DAYth day of YEAR, counting from 1.
+fn month_day_from_ordinal(year: i64, mut day: i64) -> (i64, i64) {
+    let mut month = 1;
+    while month < 12 && day > days_in_month(year, month) {
+        day -= days_in_month(year, month);
+        month += 1;
+    }
+    (month, day)
+}
+
+/// The day of the week of the DAYth day of YEAR, from 1 for Monday to
+/// 7 for Sunday.
+fn iso_weekday(year: i64, day: i64) -> i64 {
+    // January 1 of the year 1 was a Monday.
+    let prior = year - 1;
+    let days = prior * 365 + prior / 4 - prior / 100 + prior / 400 + day - 1;
+    days % 7 + 1
+}
+
+/// The (YEAR, MONTH, DAY) of DAY of WEEK of the ISO week-numbering YEAR.
+fn date_from_week(year: i64, week: i64, day: i64) -> (i64, i64, i64) {
+    // The week containing January 4 is the first week.
+    let ordinal = 4 - (iso_weekday(year, 4) - 1) + (week - 1) * 7 + day - 1;
+    let days_in_year = |year| if is_leap_year(year) { 366 } else { 365 };
+    let (year, ordinal) = if ordinal < 1 {
+        (year - 1, ordinal + days_in_year(year - 1))
+    } else if ordinal > days_in_year(year) {
+        (year + 1, ordinal - days_in_year(year))
+    } else {
+        (year, ordinal)
+    };
+    let (month, day) = month_day_from_ordinal(year, ordinal);
+    (year, month, day)
+}
+
+/// A cursor over the bytes of a time string.
+struct Scanner<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The number of decimal digits at point.
+    fn digit_count(&self) -> usize {
+        self.text[self.pos..]
+            .iter()
+            .take_while(|&&b| b >= b'0' && b <= b'9')
+            .count()
+    }
+
+    /// Read exactly N decimal digits.
+    fn digits(&mut self, n: usize) -> Result<i64, ()> {
+        if self.digit_count() < n {
+            return Err(());
+        }
+        let value = self.text[self.pos..self.pos + n]
+            .iter()
+            .fold(0, |value, &b| value * 10 + i64::from(b - b'0'));
+        self.pos += n;
+        Ok(value)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.text.len()
+    }
+}
+
+/// Read a date as ISO 8601 allows it: a calendar date, a week date or
+/// an ordinal date, with or without hyphens.  STRICT allows only the
+/// full extended calendar date of RFC 3339.
+fn parse_date(scanner: &mut Scanner, strict: bool) -> Result<(i64, i64, i64), ()> {
+    let year = scanner.digits(4)?;
+    let extended = scanner.eat(b'-');
+    if strict && !extended {
+        return Err(());
+    }
+    if !strict && (scanner.eat(b'W') || scanner.eat(b'w')) {
+        let week = scanner.digits(2)?;
+        // The day of the week may be omitted, for the Monday.
+        let day = if !extended || scanner.eat(b'-') {
+            if extended || scanner.digit_count() > 0 {
+                scanner.digits(1)?
+            } else {
+                1
+            }
+        } else {
+            1
+        };
+        if week < 1 || week > 53 || day < 1 || day > 7 {
+            return Err(());
+        }
+        return Ok(date_from_week(year, week, day));
+    }
+    if !strict && scanner.digit_count() == 3 {
+        let ordinal = scanner.digits(3)?;
+        let days = if is_leap_year(year) { 366 } else { 365 };
+        if ordinal < 1 || ordinal > days {
+            return Err(());
+        }
+        let (month, day) = month_day_from_ordinal(year, ordinal);
+        return Ok((year, month, day));
+    }
+    let month = scanner.digits(2)?;
+    let day = if !extended {
+        scanner.digits(2)?
+    } else if scanner.eat(b'-') {
+        scanner.digits(2)?
+    } else if strict {
+        return Err(());
+    } else {
+        // A year and month, as in 2018-07.
+        1
+    };
+    if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+        return Err(());
+    }
+    Ok((year, month, day))
+}
+
+/// Read the digits of the fraction of a second, after its decimal mark.
+/// Return the fraction in picoseconds.
+fn parse_fraction(scanner: &mut Scanner) -> Result<i64, ()> {
+    let count = scanner.digit_count();
+    if count == 0 {
+        return Err(());
+    }
+    let digits = &scanner.text[scanner.pos..scanner.pos + count.min(12)];
+    let picoseconds = digits
+        .iter()
+        .fold(0, |value, &b| value * 10 + i64::from(b - b'0'));
+    scanner.pos += count;
+    Ok(picoseconds * 10_i64.pow(12 - digits.len() as u32))
+}
+
+/// Read a time of day, hh:mm:ss with an optional fraction, or in
+/// lenient mode also hh:mm, hh and the basic forms without colons.
+/// Return the hour, minute, second and picoseconds.
+fn parse_clock(scanner: &mut Scanner, strict: bool) -> Result<(i64, i64, i64, i64), ()> {
+    let hour = scanner.digits(2)?;
+    let extended = scanner.eat(b':');
+    if strict && !extended {
+        return Err(());
+    }
+    let mut minute = 0;
+    let mut second = 0;
+    let mut picoseconds = 0;
+    if extended || scanner.digit_count() >= 2 {
+        minute = scanner.digits(2)?;
+        let more = if extended {
+            scanner.eat(b':')
+        } else {
+            scanner.digit_count() >= 2
+        };
+        if more {
+            second = scanner.digits(2)?;
+            if scanner.eat(b'.') || (!strict && scanner.eat(b',')) {
+                picoseconds = parse_fraction(scanner)?;
+            }
+        } else if strict {
+            return Err(());
+        }
+    }
+    let valid = if hour == 24 {
+        !strict && minute == 0 && second == 0 && picoseconds == 0
+    } else {
+        hour < 24 && minute < 60 && second <= 60
+    };
+    if valid {
+        Ok((hour, minute, second, picoseconds))
+    } else {
+        Err(())
+    }
+}
+
+/// Read a UTC offset, `Z' or a signed hh:mm, and return it in seconds.
+/// Lenient mode also allows hhmm and hh.
+fn parse_offset(scanner: &mut Scanner, strict: bool) -> Result<i64, ()> {
+    if scanner.eat(b'Z') || scanner.eat(b'z') {
+        return Ok(0);
+    }
+    let sign = if scanner.eat(b'+') {
+        1
+    } else if scanner.eat(b'-') {
+        -1
+    } else {
+        return Err(());
+    };
+    let hours = scanner.digits(2)?;
+    let minutes = if scanner.eat(b':') {
+        scanner.digits(2)?
+    } else if strict {
+        return Err(());
+    } else if scanner.digit_count() > 0 {
+        scanner.digits(2)?
+    } else {
+        0
+    };
+    if hours > 23 || minutes > 59 {
+        return Err(());
+    }
+    Ok(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parse TEXT as an ISO 8601 time, or as an RFC 3339 one if STRICT.
+fn parse_time(text: &[u8], strict: bool) -> Result<ParsedTime, ()> {
+    let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+    let text = if strict {
+        text
+    } else {
+        let start = text.iter().position(|b| !is_blank(b)).unwrap_or(text.len());
+        let end = text.iter().rposition(|b| !is_blank(b)).map_or(start, |n| n + 1);
+        &text[start..end]
+    };
+    let mut scanner = Scanner { text, pos: 0 };
+    let (year, month, day) = parse_date(&mut scanner, strict)?;
+    let mut time = ParsedTime {
+        year,
+        month,
+        day,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        picoseconds: 0,
+        offset: None,
+    };
+    if scanner.eat(b'T') || scanner.eat(b't') || scanner.eat(b' ') {
+        let (hour, minute, second, picoseconds) = parse_clock(&mut scanner, strict)?;
+        time.hour = hour;
+        time.minute = minute;
+        time.second = second;
+        time.picoseconds = picoseconds;
+        if !scanner.at_end() {
+            time.offset = Some(parse_offset(&mut scanner, strict)?);
+        }
+    }
+    if !scanner.at_end() || (strict && time.offset.is_none()) {
+        return Err(());
+    }
+    Ok(time)
+}
+
+/// Parse the ISO 8601 time string STRING, such as 2016-12-01T23:35:06-05:00.
+/// Return the time as a Lisp time value, or nil if STRING isn't valid.
+/// Times without a UTC offset are in local time.
+///
+/// All the forms of ISO 8601 are accepted: calendar dates as in
+/// 2016-12-01 or 20161201, week dates as in 2016-W48-4, ordinal dates
+/// as in 2016-336, times with or without seconds and colons, fractions
+/// of seconds after a period or a comma, and offsets such as Z, +01,
+/// +0100 or +01:00.  The date may be followed by the time after a T or
+/// a space, and surrounding whitespace is ignored.
+///
+/// If STRICT is non-nil, only the date and time format of RFC 3339 is
+/// accepted, which is a complete date and time with seconds and an
+/// offset, as used by Atom feeds and most Internet protocols.
+#[lisp_fn(min = "1")]
+pub fn parse_iso8601_time_string_native(string: LispObject, strict: LispObject) -> LispObject {
+    let string = string.as_string_or_error();
+    let time = match parse_time(string.as_slice(), strict.is_not_nil()) {
+        Ok(time) => time,
+        Err(()) => return LispObject::constant_nil(),
+    };
+    let number = |n: i64| LispObject::from_fixnum(n as EmacsInt);
+    let zone = match time.offset {
+        Some(offset) => number(offset),
+        None => LispObject::constant_nil(),
+    };
+    let encoded = call!(
+        intern("encode-time"),
+        number(time.second),
+        number(time.minute),
+        number(time.hour),
+        number(time.day),
+        number(time.month),
+        number(time.year),
+        zone
+    );
+    if time.picoseconds == 0 {
+        return encoded;
+    }
+    let fraction = list!(
+        number(0),
+        number(0),
+        number(time.picoseconds / 1_000_000),
+        number(time.picoseconds % 1_000_000)
+    );
+    call!(intern("time-add"), encoded, fraction)
+}
+
+#[test]
+fn test_dates() {
+    assert_eq!(date_from_week(2016, 48, 4), (2016, 12, 1));
+    assert_eq!(date_from_week(2009, 1, 1), (2008, 12, 29));
+    assert_eq!(date_from_week(2009, 53, 7), (2010, 1, 3));
+    assert_eq!(month_day_from_ordinal(2016, 336), (12, 1));
+    assert_eq!(month_day_from_ordinal(2015, 60), (3, 1));
+}
+
+#[test]
+fn test_parse_time() {
+    let time = |date: (i64, i64, i64), hour, minute, second, picoseconds, offset| ParsedTime {
+        year: date.0,
+        month: date.1,
+        day: date.2,
+        hour,
+        minute,
+        second,
+        picoseconds,
+        offset,
+    };
+    let date = (2016, 12, 1);
+    assert_eq!(
+        parse_time(b"2016-12-01T23:35:06.25-05:00", true),
+        Ok(time(date, 23, 35, 6, 250_000_000_000, Some(-18_000)))
+    );
+    assert_eq!(
+        parse_time(b"2016-12-01t23:35:06z", true),
+        Ok(time(date, 23, 35, 6, 0, Some(0)))
+    );
+    for text in &["2016-12-01", "2016-12-01T23:35Z", "20161201T233506Z", "2016-12-01T23:35:06+05"] {
+        assert_eq!(parse_time(text.as_bytes(), true), Err(()));
+    }
+
+    let midnight = time(date, 0, 0, 0, 0, None);
+    for text in &["2016-12-01", "20161201", "2016-W48-4", "2016W484", "2016-336", " 2016336 "] {
+        assert_eq!(parse_time(text.as_bytes(), false), Ok(midnight.clone()));
+    }
+    assert_eq!(
+        parse_time(b"2016-W48", false),
+        Ok(time((2016, 11, 28), 0, 0, 0, 0, None))
+    );
+    assert_eq!(
+        parse_time(b"20161201T2335,5+0130", false),
+        Err(())
+    );
+    assert_eq!(
+        parse_time(b"20161201T233506,5+0130", false),
+        Ok(time(date, 23, 35, 6, 500_000_000_000, Some(5400)))
+    );
+    assert_eq!(
+        parse_time(b"2016-12-01 23:35-08", false),
+        Ok(time(date, 23, 35, 0, 0, Some(-28_800)))
+    );
+    assert_eq!(parse_time(b"2016-12-01T24:00", false), Ok(time(date, 24, 0, 0, 0, None)));
+    for text in &["2016-02-30", "2016-13-01", "2016-W54", "2016-12-01T25:00", "2016-12-01x"] {
+        assert_eq!(parse_time(text.as_bytes(), false), Err(()));
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/time_exports.rs"));
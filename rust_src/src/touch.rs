@@ -0,0 +1,201 @@
+//! Touchpad gestures.
+//!
+//! The window system code reports pinch, rotate and swipe gestures
+//! with `touch_event_push', which keeps the details of the gesture here
+//! and returns an id that travels through the keyboard buffer in the
+//! `code' of a TOUCH_GESTURE_EVENT.  When the event is read,
+//! `touch_event_lisp' turns the details into the Lisp event
+//!
+//!     (KIND POSITION TOUCH)
+//!
+//! where KIND is `pinch', `rotate' or `swipe', POSITION is as for mouse
+//! events and TOUCH is a plist with the properties `:phase', `begin',
+//! `update' or `end'; `:fingers', the number of fingers; `:delta', the
+//! (DX . DY) the fingers moved by since the last event, in pixels;
+//! `:scale', the distance between the fingers relative to the start of
+//! the gesture; and `:angle', the degrees the fingers turned clockwise
+//! since the last event.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use libc::{c_int, c_uint};
+
+use remacs_sys::{EmacsInt, Lisp_Object};
+
+use lisp::{intern, LispObject};
+
+/// The most gestures kept for events that have not been read yet.
+/// Older ones belong to events that were thrown away, for instance by
+/// `discard-input'.
+const MAX_PENDING: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Pinch,
+    Rotate,
+    Swipe,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    Begin,
+    Update,
+    End,
+}
+
+/// A gesture as reported by the window system.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TouchEvent {
+    kind: Kind,
+    phase: Phase,
+    fingers: u32,
+    delta: (f64, f64),
+    scale: f64,
+    angle: f64,
+}
+
+impl TouchEvent {
+    fn to_lisp(&self) -> LispObject {
+        let kind = match self.kind {
+            Kind::Pinch => "pinch",
+            Kind::Rotate => "rotate",
+            Kind::Swipe => "swipe",
+        };
+        let phase = match self.phase {
+            Phase::Begin => "begin",
+            Phase::Update => "update",
+            Phase::End => "end",
+        };
+        let plist = list!(
+            intern(":phase"),
+            intern(phase),
+            intern(":fingers"),
+            LispObject::from_natnum(EmacsInt::from(self.fingers)),
+            intern(":delta"),
+            LispObject::cons(
+                LispObject::from_float(self.delta.0),
+                LispObject::from_float(self.delta.1)
+            ),
+            intern(":scale"),
+            LispObject::from_float(self.scale),
+            intern(":angle"),
+            LispObject::from_float(self.angle)
+        );
+        LispObject::cons(intern(kind), plist)
+    }
+}
+
+/// The gestures of events in the keyboard buffer, oldest first, with
+/// their ids, and the id of the last one.
+struct Pending {
+    last_id: c_uint,
+    events: VecDeque<(c_uint, TouchEvent)>,
+}
+
+impl Pending {
+    fn push(&mut self, event: TouchEvent) -> c_uint {
+        self.last_id = self.last_id.wrapping_add(1);
+        if self.events.len() == MAX_PENDING {
+            self.events.pop_front();
+        }
+        self.events.push_back((self.last_id, event));
+        self.last_id
+    }
+
+    /// Remove and return the gesture ID, forgetting the ones before it,
+    /// whose events must have been thrown away.
+    fn take(&mut self, id: c_uint) -> Option<TouchEvent> {
+        let index = self.events.iter().position(|&(n, _)| n == id)?;
+        let event = self.events[index].1;
+        self.events.drain(..index + 1);
+        Some(event)
+    }
+}
+
+lazy_static! {
+    static ref PENDING: Mutex<Pending> = Mutex::new(Pending {
+        last_id: 0,
+        events: VecDeque::new(),
+    });
+}
+
+/// Record a gesture of KIND (0 for a pinch, 1 for a rotation and 2 for
+/// a swipe) in PHASE (0 when it begins, 1 as it goes on and 2 when it
+/// ends) made with FINGERS fingers, and return the id to put in the
+/// `code' of its TOUCH_GESTURE_EVENT.  DX and DY are the motion since
+/// the last event of the gesture, SCALE the distance of the fingers
+/// relative to the start and ANGLE the rotation since the last event.
+#[no_mangle]
+pub extern "C" fn touch_event_push(
+    kind: c_int,
+    phase: c_int,
+    fingers: c_int,
+    dx: f64,
+    dy: f64,
+    scale: f64,
+    angle: f64,
+) -> c_uint {
+    let event = TouchEvent {
+        kind: match kind {
+            0 => Kind::Pinch,
+            1 => Kind::Rotate,
+            _ => Kind::Swipe,
+        },
+        phase: match phase {
+            0 => Phase::Begin,
+            1 => Phase::Update,
+            _ => Phase::End,
+        },
+        fingers: fingers.max(0) as u32,
+        delta: (dx, dy),
+        scale: scale,
+        angle: angle,
+    };
+    PENDING.lock().unwrap().push(event)
+}
+
+/// Return (KIND . TOUCH) for the gesture ID, or nil if it is not known.
+/// Called when a TOUCH_GESTURE_EVENT is made into a Lisp event.
+#[no_mangle]
+pub extern "C" fn touch_event_lisp(id: c_uint) -> Lisp_Object {
+    let event = PENDING.lock().unwrap().take(id);
+    event
+        .map_or_else(LispObject::constant_nil, |event| event.to_lisp())
+        .to_raw()
+}
+
+#[cfg(test)]
+fn test_event(kind: Kind, fingers: u32) -> TouchEvent {
+    TouchEvent {
+        kind: kind,
+        phase: Phase::Update,
+        fingers: fingers,
+        delta: (1.0, -2.0),
+        scale: 1.5,
+        angle: 0.0,
+    }
+}
+
+#[test]
+fn test_pending() {
+    let mut pending = Pending {
+        last_id: c_uint::max_value() - 1,
+        events: VecDeque::new(),
+    };
+    let first = pending.push(test_event(Kind::Pinch, 2));
+    let second = pending.push(test_event(Kind::Swipe, 3));
+    let third = pending.push(test_event(Kind::Rotate, 2));
+    assert_eq!(second, 0);
+    assert_eq!(pending.take(second), Some(test_event(Kind::Swipe, 3)));
+    // The event of the first gesture was lost.
+    assert_eq!(pending.take(first), None);
+    assert_eq!(pending.events.len(), 1);
+    assert_eq!(pending.take(third).map(|event| event.kind), Some(Kind::Rotate));
+
+    for n in 0..MAX_PENDING + 1 {
+        pending.push(test_event(Kind::Pinch, n as u32));
+    }
+    assert_eq!(pending.events.len(), MAX_PENDING);
+    assert_eq!(pending.events[0].1.fingers, 1);
+}
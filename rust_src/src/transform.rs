@@ -0,0 +1,273 @@
+//! Character by character transformations of strings and buffer text.
+
+use std::ptr;
+
+use libc::{c_char, c_int, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{buf_charpos_to_bytepos, char_table_ref, make_specified_string, modify_text,
+                 record_change, replace_range, signal_after_change, update_compositions,
+                 EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use multibyte::{char_string, raw_byte_codepoint, raw_byte_from_codepoint, string_char,
+                Codepoint, MAX_5_BYTE_CHAR, MAX_MULTIBYTE_LENGTH};
+use symbols::symbol_value;
+use threads::ThreadState;
+
+/// Check both compositions that start before and end after a change,
+/// `CHECK_BORDER` in composite.h.
+const CHECK_BORDER: c_int = 3;
+
+fn rot13(c: Codepoint) -> Codepoint {
+    match c {
+        0x41...0x5A => (c - 0x41 + 13) % 26 + 0x41,
+        0x61...0x7A => (c - 0x61 + 13) % 26 + 0x61,
+        _ => c,
+    }
+}
+
+/// Return the characters of CHARS in reverse order, keeping each
+/// character for which IS_MARK is true after the character it follows.
+fn reverse_clusters<F: Fn(Codepoint) -> bool>(chars: &[Codepoint], is_mark: F) -> Vec<Codepoint> {
+    let mut result = Vec::with_capacity(chars.len());
+    let mut end = chars.len();
+    while end > 0 {
+        let mut start = end - 1;
+        while start > 0 && is_mark(chars[start]) {
+            start -= 1;
+        }
+        result.extend_from_slice(&chars[start..end]);
+        end = start;
+    }
+    result
+}
+
+/// Return the bytes that store C in a buffer or string, or None if C
+/// can't be stored in a unibyte one.
+fn encode(c: Codepoint, multibyte: bool, buf: &mut [u8; MAX_MULTIBYTE_LENGTH]) -> Option<usize> {
+    if multibyte {
+        Some(char_string(c, buf.as_mut_ptr()) as usize)
+    } else if c <= 0xFF {
+        buf[0] = c as u8;
+        Some(1)
+    } else if c > MAX_5_BYTE_CHAR {
+        buf[0] = raw_byte_from_codepoint(c);
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// Replace each character C from START to END in the current buffer by
+/// MAP(C), if that is another character.  Return the number of
+/// characters changed.
+///
+/// The region is read once to find the changed span, which is then
+/// prepared, recorded for undo and reported to the change hooks as a
+/// whole.  Characters whose replacement has as many bytes are
+/// overwritten in place, so that markers and text properties stay as
+/// they are.
+fn translate<F: Fn(Codepoint) -> Option<Codepoint>>(
+    start: LispObject,
+    end: LispObject,
+    map: F,
+) -> EmacsInt {
+    let (a, b) = (start.as_fixnum_or_error(), end.as_fixnum_or_error());
+    let (start, end) = (a.min(b) as ptrdiff_t, a.max(b) as ptrdiff_t);
+    let buffer = ThreadState::current_buffer();
+    if start < buffer.begv || end > buffer.zv {
+        args_out_of_range!(
+            LispObject::from_fixnum(start as EmacsInt),
+            LispObject::from_fixnum(end as EmacsInt)
+        );
+    }
+    let multibyte = LispObject::from(buffer.enable_multibyte_characters).is_not_nil();
+    let mut buf = [0; MAX_MULTIBYTE_LENGTH];
+
+    // Return the character at POS_BYTE and its length in bytes.
+    let char_at = |pos_byte: ptrdiff_t| {
+        let buffer = ThreadState::current_buffer();
+        let p = buffer.byte_pos_addr(pos_byte);
+        if multibyte {
+            let mut len = 0;
+            let c = string_char(p, ptr::null_mut(), &mut len);
+            (c as Codepoint, len as ptrdiff_t)
+        } else {
+            (Codepoint::from(unsafe { *p }), 1)
+        }
+    };
+
+    let mut changed = None;
+    let mut pos_byte = unsafe { buf_charpos_to_bytepos(buffer.as_ptr(), start) };
+    for pos in start..end {
+        let (c, len) = char_at(pos_byte);
+        if map(c).map_or(false, |new| new != c && encode(new, multibyte, &mut buf).is_some()) {
+            let first = changed.map_or(pos, |(first, _)| first);
+            changed = Some((first, pos + 1));
+        }
+        pos_byte += len;
+    }
+    let (first, last) = match changed {
+        None => return 0,
+        Some(span) => span,
+    };
+
+    unsafe {
+        modify_text(first, last);
+        record_change(first, last - first);
+    }
+    // The change hooks may have shortened the buffer.
+    let last = last.min(ThreadState::current_buffer().zv);
+    let mut count = 0;
+    let mut pos_byte = unsafe { buf_charpos_to_bytepos(buffer.as_ptr(), first) };
+    for pos in first..last {
+        let (c, mut len) = char_at(pos_byte);
+        let new = match map(c) {
+            Some(new) if new != c => new,
+            _ => {
+                pos_byte += len;
+                continue;
+            }
+        };
+        if let Some(new_len) = encode(new, multibyte, &mut buf) {
+            if new_len as ptrdiff_t == len {
+                let p = ThreadState::current_buffer().byte_pos_addr(pos_byte);
+                unsafe { ptr::copy_nonoverlapping(buf.as_ptr(), p, new_len) };
+            } else {
+                // This moves the gap, but handles the change of length.
+                unsafe {
+                    let string = make_specified_string(
+                        buf.as_ptr() as *const c_char,
+                        1,
+                        new_len as ptrdiff_t,
+                        true,
+                    );
+                    replace_range(pos, pos + 1, string, true, false, true, false);
+                }
+                len = new_len as ptrdiff_t;
+            }
+            count += 1;
+        }
+        pos_byte += len;
+    }
+    unsafe {
+        signal_after_change(first, last - first, last - first);
+        update_compositions(first, last, CHECK_BORDER);
+    }
+    count
+}
+
+/// ROT13 encrypt the region between START and END in current buffer.
+/// Return the number of characters changed.
+#[lisp_fn(intspec = "r")]
+pub fn rot13_region(start: LispObject, end: LispObject) -> LispObject {
+    LispObject::from_natnum(translate(start, end, |c| Some(rot13(c))))
+}
+
+/// From START to END, translate characters according to TABLE, in a
+/// single pass over the buffer text.
+/// TABLE is a string or a char-table.  If TABLE is a string, the Nth
+/// character in it is the mapping for the character with code N; a
+/// table of 256 characters covers unibyte text.  If TABLE is a
+/// char-table, the element for character N is the mapping for the
+/// character with code N.  Characters beyond the end of a string and
+/// char-table elements that aren't characters leave the character
+/// unchanged; use `translate-region' for tables that map characters to
+/// sequences.
+/// It returns the number of characters changed.
+#[lisp_fn]
+pub fn translate_region_native(
+    start: LispObject,
+    end: LispObject,
+    table: LispObject,
+) -> LispObject {
+    let count = if let Some(char_table) = table.as_char_table() {
+        if !LispObject::from(char_table.purpose).eq(intern("translation-table")) {
+            error!("Not a translation table");
+        }
+        translate(start, end, |c| {
+            let value = LispObject::from(unsafe { char_table_ref(table.to_raw(), c as c_int) });
+            match value.as_fixnum() {
+                Some(c) if c >= 0 => Some(c as Codepoint),
+                _ => None,
+            }
+        })
+    } else {
+        let string = table.as_string_or_error();
+        let chars: Vec<Codepoint> = if string.is_multibyte() {
+            string.chars().collect()
+        } else {
+            string
+                .as_slice()
+                .iter()
+                .map(|&b| {
+                    if b < 0x80 {
+                        Codepoint::from(b)
+                    } else {
+                        raw_byte_codepoint(b)
+                    }
+                })
+                .collect()
+        };
+        translate(start, end, |c| chars.get(c as usize).cloned())
+    };
+    LispObject::from_natnum(count)
+}
+
+/// Return STRING with its characters in reverse order.
+/// Zero-width characters, such as combining accents, stay after the
+/// character they modify.  Text properties are not copied.
+#[lisp_fn]
+pub fn string_reverse(string: LispObject) -> LispObject {
+    let s = string.as_string_or_error();
+    let multibyte = s.is_multibyte();
+    let chars: Vec<Codepoint> = s.chars().collect();
+    let widths = symbol_value(intern("char-width-table"));
+    let zero_width = |c: Codepoint| {
+        let width = unsafe { char_table_ref(widths.to_raw(), c as c_int) };
+        LispObject::from(width).eq(LispObject::from_fixnum(0))
+    };
+    let reversed = reverse_clusters(&chars, |c| {
+        multibyte && widths.as_char_table().is_some() && zero_width(c)
+    });
+
+    let mut bytes = Vec::with_capacity(s.len_bytes() as usize);
+    let mut buf = [0; MAX_MULTIBYTE_LENGTH];
+    for c in reversed {
+        let len = if multibyte {
+            char_string(c, buf.as_mut_ptr()) as usize
+        } else {
+            buf[0] = c as u8;
+            1
+        };
+        bytes.extend_from_slice(&buf[..len]);
+    }
+    LispObject::from(unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            chars.len() as ptrdiff_t,
+            bytes.len() as ptrdiff_t,
+            multibyte,
+        )
+    })
+}
+
+#[test]
+fn test_transformations() {
+    let rot: String = "Hello, World!"
+        .chars()
+        .map(|c| ::std::char::from_u32(rot13(c as Codepoint)).unwrap())
+        .collect();
+    assert_eq!(rot, "Uryyb, Jbeyq!");
+
+    let chars: Vec<Codepoint> = "ae\u{301}b".chars().map(|c| c as Codepoint).collect();
+    let reversed: String = reverse_clusters(&chars, |c| c == 0x301)
+        .into_iter()
+        .map(|c| ::std::char::from_u32(c).unwrap())
+        .collect();
+    assert_eq!(reversed, "be\u{301}a");
+}
+
+include!(concat!(env!("OUT_DIR"), "/transform_exports.rs"));
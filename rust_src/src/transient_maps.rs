@@ -0,0 +1,244 @@
+//! Transient keymaps.
+//!
+//! `set-transient-map-native' does what `set-transient-map' does, but
+//! the maps it activates are kept track of here rather than by functions
+//! on `pre-command-hook'.  The command loop calls
+//! `transient_maps_pre_command' before running each command, whatever
+//! has become of the hook, and each activation pushes a keymap of its
+//! own onto `overriding-terminal-local-map', so that deactivating it
+//! can never take away a map that another activation put there.  A map
+//! can also be deactivated after Emacs has been idle for some time.
+
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{call0, internal_condition_case_1, mark_object, EmacsInt, Lisp_Object, Qerror,
+                 Qnil};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use lists::{car_safe, cdr_safe, memq};
+use strings::lisp_string;
+use symbols::symbol_value;
+
+/// An active transient map.
+#[derive(Clone, Copy)]
+struct Transient {
+    id: EmacsInt,
+    /// The keymap pushed onto `overriding-terminal-local-map': a keymap
+    /// made for this activation, composed of MAP alone.
+    wrapper: LispObject,
+    map: LispObject,
+    keep_pred: LispObject,
+    on_exit: LispObject,
+    /// The idle timer that deactivates the map, or nil.
+    timer: LispObject,
+}
+
+struct Transients {
+    last_id: EmacsInt,
+    /// The active maps, the most recently activated last.
+    active: Vec<Transient>,
+}
+
+impl Transients {
+    fn find(&self, id: EmacsInt) -> Option<Transient> {
+        self.active.iter().find(|t| t.id == id).cloned()
+    }
+
+    fn remove(&mut self, id: EmacsInt) -> Option<Transient> {
+        let index = self.active.iter().position(|t| t.id == id)?;
+        Some(self.active.remove(index))
+    }
+}
+
+lazy_static! {
+    static ref TRANSIENTS: Mutex<Transients> = Mutex::new(Transients {
+        last_id: 0,
+        active: Vec::new(),
+    });
+}
+
+/// Mark the objects of the active transient maps.
+/// Called by the garbage collector.
+#[no_mangle]
+pub extern "C" fn mark_transient_maps() {
+    for transient in &TRANSIENTS.lock().unwrap().active {
+        for &object in &[
+            transient.wrapper,
+            transient.map,
+            transient.keep_pred,
+            transient.on_exit,
+            transient.timer,
+        ] {
+            unsafe { mark_object(object.to_raw()) };
+        }
+    }
+}
+
+/// Report ERR, an error signaled by a function of a transient map, in
+/// the echo area.
+unsafe extern "C" fn report_error(err: Lisp_Object) -> Lisp_Object {
+    call!(
+        intern("message"),
+        lisp_string("set-transient-map-native: %S"),
+        LispObject::from(err)
+    );
+    Qnil
+}
+
+/// Call FUNCTION with no arguments and return its value, or nil if it
+/// signals an error, which is reported in the echo area.
+fn call_demoted(function: LispObject) -> LispObject {
+    LispObject::from(unsafe {
+        internal_condition_case_1(call0, function.to_raw(), Qerror, report_error)
+    })
+}
+
+/// Deactivate the transient map ID, if it is still active.  If DEMOTE,
+/// errors in its ON-EXIT function are only reported.  Return whether
+/// it was active.
+fn exit(id: EmacsInt, demote: bool) -> bool {
+    // Take it out first, so that it is deactivated only once even if
+    // the rest signals an error or exits it again.
+    let transient = match TRANSIENTS.lock().unwrap().remove(id) {
+        Some(transient) => transient,
+        None => return false,
+    };
+    if transient.timer.is_not_nil() {
+        call!(intern("cancel-timer"), transient.timer);
+    }
+    call!(
+        intern("internal-pop-keymap"),
+        transient.wrapper,
+        intern("overriding-terminal-local-map")
+    );
+    if transient.on_exit.is_not_nil() {
+        if demote {
+            call_demoted(transient.on_exit);
+        } else {
+            call!(transient.on_exit);
+        }
+    }
+    true
+}
+
+/// Return whether the transient map TRANSIENT should stay active for
+/// the command about to run.  OVERRIDING is the value of
+/// `overriding-terminal-local-map'.
+fn keep(transient: Transient, overriding: LispObject) -> bool {
+    if memq(transient.wrapper, cdr_safe(overriding)).is_nil() {
+        // Something took it away.
+        false
+    } else if transient.keep_pred.is_nil() {
+        false
+    } else if !car_safe(cdr_safe(overriding)).eq(transient.wrapper) {
+        // Another transient map activated later is in effect.  This
+        // one waits for it to be deactivated, so that a prefix argument
+        // typed in the middle of, say, an isearch doesn't end it.
+        true
+    } else if transient.keep_pred.eq(LispObject::constant_t()) {
+        let keys = call!(intern("this-command-keys-vector"));
+        let command = call!(intern("lookup-key"), transient.map, keys);
+        // An unbound key has no command, and neither does `this-command'.
+        command.is_not_nil() && command.eq(symbol_value(intern("this-command")))
+    } else {
+        call_demoted(transient.keep_pred).is_not_nil()
+    }
+}
+
+/// Deactivate the transient maps that the command about to run doesn't
+/// keep.  Called by the command loop before `pre-command-hook'.
+#[no_mangle]
+pub extern "C" fn transient_maps_pre_command() {
+    let ids: Vec<EmacsInt> = TRANSIENTS
+        .lock()
+        .unwrap()
+        .active
+        .iter()
+        .rev()
+        .map(|t| t.id)
+        .collect();
+    for id in ids {
+        // Exiting a map runs Lisp, which may have exited others.
+        let transient = match TRANSIENTS.lock().unwrap().find(id) {
+            Some(transient) => transient,
+            None => continue,
+        };
+        let overriding = symbol_value(intern("overriding-terminal-local-map"));
+        if !keep(transient, overriding) {
+            exit(id, true);
+        }
+    }
+}
+
+/// Set MAP as a temporary keymap taking precedence over other keymaps.
+/// This is like `set-transient-map', with the same meaning of KEEP-PRED
+/// and ON-EXIT, and likewise returns a function that deactivates MAP
+/// when called with no arguments.  But MAP is deactivated whether or not
+/// `pre-command-hook' is run, ON-EXIT is called once only, and
+/// activating the same MAP more than once, or activating other maps,
+/// doesn't make deactivating it take the others away.
+///
+/// If TIMEOUT is non-nil, it is a number of seconds: MAP is deactivated
+/// once Emacs has been idle that long, regardless of KEEP-PRED.
+#[lisp_fn(min = "1")]
+pub fn set_transient_map_native(
+    map: LispObject,
+    keep_pred: LispObject,
+    on_exit: LispObject,
+    timeout: LispObject,
+) -> LispObject {
+    if call!(intern("keymapp"), map).is_nil() {
+        wrong_type!(intern("keymapp"), map);
+    }
+    if timeout.is_not_nil() {
+        timeout.any_to_float_or_error();
+    }
+    let wrapper = call!(intern("make-composed-keymap"), map);
+    let id = {
+        let mut transients = TRANSIENTS.lock().unwrap();
+        transients.last_id += 1;
+        transients.last_id
+    };
+    let timer = if timeout.is_nil() {
+        LispObject::constant_nil()
+    } else {
+        call!(
+            intern("run-with-idle-timer"),
+            timeout,
+            LispObject::constant_nil(),
+            intern("transient-map-native-exit"),
+            LispObject::from_fixnum(id)
+        )
+    };
+    TRANSIENTS.lock().unwrap().active.push(Transient {
+        id,
+        wrapper,
+        map,
+        keep_pred,
+        on_exit,
+        timer,
+    });
+    call!(
+        intern("internal-push-keymap"),
+        wrapper,
+        intern("overriding-terminal-local-map")
+    );
+    call!(
+        intern("apply-partially"),
+        intern("transient-map-native-exit"),
+        LispObject::from_fixnum(id)
+    )
+}
+
+/// Deactivate the transient map that `set-transient-map-native'
+/// activated with ID, and call its ON-EXIT function.  Return t if it was
+/// still active, nil otherwise.  This is called by the function that
+/// `set-transient-map-native' returns.
+#[lisp_fn]
+pub fn transient_map_native_exit(id: LispObject) -> LispObject {
+    LispObject::from_bool(exit(id.as_fixnum_or_error(), false))
+}
+
+include!(concat!(env!("OUT_DIR"), "/transient_maps_exports.rs"));
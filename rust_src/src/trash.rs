@@ -0,0 +1,459 @@
+//! Moving files to the trash.
+//!
+//! On GNU/Linux and the BSDs this follows the freedesktop.org trash
+//! specification: a file is moved into the `files' directory of a trash
+//! can, and a `.trashinfo' file recording its original name and the
+//! time of deletion is written to the `info' directory.  Files on the
+//! file system of the home directory go to the home trash can, and
+//! files on other mounted file systems go to the trash can at the top
+//! of that file system, so that trashing them does not copy them.  If
+//! there is no usable trash can there, they are copied to the home
+//! trash can instead.  On macOS files are moved to ~/.Trash, and on
+//! MS-Windows to the recycle bin.
+
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use libc;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{clear_unwind_protect, encode_file_name, make_unibyte_string,
+                 record_unwind_protect, unbind_to, Lisp_Object, Qdelete_by_moving_to_trash, Qnil};
+#[cfg(not(unix))]
+use remacs_sys::w32_move_file_to_trash;
+
+use eval_call::{specbind, specpdl_index};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use util::{expand_file_name_to_path, file_name_from_bytes, path_from_bytes, report_io_error};
+
+/// Characters left alone when the original name of a file is written
+/// to its `.trashinfo' file; everything else is percent-encoded.
+fn is_unreserved(b: u8) -> bool {
+    match b {
+        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' => true,
+        b'/' | b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => true,
+        _ => false,
+    }
+}
+
+fn percent_encode(name: &[u8]) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for &b in name {
+        if is_unreserved(b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02x}", b));
+        }
+    }
+    encoded
+}
+
+fn trash_info(name: &[u8], deletion_date: &str) -> String {
+    format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(name),
+        deletion_date
+    )
+}
+
+/// Return the name NAME with the number N inserted before its
+/// extension, like `foo.2.txt', to make it unique.
+fn numbered_name(name: &[u8], n: u32) -> Vec<u8> {
+    if n < 2 {
+        return name.to_vec();
+    }
+    let split = match name.iter().rposition(|&b| b == b'.') {
+        Some(0) | None => name.len(),
+        Some(dot) => dot,
+    };
+    let mut numbered = name[..split].to_vec();
+    numbered.extend_from_slice(format!(".{}", n).as_bytes());
+    numbered.extend_from_slice(&name[split..]);
+    numbered
+}
+
+/// Return the current local time in the format of `DeletionDate'.
+fn deletion_date() -> String {
+    let date = call!(intern("format-time-string"), lisp_string("%Y-%m-%dT%T"));
+    String::from_utf8_lossy(date.as_string_or_error().as_slice()).into_owned()
+}
+
+#[cfg(unix)]
+fn os_str_bytes(name: OsString) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    name.into_vec()
+}
+
+#[cfg(not(unix))]
+fn os_str_bytes(name: OsString) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn make_private_directory(directory: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(directory)
+}
+
+#[cfg(not(unix))]
+fn make_private_directory(directory: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).create(directory)
+}
+
+/// Copy FROM to TO, recursing into directories and copying symbolic
+/// links as links.
+fn copy_recursively(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        symlink(&fs::read_link(from)?, to)
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::from_raw_os_error(libc::ENOSYS))
+}
+
+/// Move FROM to TO, copying it and removing the original if they are on
+/// different file systems.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(ref err) if err.raw_os_error() == Some(libc::EXDEV) => (),
+        result => return result,
+    }
+    if let Err(err) = copy_recursively(from, to) {
+        let _ = remove_recursively(to);
+        return Err(err);
+    }
+    remove_recursively(from)
+}
+
+fn remove_recursively(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// A trash can following the freedesktop.org specification.
+struct TrashCan {
+    directory: PathBuf,
+    /// The top of the file system the trash can is on, if the names of
+    /// trashed files are recorded relative to it.
+    top: Option<PathBuf>,
+}
+
+impl TrashCan {
+    /// The home trash can, in $XDG_DATA_HOME/Trash.
+    fn home() -> TrashCan {
+        let data_home = call!(intern("getenv"), lisp_string("XDG_DATA_HOME"));
+        let data_home = if data_home.is_nil() {
+            expand_file_name_to_path(lisp_string("~/.local/share"))
+        } else {
+            expand_file_name_to_path(data_home)
+        };
+        TrashCan {
+            directory: data_home.join("Trash"),
+            top: None,
+        }
+    }
+
+    /// Return the trash can at the top of the file system mounted at TOP:
+    /// TOP/.Trash/UID if TOP/.Trash is a sticky directory, and
+    /// TOP/.Trash-UID otherwise.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn on_volume(top: &Path) -> Option<TrashCan> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let uid = unsafe { libc::getuid() };
+        let usable = |directory: &Path| match fs::symlink_metadata(directory) {
+            Ok(metadata) => metadata.is_dir() && metadata.uid() == uid,
+            Err(_) => make_private_directory(directory).is_ok(),
+        };
+
+        let shared = top.join(".Trash");
+        if let Ok(metadata) = fs::symlink_metadata(&shared) {
+            if metadata.is_dir() && metadata.permissions().mode() & 0o1000 != 0 {
+                let directory = shared.join(uid.to_string());
+                if usable(&directory) {
+                    return Some(TrashCan {
+                        directory,
+                        top: Some(top.to_path_buf()),
+                    });
+                }
+            }
+        }
+        let directory = top.join(format!(".Trash-{}", uid));
+        if usable(&directory) {
+            Some(TrashCan {
+                directory,
+                top: Some(top.to_path_buf()),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Reserve a name for the file NAME in this trash can by creating
+    /// its `.trashinfo' file with contents INFO.  Return the paths of the
+    /// info file and of the place to move the file to.
+    fn reserve(&self, name: &[u8], info: &str) -> io::Result<(PathBuf, PathBuf)> {
+        let files = self.directory.join("files");
+        let infos = self.directory.join("info");
+        make_private_directory(&files)?;
+        make_private_directory(&infos)?;
+        let mut n = 1;
+        loop {
+            let name = numbered_name(name, n);
+            let mut info_name = name.clone();
+            info_name.extend_from_slice(b".trashinfo");
+            let info_path = infos.join(path_from_bytes(&info_name));
+            let destination = files.join(path_from_bytes(&name));
+            n += 1;
+            if fs::symlink_metadata(&destination).is_ok() {
+                continue;
+            }
+            match OpenOptions::new().write(true).create_new(true).open(&info_path) {
+                Ok(mut file) => {
+                    if let Err(err) = file.write_all(info.as_bytes()) {
+                        let _ = fs::remove_file(&info_path);
+                        return Err(err);
+                    }
+                    return Ok((info_path, destination));
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Return the name to record for the file PATH.
+    fn recorded_name(&self, path: &Path) -> Vec<u8> {
+        let relative = match self.top {
+            Some(ref top) => path.strip_prefix(top).unwrap_or(path),
+            None => path,
+        };
+        os_str_bytes(relative.as_os_str().to_os_string())
+    }
+
+    /// Move the file PATH into this trash can.
+    fn trash(&self, path: &Path) -> io::Result<()> {
+        let name = match path.file_name() {
+            Some(name) => os_str_bytes(name.to_os_string()),
+            None => return Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        };
+        let info = trash_info(&self.recorded_name(path), &deletion_date());
+        let (info_path, destination) = self.reserve(&name, &info)?;
+        move_file(path, &destination).map_err(|err| {
+            let _ = fs::remove_file(&info_path);
+            err
+        })
+    }
+}
+
+/// Return the device of PATH, or of its nearest existing ancestor.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    let mut ancestor = Some(path);
+    while let Some(path) = ancestor {
+        if let Ok(metadata) = fs::metadata(path) {
+            return Some(metadata.dev());
+        }
+        ancestor = path.parent();
+    }
+    None
+}
+
+/// Return the top of the file system that DIRECTORY is on.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn mount_point(directory: &Path) -> Option<PathBuf> {
+    let dev = match device(directory) {
+        Some(dev) => dev,
+        None => return None,
+    };
+    let mut top = directory;
+    while let Some(parent) = top.parent() {
+        if device(parent) != Some(dev) {
+            break;
+        }
+        top = parent;
+    }
+    Some(top.to_path_buf())
+}
+
+fn check_not_parent_of_trash(path: &Path, trash: &Path) {
+    if trash.starts_with(path) {
+        error!(
+            "The trash directory {} is a subdirectory of {}",
+            trash.display(),
+            path.display()
+        );
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn trash_local_file(file: LispObject, path: &Path) {
+    let home = TrashCan::home();
+    check_not_parent_of_trash(path, &home.directory);
+    let parent = path.parent().unwrap_or(path);
+    let volume = if device(parent) != device(&home.directory) {
+        mount_point(parent).and_then(|top| TrashCan::on_volume(&top))
+    } else {
+        None
+    };
+    let result = match volume {
+        Some(ref volume) => volume.trash(path).or_else(|_| home.trash(path)),
+        None => home.trash(path),
+    };
+    if let Err(err) = result {
+        report_io_error(b"Moving file to trash\0", file, &err);
+    }
+}
+
+/// Finder keeps no record of where trashed files came from that other
+/// programs can write, so just move the file to ~/.Trash under a name
+/// not already taken there.
+#[cfg(target_os = "macos")]
+fn trash_local_file(file: LispObject, path: &Path) {
+    let trash = expand_file_name_to_path(lisp_string("~/.Trash"));
+    check_not_parent_of_trash(path, &trash);
+    let name = match path.file_name() {
+        Some(name) => os_str_bytes(name.to_os_string()),
+        None => report_io_error(
+            b"Moving file to trash\0",
+            file,
+            &io::Error::from_raw_os_error(libc::EINVAL),
+        ),
+    };
+    let result = make_private_directory(&trash).and_then(|_| {
+        let mut n = 1;
+        loop {
+            let destination = trash.join(path_from_bytes(&numbered_name(&name, n)));
+            if fs::symlink_metadata(&destination).is_err() {
+                return move_file(path, &destination);
+            }
+            n += 1;
+        }
+    });
+    if let Err(err) = result {
+        report_io_error(b"Moving file to trash\0", file, &err);
+    }
+}
+
+/// Move the remote file FILE, whose name is handled by a file name
+/// handler, into the home trash can.
+fn trash_remote_file(file: LispObject) {
+    let home = TrashCan::home();
+    let name = call!(intern("file-name-nondirectory"), file);
+    let name = LispObject::from(unsafe { encode_file_name(name.to_raw()) });
+    let encoded = call!(intern("encode-coding-string"), file, intern("utf-8"));
+    let info = trash_info(encoded.as_string_or_error().as_slice(), &deletion_date());
+    let reserved = home.reserve(name.as_string_or_error().as_slice(), &info);
+    let (info_path, destination) = match reserved {
+        Ok(paths) => paths,
+        Err(err) => report_io_error(b"Moving file to trash\0", file, &err),
+    };
+    let destination = file_name_from_bytes(&os_str_bytes(destination.into_os_string()));
+
+    let count = specpdl_index();
+    let info_path = os_str_bytes(info_path.into_os_string());
+    unsafe {
+        record_unwind_protect(
+            remove_trash_info,
+            make_unibyte_string(
+                info_path.as_ptr() as *const libc::c_char,
+                info_path.len() as libc::ptrdiff_t,
+            ),
+        )
+    };
+    // Renaming across hosts deletes the original, which must not
+    // trash it again.
+    specbind(Qdelete_by_moving_to_trash, Qnil);
+    call!(intern("rename-file"), file, destination);
+    unsafe {
+        clear_unwind_protect(count);
+        unbind_to(count, Qnil)
+    };
+}
+
+/// Remove the `.trashinfo' file named by the unibyte string INFO, after
+/// the file it describes could not be moved into the trash.
+unsafe extern "C" fn remove_trash_info(info: Lisp_Object) {
+    let info = LispObject::from(info);
+    let _ = fs::remove_file(path_from_bytes(info.as_string_or_error().as_slice()));
+}
+
+/// Move file or directory named FILENAME to the trash.
+/// On GNU/Linux and the BSDs, use the freedesktop.org trash can of the
+/// file system FILENAME is on, or else the home trash can in
+/// $XDG_DATA_HOME/Trash, and record where FILENAME came from so that
+/// desktop file managers can restore it.  On macOS, move FILENAME to
+/// ~/.Trash, and on MS-Windows to the recycle bin.
+#[lisp_fn]
+pub fn system_move_file_to_trash(filename: LispObject) -> LispObject {
+    move_to_trash(filename);
+    LispObject::constant_nil()
+}
+
+#[cfg(unix)]
+fn move_to_trash(filename: LispObject) {
+    let expanded = call!(intern("expand-file-name"), filename);
+    let file = call!(intern("directory-file-name"), expanded);
+    let handler = call!(
+        intern("find-file-name-handler"),
+        file,
+        intern("rename-file")
+    );
+    if handler.is_not_nil() {
+        trash_remote_file(file);
+    } else {
+        trash_local_file(file, &expand_file_name_to_path(file));
+    }
+}
+
+#[cfg(not(unix))]
+fn move_to_trash(filename: LispObject) {
+    unsafe { w32_move_file_to_trash(filename.to_raw()) };
+}
+
+#[test]
+fn test_trash_info() {
+    assert_eq!(
+        trash_info(b"/home/u/a b%.txt", "2017-11-12T10:20:30"),
+        "[Trash Info]\nPath=/home/u/a%20b%25.txt\nDeletionDate=2017-11-12T10:20:30\n"
+    );
+    assert_eq!(percent_encode("\u{e9}".as_bytes()), "%c3%a9");
+}
+
+#[test]
+fn test_numbered_name() {
+    assert_eq!(numbered_name(b"foo.txt", 1), b"foo.txt".to_vec());
+    assert_eq!(numbered_name(b"foo.txt", 2), b"foo.2.txt".to_vec());
+    assert_eq!(numbered_name(b"foo", 3), b"foo.3".to_vec());
+    assert_eq!(numbered_name(b".emacs", 2), b".emacs.2".to_vec());
+}
+
+include!(concat!(env!("OUT_DIR"), "/trash_exports.rs"));
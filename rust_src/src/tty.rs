@@ -0,0 +1,704 @@
+//! Capabilities of text terminals.
+//!
+//! The terminfo entry of a terminal says what its type supports in
+//! general, but many terminals do more than their entry tells, or run
+//! under a TERM that belongs to another terminal.  So besides reading
+//! the compiled terminfo entry, `tty-query-capabilities' asks the
+//! terminal itself: the primary and secondary device attributes
+//! (DA1 and DA2), XTGETTCAP, the kitty keyboard and graphics
+//! protocols, and the size of its character cells.  The replies arrive
+//! as keyboard input; the query for DA1, which every terminal answers,
+//! is sent last, so that its reply marks the end of the others.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{find_symbol_value, EmacsInt};
+
+use floatfns::extract_float;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// Seconds to wait for the replies of the terminal by default.
+const DEFAULT_QUERY_TIMEOUT: f64 = 1.0;
+
+/// The capabilities asked for with XTGETTCAP.
+const TCAP_NAMES: [&str; 4] = ["TN", "Co", "RGB", "Tc"];
+
+/// The standard boolean capabilities reported, with their index.
+const BOOLEANS: [(&str, usize); 4] = [("am", 1), ("xenl", 4), ("km", 8), ("bce", 28)];
+
+/// The standard numeric capabilities reported, with their index.
+const NUMBERS: [(&str, usize); 4] = [("cols", 0), ("lines", 2), ("colors", 13), ("pairs", 14)];
+
+/// The value of a capability.
+#[derive(Clone, Debug, PartialEq)]
+enum Capability {
+    Flag,
+    Number(i32),
+    String(Vec<u8>),
+}
+
+/// A compiled terminfo entry.
+#[derive(Debug, Default, PartialEq)]
+struct Terminfo {
+    booleans: Vec<bool>,
+    /// Absent numbers are negative.
+    numbers: Vec<i32>,
+    /// The user-defined capabilities of the extended format, such as
+    /// `RGB' and `Tc'.
+    extended: Vec<(String, Capability)>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() - self.pos < n {
+            return None;
+        }
+        let data = self.data;
+        let bytes = &data[self.pos..self.pos + n];
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn short(&mut self) -> Option<i16> {
+        self.bytes(2).map(|b| (u16::from(b[0]) | u16::from(b[1]) << 8) as i16)
+    }
+
+    /// Read a number, two or four bytes long.
+    fn number(&mut self, wide: bool) -> Option<i32> {
+        if wide {
+            self.bytes(4).map(|b| {
+                (u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16
+                    | u32::from(b[3]) << 24) as i32
+            })
+        } else {
+            self.short().map(i32::from)
+        }
+    }
+
+    /// Read COUNT counts or offsets; negative values stand for none.
+    fn shorts(&mut self, count: usize) -> Option<Vec<i16>> {
+        (0..count).map(|_| self.short()).collect()
+    }
+
+    fn align(&mut self) {
+        self.pos += self.pos % 2;
+    }
+}
+
+fn count(n: i16) -> Option<usize> {
+    if n < 0 {
+        None
+    } else {
+        Some(n as usize)
+    }
+}
+
+/// Return the NUL-terminated string at OFFSET in TABLE.
+fn table_string(table: &[u8], offset: i16) -> Option<&[u8]> {
+    let start = count(offset)?;
+    if start >= table.len() {
+        return None;
+    }
+    let end = table[start..]
+        .iter()
+        .position(|&b| b == 0)
+        .map_or(table.len(), |i| start + i);
+    Some(&table[start..end])
+}
+
+/// Parse the compiled terminfo entry DATA, in the legacy format or the
+/// one with 32-bit numbers.
+fn parse_terminfo(data: &[u8]) -> Option<Terminfo> {
+    let mut reader = Reader { data, pos: 0 };
+    let wide = match reader.short()? {
+        0o432 => false,
+        0o1036 => true,
+        _ => return None,
+    };
+    let names_size = count(reader.short()?)?;
+    let boolean_count = count(reader.short()?)?;
+    let number_count = count(reader.short()?)?;
+    let string_count = count(reader.short()?)?;
+    let table_size = count(reader.short()?)?;
+
+    reader.bytes(names_size)?;
+    let booleans = reader.bytes(boolean_count)?.iter().map(|&b| b == 1).collect();
+    reader.align();
+    let numbers = (0..number_count)
+        .map(|_| reader.number(wide))
+        .collect::<Option<Vec<i32>>>()?;
+    reader.shorts(string_count)?;
+    reader.bytes(table_size)?;
+
+    let mut info = Terminfo {
+        booleans,
+        numbers,
+        extended: Vec::new(),
+    };
+    // The extended section is optional.
+    reader.align();
+    if let Some(extended) = parse_extended(&mut reader, wide) {
+        info.extended = extended;
+    }
+    Some(info)
+}
+
+fn parse_extended(reader: &mut Reader, wide: bool) -> Option<Vec<(String, Capability)>> {
+    let boolean_count = count(reader.short()?)?;
+    let number_count = count(reader.short()?)?;
+    let string_count = count(reader.short()?)?;
+    let _items = reader.short()?;
+    let table_size = count(reader.short()?)?;
+
+    let booleans = reader.bytes(boolean_count)?;
+    reader.align();
+    let numbers = (0..number_count)
+        .map(|_| reader.number(wide))
+        .collect::<Option<Vec<i32>>>()?;
+    let values = reader.shorts(string_count)?;
+    let names = reader.shorts(boolean_count + number_count + string_count)?;
+    let table = reader.bytes(table_size)?;
+
+    // The names follow the values in the string table.
+    let names_start = values
+        .iter()
+        .filter_map(|&offset| table_string(table, offset).map(|s| offset as usize + s.len() + 1))
+        .max()
+        .unwrap_or(0);
+    let name_table = &table[names_start.min(table.len())..];
+
+    let mut capabilities = Vec::new();
+    for (i, &offset) in names.iter().enumerate() {
+        let name = match table_string(name_table, offset) {
+            Some(name) => String::from_utf8_lossy(name).into_owned(),
+            None => continue,
+        };
+        let value = if i < boolean_count {
+            if booleans[i] != 1 {
+                continue;
+            }
+            Capability::Flag
+        } else if i < boolean_count + number_count {
+            let n = numbers[i - boolean_count];
+            if n < 0 {
+                continue;
+            }
+            Capability::Number(n)
+        } else {
+            match table_string(table, values[i - boolean_count - number_count]) {
+                Some(s) => Capability::String(s.to_vec()),
+                None => continue,
+            }
+        };
+        capabilities.push((name, value));
+    }
+    Some(capabilities)
+}
+
+/// Return the file of the compiled terminfo entry for NAME, searching
+/// the directories that ncurses searches.
+fn terminfo_file(name: &str) -> Option<PathBuf> {
+    let first = name.bytes().next()?;
+    if name.contains('/') {
+        return None;
+    }
+    let mut dirs = Vec::new();
+    if let Ok(dir) = env::var("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::home_dir() {
+        dirs.push(home.join(".terminfo"));
+    }
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        for dir in list.split(':') {
+            dirs.push(PathBuf::from(if dir.is_empty() {
+                "/usr/share/terminfo"
+            } else {
+                dir
+            }));
+        }
+    }
+    for dir in &[
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+        "/usr/share/lib/terminfo",
+    ] {
+        dirs.push(PathBuf::from(dir));
+    }
+    for dir in dirs {
+        // macOS names the subdirectories by the hex code of the letter.
+        for sub in &[(first as char).to_string(), format!("{:02x}", first)] {
+            let path = dir.join(sub).join(name);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+fn read_terminfo(name: &str) -> Option<Terminfo> {
+    let path = terminfo_file(name)?;
+    let mut data = Vec::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut data))
+        .ok()?;
+    parse_terminfo(&data)
+}
+
+/// What the terminal replied to the queries.
+#[derive(Debug, Default, PartialEq)]
+struct Replies {
+    da1: Option<Vec<u32>>,
+    da2: Option<Vec<u32>>,
+    kitty_keyboard: Option<u32>,
+    kitty_graphics: bool,
+    /// The (width, height) of a character cell in pixels.
+    cell_size: Option<(u32, u32)>,
+    /// The XTGETTCAP replies, with the value of known capabilities.
+    tcap: Vec<(String, Option<String>)>,
+}
+
+fn from_hex(hex: &[u8]) -> Option<String> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = hex.chunks(2)
+        .map(|pair| {
+            let digits = ::std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(digits, 16).ok()
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn to_hex(s: &str) -> String {
+    s.bytes().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn params(bytes: &[u8]) -> Vec<u32> {
+    String::from_utf8_lossy(bytes)
+        .split(';')
+        .filter_map(|p| p.parse().ok())
+        .collect()
+}
+
+/// Return the end of the string terminated by ST or BEL that starts
+/// at START in INPUT, and the position after the terminator.
+fn string_end(input: &[u8], start: usize) -> Option<(usize, usize)> {
+    let mut i = start;
+    while i < input.len() {
+        match input[i] {
+            0x07 => return Some((i, i + 1)),
+            0x1b if input.get(i + 1) == Some(&b'\\') => return Some((i, i + 2)),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Parse the replies in INPUT.  Return them, the input that is not part
+/// of a reply, and whether the reply to DA1, which ends them, was seen.
+fn parse_replies(input: &[u8]) -> (Replies, Vec<u8>, bool) {
+    let mut replies = Replies::default();
+    let mut rest = Vec::new();
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] != 0x1b {
+            rest.push(input[i]);
+            i += 1;
+            continue;
+        }
+        match input.get(i + 1) {
+            Some(&b'[') => {
+                let start = i + 2;
+                let end = input[start..]
+                    .iter()
+                    .position(|&b| b >= 0x40 && b <= 0x7e)
+                    .map(|n| start + n);
+                let end = match end {
+                    Some(end) => end,
+                    // An incomplete reply.
+                    None => break,
+                };
+                let body = &input[start..end];
+                match (body.first(), input[end]) {
+                    (Some(&b'?'), b'c') => replies.da1 = Some(params(&body[1..])),
+                    (Some(&b'>'), b'c') => replies.da2 = Some(params(&body[1..])),
+                    (Some(&b'?'), b'u') => {
+                        replies.kitty_keyboard = Some(params(&body[1..]).first().cloned().unwrap_or(0))
+                    }
+                    (Some(&b'6'), b't') => {
+                        let size = params(body);
+                        if size.len() == 3 && size[1] > 0 && size[2] > 0 {
+                            replies.cell_size = Some((size[2], size[1]));
+                        }
+                    }
+                    _ => rest.extend_from_slice(&input[i..end + 1]),
+                }
+                i = end + 1;
+            }
+            Some(&b'P') | Some(&b'_') => {
+                let (end, next) = match string_end(input, i + 2) {
+                    Some(end) => end,
+                    None => break,
+                };
+                let body = &input[i + 2..end];
+                if input[i + 1] == b'_' {
+                    if body.starts_with(b"G") && body.ends_with(b";OK") {
+                        replies.kitty_graphics = true;
+                    }
+                } else if body.starts_with(b"1+r") || body.starts_with(b"0+r") {
+                    let valid = body[0] == b'1';
+                    for item in body[3..].split(|&b| b == b';') {
+                        let mut parts = item.splitn(2, |&b| b == b'=');
+                        let name = parts.next().and_then(from_hex);
+                        let value = parts.next().and_then(from_hex);
+                        if let Some(name) = name {
+                            replies.tcap.push((name, if valid { value } else { None }));
+                        }
+                    }
+                }
+                i = next;
+            }
+            None => break,
+            _ => {
+                rest.push(input[i]);
+                i += 1;
+            }
+        }
+    }
+    let complete = replies.da1.is_some();
+    if !complete {
+        rest.extend_from_slice(&input[i..]);
+    }
+    (replies, rest, complete)
+}
+
+/// Return the queries to send, DA1 last.
+fn queries() -> String {
+    let tcap: Vec<String> = TCAP_NAMES.iter().map(|name| to_hex(name)).collect();
+    format!(
+        "\x1bP+q{}\x1b\\\x1b[>c\x1b[?u\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[16t\x1b[c",
+        tcap.join(";")
+    )
+}
+
+/// Send the queries to TERMINAL and read the replies until the reply to
+/// DA1 arrives or TIMEOUT passes.  Input that is not part of a reply is
+/// put back into `unread-command-events'.
+fn query_terminal(terminal: LispObject, timeout: Duration) -> Replies {
+    call!(
+        intern("send-string-to-terminal"),
+        lisp_string(&queries()),
+        terminal
+    );
+    let deadline = Instant::now() + timeout;
+    let mut input = Vec::new();
+    // Other events, in reverse order; a Lisp list, so that the
+    // garbage collector sees them.
+    let mut others = LispObject::constant_nil();
+    loop {
+        if parse_replies(&input).2 {
+            break;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        let left = deadline - now;
+        let seconds = left.as_secs() as f64 + f64::from(left.subsec_nanos()) / 1e9;
+        let event = call!(
+            intern("read-event"),
+            LispObject::constant_nil(),
+            LispObject::constant_nil(),
+            LispObject::from_float(seconds)
+        );
+        if event.is_nil() {
+            break;
+        }
+        match event.as_fixnum() {
+            Some(c) if c >= 0 && c < 0x100 => input.push(c as u8),
+            _ => others = LispObject::cons(event, others),
+        }
+    }
+
+    let (replies, rest, _) = parse_replies(&input);
+    let unread = rest.iter()
+        .rev()
+        .fold(call!(intern("nreverse"), others), |tail, &c| {
+            LispObject::cons(LispObject::from_natnum(EmacsInt::from(c)), tail)
+        });
+    if unread.is_not_nil() {
+        let pending = LispObject::from(unsafe {
+            find_symbol_value(intern("unread-command-events").to_raw())
+        });
+        call!(
+            intern("set"),
+            intern("unread-command-events"),
+            call!(intern("append"), pending, unread)
+        );
+    }
+    replies
+}
+
+fn capability_to_lisp(value: &Capability) -> LispObject {
+    match *value {
+        Capability::Flag => LispObject::constant_t(),
+        Capability::Number(n) => LispObject::from_fixnum(EmacsInt::from(n)),
+        Capability::String(ref s) => lisp_string(&String::from_utf8_lossy(s)),
+    }
+}
+
+fn terminfo_to_lisp(info: &Terminfo) -> LispObject {
+    let mut items = Vec::new();
+    for &(name, index) in &BOOLEANS {
+        if info.booleans.get(index) == Some(&true) {
+            items.push((name.to_string(), Capability::Flag));
+        }
+    }
+    for &(name, index) in &NUMBERS {
+        if let Some(&n) = info.numbers.get(index) {
+            if n >= 0 {
+                items.push((name.to_string(), Capability::Number(n)));
+            }
+        }
+    }
+    items.extend(info.extended.iter().cloned());
+    items
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, &(ref name, ref value)| {
+            LispObject::cons(
+                LispObject::cons(lisp_string(name), capability_to_lisp(value)),
+                tail,
+            )
+        })
+}
+
+fn numbers_to_lisp(numbers: &Option<Vec<u32>>) -> LispObject {
+    match *numbers {
+        Some(ref numbers) => numbers
+            .iter()
+            .rev()
+            .fold(LispObject::constant_nil(), |tail, &n| {
+                LispObject::cons(LispObject::from_natnum(EmacsInt::from(n)), tail)
+            }),
+        None => LispObject::constant_nil(),
+    }
+}
+
+/// Return the capabilities of the text terminal TERMINAL as a plist.
+/// TERMINAL may be a terminal or a frame, and defaults to the terminal
+/// of the selected frame; the value is nil if it is not a text
+/// terminal.  TERMINAL may also be a terminal type such as
+/// \"xterm-256color\", whose terminfo entry alone is then consulted.
+///
+/// The terminal is asked about itself, and its replies are awaited for
+/// at most TIMEOUT seconds, 1 by default.  The properties are:
+///
+/// :type            The terminal type.
+/// :terminfo        An alist of the capabilities in the terminfo entry,
+///                  by name: the flags am, xenl, km and bce, the numbers
+///                  cols, lines, colors and pairs, and all extended
+///                  capabilities, such as RGB or Tc.  Flags have the
+///                  value t.
+/// :colors          The number of colors in the terminfo entry.
+/// :truecolor       Non-nil if the terminal supports 24-bit colors,
+///                  according to terminfo, XTGETTCAP or COLORTERM.
+/// :da1, :da2       The parameters of the replies to the primary and
+///                  secondary device attributes queries, as lists of
+///                  integers, or nil without a reply.
+/// :sixel           Non-nil if DA1 announces sixel graphics.
+/// :xtgettcap       An alist of the replies to XTGETTCAP for TN, Co,
+///                  RGB and Tc; a capability the terminal does not know
+///                  has the value nil.
+/// :kitty-keyboard  The enabled flags of the kitty keyboard protocol, or
+///                  nil if the terminal does not support it.
+/// :kitty-graphics  Non-nil if the terminal supports the kitty graphics
+///                  protocol.
+/// :cell-size       The (WIDTH . HEIGHT) of a character cell in pixels,
+///                  or nil if the terminal doesn't say.
+#[lisp_fn(min = "0")]
+pub fn tty_query_capabilities(terminal: LispObject, timeout: LispObject) -> LispObject {
+    let seconds = if timeout.is_nil() {
+        DEFAULT_QUERY_TIMEOUT
+    } else {
+        extract_float(timeout.to_raw())
+    };
+    let (name, live) = match terminal.as_string() {
+        Some(name) => (String::from_utf8_lossy(name.as_slice()).into_owned(), false),
+        None => match call!(intern("tty-type"), terminal).as_string() {
+            Some(name) => (String::from_utf8_lossy(name.as_slice()).into_owned(), true),
+            None => return LispObject::constant_nil(),
+        },
+    };
+    let batch = LispObject::from(unsafe { find_symbol_value(intern("noninteractive").to_raw()) });
+    let replies = if live && batch.is_nil() && seconds > 0.0 {
+        query_terminal(terminal, Duration::from_millis((seconds * 1000.0) as u64))
+    } else {
+        Replies::default()
+    };
+    let info = read_terminfo(&name).unwrap_or_default();
+
+    let colors = info.numbers.get(13).cloned().unwrap_or(-1);
+    let colorterm = if live {
+        call!(intern("getenv"), lisp_string("COLORTERM"), terminal)
+    } else {
+        LispObject::constant_nil()
+    };
+    let truecolor = info.extended
+        .iter()
+        .any(|&(ref name, _)| name == "RGB" || name == "Tc")
+        || replies
+            .tcap
+            .iter()
+            .any(|&(ref name, ref value)| (name == "RGB" || name == "Tc") && value.is_some())
+        || colorterm.as_string().map_or(false, |s| {
+            let s = s.as_slice();
+            s == b"truecolor" || s == b"24bit"
+        });
+    let tcap = replies
+        .tcap
+        .iter()
+        .rev()
+        .fold(LispObject::constant_nil(), |tail, &(ref name, ref value)| {
+            let value = value
+                .as_ref()
+                .map_or_else(LispObject::constant_nil, |v| lisp_string(v));
+            LispObject::cons(LispObject::cons(lisp_string(name), value), tail)
+        });
+
+    list!(
+        intern(":type"),
+        lisp_string(&name),
+        intern(":terminfo"),
+        terminfo_to_lisp(&info),
+        intern(":colors"),
+        if colors >= 0 {
+            LispObject::from_fixnum(EmacsInt::from(colors))
+        } else {
+            LispObject::constant_nil()
+        },
+        intern(":truecolor"),
+        LispObject::from_bool(truecolor),
+        intern(":da1"),
+        numbers_to_lisp(&replies.da1),
+        intern(":da2"),
+        numbers_to_lisp(&replies.da2),
+        intern(":sixel"),
+        LispObject::from_bool(replies.da1.as_ref().map_or(false, |p| p.contains(&4))),
+        intern(":xtgettcap"),
+        tcap,
+        intern(":kitty-keyboard"),
+        replies.kitty_keyboard.map_or_else(LispObject::constant_nil, |flags| {
+            LispObject::from_natnum(EmacsInt::from(flags))
+        }),
+        intern(":kitty-graphics"),
+        LispObject::from_bool(replies.kitty_graphics),
+        intern(":cell-size"),
+        replies.cell_size.map_or_else(LispObject::constant_nil, |(width, height)| {
+            LispObject::cons(
+                LispObject::from_natnum(EmacsInt::from(width)),
+                LispObject::from_natnum(EmacsInt::from(height)),
+            )
+        })
+    )
+}
+
+#[cfg(test)]
+fn push_short(data: &mut Vec<u8>, n: i16) {
+    data.push(n as u8);
+    data.push((n >> 8) as u8);
+}
+
+#[test]
+fn test_parse_terminfo() {
+    let mut data = Vec::new();
+    for &n in &[0o432, 4, 29, 14, 1, 3] {
+        push_short(&mut data, n);
+    }
+    data.extend_from_slice(b"xt\0\0");
+    let mut booleans = vec![0u8; 29];
+    booleans[1] = 1;
+    booleans[28] = 1;
+    data.extend_from_slice(&booleans);
+    data.push(0); // Alignment.
+    for i in 0..14 {
+        push_short(&mut data, if i == 13 { 256 } else { -1 });
+    }
+    push_short(&mut data, 0);
+    data.extend_from_slice(b"ab\0");
+    data.push(0); // Alignment.
+    // Extended: the flag Tc, the number U8 and the string Ss.
+    for &n in &[1, 1, 1, 4, 13] {
+        push_short(&mut data, n);
+    }
+    data.push(1);
+    data.push(0); // Alignment.
+    push_short(&mut data, 1);
+    push_short(&mut data, 0);
+    for &n in &[0, 3, 6] {
+        push_short(&mut data, n);
+    }
+    data.extend_from_slice(b"\x1b[q\0Tc\0U8\0Ss\0");
+
+    let info = parse_terminfo(&data).unwrap();
+    assert_eq!(info.booleans.len(), 29);
+    assert!(info.booleans[1] && info.booleans[28] && !info.booleans[0]);
+    assert_eq!(info.numbers[13], 256);
+    assert_eq!(
+        info.extended,
+        vec![
+            ("Tc".to_string(), Capability::Flag),
+            ("U8".to_string(), Capability::Number(1)),
+            ("Ss".to_string(), Capability::String(b"\x1b[q".to_vec())),
+        ]
+    );
+    assert_eq!(parse_terminfo(b"not terminfo"), None);
+}
+
+#[test]
+fn test_parse_replies() {
+    let input = b"x\x1bP1+r524742=382F382F38;544E=787465726D\x1b\\\x1bP0+r5463\x1b\\\
+                  \x1b[>41;351;0c\x1b[?1u\x1b_Gi=31;OK\x1b\\\x1b[6;20;10ty\x1b[?64;1;4;22c";
+    let (replies, rest, complete) = parse_replies(input);
+    assert!(complete);
+    assert_eq!(rest, b"xy");
+    assert_eq!(replies.da1, Some(vec![64, 1, 4, 22]));
+    assert_eq!(replies.da2, Some(vec![41, 351, 0]));
+    assert_eq!(replies.kitty_keyboard, Some(1));
+    assert!(replies.kitty_graphics);
+    assert_eq!(replies.cell_size, Some((10, 20)));
+    assert_eq!(
+        replies.tcap,
+        vec![
+            ("RGB".to_string(), Some("8/8/8".to_string())),
+            ("TN".to_string(), Some("xterm".to_string())),
+            ("Tc".to_string(), None),
+        ]
+    );
+
+    let (replies, rest, complete) = parse_replies(b"\x1b[>1;2c\x1b[?6");
+    assert!(!complete);
+    assert_eq!(replies.da2, Some(vec![1, 2]));
+    assert_eq!(rest, b"\x1b[?6");
+    assert!(queries().ends_with("\x1b[c"));
+    assert!(queries().starts_with("\x1bP+q544E;436F;524742;5463\x1b\\"));
+}
+
+include!(concat!(env!("OUT_DIR"), "/tty_exports.rs"));
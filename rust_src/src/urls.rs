@@ -0,0 +1,243 @@
+//! Parsing and encoding URLs.
+//!
+//! `url-generic-parse-url' takes URLs apart with regexps, which goes
+//! wrong for IPv6 hosts, user names with `@' in them and other cases
+//! that the URL standard covers.  Here URLs are parsed with the url
+//! crate, international domain names are converted with the idna crate,
+//! and the result is the same `url' structure that url-parse.el makes.
+
+use libc::{c_char, ptrdiff_t};
+
+use idna;
+use url::{ParseError, Url};
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{make_unibyte_string, EmacsInt};
+
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+
+/// Return the UTF-8 text of STRING.
+pub fn utf8_bytes(string: LispObject) -> Vec<u8> {
+    let lisp = string.as_string_or_error();
+    if lisp.is_multibyte() {
+        let encoded = call!(intern("encode-coding-string"), string, intern("utf-8"));
+        encoded.as_string_or_error().as_slice().to_vec()
+    } else {
+        lisp.as_slice().to_vec()
+    }
+}
+
+fn utf8_string(string: LispObject) -> String {
+    String::from_utf8_lossy(&utf8_bytes(string)).into_owned()
+}
+
+/// Return a Lisp string of TEXT, or nil if TEXT is empty or None.
+fn lisp_string_or_nil(text: Option<&str>) -> LispObject {
+    match text {
+        Some(text) if !text.is_empty() => lisp_string(text),
+        _ => LispObject::constant_nil(),
+    }
+}
+
+/// Whether BYTE is left as it is in an encoded URL component: an ASCII
+/// letter or digit, one of `-._~', or a byte of ALLOWED.
+fn is_unreserved(byte: u8, allowed: &[u8]) -> bool {
+    match byte {
+        b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => true,
+        _ => allowed.contains(&byte),
+    }
+}
+
+/// Percent-encode the bytes of TEXT that are not unreserved.
+pub fn encode(text: &[u8], allowed: &[u8]) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for &byte in text {
+        if is_unreserved(byte, allowed) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'...b'9' => Some(byte - b'0'),
+        b'a'...b'f' => Some(byte - b'a' + 10),
+        b'A'...b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decode the %XX sequences of TEXT.  A `%' that is not followed by two
+/// hex digits stands for itself.
+pub fn decode(text: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] == b'%' {
+            let high = text.get(i + 1).cloned().and_then(hex_value);
+            let low = text.get(i + 2).cloned().and_then(hex_value);
+            if let (Some(high), Some(low)) = (high, low) {
+                decoded.push(high * 16 + low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(text[i]);
+        i += 1;
+    }
+    decoded
+}
+
+/// Split a URL without a scheme into its file name, with the query, and
+/// its target, as `url-generic-parse-url' does.
+fn split_relative(text: &str) -> (&str, Option<&str>) {
+    match text.find('#') {
+        Some(hash) => (&text[..hash], Some(&text[hash + 1..])),
+        None => (text, None),
+    }
+}
+
+/// Parse URL, a string, and return a `url' structure for it.
+/// This is like `url-generic-parse-url', but follows the URL standard:
+/// the scheme and host are lowercased, host names are converted to
+/// punycode, IPv6 addresses are kept in their brackets, and `.' and
+/// `..' segments of the path are resolved.  The file name holds the
+/// path and the query, and the port is nil when it is the default port
+/// of the scheme, for which `url-port' then returns the default.  A URL
+/// without a scheme only has a file name and a target.  Signal an
+/// error if URL is not valid.
+#[lisp_fn]
+pub fn url_parse_native(url: LispObject) -> LispObject {
+    let text = utf8_string(url);
+    call!(intern("require"), intern("url-parse"));
+    let make = intern("url-parse-make-urlobj");
+    let parsed = match Url::parse(&text) {
+        Ok(parsed) => parsed,
+        Err(ParseError::RelativeUrlWithoutBase) => {
+            let (filename, target) = split_relative(&text);
+            let nil = LispObject::constant_nil();
+            return call!(
+                make,
+                nil,
+                nil,
+                nil,
+                nil,
+                nil,
+                lisp_string(filename),
+                lisp_string_or_nil(target)
+            );
+        }
+        Err(err) => error!("Invalid URL: {}", err),
+    };
+    let mut filename = parsed.path().to_string();
+    if let Some(query) = parsed.query() {
+        filename.push('?');
+        filename.push_str(query);
+    }
+    let port = parsed
+        .port()
+        .map_or(LispObject::constant_nil(), |port| {
+            LispObject::from_natnum(EmacsInt::from(port))
+        });
+    call!(
+        make,
+        lisp_string(parsed.scheme()),
+        lisp_string_or_nil(Some(parsed.username())),
+        lisp_string_or_nil(parsed.password()),
+        lisp_string_or_nil(parsed.host_str()),
+        port,
+        lisp_string(&filename),
+        lisp_string_or_nil(parsed.fragment()),
+        LispObject::constant_nil(),
+        LispObject::from_bool(!parsed.cannot_be_a_base())
+    )
+}
+
+/// Percent-encode STRING for use in a URL.
+/// The UTF-8 bytes of STRING are encoded, except for ASCII letters and
+/// digits, `-', `.', `_' and `~', and the characters of the string
+/// ALLOWED-CHARS, if given.  Unlike `url-hexify-string', the hex digits
+/// are always uppercase.
+#[lisp_fn(min = "1")]
+pub fn url_encode_string(string: LispObject, allowed_chars: LispObject) -> LispObject {
+    let allowed = if allowed_chars.is_nil() {
+        Vec::new()
+    } else {
+        utf8_bytes(allowed_chars)
+    };
+    lisp_string(&encode(&utf8_bytes(string), &allowed))
+}
+
+/// Decode the %XX sequences of STRING, a percent-encoded URL component.
+/// If the result is valid UTF-8, it is decoded into a multibyte string;
+/// otherwise the unibyte string of the bytes is returned, as
+/// `url-unhex-string' does.
+#[lisp_fn]
+pub fn url_decode_string(string: LispObject) -> LispObject {
+    let decoded = decode(string.as_string_or_error().as_slice());
+    match String::from_utf8(decoded) {
+        Ok(text) => lisp_string(&text),
+        Err(err) => {
+            let bytes = err.into_bytes();
+            LispObject::from(unsafe {
+                make_unibyte_string(bytes.as_ptr() as *const c_char, bytes.len() as ptrdiff_t)
+            })
+        }
+    }
+}
+
+/// Convert DOMAIN, an international domain name, to ASCII.
+/// Labels with non-ASCII characters are encoded with punycode and get
+/// the "xn--" prefix, as in a URL.  Signal an error if DOMAIN is not a
+/// valid domain name.
+#[lisp_fn]
+pub fn url_domain_to_ascii(domain: LispObject) -> LispObject {
+    match idna::domain_to_ascii(&utf8_string(domain)) {
+        Ok(ascii) => lisp_string(&ascii),
+        Err(err) => error!("Invalid domain name: {:?}", err),
+    }
+}
+
+/// Convert DOMAIN, a domain name in ASCII, to Unicode.
+/// Labels with the "xn--" prefix are decoded from punycode; this undoes
+/// `url-domain-to-ascii'.  Signal an error if DOMAIN is not a valid
+/// domain name.
+#[lisp_fn]
+pub fn url_domain_to_unicode(domain: LispObject) -> LispObject {
+    let (unicode, result) = idna::domain_to_unicode(&utf8_string(domain));
+    if let Err(err) = result {
+        error!("Invalid domain name: {:?}", err);
+    }
+    lisp_string(&unicode)
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(encode(b"a-z_0.9~", b""), "a-z_0.9~");
+    assert_eq!(encode(b"a b/c?d", b""), "a%20b%2Fc%3Fd");
+    assert_eq!(encode(b"a b/c", b"/"), "a%20b/c");
+    assert_eq!(encode("é".as_bytes(), b""), "%C3%A9");
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(decode(b"a%20b%2fc"), b"a b/c".to_vec());
+    assert_eq!(decode(b"%C3%A9"), "é".as_bytes().to_vec());
+    assert_eq!(decode(b"100%"), b"100%".to_vec());
+    assert_eq!(decode(b"%zz%4"), b"%zz%4".to_vec());
+    assert_eq!(decode(b"+"), b"+".to_vec());
+}
+
+#[test]
+fn test_split_relative() {
+    assert_eq!(split_relative("a/b?c#d"), ("a/b?c", Some("d")));
+    assert_eq!(split_relative("a/b"), ("a/b", None));
+    assert_eq!(split_relative("#top"), ("", Some("top")));
+}
+
+include!(concat!(env!("OUT_DIR"), "/urls_exports.rs"));
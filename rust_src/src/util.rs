@@ -1,6 +1,15 @@
 //! Miscellaneous utility functions
 
-use remacs_sys::EmacsInt;
+use std::ffi::OsStr;
+use std::io;
+use std::path::PathBuf;
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_sys::{decode_file_name, encode_file_name, make_unibyte_string, report_file_errno,
+                 EmacsInt, Fexpand_file_name, Qnil};
+
+use lisp::LispObject;
 
 #[no_mangle]
 pub extern "C" fn clip_to_bounds(lower: isize, num: EmacsInt, upper: isize) -> isize {
@@ -13,3 +22,61 @@ pub extern "C" fn clip_to_bounds(lower: isize, num: EmacsInt, upper: isize) -> i
         upper
     }
 }
+
+/// Convert the bytes of an encoded file name to a path.
+#[cfg(unix)]
+pub fn path_from_bytes(name: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(OsStr::from_bytes(name))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(name: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Return the bytes of the file name NAME, the reverse of
+/// `path_from_bytes'.
+#[cfg(unix)]
+pub fn os_str_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    name.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn os_str_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Return the file name NAME, expanded and encoded, as a path.
+pub fn expand_file_name_to_path(name: LispObject) -> PathBuf {
+    name.as_string_or_error();
+    let expanded = LispObject::from(unsafe { Fexpand_file_name(name.to_raw(), Qnil) });
+    let encoded = LispObject::from(unsafe { encode_file_name(expanded.to_raw()) });
+    path_from_bytes(encoded.as_string_or_error().as_slice())
+}
+
+/// Return the bytes of an encoded file name as a Lisp file name.
+pub fn file_name_from_bytes(name: &[u8]) -> LispObject {
+    let encoded =
+        unsafe { make_unibyte_string(name.as_ptr() as *const c_char, name.len() as ptrdiff_t) };
+    LispObject::from(unsafe { decode_file_name(encoded) })
+}
+
+/// Signal an error for ERR, which happened while doing to FILE what
+/// MESSAGE, a NUL-terminated string, says.  System errors are signaled
+/// as `file-error' or one of its subtypes.
+pub fn report_io_error(message: &[u8], file: LispObject, err: &io::Error) -> ! {
+    match err.raw_os_error() {
+        Some(errno) => unsafe {
+            report_file_errno(message.as_ptr() as *const c_char, file.to_raw(), errno)
+        },
+        None => error!(
+            "{}: {}",
+            String::from_utf8_lossy(&message[..message.len() - 1]),
+            err
+        ),
+    }
+}
@@ -8,14 +8,14 @@ use std::slice;
 use libc::ptrdiff_t;
 
 use remacs_macros::lisp_fn;
-use remacs_sys::{EmacsInt, Faref, Lisp_Bool_Vector, Lisp_Vector, Lisp_Vectorlike, PseudovecType,
-                 Qsequencep, MOST_POSITIVE_FIXNUM, PSEUDOVECTOR_AREA_BITS, PSEUDOVECTOR_FLAG,
-                 PSEUDOVECTOR_SIZE_MASK, PVEC_TYPE_MASK};
+use remacs_sys::{EmacsInt, Faref, Lisp_Bool_Vector, Lisp_Sub_Char_Table, Lisp_Vector,
+                 Lisp_Vectorlike, PseudovecType, Qsequencep, MOST_POSITIVE_FIXNUM,
+                 PSEUDOVECTOR_AREA_BITS, PSEUDOVECTOR_FLAG, PSEUDOVECTOR_SIZE_MASK, PVEC_TYPE_MASK};
 
 use buffers::LispBufferRef;
 use chartable::LispCharTableRef;
 use frames::LispFrameRef;
-use lisp::{ExternalPtr, LispObject};
+use lisp::{ExternalPtr, LispObject, LispSubrRef};
 use lisp::defsubr;
 use lists::{car, inorder, nthcdr, sort_list};
 use multibyte::MAX_CHAR;
@@ -26,6 +26,7 @@ use windows::LispWindowRef;
 pub type LispVectorlikeRef = ExternalPtr<Lisp_Vectorlike>;
 pub type LispVectorRef = ExternalPtr<Lisp_Vector>;
 pub type LispBoolVecRef = ExternalPtr<Lisp_Bool_Vector>;
+pub type LispSubCharTableRef = ExternalPtr<Lisp_Sub_Char_Table>;
 
 impl LispVectorlikeRef {
     #[inline]
@@ -74,6 +75,15 @@ impl LispVectorlikeRef {
         }
     }
 
+    #[inline]
+    pub fn as_subr(&self) -> Option<LispSubrRef> {
+        if self.is_pseudovector(PseudovecType::PVEC_SUBR) {
+            Some(unsafe { mem::transmute(*self) })
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub fn as_buffer(&self) -> Option<LispBufferRef> {
         if self.is_pseudovector(PseudovecType::PVEC_BUFFER) {
@@ -127,6 +137,28 @@ impl LispVectorlikeRef {
             None
         }
     }
+
+    #[inline]
+    pub fn as_sub_char_table(&self) -> Option<LispSubCharTableRef> {
+        if self.is_pseudovector(PseudovecType::PVEC_SUB_CHAR_TABLE) {
+            Some(unsafe { mem::transmute(*self) })
+        } else {
+            None
+        }
+    }
+
+    /// The Lisp slots of a pseudovector, i.e. the words counted by
+    /// `pseudovector_size`, as AREF sees them.
+    #[inline]
+    pub fn pseudovector_slots(&self) -> &[LispObject] {
+        unsafe {
+            let vector = self.as_vector_unchecked();
+            slice::from_raw_parts(
+                mem::transmute::<_, *const LispObject>(&vector.contents),
+                self.pseudovector_size() as usize,
+            )
+        }
+    }
 }
 
 impl LispVectorRef {
@@ -171,6 +203,17 @@ impl LispBoolVecRef {
     pub fn len(&self) -> usize {
         self.size as usize
     }
+
+    /// The bits of the vector packed into bytes, least significant bit
+    /// first, as in the `#&N"..."' read syntax.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(
+                mem::transmute::<_, *const u8>(&self._data),
+                (self.len() + 7) / 8,
+            )
+        }
+    }
 }
 
 /// Return the length of vector, list or string SEQUENCE.
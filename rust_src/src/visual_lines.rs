@@ -0,0 +1,240 @@
+//! A cache of where the screen lines of long lines start.
+//!
+//! Finding the screen line that a position is on, when lines are
+//! continued or wrapped, means laying out its logical line from the
+//! start, which is slow for the long lines of prose or minified code.
+//! The starts of the screen lines of each logical line laid out here
+//! are remembered per buffer and window width, and forgotten when the
+//! change journal says the text of the line changed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{record_unwind_protect, save_excursion_restore, save_excursion_save,
+                 save_restriction_restore, save_restriction_save, unbind_to, EmacsInt,
+                 Lisp_Buffer, Qnil};
+
+use buffer_text::unchanged_before;
+use buffers::LispBufferRef;
+use eval_call::specpdl_index;
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use threads::ThreadState;
+use windows::window_or_selected_unchecked;
+
+/// The screen lines of a logical line.
+struct Line {
+    /// The position of the newline that ends the line, or of the end of
+    /// the buffer.
+    end: EmacsInt,
+    /// The start of each screen line, in order; the first is the start
+    /// of the logical line.
+    rows: Vec<EmacsInt>,
+}
+
+impl Line {
+    /// Return the start of the screen line that POSITION is on.
+    fn row_start(&self, position: EmacsInt) -> EmacsInt {
+        match self.rows.binary_search(&position) {
+            Ok(index) => self.rows[index],
+            Err(index) => self.rows[index.max(1) - 1],
+        }
+    }
+}
+
+/// The logical lines laid out in a buffer at a given width.
+struct Layout {
+    /// The character-change tick of the buffer they were laid out in.
+    tick: EmacsInt,
+    /// The lines, by the position of their start.
+    lines: BTreeMap<EmacsInt, Line>,
+}
+
+impl Layout {
+    fn new(tick: EmacsInt) -> Layout {
+        Layout {
+            tick,
+            lines: BTreeMap::new(),
+        }
+    }
+
+    /// Forget the lines whose text may have changed at or after
+    /// UNCHANGED.
+    fn truncate(&mut self, unchanged: EmacsInt) {
+        self.lines.split_off(&unchanged);
+        let last = self.lines
+            .iter()
+            .next_back()
+            .map(|(&start, line)| (start, line.end));
+        if let Some((start, end)) = last {
+            if end >= unchanged {
+                self.lines.remove(&start);
+            }
+        }
+    }
+
+    /// Return the line that contains POSITION, if it was laid out.
+    fn line_at(&self, position: EmacsInt) -> Option<&Line> {
+        self.lines
+            .range(..position + 1)
+            .next_back()
+            .map(|(_, line)| line)
+            .and_then(|line| if position <= line.end { Some(line) } else { None })
+    }
+}
+
+/// What the layout of a buffer depends on: the buffer, the body width
+/// of the window in pixels, and whether lines are wrapped at word
+/// boundaries or truncated.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct LayoutKey {
+    buffer: usize,
+    width: EmacsInt,
+    word_wrap: bool,
+    truncate: bool,
+}
+
+lazy_static! {
+    static ref LAYOUTS: Mutex<HashMap<LayoutKey, Layout>> = Mutex::new(HashMap::new());
+}
+
+/// Forget the layouts of BUFFER.  Called when BUFFER is killed or its
+/// text is swapped with another's.
+#[no_mangle]
+pub extern "C" fn forget_visual_line_starts(buffer: *const Lisp_Buffer) {
+    let buffer = buffer as usize;
+    let mut layouts = LAYOUTS.lock().unwrap();
+    let keys: Vec<LayoutKey> = layouts
+        .keys()
+        .filter(|key| key.buffer == buffer)
+        .cloned()
+        .collect();
+    for key in keys {
+        layouts.remove(&key);
+    }
+}
+
+/// Lay out the logical line of the current buffer that POSITION is on
+/// in WINDOW, and return its start and screen lines.  The line is laid
+/// out in the whole buffer, so that narrowing does not cut it.
+fn lay_out_line(window: LispObject, position: EmacsInt) -> (EmacsInt, Line) {
+    let count = specpdl_index();
+    unsafe {
+        record_unwind_protect(save_excursion_restore, save_excursion_save());
+        record_unwind_protect(save_restriction_restore, save_restriction_save());
+    }
+    call!(intern("widen"));
+    let point = || ThreadState::current_buffer().pt() as EmacsInt;
+    call!(intern("goto-char"), LispObject::from_fixnum(position));
+    call!(intern("skip-chars-forward"), lisp_string("^\n"));
+    let end = point();
+    call!(intern("goto-char"), LispObject::from_fixnum(position));
+    call!(intern("forward-line"), LispObject::from_fixnum(0));
+    let start = point();
+    let mut rows = vec![start];
+    loop {
+        call!(intern("vertical-motion"), LispObject::from_fixnum(1), window);
+        let row = point();
+        if row <= *rows.last().unwrap() || row > end {
+            break;
+        }
+        rows.push(row);
+    }
+    unsafe { unbind_to(count, Qnil) };
+    (start, Line { end, rows })
+}
+
+/// Return the start of the screen line that POS is on in WINDOW.
+/// POS is a position in the current buffer, and WINDOW defaults to the
+/// selected window; the buffer is laid out as WINDOW would show it, as
+/// with `vertical-motion'.  The screen lines of a logical line are found
+/// once and then remembered, for the body width of WINDOW and the
+/// values of `word-wrap' and `truncate-lines', until the text of the
+/// line changes, so that this is fast in long wrapped lines.  Changes
+/// of fonts or display properties are not noticed.
+#[lisp_fn(min = "1")]
+pub fn visual_line_start_cache_lookup(pos: LispObject, window: LispObject) -> LispObject {
+    let window = window_or_selected_unchecked(window);
+    let buffer: LispBufferRef = ThreadState::current_buffer();
+    let position = pos.as_fixnum_coerce_marker_or_error();
+    if position < buffer.begv as EmacsInt || position > buffer.zv() as EmacsInt {
+        args_out_of_range!(
+            pos,
+            LispObject::from_fixnum(buffer.begv as EmacsInt),
+            LispObject::from_fixnum(buffer.zv() as EmacsInt)
+        );
+    }
+    let key = LayoutKey {
+        buffer: buffer.as_ptr() as usize,
+        width: call!(intern("window-body-width"), window, LispObject::constant_t())
+            .as_fixnum_or_error(),
+        word_wrap: LispObject::from(buffer.word_wrap).is_not_nil(),
+        truncate: LispObject::from(buffer.truncate_lines).is_not_nil(),
+    };
+    let tick = buffer.char_modifications();
+    let known = {
+        let mut layouts = LAYOUTS.lock().unwrap();
+        let layout = layouts.entry(key).or_insert_with(|| Layout::new(tick));
+        if layout.tick != tick {
+            layout.truncate(unchanged_before(buffer, layout.tick) as EmacsInt);
+            layout.tick = tick;
+        }
+        layout.line_at(position).map(|line| line.row_start(position))
+    };
+    if let Some(start) = known {
+        return LispObject::from_fixnum(start);
+    }
+
+    let (start, line) = lay_out_line(window, position);
+    let row_start = line.row_start(position);
+    let mut layouts = LAYOUTS.lock().unwrap();
+    let layout = layouts.entry(key).or_insert_with(|| Layout::new(tick));
+    // Laying out runs Lisp, which may have changed the text meanwhile.
+    if layout.tick == tick {
+        layout.lines.insert(start, line);
+    }
+    LispObject::from_fixnum(row_start)
+}
+
+#[test]
+fn test_layout() {
+    let mut layout = Layout::new(0);
+    layout.lines.insert(
+        1,
+        Line {
+            end: 100,
+            rows: vec![1, 40, 80],
+        },
+    );
+    layout.lines.insert(
+        101,
+        Line {
+            end: 101,
+            rows: vec![101],
+        },
+    );
+    layout.lines.insert(
+        102,
+        Line {
+            end: 300,
+            rows: vec![102, 200],
+        },
+    );
+    assert_eq!(layout.line_at(1).map(|line| line.row_start(1)), Some(1));
+    assert_eq!(layout.line_at(39).map(|line| line.row_start(39)), Some(1));
+    assert_eq!(layout.line_at(40).map(|line| line.row_start(40)), Some(40));
+    assert_eq!(layout.line_at(100).map(|line| line.row_start(100)), Some(80));
+    assert_eq!(layout.line_at(101).map(|line| line.row_start(101)), Some(101));
+    assert_eq!(layout.line_at(250).map(|line| line.row_start(250)), Some(200));
+    assert!(layout.line_at(301).is_none());
+    // A change at 250 loses the line it is in, and those after it.
+    layout.truncate(250);
+    assert_eq!(layout.lines.keys().cloned().collect::<Vec<_>>(), vec![1, 101]);
+    // So does a change at the newline that ends a line.
+    layout.truncate(101);
+    assert_eq!(layout.lines.keys().cloned().collect::<Vec<_>>(), vec![1]);
+}
+
+include!(concat!(env!("OUT_DIR"), "/visual_lines_exports.rs"));
@@ -0,0 +1,836 @@
+//! A VT100/xterm terminal emulator core.
+//!
+//! A terminal is a grid of cells driven by a state machine that
+//! understands the control sequences commonly emitted by programs
+//! running under term-mode: cursor movement, erasing, inserting and
+//! deleting lines and characters, scrolling regions, SGR attributes and
+//! the alternate screen.  Lines scrolled off the top of the main screen
+//! are kept until the next render, which inserts them above the screen
+//! in the current buffer.
+//!
+//! Terminals live on the Rust side and are referred to from Lisp by an
+//! integer handle.  Lisp errors must not be signaled while the terminal
+//! table is locked, so all conversions from and to Lisp objects happen
+//! outside of `with_terminal`.
+
+use std::char;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Mutex;
+
+use remacs_macros::lisp_fn;
+use remacs_sys::{del_range, insert_from_string, set_point, EmacsInt};
+
+use ansi_color::{parse_params, GraphicState};
+use lisp::{intern, LispObject};
+use lisp::defsubr;
+use strings::lisp_string;
+use textprop::put_text_property;
+use threads::ThreadState;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    ch: char,
+    attrs: GraphicState,
+}
+
+impl Cell {
+    fn blank(attrs: GraphicState) -> Cell {
+        // Erased cells keep the background color, as in xterm.
+        Cell {
+            ch: ' ',
+            attrs: GraphicState {
+                background: attrs.background,
+                ..GraphicState::default()
+            },
+        }
+    }
+
+    fn is_default_blank(&self) -> bool {
+        self.ch == ' ' && self.attrs == GraphicState::default()
+    }
+}
+
+type Row = Vec<Cell>;
+
+#[derive(Clone, Copy)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    attrs: GraphicState,
+}
+
+enum ParserState {
+    Ground,
+    Escape,
+    /// ESC followed by a charset designator; the next character is
+    /// consumed and ignored.
+    Charset,
+    Csi(Vec<u8>),
+    /// Operating system command; ESC was seen if the flag is set.
+    Osc(bool),
+}
+
+struct Terminal {
+    rows: usize,
+    cols: usize,
+    grid: Vec<Row>,
+    /// The main screen and its cursor while the alternate screen is shown.
+    main_screen: Option<(Vec<Row>, SavedCursor)>,
+    row: usize,
+    col: usize,
+    /// The cursor is past the last column; the next character wraps.
+    pending_wrap: bool,
+    attrs: GraphicState,
+    saved_cursor: Option<SavedCursor>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    autowrap: bool,
+    insert_mode: bool,
+    cursor_visible: bool,
+    state: ParserState,
+    /// Lines scrolled off the top since the last render.
+    scrolled_out: Vec<Row>,
+    /// Replies to queries, to be sent back to the process.
+    responses: String,
+    /// Number of characters the screen took up in the buffer at the
+    /// last render.
+    rendered_chars: usize,
+}
+
+impl Terminal {
+    fn new(rows: usize, cols: usize) -> Terminal {
+        Terminal {
+            rows,
+            cols,
+            grid: Terminal::blank_grid(rows, cols),
+            main_screen: None,
+            row: 0,
+            col: 0,
+            pending_wrap: false,
+            attrs: GraphicState::default(),
+            saved_cursor: None,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            autowrap: true,
+            insert_mode: false,
+            cursor_visible: true,
+            state: ParserState::Ground,
+            scrolled_out: Vec::new(),
+            responses: String::new(),
+            rendered_chars: 0,
+        }
+    }
+
+    fn blank_grid(rows: usize, cols: usize) -> Vec<Row> {
+        vec![vec![Cell::blank(GraphicState::default()); cols]; rows]
+    }
+
+    fn blank_row(&self) -> Row {
+        vec![Cell::blank(self.attrs); self.cols]
+    }
+
+    fn feed(&mut self, c: char) {
+        match mem::replace(&mut self.state, ParserState::Ground) {
+            ParserState::Ground => self.ground(c),
+            ParserState::Escape => self.escape(c),
+            ParserState::Charset => {}
+            ParserState::Csi(mut params) => match c {
+                '\x1b' => self.state = ParserState::Escape,
+                '\x20'...'\x3f' => {
+                    params.push(c as u8);
+                    self.state = ParserState::Csi(params);
+                }
+                '\x40'...'\x7e' => self.csi(&params, c),
+                // Other control characters are ignored inside a sequence.
+                _ => self.state = ParserState::Csi(params),
+            },
+            ParserState::Osc(esc) => match c {
+                '\x07' => {}
+                '\\' if esc => {}
+                _ => self.state = ParserState::Osc(c == '\x1b'),
+            },
+        }
+    }
+
+    fn ground(&mut self, c: char) {
+        match c {
+            '\x1b' => self.state = ParserState::Escape,
+            '\r' => {
+                self.col = 0;
+                self.pending_wrap = false;
+            }
+            '\n' | '\x0b' | '\x0c' => self.linefeed(),
+            '\x08' => {
+                self.col = self.col.saturating_sub(1);
+                self.pending_wrap = false;
+            }
+            '\t' => {
+                self.col = ((self.col / 8 + 1) * 8).min(self.cols - 1);
+                self.pending_wrap = false;
+            }
+            '\x00'...'\x1f' | '\x7f' => {}
+            _ => self.put_char(c),
+        }
+    }
+
+    fn escape(&mut self, c: char) {
+        match c {
+            '[' => self.state = ParserState::Csi(Vec::new()),
+            ']' => self.state = ParserState::Osc(false),
+            '(' | ')' | '*' | '+' => self.state = ParserState::Charset,
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            'D' => self.linefeed(),
+            'E' => {
+                self.col = 0;
+                self.linefeed();
+            }
+            'M' => self.reverse_index(),
+            'c' => {
+                let (rows, cols) = (self.rows, self.cols);
+                let scrolled_out = mem::replace(&mut self.scrolled_out, Vec::new());
+                let rendered_chars = self.rendered_chars;
+                *self = Terminal::new(rows, cols);
+                self.scrolled_out = scrolled_out;
+                self.rendered_chars = rendered_chars;
+            }
+            _ => {}
+        }
+    }
+
+    fn put_char(&mut self, c: char) {
+        if self.pending_wrap {
+            self.col = 0;
+            self.linefeed();
+        }
+        let cell = Cell {
+            ch: c,
+            attrs: self.attrs,
+        };
+        let (row, col) = (self.row, self.col);
+        if self.insert_mode {
+            let line = &mut self.grid[row];
+            line.insert(col, cell);
+            line.pop();
+        } else {
+            self.grid[row][col] = cell;
+        }
+        if col + 1 < self.cols {
+            self.col += 1;
+        } else if self.autowrap {
+            self.pending_wrap = true;
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.pending_wrap = false;
+        if self.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.row + 1 < self.rows {
+            self.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.pending_wrap = false;
+        if self.row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.row > 0 {
+            self.row -= 1;
+        }
+    }
+
+    /// Delete N lines at TOP, shifting the lines below it up to the
+    /// bottom of the scrolling region.  If KEEP is true, the deleted
+    /// lines go to the scrollback.
+    fn delete_lines(&mut self, top: usize, n: usize, keep: bool) {
+        let bottom = self.scroll_bottom;
+        for _ in 0..n.min(bottom - top + 1) {
+            let line = self.grid.remove(top);
+            if keep {
+                self.scrolled_out.push(line);
+            }
+            let blank = self.blank_row();
+            self.grid.insert(bottom, blank);
+        }
+    }
+
+    /// Insert N blank lines at TOP, pushing lines off the bottom of the
+    /// scrolling region.
+    fn insert_lines(&mut self, top: usize, n: usize) {
+        let bottom = self.scroll_bottom;
+        for _ in 0..n.min(bottom - top + 1) {
+            self.grid.remove(bottom);
+            let blank = self.blank_row();
+            self.grid.insert(top, blank);
+        }
+    }
+
+    /// Scroll the scrolling region up by N lines.  Lines leaving the
+    /// top of the main screen go to the scrollback.
+    fn scroll_up(&mut self, n: usize) {
+        let top = self.scroll_top;
+        let keep = top == 0 && self.main_screen.is_none();
+        self.delete_lines(top, n, keep);
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let top = self.scroll_top;
+        self.insert_lines(top, n);
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor {
+            row: self.row,
+            col: self.col,
+            attrs: self.attrs,
+        });
+    }
+
+    fn restore_cursor(&mut self) {
+        if let Some(saved) = self.saved_cursor {
+            self.row = saved.row.min(self.rows - 1);
+            self.col = saved.col.min(self.cols - 1);
+            self.attrs = saved.attrs;
+        }
+        self.pending_wrap = false;
+    }
+
+    fn erase(&mut self, row: usize, from: usize, to: usize) {
+        let blank = Cell::blank(self.attrs);
+        for cell in &mut self.grid[row][from..to] {
+            *cell = blank;
+        }
+    }
+
+    fn set_alternate_screen(&mut self, on: bool) {
+        if on && self.main_screen.is_none() {
+            let cursor = SavedCursor {
+                row: self.row,
+                col: self.col,
+                attrs: self.attrs,
+            };
+            let grid = Terminal::blank_grid(self.rows, self.cols);
+            self.main_screen = Some((mem::replace(&mut self.grid, grid), cursor));
+        } else if !on {
+            if let Some((grid, cursor)) = self.main_screen.take() {
+                self.grid = grid;
+                self.row = cursor.row;
+                self.col = cursor.col;
+                self.attrs = cursor.attrs;
+                self.pending_wrap = false;
+            }
+        }
+    }
+
+    fn set_mode(&mut self, private: bool, mode: EmacsInt, on: bool) {
+        match (private, mode) {
+            (false, 4) => self.insert_mode = on,
+            (true, 7) => self.autowrap = on,
+            (true, 25) => self.cursor_visible = on,
+            (true, 47) | (true, 1047) => self.set_alternate_screen(on),
+            (true, 1049) => {
+                if on {
+                    self.save_cursor();
+                    self.set_alternate_screen(true);
+                } else {
+                    self.set_alternate_screen(false);
+                    self.restore_cursor();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, raw: &[u8], command: char) {
+        let private = raw.first().map_or(false, |&b| b == b'?' || b == b'>');
+        let intermediate = raw.iter().any(|&b| b < 0x30);
+        let params = if private {
+            parse_params(&raw[1..])
+        } else {
+            parse_params(raw)
+        };
+        // The Nth parameter, with 0 meaning the default of 1.
+        let arg = |n: usize| params.get(n).map_or(1, |&p| p.max(1)) as usize;
+        let (rows, cols) = (self.rows, self.cols);
+
+        if intermediate {
+            return;
+        }
+        if command != 'm' {
+            self.pending_wrap = false;
+        }
+        match command {
+            'A' => {
+                let top = if self.row >= self.scroll_top {
+                    self.scroll_top
+                } else {
+                    0
+                };
+                self.row = self.row.saturating_sub(arg(0)).max(top);
+            }
+            'B' | 'e' => {
+                let bottom = if self.row <= self.scroll_bottom {
+                    self.scroll_bottom
+                } else {
+                    rows - 1
+                };
+                self.row = (self.row + arg(0)).min(bottom);
+            }
+            'C' | 'a' => self.col = (self.col + arg(0)).min(cols - 1),
+            'D' => self.col = self.col.saturating_sub(arg(0)),
+            'E' => {
+                self.row = (self.row + arg(0)).min(rows - 1);
+                self.col = 0;
+            }
+            'F' => {
+                self.row = self.row.saturating_sub(arg(0));
+                self.col = 0;
+            }
+            'G' | '`' => self.col = (arg(0) - 1).min(cols - 1),
+            'd' => self.row = (arg(0) - 1).min(rows - 1),
+            'H' | 'f' => {
+                self.row = (arg(0) - 1).min(rows - 1);
+                self.col = (arg(1) - 1).min(cols - 1);
+            }
+            'J' => {
+                let row = self.row;
+                let col = self.col;
+                match params.first().cloned().unwrap_or(0) {
+                    0 => {
+                        self.erase(row, col, cols);
+                        for r in row + 1..rows {
+                            self.erase(r, 0, cols);
+                        }
+                    }
+                    1 => {
+                        for r in 0..row {
+                            self.erase(r, 0, cols);
+                        }
+                        self.erase(row, 0, col + 1);
+                    }
+                    2 | 3 => for r in 0..rows {
+                        self.erase(r, 0, cols);
+                    },
+                    _ => {}
+                }
+            }
+            'K' => {
+                let row = self.row;
+                let col = self.col;
+                match params.first().cloned().unwrap_or(0) {
+                    0 => self.erase(row, col, cols),
+                    1 => self.erase(row, 0, col + 1),
+                    2 => self.erase(row, 0, cols),
+                    _ => {}
+                }
+            }
+            'L' | 'M' => if self.row >= self.scroll_top && self.row <= self.scroll_bottom {
+                let row = self.row;
+                if command == 'L' {
+                    self.insert_lines(row, arg(0));
+                } else {
+                    self.delete_lines(row, arg(0), false);
+                }
+                self.col = 0;
+            },
+            '@' => {
+                let (row, col) = (self.row, self.col);
+                let blank = Cell::blank(self.attrs);
+                for _ in 0..arg(0).min(cols - col) {
+                    self.grid[row].insert(col, blank);
+                    self.grid[row].pop();
+                }
+            }
+            'P' => {
+                let (row, col) = (self.row, self.col);
+                let blank = Cell::blank(self.attrs);
+                for _ in 0..arg(0).min(cols - col) {
+                    self.grid[row].remove(col);
+                    self.grid[row].push(blank);
+                }
+            }
+            'X' => {
+                let (row, col) = (self.row, self.col);
+                let end = (col + arg(0)).min(cols);
+                self.erase(row, col, end);
+            }
+            'S' => self.scroll_up(arg(0)),
+            'T' => self.scroll_down(arg(0)),
+            'm' if !private => self.attrs.apply(&params),
+            'r' => {
+                let top = arg(0) - 1;
+                let bottom = match params.get(1) {
+                    Some(&p) if p > 0 => (p as usize).min(rows) - 1,
+                    _ => rows - 1,
+                };
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.row = 0;
+                    self.col = 0;
+                }
+            }
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            'n' => match params.first().cloned().unwrap_or(0) {
+                5 => self.responses.push_str("\x1b[0n"),
+                6 => {
+                    let report = format!("\x1b[{};{}R", self.row + 1, self.col + 1);
+                    self.responses.push_str(&report);
+                }
+                _ => {}
+            },
+            'c' if !private => self.responses.push_str("\x1b[?1;2c"),
+            'h' | 'l' => for &mode in &params {
+                self.set_mode(private, mode, command == 'h');
+            },
+            _ => {}
+        }
+    }
+
+    fn resize(&mut self, rows: usize, cols: usize) {
+        fn resize_grid(grid: &mut Vec<Row>, cols: usize) {
+            for line in grid.iter_mut() {
+                line.resize(cols, Cell::blank(GraphicState::default()));
+            }
+        }
+
+        // Drop blank lines below the cursor first, then lines from the top.
+        while self.grid.len() > rows && self.grid.len() > self.row + 1 {
+            if !self.grid[self.grid.len() - 1].iter().all(Cell::is_default_blank) {
+                break;
+            }
+            self.grid.pop();
+        }
+        while self.grid.len() > rows {
+            let line = self.grid.remove(0);
+            if self.main_screen.is_none() {
+                self.scrolled_out.push(line);
+            }
+            self.row = self.row.saturating_sub(1);
+        }
+        while self.grid.len() < rows {
+            self.grid.push(vec![Cell::blank(GraphicState::default()); cols]);
+        }
+        resize_grid(&mut self.grid, cols);
+        if let Some((ref mut grid, ref mut cursor)) = self.main_screen {
+            grid.truncate(rows);
+            while grid.len() < rows {
+                grid.push(vec![Cell::blank(GraphicState::default()); cols]);
+            }
+            resize_grid(grid, cols);
+            cursor.row = cursor.row.min(rows - 1);
+            cursor.col = cursor.col.min(cols - 1);
+        }
+
+        self.rows = rows;
+        self.cols = cols;
+        self.row = self.row.min(rows - 1);
+        self.col = self.col.min(cols - 1);
+        self.pending_wrap = false;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+    }
+}
+
+/// Text to insert into a buffer, with the attributes of its runs.
+struct RenderedText {
+    text: String,
+    nchars: usize,
+    /// (START, END, ATTRS) in characters, for runs with non-default
+    /// attributes.
+    runs: Vec<(usize, usize, GraphicState)>,
+}
+
+impl RenderedText {
+    fn new() -> RenderedText {
+        RenderedText {
+            text: String::new(),
+            nchars: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Append the characters of LINE.
+    fn push_cells(&mut self, line: &[Cell]) {
+        for cell in line {
+            let start = self.nchars;
+            self.text.push(cell.ch);
+            self.nchars += 1;
+            if cell.attrs != GraphicState::default() {
+                match self.runs.last_mut() {
+                    Some(run) if run.1 == start && run.2 == cell.attrs => {
+                        run.1 += 1;
+                        continue;
+                    }
+                    _ => {}
+                }
+                self.runs.push((start, start + 1, cell.attrs));
+            }
+        }
+    }
+
+    fn push_newline(&mut self) {
+        self.text.push('\n');
+        self.nchars += 1;
+    }
+}
+
+/// The number of cells of LINE worth rendering: trailing default blanks
+/// are left out, but at least MIN_LEN cells are kept.
+fn rendered_len(line: &[Cell], min_len: usize) -> usize {
+    let len = line.iter()
+        .rposition(|c| !c.is_default_blank())
+        .map_or(0, |i| i + 1);
+    len.max(min_len).min(line.len())
+}
+
+struct Render {
+    /// Scrollback lines, each followed by a newline.
+    scrollback: RenderedText,
+    screen: RenderedText,
+    /// Offset of the cursor in `screen', in characters.
+    cursor: usize,
+    /// Characters taken by the previous screen.
+    old_screen_chars: usize,
+}
+
+impl Terminal {
+    fn render(&mut self) -> Render {
+        let mut scrollback = RenderedText::new();
+        for line in self.scrolled_out.drain(..) {
+            let len = rendered_len(&line, 0);
+            scrollback.push_cells(&line[..len]);
+            scrollback.push_newline();
+        }
+
+        let mut screen = RenderedText::new();
+        let mut cursor = 0;
+        for (r, line) in self.grid.iter().enumerate() {
+            if r > 0 {
+                screen.push_newline();
+            }
+            let min_len = if r == self.row { self.col } else { 0 };
+            if r == self.row {
+                cursor = screen.nchars + self.col;
+            }
+            let len = rendered_len(line, min_len);
+            screen.push_cells(&line[..len]);
+        }
+
+        let old_screen_chars = self.rendered_chars;
+        self.rendered_chars = screen.nchars;
+        Render {
+            scrollback,
+            screen,
+            cursor,
+            old_screen_chars,
+        }
+    }
+}
+
+lazy_static! {
+    static ref TERMINALS: Mutex<(EmacsInt, HashMap<EmacsInt, Terminal>)> =
+        Mutex::new((0, HashMap::new()));
+}
+
+/// Run F on the terminal with handle TERM, or return `None` if there is
+/// no such terminal.
+fn with_terminal<F, R>(term: EmacsInt, f: F) -> Option<R>
+where
+    F: FnOnce(&mut Terminal) -> R,
+{
+    let mut terminals = TERMINALS.lock().unwrap();
+    terminals.1.get_mut(&term).map(f)
+}
+
+fn terminal_handle(term: LispObject) -> EmacsInt {
+    let handle = term.as_fixnum_or_error();
+    if with_terminal(handle, |_| ()).is_none() {
+        error!("No terminal with handle {}", handle);
+    }
+    handle
+}
+
+fn dimension(n: LispObject) -> usize {
+    let n = n.as_natnum_or_error();
+    if n == 0 {
+        args_out_of_range!(LispObject::from_natnum(n));
+    }
+    n as usize
+}
+
+/// Make a terminal emulator with ROWS rows and COLS columns.
+/// Return an integer handle to pass to the other `vterm-' functions.
+/// The terminal stays alive until `vterm-delete' is called on it.
+#[lisp_fn]
+pub fn vterm_make(rows: LispObject, cols: LispObject) -> LispObject {
+    let rows = dimension(rows);
+    let cols = dimension(cols);
+    let mut terminals = TERMINALS.lock().unwrap();
+    terminals.0 += 1;
+    let handle = terminals.0;
+    terminals.1.insert(handle, Terminal::new(rows, cols));
+    LispObject::from_natnum(handle)
+}
+
+/// Free the terminal emulator TERM.
+#[lisp_fn]
+pub fn vterm_delete(term: LispObject) -> LispObject {
+    let handle = term.as_fixnum_or_error();
+    TERMINALS.lock().unwrap().1.remove(&handle);
+    LispObject::constant_nil()
+}
+
+/// Feed STRING, output of the program running in the terminal, to the
+/// terminal emulator TERM.
+/// Return a string to send back to the program in reply to terminal
+/// queries it made, or nil if there is none.
+#[lisp_fn]
+pub fn vterm_write(term: LispObject, string: LispObject) -> LispObject {
+    let handle = terminal_handle(term);
+    let chars: Vec<char> = string
+        .as_string_or_error()
+        .chars()
+        .map(|c| char::from_u32(c).unwrap_or('\u{fffd}'))
+        .collect();
+    let responses = with_terminal(handle, |t| {
+        for &c in &chars {
+            t.feed(c);
+        }
+        mem::replace(&mut t.responses, String::new())
+    }).unwrap_or_default();
+    if responses.is_empty() {
+        LispObject::constant_nil()
+    } else {
+        lisp_string(&responses)
+    }
+}
+
+/// Resize the terminal emulator TERM to ROWS rows and COLS columns.
+#[lisp_fn]
+pub fn vterm_resize(term: LispObject, rows: LispObject, cols: LispObject) -> LispObject {
+    let handle = terminal_handle(term);
+    let rows = dimension(rows);
+    let cols = dimension(cols);
+    with_terminal(handle, |t| t.resize(rows, cols));
+    LispObject::constant_nil()
+}
+
+/// Return the position of the cursor of terminal TERM, as a list
+/// (ROW COLUMN VISIBLE), with ROW and COLUMN counting from 0.
+#[lisp_fn]
+pub fn vterm_cursor(term: LispObject) -> LispObject {
+    let handle = terminal_handle(term);
+    let (row, col, visible) = with_terminal(handle, |t| (t.row, t.col, t.cursor_visible))
+        .unwrap_or((0, 0, false));
+    list!(
+        LispObject::from_natnum(row as EmacsInt),
+        LispObject::from_natnum(col as EmacsInt),
+        LispObject::from_bool(visible)
+    )
+}
+
+fn propertized(rendered: &RenderedText) -> LispObject {
+    let string = lisp_string(&rendered.text);
+    let face = intern("face");
+    for &(start, end, ref attrs) in &rendered.runs {
+        put_text_property(
+            LispObject::from_natnum(start as EmacsInt),
+            LispObject::from_natnum(end as EmacsInt),
+            face,
+            attrs.face(),
+            string,
+        );
+    }
+    string
+}
+
+fn insert_string(string: LispObject) {
+    let s = string.as_string_or_error();
+    unsafe { insert_from_string(string.to_raw(), 0, 0, s.len_chars(), s.len_bytes(), false) };
+}
+
+/// Render the terminal emulator TERM at the end of the current buffer.
+/// The screen drawn by the previous call is replaced, after inserting
+/// the lines that scrolled off the top of the screen since then.  Point
+/// is left at the cursor position.  Text attributes are rendered as
+/// `face' properties.
+#[lisp_fn]
+pub fn vterm_render(term: LispObject) -> LispObject {
+    let handle = terminal_handle(term);
+    let render = match with_terminal(handle, Terminal::render) {
+        Some(render) => render,
+        None => return LispObject::constant_nil(),
+    };
+
+    let buffer = ThreadState::current_buffer();
+    let z = buffer.z();
+    let start = (z - render.old_screen_chars as isize).max(buffer.beg());
+    unsafe {
+        del_range(start, z);
+        set_point(start);
+    }
+    insert_string(propertized(&render.scrollback));
+    let screen_start = ThreadState::current_buffer().pt();
+    insert_string(propertized(&render.screen));
+    unsafe { set_point(screen_start + render.cursor as isize) };
+    LispObject::constant_nil()
+}
+
+#[cfg(test)]
+fn screen_text(t: &Terminal) -> Vec<String> {
+    t.grid
+        .iter()
+        .map(|line| {
+            let len = rendered_len(line, 0);
+            line[..len].iter().map(|c| c.ch).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn feed_str(t: &mut Terminal, s: &str) {
+    for c in s.chars() {
+        t.feed(c);
+    }
+}
+
+#[test]
+fn test_vterm_wrap_and_scroll() {
+    let mut t = Terminal::new(2, 4);
+    feed_str(&mut t, "abcdef\r\nxy");
+    assert_eq!(screen_text(&t), vec!["ef", "xy"]);
+    assert_eq!(t.scrolled_out.len(), 1);
+    assert_eq!((t.row, t.col), (1, 2));
+}
+
+#[test]
+fn test_vterm_cursor_and_erase() {
+    let mut t = Terminal::new(3, 5);
+    feed_str(&mut t, "hello\x1b[2;3Hab\x1b[1;2H\x1b[K\x1b[6n");
+    assert_eq!(screen_text(&t), vec!["h", "  ab", ""]);
+    assert_eq!(t.responses, "\x1b[1;2R");
+}
+
+#[test]
+fn test_vterm_alternate_screen() {
+    let mut t = Terminal::new(2, 5);
+    feed_str(&mut t, "main\x1b[?1049h\x1b[Halt");
+    assert_eq!(screen_text(&t), vec!["alt", ""]);
+    feed_str(&mut t, "\x1b[?1049l");
+    assert_eq!(screen_text(&t), vec!["main", ""]);
+    assert_eq!((t.row, t.col), (0, 4));
+}
+
+#[test]
+fn test_vterm_scroll_region() {
+    let mut t = Terminal::new(4, 3);
+    feed_str(&mut t, "1\r\n2\r\n3\r\n4\x1b[2;3r\x1b[3;1H\n");
+    assert_eq!(screen_text(&t), vec!["1", "3", "", "4"]);
+    assert!(t.scrolled_out.is_empty());
+}
+
+include!(concat!(env!("OUT_DIR"), "/vterm_exports.rs"));
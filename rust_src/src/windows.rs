@@ -6,13 +6,14 @@ use remacs_macros::lisp_fn;
 use remacs_sys::{EmacsInt, Lisp_Window};
 use remacs_sys::{Qceiling, Qfloor, Qheader_line_format, Qmode_line_format, Qnone};
 use remacs_sys::{is_minibuffer, minibuf_level, minibuf_selected_window as current_minibuf_window,
-                 selected_window as current_window, wget_parent, wget_pixel_height,
-                 wget_pseudo_window_p, window_parameter};
+                 selected_window as current_window, wget_horizontal, wget_parent,
+                 wget_pixel_height, wget_pseudo_window_p, window_parameter};
 
 use editfns::point;
 use frames::{frame_live_or_selected, window_frame_live_or_selected};
-use lisp::{ExternalPtr, LispObject};
+use lisp::{intern, ExternalPtr, LispObject};
 use lisp::defsubr;
+use lists::{car_safe, cdr_safe, list, plist_get};
 use marker::marker_position;
 
 pub type LispWindowRef = ExternalPtr<Lisp_Window>;
@@ -62,6 +63,13 @@ impl LispWindowRef {
         self.contents().is_window()
     }
 
+    /// True if window is an internal window whose children are side
+    /// by side.
+    #[inline]
+    pub fn is_horizontal(&self) -> bool {
+        unsafe { wget_horizontal(self.as_ptr()) }
+    }
+
     #[inline]
     pub fn is_minibuffer(&self) -> bool {
         unsafe { is_minibuffer(self.as_ptr()) }
@@ -434,6 +442,251 @@ pub fn minibuffer_window(frame: LispObject) -> LispObject {
     frame.minibuffer_window()
 }
 
+/// Return the description of WINDOW and of its children, if it is an
+/// internal window, for `window-configuration-to-sexp'.
+fn window_to_sexp(window: LispObject) -> LispObject {
+    let win = window.as_window_or_error();
+    let mut plist = vec![
+        intern(":width"),
+        LispObject::from_natnum(EmacsInt::from(win.total_cols)),
+        intern(":height"),
+        LispObject::from_natnum(EmacsInt::from(win.total_lines)),
+        intern(":normal-width"),
+        LispObject::from(win.normal_cols),
+        intern(":normal-height"),
+        LispObject::from(win.normal_lines),
+    ];
+    if !win.is_internal() {
+        let buffer = win.contents();
+        plist.extend_from_slice(&[
+            intern(":buffer"),
+            call!(intern("buffer-name"), buffer),
+            intern(":file"),
+            call!(intern("buffer-file-name"), buffer),
+            intern(":start"),
+            window_start(window),
+            intern(":point"),
+            window_point(window),
+            intern(":hscroll"),
+            LispObject::from_natnum(win.hscroll as EmacsInt),
+            intern(":dedicated"),
+            LispObject::from(win.dedicated),
+        ]);
+        if window.eq(selected_window()) {
+            plist.extend_from_slice(&[intern(":selected"), LispObject::constant_t()]);
+        }
+        return list!(intern("leaf"), list(&mut plist));
+    }
+
+    let kind = intern(if win.is_horizontal() {
+        "horizontal"
+    } else {
+        "vertical"
+    });
+    let mut node = vec![kind, list(&mut plist)];
+    let mut child = win.contents();
+    while child.is_window() {
+        node.push(window_to_sexp(child));
+        child = LispObject::from(child.as_window_or_error().next);
+    }
+    list(&mut node)
+}
+
+/// Return a description of the windows of FRAME as a list of numbers,
+/// strings and symbols.
+/// The description can be printed, read back in another session and
+/// turned into windows again by `sexp-to-window-configuration', which
+/// can't be done with the objects `current-window-configuration'
+/// returns.  FRAME defaults to the selected frame.
+///
+/// The description has the form
+///
+///   (window-configuration :frame-size (COLUMNS . LINES) :root WINDOW)
+///
+/// where WINDOW describes the root window of FRAME.  A live window is
+/// described by (leaf PROPERTIES), where the plist PROPERTIES gives its
+/// `:width', `:height', `:normal-width' and `:normal-height'; the name
+/// and file of its `:buffer' and `:file'; its `:start', `:point' and
+/// `:hscroll'; whether it is `:dedicated'; and `:selected' if it is
+/// the selected window.  An internal window is described by
+/// (horizontal PROPERTIES CHILD...) if its children are side by side
+/// and (vertical PROPERTIES CHILD...) if they are stacked, with the
+/// same sizes in PROPERTIES.
+#[lisp_fn(min = "0")]
+pub fn window_configuration_to_sexp(frame: LispObject) -> LispObject {
+    let root = frame_live_or_selected(frame).root_window();
+    let frame = window_frame(root);
+    list!(
+        intern("window-configuration"),
+        intern(":frame-size"),
+        LispObject::cons(
+            call!(intern("frame-width"), frame),
+            call!(intern("frame-height"), frame)
+        ),
+        intern(":root"),
+        window_to_sexp(root)
+    )
+}
+
+fn invalid_configuration(sexp: LispObject) -> ! {
+    wrong_type!(intern("window-configuration-sexp"), sexp)
+}
+
+/// Signal an error unless NODE is a valid window description, so that
+/// the windows are left alone when the description is bad.
+fn check_window_sexp(node: LispObject) {
+    let kind = car_safe(node);
+    if !cdr_safe(node).is_cons() {
+        invalid_configuration(node);
+    }
+    if kind.eq(intern("horizontal")) || kind.eq(intern("vertical")) {
+        let children = cdr_safe(cdr_safe(node));
+        if !children.is_cons() {
+            invalid_configuration(node);
+        }
+        for child in children.iter_cars_safe() {
+            check_window_sexp(child);
+        }
+    } else if !kind.eq(intern("leaf")) {
+        invalid_configuration(node);
+    }
+}
+
+/// Split WINDOW into the children described by CHILDREN, side by side if
+/// HORIZONTAL, and return the windows made for them, in order.
+fn split_window_for(
+    window: LispObject,
+    children: &[LispObject],
+    horizontal: bool,
+) -> Vec<LispObject> {
+    let (side, normal) = if horizontal {
+        (intern("right"), intern(":normal-width"))
+    } else {
+        (intern("below"), intern(":normal-height"))
+    };
+    let horizontal = LispObject::from_bool(horizontal);
+    let total = call!(intern("window-total-size"), window, horizontal).as_fixnum_or_error();
+    let mut windows = Vec::with_capacity(children.len());
+    let mut current = window;
+    for &child in &children[..children.len() - 1] {
+        let fraction = plist_get(car_safe(cdr_safe(child)), normal)
+            .any_to_float()
+            .unwrap_or(1.0 / children.len() as f64);
+        let available =
+            call!(intern("window-total-size"), current, horizontal).as_fixnum_or_error();
+        let minimum = call!(intern("window-min-size"), current, horizontal).as_fixnum_or_error();
+        let size = ((fraction * total as f64).round() as EmacsInt)
+            .min(available - minimum)
+            .max(minimum);
+        let next = call!(
+            intern("split-window"),
+            current,
+            LispObject::from_fixnum(size),
+            side
+        );
+        windows.push(current);
+        current = next;
+    }
+    windows.push(current);
+    windows
+}
+
+/// Make WINDOW look like the description NODE, setting SELECTED to the
+/// window that was selected.
+fn sexp_to_window(window: LispObject, node: LispObject, selected: &mut LispObject) {
+    if !cdr_safe(node).is_cons() {
+        invalid_configuration(node);
+    }
+    let kind = car_safe(node);
+    let plist = car_safe(cdr_safe(node));
+    if kind.eq(intern("leaf")) {
+        let mut buffer = call!(intern("get-buffer"), plist_get(plist, intern(":buffer")));
+        let file = plist_get(plist, intern(":file"));
+        if buffer.is_nil()
+            && file.is_string()
+            && call!(intern("file-readable-p"), file).is_not_nil()
+        {
+            buffer = call!(intern("find-file-noselect"), file);
+        }
+        if buffer.is_not_nil() {
+            call!(intern("set-window-buffer"), window, buffer);
+            let start = plist_get(plist, intern(":start"));
+            if start.is_natnum() {
+                call!(
+                    intern("set-window-start"),
+                    window,
+                    start,
+                    LispObject::constant_t()
+                );
+            }
+            let point = plist_get(plist, intern(":point"));
+            if point.is_natnum() {
+                call!(intern("set-window-point"), window, point);
+            }
+        }
+        let hscroll = plist_get(plist, intern(":hscroll"));
+        if hscroll.is_natnum() {
+            call!(intern("set-window-hscroll"), window, hscroll);
+        }
+        call!(
+            intern("set-window-dedicated-p"),
+            window,
+            plist_get(plist, intern(":dedicated"))
+        );
+        if plist_get(plist, intern(":selected")).is_not_nil() {
+            *selected = window;
+        }
+    } else if kind.eq(intern("horizontal")) || kind.eq(intern("vertical")) {
+        let children: Vec<LispObject> = cdr_safe(cdr_safe(node)).iter_cars_safe().collect();
+        if children.is_empty() {
+            invalid_configuration(node);
+        }
+        let windows = split_window_for(window, &children, kind.eq(intern("horizontal")));
+        for (&window, &child) in windows.iter().zip(children.iter()) {
+            sexp_to_window(window, child, selected);
+        }
+    } else {
+        invalid_configuration(node);
+    }
+}
+
+/// Give FRAME the windows described by SEXP.
+/// SEXP is a description made by `window-configuration-to-sexp',
+/// possibly in another session.  The windows of FRAME, which defaults to
+/// the selected frame, are replaced by windows with the same layout,
+/// showing the same buffers.  A buffer that no longer exists is visited
+/// again if it was visiting a file that can still be read; otherwise
+/// its windows keep showing whatever buffer they get when split.
+/// Sizes are given to windows in proportion to the ones they had, so
+/// the layout fits a frame of another size.  If RESIZE is non-nil, the
+/// frame is first given the size it had.
+#[lisp_fn(min = "1")]
+pub fn sexp_to_window_configuration(
+    sexp: LispObject,
+    frame: LispObject,
+    resize: LispObject,
+) -> LispObject {
+    if !car_safe(sexp).eq(intern("window-configuration")) {
+        invalid_configuration(sexp);
+    }
+    let root = plist_get(cdr_safe(sexp), intern(":root"));
+    check_window_sexp(root);
+    let frame = window_frame(frame_live_or_selected(frame).root_window());
+    let size = plist_get(cdr_safe(sexp), intern(":frame-size"));
+    if resize.is_not_nil() && size.is_cons() {
+        call!(intern("set-frame-size"), frame, car_safe(size), cdr_safe(size));
+    }
+
+    let window = call!(intern("frame-first-window"), frame);
+    call!(intern("delete-other-windows"), window);
+    let mut selected = LispObject::constant_nil();
+    sexp_to_window(frame_root_window(frame), root, &mut selected);
+    if selected.is_not_nil() {
+        call!(intern("set-frame-selected-window"), frame, selected);
+    }
+    LispObject::constant_nil()
+}
+
 #[no_mangle]
 pub fn window_wants_mode_line(window: LispWindowRef) -> bool {
     window.wants_mode_line()
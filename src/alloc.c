@@ -5543,6 +5543,7 @@ purecopy_hash_table (struct Lisp_Hash_Table *table)
   pure->rehash_size = table->rehash_size;
   pure->key_and_value = purecopy (table->key_and_value);
   pure->test = pure_test;
+  pure->key_stamp = table->key_stamp;
 
   return pure;
 }
@@ -5963,6 +5964,7 @@ garbage_collect_1 (void *end)
   mark_pinned_symbols ();
   mark_terminals ();
   mark_kboards ();
+  mark_transient_maps ();
   mark_threads ();
 
 #ifdef USE_GTK
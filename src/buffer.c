@@ -1670,6 +1670,13 @@ cleaning up all windows currently displaying the buffer to be killed. */)
   /* Unlock this buffer's file, if it is locked.  */
   unlock_buffer (b);
 
+  /* Forget what is indexed about its text.  */
+  forget_buffer_changes (b);
+  forget_buffer_anchors (b);
+  forget_invisible_ranges (b);
+  forget_line_checkpoints (b);
+  forget_visual_line_starts (b);
+
   kill_buffer_processes (buffer);
   kill_buffer_xwidgets (buffer);
 
@@ -2164,6 +2171,13 @@ results, see Info node `(elisp)Swapping Text'.  */)
   } while (0)
 
   swapfield (own_text, struct buffer_text);
+  swap_buffer_changes (current_buffer, other_buffer);
+  swap_buffer_anchors (current_buffer, other_buffer);
+  swap_line_checkpoints (current_buffer, other_buffer);
+  forget_visual_line_starts (current_buffer);
+  forget_visual_line_starts (other_buffer);
+  forget_invisible_ranges (current_buffer);
+  forget_invisible_ranges (other_buffer);
   eassert (current_buffer->text == &current_buffer->own_text);
   eassert (other_buffer->text == &other_buffer->own_text);
 #ifdef REL_ALLOC
@@ -4858,6 +4872,25 @@ enlarge_buffer_text (struct buffer *b, ptrdiff_t delta)
   ptrdiff_t nbytes = (BUF_Z_BYTE (b) - BUF_BEG_BYTE (b) + BUF_GAP_SIZE (b) + 1
 		      + delta);
   block_input ();
+  if (mapped_text_p (b->text->beg))
+    {
+      /* Text mapped from a file grows in place while the addresses
+	 reserved after it last, and is then copied into ordinary
+	 buffer text.  */
+      if (resize_mapped_text (b->text->beg, nbytes))
+	{
+	  unblock_input ();
+	  return;
+	}
+      unsigned char *mapped = b->text->beg;
+      ptrdiff_t oldbytes = (BUF_Z_BYTE (b) - BUF_BEG_BYTE (b)
+			    + BUF_GAP_SIZE (b) + 1);
+      alloc_buffer_text (b, nbytes);
+      memcpy (b->text->beg, mapped, min (oldbytes, nbytes));
+      free_mapped_text (mapped);
+      unblock_input ();
+      return;
+    }
 #if defined USE_MMAP_FOR_BUFFERS
   p = mmap_realloc ((void **) &b->text->beg, nbytes);
 #elif defined REL_ALLOC
@@ -4884,18 +4917,60 @@ free_buffer_text (struct buffer *b)
 {
   block_input ();
 
+  if (mapped_text_p (b->text->beg))
+    free_mapped_text (b->text->beg);
+  else
+    {
 #if defined USE_MMAP_FOR_BUFFERS
-  mmap_free ((void **) &b->text->beg);
+      mmap_free ((void **) &b->text->beg);
 #elif defined REL_ALLOC
-  r_alloc_free ((void **) &b->text->beg);
+      r_alloc_free ((void **) &b->text->beg);
 #else
-  xfree (b->text->beg);
+      xfree (b->text->beg);
 #endif
+    }
 
   BUF_BEG_ADDR (b) = NULL;
   unblock_input ();
 }
 
+/* Check that the current buffer can take the text of a file mapped by
+   insert-file-contents-mmap, make it unibyte and run the hooks that
+   go before changing it.  */
+
+void
+prepare_for_mapped_text (void)
+{
+  if (current_buffer->indirections != 0)
+    error ("Cannot map a file into an indirect buffer or the base of one");
+  if (Z_BYTE != BEG_BYTE)
+    error ("Cannot map a file into a buffer that is not empty");
+  prepare_to_modify_buffer (BEG, BEG, NULL);
+  if (Z_BYTE != BEG_BYTE)
+    error ("Cannot map a file into a buffer that is not empty");
+  if (!NILP (BVAR (current_buffer, enable_multibyte_characters)))
+    Fset_buffer_multibyte (Qnil);
+}
+
+/* Make TEXT, which holds the NBYTES bytes of a file mapped by
+   insert-file-contents-mmap followed by a gap of GAP bytes and one
+   more byte, the text of the current buffer, and insert those NBYTES
+   bytes.  The buffer must have passed prepare_for_mapped_text.  The
+   text is unmapped by free_buffer_text.  */
+
+void
+insert_mapped_text (unsigned char *text, ptrdiff_t nbytes, ptrdiff_t gap)
+{
+  eassert (Z_BYTE == BEG_BYTE && current_buffer->indirections == 0);
+  free_buffer_text (current_buffer);
+  block_input ();
+  BUF_BEG_ADDR (current_buffer) = text;
+  unblock_input ();
+  GPT = GPT_BYTE = BEG;
+  GAP_SIZE = nbytes + gap;
+  insert_from_gap (nbytes, nbytes, false);
+}
+
 
 
 /***********************************************************************
@@ -254,6 +254,8 @@ extern void temp_set_point_both (struct buffer *,
 				 ptrdiff_t, ptrdiff_t);
 extern void set_point_from_marker (Lisp_Object);
 extern void enlarge_buffer_text (struct buffer *, ptrdiff_t);
+extern void prepare_for_mapped_text (void);
+extern void insert_mapped_text (unsigned char *, ptrdiff_t, ptrdiff_t);
 
 
 /* Macros for setting the BEGV, ZV or PT of a given buffer.
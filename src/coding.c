@@ -5496,6 +5496,32 @@ detect_coding_charset (struct coding_system *coding,
   return 1;
 }
 
+/* Decode with the decoder written in Rust for the MIME charset of
+   CODING.  Unlike the decoders above, it produces no charset
+   annotations.  */
+
+static void
+decode_coding_native (struct coding_system *coding)
+{
+  bool eol_dos
+    = !inhibit_eol_conversion && EQ (CODING_ID_EOL_TYPE (coding->id), Qdos);
+  ptrdiff_t consumed, consumed_chars;
+  ptrdiff_t produced
+    = codecs_decode (coding->native_codec,
+		     coding->source + coding->consumed,
+		     coding->src_bytes - coding->consumed,
+		     coding->src_multibyte,
+		     (coding->mode & CODING_MODE_LAST_BLOCK) != 0,
+		     eol_dos,
+		     coding->charbuf + coding->charbuf_used,
+		     coding->charbuf_size - coding->charbuf_used,
+		     &consumed, &consumed_chars);
+
+  coding->consumed += consumed;
+  coding->consumed_char += consumed_chars;
+  coding->charbuf_used += produced;
+}
+
 static void
 decode_coding_charset (struct coding_system *coding)
 {
@@ -5899,6 +5925,18 @@ setup_coding_system (Lisp_Object coding_system, struct coding_system *coding)
 
     }
 
+  /* Prefer the decoders written in Rust, where there is one for the
+     MIME charset of the coding system.  */
+  coding->native_codec = -1;
+  if (EQ (coding_type, Qcharset) || EQ (coding_type, Qiso_2022)
+      || EQ (coding_type, Qshift_jis) || EQ (coding_type, Qbig5))
+    {
+      coding->native_codec
+	= codecs_find (Fplist_get (CODING_ATTR_PLIST (attrs), QCmime_charset));
+      if (coding->native_codec >= 0)
+	coding->decoder = decode_coding_native;
+    }
+
   return;
 }
 
@@ -10885,6 +10923,7 @@ syms_of_coding (void)
   DEFSYM (QCpost_read_conversion, ":post-read-conversion");
   DEFSYM (QCpre_write_conversion, ":pre-write-conversion");
   DEFSYM (QCascii_compatible_p, ":ascii-compatible-p");
+  DEFSYM (QCmime_charset, ":mime-charset");
 
   Vcoding_category_table
     = Fmake_vector (make_number (coding_category_max), Qnil);
@@ -490,6 +490,10 @@ struct coding_system
 
   int default_char;
 
+  /* Index of the decoder written in Rust that decodes this coding
+     system, or -1 if there is none.  */
+  int native_codec;
+
   bool (*detector) (struct coding_system *, struct coding_detection_info *);
   void (*decoder) (struct coding_system *);
   bool (*encoder) (struct coding_system *);
@@ -672,6 +676,12 @@ extern Lisp_Object code_convert_string (Lisp_Object, Lisp_Object,
                                         Lisp_Object, bool, bool, bool);
 extern Lisp_Object code_convert_string_norecord (Lisp_Object, Lisp_Object,
                                                  bool);
+/* Defined in Rust.  */
+extern int codecs_find (Lisp_Object);
+extern ptrdiff_t codecs_decode (int, const unsigned char *, ptrdiff_t,
+				bool, bool, bool, int *, ptrdiff_t,
+				ptrdiff_t *, ptrdiff_t *);
+
 extern Lisp_Object encode_file_name (Lisp_Object);
 extern Lisp_Object decode_file_name (Lisp_Object);
 extern Lisp_Object raw_text_coding_system (Lisp_Object);
@@ -1714,6 +1714,7 @@ bool-vector.  IDX starts at 0.  */)
 	args_out_of_range (array, idx);
       CHECK_CHARACTER (newelt);
       c = XFASTINT (newelt);
+      string_text_stamp++;
 
       if (STRING_MULTIBYTE (array))
 	{
@@ -3127,7 +3127,11 @@ update_frame (struct frame *f, bool force_p, bool inhibit_hairy_id_p)
           if (FRAME_TTY (f)->termscript)
 	    fflush_unlocked (FRAME_TTY (f)->termscript);
 	  if (FRAME_TERMCAP_P (f))
-	    fflush_unlocked (FRAME_TTY (f)->output);
+	    {
+	      fflush_unlocked (FRAME_TTY (f)->output);
+	      /* Draw images that the update wrote text over again.  */
+	      tty_images_repaint (f, fileno (FRAME_TTY (f)->output));
+	    }
         }
 
       /* Check window matrices for lost pointers.  */
@@ -4790,6 +4794,9 @@ update_frame_line (struct frame *f, int vpos)
   if (colored_spaces_p)
     write_spaces_p = 1;
 
+  /* Text written to this line covers any image shown on it.  */
+  tty_image_row_updated (f, vpos);
+
   /* Current row not enabled means it has unknown contents.  We must
      write the whole desired line in that case.  */
   must_write_whole_line_p = !current_row->enabled_p;
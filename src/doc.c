@@ -37,260 +37,8 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
 #include "intervals.h"
 #include "keymap.h"
 
-/* Buffer used for reading from documentation file.  */
-static char *get_doc_string_buffer;
-static ptrdiff_t get_doc_string_buffer_size;
-
-static unsigned char *read_bytecode_pointer;
-
 static char const sibling_etc[] = "../etc/";
 
-/* `readchar' in lread.c calls back here to fetch the next byte.
-   If UNREADFLAG is 1, we unread a byte.  */
-
-int
-read_bytecode_char (bool unreadflag)
-{
-  if (unreadflag)
-    {
-      read_bytecode_pointer--;
-      return 0;
-    }
-  return *read_bytecode_pointer++;
-}
-
-/* Extract a doc string from a file.  FILEPOS says where to get it.
-   If it is an integer, use that position in the standard DOC file.
-   If it is (FILE . INTEGER), use FILE as the file name
-   and INTEGER as the position in that file.
-   But if INTEGER is negative, make it positive.
-   (A negative integer is used for user variables, so we can distinguish
-   them without actually fetching the doc string.)
-
-   If the location does not point to the beginning of a docstring
-   (e.g. because the file has been modified and the location is stale),
-   return nil.
-
-   If UNIBYTE, always make a unibyte string.
-
-   If DEFINITION, assume this is for reading
-   a dynamic function definition; convert the bytestring
-   and the constants vector with appropriate byte handling,
-   and return a cons cell.  */
-
-Lisp_Object
-get_doc_string (Lisp_Object filepos, bool unibyte, bool definition)
-{
-  char *from, *to, *name, *p, *p1;
-  int fd;
-  int offset;
-  EMACS_INT position;
-  Lisp_Object file, tem, pos;
-  ptrdiff_t count;
-  USE_SAFE_ALLOCA;
-
-  if (INTEGERP (filepos))
-    {
-      file = Vdoc_file_name;
-      pos = filepos;
-    }
-  else if (CONSP (filepos))
-    {
-      file = XCAR (filepos);
-      pos = XCDR (filepos);
-    }
-  else
-    return Qnil;
-
-  position = eabs (XINT (pos));
-
-  if (!STRINGP (Vdoc_directory))
-    return Qnil;
-
-  if (!STRINGP (file))
-    return Qnil;
-
-  /* Put the file name in NAME as a C string.
-     If it is relative, combine it with Vdoc_directory.  */
-
-  tem = Ffile_name_absolute_p (file);
-  file = ENCODE_FILE (file);
-  Lisp_Object docdir
-    = NILP (tem) ? ENCODE_FILE (Vdoc_directory) : empty_unibyte_string;
-  ptrdiff_t docdir_sizemax = SBYTES (docdir) + 1;
-#ifndef CANNOT_DUMP
-  docdir_sizemax = max (docdir_sizemax, sizeof sibling_etc);
-#endif
-  name = SAFE_ALLOCA (docdir_sizemax + SBYTES (file));
-  lispstpcpy (lispstpcpy (name, docdir), file);
-
-  fd = emacs_open (name, O_RDONLY, 0);
-  if (fd < 0)
-    {
-#ifndef CANNOT_DUMP
-      if (!NILP (Vpurify_flag))
-	{
-	  /* Preparing to dump; DOC file is probably not installed.
-	     So check in ../etc.  */
-	  lispstpcpy (stpcpy (name, sibling_etc), file);
-
-	  fd = emacs_open (name, O_RDONLY, 0);
-	}
-#endif
-      if (fd < 0)
-	{
-	  if (errno == EMFILE || errno == ENFILE)
-	    report_file_error ("Read error on documentation file", file);
-
-	  SAFE_FREE ();
-	  AUTO_STRING (cannot_open, "Cannot open doc string file \"");
-	  AUTO_STRING (quote_nl, "\"\n");
-	  return concat3 (cannot_open, file, quote_nl);
-	}
-    }
-  count = SPECPDL_INDEX ();
-  record_unwind_protect_int (close_file_unwind, fd);
-
-  /* Seek only to beginning of disk block.  */
-  /* Make sure we read at least 1024 bytes before `position'
-     so we can check the leading text for consistency.  */
-  offset = min (position, max (1024, position % (8 * 1024)));
-  if (TYPE_MAXIMUM (off_t) < position
-      || lseek (fd, position - offset, 0) < 0)
-    error ("Position %"pI"d out of range in doc string file \"%s\"",
-	   position, name);
-
-  /* Read the doc string into get_doc_string_buffer.
-     P points beyond the data just read.  */
-
-  p = get_doc_string_buffer;
-  while (1)
-    {
-      ptrdiff_t space_left = (get_doc_string_buffer_size - 1
-			      - (p - get_doc_string_buffer));
-      int nread;
-
-      /* Allocate or grow the buffer if we need to.  */
-      if (space_left <= 0)
-	{
-	  ptrdiff_t in_buffer = p - get_doc_string_buffer;
-	  get_doc_string_buffer
-	    = xpalloc (get_doc_string_buffer, &get_doc_string_buffer_size,
-		       16 * 1024, -1, 1);
-	  p = get_doc_string_buffer + in_buffer;
-	  space_left = (get_doc_string_buffer_size - 1
-			- (p - get_doc_string_buffer));
-	}
-
-      /* Read a disk block at a time.
-         If we read the same block last time, maybe skip this?  */
-      if (space_left > 1024 * 8)
-	space_left = 1024 * 8;
-      nread = emacs_read_quit (fd, p, space_left);
-      if (nread < 0)
-	report_file_error ("Read error on documentation file", file);
-      p[nread] = 0;
-      if (!nread)
-	break;
-      if (p == get_doc_string_buffer)
-	p1 = strchr (p + offset, '\037');
-      else
-	p1 = strchr (p, '\037');
-      if (p1)
-	{
-	  *p1 = 0;
-	  p = p1;
-	  break;
-	}
-      p += nread;
-    }
-  unbind_to (count, Qnil);
-  SAFE_FREE ();
-
-  /* Sanity checking.  */
-  if (CONSP (filepos))
-    {
-      int test = 1;
-      /* A dynamic docstring should be either at the very beginning of a "#@
-	 comment" or right after a dynamic docstring delimiter (in case we
-	 pack several such docstrings within the same comment).  */
-      if (get_doc_string_buffer[offset - test] != '\037')
-	{
-	  if (get_doc_string_buffer[offset - test++] != ' ')
-	    return Qnil;
-	  while (get_doc_string_buffer[offset - test] >= '0'
-		 && get_doc_string_buffer[offset - test] <= '9')
-	    test++;
-	  if (get_doc_string_buffer[offset - test++] != '@'
-	      || get_doc_string_buffer[offset - test] != '#')
-	    return Qnil;
-	}
-    }
-  else
-    {
-      int test = 1;
-      if (get_doc_string_buffer[offset - test++] != '\n')
-	return Qnil;
-      while (get_doc_string_buffer[offset - test] > ' ')
-	test++;
-      if (get_doc_string_buffer[offset - test] != '\037')
-	return Qnil;
-    }
-
-  /* Scan the text and perform quoting with ^A (char code 1).
-     ^A^A becomes ^A, ^A0 becomes a null char, and ^A_ becomes a ^_.  */
-  from = get_doc_string_buffer + offset;
-  to = get_doc_string_buffer + offset;
-  while (from != p)
-    {
-      if (*from == 1)
-	{
-	  int c;
-
-	  from++;
-	  c = *from++;
-	  if (c == 1)
-	    *to++ = c;
-	  else if (c == '0')
-	    *to++ = 0;
-	  else if (c == '_')
-	    *to++ = 037;
-	  else
-	    {
-	      unsigned char uc = c;
-	      error ("\
-Invalid data in documentation file -- %c followed by code %03o",
-		     1, uc);
-	    }
-	}
-      else
-	*to++ = *from++;
-    }
-
-  /* If DEFINITION, read from this buffer
-     the same way we would read bytes from a file.  */
-  if (definition)
-    {
-      read_bytecode_pointer = (unsigned char *) get_doc_string_buffer + offset;
-      return Fread (Qlambda);
-    }
-
-  if (unibyte)
-    return make_unibyte_string (get_doc_string_buffer + offset,
-				to - (get_doc_string_buffer + offset));
-  else
-    {
-      /* The data determines whether the string is multibyte.  */
-      ptrdiff_t nchars
-	= multibyte_chars_in_text (((unsigned char *) get_doc_string_buffer
-				    + offset),
-				   to - (get_doc_string_buffer + offset));
-      return make_string_from_bytes (get_doc_string_buffer + offset,
-				     nchars,
-				     to - (get_doc_string_buffer + offset));
-    }
-}
-
 /* Get a string from position FILEPOS and pass it through the Lisp reader.
    We use this for fetching the bytecode string and constants vector
    of a compiled function from the .elc file.  */
@@ -301,7 +49,9 @@ read_doc_string (Lisp_Object filepos)
   return get_doc_string (filepos, 0, 1);
 }
 
-static bool
+/* Reload the documentation pointers of FILE, or of the DOC file if FILE
+   is nil, after a lookup found them to be stale.  */
+bool
 reread_doc_file (Lisp_Object file)
 {
   if (NILP (file))
@@ -312,114 +62,6 @@ reread_doc_file (Lisp_Object file)
   return 1;
 }
 
-DEFUN ("documentation", Fdocumentation, Sdocumentation, 1, 2, 0,
-       doc: /* Return the documentation string of FUNCTION.
-Unless a non-nil second argument RAW is given, the
-string is passed through `substitute-command-keys'.  */)
-  (Lisp_Object function, Lisp_Object raw)
-{
-  Lisp_Object fun;
-  Lisp_Object funcar;
-  Lisp_Object doc;
-  bool try_reload = 1;
-
- documentation:
-
-  doc = Qnil;
-
-  if (SYMBOLP (function))
-    {
-      Lisp_Object tem = Fget (function, Qfunction_documentation);
-      if (!NILP (tem))
-	return Fdocumentation_property (function, Qfunction_documentation,
-					raw);
-    }
-
-  fun = Findirect_function (function, Qnil);
-  if (CONSP (fun) && EQ (XCAR (fun), Qmacro))
-    fun = XCDR (fun);
-  if (SUBRP (fun))
-    doc = make_number (XSUBR (fun)->doc);
-  else if (MODULE_FUNCTIONP (fun))
-    doc = XMODULE_FUNCTION (fun)->documentation;
-  else if (COMPILEDP (fun))
-    {
-      if (PVSIZE (fun) <= COMPILED_DOC_STRING)
-	return Qnil;
-      else
-	{
-	  Lisp_Object tem = AREF (fun, COMPILED_DOC_STRING);
-	  if (STRINGP (tem))
-	    doc = tem;
-	  else if (NATNUMP (tem) || CONSP (tem))
-	    doc = tem;
-	  else
-	    return Qnil;
-	}
-    }
-  else if (STRINGP (fun) || VECTORP (fun))
-    {
-      return build_string ("Keyboard macro.");
-    }
-  else if (CONSP (fun))
-    {
-      funcar = XCAR (fun);
-      if (!SYMBOLP (funcar))
-	xsignal1 (Qinvalid_function, fun);
-      else if (EQ (funcar, Qkeymap))
-	return build_string ("Prefix command (definition is a keymap associating keystrokes with commands).");
-      else if (EQ (funcar, Qlambda)
-	       || (EQ (funcar, Qclosure) && (fun = XCDR (fun), 1))
-	       || EQ (funcar, Qautoload))
-	{
-	  Lisp_Object tem1 = Fcdr (Fcdr (fun));
-	  Lisp_Object tem = Fcar (tem1);
-	  if (STRINGP (tem))
-	    doc = tem;
-	  /* Handle a doc reference--but these never come last
-	     in the function body, so reject them if they are last.  */
-	  else if ((NATNUMP (tem) || (CONSP (tem) && INTEGERP (XCDR (tem))))
-		   && !NILP (XCDR (tem1)))
-	    doc = tem;
-	  else
-	    return Qnil;
-	}
-      else
-	goto oops;
-    }
-  else
-    {
-    oops:
-      xsignal1 (Qinvalid_function, fun);
-    }
-
-  /* If DOC is 0, it's typically because of a dumped file missing
-     from the DOC file (bug in src/Makefile.in).  */
-  if (EQ (doc, make_number (0)))
-    doc = Qnil;
-  if (INTEGERP (doc) || CONSP (doc))
-    {
-      Lisp_Object tem;
-      tem = get_doc_string (doc, 0, 0);
-      if (NILP (tem) && try_reload)
-	{
-	  /* The file is newer, we need to reset the pointers.  */
-	  try_reload = reread_doc_file (Fcar_safe (doc));
-	  if (try_reload)
-	    {
-	      try_reload = 0;
-	      goto documentation;
-	    }
-	}
-      else
-	doc = tem;
-    }
-
-  if (NILP (raw))
-    doc = Fsubstitute_command_keys (doc);
-  return doc;
-}
-
 DEFUN ("documentation-property", Fdocumentation_property,
        Sdocumentation_property, 2, 3, 0,
        doc: /* Return the documentation string that is SYMBOL's PROP property.
@@ -1036,7 +678,6 @@ otherwise.  */);
 	       doc: /* If nil, a nil `text-quoting-style' is treated as `grave'.  */);
   /* Initialized by ‘main’.  */
 
-  defsubr (&Sdocumentation);
   defsubr (&Sdocumentation_property);
   defsubr (&Ssnarf_documentation);
   defsubr (&Ssubstitute_command_keys);
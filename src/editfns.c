@@ -3826,7 +3826,7 @@ width, and precision specifiers, as follows:
   %<field><flags><width><precision>character
 
 where field is [0-9]+ followed by a literal dollar "$", flags is
-[+ #-0]+, width is [0-9]+, and precision is a literal period "."
+[+ #-0,]+, width is [0-9]+, and precision is a literal period "."
 followed by [0-9]+.
 
 If a %-sequence is numbered with a field with positive value N, the
@@ -3847,6 +3847,10 @@ the precision is zero; for %g, it causes a decimal point to be
 included even if the the precision is zero, and also forces trailing
 zeros after the decimal point to be left in place.
 
+The , flag groups the digits before the decimal point of %d and %f
+sequences in thousands with commas, as in \"1,234,567\".  Use
+`format-number' to group digits as the current locale does.
+
 The width specifier supplies a lower limit for the length of the
 printed representation.  The padding, if any, normally goes on the
 left, but it goes on the right if the - flag is present.  The padding
@@ -4006,7 +4010,7 @@ styled_format (ptrdiff_t nargs, Lisp_Object *args, bool message,
 	     where
 
              field-number ::= [0-9]+ '$'
-	     flags ::= [-+0# ]+
+	     flags ::= [-+0#, ]+
 	     field-width ::= [0-9]+
 	     precision ::= '.' [0-9]*
 
@@ -4038,6 +4042,7 @@ styled_format (ptrdiff_t nargs, Lisp_Object *args, bool message,
 	  bool space_flag = false;
 	  bool sharp_flag = false;
 	  bool  zero_flag = false;
+	  bool comma_flag = false;
 
 	  for (; ; format++)
 	    {
@@ -4048,6 +4053,7 @@ styled_format (ptrdiff_t nargs, Lisp_Object *args, bool message,
 		case ' ': space_flag = true; continue;
 		case '#': sharp_flag = true; continue;
 		case '0':  zero_flag = true; continue;
+		case ',': comma_flag = true; continue;
 		}
 	      break;
 	    }
@@ -4055,6 +4061,7 @@ styled_format (ptrdiff_t nargs, Lisp_Object *args, bool message,
 	  /* Ignore flags when sprintf ignores them.  */
 	  space_flag &= ! plus_flag;
 	  zero_flag &= ! minus_flag;
+	  zero_flag &= ! comma_flag;
 
 	  num = str2num (format, &num_end);
 	  if (max_bufsize <= num)
@@ -4399,6 +4406,11 @@ styled_format (ptrdiff_t nargs, Lisp_Object *args, bool message,
 		  sprintf_bytes = sprintf (sprintf_buf, convspec, prec, x);
 		}
 
+	      if (comma_flag
+		  && (conversion == 'd' || conversion == 'i' || conversion == 'f'))
+		sprintf_bytes = group_digits (sprintf_buf, sprintf_bytes,
+					      sizeof sprintf_buf);
+
 	      /* Now the length of the formatted item is known, except it omits
 		 padding and excess precision.  Deal with excess precision
 		 first.  This happens only when the format specifies
@@ -1401,6 +1401,11 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
   /* Init buffer storage and default directory of main buffer.  */
   init_buffer (initialized);
 
+  /* Index the symbols that were dumped, so that looking them up is
+     quick.  The index is not part of the dump, so build it now.  */
+  if (initialized)
+    init_core_obarray (Vobarray);
+
   init_callproc_1 ();	/* Must precede init_cmdargs and init_sys_modes.  */
 
   /* Must precede init_lread.  */
@@ -1592,10 +1597,7 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
      it sets Voperating_system_release, which init_process_emacs uses.  */
   init_editfns (dumping);
 
-  /* These two call putenv.  */
-#ifdef HAVE_DBUS
-  init_dbusbind ();
-#endif
+  /* This calls putenv.  */
 #ifdef USE_GTK
   init_xterm ();
 #endif
@@ -1969,6 +1971,9 @@ all of which are called before Emacs is actually killed.  */
   waiting_for_input = 0;
   run_hook (Qkill_emacs_hook);
 
+  /* Don't leave files being written in the background half done.  */
+  lisp_data_finish_writes ();
+
 #ifdef HAVE_X_WINDOWS
   /* Transfer any clipboards we own to the clipboard manager.  */
   x_clipboard_manager_save_all ();
@@ -842,135 +842,6 @@ DEFUN ("internal-make-var-non-special", Fmake_var_non_special,
 }
 
 
-DEFUN ("let*", FletX, SletX, 1, UNEVALLED, 0,
-       doc: /* Bind variables according to VARLIST then eval BODY.
-The value of the last form in BODY is returned.
-Each element of VARLIST is a symbol (which is bound to nil)
-or a list (SYMBOL VALUEFORM) (which binds SYMBOL to the value of VALUEFORM).
-Each VALUEFORM can refer to the symbols already bound by this VARLIST.
-usage: (let* VARLIST BODY...)  */)
-  (Lisp_Object args)
-{
-  Lisp_Object var, val, elt, lexenv;
-  ptrdiff_t count = SPECPDL_INDEX ();
-
-  lexenv = Vinternal_interpreter_environment;
-
-  Lisp_Object varlist = XCAR (args);
-  while (CONSP (varlist))
-    {
-      maybe_quit ();
-
-      elt = XCAR (varlist);
-      varlist = XCDR (varlist);
-      if (SYMBOLP (elt))
-	{
-	  var = elt;
-	  val = Qnil;
-	}
-      else
-	{
-	  var = Fcar (elt);
-	  if (! NILP (Fcdr (XCDR (elt))))
-	    signal_error ("`let' bindings can have only one value-form", elt);
-	  val = eval_sub (Fcar (XCDR (elt)));
-	}
-
-      if (!NILP (lexenv) && SYMBOLP (var)
-	  && !XSYMBOL (var)->declared_special
-	  && NILP (Fmemq (var, Vinternal_interpreter_environment)))
-	/* Lexically bind VAR by adding it to the interpreter's binding
-	   alist.  */
-	{
-	  Lisp_Object newenv
-	    = Fcons (Fcons (var, val), Vinternal_interpreter_environment);
-	  if (EQ (Vinternal_interpreter_environment, lexenv))
-	    /* Save the old lexical environment on the specpdl stack,
-	       but only for the first lexical binding, since we'll never
-	       need to revert to one of the intermediate ones.  */
-	    specbind (Qinternal_interpreter_environment, newenv);
-	  else
-	    Vinternal_interpreter_environment = newenv;
-	}
-      else
-	specbind (var, val);
-    }
-  CHECK_LIST_END (varlist, XCAR (args));
-
-  val = Fprogn (XCDR (args));
-  return unbind_to (count, val);
-}
-
-DEFUN ("let", Flet, Slet, 1, UNEVALLED, 0,
-       doc: /* Bind variables according to VARLIST then eval BODY.
-The value of the last form in BODY is returned.
-Each element of VARLIST is a symbol (which is bound to nil)
-or a list (SYMBOL VALUEFORM) (which binds SYMBOL to the value of VALUEFORM).
-All the VALUEFORMs are evalled before any symbols are bound.
-usage: (let VARLIST BODY...)  */)
-  (Lisp_Object args)
-{
-  Lisp_Object *temps, tem, lexenv;
-  Lisp_Object elt, varlist;
-  ptrdiff_t count = SPECPDL_INDEX ();
-  ptrdiff_t argnum;
-  USE_SAFE_ALLOCA;
-
-  varlist = XCAR (args);
-  CHECK_LIST (varlist);
-
-  /* Make space to hold the values to give the bound variables.  */
-  EMACS_INT varlist_len = XFASTINT (Flength (varlist));
-  SAFE_ALLOCA_LISP (temps, varlist_len);
-  ptrdiff_t nvars = varlist_len;
-
-  /* Compute the values and store them in `temps'.  */
-
-  for (argnum = 0; argnum < nvars && CONSP (varlist); argnum++)
-    {
-      maybe_quit ();
-      elt = XCAR (varlist);
-      varlist = XCDR (varlist);
-      if (SYMBOLP (elt))
-	temps[argnum] = Qnil;
-      else if (! NILP (Fcdr (Fcdr (elt))))
-	signal_error ("`let' bindings can have only one value-form", elt);
-      else
-	temps[argnum] = eval_sub (Fcar (Fcdr (elt)));
-    }
-  nvars = argnum;
-
-  lexenv = Vinternal_interpreter_environment;
-
-  varlist = XCAR (args);
-  for (argnum = 0; argnum < nvars && CONSP (varlist); argnum++)
-    {
-      Lisp_Object var;
-
-      elt = XCAR (varlist);
-      varlist = XCDR (varlist);
-      var = SYMBOLP (elt) ? elt : Fcar (elt);
-      tem = temps[argnum];
-
-      if (!NILP (lexenv) && SYMBOLP (var)
-	  && !XSYMBOL (var)->declared_special
-	  && NILP (Fmemq (var, Vinternal_interpreter_environment)))
-	/* Lexically bind VAR by adding it to the lexenv alist.  */
-	lexenv = Fcons (Fcons (var, tem), lexenv);
-      else
-	/* Dynamically bind VAR.  */
-	specbind (var, tem);
-    }
-
-  if (!EQ (lexenv, Vinternal_interpreter_environment))
-    /* Instantiate a new lexical environment.  */
-    specbind (Qinternal_interpreter_environment, lexenv);
-
-  elt = Fprogn (XCDR (args));
-  SAFE_FREE ();
-  return unbind_to (count, elt);
-}
-
 DEFUN ("while", Fwhile, Swhile, 1, UNEVALLED, 0,
        doc: /* If TEST yields non-nil, eval BODY... and repeat.
 The order of execution is thus TEST, BODY, TEST, BODY and so on
@@ -991,89 +862,6 @@ usage: (while TEST BODY...)  */)
   return Qnil;
 }
 
-DEFUN ("macroexpand", Fmacroexpand, Smacroexpand, 1, 2, 0,
-       doc: /* Return result of expanding macros at top level of FORM.
-If FORM is not a macro call, it is returned unchanged.
-Otherwise, the macro is expanded and the expansion is considered
-in place of FORM.  When a non-macro-call results, it is returned.
-
-The second optional arg ENVIRONMENT specifies an environment of macro
-definitions to shadow the loaded ones for use in file byte-compilation.  */)
-  (Lisp_Object form, Lisp_Object environment)
-{
-  /* With cleanups from Hallvard Furuseth.  */
-  register Lisp_Object expander, sym, def, tem;
-
-  while (1)
-    {
-      /* Come back here each time we expand a macro call,
-	 in case it expands into another macro call.  */
-      if (!CONSP (form))
-	break;
-      /* Set SYM, give DEF and TEM right values in case SYM is not a symbol. */
-      def = sym = XCAR (form);
-      tem = Qnil;
-      /* Trace symbols aliases to other symbols
-	 until we get a symbol that is not an alias.  */
-      while (SYMBOLP (def))
-	{
-	  maybe_quit ();
-	  sym = def;
-	  tem = Fassq (sym, environment);
-	  if (NILP (tem))
-	    {
-	      def = XSYMBOL (sym)->function;
-	      if (!NILP (def))
-		continue;
-	    }
-	  break;
-	}
-      /* Right now TEM is the result from SYM in ENVIRONMENT,
-	 and if TEM is nil then DEF is SYM's function definition.  */
-      if (NILP (tem))
-	{
-	  /* SYM is not mentioned in ENVIRONMENT.
-	     Look at its function definition.  */
-	  def = Fautoload_do_load (def, sym, Qmacro);
-	  if (!CONSP (def))
-	    /* Not defined or definition not suitable.  */
-	    break;
-	  if (!EQ (XCAR (def), Qmacro))
-	    break;
-	  else expander = XCDR (def);
-	}
-      else
-	{
-	  expander = XCDR (tem);
-	  if (NILP (expander))
-	    break;
-	}
-      {
-	Lisp_Object newform = apply1 (expander, XCDR (form));
-	if (EQ (form, newform))
-	  break;
-	else
-	  form = newform;
-      }
-    }
-  return form;
-}
-
-DEFUN ("catch", Fcatch, Scatch, 1, UNEVALLED, 0,
-       doc: /* Eval BODY allowing nonlocal exits using `throw'.
-TAG is evalled to get the tag to use; it must not be nil.
-
-Then the BODY is executed.
-Within BODY, a call to `throw' with the same TAG exits BODY and this `catch'.
-If no throw happens, `catch' returns the value of the last BODY form.
-If a throw happens, it specifies the value to return from `catch'.
-usage: (catch TAG BODY...)  */)
-  (Lisp_Object args)
-{
-  Lisp_Object tag = eval_sub (XCAR (args));
-  return internal_catch (tag, Fprogn, XCDR (args));
-}
-
 /* Assert that E is true, but do not evaluate E.  Use this instead of
    eassert (E) when E contains variables that might be clobbered by a
    longjmp.  */
@@ -1124,7 +912,7 @@ internal_catch (Lisp_Object tag,
 
    This is used for correct unwinding in Fthrow and Fsignal.  */
 
-static _Noreturn void
+_Noreturn void
 unwind_to_catch (struct handler *catch, Lisp_Object value)
 {
   bool last_time;
@@ -1156,26 +944,6 @@ unwind_to_catch (struct handler *catch, Lisp_Object value)
   sys_longjmp (catch->jmp, 1);
 }
 
-DEFUN ("throw", Fthrow, Sthrow, 2, 2, 0,
-       doc: /* Throw to the catch for TAG and return VALUE from it.
-Both TAG and VALUE are evalled.  */
-       attributes: noreturn)
-  (register Lisp_Object tag, Lisp_Object value)
-{
-  struct handler *c;
-
-  if (!NILP (tag))
-    for (c = handlerlist; c; c = c->next)
-      {
-	if (c->type == CATCHER_ALL)
-          unwind_to_catch (c, Fcons (tag, value));
-	if (c->type == CATCHER && EQ (c->tag_or_ch, tag))
-	  unwind_to_catch (c, value);
-      }
-  xsignal2 (Qno_catch, tag, value);
-}
-
-
 DEFUN ("unwind-protect", Funwind_protect, Sunwind_protect, 1, UNEVALLED, 0,
        doc: /* Do BODYFORM, protecting with UNWINDFORMS.
 If BODYFORM completes normally, its value is returned
@@ -1452,7 +1220,6 @@ push_handler_nosignal (Lisp_Object tag_ch_val, enum handlertype handlertype)
 }
 
 
-static Lisp_Object signal_or_quit (Lisp_Object, Lisp_Object, bool);
 static Lisp_Object find_handler_clause (Lisp_Object, Lisp_Object);
 static bool maybe_call_debugger (Lisp_Object conditions, Lisp_Object sig,
 				 Lisp_Object data);
@@ -1494,27 +1261,6 @@ maybe_quit (void)
     process_pending_signals ();
 }
 
-DEFUN ("signal", Fsignal, Ssignal, 2, 2, 0,
-       doc: /* Signal an error.  Args are ERROR-SYMBOL and associated DATA.
-This function does not return.
-
-An error symbol is a symbol with an `error-conditions' property
-that is a list of condition names.
-A handler for any of those names will get to handle this signal.
-The symbol `error' should normally be one of them.
-
-DATA should be a list.  Its elements are printed as part of the error message.
-See Info anchor `(elisp)Definition of signal' for some details on how this
-error message is constructed.
-If the signal is handled, DATA is made available to the handler.
-See also the function `condition-case'.  */
-       attributes: noreturn)
-  (Lisp_Object error_symbol, Lisp_Object data)
-{
-  signal_or_quit (error_symbol, data, false);
-  eassume (false);
-}
-
 /* Quit, in response to a keyboard quit request.  */
 Lisp_Object
 quit (void)
@@ -1527,7 +1273,7 @@ quit (void)
    Qquit and DATA should be Qnil, and this function may return.
    Otherwise this function is like Fsignal and does not return.  */
 
-static Lisp_Object
+Lisp_Object
 signal_or_quit (Lisp_Object error_symbol, Lisp_Object data, bool keyboard_quit)
 {
   /* When memory is full, ERROR-SYMBOL is nil,
@@ -1913,40 +1659,6 @@ then strings and vectors are not accepted.  */)
     return Qnil;
 }
 
-DEFUN ("autoload", Fautoload, Sautoload, 2, 5, 0,
-       doc: /* Define FUNCTION to autoload from FILE.
-FUNCTION is a symbol; FILE is a file name string to pass to `load'.
-Third arg DOCSTRING is documentation for the function.
-Fourth arg INTERACTIVE if non-nil says function can be called interactively.
-Fifth arg TYPE indicates the type of the object:
-   nil or omitted says FUNCTION is a function,
-   `keymap' says FUNCTION is really a keymap, and
-   `macro' or t says FUNCTION is really a macro.
-Third through fifth args give info about the real definition.
-They default to nil.
-If FUNCTION is already defined other than as an autoload,
-this does nothing and returns nil.  */)
-  (Lisp_Object function, Lisp_Object file, Lisp_Object docstring, Lisp_Object interactive, Lisp_Object type)
-{
-  CHECK_SYMBOL (function);
-  CHECK_STRING (file);
-
-  /* If function is defined and not as an autoload, don't override.  */
-  if (!NILP (XSYMBOL (function)->function)
-      && !AUTOLOADP (XSYMBOL (function)->function))
-    return Qnil;
-
-  if (!NILP (Vpurify_flag) && EQ (docstring, make_number (0)))
-    /* `read1' in lread.c has found the docstring starting with "\
-       and assumed the docstring will be provided by Snarf-documentation, so it
-       passed us 0 instead.  But that leads to accidental sharing in purecopy's
-       hash-consing, so we use a (hopefully) unique integer instead.  */
-    docstring = make_number (XHASH (function));
-  return Fdefalias (function,
-		    list5 (Qautoload, file, docstring, interactive, type),
-		    Qnil);
-}
-
 void
 un_autoload (Lisp_Object oldqueue)
 {
@@ -1969,75 +1681,6 @@ un_autoload (Lisp_Object oldqueue)
     }
 }
 
-/* Load an autoloaded function.
-   FUNNAME is the symbol which is the function's name.
-   FUNDEF is the autoload definition (a list).  */
-
-DEFUN ("autoload-do-load", Fautoload_do_load, Sautoload_do_load, 1, 3, 0,
-       doc: /* Load FUNDEF which should be an autoload.
-If non-nil, FUNNAME should be the symbol whose function value is FUNDEF,
-in which case the function returns the new autoloaded function value.
-If equal to `macro', MACRO-ONLY specifies that FUNDEF should only be loaded if
-it defines a macro.  */)
-  (Lisp_Object fundef, Lisp_Object funname, Lisp_Object macro_only)
-{
-  ptrdiff_t count = SPECPDL_INDEX ();
-
-  if (!CONSP (fundef) || !EQ (Qautoload, XCAR (fundef)))
-    return fundef;
-
-  if (EQ (macro_only, Qmacro))
-    {
-      Lisp_Object kind = Fnth (make_number (4), fundef);
-      if (! (EQ (kind, Qt) || EQ (kind, Qmacro)))
-	return fundef;
-    }
-
-  /* This is to make sure that loadup.el gives a clear picture
-     of what files are preloaded and when.  */
-  if (! NILP (Vpurify_flag))
-    error ("Attempt to autoload %s while preparing to dump",
-	   SDATA (SYMBOL_NAME (funname)));
-
-  CHECK_SYMBOL (funname);
-
-  /* Preserve the match data.  */
-  record_unwind_save_match_data ();
-
-  /* If autoloading gets an error (which includes the error of failing
-     to define the function being called), we use Vautoload_queue
-     to undo function definitions and `provide' calls made by
-     the function.  We do this in the specific case of autoloading
-     because autoloading is not an explicit request "load this file",
-     but rather a request to "call this function".
-
-     The value saved here is to be restored into Vautoload_queue.  */
-  record_unwind_protect (un_autoload, Vautoload_queue);
-  Vautoload_queue = Qt;
-  /* If `macro_only', assume this autoload to be a "best-effort",
-     so don't signal an error if autoloading fails.  */
-  Fload (Fcar (Fcdr (fundef)), macro_only, Qt, Qnil, Qt);
-
-  /* Once loading finishes, don't undo it.  */
-  Vautoload_queue = Qt;
-  unbind_to (count, Qnil);
-
-  if (NILP (funname))
-    return Qnil;
-  else
-    {
-      Lisp_Object fun = Findirect_function (funname, Qnil);
-
-      if (!NILP (Fequal (fun, fundef)))
-	error ("Autoloading file %s failed to define function %s",
-	       SDATA (Fcar (Fcar (Vload_history))),
-	       SDATA (SYMBOL_NAME (funname)));
-      else
-	return fun;
-    }
-}
-
-
 DEFUN ("eval", Feval, Seval, 1, 2, 0,
        doc: /* Evaluate FORM and return its value.
 If LEXICAL is t, evaluate using lexical scoping.
@@ -3223,7 +2866,7 @@ let_shadows_buffer_binding_p (struct Lisp_Symbol *symbol)
   return 0;
 }
 
-static void
+void
 do_specbind (struct Lisp_Symbol *sym, union specbinding *bind,
              Lisp_Object value, enum Set_Internal_Bind bindflag)
 {
@@ -3253,83 +2896,24 @@ do_specbind (struct Lisp_Symbol *sym, union specbinding *bind,
     }
 }
 
-/* `specpdl_ptr' describes which variable is
-   let-bound, so it can be properly undone when we unbind_to.
-   It can be either a plain SPECPDL_LET or a SPECPDL_LET_LOCAL/DEFAULT.
-   - SYMBOL is the variable being bound.  Note that it should not be
-     aliased (i.e. when let-binding V1 that's aliased to V2, we want
-     to record V2 here).
-   - WHERE tells us in which buffer the binding took place.
-     This is used for SPECPDL_LET_LOCAL bindings (i.e. bindings to a
-     buffer-local variable) as well as for SPECPDL_LET_DEFAULT bindings,
-     i.e. bindings to the default value of a variable which can be
-     buffer-local.  */
-
-void
-specbind (Lisp_Object symbol, Lisp_Object value)
-{
-  struct Lisp_Symbol *sym;
-
-  CHECK_SYMBOL (symbol);
-  sym = XSYMBOL (symbol);
-
- start:
-  switch (sym->redirect)
-    {
-    case SYMBOL_VARALIAS:
-      sym = indirect_variable (sym); XSETSYMBOL (symbol, sym); goto start;
-    case SYMBOL_PLAINVAL:
-      /* The most common case is that of a non-constant symbol with a
-	 trivial value.  Make that as fast as we can.  */
-      specpdl_ptr->let.kind = SPECPDL_LET;
-      specpdl_ptr->let.symbol = symbol;
-      specpdl_ptr->let.old_value = SYMBOL_VAL (sym);
-      specpdl_ptr->let.saved_value = Qnil;
-      grow_specpdl ();
-      do_specbind (sym, specpdl_ptr - 1, value, SET_INTERNAL_BIND);
-      break;
-    case SYMBOL_LOCALIZED:
-    case SYMBOL_FORWARDED:
-      {
-	Lisp_Object ovalue = find_symbol_value (symbol);
-	specpdl_ptr->let.kind = SPECPDL_LET_LOCAL;
-	specpdl_ptr->let.symbol = symbol;
-	specpdl_ptr->let.old_value = ovalue;
-	specpdl_ptr->let.where = Fcurrent_buffer ();
-	specpdl_ptr->let.saved_value = Qnil;
-
-	eassert (sym->redirect != SYMBOL_LOCALIZED
-		 || (EQ (SYMBOL_BLV (sym)->where, Fcurrent_buffer ())));
-
-	if (sym->redirect == SYMBOL_LOCALIZED)
-	  {
-	    if (!blv_found (SYMBOL_BLV (sym)))
-	      specpdl_ptr->let.kind = SPECPDL_LET_DEFAULT;
-	  }
-	else if (BUFFER_OBJFWDP (SYMBOL_FWD (sym)))
-	  {
-	    /* If SYMBOL is a per-buffer variable which doesn't have a
-	       buffer-local value here, make the `let' change the global
-	       value by changing the value of SYMBOL in all buffers not
-	       having their own value.  This is consistent with what
-	       happens with other buffer-local variables.  */
-	    if (NILP (Flocal_variable_p (symbol, Qnil)))
-	      {
-		specpdl_ptr->let.kind = SPECPDL_LET_DEFAULT;
-		grow_specpdl ();
-                do_specbind (sym, specpdl_ptr - 1, value, SET_INTERNAL_BIND);
-		return;
-	      }
-	  }
-	else
-	  specpdl_ptr->let.kind = SPECPDL_LET;
+/* Push a let-binding record of kind KIND for SYMBOL onto the specpdl
+   stack and return it.  The caller (`specbind', now in Rust) decides
+   which kind of binding is needed and then calls `do_specbind' on the
+   returned record.  See the comment before `specbind' in
+   rust_src/src/eval_call.rs for the meaning of the fields.  */
 
-	grow_specpdl ();
-        do_specbind (sym, specpdl_ptr - 1, value, SET_INTERNAL_BIND);
-	break;
-      }
-    default: emacs_abort ();
-    }
+union specbinding *
+record_specbind (enum specbind_tag kind, Lisp_Object symbol,
+                 Lisp_Object old_value, Lisp_Object where)
+{
+  eassert (kind >= SPECPDL_LET);
+  specpdl_ptr->let.kind = kind;
+  specpdl_ptr->let.symbol = symbol;
+  specpdl_ptr->let.old_value = old_value;
+  specpdl_ptr->let.where = where;
+  specpdl_ptr->let.saved_value = Qnil;
+  grow_specpdl ();
+  return specpdl_ptr - 1;
 }
 
 /* Push unwind-protect entries of various types.  */
@@ -3590,6 +3174,8 @@ backtrace_frame_apply (Lisp_Object function, union specbinding *pdl)
   Lisp_Object flags = Qnil;
   if (backtrace_debug_on_exit (pdl))
     flags = Fcons (QCdebug_on_exit, Fcons (Qt, Qnil));
+  if (!NILP (Fsubr_rust_p (indirect_function (backtrace_function (pdl)))))
+    flags = Fcons (QCrust, Fcons (Qt, flags));
 
   if (backtrace_nargs (pdl) == UNEVALLED)
     return call4 (function, Qnil, backtrace_function (pdl), *backtrace_args (pdl), flags);
@@ -3624,7 +3210,8 @@ EVALD is nil and ARGS is a list of forms.  If a frame has evaluated
 its arguments and called its function already, EVALD is t and ARGS is
 a list of values.
 FLAGS is a plist of properties of the current frame: currently, the
-only supported property is :debug-on-exit.  `mapbacktrace' always
+supported properties are :debug-on-exit, and :rust, which is non-nil
+if FUNC is a primitive implemented in Rust.  `mapbacktrace' always
 returns nil.  */)
      (Lisp_Object function, Lisp_Object base)
 {
@@ -3933,6 +3520,21 @@ You can safely make it considerably larger than its default value,
 if that proves inconveniently small.  However, if you increase it too far,
 Emacs could overflow the real C stack, and crash.  */);
 
+  DEFVAR_INT ("max-macroexpand-depth", max_macroexpand_depth,
+	      doc: /* Limit on the depth of macro expansion before error.
+`macroexpand' signals an error when a form keeps expanding into another
+macro call more than this many times, and `macroexpand-all' signals an
+error when forms are nested more than this many levels deep.  This
+catches macros whose expansion never terminates.
+A value of zero or less means there is no limit.  */);
+  max_macroexpand_depth = 1600;
+
+  DEFVAR_LISP ("macroexpand-all-environment", Vmacroexpand_all_environment,
+	       doc: /* The macro environment of the current `macroexpand-all'.
+It is bound by `macroexpand-all' to its ENVIRONMENT argument, and may be
+extended by macros that define local macros, such as `cl-macrolet'.  */);
+  Vmacroexpand_all_environment = Qnil;
+
   DEFVAR_LISP ("quit-flag", Vquit_flag,
 	       doc: /* Non-nil causes `eval' to abort, unless `inhibit-quit' is non-nil.
 If the value is t, that means do an ordinary quit.
@@ -3968,6 +3570,21 @@ before making `inhibit-quit' nil.  */);
   DEFSYM (QCdocumentation, ":documentation");
   DEFSYM (Qdebug, "debug");
 
+  /* Used by macroexpand-all in macroexp.rs.  */
+  DEFSYM (Qcond, "cond");
+  DEFSYM (Qcondition_case, "condition-case");
+  DEFSYM (Qdefvar, "defvar");
+  DEFSYM (Qdefconst, "defconst");
+  DEFSYM (Qmapcar, "mapcar");
+  DEFSYM (Qmapc, "mapc");
+  DEFSYM (Qmapatoms, "mapatoms");
+  DEFSYM (Qmapconcat, "mapconcat");
+  DEFSYM (Qsort, "sort");
+  DEFSYM (Qbackquote_listX, "backquote-list*");
+  DEFSYM (Qcompiler_macro, "compiler-macro");
+  DEFSYM (Qbyte_obsolete_info, "byte-obsolete-info");
+  DEFSYM (Qmacroexpand_all_environment, "macroexpand-all-environment");
+
   DEFVAR_LISP ("inhibit-debugger", Vinhibit_debugger,
 	       doc: /* Non-nil means never enter the debugger.
 Normally set while the debugger is already active, to avoid recursive
@@ -4060,6 +3677,12 @@ alist of active lexical bindings.  */);
 
   staticpro (&Vautoload_queue);
   Vautoload_queue = Qnil;
+
+  DEFVAR_LISP ("autoload--in-progress", Vautoload_in_progress,
+	       doc: /* List of files currently being loaded by `autoload-do-load'.
+Used to detect an autoload that recursively triggers itself.  */);
+  Vautoload_in_progress = Qnil;
+
   staticpro (&Vsignaling_function);
   Vsignaling_function = Qnil;
 
@@ -4082,18 +3705,10 @@ alist of active lexical bindings.  */);
   DEFSYM (Qdefvaralias, "defvaralias");
   defsubr (&Sdefconst);
   defsubr (&Smake_var_non_special);
-  defsubr (&Slet);
-  defsubr (&SletX);
   defsubr (&Swhile);
-  defsubr (&Smacroexpand);
-  defsubr (&Scatch);
-  defsubr (&Sthrow);
   defsubr (&Sunwind_protect);
   defsubr (&Scondition_case);
-  defsubr (&Ssignal);
   defsubr (&Scommandp);
-  defsubr (&Sautoload);
-  defsubr (&Sautoload_do_load);
   defsubr (&Seval);
   defsubr (&Sapply);
   defsubr (&Sfuncall);
@@ -4106,6 +3721,7 @@ alist of active lexical bindings.  */);
   defsubr (&Sfetch_bytecode);
   defsubr (&Sbacktrace_debug);
   DEFSYM (QCdebug_on_exit, ":debug-on-exit");
+  DEFSYM (QCrust, ":rust");
   defsubr (&Smapbacktrace);
   defsubr (&Sbacktrace_frame_internal);
   defsubr (&Sbacktrace_eval);
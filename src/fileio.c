@@ -4490,6 +4490,16 @@ by calling `format-decode', which see.  */)
   if (read_quit)
     quit ();
 
+  /* Look for text that would make redisplay slow before the buffer
+     is displayed.  */
+  if (inserted > 0)
+    {
+      if (NILP (replace))
+	classify_inserted_text (PT, PT + inserted, visit);
+      else
+	classify_inserted_text (BEGV, ZV, visit);
+    }
+
   /* Retval needs to be dealt with in all cases consistently.  */
   if (NILP (val))
     val = list2 (orig_filename, make_number (inserted));
@@ -5940,6 +5950,38 @@ If `insert-file-contents' is intercepted by a handler from
 functions in `after-insert-file-functions' if appropriate.  */);
   Vafter_insert_file_functions = Qnil;
 
+  DEFVAR_LISP ("file-content-long-line-threshold",
+	       Vfile_content_long_line_threshold,
+	       doc: /* Length from which `insert-file-contents' considers a line long.
+Text inserted by `insert-file-contents' with lines of at least this
+many characters is classified, as described for
+`file-content-classify-region'.  If nil, inserted text is not
+classified.  */);
+  Vfile_content_long_line_threshold = make_number (10000);
+
+  DEFVAR_LISP ("file-content-classification", Vfile_content_classification,
+	       doc: /* Classification of the text inserted by `insert-file-contents'.
+The value is `binary', `minified', `long-lines' or nil; see
+`file-content-classify-region'.  It is set when the text of a file
+visited by the buffer has been inserted, or when inserted text is
+found to be troublesome.  It survives changes of major mode, so
+that the mode can consult it.  */);
+  Vfile_content_classification = Qnil;
+  DEFSYM (Qfile_content_classification, "file-content-classification");
+  Fmake_variable_buffer_local (Qfile_content_classification);
+  Fput (Qfile_content_classification, Qpermanent_local, Qt);
+
+  DEFVAR_LISP ("file-content-classification-functions",
+	       Vfile_content_classification_functions,
+	       doc: /* Functions to call when troublesome file contents are inserted.
+`insert-file-contents' runs these functions, with point at the start
+of the inserted text, when that text is classified as binary, minified
+or having long lines.  Each is called with one argument, the
+classification, as for `file-content-classification'.  Functions can
+use it to turn off features that are slow on such text before the
+buffer is displayed.  */);
+  Vfile_content_classification_functions = Qnil;
+
   DEFVAR_LISP ("write-region-annotate-functions", Vwrite_region_annotate_functions,
 	       doc: /* A list of functions to be called at the start of `write-region'.
 Each is passed two arguments, START and END as for `write-region'.
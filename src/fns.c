@@ -1554,6 +1554,7 @@ ARRAY is a vector, string, char-table, or bool-vector.  */)
       CHECK_CHARACTER (item);
       charval = XFASTINT (item);
       size = SCHARS (array);
+      string_text_stamp++;
       if (STRING_MULTIBYTE (array))
 	{
 	  unsigned char str[MAX_MULTIBYTE_LENGTH];
@@ -1586,6 +1587,7 @@ This makes STRING unibyte and may change its length.  */)
   ptrdiff_t len;
   CHECK_STRING (string);
   len = SBYTES (string);
+  string_text_stamp++;
   memset (SDATA (string), 0, len);
   STRING_SET_CHARS (string, len);
   STRING_SET_UNIBYTE (string);
@@ -2548,6 +2550,25 @@ allocate_hash_table (void)
 #define INDEX_SIZE_BOUND \
   ((ptrdiff_t) min (MOST_POSITIVE_FIXNUM, PTRDIFF_MAX / word_size))
 
+/* Source of the stamps stored in the `key_stamp' slot of hash tables.  */
+
+static EMACS_UINT hash_table_key_stamp;
+
+/* Incremented whenever the text of a string is changed in place, by
+   `aset', `fillarray' or `clear-string'.  The key stamp of a hash table
+   does not change when one of its string keys does, so caches derived
+   from the names of keys check this too.  */
+
+EMACS_UINT string_text_stamp;
+
+/* Record that the set of keys in hash table H has changed.  */
+
+static void
+hash_table_keys_changed (struct Lisp_Hash_Table *h)
+{
+  h->key_stamp = ++hash_table_key_stamp;
+}
+
 /* Create and initialize a new hash table.
 
    TEST specifies the test the hash table will use to compare keys.
@@ -2611,6 +2632,7 @@ make_hash_table (struct hash_table_test test, EMACS_INT size,
   h->rehash_threshold = rehash_threshold;
   h->rehash_size = rehash_size;
   h->count = 0;
+  hash_table_keys_changed (h);
   h->key_and_value = Fmake_vector (make_number (2 * size), Qnil);
   h->hash = Fmake_vector (make_number (size), Qnil);
   h->next = Fmake_vector (make_number (size), make_number (-1));
@@ -2762,6 +2784,7 @@ hash_put (struct Lisp_Hash_Table *h, Lisp_Object key, Lisp_Object value,
   /* Increment count after resizing because resizing may fail.  */
   maybe_resize_hash_table (h);
   h->count++;
+  hash_table_keys_changed (h);
 
   /* Store key/value in the key_and_value vector.  */
   i = h->next_free;
@@ -2814,6 +2837,7 @@ hash_remove_from_table (struct Lisp_Hash_Table *h, Lisp_Object key)
 	  h->next_free = i;
 	  h->count--;
 	  eassert (h->count >= 0);
+	  hash_table_keys_changed (h);
 	  break;
 	}
 
@@ -2844,6 +2868,7 @@ hash_clear (struct Lisp_Hash_Table *h)
 
       h->next_free = 0;
       h->count = 0;
+      hash_table_keys_changed (h);
     }
 }
 
@@ -2909,6 +2934,7 @@ sweep_weak_table (struct Lisp_Hash_Table *h, bool remove_entries_p)
 		  set_hash_hash_slot (h, i, Qnil);
 
 		  h->count--;
+		  hash_table_keys_changed (h);
 		}
 	      else
 		{
@@ -1887,6 +1887,9 @@ delete_frame (Lisp_Object frame, Lisp_Object force)
     x_clear_frame_selections (f);
 #endif
 
+  /* Forget the images shown on this frame by `image-tty-place'.  */
+  tty_images_forget_frame (f);
+
   /* Free glyphs.
      This function must be called before the window tree of the
      frame is deleted because windows contain dynamically allocated
@@ -260,6 +260,8 @@ adjust_markers_for_delete (ptrdiff_t from, ptrdiff_t from_byte,
 	  m->bytepos = from_byte;
 	}
     }
+
+  adjust_anchors (from, to - from, 0, false);
 }
 
 
@@ -281,6 +283,7 @@ adjust_markers_for_insert (ptrdiff_t from, ptrdiff_t from_byte,
   ptrdiff_t nbytes = to_byte - from_byte;
 
   adjust_suspend_auto_hscroll (from, to);
+  adjust_anchors (from, 0, nchars, before_markers);
   for (m = BUF_MARKERS (current_buffer); m; m = m->next)
     {
       eassert (m->bytepos >= m->charpos
@@ -361,6 +364,7 @@ adjust_markers_for_replace (ptrdiff_t from, ptrdiff_t from_byte,
 	}
     }
 
+  adjust_anchors (from, old_chars, new_chars, false);
   check_markers ();
 }
 
@@ -2170,6 +2174,10 @@ signal_after_change (ptrdiff_t charpos, ptrdiff_t lendel, ptrdiff_t lenins)
   ptrdiff_t count = SPECPDL_INDEX ();
   struct rvoe_arg rvoe_arg;
 
+  /* Keep the journal used by `buffer-chars-modified-tick-range'
+     whether or not the hooks run.  */
+  record_buffer_change (charpos, lendel, lenins);
+
   if (inhibit_modification_hooks)
     return;
 
@@ -2348,5 +2356,14 @@ as well as hooks attached to text properties and overlays.  */);
 
   DEFSYM (Qregion_extract_function, "region-extract-function");
 
+  DEFVAR_LISP ("buffer-recent-changes-text", Vbuffer_recent_changes_text,
+	       doc: /* Non-nil means record the text inserted by each change.
+The text is then part of the changes returned by `buffer-recent-changes',
+unless it is very long.  This variable automatically becomes buffer-local
+when set, so that only the buffers that need it pay for it.  */);
+  Vbuffer_recent_changes_text = Qnil;
+  DEFSYM (Qbuffer_recent_changes_text, "buffer-recent-changes-text");
+  Fmake_variable_buffer_local (Qbuffer_recent_changes_text);
+
   defsubr (&Scombine_after_change_execute);
 }
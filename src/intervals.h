@@ -267,6 +267,21 @@ extern bool get_property_and_range (ptrdiff_t, Lisp_Object, Lisp_Object *,
 extern Lisp_Object get_local_map (ptrdiff_t, struct buffer *, Lisp_Object);
 extern INTERVAL update_interval (INTERVAL, ptrdiff_t);
 extern void set_intervals_multibyte (bool);
+
+/* A range of text and the properties to add to it, for
+   add_text_property_runs.  This must match text_property_run in
+   remacs-sys.  */
+struct text_property_run
+{
+  ptrdiff_t start;
+  ptrdiff_t end;
+  Lisp_Object properties;
+};
+
+extern bool add_text_property_runs (Lisp_Object, ptrdiff_t,
+				    struct text_property_run *);
+extern Lisp_Object validate_plist (Lisp_Object);
+
 extern INTERVAL validate_interval_range (Lisp_Object, Lisp_Object *,
                                          Lisp_Object *, bool);
 extern INTERVAL interval_of (ptrdiff_t, Lisp_Object);
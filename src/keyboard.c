@@ -1447,6 +1447,7 @@ command_loop_1 (void)
       }
       Vthis_command = cmd;
       Vreal_this_command = cmd;
+      transient_maps_pre_command ();
       safe_run_hooks (Qpre_command_hook);
 
       already_adjusted = 0;
@@ -3673,6 +3674,24 @@ kbd_buffer_store_help_event (Lisp_Object frame, Lisp_Object help)
   kbd_buffer_store_event (&event);
 }
 
+/* Store a D-Bus event whose argument is ARG in the input queue.
+   dbus.rs calls this for each message it reads; without D-Bus
+   support, the event is dropped.  */
+
+void
+kbd_buffer_store_dbus_event (Lisp_Object arg)
+{
+#ifdef HAVE_DBUS
+  struct input_event event;
+
+  EVENT_INIT (event);
+  event.kind = DBUS_EVENT;
+  event.frame_or_window = Qnil;
+  event.arg = arg;
+  kbd_buffer_store_event (&event);
+#endif
+}
+
 
 /* Discard any mouse events in the event buffer by setting them to
    NO_EVENT.  */
@@ -6079,6 +6098,25 @@ make_lispy_event (struct input_event *event)
       }
 #endif /* HAVE_INOTIFY || HAVE_KQUEUE || HAVE_GFILENOTIFY */
 
+    case TOUCH_GESTURE_EVENT:
+      {
+	struct frame *f = XFRAME (event->frame_or_window);
+	Lisp_Object touch;
+
+	/* Ignore gestures on frames that have been deleted.  */
+	if (! FRAME_LIVE_P (f))
+	  return Qnil;
+
+	/* TOUCH is (KIND . PLIST).  */
+	touch = touch_event_lisp (event->code);
+	if (NILP (touch))
+	  return Qnil;
+	return list3 (apply_modifiers (event->modifiers, XCAR (touch)),
+		      make_lispy_position (f, event->x, event->y,
+					   event->timestamp),
+		      XCDR (touch));
+      }
+
     case CONFIG_CHANGED_EVENT:
 	return list3 (Qconfig_changed_event,
 		      event->arg, event->frame_or_window);
@@ -8682,7 +8720,8 @@ active_maps (Lisp_Object first_event)
 }
 
 /* Structure used to keep track of partial application of key remapping
-   such as Vfunction_key_map and Vkey_translation_map.  */
+   such as Vfunction_key_map and Vkey_translation_map.  This must match
+   KeyRemap in key_translation.rs.  */
 typedef struct keyremap
 {
   /* This is the map originally specified for this use.  */
@@ -8697,113 +8736,11 @@ typedef struct keyremap
   int start, end;
 } keyremap;
 
-/* Lookup KEY in MAP.
-   MAP is a keymap mapping keys to key vectors or functions.
-   If the mapping is a function and DO_FUNCALL is true,
-   the function is called with PROMPT as parameter and its return
-   value is used as the return value of this function (after checking
-   that it is indeed a vector).  */
-
-static Lisp_Object
-access_keymap_keyremap (Lisp_Object map, Lisp_Object key, Lisp_Object prompt,
-			bool do_funcall)
-{
-  Lisp_Object next;
-
-  next = access_keymap (map, key, 1, 0, 1);
-
-  /* Handle a symbol whose function definition is a keymap
-     or an array.  */
-  if (SYMBOLP (next) && !NILP (Ffboundp (next))
-      && (ARRAYP (XSYMBOL (next)->function)
-	  || KEYMAPP (XSYMBOL (next)->function)))
-    next = Fautoload_do_load (XSYMBOL (next)->function, next, Qnil);
-
-  /* If the keymap gives a function, not an
-     array, then call the function with one arg and use
-     its value instead.  */
-  if (do_funcall && FUNCTIONP (next))
-    {
-      Lisp_Object tem;
-      tem = next;
-
-      next = call1 (next, prompt);
-      /* If the function returned something invalid,
-	 barf--don't ignore it.  */
-      if (! (NILP (next) || VECTORP (next) || STRINGP (next)))
-	error ("Function %s returns invalid key sequence",
-	       SSDATA (SYMBOL_NAME (tem)));
-    }
-  return next;
-}
-
-/* Do one step of the key remapping used for function-key-map and
-   key-translation-map:
-   KEYBUF is the buffer holding the input events.
-   BUFSIZE is its maximum size.
-   FKEY is a pointer to the keyremap structure to use.
-   INPUT is the index of the last element in KEYBUF.
-   DOIT if true says that the remapping can actually take place.
-   DIFF is used to return the number of keys added/removed by the remapping.
-   PARENT is the root of the keymap.
-   PROMPT is the prompt to use if the remapping happens through a function.
-   Return true if the remapping actually took place.  */
-
-static bool
-keyremap_step (Lisp_Object *keybuf, int bufsize, volatile keyremap *fkey,
-	       int input, bool doit, int *diff, Lisp_Object prompt)
-{
-  Lisp_Object next, key;
-
-  key = keybuf[fkey->end++];
-
-  if (KEYMAPP (fkey->parent))
-    next = access_keymap_keyremap (fkey->map, key, prompt, doit);
-  else
-    next = Qnil;
-
-  /* If keybuf[fkey->start..fkey->end] is bound in the
-     map and we're in a position to do the key remapping, replace it with
-     the binding and restart with fkey->start at the end.  */
-  if ((VECTORP (next) || STRINGP (next)) && doit)
-    {
-      int len = XFASTINT (Flength (next));
-      int i;
-
-      *diff = len - (fkey->end - fkey->start);
-
-      if (bufsize - input <= *diff)
-	error ("Key sequence too long");
-
-      /* Shift the keys that follow fkey->end.  */
-      if (*diff < 0)
-	for (i = fkey->end; i < input; i++)
-	  keybuf[i + *diff] = keybuf[i];
-      else if (*diff > 0)
-	for (i = input - 1; i >= fkey->end; i--)
-	  keybuf[i + *diff] = keybuf[i];
-      /* Overwrite the old keys with the new ones.  */
-      for (i = 0; i < len; i++)
-	keybuf[fkey->start + i]
-	  = Faref (next, make_number (i));
-
-      fkey->start = fkey->end += *diff;
-      fkey->map = fkey->parent;
-
-      return 1;
-    }
-
-  fkey->map = get_keymap (next, 0, 1);
-
-  /* If we no longer have a bound suffix, try a new position for
-     fkey->start.  */
-  if (!CONSP (fkey->map))
-    {
-      fkey->end = ++fkey->start;
-      fkey->map = fkey->parent;
-    }
-  return 0;
-}
+/* Do one step of the key remapping used for input-decode-map,
+   function-key-map and key-translation-map.  Defined in
+   key_translation.rs.  */
+extern bool keyremap_step (Lisp_Object *, int, volatile keyremap *,
+			   int, bool, int *, Lisp_Object);
 
 static bool
 test_undefined (Lisp_Object binding)
@@ -10748,8 +10685,6 @@ init_kboard (KBOARD *kb, Lisp_Object type)
   kb->immediate_echo = false;
   kset_echo_string (kb, Qnil);
   kset_echo_prompt (kb, Qnil);
-  kb->kbd_macro_buffer = 0;
-  kb->kbd_macro_bufsize = 0;
   kset_defining_kbd_macro (kb, Qnil);
   kset_last_kbd_macro (kb, Qnil);
   kb->reference_count = 0;
@@ -10784,7 +10719,7 @@ allocate_kboard (Lisp_Object type)
 static void
 wipe_kboard (KBOARD *kb)
 {
-  xfree (kb->kbd_macro_buffer);
+  forget_kbd_macro (kb);
 }
 
 /* Free KB and memory referenced from it.  */
@@ -11848,12 +11783,9 @@ void
 mark_kboards (void)
 {
   KBOARD *kb;
-  Lisp_Object *p;
+  mark_kbd_macros ();
   for (kb = all_kboards; kb; kb = kb->next_kboard)
     {
-      if (kb->kbd_macro_buffer)
-	for (p = kb->kbd_macro_buffer; p < kb->kbd_macro_ptr; p++)
-	  mark_object (*p);
       mark_object (KVAR (kb, Voverriding_terminal_local_map));
       mark_object (KVAR (kb, Vlast_command));
       mark_object (KVAR (kb, Vreal_last_command));
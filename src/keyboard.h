@@ -111,24 +111,8 @@ struct kboard
     /* Non-nil while a kbd macro is being defined.  */
     Lisp_Object defining_kbd_macro_;
 
-    /* The start of storage for the current keyboard macro.  */
-    Lisp_Object *kbd_macro_buffer;
-
-    /* Where to store the next keystroke of the macro.  */
-    Lisp_Object *kbd_macro_ptr;
-
-    /* The finalized section of the macro starts at kbd_macro_buffer and
-       ends before this.  This is not the same as kbd_macro_ptr, because
-       we advance this to kbd_macro_ptr when a key's command is complete.
-       This way, the keystrokes for "end-kbd-macro" are not included in the
-       macro.  This also allows us to throw away the events added to the
-       macro by the last command: all the events between kbd_macro_end and
-       kbd_macro_ptr belong to the last command; see
-       cancel-kbd-macro-events.  */
-    Lisp_Object *kbd_macro_end;
-
-    /* Allocated size of kbd_macro_buffer.  */
-    ptrdiff_t kbd_macro_bufsize;
+    /* The events of the keyboard macro being defined are kept in Rust,
+       see kbd_macros.rs.  */
 
     /* Last anonymous kbd macro defined.  */
     Lisp_Object Vlast_kbd_macro_;
@@ -481,6 +465,7 @@ extern void show_help_echo (Lisp_Object, Lisp_Object, Lisp_Object,
 extern void gen_help_event (Lisp_Object, Lisp_Object, Lisp_Object,
                             Lisp_Object, ptrdiff_t);
 extern void kbd_buffer_store_help_event (Lisp_Object, Lisp_Object);
+extern void kbd_buffer_store_dbus_event (Lisp_Object);
 extern Lisp_Object menu_item_eval_property (Lisp_Object);
 extern bool kbd_buffer_events_waiting (void);
 extern void add_user_signal (int, const char *);
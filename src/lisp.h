@@ -561,6 +561,44 @@ extern bool initialized;
 
 /* Defined in Rust.  */
 extern double extract_float (Lisp_Object);
+extern void lock_file (Lisp_Object);
+extern void unlock_file (Lisp_Object);
+extern bool mapped_text_p (unsigned char *);
+extern bool resize_mapped_text (unsigned char *, ptrdiff_t);
+extern void free_mapped_text (unsigned char *);
+struct buffer;
+extern void record_buffer_change (ptrdiff_t, ptrdiff_t, ptrdiff_t);
+extern void forget_buffer_changes (struct buffer *);
+extern void swap_buffer_changes (struct buffer *, struct buffer *);
+extern void adjust_anchors (ptrdiff_t, ptrdiff_t, ptrdiff_t, bool);
+extern void forget_buffer_anchors (struct buffer *);
+extern void swap_buffer_anchors (struct buffer *, struct buffer *);
+extern void forget_invisible_ranges (struct buffer *);
+extern ptrdiff_t count_display_lines (ptrdiff_t, ptrdiff_t);
+extern void forget_line_checkpoints (struct buffer *);
+extern void swap_line_checkpoints (struct buffer *, struct buffer *);
+extern void forget_visual_line_starts (struct buffer *);
+extern int scroll_margin_lines (EMACS_INT, int, Lisp_Object);
+extern int scroll_max_distance (ptrdiff_t *, ptrdiff_t, bool, bool, int);
+extern int scroll_amount (bool, int, ptrdiff_t, ptrdiff_t, bool, int,
+			  Lisp_Object, int, int, int);
+extern void classify_inserted_text (ptrdiff_t, ptrdiff_t, Lisp_Object);
+extern Lisp_Object read_extended_object (Lisp_Object);
+extern void load_elc_stream (void *, Lisp_Object, Lisp_Object);
+extern void init_core_obarray (Lisp_Object);
+extern bool core_obarray_lookup (Lisp_Object, const char *, ptrdiff_t, ptrdiff_t,
+				 Lisp_Object *);
+extern void forget_core_symbol (Lisp_Object);
+extern ptrdiff_t group_digits (char *, ptrdiff_t, ptrdiff_t);
+extern void tty_image_row_updated (struct frame *, int);
+extern void tty_images_repaint (struct frame *, int);
+extern void tty_images_forget_frame (struct frame *);
+extern unsigned touch_event_push (int, int, int, double, double, double,
+				  double);
+extern Lisp_Object touch_event_lisp (unsigned);
+extern void lisp_data_finish_writes (void);
+extern void transient_maps_pre_command (void);
+extern void mark_transient_maps (void);
 
 
 /* Low-level conversion and type checking.  */
@@ -2023,6 +2061,11 @@ struct Lisp_Hash_Table
   /* Next weak hash table if this is a weak hash table.  The head
      of the list is in weak_hash_tables.  */
   struct Lisp_Hash_Table *next_weak;
+
+  /* Stamp that changes whenever a key is added to or removed from
+     the table.  Stamps are unique across all tables, so caches
+     derived from the set of keys can be validated cheaply.  */
+  EMACS_UINT key_stamp;
 };
 
 
@@ -3373,6 +3416,7 @@ ptrdiff_t hash_put (struct Lisp_Hash_Table *, Lisp_Object, Lisp_Object,
 		    EMACS_UINT);
 void hash_remove_from_table (struct Lisp_Hash_Table *, Lisp_Object);
 extern struct hash_table_test const hashtest_eq, hashtest_eql, hashtest_equal;
+extern EMACS_UINT string_text_stamp;
 extern void validate_subarray (Lisp_Object, Lisp_Object, Lisp_Object,
 			       ptrdiff_t, ptrdiff_t *, ptrdiff_t *);
 extern Lisp_Object substring_both (Lisp_Object, ptrdiff_t, ptrdiff_t,
@@ -3718,6 +3762,8 @@ extern void syms_of_chartab (void);
 
 /* Defined in print.c.  */
 extern Lisp_Object Vprin1_to_string_buffer;
+extern void print_output_string (Lisp_Object, Lisp_Object);
+extern Lisp_Object print_object_to_string (Lisp_Object, bool);
 extern void debug_print (Lisp_Object) EXTERNALLY_VISIBLE;
 extern void temp_output_buffer_setup (const char *);
 extern int print_level;
@@ -3749,6 +3795,8 @@ extern Lisp_Object intern_1 (const char *, ptrdiff_t);
 extern Lisp_Object intern_c_string_1 (const char *, ptrdiff_t);
 extern Lisp_Object intern_driver (Lisp_Object, Lisp_Object, Lisp_Object);
 extern void init_symbol (Lisp_Object, Lisp_Object);
+extern void build_load_history (Lisp_Object, bool);
+extern Lisp_Object read_elc_form_at (void *, ptrdiff_t, ptrdiff_t *);
 extern Lisp_Object oblookup (Lisp_Object, const char *, ptrdiff_t, ptrdiff_t);
 INLINE void
 LOADHIST_ATTACH (Lisp_Object x)
@@ -3797,6 +3845,10 @@ extern Lisp_Object run_hook_with_args (ptrdiff_t nargs, Lisp_Object *args,
 				       Lisp_Object (*funcall)
 				       (ptrdiff_t nargs, Lisp_Object *args));
 extern Lisp_Object quit (void);
+extern Lisp_Object signal_or_quit (Lisp_Object, Lisp_Object, bool);
+/* Fsignal is defined in Rust, so make-docfile cannot mark its
+   prototype as _Noreturn, which xsignal relies on.  Do it here.  */
+extern _Noreturn Lisp_Object Fsignal (Lisp_Object, Lisp_Object);
 INLINE _Noreturn void
 xsignal (Lisp_Object error_symbol, Lisp_Object data)
 {
@@ -3822,6 +3874,10 @@ extern Lisp_Object call6 (Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Li
 extern Lisp_Object call7 (Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object);
 extern Lisp_Object call8 (Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object);
 extern Lisp_Object internal_catch (Lisp_Object, Lisp_Object (*) (Lisp_Object), Lisp_Object);
+extern _Noreturn void unwind_to_catch (struct handler *, Lisp_Object);
+/* Fthrow is defined in Rust, so make-docfile cannot mark its
+   prototype as _Noreturn.  Do it here instead.  */
+extern _Noreturn Lisp_Object Fthrow (Lisp_Object, Lisp_Object);
 extern Lisp_Object internal_lisp_condition_case (Lisp_Object, Lisp_Object, Lisp_Object);
 extern Lisp_Object internal_condition_case (Lisp_Object (*) (void), Lisp_Object, Lisp_Object (*) (Lisp_Object));
 extern Lisp_Object internal_condition_case_1 (Lisp_Object (*) (Lisp_Object), Lisp_Object, Lisp_Object, Lisp_Object (*) (Lisp_Object));
@@ -3832,6 +3888,10 @@ extern Lisp_Object internal_condition_case_n
 extern struct handler *push_handler (Lisp_Object, enum handlertype);
 extern struct handler *push_handler_nosignal (Lisp_Object, enum handlertype);
 extern void specbind (Lisp_Object, Lisp_Object);
+extern union specbinding *record_specbind (enum specbind_tag, Lisp_Object,
+                                           Lisp_Object, Lisp_Object);
+extern void do_specbind (struct Lisp_Symbol *, union specbinding *,
+                         Lisp_Object, enum Set_Internal_Bind);
 extern void record_unwind_protect (void (*) (Lisp_Object), Lisp_Object);
 extern void record_unwind_protect_ptr (void (*) (void *), void *);
 extern void record_unwind_protect_int (void (*) (int), int);
@@ -4202,6 +4262,7 @@ enum text_quoting_style
 extern enum text_quoting_style text_quoting_style (void);
 extern Lisp_Object read_doc_string (Lisp_Object);
 extern Lisp_Object get_doc_string (Lisp_Object, bool, bool);
+extern bool reread_doc_file (Lisp_Object);
 extern void syms_of_doc (void);
 extern int read_bytecode_char (bool);
 
@@ -4277,8 +4338,7 @@ extern int renameat_noreplace (int, char const *, int, char const *);
 extern int str_collate (Lisp_Object, Lisp_Object, Lisp_Object, Lisp_Object);
 
 /* Defined in filelock.c.  */
-extern void lock_file (Lisp_Object);
-extern void unlock_file (Lisp_Object);
+extern time_t get_boot_time (void);
 extern void unlock_all_files (void);
 extern void unlock_buffer (struct buffer *);
 extern void syms_of_filelock (void);
@@ -4383,7 +4443,6 @@ extern void syms_of_decompress (void);
 
 #ifdef HAVE_DBUS
 /* Defined in dbusbind.c.  */
-void init_dbusbind (void);
 void syms_of_dbusbind (void);
 #endif
 
@@ -59,15 +59,19 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
 #ifdef HAVE_FSEEKO
 #define file_offset off_t
 #define file_tell ftello
+#define file_seek fseeko
 #else
 #define file_offset long
 #define file_tell ftell
+#define file_seek fseek
 #endif
 
 // Used by Rust to interact with bitfield properties.
 bool symbol_is_interned(struct Lisp_Symbol *symbol);
 bool symbol_is_alias(struct Lisp_Symbol *symbol);
 bool symbol_is_constant(struct Lisp_Symbol *symbol);
+bool symbol_is_declared_special(struct Lisp_Symbol *symbol);
+int symbol_get_redirect(struct Lisp_Symbol *symbol);
 uint16_t misc_get_ty(struct Lisp_Misc_Any *any);
 
 /* The objects or placeholders read with the #n=object form.
@@ -1419,7 +1423,11 @@ Return t if the file exists and loads successfully.  */)
   if (lisp_file_lexically_bound_p (Qget_file_char))
     Fset (Qlexical_binding, Qt);
 
-  if (! version || version >= 22)
+  if (load_use_native_elc && compiled && version >= 22
+      && !load_force_doc_strings && NILP (Vload_read_function)
+      && NILP (Vread_with_symbol_positions))
+    load_elc_stream (stream, found, hist_file_name);
+  else if (! version || version >= 22)
     readevalloop (Qget_file_char, &input, hist_file_name,
 		  0, Qnil, Qnil, Qnil, Qnil);
   else
@@ -1749,7 +1757,7 @@ openp (Lisp_Object path, Lisp_Object str, Lisp_Object suffixes,
    ENTIRE is true if loading that entire file, false if evaluating
    part of it.  */
 
-static void
+void
 build_load_history (Lisp_Object filename, bool entire)
 {
   Lisp_Object tail, prev, newelt;
@@ -2053,6 +2061,37 @@ readevalloop (Lisp_Object readcharfun,
   unbind_to (count, Qnil);
 }
 
+/* Read the form that starts OFFSET bytes into STREAM, the stdio
+   stream that `load' has open on a compiled file, and store in *END
+   the offset of the first byte after it.  The Rust .elc loader uses
+   this for the forms it does not read itself.  */
+
+static void
+restore_infile (void *arg)
+{
+  infile = arg;
+}
+
+Lisp_Object
+read_elc_form_at (void *stream, ptrdiff_t offset, ptrdiff_t *end)
+{
+  ptrdiff_t count = SPECPDL_INDEX ();
+  struct infile input;
+  Lisp_Object val;
+
+  if (file_seek (stream, offset, SEEK_SET) != 0)
+    report_file_error ("Seeking in file", Vload_file_name);
+  input.stream = stream;
+  input.lookahead = 0;
+  /* INPUT lives only as long as this call, so put back the infile of
+     `load' however the read ends.  */
+  record_unwind_protect_ptr (restore_infile, infile);
+  infile = &input;
+  val = read_internal_start (Qget_file_char, Qnil, Qnil);
+  *end = file_tell (input.stream) - input.lookahead;
+  return unbind_to (count, val);
+}
+
 DEFUN ("eval-buffer", Feval_buffer, Seval_buffer, 0, 5, "",
        doc: /* Execute the accessible portion of current buffer as Lisp code.
 You can use \\[narrow-to-region] to limit the part of buffer to be evaluated.
@@ -2708,83 +2747,10 @@ read1 (Lisp_Object readcharfun, int *pch, bool first_in_list)
 	    {
 	      /* Accept extended format for hash tables (extensible to
 		 other types), e.g.
-		 #s(hash-table size 2 test equal data (k1 v1 k2 v2))  */
+		 #s(hash-table size 2 test equal data (k1 v1 k2 v2))
+		 and for records, e.g. #s(foo 1 2).  */
 	      Lisp_Object tmp = read_list (0, readcharfun);
-	      Lisp_Object head = CAR_SAFE (tmp);
-	      Lisp_Object data = Qnil;
-	      Lisp_Object val = Qnil;
-	      /* The size is 2 * number of allowed keywords to
-		 make-hash-table.  */
-	      Lisp_Object params[12];
-	      Lisp_Object ht;
-	      Lisp_Object key = Qnil;
-	      int param_count = 0;
-
-	      if (!EQ (head, Qhash_table))
-		{
-		  ptrdiff_t size = XINT (Flength (tmp));
-		  Lisp_Object record = Fmake_record (CAR_SAFE (tmp),
-						     make_number (size - 1),
-						     Qnil);
-		  for (int i = 1; i < size; i++)
-		    {
-		      tmp = Fcdr (tmp);
-		      ASET (record, i, Fcar (tmp));
-		    }
-		  return record;
-		}
-
-	      tmp = CDR_SAFE (tmp);
-
-	      /* This is repetitive but fast and simple.  */
-	      params[param_count] = QCsize;
-	      params[param_count + 1] = Fplist_get (tmp, Qsize);
-	      if (!NILP (params[param_count + 1]))
-		param_count += 2;
-
-	      params[param_count] = QCtest;
-	      params[param_count + 1] = Fplist_get (tmp, Qtest);
-	      if (!NILP (params[param_count + 1]))
-		param_count += 2;
-
-	      params[param_count] = QCweakness;
-	      params[param_count + 1] = Fplist_get (tmp, Qweakness);
-	      if (!NILP (params[param_count + 1]))
-		param_count += 2;
-
-	      params[param_count] = QCrehash_size;
-	      params[param_count + 1] = Fplist_get (tmp, Qrehash_size);
-	      if (!NILP (params[param_count + 1]))
-		param_count += 2;
-
-	      params[param_count] = QCrehash_threshold;
-	      params[param_count + 1] = Fplist_get (tmp, Qrehash_threshold);
-	      if (!NILP (params[param_count + 1]))
-		param_count += 2;
-
-              params[param_count] = QCpurecopy;
-              params[param_count + 1] = Fplist_get (tmp, Qpurecopy);
-              if (!NILP (params[param_count + 1]))
-                param_count += 2;
-
-	      /* This is the hash table data.  */
-	      data = Fplist_get (tmp, Qdata);
-
-	      /* Now use params to make a new hash table and fill it.  */
-	      ht = Fmake_hash_table (param_count, params);
-
-	      while (CONSP (data))
-	      	{
-	      	  key = XCAR (data);
-	      	  data = XCDR (data);
-	      	  if (!CONSP (data))
-		    error ("Odd number of elements in hash table data");
-	      	  val = XCAR (data);
-	      	  data = XCDR (data);
-	      	  Fputhash (key, val, ht);
-	      	}
-
-	      return ht;
+	      return read_extended_object (tmp);
 	    }
 	  UNREAD (c);
 	  invalid_syntax ("#");
@@ -4011,10 +3977,6 @@ read_list (bool flag, Lisp_Object readcharfun)
 
 static Lisp_Object initial_obarray;
 
-/* `oblookup' stores the bucket number here, for the sake of Funintern.  */
-
-static size_t oblookup_last_bucket_number;
-
 /* Get an error if OBARRAY is not an obarray.
    If it is one, return it.  */
 
@@ -4151,8 +4113,9 @@ usage: (unintern NAME OBARRAY)  */)
        error ("Attempt to unintern t or nil"); */
 
   XSYMBOL (tem)->interned = SYMBOL_UNINTERNED;
+  forget_core_symbol (tem);
 
-  hash = oblookup_last_bucket_number;
+  hash = hash_string (SSDATA (string), SBYTES (string)) % ASIZE (obarray);
 
   if (EQ (AREF (obarray, hash), tem))
     {
@@ -4190,7 +4153,8 @@ usage: (unintern NAME OBARRAY)  */)
    If there is no such symbol, return the integer bucket number of
    where the symbol would be if it were present.
 
-   Also store the bucket number in oblookup_last_bucket_number.  */
+   The symbols OBARRAY held when Emacs was dumped are looked up in
+   its core first; see obarray.rs.  */
 
 Lisp_Object
 oblookup (Lisp_Object obarray, register const char *ptr, ptrdiff_t size, ptrdiff_t size_byte)
@@ -4201,11 +4165,12 @@ oblookup (Lisp_Object obarray, register const char *ptr, ptrdiff_t size, ptrdiff
   Lisp_Object bucket, tem;
 
   obarray = check_obarray (obarray);
+  if (core_obarray_lookup (obarray, ptr, size, size_byte, &tem))
+    return tem;
   /* This is sometimes needed in the middle of GC.  */
   obsize = gc_asize (obarray);
   hash = hash_string (ptr, size_byte) % obsize;
   bucket = AREF (obarray, hash);
-  oblookup_last_bucket_number = hash;
   if (EQ (bucket, make_number (0)))
     ;
   else if (!SYMBOLP (bucket))
@@ -4705,6 +4670,18 @@ symbol_is_constant (struct Lisp_Symbol *symbol)
   return symbol->trapped_write == SYMBOL_NOWRITE;
 }
 
+bool
+symbol_is_declared_special (struct Lisp_Symbol *symbol)
+{
+  return symbol->declared_special;
+}
+
+int
+symbol_get_redirect (struct Lisp_Symbol *symbol)
+{
+  return symbol->redirect;
+}
+
 uint16_t
 misc_get_ty (struct Lisp_Misc_Any *any)
 {
@@ -4941,6 +4918,14 @@ them.  */);
 This overrides the value of the NOMESSAGE argument to `load'.  */);
   force_load_messages = 0;
 
+  DEFVAR_BOOL ("load-use-native-elc", load_use_native_elc,
+	       doc: /* Non-nil means `load' reads compiled Lisp files natively.
+The forms of a .elc file are then read from a memory mapping of the
+file by a reader that handles the syntax the byte-compiler writes,
+falling back to the usual reader for anything else.  This is meant for
+comparing the two; see also `load-native-elc'.  */);
+  load_use_native_elc = 0;
+
   DEFVAR_LISP ("bytecomp-version-regexp", Vbytecomp_version_regexp,
 	       doc: /* Regular expression matching safe to load compiled Lisp files.
 When Emacs loads a compiled Lisp file, it reads the first 512 bytes
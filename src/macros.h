@@ -47,4 +47,13 @@ extern void finalize_kbd_macro_chars (void);
 
 extern void store_kbd_macro_char (Lisp_Object);
 
+/* Mark the events of the keyboard macros being defined.  */
+
+extern void mark_kbd_macros (void);
+
+/* Forget the keyboard macro being defined on a kboard being deleted.  */
+
+struct kboard;
+extern void forget_kbd_macro (struct kboard *);
+
 #endif /* EMACS_MACROS_H */
@@ -75,9 +75,8 @@ static ptrdiff_t print_buffer_pos_byte;
      t    the object will be printed only once.
      -N   the object will be printed several times and will take number N.
      N    the object has been printed so we can refer to it as #N#.
-   print_number_index holds the largest N already used.
+   The largest N already used is kept by the printer in printer.rs.
    N has to be striclty larger than 0 since we need to distinguish -N.  */
-static ptrdiff_t print_number_index;
 static void print_interval (INTERVAL interval, Lisp_Object printcharfun);
 
 /* GDB resets this to zero on W32 to disable OutputDebugString calls.  */
@@ -564,9 +563,6 @@ temp_output_buffer_setup (const char *bufname)
   specbind (Qstandard_output, buf);
 }
 
-static void print (Lisp_Object, Lisp_Object, bool);
-static void print_preprocess (Lisp_Object);
-static void print_preprocess_string (INTERVAL, void *);
 static void print_object (Lisp_Object, Lisp_Object, bool);
 
 DEFUN ("terpri", Fterpri, Sterpri, 0, 2, 0,
@@ -598,58 +594,34 @@ If PRINTCHARFUN is omitted or nil, the value of `standard-output' is used.  */)
   return val;
 }
 
-DEFUN ("prin1", Fprin1, Sprin1, 1, 2, 0,
-       doc: /* Output the printed representation of OBJECT, any Lisp object.
-Quoting characters are printed when needed to make output that `read'
-can handle, whenever this is possible.  For complex objects, the behavior
-is controlled by `print-level' and `print-length', which see.
+/* Output STRING to PRINTCHARFUN as it stands.  The printer in
+   printer.rs builds the printed representation of an object and uses
+   this to send it to a buffer, a marker, a function or the echo area.  */
 
-OBJECT is any of the Lisp data types: a number, a string, a symbol,
-a list, a buffer, a window, a frame, etc.
-
-A printed representation of an object is text which describes that object.
-
-Optional argument PRINTCHARFUN is the output stream, which can be one
-of these:
-
-   - a buffer, in which case output is inserted into that buffer at point;
-   - a marker, in which case output is inserted at marker's position;
-   - a function, in which case that function is called once for each
-     character of OBJECT's printed representation;
-   - a symbol, in which case that symbol's function definition is called; or
-   - t, in which case the output is displayed in the echo area.
-
-If PRINTCHARFUN is omitted, the value of `standard-output' (which see)
-is used instead.  */)
-  (Lisp_Object object, Lisp_Object printcharfun)
+void
+print_output_string (Lisp_Object string, Lisp_Object printcharfun)
 {
-  if (NILP (printcharfun))
-    printcharfun = Vstandard_output;
   PRINTPREPARE;
-  print (object, printcharfun, 1);
+  print_string (string, printcharfun);
   PRINTFINISH;
-  return object;
 }
 
-/* A buffer which is used to hold output being built by prin1-to-string.  */
+/* A buffer which is used to hold output being built by
+   print_object_to_string.  */
 Lisp_Object Vprin1_to_string_buffer;
 
-DEFUN ("prin1-to-string", Fprin1_to_string, Sprin1_to_string, 1, 2, 0,
-       doc: /* Return a string containing the printed representation of OBJECT.
-OBJECT can be any Lisp object.  This function outputs quoting characters
-when necessary to make output that `read' can handle, whenever possible,
-unless the optional second argument NOESCAPE is non-nil.  For complex objects,
-the behavior is controlled by `print-level' and `print-length', which see.
+/* Return a string containing the printed representation of OBJ.  This
+   is used by printer.rs for the objects it does not print itself,
+   such as markers, windows and processes.  */
 
-OBJECT is any of the Lisp data types: a number, a string, a symbol,
-a list, a buffer, a window, a frame, etc.
-
-A printed representation of an object is text which describes that object.  */)
-  (Lisp_Object object, Lisp_Object noescape)
+Lisp_Object
+print_object_to_string (Lisp_Object obj, bool escapeflag)
 {
   ptrdiff_t count = SPECPDL_INDEX ();
 
   specbind (Qinhibit_modification_hooks, Qt);
+  /* Sharing has been dealt with by the caller.  */
+  specbind (Qprint_circle, Qnil);
 
   /* Save and restore this: we are altering a buffer
      but we don't want to deactivate the mark just for that.
@@ -658,15 +630,17 @@ A printed representation of an object is text which describes that object.  */)
 
   Lisp_Object printcharfun = Vprin1_to_string_buffer;
   PRINTPREPARE;
-  print (object, printcharfun, NILP (noescape));
+  new_backquote_output = 0;
+  print_depth = 0;
+  print_object (obj, printcharfun, escapeflag);
   /* Make Vprin1_to_string_buffer be the default buffer after PRINTFINISH */
   PRINTFINISH;
 
   struct buffer *previous = current_buffer;
   set_buffer_internal (XBUFFER (Vprin1_to_string_buffer));
-  object = Fbuffer_string ();
-  if (SBYTES (object) == SCHARS (object))
-    STRING_SET_UNIBYTE (object);
+  Lisp_Object string = Fbuffer_string ();
+  if (SBYTES (string) == SCHARS (string))
+    STRING_SET_UNIBYTE (string);
 
   /* Note that this won't make prepare_to_modify_buffer call
      ask-user-about-supersession-threat because this buffer
@@ -676,74 +650,7 @@ A printed representation of an object is text which describes that object.  */)
 
   Vdeactivate_mark = save_deactivate_mark;
 
-  return unbind_to (count, object);
-}
-
-DEFUN ("princ", Fprinc, Sprinc, 1, 2, 0,
-       doc: /* Output the printed representation of OBJECT, any Lisp object.
-No quoting characters are used; no delimiters are printed around
-the contents of strings.
-
-OBJECT is any of the Lisp data types: a number, a string, a symbol,
-a list, a buffer, a window, a frame, etc.
-
-A printed representation of an object is text which describes that object.
-
-Optional argument PRINTCHARFUN is the output stream, which can be one
-of these:
-
-   - a buffer, in which case output is inserted into that buffer at point;
-   - a marker, in which case output is inserted at marker's position;
-   - a function, in which case that function is called once for each
-     character of OBJECT's printed representation;
-   - a symbol, in which case that symbol's function definition is called; or
-   - t, in which case the output is displayed in the echo area.
-
-If PRINTCHARFUN is omitted, the value of `standard-output' (which see)
-is used instead.  */)
-  (Lisp_Object object, Lisp_Object printcharfun)
-{
-  if (NILP (printcharfun))
-    printcharfun = Vstandard_output;
-  PRINTPREPARE;
-  print (object, printcharfun, 0);
-  PRINTFINISH;
-  return object;
-}
-
-DEFUN ("print", Fprint, Sprint, 1, 2, 0,
-       doc: /* Output the printed representation of OBJECT, with newlines around it.
-Quoting characters are printed when needed to make output that `read'
-can handle, whenever this is possible.  For complex objects, the behavior
-is controlled by `print-level' and `print-length', which see.
-
-OBJECT is any of the Lisp data types: a number, a string, a symbol,
-a list, a buffer, a window, a frame, etc.
-
-A printed representation of an object is text which describes that object.
-
-Optional argument PRINTCHARFUN is the output stream, which can be one
-of these:
-
-   - a buffer, in which case output is inserted into that buffer at point;
-   - a marker, in which case output is inserted at marker's position;
-   - a function, in which case that function is called once for each
-     character of OBJECT's printed representation;
-   - a symbol, in which case that symbol's function definition is called; or
-   - t, in which case the output is displayed in the echo area.
-
-If PRINTCHARFUN is omitted, the value of `standard-output' (which see)
-is used instead.  */)
-  (Lisp_Object object, Lisp_Object printcharfun)
-{
-  if (NILP (printcharfun))
-    printcharfun = Vstandard_output;
-  PRINTPREPARE;
-  printchar ('\n', printcharfun);
-  print (object, printcharfun, 1);
-  printchar ('\n', printcharfun);
-  PRINTFINISH;
-  return object;
+  return unbind_to (count, string);
 }
 
 DEFUN ("external-debugging-output", Fexternal_debugging_output, Sexternal_debugging_output, 1, 1, 0,
@@ -1090,47 +997,6 @@ float_to_string (char *buf, double data)
 }
 
 
-static void
-print (Lisp_Object obj, Lisp_Object printcharfun, bool escapeflag)
-{
-  new_backquote_output = 0;
-
-  /* Reset print_number_index and Vprint_number_table only when
-     the variable Vprint_continuous_numbering is nil.  Otherwise,
-     the values of these variables will be kept between several
-     print functions.  */
-  if (NILP (Vprint_continuous_numbering)
-      || NILP (Vprint_number_table))
-    {
-      print_number_index = 0;
-      Vprint_number_table = Qnil;
-    }
-
-  /* Construct Vprint_number_table for print-gensym and print-circle.  */
-  if (!NILP (Vprint_gensym) || !NILP (Vprint_circle))
-    {
-      /* Construct Vprint_number_table.
-	 This increments print_number_index for the objects added.  */
-      print_depth = 0;
-      print_preprocess (obj);
-
-      if (HASH_TABLE_P (Vprint_number_table))
-	{ /* Remove unnecessary objects, which appear only once in OBJ;
-	     that is, whose status is Qt.  */
-	  struct Lisp_Hash_Table *h = XHASH_TABLE (Vprint_number_table);
-	  ptrdiff_t i;
-
-	  for (i = 0; i < HASH_TABLE_SIZE (h); ++i)
-	    if (!NILP (HASH_HASH (h, i))
-		&& EQ (HASH_VALUE (h, i), Qt))
-	      Fremhash (HASH_KEY (h, i), Vprint_number_table);
-	}
-    }
-
-  print_depth = 0;
-  print_object (obj, printcharfun, escapeflag);
-}
-
 #define PRINT_CIRCLE_CANDIDATE_P(obj)			   \
   (STRINGP (obj) || CONSP (obj)				   \
    || (VECTORLIKEP (obj)				   \
@@ -1142,132 +1008,6 @@ print (Lisp_Object obj, Lisp_Object printcharfun, bool escapeflag)
        && SYMBOLP (obj)					   \
        && !SYMBOL_INTERNED_P (obj)))
 
-/* Construct Vprint_number_table according to the structure of OBJ.
-   OBJ itself and all its elements will be added to Vprint_number_table
-   recursively if it is a list, vector, compiled function, char-table,
-   string (its text properties will be traced), or a symbol that has
-   no obarray (this is for the print-gensym feature).
-   The status fields of Vprint_number_table mean whether each object appears
-   more than once in OBJ: Qnil at the first time, and Qt after that.  */
-static void
-print_preprocess (Lisp_Object obj)
-{
-  int i;
-  ptrdiff_t size;
-  int loop_count = 0;
-  Lisp_Object halftail;
-
-  /* Avoid infinite recursion for circular nested structure
-     in the case where Vprint_circle is nil.  */
-  if (NILP (Vprint_circle))
-    {
-      /* Give up if we go so deep that print_object will get an error.  */
-      /* See similar code in print_object.  */
-      if (print_depth >= PRINT_CIRCLE)
-	error ("Apparently circular structure being printed");
-
-      for (i = 0; i < print_depth; i++)
-	if (EQ (obj, being_printed[i]))
-	  return;
-      being_printed[print_depth] = obj;
-    }
-
-  print_depth++;
-  halftail = obj;
-
- loop:
-  if (PRINT_CIRCLE_CANDIDATE_P (obj))
-    {
-      if (!HASH_TABLE_P (Vprint_number_table))
-	Vprint_number_table = CALLN (Fmake_hash_table, QCtest, Qeq);
-
-      /* In case print-circle is nil and print-gensym is t,
-	 add OBJ to Vprint_number_table only when OBJ is a symbol.  */
-      if (! NILP (Vprint_circle) || SYMBOLP (obj))
-	{
-	  Lisp_Object num = Fgethash (obj, Vprint_number_table, Qnil);
-	  if (!NILP (num)
-	      /* If Vprint_continuous_numbering is non-nil and OBJ is a gensym,
-		 always print the gensym with a number.  This is a special for
-		 the lisp function byte-compile-output-docform.  */
-	      || (!NILP (Vprint_continuous_numbering)
-		  && SYMBOLP (obj)
-		  && !SYMBOL_INTERNED_P (obj)))
-	    { /* OBJ appears more than once.	Let's remember that.  */
-	      if (!INTEGERP (num))
-		{
-		  print_number_index++;
-		  /* Negative number indicates it hasn't been printed yet.  */
-		  Fputhash (obj, make_number (- print_number_index),
-			    Vprint_number_table);
-		}
-	      print_depth--;
-	      return;
-	    }
-	  else
-	    /* OBJ is not yet recorded.  Let's add to the table.  */
-	    Fputhash (obj, Qt, Vprint_number_table);
-	}
-
-      switch (XTYPE (obj))
-	{
-	case Lisp_String:
-	  /* A string may have text properties, which can be circular.  */
-	  traverse_intervals_noorder (string_intervals (obj),
-				      print_preprocess_string, NULL);
-	  break;
-
-	case Lisp_Cons:
-	  /* Use HALFTAIL and LOOP_COUNT to detect circular lists,
-	     just as in print_object.  */
-	  if (loop_count && EQ (obj, halftail))
-	    break;
-	  print_preprocess (XCAR (obj));
-	  obj = XCDR (obj);
-	  loop_count++;
-	  if (!(loop_count & 1))
-	    halftail = XCDR (halftail);
-	  goto loop;
-
-	case Lisp_Vectorlike:
-	  size = ASIZE (obj);
-	  if (size & PSEUDOVECTOR_FLAG)
-	    size &= PSEUDOVECTOR_SIZE_MASK;
-	  for (i = (SUB_CHAR_TABLE_P (obj)
-		    ? SUB_CHAR_TABLE_OFFSET : 0); i < size; i++)
-	    print_preprocess (AREF (obj, i));
-	  if (HASH_TABLE_P (obj))
-	    { /* For hash tables, the key_and_value slot is past
-		 `size' because it needs to be marked specially in case
-		 the table is weak.  */
-	      struct Lisp_Hash_Table *h = XHASH_TABLE (obj);
-	      print_preprocess (h->key_and_value);
-	    }
-	  break;
-
-	default:
-	  break;
-	}
-    }
-  print_depth--;
-}
-
-DEFUN ("print--preprocess", Fprint_preprocess, Sprint_preprocess, 1, 1, 0,
-       doc: /* Extract sharing info from OBJECT needed to print it.
-Fills `print-number-table'.  */)
-  (Lisp_Object object)
-{
-  print_number_index = 0;
-  print_preprocess (object);
-  return Qnil;
-}
-
-static void
-print_preprocess_string (INTERVAL interval, void *arg)
-{
-  print_preprocess (interval->plist);
-}
-
 static void print_check_string_charset_prop (INTERVAL interval, Lisp_Object string);
 
 #define PRINT_STRING_NON_CHARSET_FOUND 1
@@ -1757,7 +1497,7 @@ print_object (Lisp_Object obj, Lisp_Object printcharfun, bool escapeflag)
       /* Simple but incomplete way.  */
       int i;
 
-      /* See similar code in print_preprocess.  */
+      /* See similar code in Preprocessor::preprocess in printer.rs.  */
       if (print_depth >= PRINT_CIRCLE)
 	error ("Apparently circular structure being printed");
 
@@ -2425,16 +2165,12 @@ priorities.  */);
   /* prin1_to_string_buffer initialized in init_buffer_once in buffer.c */
   staticpro (&Vprin1_to_string_buffer);
 
-  defsubr (&Sprin1);
-  defsubr (&Sprin1_to_string);
   defsubr (&Serror_message_string);
-  defsubr (&Sprinc);
-  defsubr (&Sprint);
   defsubr (&Sterpri);
   defsubr (&Swrite_char);
   defsubr (&Sredirect_debugging_output);
-  defsubr (&Sprint_preprocess);
 
+  DEFSYM (Qprint_circle, "print-circle");
   DEFSYM (Qprint_escape_newlines, "print-escape-newlines");
   DEFSYM (Qprint_escape_multibyte, "print-escape-multibyte");
   DEFSYM (Qprint_escape_nonascii, "print-escape-nonascii");
@@ -250,6 +250,8 @@ static bool keyboard_bit_set (fd_set *);
 static void deactivate_process (Lisp_Object);
 static int status_notify (struct Lisp_Process *, struct Lisp_Process *);
 static int read_process_output (Lisp_Object, int);
+static bool process_output_held_p (void);
+static int flush_held_process_output (struct Lisp_Process *);
 static void create_pty (Lisp_Object);
 static void exec_sentinel (Lisp_Object, Lisp_Object);
 
@@ -402,6 +404,26 @@ bool_bf pget_kill_without_query(const struct Lisp_Process *p)
 {
   return p->kill_without_query;
 }
+
+Lisp_Object pget_accumulation(const struct Lisp_Process *p)
+{
+  return p->accumulation;
+}
+
+int pget_infd(const struct Lisp_Process *p)
+{
+  return p->infd;
+}
+
+bool_bf pget_pty_flag(const struct Lisp_Process *p)
+{
+  return p->pty_flag;
+}
+
+bool_bf pget_alive(const struct Lisp_Process *p)
+{
+  return p->alive;
+}
 /* End Rust Accessors */
 
 /* Setters to enable Rust code to set data in the Lisp_Process struct */
@@ -410,6 +432,12 @@ pset_kill_without_query (struct Lisp_Process *p, bool_bf val)
 {
   p->kill_without_query = val;
 }
+
+void
+pset_accumulation (struct Lisp_Process *p, Lisp_Object val)
+{
+  p->accumulation = val;
+}
 /* End Rust Setters */
 
 
@@ -787,12 +815,14 @@ status_message (struct Lisp_Process *p)
 
 enum { PTY_NAME_SIZE = 24 };
 
+#ifndef RUST_PTYS
 /* Open an available pty, returning a file descriptor.
    Store into PTY_NAME the file name of the terminal corresponding to the pty.
+   SIZE is always PTY_NAME_SIZE; it is there to match the Rust version.
    Return -1 on failure.  */
 
 static int
-allocate_pty (char pty_name[PTY_NAME_SIZE])
+allocate_pty (char pty_name[PTY_NAME_SIZE], ptrdiff_t size)
 {
 #ifdef HAVE_PTYS
   int fd;
@@ -849,6 +879,7 @@ allocate_pty (char pty_name[PTY_NAME_SIZE])
 #endif /* HAVE_PTYS */
   return -1;
 }
+#endif /* not RUST_PTYS */
 
 /* Allocate basically initialized process.  */
 
@@ -1202,29 +1233,6 @@ If PROCESS is unlocked, this function returns nil.  */)
   return XPROCESS (process)->thread;
 }
 
-DEFUN ("set-process-window-size", Fset_process_window_size,
-       Sset_process_window_size, 3, 3, 0,
-       doc: /* Tell PROCESS that it has logical window size WIDTH by HEIGHT.
-Value is t if PROCESS was successfully told about the window size,
-nil otherwise.  */)
-  (Lisp_Object process, Lisp_Object height, Lisp_Object width)
-{
-  CHECK_PROCESS (process);
-
-  /* All known platforms store window sizes as 'unsigned short'.  */
-  CHECK_RANGED_INTEGER (height, 0, USHRT_MAX);
-  CHECK_RANGED_INTEGER (width, 0, USHRT_MAX);
-
-  if (NETCONN_P (process)
-      || XPROCESS (process)->infd < 0
-      || (set_window_size (XPROCESS (process)->infd,
-			   XINT (height), XINT (width))
-	  < 0))
-    return Qnil;
-  else
-    return Qt;
-}
-
 DEFUN ("set-process-inherit-coding-system-flag",
        Fset_process_inherit_coding_system_flag,
        Sset_process_inherit_coding_system_flag, 2, 2, 0,
@@ -1763,7 +1771,7 @@ create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
   inchannel = outchannel = -1;
 
   if (p->pty_flag)
-    outchannel = inchannel = allocate_pty (pty_name);
+    outchannel = inchannel = allocate_pty (pty_name, PTY_NAME_SIZE);
 
   if (inchannel >= 0)
     {
@@ -1876,6 +1884,17 @@ create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
     {
       /* Make the pty be the controlling terminal of the process.  */
 #ifdef HAVE_PTYS
+#ifdef RUST_PTYS
+      if (pty_flag)
+	{
+	  forkout = forkin = pty_child_setup (forkin, SSDATA (lisp_pty_name));
+	  if (forkin < 0)
+	    {
+	      emacs_perror (SSDATA (lisp_pty_name));
+	      _exit (EXIT_CANCELED);
+	    }
+	}
+#else /* not RUST_PTYS */
       /* First, disconnect its current controlling terminal.  */
       if (pty_flag)
 	setsid ();
@@ -1948,6 +1967,7 @@ create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
 
 	}
 #endif /* not DONT_REOPEN_PTY */
+#endif /* not RUST_PTYS */
 
 #ifdef SETUP_SLAVE_PTY
       if (pty_flag)
@@ -2035,7 +2055,7 @@ create_pty (Lisp_Object process)
 {
   struct Lisp_Process *p = XPROCESS (process);
   char pty_name[PTY_NAME_SIZE];
-  int pty_fd = !p->pty_flag ? -1 : allocate_pty (pty_name);
+  int pty_fd = !p->pty_flag ? -1 : allocate_pty (pty_name, PTY_NAME_SIZE);
 
   if (pty_fd >= 0)
     {
@@ -2529,7 +2549,8 @@ static const struct socket_options {
   int optlevel;
   /* Option number SO_...  */
   int optnum;
-  enum { SOPT_UNKNOWN, SOPT_BOOL, SOPT_INT, SOPT_IFNAME, SOPT_LINGER } opttype;
+  enum { SOPT_UNKNOWN, SOPT_BOOL, SOPT_INT, SOPT_IFNAME, SOPT_LINGER,
+	 SOPT_MULTICAST } opttype;
   enum { OPIX_NONE = 0, OPIX_MISC = 1, OPIX_REUSEADDR = 2 } optbit;
 } socket_options[] =
   {
@@ -2548,6 +2569,14 @@ static const struct socket_options {
 #ifdef SO_LINGER
     { ":linger", SOL_SOCKET, SO_LINGER, SOPT_LINGER, OPIX_MISC },
 #endif
+#ifdef IP_ADD_MEMBERSHIP
+    /* These apply to IPv4 and IPv6 sockets alike; the Rust side
+       picks the level and option number to use.  */
+    { ":multicast-join", IPPROTO_IP, IP_ADD_MEMBERSHIP, SOPT_MULTICAST, OPIX_MISC },
+    { ":multicast-leave", IPPROTO_IP, IP_DROP_MEMBERSHIP, SOPT_MULTICAST, OPIX_MISC },
+    { ":multicast-loop", IPPROTO_IP, IP_MULTICAST_LOOP, SOPT_MULTICAST, OPIX_MISC },
+    { ":multicast-ttl", IPPROTO_IP, IP_MULTICAST_TTL, SOPT_MULTICAST, OPIX_MISC },
+#endif
 #ifdef SO_OOBINLINE
     { ":oobinline", SOL_SOCKET, SO_OOBINLINE, SOPT_BOOL, OPIX_MISC },
 #endif
@@ -2643,6 +2672,12 @@ set_socket_option (int s, Lisp_Object opt, Lisp_Object val)
       }
 #endif
 
+#ifdef IP_ADD_MEMBERSHIP
+    case SOPT_MULTICAST:
+      ret = set_multicast_option (s, opt, val);
+      break;
+#endif
+
     default:
       return 0;
     }
@@ -3552,6 +3587,10 @@ process filter are multibyte, otherwise they are unibyte.
 If this keyword is not specified, the strings are multibyte if
 the default value of `enable-multibyte-characters' is non-nil.
 
+:datagram-source BOOL -- If BOOL is non-nil, the filter of a datagram
+process is called with a third argument, the address the datagram
+came from, in the format described under ADDRESS above.
+
 :sentinel SENTINEL -- Install SENTINEL as the process sentinel.
 
 :log LOG -- Install LOG as the server process log function.  This
@@ -3586,6 +3625,14 @@ The following network options can be specified for this connection:
                       (this is allowed by default for a server process).
 :bindtodevice NAME -- bind to interface NAME.  Using this may require
                       special privileges on some systems.
+:multicast-join GROUP -- Join the multicast group GROUP, an address
+                      such as [224 0 0 251 0], or (GROUP . INTERFACE)
+                      where INTERFACE is an interface name or address.
+:multicast-leave GROUP -- Leave the multicast group GROUP.
+:multicast-loop BOOL -- Receive the multicast datagrams sent from
+                      this host.
+:multicast-ttl INT -- Set the time-to-live (IPv4) or hop limit (IPv6)
+                      of multicast datagrams.
 :use-external-socket BOOL -- Use any pre-allocated sockets that have
                              been passed to Emacs.  If Emacs wasn't
                              passed a socket, this option is silently
@@ -3975,338 +4022,6 @@ usage: (make-network-process &rest ARGS)  */)
 }
 
 
-#ifdef HAVE_NET_IF_H
-
-#ifdef SIOCGIFCONF
-static Lisp_Object
-network_interface_list (void)
-{
-  struct ifconf ifconf;
-  struct ifreq *ifreq;
-  void *buf = NULL;
-  ptrdiff_t buf_size = 512;
-  int s;
-  Lisp_Object res;
-  ptrdiff_t count;
-
-  s = socket (AF_INET, SOCK_STREAM | SOCK_CLOEXEC, 0);
-  if (s < 0)
-    return Qnil;
-  count = SPECPDL_INDEX ();
-  record_unwind_protect_int (close_file_unwind, s);
-
-  do
-    {
-      buf = xpalloc (buf, &buf_size, 1, INT_MAX, 1);
-      ifconf.ifc_buf = buf;
-      ifconf.ifc_len = buf_size;
-      if (ioctl (s, SIOCGIFCONF, &ifconf))
-	{
-	  emacs_close (s);
-	  xfree (buf);
-	  return Qnil;
-	}
-    }
-  while (ifconf.ifc_len == buf_size);
-
-  res = unbind_to (count, Qnil);
-  ifreq = ifconf.ifc_req;
-  while ((char *) ifreq < (char *) ifconf.ifc_req + ifconf.ifc_len)
-    {
-      struct ifreq *ifq = ifreq;
-#ifdef HAVE_STRUCT_IFREQ_IFR_ADDR_SA_LEN
-#define SIZEOF_IFREQ(sif)						\
-      ((sif)->ifr_addr.sa_len < sizeof (struct sockaddr)		\
-       ? sizeof (*(sif)) : sizeof ((sif)->ifr_name) + (sif)->ifr_addr.sa_len)
-
-      int len = SIZEOF_IFREQ (ifq);
-#else
-      int len = sizeof (*ifreq);
-#endif
-      char namebuf[sizeof (ifq->ifr_name) + 1];
-      ifreq = (struct ifreq *) ((char *) ifreq + len);
-
-      if (ifq->ifr_addr.sa_family != AF_INET)
-	continue;
-
-      memcpy (namebuf, ifq->ifr_name, sizeof (ifq->ifr_name));
-      namebuf[sizeof (ifq->ifr_name)] = 0;
-      res = Fcons (Fcons (build_string (namebuf),
-			  conv_sockaddr_to_lisp (&ifq->ifr_addr,
-						 sizeof (struct sockaddr))),
-		   res);
-    }
-
-  xfree (buf);
-  return res;
-}
-#endif /* SIOCGIFCONF */
-
-#if defined (SIOCGIFADDR) || defined (SIOCGIFHWADDR) || defined (SIOCGIFFLAGS)
-
-struct ifflag_def {
-  int flag_bit;
-  const char *flag_sym;
-};
-
-static const struct ifflag_def ifflag_table[] = {
-#ifdef IFF_UP
-  { IFF_UP,		"up" },
-#endif
-#ifdef IFF_BROADCAST
-  { IFF_BROADCAST,	"broadcast" },
-#endif
-#ifdef IFF_DEBUG
-  { IFF_DEBUG,		"debug" },
-#endif
-#ifdef IFF_LOOPBACK
-  { IFF_LOOPBACK,	"loopback" },
-#endif
-#ifdef IFF_POINTOPOINT
-  { IFF_POINTOPOINT,	"pointopoint" },
-#endif
-#ifdef IFF_RUNNING
-  { IFF_RUNNING,	"running" },
-#endif
-#ifdef IFF_NOARP
-  { IFF_NOARP,		"noarp" },
-#endif
-#ifdef IFF_PROMISC
-  { IFF_PROMISC,	"promisc" },
-#endif
-#ifdef IFF_NOTRAILERS
-#ifdef NS_IMPL_COCOA
-  /* Really means smart, notrailers is obsolete.  */
-  { IFF_NOTRAILERS,	"smart" },
-#else
-  { IFF_NOTRAILERS,	"notrailers" },
-#endif
-#endif
-#ifdef IFF_ALLMULTI
-  { IFF_ALLMULTI,	"allmulti" },
-#endif
-#ifdef IFF_MASTER
-  { IFF_MASTER,		"master" },
-#endif
-#ifdef IFF_SLAVE
-  { IFF_SLAVE,		"slave" },
-#endif
-#ifdef IFF_MULTICAST
-  { IFF_MULTICAST,	"multicast" },
-#endif
-#ifdef IFF_PORTSEL
-  { IFF_PORTSEL,	"portsel" },
-#endif
-#ifdef IFF_AUTOMEDIA
-  { IFF_AUTOMEDIA,	"automedia" },
-#endif
-#ifdef IFF_DYNAMIC
-  { IFF_DYNAMIC,	"dynamic" },
-#endif
-#ifdef IFF_OACTIVE
-  { IFF_OACTIVE,	"oactive" }, /* OpenBSD: transmission in progress.  */
-#endif
-#ifdef IFF_SIMPLEX
-  { IFF_SIMPLEX,	"simplex" }, /* OpenBSD: can't hear own transmissions.  */
-#endif
-#ifdef IFF_LINK0
-  { IFF_LINK0,		"link0" }, /* OpenBSD: per link layer defined bit.  */
-#endif
-#ifdef IFF_LINK1
-  { IFF_LINK1,		"link1" }, /* OpenBSD: per link layer defined bit.  */
-#endif
-#ifdef IFF_LINK2
-  { IFF_LINK2,		"link2" }, /* OpenBSD: per link layer defined bit.  */
-#endif
-  { 0, 0 }
-};
-
-static Lisp_Object
-network_interface_info (Lisp_Object ifname)
-{
-  struct ifreq rq;
-  Lisp_Object res = Qnil;
-  Lisp_Object elt;
-  int s;
-  bool any = 0;
-  ptrdiff_t count;
-#if (! (defined SIOCGIFHWADDR && defined HAVE_STRUCT_IFREQ_IFR_HWADDR)	\
-     && defined HAVE_GETIFADDRS && defined LLADDR)
-  struct ifaddrs *ifap;
-#endif
-
-  CHECK_STRING (ifname);
-
-  if (sizeof rq.ifr_name <= SBYTES (ifname))
-    error ("interface name too long");
-  lispstpcpy (rq.ifr_name, ifname);
-
-  s = socket (AF_INET, SOCK_STREAM | SOCK_CLOEXEC, 0);
-  if (s < 0)
-    return Qnil;
-  count = SPECPDL_INDEX ();
-  record_unwind_protect_int (close_file_unwind, s);
-
-  elt = Qnil;
-#if defined (SIOCGIFFLAGS) && defined (HAVE_STRUCT_IFREQ_IFR_FLAGS)
-  if (ioctl (s, SIOCGIFFLAGS, &rq) == 0)
-    {
-      int flags = rq.ifr_flags;
-      const struct ifflag_def *fp;
-      int fnum;
-
-      /* If flags is smaller than int (i.e. short) it may have the high bit set
-         due to IFF_MULTICAST.  In that case, sign extending it into
-         an int is wrong.  */
-      if (flags < 0 && sizeof (rq.ifr_flags) < sizeof (flags))
-        flags = (unsigned short) rq.ifr_flags;
-
-      any = 1;
-      for (fp = ifflag_table; flags != 0 && fp->flag_sym; fp++)
-	{
-	  if (flags & fp->flag_bit)
-	    {
-	      elt = Fcons (intern (fp->flag_sym), elt);
-	      flags -= fp->flag_bit;
-	    }
-	}
-      for (fnum = 0; flags && fnum < 32; flags >>= 1, fnum++)
-	{
-	  if (flags & 1)
-	    {
-	      elt = Fcons (make_number (fnum), elt);
-	    }
-	}
-    }
-#endif
-  res = Fcons (elt, res);
-
-  elt = Qnil;
-#if defined (SIOCGIFHWADDR) && defined (HAVE_STRUCT_IFREQ_IFR_HWADDR)
-  if (ioctl (s, SIOCGIFHWADDR, &rq) == 0)
-    {
-      Lisp_Object hwaddr = Fmake_vector (make_number (6), Qnil);
-      register struct Lisp_Vector *p = XVECTOR (hwaddr);
-      int n;
-
-      any = 1;
-      for (n = 0; n < 6; n++)
-	p->contents[n] = make_number (((unsigned char *)
-				       &rq.ifr_hwaddr.sa_data[0])
-				      [n]);
-      elt = Fcons (make_number (rq.ifr_hwaddr.sa_family), hwaddr);
-    }
-#elif defined (HAVE_GETIFADDRS) && defined (LLADDR)
-  if (getifaddrs (&ifap) != -1)
-    {
-      Lisp_Object hwaddr = Fmake_vector (make_number (6), Qnil);
-      register struct Lisp_Vector *p = XVECTOR (hwaddr);
-      struct ifaddrs *it;
-
-      for (it = ifap; it != NULL; it = it->ifa_next)
-        {
-	  DECLARE_POINTER_ALIAS (sdl, struct sockaddr_dl, it->ifa_addr);
-          unsigned char linkaddr[6];
-          int n;
-
-          if (it->ifa_addr->sa_family != AF_LINK
-              || strcmp (it->ifa_name, SSDATA (ifname)) != 0
-              || sdl->sdl_alen != 6)
-            continue;
-
-          memcpy (linkaddr, LLADDR (sdl), sdl->sdl_alen);
-          for (n = 0; n < 6; n++)
-            p->contents[n] = make_number (linkaddr[n]);
-
-          elt = Fcons (make_number (it->ifa_addr->sa_family), hwaddr);
-          break;
-        }
-    }
-#ifdef HAVE_FREEIFADDRS
-  freeifaddrs (ifap);
-#endif
-
-#endif /* HAVE_GETIFADDRS && LLADDR */
-
-  res = Fcons (elt, res);
-
-  elt = Qnil;
-#if defined (SIOCGIFNETMASK) && (defined (HAVE_STRUCT_IFREQ_IFR_NETMASK) || defined (HAVE_STRUCT_IFREQ_IFR_ADDR))
-  if (ioctl (s, SIOCGIFNETMASK, &rq) == 0)
-    {
-      any = 1;
-#ifdef HAVE_STRUCT_IFREQ_IFR_NETMASK
-      elt = conv_sockaddr_to_lisp (&rq.ifr_netmask, sizeof (rq.ifr_netmask));
-#else
-      elt = conv_sockaddr_to_lisp (&rq.ifr_addr, sizeof (rq.ifr_addr));
-#endif
-    }
-#endif
-  res = Fcons (elt, res);
-
-  elt = Qnil;
-#if defined (SIOCGIFBRDADDR) && defined (HAVE_STRUCT_IFREQ_IFR_BROADADDR)
-  if (ioctl (s, SIOCGIFBRDADDR, &rq) == 0)
-    {
-      any = 1;
-      elt = conv_sockaddr_to_lisp (&rq.ifr_broadaddr, sizeof (rq.ifr_broadaddr));
-    }
-#endif
-  res = Fcons (elt, res);
-
-  elt = Qnil;
-#if defined (SIOCGIFADDR) && defined (HAVE_STRUCT_IFREQ_IFR_ADDR)
-  if (ioctl (s, SIOCGIFADDR, &rq) == 0)
-    {
-      any = 1;
-      elt = conv_sockaddr_to_lisp (&rq.ifr_addr, sizeof (rq.ifr_addr));
-    }
-#endif
-  res = Fcons (elt, res);
-
-  return unbind_to (count, any ? res : Qnil);
-}
-#endif	/* !SIOCGIFADDR && !SIOCGIFHWADDR && !SIOCGIFFLAGS */
-#endif	/* defined (HAVE_NET_IF_H) */
-
-DEFUN ("network-interface-list", Fnetwork_interface_list,
-       Snetwork_interface_list, 0, 0, 0,
-       doc: /* Return an alist of all network interfaces and their network address.
-Each element is a cons, the car of which is a string containing the
-interface name, and the cdr is the network address in internal
-format; see the description of ADDRESS in `make-network-process'.
-
-If the information is not available, return nil.  */)
-  (void)
-{
-#if (defined HAVE_NET_IF_H && defined SIOCGIFCONF) || defined WINDOWSNT
-  return network_interface_list ();
-#else
-  return Qnil;
-#endif
-}
-
-DEFUN ("network-interface-info", Fnetwork_interface_info,
-       Snetwork_interface_info, 1, 1, 0,
-       doc: /* Return information about network interface named IFNAME.
-The return value is a list (ADDR BCAST NETMASK HWADDR FLAGS),
-where ADDR is the layer 3 address, BCAST is the layer 3 broadcast address,
-NETMASK is the layer 3 network mask, HWADDR is the layer 2 address, and
-FLAGS is the current flags of the interface.
-
-Data that is unavailable is returned as nil.  */)
-  (Lisp_Object ifname)
-{
-#if ((defined HAVE_NET_IF_H			       \
-      && (defined SIOCGIFADDR || defined SIOCGIFHWADDR \
-	  || defined SIOCGIFFLAGS))		       \
-     || defined WINDOWSNT)
-  return network_interface_info (ifname);
-#else
-  return Qnil;
-#endif
-}
-
 /* Turn off input and output for process PROC.  */
 
 static void
@@ -5149,6 +4864,12 @@ wait_reading_process_output (intmax_t time_limit, int nsecs, int read_kbd,
 	  else
 	    got_output_end_time = invalid_timespec ();
 
+	  /* Don't keep output held back by native accumulation waiting:
+	     if nothing is readable right now, the processes have paused
+	     and it is flushed below.  */
+	  if (process_output_held_p ())
+	    timeout = make_timespec (0, 0);
+
 	  /* NOW can become inaccurate if time can pass during pselect.  */
 	  if (timeout.tv_sec > 0 || timeout.tv_nsec > 0)
 	    now = invalid_timespec ();
@@ -5248,6 +4969,14 @@ wait_reading_process_output (intmax_t time_limit, int nsecs, int read_kbd,
 
       if (nfds == 0)
 	{
+	  /* Nothing is readable, so hand any output held back by native
+	     accumulation to the filters.  */
+	  int flushed = flush_held_process_output (wait_proc);
+	  if (got_some_output < flushed)
+	    got_some_output = flushed;
+	  if (flushed > 0 && wait_proc)
+	    wait = MINIMUM;
+
           /* Exit the main loop if we've passed the requested timeout,
              or aren't skipping processes and got some output and
              haven't lowered our timeout due to timers or SIGIO and
@@ -5595,7 +5324,8 @@ read_process_output_error_handler (Lisp_Object error_val)
 static void
 read_and_dispose_of_process_output (struct Lisp_Process *p, char *chars,
 				    ssize_t nbytes,
-				    struct coding_system *coding);
+				    struct coding_system *coding,
+				    bool more);
 
 /* Read pending output from the process channel,
    starting with our buffered-ahead character if we have one.
@@ -5681,7 +5411,9 @@ read_process_output (Lisp_Object proc, int channel)
   p->decoding_carryover = 0;
 
   /* At this point, NBYTES holds number of bytes just received
-     (including the one in proc_buffered_char[channel]).  */
+     (including the one in proc_buffered_char[channel]).  A full read
+     means more output is probably on its way.  */
+  bool more = nbytes == readmax;
   if (nbytes <= 0)
     {
       if (nbytes < 0 || coding->mode & CODING_MODE_LAST_BLOCK)
@@ -5698,7 +5430,7 @@ read_process_output (Lisp_Object proc, int channel)
      friends don't expect current-buffer to be changed from under them.  */
   record_unwind_current_buffer ();
 
-  read_and_dispose_of_process_output (p, chars, nbytes, coding);
+  read_and_dispose_of_process_output (p, chars, nbytes, coding, more);
 
   /* Handling the process output should not deactivate the mark.  */
   Vdeactivate_mark = odeactivate;
@@ -5707,10 +5439,62 @@ read_process_output (Lisp_Object proc, int channel)
   return nbytes;
 }
 
+/* Return true if some process has output held back by native
+   accumulation.  */
+static bool
+process_output_held_p (void)
+{
+  Lisp_Object tail, proc;
+
+  FOR_EACH_PROCESS (tail, proc)
+    {
+      Lisp_Object acc = XPROCESS (proc)->accumulation;
+      if (CONSP (acc) && STRINGP (XCDR (acc)))
+	return true;
+    }
+  return false;
+}
+
+/* Hand the output held back by native accumulation to the process
+   filters, as none of the processes is writing right now.  Return the
+   number of bytes handed to the filter of WAIT_PROC, or of any process
+   if WAIT_PROC is null, or -1 if there were none.  */
+static int
+flush_held_process_output (struct Lisp_Process *wait_proc)
+{
+  Lisp_Object tail, proc;
+  int flushed = -1;
+
+  FOR_EACH_PROCESS (tail, proc)
+    {
+      struct Lisp_Process *p = XPROCESS (proc);
+      Lisp_Object acc = p->accumulation;
+      if (! (CONSP (acc) && STRINGP (XCDR (acc))))
+	continue;
+
+      int nbytes = SBYTES (XCDR (acc));
+      ptrdiff_t count = SPECPDL_INDEX ();
+      Lisp_Object odeactivate = Vdeactivate_mark;
+      record_unwind_current_buffer ();
+      read_and_dispose_of_process_output (p, NULL, 0, NULL, false);
+      Vdeactivate_mark = odeactivate;
+      unbind_to (count, Qnil);
+
+      if ((!wait_proc || wait_proc == p) && flushed < nbytes)
+	flushed = nbytes;
+    }
+  return flushed;
+}
+
+/* Decode the NBYTES bytes of output at CHARS with CODING and hand them
+   to the filter of P.  MORE means P is probably still writing.  If
+   CHARS is null, just hand over the output held back by native
+   accumulation.  */
 static void
 read_and_dispose_of_process_output (struct Lisp_Process *p, char *chars,
 				    ssize_t nbytes,
-				    struct coding_system *coding)
+				    struct coding_system *coding,
+				    bool more)
 {
   Lisp_Object outstream = p->filter;
   Lisp_Object text;
@@ -5746,51 +5530,66 @@ read_and_dispose_of_process_output (struct Lisp_Process *p, char *chars,
      save the match data in a special nonrecursive fashion.  */
   running_asynch_code = 1;
 
-  decode_coding_c_string (coding, (unsigned char *) chars, nbytes, Qt);
-  text = coding->dst_object;
-  Vlast_coding_system_used = CODING_ID_NAME (coding->id);
-  /* A new coding system might be found.  */
-  if (!EQ (p->decode_coding_system, Vlast_coding_system_used))
+  if (!chars)
+    text = empty_unibyte_string;
+  else
     {
-      pset_decode_coding_system (p, Vlast_coding_system_used);
+      decode_coding_c_string (coding, (unsigned char *) chars, nbytes, Qt);
+      text = coding->dst_object;
+      Vlast_coding_system_used = CODING_ID_NAME (coding->id);
+      /* A new coding system might be found.  */
+      if (!EQ (p->decode_coding_system, Vlast_coding_system_used))
+	{
+	  pset_decode_coding_system (p, Vlast_coding_system_used);
 
-      /* Don't call setup_coding_system for
-	 proc_decode_coding_system[channel] here.  It is done in
-	 detect_coding called via decode_coding above.  */
+	  /* Don't call setup_coding_system for
+	     proc_decode_coding_system[channel] here.  It is done in
+	     detect_coding called via decode_coding above.  */
 
-      /* If a coding system for encoding is not yet decided, we set
-	 it as the same as coding-system for decoding.
+	  /* If a coding system for encoding is not yet decided, we set
+	     it as the same as coding-system for decoding.
 
-	 But, before doing that we must check if
-	 proc_encode_coding_system[p->outfd] surely points to a
-	 valid memory because p->outfd will be changed once EOF is
-	 sent to the process.  */
-      if (NILP (p->encode_coding_system) && p->outfd >= 0
-	  && proc_encode_coding_system[p->outfd])
+	     But, before doing that we must check if
+	     proc_encode_coding_system[p->outfd] surely points to a
+	     valid memory because p->outfd will be changed once EOF is
+	     sent to the process.  */
+	  if (NILP (p->encode_coding_system) && p->outfd >= 0
+	      && proc_encode_coding_system[p->outfd])
+	    {
+	      pset_encode_coding_system
+		(p, coding_inherit_eol_type (Vlast_coding_system_used, Qnil));
+	      setup_coding_system (p->encode_coding_system,
+				   proc_encode_coding_system[p->outfd]);
+	    }
+	}
+
+      if (coding->carryover_bytes > 0)
 	{
-	  pset_encode_coding_system
-	    (p, coding_inherit_eol_type (Vlast_coding_system_used, Qnil));
-	  setup_coding_system (p->encode_coding_system,
-			       proc_encode_coding_system[p->outfd]);
+	  if (SCHARS (p->decoding_buf) < coding->carryover_bytes)
+	    pset_decoding_buf (p, make_uninit_string (coding->carryover_bytes));
+	  memcpy (SDATA (p->decoding_buf), coding->carryover,
+		  coding->carryover_bytes);
+	  p->decoding_carryover = coding->carryover_bytes;
 	}
     }
-
-  if (coding->carryover_bytes > 0)
-    {
-      if (SCHARS (p->decoding_buf) < coding->carryover_bytes)
-	pset_decoding_buf (p, make_uninit_string (coding->carryover_bytes));
-      memcpy (SDATA (p->decoding_buf), coding->carryover,
-	      coding->carryover_bytes);
-      p->decoding_carryover = coding->carryover_bytes;
+  /* With native accumulation, hold back output until a line is
+     complete or the process pauses.  */
+  if (!NILP (p->accumulation))
+    text = accumulate_process_output (make_lisp_proc (p), text, more);
+  if (STRINGP (text) && SBYTES (text) > 0)
+    {
+      /* FIXME: It's wrong to wrap or not based on debug-on-error, and
+	 sometimes it's simply wrong to wrap (e.g. when called from
+	 accept-process-output).  */
+      internal_condition_case_1 (read_process_output_call,
+				 process_filter_call_args (outstream,
+							   make_lisp_proc (p),
+							   text),
+				 !NILP (Vdebug_on_error) ? Qnil : Qerror,
+				 read_process_output_error_handler);
+      if (!NILP (p->accumulation))
+	trim_process_scrollback (make_lisp_proc (p));
     }
-  if (SBYTES (text) > 0)
-    /* FIXME: It's wrong to wrap or not based on debug-on-error, and
-       sometimes it's simply wrong to wrap (e.g. when called from
-       accept-process-output).  */
-    internal_condition_case_1 (read_process_output_call,
-			       list3 (outstream, make_lisp_proc (p), text),
-			       !NILP (Vdebug_on_error) ? Qnil : Qerror,
-			       read_process_output_error_handler);
 
   /* If we saved the match data nonrecursively, restore it now.  */
   restore_search_regs ();
@@ -6250,7 +6049,7 @@ set up yet, this function will block until socket setup has completed.  */)
 
 /* Return the foreground process group for the tty/pty that
    the process P uses.  */
-static pid_t
+pid_t
 emacs_get_tty_pgrp (struct Lisp_Process *p)
 {
   pid_t gid = -1;
@@ -6303,187 +6102,50 @@ process group.  */)
   return Qt;
 }
 
-/* Send a signal number SIGNO to PROCESS.
-   If CURRENT_GROUP is t, that means send to the process group
-   that currently owns the terminal being used to communicate with PROCESS.
-   This is used for various commands in shell mode.
-   If CURRENT_GROUP is lambda, that means send to the process group
-   that currently owns the terminal, but only if it is NOT the shell itself.
-
-   If NOMSG is false, insert signal-announcements into process's buffers
-   right away.
-
-   If we can, we try to signal PROCESS by sending control characters
-   down the pty.  This allows us to signal inferiors who have changed
-   their uid, for which kill would return an EPERM error.  */
+/* Record that the stopped process P has been continued.  If NOMSG is
+   false, announce the change in the process's buffer right away.  */
 
-static void
-process_send_signal (Lisp_Object process, int signo, Lisp_Object current_group,
-		     bool nomsg)
+void
+process_continued (struct Lisp_Process *p, bool nomsg)
 {
-  Lisp_Object proc;
-  struct Lisp_Process *p;
-  pid_t gid;
-  bool no_pgrp = 0;
-
-  proc = get_process (process);
-  p = XPROCESS (proc);
-
-  if (!EQ (p->type, Qreal))
-    error ("Process %s is not a subprocess",
-	   SDATA (p->name));
-  if (p->infd < 0)
-    error ("Process %s is not active",
-	   SDATA (p->name));
-
-  if (!p->pty_flag)
-    current_group = Qnil;
-
-  /* If we are using pgrps, get a pgrp number and make it negative.  */
-  if (NILP (current_group))
-    /* Send the signal to the shell's process group.  */
-    gid = p->pid;
-  else
+  p->raw_status_new = 0;
+  pset_status (p, Qrun);
+  p->tick = ++process_tick;
+  if (!nomsg)
     {
-#ifdef SIGNALS_VIA_CHARACTERS
-      /* If possible, send signals to the entire pgrp
-	 by sending an input character to it.  */
-
-      struct termios t;
-      cc_t *sig_char = NULL;
-
-      tcgetattr (p->infd, &t);
-
-      switch (signo)
-	{
-	case SIGINT:
-	  sig_char = &t.c_cc[VINTR];
-	  break;
-
-	case SIGQUIT:
-	  sig_char = &t.c_cc[VQUIT];
-	  break;
-
-  	case SIGTSTP:
-#ifdef VSWTCH
-	  sig_char = &t.c_cc[VSWTCH];
-#else
-	  sig_char = &t.c_cc[VSUSP];
-#endif
-	  break;
-	}
-
-      if (sig_char && *sig_char != CDISABLE)
-	{
-	  send_process (proc, (char *) sig_char, 1, Qnil);
-	  return;
-	}
-      /* If we can't send the signal with a character,
-	 fall through and send it another way.  */
+      status_notify (NULL, NULL);
+      redisplay_preserve_echo_area (13);
+    }
+}
 
-      /* The code above may fall through if it can't
-	 handle the signal.  */
-#endif /* defined (SIGNALS_VIA_CHARACTERS) */
+/* Inhibit (if STOPPED) or resume handling of incoming traffic on the
+   network, serial or pipe connection P.  */
 
-#ifdef TIOCGPGRP
-      /* Get the current pgrp using the tty itself, if we have that.
-	 Otherwise, use the pty to get the pgrp.
-	 On pfa systems, saka@pfu.fujitsu.co.JP writes:
-	 "TIOCGPGRP symbol defined in sys/ioctl.h at E50.
-	 But, TIOCGPGRP does not work on E50 ;-P works fine on E60"
-	 His patch indicates that if TIOCGPGRP returns an error, then
-	 we should just assume that p->pid is also the process group id.  */
-
-      gid = emacs_get_tty_pgrp (p);
-
-      if (gid == -1)
-	/* If we can't get the information, assume
-	   the shell owns the tty.  */
-	gid = p->pid;
-
-      /* It is not clear whether anything really can set GID to -1.
-	 Perhaps on some system one of those ioctls can or could do so.
-	 Or perhaps this is vestigial.  */
-      if (gid == -1)
-	no_pgrp = 1;
-#else  /* ! defined (TIOCGPGRP) */
-      /* Can't select pgrps on this system, so we know that
-	 the child itself heads the pgrp.  */
-      gid = p->pid;
-#endif /* ! defined (TIOCGPGRP) */
-
-      /* If current_group is lambda, and the shell owns the terminal,
-	 don't send any signal.  */
-      if (EQ (current_group, Qlambda) && gid == p->pid)
-	return;
+void
+set_connection_stopped (struct Lisp_Process *p, bool stopped)
+{
+  if (stopped)
+    {
+      if (NILP (p->command)
+	  && p->infd >= 0)
+	delete_read_fd (p->infd);
+      pset_command (p, Qt);
+      return;
     }
 
-#ifdef SIGCONT
-  if (signo == SIGCONT)
+  if (EQ (p->command, Qt)
+      && p->infd >= 0
+      && (!EQ (p->filter, Qt) || EQ (p->status, Qlisten)))
     {
-      p->raw_status_new = 0;
-      pset_status (p, Qrun);
-      p->tick = ++process_tick;
-      if (!nomsg)
-	{
-	  status_notify (NULL, NULL);
-	  redisplay_preserve_echo_area (13);
-	}
+      add_process_read_fd (p->infd);
+#ifdef WINDOWSNT
+      if (fd_info[ p->infd ].flags & FILE_SERIAL)
+	PurgeComm (fd_info[ p->infd ].hnd, PURGE_RXABORT | PURGE_RXCLEAR);
+#else /* not WINDOWSNT */
+      tcflush (p->infd, TCIFLUSH);
+#endif /* not WINDOWSNT */
     }
-#endif
-
-#ifdef TIOCSIGSEND
-  /* Work around a HP-UX 7.0 bug that mishandles signals to subjobs.
-     We don't know whether the bug is fixed in later HP-UX versions.  */
-  if (! NILP (current_group) && ioctl (p->infd, TIOCSIGSEND, signo) != -1)
-    return;
-#endif
-
-  /* If we don't have process groups, send the signal to the immediate
-     subprocess.  That isn't really right, but it's better than any
-     obvious alternative.  */
-  pid_t pid = no_pgrp ? gid : - gid;
-
-  /* Do not kill an already-reaped process, as that could kill an
-     innocent bystander that happens to have the same process ID.  */
-  sigset_t oldset;
-  block_child_signal (&oldset);
-  if (p->alive)
-    kill (pid, signo);
-  unblock_child_signal (&oldset);
-}
-
-DEFUN ("internal-default-interrupt-process",
-       Finternal_default_interrupt_process,
-       Sinternal_default_interrupt_process, 0, 2, 0,
-       doc: /* Default function to interrupt process PROCESS.
-It shall be the last element in list `interrupt-process-functions'.
-See function `interrupt-process' for more details on usage.  */)
-  (Lisp_Object process, Lisp_Object current_group)
-{
-  process_send_signal (process, SIGINT, current_group, 0);
-  return process;
-}
-
-DEFUN ("interrupt-process", Finterrupt_process, Sinterrupt_process, 0, 2, 0,
-       doc: /* Interrupt process PROCESS.
-PROCESS may be a process, a buffer, or the name of a process or buffer.
-No arg or nil means current buffer's process.
-Second arg CURRENT-GROUP non-nil means send signal to
-the current process-group of the process's controlling terminal
-rather than to the process's own process group.
-If the process is a shell, this means interrupt current subjob
-rather than the shell.
-
-If CURRENT-GROUP is `lambda', and if the shell owns the terminal,
-don't send the signal.
-
-This function calls the functions of `interrupt-process-functions' in
-the order of the list, until one of them returns non-`nil'.  */)
-  (Lisp_Object process, Lisp_Object current_group)
-{
-  return CALLN (Frun_hook_with_args_until_success, Qinterrupt_process_functions,
-		process, current_group);
+  pset_command (p, Qnil);
 }
 
 DEFUN ("kill-process", Fkill_process, Skill_process, 0, 2, 0,
@@ -6504,69 +6166,6 @@ See function `interrupt-process' for more details on usage.  */)
   return process;
 }
 
-DEFUN ("stop-process", Fstop_process, Sstop_process, 0, 2, 0,
-       doc: /* Stop process PROCESS.  May be process or name of one.
-See function `interrupt-process' for more details on usage.
-If PROCESS is a network or serial or pipe connection, inhibit handling
-of incoming traffic.  */)
-  (Lisp_Object process, Lisp_Object current_group)
-{
-  if (PROCESSP (process) && (NETCONN_P (process) || SERIALCONN_P (process)
-			     || PIPECONN_P (process)))
-    {
-      struct Lisp_Process *p;
-
-      p = XPROCESS (process);
-      if (NILP (p->command)
-	  && p->infd >= 0)
-	delete_read_fd (p->infd);
-      pset_command (p, Qt);
-      return process;
-    }
-#ifndef SIGTSTP
-  error ("No SIGTSTP support");
-#else
-  process_send_signal (process, SIGTSTP, current_group, 0);
-#endif
-  return process;
-}
-
-DEFUN ("continue-process", Fcontinue_process, Scontinue_process, 0, 2, 0,
-       doc: /* Continue process PROCESS.  May be process or name of one.
-See function `interrupt-process' for more details on usage.
-If PROCESS is a network or serial process, resume handling of incoming
-traffic.  */)
-  (Lisp_Object process, Lisp_Object current_group)
-{
-  if (PROCESSP (process) && (NETCONN_P (process) || SERIALCONN_P (process)
-			     || PIPECONN_P (process)))
-    {
-      struct Lisp_Process *p;
-
-      p = XPROCESS (process);
-      if (EQ (p->command, Qt)
-	  && p->infd >= 0
-	  && (!EQ (p->filter, Qt) || EQ (p->status, Qlisten)))
-	{
-	  add_process_read_fd (p->infd);
-#ifdef WINDOWSNT
-	  if (fd_info[ p->infd ].flags & FILE_SERIAL)
-	    PurgeComm (fd_info[ p->infd ].hnd, PURGE_RXABORT | PURGE_RXCLEAR);
-#else /* not WINDOWSNT */
-	  tcflush (p->infd, TCIFLUSH);
-#endif /* not WINDOWSNT */
-	}
-      pset_command (p, Qnil);
-      return process;
-    }
-#ifdef SIGCONT
-    process_send_signal (process, SIGCONT, current_group, 0);
-#else
-    error ("No SIGCONT support");
-#endif
-  return process;
-}
-
 /* Return the integer value of the signal whose abbreviation is ABBR,
    or a negative number if there is no such signal.  */
 static int
@@ -7717,7 +7316,6 @@ returns non-`nil'.  */);
   defsubr (&Sset_process_sentinel);
   defsubr (&Sset_process_thread);
   defsubr (&Sprocess_thread);
-  defsubr (&Sset_process_window_size);
   defsubr (&Sset_process_inherit_coding_system_flag);
   defsubr (&Sprocess_contact);
   defsubr (&Smake_process);
@@ -7727,20 +7325,14 @@ returns non-`nil'.  */);
   defsubr (&Sset_network_process_option);
   defsubr (&Smake_network_process);
   defsubr (&Sformat_network_address);
-  defsubr (&Snetwork_interface_list);
-  defsubr (&Snetwork_interface_info);
 #ifdef DATAGRAM_SOCKETS
   defsubr (&Sprocess_datagram_address);
   defsubr (&Sset_process_datagram_address);
 #endif
   defsubr (&Saccept_process_output);
   defsubr (&Sprocess_send_region);
-  defsubr (&Sinternal_default_interrupt_process);
-  defsubr (&Sinterrupt_process);
   defsubr (&Skill_process);
   defsubr (&Squit_process);
-  defsubr (&Sstop_process);
-  defsubr (&Scontinue_process);
   defsubr (&Sprocess_running_child_p);
   defsubr (&Sprocess_send_eof);
   defsubr (&Ssignal_process);
@@ -118,6 +118,11 @@ struct Lisp_Process
     /* The thread a process is linked to, or nil for any thread.  */
     Lisp_Object thread;
 
+    /* Native output accumulation: nil if disabled, otherwise a cons
+       (MAX-LINES . PENDING) where PENDING is output held back from the
+       filter, or nil.  See `set-process-native-accumulation'.  */
+    Lisp_Object accumulation;
+
     /* After this point, there are no Lisp_Objects any more.  */
     /* alloc.c assumes that `pid' is the first such non-Lisp slot.  */
 
@@ -208,6 +213,15 @@ pget_pid(const struct Lisp_Process *p);
 bool_bf
 pget_kill_without_query(const struct Lisp_Process *p);
 
+int
+pget_infd(const struct Lisp_Process *p);
+
+bool_bf
+pget_pty_flag(const struct Lisp_Process *p);
+
+bool_bf
+pget_alive(const struct Lisp_Process *p);
+
 INLINE bool
 PROCESSP (Lisp_Object a)
 {
@@ -304,7 +318,22 @@ extern Lisp_Object remove_slash_colon (Lisp_Object);
 
 extern void update_processes_for_thread_death (Lisp_Object);
 
+/* Defined in Rust.  */
+extern Lisp_Object accumulate_process_output (Lisp_Object, Lisp_Object, bool);
+extern void trim_process_scrollback (Lisp_Object);
+extern void process_send_signal (Lisp_Object, int, Lisp_Object, bool);
+extern int set_multicast_option (int, Lisp_Object, Lisp_Object);
+extern Lisp_Object process_filter_call_args (Lisp_Object, Lisp_Object,
+					     Lisp_Object);
+#ifdef RUST_PTYS
+extern int allocate_pty (char *, ptrdiff_t);
+extern int pty_child_setup (int, const char *);
+#endif
+
 void pset_kill_without_query (struct Lisp_Process *p, bool_bf val);
+pid_t emacs_get_tty_pgrp (struct Lisp_Process *p);
+void process_continued (struct Lisp_Process *p, bool nomsg);
+void set_connection_stopped (struct Lisp_Process *p, bool stopped);
 
 INLINE_HEADER_END
 
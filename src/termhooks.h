@@ -224,6 +224,11 @@ enum event_kind
 
   , CONFIG_CHANGED_EVENT
 
+  /* A touchpad gesture.  .code is the id that touch_event_push
+     returned for it, .x and .y give the position and .modifiers the
+     modifier keys held.  */
+  , TOUCH_GESTURE_EVENT
+
 #ifdef HAVE_NTGUI
   /* Generated when an APPCOMMAND event is received, in response to
      Multimedia or Internet buttons on some keyboards.
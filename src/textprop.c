@@ -196,7 +196,7 @@ validate_interval_range (Lisp_Object object, Lisp_Object *begin,
    make one consisting of (LIST nil).  Otherwise, verify that LIST
    is even numbered and thus suitable as a plist.  */
 
-static Lisp_Object
+Lisp_Object
 validate_plist (Lisp_Object list)
 {
   if (NILP (list))
@@ -1254,6 +1254,87 @@ add_text_properties_1 (Lisp_Object start, Lisp_Object end,
     }
 }
 
+/* Add the properties of each of the NRUNS runs in RUNS to OBJECT, a
+   buffer or a string, walking its intervals once.  The runs have been
+   checked with validate_interval_range, and are sorted by their start
+   positions.  Where two runs overlap, the later
+   one's properties win.  The modification hooks of a buffer are run
+   once, for the text from the start of the first run to the end of the
+   last one.  Return true if any property changed.
+
+   Callers note, this can GC when OBJECT is a buffer.  */
+
+bool
+add_text_property_runs (Lisp_Object object, ptrdiff_t nruns,
+			struct text_property_run *runs)
+{
+  INTERVAL i;
+  ptrdiff_t r, last = runs[0].end;
+  bool modified = false;
+  Lisp_Object start, end;
+
+  for (r = 1; r < nruns; r++)
+    last = max (last, runs[r].end);
+  start = make_number (runs[0].start);
+  end = make_number (last);
+
+  if (BUFFERP (object))
+    modify_text_properties (object, start, end);
+
+  /* The hooks may have changed the text, so check the span again, and
+     look up its intervals only now.  */
+  i = validate_interval_range (object, &start, &end, hard);
+  if (!i)
+    return false;
+
+  for (r = 0; r < nruns; r++)
+    {
+      ptrdiff_t s = runs[r].start, e = runs[r].end;
+      Lisp_Object properties = validate_plist (runs[r].properties);
+
+      if (s == e || NILP (properties))
+	continue;
+      if (s < i->position)
+	/* This run overlaps the previous one.  */
+	i = find_interval (BUFFERP (object)
+			   ? buffer_intervals (XBUFFER (object))
+			   : string_intervals (object), s);
+      while (INTERVAL_LAST_POS (i) <= s)
+	i = next_interval (i);
+
+      /* Add the properties to each interval from S to E, splitting the
+	 ones that stick out at either end.  */
+      for (;;)
+	{
+	  if (! interval_has_all_properties (properties, i))
+	    {
+	      if (i->position < s)
+		{
+		  INTERVAL unchanged = i;
+		  i = split_interval_right (unchanged, s - unchanged->position);
+		  copy_properties (unchanged, i);
+		}
+	      if (INTERVAL_LAST_POS (i) > e)
+		{
+		  INTERVAL unchanged = i;
+		  i = split_interval_left (unchanged, e - unchanged->position);
+		  copy_properties (unchanged, i);
+		}
+	      modified |= add_properties (properties, i, object,
+					  TEXT_PROPERTY_REPLACE);
+	    }
+	  if (INTERVAL_LAST_POS (i) >= e)
+	    break;
+	  i = next_interval (i);
+	}
+    }
+
+  if (BUFFERP (object))
+    signal_after_change (XINT (start), XINT (end) - XINT (start),
+			 XINT (end) - XINT (start));
+  return modified;
+}
+
 /* Callers note, this can GC when OBJECT is a buffer (or nil).  */
 
 DEFUN ("add-text-properties", Fadd_text_properties,
@@ -1272,24 +1353,6 @@ Return t if any property value actually changed, nil otherwise.  */)
 				TEXT_PROPERTY_REPLACE);
 }
 
-/* Callers note, this can GC when OBJECT is a buffer (or nil).  */
-
-DEFUN ("put-text-property", Fput_text_property,
-       Sput_text_property, 4, 5, 0,
-       doc: /* Set one property of the text from START to END.
-The third and fourth arguments PROPERTY and VALUE
-specify the property to add.
-If the optional fifth argument OBJECT is a buffer (or nil, which means
-the current buffer), START and END are buffer positions (integers or
-markers).  If OBJECT is a string, START and END are 0-based indices into it.  */)
-  (Lisp_Object start, Lisp_Object end, Lisp_Object property,
-   Lisp_Object value, Lisp_Object object)
-{
-  AUTO_LIST2 (properties, property, value);
-  Fadd_text_properties (start, end, properties, object);
-  return Qnil;
-}
-
 DEFUN ("set-text-properties", Fset_text_properties,
        Sset_text_properties, 3, 4, 0,
        doc: /* Completely replace properties of text from START to END.
@@ -2378,7 +2441,6 @@ inherits it if NONSTICKINESS is nil.  The `front-sticky' and
   defsubr (&Sprevious_property_change);
   defsubr (&Sprevious_single_property_change);
   defsubr (&Sadd_text_properties);
-  defsubr (&Sput_text_property);
   defsubr (&Sset_text_properties);
   defsubr (&Sadd_face_text_property);
   defsubr (&Sremove_text_properties);
@@ -8044,12 +8044,10 @@ value of DIR as in previous invocations; this is standard Windows behavior.  */)
 
 
 #ifdef WINDOWSNT
-/* Moving files to the system recycle bin.
-   Used by `move-file-to-trash' instead of the default moving to ~/.Trash  */
-DEFUN ("system-move-file-to-trash", Fsystem_move_file_to_trash,
-       Ssystem_move_file_to_trash, 1, 1, 0,
-       doc: /* Move file or directory named FILENAME to the recycle bin.  */)
-  (Lisp_Object filename)
+/* Move file or directory named FILENAME to the recycle bin.
+   Used by `system-move-file-to-trash', which is defined in Rust.  */
+Lisp_Object
+w32_move_file_to_trash (Lisp_Object filename)
 {
   Lisp_Object handler;
   Lisp_Object encoded_file;
@@ -10791,9 +10789,6 @@ tip frame.  */);
   staticpro (&last_show_tip_args);
 
   defsubr (&Sx_file_dialog);
-#ifdef WINDOWSNT
-  defsubr (&Ssystem_move_file_to_trash);
-#endif
 }
 
 
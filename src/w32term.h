@@ -855,6 +855,9 @@ typedef char guichar_t;
 
 extern Lisp_Object w32_popup_dialog (struct frame *, Lisp_Object, Lisp_Object);
 extern void w32_arrow_cursor (void);
+#ifdef WINDOWSNT
+extern Lisp_Object w32_move_file_to_trash (Lisp_Object);
+#endif
 
 extern void syms_of_w32term (void);
 extern void syms_of_w32menu (void);
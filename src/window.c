@@ -239,6 +239,12 @@ wget_pseudo_window_p(struct window *w)
   return WINDOW_PSEUDO_P(w);
 }
 
+bool
+wget_horizontal(struct window *w)
+{
+  return WINDOW_HORIZONTAL_COMBINATION_P(w);
+}
+
 /* True if leaf window W doesn't reflect the actual state
    of displayed buffer due to its text or overlays change.  */
 
@@ -4691,17 +4697,8 @@ window_scroll_margin (struct window *window, enum margin_unit unit)
     {
       int frame_line_height = default_line_pixel_height (window);
       int window_lines = window_box_height (window) / frame_line_height;
-
-      double ratio = 0.25;
-      if (FLOATP (Vmaximum_scroll_margin))
-        {
-          ratio = XFLOAT_DATA (Vmaximum_scroll_margin);
-          ratio = max (0.0, ratio);
-          ratio = min (ratio, 0.5);
-        }
-      int max_margin = min ((window_lines - 1)/2,
-                            (int) (window_lines * ratio));
-      int margin = clip_to_bounds (0, scroll_margin, max_margin);
+      int margin = scroll_margin_lines (scroll_margin, window_lines,
+					Vmaximum_scroll_margin);
       return (unit == MARGIN_IN_PIXELS)
         ? margin * frame_line_height
         : margin;
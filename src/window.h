@@ -485,6 +485,8 @@ int
 wget_pixel_height(struct window *w);
 bool
 wget_pseudo_window_p(struct window *w);
+bool
+wget_horizontal(struct window *w);
 
 /* True if W is a minibuffer window.  */
 #define MINI_WINDOW_P(W) ((W)->mini)
@@ -15558,11 +15558,10 @@ try_scrolling (Lisp_Object window, bool just_this_one_p,
   struct window *w = XWINDOW (window);
   struct text_pos pos, startp;
   struct it it;
-  int this_scroll_margin, scroll_max, rc, height;
+  int this_scroll_margin, scroll_max, rc;
   int dy = 0, amount_to_scroll = 0;
   bool scroll_down_p = false;
   int extra_scroll_margin_lines = last_line_misfit;
-  Lisp_Object aggressive;
   /* We will never try scrolling more than this number of lines.  */
   int scroll_limit = SCROLL_LIMIT;
   int frame_line_height = default_line_pixel_height (w);
@@ -15576,27 +15575,19 @@ try_scrolling (Lisp_Object window, bool just_this_one_p,
   this_scroll_margin = window_scroll_margin (w, MARGIN_IN_PIXELS);
 
   /* Force arg_scroll_conservatively to have a reasonable value, to
-     avoid scrolling too far away with slow move_it_* functions.  Note
-     that the user can supply scroll-conservatively equal to
-     `most-positive-fixnum', which can be larger than INT_MAX.  */
-  if (arg_scroll_conservatively > scroll_limit)
-    {
-      arg_scroll_conservatively = scroll_limit + 1;
-      scroll_max = scroll_limit * frame_line_height;
-    }
-  else if (scroll_step || arg_scroll_conservatively || temp_scroll_step)
-    /* Compute how much we should try to scroll maximally to bring
-       point into view.  */
-    scroll_max = (max (scroll_step,
-		       max (arg_scroll_conservatively, temp_scroll_step))
-		  * frame_line_height);
-  else if (NUMBERP (BVAR (current_buffer, scroll_down_aggressively))
-	   || NUMBERP (BVAR (current_buffer, scroll_up_aggressively)))
-    /* We're trying to scroll because of aggressive scrolling but no
-       scroll_step is set.  Choose an arbitrary one.  */
-    scroll_max = 10 * frame_line_height;
-  else
-    scroll_max = 0;
+     avoid scrolling too far away with slow move_it_* functions, and
+     compute how much we should try to scroll maximally to bring point
+     into view.  Note that the user can supply scroll-conservatively
+     equal to `most-positive-fixnum', which can be larger than
+     INT_MAX.  */
+  scroll_max
+    = scroll_max_distance (&arg_scroll_conservatively, scroll_step,
+			   temp_scroll_step,
+			   (NUMBERP (BVAR (current_buffer,
+					   scroll_down_aggressively))
+			    || NUMBERP (BVAR (current_buffer,
+					      scroll_up_aggressively))),
+			   frame_line_height);
 
  too_near_end:
 
@@ -15681,33 +15672,12 @@ try_scrolling (Lisp_Object window, bool just_this_one_p,
 	 window start down.  If scrolling conservatively, move it just
 	 enough down to make point visible.  If scroll_step is set,
 	 move it down by scroll_step.  */
-      if (arg_scroll_conservatively)
-	amount_to_scroll
-	  = min (max (dy, frame_line_height),
-		 frame_line_height * arg_scroll_conservatively);
-      else if (scroll_step || temp_scroll_step)
-	amount_to_scroll = scroll_max;
-      else
-	{
-	  aggressive = BVAR (current_buffer, scroll_up_aggressively);
-	  height = WINDOW_BOX_TEXT_HEIGHT (w);
-	  if (NUMBERP (aggressive))
-	    {
-	      double float_amount = XFLOATINT (aggressive) * height;
-	      int aggressive_scroll = float_amount;
-	      if (aggressive_scroll == 0 && float_amount > 0)
-		aggressive_scroll = 1;
-	      /* Don't let point enter the scroll margin near top of
-		 the window.  This could happen if the value of
-		 scroll_up_aggressively is too large and there are
-		 non-zero margins, because scroll_up_aggressively
-		 means put point that fraction of window height
-		 _from_the_bottom_margin_.  */
-	      if (aggressive_scroll + 2 * this_scroll_margin > height)
-		aggressive_scroll = height - 2 * this_scroll_margin;
-	      amount_to_scroll = dy + aggressive_scroll;
-	    }
-	}
+      amount_to_scroll
+	= scroll_amount (true, dy, arg_scroll_conservatively, scroll_step,
+			 temp_scroll_step, scroll_max,
+			 BVAR (current_buffer, scroll_up_aggressively),
+			 WINDOW_BOX_TEXT_HEIGHT (w), this_scroll_margin,
+			 frame_line_height);
 
       if (amount_to_scroll <= 0)
 	return SCROLLING_FAILED;
@@ -15798,30 +15768,12 @@ try_scrolling (Lisp_Object window, bool just_this_one_p,
 	  /* Compute new window start.  */
 	  start_display (&it, w, startp);
 
-	  if (arg_scroll_conservatively)
-	    amount_to_scroll = max (dy, frame_line_height
-				    * max (scroll_step, temp_scroll_step));
-	  else if (scroll_step || temp_scroll_step)
-	    amount_to_scroll = scroll_max;
-	  else
-	    {
-	      aggressive = BVAR (current_buffer, scroll_down_aggressively);
-	      height = WINDOW_BOX_TEXT_HEIGHT (w);
-	      if (NUMBERP (aggressive))
-		{
-		  double float_amount = XFLOATINT (aggressive) * height;
-		  int aggressive_scroll = float_amount;
-		  if (aggressive_scroll == 0 && float_amount > 0)
-		    aggressive_scroll = 1;
-		  /* Don't let point enter the scroll margin near
-		     bottom of the window, if the value of
-		     scroll_down_aggressively happens to be too
-		     large.  */
-		  if (aggressive_scroll + 2 * this_scroll_margin > height)
-		    aggressive_scroll = height - 2 * this_scroll_margin;
-		  amount_to_scroll = dy + aggressive_scroll;
-		}
-	    }
+	  amount_to_scroll
+	    = scroll_amount (false, dy, arg_scroll_conservatively,
+			     scroll_step, temp_scroll_step, scroll_max,
+			     BVAR (current_buffer, scroll_down_aggressively),
+			     WINDOW_BOX_TEXT_HEIGHT (w), this_scroll_margin,
+			     frame_line_height);
 
 	  if (amount_to_scroll <= 0)
 	    return SCROLLING_FAILED;
@@ -20797,22 +20749,16 @@ find_row_edges (struct it *it, struct glyph_row *row,
     row->maxpos = it->current.pos;
 }
 
-/* Like display_count_lines, but capable of counting outside of the
-   current narrowed region.  */
+/* Count the lines between START_BYTE and LIMIT_BYTE like
+   display_count_lines, but outside of the current narrowed region too,
+   and from the line counts that count_display_lines remembers, so that
+   large buffers are not scanned from the start at each redisplay.  */
 static ptrdiff_t
 display_count_lines_logically (ptrdiff_t start_byte, ptrdiff_t limit_byte,
-			       ptrdiff_t count, ptrdiff_t *byte_pos_ptr)
+			       ptrdiff_t *byte_pos_ptr)
 {
-  if (!display_line_numbers_widen || (BEGV == BEG && ZV == Z))
-    return display_count_lines (start_byte, limit_byte, count, byte_pos_ptr);
-
-  ptrdiff_t val;
-  ptrdiff_t pdl_count = SPECPDL_INDEX ();
-  record_unwind_protect (save_restriction_restore, save_restriction_save ());
-  Fwiden ();
-  val = display_count_lines (start_byte, limit_byte, count, byte_pos_ptr);
-  unbind_to (pdl_count, Qnil);
-  return val;
+  *byte_pos_ptr = limit_byte;
+  return count_display_lines (start_byte, limit_byte);
 }
 
 /* Count the number of screen lines in window IT->w between character
@@ -20914,8 +20860,7 @@ maybe_produce_line_number (struct it *it)
 
       this_line =
 	last_line + display_count_lines_logically (start_from,
-						   IT_BYTEPOS (*it),
-						   IT_CHARPOS (*it), &bytepos);
+						   IT_BYTEPOS (*it), &bytepos);
       eassert (this_line > 0 || (this_line == 0 && start_from == beg_byte));
       eassert (bytepos == IT_BYTEPOS (*it));
     }
@@ -20945,9 +20890,9 @@ maybe_produce_line_number (struct it *it)
       if (PT_BYTE > it->lnum_bytepos && !EQ (Vdisplay_line_numbers, Qvisual))
 	it->pt_lnum =
 	  this_line + display_count_lines_logically (it->lnum_bytepos, PT_BYTE,
-						     PT, &ignored);
+						     &ignored);
       else
-	it->pt_lnum = display_count_lines_logically (beg_byte, PT_BYTE, PT,
+	it->pt_lnum = display_count_lines_logically (beg_byte, PT_BYTE,
 						     &ignored);
     }
   /* Compute the required width if needed.  */
@@ -32366,6 +32311,13 @@ A value of nil means to show the text pointer.  Other options are
 This is used for internal purposes.  */);
   Vinhibit_redisplay = Qnil;
 
+  DEFVAR_LISP ("format-mode-line-cache", Vformat_mode_line_cache,
+    doc: /* Values of `:eval' forms kept by `format-mode-line-cached'.
+This is a weak hash table mapping each form to an alist of elements
+\(WINDOW TICK . VALUE), or nil before the first use.  Setting it to nil
+forgets all the values.  */);
+  Vformat_mode_line_cache = Qnil;
+
   DEFVAR_LISP ("global-mode-string", Vglobal_mode_string,
     doc: /* String (or mode line construct) included (normally) in `mode-line-format'.  */);
   Vglobal_mode_string = Qnil;
@@ -3916,6 +3916,7 @@ This function is an internal primitive--use `make-frame' instead.  */)
 #else
   x_window (f);
 #endif
+  x_select_gesture_events (f);
 
   x_icon (f, parms);
   x_make_gc (f);
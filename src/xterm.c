@@ -38,6 +38,11 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
 #include <X11/extensions/Xfixes.h>
 #endif
 
+/* If we have XInput 2.4, use it for touchpad gestures.  */
+#ifdef HAVE_XINPUT2
+#include <X11/extensions/XInput2.h>
+#endif
+
 /* Using Xft implies that XRender is available.  */
 #ifdef HAVE_XFT
 #include <X11/extensions/Xrender.h>
@@ -7650,6 +7655,75 @@ flush_dirty_back_buffers (void)
 
    We return the number of characters stored into the buffer.  */
 
+#ifdef HAVE_XINPUT2
+
+/* Fill in IE for the XInput gesture event of type EVTYPE whose data
+   is DATA.  Leave IE alone if the gesture is not on one of our
+   frames.  */
+
+static void
+x_handle_gesture_event (struct x_display_info *dpyinfo, int evtype,
+			void *data, struct input_event *ie)
+{
+  int kind, phase;
+  XIGesturePinchEvent *pinch = data;
+  XIGestureSwipeEvent *swipe = data;
+  struct frame *f;
+
+  switch (evtype)
+    {
+    case XI_GesturePinchBegin:
+    case XI_GestureSwipeBegin:
+      phase = 0;
+      break;
+    case XI_GesturePinchUpdate:
+    case XI_GestureSwipeUpdate:
+      phase = 1;
+      break;
+    case XI_GesturePinchEnd:
+    case XI_GestureSwipeEnd:
+      phase = 2;
+      break;
+    default:
+      return;
+    }
+
+  if (evtype == XI_GesturePinchBegin || evtype == XI_GesturePinchUpdate
+      || evtype == XI_GesturePinchEnd)
+    {
+      f = x_window_to_frame (dpyinfo, pinch->event);
+      if (!f)
+	return;
+      kind = 0;
+      ie->code = touch_event_push (kind, phase, pinch->detail,
+				   pinch->delta_x, pinch->delta_y,
+				   pinch->scale, pinch->delta_angle);
+      ie->modifiers = x_x_to_emacs_modifiers (dpyinfo,
+					      pinch->mods.effective);
+      XSETINT (ie->x, lrint (pinch->event_x));
+      XSETINT (ie->y, lrint (pinch->event_y));
+      ie->timestamp = pinch->time;
+    }
+  else
+    {
+      f = x_window_to_frame (dpyinfo, swipe->event);
+      if (!f)
+	return;
+      kind = 2;
+      ie->code = touch_event_push (kind, phase, swipe->detail,
+				   swipe->delta_x, swipe->delta_y, 1.0, 0.0);
+      ie->modifiers = x_x_to_emacs_modifiers (dpyinfo,
+					      swipe->mods.effective);
+      XSETINT (ie->x, lrint (swipe->event_x));
+      XSETINT (ie->y, lrint (swipe->event_y));
+      ie->timestamp = swipe->time;
+    }
+  ie->kind = TOUCH_GESTURE_EVENT;
+  XSETFRAME (ie->frame_or_window, f);
+}
+
+#endif /* HAVE_XINPUT2 */
+
 static int
 handle_one_xevent (struct x_display_info *dpyinfo,
 		   const XEvent *event,
@@ -8994,6 +9068,23 @@ handle_one_xevent (struct x_display_info *dpyinfo,
       xft_settings_event (dpyinfo, event);
       break;
 
+#ifdef HAVE_XINPUT2
+    case GenericEvent:
+      if (dpyinfo->supports_xi2_gestures
+	  && event->xcookie.extension == dpyinfo->xi2_opcode
+	  && XGetEventData (dpyinfo->display,
+			    (XGenericEventCookie *) &event->xcookie))
+	{
+	  x_handle_gesture_event (dpyinfo, event->xcookie.evtype,
+				  event->xcookie.data, &inev.ie);
+	  XFreeEventData (dpyinfo->display,
+			  (XGenericEventCookie *) &event->xcookie);
+	  if (inev.ie.kind != NO_EVENT)
+	    break;
+	}
+      goto OTHER;
+#endif
+
     default:
     OTHER:
 #ifdef USE_X_TOOLKIT
@@ -12279,6 +12370,50 @@ x_toggle_visible_pointer (struct frame *f, bool invisible)
   f->pointer_invisible = invisible;
 }
 
+/* Find out whether the X server reports touchpad gestures, which
+   takes XInput 2.4.  */
+
+static void
+x_probe_xinput2_gestures (struct x_display_info *dpyinfo)
+{
+#ifdef HAVE_XINPUT2
+  int event_base, error_base;
+  int major = 2, minor = 4;
+
+  dpyinfo->supports_xi2_gestures
+    = (XQueryExtension (dpyinfo->display, "XInputExtension",
+			&dpyinfo->xi2_opcode, &event_base, &error_base)
+       && XIQueryVersion (dpyinfo->display, &major, &minor) == Success
+       && (major > 2 || (major == 2 && minor >= 4)));
+#endif
+}
+
+/* Ask for the touchpad gestures on the window of frame F.  */
+
+void
+x_select_gesture_events (struct frame *f)
+{
+#ifdef HAVE_XINPUT2
+  unsigned char mask[XIMaskLen (XI_LASTEVENT)];
+  XIEventMask event_mask;
+
+  if (!FRAME_DISPLAY_INFO (f)->supports_xi2_gestures)
+    return;
+
+  memset (mask, 0, sizeof mask);
+  XISetMask (mask, XI_GesturePinchBegin);
+  XISetMask (mask, XI_GesturePinchUpdate);
+  XISetMask (mask, XI_GesturePinchEnd);
+  XISetMask (mask, XI_GestureSwipeBegin);
+  XISetMask (mask, XI_GestureSwipeUpdate);
+  XISetMask (mask, XI_GestureSwipeEnd);
+  event_mask.deviceid = XIAllMasterDevices;
+  event_mask.mask_len = sizeof mask;
+  event_mask.mask = mask;
+  XISelectEvents (FRAME_X_DISPLAY (f), FRAME_X_WINDOW (f), &event_mask, 1);
+#endif
+}
+
 /* Setup pointer blanking, prefer Xfixes if available.  */
 
 static void
@@ -12773,6 +12908,7 @@ x_term_init (Lisp_Object display_name, char *xrm_option, char *resource_name)
 				   1, 0, 1);
 
   x_setup_pointer_blanking (dpyinfo);
+  x_probe_xinput2_gestures (dpyinfo);
 
 #ifdef HAVE_X_I18N
   xim_initialize (dpyinfo, resource_name);
@@ -475,6 +475,13 @@ struct x_display_info
   int xrandr_minor_version;
 #endif
 
+#ifdef HAVE_XINPUT2
+  /* The major opcode of the XInput extension, and whether the server
+     reports touchpad gestures.  */
+  int xi2_opcode;
+  bool supports_xi2_gestures;
+#endif
+
 #ifdef USE_CAIRO
   XExtCodes *ext_codes;
 #endif
@@ -1103,6 +1110,7 @@ extern void x_uncatch_errors_after_check (void);
 extern void x_clear_errors (Display *);
 extern void xembed_request_focus (struct frame *);
 extern void x_ewmh_activate_frame (struct frame *);
+extern void x_select_gesture_events (struct frame *);
 extern void x_delete_terminal (struct terminal *terminal);
 extern unsigned long x_copy_color (struct frame *, unsigned long);
 #ifdef USE_X_TOOLKIT
@@ -0,0 +1,52 @@
+;;; ansi-color-tests.el --- tests for native ANSI color translation  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest ansi-color-apply-native-basic ()
+  (let ((s (ansi-color-apply-native "a\e[1;31mbc\e[0md")))
+    (should (equal s "abcd"))
+    (should-not (get-text-property 0 'font-lock-face s))
+    (should (equal (get-text-property 1 'font-lock-face s)
+                   '(:weight bold :foreground "red3")))
+    (should (eq (next-single-property-change 1 'font-lock-face s) 3))
+    (should-not (get-text-property 3 'font-lock-face s))))
+
+(ert-deftest ansi-color-apply-native-extended-colors ()
+  (let ((s (ansi-color-apply-native "\e[38;5;196;48;2;0;0;255mx")))
+    (should (equal (get-text-property 0 'font-lock-face s)
+                   '(:foreground "#ff0000" :background "#0000ff")))))
+
+(ert-deftest ansi-color-apply-native-removes-other-sequences ()
+  (should (equal (ansi-color-apply-native "a\e[2Kb\e[1Ac") "abc"))
+  (should (equal (ansi-color-apply-native "é\e[32mé") "éé")))
+
+(ert-deftest ansi-color-apply-native-stream ()
+  (let* ((context (list nil))
+         (first (ansi-color-apply-native-stream "a\e[3" context))
+         (second (ansi-color-apply-native-stream "2mb" context)))
+    (should (equal first "a"))
+    (should (equal context '((32))))
+    (should (equal second "b"))
+    (should (equal (get-text-property 0 'font-lock-face second)
+                   '(:foreground "green3")))
+    (ansi-color-apply-native-stream "\e[m" context)
+    (should (equal context '(nil)))))
+
+(provide 'ansi-color-tests)
+;;; ansi-color-tests.el ends here
@@ -0,0 +1,46 @@
+;;; face-remap-tests.el --- tests for face-remap.el  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+(require 'face-remap)
+
+(defun face-remap-tests--pinch (phase scale)
+  (list 'pinch (posn-at-point)
+        (list :phase phase :fingers 2 :delta '(0.0 . 0.0)
+              :scale scale :angle 0.0)))
+
+(ert-deftest text-scale-pinch ()
+  (with-temp-buffer
+    (let ((text-scale-mode-step 2.0))
+      (text-scale-pinch (face-remap-tests--pinch 'begin 1.0))
+      (should-not text-scale-mode)
+      (text-scale-pinch (face-remap-tests--pinch 'update 2.0))
+      (should text-scale-mode)
+      (should (= text-scale-mode-amount 1))
+      (text-scale-pinch (face-remap-tests--pinch 'end 4.0))
+      (should (= text-scale-mode-amount 2))
+      ;; A new pinch starts from the current size.
+      (text-scale-pinch (face-remap-tests--pinch 'begin 1.0))
+      (text-scale-pinch (face-remap-tests--pinch 'update 0.5))
+      (should (= text-scale-mode-amount 1))
+      (text-scale-pinch (face-remap-tests--pinch 'end 0.25))
+      (should-not text-scale-mode))))
+
+(provide 'face-remap-tests)
+;;; face-remap-tests.el ends here
@@ -173,6 +173,47 @@ This includes initialization and closing the bus."
   (dbus-unregister-service :session dbus-service-emacs)
   (should-not (dbus-ping :session dbus-service-emacs 100)))
 
+;; The following tests need the D-Bus client written in Rust, which
+;; waits for replies to its own calls without holding up Emacs.
+
+(ert-deftest dbus-test04-init-bus ()
+  "Check reconnecting to the `:session' bus."
+  (skip-unless dbus--test-enabled-session-bus)
+  (let ((name (dbus-get-unique-name :session)))
+    (should (= (dbus--init-bus :session) 1))
+    (should (string-prefix-p ":" (dbus-get-unique-name :session)))
+    (should-not (string-equal (dbus-get-unique-name :session) name))
+    (should (dbus-ping :session dbus-service-dbus 1000))))
+
+(ert-deftest dbus-test04-native-call ()
+  "Check a method call from Rust code that fails.
+Messages that arrive while it waits for the reply must still reach
+their handlers."
+  (skip-unless
+   (and dbus--test-enabled-session-bus
+	(fboundp 'secrets-lookup)
+	;; The call below must fail without prompting anybody.
+	(not (member "org.freedesktop.secrets"
+		     (dbus-list-known-names :session)))
+	(not (member "org.freedesktop.secrets"
+		     (dbus-list-activatable-names :session)))))
+  (let* (received
+	 (object (dbus-register-signal
+		  :session nil dbus-path-emacs dbus-interface-emacs
+		  "TestNativeCall" (lambda (&rest args) (setq received args)))))
+    (unwind-protect
+	(progn
+	  (dbus-send-signal
+	   :session nil dbus-path-emacs dbus-interface-emacs
+	   "TestNativeCall" "hello")
+	  ;; The signal comes in while the error reply is awaited.
+	  (should-error (secrets-lookup "dbus-tests") :type 'dbus-error)
+	  (with-timeout (5 (ert-fail "Signal not received"))
+	    (while (not received)
+	      (read-event nil nil 0.1)))
+	  (should (equal received '("hello"))))
+      (dbus-unregister-object object))))
+
 (defun dbus-test-all (&optional interactive)
   "Run all tests for \\[dbus]."
   (interactive "p")
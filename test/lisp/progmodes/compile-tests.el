@@ -379,4 +379,55 @@ The test data is in `compile-tests--test-regexps-data'."
     (font-lock-mode -1)
     (mapc #'compile--test-error-line compile-tests--test-regexps-data)))
 
+(ert-deftest compile-test-error-regexps-lisp ()
+  "Test the `compilation-error-regexp-alist' regexps searched in Lisp."
+  (let ((compilation-parse-native nil))
+    (with-temp-buffer
+      (font-lock-mode -1)
+      (mapc #'compile--test-error-line compile-tests--test-regexps-data))))
+
+(defconst compile-tests--native-rules
+  '(("^\\([^:\n]+\\):\\([0-9]+\\):\\(?:\\([0-9]+\\):\\)? \\(?:\\(warning\\)\\|error\\)"
+     1 2 3 (4))
+    ;; A back reference, which is searched for in Lisp.
+    ("^\\(x+\\)\\1$" nil 1))
+  "Rules for `compile-test-parse-region-native'.")
+
+(ert-deftest compile-test-parse-region-native ()
+  (skip-unless (fboundp 'compilation-parse-region-native))
+  (with-temp-buffer
+    (insert "café.c:12:3: warning: w\nxx\nbar.c:7: ERROR: e\n")
+    (let* ((case-fold-search t)
+           (result (compilation-parse-region-native
+                    (point-min) (point-max) compile-tests--native-rules))
+           (records (car result)))
+      (should (equal (cdr result) (last compile-tests--native-rules)))
+      (should (= (length records) 2))
+      (should (eq (plist-get (car records) :rule)
+                  (car compile-tests--native-rules)))
+      (should (equal (mapcar (lambda (r) (plist-get r :file)) records)
+                     '("café.c" "bar.c")))
+      (should (equal (mapcar (lambda (r) (plist-get r :line)) records)
+                     '(12 7)))
+      (should (equal (mapcar (lambda (r) (plist-get r :column)) records)
+                     '(3 nil)))
+      (should (equal (mapcar (lambda (r) (plist-get r :type)) records)
+                     '(1 2)))
+      ;; The match data is that of `re-search-forward'.
+      (dolist (record records)
+        (goto-char (car (plist-get record :match-data)))
+        (should (re-search-forward
+                 (caar compile-tests--native-rules) nil t))
+        (should (equal (plist-get record :match-data)
+                       (match-data t)))))
+    ;; Case is significant without `case-fold-search'.
+    (let ((case-fold-search nil))
+      (should (= (length (car (compilation-parse-region-native
+                               (point-min) (point-max)
+                               compile-tests--native-rules)))
+                 1)))
+    ;; `^' does not match in the middle of a line.
+    (should-not (car (compilation-parse-region-native
+                      2 21 (list (car compile-tests--native-rules)))))))
+
 ;;; compile-tests.el ends here
@@ -111,3 +111,62 @@
             (should (visit-tags-table-buffer))
             (should (equal tags-file-name (car tag-tables)))))
       (delete-file file))))
+
+(ert-deftest etags-native-completion-table ()
+  "Test that the native completion table has the names a scan finds."
+  (skip-unless (fboundp 'tags-completions-native))
+  (with-current-buffer
+      (find-file-noselect
+       (expand-file-name "manual/etags/ETAGS.good_1" etags-tests--test-dir))
+    (should (etags--use-native-p))
+    (should (equal (etags-tags-completion-table)
+                   (sort (delete "" (delete-dups
+                                     (etags--scan-tags-completion-table)))
+                         #'string<)))
+    (should (equal (tags-completions-native "LL_" buffer-file-name)
+                   (all-completions "LL_" (etags-tags-completion-table))))
+    (should (member "ll_task_procedure_access/t"
+                    (mapcar #'downcase
+                            (tags-completions-native "ll_" buffer-file-name
+                                                     t))))))
+
+(ert-deftest etags-native-find-definitions ()
+  "Test that native lookup finds the definitions a search finds."
+  (skip-unless (fboundp 'tags-lookup-native))
+  (let ((tags-file-name nil)
+        (tags-table-list nil))
+    (visit-tags-table
+     (expand-file-name "manual/etags/ETAGS.good_1" etags-tests--test-dir))
+    (dolist (name '("PrintAdd" "LL_Task_Procedure_Access/t" "main" "nosuch"))
+      (should (equal (etags--xref-find-definitions name)
+                     (cl-letf (((symbol-function 'etags--use-native-p)
+                                #'ignore))
+                       (etags--xref-find-definitions name)))))))
+
+(ert-deftest etags-native-lookup ()
+  "Test `tags-lookup-native' on a small tags table."
+  (skip-unless (fboundp 'tags-lookup-native))
+  (let ((file (make-temp-file "etags-tests" nil nil
+                              (concat "\f\nsrc/a.c,50\n"
+                                      "int foo(\1773,10\n"
+                                      "#define BAR \177BAR\0015,40\n"
+                                      "\f\nsrc/b.el,20\n"
+                                      "(defun foo \177\n"))))
+    (unwind-protect
+        (progn
+          (should (equal (tags-lookup-native "foo" file)
+                         '(("src/a.c" "int foo(" 3 . 10)
+                           ("src/b.el" "(defun foo " nil))))
+          (should (equal (tags-lookup-native 'BAR file)
+                         '(("src/a.c" "#define BAR " 5 . 40))))
+          (should-not (tags-lookup-native "bar" file))
+          (should (tags-lookup-native "bar" file t))
+          ;; The index follows changes to the file.
+          (with-temp-file file
+            (insert "\f\nc.c,10\nint baz;\1771,0\n"))
+          (should-not (tags-lookup-native "foo" file))
+          (should (equal (tags-completions-native "" file) '("baz")))
+          (with-temp-file file
+            (insert "not a tags table\n"))
+          (should-error (tags-lookup-native "foo" file)))
+      (delete-file file))))
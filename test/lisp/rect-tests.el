@@ -0,0 +1,61 @@
+;;; rect-tests.el --- tests for rect.el  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+(require 'rect)
+
+(defun rect-tests--extract (text startcol endcol)
+  (with-temp-buffer
+    (setq tab-width 8)
+    (insert text)
+    (goto-char (point-min))
+    (let ((lines (list nil)))
+      (extract-rectangle-line startcol endcol lines)
+      (cadr lines))))
+
+(ert-deftest rect-test-extract-line ()
+  (should (equal (rect-tests--extract "abcdef" 1 4) "bcd"))
+  (should (equal (rect-tests--extract "ab" 1 4) "b  "))
+  (should (equal (rect-tests--extract "" 1 4) "   ")))
+
+(ert-deftest rect-test-extract-line-tabs ()
+  (should (equal (rect-tests--extract "a\tb" 0 10) "a       b "))
+  (should (equal (rect-tests--extract "\tx" 2 10) "      x ")))
+
+(ert-deftest rect-test-extract-line-wide-chars ()
+  (should (equal (rect-tests--extract "a\u4e2db" 0 2) "a "))
+  (should (equal (rect-tests--extract "a\u4e2db" 2 4) " b"))
+  (should (equal (rect-tests--extract "a\u4e2db" 2 3) " ")))
+
+(ert-deftest rect-test-extract-rectangle ()
+  (with-temp-buffer
+    (insert "abcd\nefgh\nij\n")
+    (should (equal (extract-rectangle 2 13) '("b" "f" "j")))
+    (should (equal (extract-rectangle 4 12) '("bc" "fg" "j ")))))
+
+(ert-deftest rect-test-insert-rectangle ()
+  (with-temp-buffer
+    (insert "ab\ncd")
+    (goto-char 2)
+    (insert-rectangle '("12" "34" "56"))
+    (should (equal (buffer-string) "a12b\nc34d\n 56"))
+    (should (= (mark) 2))
+    (should (= (point) (point-max)))))
+
+(provide 'rect-tests)
+;;; rect-tests.el ends here
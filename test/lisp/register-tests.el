@@ -39,5 +39,17 @@
                        (quit (car err)))))
       (should-not register-alist))))
 
+(ert-deftest register-test-get-set ()
+  (let ((register-alist nil)
+        (marker (make-marker)))
+    (should-not (get-register ?a))
+    (should (equal (set-register ?a "text") "text"))
+    (should (equal (get-register ?a) "text"))
+    (set-register ?b '("rect" "angle"))
+    (set-register ?a marker)
+    (should (eq (get-register ?a) marker))
+    (should (equal (get-register ?b) '("rect" "angle")))
+    (should (= (length register-alist) 2))))
+
 (provide 'register-tests)
 ;;; register-tests.el ends here
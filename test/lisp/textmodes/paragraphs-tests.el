@@ -0,0 +1,81 @@
+;;; paragraphs-tests.el --- tests for paragraph and sentence motion  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest paragraphs-tests-forward-paragraph ()
+  (with-temp-buffer
+    (insert "One. Two.\n\nThree.\nFour.\n\nFive.\n")
+    (goto-char (point-min))
+    (forward-paragraph)
+    (should (= (point) 11))
+    (forward-paragraph)
+    (should (= (point) 25))
+    (backward-paragraph)
+    (should (= (point) 11))
+    (backward-paragraph 2)
+    (should (bobp))
+    (should (= (forward-paragraph 5) 2))
+    (should (eobp))))
+
+(ert-deftest paragraphs-tests-fill-prefix ()
+  (with-temp-buffer
+    (insert ";; a\n;; b\nx\n;; c\n")
+    (let ((fill-prefix ";; "))
+      (goto-char (point-min))
+      (forward-paragraph)
+      (should (= (point) 11))
+      (let ((paragraph-ignore-fill-prefix t))
+        (goto-char (point-min))
+        (forward-paragraph)
+        (should (eobp))))))
+
+(ert-deftest paragraphs-tests-hard-newlines ()
+  (with-temp-buffer
+    (insert "a\nb" (propertize "\n" 'hard t) "c\nd\n")
+    (let ((paragraph-start "[a-z]"))
+      (goto-char (point-min))
+      (forward-paragraph)
+      (should (= (point) 3))
+      (let ((use-hard-newlines t))
+        (goto-char (point-min))
+        (forward-paragraph)
+        (should (= (point) 5))))))
+
+(ert-deftest paragraphs-tests-sentences ()
+  (with-temp-buffer
+    (insert "One.  Two.  Three.\n\nFour.")
+    (goto-char (point-min))
+    (forward-sentence)
+    (should (= (point) 5))
+    (forward-sentence)
+    (should (= (point) 11))
+    (forward-sentence)
+    (should (= (point) 19))
+    (forward-sentence)
+    (should (eobp))
+    (goto-char 19)
+    (backward-sentence)
+    (should (= (point) 13))
+    (let ((sentence-end-double-space nil))
+      (goto-char 19)
+      (backward-sentence 2)
+      (should (= (point) 7)))))
+
+(provide 'paragraphs-tests)
+;;; paragraphs-tests.el ends here
@@ -0,0 +1,116 @@
+;;; bookmarks-tests.el --- tests for bookmarks.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro bookmarks-tests--saving-store (&rest body)
+  "Run BODY and put back the bookmarks in the store it may change."
+  (declare (indent 0))
+  (let ((saved (make-symbol "saved")))
+    `(let ((,saved (bookmark-store-records)))
+       (unwind-protect
+           (progn ,@body)
+         (bookmark-store-replace ,saved)))))
+
+(ert-deftest bookmarks-tests-store ()
+  (bookmarks-tests--saving-store
+    (should-not (bookmark-store-replace
+                 '(("init" (filename . "~/.emacs.d/init.el") (position . 1))
+                   ("notes" (filename . "~/notes.org"))
+                   ("init" (filename . "/other")))))
+    (should (equal (mapcar #'car (bookmark-store-records)) '("init" "notes")))
+    (should (equal (bookmark-store-get "notes")
+                   '("notes" (filename . "~/notes.org"))))
+    (should-not (bookmark-store-get "missing"))
+    (bookmark-store-set '("notes" (filename . "~/todo.org")))
+    (should (equal (bookmark-store-get "notes")
+                   '("notes" (filename . "~/todo.org"))))
+    (should (eq (bookmark-store-delete "notes") t))
+    (should-not (bookmark-store-delete "notes"))
+    (should (equal (mapcar #'car (bookmark-store-records)) '("init")))
+    (should-error (bookmark-store-set '(1 (filename . "/x"))))
+    ;; Records the store can't write are refused.
+    (with-temp-buffer
+      (should (equal (bookmark-store-replace
+                      `(("ok" (filename . "/ok"))
+                        ("marker" (position . ,(point-marker)))))
+                     '("marker"))))
+    (should (equal (mapcar #'car (bookmark-store-records)) '("ok")))))
+
+(ert-deftest bookmarks-tests-usage ()
+  (bookmarks-tests--saving-store
+    (bookmark-store-replace '(("a" (filename . "/a"))))
+    (should (equal (bookmark-store-usage "a") '(0)))
+    (should (eq (bookmark-store-touch "a") t))
+    (should (eq (bookmark-store-touch "a") t))
+    (should-not (bookmark-store-touch "b"))
+    (should-not (bookmark-store-usage "b"))
+    (should (= (car (bookmark-store-usage "a")) 2))
+    (should (integerp (cdr (bookmark-store-usage "a"))))
+    ;; The usage is kept when the bookmarks are replaced.
+    (bookmark-store-replace '(("a" (filename . "/new"))))
+    (should (= (car (bookmark-store-usage "a")) 2))))
+
+(ert-deftest bookmarks-tests-query ()
+  (bookmarks-tests--saving-store
+    (bookmark-store-replace
+     '(("buffer-offset" (filename . "/b"))
+       ("find-file" (filename . "/f"))
+       ("notes" (filename . "/n") (annotation . "find it here"))
+       ("other" (filename . "/o"))))
+    (should (equal (mapcar #'car (bookmark-query "ff"))
+                   '("find-file" "buffer-offset")))
+    (should (equal (mapcar #'car (bookmark-query "find"))
+                   '("find-file" "notes")))
+    (should (equal (mapcar #'car (bookmark-query "find" 1)) '("find-file")))
+    (should-not (bookmark-query "FF"))
+    ;; Equal matches come in order of use.
+    (bookmark-store-replace '(("x1" (filename . "/1")) ("x2" (filename . "/2"))))
+    (bookmark-store-touch "x2")
+    (should (equal (mapcar #'car (bookmark-query "x")) '("x2" "x1")))))
+
+(ert-deftest bookmarks-tests-save-load ()
+  (bookmarks-tests--saving-store
+    (let ((file (make-temp-file "bookmarks-tests")))
+      (unwind-protect
+          (progn
+            (bookmark-store-replace '(("é" (filename . "/é"))
+                                      ("b" (filename . "/b"))))
+            (bookmark-store-touch "b")
+            (bookmark-store-save file)
+            (bookmark-store-replace nil)
+            (should (equal (bookmark-store-load file)
+                           '(("é" (filename . "/é")) ("b" (filename . "/b")))))
+            (should (= (car (bookmark-store-usage "b")) 1))
+            (should (equal (bookmark-store-usage "é") '(0)))
+            (delete-file file)
+            (should-not (bookmark-store-load file)))
+        (when (file-exists-p file)
+          (delete-file file))))))
+
+(ert-deftest bookmarks-tests-fuzzy-match-score ()
+  (should (eql (fuzzy-match-score "" "anything") 0))
+  (should-not (fuzzy-match-score "ba" "abc"))
+  (should (> (fuzzy-match-score "ff" "find-file")
+             (fuzzy-match-score "ff" "buffer-offset")))
+  (should (fuzzy-match-score "ab" "ABC"))
+  (should-not (fuzzy-match-score "Ab" "abc")))
+
+(provide 'bookmarks-tests)
+;;; bookmarks-tests.el ends here
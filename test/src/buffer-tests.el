@@ -50,4 +50,110 @@ with parameters from the *Messages* buffer modification."
                             (progn (get-buffer-create "nil")
                                    (generate-new-buffer-name "nil")))))
 
+;; Buffer hash and change tracking.
+
+(ert-deftest buffer-hash ()
+  (with-temp-buffer
+    (insert "hello world")
+    (let ((hash (buffer-hash)))
+      (should (stringp hash))
+      (should (equal hash (with-temp-buffer
+                            (insert "hello world")
+                            (buffer-hash))))
+      ;; The hash is of the whole buffer, wherever the gap is.
+      (goto-char 3)
+      (insert "x")
+      (should-not (equal hash (buffer-hash)))
+      (delete-char -1)
+      (should (equal hash (buffer-hash)))
+      (narrow-to-region 1 3)
+      (should (equal hash (buffer-hash)))
+      (should (equal hash (buffer-hash (current-buffer))))
+      (should (equal hash (buffer-hash (buffer-name)))))
+    (should-error (buffer-hash "no such buffer, really"))))
+
+(ert-deftest buffer-chars-modified-tick-range ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((tick (buffer-chars-modified-tick)))
+      (should-not (buffer-chars-modified-tick-range tick))
+      (put-text-property 1 3 'face 'bold)
+      (should-not (buffer-chars-modified-tick-range tick))
+      (goto-char 3)
+      (insert "ab")
+      (should (equal (buffer-chars-modified-tick-range tick) '((3 . 5))))
+      (delete-region 8 10)
+      (should (equal (buffer-chars-modified-tick-range tick)
+                     '((3 . 5) (8 . 8))))
+      (let ((inhibit-modification-hooks t))
+        (goto-char 1)
+        (insert "z"))
+      (should (equal (buffer-chars-modified-tick-range tick)
+                     '((1 . 2) (4 . 6) (9 . 9))))
+      (should (equal (buffer-chars-modified-tick-range
+                      tick (current-buffer))
+                     '((1 . 2) (4 . 6) (9 . 9)))))
+    ;; Only the last few hundred changes are remembered.
+    (let ((tick (buffer-chars-modified-tick)))
+      (dotimes (_ 1000)
+        (insert "x"))
+      (should (eq (buffer-chars-modified-tick-range tick) t)))))
+
+(ert-deftest buffer-recent-changes ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((tick (buffer-chars-modified-tick)))
+      (should-not (buffer-recent-changes tick))
+      (goto-char 3)
+      (insert "ab")
+      (delete-region 8 10)
+      (let ((changes (buffer-recent-changes tick)))
+        (should (equal (mapcar #'cdr changes) '((3 0 2 nil) (8 2 0 nil))))
+        (should (< tick (car (nth 0 changes)) (car (nth 1 changes))))
+        (should (= (car (nth 1 changes)) (buffer-chars-modified-tick))))
+      (should (equal (buffer-recent-changes tick (current-buffer))
+                     (buffer-recent-changes tick))))
+    (setq buffer-recent-changes-text t)
+    (let ((tick (buffer-chars-modified-tick)))
+      (goto-char (point-max))
+      (insert "héllo")
+      (subst-char-in-region 1 3 ?0 ?z)
+      (should (equal (mapcar #'cdr (buffer-recent-changes tick))
+                     '((11 0 5 "héllo") (1 1 1 "z")))))
+    (let ((tick (buffer-chars-modified-tick)))
+      (dotimes (_ 1000)
+        (insert "x"))
+      (should (eq (buffer-recent-changes tick) t))))
+  (with-temp-buffer
+    (should-not (local-variable-p 'buffer-recent-changes-text))))
+
+(ert-deftest make-anchor ()
+  (with-temp-buffer
+    (insert "0123456789")
+    (let ((a (make-anchor 3))
+          (b (make-anchor 3 :gravity 'after))
+          (c (make-anchor 8)))
+      (should (eq (anchor-buffer a) (current-buffer)))
+      (goto-char 3)
+      (insert "xy")
+      (should (equal (mapcar #'anchor-position (list a b c)) '(3 5 10)))
+      (delete-region 4 9)
+      (should (equal (mapcar #'anchor-position (list a b c)) '(3 4 5)))
+      (should (equal (buffer-anchors)
+                     (list (list a 3 'before) (list b 4 'after)
+                           (list c 5 'before))))
+      (should (delete-anchor b))
+      (should-not (delete-anchor b))
+      (should-not (anchor-position b))
+      ;; An anchor can be made again with its ID.
+      (should (eq (make-anchor 2 :id b) b))
+      (should (= (anchor-position b) 2))
+      (should-error (make-anchor 1 :id b))
+      (let ((anchors (make-anchors '(1 2 100))))
+        (should (equal (mapcar #'anchor-position anchors) '(1 2 8))))))
+  (let ((buffer (generate-new-buffer " anchors")))
+    (let ((anchor (make-anchor 1 :buffer buffer)))
+      (kill-buffer buffer)
+      (should-not (anchor-buffer anchor)))))
+
 ;;; buffer-tests.el ends here
@@ -0,0 +1,66 @@
+;;; character-tests.el --- tests for character.c  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+(require 'mule-util)
+
+(ert-deftest character-tests-string-width-batch ()
+  (should (equal (string-width-batch '("" "abc" "日本" "a\tb"))
+                 (mapcar #'string-width '("" "abc" "日本" "a\tb"))))
+  (should (equal (string-width-batch ["x" "日"]) [1 2]))
+  (should (equal (string-width-batch nil) nil))
+  (should-error (string-width-batch '("a" b))))
+
+(ert-deftest character-tests-truncate-string-to-width-batch ()
+  (let ((strings '("" "short" "exactly10!" "a longer string" "日本語の文字列"
+                   "ab日本")))
+    (dolist (end '(0 1 3 5 10))
+      (dolist (padding '(nil ?\s))
+        ;; `truncate-string-to-width' mishandles an ellipsis wider
+        ;; than END-COLUMN, so don't compare those cases.
+        (dolist (ellipsis (if (< end 3) '(nil) '(nil "…" t)))
+          (should (equal (truncate-string-to-width-batch
+                          strings end padding ellipsis)
+                         (mapcar (lambda (s)
+                                   (truncate-string-to-width
+                                    s end nil padding ellipsis))
+                                 strings)))))))
+  (should (equal (truncate-string-to-width-batch ["abcdef" "ab"] 4 ?.)
+                 ["abcd" "ab.."]))
+  (should-error (truncate-string-to-width-batch '("a") -1)))
+
+;; In batch mode, there are no fonts, and a pixel is a column.
+(ert-deftest character-tests-truncate-and-pad-string ()
+  (should (equal (truncate-and-pad-string '("ab" "abcdef" "日本語") 4)
+                 '("ab  " "abcd" "日本")))
+  (should (equal (truncate-and-pad-string ["ab" "abcdef"] 4 'right "…")
+                 ["  ab" "abc…"]))
+  (should (equal (truncate-and-pad-string '("ab") 5 'center)
+                 '(" ab  ")))
+  (should (equal (truncate-and-pad-string '("abcdef") 2 nil "...")
+                 '("ab")))
+  (let ((padded (car (truncate-and-pad-string '("ab") 5 'right nil t))))
+    (should (equal (substring-no-properties padded) " ab"))
+    (should (equal (get-text-property 0 'display padded)
+                   '(space :width (3)))))
+  (should (equal (truncate-and-pad-string '("abcdef") 4 nil "." t)
+                 '("abc.")))
+  (should-error (truncate-and-pad-string '("a") -1)))
+
+(provide 'character-tests)
+;;; character-tests.el ends here
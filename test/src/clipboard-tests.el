@@ -0,0 +1,51 @@
+;;; clipboard-tests.el --- tests for clipboard.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest clipboard-osc52-sequence ()
+  (should (equal (clipboard-osc52-sequence 'CLIPBOARD "hi") "\e]52;c;aGk=\a"))
+  (should (equal (clipboard-osc52-sequence 'PRIMARY "é") "\e]52;p;w6k=\a"))
+  (let ((wrapped (clipboard-osc52-sequence 'CLIPBOARD (make-string 100 ?x) t)))
+    (should (string-prefix-p "\eP\e]52;c;" wrapped))
+    (should (string-suffix-p "\a\e\\" wrapped))
+    (should (string-match-p "\e\\\\\eP" wrapped)))
+  (should-error (clipboard-osc52-sequence 'SECONDARY "hi")))
+
+(ert-deftest clipboard-osc52-decode ()
+  (should (equal (clipboard-osc52-decode "c;aGk=") "hi"))
+  (should (equal (clipboard-osc52-decode "w6k=") "é"))
+  (should (equal (clipboard-osc52-decode
+                  (substring (clipboard-osc52-sequence 'CLIPBOARD "round trip")
+                             5 -1))
+                 "round trip"))
+  (should-not (clipboard-osc52-decode "c;!!")))
+
+(ert-deftest clipboard-native-primary ()
+  ;; Only the Wayland clipboard serves PRIMARY.
+  (skip-unless (not (getenv "WAYLAND_DISPLAY")))
+  (should-not (clipboard-native-set 'PRIMARY "hi"))
+  (should-not (clipboard-native-get 'PRIMARY)))
+
+(ert-deftest clipboard-native-persist ()
+  (should-error (clipboard-native-persist 'SECONDARY "hi"))
+  (should-error (clipboard-native-persist 'CLIPBOARD 42)
+                :type 'wrong-type-argument))
+
+(provide 'clipboard-tests)
+;;; clipboard-tests.el ends here
@@ -0,0 +1,64 @@
+;;; codecs-tests.el --- tests for codecs.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest codecs-tests--charsets ()
+  (should (memq 'shift_jis (native-decoder-charsets)))
+  (should (memq 'iso-8859-2 (native-decoder-charsets)))
+  (should-not (memq 'utf-8 (native-decoder-charsets))))
+
+(ert-deftest codecs-tests--single-byte ()
+  (should (equal (decode-coding-string "caf\351" 'iso-latin-1) "café"))
+  (should (equal (decode-coding-string "\261" 'iso-latin-2) "ą"))
+  (should (equal (decode-coding-string "\200" 'windows-1252) "€"))
+  (should (equal (decode-coding-string "\300\301" 'windows-1251) "АБ")))
+
+(ert-deftest codecs-tests--multibyte ()
+  (should (equal (decode-coding-string "\202\240a" 'japanese-shift-jis) "あa"))
+  (should (equal (decode-coding-string "\244\242" 'euc-jp) "あ"))
+  (should (equal (decode-coding-string "\304\343" 'chinese-gbk) "你"))
+  (should (equal (decode-coding-string "\244\100" 'chinese-big5) "一")))
+
+(ert-deftest codecs-tests--invalid-bytes ()
+  ;; Undecodable bytes survive a round trip.
+  (dolist (test '(("\201" . windows-1252)
+                  ("\245" . iso-latin-3)
+                  ("a\377b" . japanese-shift-jis)
+                  ("\202" . japanese-shift-jis)))
+    (let ((decoded (decode-coding-string (car test) (cdr test))))
+      (should (equal (encode-coding-string decoded (cdr test)) (car test))))))
+
+(ert-deftest codecs-tests--eol ()
+  (should (equal (decode-coding-string "a\r\nb" 'iso-latin-1-dos) "a\nb"))
+  (should (equal (decode-coding-string "\202\240\r\n" 'japanese-shift-jis-dos)
+                 "あ\n")))
+
+(ert-deftest codecs-tests--multibyte-source ()
+  (should (equal (decode-coding-string (string-to-multibyte "caf\351")
+                                       'iso-latin-1)
+                 "café")))
+
+(ert-deftest codecs-tests--long-text ()
+  ;; Longer than the buffer the decoders fill at a time.
+  (let ((text (apply #'concat (make-list 10000 "\202\240\r\n"))))
+    (should (equal (decode-coding-string text 'japanese-shift-jis-dos)
+                   (apply #'concat (make-list 10000 "あ\n"))))))
+
+(provide 'codecs-tests)
+;;; codecs-tests.el ends here
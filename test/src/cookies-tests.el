@@ -0,0 +1,78 @@
+;;; cookies-tests.el --- tests for cookies.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest url-cookie-native-store-and-header ()
+  (url-cookie-native-delete)
+  (should (equal (url-cookie-native-store "a=1" "www.example.com" "/docs/index.html")
+                 "a"))
+  (url-cookie-native-store "b=2; Path=/" "www.example.com" "/")
+  (url-cookie-native-store "c=3; Domain=.example.com; Secure" "www.example.com" "/")
+  (should (equal (url-cookie-native-header "www.example.com" "/docs/x?y=1" t)
+                 "a=1; b=2; c=3"))
+  (should (equal (url-cookie-native-header "www.example.com" "/" nil) "b=2"))
+  (should (equal (url-cookie-native-header "api.example.com" "/" t) "c=3"))
+  (should-not (url-cookie-native-header "example.org" "/" t))
+  (url-cookie-native-delete))
+
+(ert-deftest url-cookie-native-rejects ()
+  (url-cookie-native-delete)
+  (should-not (url-cookie-native-store "a=1; Domain=example.org" "example.com" "/"))
+  (should-not (url-cookie-native-store "no-value" "example.com" "/"))
+  (url-cookie-native-store "a=1" "example.com" "/")
+  (url-cookie-native-store "a=1; Max-Age=0" "example.com" "/")
+  (should-not (url-cookie-native-list)))
+
+(ert-deftest url-cookie-native-list ()
+  (url-cookie-native-delete)
+  (url-cookie-native-store "id=x; Expires=Wed, 30 Dec 2037 16:00:00 GMT; HttpOnly"
+                           "Example.COM" "/")
+  (url-cookie-native-store "other=y" "example.org" "/")
+  (let ((cookies (url-cookie-native-list "example.com")))
+    (should (= (length cookies) 1))
+    (let ((cookie (car cookies)))
+      (should (equal (plist-get cookie :name) "id"))
+      (should (equal (plist-get cookie :domain) "example.com"))
+      (should (equal (plist-get cookie :path) "/"))
+      (should (= (plist-get cookie :expires) 2145801600))
+      (should (plist-get cookie :http-only))
+      (should (plist-get cookie :host-only))
+      (should-not (plist-get cookie :secure))))
+  (should (= (url-cookie-native-delete "example.org") 1))
+  (should (= (length (url-cookie-native-list)) 1))
+  (url-cookie-native-delete))
+
+(ert-deftest url-cookie-native-save-and-load ()
+  (let ((file (make-temp-file "cookies")))
+    (unwind-protect
+        (progn
+          (url-cookie-native-delete)
+          (url-cookie-native-store "kept=1; Max-Age=3600" "example.com" "/")
+          (url-cookie-native-store "session=2" "example.com" "/")
+          (url-cookie-native-save file)
+          (url-cookie-native-delete)
+          (should (= (url-cookie-native-load file) 1))
+          (should (equal (url-cookie-native-header "example.com" "/" nil)
+                         "kept=1")))
+      (url-cookie-native-delete)
+      (delete-file file))))
+
+(provide 'cookies-tests)
+;;; cookies-tests.el ends here
@@ -0,0 +1,48 @@
+;;; csv-tests.el --- tests for native CSV parsing  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest csv-tests-parse-string ()
+  (should (equal (csv-parse-string "a,b\n1,2\n") '(["a" "b"] ["1" "2"])))
+  (should (equal (csv-parse-string "a,\"b,c\"\r\n\"say \"\"hi\"\"\",\"x\ny\"")
+                 '(["a" "b,c"] ["say \"hi\"" "x\ny"])))
+  (should (equal (csv-parse-string "a,,\n\nb") '(["a" "" ""] ["b"])))
+  (should (equal (csv-parse-string "") nil))
+  (should (equal (csv-parse-string "é;ü" ?\;) '(["é" "ü"])))
+  (should (multibyte-string-p (aref (car (csv-parse-string "é")) 0)))
+  (should (equal (csv-parse-string "a\tb c" ?\t) '(["a" "b c"])))
+  (should-error (csv-parse-string "a,\"b"))
+  (should-error (csv-parse-string "a" ?\")))
+
+(ert-deftest csv-tests-header ()
+  (let ((rows (csv-parse-string "name,age\nann,30\nbob\n" nil t)))
+    (should (= (length rows) 2))
+    (should (equal (gethash "name" (car rows)) "ann"))
+    (should (equal (gethash "age" (car rows)) "30"))
+    (should (equal (gethash "name" (cadr rows)) "bob"))
+    (should-not (gethash "age" (cadr rows))))
+  (should (equal (csv-parse-string "only,header\n" nil t) nil)))
+
+(ert-deftest csv-tests-parse-region ()
+  (with-temp-buffer
+    (insert "skip\nx,y\n1,2\n")
+    (should (equal (csv-parse-region 6 (point-max)) '(["x" "y"] ["1" "2"])))))
+
+(provide 'csv-tests)
+;;; csv-tests.el ends here
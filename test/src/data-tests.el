@@ -484,3 +484,9 @@ comparing the subr with a much slower lisp implementation."
       (remove-variable-watcher 'data-tests-lvar collect-watch-data)
       (setq data-tests-lvar 6)
       (should (null watch-data)))))
+
+(ert-deftest data-tests-subr-rust-p ()
+  (should (subr-rust-p (symbol-function 'car)))
+  (should-not (subr-rust-p (symbol-function 'eval)))
+  (should-not (subr-rust-p 'car))
+  (should-not (subr-rust-p (lambda () nil))))
@@ -0,0 +1,68 @@
+;;; diff-tests.el --- tests for native diff primitives  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest diff-strings-identical ()
+  (should-not (diff-strings "a\nb\n" "a\nb\n"))
+  (should-not (diff-strings "" "")))
+
+(ert-deftest diff-strings-myers ()
+  (should (equal (diff-strings "a\nb\nc\n" "a\nx\nc\n")
+                 '((2 4 2 4))))
+  (should (equal (diff-strings "a\nc\n" "a\nb\nc\n" 'myers)
+                 '((2 2 2 4))))
+  (should (equal (diff-strings "é\nb" "é\n")
+                 '((2 3 2 2)))))
+
+(ert-deftest diff-strings-patience ()
+  (should (equal (diff-strings "a\nx\nb\ny\nc\n" "a\ny\nx\nb\nc\n" 'patience)
+                 '((2 2 2 4) (6 8 8 8)))))
+
+(ert-deftest diff-strings-unknown-algorithm ()
+  (should-error (diff-strings "a" "b" 'histogram)))
+
+(ert-deftest diff-regions-buffers ()
+  (with-temp-buffer
+    (insert "one\ntwo\nthree\n")
+    (let ((a (current-buffer)))
+      (with-temp-buffer
+        (insert "zero\none\nthree\n")
+        (should (equal (diff-regions 1 (point-max) 1 (point-max) a nil)
+                       '((1 1 1 6) (5 9 10 10))))))))
+
+;; The removed text starts at 1 and the added text at 17.
+(ert-deftest diff-refine-hunk-native-words ()
+  (with-temp-buffer
+    (insert "(foo bar baz)\n-\n(foo qux baz)\n")
+    (should (equal (diff-refine-hunk-native 1 14 17 30)
+                   '((6 9 22 25))))
+    (should (equal (diff-refine-hunk-native 1 14 17 30 nil nil 'char)
+                   '((6 9 22 25))))
+    (should-not (diff-refine-hunk-native 1 14 1 14))))
+
+(ert-deftest diff-refine-hunk-native-chars ()
+  (with-temp-buffer
+    (insert "colour\ncolor\n")
+    (should (equal (diff-refine-hunk-native 1 7 8 13 nil nil 'char)
+                   '((5 6 12 12))))
+    (should (equal (diff-refine-hunk-native 1 7 8 13)
+                   '((1 7 8 13))))))
+
+(provide 'diff-tests)
+;;; diff-tests.el ends here
@@ -0,0 +1,62 @@
+;;; dnd-tests.el --- tests for dnd.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest dnd-parse-payload-uri-list ()
+  (let ((items (dnd-parse-payload
+                "text/uri-list"
+                "# dropped\r\nfile:///tmp/a%20b\r\nhttps://www.gnu.org/software/emacs/\r\n")))
+    (should (= (length items) 2))
+    (should (eq (plist-get (car items) :kind) 'file))
+    (should (equal (plist-get (car items) :file) "/tmp/a b"))
+    (should (equal (plist-get (car items) :url) "file:///tmp/a%20b"))
+    (should (eq (plist-get (cadr items) :kind) 'url))
+    (should (equal (plist-get (cadr items) :scheme) "https"))
+    (should (equal (plist-get (cadr items) :host) "www.gnu.org")))
+  (let ((item (car (dnd-parse-payload
+                    "text/uri-list"
+                    (concat "file://" (system-name) "/etc/hosts")))))
+    (should (equal (plist-get item :file) "/etc/hosts")))
+  (let ((item (car (dnd-parse-payload "text/uri-list"
+                                      "file://elsewhere.invalid/share/x"))))
+    (should (eq (plist-get item :kind) 'url))
+    (should (equal (plist-get item :host) "elsewhere.invalid"))))
+
+(ert-deftest dnd-parse-payload-moz-url ()
+  (let ((item (car (dnd-parse-payload
+                    "text/x-moz-url"
+                    (encode-coding-string "https://gnu.org\nGNU" 'utf-16le)))))
+    (should (equal (plist-get item :url) "https://gnu.org"))
+    (should (equal (plist-get item :title) "GNU"))))
+
+(ert-deftest dnd-parse-payload-text ()
+  (should (equal (dnd-parse-payload "text/plain;charset=utf-8" "héllo wörld")
+                 '((:kind text :text "héllo wörld"))))
+  (let ((items (dnd-parse-payload "text/plain" "/\nhttps://gnu.org\n")))
+    (should (equal (mapcar (lambda (item) (plist-get item :kind)) items)
+                   '(file url))))
+  (should (equal (dnd-parse-payload "XdndDirectSave0" "notes.txt")
+                 '((:kind promise :name "notes.txt"))))
+  (should-not (dnd-parse-payload "FILE_NAME" ""))
+  (should-error (dnd-parse-payload "image/png" "x"))
+  (should-error (dnd-parse-payload 'text/plain "x") :type 'wrong-type-argument))
+
+(provide 'dnd-tests)
+;;; dnd-tests.el ends here
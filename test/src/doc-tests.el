@@ -88,5 +88,14 @@ M-s		next-matching-history-element
   (should (string= (substitute-command-keys "\\=") "\\="))
   )
 
+(ert-deftest doc-test-documentation ()
+  ;; Primitives get their docstrings from the DOC file.
+  (should (string-prefix-p "Return the car of LIST." (documentation 'car t)))
+  (should (equal (documentation (lambda () "Lambda doc." nil) t) "Lambda doc."))
+  (should (null (documentation (lambda () nil) t)))
+  (should (equal (documentation [?a] t) "Keyboard macro."))
+  (should-error (documentation '(1 2)) :type 'invalid-function))
+
 (provide 'doc-tests)
+
 ;;; doc-tests.el ends here
@@ -216,6 +216,16 @@
                  '(error "Invalid format operation %$")))
   (should (equal (format "%1$c %1$s" ?±) "± 177")))
 
+(ert-deftest format-comma-flag ()
+  (should (equal (format "%,d" 1234567) "1,234,567"))
+  (should (equal (format "%,d" -1234) "-1,234"))
+  (should (equal (format "%,d" 123) "123"))
+  (should (equal (format "%+,d" 1234) "+1,234"))
+  (should (equal (format "%,.2f" 1234.5) "1,234.50"))
+  (should (equal (format "%,8d|%-,8d|" 1234 1234) "   1,234|1,234   |"))
+  (should (equal (format "%0,8d" 1234) "   1,234"))
+  (should (equal (format "%,x %,e" 65535 12345.0) "ffff 1.234500e+04")))
+
 (ert-deftest replace-buffer-contents-1 ()
   (with-temp-buffer
     (insert #("source" 2 4 (prop 7)))
@@ -0,0 +1,57 @@
+;;; encryption-tests.el --- tests for encryption.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest encryption-age-passphrase-round-trip ()
+  (skip-unless (fboundp 'encrypt-region-native))
+  (with-temp-buffer
+    (insert "héllo\n")
+    (encrypt-region-native (point-min) (point-max) "secret" 'age t)
+    (should (string-prefix-p "-----BEGIN AGE ENCRYPTED FILE-----"
+                             (buffer-string)))
+    (should-error (decrypt-region-native (point-min) (point-max) "wrong"))
+    (decrypt-region-native (point-min) (point-max) "secret")
+    (should (equal (buffer-string) "héllo\n"))))
+
+(ert-deftest encryption-formats ()
+  (skip-unless (fboundp 'native-encryption-formats))
+  (should (memq 'age (native-encryption-formats)))
+  (unless (memq 'openpgp (native-encryption-formats))
+    (with-temp-buffer
+      (insert "text")
+      (should-error (encrypt-region-native (point-min) (point-max) "k"
+                                           'openpgp))
+      (should (equal (buffer-string) "text")))))
+
+(ert-deftest encryption-argument-errors ()
+  (skip-unless (fboundp 'encrypt-region-native))
+  (with-temp-buffer
+    (insert "text")
+    (should-error (encrypt-region-native (point-min) (point-max) 42)
+                  :type 'wrong-type-argument)
+    (should-error (encrypt-region-native (point-min) (point-max) "k" 'rot13))
+    (should-error (encrypt-region-native (point-min) (point-max)
+                                         '("not a recipient")))
+    (should-error (decrypt-region-native (point-min) (point-max) "k"))
+    (should (equal (buffer-string) "text"))))
+
+(provide 'encryption-tests)
+
+;;; encryption-tests.el ends here
@@ -0,0 +1,36 @@
+;;; epub-tests.el --- tests for epub.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest epub-extract-chapter-errors ()
+  (let ((file (make-temp-file "epub-tests" nil ".epub")))
+    (unwind-protect
+        (progn
+          (write-region "not a zip archive" nil file nil 'silent)
+          (should-error (epub-extract-chapter file 0))
+          (should-error (epub-extract-file file "mimetype"))
+          (delete-file file)
+          (should-error (epub-extract-chapter file 0) :type 'file-missing)
+          (should-error (epub-extract-chapter file -1) :type 'wrong-type-argument))
+      (when (file-exists-p file)
+        (delete-file file)))))
+
+(provide 'epub-tests)
+;;; epub-tests.el ends here
@@ -79,4 +79,101 @@ Bug#24912 and Bug#24913."
   (let ((clauses (list '((progn (setcdr clauses "ouch") nil)))))
     (should-error (eval (cons 'cond clauses)))))
 
+(defvar eval-tests--local-var 'global)
+(make-variable-buffer-local 'eval-tests--local-var)
+
+(ert-deftest eval-tests--let-buffer-local ()
+  "Check that `let' of a buffer-local variable only affects this buffer."
+  (with-temp-buffer
+    (setq eval-tests--local-var 'local)
+    (let ((eval-tests--local-var 'bound))
+      (should (eq eval-tests--local-var 'bound))
+      (should (eq (default-value 'eval-tests--local-var) 'global)))
+    (should (eq eval-tests--local-var 'local))))
+
+(ert-deftest eval-tests--let-default-value ()
+  "Check that `let' of a variable with no local value binds the default."
+  (with-temp-buffer
+    (let ((eval-tests--local-var 'bound))
+      (should (eq (default-value 'eval-tests--local-var) 'bound)))
+    (should (eq (default-value 'eval-tests--local-var) 'global))))
+
+(ert-deftest eval-tests--let*-sees-earlier-bindings ()
+  (should (equal (let* ((a 1) (b (1+ a))) (list a b)) '(1 2)))
+  (should (equal (eval '(let* ((a 1) (b (1+ a))) (list a b)) t) '(1 2)))
+  (should-error (eval '(let ((a 1 2)) a)) :type 'error))
+
+(ert-deftest eval-tests--let*-improper-varlist ()
+  (should (equal (should-error (eval '(let* ((a 1) . b) a) t))
+                 '(wrong-type-argument listp ((a 1) . b))))
+  (should (equal (should-error (eval '(let* (a . b) a)))
+                 '(wrong-type-argument listp (a . b)))))
+
+(ert-deftest eval-tests--catch-throw ()
+  (should (eq (catch 'tag (throw 'tag 'thrown) 'not-thrown) 'thrown))
+  (should (eq (catch 'tag 'not-thrown) 'not-thrown))
+  (should (eq (catch 'outer (catch 'inner (throw 'outer 1)) 2) 1)))
+
+(ert-deftest eval-tests--signal ()
+  (should (equal (condition-case err
+                     (signal 'wrong-type-argument '(integerp x))
+                   (error err))
+                 '(wrong-type-argument integerp x)))
+  (should (eq (condition-case nil
+                  (signal 'arith-error nil)
+                (arith-error 'caught))
+              'caught))
+  (should (equal (condition-case err
+                     (signal 'quit '(eval-tests))
+                   (quit err))
+                 '(quit eval-tests))))
+
+(ert-deftest eval-tests--throw-unwinds-bindings ()
+  (with-temp-buffer
+    (catch 'tag
+      (let ((eval-tests--local-var 'bound))
+        (throw 'tag nil)))
+    (should (eq eval-tests--local-var 'global))))
+
+(ert-deftest eval-tests--throw-without-catch ()
+  (should (equal (should-error (throw 'eval-tests--no-such-tag 1)
+                               :type 'no-catch)
+                 '(no-catch eval-tests--no-such-tag 1))))
+
+(ert-deftest eval-tests--backtrace-marks-rust-frames ()
+  "Check that frames of Rust primitives are flagged in backtraces."
+  (let ((frames (eval '(let ((frames (backtrace-frames))) frames) t)))
+    (should (plist-get (nth 2 (assq 'let (mapcar #'cdr frames))) :rust))))
+
+(ert-deftest eval-tests--backtrace-to-string ()
+  (let ((trace (eval '(let ((trace (backtrace-to-string))) trace) t)))
+    (should (stringp trace))
+    (should (string-match-p "^  (let ((trace (backtrace-to-string))) trace)  \\[Rust\\]$"
+                            trace))))
+
+(ert-deftest eval-tests--autoload-fails-to-define ()
+  (let ((file (make-temp-file "eval-tests-autoload" nil ".el")))
+    (unwind-protect
+        (progn
+          (autoload 'eval-tests--autoload-undefined file)
+          (should (string-match-p
+                   "failed to define function eval-tests--autoload-undefined\\'"
+                   (cadr (should-error (eval-tests--autoload-undefined))))))
+      (fmakunbound 'eval-tests--autoload-undefined)
+      (delete-file file))))
+
+(ert-deftest eval-tests--autoload-recursive ()
+  (let ((file (make-temp-file "eval-tests-autoload" nil ".el"
+                              "(eval-tests--autoload-recursive)\n")))
+    (unwind-protect
+        (progn
+          (autoload 'eval-tests--autoload-recursive file)
+          (should (string-match-p
+                   "\\`Recursive autoload"
+                   (cadr (should-error (eval-tests--autoload-recursive)))))
+          ;; The failed autoload must be undone.
+          (should (autoloadp (symbol-function 'eval-tests--autoload-recursive))))
+      (fmakunbound 'eval-tests--autoload-recursive)
+      (delete-file file))))
+
 ;;; eval-tests.el ends here
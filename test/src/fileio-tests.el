@@ -97,3 +97,78 @@ Also check that an encoding error can appear in a symlink."
   (should (equal (file-name-as-directory "d:/abc/") "d:/abc/"))
   (should (equal (file-name-as-directory "D:\\abc/") "d:/abc/"))
   (should (equal (file-name-as-directory "D:/abc//") "d:/abc//")))
+
+(ert-deftest fileio-tests--expand-wildcards-native ()
+  (let* ((dir (make-temp-file "fileio" t))
+         (default-directory (file-name-as-directory dir)))
+    (unwind-protect
+        (progn
+          (make-directory "a/b" t)
+          (make-directory "lib")
+          (make-directory ".hidden")
+          (dolist (file '("a/x.c" "a/b/y.c" "lib/l.h" ".hidden/h.c" ".dot.c"))
+            (write-region "" nil file))
+          (should (equal (file-expand-wildcards-native "**/*.c")
+                         '("a/b/y.c" "a/x.c")))
+          (should (equal (file-expand-wildcards-native "{a,lib}/*.{c,h}")
+                         '("a/x.c" "lib/l.h")))
+          (should (equal (file-expand-wildcards-native "*/") '("a/" "lib/")))
+          (should (equal (file-expand-wildcards-native ".*.c") '(".dot.c")))
+          (should (equal (file-expand-wildcards-native "a/[x-z].c" t)
+                         (list (expand-file-name "a/x.c"))))
+          (should-not (file-expand-wildcards-native "nothing/*")))
+      (delete-directory dir t))))
+
+(ert-deftest fileio-tests--insert-file-contents-mmap ()
+  (let ((file (make-temp-file "mmap")))
+    (unwind-protect
+        (progn
+          (with-temp-file file
+            (insert "first line\nsecond line\nthird\n"))
+          (with-temp-buffer
+            (should (equal (insert-file-contents-mmap file)
+                           (list file 30)))
+            (should-not enable-multibyte-characters)
+            (should (equal (buffer-string)
+                           "first line\nsecond line\nthird\n"))
+            (should (= (point) 1))
+            ;; Editing the text copies pages; the file stays as it was.
+            (goto-char (point-max))
+            (insert (make-string 5000 ?x))
+            (goto-char 7)
+            (delete-char 5)
+            (should (equal (buffer-substring 1 12) "first secon"))
+            (should (= (buffer-size) 5025))
+            (should (equal (with-temp-buffer
+                             (insert-file-contents-literally file)
+                             (buffer-string))
+                           "first line\nsecond line\nthird\n"))
+            ;; Only empty buffers take a mapped file.
+            (should-error (insert-file-contents-mmap file))
+            (erase-buffer)
+            (should (equal (insert-file-contents-mmap file)
+                           (list file 30)))
+            (should (equal (buffer-substring 1 11) "first line"))))
+      (delete-file file))))
+
+(ert-deftest fileio-tests--mmap-empty-file ()
+  (let ((file (make-temp-file "mmap")))
+    (unwind-protect
+        (with-temp-buffer
+          (should (equal (insert-file-contents-mmap file) (list file 0)))
+          (insert "text")
+          (should (equal (buffer-string) "text")))
+      (delete-file file))))
+
+(ert-deftest fileio-tests--mmap-indirect-buffer ()
+  (let ((file (make-temp-file "mmap" nil nil "text\n")))
+    (unwind-protect
+        (with-temp-buffer
+          (let ((indirect (make-indirect-buffer (current-buffer) " mmap")))
+            (unwind-protect
+                (progn
+                  (should-error (insert-file-contents-mmap file))
+                  (with-current-buffer indirect
+                    (should-error (insert-file-contents-mmap file))))
+              (kill-buffer indirect))))
+      (delete-file file))))
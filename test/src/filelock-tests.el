@@ -0,0 +1,65 @@
+;;; filelock-tests.el --- tests for filelock.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defun filelock-tests--lock-and-check (backend)
+  (let* ((dir (make-temp-file "filelock" t))
+         (file (expand-file-name "locked.txt" dir))
+         (create-lockfiles t)
+         (file-lock-backend backend))
+    (unwind-protect
+        (with-current-buffer (find-file-noselect file)
+          (unwind-protect
+              (progn
+                (should-not (file-locked-p file))
+                (insert "modified")
+                (should (eq (file-locked-p file) t))
+                (let ((details (file-locked-p file t)))
+                  (should (equal (plist-get details :user) (user-login-name)))
+                  (should (equal (plist-get details :host) (system-name)))
+                  (should (eql (plist-get details :pid) (emacs-pid)))
+                  (should (plist-get details :self)))
+                (unlock-buffer)
+                (should-not (file-locked-p file)))
+            (set-buffer-modified-p nil)
+            (kill-buffer)))
+      (delete-directory dir t))))
+
+(ert-deftest filelock-symlink-backend ()
+  (filelock-tests--lock-and-check 'symlink))
+
+(ert-deftest filelock-flock-backend ()
+  (skip-unless (not (eq system-type 'windows-nt)))
+  (filelock-tests--lock-and-check 'flock))
+
+(ert-deftest filelock-stale-lock ()
+  (skip-unless (not (eq system-type 'windows-nt)))
+  (let* ((dir (make-temp-file "filelock" t))
+         (file (expand-file-name "stale.txt" dir)))
+    (unwind-protect
+        (progn
+          ;; No process has pid 0, so this lock is stale.
+          (make-symbolic-link (format "%s@%s.0" (user-login-name) (system-name))
+                              (expand-file-name ".#stale.txt" dir))
+          (should-not (file-locked-p file))
+          (should-not (file-symlink-p (expand-file-name ".#stale.txt" dir))))
+      (delete-directory dir t))))
+
+(provide 'filelock-tests)
+;;; filelock-tests.el ends here
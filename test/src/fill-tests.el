@@ -0,0 +1,75 @@
+;;; fill-tests.el --- tests for fill-region-native  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest fill-tests-fill-region-native ()
+  (with-temp-buffer
+    (insert "aaa bbb ccc\nddd  eee.  fff\n\nggg\n")
+    (let ((fill-column 9)
+          (fill-prefix nil)
+          (sentence-end-double-space t))
+      (fill-region-native (point-min) (point-max) 'left))
+    (should (equal (buffer-string) "aaa bbb\nccc ddd\neee.  fff\n\nggg\n"))))
+
+(ert-deftest fill-tests-fill-prefix ()
+  (with-temp-buffer
+    (insert "> aa bb cc dd\n> ee\n")
+    (let ((fill-column 9)
+          (fill-prefix "> "))
+      (should (equal (fill-region-native (point-min) (point-max) 'left) "> ")))
+    (should (equal (buffer-string) "> aa bb\n> cc dd\n> ee\n"))))
+
+(ert-deftest fill-tests-justify ()
+  (with-temp-buffer
+    (insert "a bb c dd e\n")
+    (let ((fill-column 8))
+      (fill-region-native (point-min) (point-max) 'full)
+      (should (equal (buffer-string) "a  bb  c\ndd e\n"))
+      (erase-buffer)
+      (insert "ab cd\n")
+      (fill-region-native (point-min) (point-max) 'right)
+      (should (equal (buffer-string) "   ab cd\n")))))
+
+(ert-deftest fill-tests-keeps-properties ()
+  (with-temp-buffer
+    (insert (propertize "word" 'face 'bold) " other text here\n")
+    (let ((fill-column 10))
+      (fill-region-native (point-min) (point-max) 'left))
+    (should (equal (buffer-string) "word other\ntext here\n"))
+    (should (eq (get-text-property 1 'face) 'bold))))
+
+(ert-deftest fill-tests-same-as-fill-region ()
+  (let ((text "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do
+eiusmod tempor incididunt ut labore et dolore magna aliqua.  Ut enim ad
+minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip
+ex ea commodo consequat.\n"))
+    (dolist (column '(20 40 70))
+      (let ((fill-column column)
+            (sentence-end-double-space t))
+        (should (equal (with-temp-buffer
+                         (insert text)
+                         (fill-region-native (point-min) (point-max) 'left)
+                         (buffer-string))
+                       (with-temp-buffer
+                         (insert text)
+                         (fill-region (point-min) (point-max) 'left)
+                         (buffer-string))))))))
+
+(provide 'fill-tests)
+;;; fill-tests.el ends here
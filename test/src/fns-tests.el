@@ -575,4 +575,97 @@
                                :type 'wrong-type-argument)
                  '(wrong-type-argument plistp (:foo 1 . :bar)))))
 
+(ert-deftest string-distance ()
+  (should (equal 0 (string-distance "" "")))
+  (should (equal 3 (string-distance "kitten" "sitting")))
+  (should (equal 1 (string-distance "é" "e")))
+  (should (equal 2 (string-distance "é" "e" t)))
+  (should-error (string-distance "a" 'b)))
+
+(ert-deftest string-jaro-winkler ()
+  (should (= 1.0 (string-jaro-winkler "abc" "abc")))
+  (should (= 0.0 (string-jaro-winkler "abc" "xyz")))
+  (should (> (string-jaro-winkler "martha" "marhta")
+             (string-jaro-winkler "martha" "amrhta"))))
+
+(ert-deftest closest-strings ()
+  (let ((candidates '("find-file" "find-tag" "kill-buffer" find-file-other-window)))
+    (should (equal (closest-strings "find-fil" candidates 1) '("find-file")))
+    (should (equal (closest-strings "fnid-tag" candidates 2)
+                   '("find-tag" "find-file")))
+    (should (equal (length (closest-strings "x" candidates)) 4))
+    (should-not (closest-strings "x" nil 3))))
+
+(ert-deftest seq-filter-native ()
+  (should (equal (seq-filter-native #'cl-evenp '(1 2 3 4)) '(2 4)))
+  (should (equal (seq-filter-native #'cl-evenp [1 2 3 4]) '(2 4)))
+  (should (equal (seq-filter-native (lambda (c) (/= c ?b)) "abcb") '(?a ?c)))
+  (should (equal (seq-filter-native #'identity (bool-vector t nil t)) '(t t)))
+  (should-not (seq-filter-native #'identity nil))
+  (should-error (seq-filter-native #'identity 3)))
+
+(ert-deftest seq-reduce-native ()
+  (should (= (seq-reduce-native #'+ '(1 2 3) 10) 16))
+  (should (equal (seq-reduce-native (lambda (acc c) (cons c acc)) "ab" nil)
+                 '(?b ?a)))
+  (should (eq (seq-reduce-native #'ignore [] 'init) 'init)))
+
+(ert-deftest seq-uniq-native ()
+  (should (equal (seq-uniq-native '(2 4 6 8 6 4 3)) '(2 4 6 8 3)))
+  (should (equal (seq-uniq-native ["a" "b" "a"]) '("a" "b")))
+  (should (equal (seq-uniq-native (list "a" (copy-sequence "a")) #'eq)
+                 '("a" "a")))
+  (should (equal (seq-uniq-native '(1 2 3 4 5)
+                                  (lambda (a b) (= (% a 2) (% b 2))))
+                 '(1 2)))
+  (should-not (seq-uniq-native "")))
+
+(ert-deftest seq-difference-native ()
+  (should (equal (seq-difference-native [2 3 4 5] '(1 3 5 6 7)) '(2 4)))
+  (should (equal (seq-difference-native "abc" "b") '(?a ?c)))
+  (should (equal (seq-difference-native '(1 2 3) '(2.0) #'=) '(1 3)))
+  (should (equal (seq-difference-native '(1 2 3) '(2.0)) '(1 2 3))))
+
+(ert-deftest seq-group-by-native ()
+  (let ((groups (seq-group-by-native #'car '((a 1) (b 3) (c 4) (a 2)))))
+    (should (hash-table-p groups))
+    (should (eq (hash-table-test groups) 'equal))
+    (should (= (hash-table-count groups) 3))
+    (should (equal (gethash 'a groups) '((a 1) (a 2))))
+    (should (equal (gethash 'c groups) '((c 4)))))
+  (require 'seq)
+  (should (equal (seq-group-by #'car '((a 1) (b 3) (c 4) (a 2)))
+                 '((b (b 3)) (c (c 4)) (a (a 1) (a 2)))))
+  (should (equal (seq-group-by #'cl-oddp [1 2 3 4])
+                 '((t 1 3) (nil 2 4)))))
+
+(ert-deftest alist-to-hash-table ()
+  (let ((table (alist-to-hash-table '((a . 1) (b . 2) c (a . 3)))))
+    (should (eq (hash-table-test table) 'eql))
+    (should (= (hash-table-count table) 2))
+    (should (= (gethash 'a table) 1))
+    (should (= (gethash 'b table) 2)))
+  (let ((table (alist-to-hash-table '(("x" . 1)) :test #'equal)))
+    (should (eq (hash-table-test table) 'equal))
+    (should (= (gethash (copy-sequence "x") table) 1)))
+  (should (= (hash-table-size (alist-to-hash-table nil :size 100)) 100)))
+
+(ert-deftest plist-to-hash-table ()
+  (let ((table (plist-to-hash-table '(:a 1 :b 2 :a 3) :test 'eq)))
+    (should (eq (hash-table-test table) 'eq))
+    (should (= (gethash :a table) 1))
+    (should (= (gethash :b table) 2)))
+  (should-error (plist-to-hash-table '(:a 1 :b)) :type 'wrong-type-argument))
+
+(ert-deftest hash-table-to-alist-and-plist ()
+  (let ((table (make-hash-table)))
+    (puthash 'b 2 table)
+    (puthash 'a 1 table)
+    (puthash 'c 3 table)
+    (remhash 'a table)
+    (should (equal (hash-table-to-alist table) '((b . 2) (c . 3))))
+    (should (equal (hash-table-to-plist table) '(b 2 c 3))))
+  (should-not (hash-table-to-alist (make-hash-table)))
+  (should-error (hash-table-to-plist '((a . 1)))))
+
 (provide 'fns-tests)
@@ -0,0 +1,43 @@
+;;; format-spec-tests.el --- tests for format_spec.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+(require 'format-spec)
+
+(ert-deftest format-spec-native ()
+  (let ((spec '((?n . "name") (?c . 42) (?e . ""))))
+    (dolist (format '("" "plain" "%n" "<%n|%c>" "%-8n|" "%6c" "%.2n"
+                      "100%%" "%%n" "%e%n" "日本%n"))
+      (should (equal (format-spec-native format spec)
+                     (format-spec format spec))))
+    (should-error (format-spec-native "%x" spec))
+    (should-error (format-spec-native "50%" spec))
+    (should-error (format-spec-native "%-!" spec))))
+
+(ert-deftest format-spec-native-properties ()
+  (let ((result (format-spec-native
+                 (concat "a " (propertize "%n" 'face 'bold) " b")
+                 '((?n . "name")))))
+    (should (equal result "a name b"))
+    (should (eq (get-text-property 2 'face result) 'bold))
+    (should (eq (get-text-property 5 'face result) 'bold))
+    (should-not (get-text-property 0 'face result))
+    (should-not (get-text-property 7 'face result))))
+
+(provide 'format-spec-tests)
+;;; format-spec-tests.el ends here
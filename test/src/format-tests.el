@@ -0,0 +1,56 @@
+;;; format-tests.el --- tests for format.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest format-number-grouped ()
+  (should (equal (format-number 1234567 nil nil "C") "1,234,567"))
+  (should (equal (format-number -1234.5 'grouped nil "en_US.UTF-8")
+                 "-1,234.5"))
+  (should (equal (format-number 1234567.891 nil 2 "de_DE.UTF-8")
+                 "1.234.567,89"))
+  (should (equal (format-number 1234567 nil 1 "fr_FR") "1\u00a0234\u00a0567,0"))
+  (should (equal (format-number 12345678 nil nil "en_IN") "1,23,45,678"))
+  (should (stringp (format-number 1000)))
+  (should-error (format-number "1000") :type 'wrong-type-argument)
+  (should-error (format-number 1 'roman)))
+
+(ert-deftest format-number-engineering ()
+  (should (equal (format-number 12345 'engineering nil "C") "12.345e3"))
+  (should (equal (format-number 0.00047 'engineering nil "C") "470e-6"))
+  (should (equal (format-number 999.96 'engineering 1 "C") "1.0e3"))
+  (should (equal (format-number 12 'engineering nil "C") "12"))
+  (should (equal (format-number 1.5e6 'si nil "C") "1.5M"))
+  (should (equal (format-number 2.2e-9 'si 1 "de_DE") "2,2n"))
+  (should (equal (format-number 1e30 'si nil "C") "1e30")))
+
+(ert-deftest format-number-words ()
+  (should (equal (format-number 0 'words) "zero"))
+  (should (equal (format-number 123 'words) "one hundred twenty-three"))
+  (should (equal (format-number -2000001 'words)
+                 "minus two million one"))
+  (should (equal (format-number 1001 'ordinal nil "C") "1,001st"))
+  (should (equal (format-number 12 'ordinal) "12th"))
+  (should (equal (format-number 23 'ordinal) "23rd"))
+  (should (equal (format-number 21 'ordinal-words) "twenty-first"))
+  (should (equal (format-number 90 'ordinal-words) "ninetieth"))
+  (should-error (format-number 1.5 'words) :type 'wrong-type-argument))
+
+(provide 'format-tests)
+;;; format-tests.el ends here
@@ -0,0 +1,83 @@
+;;; frecency-tests.el --- tests for frecency.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro frecency-tests--with-empty-store (&rest body)
+  "Run BODY with no selections recorded, and put back the ones that were."
+  (declare (indent 0))
+  (let ((file (make-symbol "file")))
+    `(let ((,file (make-temp-file "frecency-tests")))
+       (unwind-protect
+           (progn
+             (frecency-save ,file)
+             (frecency-clear)
+             ,@body)
+         (frecency-load ,file)
+         (delete-file ,file)))))
+
+(ert-deftest frecency-tests-record ()
+  (frecency-tests--with-empty-store
+    (should (= (frecency-score "a" 'test) 0.0))
+    (frecency-record "a" 'test)
+    (frecency-record 'a 'test)
+    (should (< 1.99 (frecency-score "a" 'test) 2.0001))
+    (should (= (frecency-score "a" 'other) 0.0))
+    (should (eq (frecency-forget "a" 'test) t))
+    (should-not (frecency-forget "a" 'test))
+    (should (= (frecency-score "a" 'test) 0.0))
+    (should-error (frecency-record 1 'test))
+    (should-error (frecency-record "a" "test"))))
+
+(ert-deftest frecency-tests-sort ()
+  (frecency-tests--with-empty-store
+    (frecency-record "c" 'test)
+    (frecency-record "c" 'test)
+    (frecency-record "b" 'test)
+    (let ((candidates (list "a" "b" "c" "d")))
+      (should (equal (frecency-sort candidates nil 'test) '("c" "b" "a" "d")))
+      ;; The list itself is left alone.
+      (should (equal candidates '("a" "b" "c" "d"))))
+    (should (equal (frecency-sort '(("a" . 1) ("b" . 2)) nil 'test)
+                   '(("b" . 2) ("a" . 1))))
+    (should (equal (frecency-sort '(0 1 2)
+                                  (lambda (n) (string (+ ?a n)))
+                                  'test)
+                   '(2 1 0)))
+    (should (equal (frecency-sort '("a" "b") nil 'other) '("a" "b")))))
+
+(ert-deftest frecency-tests-save-load ()
+  (frecency-tests--with-empty-store
+    (let ((file (make-temp-file "frecency-tests")))
+      (unwind-protect
+          (progn
+            (frecency-record "é" 'test)
+            (frecency-save file)
+            (frecency-clear)
+            (should (= (frecency-score "é" 'test) 0.0))
+            (frecency-load file)
+            (should (> (frecency-score "é" 'test) 0.99))
+            (delete-file file)
+            (frecency-load file)
+            (should (= (frecency-score "é" 'test) 0.0)))
+        (when (file-exists-p file)
+          (delete-file file))))))
+
+(provide 'frecency-tests)
+;;; frecency-tests.el ends here
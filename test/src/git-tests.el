@@ -0,0 +1,86 @@
+;;; git-tests.el --- tests for native git repository access  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defmacro git-tests--with-repository (&rest body)
+  "Run BODY in a fresh git repository with one commit."
+  (declare (indent 0))
+  `(let* ((default-directory (file-name-as-directory
+                              (make-temp-file "git-tests" t)))
+          (process-environment (append '("GIT_AUTHOR_NAME=A"
+                                         "GIT_AUTHOR_EMAIL=a@example.com"
+                                         "GIT_COMMITTER_NAME=A"
+                                         "GIT_COMMITTER_EMAIL=a@example.com")
+                                       process-environment)))
+     (unwind-protect
+         (progn
+           (call-process "git" nil nil nil "init" "-q")
+           (write-region "one\n" nil "a.txt")
+           (write-region "two\n" nil "b.txt")
+           (write-region "*.o\n" nil ".gitignore")
+           (call-process "git" nil nil nil "add" ".")
+           (call-process "git" nil nil nil "commit" "-q" "-m" "first")
+           ,@body)
+       (delete-directory default-directory t))))
+
+(ert-deftest git-status-files ()
+  (skip-unless (executable-find "git"))
+  (git-tests--with-repository
+    (should-not (git-status-files default-directory t))
+    (write-region "changed\n" nil "a.txt")
+    (delete-file "b.txt")
+    (write-region "new\n" nil "c.txt")
+    (write-region "" nil "c.o")
+    (should (equal (git-status-files default-directory)
+                   '(("a.txt" . edited) ("b.txt" . removed))))
+    (should (equal (git-status-files default-directory t)
+                   '(("a.txt" . edited) ("b.txt" . removed)
+                     ("c.txt" . unregistered))))
+    (call-process "git" nil nil nil "add" "c.txt")
+    (should (equal (cdr (assoc "c.txt" (git-status-files default-directory)))
+                   'added))))
+
+(ert-deftest git-blob-content ()
+  (skip-unless (executable-find "git"))
+  (git-tests--with-repository
+    (write-region "staged\n" nil "a.txt")
+    (call-process "git" nil nil nil "add" "a.txt")
+    (write-region "unstaged\n" nil "a.txt")
+    (should (equal (git-blob-content "a.txt") "one\n"))
+    (should (equal (git-blob-content "a.txt" 'index) "staged\n"))
+    (should-not (git-blob-content "missing.txt"))
+    (should-error (git-blob-content "a.txt" "no-such-branch"))))
+
+(ert-deftest git-symbolic-ref ()
+  (skip-unless (executable-find "git"))
+  (git-tests--with-repository
+    (call-process "git" nil nil nil "checkout" "-q" "-b" "topic")
+    (should (equal (git-symbolic-ref default-directory) "refs/heads/topic"))
+    (should (equal (git-symbolic-ref default-directory t) "topic"))
+    (call-process "git" nil nil nil "checkout" "-q" "--detach")
+    (should-not (git-symbolic-ref default-directory))))
+
+(ert-deftest git-not-a-repository ()
+  (let ((dir (make-temp-file "git-tests" t)))
+    (unwind-protect
+        (should-error (git-symbolic-ref dir))
+      (delete-directory dir t))))
+
+(provide 'git-tests)
+;;; git-tests.el ends here
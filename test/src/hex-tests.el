@@ -0,0 +1,91 @@
+;;; hex-tests.el --- tests for the native hexl conversions  -*- lexical-binding: t -*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+(require 'hexl)
+
+(defconst hex-tests--data
+  (apply #'unibyte-string (append (number-sequence 0 255) '(?t ?a ?i ?l))))
+
+(ert-deftest hex-tests-hexlify ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (setq buffer-file-coding-system 'no-conversion)
+    (insert "Hello, world!\n")
+    (should (= (hexlify-region-native (point-min) (point-max) 16) 14))
+    (should (equal (buffer-string)
+                   (concat "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a"
+                           "       Hello, world!.\n")))))
+
+(ert-deftest hex-tests-round-trip ()
+  (dolist (bits '(8 16 32 64))
+    (with-temp-buffer
+      (set-buffer-multibyte nil)
+      (setq buffer-file-coding-system 'no-conversion)
+      (insert hex-tests--data)
+      (should (= (hexlify-region-native (point-min) (point-max) bits)
+                 (length hex-tests--data)))
+      (should (= (count-lines (point-min) (point-max)) 17))
+      (should (= (dehexlify-region-native (point-min) (point-max) bits)
+                 (length hex-tests--data)))
+      (should (equal (buffer-string) hex-tests--data)))))
+
+(ert-deftest hex-tests-coding ()
+  (with-temp-buffer
+    (setq buffer-file-coding-system 'utf-8-unix)
+    (insert "été")
+    (should (= (hexlify-region-native (point-min) (point-max) 16) 5))
+    (should (string-prefix-p "00000000: c3a9 74c3 a9 " (buffer-string)))
+    (dehexlify-region-native (point-min) (point-max) 16)
+    (should (equal (buffer-string) "été"))))
+
+(ert-deftest hex-tests-bad-bits ()
+  (with-temp-buffer
+    (insert "x")
+    (should-error (hexlify-region-native (point-min) (point-max) 12)
+                  :type 'args-out-of-range)))
+
+(ert-deftest hex-tests-byte-at ()
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (setq buffer-file-coding-system 'no-conversion)
+    (insert hex-tests--data)
+    (hexlify-region-native (point-min) (point-max) 32)
+    (should (= (buffer-byte-at 0 32) 0))
+    (should (= (buffer-byte-at 200 32) 200))
+    (should (= (buffer-byte-at 257 32) ?a))
+    (should (null (buffer-byte-at 260 32)))
+    (should (equal (buffer-bytes-at 14 4 32) (unibyte-string 14 15 16 17)))
+    (should (equal (buffer-bytes-at 254 10 32) (unibyte-string 254 255 ?t ?a ?i ?l)))
+    (should (equal (buffer-bytes-at 300 2 32) ""))))
+
+(ert-deftest hex-tests-hexl-mode ()
+  "The byte at an address is where `hexl-address-to-marker' puts it."
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (setq buffer-file-coding-system 'no-conversion)
+    (insert hex-tests--data)
+    (let ((hexl-bits 16))
+      (hexlify-buffer)
+      (dolist (address '(0 15 16 100 259))
+        (goto-char (hexl-address-to-marker address))
+        (should (= (hexl-char-after-point) (buffer-byte-at address))))
+      (dehexlify-buffer)
+      (should (equal (buffer-string) hex-tests--data)))))
+
+;;; hex-tests.el ends here
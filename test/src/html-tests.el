@@ -0,0 +1,77 @@
+;;; html-tests.el --- tests for html.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defun html-tests-render (html)
+  "Render HTML with `html-render-region-native'.
+Return whether it was laid out natively, and the text."
+  (let ((shr-width 30)
+        (output (generate-new-buffer " *html-tests*")))
+    (unwind-protect
+        (with-temp-buffer
+          (insert html)
+          (cons (html-render-region-native (point-min) (point-max) output)
+                (with-current-buffer output (buffer-string))))
+      (kill-buffer output))))
+
+(ert-deftest html-render-region-native ()
+  (skip-unless (fboundp 'libxml-parse-html-region))
+  (let ((result (html-tests-render
+                 (concat "<h1>Title</h1>"
+                         "<p>The quick brown fox jumps over the lazy dog.</p>"
+                         "<ul><li>one</li><li>two words</li></ul>"
+                         "<ol><li>first</li><li>second</li></ol>"))))
+    (should (car result))
+    (should (equal (substring-no-properties (cdr result))
+                   (concat "Title\n\n"
+                           "The quick brown fox jumps over\n"
+                           "the lazy dog.\n\n"
+                           "* one\n* two words\n\n"
+                           "1 first\n2 second\n")))
+    (should (eq (get-text-property 0 'face (cdr result)) 'bold))))
+
+(ert-deftest html-render-region-native-links ()
+  (skip-unless (fboundp 'libxml-parse-html-region))
+  (let* ((result (html-tests-render
+                  "<p>See <a href=\"https://example.org/\">the site</a>.</p>"))
+         (text (cdr result)))
+    (should (car result))
+    (should (equal (substring-no-properties text) "See the site.\n"))
+    (should (equal (get-text-property 4 'shr-url text) "https://example.org/"))
+    (should (equal (get-text-property 7 'shr-url text) "https://example.org/"))
+    (should-not (get-text-property 0 'shr-url text))))
+
+(ert-deftest html-render-region-native-tables ()
+  (skip-unless (fboundp 'libxml-parse-html-region))
+  (let ((result (html-tests-render
+                 "<table><tr><td>a</td><td>bb</td></tr><tr><td>ccc</td><td>d</td></tr></table>")))
+    (should (car result))
+    (should (equal (cdr result) " a   bb\n ccc d\n")))
+  ;; Cells that span columns are left to shr.
+  (should-not (car (html-tests-render
+                    "<table><tr><td colspan=\"2\">a</td></tr></table>"))))
+
+(ert-deftest html-render-region-native-fallback ()
+  (skip-unless (fboundp 'libxml-parse-html-region))
+  (let ((result (html-tests-render "<p>An <img src=\"x.png\" alt=\"image\"></p>")))
+    (should-not (car result))
+    (should (string-match-p "An" (cdr result)))))
+
+(provide 'html-tests)
+;;; html-tests.el ends here
@@ -0,0 +1,53 @@
+;;; ical-tests.el --- tests for ical.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest ical-tests--parse-region ()
+  (with-temp-buffer
+    (insert "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n"
+            "DTSTART:20200106T090000Z\r\nSUMMARY;LANGUAGE=en:Stand\r\n up\r\n"
+            "END:VEVENT\r\nEND:VCALENDAR\r\n")
+    (let* ((calendar (car (icalendar-parse-region-native (point-min) (point-max))))
+           (event (car (nth 3 calendar))))
+      (should (eq (car calendar) 'VCALENDAR))
+      (should (eq (car event) 'VEVENT))
+      (should (equal (nth 2 event)
+                     '((DTSTART nil "20200106T090000Z")
+                       (SUMMARY (LANGUAGE "en") "Stand up"))))
+      ;; No recurrence rule, no occurrences.
+      (should (= (length event) 4)))))
+
+(ert-deftest ical-tests--occurrences ()
+  (with-temp-buffer
+    (insert "BEGIN:VEVENT\nDTSTART:20200106T090000Z\n"
+            "RRULE:FREQ=DAILY;COUNT=10\nEND:VEVENT\n")
+    (let ((event (car (icalendar-parse-region-native
+                       (point-min) (point-max)
+                       (encode-time 0 0 0 8 1 2020 t)
+                       (encode-time 0 0 0 11 1 2020 t)))))
+      (should (equal (mapcar #'float-time (nth 4 event))
+                     (mapcar (lambda (day)
+                               (float-time (encode-time 0 0 9 day 1 2020 t)))
+                             '(8 9 10)))))
+    (let ((event (car (icalendar-parse-region-native
+                       (point-min) (point-max) nil nil 3))))
+      (should (= (length (nth 4 event)) 3)))))
+
+(provide 'ical-tests)
+;;; ical-tests.el ends here
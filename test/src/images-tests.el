@@ -0,0 +1,137 @@
+;;; images-tests.el --- tests for images.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defconst images-tests-image
+  (expand-file-name "../data/image/blank-200x100.png"
+                    (file-name-directory (or load-file-name buffer-file-name))))
+
+(defun images-tests-png-size (spec)
+  "Return the (WIDTH . HEIGHT) of the PNG data in image SPEC."
+  (let ((data (plist-get (cdr spec) :data))
+        (int (lambda (data start)
+               (let ((n 0))
+                 (dotimes (i 4)
+                   (setq n (+ (* n 256) (aref data (+ start i)))))
+                 n))))
+    (should (equal (substring data 1 4) "PNG"))
+    (cons (funcall int data 16) (funcall int data 20))))
+
+(ert-deftest images-tests-resize ()
+  (let ((spec (list 'image :file images-tests-image)))
+    (should (equal (images-tests-png-size (image-resize-native spec 50 50))
+                   '(50 . 25)))
+    (should (equal (images-tests-png-size (image-resize-native spec nil 50))
+                   '(100 . 50)))
+    (let ((resized (image-resize-native spec 400 nil)))
+      (should (eq (plist-get (cdr resized) :type) 'png))
+      (should (equal (images-tests-png-size (image-resize-native resized 20 nil))
+                     '(20 . 10))))
+    (should-error (image-resize-native spec 0 10) :type 'args-out-of-range)
+    (should-error (image-resize-native '(image :file "/nonexistent.png") 1 1)
+                  :type 'file-missing)
+    (should-error (image-resize-native '(image :data "not an image") 1 1))
+    (should-error (image-resize-native "image" 1 1) :type 'wrong-type-argument)))
+
+(ert-deftest images-tests-crop ()
+  (let ((spec (list 'image :file images-tests-image)))
+    (should (equal (images-tests-png-size (image-crop-native spec 10 20 30 40))
+                   '(30 . 40)))
+    (should (equal (images-tests-png-size (image-crop-native spec 150 50 100 nil))
+                   '(50 . 50)))
+    (should-error (image-crop-native spec 200 0 1 1) :type 'args-out-of-range)))
+
+(ert-deftest images-tests-rotate ()
+  (let ((spec (list 'image :file images-tests-image)))
+    (should (equal (images-tests-png-size (image-rotate-native spec 90))
+                   '(100 . 200)))
+    (should (equal (images-tests-png-size (image-rotate-native spec -180))
+                   '(200 . 100)))
+    (should-error (image-rotate-native spec 45))))
+
+(defconst images-tests-gif
+  (unibyte-string ?G ?I ?F ?8 ?9 ?a 1 0 1 0 0 0 0
+                  #x21 #xf9 4 0 10 0 0 0
+                  #x2c 0 0 0 0 1 0 1 0 0 2 1 0 0
+                  #x21 #xf9 4 0 20 0 0 0
+                  #x2c 0 0 0 0 1 0 1 0 0 2 1 0 0
+                  #x3b)
+  "A GIF of two frames shown for 0.1 and 0.2 seconds.")
+
+(ert-deftest images-tests-animation ()
+  (let ((handle (image-animation-start
+                 (list 'image :type 'gif :data images-tests-gif) 0 t)))
+    (should (natnump handle))
+    (unwind-protect
+        (let ((result (image-animation-tick)))
+          ;; The first frame is due after 0.2 seconds.
+          (should (floatp (car result)))
+          (should-not (assq handle (cdr result)))
+          (sleep-for 0.25)
+          (should (equal (assq handle (cdr (image-animation-tick)))
+                         (cons handle 0)))
+          (should-not (assq handle (cdr (image-animation-tick)))))
+      (image-animation-stop handle))
+    (should-not (assq handle (cdr (image-animation-tick))))))
+
+(ert-deftest images-tests-animation-limit ()
+  (let ((handle (image-animation-start
+                 (list 'image :type 'gif :data images-tests-gif :speed 100)
+                 1 nil)))
+    (sleep-for 0.25)
+    ;; Played at 100 times the speed, the last frame is over.
+    (should (equal (assq handle (cdr (image-animation-tick)))
+                   (list handle)))))
+
+(ert-deftest images-tests-animation-not-animated ()
+  (should-not (image-animation-start
+               (list 'image :file images-tests-image) nil t)))
+
+(ert-deftest images-tests-file-metadata ()
+  (let ((metadata (image-file-metadata images-tests-image)))
+    (should (eq (plist-get metadata :format) 'png))
+    (should (= (plist-get metadata :width) 200))
+    (should (= (plist-get metadata :height) 100))
+    (should (plist-member metadata :exif))
+    (should-not (plist-get metadata :orientation)))
+  (should-error (image-file-metadata "/nonexistent.png") :type 'file-missing))
+
+(ert-deftest images-tests-tty-sequence ()
+  (let ((spec (list 'image :file images-tests-image)))
+    (let ((sixel (image-tty-sequence spec 'sixel '(10 . 5) '(10 . 20))))
+      (should-not (multibyte-string-p sixel))
+      (should (string-prefix-p "\eP0;1;0q\"1;1;100;50" sixel))
+      (should (string-suffix-p "\e\\" sixel)))
+    (should (string-prefix-p "\e_Ga=T,f=100,c=10,r=3,C=1,q=2,m=0;"
+                             (image-tty-sequence spec 'kitty '(10 . 5) '(10 . 20))))
+    (should (string-prefix-p "\e_Ga=T,f=100,c=4,r=1,"
+                             (image-tty-sequence spec 'kitty '(4 . 4))))
+    (should-error (image-tty-sequence spec 'iterm '(1 . 1)))
+    (should-error (image-tty-sequence spec 'sixel '(0 . 1))
+                  :type 'args-out-of-range)))
+
+(ert-deftest images-tests-tty-place ()
+  (skip-unless noninteractive)
+  (should-error (image-tty-place (list 'image :file images-tests-image)
+                                 'kitty '(0 . 0) '(4 . 4)))
+  (should-not (image-tty-placements))
+  (should-not (image-tty-remove 12345)))
+
+(provide 'images-tests)
+;;; images-tests.el ends here
@@ -0,0 +1,50 @@
+;;; imap-tests.el --- tests for imap.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest imap-quote ()
+  (should (equal (imap-quote "INBOX") "\"INBOX\""))
+  (should (equal (imap-quote "a\"b\\c") "\"a\\\"b\\\\c\""))
+  (should-error (imap-quote "a\nb")))
+
+(ert-deftest imap-process-filter ()
+  (let ((process (make-pipe-process :name "imap-tests" :noquery t))
+        untagged completed)
+    (unwind-protect
+        (progn
+          (process-put process 'imap--untagged-function
+                       (lambda (_process response) (push response untagged)))
+          (imap-process-filter process "* OK [CAPABILITY IMAP4rev1] Hi\r\n")
+          (should (equal untagged '(("OK" "[CAPABILITY IMAP4rev1] Hi"))))
+          (process-put process 'imap--callbacks
+                       (list (cons "A0001"
+                                   (lambda (status text responses)
+                                     (setq completed
+                                           (list status text responses))))))
+          (imap-process-filter process "* 1 FETCH (UID 9 BODY[] {4}\r\nab")
+          (should-not completed)
+          (imap-process-filter process "\r\n)\r\nA0001 OK Done\r\n")
+          (should (equal completed
+                         '(ok "Done" ((1 "FETCH" ("UID" 9 "BODY[]" "ab\r\n"))))))
+          (should-not (process-get process 'imap--callbacks)))
+      (delete-process process))))
+
+(provide 'imap-tests)
+;;; imap-tests.el ends here
@@ -0,0 +1,71 @@
+;;; imenu-tests.el --- tests for imenu.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+(require 'imenu)
+
+(ert-deftest imenu-tests-languages ()
+  (should (memq 'rust (imenu-native-languages)))
+  (should (memq 'emacs-lisp (imenu-native-languages)))
+  (with-temp-buffer
+    (should-error (imenu-build-index-native 'no-such-language))
+    (should-error (imenu-build-index-native '((nil . "fn (\\w+)"))))))
+
+(ert-deftest imenu-tests-build-index ()
+  (with-temp-buffer
+    (insert "(defvar é 1)\n(defun f ()\n  nil)\n(defmacro m ())\n")
+    (should (equal (imenu-build-index-native 'emacs-lisp)
+                   '(("Variables" ("é" . 9)) ("f" . 21) ("m" . 43))))
+    ;; Only the accessible portion is indexed.
+    (narrow-to-region 14 (point-max))
+    (should (equal (imenu-build-index-native 'emacs-lisp)
+                   '(("f" . 21) ("m" . 43))))
+    (should (equal (imenu-build-index-native
+                    '(("Functions" . "\\(defun (?P<name>\\w+)")))
+                   '(("Functions" ("f" . 21)))))))
+
+(ert-deftest imenu-tests-jobs ()
+  (with-temp-buffer
+    (insert "fn main() {}\n")
+    (let ((job (imenu-build-index-native 'rust t)))
+      (should (integerp job))
+      (should (equal (imenu-native-job-index job) '(("main" . 4))))
+      ;; The job is gone once its index has been taken.
+      (should-error (imenu-native-job-finished-p job)))
+    (let ((job (imenu-build-index-native 'rust t)))
+      (imenu-native-job-cancel job)
+      (should-error (imenu-native-job-index job)))))
+
+(ert-deftest imenu-tests-create-index ()
+  (with-temp-buffer
+    (setq imenu-native-language 'python)
+    (insert "def f():\n    pass\n")
+    (should (equal (imenu-native-create-index) '(("f" . 5))))
+    (goto-char (point-max))
+    (insert "def g():\n    pass\n")
+    ;; The old index is returned until the new one is ready.
+    (let ((index (imenu-native-create-index t)))
+      (should (or (equal index '(("f" . 5)))
+                  (equal index '(("f" . 5) ("g" . 23))))))
+    (should (equal (imenu-native-create-index) '(("f" . 5) ("g" . 23))))
+    (should (equal (cdr (imenu--make-index-alist))
+                   '(("f" . 5) ("g" . 23))))))
+
+(provide 'imenu-tests)
+;;; imenu-tests.el ends here
@@ -0,0 +1,52 @@
+;;; indent-tests.el --- tests for indent.c  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest indent-tests-indentation-stats ()
+  (with-temp-buffer
+    (insert "a\n  b\n    c\n\n\td \n  \n")
+    (setq tab-width 4)
+    (let ((stats (buffer-indentation-stats)))
+      (should (= (plist-get stats :lines) 6))
+      (should (= (plist-get stats :blank) 2))
+      (should (= (plist-get stats :unindented) 1))
+      (should (= (plist-get stats :spaces) 2))
+      (should (= (plist-get stats :tabs) 1))
+      (should (= (plist-get stats :mixed) 0))
+      (should (= (plist-get stats :trailing) 1))
+      (should (equal (plist-get stats :widths) '((2 . 1) (4 . 1))))
+      (should (equal (plist-get stats :deltas) '((2 . 2)))))
+    (narrow-to-region 1 3)
+    (should (= (plist-get (buffer-indentation-stats) :lines) 1))))
+
+(ert-deftest indent-tests-detect-indentation ()
+  (with-temp-buffer
+    (insert "if x:\n    y\n    if z:\n        w\n")
+    (should (equal (detect-indentation) '(nil . 4)))
+    (let ((buffer (current-buffer)))
+      (with-temp-buffer
+        (insert "f {\n\tg {\n\t\th\n")
+        (should (equal (detect-indentation) '(t . 8)))
+        (should (equal (detect-indentation buffer) '(nil . 4)))))
+    (erase-buffer)
+    (insert "no\nindentation\n")
+    (should-not (detect-indentation))))
+
+(provide 'indent-tests)
+;;; indent-tests.el ends here
@@ -0,0 +1,44 @@
+;;; invisibility-tests.el --- tests for invisibility.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest next-visible-position ()
+  (with-temp-buffer
+    (insert "0123456789abcdefghij")
+    (put-text-property 3 6 'invisible t)
+    (overlay-put (make-overlay 6 9) 'invisible 'fold)
+    (should (= (next-visible-position 1) 1))
+    (should (= (next-visible-position 3) 9))
+    (should (= (next-visible-position 4 7) 7))
+    (should (= (previous-visible-position 9) 3))
+    (should (= (previous-visible-position 12) 12))
+    (should (= (previous-visible-position 9 5) 5))
+    ;; Changes of the invisibility spec and the properties are seen.
+    (setq buffer-invisibility-spec '(t))
+    (should (= (next-visible-position 3) 6))
+    (should (= (next-visible-position 6) 6))
+    (setq buffer-invisibility-spec '((fold . t)))
+    (should (= (previous-visible-position 9) 6))
+    (put-text-property 12 15 'invisible 'fold)
+    (should (= (next-visible-position 13) 15))
+    (narrow-to-region 13 21)
+    (should (= (previous-visible-position 15) 13))))
+
+(provide 'invisibility-tests)
+;;; invisibility-tests.el ends here
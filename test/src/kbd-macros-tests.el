@@ -0,0 +1,77 @@
+;;; kbd-macros-tests.el --- tests for kbd_macros.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro kbd-macros-tests--in-buffer (&rest body)
+  "Run BODY in a temporary buffer shown in the selected window."
+  (declare (indent 0))
+  `(with-temp-buffer
+     (save-window-excursion
+       (set-window-buffer nil (current-buffer))
+       ,@body)))
+
+(ert-deftest kbd-macros-tests-execute ()
+  (kbd-macros-tests--in-buffer
+    (execute-kbd-macro "ab" 3)
+    (should (equal (buffer-string) "ababab"))
+    (erase-buffer)
+    (let ((count 0))
+      (execute-kbd-macro [?x] 0 (lambda () (< (setq count (1+ count)) 3))))
+    (should (equal (buffer-string) "xx"))
+    (should-error (execute-kbd-macro 'car))))
+
+(ert-deftest kbd-macros-tests-unread-events ()
+  "Events unread before a macro are not read by it."
+  (kbd-macros-tests--in-buffer
+    (let ((unread-command-events (list ?z)))
+      (execute-kbd-macro "ab")
+      (should (equal (buffer-string) "ab"))
+      (should (equal unread-command-events '(?z))))))
+
+(ert-deftest kbd-macros-tests-termination-hook ()
+  (kbd-macros-tests--in-buffer
+    (let* ((ran nil)
+           (kbd-macro-termination-hook (list (lambda () (setq ran t)))))
+      (execute-kbd-macro "a")
+      (should ran)
+      (should-not executing-kbd-macro))))
+
+(ert-deftest kbd-macros-tests-to-lisp ()
+  (with-temp-buffer
+    (should (equal (kmacro-to-lisp "abc") '((insert "abc"))))
+    (should (equal (kmacro-to-lisp (kbd "a C-f b"))
+                   '((insert "a")
+                     (call-interactively #'forward-char)
+                     (insert "b"))))
+    (should (equal (kmacro-to-lisp (kbd "C-u C-u C-f C-u 1 2 C-b"))
+                   '((let ((current-prefix-arg '(16)))
+                       (call-interactively #'forward-char))
+                     (let ((current-prefix-arg 12))
+                       (call-interactively #'backward-char)))))
+    (should (equal (kmacro-to-lisp (kbd "M-- C-a C-u 3 x"))
+                   '((let ((current-prefix-arg '-))
+                       (call-interactively #'move-beginning-of-line))
+                     (insert "xxx"))))
+    (should (equal (kmacro-to-lisp (kbd "C-x C-x"))
+                   '((call-interactively #'exchange-point-and-mark))))
+    (should-error (kmacro-to-lisp 1))))
+
+(provide 'kbd-macros-tests)
+;;; kbd-macros-tests.el ends here
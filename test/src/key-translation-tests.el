@@ -0,0 +1,109 @@
+;;; key-translation-tests.el --- tests for key_translation.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro key-translation-tests--with-maps (&rest body)
+  "Run BODY with empty translation maps and a local map in a buffer.
+The local map is bound to `map'."
+  (declare (indent 0))
+  `(with-temp-buffer
+     (let ((input-decode-map (make-sparse-keymap))
+           (local-function-key-map (make-sparse-keymap))
+           (key-translation-map (make-sparse-keymap))
+           (map (make-sparse-keymap)))
+       (use-local-map map)
+       ,@body)))
+
+(ert-deftest key-translation-tests-order ()
+  (key-translation-tests--with-maps
+    (define-key input-decode-map [f29] [f30])
+    (define-key local-function-key-map [f30] [f31])
+    (define-key key-translation-map [f31] [f32])
+    (define-key map [f32] #'forward-char)
+    (should (equal (key-translation-pipeline [f29])
+                   '((input-decode-map [f29] [f30])
+                     (local-function-key-map [f30] [f31])
+                     (key-translation-map [f31] [f32])
+                     (binding [f32] forward-char))))))
+
+(ert-deftest key-translation-tests-escape-sequence ()
+  (key-translation-tests--with-maps
+    (define-key input-decode-map "\e[A" [f30])
+    (define-key map [f30] #'forward-char)
+    (should (equal (key-translation-pipeline "\e[A")
+                   '((input-decode-map [27 91 65] [f30])
+                     (binding [f30] forward-char))))))
+
+(ert-deftest key-translation-tests-function-key-map ()
+  "`local-function-key-map' applies only to keys without a binding."
+  (key-translation-tests--with-maps
+    (define-key local-function-key-map [f30] [f31])
+    (define-key map [f31] #'forward-char)
+    (should (equal (key-translation-pipeline [f30])
+                   '((local-function-key-map [f30] [f31])
+                     (binding [f31] forward-char))))
+    (define-key map [f30] #'backward-char)
+    (should (equal (key-translation-pipeline [f30])
+                   '((binding [f30] backward-char))))
+    ;; `key-translation-map' applies regardless.
+    (define-key key-translation-map [f30] [f31])
+    (should (equal (key-translation-pipeline [f30])
+                   '((key-translation-map [f30] [f31])
+                     (binding [f31] forward-char))))))
+
+(ert-deftest key-translation-tests-function ()
+  "Function bindings are shown but not called."
+  (key-translation-tests--with-maps
+    (let ((translate (lambda (_prompt) (error "Called"))))
+      (define-key key-translation-map [f30] translate)
+      (should (equal (key-translation-pipeline [f30])
+                     `((key-translation-map [f30] ,translate)
+                       (binding [f30] nil)))))))
+
+(ert-deftest key-translation-tests-remap ()
+  (key-translation-tests--with-maps
+    (define-key map [f30] #'forward-char)
+    (define-key map [remap forward-char] #'forward-word)
+    (should (equal (key-translation-pipeline [f30])
+                   '((binding [f30] forward-char)
+                     (remap forward-char forward-word))))))
+
+(ert-deftest key-translation-tests-complete-key ()
+  "Events after a complete key sequence are not part of it."
+  (key-translation-tests--with-maps
+    (define-key map [f30] #'forward-char)
+    (define-key key-translation-map [f31] [f32])
+    (should (equal (key-translation-pipeline [f30 f31])
+                   '((binding [f30] forward-char))))))
+
+(ert-deftest key-translation-tests-describe ()
+  (key-translation-tests--with-maps
+    (define-key key-translation-map [f30] [f31])
+    (define-key map [f31] #'forward-char)
+    (save-window-excursion
+      (describe-key-translation-pipeline [f30])
+      (with-current-buffer (help-buffer)
+        (should (string-match-p "`key-translation-map' translates <f30> to <f31>"
+                                (buffer-string)))
+        (should (string-match-p "<f31> runs `forward-char'"
+                                (buffer-string)))))))
+
+(provide 'key-translation-tests)
+;;; key-translation-tests.el ends here
@@ -0,0 +1,54 @@
+;;; ldap-tests.el --- tests for ldap.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defconst ldap-tests--reply
+  (concat
+   ;; A search result entry for "cn=a" with mail "a@b.c".
+   (unibyte-string #x30 #x1c #x02 #x01 #x02 #x64 #x17 #x04 #x04) "cn=a"
+   (unibyte-string #x30 #x0f #x04 #x04) "mail"
+   (unibyte-string #x31 #x07 #x04 #x05) "a@b.c"
+   ;; A successful search result done.
+   (unibyte-string #x30 #x0c #x02 #x01 #x02
+                   #x65 #x07 #x0a #x01 #x00 #x04 #x00 #x04 #x00))
+  "The reply of the test server to a search.")
+
+(ert-deftest ldap-search-native-invalid-filter ()
+  (should-error (ldap-search-native "(cn=a" "localhost"))
+  (should-error (ldap-search-native "(cn>=a*)" "localhost")))
+
+(ert-deftest ldap-search-native ()
+  (let* ((server (make-network-process
+                  :name "ldap-tests" :server t :host 'local :service t
+                  :family 'ipv4 :coding 'binary :noquery t
+                  :filter (lambda (process string)
+                            ;; Answer the search request, tagged #x63.
+                            (when (memq #x63 (string-to-list string))
+                              (process-send-string process ldap-tests--reply)))))
+         (host (format "127.0.0.1:%d" (process-contact server :service))))
+    (unwind-protect
+        (let ((entries (ldap-search-native "(mail=*)" host '("mail"))))
+          (should (= (length entries) 1))
+          (should (equal (gethash "dn" (car entries)) "cn=a"))
+          (should (equal (gethash "mail" (car entries)) '("a@b.c"))))
+      (delete-process server))))
+
+(provide 'ldap-tests)
+;;; ldap-tests.el ends here
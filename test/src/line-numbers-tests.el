@@ -0,0 +1,45 @@
+;;; line-numbers-tests.el --- tests for line_numbers.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest buffer-line-number ()
+  (with-temp-buffer
+    (dotimes (i 20000)
+      (insert (format "line %d\n" i)))
+    (should (= (buffer-line-number 1) 1))
+    (should (= (buffer-line-number) 20001))
+    (goto-char (point-min))
+    (forward-line 15000)
+    (should (= (buffer-line-number) 15001))
+    (should (= (buffer-line-number (point)) (line-number-at-pos)))
+    ;; Changes before the remembered counts are seen.
+    (goto-char (point-min))
+    (insert "one\ntwo\n")
+    (forward-line 15000)
+    (should (= (buffer-line-number) 15003))
+    (goto-char (point-max))
+    (delete-region (line-beginning-position -10) (point))
+    (should (= (buffer-line-number) (line-number-at-pos)))
+    (narrow-to-region (line-beginning-position -99) (point-max))
+    (should (= (buffer-line-number) 101))
+    (should (= (buffer-line-number nil t) (line-number-at-pos nil t)))
+    (should-error (buffer-line-number 1))))
+
+(provide 'line-numbers-tests)
+;;; line-numbers-tests.el ends here
@@ -0,0 +1,96 @@
+;;; lines-tests.el --- tests for the streaming line reader  -*- lexical-binding: t -*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defun lines-tests--file-lines (contents &optional coding)
+  (let ((file (make-temp-file "lines-tests"))
+        (lines nil))
+    (unwind-protect
+        (progn
+          (let ((coding-system-for-write 'no-conversion))
+            (write-region contents nil file nil 'silent))
+          (list (with-file-lines file (lambda (line) (push line lines)) coding)
+                (nreverse lines)))
+      (delete-file file))))
+
+(ert-deftest lines-tests--with-file-lines ()
+  (should (equal (lines-tests--file-lines "one\ntwo\r\n\nthree")
+                 '(4 ("one" "two" "" "three"))))
+  (should (equal (lines-tests--file-lines "one\n") '(1 ("one"))))
+  (should (equal (lines-tests--file-lines "") '(0 nil)))
+  ;; Lines longer than a read chunk are delivered whole.
+  (let ((long (make-string 100000 ?x)))
+    (should (equal (lines-tests--file-lines (concat long "\nend"))
+                   (list 2 (list long "end"))))))
+
+(ert-deftest lines-tests--with-file-lines-decoding ()
+  (let ((utf-8 (encode-coding-string "café\n" 'utf-8)))
+    (should (equal (lines-tests--file-lines utf-8) '(1 ("café"))))
+    (should (equal (lines-tests--file-lines utf-8 'latin-1)
+                   (list 1 (list (decode-coding-string "caf\303\251" 'latin-1))))))
+  (should-not (multibyte-string-p
+               (car (nth 1 (lines-tests--file-lines "\377\376\n"))))))
+
+(ert-deftest lines-tests--with-file-lines-nonlocal-exit ()
+  (let ((file (make-temp-file "lines-tests" nil nil "a\nb\n"))
+        (seen nil))
+    (unwind-protect
+        (should (eq (catch 'done
+                      (with-file-lines file
+                                       (lambda (line)
+                                         (push line seen)
+                                         (throw 'done 'thrown))))
+                    'thrown))
+      (delete-file file))
+    (should (equal seen '("a"))))
+  (should-error (with-file-lines "/nonexistent/lines-tests" #'ignore)
+                :type 'file-missing))
+
+(ert-deftest lines-tests--line-filter ()
+  (let* ((proc (make-pipe-process :name "lines-tests" :noquery t))
+         (lines nil)
+         (fn (lambda (p line) (push (cons p line) lines))))
+    (unwind-protect
+        (progn
+          (line-filter-feed fn proc "one\r")
+          (should-not lines)
+          (line-filter-feed fn proc "\ntw")
+          (line-filter-feed fn proc "o\nthree\n\nfo")
+          (should (equal (mapcar #'cdr (reverse lines))
+                         '("one" "two" "three" "")))
+          (should (eq (car (car lines)) proc))
+          (setq lines nil)
+          (should (equal (line-filter-flush fn proc) "fo"))
+          (should (equal lines (list (cons proc "fo"))))
+          (should-not (line-filter-flush fn proc)))
+      (delete-process proc))))
+
+(ert-deftest lines-tests--make-line-filter ()
+  (let* ((proc (make-pipe-process :name "lines-tests" :noquery t))
+         (lines nil)
+         (filter (make-line-filter (lambda (_ line) (push line lines)))))
+    (unwind-protect
+        (progn
+          (funcall filter proc "a\nb")
+          (funcall filter proc "c\n")
+          (should (equal (nreverse lines) '("a" "bc"))))
+      (delete-process proc))))
+
+(provide 'lines-tests)
+;;; lines-tests.el ends here
@@ -0,0 +1,72 @@
+;;; longlines-tests.el --- tests for longlines.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defun longlines-tests--insert-file (contents)
+  "Insert a file holding CONTENTS in the current buffer.
+Return the classifications passed to the hook."
+  (let* ((file (make-temp-file "longlines" nil nil contents))
+         (classifications nil)
+         (file-content-classification-functions
+          (list (lambda (classification)
+                  (push classification classifications)))))
+    (unwind-protect
+        (let ((coding-system-for-read 'utf-8))
+          (insert-file-contents file)
+          classifications)
+      (delete-file file))))
+
+(ert-deftest longlines-tests--minified ()
+  (with-temp-buffer
+    (let ((file-content-long-line-threshold 100))
+      (should (equal (longlines-tests--insert-file
+                      (apply #'concat (make-list 50 "function(a,b){return a+b};")))
+                     '(minified)))
+      (should (eq file-content-classification 'minified)))))
+
+(ert-deftest longlines-tests--long-lines ()
+  (with-temp-buffer
+    (let ((file-content-long-line-threshold 100))
+      (should (equal (longlines-tests--insert-file
+                      (apply #'concat (make-list 50 "some words ")))
+                     '(long-lines)))
+      (should (eq (file-content-classify-region (point-min) (point-max))
+                  'long-lines)))))
+
+(ert-deftest longlines-tests--binary ()
+  (with-temp-buffer
+    (should (equal (longlines-tests--insert-file "\177ELF\0\1\1\0")
+                   '(binary)))))
+
+(ert-deftest longlines-tests--ordinary ()
+  (with-temp-buffer
+    (should-not (longlines-tests--insert-file "short\nlines\n"))
+    (should-not file-content-classification)
+    (let ((file-content-long-line-threshold nil))
+      (insert (make-string 20000 ?x))
+      (should-not (file-content-classify-region (point-min) (point-max))))))
+
+(ert-deftest longlines-tests--permanent-local ()
+  (with-temp-buffer
+    (setq file-content-classification 'binary)
+    (fundamental-mode)
+    (should (eq file-content-classification 'binary))))
+
+(provide 'longlines-tests)
+;;; longlines-tests.el ends here
@@ -187,4 +187,86 @@ literals (Bug#20852)."
     (lread--substitute-object-in-subtree x 1 t)
     (should (eq x (cdr x)))))
 
+(ert-deftest lread-tests--hash-table-syntax ()
+  (let ((table (read "#s(hash-table size 3 test equal data (\"a\" 1 b 2))")))
+    (should (hash-table-p table))
+    (should (eq (hash-table-test table) 'equal))
+    (should (= (gethash "a" table) 1))
+    (should (= (gethash 'b table) 2))
+    (should (= (hash-table-count table) 2)))
+  (should (eq (hash-table-test (read "#s(hash-table)")) 'eql))
+  (should-error (read "#s(hash-table data (a))")))
+
+(ert-deftest lread-tests--record-syntax ()
+  (let ((record (read "#s(foo 1 \"two\")")))
+    (should (recordp record))
+    (should (eq (type-of record) 'foo))
+    (should (equal (aref record 2) "two")))
+  (should-error (read "#s()") :type 'invalid-read-syntax))
+
+(ert-deftest lread-tests--load-native-elc ()
+  (let* ((source (make-temp-file "lread-tests" nil ".el"))
+         (compiled (concat source "c")))
+    (unwind-protect
+        (progn
+          (with-temp-file source
+            (insert ";;; -*- lexical-binding: t -*-\n"
+                    "(defvar lread-tests--native-data\n"
+                    "  '(1 -2.5 \"caf\\u00e9\" \"\\377\" [a (b . c)] #&3\"\\5\"))\n"
+                    "(defun lread-tests--native-fun (x)\n"
+                    "  \"Return a function that returns X plus one.\"\n"
+                    "  (let ((y (1+ x))) (lambda () y)))\n"))
+          (byte-compile-file source)
+          (makunbound 'lread-tests--native-data)
+          (fmakunbound 'lread-tests--native-fun)
+          (should (eq (load-native-elc compiled nil t) t))
+          (should (equal lread-tests--native-data
+                         '(1 -2.5 "caf\u00e9" "\377" [a (b . c)] #&3"\5")))
+          (should (multibyte-string-p (nth 2 lread-tests--native-data)))
+          (should-not (multibyte-string-p (nth 3 lread-tests--native-data)))
+          (should (byte-code-function-p
+                   (symbol-function 'lread-tests--native-fun)))
+          (should (= (funcall (lread-tests--native-fun 1)) 2))
+          (should (equal (documentation 'lread-tests--native-fun)
+                         "Return a function that returns X plus one."))
+          (should (assoc (file-truename compiled) load-history)))
+      (makunbound 'lread-tests--native-data)
+      (fmakunbound 'lread-tests--native-fun)
+      (delete-file source)
+      (when (file-exists-p compiled)
+        (delete-file compiled)))))
+
+(ert-deftest lread-tests--obarray-statistics ()
+  (let ((ob (make-vector 7 0)))
+    (dolist (name '("a" "b" "c" "d" "e" "f" "g" "h" "i" "j"))
+      (intern name ob))
+    (let ((stats (obarray-statistics ob)))
+      (should (= (cdr (assq 'symbols stats)) 10))
+      (should (= (cdr (assq 'buckets stats)) 7))
+      (should (= (cdr (assq 'collisions stats))
+                 (- 10 (cdr (assq 'used-buckets stats)))))
+      (should (floatp (cdr (assq 'collision-rate stats))))
+      (should (= (cdr (assq 'core-symbols stats)) 0)))
+    (should (unintern "c" ob))
+    (should-not (intern-soft "c" ob))
+    (should (intern-soft "d" ob))
+    (should (= (cdr (assq 'symbols (obarray-statistics ob))) 9))))
+
+(ert-deftest lread-tests--core-obarray-lookup ()
+  ;; The core is rebuilt at startup and holds the dumped symbols.
+  (let ((stats (obarray-statistics)))
+    (should (> (cdr (assq 'core-symbols stats)) 0)))
+  (should (eq (intern-soft "car") 'car))
+  (should (eq (intern "emacs-version") 'emacs-version)))
+
+(ert-deftest lread-tests--core-obarray-unintern ()
+  ;; Symbols in the dumped core must still be uninternable.
+  (let ((stats (obarray-statistics)))
+    (should (<= (cdr (assq 'core-symbols stats))
+                (cdr (assq 'symbols stats)))))
+  (let ((sym (intern "lread-tests--core-obarray-unintern-sym")))
+    (should (eq (intern-soft (symbol-name sym)) sym))
+    (unintern sym obarray)
+    (should-not (intern-soft "lread-tests--core-obarray-unintern-sym"))))
+
 ;;; lread-tests.el ends here
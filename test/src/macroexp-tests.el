@@ -0,0 +1,125 @@
+;;; macroexp-tests.el --- tests for native macro expansion  -*- lexical-binding: t -*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defmacro macroexp-tests--inc (place)
+  `(setq ,place (1+ ,place)))
+
+(defmacro macroexp-tests--twice (form)
+  `(progn ,form ,form))
+
+(defalias 'macroexp-tests--inc-alias 'macroexp-tests--inc)
+
+(defmacro macroexp-tests--forever ()
+  (list 'macroexp-tests--forever))
+
+(ert-deftest macroexp-tests--macroexpand-1 ()
+  (should (equal (macroexpand-1 '(macroexp-tests--twice (foo)))
+                 '(progn (foo) (foo))))
+  ;; Only one step is taken.
+  (should (equal (macroexpand-1 '(macroexp-tests--twice
+                                  (macroexp-tests--inc x)))
+                 '(progn (macroexp-tests--inc x) (macroexp-tests--inc x))))
+  ;; Aliases to macros are followed without expanding.
+  (should (equal (macroexpand-1 '(macroexp-tests--inc-alias x))
+                 '(macroexp-tests--inc x)))
+  (should (equal (macroexpand-1 '(car x)) '(car x)))
+  (should (eq (macroexpand-1 'x) 'x))
+  (should (equal (macroexpand-1 '(macroexp-tests--inc x)
+                                '((macroexp-tests--inc . nil)))
+                 '(macroexp-tests--inc x)))
+  (should (equal (macroexpand-1 '(foo 1 2)
+                                `((foo . ,(lambda (a b) (list '+ a b)))))
+                 '(+ 1 2))))
+
+(ert-deftest macroexp-tests--macroexpand ()
+  (should (equal (macroexpand '(macroexp-tests--inc-alias x))
+                 '(setq x (1+ x))))
+  (let ((form '(car x)))
+    (should (eq (macroexpand form) form)))
+  ;; The environment shadows global definitions.
+  (should (equal (macroexpand '(macroexp-tests--inc x)
+                              '((macroexp-tests--inc . nil)))
+                 '(macroexp-tests--inc x)))
+  (should (equal (macroexpand '(when a b)
+                              `((when . ,(lambda (&rest _) 'shadowed))))
+                 'shadowed)))
+
+(ert-deftest macroexp-tests--macroexpand-runaway ()
+  (let ((max-macroexpand-depth 50))
+    (should-error (macroexpand '(macroexp-tests--forever)))
+    (should-error (macroexpand-all '(progn (macroexp-tests--forever))))))
+
+(ert-deftest macroexp-tests--macroexpand-all ()
+  (should (equal (macroexpand-all
+                  '(let ((a (macroexp-tests--twice (macroexp-tests--inc b))))
+                     (cond ((macroexp-tests--inc c) 'quote-me)
+                           (t '(macroexp-tests--inc d)))))
+                 '(let ((a (progn (setq b (1+ b)) (setq b (1+ b)))))
+                    (cond ((setq c (1+ c)) 'quote-me)
+                          (t '(macroexp-tests--inc d))))))
+  (should (equal (macroexpand-all
+                  '(condition-case err (macroexp-tests--inc x)
+                     (error (macroexp-tests--inc y))))
+                 '(condition-case err (setq x (1+ x))
+                    (error (setq y (1+ y))))))
+  (should (equal (macroexpand-all
+                  '(function (lambda (x) (macroexp-tests--inc x))))
+                 '(function (lambda (x) (setq x (1+ x))))))
+  (should (equal (macroexpand-all '(funcall #'macroexp-tests--inc x))
+                 '(setq x (1+ x))))
+  (should (equal (macroexpand-all '(defvar v (macroexp-tests--inc x)))
+                 '(defvar v (setq x (1+ x))))))
+
+(ert-deftest macroexp-tests--macroexpand-all-shares-structure ()
+  (let ((form '(list (car x) (cdr y) (+ 1 2))))
+    (should (eq (macroexpand-all form) form)))
+  (let* ((tail '((car y) (cdr z)))
+         (form (cons 'list (cons '(macroexp-tests--inc x) tail)))
+         (expanded (macroexpand-all form)))
+    (should (equal expanded '(list (setq x (1+ x)) (car y) (cdr z))))
+    (should (eq (cddr expanded) tail))))
+
+(ert-deftest macroexp-tests--macroexpand-all-environment ()
+  (should (equal (macroexpand-all
+                  '(list (foo 1))
+                  `((foo . ,(lambda (x) (list 'bar x)))))
+                 '(list (bar 1))))
+  ;; The environment is visible to expanders while they run.
+  (should (equal (macroexpand-all
+                  '(env-p)
+                  `((env-p . ,(lambda ()
+                                (list 'quote
+                                      (consp (assq 'env-p
+                                                   macroexpand-all-environment)))))))
+                 '(quote t)))
+  (should-not macroexpand-all-environment))
+
+(ert-deftest macroexp-tests--macroexpand-all-deep ()
+  (let ((form 'x))
+    (dotimes (_ 500)
+      (setq form (list 'when t form)))
+    (should (eq (car (nth 1 (nth 2 (macroexpand-all form)))) 'if)))
+  (let ((max-macroexpand-depth 20)
+        (form 'x))
+    (dotimes (_ 30)
+      (setq form (list 'car form)))
+    (should-error (macroexpand-all form))))
+
+;;; macroexp-tests.el ends here
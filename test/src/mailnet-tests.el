@@ -0,0 +1,38 @@
+;;; mailnet-tests.el --- tests for mailnet.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest mailnet-not-a-connection ()
+  (let ((process (make-pipe-process :name "mailnet-tests" :noquery t)))
+    (unwind-protect
+        (progn
+          (should-error (smtp-native-command process "NOOP" #'ignore))
+          (should-error (nntp-native-command process "DATE" nil #'ignore))
+          (should-not (mailnet-process-filter process "250 OK\r\n")))
+      (delete-process process))))
+
+(ert-deftest mailnet-smtp-auth-mechanism ()
+  (let ((process (make-pipe-process :name "mailnet-tests" :noquery t)))
+    (unwind-protect
+        (should-error (smtp-native-auth process "user" "secret" 'cram-md5))
+      (delete-process process))))
+
+(provide 'mailnet-tests)
+;;; mailnet-tests.el ends here
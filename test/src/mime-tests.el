@@ -0,0 +1,80 @@
+;;; mime-tests.el --- tests for mime.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest mime-tests--quoted-printable-decode-region ()
+  (with-temp-buffer
+    (insert "caf=C3=A9 =3d soft=\nbreak=20\nodd=zz")
+    (quoted-printable-decode-region-native (point-min) (point-max) 'utf-8)
+    (should (equal (buffer-string) "café = softbreak \nodd=zz")))
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "a=FFb")
+    (quoted-printable-decode-region-native (point-min) (point-max))
+    (should (equal (buffer-string) "a\377b"))))
+
+(ert-deftest mime-tests--rfc2047-decode-string ()
+  (should (equal (rfc2047-decode-string-native "plain") "plain"))
+  (should (equal (rfc2047-decode-string-native
+                  "Re: =?UTF-8?Q?caf=C3=A9?= =?utf-8?B?IG5vaXI=?= ok")
+                 "Re: café noir ok"))
+  ;; A character split between two words.
+  (should (equal (rfc2047-decode-string-native
+                  "=?utf-8?q?caf=C3?=\n =?utf-8?q?=A9?=")
+                 "café"))
+  (should (equal (rfc2047-decode-string-native "=?no-such-charset?q?x?= y")
+                 "=?no-such-charset?q?x?= y"))
+  (should (multibyte-string-p (rfc2047-decode-string-native "abc"))))
+
+(ert-deftest mime-tests--parse-buffer ()
+  (with-temp-buffer
+    (insert "Subject: caf\u00e9\nContent-Type: multipart/mixed; boundary=\"b\"\n\n"
+            "--b\nContent-Type: text/plain; charset=utf-8\n\nnaïve\n"
+            "--b\n\nsecond\n--b--\n")
+    (let* ((tree (mime-parse-buffer))
+           (parts (plist-get tree :parts))
+           (first (car parts)))
+      (should (equal (plist-get tree :type) "multipart/mixed"))
+      (should (equal (gethash "subject" (plist-get tree :headers)) "café"))
+      (should (= (length parts) 2))
+      (should (equal (plist-get first :parameters) '(("charset" . "utf-8"))))
+      (should (equal (buffer-substring (plist-get first :body)
+                                       (plist-get first :end))
+                     "naïve"))
+      (should (equal (plist-get (cadr parts) :type) "text/plain"))
+      (should (markerp (plist-get first :start))))))
+
+(ert-deftest mime-tests--parse-addresses ()
+  (should (equal (mail-parse-addresses
+                  "\"Doe, John\" <john@example.com>, jane@example.org (Jane)")
+                 '((:name "Doe, John" :address "john@example.com" :comments nil)
+                   (:name nil :address "jane@example.org" :comments ("Jane")))))
+  (should (equal (mail-parse-addresses "Team: a@b, c@d;, not an address")
+                 '((:group "Team"
+                    :addresses ((:name nil :address "a@b" :comments nil)
+                                (:name nil :address "c@d" :comments nil)))
+                   (:invalid "not an address"))))
+  (should (equal (mail-extract-address-components
+                  "\"Doe, John\" <john@example.com>")
+                 '("Doe, John" "john@example.com")))
+  (should (equal (mail-extract-address-components "a@b (A  B), c@d" t)
+                 '(("A B" "a@b") (nil "c@d")))))
+
+(provide 'mime-tests)
+;;; mime-tests.el ends here
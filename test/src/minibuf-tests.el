@@ -400,4 +400,104 @@
    #'minibuf-tests--strings-to-symbol-hashtable))
 
 
+;;; Hash tables are searched through a cached sorted index of their
+;;; keys when completion is case-sensitive.
+
+(ert-deftest completion-hashtable-after-modification ()
+  (let ((ht (minibuf-tests--strings-to-string-hashtable
+             '("abc" "def" "abd"))))
+    (should (equal (all-completions "ab" ht) '("abc" "abd")))
+    (remhash "abc" ht)
+    (puthash "abx" 4 ht)
+    (should (equal (sort (all-completions "ab" ht) #'string<)
+                   '("abd" "abx")))
+    (should (equal (try-completion "abx" ht) t))
+    (should (test-completion "abx" ht))
+    (should-not (test-completion "abc" ht))
+    (clrhash ht)
+    (should-not (all-completions "" ht))))
+
+(ert-deftest completion-hashtable-after-aset ()
+  (let* ((key (copy-sequence "abc"))
+         (ht (minibuf-tests--strings-to-string-hashtable (list key "abd"))))
+    (should (equal (all-completions "ab" ht) '("abc" "abd")))
+    (aset key 0 ?x)
+    (should (equal (all-completions "ab" ht) '("abd")))
+    (should (equal (all-completions "xb" ht) '("xbc")))
+    (fillarray key ?z)
+    (should (equal (all-completions "zz" ht) '("zzz")))))
+
+(ert-deftest completion-hashtable-mixed-keys ()
+  (let ((ht (make-hash-table :test #'equal)))
+    (puthash "abc" 1 ht)
+    (puthash 'abd 2 ht)
+    (puthash 42 3 ht)
+    (puthash "\u00e9t\u00e9" 4 ht)
+    (should (equal (all-completions "ab" ht) '("abc" "abd")))
+    (should (equal (try-completion "\u00e9" ht) "\u00e9t\u00e9"))
+    (should (test-completion "abd" ht))))
+
+(ert-deftest completion-hashtable-ignore-case ()
+  (let ((ht (minibuf-tests--strings-to-string-hashtable
+             '("Abc" "abd")))
+        (completion-ignore-case t))
+    (should (equal (all-completions "AB" ht) '("Abc" "abd")))
+    (should (test-completion "ABC" ht))))
+
+;;; History.
+
+(defvar minibuf-tests--history nil)
+
+(ert-deftest add-to-history-dedup-and-trim ()
+  (let ((minibuf-tests--history (list "b" "a" "c" "a"))
+        (history-delete-duplicates t))
+    (should (equal (add-to-history 'minibuf-tests--history "a" 3)
+                   '("a" "b" "c")))
+    (should (equal minibuf-tests--history '("a" "b" "c")))
+    ;; The most recent entry and empty strings are not added again.
+    (should (equal (add-to-history 'minibuf-tests--history "a")
+                   '("a" "b" "c")))
+    (should (equal (add-to-history 'minibuf-tests--history "")
+                   '("a" "b" "c")))
+    (should (equal (add-to-history 'minibuf-tests--history "" nil t)
+                   '("" "a" "b" "c")))
+    (should-not (add-to-history 'minibuf-tests--history "d" 0))
+    ;; A negative maximum length keeps just the new element.
+    (setq minibuf-tests--history (list "b" "a"))
+    (should (equal (add-to-history 'minibuf-tests--history "d" -1)
+                   '("d")))))
+
+(ert-deftest add-to-history-length-property ()
+  (let ((minibuf-tests--history (list "b" "a"))
+        (history-delete-duplicates nil))
+    (put 'minibuf-tests--history 'history-length 2)
+    (unwind-protect
+        (should (equal (add-to-history 'minibuf-tests--history "a")
+                       '("a" "b")))
+      (put 'minibuf-tests--history 'history-length nil))
+    (should (equal (add-to-history 'minibuf-tests--history "c" t)
+                   '("c" "a" "b")))))
+
+(defvar minibuf-tests--other-history nil)
+
+(ert-deftest history-native-save-load ()
+  (let ((file (make-temp-file "minibuf-tests")))
+    (unwind-protect
+        (progn
+          (let ((minibuf-tests--history (list "a" (point-marker) "b"))
+                (minibuf-tests--other-history (list 'x 1.5)))
+            (history-native-save file '(minibuf-tests--history
+                                        minibuf-tests--other-history
+                                        minibuf-tests--unbound)))
+          (let ((minibuf-tests--history nil)
+                (minibuf-tests--other-history nil))
+            (should (equal (history-native-load file)
+                           '(minibuf-tests--history
+                             minibuf-tests--other-history)))
+            ;; The marker can't be saved and is left out.
+            (should (equal minibuf-tests--history '("a" "b")))
+            (should (equal minibuf-tests--other-history '(x 1.5)))))
+      (delete-file file))
+    (should-not (history-native-load file))))
+
 ;;; minibuf-tests.el ends here
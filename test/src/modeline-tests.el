@@ -0,0 +1,47 @@
+;;; modeline-tests.el --- tests for modeline.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defvar modeline-tests--evaluations 0)
+(defvar modeline-tests--flag nil)
+
+(ert-deftest format-mode-line-cached ()
+  (with-temp-buffer
+    (set-window-buffer nil (current-buffer))
+    (setq modeline-tests--evaluations 0)
+    (let ((format `("a" (:eval (progn (setq modeline-tests--evaluations
+                                            (1+ modeline-tests--evaluations))
+                                      "b"))
+                    (modeline-tests--flag "yes" "no")
+                    (6 "cd")
+                    (-2 "efgh")
+                    (:propertize "i" face bold))))
+      (should (equal (format-mode-line-cached format) "abnocd    efi"))
+      (should (eq (get-text-property 12 'face (format-mode-line-cached format))
+                  'bold))
+      ;; The :eval form was evaluated once.
+      (should (= modeline-tests--evaluations 1))
+      (insert "x")
+      (setq modeline-tests--flag t)
+      (should (equal (format-mode-line-cached format) "abyescd    efi"))
+      (should (= modeline-tests--evaluations 2))
+      (should (equal (format-mode-line-cached "%b") (buffer-name))))))
+
+(provide 'modeline-tests)
+;;; modeline-tests.el ends here
@@ -0,0 +1,159 @@
+;;; motion-tests.el --- tests for motion.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Commentary:
+
+;; `vertical-motion-native' should move exactly as `vertical-motion'
+;; does in batch mode, so each test compares the two from every
+;; position of a buffer.
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro motion-tests--with-text (text &rest body)
+  "Run BODY in a buffer with TEXT shown in the selected window."
+  (declare (indent 1))
+  `(save-window-excursion
+     (with-temp-buffer
+       (set-window-buffer nil (current-buffer))
+       (let ((standard-display-table nil))
+         (insert ,text)
+         ,@body))))
+
+(defun motion-tests--check ()
+  "Check `vertical-motion-native' against `vertical-motion'.
+Move by several numbers of lines from each accessible position."
+  (dolist (lines '(-5 -2 -1 0 1 2 5))
+    (let ((pos (point-min)))
+      (while (<= pos (point-max))
+        (goto-char pos)
+        (let ((expected (list (vertical-motion lines) (point))))
+          (goto-char pos)
+          (should (equal (list pos lines (vertical-motion-native lines) (point))
+                         (cons pos (cons lines expected)))))
+        (setq pos (1+ pos))))))
+
+(defun motion-tests--long-line (n)
+  "Return a line of N words."
+  (mapconcat (lambda (i) (format "word%d" i)) (number-sequence 1 n) " "))
+
+(ert-deftest motion-tests-wrapped-lines ()
+  (motion-tests--with-text
+      (concat (motion-tests--long-line 40) "\n\nshort\n"
+              (make-string (* 3 (window-body-width)) ?x) "\n"
+              (make-string (1- (window-body-width)) ?y) "\n"
+              (make-string (window-body-width) ?z))
+    (motion-tests--check)
+    (narrow-to-region 50 250)
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-characters ()
+  (motion-tests--with-text
+      (concat "a\tb\t\tc\n" "\C-a\C-b\177x\n"
+              (make-string (- (window-body-width) 3) ?.) "漢字かな\n"
+              (make-string (- (window-body-width) 2) ?.) "\tafter\n"
+              "é" (motion-tests--long-line 20) "\n")
+    (motion-tests--check)
+    (let ((ctl-arrow nil)
+          (tab-width 3))
+      (motion-tests--check))))
+
+(ert-deftest motion-tests-unibyte ()
+  (motion-tests--with-text ""
+    (set-buffer-multibyte nil)
+    (insert "abc\300\377def\n" (make-string 100 ?\200) "\n")
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-images ()
+  (motion-tests--with-text
+      (concat "before " (make-string 10 ?i) " after\n"
+              (motion-tests--long-line 30) "\n")
+    (put-text-property 8 18 'display
+                       '(image :type xbm :width 8 :height 1
+                               :data "\377"))
+    (put-text-property 30 31 'display
+                       '((margin left-margin) (image :type xbm :width 8
+                                                     :height 1 :data "\377")))
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-display-properties ()
+  (motion-tests--with-text
+      (concat "one two three\n" (motion-tests--long-line 25) "\nend\n")
+    (put-text-property 5 8 'display "a much longer replacement")
+    (put-text-property 20 21 'display '(space :width 30))
+    (put-text-property 40 41 'display '(space :align-to 70))
+    (put-text-property 60 62 'display '(height 2.0))
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-invisible ()
+  (motion-tests--with-text
+      (concat "visible\nhidden line\nmore\n" (motion-tests--long-line 30) "\nlast\n")
+    (put-text-property 9 21 'invisible t)
+    (put-text-property 40 90 'invisible 'folded)
+    (motion-tests--check)
+    (add-to-invisibility-spec 'folded)
+    (motion-tests--check)
+    (let ((overlay (make-overlay 100 150)))
+      (overlay-put overlay 'invisible t)
+      (motion-tests--check))))
+
+(ert-deftest motion-tests-overlay-strings ()
+  (motion-tests--with-text
+      (concat "start\n" (motion-tests--long-line 30) "\nend\n")
+    (overlay-put (make-overlay 3 3) 'before-string "[before]")
+    (overlay-put (make-overlay 20 25) 'after-string (make-string 50 ?-))
+    (let ((other (make-overlay 30 30)))
+      (overlay-put other 'before-string "elsewhere")
+      (overlay-put other 'window (next-window)))
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-selective-display ()
+  (motion-tests--with-text
+      "top\n  indented\n    deeper\nback\n\tmore\nend\rhidden\nlast\n"
+    (setq selective-display 2)
+    (motion-tests--check)
+    (setq selective-display t)
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-truncation ()
+  (motion-tests--with-text
+      (concat (motion-tests--long-line 40) "\nshort\n"
+              (motion-tests--long-line 30) "\n")
+    (setq truncate-lines t)
+    (motion-tests--check)
+    (setq truncate-lines nil)
+    (set-window-hscroll nil 10)
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-compositions ()
+  (motion-tests--with-text
+      (concat "abc" (motion-tests--long-line 30) "\n")
+    (compose-region 1 4 ?X)
+    (motion-tests--check)))
+
+(ert-deftest motion-tests-other-buffer ()
+  (motion-tests--with-text (motion-tests--long-line 40)
+    (let ((window (selected-window)))
+      (with-temp-buffer
+        (insert (motion-tests--long-line 60))
+        (goto-char 100)
+        (should (equal (vertical-motion-native -1 window)
+                       (progn (goto-char 100) (vertical-motion -1 window))))))))
+
+(provide 'motion-tests)
+;;; motion-tests.el ends here
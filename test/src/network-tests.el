@@ -0,0 +1,205 @@
+;;; network-tests.el --- tests for network.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest network-interface-list-loopback ()
+  (let ((interfaces (network-interface-list nil 'ipv4)))
+    (skip-unless interfaces)
+    (should (member [127 0 0 1 0] (mapcar #'cdr interfaces)))
+    (dolist (interface interfaces)
+      (should (stringp (car interface)))
+      (should (= (length (cdr interface)) 5)))))
+
+(ert-deftest network-interface-list-full ()
+  (dolist (interface (network-interface-list t))
+    (should (= (length interface) 4))
+    (should (memq (length (nth 1 interface)) '(5 9)))))
+
+(ert-deftest network-interface-list-bad-family ()
+  (should-error (network-interface-list nil 'ipx)))
+
+(ert-deftest network-interface-info-loopback ()
+  (let ((loopback (car (rassoc [127 0 0 1 0] (network-interface-list nil 'ipv4)))))
+    (skip-unless loopback)
+    (let ((info (network-interface-info loopback)))
+      (should (equal (nth 0 info) [127 0 0 1 0]))
+      (should (memq 'loopback (nth 4 info)))
+      (should (memq 'up (nth 4 info))))))
+
+(ert-deftest network-interface-info-unknown ()
+  (should-not (network-interface-info "no-such-interface0")))
+
+(ert-deftest process-connection-info-server ()
+  (let ((server (make-network-process :name "server" :server t
+                                      :host 'local :service t
+                                      :family 'ipv4)))
+    (unwind-protect
+        (let ((info (process-connection-info server)))
+          (should (eq (plist-get info :family) 'ipv4))
+          (should (vectorp (plist-get info :local)))
+          (should-not (plist-get info :remote))
+          (should (eq (plist-get info :server) t))
+          (should-not (plist-get info :tls)))
+      (delete-process server))))
+
+(ert-deftest process-connection-info-not-network ()
+  (let ((proc (make-pipe-process :name "test")))
+    (unwind-protect
+        (should-not (process-connection-info proc))
+      (delete-process proc))))
+
+;; A datagram server on a free local port, calling FILTER with sources.
+(defun network-test--datagram-server (filter)
+  (make-network-process :name "udp-server" :server t :type 'datagram
+                        :host "127.0.0.1" :service t :family 'ipv4
+                        :coding 'binary :datagram-source t
+                        :filter filter))
+
+(ert-deftest process-send-datagram-source-address ()
+  (let* ((received nil)
+         (server (network-test--datagram-server
+                  (lambda (_proc string source)
+                    (push (cons string source) received))))
+         (client (make-network-process :name "udp-client" :type 'datagram
+                                       :host "127.0.0.1" :service 9
+                                       :family 'ipv4 :coding 'binary)))
+    (unwind-protect
+        (let ((address (plist-get (process-contact server t) :local))
+              (client-port (aref (plist-get (process-contact client t) :local) 4)))
+          (should-not (process-send-datagram client "ping" address))
+          (with-timeout (2 (ert-fail "No datagram received"))
+            (while (not received)
+              (accept-process-output server 0.1)))
+          (should (equal (caar received) "ping"))
+          (should (equal (aref (cdar received) 4) client-port))
+          ;; The default destination of the client is unchanged.
+          (should (equal (aref (process-datagram-address client) 4) 9)))
+      (delete-process client)
+      (delete-process server))))
+
+(ert-deftest process-send-datagram-not-datagram ()
+  (let ((proc (make-pipe-process :name "test")))
+    (unwind-protect
+        (should-error (process-send-datagram proc "x" [127 0 0 1 9]))
+      (delete-process proc))))
+
+(ert-deftest network-multicast-options ()
+  (let ((server (network-test--datagram-server #'ignore)))
+    (unwind-protect
+        (progn
+          (should (set-network-process-option server :multicast-ttl 2))
+          (should (set-network-process-option server :multicast-loop t))
+          (should-error (set-network-process-option server :multicast-ttl 300))
+          (should-error (set-network-process-option
+                         server :multicast-join [0 0 0 0 0 0 0 1 0])))
+      (delete-process server))))
+
+(ert-deftest make-local-server-peer-credentials ()
+  (skip-unless (featurep 'make-network-process '(:family local)))
+  (let* ((dir (make-temp-file "network-tests" t))
+         (file (expand-file-name "socket" dir))
+         (clients nil)
+         (server (make-local-server "local-server" file
+                                    :log (lambda (_server client _message)
+                                           (push client clients))))
+         (client (make-network-process :name "local-client"
+                                       :family 'local :service file)))
+    (unwind-protect
+        (progn
+          (should (= (logand (file-modes file) #o077) 0))
+          (with-timeout (2 (ert-fail "No connection accepted"))
+            (while (not clients)
+              (accept-process-output nil 0.1)))
+          (let ((credentials (process-peer-credentials (car clients))))
+            (should (equal (plist-get credentials :uid) (user-uid)))
+            (should (equal (plist-get credentials :gid) (group-gid))))
+          (should-not (process-peer-credentials server))
+          (should-error (make-local-server "local-server-2" file)
+                        :type 'file-already-exists))
+      (delete-process client)
+      (delete-process server)
+      (delete-directory dir t))))
+
+(ert-deftest make-local-server-stale-socket ()
+  (skip-unless (featurep 'make-network-process '(:family local)))
+  (let* ((dir (make-temp-file "network-tests" t))
+         (file (expand-file-name "socket" dir)))
+    (unwind-protect
+        (progn
+          (delete-process (make-local-server "local-server" file))
+          (should (file-exists-p file))
+          (delete-process (make-local-server "local-server" file))
+          (write-region "" nil (expand-file-name "plain" dir))
+          (should-error (make-local-server "local-server"
+                                           (expand-file-name "plain" dir))
+                        :type 'file-already-exists))
+      (delete-directory dir t))))
+
+;; Return a port on the local host with nothing listening on it.
+(defun network-tests--closed-port ()
+  (let* ((server (make-network-process :name "closed-port" :server t
+                                       :host 'local :service t
+                                       :family 'ipv4))
+         (port (process-contact server :service)))
+    (delete-process server)
+    port))
+
+(ert-deftest network-reachable-p ()
+  (let ((server (make-network-process :name "reachable" :server t
+                                      :host 'local :service t
+                                      :family 'ipv4)))
+    (unwind-protect
+        (should (eq (network-reachable-p "127.0.0.1"
+                                         (process-contact server :service)
+                                         2)
+                    t))
+      (delete-process server)))
+  (should-not (network-reachable-p "127.0.0.1" (network-tests--closed-port) 2))
+  (should-error (network-reachable-p "127.0.0.1" 70000))
+  (should-error (network-reachable-p "127.0.0.1" 80 0)))
+
+(ert-deftest network-reachable-async ()
+  (let* ((server (make-network-process :name "reachable" :server t
+                                       :host 'local :service t
+                                       :family 'ipv4))
+         (results nil))
+    (unwind-protect
+        (progn
+          (should (integerp
+                   (network-reachable-async
+                    "127.0.0.1" (process-contact server :service)
+                    (lambda (&rest result) (push (cons 'open result) results))
+                    2)))
+          (network-reachable-async
+           "127.0.0.1" (network-tests--closed-port)
+           (lambda (&rest result) (push (cons 'closed result) results))
+           2)
+          (with-timeout (5 (ert-fail "No result"))
+            (while (< (length results) 2)
+              (accept-process-output nil 0.1)))
+          (should (equal (assq 'open results) '(open t)))
+          (let ((closed (assq 'closed results)))
+            (should-not (nth 1 closed))
+            (should (stringp (nth 2 closed))))
+          (should-not (get 'network-reachable-async 'network--timer)))
+      (delete-process server))))
+
+(provide 'network-tests)
+;;; network-tests.el ends here
@@ -0,0 +1,33 @@
+;;; notifications-tests.el --- tests for notifications.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest notify-send-native-arguments ()
+  ;; These are all rejected before anything is shown.
+  (should-error (notify-send-native 'title "body") :type 'wrong-type-argument)
+  (should-error (notify-send-native "title" "body" :urgency))
+  (should-error (notify-send-native "title" "body" :urgency 'urgent))
+  (should-error (notify-send-native "title" "body" :colour "red"))
+  (should-error (notify-send-native "title" "body" :actions '("default")))
+  (should-error (notify-send-native "title" "body" :timeout -2)
+                :type 'args-out-of-range))
+
+(provide 'notifications-tests)
+;;; notifications-tests.el ends here
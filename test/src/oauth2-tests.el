@@ -0,0 +1,36 @@
+;;; oauth2-tests.el --- tests for oauth2.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest oauth2-pkce-pair ()
+  (let ((pair (oauth2-pkce-pair)))
+    (should (= (length (car pair)) 43))
+    (should (= (length (cdr pair)) 43))
+    (should (string-match-p "\\`[-_A-Za-z0-9]+\\'" (car pair)))
+    (should (string-match-p "\\`[-_A-Za-z0-9]+\\'" (cdr pair)))
+    (should-not (equal (car pair) (car (oauth2-pkce-pair))))))
+
+(ert-deftest oauth2-native-refresh-without-token ()
+  (skip-unless (ignore-errors (secrets-lookup "oauth2-tests") t))
+  (should-not (oauth2-native-refresh "https://oauth2-tests.invalid/token"
+                                     "oauth2-tests-client")))
+
+(provide 'oauth2-tests)
+;;; oauth2-tests.el ends here
@@ -0,0 +1,75 @@
+;;; orgdates-tests.el --- tests for native Org date arithmetic  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+(require 'calendar)
+(require 'diary-lib)
+
+(defconst orgdates-tests--sexps
+  '((diary-date 12 t t)
+    (diary-date (1 7) (1 15) t)
+    (diary-block 2 27 2016 3 2 2016)
+    (diary-anniversary 2 29 2000)
+    (diary-anniversary 6 1)
+    (diary-cyclic 10 1 3 2016)
+    (diary-float 11 4 4)
+    (diary-float t 1 -1)
+    (diary-float (3 9) 0 2 25)
+    (diary-float 1 5 -2 3)
+    (and (diary-float t 1 1) (not (diary-date 1 t t)))
+    (or (diary-date 7 4 t) (diary-cyclic 30 1 1 2016))))
+
+(ert-deftest orgdates-tests-matches-diary ()
+  (let ((calendar-date-style 'american)
+        (entry ""))
+    (dolist (sexp orgdates-tests--sexps)
+      (let ((day (calendar-absolute-from-gregorian '(1 1 2016))))
+        (while (< day (calendar-absolute-from-gregorian '(1 1 2018)))
+          (let ((date (calendar-gregorian-from-absolute day)))
+            (should (eq (and (eval sexp t) t)
+                        (and (org-date-matches-p date sexp) t)))
+            (should (eq (org-date-matches-p date sexp)
+                        (org-date-matches-p day sexp))))
+          (setq day (1+ day)))))))
+
+(ert-deftest orgdates-tests-unsupported ()
+  (should-error (org-date-matches-p '(1 1 2018) '(diary-lunar-phases)))
+  (should-error (org-date-matches-p '(1 1 2018) '(diary-date month 1 t)))
+  (should-error (org-date-matches-p '(1 1) '(diary-date t t t))))
+
+(ert-deftest orgdates-tests-repeat-expand ()
+  (let ((abs (lambda (m d y) (calendar-absolute-from-gregorian (list m d y)))))
+    (should (equal (org-date-repeat-expand '(1 1 2018) "+1w" '(1 10 2018)
+                                           '(1 31 2018))
+                   (mapcar (lambda (d) (funcall abs 1 d 2018)) '(15 22 29))))
+    (should (equal (org-date-repeat-expand '(1 31 2018) ".+1m" '(1 1 2018)
+                                           '(4 30 2018))
+                   (list (funcall abs 1 31 2018) (funcall abs 3 3 2018)
+                         (funcall abs 3 31 2018))))
+    (should (equal (org-date-repeat-expand '(2 29 2016) "++1y" '(1 1 2016)
+                                           '(12 31 2020))
+                   (list (funcall abs 2 29 2016) (funcall abs 3 1 2017)
+                         (funcall abs 3 1 2018) (funcall abs 3 1 2019)
+                         (funcall abs 2 29 2020))))
+    (should-not (org-date-repeat-expand '(6 1 2018) "+1d" '(1 1 2018)
+                                        '(5 31 2018)))
+    (should-error (org-date-repeat-expand '(1 1 2018) "+1x" 1 2))
+    (should-error (org-date-repeat-expand '(1 1 2018) "1d" 1 2))))
+
+(provide 'orgdates-tests)
+;;; orgdates-tests.el ends here
@@ -0,0 +1,37 @@
+;;; pdf-tests.el --- tests for pdf.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest pdf-page-text-errors ()
+  (let ((file (make-temp-file "pdf-tests" nil ".pdf")))
+    (unwind-protect
+        (progn
+          (write-region "not a PDF file" nil file nil 'silent)
+          (should-error (pdf-page-text file 1))
+          (should-error (pdf-outline file))
+          (should-error (pdf-page-text file -1) :type 'wrong-type-argument)
+          (delete-file file)
+          (should-error (pdf-page-text file 1) :type 'file-missing)
+          (should-error (pdf-outline file) :type 'file-missing))
+      (when (file-exists-p file)
+        (delete-file file)))))
+
+(provide 'pdf-tests)
+;;; pdf-tests.el ends here
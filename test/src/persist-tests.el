@@ -0,0 +1,87 @@
+;;; persist-tests.el --- tests for lisp-data-write and lisp-data-read  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defmacro persist-tests--with-file (var &rest body)
+  "Bind VAR to a temporary file name while running BODY."
+  (declare (indent 1))
+  `(let ((,var (make-temp-file "persist-tests")))
+     (unwind-protect
+         (progn ,@body)
+       (delete-file ,var))))
+
+(defun persist-tests--round-trip (object)
+  (persist-tests--with-file file
+    (lisp-data-write file object)
+    (lisp-data-read file)))
+
+(ert-deftest persist-tests-round-trip ()
+  (dolist (object (list nil t 0 -1 most-positive-fixnum most-negative-fixnum
+                        1.5 -0.0 1.0e+INF "" "ascii" "Ünïcödé ✓"
+                        (string-to-unibyte "\377\0") 'foo :key
+                        '(1 2 3) '(a . b) '(1 2 . 3) [] [1 "two" (3)]
+                        '(("bookmark" (filename . "~/x") (position . 12)))))
+    (should (equal (persist-tests--round-trip object) object)))
+  (should (multibyte-string-p (persist-tests--round-trip "é")))
+  (should-not (multibyte-string-p
+               (persist-tests--round-trip (string-to-unibyte "abc")))))
+
+(ert-deftest persist-tests-hash-table ()
+  (let ((table (make-hash-table :test 'equal)))
+    (puthash "a" 1 table)
+    (puthash '(b) [2] table)
+    (let ((copy (persist-tests--round-trip table)))
+      (should (eq (hash-table-test copy) 'equal))
+      (should (= (hash-table-count copy) 2))
+      (should (equal (gethash "a" copy) 1))
+      (should (equal (gethash '(b) copy) [2])))))
+
+(ert-deftest persist-tests-unsupported ()
+  (persist-tests--with-file file
+    (write-region "old" nil file)
+    (should-error (lisp-data-write file (list 1 (lambda () 2))))
+    (should-error (lisp-data-write file (current-buffer)))
+    (should-error (lisp-data-write file (make-symbol "x")))
+    (let ((circular (list 1 2)))
+      (setcdr (cdr circular) circular)
+      (should-error (lisp-data-write file circular)))
+    ;; Nothing was written.
+    (should (equal (with-temp-buffer
+                     (insert-file-contents file)
+                     (buffer-string))
+                   "old"))))
+
+(ert-deftest persist-tests-damaged ()
+  (persist-tests--with-file file
+    (lisp-data-write file '(1 2 3))
+    (let ((coding-system-for-read 'no-conversion)
+          (coding-system-for-write 'no-conversion))
+      (with-temp-buffer
+        (set-buffer-multibyte nil)
+        (insert-file-contents-literally file)
+        (goto-char 12)
+        (insert "x")
+        (delete-char 1)
+        (write-region nil nil file)))
+    (should-error (lisp-data-read file))
+    (write-region "(1 2 3)" nil file)
+    (should-error (lisp-data-read file))))
+
+(provide 'persist-tests)
+;;; persist-tests.el ends here
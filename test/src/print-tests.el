@@ -58,5 +58,76 @@
                        (buffer-string))
                      "--------\n"))))
 
+;; The tests below exercise the printer in printer.rs.
+
+(ert-deftest print-symbols-and-numbers ()
+  (should (string= (prin1-to-string '(a\ b \12 \? -1.5 100.0 1e+20))
+                   "(a\\ b \\12 \\? -1.5 100.0 1e+20)"))
+  (should (string= (prin1-to-string (intern "")) "##"))
+  (should (string= (let ((print-gensym t)) (prin1-to-string (make-symbol "g")))
+                   "#:g"))
+  (should (string= (prin1-to-string (/ 0.0 0.0)) "-0.0e+NaN"))
+  (should (string= (let ((float-output-format "%.2f")) (prin1-to-string 1.0))
+                   "1.00")))
+
+(ert-deftest print-strings ()
+  (should (string= (prin1-to-string "a\"b\\c") "\"a\\\"b\\\\c\""))
+  (should (string= (princ "a\"b" #'ignore) "a\"b"))
+  (should (string= (prin1-to-string (propertize "ab" 'face 'bold))
+                   "#(\"ab\" 0 2 (face bold))"))
+  (should (string= (let ((print-escape-control-characters t))
+                     (prin1-to-string "\0\1"))
+                   "\"\\0\\001\"")))
+
+(ert-deftest print-quote-shorthand ()
+  (should (string= (let ((print-quoted t))
+                     (prin1-to-string '('a #'b `(c ,d ,@e))))
+                   "('a #'b `(c ,d ,@e))"))
+  (should (string= (let ((print-quoted nil)) (prin1-to-string ''a))
+                   "(quote a)")))
+
+(ert-deftest print-level-and-length ()
+  (let ((print-level 2) (print-length 3))
+    (should (string= (prin1-to-string '(1 (2 (3)) 4 5)) "(1 (2 ...) 4 ...)"))
+    (should (string= (prin1-to-string [1 2 3 4]) "[1 2 3 ...]"))))
+
+(ert-deftest print-circular ()
+  (let ((x (list 1 2)))
+    (setcdr (cdr x) x)
+    (should (string= (let ((print-circle t)) (prin1-to-string x))
+                     "#1=(1 2 . #1#)"))
+    (should (string= (let ((print-circle nil)) (prin1-to-string x))
+                     "(1 2 1 . #1)")))
+  (let* ((shared (list 'a))
+         (x (list shared shared)))
+    (should (string= (let ((print-circle t)) (prin1-to-string x))
+                     "(#1=(a) #1#)"))
+    (should (string= (let ((print-circle nil)) (prin1-to-string x))
+                     "((a) (a))"))))
+
+(ert-deftest print-vectorlike ()
+  (should (string= (prin1-to-string (record 'foo 1)) "#s(foo 1)"))
+  (should (string= (prin1-to-string (bool-vector t nil t)) "#&3\"\5\""))
+  (let ((table (make-hash-table :test 'equal :size 2)))
+    (puthash "k" 'v table)
+    (should (string-match-p "\\`#s(hash-table size 2 test equal .* data (\"k\" v))\\'"
+                            (prin1-to-string table))))
+  (should (string-prefix-p "#<marker" (prin1-to-string (make-marker)))))
+
+(ert-deftest print-to-destinations ()
+  (should (string= (with-temp-buffer
+                     (print '(a "b") (current-buffer))
+                     (buffer-string))
+                   "\n(a \"b\")\n"))
+  (let ((chars nil))
+    (prin1 'ab (lambda (c) (push c chars)))
+    (should (equal (nreverse chars) '(?a ?b))))
+  (with-temp-buffer
+    (insert "xy")
+    (let ((marker (copy-marker 2)))
+      (princ 'z marker)
+      (should (string= (buffer-string) "xzy"))
+      (should (= marker 3)))))
+
 (provide 'print-tests)
 ;;; print-tests.el ends here
@@ -166,5 +166,73 @@
                               (error nil))))
     (should (equal path samepath))))
 
+(ert-deftest process-test-native-accumulation ()
+  (skip-unless (executable-find "seq"))
+  (with-temp-buffer
+    (let ((proc (start-process "test" (current-buffer) "seq" "1" "20000")))
+      (set-process-sentinel proc #'ignore)
+      (should-not (set-process-native-accumulation proc t 10))
+      (while (process-live-p proc)
+        (accept-process-output proc 0.1))
+      (accept-process-output proc 0.1)
+      (should (= (count-lines (point-min) (point-max)) 10))
+      (should (string-prefix-p "19991\n" (buffer-string)))
+      (should (string-suffix-p "\n20000\n" (buffer-string)))
+      (should-not (set-process-native-accumulation proc nil)))))
+
+(ert-deftest process-test-native-accumulation-flush ()
+  ;; A process that fills the read buffer exactly and then pauses must
+  ;; not have its output held back until it writes again.
+  (skip-unless (executable-find "sh"))
+  (with-temp-buffer
+    (let* ((process-connection-type nil)
+           (line (make-string 4095 ?x))
+           (proc (start-process "test" (current-buffer) "sh" "-c"
+                                (format "printf '%%s\\n' %s; sleep 10" line))))
+      (set-process-sentinel proc #'ignore)
+      (set-process-native-accumulation proc t)
+      (unwind-protect
+          (let ((deadline (+ (float-time) 5)))
+            (while (and (< (buffer-size) 4096) (< (float-time) deadline))
+              (accept-process-output proc 0.1))
+            (should (process-live-p proc))
+            (should (equal (buffer-string) (concat line "\n"))))
+        (delete-process proc)))))
+
+(ert-deftest process-test-pty-name ()
+  (skip-unless (executable-find "sleep"))
+  (let* ((process-connection-type t)
+         (proc (start-process "test" nil "sleep" "5")))
+    (unwind-protect
+        (progn
+          (should (stringp (process-pty-name proc)))
+          (should (equal (process-pty-name proc) (process-tty-name proc)))
+          (should (set-process-window-size proc 24 80))
+          (should-error (set-process-window-size proc -1 80)))
+      (delete-process proc)))
+  (let* ((process-connection-type nil)
+         (proc (start-process "test" nil "sleep" "5")))
+    (unwind-protect
+        (progn
+          (should-not (process-pty-name proc))
+          (should-not (set-process-window-size proc 24 80)))
+      (delete-process proc))))
+
+(ert-deftest process-test-process-tree ()
+  (skip-unless (executable-find "sh"))
+  (let ((proc (start-process "test" nil "sh" "-c" "sleep 10 & wait")))
+    (unwind-protect
+        (progn
+          (sleep-for 0.5)
+          (let ((tree (process-tree proc)))
+            (should (equal (car tree) (process-id proc)))
+            (should (= (length (cdr tree)) 1))
+            (should (integerp (car (cadr tree))))))
+      (delete-process proc)))
+  (let ((proc (make-pipe-process :name "test")))
+    (unwind-protect
+        (should-not (process-tree proc))
+      (delete-process proc))))
+
 (provide 'process-tests)
 ;; process-tests.el ends here.
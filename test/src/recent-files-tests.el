@@ -0,0 +1,77 @@
+;;; recent-files-tests.el --- tests for recent_files.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defmacro recent-files-tests--saving-list (&rest body)
+  "Run BODY and put back the list of recent files it may change."
+  (declare (indent 0))
+  (let ((saved (make-symbol "saved")))
+    `(let ((,saved (recent-files-native)))
+       (unwind-protect
+           (progn ,@body)
+         (recent-files-native-set ,saved)))))
+
+(ert-deftest recent-files-tests-add-remove ()
+  (recent-files-tests--saving-list
+    (should (equal (recent-files-native-set '("/a" "/b" "/a" "/c"))
+                   '("/a" "/b" "/c")))
+    (recent-files-native-add "/c")
+    (should (equal (recent-files-native) '("/c" "/a" "/b")))
+    (should (equal (recent-files-native 2) '("/c" "/a")))
+    (recent-files-native-add "/d" 3)
+    (should (equal (recent-files-native) '("/d" "/c" "/a")))
+    (should (eq (recent-files-native-remove "/c") t))
+    (should-not (recent-files-native-remove "/c"))
+    (should (equal (recent-files-native) '("/d" "/a")))
+    (should-error (recent-files-native-set '("/a" 1)))))
+
+(ert-deftest recent-files-tests-save-load ()
+  (recent-files-tests--saving-list
+    (let ((file (make-temp-file "recent-files-tests")))
+      (unwind-protect
+          (progn
+            (recent-files-native-set '("/x/é" "/y" "/z"))
+            (recent-files-native-save file 2)
+            (recent-files-native-set nil)
+            (should (equal (recent-files-native-load file) '("/x/é" "/y")))
+            (should (equal (recent-files-native) '("/x/é" "/y")))
+            (delete-file file)
+            (should-not (recent-files-native-load file)))
+        (when (file-exists-p file)
+          (delete-file file))))))
+
+(ert-deftest recent-files-tests-check ()
+  (recent-files-tests--saving-list
+    (let ((file (make-temp-file "recent-files-tests"))
+          (missing (make-temp-name
+                    (expand-file-name "recent-files-tests"
+                                      temporary-file-directory))))
+      (unwind-protect
+          (progn
+            (recent-files-native-set (list missing file))
+            (recent-files-native-check)
+            (with-timeout (5 (ert-fail "Check did not finish"))
+              (while (member missing (recent-files-native))
+                (sleep-for 0.01)))
+            (should (equal (recent-files-native) (list file))))
+        (delete-file file)))))
+
+(provide 'recent-files-tests)
+;;; recent-files-tests.el ends here
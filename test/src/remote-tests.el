@@ -0,0 +1,43 @@
+;;; remote-tests.el --- tests for remote.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+(require 'tramp)
+
+(ert-deftest remote-not-a-remote-file-name ()
+  (skip-unless (fboundp 'remote-read-file))
+  (should-error (remote-read-file "/etc/hosts") :type 'file-error)
+  (should-error (remote-file-attributes "/ssh:host:/") :type 'file-error)
+  (should-error (remote-write-file "/rssh:host#port:/x" "") :type 'file-error)
+  (should-error (remote-read-file 'host) :type 'wrong-type-argument))
+
+(ert-deftest remote-tramp-file-names ()
+  (require 'tramp-rssh)
+  (should (tramp-rssh-file-name-p "/rssh:user@host#2222:/tmp/x"))
+  (should-not (tramp-rssh-file-name-p "/ssh:user@host:/tmp/x"))
+  (should (equal (tramp-rssh-remote-name
+                  (tramp-dissect-file-name "/rssh:user@host#2222:/tmp/x"))
+                 "/rssh:user@host#2222:/tmp/x"))
+  (should (equal (tramp-rssh-remote-name
+                  (tramp-dissect-file-name "/rssh:host:/tmp/x") "/etc")
+                 "/rssh:host:/etc")))
+
+(provide 'remote-tests)
+
+;;; remote-tests.el ends here
@@ -0,0 +1,45 @@
+;;; secrets-tests.el --- tests for secrets.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+(require 'auth-source)
+
+(ert-deftest secrets-store-type-errors ()
+  (should-error (secrets-store 1 "account" "secret")
+                :type 'wrong-type-argument)
+  (should-error (secrets-store "service" 'account "secret")
+                :type 'wrong-type-argument)
+  (should-error (secrets-store "service" "account" nil)
+                :type 'wrong-type-argument))
+
+(ert-deftest secrets-lookup-type-errors ()
+  (should-error (secrets-lookup nil) :type 'wrong-type-argument)
+  (should-error (secrets-lookup "service" 2) :type 'wrong-type-argument))
+
+(ert-deftest secrets-auth-source-backend ()
+  (let ((backend (auth-source-backend-parse 'native-keychain)))
+    (should (eq (slot-value backend 'type) 'native-keychain))
+    (should (equal (auth-source-native-keychain-service "host" 993)
+                   "host:993"))
+    (should (equal (auth-source-native-keychain-service "host" nil)
+                   "host"))))
+
+(provide 'secrets-tests)
+
+;;; secrets-tests.el ends here
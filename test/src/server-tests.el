@@ -0,0 +1,49 @@
+;;; server-tests.el --- tests for server.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest server-quote-arg-round-trip ()
+  (let ((name "-my file\nwith&newline é"))
+    (should (equal (server-quote-arg name)
+                   "&-my&_file&nwith&&newline&_é"))
+    (should (equal (server-unquote-arg (server-quote-arg name)) name))))
+
+(ert-deftest server-parse-request ()
+  (should-not (server-parse-request "-dir /tmp/ -file a"))
+  (should (equal (server-parse-request "-file  a&nb&_c\n")
+                 '(("-file" "a\nb c") . ""))))
+
+(ert-deftest server-authenticate ()
+  (let ((key (make-string 64 ?k)))
+    (should (equal (server-authenticate (concat "-auth " key " -nowait\n") key)
+                   " -nowait\n"))
+    (should (equal (server-authenticate (concat "-auth " key "\n") key) ""))
+    (should-not (server-authenticate (concat "-auth " key "x -nowait\n") key))
+    (should-not (server-authenticate "-nowait\n" key))
+    (should-not (server-authenticate (concat "-auth " key "\n") nil))))
+
+(ert-deftest server-split-reply ()
+  (should (equal (server-split-reply "abc" 1024) '("-print abc\n")))
+  (should (equal (server-split-reply "abcd&&e" 13)
+                 '("-print abcd\n" "-print-nonl &&e\n")))
+  (dolist (message (server-split-reply (make-string 100 ?é) 50))
+    (should (<= (string-bytes message) 50))))
+
+(provide 'server-tests)
+;;; server-tests.el ends here
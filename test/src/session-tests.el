@@ -0,0 +1,87 @@
+;;; session-tests.el --- tests for session.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defvar session-tests--value nil)
+(defvar session-tests--marker nil)
+
+(ert-deftest session-tests-round-trip ()
+  (let ((file (make-temp-file "session-tests" nil ".txt"))
+        (snapshot (make-temp-file "session-tests"))
+        (session-tests--value '(1 "two" three))
+        (session-tests--marker (point-marker)))
+    (unwind-protect
+        (progn
+          (with-temp-file file
+            (insert "one\ntwo\nthree\n"))
+          (with-current-buffer (find-file-noselect file)
+            (goto-char 6)
+            (set-marker (mark-marker) 2)
+            (setq buffer-read-only t))
+          (session-snapshot-save snapshot
+                                 '(session-tests--value
+                                   session-tests--marker
+                                   session-tests--unbound)
+                                 t)
+          (session-snapshot-wait)
+          (kill-buffer (get-file-buffer file))
+          (setq session-tests--value nil
+                session-tests--marker 'unchanged)
+          (let ((buffers (session-snapshot-restore snapshot t)))
+            (should (memq (get-file-buffer file) buffers))
+            (with-current-buffer (get-file-buffer file)
+              (should (= (point) 6))
+              (should (= (marker-position (mark-marker)) 2))
+              (should buffer-read-only)))
+          (should (equal session-tests--value '(1 "two" three)))
+          ;; Markers can't be saved, so the variable was left out.
+          (should (eq session-tests--marker 'unchanged)))
+      (when (get-file-buffer file)
+        (kill-buffer (get-file-buffer file)))
+      (delete-file file)
+      (delete-file snapshot))))
+
+(ert-deftest session-tests-missing-file ()
+  (let ((file (make-temp-file "session-tests"))
+        (snapshot (make-temp-file "session-tests")))
+    (unwind-protect
+        (progn
+          (find-file-noselect file)
+          (session-snapshot-save snapshot nil t)
+          (kill-buffer (get-file-buffer file))
+          (delete-file file)
+          (should-not (memq nil (session-snapshot-restore snapshot t)))
+          (should-not (get-file-buffer file)))
+      (when (get-file-buffer file)
+        (kill-buffer (get-file-buffer file)))
+      (delete-file file)
+      (delete-file snapshot))))
+
+(ert-deftest session-tests-invalid ()
+  (let ((file (make-temp-file "session-tests")))
+    (unwind-protect
+        (progn
+          (should-error (session-snapshot-restore file))
+          (lisp-data-write file 42)
+          (should-error (session-snapshot-restore file)))
+      (delete-file file))))
+
+(provide 'session-tests)
+;;; session-tests.el ends here
@@ -0,0 +1,43 @@
+;;; spell-tests.el --- tests for native spell checking  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defun spell-tests--english ()
+  (car (member "en_US" (spell-dictionaries))))
+
+(ert-deftest spell-tests-check-word ()
+  (skip-unless (spell-tests--english))
+  (should (spell-check-word "hello" "en_US"))
+  (should-not (spell-check-word "helo" "en_US"))
+  (should (member "hello" (spell-suggest "helo" "en_US"))))
+
+(ert-deftest spell-tests-session ()
+  (skip-unless (spell-tests--english))
+  (should-not (spell-check-word "remacsish" "en_US"))
+  (spell-session-add "remacsish" "en_US")
+  (should (spell-check-word "remacsish" "en_US"))
+  (spell-session-remove "remacsish" "en_US")
+  (should-not (spell-check-word "remacsish" "en_US")))
+
+(ert-deftest spell-tests-errors ()
+  (should-error (spell-check-word 'hello))
+  (should-error (spell-check-word "hello" "xx_NO_SUCH_LANGUAGE")))
+
+(provide 'spell-tests)
+;;; spell-tests.el ends here
@@ -0,0 +1,48 @@
+;;; svg-tests.el --- tests for svg.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest sparkline-svg ()
+  (let ((spec (sparkline-svg '(1 3 2) 21 11 "red")))
+    (should (eq (car spec) 'image))
+    (should (eq (plist-get (cdr spec) :type) 'svg))
+    (should (equal (plist-get (cdr spec) :data)
+                   (concat "<svg xmlns=\"http://www.w3.org/2000/svg\" "
+                           "width=\"21\" height=\"11\">"
+                           "<polyline fill=\"none\" stroke=\"red\" "
+                           "stroke-width=\"1\" "
+                           "points=\"0.5,10.5 10.5,0.5 20.5,5.5\"/></svg>"))))
+  (should (equal (sparkline-svg [1 3 2] 21 11 "red")
+                 (sparkline-svg '(1.0 3.0 2.0) 21 11 "red")))
+  (should-error (sparkline-svg '(1 a) 20 10))
+  (should-error (sparkline-svg '(1 2) nil 10))
+  (should-error (sparkline-svg '(1 2) 0 10)))
+
+(ert-deftest progress-bar-svg ()
+  (let ((data (plist-get (cdr (progress-bar-svg 0.5 40 8 "blue" "white"))
+                         :data)))
+    (should (string-match-p
+             "<rect width=\"40\" height=\"8\" fill=\"white\"/><rect width=\"20\" height=\"8\" fill=\"blue\"/>"
+             data)))
+  ;; Fractions that fill the same pixels give equal images.
+  (should (equal (progress-bar-svg 0.501 40 8) (progress-bar-svg 0.499 40 8)))
+  (should-error (progress-bar-svg 'half)))
+
+(provide 'svg-tests)
+;;; svg-tests.el ends here
@@ -0,0 +1,45 @@
+;;; sysinfo-tests.el --- tests for sysinfo.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest battery-status-native ()
+  (let ((status (battery-status-native)))
+    (when status
+      (dolist (conversion '(?c ?r ?B ?b ?d ?L ?p ?m ?h ?t))
+        (should (stringp (cdr (assq conversion status))))))))
+
+(ert-deftest cpu-usage-native ()
+  (let ((usage (cpu-usage-native)))
+    (when usage
+      (should (floatp usage))
+      (should (<= 0 usage 100))
+      (should (<= 0 (cpu-usage-native) 100)))))
+
+(ert-deftest memory-usage-native ()
+  (let ((memory (memory-usage-native)))
+    (dolist (entry memory)
+      (should (memq (car entry)
+                    '(total free available swap-total swap-free)))
+      (should (natnump (cdr entry))))
+    (when (assq 'total memory)
+      (should (<= (alist-get 'free memory 0) (alist-get 'total memory))))))
+
+(provide 'sysinfo-tests)
+;;; sysinfo-tests.el ends here
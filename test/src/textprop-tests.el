@@ -68,5 +68,64 @@
     (should (and (equal-including-properties (pop stack) string)
 		 (null stack)))))
 
+;; add-text-properties-batch
+
+(ert-deftest textprop-tests-add-text-properties-batch ()
+  (let ((string (copy-sequence "abcdef")))
+    (should (eq (add-text-properties-batch
+                 (vector '(0 2 (face bold))
+                         '(2 4 (face italic))
+                         '(4 6 (face bold)))
+                 string)
+                t))
+    (should (equal-including-properties
+             string
+             #("abcdef" 0 2 (face bold) 2 4 (face italic) 4 6 (face bold))))
+    ;; Nothing changes the second time round.
+    (should-not (add-text-properties-batch (vector '(0 2 (face bold))) string))))
+
+(ert-deftest textprop-tests-add-text-properties-batch-merges-runs ()
+  (let ((string (copy-sequence "abcdef"))
+        (plist (list 'face 'bold)))
+    (add-text-properties-batch (vector (list 0 2 plist) (list 2 6 plist)) string)
+    (should (equal-including-properties string #("abcdef" 0 6 (face bold))))))
+
+(ert-deftest textprop-tests-add-text-properties-batch-buffer ()
+  (with-temp-buffer
+    (insert "abcdef")
+    (add-text-properties-batch (vector '(1 3 (face bold)) '(5 7 (face italic))))
+    (should (eq (get-text-property 2 'face) 'bold))
+    (should-not (get-text-property 3 'face))
+    (should (eq (get-text-property 6 'face) 'italic))))
+
+(ert-deftest textprop-tests-add-text-properties-batch-checks-first ()
+  (let ((string (copy-sequence "abcdef")))
+    (should-error (add-text-properties-batch
+                   (vector '(0 2 (face bold)) '(2 4))
+                   string)
+                  :type 'wrong-type-argument)
+    (should-not (text-properties-at 0 string))
+    (should-error (add-text-properties-batch '((0 2 (face bold))) string)
+                  :type 'wrong-type-argument)))
+
+(ert-deftest textprop-tests-add-text-properties-batch-checks-ranges ()
+  (let ((string (copy-sequence "abcdef")))
+    (should-error (add-text-properties-batch
+                   (vector '(0 2 (face bold)) '(4 9 (face italic)))
+                   string)
+                  :type 'args-out-of-range)
+    (should-not (next-property-change 0 string))))
+
+(ert-deftest textprop-tests-add-text-properties-batch-position-order ()
+  (let ((string (copy-sequence "abcdef")))
+    (add-text-properties-batch (vector '(3 6 (face italic))
+                                       '(0 4 (face bold))
+                                       '(1 2 (mouse-face highlight)))
+                               string)
+    (should (equal-including-properties
+             string
+             #("abcdef" 0 1 (face bold) 1 2 (face bold mouse-face highlight)
+               2 3 (face bold) 3 6 (face italic))))))
+
 (provide 'textprop-tests)
 ;; textprop-tests.el ends here.
@@ -0,0 +1,38 @@
+;;; time-tests.el --- tests for time.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest time-tests--parse-iso8601-native ()
+  (should (equal (parse-iso8601-time-string-native "1998-09-12T12:21:54-02:00" t)
+                 '(13818 33666)))
+  (should (equal (parse-iso8601-time-string-native "1998-W37-6T12:21:54Z")
+                 '(13818 26466)))
+  (should (equal (parse-iso8601-time-string-native "1998-255T122154Z")
+                 '(13818 26466)))
+  (should (equal (parse-iso8601-time-string-native "1998-09-12T12:21:54.5Z")
+                 '(13818 26466 500000 0)))
+  (should (equal (parse-iso8601-time-string-native "1998-09-12")
+                 (encode-time 0 0 0 12 9 1998)))
+  (should-not (parse-iso8601-time-string-native "1998-09-12" t))
+  (should-not (parse-iso8601-time-string-native "1998-09-12T12:21:54" t))
+  (should-not (parse-iso8601-time-string-native "1998-02-30"))
+  (should-not (parse-iso8601-time-string-native "yesterday")))
+
+(provide 'time-tests)
+;;; time-tests.el ends here
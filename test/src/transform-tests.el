@@ -0,0 +1,68 @@
+;;; transform-tests.el --- tests for transform.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest transform-tests-rot13-region ()
+  (with-temp-buffer
+    (insert (propertize "Hello" 'face 'bold) ", World! é")
+    (should (= (rot13-region (point-min) (point-max)) 10))
+    (should (equal (buffer-string) "Uryyb, Jbeyq! é"))
+    (should (eq (get-text-property 1 'face) 'bold))
+    (rot13-region (point-min) (point-max))
+    (should (equal (buffer-string) "Hello, World! é"))
+    (should (= (rot13-region 8 8) 0))
+    (should-error (rot13-region 1 100) :type 'args-out-of-range)))
+
+(ert-deftest transform-tests-translate-region-native ()
+  (with-temp-buffer
+    (insert "abc abc")
+    (let ((table (make-string 256 0)))
+      (dotimes (i 256)
+        (aset table i i))
+      (aset table ?a ?x)
+      (aset table ?c ?é)
+      (should (= (translate-region-native (point-min) (point-max) table) 4))
+      (should (equal (buffer-string) "xbé xbé")))
+    (let ((table (make-char-table 'translation-table)))
+      (aset table ?é ?c)
+      (aset table ?b [?y ?z])
+      (should (= (translate-region-native (point-min) (point-max) table) 2))
+      (should (equal (buffer-string) "xbc xbc"))
+      (should-error (translate-region-native (point-min) (point-max)
+                                             (make-char-table 'foo))))))
+
+(ert-deftest transform-tests-undo ()
+  (with-temp-buffer
+    (buffer-enable-undo)
+    (insert "abc")
+    (undo-boundary)
+    (rot13-region (point-min) (point-max))
+    (undo-boundary)
+    (primitive-undo 1 (cdr buffer-undo-list))
+    (should (equal (buffer-string) "abc"))))
+
+(ert-deftest transform-tests-string-reverse ()
+  (should (equal (string-reverse "") ""))
+  (should (equal (string-reverse "abc") "cba"))
+  (should (equal (string-reverse "aéb") "béa"))
+  (should-not (multibyte-string-p (string-reverse "abc")))
+  (should (equal (string-reverse (string-to-multibyte "\377a")) "a\377")))
+
+(provide 'transform-tests)
+;;; transform-tests.el ends here
@@ -0,0 +1,92 @@
+;;; transient-maps-tests.el --- tests for transient_maps.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+(require 'cl-lib)
+
+(defmacro transient-maps-tests--in-buffer (&rest body)
+  "Run BODY in a temporary buffer shown in the selected window."
+  (declare (indent 0))
+  `(with-temp-buffer
+     (save-window-excursion
+       (set-window-buffer nil (current-buffer))
+       ,@body)))
+
+(defun transient-maps-tests--insert-A ()
+  (interactive)
+  (insert "A"))
+
+(defvar transient-maps-tests--map
+  (let ((map (make-sparse-keymap)))
+    (define-key map "a" #'transient-maps-tests--insert-A)
+    map))
+
+(ert-deftest transient-maps-tests-once ()
+  (transient-maps-tests--in-buffer
+    (let ((exits 0))
+      (set-transient-map-native transient-maps-tests--map nil
+                                (lambda () (setq exits (1+ exits))))
+      (execute-kbd-macro "aa")
+      (should (equal (buffer-string) "Aa"))
+      (should (= exits 1))
+      (should-not overriding-terminal-local-map))))
+
+(ert-deftest transient-maps-tests-keep ()
+  (transient-maps-tests--in-buffer
+    (set-transient-map-native transient-maps-tests--map t)
+    (execute-kbd-macro "aaba")
+    (should (equal (buffer-string) "AAba"))
+    (should-not overriding-terminal-local-map)))
+
+(ert-deftest transient-maps-tests-exit-function ()
+  (let* ((exits 0)
+         (exit (set-transient-map-native transient-maps-tests--map t
+                                         (lambda () (setq exits (1+ exits))))))
+    (should (keymapp overriding-terminal-local-map))
+    (should (eq (funcall exit) t))
+    (should-not (funcall exit))
+    (should (= exits 1))
+    (should-not overriding-terminal-local-map)))
+
+(ert-deftest transient-maps-tests-same-map-twice ()
+  "Exiting one activation of a map leaves the other one active."
+  (let ((first (set-transient-map-native transient-maps-tests--map t))
+        (second (set-transient-map-native transient-maps-tests--map t)))
+    (unwind-protect
+        (progn
+          (funcall first)
+          (should (eq (lookup-key overriding-terminal-local-map "a")
+                      #'transient-maps-tests--insert-A)))
+      (funcall first)
+      (funcall second))
+    (should-not overriding-terminal-local-map)))
+
+(ert-deftest transient-maps-tests-timeout ()
+  (let* ((timers (copy-sequence timer-idle-list))
+         (exit (set-transient-map-native transient-maps-tests--map t nil 60))
+         (timer (car (cl-set-difference timer-idle-list timers))))
+    (should (timerp timer))
+    (funcall exit)
+    (should-not (memq timer timer-idle-list))
+    (should-error (set-transient-map-native transient-maps-tests--map nil nil
+                                            "soon"))
+    (should-error (set-transient-map-native 'not-a-map))))
+
+(provide 'transient-maps-tests)
+;;; transient-maps-tests.el ends here
@@ -0,0 +1,79 @@
+;;; trash-tests.el --- tests for trash.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defmacro trash-tests--with-home-trash (dir &rest body)
+  "Run BODY with the home trash in a fresh directory DIR."
+  (declare (indent 1))
+  `(let* ((,dir (make-temp-file "trash" t))
+          (process-environment
+           (cons (concat "XDG_DATA_HOME=" ,dir) process-environment))
+          (trash-directory nil))
+     (unwind-protect
+         (progn ,@body)
+       (delete-directory ,dir t))))
+
+(ert-deftest trash-tests--move-to-home-trash ()
+  (skip-unless (memq system-type '(gnu/linux berkeley-unix)))
+  (trash-tests--with-home-trash dir
+    (let ((file (expand-file-name "foo.txt" dir)))
+      (write-region "trashed" nil file)
+      (move-file-to-trash file)
+      (should-not (file-exists-p file))
+      (should (file-exists-p (expand-file-name "Trash/files/foo.txt" dir)))
+      (with-temp-buffer
+        (insert-file-contents
+         (expand-file-name "Trash/info/foo.txt.trashinfo" dir))
+        (should (looking-at-p "\\[Trash Info\\]\n"))
+        (should (search-forward (concat "Path=" file "\n") nil t))
+        (should (re-search-forward
+                 "^DeletionDate=[0-9]\\{4\\}-[0-9]\\{2\\}-[0-9]\\{2\\}T" nil t))))))
+
+(ert-deftest trash-tests--name-collision ()
+  (skip-unless (memq system-type '(gnu/linux berkeley-unix)))
+  (trash-tests--with-home-trash dir
+    (let ((file (expand-file-name "foo.txt" dir)))
+      (write-region "first" nil file)
+      (move-file-to-trash file)
+      (write-region "second" nil file)
+      (move-file-to-trash file)
+      (should (file-exists-p (expand-file-name "Trash/files/foo.txt" dir)))
+      (should (file-exists-p (expand-file-name "Trash/files/foo.2.txt" dir)))
+      (should (file-exists-p
+               (expand-file-name "Trash/info/foo.2.txt.trashinfo" dir))))))
+
+(ert-deftest trash-tests--directory ()
+  (skip-unless (memq system-type '(gnu/linux berkeley-unix)))
+  (trash-tests--with-home-trash dir
+    (let ((subdir (expand-file-name "sub" dir)))
+      (make-directory subdir)
+      (write-region "inside" nil (expand-file-name "a" subdir))
+      (move-file-to-trash (file-name-as-directory subdir))
+      (should-not (file-exists-p subdir))
+      (should (file-exists-p (expand-file-name "Trash/files/sub/a" dir))))))
+
+(ert-deftest trash-tests--parent-of-trash ()
+  (skip-unless (memq system-type '(gnu/linux berkeley-unix)))
+  (trash-tests--with-home-trash dir
+    (make-directory (expand-file-name "Trash" dir))
+    (should-error (move-file-to-trash dir))
+    (should (file-directory-p dir))))
+
+(provide 'trash-tests)
+;;; trash-tests.el ends here
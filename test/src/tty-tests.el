@@ -0,0 +1,39 @@
+;;; tty-tests.el --- tests for tty.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest tty-query-capabilities-terminfo ()
+  (let ((caps (tty-query-capabilities "no-such-terminal-type")))
+    (should (equal (plist-get caps :type) "no-such-terminal-type"))
+    (should-not (plist-get caps :terminfo))
+    (should-not (plist-get caps :colors))
+    (should-not (plist-get caps :da1))
+    (should-not (plist-get caps :cell-size)))
+  (let ((caps (tty-query-capabilities "xterm-256color")))
+    (skip-unless (plist-get caps :terminfo))
+    (should (equal (plist-get caps :colors) 256))
+    (should (eq (cdr (assoc "am" (plist-get caps :terminfo))) t))))
+
+(ert-deftest tty-query-capabilities-batch ()
+  (skip-unless noninteractive)
+  (should-not (tty-query-capabilities)))
+
+(provide 'tty-tests)
+;;; tty-tests.el ends here
@@ -0,0 +1,47 @@
+;;; visual-lines-tests.el --- tests for visual_lines.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(defun visual-lines-tests--row-start (pos)
+  "Return the start of the screen line POS is on, by `vertical-motion'."
+  (save-excursion
+    (goto-char pos)
+    (vertical-motion 0)
+    (point)))
+
+(ert-deftest visual-line-start-cache-lookup ()
+  (with-temp-buffer
+    (set-window-buffer nil (current-buffer))
+    (insert "short\n" (make-string 1000 ?x) "\n" (make-string 500 ?y))
+    (dolist (pos '(1 3 7 8 100 500 1007 1008 1300 1509))
+      (should (= (visual-line-start-cache-lookup pos)
+                 (visual-lines-tests--row-start pos)))
+      ;; The second lookup is answered from the cache.
+      (should (= (visual-line-start-cache-lookup pos)
+                 (visual-lines-tests--row-start pos))))
+    ;; Changing a line lays it out again.
+    (goto-char 10)
+    (insert "\n")
+    (dolist (pos '(8 9 10 11 500 1008 1009))
+      (should (= (visual-line-start-cache-lookup pos)
+                 (visual-lines-tests--row-start pos))))
+    (should-error (visual-line-start-cache-lookup 5000))))
+
+(provide 'visual-lines-tests)
+;;; visual-lines-tests.el ends here
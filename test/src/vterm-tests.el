@@ -0,0 +1,47 @@
+;;; vterm-tests.el --- tests for the terminal emulator core  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+(require 'ert)
+
+(ert-deftest vterm-tests-render ()
+  (let ((term (vterm-make 2 10)))
+    (unwind-protect
+        (with-temp-buffer
+          (should-not (vterm-write term "hello\r\nworld\r\n\e[1magain"))
+          (vterm-render term)
+          (should (equal (buffer-string) "hello\nworld\nagain"))
+          (should (= (point) (point-max)))
+          (should (equal (get-text-property 13 'face) '(:weight bold)))
+          (vterm-write term "\e[1;1HW")
+          (vterm-render term)
+          (should (equal (buffer-string) "hello\nWorld\nagain"))
+          (should (= (point) 8))
+          (should (equal (vterm-cursor term) '(0 1 t))))
+      (vterm-delete term))))
+
+(ert-deftest vterm-tests-replies ()
+  (let ((term (vterm-make 5 20)))
+    (unwind-protect
+        (progn
+          (vterm-write term "\e[3;4H")
+          (should (equal (vterm-write term "\e[6n") "\e[3;4R")))
+      (vterm-delete term))
+    (should-error (vterm-write term "x"))))
+
+(provide 'vterm-tests)
+;;; vterm-tests.el ends here
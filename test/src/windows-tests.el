@@ -0,0 +1,67 @@
+;;; windows-tests.el --- tests for windows.rs  -*- lexical-binding: t-*-
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defun windows-tests--shape (node)
+  "Return the kinds of the windows in NODE and the buffers they show."
+  (if (eq (car node) 'leaf)
+      (plist-get (nth 1 node) :buffer)
+    (cons (car node) (mapcar #'windows-tests--shape (nthcdr 2 node)))))
+
+(ert-deftest windows-tests-configuration-round-trip ()
+  (save-window-excursion
+    (let ((a (get-buffer-create "windows-tests-a"))
+          (b (get-buffer-create "windows-tests-b")))
+      (unwind-protect
+          (progn
+            (delete-other-windows)
+            (switch-to-buffer a)
+            (let ((right (split-window nil nil 'right)))
+              (set-window-buffer right b)
+              (select-window right))
+            (let* ((sexp (window-configuration-to-sexp))
+                   (root (plist-get (cdr sexp) :root)))
+              (should (eq (car sexp) 'window-configuration))
+              (should (equal (windows-tests--shape root)
+                             '(horizontal "windows-tests-a" "windows-tests-b")))
+              ;; The description survives printing and reading.
+              (setq sexp (car (read-from-string (prin1-to-string sexp))))
+              (delete-other-windows)
+              (switch-to-buffer "*scratch*")
+              (sexp-to-window-configuration sexp)
+              (should (= (length (window-list)) 2))
+              (should (eq (window-buffer (selected-window)) b))
+              (should (eq (window-buffer (frame-first-window)) a))
+              (should (equal (windows-tests--shape
+                              (plist-get (cdr (window-configuration-to-sexp))
+                                         :root))
+                             '(horizontal "windows-tests-a"
+                                          "windows-tests-b")))))
+        (kill-buffer a)
+        (kill-buffer b)))))
+
+(ert-deftest windows-tests-configuration-invalid ()
+  (should-error (sexp-to-window-configuration nil))
+  (should-error (sexp-to-window-configuration '(window-configuration)))
+  (should-error (sexp-to-window-configuration
+                 '(window-configuration :root (diagonal nil)))))
+
+(provide 'windows-tests)
+;;; windows-tests.el ends here